@@ -0,0 +1,58 @@
+//! Minimal interactive chat in the terminal.
+//!
+//! Tool permission requests are confirmed on stdin.
+//!
+//! ```sh
+//! OPENAI_API_KEY=... cargo run --example cli_chat -- openai gpt-4o
+//! ```
+
+use anyagents::{AgentBuilder, RuntimeBuilder};
+use std::io::{self, BufRead, Write};
+
+#[tokio::main]
+async fn main() -> Result<(), String> {
+    let mut args = std::env::args().skip(1);
+    let provider = args.next().unwrap_or_else(|| "openai".to_string());
+    let model = args.next().unwrap_or_else(|| "gpt-4o".to_string());
+
+    let runtime = RuntimeBuilder::new()
+        .permission_handler(|req| {
+            print!("\n[permission] {} - allow? [y/N] ", req.message);
+            io::stdout().flush().ok();
+            let mut answer = String::new();
+            io::stdin().lock().read_line(&mut answer).ok();
+            answer.trim().eq_ignore_ascii_case("y")
+        })
+        .event_sink(|_channel, event| {
+            if event["type"] == "step_started" {
+                if let Some(tool) = event["step"]["tool_name"].as_str() {
+                    println!("  -> {}", tool);
+                }
+            }
+        })
+        .build()?;
+
+    let mut agent = AgentBuilder::new(&provider, &model)
+        .system_prompt("You are a concise assistant working in the user's current directory.")
+        .with_default_tools()
+        .build();
+
+    println!("Chatting with {}/{} (empty line to quit)", provider, model);
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush().ok();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).map_err(|e| e.to_string())? == 0 || line.trim().is_empty() {
+            break;
+        }
+
+        match runtime.chat(&mut agent, line.trim()).await {
+            Ok(reply) => println!("{}\n", reply),
+            Err(e) => eprintln!("error: {}\n", e),
+        }
+    }
+
+    Ok(())
+}
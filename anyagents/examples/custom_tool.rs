@@ -0,0 +1,70 @@
+//! Registering a custom tool with an embedded agent.
+//!
+//! ```sh
+//! OPENAI_API_KEY=... cargo run --example custom_tool
+//! ```
+
+use anyagents::tools::{Tool, ToolContext};
+use anyagents::{AgentBuilder, RuntimeBuilder};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+/// Counts words in a piece of text
+struct WordCountTool;
+
+#[async_trait]
+impl Tool for WordCountTool {
+    fn name(&self) -> &str {
+        "word_count"
+    }
+
+    fn description(&self) -> &str {
+        "Count the words in a piece of text."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "text": {
+                    "type": "string",
+                    "description": "The text to count words in"
+                }
+            },
+            "required": ["text"]
+        })
+    }
+
+    async fn execute(&self, args: Value, _ctx: &ToolContext) -> Result<Value, String> {
+        let text = args["text"].as_str().ok_or("Missing text")?;
+        Ok(json!({ "words": text.split_whitespace().count() }))
+    }
+
+    fn is_read_only(&self, _args: &Value) -> bool {
+        true
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), String> {
+    let runtime = RuntimeBuilder::new()
+        .auto_approve()
+        .event_sink(|_channel, event| {
+            if event["type"] == "step_completed" {
+                println!("[tool] {} -> {}", event["step"]["tool_name"], event["step"]["result"]);
+            }
+        })
+        .build()?;
+
+    let mut agent = AgentBuilder::new("openai", "gpt-4o")
+        .system_prompt("Use the word_count tool whenever asked about word counts.")
+        .tool(WordCountTool)
+        .build();
+
+    let reply = runtime
+        .chat(&mut agent, "How many words are in 'the quick brown fox jumps over the lazy dog'?")
+        .await?;
+    println!("{}", reply);
+
+    Ok(())
+}
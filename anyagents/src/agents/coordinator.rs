@@ -1,13 +1,48 @@
+use crate::agents::critic::{self, CriticSettings};
+use crate::agents::dry_run;
+use crate::agents::hooks::{AgentHooks, HookObserver};
+use crate::agents::queue::{self, QueueWait, QueuedJob, RunningJob};
 use crate::agents::{planner::PlanningAgent, router::{Router, QueryType}, simple_chat::SimpleChatAgent, AgentLoop};
 use crate::database::DbPool;
 use crate::events::{AgentEvent, ExecutionJob, AgentObserver};
+use crate::models::agent_version;
 use crate::models::Agent as DbAgent;
 use crate::permissions::{PermissionManager, AutonomousPermissionManager};
 use log::info;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::oneshot;
 use uuid::Uuid;
 
+/// `observer` with the agent's `on_job_complete` hooks run off its JobCompleted events
+fn with_job_hooks(
+    agent_db: &DbAgent,
+    observer: Arc<dyn AgentObserver>,
+    permission_manager: Arc<PermissionManager>,
+) -> Arc<dyn AgentObserver> {
+    let hooks = agent_db
+        .execution_settings
+        .as_ref()
+        .and_then(|s| serde_json::from_str::<serde_json::Value>(s).ok())
+        .and_then(|settings| {
+            AgentHooks::from_execution_settings(&settings, &agent_db.id, crate::agents::workspace_path(agent_db))
+                .map_err(|e| log::error!("Ignoring hooks of agent {}: {}", agent_db.id, e))
+                .ok()
+                .flatten()
+        });
+    HookObserver::wrap(observer, hooks.map(Arc::new), permission_manager)
+}
+
+/// `dry_run` execution setting
+fn dry_run_setting(agent_db: &DbAgent) -> bool {
+    agent_db
+        .execution_settings
+        .as_ref()
+        .and_then(|s| serde_json::from_str::<serde_json::Value>(s).ok())
+        .and_then(|json| json.get("dry_run").and_then(|v| v.as_bool()))
+        .unwrap_or(false)
+}
+
 pub struct Coordinator {
     pub session_id: String,
     pub agent_db: DbAgent,
@@ -16,7 +51,20 @@ pub struct Coordinator {
     pub permission_manager: Arc<PermissionManager>,
     pub autonomous_pm: Option<Arc<AutonomousPermissionManager>>,
     pub pending_approvals: Arc<dashmap::DashMap<String, oneshot::Sender<bool>>>,
+    /// "fast" (no planning), "planning", "plan_approval" (plan must be approved first),
+    /// or "team" (tasks are delegated to other agents and the results synthesized)
     pub mode: String,
+    /// Upper bound on plan tasks running at once (`max_parallel_tasks` config key,
+    /// which the agent may override)
+    pub max_parallel_tasks: usize,
+    /// Agent bus attached to every worker (None disables inter-agent messaging)
+    pub bus: Option<Arc<crate::bus::AgentBus>>,
+    /// Images sent with the user message passed to `run`
+    pub images: Vec<crate::llm::ImageAttachment>,
+    /// Place in the job queue relative to other waiting jobs
+    pub priority: i32,
+    /// Hold back changes and propose them as a plan instead (see `dry_run`)
+    pub dry_run: bool,
 }
 
 impl Coordinator {
@@ -37,13 +85,18 @@ impl Coordinator {
 
         Self {
             session_id,
+            max_parallel_tasks: crate::config::for_agent(&agent_db).max_parallel_tasks,
+            dry_run: dry_run_setting(&agent_db),
+            observer: with_job_hooks(&agent_db, observer, permission_manager.clone()),
             agent_db,
-            observer,
             db_pool,
             permission_manager,
             autonomous_pm: None,
             pending_approvals,
             mode,
+            bus: None,
+            images: vec![],
+            priority: queue::PRIORITY_INTERACTIVE,
         }
     }
 
@@ -67,16 +120,88 @@ impl Coordinator {
 
         Self {
             session_id,
+            max_parallel_tasks: crate::config::for_agent(&agent_db).max_parallel_tasks,
+            dry_run: dry_run_setting(&agent_db),
+            observer: with_job_hooks(&agent_db, observer, base_pm.clone()),
             agent_db,
-            observer,
             db_pool,
             permission_manager: base_pm,
             autonomous_pm: Some(autonomous_pm),
             pending_approvals,
             mode,
+            bus: None,
+            images: vec![],
+            priority: queue::PRIORITY_INTERACTIVE,
         }
     }
 
+    pub fn with_bus(mut self, bus: Arc<crate::bus::AgentBus>) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    /// Attach images to the message given to `run`. Plan tasks see them through
+    /// the session history, where the caller stored them with the message.
+    pub fn with_images(mut self, images: Vec<crate::llm::ImageAttachment>) -> Self {
+        self.images = images;
+        self
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Wait in the job queue until `job` may start. None when it was
+    /// cancelled while waiting; the job is then reported as cancelled.
+    async fn wait_for_turn(&self, job: &ExecutionJob) -> Option<RunningJob> {
+        let channel = format!("session:{}", self.session_id);
+        let entry = QueuedJob::for_agent(&job.id, &self.agent_db, &self.session_id, &job.query, self.priority);
+        let admitted = queue::global()
+            .admit(entry, |wait| {
+                let event = match wait {
+                    QueueWait::Queued { ahead } => AgentEvent::JobQueued {
+                        job: ExecutionJob { status: "queued".to_string(), ..job.clone() },
+                        position: ahead,
+                    },
+                    QueueWait::Offline { provider } => AgentEvent::JobPending {
+                        job: ExecutionJob { status: "pending".to_string(), ..job.clone() },
+                        provider,
+                    },
+                };
+                let _ = self.observer.emit(&channel, serde_json::to_value(event).unwrap());
+            })
+            .await;
+
+        match admitted {
+            Ok(running) => Some(running),
+            Err(message) => {
+                let cancelled = ExecutionJob { status: "cancelled".to_string(), ..job.clone() };
+                let _ = self.observer.emit(
+                    &channel,
+                    serde_json::to_value(AgentEvent::JobCompleted { job: cancelled, message }).unwrap(),
+                );
+                None
+            }
+        }
+    }
+
+    /// Worker for `agent_db` in this coordinator's session, on `job_id`
+    async fn new_worker(&self, agent_db: &DbAgent, job_id: &str) -> AgentLoop {
+        let mut worker = AgentLoop::new(agent_db, self.db_pool.clone()).await;
+        worker.session_id = self.session_id.clone();
+        worker.dry_run = dry_run::get(job_id);
+        if let Some(bus) = &self.bus {
+            worker.attach_bus(bus.clone());
+        }
+        worker
+    }
+
     /// Get the effective permission manager (autonomous if available, otherwise base)
     fn get_permission_manager(&self) -> Arc<PermissionManager> {
         if let Some(ref _auto_pm) = self.autonomous_pm {
@@ -89,6 +214,17 @@ impl Coordinator {
         }
     }
 
+    /// API key of the agent's provider saved in settings
+    fn api_key(&self) -> Option<String> {
+        let key_name = match self.agent_db.ai_provider.as_str() {
+            "openai" => "OPENAI_API_KEY",
+            "gemini" => "GEMINI_API_KEY",
+            "anthropic" => "ANTHROPIC_API_KEY",
+            _ => "",
+        };
+        crate::models::settings::get_setting(&self.db_pool, key_name)
+    }
+
     /// Check if this coordinator is in autonomous mode
     pub fn is_autonomous(&self) -> bool {
         self.autonomous_pm.as_ref().map(|pm| pm.is_autonomous()).unwrap_or(false)
@@ -96,7 +232,24 @@ impl Coordinator {
 
     pub async fn run(&self, user_message: String) {
         let job_id = Uuid::new_v4().to_string();
+        if !self.dry_run {
+            return self.run_job(job_id, user_message).await;
+        }
+
+        dry_run::start(&job_id, &self.session_id);
+        self.run_job(job_id.clone(), user_message).await;
+        let calls = dry_run::get(&job_id).map(|plan| plan.calls()).unwrap_or_default();
+        if calls.is_empty() {
+            dry_run::take(&job_id);
+            return;
+        }
+        let _ = self.observer.emit(
+            &format!("session:{}", self.session_id),
+            serde_json::to_value(AgentEvent::ChangePlanProposed { job_id, calls }).unwrap(),
+        );
+    }
 
+    async fn run_job(&self, job_id: String, user_message: String) {
         // Notify Job Started
         let job = ExecutionJob {
             id: job_id.clone(),
@@ -106,6 +259,10 @@ impl Coordinator {
             steps: vec![],
             current_step_index: 0,
             created_at: chrono::Utc::now().to_rfc3339(),
+            agent_version: agent_version::current_version(&self.db_pool, &self.agent_db.id),
+        };
+        let Some(_turn) = self.wait_for_turn(&job).await else {
+            return;
         };
         let _ = self.observer.emit(
             &format!("session:{}", self.session_id),
@@ -121,8 +278,12 @@ impl Coordinator {
                 }).unwrap(),
             );
 
-            let mut worker = AgentLoop::new(&self.agent_db, self.db_pool.clone()).await;
-            worker.session_id = self.session_id.clone();
+            let mut worker = self.new_worker(&self.agent_db, &job_id).await;
+            worker.images = self.images.clone();
+
+            let verify_settings = CriticSettings::from_agent(&self.agent_db);
+            let before = verify_settings.as_ref().and_then(|_| worker.snapshot_manager.create_snapshot().ok());
+            let history_len = worker.history.len();
 
             worker
                 .run(
@@ -135,6 +296,27 @@ impl Coordinator {
                 )
                 .await;
 
+            if let (Some(settings), Some(before)) = (verify_settings, before) {
+                let answer = last_answer(&worker.history[history_len.min(worker.history.len())..]).unwrap_or_default();
+                let changes = worker
+                    .snapshot_manager
+                    .create_snapshot()
+                    .map(|after| worker.snapshot_manager.diff_contents(&before, &after))
+                    .unwrap_or_default();
+                if let Some(critique) = self.verify(&settings, &user_message, &[], &answer, &changes).await {
+                    worker
+                        .run(
+                            critic::correction_prompt(&critique),
+                            self.observer.clone(),
+                            job_id.clone(),
+                            self.pending_approvals.clone(),
+                            self.permission_manager.clone(),
+                            self.db_pool.clone(),
+                        )
+                        .await;
+                }
+            }
+
             let _ = self.observer.emit(
                 &format!("session:{}", self.session_id),
                 serde_json::to_value(AgentEvent::JobCompleted {
@@ -157,15 +339,7 @@ impl Coordinator {
             }).unwrap(),
         );
 
-        // Determine API Key
-        let provider = &self.agent_db.ai_provider;
-        let key_name = match provider.as_str() {
-            "openai" => "OPENAI_API_KEY",
-            "gemini" => "GEMINI_API_KEY",
-            "anthropic" => "ANTHROPIC_API_KEY",
-            _ => "",
-        };
-        let api_key = crate::models::settings::get_setting(&self.db_pool, key_name);
+        let api_key = self.api_key();
 
         let router = Router::new(
             self.agent_db.ai_model.clone(),
@@ -176,7 +350,7 @@ impl Coordinator {
         info!("Query classified as: {:?}", query_type);
 
         // SIMPLE QUERY: Use simple chat agent (no tools)
-        if query_type == QueryType::Simple {
+        if query_type == QueryType::Simple && self.mode != "team" {
             let _ = self.observer.emit(
                 &format!("session:{}", self.session_id),
                 serde_json::to_value(AgentEvent::Thinking {
@@ -241,11 +415,14 @@ impl Coordinator {
             }).unwrap(),
         );
 
+        let team = self.load_team();
         let planner = PlanningAgent::new(
             self.agent_db.ai_model.clone(),
             self.agent_db.ai_provider.clone(),
             api_key.clone(),
-        );
+        )
+        .with_team(team.values().map(crate::agents::team::TeamMember::from).collect())
+        .with_prompt_overrides(self.db_pool.clone(), &self.agent_db.id);
 
         let observer_clone = self.observer.clone();
         let session_id_clone = self.session_id.clone();
@@ -258,7 +435,7 @@ impl Coordinator {
         };
 
         // Pass history to planner
-        let plan = match planner.plan(&user_message, &history_context, on_token).await {
+        let mut plan = match planner.plan(&user_message, &history_context, on_token).await {
             Ok(p) => p,
             Err(e) => {
                 let _ = self.observer.emit(
@@ -282,7 +459,127 @@ impl Coordinator {
             }
         };
 
-        let mut plan_update = crate::models::PlanUpdate::from(plan.clone());
+        crate::agents::team::sanitize_assignments(&mut plan, &team);
+
+        // Persist the plan so it can be edited and resumed after an interruption
+        let needs_approval = self.mode == "plan_approval";
+        let plan_id = match crate::models::plan::create_plan(
+            &self.db_pool,
+            &self.session_id,
+            &job_id,
+            &user_message,
+            &plan,
+            if needs_approval { "proposed" } else { "running" },
+        ) {
+            Ok(id) => Some(id),
+            Err(e) => {
+                log::warn!("Failed to persist plan: {}", e);
+                None
+            }
+        };
+        let mut plan_update = crate::models::PlanUpdate::from(plan);
+        plan_update.plan_id = plan_id;
+
+        // Approval gate: block until the user approves (possibly after editing) or rejects
+        if needs_approval && !self.await_plan_approval(&job, &plan_update).await {
+            if let Some(plan_id) = &plan_update.plan_id {
+                let _ = crate::models::plan::set_plan_status(&self.db_pool, plan_id, "rejected");
+            }
+            let _ = self.observer.emit(
+                &format!("session:{}", self.session_id),
+                serde_json::to_value(AgentEvent::JobCompleted {
+                    job: ExecutionJob {
+                        status: "cancelled".to_string(),
+                        ..job.clone()
+                    },
+                    message: "Plan rejected.".to_string(),
+                }).unwrap(),
+            );
+            return;
+        }
+        if let Some(plan_id) = plan_update.plan_id.clone() {
+            let _ = crate::models::plan::set_plan_status(&self.db_pool, &plan_id, "running");
+            if let Ok(latest) = crate::models::plan::get_plan_update(&self.db_pool, &plan_id) {
+                plan_update = latest;
+            }
+        }
+
+        // 3. Execution Phase
+        self.execute_plan(&job, plan_update).await;
+    }
+
+    /// Emit PlanProposed and wait for `approve_plan`. Edits are applied to the
+    /// persisted plan before approval arrives, so execution just re-reads it.
+    async fn await_plan_approval(&self, job: &ExecutionJob, plan_update: &crate::models::PlanUpdate) -> bool {
+        let key = plan_update.plan_id.clone().unwrap_or_else(|| job.id.clone());
+        let (tx, rx) = oneshot::channel();
+        self.pending_approvals.insert(key.clone(), tx);
+
+        let _ = self.observer.emit(
+            &format!("session:{}", self.session_id),
+            serde_json::to_value(AgentEvent::PlanProposed {
+                job: ExecutionJob {
+                    status: "waiting_approval".to_string(),
+                    ..job.clone()
+                },
+                plan: plan_update.clone(),
+            }).unwrap(),
+        );
+
+        let approved = rx.await.unwrap_or(false);
+        self.pending_approvals.remove(&key);
+        approved
+    }
+
+    /// Continue a persisted plan from its first pending task
+    pub async fn resume_plan(&self, plan_id: &str) {
+        let prepared = crate::models::plan::get_plan(&self.db_pool, plan_id).and_then(|record| {
+            crate::models::plan::reset_for_resume(&self.db_pool, plan_id)?;
+            let plan_update = crate::models::plan::get_plan_update(&self.db_pool, plan_id)?;
+            Ok((record, plan_update))
+        });
+
+        let (record, plan_update) = match prepared {
+            Ok(p) => p,
+            Err(e) => {
+                let _ = self.observer.emit(
+                    &format!("session:{}", self.session_id),
+                    serde_json::to_value(AgentEvent::Error {
+                        message: "Failed to resume plan".to_string(),
+                        error: Some(e),
+                    }).unwrap_or(serde_json::Value::Null),
+                );
+                return;
+            }
+        };
+
+        let job = ExecutionJob {
+            id: Uuid::new_v4().to_string(),
+            session_id: self.session_id.clone(),
+            status: "running".to_string(),
+            query: record.objective,
+            steps: vec![],
+            current_step_index: 0,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            agent_version: agent_version::current_version(&self.db_pool, &self.agent_db.id),
+        };
+        let Some(_turn) = self.wait_for_turn(&job).await else {
+            return;
+        };
+        let _ = self.observer.emit(
+            &format!("session:{}", self.session_id),
+            serde_json::to_value(AgentEvent::JobStarted { job: job.clone() }).unwrap(),
+        );
+
+        self.execute_plan(&job, plan_update).await;
+    }
+
+    /// Run the plan as a dependency graph: ready tasks run concurrently (up to
+    /// `max_parallel_tasks`), each receiving its dependencies' results. Persisted
+    /// plans are re-read between tasks, so reordering or skipping takes effect live.
+    async fn execute_plan(&self, job: &ExecutionJob, mut plan_update: crate::models::PlanUpdate) {
+        use futures::stream::{FuturesUnordered, StreamExt};
+
         let _ = self.observer.emit(
             &format!("session:{}", self.session_id),
             serde_json::to_value(AgentEvent::PlanUpdate {
@@ -290,55 +587,173 @@ impl Coordinator {
             }).unwrap(),
         );
 
-        // 3. Execution Phase
-        // Initialize Worker (AgentLoop)
-        // We reuse the same agent loop for sequential tasks to maintain context
-        let mut worker = AgentLoop::new(&self.agent_db, self.db_pool.clone()).await;
-        worker.session_id = self.session_id.clone();
-        
-        // Initialize worker with history
-        // Convert history context string back to messages or load them?
-        // AgentLoop expects Vec<rig::completion::Message>.
-        // Let's reuse the load logic but return Rig messages.
-        worker.history = self.load_rig_history(&self.session_id);
-
-        for (i, task) in plan.tasks.iter().enumerate() {
-            // Update Task Status to Running
-            plan_update.tasks[i].status = "running".to_string();
-            let _ = self.observer.emit(
-                &format!("session:{}", self.session_id),
-                serde_json::to_value(AgentEvent::PlanUpdate {
-                    plan: plan_update.clone(),
-                }).unwrap(),
-            );
+        let team = self.load_team();
 
-            let _ = self.observer.emit(
-                &format!("session:{}", self.session_id),
-                serde_json::to_value(AgentEvent::Thinking {
-                    message: format!("Starting Task: {}", task.description),
-                }).unwrap(),
-            );
+        let verify_settings = CriticSettings::from_agent(&self.agent_db);
+        let snapshots = verify_settings
+            .as_ref()
+            .map(|_| crate::snapshots::SnapshotManager::new(crate::agents::workspace_path(&self.agent_db)));
+        let before = snapshots.as_ref().and_then(|s| s.create_snapshot().ok());
 
-            // Execute the valid task description
-            worker
-                .run(
-                    task.description.clone(),
-                    self.observer.clone(),
-                    job_id.clone(),
-                    self.pending_approvals.clone(),
-                    self.permission_manager.clone(),
-                    self.db_pool.clone(),
-                )
-                .await;
+        // Idle workers (keyed by assignee, None for this agent) are reused so a
+        // linear plan keeps a single conversation context per agent
+        let mut idle_workers: HashMap<Option<String>, Vec<AgentLoop>> = HashMap::new();
+        let mut in_flight = FuturesUnordered::new();
+        let mut started = std::collections::HashSet::new();
+
+        loop {
+            if let Some(plan_id) = &plan_update.plan_id {
+                if let Ok(latest) = crate::models::plan::get_plan_update(&self.db_pool, plan_id) {
+                    plan_update = latest;
+                }
+            }
+
+            let ready: Vec<crate::models::TaskState> = crate::agents::dag::ready_tasks(&plan_update.tasks, &started)
+                .into_iter()
+                .take(self.max_parallel_tasks.saturating_sub(in_flight.len()))
+                .cloned()
+                .collect();
+
+            for task in ready {
+                started.insert(task.id.clone());
+                let prompt = crate::agents::dag::task_prompt(&plan_update.tasks, &task);
+
+                // Update Task Status to Running
+                self.update_task(&mut plan_update, &task.id, "running", None);
+
+                let _ = self.observer.emit(
+                    &format!("session:{}", self.session_id),
+                    serde_json::to_value(AgentEvent::Thinking {
+                        message: format!("Starting Task: {}", task.description),
+                    }).unwrap(),
+                );
+
+                // Assigned tasks run as child jobs with the member's own model,
+                // tools and workspace, streaming into this session's channel
+                let member = task.assignee.as_ref().and_then(|id| team.get(id)).cloned();
+                let assignee = member.as_ref().map(|m| m.id.clone());
+                let mut worker = match idle_workers.get_mut(&assignee).and_then(|w| w.pop()) {
+                    Some(worker) => worker,
+                    None => {
+                        let agent_db = member.as_ref().unwrap_or(&self.agent_db);
+                        let mut worker = self.new_worker(agent_db, &job.id).await;
+                        if member.is_none() {
+                            worker.reload_history(&self.db_pool);
+                        }
+                        worker
+                    }
+                };
+
+                let child_job = member.as_ref().map(|m| {
+                    let child = ExecutionJob {
+                        id: Uuid::new_v4().to_string(),
+                        session_id: self.session_id.clone(),
+                        status: "running".to_string(),
+                        query: prompt.clone(),
+                        steps: vec![],
+                        current_step_index: 0,
+                        created_at: chrono::Utc::now().to_rfc3339(),
+                        agent_version: agent_version::current_version(&self.db_pool, &m.id),
+                    };
+                    let _ = self.observer.emit(
+                        &format!("session:{}", self.session_id),
+                        serde_json::to_value(AgentEvent::ChildJobStarted {
+                            parent_job_id: job.id.clone(),
+                            job: child.clone(),
+                            agent_id: m.id.clone(),
+                            agent_name: m.name.clone(),
+                        }).unwrap(),
+                    );
+                    child
+                });
+
+                let observer = self.observer.clone();
+                let job_id = child_job.as_ref().map(|c| c.id.clone()).unwrap_or_else(|| job.id.clone());
+                let pending_approvals = self.pending_approvals.clone();
+                let permission_manager = self.permission_manager.clone();
+                let db_pool = self.db_pool.clone();
+                in_flight.push(async move {
+                    let history_len = worker.history.len();
+                    worker
+                        .run(prompt, observer, job_id, pending_approvals, permission_manager, db_pool)
+                        .await;
+
+                    let result = last_answer(&worker.history[history_len.min(worker.history.len())..]);
+                    (task.id, assignee, child_job, worker, result)
+                });
+            }
+
+            let Some((task_id, assignee, child_job, worker, result)) = in_flight.next().await else {
+                break;
+            };
+
+            if let (Some(child), Some(agent_id)) = (child_job, assignee.clone()) {
+                let _ = self.observer.emit(
+                    &format!("session:{}", self.session_id),
+                    serde_json::to_value(AgentEvent::ChildJobCompleted {
+                        parent_job_id: job.id.clone(),
+                        job: ExecutionJob {
+                            status: "completed".to_string(),
+                            ..child
+                        },
+                        agent_id,
+                        result: result.clone(),
+                    }).unwrap(),
+                );
+            }
+            idle_workers.entry(assignee).or_default().push(worker);
 
             // Update Task Status to Completed
-            plan_update.tasks[i].status = "completed".to_string();
-            let _ = self.observer.emit(
-                &format!("session:{}", self.session_id),
-                serde_json::to_value(AgentEvent::PlanUpdate {
-                    plan: plan_update.clone(),
-                }).unwrap(),
-            );
+            self.update_task(&mut plan_update, &task_id, "completed", result);
+        }
+
+        // Anything still pending is waiting on a dependency that never completed
+        let blocked = plan_update.tasks.iter().filter(|t| t.status == "pending").count();
+        let (plan_status, mut message) = if blocked == 0 && self.mode == "team" {
+            ("completed", self.synthesize(&job.query, &plan_update, &team).await)
+        } else if blocked == 0 {
+            ("completed", "All tasks executed.".to_string())
+        } else {
+            ("failed", format!("{} task(s) could not run because their dependencies did not complete.", blocked))
+        };
+
+        if let (Some(settings), Some(snapshots), Some(before), "completed") =
+            (&verify_settings, &snapshots, &before, plan_status)
+        {
+            let changes = snapshots
+                .create_snapshot()
+                .map(|after| snapshots.diff_contents(before, &after))
+                .unwrap_or_default();
+            if let Some(critique) = self.verify(settings, &job.query, &plan_update.tasks, &message, &changes).await {
+                let mut worker = match idle_workers.get_mut(&None).and_then(|w| w.pop()) {
+                    Some(worker) => worker,
+                    None => {
+                        let mut worker = self.new_worker(&self.agent_db, &job.id).await;
+                        worker.reload_history(&self.db_pool);
+                        worker
+                    }
+                };
+                let history_len = worker.history.len();
+                worker
+                    .run(
+                        critic::correction_prompt(&critique),
+                        self.observer.clone(),
+                        job.id.clone(),
+                        self.pending_approvals.clone(),
+                        self.permission_manager.clone(),
+                        self.db_pool.clone(),
+                    )
+                    .await;
+                if let Some(answer) = last_answer(&worker.history[history_len.min(worker.history.len())..]) {
+                    message = answer;
+                }
+            }
+        }
+
+        if let Some(plan_id) = &plan_update.plan_id {
+            if let Err(e) = crate::models::plan::set_plan_status(&self.db_pool, plan_id, plan_status) {
+                log::warn!("Failed to update plan status: {}", e);
+            }
         }
 
         // Finalize
@@ -346,10 +761,124 @@ impl Coordinator {
             &format!("session:{}", self.session_id),
             serde_json::to_value(AgentEvent::JobCompleted {
                 job: ExecutionJob {
-                    status: "completed".to_string(),
+                    status: plan_status.to_string(),
                     ..job.clone()
                 },
-                message: "All tasks executed.".to_string(),
+                message,
+            }).unwrap(),
+        );
+    }
+
+    /// Have the critic check the job's work. Returns the critique to act on
+    /// when it finds the work incomplete; a failed review lets the job finish.
+    async fn verify(
+        &self,
+        settings: &CriticSettings,
+        request: &str,
+        tasks: &[crate::models::TaskState],
+        answer: &str,
+        changes: &[crate::snapshots::FileChange],
+    ) -> Option<String> {
+        let _ = self.observer.emit(
+            &format!("session:{}", self.session_id),
+            serde_json::to_value(AgentEvent::Thinking {
+                message: "Checking the work...".to_string(),
+            }).unwrap(),
+        );
+
+        let provider = &self.agent_db.ai_provider;
+        let model = settings
+            .model
+            .clone()
+            .unwrap_or_else(|| crate::llm::LlmClient::fast_model(provider).to_string());
+        let review = critic::Review { request, tasks, answer, changes };
+        let verdict = match critic::review(provider, &model, self.api_key().as_deref(), &self.session_id, &review).await {
+            Ok(verdict) => verdict,
+            Err(e) => {
+                log::warn!("Verification failed: {}", e);
+                return None;
+            }
+        };
+
+        let _ = self.observer.emit(
+            &format!("session:{}", self.session_id),
+            serde_json::to_value(AgentEvent::Verification {
+                complete: verdict.complete,
+                critique: verdict.critique.clone(),
+                retrying: !verdict.complete,
+            }).unwrap(),
+        );
+        (!verdict.complete).then_some(verdict.critique)
+    }
+
+    /// Agents this coordinator may delegate to, keyed by id (empty outside team mode)
+    fn load_team(&self) -> HashMap<String, DbAgent> {
+        if self.mode != "team" {
+            return HashMap::new();
+        }
+        crate::agents::team::load_team(&self.db_pool, &self.agent_db)
+            .into_iter()
+            .map(|agent| (agent.id.clone(), agent))
+            .collect()
+    }
+
+    /// Have the lead combine the team's task results into the final answer
+    async fn synthesize(
+        &self,
+        objective: &str,
+        plan_update: &crate::models::PlanUpdate,
+        team: &HashMap<String, DbAgent>,
+    ) -> String {
+        let _ = self.observer.emit(
+            &format!("session:{}", self.session_id),
+            serde_json::to_value(AgentEvent::Thinking {
+                message: "Combining results from the team...".to_string(),
+            }).unwrap(),
+        );
+
+        let prompt = crate::agents::team::synthesis_prompt(objective, &plan_update.tasks, team);
+        match SimpleChatAgent::new(&self.agent_db)
+            .chat(&prompt, &self.session_id, self.observer.clone(), &self.db_pool)
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                log::warn!("Team synthesis failed: {}", e);
+                "All tasks executed.".to_string()
+            }
+        }
+    }
+
+    /// Record a task state change (in the DB when persisted) and emit PlanUpdate
+    fn update_task(
+        &self,
+        plan_update: &mut crate::models::PlanUpdate,
+        task_id: &str,
+        status: &str,
+        result: Option<String>,
+    ) {
+        let Some(task) = plan_update.tasks.iter_mut().find(|t| t.id == task_id) else {
+            return;
+        };
+        task.status = status.to_string();
+        task.result = result;
+
+        if let Some(plan_id) = &plan_update.plan_id {
+            if let Err(e) = crate::models::plan::set_task_status(
+                &self.db_pool,
+                plan_id,
+                &task.id,
+                status,
+                task.result.as_deref(),
+            ) {
+                log::warn!("Failed to persist task state: {}", e);
+            }
+        }
+
+        let _ = self.observer.emit(
+            &format!("session:{}", self.session_id),
+            serde_json::to_value(AgentEvent::PlanUpdate {
+                plan: plan_update.clone(),
             }).unwrap(),
         );
     }
@@ -364,19 +893,6 @@ impl Coordinator {
         context
     }
 
-    fn load_rig_history(&self, session_id: &str) -> Vec<rig::completion::Message> {
-        let messages = self.load_messages(session_id, 20); // More history for execution
-        let mut history = vec![];
-        for msg in messages {
-             match msg.role.as_str() {
-                "user" => history.push(rig::completion::Message::user(&msg.content)),
-                "assistant" | "model" => history.push(rig::completion::Message::assistant(&msg.content)),
-                _ => {}
-            }
-        }
-        history
-    }
-
     fn load_messages(&self, session_id: &str, limit: i64) -> Vec<crate::models::Message> {
         use crate::schema::messages;
         use diesel::prelude::*;
@@ -384,6 +900,7 @@ impl Coordinator {
         if let Ok(mut conn) = self.db_pool.get() {
             messages::table
                 .filter(messages::session_id.eq(session_id))
+                .filter(messages::deleted_at.is_null())
                 .order(messages::created_at.desc()) // Load most recent
                 .limit(limit)
                 .load::<crate::models::Message>(&mut conn)
@@ -397,3 +914,12 @@ impl Coordinator {
         }
     }
 }
+
+/// Text of the last assistant message in `history`
+fn last_answer(history: &[rig::completion::Message]) -> Option<String> {
+    history
+        .iter()
+        .rev()
+        .find(|m| matches!(m, rig::completion::Message::Assistant { .. }))
+        .map(crate::agents::optimizations::get_message_content)
+}
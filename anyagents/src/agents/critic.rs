@@ -0,0 +1,180 @@
+//! Verification pass before a job finishes
+//!
+//! With `"verify": true` in an agent's execution settings (or
+//! `"verify": {"model": "..."}` to pick the critic's model), the coordinator
+//! shows a cheap model the original request, the plan, the final answer and
+//! what changed in the workspace, and asks whether the task was actually
+//! done. If not, the critique goes back to the agent for one corrective run
+//! before the job completes. The verdict is emitted as a `verification`
+//! event either way.
+
+use crate::llm::LlmClient;
+use crate::models::{Agent as DbAgent, TaskState};
+use crate::snapshots::FileChange;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Characters of the final answer and of each task result the critic sees
+const MAX_ANSWER_CHARS: usize = 8_000;
+const MAX_TASK_RESULT_CHARS: usize = 1_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CriticSettings {
+    /// Model of the agent's provider; the provider's fast model when unset
+    pub model: Option<String>,
+}
+
+impl CriticSettings {
+    pub fn from_agent(agent_db: &DbAgent) -> Option<Self> {
+        agent_db
+            .execution_settings
+            .as_ref()
+            .and_then(|s| serde_json::from_str::<Value>(s).ok())
+            .and_then(|settings| Self::from_execution_settings(&settings))
+    }
+
+    /// `verify` of an agent's execution settings; None when verification is off
+    pub fn from_execution_settings(settings: &Value) -> Option<Self> {
+        match settings.get("verify")? {
+            Value::Bool(true) => Some(Self { model: None }),
+            Value::Object(options) => Some(Self {
+                model: options.get("model").and_then(|m| m.as_str()).map(String::from),
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct Verdict {
+    /// Whether everything the user asked for was done
+    pub complete: bool,
+    /// What is missing or wrong, specific enough to act on; empty when complete
+    pub critique: String,
+}
+
+/// What the critic is shown
+pub struct Review<'a> {
+    pub request: &'a str,
+    pub tasks: &'a [TaskState],
+    pub answer: &'a str,
+    pub changes: &'a [FileChange],
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}… (truncated)", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+pub fn critic_prompt(review: &Review) -> String {
+    let mut prompt = format!("## Request\n{}\n", review.request);
+
+    if !review.tasks.is_empty() {
+        prompt.push_str("\n## Plan\n");
+        for task in review.tasks {
+            prompt.push_str(&format!("- [{}] {}\n", task.status, task.description));
+            if let Some(result) = &task.result {
+                prompt.push_str(&format!("  Result: {}\n", truncate(result, MAX_TASK_RESULT_CHARS)));
+            }
+        }
+    }
+
+    prompt.push_str(&format!("\n## Final answer\n{}\n", truncate(review.answer, MAX_ANSWER_CHARS)));
+
+    prompt.push_str("\n## Workspace changes\n");
+    if review.changes.is_empty() {
+        prompt.push_str("No files changed.\n");
+    }
+    for change in review.changes {
+        prompt.push_str(&format!("- {} ({:?})\n", change.path, change.kind));
+        if let Some(diff) = &change.diff {
+            prompt.push_str(&format!("```diff\n{}\n```\n", diff));
+        }
+    }
+    prompt
+}
+
+const CRITIC_PREAMBLE: &str = "You review the work of an AI agent. Decide whether the agent fully \
+did what the request asked, judging by its final answer and the files it changed. Don't ask for \
+improvements beyond the request. If something is missing or wrong, say exactly what.";
+
+/// Ask the critic model for a verdict
+pub async fn review(
+    provider: &str,
+    model: &str,
+    api_key: Option<&str>,
+    session_id: &str,
+    work: &Review<'_>,
+) -> Result<Verdict, String> {
+    let mut client = LlmClient::new(provider, model)
+        .with_preamble(CRITIC_PREAMBLE)
+        .with_session(session_id);
+    if let Some(key) = api_key {
+        client = client.with_api_key(key);
+    }
+    client.prompt_structured::<Verdict>(&critic_prompt(work)).await
+}
+
+/// The message of the corrective run
+pub fn correction_prompt(critique: &str) -> String {
+    format!(
+        "A review found that the task is not finished yet:\n\n{}\n\nFix this now, then give your final answer.",
+        critique
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshots::changes::ChangeKind;
+    use serde_json::json;
+
+    #[test]
+    fn test_critic_settings() {
+        let settings = |json: Value| CriticSettings::from_execution_settings(&json);
+        assert_eq!(settings(json!({"verify": true})), Some(CriticSettings { model: None }));
+        assert_eq!(
+            settings(json!({"verify": {"model": "gpt-4o-mini"}})),
+            Some(CriticSettings { model: Some("gpt-4o-mini".to_string()) })
+        );
+        assert_eq!(settings(json!({"verify": false})), None);
+        assert_eq!(settings(json!({})), None);
+    }
+
+    #[test]
+    fn test_critic_prompt_shows_plan_answer_and_changes() {
+        let tasks = vec![TaskState {
+            id: "1".to_string(),
+            description: "Write the README".to_string(),
+            status: "completed".to_string(),
+            result: Some("x".repeat(5_000)),
+            depends_on: vec![],
+            assignee: None,
+        }];
+        let changes = vec![FileChange {
+            path: "README.md".to_string(),
+            kind: ChangeKind::Added,
+            binary: false,
+            size: Some(12),
+            diff: Some("+# Project".to_string()),
+            truncated: false,
+        }];
+        let prompt = critic_prompt(&Review {
+            request: "Add a README and a LICENSE",
+            tasks: &tasks,
+            answer: "Added both files.",
+            changes: &changes,
+        });
+        assert!(prompt.contains("- [completed] Write the README"));
+        assert!(prompt.contains("(truncated)"));
+        assert!(prompt.contains("- README.md (Added)"));
+        assert!(prompt.contains("+# Project"));
+
+        let empty = critic_prompt(&Review { request: "Hi", tasks: &[], answer: "Hello", changes: &[] });
+        assert!(!empty.contains("## Plan"));
+        assert!(empty.contains("No files changed."));
+    }
+}
@@ -0,0 +1,168 @@
+//! Dependency-graph helpers for plan execution
+//!
+//! Tasks declare `depends_on`; a task becomes ready once every dependency is
+//! completed (or skipped by the user). Independent tasks may run concurrently.
+
+use crate::models::{Plan, TaskState};
+use std::collections::{HashMap, HashSet};
+
+/// Longest dependency result passed on to a downstream task
+const MAX_UPSTREAM_RESULT_CHARS: usize = 2000;
+
+/// Reject plans with duplicate ids, unknown dependencies or cycles
+pub fn validate_plan(plan: &Plan) -> Result<(), String> {
+    let ids: HashSet<&str> = plan.tasks.iter().map(|t| t.id.as_str()).collect();
+    if ids.len() != plan.tasks.len() {
+        return Err("Task ids must be unique".to_string());
+    }
+
+    for task in &plan.tasks {
+        for dep in &task.depends_on {
+            if !ids.contains(dep.as_str()) {
+                return Err(format!("Task '{}' depends on unknown task '{}'", task.id, dep));
+            }
+            if dep == &task.id {
+                return Err(format!("Task '{}' depends on itself", task.id));
+            }
+        }
+    }
+
+    // Kahn's algorithm: every task must eventually have no unfinished dependencies
+    let mut remaining: HashMap<&str, usize> = plan
+        .tasks
+        .iter()
+        .map(|t| (t.id.as_str(), t.depends_on.iter().collect::<HashSet<_>>().len()))
+        .collect();
+    let mut queue: Vec<&str> = remaining
+        .iter()
+        .filter(|(_, n)| **n == 0)
+        .map(|(id, _)| *id)
+        .collect();
+    let mut visited = 0;
+    while let Some(id) = queue.pop() {
+        visited += 1;
+        for task in plan.tasks.iter().filter(|t| t.depends_on.iter().any(|d| d == id)) {
+            let count = remaining.get_mut(task.id.as_str()).expect("task id present");
+            *count -= 1;
+            if *count == 0 {
+                queue.push(task.id.as_str());
+            }
+        }
+    }
+
+    if visited != plan.tasks.len() {
+        return Err("Plan dependencies contain a cycle".to_string());
+    }
+    Ok(())
+}
+
+fn is_satisfied(status: &str) -> bool {
+    status == "completed" || status == "skipped"
+}
+
+/// Pending tasks (in plan order) whose dependencies are all satisfied
+pub fn ready_tasks<'a>(tasks: &'a [TaskState], started: &HashSet<String>) -> Vec<&'a TaskState> {
+    let status: HashMap<&str, &str> = tasks
+        .iter()
+        .map(|t| (t.id.as_str(), t.status.as_str()))
+        .collect();
+
+    tasks
+        .iter()
+        .filter(|t| t.status == "pending" && !started.contains(&t.id))
+        .filter(|t| {
+            t.depends_on
+                .iter()
+                .all(|dep| status.get(dep.as_str()).is_some_and(|s| is_satisfied(s)))
+        })
+        .collect()
+}
+
+/// Task prompt with the results of its dependencies prepended (fan-in)
+pub fn task_prompt(tasks: &[TaskState], task: &TaskState) -> String {
+    let upstream: Vec<String> = task
+        .depends_on
+        .iter()
+        .filter_map(|dep| tasks.iter().find(|t| &t.id == dep))
+        .filter_map(|dep| {
+            let result = dep.result.as_deref()?;
+            let result: String = result.chars().take(MAX_UPSTREAM_RESULT_CHARS).collect();
+            Some(format!("[{}] {}\n{}", dep.id, dep.description, result))
+        })
+        .collect();
+
+    if upstream.is_empty() {
+        return task.description.clone();
+    }
+    format!(
+        "Results from prerequisite tasks:\n\n{}\n\nYour task: {}",
+        upstream.join("\n\n"),
+        task.description
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TaskSpec;
+
+    fn spec(id: &str, deps: &[&str]) -> TaskSpec {
+        TaskSpec {
+            id: id.to_string(),
+            description: format!("Task {}", id),
+            depends_on: deps.iter().map(|d| d.to_string()).collect(),
+            assignee: None,
+        }
+    }
+
+    fn state(id: &str, status: &str, deps: &[&str]) -> TaskState {
+        TaskState {
+            id: id.to_string(),
+            description: format!("Task {}", id),
+            status: status.to_string(),
+            result: (status == "completed").then(|| format!("result of {}", id)),
+            depends_on: deps.iter().map(|d| d.to_string()).collect(),
+            assignee: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_plan() {
+        let ok = Plan {
+            tasks: vec![spec("a", &[]), spec("b", &[]), spec("c", &["a", "b"])],
+        };
+        assert!(validate_plan(&ok).is_ok());
+
+        let cycle = Plan {
+            tasks: vec![spec("a", &["b"]), spec("b", &["a"])],
+        };
+        assert!(validate_plan(&cycle).unwrap_err().contains("cycle"));
+
+        let unknown = Plan {
+            tasks: vec![spec("a", &["missing"])],
+        };
+        assert!(validate_plan(&unknown).is_err());
+    }
+
+    #[test]
+    fn test_fan_out_then_fan_in() {
+        let mut tasks = vec![
+            state("a", "pending", &[]),
+            state("b", "pending", &[]),
+            state("c", "pending", &["a", "b"]),
+        ];
+        let started = HashSet::new();
+
+        let ready: Vec<&str> = ready_tasks(&tasks, &started).iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ready, vec!["a", "b"]);
+
+        tasks[0] = state("a", "completed", &[]);
+        tasks[1] = state("b", "skipped", &[]);
+        let ready: Vec<&str> = ready_tasks(&tasks, &started).iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ready, vec!["c"]);
+
+        let prompt = task_prompt(&tasks, &tasks[2]);
+        assert!(prompt.contains("result of a"));
+        assert!(prompt.ends_with("Your task: Task c"));
+    }
+}
@@ -0,0 +1,300 @@
+//! Dry runs: the whole agent loop, minus the side effects
+//!
+//! A coordinator with `dry_run` set (the `dry_run` execution setting, or
+//! `Coordinator::with_dry_run`) starts a `ChangePlan` for the job. Its workers run every
+//! tool call through `DryRunMiddleware`: read-only calls go through, calls
+//! that would change something (writes, bash commands that aren't read-only,
+//! HTTP POSTs, emails...) are recorded in the plan and answered with a
+//! simulated result, so the model carries on as if they had succeeded.
+//!
+//! At the end of the job the plan goes out as `AgentEvent::ChangePlanProposed`.
+//! The user either approves it (`apply`, which makes the recorded calls for
+//! real, in order) or discards it. Plans live in memory until then; the
+//! oldest are dropped past `MAX_PLANS`.
+
+use crate::agents::AgentLoop;
+use crate::events::{AgentEvent, AgentObserver, ExecutionJob, ExecutionStep};
+use crate::permissions::PermissionManager;
+use crate::tools::middleware::{Next, ToolMiddleware};
+use crate::tools::{Tool, ToolContext, ToolRunError};
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex, MutexGuard};
+
+const MAX_PLANS: usize = 50;
+const MAX_SUMMARY_CHARS: usize = 200;
+
+/// A tool call a dry run held back
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct PlannedCall {
+    /// Agent whose tools make the call (team members plan calls too)
+    pub agent_id: String,
+    pub tool: String,
+    #[cfg_attr(feature = "ts", ts(type = "unknown"))]
+    pub args: Value,
+    /// "Would ..." line for the UI
+    pub summary: String,
+}
+
+pub struct ChangePlan {
+    pub job_id: String,
+    pub session_id: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+    calls: Mutex<Vec<PlannedCall>>,
+}
+
+impl ChangePlan {
+    pub fn calls(&self) -> Vec<PlannedCall> {
+        self.calls.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    fn record(&self, call: PlannedCall) {
+        self.calls.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(call);
+    }
+}
+
+static PLANS: LazyLock<Mutex<HashMap<String, Arc<ChangePlan>>>> = LazyLock::new(Default::default);
+
+fn plans() -> MutexGuard<'static, HashMap<String, Arc<ChangePlan>>> {
+    PLANS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Start collecting the plan of `job_id`
+pub fn start(job_id: &str, session_id: &str) -> Arc<ChangePlan> {
+    let plan = Arc::new(ChangePlan {
+        job_id: job_id.to_string(),
+        session_id: session_id.to_string(),
+        created_at: chrono::Utc::now(),
+        calls: Mutex::default(),
+    });
+    let mut plans = plans();
+    while plans.len() >= MAX_PLANS {
+        let Some(oldest) = plans.values().min_by_key(|p| p.created_at).map(|p| p.job_id.clone()) else {
+            break;
+        };
+        plans.remove(&oldest);
+    }
+    plans.insert(job_id.to_string(), plan.clone());
+    plan
+}
+
+/// The plan of `job_id`, when that job is (or was) a dry run not yet decided on
+pub fn get(job_id: &str) -> Option<Arc<ChangePlan>> {
+    plans().get(job_id).cloned()
+}
+
+/// Forget the plan of `job_id`, returning it
+pub fn take(job_id: &str) -> Option<Arc<ChangePlan>> {
+    plans().remove(job_id)
+}
+
+/// "Would ..." line for a held-back call
+pub fn summarize(tool: &str, args: &Value) -> String {
+    let summary = match tool {
+        "bash" => format!("run `{}`", args["command"].as_str().unwrap_or_default()),
+        "filesystem" => format!(
+            "{} {}",
+            args["operation"].as_str().unwrap_or("change").replace('_', " "),
+            args["path"].as_str().unwrap_or_default()
+        ),
+        "http" => format!(
+            "send {} {}",
+            args["method"].as_str().unwrap_or("GET").to_uppercase(),
+            args["url"].as_str().unwrap_or_default()
+        ),
+        tool => format!("call {} with {}", tool, args),
+    };
+    match summary.chars().count() > MAX_SUMMARY_CHARS {
+        true => format!("Would {}…", summary.chars().take(MAX_SUMMARY_CHARS).collect::<String>()),
+        false => format!("Would {}", summary),
+    }
+}
+
+/// Holds back calls that would change something (see the module docs)
+pub struct DryRunMiddleware {
+    plan: Arc<ChangePlan>,
+    agent_id: String,
+}
+
+impl DryRunMiddleware {
+    pub fn new(plan: Arc<ChangePlan>, agent_id: &str) -> Self {
+        Self { plan, agent_id: agent_id.to_string() }
+    }
+}
+
+#[async_trait]
+impl ToolMiddleware for DryRunMiddleware {
+    fn name(&self) -> &str {
+        "dry_run"
+    }
+
+    async fn handle(
+        &self,
+        tool: &dyn Tool,
+        args: Value,
+        ctx: &ToolContext,
+        next: Next<'_>,
+    ) -> Result<Value, ToolRunError> {
+        if tool.is_read_only(&args) {
+            return next.run(tool, args, ctx).await;
+        }
+        let summary = summarize(tool.name(), &args);
+        self.plan.record(PlannedCall {
+            agent_id: self.agent_id.clone(),
+            tool: tool.name().to_string(),
+            args,
+            summary: summary.clone(),
+        });
+        Ok(json!({
+            "status": "dry_run",
+            "message": format!(
+                "{}. This is a dry run: nothing was changed. Continue as if it succeeded.",
+                summary
+            ),
+        }))
+    }
+}
+
+/// Outcome of one approved call
+#[derive(Serialize, Debug, Clone)]
+pub struct AppliedCall {
+    pub tool: String,
+    pub summary: String,
+    pub success: bool,
+    pub result: Value,
+}
+
+/// Make the calls of `plan` for real, in order, with the tools of `workers`
+/// (by agent id). Stops at the first call that fails. Progress is emitted as
+/// a job of its own on the session channel.
+pub async fn apply(
+    plan: &ChangePlan,
+    workers: &HashMap<String, AgentLoop>,
+    permissions: Arc<PermissionManager>,
+    observer: Arc<dyn AgentObserver>,
+) -> Vec<AppliedCall> {
+    let channel = format!("session:{}", plan.session_id);
+    let job = ExecutionJob {
+        id: uuid::Uuid::new_v4().to_string(),
+        session_id: plan.session_id.clone(),
+        status: "running".to_string(),
+        query: format!("Apply the changes planned by job {}", plan.job_id),
+        steps: vec![],
+        current_step_index: 0,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        agent_version: None,
+    };
+    let emit = |event: AgentEvent| {
+        let _ = observer.emit(&channel, serde_json::to_value(event).unwrap_or(Value::Null));
+    };
+    emit(AgentEvent::JobStarted { job: job.clone() });
+
+    let mut applied = vec![];
+    for call in plan.calls() {
+        let step = ExecutionStep {
+            id: uuid::Uuid::new_v4().to_string(),
+            tool_name: call.tool.clone(),
+            tool_args: call.args.clone(),
+            status: "executing".to_string(),
+            result: None,
+            requires_approval: false,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        };
+        emit(AgentEvent::StepStarted { job: job.clone(), step: step.clone() });
+        let worker = workers.get(&call.agent_id);
+        let (success, result) = match worker.and_then(|w| w.tools.get(&call.tool).map(|tool| (w, tool))) {
+            Some((worker, tool)) => {
+                let ctx = ToolContext {
+                    permissions: permissions.clone(),
+                    observer: Some(observer.clone()),
+                    session_id: plan.session_id.clone(),
+                    scope: worker.scope.clone(),
+                    job_id: Some(job.id.clone()),
+                };
+                match worker.middleware.execute(tool.as_ref(), call.args.clone(), &ctx).await {
+                    Ok(result) => (tool.verify_result(&result), result),
+                    Err(e) => (false, e.to_result(&call.tool)),
+                }
+            }
+            None => (false, json!({ "error": format!("Tool '{}' is no longer available", call.tool) })),
+        };
+        emit(AgentEvent::StepCompleted {
+            job: job.clone(),
+            step: ExecutionStep {
+                status: if success { "completed" } else { "failed" }.to_string(),
+                result: Some(result.to_string()),
+                ..step
+            },
+        });
+        applied.push(AppliedCall { tool: call.tool, summary: call.summary, success, result });
+        if !success {
+            break;
+        }
+    }
+
+    let failed = applied.iter().any(|call| !call.success);
+    let message = match failed {
+        false => format!("Applied {} planned change(s).", applied.len()),
+        true => format!("Stopped after {} of {} planned change(s): the last one failed.", applied.len(), plan.calls().len()),
+    };
+    emit(AgentEvent::JobCompleted {
+        job: ExecutionJob { status: if failed { "failed" } else { "completed" }.to_string(), ..job },
+        message,
+    });
+    applied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::permissions::ScopeEnforcer;
+    use crate::tools::middleware::ToolMiddlewareChain;
+
+    struct Writer;
+
+    #[async_trait]
+    impl Tool for Writer {
+        fn name(&self) -> &str {
+            "filesystem"
+        }
+        fn description(&self) -> &str {
+            "Writes files"
+        }
+        fn parameters_schema(&self) -> Value {
+            json!({ "type": "object" })
+        }
+        fn is_read_only(&self, args: &Value) -> bool {
+            args["operation"] == "read_file"
+        }
+        async fn execute(&self, args: Value, _ctx: &ToolContext) -> Result<Value, String> {
+            Ok(json!({ "done": args["operation"] }))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_holds_back_changes() {
+        let plan = start("job-dry", "s1");
+        let chain = ToolMiddlewareChain::default().with(DryRunMiddleware::new(plan.clone(), "a1"));
+        let ctx = ToolContext {
+            permissions: Arc::new(PermissionManager::new()),
+            observer: None,
+            session_id: "s1".to_string(),
+            scope: ScopeEnforcer::global(),
+            job_id: Some("job-dry".to_string()),
+        };
+
+        let read = chain.execute(&Writer, json!({ "operation": "read_file", "path": "a.txt" }), &ctx).await.unwrap();
+        assert_eq!(read["done"], "read_file");
+        let write = chain.execute(&Writer, json!({ "operation": "write_file", "path": "a.txt" }), &ctx).await.unwrap();
+        assert_eq!(write["status"], "dry_run");
+
+        let calls = get("job-dry").unwrap().calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].summary, "Would write file a.txt");
+        assert!(take("job-dry").is_some());
+        assert!(get("job-dry").is_none());
+    }
+}
@@ -0,0 +1,481 @@
+//! User-defined agent hooks
+//!
+//! An agent's `hooks` execution setting lists commands or skills to run at
+//! points of a job:
+//!
+//! ```json
+//! "hooks": [
+//!   { "event": "pre_tool_call", "tools": ["bash"], "command": "./guard.sh" },
+//!   { "event": "post_tool_call", "tools": ["filesystem"], "command": "npm run lint --silent" },
+//!   { "event": "on_job_complete", "command": "git commit -am 'Agent run' || true" }
+//! ]
+//! ```
+//!
+//! A command runs with `sh -c` in the agent's workspace and gets the hook's
+//! context (event, agent, session, job, tool call...) as JSON on stdin. A
+//! skill is run as a tool with the hook's `args`, and the context in its
+//! `hook` argument when its parameters declare one.
+//!
+//! What a hook prints (or a skill returns) may be a `HookResponse`:
+//! `{"decision": "deny", "reason": "..."}` vetoes the tool call or LLM
+//! request, `{"args": {...}}` replaces the arguments of the tool call and
+//! `{"result": ...}` the result it returned. A pre hook that exits non-zero,
+//! fails or times out vetoes too; post hooks only log failures.
+
+use crate::events::{AgentObserver, ExecutionJob};
+use crate::permissions::{PermissionManager, ScopeEnforcer};
+use crate::tools::registry::{self, ToolRegistry};
+use crate::tools::{Tool, ToolContext};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+/// Hook output kept in logs and veto reasons
+const MAX_OUTPUT_CHARS: usize = 2_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEvent {
+    /// Before a tool runs; may veto the call or change its arguments
+    PreToolCall,
+    /// After a tool ran; may replace its result
+    PostToolCall,
+    /// Before each request to the model; may veto it, ending the job
+    PreLlm,
+    /// After the job finished, whatever its status
+    OnJobComplete,
+}
+
+impl HookEvent {
+    fn is_pre(self) -> bool {
+        matches!(self, HookEvent::PreToolCall | HookEvent::PreLlm)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Hook {
+    pub event: HookEvent,
+    /// Shell command, run in the workspace with the context on stdin
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Skill (or other tool of the agent) run instead of a command
+    #[serde(default)]
+    pub skill: Option<String>,
+    /// Arguments of `skill`
+    #[serde(default)]
+    pub args: Option<Value>,
+    /// Tool calls the hook applies to; all when empty
+    #[serde(default)]
+    pub tools: Vec<String>,
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
+}
+
+impl Hook {
+    fn validate(&self) -> Result<(), String> {
+        match (&self.command, &self.skill) {
+            (Some(command), None) if !command.trim().is_empty() => Ok(()),
+            (None, Some(skill)) if !skill.trim().is_empty() => Ok(()),
+            _ => Err("A hook needs either a command or a skill".to_string()),
+        }
+    }
+
+    fn applies_to(&self, event: HookEvent, tool: Option<&str>) -> bool {
+        self.event == event && (self.tools.is_empty() || tool.is_some_and(|t| self.tools.iter().any(|n| n == t)))
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_seconds.unwrap_or(DEFAULT_TIMEOUT_SECS))
+    }
+
+    fn label(&self) -> String {
+        match (&self.command, &self.skill) {
+            (Some(command), _) => format!("{:?} hook '{}'", self.event, command),
+            (_, Some(skill)) => format!("{:?} hook (skill {})", self.event, skill),
+            _ => format!("{:?} hook", self.event),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HookDecision {
+    Allow,
+    Deny,
+}
+
+/// What a hook may answer with
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct HookResponse {
+    pub decision: Option<HookDecision>,
+    pub reason: Option<String>,
+    /// New arguments for the tool call (pre_tool_call)
+    pub args: Option<Value>,
+    /// New result of the tool call (post_tool_call)
+    pub result: Option<Value>,
+}
+
+impl HookResponse {
+    /// Output that isn't a response object (plain text, nothing) means "go on"
+    fn parse(output: &str) -> Self {
+        let output = output.trim();
+        if !output.starts_with('{') {
+            return Self::default();
+        }
+        serde_json::from_str(output).unwrap_or_default()
+    }
+
+    fn from_value(value: Value) -> Self {
+        serde_json::from_value(value).unwrap_or_default()
+    }
+}
+
+/// The hooks of one agent
+#[derive(Clone)]
+pub struct AgentHooks {
+    pub agent_id: String,
+    pub workspace: PathBuf,
+    pub hooks: Vec<Hook>,
+    /// Where skill hooks are looked up, before the agent's live registries
+    tools: Vec<Arc<ToolRegistry>>,
+}
+
+impl AgentHooks {
+    /// The `hooks` of an agent's execution settings; None when it has none
+    pub fn from_execution_settings(
+        settings: &Value,
+        agent_id: &str,
+        workspace: PathBuf,
+    ) -> Result<Option<Self>, String> {
+        let hooks: Vec<Hook> = match settings.get("hooks") {
+            None | Some(Value::Null) => return Ok(None),
+            Some(value) => serde_json::from_value(value.clone()).map_err(|e| format!("Invalid hooks: {}", e))?,
+        };
+        for hook in &hooks {
+            hook.validate()?;
+        }
+        if hooks.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Self { agent_id: agent_id.to_string(), workspace, hooks, tools: vec![] }))
+    }
+
+    pub fn with_tools(mut self, tools: Arc<ToolRegistry>) -> Self {
+        self.tools.push(tools);
+        self
+    }
+
+    pub fn has(&self, event: HookEvent) -> bool {
+        self.hooks.iter().any(|h| h.event == event)
+    }
+
+    /// Context every hook gets, plus `fields`
+    fn context(&self, event: HookEvent, session_id: &str, job_id: &str, fields: Value) -> Value {
+        let mut context = json!({
+            "event": event,
+            "agent_id": self.agent_id,
+            "session_id": session_id,
+            "job_id": job_id,
+            "workspace": self.workspace.display().to_string(),
+        });
+        if let (Some(context), Value::Object(fields)) = (context.as_object_mut(), fields) {
+            context.extend(fields);
+        }
+        context
+    }
+
+    async fn run_hook(&self, hook: &Hook, context: &Value, ctx: &ToolContext) -> Result<HookResponse, String> {
+        match (&hook.command, &hook.skill) {
+            (Some(command), _) => self.run_command(hook, command, context).await,
+            (_, Some(skill)) => {
+                let tool = self
+                    .tools
+                    .iter()
+                    .find_map(|tools| tools.get(skill))
+                    .or_else(|| registry::live_registries(&self.agent_id).iter().find_map(|tools| tools.get(skill)))
+                    .ok_or_else(|| format!("Skill '{}' is not loaded for this agent", skill))?;
+                let args = skill_args(tool.as_ref(), hook.args.clone(), context);
+                let result = tokio::time::timeout(hook.timeout(), tool.execute(args, ctx))
+                    .await
+                    .map_err(|_| format!("Timed out after {}s", hook.timeout().as_secs()))??;
+                Ok(HookResponse::from_value(result))
+            }
+            _ => Err("A hook needs either a command or a skill".to_string()),
+        }
+    }
+
+    async fn run_command(&self, hook: &Hook, command: &str, context: &Value) -> Result<HookResponse, String> {
+        let mut child = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .current_dir(&self.workspace)
+            .env("ANYCOWORK_HOOK_EVENT", context["event"].as_str().unwrap_or_default())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Failed to start: {}", e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // A hook that doesn't read its stdin closes it early; that's fine
+            let _ = stdin.write_all(context.to_string().as_bytes()).await;
+        }
+        let output = tokio::time::timeout(hook.timeout(), child.wait_with_output())
+            .await
+            .map_err(|_| format!("Timed out after {}s", hook.timeout().as_secs()))?
+            .map_err(|e| e.to_string())?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let message = if stderr.trim().is_empty() { stdout.trim() } else { stderr.trim() };
+            return Err(match message {
+                "" => format!("Exited with {}", output.status),
+                message => message.chars().take(MAX_OUTPUT_CHARS).collect(),
+            });
+        }
+        Ok(HookResponse::parse(&stdout))
+    }
+
+    /// Run the hooks of `event` in order. Each sees what the ones before it
+    /// changed (`update`). Err is a veto, from a pre hook.
+    async fn run(
+        &self,
+        event: HookEvent,
+        tool: Option<&str>,
+        mut context: Value,
+        ctx: &ToolContext,
+        mut update: impl FnMut(&mut Value, HookResponse),
+    ) -> Result<Value, String> {
+        for hook in self.hooks.iter().filter(|h| h.applies_to(event, tool)) {
+            match self.run_hook(hook, &context, ctx).await {
+                Ok(response) if response.decision == Some(HookDecision::Deny) && event.is_pre() => {
+                    return Err(response.reason.unwrap_or_else(|| format!("Denied by {}", hook.label())));
+                }
+                Ok(response) => update(&mut context, response),
+                Err(e) if event.is_pre() => return Err(format!("{} failed: {}", hook.label(), e)),
+                Err(e) => log::warn!("{} of agent {} failed: {}", hook.label(), self.agent_id, e),
+            }
+        }
+        Ok(context)
+    }
+
+    /// Err(reason) when a hook vetoes the call; `args` may come back changed
+    pub async fn pre_tool_call(&self, ctx: &ToolContext, tool: &str, args: &mut Value) -> Result<(), String> {
+        if !self.has(HookEvent::PreToolCall) {
+            return Ok(());
+        }
+        let job_id = ctx.job_id.as_deref().unwrap_or_default();
+        let fields = json!({ "tool": tool, "args": args });
+        let context = self.context(HookEvent::PreToolCall, &ctx.session_id, job_id, fields);
+        let context = self
+            .run(HookEvent::PreToolCall, Some(tool), context, ctx, |context, response| {
+                if let Some(new_args) = response.args {
+                    context["args"] = new_args;
+                }
+            })
+            .await?;
+        *args = context["args"].clone();
+        Ok(())
+    }
+
+    /// `result` may come back replaced
+    pub async fn post_tool_call(&self, ctx: &ToolContext, tool: &str, args: &Value, result: &mut Value, success: bool) {
+        if !self.has(HookEvent::PostToolCall) {
+            return;
+        }
+        let job_id = ctx.job_id.as_deref().unwrap_or_default();
+        let fields = json!({ "tool": tool, "args": args, "result": result, "success": success });
+        let context = self.context(HookEvent::PostToolCall, &ctx.session_id, job_id, fields);
+        let update = |context: &mut Value, response: HookResponse| {
+            if let Some(new_result) = response.result {
+                context["result"] = new_result;
+            }
+        };
+        if let Ok(context) = self.run(HookEvent::PostToolCall, Some(tool), context, ctx, update).await {
+            *result = context["result"].clone();
+        }
+    }
+
+    /// Err(reason) when a hook vetoes the request
+    pub async fn pre_llm(&self, ctx: &ToolContext, step: usize, route: &crate::llm::ModelRoute, message: &str) -> Result<(), String> {
+        if !self.has(HookEvent::PreLlm) {
+            return Ok(());
+        }
+        let job_id = ctx.job_id.as_deref().unwrap_or_default();
+        let fields = json!({ "step": step, "provider": route.provider, "model": route.model, "message": message });
+        let context = self.context(HookEvent::PreLlm, &ctx.session_id, job_id, fields);
+        self.run(HookEvent::PreLlm, None, context, ctx, |_, _| {}).await.map(|_| ())
+    }
+
+    pub async fn on_job_complete(&self, ctx: &ToolContext, job: &ExecutionJob, message: &str) {
+        let fields = json!({ "status": job.status, "query": job.query, "message": message });
+        let context = self.context(HookEvent::OnJobComplete, &job.session_id, &job.id, fields);
+        let _ = self.run(HookEvent::OnJobComplete, None, context, ctx, |_, _| {}).await;
+    }
+}
+
+/// A skill's arguments: the hook's `args`, with the context in `hook` when
+/// the skill declares that parameter
+fn skill_args(tool: &dyn Tool, args: Option<Value>, context: &Value) -> Value {
+    let mut args = args.unwrap_or_else(|| json!({}));
+    let wants_context = tool.parameters_schema()["properties"].get("hook").is_some();
+    if let (true, Some(fields)) = (wants_context, args.as_object_mut()) {
+        fields.insert("hook".to_string(), context.clone());
+    }
+    args
+}
+
+/// Runs `on_job_complete` hooks when a job of the session finishes. Wraps
+/// the coordinator's observer, which sees every way a job can end.
+pub struct HookObserver {
+    inner: Arc<dyn AgentObserver>,
+    hooks: Arc<AgentHooks>,
+    permissions: Arc<PermissionManager>,
+}
+
+impl HookObserver {
+    /// `inner` itself when there are no `on_job_complete` hooks
+    pub fn wrap(
+        inner: Arc<dyn AgentObserver>,
+        hooks: Option<Arc<AgentHooks>>,
+        permissions: Arc<PermissionManager>,
+    ) -> Arc<dyn AgentObserver> {
+        match hooks {
+            Some(hooks) if hooks.has(HookEvent::OnJobComplete) => Arc::new(Self { inner, hooks, permissions }),
+            _ => inner,
+        }
+    }
+}
+
+impl AgentObserver for HookObserver {
+    fn emit(&self, event: &str, payload: Value) -> Result<(), String> {
+        let completed = event.starts_with("session:") && payload["type"] == "job_completed";
+        let job = completed
+            .then(|| serde_json::from_value::<JobCompletedPayload>(payload.clone()).ok())
+            .flatten();
+        self.inner.emit(event, payload)?;
+
+        let (Some(done), Ok(runtime)) = (job, tokio::runtime::Handle::try_current()) else {
+            return Ok(());
+        };
+        // A dry run changed nothing for the hooks to act on
+        if crate::agents::dry_run::get(&done.job.id).is_some() {
+            return Ok(());
+        }
+        // The job's workers, and their tools, may be gone by the time the hooks run
+        let mut hooks = AgentHooks::clone(&self.hooks);
+        hooks.tools.extend(registry::live_registries(&hooks.agent_id));
+        let ctx = ToolContext {
+            permissions: self.permissions.clone(),
+            observer: Some(self.inner.clone()),
+            session_id: done.job.session_id.clone(),
+            scope: ScopeEnforcer::global(),
+            job_id: Some(done.job.id.clone()),
+        };
+        runtime.spawn(async move {
+            let job = ExecutionJob {
+                id: done.job.id,
+                session_id: done.job.session_id,
+                status: done.job.status,
+                query: done.job.query,
+                steps: vec![],
+                current_step_index: 0,
+                created_at: done.job.created_at,
+                agent_version: done.job.agent_version,
+            };
+            hooks.on_job_complete(&ctx, &job, &done.message).await;
+        });
+        Ok(())
+    }
+}
+
+/// `AgentEvent::JobCompleted` read back from its JSON
+#[derive(Deserialize)]
+struct JobCompletedPayload {
+    job: JobFields,
+    #[serde(default)]
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct JobFields {
+    id: String,
+    session_id: String,
+    status: String,
+    #[serde(default)]
+    query: String,
+    #[serde(default)]
+    created_at: String,
+    #[serde(default)]
+    agent_version: Option<i32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hooks(hooks: Value) -> AgentHooks {
+        let workspace = std::env::temp_dir();
+        AgentHooks::from_execution_settings(&json!({ "hooks": hooks }), "a1", workspace).unwrap().unwrap()
+    }
+
+    fn ctx() -> ToolContext {
+        ToolContext {
+            permissions: Arc::new(PermissionManager::new()),
+            observer: None,
+            session_id: "s1".to_string(),
+            scope: ScopeEnforcer::global(),
+            job_id: Some("j1".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_hooks_from_execution_settings() {
+        let workspace = std::env::temp_dir();
+        assert!(AgentHooks::from_execution_settings(&json!({}), "a1", workspace.clone()).unwrap().is_none());
+        let missing = json!({ "hooks": [{ "event": "pre_llm" }] });
+        assert!(AgentHooks::from_execution_settings(&missing, "a1", workspace.clone()).is_err());
+        let unknown = json!({ "hooks": [{ "event": "on_save", "command": "true" }] });
+        assert!(AgentHooks::from_execution_settings(&unknown, "a1", workspace).is_err());
+
+        let hooks = hooks(json!([{ "event": "pre_tool_call", "tools": ["bash"], "command": "true" }]));
+        assert!(hooks.hooks[0].applies_to(HookEvent::PreToolCall, Some("bash")));
+        assert!(!hooks.hooks[0].applies_to(HookEvent::PreToolCall, Some("filesystem")));
+        assert!(!hooks.has(HookEvent::OnJobComplete));
+    }
+
+    #[tokio::test]
+    async fn test_pre_tool_call_hooks_veto_and_rewrite() {
+        // Reads the call from stdin and pins the command to a safe one
+        let rewrite = r#"grep -q '"tool":"bash"' && echo '{"args": {"command": "ls"}}'"#;
+        let hooks = hooks(json!([
+            { "event": "pre_tool_call", "tools": ["bash"], "command": rewrite },
+            { "event": "pre_tool_call", "tools": ["http"], "command": "echo 'no network' >&2; exit 1" },
+        ]));
+
+        let mut args = json!({ "command": "rm -rf ~" });
+        hooks.pre_tool_call(&ctx(), "bash", &mut args).await.unwrap();
+        assert_eq!(args, json!({ "command": "ls" }));
+
+        let mut args = json!({ "url": "https://example.com" });
+        let veto = hooks.pre_tool_call(&ctx(), "http", &mut args).await.unwrap_err();
+        assert!(veto.contains("no network"));
+    }
+
+    #[tokio::test]
+    async fn test_post_tool_call_hooks_replace_results_and_never_veto() {
+        let hooks = hooks(json!([
+            { "event": "post_tool_call", "command": "exit 3" },
+            { "event": "post_tool_call", "command": "echo '{\"decision\": \"deny\", \"result\": \"linted\"}'" },
+        ]));
+        let mut result = json!("written");
+        hooks.post_tool_call(&ctx(), "filesystem", &json!({}), &mut result, true).await;
+        assert_eq!(result, json!("linted"));
+    }
+}
@@ -1,7 +1,13 @@
 pub mod optimizations;
+pub mod critic;
+pub mod dag;
+pub mod dry_run;
+pub mod hooks;
 pub mod processor;
 pub mod router;
 pub mod simple_chat;
+pub mod title;
+pub mod trace;
 
 #[cfg(test)]
 mod tests;
@@ -13,31 +19,34 @@ mod skill_tests;
 use crate::database::DbPool;
 use crate::events::{AgentEvent, ExecutionJob, ExecutionStep, AgentObserver};
 use crate::models::Agent as DbAgent;
-use crate::permissions::PermissionManager;
+use crate::permissions::{
+    dlp, AgentPolicy, DlpFilter, DlpReport, DlpTarget, PermissionManager, ReadOnlyPolicy, ScopeEnforcer, ScopeType,
+};
+use crate::secrets::{AgentSecrets, RedactingObserver};
 use crate::tools::{
-    bash::BashTool, filesystem::FilesystemTool, search::SearchTool,
-    transcribe::TranscribeTool, Tool, ToolContext,
+    bash::BashTool, config::AgentToolsConfig, filesystem::FilesystemTool, http::HttpTool,
+    image_gen::GenerateImageTool,
+    middleware::ToolMiddlewareChain,
+    pdf::PdfTool,
+    registry::{self, ToolRegistry, GROUP_BUILTIN, GROUP_MCP, GROUP_SKILLS},
+    search::SearchTool, transcribe::TranscribeTool, view_image::ViewImageTool, Tool, ToolContext,
 };
 use diesel::prelude::*;
 use jsonschema::JSONSchema;
 use log::error;
 use optimizations::{
-    create_assistant_message, create_user_message, get_message_content, optimize_history_by_tokens,
-    truncate_message_content, truncate_tool_result, MAX_HISTORY_TOKENS,
+    create_assistant_message, create_user_message, optimize_history_by_tokens,
+    truncate_message_content, truncate_tool_result,
 };
-use rig::client::CompletionClient;
-use rig::client::ProviderClient;
-use crate::llm::LlmClient;
-use rig::completion::Prompt;
-use rig::providers::anthropic;
-use rig::providers::gemini;
-use rig::providers::openai;
+use crate::llm::image::{user_message_with_images, ImageAttachment};
+use crate::llm::{GenerationParams, LlmClient, ModelContext, ModelRoute};
+use crate::prompts::{PromptBuilder, WorkspaceFacts};
 use serde_json::{json, Value};
 use std::sync::Arc;
 use tokio::sync::oneshot;
+use tracing::Instrument;
 use uuid::Uuid;
 
-
 pub struct AgentLoop {
     pub agent_id: String,
     pub session_id: String,
@@ -45,41 +54,117 @@ pub struct AgentLoop {
     pub provider: String,
     pub system_prompt: Option<String>,
     pub history: Vec<rig::completion::Message>,
-    pub tools: Vec<Box<dyn Tool>>,
+    /// Shared with `refresh_agent_tools`, which reloads skills and MCP tools mid-session
+    pub tools: Arc<ToolRegistry>,
+    /// Skills loaded as tools, kept for trigger-based prompt injection
+    pub skills: Vec<crate::models::ParsedSkill>,
     pub snapshot_manager: crate::snapshots::SnapshotManager,
+    /// Set when the agent runs in read-only (analysis) mode
+    pub read_only_policy: Option<ReadOnlyPolicy>,
+    /// Allow/deny/ask rules checked before the user is asked for permission
+    pub policy: Option<AgentPolicy>,
+    /// The agent's `scope_type`: in workspace scope tools stay inside the workspace
+    pub scope: ScopeEnforcer,
+    /// Secrets granted to the agent: injected into bash on request, redacted
+    /// from tool output, events and saved messages
+    pub secrets: Arc<AgentSecrets>,
+    /// Masks or blocks sensitive data in prompts and tool results before
+    /// they are sent to the LLM
+    pub dlp: DlpFilter,
+    /// Agent-to-agent messaging; see `attach_bus`
+    pub bus: Option<Arc<crate::bus::AgentBus>>,
+    /// Images sent with the next `run` message; taken by `run`
+    pub images: Vec<ImageAttachment>,
+    /// Each job writes its execution trace here (see `trace`)
+    pub trace_dir: Option<std::path::PathBuf>,
+    /// Take LLM responses and tool results from a recorded trace
+    pub replay: Option<Arc<trace::Replay>>,
+    /// Models tried in order when the agent's own model keeps failing
+    pub fallbacks: Vec<ModelRoute>,
+    /// Sampling parameters from the agent's `ai_config`
+    pub params: GenerationParams,
+    /// Recall notes from pages into the prompt (`memory` execution setting)
+    pub recall_notes: bool,
+    /// User hooks run around tool calls and model requests (`hooks` execution setting)
+    pub hooks: Option<Arc<hooks::AgentHooks>>,
+    /// Every tool call runs through it (see `tools::middleware`)
+    pub middleware: ToolMiddlewareChain,
+    /// Set for a dry run: calls that would change something are held back in
+    /// the plan instead of made (see `dry_run`)
+    pub dry_run: Option<Arc<dry_run::ChangePlan>>,
 }
 
 impl AgentLoop {
     pub async fn new(agent_db: &DbAgent, db_pool: DbPool) -> Self {
         // Register default tools
         // Initialize workspace path early
-        let workspace_path = if let Some(path) = &agent_db.workspace_path {
-             std::path::PathBuf::from(path)
-        } else {
-             std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."))
-        };
+        let workspace_path = workspace_path(agent_db);
 
         // Parse Execution Settings
         // sandbox_mode controls skill/tool execution (sandbox, direct, flexible)
         // mode controls approval workflow (autopilot, require_approval, smart_approval)
-        let execution_mode = if let Some(settings_str) = &agent_db.execution_settings {
-             if let Ok(json) = serde_json::from_str::<serde_json::Value>(settings_str) {
-                 // Use sandbox_mode for tool execution, default to "flexible"
-                 json.get("sandbox_mode").and_then(|m| m.as_str()).unwrap_or("flexible").to_string()
-             } else {
-                 "flexible".to_string()
-             }
-        } else {
-             "flexible".to_string()
+        let execution_settings_json = agent_db
+            .execution_settings
+            .as_ref()
+            .and_then(|s| serde_json::from_str::<serde_json::Value>(s).ok());
+        let read_only_policy = execution_settings_json
+            .as_ref()
+            .and_then(ReadOnlyPolicy::from_execution_settings);
+        if read_only_policy.is_some() {
+            log::info!("Agent {} running in read-only mode", agent_db.id);
+        }
+        // A broken policy must not widen what the agent may do: without it
+        // every request is asked about
+        let policy = match execution_settings_json.as_ref().map(AgentPolicy::from_execution_settings) {
+            Some(Ok(policy)) => policy,
+            Some(Err(e)) => {
+                log::error!("Ignoring permission policy of agent {}: {}", agent_db.id, e);
+                None
+            }
+            None => None,
         };
 
-        // Register default tools
+        let hooks = match execution_settings_json
+            .as_ref()
+            .map(|settings| hooks::AgentHooks::from_execution_settings(settings, &agent_db.id, workspace_path.clone()))
+        {
+            Some(Ok(hooks)) => hooks,
+            Some(Err(e)) => {
+                log::error!("Ignoring hooks of agent {}: {}", agent_db.id, e);
+                None
+            }
+            None => None,
+        };
+
+        let execution_mode = execution_mode(execution_settings_json.as_ref());
+        let fallbacks = execution_settings_json
+            .as_ref()
+            .map(ModelRoute::fallbacks_from_execution_settings)
+            .unwrap_or_default();
+
+        // Register built-in tools, as selected and configured for this agent
+        let tools_config = AgentToolsConfig::from_agent(agent_db);
+        let secrets = Arc::new(AgentSecrets::load(&db_pool, &agent_db.id));
+        let mut bash = BashTool::new(workspace_path.clone(), execution_mode.clone()).with_secrets(secrets.clone());
+        if let Some(timeout) = tools_config.bash.timeout_seconds {
+            bash = bash.with_timeout(timeout);
+        }
         let mut tools: Vec<Box<dyn Tool>> = vec![
-            Box::new(FilesystemTool::new(workspace_path.clone())),
+            Box::new(FilesystemTool::new(tools_config.filesystem_root(&workspace_path))),
             Box::new(SearchTool),
-            Box::new(BashTool::new(workspace_path.clone(), execution_mode.clone())),
+            Box::new(bash),
             Box::new(TranscribeTool::new()),
+            Box::new(HttpTool::new()),
+            Box::new(PdfTool::new(workspace_path.clone())),
+            Box::new(GenerateImageTool::new(
+                workspace_path.clone(),
+                db_pool.clone(),
+                &agent_db.ai_provider,
+            )),
         ];
+        if crate::llm::image::supports_vision(&agent_db.ai_provider, &agent_db.ai_model) {
+            tools.push(Box::new(ViewImageTool::new(workspace_path.clone())));
+        }
 
         // Register communication tools (ListColleagues and SendEmail)
         {
@@ -102,7 +187,7 @@ impl AgentLoop {
                 agent_db.id.clone(),
                 agent_db.name.clone(),
                 all_agents,
-            )));
+            ).with_workspace(workspace_path.clone())));
 
             // Add mail reading tools (check inbox/sent, read threads)
             tools.push(Box::new(crate::tools::mail_reader::MailReaderTool::new(
@@ -110,126 +195,105 @@ impl AgentLoop {
                 agent_db.id.clone(),
             )));
 
-            tools.push(Box::new(crate::tools::mail_reader::ReadEmailThreadTool::new(
-                db_pool.clone(),
-            )));
+            tools.push(Box::new(
+                crate::tools::mail_reader::ReadEmailThreadTool::new(db_pool.clone())
+                    .with_workspace(workspace_path.clone()),
+            ));
         }
 
-        // Load Assigned Skills
-        if let Ok(mut conn) = db_pool.get() {
-            use crate::schema::{agent_skill_assignments, agent_skills, skill_files};
-            
-            // Check if this is the default agent
-            let is_default_agent = agent_db.name == "AnyCoworker Default";
-            
-            // 1. Get skill IDs - either all enabled (for default) or assigned (for custom agents)
-            let skill_ids: Vec<String> = if is_default_agent {
-                // Default agent uses ALL enabled skills
-                agent_skills::table
-                    .filter(agent_skills::enabled.eq(1))
-                    .select(agent_skills::id)
-                    .load::<String>(&mut conn)
-                    .unwrap_or_default()
-            } else {
-                // Custom agents use only assigned skills
-                agent_skill_assignments::table
-                    .filter(agent_skill_assignments::agent_id.eq(&agent_db.id))
-                    .select(agent_skill_assignments::skill_id)
-                    .load::<String>(&mut conn)
-                    .unwrap_or_default()
-            };
-
-            if !skill_ids.is_empty() {
-                // 2. Fetch Skills
-                let skills: Result<Vec<crate::models::AgentSkill>, _> = agent_skills::table
-                    .filter(agent_skills::id.eq_any(&skill_ids))
-                    .filter(agent_skills::enabled.eq(1))
-                    .load::<crate::models::AgentSkill>(&mut conn);
-
-                if let Ok(skills_list) = skills {
-                    for skill_db in skills_list {
-                        // 3. Fetch Skill Files
-                        let files_list: Result<Vec<crate::models::SkillFile>, _> = skill_files::table
-                            .filter(skill_files::skill_id.eq(&skill_db.id))
-                            .load::<crate::models::SkillFile>(&mut conn);
-
-                        let mut files_map = std::collections::HashMap::new();
-                        if let Ok(files) = files_list {
-                            for f in files {
-                                files_map.insert(f.relative_path, crate::skills::loader::SkillFileContent {
-                                    content: f.content,
-                                    file_type: f.file_type,
-                                });
-                            }
-                        }
-                    
-                        // Construct LoadedSkill
-                        let sandbox_config = if let Some(sc_json) = &skill_db.sandbox_config {
-                            serde_json::from_str(sc_json).ok()
-                        } else {
-                            None
-                        };
-
-                        let parsed_skill = crate::models::ParsedSkill {
-                            name: skill_db.name.clone(),
-                            description: skill_db.description.clone(),
-                            license: None,
-                            triggers: None,
-                            sandbox_config,
-                            body: skill_db.skill_content.clone(),
-                            category: skill_db.category.clone(),
-                            requires_sandbox: skill_db.requires_sandbox == 1,
-                            execution_mode: Some(skill_db.execution_mode.clone()),
-                        };
-
-                        let loaded_skill = crate::skills::loader::LoadedSkill {
-                            skill: parsed_skill,
-                            files: files_map,
-                        };
-
-                        // Determine workspace path
-                        let workspace_path = if let Some(path) = &agent_db.workspace_path {
-                            let p = std::path::PathBuf::from(path);
-                            // Ensure it exists
-                            std::fs::create_dir_all(&p).unwrap_or(());
-                            p
-                        } else {
-                            std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."))
-                        };
+        // The user's pages, so research can land in their notes
+        tools.push(Box::new(crate::tools::pages::PagesTool::new(db_pool.clone())));
+        tools.push(Box::new(crate::tools::todo::TodoTool::new(Some(db_pool.clone()))));
+        tools.retain(|t| tools_config.builtin_enabled(t.name()));
 
-                        // Create SkillTool
-                        let skill_tool = crate::skills::SkillTool::new(
-                            loaded_skill, 
-                            workspace_path.clone(),
-                            execution_mode.clone() // Pass agent mode
-                        );
-                        tools.push(Box::new(skill_tool));
-                    }
-                }
-            }
+        // Load Assigned Skills
+        let (skills, skill_tools) = load_skill_tools(agent_db, &db_pool, &tools_config, &execution_mode, read_only_policy.as_ref());
+
+        // MCP tools from the servers assigned to this agent
+        let mcp_tools = load_mcp_tools(agent_db, &db_pool, &tools_config).await;
+
+        let mut snapshot_manager = crate::snapshots::SnapshotManager::new(&workspace_path);
+        let checkpoints_requested = execution_settings_json
+            .as_ref()
+            .and_then(|json| json.get("checkpoints").and_then(|c| c.as_bool()))
+            .unwrap_or(false);
+        let recall_notes = execution_settings_json
+            .as_ref()
+            .and_then(|json| json.get("memory").and_then(|m| m.as_bool()))
+            .unwrap_or(false);
+        // Checked when the agent is saved; fitted again for configs saved before that
+        let params = agent_db.generation_params();
+        if let Err(e) = params.validate(&agent_db.ai_provider, &agent_db.ai_model) {
+            log::warn!("Agent {} has invalid generation parameters: {}", agent_db.id, e);
+        }
+        let params = params.for_provider(&agent_db.ai_provider);
+        if checkpoints_requested && !snapshot_manager.enable_checkpoints() {
+            log::warn!("Agent {} requested checkpoints but its workspace is not a git repository", agent_db.id);
         }
 
-        let workspace_path = if let Some(path) = &agent_db.workspace_path {
-             std::path::PathBuf::from(path)
-        } else {
-             std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."))
-        };
-
-
-
-        log::info!("AgentLoop initialized for agent {}. Loaded tools: {}", agent_db.id, tools.len());
+        log::info!(
+            "AgentLoop initialized for agent {}. Loaded tools: {}",
+            agent_db.id,
+            tools.len() + skill_tools.len() + mcp_tools.len()
+        );
+        let tools = Arc::new(ToolRegistry::from_tools(GROUP_BUILTIN, tools));
+        tools.replace_group(GROUP_SKILLS, skill_tools.into_iter().map(Arc::from).collect());
+        tools.replace_group(GROUP_MCP, mcp_tools.into_iter().map(Arc::from).collect());
+        registry::track(&agent_db.id, &tools);
+        let hooks = hooks.map(|hooks| Arc::new(hooks.with_tools(tools.clone())));
+        let middleware = ToolMiddlewareChain::for_agent(
+            Some(db_pool.clone()),
+            &agent_db.id,
+            execution_settings_json.as_ref(),
+            secrets.redactor().clone(),
+        );
         Self {
             agent_id: agent_db.id.clone(),
-            session_id: "temp".to_string(), // Set later
+            session_id: crate::maintenance::TEMP_SESSION_ID.to_string(), // Set later
             model: agent_db.ai_model.clone(),
             provider: agent_db.ai_provider.clone(),
             system_prompt: agent_db.system_prompt.clone(),
             history: vec![],
             tools,
-            snapshot_manager: crate::snapshots::SnapshotManager::new(workspace_path),
+            skills,
+            snapshot_manager,
+            read_only_policy,
+            policy,
+            scope: ScopeEnforcer::new(
+                ScopeType::from(agent_db.scope_type.clone()),
+                agent_db.workspace_path.as_ref().map(std::path::PathBuf::from),
+            ),
+            secrets,
+            dlp: DlpFilter::load(&db_pool),
+            bus: None,
+            images: vec![],
+            trace_dir: crate::config::for_agent(agent_db).trace_dir,
+            replay: None,
+            fallbacks,
+            params,
+            recall_notes,
+            hooks,
+            middleware,
+            dry_run: None,
         }
     }
 
+    /// Connect to the agent bus: messages for this agent are injected between
+    /// steps while it runs, and it gets the `message_agent` tool
+    pub fn attach_bus(&mut self, bus: Arc<crate::bus::AgentBus>) {
+        self.tools.register(GROUP_BUILTIN, Box::new(crate::tools::agent_message::MessageAgentTool::new(
+            bus.clone(),
+            self.agent_id.clone(),
+        )));
+        self.bus = Some(bus);
+    }
+
+    /// Replace the in-memory history with the session's stored messages, as
+    /// needed after an edit or regenerate rewound the session
+    pub fn reload_history(&mut self, db_pool: &DbPool) {
+        self.history = load_session_history(db_pool, &self.session_id, SESSION_HISTORY_LIMIT);
+    }
+
     pub async fn run(
         &mut self,
         user_message: String,
@@ -239,6 +303,10 @@ impl AgentLoop {
         permission_manager: Arc<PermissionManager>,
         db_pool: DbPool,
     ) {
+        // Messages saved without a session must outlive the orphan cleanup until we're done
+        let _temp_run = (self.session_id == crate::maintenance::TEMP_SESSION_ID)
+            .then(crate::maintenance::track_temp_run);
+
         // Initialize job (used for step events; lifecycle managed by Coordinator)
         let job = ExecutionJob {
             id: job_id.clone(),
@@ -248,16 +316,121 @@ impl AgentLoop {
             steps: vec![],
             current_step_index: 0,
             created_at: chrono::Utc::now().to_rfc3339(),
+            agent_version: crate::models::agent_version::current_version(&db_pool, &self.agent_id),
         };
 
         // 1. Add User Message to History
-        let truncated_user_message = truncate_message_content(&user_message, "user");
+        let mut user_message = user_message;
+        let report = self.dlp.scan(&mut user_message, DlpTarget::Prompts);
+        if !report.is_clean() {
+            self.record_dlp_incident(&db_pool, None, &report);
+            if report.blocked {
+                let _ = observer.emit(
+                    &format!("session:{}", self.session_id),
+                    serde_json::to_value(AgentEvent::Error {
+                        message: format!(
+                            "Message not sent: it matches DLP rule(s) {}",
+                            report.rule_names().join(", ")
+                        ),
+                        error: None,
+                    }).unwrap_or(serde_json::Value::Null),
+                );
+                return;
+            }
+        }
+        let context = ModelContext::for_model(&self.provider, &self.model);
+        let truncated_user_message = truncate_message_content(&context, &user_message, "user");
+        let images = std::mem::take(&mut self.images);
         self.history
-            .push(create_user_message(truncated_user_message));
+            .push(user_message_with_images(&truncated_user_message, &images));
+
+        let full_preamble = self.render_preamble(&db_pool, &user_message);
+
+        // Create LLM Client
+        let client = LlmClient::new(&self.provider, &self.model)
+            .with_preamble(&full_preamble)
+            .with_session(&self.session_id)
+            .with_job(&job.id)
+            .with_params(self.params.clone());
+        if self.replay.is_none() {
+            crate::llm::usage::start(&job.id);
+        }
+        
+        // Execute loop
+        let span = tracing::info_span!(
+            "agent.job",
+            job_id = %job_id,
+            session_id = %self.session_id,
+            agent_id = %self.agent_id,
+            provider = %self.provider,
+            model = %self.model,
+            steps = tracing::field::Empty,
+        );
+        self.run_loop(
+            client,
+            &observer,
+            &job,
+            permission_manager,
+            &db_pool,
+            user_message,
+        )
+        .instrument(span)
+        .await;
+
+        if self.replay.is_none() {
+            let tokens = crate::llm::usage::finish_by_model(&job.id);
+            if let Err(e) = crate::models::usage::record_job(&db_pool, self.usage_ref(&job.id, None), &tokens) {
+                log::warn!("Failed to record usage of job {}: {}", job.id, e);
+            }
+        }
+    }
+
+    /// Usage records of `job_id` are filed under this agent and session,
+    /// and the model answering (`route`, or the agent's own)
+    /// Hooks to run; none while replaying a trace or in a dry run
+    fn active_hooks(&self) -> Option<Arc<hooks::AgentHooks>> {
+        self.hooks.clone().filter(|_| self.replay.is_none() && self.dry_run.is_none())
+    }
+
+    /// The chain tool calls run through, holding back changes in a dry run
+    fn tool_middleware(&self) -> ToolMiddlewareChain {
+        match &self.dry_run {
+            Some(plan) => self.middleware.clone().with(dry_run::DryRunMiddleware::new(plan.clone(), &self.agent_id)),
+            None => self.middleware.clone(),
+        }
+    }
+
+    fn usage_ref<'a>(&'a self, job_id: &'a str, route: Option<&'a ModelRoute>) -> crate::models::usage::JobRef<'a> {
+        crate::models::usage::JobRef {
+            job_id,
+            session_id: &self.session_id,
+            agent_id: &self.agent_id,
+            provider: route.map_or(&self.provider, |r| &r.provider),
+            model: route.map_or(&self.model, |r| &r.model),
+        }
+    }
+
 
+    /// Log and audit a DLP hit; `tool` is None for prompts
+    fn record_dlp_incident(&self, db_pool: &DbPool, tool: Option<&str>, report: &DlpReport) {
+        let target = if tool.is_some() { DlpTarget::ToolResults } else { DlpTarget::Prompts };
+        log::warn!(
+            "DLP {} data in session {}: {:?}",
+            if report.blocked { "blocked" } else { "masked" },
+            self.session_id,
+            report.matches
+        );
+        if let Err(e) = dlp::record_incident(db_pool, &self.session_id, tool, target, report) {
+            log::error!("Failed to record DLP incident: {}", e);
+        }
+    }
+
+    /// System prompt plus the tool-use prompt for the tools registered right now
+    fn render_preamble(&self, db_pool: &DbPool, user_message: &str) -> String {
         // Prepare tool definitions
         let tools_desc = self
             .tools
+            .list()
             .iter()
             .map(|t| {
                 json!({
@@ -268,63 +441,112 @@ impl AgentLoop {
             })
             .collect::<Vec<_>>();
 
-        // Load tool use prompt template
-        let template_str = std::fs::read_to_string("anyagents/prompts/tool_use_system.j2")
-            .unwrap_or_else(|_| include_str!("../../prompts/tool_use_system.j2").to_string());
-
-        let mut env = minijinja::Environment::new();
-        env.add_template("tool_use", &template_str).unwrap();
-
         let tools_json = serde_json::to_string_pretty(&tools_desc).unwrap();
         log::info!("Generating prompt with tools JSON length: {}", tools_json.len());
-        
-        let tmpl = env.get_template("tool_use").unwrap();
 
-        // Render prompt
-        let tools_prompt = tmpl
-            .render(minijinja::context! {
+        // Inject full guides only for skills whose triggers match this message
+        let active_skills = crate::skills::triggers::select_triggered_skills(&self.skills, user_message)
+            .into_iter()
+            .map(|s| json!({ "name": s.name, "body": s.body }))
+            .collect::<Vec<_>>();
+        if !active_skills.is_empty() {
+            log::info!("Injecting {} triggered skill(s) into prompt", active_skills.len());
+        }
+
+        // Render the tool use template, or this agent's override of it
+        let tools_prompt = crate::prompts::render(
+            Some(db_pool),
+            crate::prompts::TOOL_USE_SYSTEM,
+            Some(&self.agent_id),
+            minijinja::context! {
                 tools_desc => tools_json,
                 plan => Value::Null,
-                current_task => Value::Null
-            })
-            .unwrap();
+                current_task => Value::Null,
+                read_only => self.read_only_policy.is_some(),
+                active_skills => active_skills
+            },
+        )
+        .unwrap_or_else(|e| {
+            log::error!("Failed to render tool use prompt: {}", e);
+            String::new()
+        });
 
-        // Combine agent's system prompt with tools prompt
-        let full_preamble = if let Some(ref custom_prompt) = self.system_prompt {
-            format!("{}\n\n---\n\n{}", custom_prompt, tools_prompt)
+        let notes = if self.recall_notes {
+            crate::prompts::builder::recall_notes(db_pool, user_message, RECALLED_NOTES)
         } else {
-            tools_prompt.clone()
+            vec![]
         };
-
-        // Create LLM Client
-        let client = LlmClient::new(&self.provider, &self.model).with_preamble(&full_preamble);
-        
-        // Execute loop
-        self.run_loop(
-            client,
-            &observer,
-            &job,
-            permission_manager,
-            &db_pool,
-            user_message,
-        )
-        .await;
+        PromptBuilder::new()
+            .persona(self.system_prompt.as_deref(), "")
+            .skills(&self.skills)
+            .workspace(WorkspaceFacts::collect(self.snapshot_manager.root_path()))
+            .memory(notes)
+            .instructions(tools_prompt)
+            .build()
     }
 
-
     // Generic run loop using the Agent struct
     pub async fn run_loop(
         &mut self,
-        client: LlmClient,
+        mut client: LlmClient,
         observer: &Arc<dyn AgentObserver>,
         job: &ExecutionJob,
         permission_manager: Arc<PermissionManager>,
         db_pool: &DbPool,
         user_message: String,
     ) {
+        let permission_manager = match &self.policy {
+            Some(policy) => Arc::new(permission_manager.with_policy(policy.clone())),
+            None => permission_manager,
+        };
+        let redactor = self.secrets.redactor().clone();
+        let observer = &RedactingObserver::wrap(observer.clone(), redactor.clone());
+        let recorder = self.trace_dir.as_ref().and_then(|dir| {
+            trace::TraceRecorder::create(dir, &job.id)
+                .map_err(|e| log::warn!("Tracing disabled for job {}: {}", job.id, e))
+                .ok()
+                .map(Arc::new)
+        });
+        let observer = &match &recorder {
+            Some(recorder) => trace::TracingObserver::wrap(observer.clone(), recorder.clone()),
+            None => observer.clone(),
+        };
+        let record = |entry: trace::TraceEntry| {
+            if let Some(recorder) = &recorder {
+                recorder.record(entry);
+            }
+        };
+        record(trace::TraceEntry::Start {
+            agent_id: self.agent_id.clone(),
+            session_id: self.session_id.clone(),
+            provider: self.provider.clone(),
+            model: self.model.clone(),
+            message: redactor.redact(&user_message).to_string(),
+        });
         let max_steps = 10;
         let mut steps_count = 0;
+        let mut checkpoint_count = 0;
         let mut final_response_text = String::new();
+        let mut inbox = self.bus.as_ref().map(|bus| bus.subscribe(&self.agent_id));
+        let mut tool_changes = self.tools.subscribe();
+        let tool_cache = optimizations::ToolResultCache::for_session(&self.session_id);
+        // Model answering now; moves down the fallback chain when it keeps failing
+        let mut route = ModelRoute::new(&self.provider, &self.model);
+        let mut fallbacks = self.fallbacks.clone().into_iter();
+        let mut on_fallback = false;
+        // Files may have changed since this session last ran a tool
+        if let Ok(snapshot) = self.snapshot_manager.create_snapshot() {
+            tool_cache.sync_workspace(&self.snapshot_manager, &snapshot);
+        }
+        let middleware = self.tool_middleware();
+        let hooks = self.active_hooks();
+        let hook_ctx = ToolContext {
+            permissions: permission_manager.clone(),
+            observer: Some(observer.clone()),
+            session_id: self.session_id.clone(),
+            scope: self.scope.clone(),
+            job_id: Some(job.id.clone()),
+        };
 
         loop {
             if steps_count >= max_steps {
@@ -332,6 +554,45 @@ impl AgentLoop {
             }
             steps_count += 1;
 
+            // History and truncation are sized to the model answering now
+            let context = ModelContext::for_model(&route.provider, &route.model);
+            optimize_history_by_tokens(&mut self.history, &context, context.history_budget());
+
+            // Messages from other agents arrive between steps
+            if let (Some(inbox), Some(bus)) = (inbox.as_mut(), self.bus.as_ref()) {
+                for message in inbox.drain() {
+                    let mut text = message.to_prompt(&bus.sender_name(&message.from));
+                    let report = self.dlp.scan(&mut text, DlpTarget::Prompts);
+                    if !report.is_clean() {
+                        self.record_dlp_incident(db_pool, None, &report);
+                        if report.blocked {
+                            continue;
+                        }
+                    }
+                    save_message(db_pool, "user", &redactor.redact(&text), &self.session_id, None);
+                    self.history.push(create_user_message(truncate_message_content(&context, &text, "user")));
+                }
+            }
+
+            // Skills and MCP servers may have come and gone since the last step
+            let mut tools_changed = false;
+            while let Ok(change) = tool_changes.try_recv() {
+                tools_changed = true;
+                if !change.is_empty() {
+                    let _ = observer.emit(
+                        &format!("session:{}", self.session_id),
+                        serde_json::to_value(AgentEvent::ToolsChanged {
+                            added: change.added,
+                            removed: change.removed,
+                            tools: self.tools.names(),
+                        }).unwrap_or(serde_json::Value::Null),
+                    );
+                }
+            }
+            if tools_changed {
+                client = client.with_preamble(&self.render_preamble(db_pool, &user_message));
+            }
+
             let prompt_msg = if let Some(last_msg) = self.history.pop() {
                 last_msg
             } else {
@@ -342,6 +603,24 @@ impl AgentLoop {
 
             // Put prompt back into history for next iteration/persistence logic
             self.history.push(prompt_msg.clone());
+            record(trace::TraceEntry::LlmRequest {
+                step: steps_count,
+                message: redactor.redact(&optimizations::get_message_content(&prompt_msg)).to_string(),
+                history_len: current_history.len(),
+            });
+            if let Some(hooks) = &hooks {
+                let message = redactor.redact(&optimizations::get_message_content(&prompt_msg)).to_string();
+                if let Err(reason) = hooks.pre_llm(&hook_ctx, steps_count, &route, &message).await {
+                    let _ = observer.emit(
+                        &format!("session:{}", self.session_id),
+                        serde_json::to_value(AgentEvent::Error {
+                            message: format!("Model request blocked by hook: {}", reason),
+                            error: None,
+                        }).unwrap_or(serde_json::Value::Null),
+                    );
+                    return;
+                }
+            }
 
             // Use chat() to include history context
             // We implement a retry loop here to handle transient provider errors
@@ -361,16 +640,62 @@ impl AgentLoop {
                     );
                 };
 
-                match client
-                    .stream_chat(&get_message_content(&prompt_msg), current_history.clone(), on_token)
-                    .await
-                {
+                let result = match &self.replay {
+                    Some(replay) => replay.next_response().inspect(|r| on_token(r.clone())),
+                    None => client
+                        .stream_chat_message(prompt_msg.clone(), current_history.clone(), on_token)
+                        .await,
+                };
+                match result {
                     Ok(r) => {
+                        record(trace::TraceEntry::LlmResponse {
+                            step: steps_count,
+                            text: redactor.redact(&r).to_string(),
+                        });
                         break r;
                     }
                     Err(e) => {
                         error!("Agent chat attempt {} failed: {}", chat_attempts, e);
+                        record(trace::TraceEntry::LlmError {
+                            step: steps_count,
+                            attempt: chat_attempts,
+                            error: redactor.redact(&e).to_string(),
+                        });
                         if chat_attempts >= max_chat_attempts {
+                            if let Some(next) = fallbacks.next() {
+                                log::warn!("Model {} failed, switching to {}", route, next);
+                                let _ = observer.emit(
+                                    &format!("session:{}", self.session_id),
+                                    serde_json::to_value(AgentEvent::ModelSwitched {
+                                        from: route.clone(),
+                                        to: next.clone(),
+                                        rate_limited: crate::llm::fallback::is_rate_limit(&e),
+                                        reason: e,
+                                    }).unwrap_or(serde_json::Value::Null),
+                                );
+                                client = client.with_route(&next);
+                                route = next;
+                                on_fallback = true;
+                                chat_attempts = 0;
+                                continue;
+                            }
+                            // Lost the connection: wait for the provider instead of failing
+                            if self.replay.is_none() && crate::llm::connectivity::is_connectivity_error(&e) {
+                                let _ = observer.emit(
+                                    &format!("session:{}", self.session_id),
+                                    serde_json::to_value(AgentEvent::JobPending {
+                                        job: ExecutionJob { status: "pending".to_string(), ..job.clone() },
+                                        provider: route.provider.clone(),
+                                    }).unwrap_or(serde_json::Value::Null),
+                                );
+                                let connectivity = crate::llm::connectivity::global();
+                                connectivity.report_offline(&route.provider, &e);
+                                if connectivity.wait_online(&route.provider, MAX_OFFLINE_WAIT).await {
+                                    log::info!("Provider {} is back, resuming job {}", route.provider, job.id);
+                                    chat_attempts = 0;
+                                    continue;
+                                }
+                            }
                             let _ = observer.emit(
                                 &format!("session:{}", self.session_id),
                                 serde_json::to_value(AgentEvent::Error {
@@ -396,7 +721,9 @@ impl AgentLoop {
                             &format!("session:{}", self.session_id),
                             serde_json::to_value(AgentEvent::Thinking { message: retry_msg }).unwrap_or(serde_json::Value::Null),
                         );
-                        tokio::time::sleep(tokio::time::Duration::from_millis(wait_ms)).await;
+                        if self.replay.is_none() {
+                            tokio::time::sleep(tokio::time::Duration::from_millis(wait_ms)).await;
+                        }
                     }
                 }
             };
@@ -420,24 +747,81 @@ impl AgentLoop {
             let tool_calls = extract_tool_calls(&response);
 
             // Filter to find valid tool calls first to decide if we should enter tool execution mode
-            let valid_calls: Vec<(String, Value, &Box<dyn Tool>)> = tool_calls
+            let valid_calls: Vec<(String, Value, Arc<dyn Tool>)> = tool_calls
                 .into_iter()
                 .filter_map(|tc| {
                     let tool_name = tc.get("tool")?.as_str()?.to_string();
                     let args = tc.get("args")?.clone();
-                    let tool = self.tools.iter().find(|t| t.name() == tool_name)?;
+                    let tool = self.tools.get(&tool_name)?;
                     Some((tool_name, args, tool))
                 })
                 .collect();
 
             if !valid_calls.is_empty() {
                 // Persist the Assistant's Response (with all tool calls) ONCE
-                let truncated_response = truncate_message_content(&response, "assistant");
-                save_message(db_pool, "assistant", &response, &self.session_id, None);
+                let truncated_response = truncate_message_content(&context, &response, "assistant");
+                save_message(
+                    db_pool,
+                    "assistant",
+                    &redactor.redact(&response),
+                    &self.session_id,
+                    Some(route.message_metadata(on_fallback)),
+                );
                 self.history
                     .push(create_assistant_message(truncated_response));
 
-                for (tool_name, args, tool) in valid_calls {
+                // A call that is not run: it shows up as a failed step, and the
+                // reason goes to the model in place of a tool result
+                let reject_call = |history: &mut Vec<rig::completion::Message>,
+                                   tool_name: &str,
+                                   args: &Value,
+                                   result: &str,
+                                   message: &str| {
+                    let step = ExecutionStep {
+                        id: Uuid::new_v4().to_string(),
+                        tool_name: tool_name.to_string(),
+                        tool_args: args.clone(),
+                        status: "failed".to_string(),
+                        result: Some(result.to_string()),
+                        requires_approval: false,
+                        created_at: chrono::Utc::now().to_rfc3339(),
+                    };
+                    let _ = observer.emit(
+                        &format!("session:{}", self.session_id),
+                        serde_json::to_value(AgentEvent::StepStarted {
+                            job: job.clone(),
+                            step: step.clone(),
+                        }).unwrap(),
+                    );
+                    let _ = observer.emit(
+                        &format!("session:{}", self.session_id),
+                        serde_json::to_value(AgentEvent::StepCompleted {
+                            job: job.clone(),
+                            step,
+                        }).unwrap(),
+                    );
+
+                    save_message(
+                        db_pool,
+                        "tool",
+                        &redactor.redact(message),
+                        &self.session_id,
+                        Some(args.to_string()),
+                    );
+                    history.push(create_user_message(truncate_message_content(&context, message, "user")));
+                };
+
+                for (tool_name, mut args, tool) in valid_calls {
+                    // HOOKS: pre_tool_call hooks may veto the call or rewrite its
+                    // arguments, which are then validated like the model's
+                    if let Some(hooks) = &hooks {
+                        if let Err(reason) = hooks.pre_tool_call(&hook_ctx, &tool_name, &mut args).await {
+                            let message = format!("Tool '{}' blocked by hook: {}", tool_name, reason);
+                            reject_call(&mut self.history, &tool_name, &args, &message, &message);
+                            continue;
+                        }
+                    }
+
                     // VALIDATION START
                     let schema_json = tool.parameters_schema();
                     let compiled_schema = JSONSchema::compile(&schema_json)
@@ -450,85 +834,29 @@ impl AgentLoop {
                             .join(", ");
                         let fail_msg =
                             format!("Tool argument validation failed (schema): {}", error_msg);
-
-                        let step_id = Uuid::new_v4().to_string();
-                        let step = ExecutionStep {
-                            id: step_id.clone(),
-                            tool_name: tool_name.to_string(),
-                            tool_args: args.clone(),
-                            status: "failed".to_string(),
-                            result: Some(fail_msg.clone()),
-                            requires_approval: false,
-                            created_at: chrono::Utc::now().to_rfc3339(),
-                        };
-
-                        let _ = observer.emit(
-                            &format!("session:{}", self.session_id),
-                            serde_json::to_value(AgentEvent::StepStarted {
-                                job: job.clone(),
-                                step: step.clone(),
-                            }).unwrap(),
-                        );
-                        let _ = observer.emit(
-                            &format!("session:{}", self.session_id),
-                            serde_json::to_value(AgentEvent::StepCompleted {
-                                job: job.clone(),
-                                step: step.clone(),
-                            }).unwrap(),
-                        );
-
-                        // Add failure to history and DB
                         let fail_msg_full =
                             format!("Tool '{}' validation failed: {}", tool_name, fail_msg);
-                        let truncated_fail_msg = truncate_message_content(&fail_msg_full, "user");
-                        save_message(
-                            db_pool,
-                            "tool",
-                            &fail_msg_full,
-                            &self.session_id,
-                            Some(args.to_string()),
-                        );
-                        self.history.push(create_user_message(truncated_fail_msg));
-
+                        reject_call(&mut self.history, &tool_name, &args, &fail_msg, &fail_msg_full);
                         continue;
                     }
 
                     // 2. Logic Validation
                     if let Err(e) = tool.validate_args(&args).await {
                         let fail_msg = format!("Tool argument validation failed (logic): {}", e);
-                        let step_id = Uuid::new_v4().to_string();
-                        let step = ExecutionStep {
-                            id: step_id.clone(),
-                            tool_name: tool_name.to_string(),
-                            tool_args: args.clone(),
-                            status: "failed".to_string(),
-                            result: Some(fail_msg.clone()),
-                            requires_approval: false,
-                            created_at: chrono::Utc::now().to_rfc3339(),
-                        };
-                        let _ = observer.emit(
-                            &format!("session:{}", self.session_id),
-                            serde_json::to_value(AgentEvent::StepStarted {
-                                job: job.clone(),
-                                step: step.clone(),
-                            }).unwrap(),
-                        );
-
-                        // Add failure to history and DB
                         let fail_msg_full =
                             format!("Tool '{}' validation failed: {}", tool_name, fail_msg);
-                        let truncated_fail_msg = truncate_message_content(&fail_msg_full, "user");
-                        save_message(
-                            db_pool,
-                            "tool",
-                            &fail_msg_full,
-                            &self.session_id,
-                            Some(args.to_string()),
-                        );
-                        self.history.push(create_user_message(truncated_fail_msg));
+                        reject_call(&mut self.history, &tool_name, &args, &fail_msg, &fail_msg_full);
                         continue;
                     }
 
+                    // 3. Read-only enforcement
+                    if let Some(policy) = &self.read_only_policy {
+                        if let Err(e) = policy.check(tool.as_ref(), &args) {
+                            reject_call(&mut self.history, &tool_name, &args, &e, &e);
+                            continue;
+                        }
+                    }
+
                     // VALIDATION END
 
                     let step_id = Uuid::new_v4().to_string();
@@ -562,17 +890,103 @@ impl AgentLoop {
                         permissions: permission_manager.clone(),
                         observer: Some(observer.clone()),
                         session_id: self.session_id.clone(),
+                        scope: self.scope.clone(),
+                        job_id: Some(job.id.clone()),
+                    };
+
+                    record(trace::TraceEntry::ToolCall {
+                        step: steps_count,
+                        tool: tool_name.clone(),
+                        args: {
+                            let mut args = args.clone();
+                            redactor.redact_value(&mut args);
+                            args
+                        },
+                    });
+
+                    // LOCK: keep other jobs out of the workspace (or the file) while this call changes it
+                    let mut lock_error = None;
+                    let workspace_lock = if self.replay.is_none() && self.dry_run.is_none() && !tool.is_read_only(&args) {
+                        let scope = tool.lock_scope(&args);
+                        let file = match &scope {
+                            crate::locks::LockScope::File(file) => Some(file.clone()),
+                            _ => None,
+                        };
+                        let holder = crate::locks::LockHolder {
+                            job_id: job.id.clone(),
+                            session_id: self.session_id.clone(),
+                            tool: tool_name.clone(),
+                        };
+                        let workspace = self.snapshot_manager.root_path();
+                        let on_wait = |held_by: &crate::locks::LockHolder| {
+                            let _ = observer.emit(
+                                &format!("session:{}", self.session_id),
+                                serde_json::to_value(AgentEvent::WorkspaceLockWaiting {
+                                    job_id: job.id.clone(),
+                                    workspace: workspace.display().to_string(),
+                                    file,
+                                    held_by: held_by.clone(),
+                                }).unwrap(),
+                            );
+                        };
+                        match crate::locks::global()
+                            .acquire(workspace, scope, holder, crate::locks::LOCK_TIMEOUT, on_wait)
+                            .await
+                        {
+                            Ok(lock) => Some(lock),
+                            Err(e) => {
+                                log::warn!("{} in job {} not run: {}", tool_name, job.id, e);
+                                lock_error = Some(e);
+                                None
+                            }
+                        }
+                    } else {
+                        None
                     };
 
+                    // CHECKPOINT (git workspaces with checkpoints enabled)
+                    if let Some(checkpoints) = self.snapshot_manager.checkpoints().filter(|_| self.replay.is_none() && self.dry_run.is_none()) {
+                        if !tool.is_read_only(&args) && lock_error.is_none() {
+                            checkpoint_count += 1;
+                            let message = format!("Before {} (job {})", tool_name, job.id);
+                            if let Err(e) = checkpoints.create(&job.id, checkpoint_count, &message) {
+                                log::warn!("Failed to create checkpoint: {}", e);
+                            }
+                        }
+                    }
+
                     // SNAPSHOT START
                     let pre_snapshot = self.snapshot_manager.create_snapshot().ok();
 
                     // REMOVE GENERIC R FROM TOOL CALL IS NOT NEEDED, execute IS ALREADY CORRECT
 
-                    let execution_result = tool
-                        .execute(args.clone(), &ctx)
-                        .await
-                        .unwrap_or_else(|e| Value::String(format!("Error: {}", e)));
+                    // Identical read-only calls reuse the result while the workspace is
+                    // unchanged; edits made outside the job since the last call clear it first
+                    if let Some(pre) = &pre_snapshot {
+                        tool_cache.sync_workspace(&self.snapshot_manager, pre);
+                    }
+                    let cache_ttl = tool.cache_ttl().filter(|_| tool.is_read_only(&args));
+                    let cached = cache_ttl.and_then(|_| tool_cache.get(&tool_name, &args));
+                    let from_cache = cached.is_some();
+
+                    // A hung tool is cancelled after its timeout (and maybe retried)
+                    // instead of stalling the whole job
+                    let (mut execution_result, timed_out) = if let Some(replay) = &self.replay {
+                        (replay.tool_result(&tool_name), false)
+                    } else if let Some(e) = lock_error {
+                        (json!({ "error": e }), false)
+                    } else if let Some(hit) = cached {
+                        log::debug!("Using cached result for {}", tool_name);
+                        (hit, false)
+                    } else {
+                        match middleware.execute(tool.as_ref(), args.clone(), &ctx).await {
+                            Ok(result) => (result, false),
+                            Err(e) => (
+                                e.to_result(&tool_name),
+                                matches!(e, crate::tools::ToolRunError::TimedOut { .. }),
+                            ),
+                        }
+                    };
 
                     // SNAPSHOT END & DIFF
                     if let Some(pre) = pre_snapshot {
@@ -590,19 +1004,93 @@ impl AgentLoop {
                                     &format!("session:{}", self.session_id),
                                     serde_json::to_value(AgentEvent::Thinking { message: diff_msg }).unwrap(),
                                 );
+                                let _ = observer.emit(
+                                    &format!("session:{}", self.session_id),
+                                    serde_json::to_value(AgentEvent::WorkspaceDiff {
+                                        job_id: job.id.clone(),
+                                        step_id: step.id.clone(),
+                                        files: self.snapshot_manager.diff_contents(&pre, &post),
+                                    }).unwrap(),
+                                );
                             }
+                            tool_cache.sync_workspace(&self.snapshot_manager, &post);
                         }
                     }
+                    drop(workspace_lock);
 
                     // 3. Verification
-                    let success = tool.verify_result(&execution_result);
+                    let success = !timed_out && tool.verify_result(&execution_result);
                     let status = if success { "completed" } else { "failed" };
+                    if success && !from_cache {
+                        if let Some(ttl) = cache_ttl {
+                            tool_cache.set_with_ttl(&tool_name, &args, execution_result.clone(), ttl);
+                        }
+                    }
+
+                    // Images (view_image) go to the model as content, not as text
+                    let images = crate::llm::image::take_images(&mut execution_result);
+
+                    // Files the tool reports as output of the job
+                    let outputs = crate::models::artifact::take_artifacts(&mut execution_result);
+                    if success && self.replay.is_none() {
+                        for output in outputs {
+                            let registered = crate::models::artifact::register(
+                                db_pool,
+                                &self.session_id,
+                                &job.id,
+                                self.snapshot_manager.root_path(),
+                                output,
+                            );
+                            match registered {
+                                Ok(artifact) => {
+                                    let _ = observer.emit(
+                                        &format!("session:{}", self.session_id),
+                                        serde_json::to_value(AgentEvent::ArtifactCreated {
+                                            job_id: job.id.clone(),
+                                            artifact,
+                                        }).unwrap(),
+                                    );
+                                }
+                                Err(e) => log::warn!("Failed to register artifact of {}: {}", tool_name, e),
+                            }
+                        }
+                    }
+
+                    let report = self.dlp.scan_value(&mut execution_result, DlpTarget::ToolResults);
+                    if !report.is_clean() {
+                        self.record_dlp_incident(db_pool, Some(&tool_name), &report);
+                        if report.blocked {
+                            execution_result = json!({
+                                "error": format!(
+                                    "Result withheld: it matches DLP rule(s) {}",
+                                    report.rule_names().join(", ")
+                                )
+                            });
+                        }
+                    }
+
+                    if let Some(hooks) = &hooks {
+                        hooks.post_tool_call(&hook_ctx, &tool_name, &args, &mut execution_result, success).await;
+                        redactor.redact_value(&mut execution_result);
+                    }
+                    record(trace::TraceEntry::ToolResult {
+                        step: steps_count,
+                        tool: tool_name.clone(),
+                        result: execution_result.clone(),
+                        success,
+                    });
+                    if self.replay.is_none() {
+                        let usage = self.usage_ref(&job.id, Some(&route));
+                        if let Err(e) = crate::models::usage::record_tool_call(db_pool, usage, &tool_name, success) {
+                            log::warn!("Failed to record tool call usage: {}", e);
+                        }
+                    }
 
                     // 4. Summarization
                     let mut final_result = execution_result.to_string();
 
                     // Smart truncation (Safety to prevent token overflow)
-                    final_result = truncate_tool_result(&tool_name, &final_result);
+                    final_result = truncate_tool_result(&context, &tool_name, &final_result);
 
                     let _ = observer.emit(
                         &format!("session:{}", self.session_id),
@@ -618,7 +1106,7 @@ impl AgentLoop {
 
                     // Add result to history and DB
                     let tool_result_msg = format!("Tool '{}' result: {}", tool_name, final_result);
-                    let truncated_tool_result = truncate_message_content(&tool_result_msg, "user");
+                    let truncated_tool_result = truncate_message_content(&context, &tool_result_msg, "user");
 
                     // IMPORTANT: Save as 'tool' role with args as metadata
                     save_message(
@@ -630,12 +1118,9 @@ impl AgentLoop {
                     );
 
                     self.history
-                        .push(create_user_message(truncated_tool_result));
+                        .push(user_message_with_images(&truncated_tool_result, &images));
                 }
 
-                // Optimize history to prevent context overflow
-                optimize_history_by_tokens(&mut self.history, MAX_HISTORY_TOKENS);
-
                 continue; // Loop again
             }
 
@@ -645,127 +1130,237 @@ impl AgentLoop {
 
             // Add to history with truncation
             let truncated_final_response =
-                truncate_message_content(&final_response_text, "assistant");
+                truncate_message_content(&context, &final_response_text, "assistant");
             self.history
                 .push(create_assistant_message(truncated_final_response));
             break;
         }
 
+        tracing::Span::current().record("steps", steps_count);
+        record(trace::TraceEntry::Finish {
+            response: redactor.redact(&final_response_text).to_string(),
+        });
+
         // Save to DB (only if we have a response)
         if !final_response_text.is_empty() {
             save_message(
                 db_pool,
                 "assistant",
-                &final_response_text,
+                &redactor.redact(&final_response_text),
                 &self.session_id,
-                None,
+                Some(route.message_metadata(on_fallback)),
             );
         }
 
-        // Auto-generate title if this is the first turn
+        // Name the session after the first message, then summarize the
+        // first exchanges (see `title`)
         let history_len = self.history.len();
-
         if history_len < 3 {
-            // FALLBACK STAGE: Set title to User Message if it's currently default
-            use crate::schema::sessions;
-            let should_fallback = if let Ok(mut conn) = db_pool.get() {
-                let current_title: Option<String> = sessions::table
-                    .filter(sessions::id.eq(&self.session_id))
-                    .select(sessions::title)
-                    .first(&mut conn)
-                    .unwrap_or(None);
-
-                match current_title {
-                    Some(t) => t.is_empty() || t == "New Chat",
-                    None => true,
-                }
-            } else {
-                false
-            };
-
-            if should_fallback {
-                let fallback_title = user_message.chars().take(30).collect::<String>();
-                if let Ok(mut conn) = db_pool.get() {
-                    let _ =
-                        diesel::update(sessions::table.filter(sessions::id.eq(&self.session_id)))
-                            .set(sessions::title.eq(&fallback_title))
-                            .execute(&mut conn);
+            match title::apply_fallback(db_pool, &self.session_id, &user_message) {
+                Ok(true) => {
                     let _ = observer.emit("sessions_updated", serde_json::Value::Null);
                 }
+                Ok(false) => {}
+                Err(e) => log::warn!("Failed to set session title: {}", e),
             }
-        } else if (3..=10).contains(&history_len) {
-            // LLM GENERATION STAGE: Generate summary title in background
-            let session_id_clone = self.session_id.clone();
-            let model_clone = self.model.clone();
-            let provider_clone = self.provider.clone();
-            let db_pool_clone = db_pool.clone();
-            let observer_clone = observer.clone();
-            let user_query = user_message.clone();
+        } else if (3..=10).contains(&history_len) && self.replay.is_none() {
+            let session_id = self.session_id.clone();
+            let provider = self.provider.clone();
+            let history = self.history.clone();
+            let db_pool = db_pool.clone();
+            let observer = observer.clone();
 
             tokio::spawn(async move {
-                let preamble = "You are a helpful assistant. Generate a very concise title (3-5 words) for a chat session based on the interaction so far. Do not use quotes. Do not say 'Title:'. Just the title.";
-
-                let title_result = match provider_clone.as_str() {
-                    "openai" => {
-                        let api_key = std::env::var("OPENAI_API_KEY").unwrap_or_default();
-                        if !api_key.is_empty() {
-                            let client = openai::Client::from_env();
-                            let agent = client.agent(&model_clone).preamble(preamble).build();
-                            agent.prompt(&user_query).await.ok()
-                        } else {
-                            None
-                        }
-                    }
-                    "gemini" => {
-                        let api_key = std::env::var("GEMINI_API_KEY").unwrap_or_default();
-                        if !api_key.is_empty() {
-                            let client = gemini::Client::from_env();
-                            let title_model = if model_clone.contains("flash") {
-                                model_clone.clone()
-                            } else {
-                                "gemini-2.0-flash".to_string()
-                            };
-                            let agent = client.agent(&title_model).preamble(preamble).build();
-                            agent.prompt(&user_query).await.ok()
-                        } else {
-                            None
-                        }
-                    }
-                    "anthropic" => {
-                        let api_key = std::env::var("ANTHROPIC_API_KEY").unwrap_or_default();
-                        if !api_key.is_empty() {
-                            let client = anthropic::Client::from_env();
-                            let title_model = "claude-3-haiku-20240307";
-                            let agent = client.agent(title_model).preamble(preamble).build();
-                            agent.prompt(&user_query).await.ok()
-                        } else {
-                            None
-                        }
-                    }
-                    _ => None,
+                let result = match title::generate(&provider, &history).await {
+                    Ok(title) => title::apply(&db_pool, &session_id, &title),
+                    Err(e) => Err(e),
                 };
-
-                if let Some(title) = title_result {
-                    let clean_title = title.trim().trim_matches('"').to_string();
-                    if !clean_title.is_empty() {
-                        use crate::schema::sessions;
-                        if let Ok(mut conn) = db_pool_clone.get() {
-                            let _ = diesel::update(
-                                sessions::table.filter(sessions::id.eq(&session_id_clone)),
-                            )
-                            .set(sessions::title.eq(&clean_title))
-                            .execute(&mut conn);
-
-                            let _ = observer_clone.emit("sessions_updated", serde_json::Value::Null);
-                        }
+                match result {
+                    Ok(true) => {
+                        let _ = observer.emit("sessions_updated", serde_json::Value::Null);
                     }
+                    Ok(false) => {}
+                    Err(e) => log::debug!("No title generated for session {}: {}", session_id, e),
                 }
             });
         }
     }
 }
 
-fn save_message(
+/// Sandbox mode for tool execution from the agent's execution settings,
+/// default "flexible". `isolation: strict` forces every command into a microVM.
+fn execution_mode(execution_settings: Option<&Value>) -> String {
+    let strict_isolation = execution_settings
+        .and_then(|json| json.get("isolation").and_then(|m| m.as_str()))
+        == Some("strict");
+    if strict_isolation {
+        "microvm".to_string()
+    } else {
+        execution_settings
+            .and_then(|json| json.get("sandbox_mode").and_then(|m| m.as_str()))
+            .unwrap_or("flexible")
+            .to_string()
+    }
+}
+
+/// The agent's skills, parsed (for triggers) and wrapped as tools
+fn load_skill_tools(
+    agent_db: &DbAgent,
+    db_pool: &DbPool,
+    tools_config: &AgentToolsConfig,
+    execution_mode: &str,
+    read_only_policy: Option<&ReadOnlyPolicy>,
+) -> (Vec<crate::models::ParsedSkill>, Vec<Box<dyn Tool>>) {
+    let mut skills: Vec<crate::models::ParsedSkill> = vec![];
+    let mut tools: Vec<Box<dyn Tool>> = vec![];
+    if let Ok(mut conn) = db_pool.get() {
+        use crate::schema::{agent_skill_assignments, agent_skills, skill_files};
+        
+        // Check if this is the default agent
+        let is_default_agent = agent_db.name == "AnyCoworker Default";
+        
+        // 1. Get skill IDs - either all enabled (for default) or assigned (for custom agents)
+        let skill_ids: Vec<String> = if is_default_agent {
+            // Default agent uses ALL enabled skills
+            agent_skills::table
+                .filter(agent_skills::enabled.eq(1))
+                .select(agent_skills::id)
+                .load::<String>(&mut conn)
+                .unwrap_or_default()
+        } else {
+            // Custom agents use only assigned skills
+            agent_skill_assignments::table
+                .filter(agent_skill_assignments::agent_id.eq(&agent_db.id))
+                .select(agent_skill_assignments::skill_id)
+                .load::<String>(&mut conn)
+                .unwrap_or_default()
+        };
+
+        if !skill_ids.is_empty() {
+            // 2. Fetch Skills
+            let skill_rows: Result<Vec<crate::models::AgentSkill>, _> = agent_skills::table
+                .filter(agent_skills::id.eq_any(&skill_ids))
+                .filter(agent_skills::enabled.eq(1))
+                .load::<crate::models::AgentSkill>(&mut conn);
+
+            if let Ok(skills_list) = skill_rows {
+                for skill_db in skills_list {
+                    if !tools_config.is_enabled(&skill_db.name) {
+                        continue;
+                    }
+                    // 3. Fetch Skill Files
+                    let files_list: Result<Vec<crate::models::SkillFile>, _> = skill_files::table
+                        .filter(skill_files::skill_id.eq(&skill_db.id))
+                        .load::<crate::models::SkillFile>(&mut conn);
+
+                    let loaded_skill = crate::skills::loader::LoadedSkill::from_db(
+                        &skill_db,
+                        files_list.unwrap_or_default(),
+                    );
+                    skills.push(loaded_skill.skill.clone());
+
+                    // Determine workspace path
+                    let workspace_path = if let Some(path) = &agent_db.workspace_path {
+                        let p = std::path::PathBuf::from(path);
+                        // Ensure it exists
+                        std::fs::create_dir_all(&p).unwrap_or(());
+                        p
+                    } else {
+                        std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."))
+                    };
+
+                    // Create SkillTool
+                    let skill_tool = crate::skills::SkillTool::new(
+                        loaded_skill, 
+                        workspace_path.clone(),
+                        execution_mode.to_string() // Pass agent mode
+                    )
+                    .with_read_only_policy(read_only_policy.cloned());
+                    tools.push(Box::new(skill_tool));
+                }
+            }
+        }
+    }
+
+    (skills, tools)
+}
+
+/// Tools of the enabled MCP servers assigned to the agent
+async fn load_mcp_tools(agent_db: &DbAgent, db_pool: &DbPool, tools_config: &AgentToolsConfig) -> Vec<Box<dyn Tool>> {
+    let server_ids: Vec<String> = agent_db
+        .mcp_servers
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    crate::mcp::load_server_tools(db_pool, &server_ids)
+        .await
+        .into_iter()
+        .filter(|tool| tools_config.is_enabled(tool.name()))
+        .collect()
+}
+
+/// Reload one tool group (`GROUP_SKILLS` or `GROUP_MCP`) in every running loop
+/// of `agent_id`, e.g. after a skill is assigned or an MCP server is started.
+/// The loops pick the change up before their next step.
+pub async fn refresh_agent_tools(db_pool: &DbPool, agent_id: &str, group: &str) -> Result<(), String> {
+    let registries = registry::live_registries(agent_id);
+    if registries.is_empty() {
+        return Ok(());
+    }
+    let agent_db = {
+        use crate::schema::agents;
+        let mut conn = db_pool.get().map_err(|e| e.to_string())?;
+        agents::table
+            .find(agent_id)
+            .first::<DbAgent>(&mut conn)
+            .map_err(|e| e.to_string())?
+    };
+    let tools_config = AgentToolsConfig::from_agent(&agent_db);
+    let tools: Vec<Arc<dyn Tool>> = match group {
+        GROUP_SKILLS => {
+            let settings = agent_db
+                .execution_settings
+                .as_ref()
+                .and_then(|s| serde_json::from_str::<Value>(s).ok());
+            load_skill_tools(
+                &agent_db,
+                db_pool,
+                &tools_config,
+                &execution_mode(settings.as_ref()),
+                settings.as_ref().and_then(ReadOnlyPolicy::from_execution_settings).as_ref(),
+            )
+                .1
+                .into_iter()
+                .map(Arc::from)
+                .collect()
+        }
+        GROUP_MCP => load_mcp_tools(&agent_db, db_pool, &tools_config)
+            .await
+            .into_iter()
+            .map(Arc::from)
+            .collect(),
+        other => return Err(format!("Tool group '{}' cannot be reloaded", other)),
+    };
+    for registry in registries {
+        let change = registry.replace_group(group, tools.clone());
+        log::info!("Reloaded {} tools of agent {}: {:?}", group, agent_id, change);
+    }
+    Ok(())
+}
+
+/// `refresh_agent_tools` for every agent that is currently running
+pub async fn refresh_live_agents(db_pool: &DbPool, group: &str) {
+    for agent_id in registry::live_agent_ids() {
+        if let Err(e) = refresh_agent_tools(db_pool, &agent_id, group).await {
+            log::warn!("Failed to reload {} tools of agent {}: {}", group, agent_id, e);
+        }
+    }
+}
+
+pub(crate) fn save_message(
     db_pool: &DbPool,
     role: &str,
     content: &str,
@@ -773,27 +1368,27 @@ fn save_message(
     metadata_json: Option<String>,
 ) {
     use crate::schema::messages;
-    if let Ok(mut conn) = db_pool.get() {
+    // Serialized so the duplicate check and the insert can't interleave with
+    // another agent's save
+    let saved = crate::database::serialized_write(db_pool, |conn| {
         // Check for duplicates in the last 5 messages to prevent accidental re-saves
         // This can happen if the same message is added to history multiple times
-        let recent_messages: Result<Vec<crate::models::Message>, _> = messages::table
+        let recent: Vec<crate::models::Message> = messages::table
             .filter(messages::session_id.eq(session_id))
             .filter(messages::role.eq(role))
+            .filter(messages::deleted_at.is_null())
             .order(messages::created_at.desc())
             .limit(5)
-            .load(&mut conn);
-
-        if let Ok(recent) = recent_messages {
-            // Check if this exact content was just saved
-            let is_duplicate = recent.iter().any(|m| m.content == content);
-            if is_duplicate {
-                log::debug!(
-                    "Skipping duplicate message save: role={}, content_preview={}",
-                    role,
-                    content.chars().take(50).collect::<String>()
-                );
-                return;
-            }
+            .load(conn)?;
+
+        // Check if this exact content was just saved
+        if recent.iter().any(|m| m.content == content) {
+            log::debug!(
+                "Skipping duplicate message save: role={}, content_preview={}",
+                role,
+                content.chars().take(50).collect::<String>()
+            );
+            return Ok(());
         }
 
         let msg = crate::models::NewMessage {
@@ -804,16 +1399,59 @@ fn save_message(
             metadata_json,
             tokens: None,
         };
-        let _ = diesel::insert_into(messages::table)
-            .values(&msg)
-            .execute(&mut conn);
+        diesel::insert_into(messages::table).values(&msg).execute(conn).map(|_| ())
+    });
+    if let Err(e) = saved {
+        log::error!("Failed to save {} message: {}", role, e);
     }
 }
 
+/// Messages of a session replayed to a worker that joins it
+pub(crate) const SESSION_HISTORY_LIMIT: i64 = 20;
+/// Notes recalled from pages into the prompt of agents with `memory` on
+const RECALLED_NOTES: i64 = 3;
+/// How long a job that lost its provider waits for it before failing
+const MAX_OFFLINE_WAIT: std::time::Duration = std::time::Duration::from_secs(30 * 60);
+
+/// Directory the agent works in: its workspace, or the current directory
+pub(crate) fn workspace_path(agent_db: &DbAgent) -> std::path::PathBuf {
+    match &agent_db.workspace_path {
+        Some(path) => std::path::PathBuf::from(path),
+        None => std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from(".")),
+    }
+}
+
+/// The last `limit` live messages of a session as LLM history. Tool steps
+/// are left out; user messages carry the images they were sent with.
+pub(crate) fn load_session_history(db_pool: &DbPool, session_id: &str, limit: i64) -> Vec<rig::completion::Message> {
+    use crate::schema::messages;
+    let Ok(mut conn) = db_pool.get() else {
+        return vec![];
+    };
+    let mut stored: Vec<crate::models::Message> = messages::table
+        .filter(messages::session_id.eq(session_id))
+        .filter(messages::deleted_at.is_null())
+        .order(messages::created_at.desc())
+        .limit(limit)
+        .load(&mut conn)
+        .unwrap_or_default();
+    stored.reverse();
+    stored
+        .into_iter()
+        .filter_map(|msg| match msg.role.as_str() {
+            "user" => Some(crate::llm::image::stored_user_message(&msg.content, msg.metadata_json.as_deref())),
+            "assistant" | "model" => Some(rig::completion::Message::assistant(&msg.content)),
+            _ => None,
+        })
+        .collect()
+}
+
 // Updated start_chat_task
 // Updated start_chat_task
 pub mod coordinator;
 pub mod planner;
+pub mod queue;
+pub mod team;
 
 
 // Updated start_chat_task
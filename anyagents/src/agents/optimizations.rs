@@ -2,6 +2,7 @@
 ///
 /// This module contains performance optimizations and improvements
 /// for agent execution in production environments.
+use crate::llm::context::ModelContext;
 use std::time::Duration;
 use tokio::time::timeout;
 use uuid::Uuid;
@@ -46,21 +47,64 @@ pub struct ToolExecutionResult {
     pub cached: bool,
 }
 
-/// Simple in-memory cache for tool results
-/// In production, consider using Redis or similar
+/// Entries a cache holds before the least recently used one is evicted
+pub const DEFAULT_CACHE_CAPACITY: usize = 256;
+/// Sessions whose caches are kept by `ToolResultCache::for_session`
+const MAX_CACHED_SESSIONS: usize = 32;
+
+struct CacheEntry {
+    value: serde_json::Value,
+    stored_at: std::time::Instant,
+    ttl: Duration,
+    last_used: std::time::Instant,
+}
+
+/// In-memory LRU cache for tool results, keyed by (tool, args hash)
 pub struct ToolResultCache {
-    cache: std::sync::Arc<dashmap::DashMap<String, (serde_json::Value, std::time::Instant)>>,
+    cache: std::sync::Arc<dashmap::DashMap<String, CacheEntry>>,
     ttl: Duration,
+    capacity: usize,
+    /// Workspace state the cached results were computed against
+    workspace: std::sync::Mutex<Option<crate::snapshots::Snapshot>>,
 }
 
+/// Session caches, least recently used first
+type SessionCaches = Vec<(String, std::sync::Arc<ToolResultCache>)>;
+static SESSION_CACHES: std::sync::LazyLock<std::sync::Mutex<SessionCaches>> =
+    std::sync::LazyLock::new(|| std::sync::Mutex::new(vec![]));
+
 impl ToolResultCache {
     pub fn new(ttl: Duration) -> Self {
+        Self::with_capacity(ttl, DEFAULT_CACHE_CAPACITY)
+    }
+
+    pub fn with_capacity(ttl: Duration, capacity: usize) -> Self {
         Self {
             cache: std::sync::Arc::new(dashmap::DashMap::new()),
             ttl,
+            capacity: capacity.max(1),
+            workspace: std::sync::Mutex::new(None),
         }
     }
 
+    /// The cache shared by every agent loop of `session_id`
+    pub fn for_session(session_id: &str) -> std::sync::Arc<Self> {
+        let mut sessions = SESSION_CACHES.lock().unwrap();
+        if let Some(pos) = sessions.iter().position(|(id, _)| id == session_id) {
+            // Most recently used session last
+            let entry = sessions.remove(pos);
+            let cache = entry.1.clone();
+            sessions.push(entry);
+            return cache;
+        }
+        if sessions.len() >= MAX_CACHED_SESSIONS {
+            sessions.remove(0);
+        }
+        let cache = std::sync::Arc::new(Self::new(Duration::from_secs(300)));
+        sessions.push((session_id.to_string(), cache.clone()));
+        cache
+    }
+
     /// Generate cache key from tool name and arguments
     fn generate_key(tool_name: &str, args: &serde_json::Value) -> String {
         use sha2::{Digest, Sha256};
@@ -73,10 +117,10 @@ impl ToolResultCache {
     /// Get cached result if available and not expired
     pub fn get(&self, tool_name: &str, args: &serde_json::Value) -> Option<serde_json::Value> {
         let key = Self::generate_key(tool_name, args);
-        if let Some(entry) = self.cache.get(&key) {
-            let (value, timestamp) = entry.value();
-            if timestamp.elapsed() < self.ttl {
-                return Some(value.clone());
+        if let Some(mut entry) = self.cache.get_mut(&key) {
+            if entry.stored_at.elapsed() < entry.ttl {
+                entry.last_used = std::time::Instant::now();
+                return Some(entry.value.clone());
             }
             // Expired, remove it
             drop(entry);
@@ -87,8 +131,32 @@ impl ToolResultCache {
 
     /// Store result in cache
     pub fn set(&self, tool_name: &str, args: &serde_json::Value, result: serde_json::Value) {
+        self.set_with_ttl(tool_name, args, result, self.ttl);
+    }
+
+    /// Store result in cache with its own time to live
+    pub fn set_with_ttl(&self, tool_name: &str, args: &serde_json::Value, result: serde_json::Value, ttl: Duration) {
         let key = Self::generate_key(tool_name, args);
-        self.cache.insert(key, (result, std::time::Instant::now()));
+        if !self.cache.contains_key(&key) && self.cache.len() >= self.capacity {
+            let oldest = self
+                .cache
+                .iter()
+                .min_by_key(|entry| entry.last_used)
+                .map(|entry| entry.key().clone());
+            if let Some(oldest) = oldest {
+                self.cache.remove(&oldest);
+            }
+        }
+        let now = std::time::Instant::now();
+        self.cache.insert(
+            key,
+            CacheEntry {
+                value: result,
+                stored_at: now,
+                ttl,
+                last_used: now,
+            },
+        );
     }
 
     /// Clear all cached results
@@ -96,11 +164,27 @@ impl ToolResultCache {
         self.cache.clear();
     }
 
+    /// Record the current workspace state, clearing the cache when it differs
+    /// from the state the cached results were computed against. Returns true
+    /// when the cache was cleared.
+    pub fn sync_workspace(&self, snapshots: &crate::snapshots::SnapshotManager, current: &crate::snapshots::Snapshot) -> bool {
+        let mut workspace = self.workspace.lock().unwrap();
+        let changed = workspace.as_ref().is_some_and(|previous| {
+            let diff = snapshots.diff(previous, current);
+            !diff.new_files.is_empty() || !diff.modified_files.is_empty() || !diff.deleted_files.is_empty()
+        });
+        if changed {
+            self.clear();
+        }
+        *workspace = Some(current.clone());
+        changed
+    }
+
     /// Get cache statistics
     pub fn stats(&self) -> CacheStats {
         CacheStats {
             size: self.cache.len(),
-            capacity: self.cache.capacity(),
+            capacity: self.capacity,
         }
     }
 }
@@ -182,35 +266,32 @@ pub fn trim_history(history: &mut Vec<rig::completion::Message>, max_size: usize
     }
 }
 
-/// Truncation configuration constants
-pub const MAX_TOOL_RESULT_CHARS: usize = 8000; // ~2000 tokens
-pub const MAX_TOOL_RESULT_TOKENS: usize = 2000;
-pub const MAX_HISTORY_TOKENS: usize = 100000; // Safe limit for context window
-pub const TRUNCATION_KEEP_START: usize = 6000; // Characters to keep from start
-pub const TRUNCATION_KEEP_END: usize = 2000; // Characters to keep from end
-
-/// Estimate token count (simplified approximation)
+/// Rough token count (four characters a token), for callers that don't know
+/// the model; `ModelContext::count_tokens` counts exactly
 pub fn estimate_tokens(text: &str) -> usize {
-    // Rough estimate: 1 token ≈ 4 characters
     text.len() / 4
 }
 
 /// Calculate total tokens in history
-pub fn calculate_history_tokens(history: &[rig::completion::Message]) -> usize {
+pub fn calculate_history_tokens(history: &[rig::completion::Message], context: &ModelContext) -> usize {
     history
         .iter()
-        .map(|msg| estimate_tokens(&get_message_content(msg)))
+        .map(|msg| context.count_tokens(&get_message_content(msg)))
         .sum()
 }
 
 /// Optimize history by removing older messages if token count is too high
-pub fn optimize_history_by_tokens(history: &mut Vec<rig::completion::Message>, max_tokens: usize) {
-    let mut total_tokens = calculate_history_tokens(history);
+pub fn optimize_history_by_tokens(
+    history: &mut Vec<rig::completion::Message>,
+    context: &ModelContext,
+    max_tokens: usize,
+) {
+    let mut total_tokens = calculate_history_tokens(history, context);
 
     while total_tokens > max_tokens && !history.is_empty() {
         // Remove oldest message
         let removed = history.remove(0);
-        total_tokens -= estimate_tokens(&get_message_content(&removed));
+        total_tokens -= context.count_tokens(&get_message_content(&removed));
     }
 }
 
@@ -224,85 +305,84 @@ pub fn smart_truncate(text: &str, max_chars: usize) -> String {
     // Calculate how much we're removing
     let removed_chars = text.len() - max_chars;
 
-    // Use configured keep amounts, but adjust if max_chars is small
-    let keep_start = TRUNCATION_KEEP_START.min(max_chars * 3 / 4);
-    let keep_end = TRUNCATION_KEEP_END.min(max_chars / 4);
-
-    // Make sure we don't exceed max_chars
-    let actual_keep_start = keep_start.min(max_chars - keep_end - 100); // 100 for truncation message
-    let actual_keep_end = keep_end.min(max_chars - actual_keep_start - 100);
+    // Three quarters from the start, the rest from the end, minus room for the marker
+    let room = max_chars.saturating_sub(100);
+    let keep_start = text.floor_char_boundary(room * 3 / 4);
+    let suffix_start = text.ceil_char_boundary(text.len() - room / 4);
 
-    // Ensure valid char boundaries
-    let actual_keep_start = text.floor_char_boundary(actual_keep_start);
-    let suffix_start = text.ceil_char_boundary(text.len() - actual_keep_end);
-
-    let start_part = &text[..actual_keep_start];
+    let start_part = &text[..keep_start];
     let end_part = &text[suffix_start..];
 
     format!(
         "{}...\n\n[TRUNCATED {} characters ({} tokens)]\n\n...{}",
         start_part,
         removed_chars,
-        estimate_tokens(&text[actual_keep_start..text.len() - actual_keep_end]),
+        estimate_tokens(&text[keep_start..suffix_start]),
         end_part
     )
 }
 
-/// Truncate tool result with smart strategy based on content type
-pub fn truncate_tool_result(tool_name: &str, result: &str) -> String {
-    // If within limits, return as-is
-    if result.len() <= MAX_TOOL_RESULT_CHARS {
-        return result.to_string();
+/// `text` cut down to about `max_tokens`, or None when it already fits
+fn truncate_to_tokens(context: &ModelContext, text: &str, max_tokens: usize) -> Option<String> {
+    // A token is at least a character; skip counting what surely fits
+    if text.len() <= max_tokens {
+        return None;
     }
-
-    // Check estimated tokens
-    let estimated_tokens = estimate_tokens(result);
-    if estimated_tokens <= MAX_TOOL_RESULT_TOKENS {
-        return result.to_string();
+    let tokens = context.count_tokens(text);
+    if tokens <= max_tokens {
+        return None;
     }
+    Some(smart_truncate(text, text.len() * max_tokens / tokens))
+}
 
-    // Apply smart truncation
-    let truncated = smart_truncate(result, MAX_TOOL_RESULT_CHARS);
-
-    log::warn!(
-        "Truncated {} result from {} chars ({} tokens) to {} chars",
-        tool_name,
-        result.len(),
-        estimated_tokens,
-        truncated.len()
-    );
-
-    truncated
+/// Truncate a tool result to what the model's window allows for one
+pub fn truncate_tool_result(context: &ModelContext, tool_name: &str, result: &str) -> String {
+    match truncate_to_tokens(context, result, context.message_budget()) {
+        Some(truncated) => {
+            log::warn!(
+                "Truncated {} result from {} chars to {} chars ({} token budget)",
+                tool_name,
+                result.len(),
+                truncated.len(),
+                context.message_budget()
+            );
+            truncated
+        }
+        None => result.to_string(),
+    }
 }
 
 /// Truncate message content before adding to history
-pub fn truncate_message_content(content: &str, role: &str) -> String {
+pub fn truncate_message_content(context: &ModelContext, content: &str, role: &str) -> String {
     // Assistant messages are usually shorter, so we're more lenient
-    let max_chars = if role == "assistant" {
-        MAX_TOOL_RESULT_CHARS * 2 // Allow longer assistant responses
+    let max_tokens = if role == "assistant" {
+        context.message_budget() * 2
     } else {
-        MAX_TOOL_RESULT_CHARS
+        context.message_budget()
     };
-
-    if content.len() <= max_chars {
-        return content.to_string();
-    }
-
-    smart_truncate(content, max_chars)
+    truncate_to_tokens(context, content, max_tokens).unwrap_or_else(|| content.to_string())
 }
 
-/// Extract content from message (handling Enum variants)
-/// Extract content from message (handling Enum variants)
+/// Text of a message; images count as a short placeholder rather than their
+/// base64 payload, other parts fall back to their Debug form
 pub fn get_message_content(msg: &rig::completion::Message) -> String {
+    use rig::completion::message::{AssistantContent, UserContent};
     match msg {
         rig::completion::Message::User { content, .. } => content
             .iter()
-            .map(|c| format!("{:?}", c))
+            .map(|c| match c {
+                UserContent::Text(t) => t.text.clone(),
+                UserContent::Image(_) => "[image]".to_string(),
+                other => format!("{:?}", other),
+            })
             .collect::<Vec<_>>()
             .join(" "),
         rig::completion::Message::Assistant { content, .. } => content
             .iter()
-            .map(|c| format!("{:?}", c))
+            .map(|c| match c {
+                AssistantContent::Text(t) => t.text.clone(),
+                other => format!("{:?}", other),
+            })
             .collect::<Vec<_>>()
             .join(" "),
     }
@@ -323,6 +403,7 @@ pub fn create_assistant_message(content: String) -> rig::completion::Message {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm::context::Tokenizer;
 
     #[test]
     fn test_optimization_config_default() {
@@ -364,6 +445,48 @@ mod tests {
         assert!(cache.get("filesystem", &args).is_none());
     }
 
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let cache = ToolResultCache::with_capacity(Duration::from_secs(60), 2);
+        let (a, b, c) = (serde_json::json!({"q": "a"}), serde_json::json!({"q": "b"}), serde_json::json!({"q": "c"}));
+        cache.set("search_files", &a, serde_json::json!(1));
+        std::thread::sleep(Duration::from_millis(2));
+        cache.set("search_files", &b, serde_json::json!(2));
+        std::thread::sleep(Duration::from_millis(2));
+        assert!(cache.get("search_files", &a).is_some());
+
+        cache.set("search_files", &c, serde_json::json!(3));
+        assert!(cache.get("search_files", &b).is_none());
+        assert!(cache.get("search_files", &a).is_some());
+        assert_eq!(cache.stats().size, 2);
+    }
+
+    #[test]
+    fn test_cache_cleared_when_workspace_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshots = crate::snapshots::SnapshotManager::new(dir.path());
+        let cache = ToolResultCache::new(Duration::from_secs(60));
+        let args = serde_json::json!({"operation": "list_dir", "path": "."});
+
+        assert!(!cache.sync_workspace(&snapshots, &snapshots.create_snapshot().unwrap()));
+        cache.set("filesystem", &args, serde_json::json!([]));
+        assert!(!cache.sync_workspace(&snapshots, &snapshots.create_snapshot().unwrap()));
+        assert!(cache.get("filesystem", &args).is_some());
+
+        std::fs::write(dir.path().join("new.txt"), "hello").unwrap();
+        assert!(cache.sync_workspace(&snapshots, &snapshots.create_snapshot().unwrap()));
+        assert!(cache.get("filesystem", &args).is_none());
+    }
+
+    #[test]
+    fn test_session_caches_are_shared() {
+        let cache = ToolResultCache::for_session("cache-test-session");
+        cache.set("search_files", &serde_json::json!({}), serde_json::json!("hit"));
+        let again = ToolResultCache::for_session("cache-test-session");
+        assert_eq!(again.get("search_files", &serde_json::json!({})), Some(serde_json::json!("hit")));
+        assert_eq!(ToolResultCache::for_session("other-session").stats().size, 0);
+    }
+
     #[test]
     fn test_trim_history() {
         let mut history = vec![
@@ -376,7 +499,6 @@ mod tests {
         trim_history(&mut history, 2);
 
         assert_eq!(history.len(), 2);
-        // get_message_content uses Debug format: Text(Text { text: "..." })
         assert!(get_message_content(&history[0]).contains("msg3"));
         assert!(get_message_content(&history[1]).contains("msg4"));
     }
@@ -398,13 +520,27 @@ mod tests {
         ];
 
         let initial_len = history.len();
-        optimize_history_by_tokens(&mut history, 300);
+        let context = ModelContext { context_window: 8_192, max_output: 2_048, tokenizer: Tokenizer::Chars };
+        optimize_history_by_tokens(&mut history, &context, 300);
 
         assert!(history.len() < initial_len);
         // Should keep the most recent message
         assert!(get_message_content(history.last().unwrap()).contains("c"));
     }
 
+    #[test]
+    fn test_truncation_follows_the_model() {
+        let result = "word ".repeat(4_000);
+        let small = ModelContext::for_model("openai", "gpt-4");
+        let large = ModelContext::for_model("anthropic", "claude-sonnet-4-5");
+
+        let truncated = truncate_tool_result(&small, "bash", &result);
+        assert!(truncated.contains("[TRUNCATED"));
+        assert!(small.count_tokens(&truncated) <= small.message_budget() + 50);
+        assert_eq!(truncate_tool_result(&large, "bash", &result), result);
+        assert_eq!(truncate_message_content(&small, "short", "user"), "short");
+    }
+
     #[tokio::test]
     async fn test_execute_with_timeout() {
         let config = AgentOptimizationConfig::default();
@@ -483,31 +619,41 @@ mod tests {
         assert!(truncated.ends_with("aaa"));
     }
 
+    fn chars_context(context_window: usize) -> ModelContext {
+        ModelContext { context_window, max_output: 4_096, tokenizer: Tokenizer::Chars }
+    }
+
     #[test]
     fn test_truncate_tool_result() {
+        let small = chars_context(8_192);
+        let large = chars_context(256_000);
+
         // Within limits
         let short_result = "OK";
-        assert_eq!(truncate_tool_result("bash", short_result), short_result);
+        assert_eq!(truncate_tool_result(&small, "bash", short_result), short_result);
 
-        // Exceeds limits
-        let long_result = "x".repeat(20000);
-        let truncated = truncate_tool_result("bash", &long_result);
+        // 5000 tokens: over the small window's 500 token budget, within the large one's 8000
+        let long_result = "x".repeat(20_000);
+        let truncated = truncate_tool_result(&small, "bash", &long_result);
         assert!(truncated.len() < long_result.len());
         assert!(truncated.contains("TRUNCATED"));
+        assert!(small.count_tokens(&truncated) <= small.message_budget());
+        assert_eq!(truncate_tool_result(&large, "bash", &long_result), long_result);
     }
 
     #[test]
     fn test_truncate_message_content() {
+        let context = chars_context(8_192);
+
         // User message within limits
         let short_msg = "List files";
-        assert_eq!(truncate_message_content(short_msg, "user"), short_msg);
-
-        // Assistant message gets more space
-        let long_msg = "a".repeat(20000);
-        let truncated_assistant = truncate_message_content(&long_msg, "assistant");
-        let truncated_user = truncate_message_content(&long_msg, "user");
-
-        // Assistant should get more space (or equal if both truncated)
-        assert!(truncated_assistant.len() >= truncated_user.len() - 200);
+        assert_eq!(truncate_message_content(&context, short_msg, "user"), short_msg);
+
+        // Assistant messages get twice the budget
+        let long_msg = "a".repeat(20_000);
+        let truncated_assistant = truncate_message_content(&context, &long_msg, "assistant");
+        let truncated_user = truncate_message_content(&context, &long_msg, "user");
+        assert!(truncated_user.len() < truncated_assistant.len());
+        assert!(truncated_assistant.len() < long_msg.len());
     }
 }
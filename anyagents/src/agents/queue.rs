@@ -0,0 +1,484 @@
+//! Job queue: how many jobs run at once
+//!
+//! Every top-level job (a chat message, a resumed plan, a trigger, webhook
+//! or connector run) is admitted here before it starts, so a burst of
+//! messages or triggers for one agent doesn't have twenty jobs writing to
+//! the same workspace. Jobs wait while their agent is at its limit
+//! (`max_concurrent_jobs` in the agent's execution settings, or the
+//! default) or every global slot is taken. Waiting jobs start by priority,
+//! then in arrival order. A queued job can be cancelled before it starts.
+//!
+//! Jobs of one session start in the order they were submitted, whatever
+//! their priority.
+//!
+//! A job whose provider is offline (see `llm::connectivity`) is pending: it
+//! keeps its place but doesn't start until the provider answers again, then
+//! resumes on its own. Jobs behind it in the same session wait with it.
+//!
+//! Work inside a job (plan tasks, team delegation, bus messages) is not
+//! queued again, so a job never waits on itself.
+//!
+//! Limits come from the `job_limits` setting (see `JobLimits`) and are
+//! applied with `global().set_limits`.
+
+use crate::database::DbPool;
+use crate::llm::connectivity;
+use crate::models::settings::{get_setting, set_setting};
+use crate::models::Agent as DbAgent;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use tokio::sync::Notify;
+
+const SETTINGS_KEY: &str = "job_limits";
+/// Finished jobs kept for `list`
+const MAX_FINISHED: usize = 50;
+
+/// Someone is waiting on the answer: chat windows and chat connectors
+pub const PRIORITY_INTERACTIVE: i32 = 10;
+/// Triggers and webhooks
+pub const PRIORITY_BACKGROUND: i32 = 0;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct JobLimits {
+    /// Jobs running at once across all agents
+    pub max_concurrent: usize,
+    /// Jobs running at once per agent, unless its execution settings say otherwise
+    pub per_agent: usize,
+}
+
+impl Default for JobLimits {
+    fn default() -> Self {
+        Self { max_concurrent: 4, per_agent: 1 }
+    }
+}
+
+impl JobLimits {
+    pub fn load(pool: &DbPool) -> Self {
+        get_setting(pool, SETTINGS_KEY)
+            .and_then(|v| serde_json::from_str(&v).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, pool: &DbPool) -> Result<(), String> {
+        set_setting(pool, SETTINGS_KEY, &serde_json::to_string(self).map_err(|e| e.to_string())?)
+    }
+}
+
+/// `max_concurrent_jobs` of an agent's execution settings
+fn agent_limit(agent_db: &DbAgent) -> Option<usize> {
+    agent_db
+        .execution_settings
+        .as_ref()
+        .and_then(|s| serde_json::from_str::<serde_json::Value>(s).ok())
+        .and_then(|json| json.get("max_concurrent_jobs").and_then(|n| n.as_u64()))
+        .map(|n| n.max(1) as usize)
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    /// Queued while its provider is offline
+    Pending,
+    Running,
+    Completed,
+    Cancelled,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct QueuedJob {
+    pub id: String,
+    pub agent_id: String,
+    pub session_id: String,
+    /// The message or prompt, shortened
+    pub label: String,
+    pub priority: i32,
+    /// Provider the job calls first; None for jobs that don't need one
+    pub provider: Option<String>,
+    pub status: JobStatus,
+    pub queued_at: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    /// The agent's own limit; the default applies when None
+    #[serde(skip)]
+    agent_limit: Option<usize>,
+}
+
+impl QueuedJob {
+    pub fn new(id: &str, agent_id: &str, session_id: &str, label: &str, priority: i32) -> Self {
+        Self {
+            id: id.to_string(),
+            agent_id: agent_id.to_string(),
+            session_id: session_id.to_string(),
+            label: label.chars().take(120).collect(),
+            priority,
+            provider: None,
+            status: JobStatus::Queued,
+            queued_at: chrono::Utc::now().to_rfc3339(),
+            started_at: None,
+            finished_at: None,
+            agent_limit: None,
+        }
+    }
+
+    /// A job of `agent_db`, limited by its execution settings and held
+    /// while the agent's provider is offline
+    pub fn for_agent(id: &str, agent_db: &DbAgent, session_id: &str, label: &str, priority: i32) -> Self {
+        Self {
+            agent_limit: agent_limit(agent_db),
+            provider: Some(agent_db.ai_provider.clone()),
+            ..Self::new(id, &agent_db.id, session_id, label, priority)
+        }
+    }
+}
+
+/// Why an admitted job hasn't started yet
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueueWait {
+    /// Behind `ahead` other jobs or its agent's running ones
+    Queued { ahead: usize },
+    /// Its provider can't be reached; it starts once it can
+    Offline { provider: String },
+}
+
+fn is_waiting(job: &QueuedJob) -> bool {
+    matches!(job.status, JobStatus::Queued | JobStatus::Pending)
+}
+
+#[derive(Default)]
+struct State {
+    limits: JobLimits,
+    /// Queued and running jobs in arrival order
+    active: Vec<QueuedJob>,
+    finished: VecDeque<QueuedJob>,
+}
+
+impl State {
+    fn running(&self, agent_id: Option<&str>) -> usize {
+        self.active
+            .iter()
+            .filter(|j| j.status == JobStatus::Running)
+            .filter(|j| match agent_id {
+                Some(id) => j.agent_id == id,
+                None => true,
+            })
+            .count()
+    }
+
+    /// Waiting jobs are pending exactly while their provider is offline
+    fn refresh_pending(&mut self) {
+        let connectivity = connectivity::global();
+        for job in self.active.iter_mut().filter(|j| is_waiting(j)) {
+            let offline = job.provider.as_deref().is_some_and(|p| !connectivity.is_online(p));
+            job.status = if offline { JobStatus::Pending } else { JobStatus::Queued };
+        }
+    }
+
+    /// The queued job that starts next, if a slot is free for it: highest
+    /// priority first, then oldest, skipping agents at their limit and jobs
+    /// with an earlier job of their session still waiting
+    fn next_startable(&self) -> Option<usize> {
+        if self.running(None) >= self.limits.max_concurrent.max(1) {
+            return None;
+        }
+        let mut best: Option<usize> = None;
+        for (i, job) in self.active.iter().enumerate() {
+            if job.status != JobStatus::Queued {
+                continue;
+            }
+            let limit = job.agent_limit.unwrap_or(self.limits.per_agent).max(1);
+            if self.running(Some(&job.agent_id)) >= limit {
+                continue;
+            }
+            if self.active[..i].iter().any(|j| j.session_id == job.session_id && is_waiting(j)) {
+                continue;
+            }
+            match best {
+                Some(b) if self.active[b].priority >= job.priority => {}
+                _ => best = Some(i),
+            }
+        }
+        best
+    }
+
+    /// Queued jobs that start before the one at `index`
+    fn jobs_ahead(&self, index: usize) -> usize {
+        let priority = self.active[index].priority;
+        self.active
+            .iter()
+            .enumerate()
+            .filter(|(i, j)| is_waiting(j) && *i != index)
+            .filter(|(i, j)| j.priority > priority || (j.priority == priority && *i < index))
+            .count()
+    }
+
+    fn finish(&mut self, id: &str, status: JobStatus) -> Option<QueuedJob> {
+        let index = self.active.iter().position(|j| j.id == id)?;
+        let mut job = self.active.remove(index);
+        job.status = status;
+        job.finished_at = Some(chrono::Utc::now().to_rfc3339());
+        self.finished.push_front(job.clone());
+        self.finished.truncate(MAX_FINISHED);
+        Some(job)
+    }
+}
+
+#[derive(Default)]
+pub struct JobQueue {
+    state: Mutex<State>,
+    notify: Notify,
+}
+
+/// The queue every top-level job goes through
+pub fn global() -> &'static Arc<JobQueue> {
+    static QUEUE: OnceLock<Arc<JobQueue>> = OnceLock::new();
+    QUEUE.get_or_init(Default::default)
+}
+
+impl JobQueue {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn limits(&self) -> JobLimits {
+        self.lock().limits.clone()
+    }
+
+    pub fn set_limits(&self, limits: JobLimits) {
+        self.lock().limits = limits;
+        self.notify.notify_waiters();
+    }
+
+    /// Queue `job` and wait for its turn. `on_wait` is called when it can't
+    /// start right away, and again when it becomes pending on an offline
+    /// provider or stops being so. The slot is held until the returned
+    /// guard is dropped. Errs if the job was cancelled while waiting.
+    pub async fn admit(
+        self: &Arc<Self>,
+        job: QueuedJob,
+        mut on_wait: impl FnMut(QueueWait),
+    ) -> Result<RunningJob, String> {
+        let id = job.id.clone();
+        self.lock().active.push(job);
+        let mut waiting = Waiting { queue: self, id: &id, done: false };
+        // Whether the last `on_wait` said offline
+        let mut reported: Option<bool> = None;
+
+        loop {
+            // Registered before checking, so a change in between isn't missed
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            let reachability = connectivity::global().changed();
+            tokio::pin!(reachability);
+            reachability.as_mut().enable();
+
+            {
+                let mut state = self.lock();
+                state.refresh_pending();
+                let Some(position) = state.active.iter().position(|j| j.id == id) else {
+                    waiting.done = true;
+                    return Err("Job was cancelled before it started".to_string());
+                };
+                if state.next_startable() == Some(position) {
+                    let job = &mut state.active[position];
+                    job.status = JobStatus::Running;
+                    job.started_at = Some(chrono::Utc::now().to_rfc3339());
+                    waiting.done = true;
+                    drop(state);
+                    // The next job in line may be able to start too
+                    self.notify.notify_waiters();
+                    return Ok(RunningJob { queue: self.clone(), id: id.clone() });
+                }
+                let wait = match (&state.active[position].status, &state.active[position].provider) {
+                    (JobStatus::Pending, Some(provider)) => QueueWait::Offline { provider: provider.clone() },
+                    _ => QueueWait::Queued { ahead: state.jobs_ahead(position) },
+                };
+                let offline = matches!(wait, QueueWait::Offline { .. });
+                if reported != Some(offline) {
+                    reported = Some(offline);
+                    drop(state);
+                    on_wait(wait);
+                }
+            }
+            tokio::select! {
+                _ = &mut notified => {}
+                _ = &mut reachability => {}
+            }
+        }
+    }
+
+    /// Jobs queued ahead of `id`, or None when it isn't queued
+    pub fn position(&self, id: &str) -> Option<usize> {
+        let state = self.lock();
+        let index = state.active.iter().position(|j| j.id == id && is_waiting(j))?;
+        Some(state.jobs_ahead(index))
+    }
+
+    /// Running and queued jobs, then recently finished ones (newest first)
+    pub fn list(&self) -> Vec<QueuedJob> {
+        let mut state = self.lock();
+        state.refresh_pending();
+        let mut jobs: Vec<QueuedJob> = state.active.iter().filter(|j| j.status == JobStatus::Running).cloned().collect();
+        let mut queued: Vec<QueuedJob> = state.active.iter().filter(|j| is_waiting(j)).cloned().collect();
+        // Stable sort keeps arrival order within a priority
+        queued.sort_by_key(|j| std::cmp::Reverse(j.priority));
+        jobs.extend(queued);
+        jobs.extend(state.finished.iter().cloned());
+        jobs
+    }
+
+    /// Cancel a job that hasn't started; running jobs are stopped elsewhere
+    pub fn cancel_queued(&self, id: &str) -> Result<(), String> {
+        let mut state = self.lock();
+        match state.active.iter().find(|j| j.id == id) {
+            Some(job) if is_waiting(job) => {
+                state.finish(id, JobStatus::Cancelled);
+                drop(state);
+                self.notify.notify_waiters();
+                Ok(())
+            }
+            Some(_) => Err(format!("Job {} is already running", id)),
+            None => Err(format!("Job {} is not queued", id)),
+        }
+    }
+}
+
+/// Takes a job whose caller went away out of the queue
+struct Waiting<'a> {
+    queue: &'a JobQueue,
+    id: &'a str,
+    done: bool,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.queue.lock().finish(self.id, JobStatus::Cancelled);
+            self.queue.notify.notify_waiters();
+        }
+    }
+}
+
+/// A job holding a slot; dropping it marks the job completed and lets the
+/// next one start
+pub struct RunningJob {
+    queue: Arc<JobQueue>,
+    id: String,
+}
+
+impl Drop for RunningJob {
+    fn drop(&mut self) {
+        self.queue.lock().finish(&self.id, JobStatus::Completed);
+        self.queue.notify.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn status(queue: &JobQueue, id: &str) -> JobStatus {
+        queue.list().into_iter().find(|j| j.id == id).unwrap().status
+    }
+
+    #[tokio::test]
+    async fn test_per_agent_limit_priority_and_cancel() {
+        let queue = Arc::new(JobQueue::default());
+        queue.set_limits(JobLimits { max_concurrent: 4, per_agent: 1 });
+        let first = queue
+            .admit(QueuedJob::new("1", "writer", "s", "first", PRIORITY_BACKGROUND), |_| panic!("nothing is running"))
+            .await
+            .unwrap();
+        let spawn = |id: &'static str, priority| {
+            let queue = queue.clone();
+            // Separate sessions: within one, jobs start in submission order
+            let job = QueuedJob::new(id, "writer", id, id, priority);
+            tokio::spawn(async move { queue.admit(job, |_| {}).await.map(|running| (id, running)) })
+        };
+        let background = spawn("2", PRIORITY_BACKGROUND);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let interactive = spawn("3", PRIORITY_INTERACTIVE);
+        let cancelled = spawn("4", PRIORITY_BACKGROUND);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert_eq!(status(&queue, "2"), JobStatus::Queued);
+        assert_eq!(queue.position("3"), Some(0));
+        assert_eq!(queue.position("2"), Some(1));
+        queue.cancel_queued("4").unwrap();
+        assert!(cancelled.await.unwrap().is_err());
+        assert!(queue.cancel_queued("1").unwrap_err().contains("running"));
+
+        // The interactive job goes first although it came later
+        drop(first);
+        let (id, running) = interactive.await.unwrap().unwrap();
+        assert_eq!(id, "3");
+        assert_eq!(status(&queue, "2"), JobStatus::Queued);
+        drop(running);
+        let (id, _running) = background.await.unwrap().unwrap();
+        assert_eq!(id, "2");
+        assert_eq!(status(&queue, "1"), JobStatus::Completed);
+        assert_eq!(status(&queue, "4"), JobStatus::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn test_global_limit_and_agent_override() {
+        let queue = Arc::new(JobQueue::default());
+        queue.set_limits(JobLimits { max_concurrent: 2, per_agent: 1 });
+        let job = |id: &str| QueuedJob {
+            agent_limit: Some(3),
+            ..QueuedJob::new(id, "parallel", id, id, 0)
+        };
+
+        let _a = queue.admit(job("a"), |_| {}).await.unwrap();
+        let _b = queue.admit(job("b"), |_| {}).await.unwrap();
+        let third = tokio::time::timeout(Duration::from_millis(50), queue.admit(job("c"), |_| {})).await;
+        assert!(third.is_err(), "global limit reached");
+        // The timed-out caller's job left the queue
+        assert_eq!(status(&queue, "c"), JobStatus::Cancelled);
+
+        queue.set_limits(JobLimits { max_concurrent: 3, per_agent: 1 });
+        assert!(queue.admit(job("d"), |_| {}).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_session_order_and_offline_provider() {
+        let queue = Arc::new(JobQueue::default());
+        queue.set_limits(JobLimits { max_concurrent: 4, per_agent: 4 });
+        let job = |id: &str, session: &str, priority| QueuedJob {
+            provider: Some("queue-test".to_string()),
+            ..QueuedJob::new(id, "agent", session, id, priority)
+        };
+        let spawn = |job: QueuedJob| {
+            let queue = queue.clone();
+            let waits = Arc::new(Mutex::new(vec![]));
+            let seen = waits.clone();
+            let handle = tokio::spawn(async move {
+                queue.admit(job, move |wait| seen.lock().unwrap().push(wait)).await
+            });
+            (handle, waits)
+        };
+
+        connectivity::global().report_offline("queue-test", "connection refused");
+        let (first, first_waits) = spawn(job("1", "s", PRIORITY_BACKGROUND));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let (second, _) = spawn(job("2", "s", PRIORITY_INTERACTIVE));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(status(&queue, "1"), JobStatus::Pending);
+        assert_eq!(
+            first_waits.lock().unwrap().clone(),
+            vec![QueueWait::Offline { provider: "queue-test".to_string() }]
+        );
+
+        // Back online: both resume, the earlier job of the session first
+        connectivity::global().report_online("queue-test");
+        let running = first.await.unwrap().unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(status(&queue, "1"), JobStatus::Running);
+        assert!(second.await.unwrap().is_ok());
+        drop(running);
+    }
+}
@@ -81,7 +81,7 @@ mod tests {
     use super::*;
 
     fn test_router() -> Router {
-        Router::new("test-model".to_string(), "test-provider".to_string())
+        Router::new("test-model".to_string(), "test-provider".to_string(), None)
     }
 
     #[tokio::test]
@@ -155,7 +155,7 @@ mod tests {
 
     #[test]
     fn test_router_creation() {
-        let router = Router::new("gpt-4".to_string(), "openai".to_string());
+        let router = Router::new("gpt-4".to_string(), "openai".to_string(), None);
         assert_eq!(router.model, "gpt-4");
         assert_eq!(router.provider, "openai");
     }
@@ -13,6 +13,7 @@ pub struct SimpleChatAgent {
     pub model: String,
     pub provider: String,
     pub system_prompt: Option<String>,
+    pub params: crate::llm::GenerationParams,
 }
 
 impl SimpleChatAgent {
@@ -21,6 +22,7 @@ impl SimpleChatAgent {
             model: agent_db.ai_model.clone(),
             provider: agent_db.ai_provider.clone(),
             system_prompt: agent_db.system_prompt.clone(),
+            params: agent_db.generation_params().for_provider(&agent_db.ai_provider),
         }
     }
 
@@ -34,9 +36,12 @@ impl SimpleChatAgent {
         // Load conversation history from DB
         let history = self.load_history(session_id, db_pool);
 
-        let preamble = self.system_prompt.clone().unwrap_or_else(|| {
-            "You are a helpful AI assistant. Respond naturally and conversationally to the user's questions. Be concise but thorough.".to_string()
-        });
+        let preamble = crate::prompts::PromptBuilder::new()
+            .persona(
+                self.system_prompt.as_deref(),
+                "You are a helpful AI assistant. Respond naturally and conversationally to the user's questions. Be concise but thorough.",
+            )
+            .build();
 
         // Create callback for streaming tokens
         let observer_clone = observer.clone();
@@ -58,7 +63,10 @@ impl SimpleChatAgent {
         };
         let api_key = crate::models::settings::get_setting(db_pool, key_name);
 
-        let mut client = LlmClient::new(&self.provider, &self.model).with_preamble(&preamble);
+        let mut client = LlmClient::new(&self.provider, &self.model)
+            .with_preamble(&preamble)
+            .with_session(session_id)
+            .with_params(self.params.clone());
         if let Some(key) = api_key {
             client = client.with_api_key(&key);
         }
@@ -82,6 +90,7 @@ impl SimpleChatAgent {
         if let Ok(mut conn) = db_pool.get() {
             let db_messages: Result<Vec<crate::models::Message>, _> = messages::table
                 .filter(messages::session_id.eq(session_id))
+                .filter(messages::deleted_at.is_null())
                 .order(messages::created_at.asc())
                 .limit(20) // Limit history for context
                 .load(&mut conn);
@@ -89,7 +98,10 @@ impl SimpleChatAgent {
             if let Ok(msgs) = db_messages {
                 for msg in msgs {
                     let rig_msg = match msg.role.as_str() {
-                        "user" => Message::user(&msg.content),
+                        "user" => crate::llm::image::stored_user_message(
+                            &msg.content,
+                            msg.metadata_json.as_deref(),
+                        ),
                         "assistant" | "model" => Message::assistant(&msg.content),
                         _ => continue,
                     };
@@ -104,18 +116,18 @@ impl SimpleChatAgent {
     fn save_message(&self, db_pool: &DbPool, role: &str, content: &str, session_id: &str) {
         use crate::schema::messages;
 
-        if let Ok(mut conn) = db_pool.get() {
-            let msg = crate::models::NewMessage {
-                id: Uuid::new_v4().to_string(),
-                role: role.to_string(),
-                content: content.to_string(),
-                session_id: session_id.to_string(),
-                metadata_json: None,
-                tokens: None,
-            };
-            let _ = diesel::insert_into(messages::table)
-                .values(&msg)
-                .execute(&mut conn);
+        let msg = crate::models::NewMessage {
+            id: Uuid::new_v4().to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            session_id: session_id.to_string(),
+            metadata_json: None,
+            tokens: None,
+        };
+        if let Err(e) = crate::database::serialized_write(db_pool, |conn| {
+            diesel::insert_into(messages::table).values(&msg).execute(conn)
+        }) {
+            log::error!("Failed to save {} message: {}", role, e);
         }
     }
 }
@@ -158,6 +170,7 @@ mod tests {
             scope_type: None,
             workspace_path: None,
             avatar: None,
+            agent_tools: None,
         };
 
         diesel::insert_into(agents::table)
@@ -220,6 +233,7 @@ mod tests {
             scope_type: None,
             workspace_path: None,
             avatar: None,
+            agent_tools: None,
         };
 
         diesel::insert_into(agents::table)
@@ -38,6 +38,7 @@ mod tests {
             scope_type: None,
             workspace_path: None,
             avatar: None,
+            agent_tools: None,
         };
 
         diesel::insert_into(agents::table)
@@ -69,8 +70,15 @@ mod tests {
             source_path: None,
             category: None,
             requires_sandbox: 0,
+            read_only: 0,
             sandbox_config: None,
             execution_mode: "direct".to_string(),
+            triggers: None,
+            dependencies: None,
+            installed_version: None,
+            invocation: None,
+            examples: None,
+            recipe: None,
         };
 
         diesel::insert_into(agent_skills::table)
@@ -110,9 +118,27 @@ mod tests {
         let agent_loop = AgentLoop::new(&agent, pool.clone()).await;
 
         // 4. Verify Tool Logic
-        let tool_names: Vec<String> = agent_loop.tools.iter().map(|t| t.name().to_string()).collect();
+        let tool_names = agent_loop.tools.names();
         println!("Loaded tools: {:?}", tool_names);
 
         assert!(tool_names.contains(&"test-tool-skill".to_string()), "AgentLoop should load assigned skill as a tool");
     }
+
+    #[tokio::test]
+    async fn test_skill_assigned_mid_session_is_registered() {
+        let pool = create_test_pool();
+        let agent = create_test_agent(&pool, "LateSkillAgent");
+        let agent_loop = AgentLoop::new(&agent, pool.clone()).await;
+        let mut changes = agent_loop.tools.subscribe();
+        assert!(!agent_loop.tools.names().contains(&"late-skill".to_string()));
+
+        let skill_id = create_test_skill(&pool, "late-skill");
+        assign_skill(&pool, &agent.id, &skill_id);
+        crate::agents::refresh_agent_tools(&pool, &agent.id, crate::tools::registry::GROUP_SKILLS)
+            .await
+            .unwrap();
+
+        assert!(agent_loop.tools.get("late-skill").is_some());
+        assert_eq!(changes.try_recv().unwrap().added, vec!["late-skill"]);
+    }
 }
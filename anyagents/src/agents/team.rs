@@ -0,0 +1,182 @@
+//! Team mode: a lead agent splits a request across other configured agents
+//!
+//! Team members come from the lead's `team_member_ids` execution setting, or
+//! default to every other agent. Each assigned task runs as a child job with
+//! the member's own model, tools and workspace.
+
+use crate::database::DbPool;
+use crate::models::{Agent as DbAgent, Plan, TaskState};
+use diesel::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// What the planner sees about each agent it can delegate to
+#[derive(Debug, Clone, Serialize)]
+pub struct TeamMember {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub expertise: Option<String>,
+}
+
+impl From<&DbAgent> for TeamMember {
+    fn from(agent: &DbAgent) -> Self {
+        Self {
+            id: agent.id.clone(),
+            name: agent.name.clone(),
+            description: agent.description.clone(),
+            expertise: agent.expertise.clone(),
+        }
+    }
+}
+
+/// Agents the lead may delegate to (never includes the lead itself)
+pub fn load_team(pool: &DbPool, lead: &DbAgent) -> Vec<DbAgent> {
+    use crate::schema::agents;
+
+    let member_ids: Option<Vec<String>> = lead
+        .execution_settings
+        .as_ref()
+        .and_then(|s| serde_json::from_str::<serde_json::Value>(s).ok())
+        .and_then(|json| json.get("team_member_ids").cloned())
+        .and_then(|ids| serde_json::from_value(ids).ok());
+
+    let Ok(mut conn) = pool.get() else {
+        return vec![];
+    };
+    let query = agents::table.filter(agents::id.ne(&lead.id)).into_boxed();
+    let query = match member_ids {
+        Some(ids) => query.filter(agents::id.eq_any(ids)),
+        None => query,
+    };
+    query.load::<DbAgent>(&mut conn).unwrap_or_default()
+}
+
+/// Drop assignments to agents that are not on the team (they fall back to the lead)
+pub fn sanitize_assignments(plan: &mut Plan, team: &HashMap<String, DbAgent>) {
+    for task in &mut plan.tasks {
+        if let Some(assignee) = &task.assignee {
+            if !team.contains_key(assignee) {
+                log::warn!("Task '{}' assigned to unknown agent '{}'; using lead", task.id, assignee);
+                task.assignee = None;
+            }
+        }
+    }
+}
+
+/// Prompt asking the lead to combine the members' results into one answer
+pub fn synthesis_prompt(objective: &str, tasks: &[TaskState], team: &HashMap<String, DbAgent>) -> String {
+    let results: Vec<String> = tasks
+        .iter()
+        .map(|task| {
+            let who = task
+                .assignee
+                .as_ref()
+                .and_then(|id| team.get(id))
+                .map(|a| a.name.as_str())
+                .unwrap_or("you");
+            format!(
+                "- {} (by {}, {}):\n{}",
+                task.description,
+                who,
+                task.status,
+                task.result.as_deref().unwrap_or("(no result)")
+            )
+        })
+        .collect();
+
+    format!(
+        "Your team has finished working on this request:\n{}\n\nResults:\n{}\n\n\
+         Combine these results into a single, complete answer for the user.",
+        objective,
+        results.join("\n\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+    use crate::models::{NewAgent, TaskSpec};
+
+    fn insert_agent(pool: &DbPool, name: &str, execution_settings: Option<String>) -> DbAgent {
+        use crate::schema::agents;
+
+        let agent = NewAgent {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            description: None,
+            status: "active".to_string(),
+            personality: None,
+            tone: None,
+            expertise: None,
+            ai_provider: "openai".to_string(),
+            ai_model: "gpt-4o".to_string(),
+            ai_temperature: 0.7,
+            ai_config: "{}".to_string(),
+            system_prompt: None,
+            permissions: None,
+            working_directories: None,
+            skills: None,
+            mcp_servers: None,
+            messaging_connections: None,
+            knowledge_bases: None,
+            api_keys: None,
+            created_at: chrono::Utc::now().timestamp(),
+            updated_at: chrono::Utc::now().timestamp(),
+            platform_configs: None,
+            execution_settings,
+            scope_type: None,
+            workspace_path: None,
+            avatar: None,
+            agent_tools: None,
+        };
+
+        let mut conn = pool.get().unwrap();
+        diesel::insert_into(agents::table).values(&agent).execute(&mut conn).unwrap();
+        agents::table.find(&agent.id).first(&mut conn).unwrap()
+    }
+
+    #[test]
+    fn test_load_team() {
+        let pool = create_test_pool();
+        let researcher = insert_agent(&pool, "Researcher", None);
+        let _writer = insert_agent(&pool, "Writer", None);
+
+        let lead = insert_agent(&pool, "Lead", None);
+        assert_eq!(load_team(&pool, &lead).len(), 2);
+
+        let settings = serde_json::json!({ "team_member_ids": [researcher.id] }).to_string();
+        let lead = insert_agent(&pool, "Picky lead", Some(settings));
+        let team = load_team(&pool, &lead);
+        assert_eq!(team.len(), 1);
+        assert_eq!(team[0].name, "Researcher");
+    }
+
+    #[test]
+    fn test_sanitize_assignments() {
+        let pool = create_test_pool();
+        let member = insert_agent(&pool, "Member", None);
+        let team = HashMap::from([(member.id.clone(), member.clone())]);
+
+        let mut plan = Plan {
+            tasks: vec![
+                TaskSpec {
+                    id: "1".to_string(),
+                    description: "known".to_string(),
+                    depends_on: vec![],
+                    assignee: Some(member.id.clone()),
+                },
+                TaskSpec {
+                    id: "2".to_string(),
+                    description: "unknown".to_string(),
+                    depends_on: vec![],
+                    assignee: Some("nobody".to_string()),
+                },
+            ],
+        };
+        sanitize_assignments(&mut plan, &team);
+        assert_eq!(plan.tasks[0].assignee.as_deref(), Some(member.id.as_str()));
+        assert_eq!(plan.tasks[1].assignee, None);
+    }
+}
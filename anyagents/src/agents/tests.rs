@@ -42,6 +42,7 @@ fn create_test_agent_db(pool: &DbPool, name: &str) -> Agent {
         scope_type: None,
         workspace_path: None,
         avatar: None,
+        agent_tools: None,
     };
 
     let mut conn = pool.get().expect("Failed to get DB connection");
@@ -114,7 +115,7 @@ mod agent_loop_tests {
         assert_eq!(agent_loop.agent_id, agent.id);
         assert_eq!(agent_loop.model, agent.ai_model);
         assert_eq!(agent_loop.history.len(), 0);
-        assert_eq!(agent_loop.tools.len(), 5); // Filesystem, Search, Bash, Transcribe, SendEmail
+        assert_eq!(agent_loop.tools.len(), crate::tools::config::DEFAULT_TOOLS.len());
     }
 
     #[tokio::test]
@@ -124,21 +125,37 @@ mod agent_loop_tests {
 
         let agent_loop = AgentLoop::new(&agent, pool.clone()).await;
 
-        let tool_names: Vec<String> = agent_loop
-            .tools
-            .iter()
-            .map(|t| t.name().to_string())
-            .collect();
+        let tool_names = agent_loop.tools.names();
 
         assert!(tool_names.contains(&"filesystem".to_string()));
         assert!(tool_names.contains(&"search_files".to_string()));
         assert!(tool_names.contains(&"bash".to_string()));
     }
+
+    #[tokio::test]
+    async fn test_agent_tools_config_applied() {
+        use crate::schema::agents;
+        use diesel::prelude::*;
+
+        let pool = create_test_pool();
+        let mut agent = create_test_agent_db(&pool, "ConfiguredToolsAgent");
+        let config = r#"{"builtin": ["filesystem", "bash", "http"], "disabled": ["bash"]}"#;
+        diesel::update(agents::table.find(&agent.id))
+            .set(agents::agent_tools.eq(config))
+            .execute(&mut pool.get().unwrap())
+            .unwrap();
+        agent.agent_tools = Some(config.to_string());
+
+        let agent_loop = AgentLoop::new(&agent, pool.clone()).await;
+        assert_eq!(agent_loop.tools.names(), vec!["filesystem", "http"]);
+        assert!(agent.into_dto().agent_tools.unwrap().builtin.is_some());
+    }
 }
 
 #[cfg(test)]
 mod message_history_tests {
     use super::*;
+    use crate::agents::optimizations::get_message_content;
     use rig::completion::Message;
 
     #[test]
@@ -154,7 +171,7 @@ mod message_history_tests {
         assert_eq!(history.len(), 2);
         assert!(matches!(history[0], Message::User { .. }));
         assert!(matches!(history[1], Message::Assistant { .. }));
-        // Verify content helper works - get_message_content uses Debug format
+        // Verify content helper works
         assert!(get_message_content(&history[0]).contains("Hello"));
     }
 
@@ -0,0 +1,217 @@
+//! Session titles
+//!
+//! Right after the first message a session is named after it. Over the next
+//! few exchanges the provider's fast model is asked for a short title that
+//! summarizes the conversation so far, answers included, not only the
+//! opening question. A session the user renamed is `title_locked` and never
+//! gets a generated title; the update itself checks the flag, so a rename
+//! racing a generation still wins.
+//!
+//! Titles are cached by the exchange they summarize, so a retried or replayed
+//! turn doesn't cost another call.
+
+use crate::database::DbPool;
+use crate::llm::LlmClient;
+use crate::schema::sessions;
+use diesel::prelude::*;
+use rig::completion::message::{AssistantContent, UserContent};
+use rig::completion::Message;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{LazyLock, Mutex};
+
+const PREAMBLE: &str = "You name chat sessions. Read the conversation and give it a very concise title (3-5 words) that says what it is about.";
+/// Characters of each message shown to the title model
+const MESSAGE_CHARS: usize = 600;
+/// Characters of the whole transcript shown to the title model
+const TRANSCRIPT_CHARS: usize = 4_000;
+/// Characters of the first message used as a title until one is generated
+const FALLBACK_CHARS: usize = 30;
+const CACHE_SIZE: usize = 256;
+
+/// Titles sessions start with, which the first message may replace
+const PLACEHOLDERS: &[&str] = &["", "New Chat"];
+
+/// Session title asked of the model
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+struct GeneratedTitle {
+    /// 3-5 words, no quotes
+    title: String,
+}
+
+static CACHE: LazyLock<Mutex<HashMap<u64, String>>> = LazyLock::new(Default::default);
+
+/// The text of the user and assistant turns in `history`, tool calls and
+/// results left out, each turn and the whole cut to a size a fast model
+/// reads cheaply
+pub fn transcript(history: &[Message]) -> String {
+    let mut lines = vec![];
+    for message in history {
+        let (role, text) = match message {
+            Message::User { content, .. } => (
+                "User",
+                content
+                    .iter()
+                    .filter_map(|c| match c {
+                        UserContent::Text(t) => Some(t.text.as_str()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            Message::Assistant { content, .. } => (
+                "Assistant",
+                content
+                    .iter()
+                    .filter_map(|c| match c {
+                        AssistantContent::Text(t) => Some(t.text.as_str()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+        };
+        let text = text.trim();
+        if !text.is_empty() {
+            lines.push(format!("{}: {}", role, text.chars().take(MESSAGE_CHARS).collect::<String>()));
+        }
+    }
+    lines.join("\n").chars().take(TRANSCRIPT_CHARS).collect()
+}
+
+fn cache_key(provider: &str, transcript: &str) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    (provider, transcript).hash(&mut hasher);
+    hasher.finish()
+}
+
+fn clean(title: &str) -> Option<String> {
+    let title = title.trim().trim_matches(|c| c == '"' || c == '\'').trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+/// A title for the conversation in `history`, from `provider`'s fast model
+pub async fn generate(provider: &str, history: &[Message]) -> Result<String, String> {
+    let transcript = transcript(history);
+    if transcript.is_empty() {
+        return Err("Nothing to summarize yet".to_string());
+    }
+    let key = cache_key(provider, &transcript);
+    if let Some(title) = CACHE.lock().unwrap_or_else(|p| p.into_inner()).get(&key) {
+        return Ok(title.clone());
+    }
+
+    let client = LlmClient::new(provider, LlmClient::fast_model(provider)).with_preamble(PREAMBLE);
+    let generated = client.prompt_structured::<GeneratedTitle>(&transcript).await?;
+    let title = clean(&generated.title).ok_or("The model returned an empty title")?;
+
+    let mut cache = CACHE.lock().unwrap_or_else(|p| p.into_inner());
+    if cache.len() >= CACHE_SIZE {
+        cache.clear();
+    }
+    cache.insert(key, title.clone());
+    Ok(title)
+}
+
+/// Set a generated title unless the user renamed the session. Returns
+/// whether the title changed.
+pub fn apply(pool: &DbPool, session_id: &str, title: &str) -> Result<bool, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let updated = diesel::update(
+        sessions::table
+            .filter(sessions::id.eq(session_id))
+            .filter(sessions::title_locked.eq(0)),
+    )
+    .set(sessions::title.eq(title))
+    .execute(&mut conn)
+    .map_err(|e| e.to_string())?;
+    Ok(updated > 0)
+}
+
+/// Name a session still carrying a placeholder title after its first
+/// message. Returns whether the title changed.
+pub fn apply_fallback(pool: &DbPool, session_id: &str, first_message: &str) -> Result<bool, String> {
+    let title: String = first_message.chars().take(FALLBACK_CHARS).collect();
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let updated = diesel::update(
+        sessions::table
+            .filter(sessions::id.eq(session_id))
+            .filter(sessions::title_locked.eq(0))
+            .filter(sessions::title.is_null().or(sessions::title.eq_any(PLACEHOLDERS))),
+    )
+    .set(sessions::title.eq(title.trim()))
+    .execute(&mut conn)
+    .map_err(|e| e.to_string())?;
+    Ok(updated > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::optimizations::{create_assistant_message, create_user_message};
+    use crate::database::create_test_pool;
+    use crate::models::NewSession;
+    use crate::schema::agents;
+
+    fn session(pool: &DbPool, id: &str, title: &str) {
+        let now = chrono::Utc::now().naive_utc();
+        diesel::insert_or_ignore_into(agents::table)
+            .values((agents::id.eq("agent"), agents::name.eq("Agent")))
+            .execute(&mut pool.get().unwrap())
+            .unwrap();
+        diesel::insert_into(sessions::table)
+            .values(&NewSession {
+                id: id.to_string(),
+                agent_id: "agent".to_string(),
+                title: Some(title.to_string()),
+                created_at: now,
+                updated_at: now,
+                archived: 0,
+                pinned: 0,
+            })
+            .execute(&mut pool.get().unwrap())
+            .unwrap();
+    }
+
+    fn title_of(pool: &DbPool, id: &str) -> Option<String> {
+        sessions::table
+            .find(id)
+            .select(sessions::title)
+            .first(&mut pool.get().unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_transcript_covers_the_exchange() {
+        let history = vec![
+            create_user_message("Can you compare our Q3 and Q4 revenue?".to_string()),
+            create_assistant_message("Q4 revenue grew 12% over Q3, mostly from EMEA.".to_string()),
+            create_user_message("x".repeat(2 * MESSAGE_CHARS)),
+        ];
+        let text = transcript(&history);
+        assert!(text.starts_with("User: Can you compare"));
+        assert!(text.contains("\nAssistant: Q4 revenue grew 12%"));
+        assert!(text.lines().last().unwrap().len() < MESSAGE_CHARS + 10);
+        assert_eq!(clean(" \"Q3 vs Q4 revenue\" ").as_deref(), Some("Q3 vs Q4 revenue"));
+    }
+
+    #[test]
+    fn test_locked_titles_are_kept() {
+        let pool = create_test_pool();
+        session(&pool, "fresh", "New Chat");
+        session(&pool, "renamed", "My budget notes");
+        diesel::update(sessions::table.find("renamed"))
+            .set(sessions::title_locked.eq(1))
+            .execute(&mut pool.get().unwrap())
+            .unwrap();
+
+        assert!(apply_fallback(&pool, "fresh", "Compare Q3 and Q4 revenue please").unwrap());
+        assert_eq!(title_of(&pool, "fresh").as_deref(), Some("Compare Q3 and Q4 revenue plea"));
+        // Only placeholders are replaced by the first message
+        assert!(!apply_fallback(&pool, "fresh", "Something else").unwrap());
+
+        assert!(apply(&pool, "fresh", "Quarterly revenue comparison").unwrap());
+        assert!(!apply(&pool, "renamed", "Budget review").unwrap());
+        assert_eq!(title_of(&pool, "renamed").as_deref(), Some("My budget notes"));
+    }
+}
@@ -0,0 +1,313 @@
+//! Execution traces: an append-only JSONL log of one job
+//!
+//! With a trace directory set (`ANYCOWORK_TRACE_DIR`, or `AgentBuilder::trace`)
+//! every job writes `<dir>/<job_id>.jsonl`: one line per LLM request and
+//! response, tool call and result, and emitted event, in order. Everything
+//! is recorded after secret redaction, like the saved messages. Token
+//! events are left out since the full response is recorded.
+//!
+//! A trace can be replayed (`AgentBuilder::replay`): the agent loop runs as
+//! usual, but responses come from the trace instead of the provider and
+//! tools return their recorded results instead of running. This reproduces
+//! parser and loop issues from a real run, and captured runs can be checked
+//! in as regression tests.
+
+use crate::events::AgentObserver;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Environment variable naming the directory traces are written to (the
+/// `trace_dir` config key, see `config`)
+pub const TRACE_DIR_ENV: &str = "ANYCOWORK_TRACE_DIR";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TraceEntry {
+    Start {
+        agent_id: String,
+        session_id: String,
+        provider: String,
+        model: String,
+        message: String,
+    },
+    LlmRequest {
+        step: usize,
+        message: String,
+        history_len: usize,
+    },
+    LlmResponse {
+        step: usize,
+        text: String,
+    },
+    LlmError {
+        step: usize,
+        attempt: usize,
+        error: String,
+    },
+    ToolCall {
+        step: usize,
+        tool: String,
+        args: Value,
+    },
+    ToolResult {
+        step: usize,
+        tool: String,
+        result: Value,
+        success: bool,
+    },
+    Event {
+        channel: String,
+        payload: Value,
+    },
+    Finish {
+        response: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TraceLine {
+    pub seq: u64,
+    pub at: String,
+    #[serde(flatten)]
+    pub entry: TraceEntry,
+}
+
+/// Appends the entries of one job to its trace file
+pub struct TraceRecorder {
+    path: PathBuf,
+    file: Mutex<std::fs::File>,
+    seq: AtomicU64,
+}
+
+impl TraceRecorder {
+    pub fn create(dir: &Path, job_id: &str) -> Result<Self, String> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let path = dir.join(format!("{}.jsonl", job_id));
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        Ok(Self { path, file: Mutex::new(file), seq: AtomicU64::new(0) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append one entry. A failed write is logged, never fatal to the job.
+    pub fn record(&self, entry: TraceEntry) {
+        let line = TraceLine {
+            seq: self.seq.fetch_add(1, Ordering::SeqCst),
+            at: chrono::Utc::now().to_rfc3339(),
+            entry,
+        };
+        let Ok(mut text) = serde_json::to_string(&line) else {
+            return;
+        };
+        text.push('\n');
+        if let Ok(mut file) = self.file.lock() {
+            if let Err(e) = file.write_all(text.as_bytes()) {
+                log::warn!("Failed to write trace {}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+/// Records every event passing through to `inner`
+pub struct TracingObserver {
+    inner: Arc<dyn AgentObserver>,
+    recorder: Arc<TraceRecorder>,
+}
+
+impl TracingObserver {
+    pub fn wrap(inner: Arc<dyn AgentObserver>, recorder: Arc<TraceRecorder>) -> Arc<dyn AgentObserver> {
+        Arc::new(Self { inner, recorder })
+    }
+}
+
+impl AgentObserver for TracingObserver {
+    fn emit(&self, event: &str, payload: Value) -> Result<(), String> {
+        if payload.get("type").and_then(|t| t.as_str()) != Some("token") {
+            self.recorder.record(TraceEntry::Event {
+                channel: event.to_string(),
+                payload: payload.clone(),
+            });
+        }
+        self.inner.emit(event, payload)
+    }
+}
+
+/// Read a trace file back
+pub fn load(path: &Path) -> Result<Vec<TraceLine>, String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    std::io::BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(l) if l.trim().is_empty()))
+        .map(|(i, line)| {
+            let line = line.map_err(|e| e.to_string())?;
+            serde_json::from_str(&line).map_err(|e| format!("{} line {}: {}", path.display(), i + 1, e))
+        })
+        .collect()
+}
+
+/// Recorded responses and tool results, handed out in order
+pub struct Replay {
+    responses: Mutex<VecDeque<Result<String, String>>>,
+    tool_results: Mutex<VecDeque<(String, Value)>>,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        Ok(Self::from_entries(load(path)?.into_iter().map(|line| line.entry)))
+    }
+
+    pub fn from_entries(entries: impl IntoIterator<Item = TraceEntry>) -> Self {
+        let mut responses = VecDeque::new();
+        let mut tool_results = VecDeque::new();
+        for entry in entries {
+            match entry {
+                TraceEntry::LlmResponse { text, .. } => responses.push_back(Ok(text)),
+                TraceEntry::LlmError { error, .. } => responses.push_back(Err(error)),
+                TraceEntry::ToolResult { tool, result, .. } => tool_results.push_back((tool, result)),
+                _ => {}
+            }
+        }
+        Self {
+            responses: Mutex::new(responses),
+            tool_results: Mutex::new(tool_results),
+        }
+    }
+
+    /// The next recorded LLM response (or error). Running past the end of
+    /// the trace is an error, so a diverging run stops instead of looping.
+    pub fn next_response(&self) -> Result<String, String> {
+        self.responses
+            .lock()
+            .map_err(|e| e.to_string())?
+            .pop_front()
+            .unwrap_or_else(|| Err("Replay trace has no more LLM responses".to_string()))
+    }
+
+    /// The next recorded result, which should belong to `tool`. A mismatch
+    /// comes back to the agent as a tool error.
+    pub fn tool_result(&self, tool: &str) -> Value {
+        let next = self.tool_results.lock().ok().and_then(|mut results| results.pop_front());
+        match next {
+            Some((recorded, result)) if recorded == tool => result,
+            Some((recorded, _)) => serde_json::json!({
+                "error": format!("Replay diverged: the trace has a {} call here, not {}", recorded, tool)
+            }),
+            None => serde_json::json!({
+                "error": format!("Replay trace has no more tool results (called {})", tool)
+            }),
+        }
+    }
+
+    /// Responses not consumed yet
+    pub fn remaining_responses(&self) -> usize {
+        self.responses.lock().map(|r| r.len()).unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::{Tool, ToolContext};
+    use crate::{AgentBuilder, RuntimeBuilder};
+    use async_trait::async_trait;
+    use serde_json::json;
+
+    struct EchoTool;
+
+    #[async_trait]
+    impl Tool for EchoTool {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn description(&self) -> &str {
+            "Echo the text back"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            json!({"type": "object", "properties": {"text": {"type": "string"}}, "required": ["text"]})
+        }
+
+        async fn execute(&self, _args: Value, _ctx: &ToolContext) -> Result<Value, String> {
+            panic!("replayed tools must not run");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_drives_loop_and_records_trace() {
+        let dir = tempfile::tempdir().unwrap();
+        let replay = Replay::from_entries([
+            TraceEntry::LlmResponse {
+                step: 1,
+                text: r#"{"tool": "echo", "args": {"text": "hi"}}"#.to_string(),
+            },
+            TraceEntry::ToolResult {
+                step: 1,
+                tool: "echo".to_string(),
+                result: json!({"echo": "hi"}),
+                success: true,
+            },
+            TraceEntry::LlmResponse { step: 2, text: "It said hi.".to_string() },
+        ]);
+        let runtime = RuntimeBuilder::new()
+            .db_pool(crate::database::create_test_pool())
+            .auto_approve()
+            .build()
+            .unwrap();
+        let mut agent = AgentBuilder::new("openai", "gpt-4o")
+            .workspace(dir.path())
+            .tool(EchoTool)
+            .trace(dir.path().join("traces"))
+            .replay(replay)
+            .build();
+
+        let reply = runtime.chat(&mut agent, "Say hi").await.unwrap();
+        assert_eq!(reply, "It said hi.");
+
+        let trace_file = std::fs::read_dir(dir.path().join("traces")).unwrap().next().unwrap().unwrap();
+        let lines = load(&trace_file.path()).unwrap();
+        assert!(lines.windows(2).all(|w| w[0].seq < w[1].seq));
+        let entries: Vec<TraceEntry> = lines.into_iter().map(|l| l.entry).collect();
+        assert!(matches!(&entries[0], TraceEntry::Start { message, .. } if message == "Say hi"));
+        assert!(entries.contains(&TraceEntry::ToolResult {
+            step: 1,
+            tool: "echo".to_string(),
+            result: json!({"echo": "hi"}),
+            success: true,
+        }));
+        assert_eq!(entries.last(), Some(&TraceEntry::Finish { response: "It said hi.".to_string() }));
+
+        // The recorded trace replays to the same reply
+        let mut again = AgentBuilder::new("openai", "gpt-4o")
+            .workspace(dir.path())
+            .tool(EchoTool)
+            .replay(Replay::from_entries(entries))
+            .build();
+        assert_eq!(runtime.chat(&mut again, "Say hi").await.unwrap(), "It said hi.");
+    }
+
+    #[test]
+    fn test_replay_reports_divergence() {
+        let replay = Replay::from_entries([TraceEntry::ToolResult {
+            step: 1,
+            tool: "bash".to_string(),
+            result: json!({"stdout": ""}),
+            success: true,
+        }]);
+        assert!(replay.tool_result("filesystem")["error"].as_str().unwrap().contains("diverged"));
+        assert!(replay.tool_result("bash")["error"].is_string());
+        assert!(replay.next_response().is_err());
+    }
+}
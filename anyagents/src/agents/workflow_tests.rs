@@ -59,6 +59,7 @@ mod daily_workflow_tests {
             scope_type: None,
             workspace_path: None,
             avatar: None,
+            agent_tools: None,
         };
 
         let mut conn = pool.get().expect("Failed to get connection");
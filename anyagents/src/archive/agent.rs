@@ -0,0 +1,488 @@
+//! Agent bundles: an agent's configuration with the skills and MCP servers it
+//! uses, as one JSON file teams can share
+//!
+//! Nothing machine- or account-specific goes into a bundle: no workspace
+//! path, API keys, connector configs or MCP OAuth tokens. MCP secrets are
+//! listed by name only and have to be set again after import.
+
+use super::ArchiveHeader;
+use crate::database::DbPool;
+use crate::models::mcp_server::{McpServer, NewMcpServer};
+use crate::models::{Agent, AgentSkill, NewAgent, NewAgentSkill, NewSkillFile, SkillFile};
+use crate::schema::{agent_skill_assignments, agent_skills, agents, mcp_servers, skill_files};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub const AGENT_BUNDLE_FORMAT: &str = "anycowork-agent";
+pub const AGENT_BUNDLE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentBundle {
+    pub header: ArchiveHeader,
+    pub agent: AgentTemplate,
+    #[serde(default)]
+    pub skills: Vec<BundledSkill>,
+    #[serde(default)]
+    pub mcp_servers: Vec<McpServer>,
+}
+
+/// The shareable part of an agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentTemplate {
+    pub name: String,
+    pub description: Option<String>,
+    pub personality: Option<String>,
+    pub tone: Option<String>,
+    pub expertise: Option<String>,
+    pub avatar: Option<String>,
+    pub ai_provider: String,
+    pub ai_model: String,
+    pub ai_temperature: f32,
+    pub ai_config: String,
+    pub system_prompt: Option<String>,
+    pub permissions: Option<String>,
+    pub scope_type: Option<String>,
+    pub execution_settings: Option<String>,
+    pub agent_tools: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundledSkill {
+    pub skill: AgentSkill,
+    #[serde(default)]
+    pub files: Vec<SkillFile>,
+}
+
+/// What to do with a bundled skill whose name is already taken by a skill
+/// with different content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SkillConflict {
+    /// Fail and list the conflicting skills, so the user can choose
+    #[default]
+    Ask,
+    /// Use the installed skill
+    Reuse,
+    /// Import under a new name ("name-2", "name-3", ...)
+    Rename,
+    /// Overwrite the installed skill (affects every agent using it)
+    Replace,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AgentImportOptions {
+    #[serde(default)]
+    pub skill_conflict: SkillConflict,
+    /// Defaults to the bundled name
+    pub name: Option<String>,
+    pub workspace_path: Option<String>,
+}
+
+/// What an import did, for the UI to report
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct AgentImportReport {
+    pub agent_id: String,
+    pub skills_created: Vec<String>,
+    pub skills_reused: Vec<String>,
+    pub skills_replaced: Vec<String>,
+    /// (bundled name, name it was imported as)
+    pub skills_renamed: Vec<(String, String)>,
+    pub mcp_servers_created: Vec<String>,
+    pub mcp_servers_reused: Vec<String>,
+    /// MCP secrets to set before the servers can start
+    pub missing_secrets: Vec<String>,
+}
+
+pub fn export_agent(pool: &DbPool, agent_id: &str) -> Result<AgentBundle, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let agent: Agent = agents::table
+        .find(agent_id)
+        .first(&mut conn)
+        .map_err(|_| "Agent not found".to_string())?;
+
+    let skills: Vec<AgentSkill> = agent_skills::table
+        .inner_join(agent_skill_assignments::table)
+        .filter(agent_skill_assignments::agent_id.eq(agent_id))
+        .select(AgentSkill::as_select())
+        .order(agent_skills::name.asc())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())?;
+    let skills = skills
+        .into_iter()
+        .map(|skill| {
+            let files = skill_files::table
+                .filter(skill_files::skill_id.eq(&skill.id))
+                .order(skill_files::relative_path.asc())
+                .load::<SkillFile>(&mut conn)
+                .map_err(|e| e.to_string())?;
+            Ok(BundledSkill { skill, files })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let server_ids = mcp_server_ids(&agent);
+    let servers: Vec<McpServer> = mcp_servers::table
+        .filter(mcp_servers::id.eq_any(&server_ids))
+        .load::<McpServer>(&mut conn)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|server| McpServer { oauth: None, ..server })
+        .collect();
+
+    Ok(AgentBundle {
+        header: ArchiveHeader::new(AGENT_BUNDLE_FORMAT, AGENT_BUNDLE_VERSION),
+        agent: AgentTemplate {
+            name: agent.name,
+            description: agent.description,
+            personality: agent.personality,
+            tone: agent.tone,
+            expertise: agent.expertise,
+            avatar: agent.avatar,
+            ai_provider: agent.ai_provider,
+            ai_model: agent.ai_model,
+            ai_temperature: agent.ai_temperature,
+            ai_config: agent.ai_config,
+            system_prompt: agent.system_prompt,
+            permissions: agent.permissions,
+            scope_type: agent.scope_type,
+            execution_settings: agent.execution_settings,
+            agent_tools: agent.agent_tools,
+        },
+        skills,
+        mcp_servers: servers,
+    })
+}
+
+/// Create a new agent from a bundle, installing or reusing its skills and
+/// MCP servers
+pub fn import_agent(pool: &DbPool, bundle: AgentBundle, options: &AgentImportOptions) -> Result<AgentImportReport, String> {
+    bundle.header.check(AGENT_BUNDLE_FORMAT, AGENT_BUNDLE_VERSION)?;
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+
+    // Resolve every skill before writing anything, so an unresolved
+    // conflict leaves the database untouched
+    let mut plans = Vec::new();
+    let mut conflicts = Vec::new();
+    for bundled in &bundle.skills {
+        let existing: Option<AgentSkill> = agent_skills::table
+            .filter(agent_skills::name.eq(&bundled.skill.name))
+            .first(&mut conn)
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let plan = match existing {
+            None => SkillPlan::Create(bundled.skill.name.clone()),
+            Some(existing) if existing.skill_content == bundled.skill.skill_content => SkillPlan::Reuse(existing.id),
+            Some(existing) => match options.skill_conflict {
+                SkillConflict::Ask => {
+                    conflicts.push(bundled.skill.name.clone());
+                    continue;
+                }
+                SkillConflict::Reuse => SkillPlan::Reuse(existing.id),
+                SkillConflict::Replace => SkillPlan::Replace(existing.id),
+                SkillConflict::Rename => SkillPlan::Create(free_skill_name(&mut conn, &bundled.skill.name)?),
+            },
+        };
+        plans.push((bundled, plan));
+    }
+    if !conflicts.is_empty() {
+        return Err(format!(
+            "Skills already installed with different content: {}. Import again choosing to reuse, rename or replace them.",
+            conflicts.join(", ")
+        ));
+    }
+
+    let mut report = AgentImportReport::default();
+    let now = chrono::Utc::now();
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let mut skill_ids = Vec::new();
+        for (bundled, plan) in plans {
+            let skill_id = match plan {
+                SkillPlan::Reuse(id) => {
+                    report.skills_reused.push(bundled.skill.name.clone());
+                    id
+                }
+                SkillPlan::Replace(id) => {
+                    diesel::delete(agent_skills::table.find(&id)).execute(conn)?;
+                    diesel::delete(skill_files::table.filter(skill_files::skill_id.eq(&id))).execute(conn)?;
+                    insert_skill(conn, &id, &bundled.skill.name, bundled)?;
+                    report.skills_replaced.push(bundled.skill.name.clone());
+                    id
+                }
+                SkillPlan::Create(name) => {
+                    let id = uuid::Uuid::new_v4().to_string();
+                    insert_skill(conn, &id, &name, bundled)?;
+                    if name == bundled.skill.name {
+                        report.skills_created.push(name);
+                    } else {
+                        report.skills_renamed.push((bundled.skill.name.clone(), name));
+                    }
+                    id
+                }
+            };
+            skill_ids.push(skill_id);
+        }
+
+        let mut server_ids = Vec::new();
+        for server in &bundle.mcp_servers {
+            let same: Option<String> = mcp_servers::table
+                .filter(mcp_servers::name.eq(&server.name))
+                .filter(mcp_servers::server_type.eq(&server.server_type))
+                .filter(mcp_servers::command.is(&server.command))
+                .filter(mcp_servers::args.is(&server.args))
+                .filter(mcp_servers::url.is(&server.url))
+                .select(mcp_servers::id)
+                .first(conn)
+                .optional()?;
+            let id = match same {
+                Some(id) => {
+                    report.mcp_servers_reused.push(server.name.clone());
+                    id
+                }
+                None => {
+                    let id = uuid::Uuid::new_v4().to_string();
+                    diesel::insert_into(mcp_servers::table)
+                        .values(NewMcpServer {
+                            id: id.clone(),
+                            name: server.name.clone(),
+                            server_type: server.server_type.clone(),
+                            command: server.command.clone(),
+                            args: server.args.clone(),
+                            env: server.env.clone(),
+                            url: server.url.clone(),
+                            is_enabled: server.is_enabled,
+                            template_id: server.template_id.clone(),
+                            created_at: now.timestamp(),
+                            updated_at: now.timestamp(),
+                            headers: server.headers.clone(),
+                            secret_names: server.secret_names.clone(),
+                            oauth: None,
+                        })
+                        .execute(conn)?;
+                    report.mcp_servers_created.push(server.name.clone());
+                    report
+                        .missing_secrets
+                        .extend(server.secret_name_list().into_iter().map(|s| format!("{}: {}", server.name, s)));
+                    id
+                }
+            };
+            server_ids.push(id);
+        }
+
+        let template = &bundle.agent;
+        let agent_id = uuid::Uuid::new_v4().to_string();
+        diesel::insert_into(agents::table)
+            .values(NewAgent {
+                id: agent_id.clone(),
+                name: options.name.clone().unwrap_or_else(|| template.name.clone()),
+                description: template.description.clone(),
+                status: "active".to_string(),
+                personality: template.personality.clone(),
+                tone: template.tone.clone(),
+                expertise: template.expertise.clone(),
+                ai_provider: template.ai_provider.clone(),
+                ai_model: template.ai_model.clone(),
+                ai_temperature: template.ai_temperature,
+                ai_config: template.ai_config.clone(),
+                system_prompt: template.system_prompt.clone(),
+                permissions: template.permissions.clone(),
+                working_directories: None,
+                skills: None,
+                mcp_servers: (!server_ids.is_empty()).then(|| server_ids.join(",")),
+                messaging_connections: None,
+                knowledge_bases: None,
+                api_keys: None,
+                created_at: now.timestamp(),
+                updated_at: now.timestamp(),
+                platform_configs: None,
+                execution_settings: template.execution_settings.clone(),
+                scope_type: template.scope_type.clone(),
+                workspace_path: options.workspace_path.clone(),
+                avatar: template.avatar.clone(),
+                agent_tools: template.agent_tools.clone(),
+            })
+            .execute(conn)?;
+        for skill_id in skill_ids {
+            diesel::insert_into(agent_skill_assignments::table)
+                .values((
+                    agent_skill_assignments::agent_id.eq(&agent_id),
+                    agent_skill_assignments::skill_id.eq(skill_id),
+                ))
+                .execute(conn)?;
+        }
+        report.agent_id = agent_id;
+        Ok(())
+    })
+    .map_err(|e| format!("Failed to import agent: {}", e))?;
+
+    Ok(report)
+}
+
+enum SkillPlan {
+    /// Insert under this name
+    Create(String),
+    Reuse(String),
+    Replace(String),
+}
+
+fn insert_skill(
+    conn: &mut SqliteConnection,
+    id: &str,
+    name: &str,
+    bundled: &BundledSkill,
+) -> Result<(), diesel::result::Error> {
+    let skill = &bundled.skill;
+    let now = chrono::Utc::now().naive_utc();
+    diesel::insert_into(agent_skills::table)
+        .values(NewAgentSkill {
+            id: id.to_string(),
+            name: name.to_string(),
+            display_title: skill.display_title.clone(),
+            description: skill.description.clone(),
+            skill_content: skill.skill_content.clone(),
+            additional_files_json: skill.additional_files_json.clone(),
+            enabled: skill.enabled,
+            version: skill.version,
+            created_at: now,
+            updated_at: now,
+            source_path: None,
+            category: skill.category.clone(),
+            requires_sandbox: skill.requires_sandbox,
+            read_only: skill.read_only,
+            sandbox_config: skill.sandbox_config.clone(),
+            execution_mode: skill.execution_mode.clone(),
+            triggers: skill.triggers.clone(),
+            dependencies: skill.dependencies.clone(),
+            installed_version: skill.installed_version.clone(),
+            invocation: skill.invocation.clone(),
+            examples: skill.examples.clone(),
+            recipe: skill.recipe.clone(),
+        })
+        .execute(conn)?;
+    for file in &bundled.files {
+        diesel::insert_into(skill_files::table)
+            .values(NewSkillFile {
+                id: uuid::Uuid::new_v4().to_string(),
+                skill_id: id.to_string(),
+                relative_path: file.relative_path.clone(),
+                content: file.content.clone(),
+                file_type: file.file_type.clone(),
+                created_at: now,
+            })
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// `name-2`, `name-3`, ... whichever is not taken
+fn free_skill_name(conn: &mut SqliteConnection, name: &str) -> Result<String, String> {
+    for n in 2.. {
+        let candidate = format!("{}-{}", name, n);
+        let taken = agent_skills::table
+            .filter(agent_skills::name.eq(&candidate))
+            .count()
+            .get_result::<i64>(conn)
+            .map_err(|e| e.to_string())?;
+        if taken == 0 {
+            return Ok(candidate);
+        }
+    }
+    unreachable!()
+}
+
+fn mcp_server_ids(agent: &Agent) -> Vec<String> {
+    agent
+        .mcp_servers
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+
+    fn seed_agent_with_skill(pool: &DbPool, content: &str) -> String {
+        let mut conn = pool.get().unwrap();
+        let agent_id = uuid::Uuid::new_v4().to_string();
+        diesel::insert_into(agents::table)
+            .values((
+                agents::id.eq(&agent_id),
+                agents::name.eq("Reviewer"),
+                agents::system_prompt.eq("Review pull requests"),
+                agents::workspace_path.eq("/home/me/repo"),
+                agents::api_keys.eq("{\"openai\":\"sk-secret\"}"),
+            ))
+            .execute(&mut conn)
+            .unwrap();
+        let skill_id = uuid::Uuid::new_v4().to_string();
+        diesel::insert_into(agent_skills::table)
+            .values((
+                agent_skills::id.eq(&skill_id),
+                agent_skills::name.eq("lint"),
+                agent_skills::display_title.eq("Lint"),
+                agent_skills::description.eq("Run the linter"),
+                agent_skills::skill_content.eq(content),
+            ))
+            .execute(&mut conn)
+            .unwrap();
+        diesel::insert_into(agent_skill_assignments::table)
+            .values((
+                agent_skill_assignments::agent_id.eq(&agent_id),
+                agent_skill_assignments::skill_id.eq(&skill_id),
+            ))
+            .execute(&mut conn)
+            .unwrap();
+        agent_id
+    }
+
+    #[test]
+    fn test_export_leaves_out_private_fields() {
+        let pool = create_test_pool();
+        let agent_id = seed_agent_with_skill(&pool, "v1");
+        let bundle = export_agent(&pool, &agent_id).unwrap();
+        let json = serde_json::to_string(&bundle).unwrap();
+        assert!(!json.contains("sk-secret"));
+        assert!(!json.contains("/home/me/repo"));
+        assert_eq!(bundle.skills.len(), 1);
+
+        // Identical skills are reused without asking
+        let report = import_agent(&pool, bundle, &AgentImportOptions::default()).unwrap();
+        assert_ne!(report.agent_id, agent_id);
+        assert_eq!(report.skills_reused, vec!["lint".to_string()]);
+    }
+
+    #[test]
+    fn test_skill_conflicts() {
+        let source = create_test_pool();
+        let bundle = export_agent(&source, &seed_agent_with_skill(&source, "v2")).unwrap();
+
+        let target = create_test_pool();
+        seed_agent_with_skill(&target, "v1");
+        assert!(import_agent(&target, bundle.clone(), &AgentImportOptions::default())
+            .unwrap_err()
+            .contains("lint"));
+
+        let rename = AgentImportOptions {
+            skill_conflict: SkillConflict::Rename,
+            ..Default::default()
+        };
+        let report = import_agent(&target, bundle.clone(), &rename).unwrap();
+        assert_eq!(report.skills_renamed, vec![("lint".to_string(), "lint-2".to_string())]);
+
+        let replace = AgentImportOptions {
+            skill_conflict: SkillConflict::Replace,
+            ..Default::default()
+        };
+        import_agent(&target, bundle, &replace).unwrap();
+        let mut conn = target.get().unwrap();
+        let content: String = agent_skills::table
+            .filter(agent_skills::name.eq("lint"))
+            .select(agent_skills::skill_content)
+            .first(&mut conn)
+            .unwrap();
+        assert_eq!(content, "v2");
+    }
+}
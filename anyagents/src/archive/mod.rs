@@ -0,0 +1,68 @@
+//! Portable archives for moving data between installations
+//!
+//! Session archives are JSON Lines: a header record naming the format and
+//! version, then one record per row, each tagged with its `type`. Agent
+//! bundles are a single JSON document carrying the same header.
+
+pub mod agent;
+pub mod session;
+
+pub use agent::{export_agent, import_agent, AgentBundle, AgentImportOptions, AgentImportReport, SkillConflict};
+pub use session::{export_session, import_session, SessionRecord};
+
+use serde::{Deserialize, Serialize};
+
+/// First line of every archive
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ArchiveHeader {
+    /// e.g. "anycowork-session"
+    pub format: String,
+    pub version: u32,
+    pub exported_at: chrono::NaiveDateTime,
+    /// Version of the app that wrote it
+    pub app_version: String,
+}
+
+impl ArchiveHeader {
+    pub fn new(format: &str, version: u32) -> Self {
+        Self {
+            format: format.to_string(),
+            version,
+            exported_at: chrono::Utc::now().naive_utc(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    /// Archives from newer versions of the format are refused
+    pub fn check(&self, format: &str, max_version: u32) -> Result<(), String> {
+        if self.format != format {
+            return Err(format!("Not a {} archive (found '{}')", format, self.format));
+        }
+        if self.version > max_version {
+            return Err(format!(
+                "Archive version {} is newer than this app supports ({}); update the app",
+                self.version, max_version
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Serialize records as JSON Lines
+pub(crate) fn write_lines<T: Serialize>(records: &[T]) -> Result<String, String> {
+    let mut out = String::new();
+    for record in records {
+        out.push_str(&serde_json::to_string(record).map_err(|e| e.to_string())?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Parse JSON Lines, skipping blank lines; errors name the line
+pub(crate) fn read_lines<T: for<'de> Deserialize<'de>>(text: &str) -> Result<Vec<T>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| serde_json::from_str(line).map_err(|e| format!("Invalid record on line {}: {}", i + 1, e)))
+        .collect()
+}
@@ -0,0 +1,331 @@
+//! Session archives: a chat with its messages (tool steps and attached
+//! images included), plans and permission decisions
+//!
+//! Importing gives the session and everything in it fresh ids, so an archive
+//! can be imported next to the session it came from. Permission decisions are
+//! exported for the record but not imported: the audit log only holds
+//! decisions made on this machine.
+
+use super::{read_lines, write_lines, ArchiveHeader};
+use crate::database::DbPool;
+use crate::models::{Message, PermissionAuditEntry, PlanRecord, PlanTaskRecord, Session};
+use crate::schema::{agents, messages, permission_audit, plan_tasks, plans, sessions};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const SESSION_ARCHIVE_FORMAT: &str = "anycowork-session";
+pub const SESSION_ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SessionRecord {
+    Header(ArchiveHeader),
+    Session {
+        session: Session,
+        /// Used to find the agent when its id does not exist on import
+        agent_name: Option<String>,
+    },
+    /// Tool steps are messages with role "tool" and their arguments as
+    /// metadata; user images are in the metadata too
+    Message(Message),
+    Plan(PlanRecord),
+    PlanTask(PlanTaskRecord),
+    PermissionDecision(PermissionAuditEntry),
+}
+
+/// The session as a JSON Lines archive
+pub fn export_session(pool: &DbPool, session_id: &str) -> Result<String, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let session: Session = sessions::table
+        .find(session_id)
+        .first(&mut conn)
+        .map_err(|_| "Session not found".to_string())?;
+    let agent_name: Option<String> = agents::table
+        .find(&session.agent_id)
+        .select(agents::name)
+        .first(&mut conn)
+        .ok();
+
+    let mut records = vec![
+        SessionRecord::Header(ArchiveHeader::new(SESSION_ARCHIVE_FORMAT, SESSION_ARCHIVE_VERSION)),
+        SessionRecord::Session { session, agent_name },
+    ];
+
+    let session_messages: Vec<Message> = messages::table
+        .filter(messages::session_id.eq(session_id))
+        .filter(messages::deleted_at.is_null())
+        .order(messages::created_at.asc())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())?;
+    records.extend(session_messages.into_iter().map(SessionRecord::Message));
+
+    let session_plans: Vec<PlanRecord> = plans::table
+        .filter(plans::session_id.eq(session_id))
+        .order(plans::created_at.asc())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())?;
+    for plan in session_plans {
+        let tasks: Vec<PlanTaskRecord> = plan_tasks::table
+            .filter(plan_tasks::plan_id.eq(&plan.id))
+            .order(plan_tasks::position.asc())
+            .load(&mut conn)
+            .map_err(|e| e.to_string())?;
+        records.push(SessionRecord::Plan(plan));
+        records.extend(tasks.into_iter().map(SessionRecord::PlanTask));
+    }
+
+    let decisions: Vec<PermissionAuditEntry> = permission_audit::table
+        .filter(permission_audit::session_id.eq(session_id))
+        .order(permission_audit::decided_at.asc())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())?;
+    records.extend(decisions.into_iter().map(SessionRecord::PermissionDecision));
+
+    write_lines(&records)
+}
+
+/// Restore an archive as a new session. It goes to `agent_id` when given,
+/// otherwise to the archived agent (by id, then by name).
+pub fn import_session(pool: &DbPool, archive: &str, agent_id: Option<&str>) -> Result<Session, String> {
+    let records: Vec<SessionRecord> = read_lines(archive)?;
+    let mut records = records.into_iter();
+    match records.next() {
+        Some(SessionRecord::Header(header)) => header.check(SESSION_ARCHIVE_FORMAT, SESSION_ARCHIVE_VERSION)?,
+        _ => return Err("Archive has no header".to_string()),
+    }
+    let records: Vec<SessionRecord> = records.collect();
+    let (archived, agent_name) = records
+        .iter()
+        .find_map(|r| match r {
+            SessionRecord::Session { session, agent_name } => Some((session.clone(), agent_name.clone())),
+            _ => None,
+        })
+        .ok_or("Archive has no session")?;
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let agent_exists = |conn: &mut SqliteConnection, id: &str| {
+        agents::table
+            .find(id)
+            .select(agents::id)
+            .first::<String>(conn)
+            .optional()
+            .map_err(|e| e.to_string())
+    };
+    let target_agent = match agent_id {
+        Some(id) => agent_exists(&mut conn, id)?.ok_or_else(|| format!("Agent '{}' not found", id))?,
+        None => match agent_exists(&mut conn, &archived.agent_id)? {
+            Some(id) => id,
+            None => agent_name
+                .and_then(|name| {
+                    agents::table
+                        .filter(agents::name.eq(name))
+                        .select(agents::id)
+                        .first::<String>(&mut conn)
+                        .ok()
+                })
+                .ok_or("The archived session's agent does not exist here; choose an agent to import it into")?,
+        },
+    };
+
+    let session_id = new_id();
+    let mut plan_ids: HashMap<String, String> = HashMap::new();
+
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        diesel::insert_into(sessions::table)
+            .values((
+                sessions::id.eq(&session_id),
+                sessions::agent_id.eq(&target_agent),
+                sessions::title.eq(&archived.title),
+                sessions::created_at.eq(archived.created_at),
+                sessions::updated_at.eq(archived.updated_at),
+                sessions::archived.eq(archived.archived),
+                sessions::pinned.eq(0),
+                sessions::title_locked.eq(archived.title_locked),
+            ))
+            .execute(conn)?;
+
+        for record in &records {
+            match record {
+                SessionRecord::Message(message) => {
+                    diesel::insert_into(messages::table)
+                        .values((
+                            messages::id.eq(new_id()),
+                            messages::role.eq(&message.role),
+                            messages::content.eq(&message.content),
+                            messages::session_id.eq(&session_id),
+                            messages::created_at.eq(message.created_at),
+                            messages::metadata_json.eq(&message.metadata_json),
+                            messages::tokens.eq(message.tokens),
+                        ))
+                        .execute(conn)?;
+                }
+                SessionRecord::Plan(plan) => {
+                    let id = new_id();
+                    plan_ids.insert(plan.id.clone(), id.clone());
+                    diesel::insert_into(plans::table)
+                        .values((
+                            plans::id.eq(id),
+                            plans::session_id.eq(&session_id),
+                            plans::job_id.eq(&plan.job_id),
+                            plans::objective.eq(&plan.objective),
+                            plans::status.eq(&plan.status),
+                            plans::created_at.eq(plan.created_at),
+                            plans::updated_at.eq(plan.updated_at),
+                        ))
+                        .execute(conn)?;
+                }
+                SessionRecord::PlanTask(task) => {
+                    // Tasks follow their plan in the archive
+                    let Some(plan_id) = plan_ids.get(&task.plan_id) else {
+                        continue;
+                    };
+                    diesel::insert_into(plan_tasks::table)
+                        .values((
+                            plan_tasks::plan_id.eq(plan_id),
+                            plan_tasks::task_id.eq(&task.task_id),
+                            plan_tasks::description.eq(&task.description),
+                            plan_tasks::dependencies.eq(&task.dependencies),
+                            plan_tasks::position.eq(task.position),
+                            plan_tasks::status.eq(&task.status),
+                            plan_tasks::result.eq(&task.result),
+                            plan_tasks::updated_at.eq(task.updated_at),
+                            plan_tasks::assignee.eq(&task.assignee),
+                        ))
+                        .execute(conn)?;
+                }
+                // The audit log only holds decisions made here
+                SessionRecord::Header(_) | SessionRecord::Session { .. } | SessionRecord::PermissionDecision(_) => {}
+            }
+        }
+        Ok(())
+    })
+    .map_err(|e| format!("Failed to import session: {}", e))?;
+
+    sessions::table
+        .find(&session_id)
+        .first(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+fn new_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+
+    fn seed(pool: &DbPool) -> (String, String) {
+        let mut conn = pool.get().unwrap();
+        let agent_id = uuid::Uuid::new_v4().to_string();
+        diesel::insert_into(agents::table)
+            .values((
+                agents::id.eq(&agent_id),
+                agents::name.eq("Archivist"),
+                agents::ai_provider.eq("openai"),
+                agents::ai_model.eq("gpt-4o"),
+            ))
+            .execute(&mut conn)
+            .unwrap();
+        let session_id = uuid::Uuid::new_v4().to_string();
+        let now = chrono::Utc::now().naive_utc();
+        diesel::insert_into(sessions::table)
+            .values((
+                sessions::id.eq(&session_id),
+                sessions::agent_id.eq(&agent_id),
+                sessions::title.eq("Quarterly report"),
+                sessions::created_at.eq(now),
+                sessions::updated_at.eq(now),
+                sessions::archived.eq(0),
+                sessions::pinned.eq(1),
+            ))
+            .execute(&mut conn)
+            .unwrap();
+        for (role, content) in [("user", "Summarize Q3"), ("tool", "Tool 'bash' result: {}"), ("assistant", "Done")] {
+            diesel::insert_into(messages::table)
+                .values((
+                    messages::id.eq(uuid::Uuid::new_v4().to_string()),
+                    messages::role.eq(role),
+                    messages::content.eq(content),
+                    messages::session_id.eq(&session_id),
+                ))
+                .execute(&mut conn)
+                .unwrap();
+        }
+        (agent_id, session_id)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let pool = create_test_pool();
+        let (agent_id, session_id) = seed(&pool);
+
+        let archive = export_session(&pool, &session_id).unwrap();
+        assert!(archive.lines().next().unwrap().contains("\"type\":\"header\""));
+
+        let imported = import_session(&pool, &archive, None).unwrap();
+        assert_ne!(imported.id, session_id);
+        assert_eq!(imported.agent_id, agent_id);
+        assert_eq!(imported.title.as_deref(), Some("Quarterly report"));
+
+        let mut conn = pool.get().unwrap();
+        let roles: Vec<String> = messages::table
+            .filter(messages::session_id.eq(&imported.id))
+            .order(messages::created_at.asc())
+            .select(messages::role)
+            .load(&mut conn)
+            .unwrap();
+        assert_eq!(roles.len(), 3);
+        assert!(roles.contains(&"tool".to_string()));
+    }
+
+    #[test]
+    fn test_permission_decisions_are_not_imported() {
+        let pool = create_test_pool();
+        let (_, session_id) = seed(&pool);
+        let now = chrono::Utc::now().naive_utc();
+        diesel::insert_into(permission_audit::table)
+            .values(crate::models::NewPermissionAuditEntry {
+                id: new_id(),
+                session_id: Some(session_id.clone()),
+                tool: Some("bash".to_string()),
+                permission_type: "shell_execute".to_string(),
+                resource: None,
+                message: "Run ls?".to_string(),
+                metadata: "{}".to_string(),
+                resolution: "approved".to_string(),
+                decided_by: "user".to_string(),
+                requested_at: now,
+                decided_at: now,
+                payload: None,
+            })
+            .execute(&mut pool.get().unwrap())
+            .unwrap();
+
+        let archive = export_session(&pool, &session_id).unwrap();
+        assert!(archive.contains("\"type\":\"permission_decision\""));
+        let imported = import_session(&pool, &archive, None).unwrap();
+        let decisions: i64 = permission_audit::table
+            .filter(permission_audit::session_id.eq(&imported.id))
+            .count()
+            .get_result(&mut pool.get().unwrap())
+            .unwrap();
+        assert_eq!(decisions, 0);
+    }
+
+    #[test]
+    fn test_rejects_foreign_archives() {
+        let pool = create_test_pool();
+        assert!(import_session(&pool, "", None).is_err());
+
+        let header = SessionRecord::Header(ArchiveHeader::new(SESSION_ARCHIVE_FORMAT, SESSION_ARCHIVE_VERSION + 1));
+        let newer = write_lines(&[header]).unwrap();
+        assert!(import_session(&pool, &newer, None).unwrap_err().contains("newer"));
+
+        let (_, session_id) = seed(&pool);
+        let archive = export_session(&pool, &session_id).unwrap();
+        assert!(import_session(&pool, &archive, Some("missing-agent")).is_err());
+    }
+}
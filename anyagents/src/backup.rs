@@ -0,0 +1,353 @@
+//! Database backups
+//!
+//! `backup_database` writes a consistent copy of the live database with
+//! `VACUUM INTO`, which SQLite runs as an online backup: readers and writers
+//! carry on while it copies. `restore_database` checks a backup, brings it up
+//! to the current schema and copies its rows over the live tables in one
+//! transaction, so the open pool stays valid and nothing needs a restart.
+//! Attachment files are not part of a backup. An encrypted database gives
+//! encrypted backups, with the same key.
+
+use crate::database::{lock_writes, DbPool};
+use crate::models::settings::{get_setting, set_setting};
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const ENABLED_KEY: &str = "backup_enabled";
+const INTERVAL_KEY: &str = "backup_interval_hours";
+const KEEP_KEY: &str = "backup_keep";
+
+const FILE_PREFIX: &str = "anycowork-";
+const FILE_SUFFIX: &str = ".db";
+
+/// When scheduled backups run and how many are kept
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct BackupSchedule {
+    pub enabled: bool,
+    pub interval_hours: u64,
+    /// Older backups beyond this many are deleted after each backup
+    pub keep: usize,
+}
+
+impl Default for BackupSchedule {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_hours: 24,
+            keep: 7,
+        }
+    }
+}
+
+impl BackupSchedule {
+    pub fn load(pool: &DbPool) -> Self {
+        let defaults = Self::default();
+        Self {
+            enabled: get_setting(pool, ENABLED_KEY).map(|v| v == "true").unwrap_or(defaults.enabled),
+            interval_hours: get_setting(pool, INTERVAL_KEY)
+                .and_then(|v| v.parse().ok())
+                .filter(|v| *v > 0)
+                .unwrap_or(defaults.interval_hours),
+            keep: get_setting(pool, KEEP_KEY)
+                .and_then(|v| v.parse().ok())
+                .filter(|v| *v > 0)
+                .unwrap_or(defaults.keep),
+        }
+    }
+
+    pub fn save(&self, pool: &DbPool) -> Result<(), String> {
+        if self.interval_hours == 0 || self.keep == 0 {
+            return Err("Backup interval and retention must be positive".to_string());
+        }
+        set_setting(pool, ENABLED_KEY, &self.enabled.to_string())?;
+        set_setting(pool, INTERVAL_KEY, &self.interval_hours.to_string())?;
+        set_setting(pool, KEEP_KEY, &self.keep.to_string())
+    }
+
+    /// Whether a scheduled backup is due, given the newest existing one
+    pub fn is_due(&self, latest: Option<&BackupInfo>) -> bool {
+        let interval = chrono::Duration::hours(self.interval_hours as i64);
+        self.enabled && latest.is_none_or(|b| chrono::Utc::now() - b.created_at >= interval)
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BackupInfo {
+    pub file_name: String,
+    pub path: String,
+    pub size_bytes: u64,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl BackupInfo {
+    fn read(path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?;
+        if !file_name.starts_with(FILE_PREFIX) || !file_name.ends_with(FILE_SUFFIX) {
+            return None;
+        }
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(Self {
+            file_name: file_name.to_string(),
+            path: path.to_string_lossy().to_string(),
+            size_bytes: metadata.len(),
+            created_at: metadata.modified().ok()?.into(),
+        })
+    }
+}
+
+/// Result of `PRAGMA integrity_check`
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DatabaseCheck {
+    pub ok: bool,
+    /// What SQLite found wrong; empty when `ok`
+    pub problems: Vec<String>,
+}
+
+#[derive(QueryableByName)]
+struct Name {
+    #[diesel(sql_type = Text)]
+    name: String,
+}
+
+fn integrity_problems(conn: &mut SqliteConnection) -> Result<Vec<String>, String> {
+    #[derive(QueryableByName)]
+    struct Row {
+        #[diesel(sql_type = Text)]
+        integrity_check: String,
+    }
+    let rows: Vec<Row> = diesel::sql_query("PRAGMA integrity_check")
+        .load(conn)
+        .map_err(|e| format!("Integrity check failed: {}", e))?;
+    Ok(rows
+        .into_iter()
+        .map(|r| r.integrity_check)
+        .filter(|line| line != "ok")
+        .collect())
+}
+
+pub fn check_database(pool: &DbPool) -> Result<DatabaseCheck, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let problems = integrity_problems(&mut conn)?;
+    Ok(DatabaseCheck { ok: problems.is_empty(), problems })
+}
+
+/// Write a snapshot of the database to a new timestamped file in `dir`
+pub fn backup_database(pool: &DbPool, dir: &Path) -> Result<BackupInfo, String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create backup directory: {}", e))?;
+    let stamp = chrono::Utc::now().format("%Y%m%d-%H%M%S-%3f");
+    let path = dir.join(format!("{}{}{}", FILE_PREFIX, stamp, FILE_SUFFIX));
+    if path.exists() {
+        return Err(format!("Backup {} already exists", path.display()));
+    }
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::sql_query("VACUUM INTO ?")
+        .bind::<Text, _>(path.to_string_lossy().to_string())
+        .execute(&mut conn)
+        .map_err(|e| format!("Backup failed: {}", e))?;
+
+    BackupInfo::read(&path).ok_or_else(|| "Backup file was not written".to_string())
+}
+
+/// Backups in `dir`, newest first
+pub fn list_backups(dir: &Path) -> Result<Vec<BackupInfo>, String> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read backup directory: {}", e)),
+    };
+    let mut backups: Vec<BackupInfo> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| BackupInfo::read(&entry.path()))
+        .collect();
+    // File names carry the creation time, so they sort like it
+    backups.sort_by(|a, b| b.file_name.cmp(&a.file_name));
+    Ok(backups)
+}
+
+/// The backup called `file_name` in `dir`; anything else (paths, other
+/// files) is refused
+pub fn find_backup(dir: &Path, file_name: &str) -> Result<PathBuf, String> {
+    list_backups(dir)?
+        .into_iter()
+        .find(|b| b.file_name == file_name)
+        .map(|b| PathBuf::from(b.path))
+        .ok_or_else(|| format!("Backup '{}' not found", file_name))
+}
+
+/// Delete all but the newest `keep` backups and return how many went
+pub fn prune_backups(dir: &Path, keep: usize) -> Result<usize, String> {
+    let mut removed = 0;
+    for backup in list_backups(dir)?.into_iter().skip(keep) {
+        match std::fs::remove_file(&backup.path) {
+            Ok(()) => removed += 1,
+            Err(e) => log::warn!("Failed to remove old backup {}: {}", backup.path, e),
+        }
+    }
+    Ok(removed)
+}
+
+/// Check a copy of the backup and migrate it to the current schema
+fn prepare_restore(staging: &Path) -> Result<(), String> {
+    use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+    const MIGRATIONS: EmbeddedMigrations = embed_migrations!("../src-tauri/migrations");
+
+    let mut conn = SqliteConnection::establish(&staging.to_string_lossy())
+        .map_err(|e| format!("Failed to open backup: {}", e))?;
+    crate::encryption::unlock(&mut conn, staging)?;
+    let problems = integrity_problems(&mut conn)?;
+    if !problems.is_empty() {
+        return Err(format!("Backup is damaged: {}", problems.join("; ")));
+    }
+    conn.run_pending_migrations(MIGRATIONS)
+        .map_err(|e| format!("Failed to migrate backup: {}", e))?;
+    Ok(())
+}
+
+fn column_names(conn: &mut SqliteConnection, table: &str, schema: &str) -> QueryResult<Vec<String>> {
+    let names: Vec<Name> = diesel::sql_query("SELECT name FROM pragma_table_info(?, ?)")
+        .bind::<Text, _>(table)
+        .bind::<Text, _>(schema)
+        .load(conn)?;
+    Ok(names.into_iter().map(|n| n.name).collect())
+}
+
+/// The live tables, each after the tables it references, so the search
+/// triggers find a message's session or a block's page when it goes in
+fn tables_in_order(conn: &mut SqliteConnection) -> QueryResult<Vec<String>> {
+    #[derive(QueryableByName)]
+    struct Reference {
+        #[diesel(sql_type = Text)]
+        name: String,
+        #[diesel(sql_type = Text)]
+        parent: String,
+    }
+
+    let mut pending: Vec<String> = diesel::sql_query(
+        "SELECT name FROM pragma_table_list WHERE schema = 'main' AND type = 'table' \
+         AND name NOT LIKE 'sqlite_%' AND name != '__diesel_schema_migrations' ORDER BY name",
+    )
+    .load::<Name>(conn)?
+    .into_iter()
+    .map(|n| n.name)
+    .collect();
+    let references: Vec<Reference> = diesel::sql_query(
+        "SELECT t.name AS name, f.\"table\" AS parent \
+         FROM pragma_table_list AS t JOIN pragma_foreign_key_list(t.name) AS f \
+         WHERE t.schema = 'main' AND t.type = 'table'",
+    )
+    .load(conn)?;
+
+    let mut ordered = Vec::with_capacity(pending.len());
+    while !pending.is_empty() {
+        let waits_on_parent = |table: &String| {
+            references
+                .iter()
+                .any(|r| r.name == *table && r.parent != *table && pending.contains(&r.parent))
+        };
+        // A reference cycle can't be ordered; take the rest as they are
+        let next = pending.iter().position(|t| !waits_on_parent(t)).unwrap_or(0);
+        ordered.push(pending.remove(next));
+    }
+    Ok(ordered)
+}
+
+/// Replace every table's rows with the attached backup's. Search indexes
+/// are left to their triggers, which rebuild them as rows go in.
+fn copy_tables(conn: &mut SqliteConnection) -> QueryResult<()> {
+    for table in tables_in_order(conn)? {
+        let backup_columns = column_names(conn, &table, "backup")?;
+        let columns: Vec<String> = column_names(conn, &table, "main")?
+            .into_iter()
+            .filter(|c| backup_columns.contains(c))
+            .map(|c| format!("\"{}\"", c))
+            .collect();
+
+        diesel::sql_query(format!("DELETE FROM main.\"{}\"", table)).execute(conn)?;
+        if !columns.is_empty() {
+            let columns = columns.join(", ");
+            diesel::sql_query(format!(
+                "INSERT INTO main.\"{t}\" ({c}) SELECT {c} FROM backup.\"{t}\"",
+                t = table,
+                c = columns
+            ))
+            .execute(conn)?;
+        }
+    }
+    Ok(())
+}
+
+/// Replace the live database's contents with the backup at `backup`
+pub fn restore_database(pool: &DbPool, backup: &Path) -> Result<(), String> {
+    let staging = backup.with_extension("restoring");
+    std::fs::copy(backup, &staging).map_err(|e| format!("Failed to read backup: {}", e))?;
+    let result = prepare_restore(&staging).and_then(|()| {
+        let mut conn = pool.get().map_err(|e| e.to_string())?;
+        let _guard = lock_writes();
+        crate::encryption::attach(&mut conn, &staging, "backup")?;
+        let copied = conn.immediate_transaction(copy_tables).map_err(|e| format!("Restore failed: {}", e));
+        if let Err(e) = diesel::sql_query("DETACH DATABASE backup").execute(&mut conn) {
+            log::warn!("Failed to detach backup: {}", e);
+        }
+        copied
+    });
+    let _ = std::fs::remove_file(&staging);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+    use crate::models::NewSetting;
+    use crate::schema::settings;
+
+    fn add_setting(pool: &DbPool, key: &str) {
+        let mut conn = pool.get().unwrap();
+        diesel::insert_into(settings::table)
+            .values(NewSetting {
+                id: uuid::Uuid::new_v4().to_string(),
+                key: key.to_string(),
+                value: Some("1".to_string()),
+            })
+            .execute(&mut conn)
+            .unwrap();
+    }
+
+    fn has_setting(pool: &DbPool, key: &str) -> bool {
+        get_setting(pool, key).is_some()
+    }
+
+    #[test]
+    fn test_backup_and_restore() {
+        let pool = create_test_pool();
+        let dir = std::env::temp_dir().join(format!("anycowork_backups_{}", uuid::Uuid::new_v4()));
+        assert!(check_database(&pool).unwrap().ok);
+        assert!(list_backups(&dir).unwrap().is_empty());
+
+        add_setting(&pool, "before_backup");
+        let backup = backup_database(&pool, &dir).unwrap();
+        assert!(backup.size_bytes > 0);
+        add_setting(&pool, "after_backup");
+
+        let path = find_backup(&dir, &backup.file_name).unwrap();
+        assert!(find_backup(&dir, "../anycowork.db").is_err());
+        restore_database(&pool, &path).unwrap();
+        assert!(has_setting(&pool, "before_backup"));
+        assert!(!has_setting(&pool, "after_backup"));
+
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        backup_database(&pool, &dir).unwrap();
+        assert_eq!(prune_backups(&dir, 1).unwrap(), 1);
+        let left = list_backups(&dir).unwrap();
+        assert_eq!(left.len(), 1);
+        assert_ne!(left[0].file_name, backup.file_name);
+
+        let schedule = BackupSchedule { enabled: true, ..Default::default() };
+        assert!(schedule.is_due(None));
+        assert!(!schedule.is_due(left.first()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
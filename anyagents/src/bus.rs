@@ -0,0 +1,417 @@
+//! Agent-to-agent messaging bus
+//!
+//! Agents exchange typed messages instead of simulated mail. A message for an
+//! agent that is currently running lands in its inbox and is injected into the
+//! conversation before its next step. Task requests and questions for idle
+//! agents wake them up in a new session; the answer is sent back to the sender
+//! as a `TaskResult`. Every message is emitted on the `agent_bus` channel.
+
+use crate::agents::AgentLoop;
+use crate::database::DbPool;
+use crate::events::{AgentEvent, AgentObserver};
+use crate::models::{Agent as DbAgent, NewSession};
+use crate::permissions::PermissionManager;
+use crate::tools::email::NoOpObserver;
+use dashmap::DashMap;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::sync::mpsc;
+
+/// Channel on which `AgentEvent::BusMessage` is emitted
+pub const BUS_CHANNEL: &str = "agent_bus";
+
+/// Sender id used for messages typed by the user
+pub const USER_SENDER: &str = "user";
+
+/// Messages kept for `recent()` (the UI's initial traffic view)
+const RECENT_LIMIT: usize = 200;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub enum BusPayload {
+    TaskRequest { task: String },
+    /// Answer to a TaskRequest or Question (see `in_reply_to`)
+    TaskResult { result: String, success: bool },
+    Question { question: String },
+    Broadcast { text: String },
+}
+
+impl BusPayload {
+    /// Task requests and questions expect an answer, so they wake idle agents
+    pub fn wants_reply(&self) -> bool {
+        matches!(self, BusPayload::TaskRequest { .. } | BusPayload::Question { .. })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct BusMessage {
+    pub id: String,
+    /// Agent id, or "user"
+    pub from: String,
+    /// Recipient agent id; None broadcasts to every running agent
+    pub to: Option<String>,
+    pub in_reply_to: Option<String>,
+    pub payload: BusPayload,
+    pub created_at: String,
+}
+
+impl BusMessage {
+    pub fn new(from: &str, to: Option<&str>, payload: BusPayload) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            from: from.to_string(),
+            to: to.map(str::to_string),
+            in_reply_to: None,
+            payload,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub fn reply(&self, from: &str, payload: BusPayload) -> Self {
+        Self {
+            in_reply_to: Some(self.id.clone()),
+            ..Self::new(from, Some(&self.from), payload)
+        }
+    }
+
+    /// Text injected into the recipient's conversation
+    pub fn to_prompt(&self, sender_name: &str) -> String {
+        match &self.payload {
+            BusPayload::TaskRequest { task } => format!(
+                "[Task request from {}]\n{}\n\nComplete the task; your final answer is sent back to {}.",
+                sender_name, task, sender_name
+            ),
+            BusPayload::TaskResult { result, success } => format!(
+                "[{} from {}]\n{}",
+                if *success { "Task result" } else { "Failed task result" },
+                sender_name,
+                result
+            ),
+            BusPayload::Question { question } => format!(
+                "[Question from {}]\n{}\n\nYour final answer is sent back to {}.",
+                sender_name, question, sender_name
+            ),
+            BusPayload::Broadcast { text } => format!("[Broadcast from {}]\n{}", sender_name, text),
+        }
+    }
+}
+
+/// What happened to a message
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub enum Delivery {
+    /// Handed to the inbox of a running agent (or every running agent, for broadcasts)
+    Delivered,
+    /// The idle recipient was started to handle it
+    Woken,
+    /// Held until the recipient next runs
+    Queued,
+}
+
+pub struct AgentBus {
+    db_pool: DbPool,
+    permission_manager: Arc<PermissionManager>,
+    observer: RwLock<Arc<dyn AgentObserver>>,
+    /// Inboxes of running agents, tagged so a finished run only removes its own
+    mailboxes: DashMap<String, (u64, mpsc::UnboundedSender<BusMessage>)>,
+    /// Messages for idle agents that did not warrant waking them
+    queued: DashMap<String, Vec<BusMessage>>,
+    recent: Mutex<VecDeque<BusMessage>>,
+    next_inbox_id: AtomicU64,
+}
+
+impl AgentBus {
+    pub fn new(db_pool: DbPool, permission_manager: Arc<PermissionManager>) -> Self {
+        Self {
+            db_pool,
+            permission_manager,
+            observer: RwLock::new(Arc::new(NoOpObserver)),
+            mailboxes: DashMap::new(),
+            queued: DashMap::new(),
+            recent: Mutex::new(VecDeque::new()),
+            next_inbox_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Where bus traffic and woken agents' events are emitted
+    pub fn set_observer(&self, observer: Arc<dyn AgentObserver>) {
+        *self.observer.write().unwrap() = observer;
+    }
+
+    fn observer(&self) -> Arc<dyn AgentObserver> {
+        self.observer.read().unwrap().clone()
+    }
+
+    pub fn db_pool(&self) -> &DbPool {
+        &self.db_pool
+    }
+
+    /// Register a running agent. Messages queued while it was idle are delivered first.
+    /// If the same agent runs twice concurrently, the newest run receives messages.
+    pub fn subscribe(self: &Arc<Self>, agent_id: &str) -> Inbox {
+        let (tx, rx) = mpsc::unbounded_channel();
+        if let Some((_, queued)) = self.queued.remove(agent_id) {
+            for message in queued {
+                let _ = tx.send(message);
+            }
+        }
+        let id = self.next_inbox_id.fetch_add(1, Ordering::Relaxed);
+        self.mailboxes.insert(agent_id.to_string(), (id, tx));
+        Inbox {
+            bus: self.clone(),
+            agent_id: agent_id.to_string(),
+            id,
+            rx,
+        }
+    }
+
+    pub fn is_running(&self, agent_id: &str) -> bool {
+        self.mailboxes.contains_key(agent_id)
+    }
+
+    pub fn send(self: &Arc<Self>, message: BusMessage) -> Delivery {
+        let delivery = match &message.to {
+            None => {
+                for entry in self.mailboxes.iter() {
+                    if entry.key() != &message.from {
+                        let _ = entry.value().1.send(message.clone());
+                    }
+                }
+                Delivery::Delivered
+            }
+            Some(to) => {
+                let delivered = self
+                    .mailboxes
+                    .get(to)
+                    .is_some_and(|mailbox| mailbox.1.send(message.clone()).is_ok());
+                if delivered {
+                    Delivery::Delivered
+                } else if message.payload.wants_reply() {
+                    self.wake(message.clone());
+                    Delivery::Woken
+                } else {
+                    self.queued.entry(to.clone()).or_default().push(message.clone());
+                    Delivery::Queued
+                }
+            }
+        };
+
+        {
+            let mut recent = self.recent.lock().unwrap();
+            recent.push_back(message.clone());
+            while recent.len() > RECENT_LIMIT {
+                recent.pop_front();
+            }
+        }
+
+        let _ = self.observer().emit(
+            BUS_CHANNEL,
+            serde_json::to_value(AgentEvent::BusMessage {
+                message,
+                delivery: delivery.clone(),
+            })
+            .unwrap(),
+        );
+        delivery
+    }
+
+    /// Latest messages, oldest first
+    pub fn recent(&self, limit: usize) -> Vec<BusMessage> {
+        let recent = self.recent.lock().unwrap();
+        recent.iter().skip(recent.len().saturating_sub(limit)).cloned().collect()
+    }
+
+    /// Display name for a sender id ("user" or an agent id)
+    pub fn sender_name(&self, sender: &str) -> String {
+        if sender == USER_SENDER {
+            return "the user".to_string();
+        }
+        self.load_agent(sender)
+            .map(|a| a.name)
+            .unwrap_or_else(|_| sender.to_string())
+    }
+
+    fn load_agent(&self, agent_id: &str) -> Result<DbAgent, String> {
+        use crate::schema::agents;
+        let mut conn = self.db_pool.get().map_err(|e| e.to_string())?;
+        agents::table
+            .find(agent_id)
+            .first::<DbAgent>(&mut conn)
+            .map_err(|e| format!("Agent not found: {}", e))
+    }
+
+    fn wake(self: &Arc<Self>, message: BusMessage) {
+        let bus = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = bus.run_woken(message).await {
+                log::error!("Failed to wake agent for bus message: {}", e);
+            }
+        });
+    }
+
+    /// Run an idle agent on a message in a fresh session and send its answer back
+    async fn run_woken(self: Arc<Self>, message: BusMessage) -> Result<(), String> {
+        let agent_id = message.to.clone().ok_or("Broadcasts do not wake agents")?;
+        let agent_db = self.load_agent(&agent_id)?;
+        let sender_name = self.sender_name(&message.from);
+
+        let now = chrono::Utc::now().naive_utc();
+        let session = NewSession {
+            id: uuid::Uuid::new_v4().to_string(),
+            agent_id: agent_id.clone(),
+            title: Some(format!("Message from {}", sender_name)),
+            created_at: now,
+            updated_at: now,
+            archived: 0,
+            pinned: 0,
+        };
+        {
+            use crate::schema::sessions;
+            let mut conn = self.db_pool.get().map_err(|e| e.to_string())?;
+            diesel::insert_into(sessions::table)
+                .values(&session)
+                .execute(&mut conn)
+                .map_err(|e| format!("Failed to create session: {}", e))?;
+        }
+        let observer = self.observer();
+        let _ = observer.emit("sessions_updated", serde_json::Value::Null);
+
+        let prompt = message.to_prompt(&sender_name);
+        crate::agents::save_message(&self.db_pool, "user", &prompt, &session.id, None);
+
+        let mut worker = AgentLoop::new(&agent_db, self.db_pool.clone()).await;
+        worker.session_id = session.id.clone();
+        worker.attach_bus(self.clone());
+        worker
+            .run(
+                prompt,
+                observer,
+                uuid::Uuid::new_v4().to_string(),
+                Arc::new(DashMap::new()),
+                self.permission_manager.clone(),
+                self.db_pool.clone(),
+            )
+            .await;
+
+        let answer = worker
+            .history
+            .iter()
+            .rev()
+            .find(|m| matches!(m, rig::completion::Message::Assistant { .. }))
+            .map(crate::agents::optimizations::get_message_content);
+
+        let payload = match answer {
+            Some(result) => BusPayload::TaskResult { result, success: true },
+            None => BusPayload::TaskResult {
+                result: format!("{} could not produce an answer.", agent_db.name),
+                success: false,
+            },
+        };
+        // Replies to the user are only emitted; a woken agent never wakes its sender
+        self.send(message.reply(&agent_id, payload));
+        Ok(())
+    }
+}
+
+/// Messages for one running agent; unregisters itself when dropped
+pub struct Inbox {
+    bus: Arc<AgentBus>,
+    agent_id: String,
+    id: u64,
+    rx: mpsc::UnboundedReceiver<BusMessage>,
+}
+
+impl Inbox {
+    /// Everything received since the last call
+    pub fn drain(&mut self) -> Vec<BusMessage> {
+        let mut messages = vec![];
+        while let Ok(message) = self.rx.try_recv() {
+            messages.push(message);
+        }
+        messages
+    }
+}
+
+impl Drop for Inbox {
+    fn drop(&mut self) {
+        self.bus.mailboxes.remove_if(&self.agent_id, |_, (id, _)| *id == self.id);
+        // Anything that arrived after the last drain waits for the next run
+        let leftover = self.drain();
+        if !leftover.is_empty() {
+            self.bus.queued.entry(self.agent_id.clone()).or_default().extend(leftover);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+
+    fn bus() -> Arc<AgentBus> {
+        Arc::new(AgentBus::new(create_test_pool(), Arc::new(PermissionManager::new())))
+    }
+
+    fn broadcast(from: &str, text: &str) -> BusMessage {
+        BusMessage::new(from, None, BusPayload::Broadcast { text: text.to_string() })
+    }
+
+    #[tokio::test]
+    async fn test_delivery_to_running_agent() {
+        let bus = bus();
+        let mut inbox = bus.subscribe("a");
+        assert!(bus.is_running("a"));
+
+        let result = BusMessage::new("b", Some("a"), BusPayload::TaskResult {
+            result: "done".to_string(),
+            success: true,
+        });
+        assert_eq!(bus.send(result), Delivery::Delivered);
+        assert_eq!(bus.send(broadcast("b", "hello")), Delivery::Delivered);
+        // Senders do not receive their own broadcasts
+        bus.send(broadcast("a", "echo"));
+
+        let received = inbox.drain();
+        assert_eq!(received.len(), 2);
+        assert!(received[1].to_prompt("B").contains("hello"));
+
+        drop(inbox);
+        assert!(!bus.is_running("a"));
+        assert_eq!(bus.recent(10).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_idle_agent_gets_queued_messages() {
+        let bus = bus();
+        let result = BusMessage::new("b", Some("a"), BusPayload::TaskResult {
+            result: "done".to_string(),
+            success: true,
+        });
+        assert_eq!(bus.send(result), Delivery::Queued);
+
+        let mut inbox = bus.subscribe("a");
+        assert_eq!(inbox.drain().len(), 1);
+    }
+
+    #[test]
+    fn test_reply_addresses_sender() {
+        let question = BusMessage::new("a", Some("b"), BusPayload::Question {
+            question: "why?".to_string(),
+        });
+        assert!(question.payload.wants_reply());
+
+        let answer = question.reply("b", BusPayload::TaskResult {
+            result: "because".to_string(),
+            success: true,
+        });
+        assert_eq!(answer.to.as_deref(), Some("a"));
+        assert_eq!(answer.in_reply_to.as_deref(), Some(question.id.as_str()));
+        assert!(!answer.payload.wants_reply());
+    }
+}
@@ -0,0 +1,350 @@
+//! Core configuration
+//!
+//! `CoreConfig` is built in layers, each overriding the keys it sets:
+//!
+//! 1. defaults
+//! 2. `config.toml` in the app directory (`~/.anycowork`), or the file
+//!    `ANYCOWORK_CONFIG` points to
+//! 3. environment variables (`ENV_VARS`)
+//! 4. for one agent, the `AGENT_KEYS` of its execution settings
+//!
+//! A layer with an unknown key or a bad value is an error naming the layer;
+//! `current` then keeps the last good configuration. `reload` re-reads the
+//! layers and reports what changed. Keys in `RESTART_KEYS` are read once at
+//! startup, so their changes only apply after a restart.
+//!
+//! ```toml
+//! max_parallel_tasks = 4
+//! trace_dir = "/var/log/anycowork/traces"
+//! ```
+
+use crate::models::Agent as DbAgent;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+pub const CONFIG_FILE_ENV: &str = "ANYCOWORK_CONFIG";
+const CONFIG_FILE: &str = "config.toml";
+const MAX_PARALLEL_TASKS: usize = 64;
+
+/// Environment variable of each key
+pub const ENV_VARS: &[(&str, &str)] = &[
+    ("database_url", "DATABASE_URL"),
+    ("otel_endpoint", crate::telemetry::ENDPOINT_ENV),
+    ("trace_dir", crate::agents::trace::TRACE_DIR_ENV),
+    ("prompts_dir", crate::prompts::PROMPTS_DIR_ENV),
+    ("max_parallel_tasks", "ANYCOWORK_MAX_PARALLEL_TASKS"),
+];
+
+/// Keys an agent may set for itself in its execution settings
+pub const AGENT_KEYS: &[&str] = &["max_parallel_tasks", "trace_dir"];
+
+/// Keys that need a restart to take effect
+pub const RESTART_KEYS: &[&str] = &["database_url", "otel_endpoint"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct CoreConfig {
+    /// SQLite database; `~/.anycowork/anycowork.db` when unset
+    pub database_url: Option<String>,
+    /// OTLP endpoint spans are exported to (see `telemetry`)
+    pub otel_endpoint: Option<String>,
+    /// Each job writes its execution trace here (see `agents::trace`)
+    #[cfg_attr(feature = "ts", ts(type = "string | null"))]
+    pub trace_dir: Option<PathBuf>,
+    /// Prompt templates in this directory override the built-in ones
+    #[cfg_attr(feature = "ts", ts(type = "string | null"))]
+    pub prompts_dir: Option<PathBuf>,
+    /// Upper bound on plan tasks running at once
+    pub max_parallel_tasks: usize,
+}
+
+impl Default for CoreConfig {
+    fn default() -> Self {
+        Self {
+            database_url: None,
+            otel_endpoint: None,
+            trace_dir: None,
+            prompts_dir: None,
+            max_parallel_tasks: 3,
+        }
+    }
+}
+
+impl CoreConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_PARALLEL_TASKS).contains(&self.max_parallel_tasks) {
+            return Err(format!("max_parallel_tasks must be between 1 and {}", MAX_PARALLEL_TASKS));
+        }
+        if let Some(endpoint) = &self.otel_endpoint {
+            if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+                return Err("otel_endpoint must be an http(s) URL".to_string());
+            }
+        }
+        if self.database_url.as_ref().is_some_and(|url| url.trim().is_empty()) {
+            return Err("database_url is empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Layer a key's value came from
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub enum ConfigSource {
+    Default,
+    File,
+    Env,
+    Agent,
+}
+
+/// A configuration and where each of its keys came from
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct EffectiveConfig {
+    pub config: CoreConfig,
+    pub sources: BTreeMap<String, ConfigSource>,
+    /// The config file read (it may not exist)
+    #[cfg_attr(feature = "ts", ts(type = "string"))]
+    pub file: PathBuf,
+}
+
+/// What a `reload` changed
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ConfigChange {
+    /// Keys now in effect
+    pub applied: Vec<String>,
+    /// Keys whose new value waits for a restart
+    pub restart_required: Vec<String>,
+}
+
+impl ConfigChange {
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty() && self.restart_required.is_empty()
+    }
+}
+
+pub fn config_file() -> PathBuf {
+    match std::env::var_os(CONFIG_FILE_ENV).filter(|p| !p.is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None => dirs::home_dir().unwrap_or_default().join(".anycowork").join(CONFIG_FILE),
+    }
+}
+
+fn object(value: Value) -> Map<String, Value> {
+    match value {
+        Value::Object(map) => map,
+        _ => Map::new(),
+    }
+}
+
+impl EffectiveConfig {
+    fn defaults(file: PathBuf) -> Self {
+        let config = CoreConfig::default();
+        let sources = object(serde_json::to_value(&config).unwrap_or_default())
+            .into_iter()
+            .map(|(key, _)| (key, ConfigSource::Default))
+            .collect();
+        Self { config, sources, file }
+    }
+}
+
+/// `layers` over `base`, in order. An unknown key or a value that doesn't
+/// fit is an error naming its layer.
+fn merge(base: EffectiveConfig, layers: Vec<(ConfigSource, Map<String, Value>)>) -> Result<EffectiveConfig, String> {
+    let EffectiveConfig { config, mut sources, file } = base;
+    let mut merged = object(serde_json::to_value(config).map_err(|e| e.to_string())?);
+
+    for (source, layer) in layers {
+        let describe = match source {
+            ConfigSource::Default => "defaults".to_string(),
+            ConfigSource::File => file.display().to_string(),
+            ConfigSource::Env => "the environment".to_string(),
+            ConfigSource::Agent => "the agent's execution settings".to_string(),
+        };
+        for (key, value) in layer {
+            if !merged.contains_key(&key) {
+                return Err(format!("Unknown config key '{}' in {}", key, describe));
+            }
+            merged.insert(key.clone(), value);
+            sources.insert(key, source);
+        }
+        let config: CoreConfig = serde_json::from_value(Value::Object(merged.clone()))
+            .map_err(|e| format!("Invalid config in {}: {}", describe, e))?;
+        config.validate().map_err(|e| format!("Invalid config in {}: {}", describe, e))?;
+    }
+
+    let config = serde_json::from_value(Value::Object(merged)).map_err(|e| e.to_string())?;
+    Ok(EffectiveConfig { config, sources, file })
+}
+
+fn file_layer(text: &str) -> Result<Map<String, Value>, String> {
+    let table: toml::Table = toml::from_str(text).map_err(|e| e.to_string())?;
+    serde_json::to_value(table).map(object).map_err(|e| e.to_string())
+}
+
+/// Env values are taken as numbers or booleans when they parse as one
+fn env_layer(var: impl Fn(&str) -> Option<String>) -> Map<String, Value> {
+    ENV_VARS
+        .iter()
+        .filter_map(|(key, name)| {
+            let raw = var(name).filter(|v| !v.trim().is_empty())?;
+            let value = match serde_json::from_str::<Value>(&raw) {
+                Ok(value @ (Value::Number(_) | Value::Bool(_))) => value,
+                _ => Value::String(raw),
+            };
+            Some((key.to_string(), value))
+        })
+        .collect()
+}
+
+fn agent_layer(execution_settings: &Value) -> Map<String, Value> {
+    AGENT_KEYS
+        .iter()
+        .filter_map(|key| Some((key.to_string(), execution_settings.get(*key)?.clone())))
+        .filter(|(_, value)| !value.is_null())
+        .collect()
+}
+
+/// Read the defaults, config file and environment
+pub fn load() -> Result<EffectiveConfig, String> {
+    let file = config_file();
+    let mut layers = vec![];
+    match std::fs::read_to_string(&file) {
+        Ok(text) => {
+            let layer = file_layer(&text).map_err(|e| format!("Failed to parse {}: {}", file.display(), e))?;
+            layers.push((ConfigSource::File, layer));
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Failed to read {}: {}", file.display(), e)),
+    }
+    layers.push((ConfigSource::Env, env_layer(|name| std::env::var(name).ok())));
+    merge(EffectiveConfig::defaults(file), layers)
+}
+
+fn global() -> MutexGuard<'static, EffectiveConfig> {
+    static CURRENT: OnceLock<Mutex<EffectiveConfig>> = OnceLock::new();
+    CURRENT
+        .get_or_init(|| {
+            Mutex::new(load().unwrap_or_else(|e| {
+                log::error!("Using the default configuration: {}", e);
+                EffectiveConfig::defaults(config_file())
+            }))
+        })
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The configuration in effect, loaded on first use
+pub fn effective() -> EffectiveConfig {
+    global().clone()
+}
+
+pub fn current() -> CoreConfig {
+    global().config.clone()
+}
+
+/// `current` with the agent's own overrides
+pub fn effective_for_agent(agent: &DbAgent) -> Result<EffectiveConfig, String> {
+    let settings = agent
+        .execution_settings
+        .as_deref()
+        .and_then(|s| serde_json::from_str::<Value>(s).ok())
+        .unwrap_or_default();
+    merge(effective(), vec![(ConfigSource::Agent, agent_layer(&settings))])
+}
+
+/// Like `effective_for_agent`, falling back to `current` (and logging why)
+/// when the agent's overrides are invalid
+pub fn for_agent(agent: &DbAgent) -> CoreConfig {
+    match effective_for_agent(agent) {
+        Ok(effective) => effective.config,
+        Err(e) => {
+            log::error!("Ignoring config overrides of agent {}: {}", agent.id, e);
+            current()
+        }
+    }
+}
+
+/// Re-read the layers. On error the configuration in effect is kept.
+pub fn reload() -> Result<ConfigChange, String> {
+    let loaded = load()?;
+    let mut current = global();
+    let before = object(serde_json::to_value(&current.config).map_err(|e| e.to_string())?);
+    let after = object(serde_json::to_value(&loaded.config).map_err(|e| e.to_string())?);
+
+    let mut change = ConfigChange::default();
+    for (key, value) in &after {
+        if before.get(key) == Some(value) {
+            continue;
+        }
+        match RESTART_KEYS.contains(&key.as_str()) {
+            true => change.restart_required.push(key.clone()),
+            false => change.applied.push(key.clone()),
+        }
+    }
+    // Restart keys keep their startup value, so `current` reflects what runs
+    let mut config = object(serde_json::to_value(&loaded.config).map_err(|e| e.to_string())?);
+    for key in &change.restart_required {
+        if let Some(value) = before.get(key) {
+            config.insert(key.clone(), value.clone());
+        }
+    }
+    current.config = serde_json::from_value(Value::Object(config)).map_err(|e| e.to_string())?;
+    current.sources = loaded.sources;
+    current.file = loaded.file;
+    Ok(change)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn defaults() -> EffectiveConfig {
+        EffectiveConfig::defaults(PathBuf::from("config.toml"))
+    }
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let file = file_layer("max_parallel_tasks = 5\ntrace_dir = \"/tmp/traces\"").unwrap();
+        let env = env_layer(|name| match name {
+            "ANYCOWORK_MAX_PARALLEL_TASKS" => Some("8".to_string()),
+            "DATABASE_URL" => Some("sqlite://test.db".to_string()),
+            _ => None,
+        });
+        let agent = agent_layer(&json!({ "max_parallel_tasks": 2, "database_url": "ignored", "mode": "autopilot" }));
+
+        let effective = merge(
+            defaults(),
+            vec![(ConfigSource::File, file), (ConfigSource::Env, env), (ConfigSource::Agent, agent)],
+        )
+        .unwrap();
+        assert_eq!(effective.config.max_parallel_tasks, 2);
+        assert_eq!(effective.config.trace_dir, Some(PathBuf::from("/tmp/traces")));
+        assert_eq!(effective.config.database_url.as_deref(), Some("sqlite://test.db"));
+        assert_eq!(effective.sources["max_parallel_tasks"], ConfigSource::Agent);
+        assert_eq!(effective.sources["trace_dir"], ConfigSource::File);
+        assert_eq!(effective.sources["database_url"], ConfigSource::Env);
+        assert_eq!(effective.sources["prompts_dir"], ConfigSource::Default);
+    }
+
+    #[test]
+    fn test_bad_layers_are_named() {
+        let unknown = file_layer("max_tasks = 5").unwrap();
+        let err = merge(defaults(), vec![(ConfigSource::File, unknown)]).unwrap_err();
+        assert_eq!(err, "Unknown config key 'max_tasks' in config.toml");
+
+        let zero = env_layer(|name| (name == "ANYCOWORK_MAX_PARALLEL_TASKS").then(|| "0".to_string()));
+        let err = merge(defaults(), vec![(ConfigSource::Env, zero)]).unwrap_err();
+        assert!(err.starts_with("Invalid config in the environment"), "{}", err);
+
+        let wrong_type = file_layer("max_parallel_tasks = \"many\"").unwrap();
+        assert!(merge(defaults(), vec![(ConfigSource::File, wrong_type)]).is_err());
+        assert!(file_layer("max_parallel_tasks = ").is_err());
+    }
+}
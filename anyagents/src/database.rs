@@ -1,12 +1,90 @@
 use crate::models::NewAgent;
+use diesel::connection::SimpleConnection;
 use diesel::prelude::*;
-use diesel::r2d2::{self, ConnectionManager};
+use diesel::r2d2::{self, ConnectionManager, CustomizeConnection};
 use std::env;
+use std::sync::{Mutex, MutexGuard};
 
 pub type DbPool = r2d2::Pool<ConnectionManager<SqliteConnection>>;
 
+/// How long a connection waits for another's write lock before SQLITE_BUSY
+pub const BUSY_TIMEOUT_MS: u32 = 5_000;
+
+/// Set up every new pooled connection: unlock it when the database is
+/// encrypted, WAL so readers never block the writer (and the other way
+/// round), and a busy timeout so concurrent writers wait instead of failing
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionOptions {
+    /// SQLCipher key, see `encryption`
+    pub key: Option<String>,
+}
+
+impl CustomizeConnection<SqliteConnection, r2d2::Error> for ConnectionOptions {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), r2d2::Error> {
+        if let Some(key) = &self.key {
+            conn.batch_execute(&crate::encryption::key_pragma(key))
+                .map_err(r2d2::Error::QueryError)?;
+        }
+        conn.batch_execute(&format!(
+            "PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL; PRAGMA busy_timeout = {};",
+            BUSY_TIMEOUT_MS
+        ))
+        .map_err(r2d2::Error::QueryError)
+    }
+}
+
+fn build_pool(database_url: &str, key: Option<String>) -> Result<DbPool, String> {
+    r2d2::Pool::builder()
+        .connection_customizer(Box::new(ConnectionOptions { key }))
+        .build(ConnectionManager::<SqliteConnection>::new(database_url))
+        .map_err(|e| format!("Failed to create pool: {}", e))
+}
+
+/// Pool for the database file at `database_url`, encrypting or decrypting
+/// it first if that was asked for
+fn open_pool(database_url: &str) -> Result<DbPool, String> {
+    let key = crate::encryption::prepare_database(&crate::encryption::database_path(database_url))?;
+    build_pool(database_url, key)
+}
+
+/// Run `f` with a connection on the blocking thread pool, so async code
+/// (Tauri commands, agent loops) doesn't stall the runtime on SQLite
+pub async fn blocking<T, F>(pool: &DbPool, f: F) -> Result<T, String>
+where
+    F: FnOnce(&mut SqliteConnection) -> Result<T, String> + Send + 'static,
+    T: Send + 'static,
+{
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|e| e.to_string())?;
+        f(&mut conn)
+    })
+    .await
+    .map_err(|e| format!("Database task failed: {}", e))?
+}
+
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Hold off `serialized_write`s while the guard lives, for writers that
+/// can't run inside its transaction (restoring a backup)
+pub(crate) fn lock_writes() -> MutexGuard<'static, ()> {
+    WRITE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Run `f` as an immediate transaction, one at a time across the process.
+/// Writes to hot tables (messages) go through here, so concurrent agents
+/// queue in order instead of racing for SQLite's write lock.
+pub fn serialized_write<T, F>(pool: &DbPool, f: F) -> Result<T, String>
+where
+    F: FnOnce(&mut SqliteConnection) -> QueryResult<T>,
+{
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let _guard = lock_writes();
+    conn.immediate_transaction(f).map_err(|e| e.to_string())
+}
+
 pub fn establish_connection() -> DbPool {
-    let database_url = env::var("DATABASE_URL").unwrap_or_else(|_| {
+    let database_url = crate::config::current().database_url.unwrap_or_else(|| {
         let home = env::var("HOME").expect("HOME environment variable not set");
         let path = std::path::Path::new(&home).join(".anycowork");
         if !path.exists() {
@@ -16,10 +94,7 @@ pub fn establish_connection() -> DbPool {
         format!("sqlite://{}", db_path.to_string_lossy())
     });
 
-    let manager = ConnectionManager::<SqliteConnection>::new(database_url);
-    r2d2::Pool::builder()
-        .build(manager)
-        .expect("Failed to create pool.")
+    open_pool(&database_url).expect("Failed to create pool.")
 }
 
 struct DefaultCharacter {
@@ -182,6 +257,7 @@ pub fn ensure_default_characters(pool: &DbPool) {
                 scope_type: None,
                 workspace_path: None,
                 avatar: Some(character.avatar.to_string()),
+                agent_tools: None,
             };
 
             match diesel::insert_into(agents)
@@ -195,16 +271,27 @@ pub fn ensure_default_characters(pool: &DbPool) {
     }
 }
 
+/// Open a SQLite database at `database_url` and apply all pending migrations
+pub fn open_database(database_url: &str) -> Result<DbPool, String> {
+    use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+    const MIGRATIONS: EmbeddedMigrations = embed_migrations!("../src-tauri/migrations");
+
+    let pool = open_pool(database_url)?;
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    conn.run_pending_migrations(MIGRATIONS)
+        .map_err(|e| format!("Failed to run migrations: {}", e))?;
+
+    Ok(pool)
+}
+
 // Helper to setup an in-memory database for testing
 pub fn create_test_pool() -> DbPool {
     let temp_dir = std::env::temp_dir();
     let db_path = temp_dir.join(format!("anycowork_test_{}.db", uuid::Uuid::new_v4()));
     let db_str = db_path.to_string_lossy().to_string();
 
-    let manager = ConnectionManager::<SqliteConnection>::new(db_str);
-    let pool = r2d2::Pool::builder()
-        .build(manager)
-        .expect("Failed to create test pool.");
+    let pool = build_pool(&db_str, None).expect("Failed to create test pool.");
 
     // Run migrations using local path since we are in a test/dev environment here
     use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
@@ -238,6 +325,100 @@ mod tests {
         assert!(conn.is_ok());
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_wal_and_concurrent_writes() {
+        #[derive(QueryableByName)]
+        struct JournalMode {
+            #[diesel(sql_type = diesel::sql_types::Text)]
+            journal_mode: String,
+        }
+        let pool = create_test_pool();
+        let mode: JournalMode = blocking(&pool, |conn| {
+            diesel::sql_query("PRAGMA journal_mode").get_result(conn).map_err(|e| e.to_string())
+        })
+        .await
+        .unwrap();
+        assert_eq!(mode.journal_mode, "wal");
+
+        let writers = (0..8).map(|_| {
+            let pool = pool.clone();
+            tokio::task::spawn_blocking(move || {
+                for _ in 0..20 {
+                    serialized_write(&pool, |conn| {
+                        diesel::insert_into(crate::schema::settings::table)
+                            .values(crate::models::NewSetting {
+                                id: uuid::Uuid::new_v4().to_string(),
+                                key: uuid::Uuid::new_v4().to_string(),
+                                value: None,
+                            })
+                            .execute(conn)
+                    })
+                    .unwrap();
+                }
+            })
+        });
+        for writer in writers.collect::<Vec<_>>() {
+            writer.await.unwrap();
+        }
+        let mut conn = pool.get().unwrap();
+        let count: i64 = crate::schema::settings::table.count().get_result(&mut conn).unwrap();
+        assert!(count >= 160);
+    }
+
+    /// A table or column added to schema.rs needs a Postgres migration too
+    /// (see `shared_db`)
+    #[test]
+    fn test_postgres_migrations_cover_schema() {
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let mut migrations: Vec<_> = std::fs::read_dir(root.join("../src-tauri/migrations-postgres"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path().join("up.sql"))
+            .collect();
+        migrations.sort();
+        let sql = migrations
+            .iter()
+            .map(|path| std::fs::read_to_string(path).unwrap().to_lowercase())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let mut postgres = std::collections::HashSet::new();
+        let mut table: Option<String> = None;
+        for line in sql.lines().map(str::trim) {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if let Some(rest) = line.strip_prefix("create table ") {
+                table = rest.trim_start_matches("if not exists ").split_whitespace().next().map(String::from);
+            } else if line.starts_with(')') {
+                table = None;
+            } else if let Some(table) = &table {
+                if let Some(column) = words.first().filter(|w| !matches!(**w, "foreign" | "primary" | "unique" | "check")) {
+                    postgres.insert((table.clone(), column.to_string()));
+                }
+            } else if words.len() >= 6 && words[..2] == ["alter", "table"] && words[3..5] == ["add", "column"] {
+                postgres.insert((words[2].to_string(), words[5].to_string()));
+            }
+        }
+
+        let mut missing = Vec::new();
+        let mut table = "";
+        let mut sql_name = None;
+        for line in include_str!("schema.rs").lines().map(str::trim) {
+            if line.starts_with("diesel::") {
+                continue;
+            }
+            if line.ends_with('{') && line.contains(" (") {
+                table = line.split_whitespace().next().unwrap();
+            } else if line.starts_with("#[sql_name") {
+                sql_name = line.split('"').nth(1);
+            } else if line.contains(" -> ") {
+                let column = sql_name.take().unwrap_or_else(|| line.split_whitespace().next().unwrap());
+                if !postgres.contains(&(table.to_string(), column.to_string())) {
+                    missing.push(format!("{}.{}", table, column));
+                }
+            }
+        }
+        assert!(missing.is_empty(), "Missing from migrations-postgres: {}", missing.join(", "));
+    }
+
     #[test]
     fn test_ensure_default_characters() {
         let pool = setup_test_db();
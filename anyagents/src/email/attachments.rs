@@ -0,0 +1,344 @@
+//! Files attached to mail messages
+//!
+//! Attachments are copied into `<root>/<message id>/` when they are added;
+//! `storage_dir` is the root the app uses. Replies written by the background
+//! processor attach workspace files with `[attach: relative/path]` lines.
+
+use crate::database::DbPool;
+use crate::models::{MailAttachment, NewMailAttachment};
+use crate::schema::mail_attachments;
+use diesel::prelude::*;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Largest single attachment
+pub const MAX_ATTACHMENT_BYTES: u64 = 25 * 1024 * 1024;
+/// Most attachments on one message
+pub const MAX_ATTACHMENTS_PER_MESSAGE: usize = 10;
+/// Text attachments up to this many characters are quoted to the model
+const TEXT_PREVIEW_CHARS: usize = 4000;
+
+/// Where the app keeps attachment copies
+pub fn storage_dir() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    Path::new(&home).join(".anycowork").join("mail_attachments")
+}
+
+pub fn mime_type_for(file_name: &str) -> &'static str {
+    let ext = Path::new(file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "txt" | "log" => "text/plain",
+        "md" => "text/markdown",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "json" => "application/json",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Keep only the final path component and characters safe on every platform
+fn safe_file_name(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    let cleaned: String = base
+        .chars()
+        .map(|c| if c.is_control() || "<>:\"|?*".contains(c) { '_' } else { c })
+        .collect();
+    let cleaned = cleaned.trim().trim_start_matches('.').to_string();
+    if cleaned.is_empty() {
+        "attachment".to_string()
+    } else {
+        cleaned
+    }
+}
+
+/// A free path for `file_name` inside `dir`
+fn unique_path(dir: &Path, file_name: &str) -> PathBuf {
+    let candidate = dir.join(file_name);
+    if !candidate.exists() {
+        return candidate;
+    }
+    let path = Path::new(file_name);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("attachment");
+    let ext = path.extension().and_then(|e| e.to_str());
+    (2..)
+        .map(|n| match ext {
+            Some(ext) => dir.join(format!("{}-{}.{}", stem, n, ext)),
+            None => dir.join(format!("{}-{}", stem, n)),
+        })
+        .find(|p| !p.exists())
+        .expect("some suffix is free")
+}
+
+fn check_room(pool: &DbPool, message_id: &str) -> Result<(), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let count: i64 = mail_attachments::table
+        .filter(mail_attachments::message_id.eq(message_id))
+        .count()
+        .get_result(&mut conn)
+        .map_err(|e| e.to_string())?;
+    if count as usize >= MAX_ATTACHMENTS_PER_MESSAGE {
+        return Err(format!(
+            "A message can have at most {} attachments",
+            MAX_ATTACHMENTS_PER_MESSAGE
+        ));
+    }
+    Ok(())
+}
+
+/// Attach `bytes` to `message_id` under `file_name`
+pub fn attach_bytes(
+    pool: &DbPool,
+    root: &Path,
+    message_id: &str,
+    file_name: &str,
+    mime_type: Option<&str>,
+    bytes: &[u8],
+) -> Result<MailAttachment, String> {
+    if bytes.len() as u64 > MAX_ATTACHMENT_BYTES {
+        return Err(format!(
+            "'{}' is {} bytes; attachments are limited to {} bytes",
+            file_name,
+            bytes.len(),
+            MAX_ATTACHMENT_BYTES
+        ));
+    }
+    check_room(pool, message_id)?;
+
+    let file_name = safe_file_name(file_name);
+    let dir = root.join(message_id);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create attachment directory: {}", e))?;
+    let path = unique_path(&dir, &file_name);
+    std::fs::write(&path, bytes).map_err(|e| format!("Failed to store attachment: {}", e))?;
+
+    let attachment = NewMailAttachment {
+        id: uuid::Uuid::new_v4().to_string(),
+        message_id: message_id.to_string(),
+        mime_type: mime_type.unwrap_or_else(|| mime_type_for(&file_name)).to_string(),
+        file_name,
+        file_size: bytes.len() as i32,
+        storage_path: path.to_string_lossy().to_string(),
+        created_at: chrono::Utc::now().naive_utc(),
+    };
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    if let Err(e) = diesel::insert_into(mail_attachments::table)
+        .values(&attachment)
+        .execute(&mut conn)
+    {
+        let _ = std::fs::remove_file(&path);
+        return Err(e.to_string());
+    }
+    mail_attachments::table
+        .find(&attachment.id)
+        .first::<MailAttachment>(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+/// Attach a copy of the file at `source`
+pub fn attach_file(pool: &DbPool, root: &Path, message_id: &str, source: &Path) -> Result<MailAttachment, String> {
+    let size = std::fs::metadata(source)
+        .map_err(|e| format!("Cannot attach {}: {}", source.display(), e))?
+        .len();
+    if size > MAX_ATTACHMENT_BYTES {
+        return Err(format!(
+            "'{}' is {} bytes; attachments are limited to {} bytes",
+            source.display(),
+            size,
+            MAX_ATTACHMENT_BYTES
+        ));
+    }
+    let bytes = std::fs::read(source).map_err(|e| format!("Cannot attach {}: {}", source.display(), e))?;
+    let name = source
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "attachment".to_string());
+    attach_bytes(pool, root, message_id, &name, None, &bytes)
+}
+
+/// `relative` inside `workspace`, refusing anything that escapes it
+pub fn workspace_file(workspace: &Path, relative: &str) -> Result<PathBuf, String> {
+    let path = Path::new(relative.trim());
+    if path.is_absolute() || path.components().any(|c| matches!(c, Component::ParentDir)) {
+        return Err(format!(
+            "Attachment paths must be relative to the workspace and cannot contain '..': {}",
+            relative
+        ));
+    }
+    let full = workspace.join(path);
+    if !full.is_file() {
+        return Err(format!("No such file in the workspace: {}", relative));
+    }
+    Ok(full)
+}
+
+/// Attachments of each message in `message_ids`
+pub fn for_messages(pool: &DbPool, message_ids: &[String]) -> Result<HashMap<String, Vec<MailAttachment>>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let rows: Vec<MailAttachment> = mail_attachments::table
+        .filter(mail_attachments::message_id.eq_any(message_ids))
+        .order(mail_attachments::created_at.asc())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())?;
+    let mut by_message: HashMap<String, Vec<MailAttachment>> = HashMap::new();
+    for row in rows {
+        by_message.entry(row.message_id.clone()).or_default().push(row);
+    }
+    Ok(by_message)
+}
+
+/// Attachments as the model sees them: a list, with short text files quoted
+pub fn describe_for_prompt(attachments: &[MailAttachment]) -> String {
+    let mut out = String::new();
+    for attachment in attachments {
+        out.push_str(&format!(
+            "[Attachment: {} ({}, {} bytes)]\n",
+            attachment.file_name, attachment.mime_type, attachment.file_size
+        ));
+        let is_text = attachment.mime_type.starts_with("text/") || attachment.mime_type == "application/json";
+        if is_text {
+            if let Ok(text) = std::fs::read_to_string(&attachment.storage_path) {
+                if text.chars().count() <= TEXT_PREVIEW_CHARS {
+                    out.push_str(&text);
+                    if !text.ends_with('\n') {
+                        out.push('\n');
+                    }
+                    out.push_str("[End of attachment]\n");
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Split `[attach: path]` lines out of a generated reply
+pub fn extract_attach_markers(reply: &str) -> (String, Vec<String>) {
+    let mut paths = Vec::new();
+    let mut kept = Vec::new();
+    for line in reply.lines() {
+        let trimmed = line.trim();
+        let marker = trimmed
+            .strip_prefix("[attach:")
+            .and_then(|rest| rest.strip_suffix(']'))
+            .map(str::trim)
+            .filter(|p| !p.is_empty());
+        match marker {
+            Some(path) => paths.push(path.to_string()),
+            None => kept.push(line),
+        }
+    }
+    (kept.join("\n").trim().to_string(), paths)
+}
+
+/// Instructions for reply prompts on how to attach files
+pub const ATTACH_INSTRUCTIONS: &str = "To attach a file from your workspace, add a line of the form [attach: relative/path/to/file] for each file.";
+
+/// Attach the workspace files named in a reply; problems are logged, not fatal
+pub fn attach_workspace_files(pool: &DbPool, root: &Path, message_id: &str, workspace: &Path, paths: &[String]) {
+    for relative in paths {
+        let result = workspace_file(workspace, relative)
+            .and_then(|path| attach_file(pool, root, message_id, &path));
+        if let Err(e) = result {
+            log::warn!("Could not attach '{}' to mail {}: {}", relative, message_id, e);
+        }
+    }
+}
+
+/// The directory an agent works in, as the agent loop resolves it
+pub fn agent_workspace(agent: &crate::models::Agent) -> PathBuf {
+    match &agent.workspace_path {
+        Some(path) => PathBuf::from(path),
+        None => std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+    }
+}
+
+/// Delete the stored copies for `message_id`
+pub fn remove_files(root: &Path, message_id: &str) {
+    let _ = std::fs::remove_dir_all(root.join(message_id));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{mail_messages, mail_threads};
+
+    /// A pool holding the mail message `msg-1` that attachments hang off
+    fn pool_with_message() -> DbPool {
+        let pool = crate::database::create_test_pool();
+        let mut conn = pool.get().unwrap();
+        diesel::insert_into(mail_threads::table)
+            .values((mail_threads::id.eq("thread-1"), mail_threads::subject.eq("Notes")))
+            .execute(&mut conn)
+            .unwrap();
+        diesel::insert_into(mail_messages::table)
+            .values((
+                mail_messages::id.eq("msg-1"),
+                mail_messages::thread_id.eq("thread-1"),
+                mail_messages::sender_type.eq("user"),
+                mail_messages::recipient_type.eq("agent"),
+                mail_messages::content.eq("See attached"),
+            ))
+            .execute(&mut conn)
+            .unwrap();
+        pool
+    }
+
+    #[test]
+    fn test_attach_file_copies_and_lists() {
+        let pool = pool_with_message();
+        let root = tempfile::tempdir().unwrap();
+        let workspace = tempfile::tempdir().unwrap();
+        std::fs::write(workspace.path().join("notes.md"), "# Notes\nShip it.").unwrap();
+
+        let source = workspace_file(workspace.path(), "notes.md").unwrap();
+        let first = attach_file(&pool, root.path(), "msg-1", &source).unwrap();
+        let second = attach_file(&pool, root.path(), "msg-1", &source).unwrap();
+        assert_eq!(first.mime_type, "text/markdown");
+        assert_eq!(first.file_size, 16);
+        assert_ne!(first.storage_path, second.storage_path);
+
+        // The copy is independent of the original
+        std::fs::write(&source, "changed").unwrap();
+        assert_eq!(std::fs::read_to_string(&first.storage_path).unwrap(), "# Notes\nShip it.");
+
+        let listed = for_messages(&pool, &["msg-1".to_string(), "msg-2".to_string()]).unwrap();
+        assert_eq!(listed["msg-1"].len(), 2);
+        assert!(!listed.contains_key("msg-2"));
+        assert!(describe_for_prompt(&listed["msg-1"]).contains("Ship it."));
+    }
+
+    #[test]
+    fn test_workspace_file_rejects_escapes() {
+        let workspace = tempfile::tempdir().unwrap();
+        assert!(workspace_file(workspace.path(), "../etc/passwd").is_err());
+        assert!(workspace_file(workspace.path(), "/etc/passwd").is_err());
+        assert!(workspace_file(workspace.path(), "missing.txt").is_err());
+    }
+
+    #[test]
+    fn test_attach_bytes_sanitizes_names() {
+        let pool = pool_with_message();
+        let root = tempfile::tempdir().unwrap();
+        let attachment = attach_bytes(&pool, root.path(), "msg-1", "../../evil?.sh", None, b"x").unwrap();
+        assert_eq!(attachment.file_name, "evil_.sh");
+        assert!(Path::new(&attachment.storage_path).starts_with(root.path().join("msg-1")));
+    }
+
+    #[test]
+    fn test_extract_attach_markers() {
+        let (text, paths) = extract_attach_markers("Here you go.\n[attach: reports/q3.pdf]\n[attach: ]\nThanks");
+        assert_eq!(text, "Here you go.\n[attach: ]\nThanks");
+        assert_eq!(paths, vec!["reports/q3.pdf"]);
+    }
+}
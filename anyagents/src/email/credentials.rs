@@ -0,0 +1,25 @@
+//! Mailbox passwords, kept in the OS keyring under `anycowork-email` with
+//! one entry per account id
+
+use crate::secrets::{KeyringStore, SecretStore};
+
+const KEYRING: KeyringStore = KeyringStore::new("anycowork-email");
+
+pub fn get_password(account_id: &str) -> Result<String, String> {
+    KEYRING
+        .get(account_id)
+        .map_err(|e| format!("Failed to read email password: {}", e))?
+        .ok_or_else(|| "No password stored for this email account".to_string())
+}
+
+pub fn set_password(account_id: &str, password: &str) -> Result<(), String> {
+    KEYRING
+        .set(account_id, password)
+        .map_err(|e| format!("Failed to store email password: {}", e))
+}
+
+pub fn delete_password(account_id: &str) -> Result<(), String> {
+    KEYRING
+        .delete(account_id)
+        .map_err(|e| format!("Failed to delete email password: {}", e))
+}
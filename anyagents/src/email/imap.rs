@@ -0,0 +1,131 @@
+//! Inbound mail over IMAP (implicit TLS). New mail is found by UID, so a
+//! message is fetched once no matter how often the folder is checked.
+
+use crate::models::EmailAccount;
+use futures::TryStreamExt;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_native_tls::TlsStream;
+
+pub type ImapSession = async_imap::Session<TlsStream<TcpStream>>;
+
+/// A message fetched from the watched folder
+pub struct FetchedMail {
+    pub uid: u32,
+    pub raw: Vec<u8>,
+}
+
+/// Log in and select the account's folder
+pub async fn connect(account: &EmailAccount, password: &str) -> Result<ImapSession, String> {
+    let tcp = TcpStream::connect((account.imap_host.as_str(), account.imap_port as u16))
+        .await
+        .map_err(|e| format!("IMAP connection failed: {}", e))?;
+    let connector = native_tls::TlsConnector::new().map_err(|e| format!("TLS setup failed: {}", e))?;
+    let stream = tokio_native_tls::TlsConnector::from(connector)
+        .connect(&account.imap_host, tcp)
+        .await
+        .map_err(|e| format!("IMAP TLS handshake failed: {}", e))?;
+
+    let mut session = async_imap::Client::new(stream)
+        .login(&account.username, password)
+        .await
+        .map_err(|(e, _)| format!("IMAP login failed: {}", e))?;
+    session
+        .select(&account.imap_folder)
+        .await
+        .map_err(|e| format!("Failed to open folder '{}': {}", account.imap_folder, e))?;
+    Ok(session)
+}
+
+/// Highest UID in the folder, or 0 when it is empty
+pub async fn latest_uid(session: &mut ImapSession) -> Result<u32, String> {
+    let uids = session
+        .uid_search("ALL")
+        .await
+        .map_err(|e| format!("IMAP search failed: {}", e))?;
+    Ok(uids.into_iter().max().unwrap_or(0))
+}
+
+/// Messages with a UID above `last_uid`, oldest first
+pub async fn fetch_since(session: &mut ImapSession, last_uid: u32) -> Result<Vec<FetchedMail>, String> {
+    let uids = session
+        .uid_search(format!("UID {}:*", last_uid + 1))
+        .await
+        .map_err(|e| format!("IMAP search failed: {}", e))?;
+    // `n:*` always matches the newest message, even when its UID is below n
+    let uids = new_uids(uids, last_uid);
+    if uids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let set = uids.iter().map(|u| u.to_string()).collect::<Vec<_>>().join(",");
+    let fetches: Vec<async_imap::types::Fetch> = session
+        .uid_fetch(set, "(UID BODY.PEEK[])")
+        .await
+        .map_err(|e| format!("IMAP fetch failed: {}", e))?
+        .try_collect()
+        .await
+        .map_err(|e| format!("IMAP fetch failed: {}", e))?;
+
+    let mut mail: Vec<FetchedMail> = fetches
+        .iter()
+        .filter_map(|fetch| {
+            Some(FetchedMail {
+                uid: fetch.uid?,
+                raw: fetch.body()?.to_vec(),
+            })
+        })
+        .collect();
+    mail.sort_by_key(|m| m.uid);
+    Ok(mail)
+}
+
+fn new_uids(uids: impl IntoIterator<Item = u32>, last_uid: u32) -> Vec<u32> {
+    let mut uids: Vec<u32> = uids.into_iter().filter(|uid| *uid > last_uid).collect();
+    uids.sort_unstable();
+    uids
+}
+
+pub async fn supports_idle(session: &mut ImapSession) -> bool {
+    session
+        .capabilities()
+        .await
+        .map(|caps| caps.has_str("IDLE"))
+        .unwrap_or(false)
+}
+
+/// Block until the server reports a change or `timeout` passes. Servers
+/// without IDLE are simply polled.
+pub async fn wait_for_mail(session: ImapSession, timeout: Duration, idle: bool) -> Result<ImapSession, String> {
+    if !idle {
+        tokio::time::sleep(timeout).await;
+        return Ok(session);
+    }
+    let mut handle = session.idle();
+    handle.init().await.map_err(|e| format!("IMAP IDLE failed: {}", e))?;
+    // Dropping `stop` would end the IDLE right away
+    let (wait, stop) = handle.wait_with_timeout(timeout);
+    let result = wait.await;
+    drop(stop);
+    result.map_err(|e| format!("IMAP IDLE failed: {}", e))?;
+    handle.done().await.map_err(|e| format!("IMAP IDLE failed: {}", e))
+}
+
+/// Log in and open the folder, then log out
+pub async fn test_connection(account: &EmailAccount, password: &str) -> Result<(), String> {
+    let mut session = connect(account, password).await?;
+    let _ = session.logout().await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_uids_skips_seen() {
+        assert_eq!(new_uids([7, 3, 9], 5), vec![7, 9]);
+        // The server answers `6:*` with the newest message even if it is older
+        assert!(new_uids([5], 5).is_empty());
+    }
+}
@@ -0,0 +1,228 @@
+//! Turning raw RFC 5322 mail into something an agent can read
+
+use mail_parser::{HeaderValue, MessageParser, MimeHeaders};
+
+/// An inbound email, reduced to what the mailbox stores
+#[derive(Debug, Clone, PartialEq)]
+pub struct InboundEmail {
+    /// Message-ID without angle brackets
+    pub message_id: Option<String>,
+    /// In-Reply-To followed by References, most specific first
+    pub references: Vec<String>,
+    pub from_address: String,
+    pub from_name: Option<String>,
+    pub subject: String,
+    /// Plain-text body with quoted history removed
+    pub body: String,
+    /// Vacation responders, bounces and mailing lists; never answered
+    pub automated: bool,
+    pub attachments: Vec<InboundAttachment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InboundAttachment {
+    pub file_name: String,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+pub fn parse_email(raw: &[u8]) -> Option<InboundEmail> {
+    let message = MessageParser::default().parse(raw)?;
+    let from = message.from()?.first()?;
+    let from_address = from.address()?.to_string();
+
+    let mut references = header_ids(message.in_reply_to());
+    for id in header_ids(message.references()).into_iter().rev() {
+        if !references.contains(&id) {
+            references.push(id);
+        }
+    }
+
+    let body = message
+        .body_text(0)
+        .map(|text| strip_quoted_reply(&text))
+        .unwrap_or_default();
+
+    let attachments = message
+        .attachments()
+        .enumerate()
+        .map(|(i, part)| InboundAttachment {
+            file_name: part
+                .attachment_name()
+                .map(|n| n.to_string())
+                .unwrap_or_else(|| format!("attachment-{}", i + 1)),
+            mime_type: part
+                .content_type()
+                .map(|ct| match ct.subtype() {
+                    Some(sub) => format!("{}/{}", ct.ctype(), sub),
+                    None => ct.ctype().to_string(),
+                })
+                .unwrap_or_else(|| "application/octet-stream".to_string())
+                .to_lowercase(),
+            data: part.contents().to_vec(),
+        })
+        .collect();
+
+    Some(InboundEmail {
+        message_id: message.message_id().map(|id| id.to_string()),
+        references,
+        from_address,
+        from_name: from.name().map(|n| n.to_string()),
+        subject: message.subject().unwrap_or("(no subject)").to_string(),
+        body,
+        automated: is_automated(raw),
+        attachments,
+    })
+}
+
+fn header_ids(value: &HeaderValue) -> Vec<String> {
+    match value {
+        HeaderValue::Text(id) => vec![id.to_string()],
+        HeaderValue::TextList(ids) => ids.iter().map(|id| id.to_string()).collect(),
+        _ => vec![],
+    }
+}
+
+/// Value of the first `name` header, with folded lines joined
+pub fn raw_header(raw: &[u8], name: &str) -> Option<String> {
+    let text = String::from_utf8_lossy(raw);
+    let mut value: Option<String> = None;
+    for line in text.lines() {
+        if line.is_empty() {
+            break;
+        }
+        if let Some(current) = value.as_mut() {
+            if line.starts_with(' ') || line.starts_with('\t') {
+                current.push(' ');
+                current.push_str(line.trim());
+                continue;
+            }
+            break;
+        }
+        if let Some((key, rest)) = line.split_once(':') {
+            if key.trim().eq_ignore_ascii_case(name) {
+                value = Some(rest.trim().to_string());
+            }
+        }
+    }
+    value
+}
+
+/// RFC 3834 auto-replies and list traffic, which must not get an agent reply
+fn is_automated(raw: &[u8]) -> bool {
+    let auto_submitted = raw_header(raw, "Auto-Submitted")
+        .map(|v| !v.eq_ignore_ascii_case("no"))
+        .unwrap_or(false);
+    let bulk = raw_header(raw, "Precedence")
+        .map(|v| matches!(v.to_lowercase().as_str(), "bulk" | "list" | "junk"))
+        .unwrap_or(false);
+    auto_submitted || bulk || raw_header(raw, "List-Id").is_some()
+}
+
+/// Drop the quoted conversation most clients append below a reply
+pub fn strip_quoted_reply(body: &str) -> String {
+    let mut kept = Vec::new();
+    for line in body.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('>') {
+            continue;
+        }
+        if (trimmed.starts_with("On ") && trimmed.ends_with("wrote:"))
+            || trimmed.starts_with("-----Original Message-----")
+        {
+            break;
+        }
+        kept.push(line);
+    }
+    kept.join("\n").trim().to_string()
+}
+
+/// Subject for a message continuing a thread
+pub fn reply_subject(subject: &str) -> String {
+    if subject.get(..3).is_some_and(|prefix| prefix.eq_ignore_ascii_case("re:")) {
+        subject.to_string()
+    } else {
+        format!("Re: {}", subject)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPLY: &str = "From: Dana Smith <dana@example.com>\r\n\
+        To: agent@example.org\r\n\
+        Subject: Re: Quarterly numbers\r\n\
+        Message-ID: <reply-2@example.com>\r\n\
+        In-Reply-To: <orig-1@example.org>\r\n\
+        References: <root-0@example.org>\r\n\
+        \x20<orig-1@example.org>\r\n\
+        Content-Type: text/plain; charset=utf-8\r\n\
+        \r\n\
+        Looks good, ship it.\r\n\
+        \r\n\
+        On Tue, Mar 3, 2026 at 9:00 AM Agent <agent@example.org> wrote:\r\n\
+        > Here are the numbers.\r\n";
+
+    #[test]
+    fn test_parse_reply() {
+        let email = parse_email(REPLY.as_bytes()).unwrap();
+        assert_eq!(email.from_address, "dana@example.com");
+        assert_eq!(email.from_name.as_deref(), Some("Dana Smith"));
+        assert_eq!(email.subject, "Re: Quarterly numbers");
+        assert_eq!(email.message_id.as_deref(), Some("reply-2@example.com"));
+        assert_eq!(email.references, vec!["orig-1@example.org", "root-0@example.org"]);
+        assert_eq!(email.body, "Looks good, ship it.");
+        assert!(!email.automated);
+        assert!(email.attachments.is_empty());
+    }
+
+    #[test]
+    fn test_parse_attachments() {
+        let raw = "From: dana@example.com\r\n\
+            Subject: Report\r\n\
+            MIME-Version: 1.0\r\n\
+            Content-Type: multipart/mixed; boundary=\"b1\"\r\n\
+            \r\n\
+            --b1\r\n\
+            Content-Type: text/plain\r\n\
+            \r\n\
+            See attached.\r\n\
+            --b1\r\n\
+            Content-Type: text/csv\r\n\
+            Content-Disposition: attachment; filename=\"q3.csv\"\r\n\
+            \r\n\
+            a,b\r\n\
+            --b1--\r\n";
+        let email = parse_email(raw.as_bytes()).unwrap();
+        assert_eq!(email.body, "See attached.");
+        assert_eq!(email.attachments.len(), 1);
+        assert_eq!(email.attachments[0].file_name, "q3.csv");
+        assert_eq!(email.attachments[0].mime_type, "text/csv");
+        assert_eq!(email.attachments[0].data, b"a,b");
+    }
+
+    #[test]
+    fn test_automated_mail() {
+        let raw = format!("Auto-Submitted: auto-replied\r\n{}", REPLY);
+        assert!(parse_email(raw.as_bytes()).unwrap().automated);
+        let raw = format!("Auto-Submitted: no\r\n{}", REPLY);
+        assert!(!parse_email(raw.as_bytes()).unwrap().automated);
+    }
+
+    #[test]
+    fn test_raw_header_unfolds() {
+        assert_eq!(
+            raw_header(REPLY.as_bytes(), "references").as_deref(),
+            Some("<root-0@example.org> <orig-1@example.org>")
+        );
+        // Body lines are not headers
+        assert!(raw_header(REPLY.as_bytes(), "On Tue, Mar 3, 2026 at 9").is_none());
+    }
+
+    #[test]
+    fn test_reply_subject() {
+        assert_eq!(reply_subject("Numbers"), "Re: Numbers");
+        assert_eq!(reply_subject("RE: Numbers"), "RE: Numbers");
+    }
+}
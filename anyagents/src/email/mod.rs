@@ -0,0 +1,431 @@
+//! Email bridge: connects agent mailboxes to real inboxes
+//!
+//! An agent can own one `EmailAccount`. Mail arriving in its IMAP folder is
+//! injected into `mail_threads` as an `external` message, threaded through
+//! In-Reply-To/References against `mail_messages.external_message_id`.
+//! Messages addressed to an `external` recipient go out over SMTP when the
+//! account has `deliver_outbound` on; otherwise they stay in the thread as
+//! drafts for the user to see. Attachments travel both ways (see
+//! `attachments`); cc and bcc recipients are in `recipients`. Finding
+//! threads by folder, text, sender, date or label is in `search`.
+
+pub mod attachments;
+pub mod credentials;
+pub mod imap;
+pub mod message;
+pub mod recipients;
+pub mod search;
+pub mod smtp;
+
+pub use message::{parse_email, InboundEmail};
+
+use crate::database::DbPool;
+use crate::models::{EmailAccount, MailMessage, MailThread, NewMailMessage, NewMailThread};
+use crate::schema::{email_accounts, mail_messages, mail_threads};
+use diesel::prelude::*;
+
+/// Shortest allowed polling interval for servers without IDLE
+pub const MIN_POLL_INTERVAL_SECS: i32 = 15;
+
+/// Loose check used to tell addresses apart from agent names
+pub fn is_email_address(value: &str) -> bool {
+    match value.trim().split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !value.contains(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+pub fn validate_account(
+    email_address: &str,
+    smtp_host: &str,
+    smtp_port: i32,
+    imap_host: &str,
+    imap_port: i32,
+    poll_interval_seconds: i32,
+) -> Result<(), String> {
+    if !is_email_address(email_address) {
+        return Err(format!("'{}' is not an email address", email_address));
+    }
+    if smtp_host.trim().is_empty() || imap_host.trim().is_empty() {
+        return Err("SMTP and IMAP hosts are required".to_string());
+    }
+    for port in [smtp_port, imap_port] {
+        if !(1..=65535).contains(&port) {
+            return Err(format!("Invalid port: {}", port));
+        }
+    }
+    if poll_interval_seconds < MIN_POLL_INTERVAL_SECS {
+        return Err(format!(
+            "Polling interval must be at least {} seconds",
+            MIN_POLL_INTERVAL_SECS
+        ));
+    }
+    Ok(())
+}
+
+pub fn account_for_agent(pool: &DbPool, agent_id: &str) -> Result<Option<EmailAccount>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    email_accounts::table
+        .filter(email_accounts::agent_id.eq(agent_id))
+        .first::<EmailAccount>(&mut conn)
+        .optional()
+        .map_err(|e| e.to_string())
+}
+
+/// A fresh Message-ID on the account's domain, without angle brackets
+pub fn new_message_id(email_address: &str) -> String {
+    let domain = email_address.rsplit_once('@').map(|(_, d)| d).unwrap_or("localhost");
+    format!("{}@{}", uuid::Uuid::new_v4(), domain)
+}
+
+/// An inbound email stored in an agent's mailbox
+pub struct InjectedMail {
+    pub thread_id: String,
+    pub message_id: String,
+    pub subject: String,
+}
+
+/// Store `email` in the mailbox of the account's agent, in the thread it
+/// replies to if there is one; its attachments are kept under
+/// `attachments_root`. Returns None for mail that was already stored.
+pub fn inject_inbound(
+    pool: &DbPool,
+    attachments_root: &std::path::Path,
+    account: &EmailAccount,
+    email: &InboundEmail,
+) -> Result<Option<InjectedMail>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+
+    if let Some(message_id) = &email.message_id {
+        let seen: i64 = mail_messages::table
+            .filter(mail_messages::external_message_id.eq(message_id))
+            .count()
+            .get_result(&mut conn)
+            .map_err(|e| e.to_string())?;
+        if seen > 0 {
+            return Ok(None);
+        }
+    }
+
+    let now = chrono::Utc::now().naive_utc();
+    let parent: Option<(String, String)> = if email.references.is_empty() {
+        None
+    } else {
+        mail_messages::table
+            .inner_join(mail_threads::table)
+            .filter(mail_messages::external_message_id.eq_any(&email.references))
+            .select((mail_threads::id, mail_threads::subject))
+            .first(&mut conn)
+            .optional()
+            .map_err(|e| e.to_string())?
+    };
+
+    let (thread_id, subject) = match parent {
+        Some((thread_id, subject)) => {
+            diesel::update(mail_threads::table.filter(mail_threads::id.eq(&thread_id)))
+                .set((mail_threads::updated_at.eq(now), mail_threads::is_read.eq(0)))
+                .execute(&mut conn)
+                .map_err(|e| e.to_string())?;
+            (thread_id, subject)
+        }
+        None => {
+            let thread = NewMailThread {
+                id: uuid::Uuid::new_v4().to_string(),
+                subject: email.subject.clone(),
+                is_read: 0,
+                is_archived: 0,
+                created_at: now,
+                updated_at: now,
+            };
+            diesel::insert_into(mail_threads::table)
+                .values(&thread)
+                .execute(&mut conn)
+                .map_err(|e| e.to_string())?;
+            (thread.id, thread.subject)
+        }
+    };
+
+    let message = NewMailMessage {
+        id: uuid::Uuid::new_v4().to_string(),
+        thread_id: thread_id.clone(),
+        sender_type: "external".to_string(),
+        sender_agent_id: None,
+        recipient_type: "agent".to_string(),
+        recipient_agent_id: Some(account.agent_id.clone()),
+        content: email.body.clone(),
+        created_at: now,
+        external_address: Some(email.from_address.clone()),
+        external_message_id: email.message_id.clone(),
+    };
+    diesel::insert_into(mail_messages::table)
+        .values(&message)
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    drop(conn);
+
+    for file in &email.attachments {
+        if let Err(e) = attachments::attach_bytes(
+            pool,
+            attachments_root,
+            &message.id,
+            &file.file_name,
+            Some(&file.mime_type),
+            &file.data,
+        ) {
+            log::warn!("Dropped attachment '{}' from {}: {}", file.file_name, email.from_address, e);
+        }
+    }
+
+    Ok(Some(InjectedMail {
+        thread_id,
+        message_id: message.id,
+        subject,
+    }))
+}
+
+/// Send a stored message to its `external` recipient from the sending agent's
+/// account. Returns false when the account keeps outbound mail as drafts.
+pub async fn deliver(pool: &DbPool, mail_message_id: &str) -> Result<bool, String> {
+    let (message, account, thread, references) = {
+        let mut conn = pool.get().map_err(|e| e.to_string())?;
+        let message = mail_messages::table
+            .find(mail_message_id)
+            .first::<MailMessage>(&mut conn)
+            .map_err(|e| format!("Message not found: {}", e))?;
+        let sender = message
+            .sender_agent_id
+            .clone()
+            .ok_or("Only agents with an email account can send real email")?;
+        let account = email_accounts::table
+            .filter(email_accounts::agent_id.eq(&sender))
+            .first::<EmailAccount>(&mut conn)
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or("The sending agent has no email account")?;
+        let thread = mail_threads::table
+            .find(&message.thread_id)
+            .first::<MailThread>(&mut conn)
+            .map_err(|e| e.to_string())?;
+        let references: Vec<String> = mail_messages::table
+            .filter(mail_messages::thread_id.eq(&message.thread_id))
+            .filter(mail_messages::id.ne(&message.id))
+            .filter(mail_messages::external_message_id.is_not_null())
+            .order(mail_messages::created_at.asc())
+            .select(mail_messages::external_message_id.assume_not_null())
+            .load(&mut conn)
+            .map_err(|e| e.to_string())?;
+        (message, account, thread, references)
+    };
+
+    let (to, cc, bcc) = external_recipients(pool, &message)?;
+    if to.is_empty() && cc.is_empty() && bcc.is_empty() {
+        return Err("Message is not addressed to an email recipient".to_string());
+    }
+    if account.deliver_outbound == 0 {
+        return Ok(false);
+    }
+
+    let files = attachments::for_messages(pool, std::slice::from_ref(&message.id))?
+        .remove(&message.id)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|a| {
+            Ok(smtp::OutboundAttachment {
+                data: std::fs::read(&a.storage_path)
+                    .map_err(|e| format!("Attachment '{}' is missing: {}", a.file_name, e))?,
+                file_name: a.file_name,
+                mime_type: a.mime_type,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let password = credentials::get_password(&account.id)?;
+    let message_id = new_message_id(&account.email_address);
+    let email = smtp::OutboundEmail {
+        // SMTP needs a To; a message with only bcc'd addresses goes to itself
+        to: if to.is_empty() { vec![account.email_address.clone()] } else { to },
+        cc,
+        bcc,
+        subject: if references.is_empty() {
+            thread.subject.clone()
+        } else {
+            message::reply_subject(&thread.subject)
+        },
+        body: message.content.clone(),
+        message_id: message_id.clone(),
+        in_reply_to: references.last().cloned(),
+        references,
+        attachments: files,
+    };
+    smtp::send(&account, &password, &email).await?;
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::update(mail_messages::table.find(&message.id))
+        .set(mail_messages::external_message_id.eq(&message_id))
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+type AddressLists = (Vec<String>, Vec<String>, Vec<String>);
+
+/// Real addresses `message` goes to, split into to, cc and bcc
+fn external_recipients(pool: &DbPool, message: &MailMessage) -> Result<AddressLists, String> {
+    let rows = recipients::for_messages(pool, std::slice::from_ref(&message.id))?
+        .remove(&message.id)
+        .unwrap_or_default();
+    let (mut to, mut cc, mut bcc) = (Vec::new(), Vec::new(), Vec::new());
+    if rows.is_empty() {
+        // Written before recipient lists: only the row's own recipient
+        if message.recipient_type == "external" {
+            to.extend(message.external_address.clone());
+        }
+        return Ok((to, cc, bcc));
+    }
+    for row in rows.into_iter().filter(|r| r.recipient_type == "external") {
+        let Some(address) = row.external_address else { continue };
+        match row.kind.as_str() {
+            recipients::CC => cc.push(address),
+            recipients::BCC => bcc.push(address),
+            _ => to.push(address),
+        }
+    }
+    Ok((to, cc, bcc))
+}
+
+/// Remember the newest UID handled for `account_id`
+pub fn record_last_seen_uid(pool: &DbPool, account_id: &str, uid: u32) -> Result<(), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::update(email_accounts::table.find(account_id))
+        .set(email_accounts::last_seen_uid.eq(uid as i64))
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::NewEmailAccount;
+    use crate::schema::agents;
+
+    fn setup() -> (DbPool, EmailAccount) {
+        let pool = crate::database::create_test_pool();
+        let mut conn = pool.get().unwrap();
+        let now = chrono::Utc::now().naive_utc();
+        diesel::insert_into(agents::table)
+            .values((agents::id.eq("agent-1"), agents::name.eq("Mailer")))
+            .execute(&mut conn)
+            .unwrap();
+        let account = NewEmailAccount {
+            id: "acc-1".to_string(),
+            agent_id: "agent-1".to_string(),
+            email_address: "agent@example.org".to_string(),
+            display_name: None,
+            username: "agent@example.org".to_string(),
+            smtp_host: "smtp.example.org".to_string(),
+            smtp_port: 587,
+            imap_host: "imap.example.org".to_string(),
+            imap_port: 993,
+            imap_folder: "INBOX".to_string(),
+            deliver_outbound: 0,
+            poll_interval_seconds: 60,
+            last_seen_uid: 0,
+            is_active: 1,
+            created_at: now,
+            updated_at: now,
+        };
+        diesel::insert_into(email_accounts::table)
+            .values(&account)
+            .execute(&mut conn)
+            .unwrap();
+        let account = account_for_agent(&pool, "agent-1").unwrap().unwrap();
+        (pool, account)
+    }
+
+    fn inbound(message_id: &str, references: &[&str]) -> InboundEmail {
+        InboundEmail {
+            message_id: Some(message_id.to_string()),
+            references: references.iter().map(|r| r.to_string()).collect(),
+            from_address: "dana@example.com".to_string(),
+            from_name: None,
+            subject: "Quarterly numbers".to_string(),
+            body: "Can you send them?".to_string(),
+            automated: false,
+            attachments: vec![],
+        }
+    }
+
+    #[test]
+    fn test_inbound_mail_is_threaded_and_deduplicated() {
+        let (pool, account) = setup();
+        let root = tempfile::tempdir().unwrap();
+
+        let first = inject_inbound(&pool, root.path(), &account, &inbound("m1@example.com", &[])).unwrap().unwrap();
+        assert!(inject_inbound(&pool, root.path(), &account, &inbound("m1@example.com", &[])).unwrap().is_none());
+
+        let reply = inject_inbound(&pool, root.path(), &account, &inbound("m2@example.com", &["m1@example.com"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply.thread_id, first.thread_id);
+
+        let unrelated = inject_inbound(&pool, root.path(), &account, &inbound("m3@example.com", &["other@x.org"]))
+            .unwrap()
+            .unwrap();
+        assert_ne!(unrelated.thread_id, first.thread_id);
+
+        let mut conn = pool.get().unwrap();
+        let stored = mail_messages::table
+            .find(&first.message_id)
+            .first::<MailMessage>(&mut conn)
+            .unwrap();
+        assert_eq!(stored.sender_type, "external");
+        assert_eq!(stored.external_address.as_deref(), Some("dana@example.com"));
+        assert_eq!(stored.recipient_agent_id.as_deref(), Some(account.agent_id.as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_drafts_are_not_delivered() {
+        let (pool, account) = setup();
+        let root = tempfile::tempdir().unwrap();
+        let injected = inject_inbound(&pool, root.path(), &account, &inbound("m1@example.com", &[])).unwrap().unwrap();
+
+        let reply = NewMailMessage {
+            id: "reply-1".to_string(),
+            thread_id: injected.thread_id,
+            sender_type: "agent".to_string(),
+            sender_agent_id: Some(account.agent_id.clone()),
+            recipient_type: "external".to_string(),
+            recipient_agent_id: None,
+            content: "Attached.".to_string(),
+            created_at: chrono::Utc::now().naive_utc(),
+            external_address: Some("dana@example.com".to_string()),
+            external_message_id: None,
+        };
+        diesel::insert_into(mail_messages::table)
+            .values(&reply)
+            .execute(&mut pool.get().unwrap())
+            .unwrap();
+
+        // deliver_outbound is off, so nothing is sent and no SMTP is attempted
+        assert!(!deliver(&pool, "reply-1").await.unwrap());
+    }
+
+    #[test]
+    fn test_validate_account() {
+        assert!(validate_account("a@example.org", "smtp", 587, "imap", 993, 60).is_ok());
+        assert!(validate_account("Jordan", "smtp", 587, "imap", 993, 60).is_err());
+        assert!(validate_account("a@example.org", "", 587, "imap", 993, 60).is_err());
+        assert!(validate_account("a@example.org", "smtp", 0, "imap", 993, 60).is_err());
+        assert!(validate_account("a@example.org", "smtp", 587, "imap", 993, 5).is_err());
+        assert!(is_email_address("dana@example.com"));
+        assert!(!is_email_address("dana@localhost"));
+        assert!(!is_email_address("user"));
+    }
+}
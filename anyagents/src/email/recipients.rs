@@ -0,0 +1,513 @@
+//! Who a mail message is addressed to
+//!
+//! An address is `user`, an email address, a group name or an agent name;
+//! groups expand to their member agents. The first `to` recipient is also
+//! copied onto `mail_messages`, so code that only knows one recipient keeps
+//! working. The full list, cc and bcc included, lives in `mail_recipients`.
+
+use crate::database::DbPool;
+use crate::models::{Agent, MailGroup, MailMessage, MailRecipient, NewMailRecipient};
+use crate::schema::{agents, mail_group_members, mail_groups, mail_recipients};
+use diesel::prelude::*;
+use std::collections::HashMap;
+
+pub const TO: &str = "to";
+pub const CC: &str = "cc";
+pub const BCC: &str = "bcc";
+
+/// A resolved recipient, before it is stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recipient {
+    pub kind: String,
+    /// `user`, `agent` or `external`, as on `mail_messages`
+    pub recipient_type: String,
+    pub agent_id: Option<String>,
+    pub external_address: Option<String>,
+}
+
+impl Recipient {
+    pub fn user(kind: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            recipient_type: "user".to_string(),
+            agent_id: None,
+            external_address: None,
+        }
+    }
+
+    pub fn agent(kind: &str, agent_id: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            recipient_type: "agent".to_string(),
+            agent_id: Some(agent_id.to_string()),
+            external_address: None,
+        }
+    }
+
+    pub fn external(kind: &str, address: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            recipient_type: "external".to_string(),
+            agent_id: None,
+            external_address: Some(address.trim().to_lowercase()),
+        }
+    }
+
+    /// Same mailbox, whatever the kind
+    fn same_mailbox(&self, other: &Recipient) -> bool {
+        self.recipient_type == other.recipient_type
+            && self.agent_id == other.agent_id
+            && self.external_address == other.external_address
+    }
+}
+
+impl From<&MailRecipient> for Recipient {
+    fn from(row: &MailRecipient) -> Self {
+        Self {
+            kind: row.kind.clone(),
+            recipient_type: row.recipient_type.clone(),
+            agent_id: row.recipient_agent_id.clone(),
+            external_address: row.external_address.clone(),
+        }
+    }
+}
+
+/// Lowercase letters, digits, `-` and `_`, like `engineering-team`
+pub fn is_valid_group_name(name: &str) -> bool {
+    (2..=64).contains(&name.len())
+        && name.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+/// Agent ids in the group called `name`, if there is one
+pub fn group_members(pool: &DbPool, name: &str) -> Result<Option<Vec<String>>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let name = name.trim().trim_start_matches(['@', '#']).to_lowercase();
+    let group = mail_groups::table
+        .filter(mail_groups::name.eq(&name))
+        .first::<MailGroup>(&mut conn)
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let Some(group) = group else {
+        return Ok(None);
+    };
+    let members = mail_group_members::table
+        .filter(mail_group_members::group_id.eq(&group.id))
+        .order(mail_group_members::created_at.asc())
+        .select(mail_group_members::agent_id)
+        .load::<String>(&mut conn)
+        .map_err(|e| e.to_string())?;
+    Ok(Some(members))
+}
+
+fn find_agent<'a>(all_agents: &'a [Agent], address: &str) -> Option<&'a Agent> {
+    let wanted = address.to_lowercase();
+    all_agents
+        .iter()
+        .find(|a| a.id == address || a.name.to_lowercase() == wanted)
+        .or_else(|| {
+            all_agents.iter().find(|a| {
+                let name = a.name.to_lowercase();
+                name.contains(&wanted) || wanted.contains(&name)
+            })
+        })
+}
+
+fn resolve_one(pool: &DbPool, all_agents: &[Agent], address: &str, kind: &str) -> Result<Vec<Recipient>, String> {
+    let address = address.trim();
+    if address.eq_ignore_ascii_case("user") {
+        return Ok(vec![Recipient::user(kind)]);
+    }
+    if super::is_email_address(address) {
+        return Ok(vec![Recipient::external(kind, address)]);
+    }
+    if let Some(members) = group_members(pool, address)? {
+        if members.is_empty() {
+            return Err(format!("Group '{}' has no members", address));
+        }
+        return Ok(members.iter().map(|id| Recipient::agent(kind, id)).collect());
+    }
+    match find_agent(all_agents, address) {
+        Some(agent) => Ok(vec![Recipient::agent(kind, &agent.id)]),
+        None => Err(format!("Recipient '{}' not found", address)),
+    }
+}
+
+/// Resolve the to, cc and bcc lists. Someone named twice keeps the first
+/// (most visible) kind, and the sending agent is never its own recipient.
+pub fn resolve(
+    pool: &DbPool,
+    to: &[String],
+    cc: &[String],
+    bcc: &[String],
+    sender_agent_id: Option<&str>,
+) -> Result<Vec<Recipient>, String> {
+    let all_agents: Vec<Agent> = {
+        let mut conn = pool.get().map_err(|e| e.to_string())?;
+        agents::table.load(&mut conn).map_err(|e| e.to_string())?
+    };
+
+    let mut resolved = Vec::new();
+    for (kind, addresses) in [(TO, to), (CC, cc), (BCC, bcc)] {
+        for address in addresses.iter().filter(|a| !a.trim().is_empty()) {
+            resolved.extend(resolve_one(pool, &all_agents, address, kind)?);
+        }
+    }
+    let recipients = dedupe(resolved, sender_agent_id);
+    if !recipients.iter().any(|r| r.kind == TO) {
+        return Err("At least one recipient is required".to_string());
+    }
+    Ok(recipients)
+}
+
+fn dedupe(recipients: Vec<Recipient>, sender_agent_id: Option<&str>) -> Vec<Recipient> {
+    let mut kept: Vec<Recipient> = Vec::new();
+    for recipient in recipients {
+        let is_sender = sender_agent_id.is_some() && recipient.agent_id.as_deref() == sender_agent_id;
+        if !is_sender && !kept.iter().any(|k| k.same_mailbox(&recipient)) {
+            kept.push(recipient);
+        }
+    }
+    kept
+}
+
+/// The recipient stored on `mail_messages` itself
+pub fn primary(recipients: &[Recipient]) -> Option<&Recipient> {
+    recipients.iter().find(|r| r.kind == TO)
+}
+
+/// `recipient_type`, `recipient_agent_id` and `external_address` for the
+/// message row, taken from the primary recipient
+pub fn primary_columns(recipients: &[Recipient]) -> Result<(String, Option<String>, Option<String>), String> {
+    let first = primary(recipients).ok_or("At least one recipient is required")?;
+    Ok((
+        first.recipient_type.clone(),
+        first.agent_id.clone(),
+        first.external_address.clone(),
+    ))
+}
+
+/// Store every recipient of `message_id`
+pub fn record(conn: &mut SqliteConnection, message_id: &str, recipients: &[Recipient]) -> Result<(), String> {
+    let now = chrono::Utc::now().naive_utc();
+    let rows: Vec<NewMailRecipient> = recipients
+        .iter()
+        .map(|r| NewMailRecipient {
+            id: uuid::Uuid::new_v4().to_string(),
+            message_id: message_id.to_string(),
+            kind: r.kind.clone(),
+            recipient_type: r.recipient_type.clone(),
+            recipient_agent_id: r.agent_id.clone(),
+            external_address: r.external_address.clone(),
+            created_at: now,
+        })
+        .collect();
+    if rows.is_empty() {
+        return Ok(());
+    }
+    diesel::insert_into(mail_recipients::table)
+        .values(&rows)
+        .execute(conn)
+        .map_err(|e| format!("Failed to store recipients: {}", e))?;
+    Ok(())
+}
+
+/// Recipients of each message in `message_ids`
+pub fn for_messages(pool: &DbPool, message_ids: &[String]) -> Result<HashMap<String, Vec<MailRecipient>>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let rows: Vec<MailRecipient> = mail_recipients::table
+        .filter(mail_recipients::message_id.eq_any(message_ids))
+        .order(mail_recipients::created_at.asc())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())?;
+    let mut by_message: HashMap<String, Vec<MailRecipient>> = HashMap::new();
+    for row in rows {
+        by_message.entry(row.message_id.clone()).or_default().push(row);
+    }
+    Ok(by_message)
+}
+
+/// Whether `message` reached `agent_id`, or the user when `agent_id` is None
+pub fn is_addressed_to(message: &MailMessage, recipients: &[MailRecipient], agent_id: Option<&str>) -> bool {
+    match agent_id {
+        Some(agent) => {
+            message.recipient_agent_id.as_deref() == Some(agent)
+                || recipients.iter().any(|r| r.recipient_agent_id.as_deref() == Some(agent))
+        }
+        None => {
+            (message.recipient_type == "user" && message.recipient_agent_id.is_none())
+                || recipients.iter().any(|r| r.recipient_type == "user")
+        }
+    }
+}
+
+/// The newest message in `thread_id` that reached `agent_id`, with its
+/// recipients. Background replies answer this one.
+pub fn latest_addressed_to(
+    pool: &DbPool,
+    thread_id: &str,
+    agent_id: &str,
+) -> Result<Option<(MailMessage, Vec<MailRecipient>)>, String> {
+    use crate::schema::mail_messages;
+
+    let messages: Vec<MailMessage> = {
+        let mut conn = pool.get().map_err(|e| e.to_string())?;
+        mail_messages::table
+            .filter(mail_messages::thread_id.eq(thread_id))
+            .order(mail_messages::created_at.desc())
+            .load(&mut conn)
+            .map_err(|e| e.to_string())?
+    };
+    let ids: Vec<String> = messages.iter().map(|m| m.id.clone()).collect();
+    let mut by_message = for_messages(pool, &ids)?;
+    for message in messages {
+        let rows = by_message.remove(&message.id).unwrap_or_default();
+        if message.sender_agent_id.as_deref() != Some(agent_id) && is_addressed_to(&message, &rows, Some(agent_id)) {
+            return Ok(Some((message, rows)));
+        }
+    }
+    Ok(None)
+}
+
+/// Who a background reply from `agent_id` in `thread_id` goes to: reply-all
+/// to the newest message it received, or `fallback` if there is none. Real
+/// addresses are dropped for agents without an email account.
+pub fn background_reply_recipients(
+    pool: &DbPool,
+    thread_id: &str,
+    agent_id: &str,
+    fallback: Recipient,
+) -> Result<Vec<Recipient>, String> {
+    let mut replies = match latest_addressed_to(pool, thread_id, agent_id)? {
+        Some((message, rows)) => reply_all(&message, &rows, Some(agent_id)),
+        None => vec![],
+    };
+    if super::account_for_agent(pool, agent_id)?.is_none() {
+        replies.retain(|r| r.recipient_type != "external");
+    }
+    if primary(&replies).is_none() {
+        match replies.first_mut() {
+            Some(first) => first.kind = TO.to_string(),
+            None => return Ok(vec![fallback]),
+        }
+    }
+    Ok(replies)
+}
+
+/// The sender of `message` as a recipient of kind `kind`
+fn sender_of(message: &MailMessage, kind: &str) -> Option<Recipient> {
+    match message.sender_type.as_str() {
+        "user" => Some(Recipient::user(kind)),
+        "agent" => message.sender_agent_id.as_deref().map(|id| Recipient::agent(kind, id)),
+        "external" => message.external_address.as_deref().map(|a| Recipient::external(kind, a)),
+        _ => None,
+    }
+}
+
+/// The visible recipients of `message`. Messages from before recipient lists
+/// only have the one on the row.
+fn visible_recipients(message: &MailMessage, recipients: &[MailRecipient]) -> Vec<Recipient> {
+    if recipients.is_empty() {
+        let primary = match message.recipient_type.as_str() {
+            "user" => Some(Recipient::user(TO)),
+            "agent" => message.recipient_agent_id.as_deref().map(|id| Recipient::agent(TO, id)),
+            "external" if message.sender_type != "external" => {
+                message.external_address.as_deref().map(|a| Recipient::external(TO, a))
+            }
+            _ => None,
+        };
+        return primary.into_iter().collect();
+    }
+    recipients.iter().filter(|r| r.kind != BCC).map(Recipient::from).collect()
+}
+
+/// Reply-all to `message` from `replier_agent_id` (the user when None): the
+/// sender goes in to, everyone else who could see the message in cc. Bcc
+/// recipients are left out. Replying to your own message goes back to its
+/// original recipients.
+pub fn reply_all(message: &MailMessage, recipients: &[MailRecipient], replier_agent_id: Option<&str>) -> Vec<Recipient> {
+    let is_replier = |r: &Recipient| match replier_agent_id {
+        Some(id) => r.agent_id.as_deref() == Some(id),
+        None => r.recipient_type == "user",
+    };
+    let visible = visible_recipients(message, recipients);
+
+    let mut all = Vec::new();
+    match sender_of(message, TO) {
+        Some(sender) if !is_replier(&sender) => {
+            all.push(sender);
+            all.extend(visible.into_iter().map(|r| Recipient { kind: CC.to_string(), ..r }));
+        }
+        _ => all.extend(visible),
+    }
+    all.retain(|r| !is_replier(r));
+    dedupe(all, replier_agent_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{NewAgent, NewMailGroup, NewMailGroupMember};
+
+    fn add_agent(pool: &DbPool, agent_id: &str, agent_name: &str) {
+        let agent = NewAgent {
+            id: agent_id.to_string(),
+            name: agent_name.to_string(),
+            description: None,
+            status: "active".to_string(),
+            personality: None,
+            tone: None,
+            expertise: None,
+            ai_provider: "openai".to_string(),
+            ai_model: "gpt-4o".to_string(),
+            ai_temperature: 0.7,
+            ai_config: "{}".to_string(),
+            system_prompt: None,
+            permissions: None,
+            working_directories: None,
+            skills: None,
+            mcp_servers: None,
+            messaging_connections: None,
+            knowledge_bases: None,
+            api_keys: None,
+            created_at: chrono::Utc::now().timestamp(),
+            updated_at: chrono::Utc::now().timestamp(),
+            platform_configs: None,
+            execution_settings: None,
+            scope_type: None,
+            workspace_path: None,
+            avatar: None,
+            agent_tools: None,
+        };
+        let mut conn = pool.get().unwrap();
+        diesel::insert_into(agents::table).values(&agent).execute(&mut conn).unwrap();
+    }
+
+    fn add_group(pool: &DbPool, name: &str, members: &[&str]) {
+        let mut conn = pool.get().unwrap();
+        let now = chrono::Utc::now().naive_utc();
+        let group_id = uuid::Uuid::new_v4().to_string();
+        diesel::insert_into(mail_groups::table)
+            .values(&NewMailGroup {
+                id: group_id.clone(),
+                name: name.to_string(),
+                description: None,
+                created_at: now,
+                updated_at: now,
+            })
+            .execute(&mut conn)
+            .unwrap();
+        for member in members {
+            diesel::insert_into(mail_group_members::table)
+                .values(&NewMailGroupMember {
+                    group_id: group_id.clone(),
+                    agent_id: member.to_string(),
+                    created_at: now,
+                })
+                .execute(&mut conn)
+                .unwrap();
+        }
+    }
+
+    fn message(sender_type: &str, sender: Option<&str>, recipient_type: &str, recipient: Option<&str>) -> MailMessage {
+        MailMessage {
+            id: "msg-1".to_string(),
+            thread_id: "thread-1".to_string(),
+            sender_type: sender_type.to_string(),
+            sender_agent_id: sender.map(String::from),
+            recipient_type: recipient_type.to_string(),
+            recipient_agent_id: recipient.map(String::from),
+            content: "Hi".to_string(),
+            created_at: chrono::Utc::now().naive_utc(),
+            external_address: None,
+            external_message_id: None,
+        }
+    }
+
+    fn row(kind: &str, recipient: Recipient) -> MailRecipient {
+        MailRecipient {
+            id: uuid::Uuid::new_v4().to_string(),
+            message_id: "msg-1".to_string(),
+            kind: kind.to_string(),
+            recipient_type: recipient.recipient_type,
+            recipient_agent_id: recipient.agent_id,
+            external_address: recipient.external_address,
+            created_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_expands_groups_and_dedupes() {
+        let pool = crate::database::create_test_pool();
+        add_agent(&pool, "a-jordan", "Jordan");
+        add_agent(&pool, "a-alex", "Alex");
+        add_agent(&pool, "a-sam", "Sam");
+        add_group(&pool, "engineering-team", &["a-jordan", "a-alex", "a-sam"]);
+
+        let recipients = resolve(
+            &pool,
+            &["Jordan".to_string()],
+            &["engineering-team".to_string(), "Dana@Example.com".to_string()],
+            &["user".to_string()],
+            Some("a-sam"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            recipients,
+            vec![
+                Recipient::agent(TO, "a-jordan"),
+                Recipient::agent(CC, "a-alex"),
+                Recipient::external(CC, "dana@example.com"),
+                Recipient::user(BCC),
+            ]
+        );
+        assert_eq!(primary(&recipients), Some(&Recipient::agent(TO, "a-jordan")));
+    }
+
+    #[test]
+    fn test_resolve_requires_a_to_recipient() {
+        let pool = crate::database::create_test_pool();
+        add_agent(&pool, "a-jordan", "Jordan");
+        assert!(resolve(&pool, &[], &["Jordan".to_string()], &[], None).is_err());
+        assert!(resolve(&pool, &["Nobody".to_string()], &[], &[], None).is_err());
+        // Mailing only yourself leaves nobody to send to
+        assert!(resolve(&pool, &["Jordan".to_string()], &[], &[], Some("a-jordan")).is_err());
+    }
+
+    #[test]
+    fn test_reply_all_skips_bcc_and_replier() {
+        let msg = message("user", None, "agent", Some("a-jordan"));
+        let rows = vec![
+            row(TO, Recipient::agent(TO, "a-jordan")),
+            row(CC, Recipient::agent(CC, "a-alex")),
+            row(BCC, Recipient::agent(BCC, "a-sam")),
+        ];
+
+        let replies = reply_all(&msg, &rows, Some("a-jordan"));
+        assert_eq!(replies, vec![Recipient::user(TO), Recipient::agent(CC, "a-alex")]);
+
+        // The user replying to their own message writes to the same people
+        let replies = reply_all(&msg, &rows, None);
+        assert_eq!(replies, vec![Recipient::agent(TO, "a-jordan"), Recipient::agent(CC, "a-alex")]);
+    }
+
+    #[test]
+    fn test_is_addressed_to_checks_recipient_list() {
+        let msg = message("user", None, "agent", Some("a-jordan"));
+        let rows = vec![row(CC, Recipient::agent(CC, "a-alex"))];
+        assert!(is_addressed_to(&msg, &rows, Some("a-jordan")));
+        assert!(is_addressed_to(&msg, &rows, Some("a-alex")));
+        assert!(!is_addressed_to(&msg, &rows, Some("a-sam")));
+        assert!(!is_addressed_to(&msg, &rows, None));
+    }
+
+    #[test]
+    fn test_group_names() {
+        assert!(is_valid_group_name("engineering-team"));
+        assert!(!is_valid_group_name("Engineering Team"));
+        assert!(!is_valid_group_name("-team"));
+    }
+}
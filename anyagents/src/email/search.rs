@@ -0,0 +1,368 @@
+//! Finding mail threads: folders, full-text search, sender, dates and labels
+//!
+//! Everything is filtered in SQL so a page of results costs the same with
+//! ten threads as with ten thousand. Text search goes through the
+//! `mail_messages_fts` index, which triggers keep in sync with
+//! `mail_messages` and thread subjects.
+
+use crate::database::DbPool;
+use crate::models::{Agent, MailLabel, MailThread, NewMailThreadLabel};
+use crate::schema::{agents, mail_labels, mail_messages, mail_recipients, mail_thread_labels, mail_threads};
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Text};
+use diesel::sqlite::Sqlite;
+use std::collections::HashMap;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 200;
+pub const MAX_LABEL_NAME_LEN: usize = 50;
+
+/// Which threads to return. Unset fields don't filter.
+#[derive(Debug, Clone, Default)]
+pub struct MailSearch {
+    /// Words to look for in subjects, bodies and outside addresses; every
+    /// word must match, and the last one may be a prefix
+    pub query: Option<String>,
+    /// `inbox`, `sent` or `all` (the default), seen from `account_id`
+    pub folder: Option<String>,
+    /// Whose mailbox: an agent id, or None for the user
+    pub account_id: Option<String>,
+    /// Agent name or id, part of an email address, or `user`
+    pub sender: Option<String>,
+    /// Only threads with a message in this range
+    pub since: Option<chrono::NaiveDateTime>,
+    pub until: Option<chrono::NaiveDateTime>,
+    /// Label name or id
+    pub label: Option<String>,
+    pub archived: Option<bool>,
+    pub unread_only: bool,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// One page of matching threads, newest first
+#[derive(Debug, Clone)]
+pub struct MailSearchPage {
+    pub threads: Vec<MailThread>,
+    /// Matches across all pages
+    pub total: i64,
+}
+
+type ThreadFilter = Box<dyn BoxableExpression<mail_threads::table, Sqlite, SqlType = Bool>>;
+type MessageFilter = Box<dyn BoxableExpression<mail_messages::table, Sqlite, SqlType = Bool>>;
+
+/// Turn what the user typed into an FTS5 query. Each word is quoted, so
+/// operators and stray quotes are searched for literally instead of failing
+/// the query.
+pub fn fts_query(query: &str) -> Option<String> {
+    let words: Vec<String> = query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect();
+    let last = words.last()?;
+    let mut expr = words[..words.len() - 1].to_vec();
+    expr.push(format!("{}*", last));
+    Some(expr.join(" "))
+}
+
+fn addressed_to(account_id: Option<&str>) -> MessageFilter {
+    match account_id {
+        Some(agent) => Box::new(
+            mail_messages::recipient_agent_id.assume_not_null().eq(agent.to_string()).or(mail_messages::id.eq_any(
+                mail_recipients::table
+                    .filter(mail_recipients::recipient_agent_id.eq(agent.to_string()))
+                    .select(mail_recipients::message_id),
+            )),
+        ),
+        None => Box::new(
+            mail_messages::recipient_type
+                .eq("user")
+                .and(mail_messages::recipient_agent_id.is_null())
+                .or(mail_messages::id.eq_any(
+                    mail_recipients::table
+                        .filter(mail_recipients::recipient_type.eq("user"))
+                        .select(mail_recipients::message_id),
+                )),
+        ),
+    }
+}
+
+fn sent_by(account_id: Option<&str>) -> MessageFilter {
+    match account_id {
+        Some(agent) => Box::new(mail_messages::sender_agent_id.assume_not_null().eq(agent.to_string())),
+        None => Box::new(
+            mail_messages::sender_type
+                .eq("user")
+                .and(mail_messages::sender_agent_id.is_null()),
+        ),
+    }
+}
+
+fn sender_matches(conn: &mut SqliteConnection, sender: &str) -> Result<MessageFilter, String> {
+    let needle = sender.trim().to_lowercase();
+    let agent_ids: Vec<String> = agents::table
+        .load::<Agent>(conn)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|a| a.id == sender.trim() || a.name.to_lowercase().contains(&needle))
+        .map(|a| a.id)
+        .collect();
+
+    let mut filter: MessageFilter = Box::new(
+        mail_messages::sender_agent_id
+            .assume_not_null()
+            .eq_any(agent_ids)
+            .or(mail_messages::external_address.assume_not_null().like(format!("%{}%", needle))),
+    );
+    if "user".contains(&needle) || needle == "you" {
+        filter = Box::new(filter.or(mail_messages::sender_type.eq("user")));
+    }
+    Ok(filter)
+}
+
+fn in_messages(filter: MessageFilter) -> ThreadFilter {
+    Box::new(mail_threads::id.eq_any(
+        mail_messages::table
+            .filter(filter)
+            .select(mail_messages::thread_id)
+            .into_boxed(),
+    ))
+}
+
+/// Every filter in `search` as conditions on `mail_threads`
+fn thread_filters(conn: &mut SqliteConnection, search: &MailSearch) -> Result<Vec<ThreadFilter>, String> {
+    let mut filters: Vec<ThreadFilter> = Vec::new();
+
+    match search.folder.as_deref().unwrap_or("all") {
+        "inbox" => filters.push(in_messages(addressed_to(search.account_id.as_deref()))),
+        "sent" => filters.push(in_messages(sent_by(search.account_id.as_deref()))),
+        "all" => {}
+        other => return Err(format!("Unknown folder '{}'", other)),
+    }
+    if let Some(expr) = search.query.as_deref().and_then(fts_query) {
+        filters.push(Box::new(
+            sql::<Bool>("mail_threads.id IN (SELECT thread_id FROM mail_messages_fts WHERE mail_messages_fts MATCH ")
+                .bind::<Text, _>(expr)
+                .sql(")"),
+        ));
+    }
+    if let Some(sender) = search.sender.as_deref().filter(|s| !s.trim().is_empty()) {
+        filters.push(in_messages(sender_matches(conn, sender)?));
+    }
+    if let Some(since) = search.since {
+        filters.push(in_messages(Box::new(mail_messages::created_at.ge(since))));
+    }
+    if let Some(until) = search.until {
+        filters.push(in_messages(Box::new(mail_messages::created_at.le(until))));
+    }
+    if let Some(label) = search.label.as_deref() {
+        filters.push(Box::new(
+            mail_threads::id.eq_any(
+                mail_thread_labels::table
+                    .inner_join(mail_labels::table)
+                    .filter(mail_labels::name.eq(label.to_string()).or(mail_labels::id.eq(label.to_string())))
+                    .select(mail_thread_labels::thread_id),
+            ),
+        ));
+    }
+    if let Some(archived) = search.archived {
+        filters.push(Box::new(mail_threads::is_archived.eq(archived as i32)));
+    }
+    if search.unread_only {
+        filters.push(Box::new(mail_threads::is_read.eq(0)));
+    }
+    Ok(filters)
+}
+
+fn filtered<'a>(filters: Vec<ThreadFilter>) -> mail_threads::BoxedQuery<'a, Sqlite> {
+    filters
+        .into_iter()
+        .fold(mail_threads::table.into_boxed(), |query, filter| query.filter(filter))
+}
+
+/// One page of threads matching `search`, most recently active first
+pub fn search_threads(pool: &DbPool, search: &MailSearch) -> Result<MailSearchPage, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+
+    let total = filtered(thread_filters(&mut conn, search)?)
+        .count()
+        .get_result::<i64>(&mut conn)
+        .map_err(|e| format!("Mail search failed: {}", e))?;
+
+    let limit = search.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let threads = filtered(thread_filters(&mut conn, search)?)
+        .order((mail_threads::updated_at.desc(), mail_threads::id.asc()))
+        .limit(limit)
+        .offset(search.offset.unwrap_or(0).max(0))
+        .load::<MailThread>(&mut conn)
+        .map_err(|e| format!("Mail search failed: {}", e))?;
+
+    Ok(MailSearchPage { threads, total })
+}
+
+/// Labels on each of `thread_ids`, by thread id, sorted by name
+pub fn labels_for_threads(pool: &DbPool, thread_ids: &[String]) -> Result<HashMap<String, Vec<MailLabel>>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let rows: Vec<(String, MailLabel)> = mail_thread_labels::table
+        .inner_join(mail_labels::table)
+        .filter(mail_thread_labels::thread_id.eq_any(thread_ids))
+        .order(mail_labels::name.asc())
+        .select((mail_thread_labels::thread_id, MailLabel::as_select()))
+        .load(&mut conn)
+        .map_err(|e| e.to_string())?;
+
+    let mut by_thread: HashMap<String, Vec<MailLabel>> = HashMap::new();
+    for (thread_id, label) in rows {
+        by_thread.entry(thread_id).or_default().push(label);
+    }
+    Ok(by_thread)
+}
+
+/// Replace the labels on a thread
+pub fn set_thread_labels(conn: &mut SqliteConnection, thread_id: &str, label_ids: &[String]) -> QueryResult<()> {
+    diesel::delete(mail_thread_labels::table.filter(mail_thread_labels::thread_id.eq(thread_id))).execute(conn)?;
+
+    let now = chrono::Utc::now().naive_utc();
+    let mut seen = std::collections::HashSet::new();
+    let rows: Vec<NewMailThreadLabel> = label_ids
+        .iter()
+        .filter(|id| seen.insert(id.as_str()))
+        .map(|label_id| NewMailThreadLabel {
+            thread_id: thread_id.to_string(),
+            label_id: label_id.clone(),
+            created_at: now,
+        })
+        .collect();
+    if !rows.is_empty() {
+        diesel::insert_into(mail_thread_labels::table).values(&rows).execute(conn)?;
+    }
+    Ok(())
+}
+
+pub fn is_valid_label_name(name: &str) -> bool {
+    let name = name.trim();
+    !name.is_empty() && name.chars().count() <= MAX_LABEL_NAME_LEN
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+    use crate::models::{NewMailLabel, NewMailMessage, NewMailThread};
+
+    fn add_thread(pool: &DbPool, subject: &str, sender_agent: Option<&str>, to_agent: Option<&str>, body: &str, days_ago: i64) -> String {
+        let mut conn = pool.get().unwrap();
+        let at = chrono::Utc::now().naive_utc() - chrono::Duration::days(days_ago);
+        let thread_id = uuid::Uuid::new_v4().to_string();
+        diesel::insert_into(mail_threads::table)
+            .values(&NewMailThread {
+                id: thread_id.clone(),
+                subject: subject.to_string(),
+                is_read: 0,
+                is_archived: 0,
+                created_at: at,
+                updated_at: at,
+            })
+            .execute(&mut conn)
+            .unwrap();
+        diesel::insert_into(mail_messages::table)
+            .values(&NewMailMessage {
+                id: uuid::Uuid::new_v4().to_string(),
+                thread_id: thread_id.clone(),
+                sender_type: if sender_agent.is_some() { "agent" } else { "user" }.to_string(),
+                sender_agent_id: sender_agent.map(String::from),
+                recipient_type: if to_agent.is_some() { "agent" } else { "user" }.to_string(),
+                recipient_agent_id: to_agent.map(String::from),
+                content: body.to_string(),
+                created_at: at,
+                external_address: None,
+                external_message_id: None,
+            })
+            .execute(&mut conn)
+            .unwrap();
+        thread_id
+    }
+
+    fn ids(page: &MailSearchPage) -> Vec<String> {
+        page.threads.iter().map(|t| t.id.clone()).collect()
+    }
+
+    #[test]
+    fn test_fts_query() {
+        assert_eq!(fts_query("  "), None);
+        assert_eq!(fts_query("test report").as_deref(), Some("\"test\" \"report\"*"));
+        assert_eq!(fts_query("say \"hi OR").as_deref(), Some("\"say\" \"\"\"hi\" \"OR\"*"));
+    }
+
+    #[test]
+    fn test_search_by_text_folder_and_date() {
+        let pool = create_test_pool();
+        let report = add_thread(&pool, "Weekly report", None, Some("agent-1"), "Please run the integration tests", 0);
+        let old = add_thread(&pool, "Old news", Some("agent-1"), None, "Tests passed last month", 40);
+        let _other = add_thread(&pool, "Lunch", None, Some("agent-2"), "Pizza?", 0);
+
+        let search = |s: MailSearch| search_threads(&pool, &s).unwrap();
+
+        let page = search(MailSearch { query: Some("test".into()), ..Default::default() });
+        assert_eq!(ids(&page), vec![report.clone(), old.clone()]);
+        assert_eq!(page.total, 2);
+
+        let page = search(MailSearch { query: Some("weekly".into()), ..Default::default() });
+        assert_eq!(ids(&page), vec![report.clone()]);
+
+        let page = search(MailSearch {
+            folder: Some("inbox".into()),
+            account_id: Some("agent-1".into()),
+            ..Default::default()
+        });
+        assert_eq!(ids(&page), vec![report.clone()]);
+
+        let page = search(MailSearch { folder: Some("inbox".into()), ..Default::default() });
+        assert_eq!(ids(&page), vec![old.clone()]);
+
+        let page = search(MailSearch {
+            query: Some("tests".into()),
+            since: Some(chrono::Utc::now().naive_utc() - chrono::Duration::days(7)),
+            ..Default::default()
+        });
+        assert_eq!(ids(&page), vec![report]);
+
+        let page = search(MailSearch { limit: Some(1), offset: Some(2), ..Default::default() });
+        assert_eq!(ids(&page), vec![old]);
+        assert_eq!(page.total, 3);
+
+        assert!(search_threads(&pool, &MailSearch { folder: Some("spam".into()), ..Default::default() }).is_err());
+    }
+
+    #[test]
+    fn test_labels() {
+        let pool = create_test_pool();
+        let thread = add_thread(&pool, "Invoice", None, Some("agent-1"), "Attached", 0);
+        let _unlabelled = add_thread(&pool, "Hello", None, Some("agent-1"), "Hi", 0);
+
+        let mut conn = pool.get().unwrap();
+        for name in ["finance", "urgent"] {
+            diesel::insert_into(mail_labels::table)
+                .values(&NewMailLabel {
+                    id: name.to_string(),
+                    name: name.to_string(),
+                    color: None,
+                    created_at: chrono::Utc::now().naive_utc(),
+                })
+                .execute(&mut conn)
+                .unwrap();
+        }
+        set_thread_labels(&mut conn, &thread, &["urgent".into(), "finance".into(), "urgent".into()]).unwrap();
+
+        let labels = labels_for_threads(&pool, &[thread.clone()]).unwrap();
+        let names: Vec<&str> = labels[&thread].iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, vec!["finance", "urgent"]);
+
+        let page = search_threads(&pool, &MailSearch { label: Some("finance".into()), ..Default::default() }).unwrap();
+        assert_eq!(ids(&page), vec![thread.clone()]);
+
+        set_thread_labels(&mut conn, &thread, &[]).unwrap();
+        assert!(labels_for_threads(&pool, &[thread]).unwrap().is_empty());
+    }
+}
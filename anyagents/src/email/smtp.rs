@@ -0,0 +1,231 @@
+//! Outbound delivery over SMTP. Port 465 uses implicit TLS, anything else
+//! STARTTLS.
+
+use crate::models::EmailAccount;
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::time::Duration;
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A message leaving an agent's mailbox. Ids are bare, without angle brackets.
+#[derive(Debug, Clone)]
+pub struct OutboundEmail {
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    /// Goes on the envelope only, never into the headers
+    pub bcc: Vec<String>,
+    pub subject: String,
+    pub body: String,
+    pub message_id: String,
+    pub in_reply_to: Option<String>,
+    pub references: Vec<String>,
+    pub attachments: Vec<OutboundAttachment>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OutboundAttachment {
+    pub file_name: String,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+pub fn build_message(account: &EmailAccount, email: &OutboundEmail) -> Result<Message, String> {
+    let from = Mailbox::new(
+        account.display_name.clone(),
+        account
+            .email_address
+            .parse()
+            .map_err(|e| format!("Invalid sender address: {}", e))?,
+    );
+    if email.to.is_empty() {
+        return Err("Email has no recipient".to_string());
+    }
+
+    let mut builder = Message::builder()
+        .from(from)
+        .subject(&email.subject)
+        .message_id(Some(format!("<{}>", email.message_id)));
+    for address in &email.to {
+        builder = builder.to(mailbox(address)?);
+    }
+    for address in &email.cc {
+        builder = builder.cc(mailbox(address)?);
+    }
+    for address in &email.bcc {
+        builder = builder.bcc(mailbox(address)?);
+    }
+    if let Some(parent) = &email.in_reply_to {
+        builder = builder.in_reply_to(format!("<{}>", parent));
+    }
+    if !email.references.is_empty() {
+        let references: Vec<String> = email.references.iter().map(|r| format!("<{}>", r)).collect();
+        builder = builder.references(references.join(" "));
+    }
+    if email.attachments.is_empty() {
+        return builder
+            .header(ContentType::TEXT_PLAIN)
+            .body(email.body.clone())
+            .map_err(|e| format!("Failed to build email: {}", e));
+    }
+
+    let mut parts = MultiPart::mixed().singlepart(SinglePart::plain(email.body.clone()));
+    for file in &email.attachments {
+        let content_type = ContentType::parse(&file.mime_type)
+            .unwrap_or_else(|_| ContentType::parse("application/octet-stream").expect("valid MIME type"));
+        parts = parts.singlepart(Attachment::new(file.file_name.clone()).body(file.data.clone(), content_type));
+    }
+    builder
+        .multipart(parts)
+        .map_err(|e| format!("Failed to build email: {}", e))
+}
+
+fn mailbox(address: &str) -> Result<Mailbox, String> {
+    address
+        .parse()
+        .map_err(|e| format!("Invalid recipient address '{}': {}", address, e))
+}
+
+fn transport(account: &EmailAccount, password: &str) -> Result<AsyncSmtpTransport<Tokio1Executor>, String> {
+    let builder = if account.smtp_port == 465 {
+        AsyncSmtpTransport::<Tokio1Executor>::relay(&account.smtp_host)
+    } else {
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&account.smtp_host)
+    }
+    .map_err(|e| format!("SMTP setup failed: {}", e))?;
+
+    Ok(builder
+        .port(account.smtp_port as u16)
+        .credentials(Credentials::new(account.username.clone(), password.to_string()))
+        .timeout(Some(SMTP_TIMEOUT))
+        .build())
+}
+
+pub async fn send(account: &EmailAccount, password: &str, email: &OutboundEmail) -> Result<(), String> {
+    let message = build_message(account, email)?;
+    transport(account, password)?
+        .send(message)
+        .await
+        .map_err(|e| format!("SMTP error: {}", e))?;
+    Ok(())
+}
+
+/// Connect and authenticate without sending anything
+pub async fn test_connection(account: &EmailAccount, password: &str) -> Result<(), String> {
+    match transport(account, password)?.test_connection().await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err("SMTP server did not accept the connection".to_string()),
+        Err(e) => Err(format!("SMTP error: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account() -> EmailAccount {
+        let now = chrono::Utc::now().naive_utc();
+        EmailAccount {
+            id: "acc".to_string(),
+            agent_id: "agent".to_string(),
+            email_address: "agent@example.org".to_string(),
+            display_name: Some("Ops Agent".to_string()),
+            username: "agent@example.org".to_string(),
+            smtp_host: "smtp.example.org".to_string(),
+            smtp_port: 587,
+            imap_host: "imap.example.org".to_string(),
+            imap_port: 993,
+            imap_folder: "INBOX".to_string(),
+            deliver_outbound: 1,
+            poll_interval_seconds: 60,
+            last_seen_uid: 0,
+            is_active: 1,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn test_build_threaded_reply() {
+        let email = OutboundEmail {
+            to: vec!["dana@example.com".to_string()],
+            cc: vec![],
+            bcc: vec![],
+            subject: "Re: Quarterly numbers".to_string(),
+            body: "Shipped.".to_string(),
+            message_id: "new-3@example.org".to_string(),
+            in_reply_to: Some("reply-2@example.com".to_string()),
+            references: vec!["orig-1@example.org".to_string(), "reply-2@example.com".to_string()],
+            attachments: vec![],
+        };
+        let formatted = String::from_utf8(build_message(&account(), &email).unwrap().formatted()).unwrap();
+        assert!(formatted.contains("Ops Agent"));
+        assert!(formatted.contains("<agent@example.org>"));
+        assert!(formatted.contains("Message-ID: <new-3@example.org>"));
+        assert!(formatted.contains("In-Reply-To: <reply-2@example.com>"));
+        assert!(formatted.contains("References: <orig-1@example.org> <reply-2@example.com>"));
+        assert!(formatted.contains("\r\n\r\nShipped."));
+    }
+
+    #[test]
+    fn test_rejects_bad_recipient() {
+        let email = OutboundEmail {
+            to: vec!["not an address".to_string()],
+            cc: vec![],
+            bcc: vec![],
+            subject: "Hi".to_string(),
+            body: "Hi".to_string(),
+            message_id: "m@example.org".to_string(),
+            in_reply_to: None,
+            references: vec![],
+            attachments: vec![],
+        };
+        assert!(build_message(&account(), &email).is_err());
+    }
+
+    #[test]
+    fn test_build_with_attachment() {
+        let email = OutboundEmail {
+            to: vec!["dana@example.com".to_string()],
+            cc: vec![],
+            bcc: vec![],
+            subject: "Report".to_string(),
+            body: "See attached.".to_string(),
+            message_id: "m@example.org".to_string(),
+            in_reply_to: None,
+            references: vec![],
+            attachments: vec![OutboundAttachment {
+                file_name: "q3.csv".to_string(),
+                mime_type: "text/csv".to_string(),
+                data: b"a,b".to_vec(),
+            }],
+        };
+        let formatted = String::from_utf8(build_message(&account(), &email).unwrap().formatted()).unwrap();
+        assert!(formatted.contains("multipart/mixed"));
+        assert!(formatted.contains("filename=\"q3.csv\""));
+        assert!(formatted.contains("See attached."));
+    }
+
+    #[test]
+    fn test_cc_is_visible_and_bcc_is_not() {
+        let email = OutboundEmail {
+            to: vec!["dana@example.com".to_string()],
+            cc: vec!["lee@example.com".to_string()],
+            bcc: vec!["audit@example.com".to_string()],
+            subject: "Plan".to_string(),
+            body: "Draft attached.".to_string(),
+            message_id: "m@example.org".to_string(),
+            in_reply_to: None,
+            references: vec![],
+            attachments: vec![],
+        };
+        let message = build_message(&account(), &email).unwrap();
+        let formatted = String::from_utf8(message.formatted()).unwrap();
+        assert!(formatted.contains("Cc: lee@example.com"));
+        assert!(!formatted.contains("audit@example.com"));
+        let envelope: Vec<String> = message.envelope().to().iter().map(|a| a.to_string()).collect();
+        assert!(envelope.contains(&"audit@example.com".to_string()));
+    }
+}
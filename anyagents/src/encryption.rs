@@ -0,0 +1,280 @@
+//! Encryption at rest with SQLCipher
+//!
+//! Built with the `sqlcipher` feature, the whole database file (messages,
+//! mail, settings, the search index and the WAL) can be encrypted with a
+//! random key kept in the OS keyring, never on disk. Every pooled connection
+//! unlocks the file with `PRAGMA key`.
+//!
+//! Converting the file needs it closed, so `enable_encryption` and
+//! `disable_encryption` only record what should happen and
+//! `prepare_database` carries it out on the next start, before the pool opens:
+//! a plain file with a key in the keyring is encrypted, an encrypted file
+//! whose key was retired is decrypted. Both go through `sqlcipher_export`
+//! into a new file that then replaces the old one. Backups taken before are
+//! not converted.
+
+use diesel::connection::SimpleConnection;
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use crate::secrets::{KeyringStore, SecretStore};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+const KEYRING: KeyringStore = KeyringStore::new("anycowork-encryption");
+/// Key of the database, or the key it should be encrypted with on next start
+const KEY_ENTRY: &str = "database";
+/// Key of a database to be decrypted on next start
+const RETIRED_KEY_ENTRY: &str = "database-retired";
+
+/// Every unencrypted SQLite file starts with this
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+static ACTIVE_KEY: RwLock<Option<String>> = RwLock::new(None);
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PendingChange {
+    Encrypt,
+    Decrypt,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct EncryptionStatus {
+    /// Built with SQLCipher
+    pub supported: bool,
+    /// The open database is encrypted
+    pub encrypted: bool,
+    /// Change that happens on the next start
+    pub pending: Option<PendingChange>,
+}
+
+fn read_key(name: &str) -> Result<Option<String>, String> {
+    KEYRING
+        .get(name)
+        .map_err(|e| format!("Failed to read the database key: {}", e))
+}
+
+fn write_key(name: &str, key: &str) -> Result<(), String> {
+    KEYRING
+        .set(name, key)
+        .map_err(|e| format!("Failed to store the database key: {}", e))
+}
+
+fn delete_key(name: &str) -> Result<(), String> {
+    KEYRING
+        .delete(name)
+        .map_err(|e| format!("Failed to delete the database key: {}", e))
+}
+
+/// A new random 256-bit key, hex encoded
+pub fn generate_key() -> Result<String, String> {
+    let mut key = [0u8; 32];
+    getrandom::getrandom(&mut key).map_err(|e| format!("No random source: {}", e))?;
+    Ok(hex::encode(key))
+}
+
+/// `PRAGMA key` for a raw (hex) key, so SQLCipher skips key derivation
+pub fn key_pragma(key: &str) -> String {
+    format!("PRAGMA key = \"x'{}'\";", key)
+}
+
+/// Key the pool's connections were opened with, for connections opened
+/// outside the pool (restoring a backup)
+pub fn active_key() -> Option<String> {
+    ACTIVE_KEY.read().ok().and_then(|key| key.clone())
+}
+
+/// Whether `path` holds a database that isn't plain SQLite. A missing or
+/// empty file is not encrypted.
+pub fn is_encrypted_file(path: &Path) -> Result<bool, String> {
+    use std::io::Read;
+
+    let mut file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let mut header = Vec::with_capacity(SQLITE_HEADER.len());
+    file.by_ref()
+        .take(SQLITE_HEADER.len() as u64)
+        .read_to_end(&mut header)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(!header.is_empty() && header != SQLITE_HEADER)
+}
+
+/// Database file of a `sqlite://` URL or plain path
+pub fn database_path(database_url: &str) -> PathBuf {
+    PathBuf::from(database_url.strip_prefix("sqlite://").unwrap_or(database_url))
+}
+
+/// Unlock a connection to the file at `path` (opened outside the pool) if it
+/// is encrypted
+pub fn unlock(conn: &mut SqliteConnection, path: &Path) -> Result<(), String> {
+    if let (true, Some(key)) = (is_encrypted_file(path)?, active_key()) {
+        conn.batch_execute(&key_pragma(&key)).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Attach the file at `path` as `schema`. SQLCipher would otherwise open it
+/// with the main database's key, which a plain file doesn't have.
+pub fn attach(conn: &mut SqliteConnection, path: &Path, schema: &str) -> Result<(), String> {
+    let key_clause = if cfg!(feature = "sqlcipher") { " KEY ?" } else { "" };
+    let mut query = diesel::sql_query(format!("ATTACH DATABASE ? AS {}{}", schema, key_clause))
+        .into_boxed()
+        .bind::<Text, _>(path.to_string_lossy().to_string());
+    if cfg!(feature = "sqlcipher") {
+        let key = match (is_encrypted_file(path)?, active_key()) {
+            (true, Some(key)) => format!("x'{}'", key),
+            _ => String::new(),
+        };
+        query = query.bind::<Text, _>(key);
+    }
+    query.execute(conn).map(|_| ()).map_err(|e| format!("Failed to open {}: {}", path.display(), e))
+}
+
+#[cfg(feature = "sqlcipher")]
+fn export(path: &Path, from_key: Option<&str>, to_key: Option<&str>) -> Result<(), String> {
+    let target = path.with_extension("converting");
+    let _ = std::fs::remove_file(&target);
+    let mut conn = SqliteConnection::establish(&path.to_string_lossy())
+        .map_err(|e| format!("Failed to open database: {}", e))?;
+    if let Some(key) = from_key {
+        conn.batch_execute(&key_pragma(key)).map_err(|e| e.to_string())?;
+    }
+    // Fold the WAL into the file first, so nothing is left behind in it
+    conn.batch_execute("PRAGMA wal_checkpoint(TRUNCATE);")
+        .map_err(|e| format!("Failed to read database (wrong key?): {}", e))?;
+
+    let target_key = to_key.map(|key| format!("x'{}'", key)).unwrap_or_default();
+    diesel::sql_query("ATTACH DATABASE ? AS converted KEY ?")
+        .bind::<Text, _>(target.to_string_lossy().to_string())
+        .bind::<Text, _>(target_key)
+        .execute(&mut conn)
+        .map_err(|e| format!("Failed to create converted database: {}", e))?;
+    conn.batch_execute("SELECT sqlcipher_export('converted'); DETACH DATABASE converted;")
+        .map_err(|e| format!("Failed to convert database: {}", e))?;
+    drop(conn);
+
+    for suffix in ["-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+    std::fs::rename(&target, path).map_err(|e| format!("Failed to replace database: {}", e))
+}
+
+#[cfg(not(feature = "sqlcipher"))]
+fn export(_path: &Path, _from_key: Option<&str>, _to_key: Option<&str>) -> Result<(), String> {
+    Err(unsupported())
+}
+
+fn unsupported() -> String {
+    "This build has no database encryption (build with the `sqlcipher` feature)".to_string()
+}
+
+/// Carry out a pending encrypt or decrypt of the file at `path` and return
+/// the key its connections need. Call before opening the pool.
+pub fn prepare_database(path: &Path) -> Result<Option<String>, String> {
+    let encrypted = is_encrypted_file(path)?;
+    if !cfg!(feature = "sqlcipher") {
+        if encrypted {
+            return Err(format!("{} is encrypted. {}", path.display(), unsupported()));
+        }
+        return Ok(None);
+    }
+
+    let key = match (read_key(KEY_ENTRY)?, encrypted) {
+        (Some(key), true) => Some(key),
+        (Some(key), false) => {
+            if path.exists() {
+                log::info!("Encrypting database {}", path.display());
+                export(path, None, Some(&key))?;
+            }
+            Some(key)
+        }
+        (None, true) => {
+            let retired = read_key(RETIRED_KEY_ENTRY)?
+                .ok_or_else(|| format!("{} is encrypted but its key is missing from the keyring", path.display()))?;
+            log::info!("Decrypting database {}", path.display());
+            export(path, Some(&retired), None)?;
+            delete_key(RETIRED_KEY_ENTRY)?;
+            None
+        }
+        (None, false) => None,
+    };
+    if let Ok(mut active) = ACTIVE_KEY.write() {
+        active.clone_from(&key);
+    }
+    Ok(key)
+}
+
+pub fn status() -> Result<EncryptionStatus, String> {
+    let supported = cfg!(feature = "sqlcipher");
+    let encrypted = active_key().is_some();
+    if !supported {
+        return Ok(EncryptionStatus { supported, encrypted, pending: None });
+    }
+    let pending = match (encrypted, read_key(KEY_ENTRY)?.is_some()) {
+        (false, true) => Some(PendingChange::Encrypt),
+        (true, false) => Some(PendingChange::Decrypt),
+        _ => None,
+    };
+    Ok(EncryptionStatus { supported, encrypted, pending })
+}
+
+/// Encrypt the database on the next start
+pub fn enable_encryption() -> Result<EncryptionStatus, String> {
+    if !cfg!(feature = "sqlcipher") {
+        return Err(unsupported());
+    }
+    if read_key(KEY_ENTRY)?.is_none() {
+        // Undoing a pending decrypt puts the old key back
+        let key = match read_key(RETIRED_KEY_ENTRY)? {
+            Some(retired) => retired,
+            None => generate_key()?,
+        };
+        write_key(KEY_ENTRY, &key)?;
+        delete_key(RETIRED_KEY_ENTRY)?;
+    }
+    status()
+}
+
+/// Decrypt the database on the next start
+pub fn disable_encryption() -> Result<EncryptionStatus, String> {
+    if !cfg!(feature = "sqlcipher") {
+        return Err(unsupported());
+    }
+    if let Some(key) = read_key(KEY_ENTRY)? {
+        // A database still waiting to be encrypted needs no key at all
+        if active_key().is_some() {
+            write_key(RETIRED_KEY_ENTRY, &key)?;
+        }
+        delete_key(KEY_ENTRY)?;
+    }
+    status()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_encrypted_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("anycowork.db");
+        assert!(!is_encrypted_file(&path).unwrap());
+
+        let pool = crate::database::open_database(&path.to_string_lossy()).unwrap();
+        drop(pool);
+        assert!(!is_encrypted_file(&path).unwrap());
+        assert_eq!(prepare_database(&path).ok().flatten(), None);
+
+        std::fs::write(&path, [0x5a; 4096]).unwrap();
+        assert!(is_encrypted_file(&path).unwrap());
+
+        let key = generate_key().unwrap();
+        assert_eq!(key.len(), 64);
+        assert_ne!(key, generate_key().unwrap());
+        assert_eq!(database_path("sqlite:///tmp/a.db"), PathBuf::from("/tmp/a.db"));
+    }
+}
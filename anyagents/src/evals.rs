@@ -0,0 +1,460 @@
+//! Evals: run task suites against agent configurations
+//!
+//! A suite is a YAML (or JSON) file listing tasks and, optionally, the agent
+//! configurations to compare:
+//!
+//! ```yaml
+//! name: file-edits
+//! agents:
+//!   - provider: openai
+//!     model: gpt-4o
+//!   - name: sonnet-terse
+//!     provider: anthropic
+//!     model: claude-3-5-sonnet-latest
+//!     system_prompt: Be brief.
+//! tasks:
+//!   - id: add-license
+//!     prompt: Add an MIT LICENSE file for "Acme"
+//!     fixture: fixtures/empty-repo    # copied into a fresh workspace
+//!     check: grep -q "MIT License" LICENSE && grep -q Acme LICENSE
+//!     timeout_secs: 120
+//! ```
+//!
+//! Every task runs once per configuration in its own temporary workspace,
+//! headlessly with every permission approved. `check` is a shell script run
+//! in the workspace afterwards; exit code 0 is a pass. The agent's reply is
+//! in `$EVAL_REPLY` for checks about the answer rather than the files.
+//! Steps and tool calls are counted from the run's trace, tokens from what
+//! the provider reports.
+
+use crate::agents::trace::{self, TraceEntry};
+use crate::llm::usage::{self, TokenUsage};
+use crate::runtime::{AgentBuilder, Runtime};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 300;
+const CHECK_TIMEOUT: Duration = Duration::from_secs(60);
+/// Characters of the check's output kept in the report
+const MAX_CHECK_OUTPUT: usize = 2_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AgentConfig {
+    /// Label in the report; `provider/model` when unset
+    #[serde(default)]
+    pub name: Option<String>,
+    pub provider: String,
+    pub model: String,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    #[serde(default)]
+    pub execution_mode: Option<String>,
+}
+
+impl AgentConfig {
+    /// `provider/model`, as given on the command line
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        match spec.split_once('/') {
+            Some((provider, model)) if !provider.is_empty() && !model.is_empty() => Ok(Self {
+                name: None,
+                provider: provider.to_string(),
+                model: model.to_string(),
+                system_prompt: None,
+                execution_mode: None,
+            }),
+            _ => Err(format!("Expected provider/model, got '{}'", spec)),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        self.name.clone().unwrap_or_else(|| format!("{}/{}", self.provider, self.model))
+    }
+
+    fn builder(&self) -> AgentBuilder {
+        let mut builder = AgentBuilder::new(&self.provider, &self.model).with_default_tools();
+        if let Some(prompt) = &self.system_prompt {
+            builder = builder.system_prompt(prompt);
+        }
+        if let Some(mode) = &self.execution_mode {
+            builder = builder.execution_mode(mode);
+        }
+        builder
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EvalTask {
+    pub id: String,
+    pub prompt: String,
+    /// Directory copied into the workspace, relative to the suite file
+    #[serde(default)]
+    pub fixture: Option<PathBuf>,
+    /// Shell script run in the workspace; exit code 0 is a pass
+    pub check: String,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Suite {
+    pub name: String,
+    #[serde(default)]
+    pub agents: Vec<AgentConfig>,
+    pub tasks: Vec<EvalTask>,
+    /// Directory fixtures are relative to
+    #[serde(skip)]
+    pub root: PathBuf,
+}
+
+impl Suite {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut suite: Suite = serde_yaml::from_str(&text).map_err(|e| format!("Invalid suite {}: {}", path.display(), e))?;
+        suite.root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        suite.validate()?;
+        Ok(suite)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.tasks.is_empty() {
+            return Err(format!("Suite '{}' has no tasks", self.name));
+        }
+        let mut ids = std::collections::HashSet::new();
+        for task in &self.tasks {
+            if !ids.insert(task.id.as_str()) {
+                return Err(format!("Task id '{}' is used twice", task.id));
+            }
+            if let Some(fixture) = &task.fixture {
+                if !self.root.join(fixture).is_dir() {
+                    return Err(format!("Fixture of '{}' is not a directory: {}", task.id, fixture.display()));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TaskResult {
+    pub agent: String,
+    pub task: String,
+    pub passed: bool,
+    pub steps: usize,
+    pub tool_calls: usize,
+    pub tokens: TokenUsage,
+    pub wall_ms: u64,
+    /// Why the run failed before the check, if it did
+    pub error: Option<String>,
+    /// Output of the check script
+    pub check_output: String,
+    pub trace: Option<PathBuf>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AgentSummary {
+    pub agent: String,
+    pub tasks: usize,
+    pub passed: usize,
+    pub success_rate: f64,
+    pub avg_steps: f64,
+    pub total_tokens: u64,
+    pub avg_wall_ms: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct EvalReport {
+    pub suite: String,
+    pub started_at: String,
+    pub summaries: Vec<AgentSummary>,
+    pub results: Vec<TaskResult>,
+}
+
+impl EvalReport {
+    fn new(suite: &str, started_at: String, results: Vec<TaskResult>) -> Self {
+        let mut agents: Vec<&str> = Vec::new();
+        for result in &results {
+            if !agents.contains(&result.agent.as_str()) {
+                agents.push(&result.agent);
+            }
+        }
+        let summaries = agents
+            .into_iter()
+            .map(|agent| {
+                let runs: Vec<&TaskResult> = results.iter().filter(|r| r.agent == agent).collect();
+                let count = runs.len().max(1);
+                let passed = runs.iter().filter(|r| r.passed).count();
+                AgentSummary {
+                    agent: agent.to_string(),
+                    tasks: runs.len(),
+                    passed,
+                    success_rate: passed as f64 / count as f64,
+                    avg_steps: runs.iter().map(|r| r.steps).sum::<usize>() as f64 / count as f64,
+                    total_tokens: runs.iter().map(|r| r.tokens.total()).sum(),
+                    avg_wall_ms: runs.iter().map(|r| r.wall_ms).sum::<u64>() / count as u64,
+                }
+            })
+            .collect();
+        Self { suite: suite.to_string(), started_at, summaries, results }
+    }
+
+    /// Summary table, one row per agent configuration
+    pub fn to_table(&self) -> String {
+        let mut table = format!(
+            "{:<32} {:>7} {:>8} {:>9} {:>10} {:>9}\n",
+            "agent", "passed", "rate", "avg steps", "tokens", "avg time"
+        );
+        for s in &self.summaries {
+            table.push_str(&format!(
+                "{:<32} {:>7} {:>7.0}% {:>9.1} {:>10} {:>8.1}s\n",
+                s.agent,
+                format!("{}/{}", s.passed, s.tasks),
+                s.success_rate * 100.0,
+                s.avg_steps,
+                s.total_tokens,
+                s.avg_wall_ms as f64 / 1000.0
+            ));
+        }
+        table
+    }
+}
+
+/// Run every task against every configuration, in order. `agents`
+/// overrides the suite's own configurations when not empty; traces are
+/// kept under `trace_dir` when one is given.
+pub async fn run_suite(
+    runtime: &Runtime,
+    suite: &Suite,
+    agents: &[AgentConfig],
+    trace_dir: Option<&Path>,
+    on_result: impl Fn(&TaskResult),
+) -> Result<EvalReport, String> {
+    let agents = if agents.is_empty() { &suite.agents } else { agents };
+    if agents.is_empty() {
+        return Err(format!("Suite '{}' names no agents to run", suite.name));
+    }
+    let started_at = chrono::Utc::now().to_rfc3339();
+    let mut results = Vec::new();
+    for config in agents {
+        for task in &suite.tasks {
+            let traces = trace_dir.map(|dir| dir.join(sanitize(&config.label())).join(sanitize(&task.id)));
+            let result = run_task(runtime, config.builder(), &config.label(), task, &suite.root, traces).await;
+            on_result(&result);
+            results.push(result);
+        }
+    }
+    Ok(EvalReport::new(&suite.name, started_at, results))
+}
+
+fn sanitize(label: &str) -> String {
+    label
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+        .collect()
+}
+
+/// One task with one agent, in a fresh workspace
+async fn run_task(
+    runtime: &Runtime,
+    builder: AgentBuilder,
+    label: &str,
+    task: &EvalTask,
+    root: &Path,
+    trace_dir: Option<PathBuf>,
+) -> TaskResult {
+    let mut result = TaskResult {
+        agent: label.to_string(),
+        task: task.id.clone(),
+        passed: false,
+        steps: 0,
+        tool_calls: 0,
+        tokens: TokenUsage::default(),
+        wall_ms: 0,
+        error: None,
+        check_output: String::new(),
+        trace: None,
+    };
+    let fail = |mut result: TaskResult, error: String| {
+        result.error = Some(error);
+        result
+    };
+
+    let workspace = match tempfile::tempdir() {
+        Ok(dir) => dir,
+        Err(e) => return fail(result, format!("Failed to create a workspace: {}", e)),
+    };
+    if let Some(fixture) = &task.fixture {
+        if let Err(e) = copy_dir(&root.join(fixture), workspace.path()) {
+            return fail(result, e);
+        }
+    }
+    // Without a trace directory the trace only lives as long as the run
+    let keep_trace = trace_dir.is_some();
+    let scratch = tempfile::tempdir();
+    let trace_dir = match (trace_dir, &scratch) {
+        (Some(dir), _) => dir,
+        (None, Ok(scratch)) => scratch.path().to_path_buf(),
+        (None, Err(e)) => return fail(result, format!("Failed to create a trace directory: {}", e)),
+    };
+
+    let mut agent = builder.workspace(workspace.path()).trace(&trace_dir).build();
+    let timeout = Duration::from_secs(task.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+    usage::start(&agent.session_id);
+    let started = Instant::now();
+    let reply = tokio::time::timeout(timeout, runtime.chat(&mut agent, &task.prompt)).await;
+    result.wall_ms = started.elapsed().as_millis() as u64;
+    result.tokens = usage::finish(&agent.session_id);
+
+    if let Some(path) = latest_trace(&trace_dir) {
+        if let Ok(lines) = trace::load(&path) {
+            for line in lines {
+                match line.entry {
+                    TraceEntry::LlmRequest { .. } => result.steps += 1,
+                    TraceEntry::ToolCall { .. } => result.tool_calls += 1,
+                    _ => {}
+                }
+            }
+        }
+        if keep_trace {
+            result.trace = Some(path);
+        }
+    }
+
+    let reply = match reply {
+        Ok(Ok(reply)) => reply,
+        Ok(Err(e)) => return fail(result, e),
+        Err(_) => return fail(result, format!("Timed out after {}s", timeout.as_secs())),
+    };
+    match run_check(&task.check, workspace.path(), &reply).await {
+        Ok((passed, output)) => {
+            result.passed = passed;
+            result.check_output = output;
+            result
+        }
+        Err(e) => fail(result, e),
+    }
+}
+
+/// Run the check script; Ok((passed, output)) when it ran to completion
+async fn run_check(script: &str, workspace: &Path, reply: &str) -> Result<(bool, String), String> {
+    let child = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(script)
+        .current_dir(workspace)
+        .env("EVAL_REPLY", reply)
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(CHECK_TIMEOUT, child)
+        .await
+        .map_err(|_| format!("Check timed out after {}s", CHECK_TIMEOUT.as_secs()))?
+        .map_err(|e| format!("Failed to run check: {}", e))?;
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    let text: String = text.trim().chars().take(MAX_CHECK_OUTPUT).collect();
+    Ok((output.status.success(), text))
+}
+
+/// The most recently written trace file in `dir`
+fn latest_trace(dir: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "jsonl"))
+        .max_by_key(|entry| entry.metadata().and_then(|m| m.modified()).ok())
+        .map(|entry| entry.path())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<(), String> {
+    for entry in walkdir::WalkDir::new(from) {
+        let entry = entry.map_err(|e| format!("Failed to read fixture {}: {}", from.display(), e))?;
+        let relative = entry.path().strip_prefix(from).map_err(|e| e.to_string())?;
+        let target = to.join(relative);
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&target).map_err(|e| format!("Failed to create {}: {}", target.display(), e))?;
+        } else {
+            std::fs::copy(entry.path(), &target)
+                .map_err(|e| format!("Failed to copy {}: {}", entry.path().display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::trace::Replay;
+    use crate::RuntimeBuilder;
+
+    fn write_suite(dir: &Path) -> Suite {
+        std::fs::create_dir_all(dir.join("fixtures/notes")).unwrap();
+        std::fs::write(dir.join("fixtures/notes/todo.txt"), "ship it\n").unwrap();
+        let path = dir.join("suite.yaml");
+        std::fs::write(
+            &path,
+            r#"
+name: smoke
+agents:
+  - provider: openai
+    model: gpt-4o
+tasks:
+  - id: answer
+    prompt: What does todo.txt say?
+    fixture: fixtures/notes
+    check: grep -q "ship it" todo.txt && [ "$EVAL_REPLY" = "It says ship it." ]
+  - id: license
+    prompt: Add a LICENSE file
+    check: test -f LICENSE
+"#,
+        )
+        .unwrap();
+        Suite::load(&path).unwrap()
+    }
+
+    #[test]
+    fn test_load_suite_and_parse_agents() {
+        let dir = tempfile::tempdir().unwrap();
+        let suite = write_suite(dir.path());
+        assert_eq!(suite.tasks.len(), 2);
+        assert_eq!(suite.agents[0].label(), "openai/gpt-4o");
+        assert_eq!(suite.root, dir.path());
+
+        assert_eq!(AgentConfig::parse("anthropic/claude-3-5-sonnet-latest").unwrap().provider, "anthropic");
+        assert!(AgentConfig::parse("gpt-4o").is_err());
+
+        std::fs::write(dir.path().join("bad.yaml"), "name: bad\ntasks:\n  - id: a\n    prompt: hi\n    check: 'true'\n    fixture: missing\n").unwrap();
+        assert!(Suite::load(&dir.path().join("bad.yaml")).unwrap_err().contains("not a directory"));
+    }
+
+    #[tokio::test]
+    async fn test_run_task_checks_workspace_and_counts_steps() {
+        let dir = tempfile::tempdir().unwrap();
+        let suite = write_suite(dir.path());
+        let runtime = RuntimeBuilder::new()
+            .db_pool(crate::database::create_test_pool())
+            .auto_approve()
+            .build()
+            .unwrap();
+        let replayed = |text: &str| {
+            AgentBuilder::new("openai", "gpt-4o")
+                .replay(Replay::from_entries([TraceEntry::LlmResponse { step: 1, text: text.to_string() }]))
+        };
+
+        let traces = dir.path().join("traces");
+        let passed = run_task(&runtime, replayed("It says ship it."), "replay", &suite.tasks[0], &suite.root, Some(traces)).await;
+        assert!(passed.passed, "{:?}", passed);
+        assert_eq!(passed.steps, 1);
+        assert!(passed.trace.as_ref().is_some_and(|path| path.exists()));
+
+        let failed = run_task(&runtime, replayed("Done."), "replay", &suite.tasks[1], &suite.root, None).await;
+        assert!(!failed.passed);
+        assert!(failed.error.is_none());
+        assert!(failed.trace.is_none());
+
+        let report = EvalReport::new("smoke", String::new(), vec![passed, failed]);
+        assert_eq!(report.summaries.len(), 1);
+        assert_eq!(report.summaries[0].passed, 1);
+        assert_eq!(report.summaries[0].success_rate, 0.5);
+        assert!(report.to_table().contains("1/2"));
+    }
+}
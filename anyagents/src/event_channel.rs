@@ -0,0 +1,285 @@
+//! Buffered event channel
+//!
+//! Fast models stream a token event per word or less, and every event costs
+//! a trip over the IPC bridge to the UI. `EventChannel` sits between the
+//! agent and an observer: text streamed in a row on a channel goes out as one
+//! `token` event once `batch_size` characters are waiting or the oldest has
+//! waited `flush_interval_ms`. `thinking` events follow `ThinkingPolicy`.
+//! Any other event first sends what is waiting on its channel, so the order
+//! the UI sees is the order things happened.
+//!
+//! Settings come from the `event_channel` setting (`EventChannelConfig`).
+
+use crate::database::DbPool;
+use crate::events::AgentObserver;
+use crate::models::settings::{get_setting, set_setting};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+const SETTINGS_KEY: &str = "event_channel";
+const MAX_FLUSH_INTERVAL_MS: u64 = 1_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ThinkingPolicy {
+    /// Send every thinking event
+    Keep,
+    /// Send the latest of a burst, at most one per flush interval
+    Merge,
+    /// Drop thinking events that come within a flush interval of the last one sent
+    Drop,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct EventChannelConfig {
+    /// Longest streamed text waits before it is sent; 0 sends every event
+    /// as it comes
+    pub flush_interval_ms: u64,
+    /// Characters of streamed text sent without waiting for the interval
+    pub batch_size: usize,
+    pub thinking: ThinkingPolicy,
+}
+
+impl Default for EventChannelConfig {
+    fn default() -> Self {
+        Self { flush_interval_ms: 50, batch_size: 512, thinking: ThinkingPolicy::Merge }
+    }
+}
+
+impl EventChannelConfig {
+    pub fn load(pool: &DbPool) -> Self {
+        get_setting(pool, SETTINGS_KEY)
+            .and_then(|v| serde_json::from_str(&v).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, pool: &DbPool) -> Result<(), String> {
+        if self.flush_interval_ms > MAX_FLUSH_INTERVAL_MS {
+            return Err(format!("The flush interval can be at most {} ms", MAX_FLUSH_INTERVAL_MS));
+        }
+        if self.batch_size == 0 {
+            return Err("The batch size must be at least 1".to_string());
+        }
+        set_setting(pool, SETTINGS_KEY, &serde_json::to_string(self).map_err(|e| e.to_string())?)
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_millis(self.flush_interval_ms)
+    }
+}
+
+/// What waits to be sent on one channel
+#[derive(Default)]
+struct Pending {
+    tokens: String,
+    /// When the oldest waiting token came
+    tokens_since: Option<Instant>,
+    /// Latest thinking event held back by `ThinkingPolicy::Merge`
+    thinking: Option<Value>,
+    thinking_sent: Option<Instant>,
+}
+
+impl Pending {
+    fn is_empty(&self) -> bool {
+        self.tokens.is_empty() && self.thinking.is_none()
+    }
+}
+
+pub struct EventChannel {
+    inner: Arc<dyn AgentObserver>,
+    config: EventChannelConfig,
+    // Held while sending, so a flush and an event can't overtake each other
+    channels: Mutex<HashMap<String, Pending>>,
+}
+
+impl EventChannel {
+    /// `inner` behind a buffer that, inside a tokio runtime, flushes itself
+    /// every interval. `inner` itself when buffering is off.
+    pub fn wrap(inner: Arc<dyn AgentObserver>, config: EventChannelConfig) -> Arc<dyn AgentObserver> {
+        if config.flush_interval_ms == 0 {
+            return inner;
+        }
+        let channel = Arc::new(Self { inner, config, channels: Mutex::default() });
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let weak = Arc::downgrade(&channel);
+            let interval = channel.config.interval();
+            runtime.spawn(async move {
+                loop {
+                    tokio::time::sleep(interval).await;
+                    let Some(channel) = weak.upgrade() else {
+                        break;
+                    };
+                    if let Err(e) = channel.flush(false) {
+                        log::warn!("Failed to flush events: {}", e);
+                    }
+                }
+            });
+        }
+        channel
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Pending>> {
+        self.channels.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn send_tokens(&self, event: &str, pending: &mut Pending) -> Result<(), String> {
+        pending.tokens_since = None;
+        if pending.tokens.is_empty() {
+            return Ok(());
+        }
+        let content = std::mem::take(&mut pending.tokens);
+        self.inner.emit(event, json!({"type": "token", "content": content}))
+    }
+
+    fn send_thinking(&self, event: &str, pending: &mut Pending) -> Result<(), String> {
+        match pending.thinking.take() {
+            Some(thinking) => {
+                pending.thinking_sent = Some(Instant::now());
+                self.inner.emit(event, thinking)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Send what has waited a full interval, or everything when `all`
+    pub fn flush(&self, all: bool) -> Result<(), String> {
+        let interval = self.config.interval();
+        let due = |since: Option<Instant>| all || since.is_some_and(|t| t.elapsed() >= interval);
+        let mut channels = self.lock();
+        for (event, pending) in channels.iter_mut() {
+            if pending.thinking.is_some() && due(pending.thinking_sent) {
+                self.send_thinking(event, pending)?;
+            }
+            if due(pending.tokens_since) {
+                self.send_tokens(event, pending)?;
+            }
+        }
+        channels.retain(|_, pending| !pending.is_empty() || pending.thinking_sent.is_some_and(|t| t.elapsed() < interval));
+        Ok(())
+    }
+}
+
+impl AgentObserver for EventChannel {
+    fn emit(&self, event: &str, payload: Value) -> Result<(), String> {
+        let interval = self.config.interval();
+        let mut channels = self.lock();
+        match payload.get("type").and_then(Value::as_str) {
+            Some("token") => {
+                let pending = channels.entry(event.to_string()).or_default();
+                pending.tokens.push_str(payload["content"].as_str().unwrap_or_default());
+                let since = *pending.tokens_since.get_or_insert_with(Instant::now);
+                if pending.tokens.len() >= self.config.batch_size || since.elapsed() >= interval {
+                    self.send_tokens(event, pending)?;
+                }
+                Ok(())
+            }
+            Some("thinking") if self.config.thinking != ThinkingPolicy::Keep => {
+                let pending = channels.entry(event.to_string()).or_default();
+                let recent = pending.thinking_sent.is_some_and(|t| t.elapsed() < interval);
+                match (self.config.thinking, recent) {
+                    (ThinkingPolicy::Drop, true) => Ok(()),
+                    (_, true) => {
+                        pending.thinking = Some(payload);
+                        Ok(())
+                    }
+                    (_, false) => {
+                        self.send_tokens(event, pending)?;
+                        pending.thinking = Some(payload);
+                        self.send_thinking(event, pending)
+                    }
+                }
+            }
+            event_type => {
+                if let Some(pending) = channels.get_mut(event) {
+                    self.send_thinking(event, pending)?;
+                    self.send_tokens(event, pending)?;
+                }
+                // A finished job leaves nothing to wait for on its channel
+                if event_type == Some("job_completed") {
+                    channels.remove(event);
+                }
+                self.inner.emit(event, payload)
+            }
+        }
+    }
+}
+
+impl Drop for EventChannel {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush(true) {
+            log::warn!("Failed to flush events: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Recorder(Mutex<Vec<Value>>);
+
+    impl AgentObserver for Recorder {
+        fn emit(&self, _event: &str, payload: Value) -> Result<(), String> {
+            self.0.lock().unwrap().push(payload);
+            Ok(())
+        }
+    }
+
+    fn channel(recorder: &Arc<Recorder>, thinking: ThinkingPolicy) -> EventChannel {
+        EventChannel {
+            inner: recorder.clone(),
+            config: EventChannelConfig { flush_interval_ms: 60_000, batch_size: 10, thinking },
+            channels: Mutex::default(),
+        }
+    }
+
+    #[test]
+    fn test_tokens_coalesce_and_keep_their_place() {
+        let recorder = Arc::new(Recorder(Mutex::new(vec![])));
+        let events = channel(&recorder, ThinkingPolicy::Keep);
+        for word in ["Hi", " there", ", how"] {
+            events.emit("session:s1", json!({"type": "token", "content": word})).unwrap();
+        }
+        events.emit("session:s1", json!({"type": "token", "content": " are"})).unwrap();
+        events.emit("session:s1", json!({"type": "step_started", "step": {}})).unwrap();
+        events.emit("session:s1", json!({"type": "token", "content": "!"})).unwrap();
+        events.flush(true).unwrap();
+
+        let sent = recorder.0.lock().unwrap().clone();
+        assert_eq!(
+            sent,
+            vec![
+                json!({"type": "token", "content": "Hi there, how"}),
+                json!({"type": "token", "content": " are"}),
+                json!({"type": "step_started", "step": {}}),
+                json!({"type": "token", "content": "!"}),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_thinking_policies() {
+        let thinking = |n: u32| json!({"type": "thinking", "message": format!("step {}", n)});
+
+        let recorder = Arc::new(Recorder(Mutex::new(vec![])));
+        let merged = channel(&recorder, ThinkingPolicy::Merge);
+        for n in 1..=3 {
+            merged.emit("session:s1", thinking(n)).unwrap();
+        }
+        merged.emit("session:s1", json!({"type": "job_completed"})).unwrap();
+        let sent = recorder.0.lock().unwrap().clone();
+        assert_eq!(sent, vec![thinking(1), thinking(3), json!({"type": "job_completed"})]);
+
+        let recorder = Arc::new(Recorder(Mutex::new(vec![])));
+        let dropped = channel(&recorder, ThinkingPolicy::Drop);
+        for n in 1..=3 {
+            dropped.emit("session:s1", thinking(n)).unwrap();
+        }
+        dropped.flush(true).unwrap();
+        assert_eq!(recorder.0.lock().unwrap().clone(), vec![thinking(1)]);
+    }
+}
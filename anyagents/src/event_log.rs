@@ -0,0 +1,163 @@
+//! Session event log
+//!
+//! What a session's jobs stream to the UI is kept (see
+//! `models::session_event`), so a window that reloads mid-job, or opens the
+//! session later, rebuilds the job's progress from the log instead of
+//! starting blank, and a finished job can be gone through event by event.
+//!
+//! Tokens are coalesced: text streamed in a row is kept as one `token`
+//! event, written when the session emits something else, when it grows past
+//! `TOKEN_FLUSH_CHARS`, or when the log is read (`flush`). Long strings in an
+//! event (image previews, file contents) are cut to `MAX_STRING_CHARS`.
+
+use crate::database::DbPool;
+use crate::models::session_event::{insert_session_events, NewSessionEvent};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+const TOKEN_FLUSH_CHARS: usize = 4_000;
+const MAX_STRING_CHARS: usize = 8_000;
+
+#[derive(Default)]
+struct SessionLog {
+    /// The job the session's events belong to; tokens don't say
+    job_id: Option<String>,
+    tokens: String,
+}
+
+impl SessionLog {
+    fn take_tokens(&mut self, session_id: &str) -> Option<NewSessionEvent> {
+        if self.tokens.is_empty() {
+            return None;
+        }
+        let content = std::mem::take(&mut self.tokens);
+        Some(new_event(session_id, self.job_id.clone(), "token", &json!({"type": "token", "content": content})))
+    }
+}
+
+#[derive(Default)]
+pub struct EventLog {
+    sessions: Mutex<HashMap<String, SessionLog>>,
+}
+
+/// The log every app observer records to
+pub fn global() -> &'static EventLog {
+    static EVENT_LOG: OnceLock<EventLog> = OnceLock::new();
+    EVENT_LOG.get_or_init(Default::default)
+}
+
+fn job_id_of(payload: &Value) -> Option<String> {
+    payload["job"]["id"]
+        .as_str()
+        .or_else(|| payload["job_id"].as_str())
+        .map(str::to_string)
+}
+
+fn shorten_strings(value: &mut Value) {
+    match value {
+        Value::String(s) if s.chars().count() > MAX_STRING_CHARS => {
+            *s = format!("{}… [truncated]", s.chars().take(MAX_STRING_CHARS).collect::<String>());
+        }
+        Value::Array(items) => items.iter_mut().for_each(shorten_strings),
+        Value::Object(fields) => fields.values_mut().for_each(shorten_strings),
+        _ => {}
+    }
+}
+
+fn new_event(session_id: &str, job_id: Option<String>, event_type: &str, payload: &Value) -> NewSessionEvent {
+    let mut payload = payload.clone();
+    shorten_strings(&mut payload);
+    NewSessionEvent {
+        session_id: session_id.to_string(),
+        job_id,
+        event_type: event_type.to_string(),
+        payload: payload.to_string(),
+        created_at: chrono::Utc::now().naive_utc(),
+    }
+}
+
+impl EventLog {
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, SessionLog>> {
+        self.sessions.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Keep `payload`, emitted on `channel`, if it is a session event
+    pub fn record(&self, pool: &DbPool, channel: &str, payload: &Value) -> Result<(), String> {
+        let Some(session_id) = channel.strip_prefix("session:") else {
+            return Ok(());
+        };
+        let Some(event_type) = payload.get("type").and_then(Value::as_str) else {
+            return Ok(());
+        };
+
+        let mut rows = vec![];
+        {
+            let mut sessions = self.lock();
+            let log = sessions.entry(session_id.to_string()).or_default();
+            if event_type == "token" {
+                log.tokens.push_str(payload["content"].as_str().unwrap_or_default());
+                if log.tokens.len() >= TOKEN_FLUSH_CHARS {
+                    rows.extend(log.take_tokens(session_id));
+                }
+            } else {
+                rows.extend(log.take_tokens(session_id));
+                if let Some(job_id) = job_id_of(payload) {
+                    log.job_id = Some(job_id);
+                }
+                rows.push(new_event(session_id, log.job_id.clone(), event_type, payload));
+                if event_type == "job_completed" {
+                    sessions.remove(session_id);
+                }
+            }
+        }
+        insert_session_events(pool, &rows)
+    }
+
+    /// Write the tokens of `session_id` still being coalesced
+    pub fn flush(&self, pool: &DbPool, session_id: &str) -> Result<(), String> {
+        let row = self.lock().get_mut(session_id).and_then(|log| log.take_tokens(session_id));
+        insert_session_events(pool, row.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+    use crate::models::session_event::{get_job_events, get_session_events};
+
+    #[test]
+    fn test_tokens_are_coalesced_and_read_from_a_cursor() {
+        let pool = create_test_pool();
+        let log = EventLog::default();
+        let channel = "session:s1";
+        let job = json!({"id": "j1", "session_id": "s1", "status": "running"});
+
+        log.record(&pool, channel, &json!({"type": "job_started", "job": job})).unwrap();
+        for word in ["Hello", ", ", "world"] {
+            log.record(&pool, channel, &json!({"type": "token", "content": word})).unwrap();
+        }
+        log.record(&pool, "permission_request", &json!({"message": "not a session event"})).unwrap();
+        let seen = get_session_events(&pool, "s1", None, 100).unwrap();
+        assert_eq!(seen.len(), 1);
+
+        // A reader mid-stream gets the tokens so far
+        log.flush(&pool, "s1").unwrap();
+        let tokens = get_session_events(&pool, "s1", Some(seen[0].id), 100).unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].job_id.as_deref(), Some("j1"));
+        assert!(tokens[0].payload.contains("Hello, world"));
+
+        log.record(&pool, channel, &json!({"type": "token", "content": "!"})).unwrap();
+        let image = json!({"type": "image_generated", "images": [{"data": "A".repeat(2 * MAX_STRING_CHARS)}]});
+        log.record(&pool, channel, &image).unwrap();
+        log.record(&pool, channel, &json!({"type": "job_completed", "job": job, "message": "done"})).unwrap();
+
+        let events = get_job_events(&pool, "j1").unwrap();
+        let types: Vec<&str> = events.iter().map(|e| e.event_type.as_str()).collect();
+        assert_eq!(types, ["job_started", "token", "token", "image_generated", "job_completed"]);
+        assert!(events[3].payload.len() < MAX_STRING_CHARS + 100);
+        assert!(log.lock().get("s1").is_none());
+    }
+}
@@ -1,8 +1,86 @@
+//! Events agents emit to the platform adapters (desktop UI, server
+//! websocket/SSE, CLI)
+//!
+//! Each `AgentEvent` goes out as an `EventEnvelope`: the event's `type` and
+//! fields at the top level, as adapters have always read them, plus the
+//! schema `version` and the session and job the event belongs to. An event
+//! without `version` predates the envelope and is version 1.
+//!
+//! With the `ts` feature the types here derive their TypeScript definitions;
+//! `npm run gen:events` writes them to `lib/generated/`.
+
 use serde::Serialize;
 use serde_json::Value;
 
+/// Version of the event schema. Bumped when an event changes shape in a way
+/// an adapter would notice (a field removed, renamed or retyped); adding an
+/// event or a field keeps the version.
+pub const EVENT_VERSION: u32 = 1;
+
+/// An event as adapters receive it
+#[derive(Serialize, Clone, Debug)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct EventEnvelope {
+    pub version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+    #[serde(flatten)]
+    pub event: AgentEvent,
+}
+
+impl EventEnvelope {
+    /// `event` as emitted on `channel`
+    pub fn new(channel: &str, event: AgentEvent) -> Self {
+        Self { version: EVENT_VERSION, session_id: None, job_id: None, event }.addressed(channel)
+    }
+
+    fn addressed(mut self, channel: &str) -> Self {
+        let payload = serde_json::to_value(&self.event).unwrap_or_default();
+        let (session_id, job_id) = address(channel, &payload);
+        self.session_id = session_id;
+        self.job_id = job_id;
+        self
+    }
+}
+
+/// Session and job of an event on `channel`, when the event doesn't carry
+/// them itself
+fn address(channel: &str, payload: &Value) -> (Option<String>, Option<String>) {
+    let session_id = channel
+        .strip_prefix("session:")
+        .filter(|_| payload.get("session_id").is_none())
+        .map(str::to_string);
+    let job_id = payload["job"]["id"]
+        .as_str()
+        .filter(|_| payload.get("job_id").is_none())
+        .map(str::to_string);
+    (session_id, job_id)
+}
+
+/// Put an event emitted as JSON (`AgentEvent` or one of the ad hoc events
+/// tools emit) in its envelope. Fields the event already has are left alone,
+/// and anything that isn't an object passes through, so adapters reading the
+/// old flat shape keep working.
+pub fn envelope(channel: &str, mut payload: Value) -> Value {
+    let (session_id, job_id) = address(channel, &payload);
+    let Some(fields) = payload.as_object_mut() else {
+        return payload;
+    };
+    fields.entry("version").or_insert(EVENT_VERSION.into());
+    if let Some(session_id) = session_id {
+        fields.insert("session_id".to_string(), session_id.into());
+    }
+    if let Some(job_id) = job_id {
+        fields.insert("job_id".to_string(), job_id.into());
+    }
+    payload
+}
+
 #[derive(Serialize, Clone, Debug)]
 #[serde(tag = "type", rename_all = "snake_case")]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
 pub enum AgentEvent {
     // Basic Streaming
     Token {
@@ -10,6 +88,18 @@ pub enum AgentEvent {
     },
 
     // Job Lifecycle
+    /// The job waits in the job queue (see agents/queue.rs) behind `position` others
+    JobQueued {
+        job: ExecutionJob,
+        position: usize,
+    },
+    /// The job waits for `provider` to be reachable again (see
+    /// llm/connectivity.rs): queued as pending, or mid-run after losing
+    /// the connection. It resumes on its own.
+    JobPending {
+        job: ExecutionJob,
+        provider: String,
+    },
     JobStarted {
         job: ExecutionJob,
     },
@@ -51,13 +141,140 @@ pub enum AgentEvent {
         error: Option<String>,
     },
 
+    // Workspace changes made by a tool step
+    WorkspaceDiff {
+        job_id: String,
+        step_id: String,
+        files: Vec<crate::snapshots::FileChange>,
+    },
+    // A tool registered a file it produced as an artifact of the job
+    ArtifactCreated {
+        job_id: String,
+        artifact: crate::models::Artifact,
+    },
+    // A tool call is waiting for another job's lock on the workspace (or one file in it)
+    WorkspaceLockWaiting {
+        job_id: String,
+        workspace: String,
+        file: Option<String>,
+        held_by: crate::locks::LockHolder,
+    },
+
     // Planning
     PlanUpdate {
         plan: crate::models::PlanUpdate,
     },
+    /// Plan awaiting user approval (answer with the `approve_plan` command)
+    PlanProposed {
+        job: ExecutionJob,
+        plan: crate::models::PlanUpdate,
+    },
+    /// Changes a dry run held back, sent after its JobCompleted. Answer with
+    /// the `apply_change_plan` or `discard_change_plan` command.
+    ChangePlanProposed {
+        job_id: String,
+        calls: Vec<crate::agents::dry_run::PlannedCall>,
+    },
+
+    // Team mode: a plan task delegated to another agent. The child's own
+    // events are emitted on the parent session channel.
+    ChildJobStarted {
+        parent_job_id: String,
+        job: ExecutionJob,
+        agent_id: String,
+        agent_name: String,
+    },
+    ChildJobCompleted {
+        parent_job_id: String,
+        job: ExecutionJob,
+        agent_id: String,
+        result: Option<String>,
+    },
+
+    /// `config.toml` or the environment changed (emitted on the `config`
+    /// channel). `restart_required` keys keep their old value until restart.
+    ConfigChanged {
+        applied: Vec<String>,
+        restart_required: Vec<String>,
+    },
+
+    // Inter-agent traffic (emitted on the `agent_bus` channel)
+    BusMessage {
+        message: crate::bus::BusMessage,
+        delivery: crate::bus::Delivery,
+    },
+
+    // A webhook POST started this session; `payload` is the request body
+    WebhookReceived {
+        webhook_id: String,
+        agent_id: String,
+        session_id: String,
+        source: String,
+        payload: serde_json::Value,
+    },
+
+    // A skill's declared dependencies are being installed (first use only)
+    SkillProvisioning {
+        skill: String,
+        dependencies: crate::models::SkillDependencies,
+    },
+    SkillProvisioningFailed {
+        skill: String,
+        error: String,
+    },
+    // Progress of a composite skill's recipe, one event per step transition
+    SkillRecipeStep {
+        skill: String,
+        index: usize,
+        step_id: Option<String>,
+        tool: String,
+        status: crate::skills::recipe::StepStatus,
+    },
+    // The agent's tool set changed mid-session; `tools` is the full new list
+    ToolsChanged {
+        added: Vec<String>,
+        removed: Vec<String>,
+        tools: Vec<String>,
+    },
+    // The model failed after retries and the job moved on to the next one
+    // in the agent's fallback chain
+    ModelSwitched {
+        from: crate::llm::ModelRoute,
+        to: crate::llm::ModelRoute,
+        reason: String,
+        rate_limited: bool,
+    },
+    // The critic checked the job's work (see agents/critic.rs); when it is
+    // incomplete and `retrying`, a corrective run follows
+    Verification {
+        complete: bool,
+        critique: String,
+        retrying: bool,
+    },
+    // The agent rewrote its checklist with the `todo` tool; `items` is the
+    // whole list
+    TodoUpdated {
+        job_id: Option<String>,
+        items: Vec<crate::models::TodoItem>,
+    },
+    // generate_image saved new images; `data` in each is a base64 preview
+    ImageGenerated {
+        prompt: String,
+        provider: String,
+        images: Vec<crate::tools::image_gen::GeneratedImage>,
+    },
+
+    // An edit or regenerate rewound the session to `message` (as edited):
+    // the UI drops `removed_ids` before the re-run job's events arrive
+    HistoryRewound {
+        session_id: String,
+        message: crate::models::Message,
+        removed_ids: Vec<String>,
+    },
 
     // Mail
     NewMail {
+        agent_id: String,
         thread_id: String,
         subject: String,
         from_name: String,
@@ -65,17 +282,21 @@ pub enum AgentEvent {
 }
 
 #[derive(Serialize, Clone, Debug)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
 pub struct ExecutionJob {
     pub id: String,
     pub session_id: String,
-    pub status: String, // running, waiting_approval, completed, failed
+    pub status: String, // running, waiting_approval, completed, failed, cancelled
     pub query: String,
     pub steps: Vec<ExecutionStep>,
     pub current_step_index: usize,
     pub created_at: String,
+    /// Version of the agent's configuration the job ran with (see `models::agent_version`)
+    pub agent_version: Option<i32>,
 }
 
 #[derive(Serialize, Clone, Debug)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
 pub struct ExecutionStep {
     pub id: String,
     pub tool_name: String,
@@ -89,3 +310,49 @@ pub struct ExecutionStep {
 pub trait AgentObserver: Send + Sync {
     fn emit(&self, event: &str, payload: Value) -> Result<(), String>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn job() -> ExecutionJob {
+        ExecutionJob {
+            id: "j1".to_string(),
+            session_id: "s1".to_string(),
+            status: "running".to_string(),
+            query: "hi".to_string(),
+            steps: vec![],
+            current_step_index: 0,
+            created_at: "2026-01-01T00:00:00Z".to_string(),
+            agent_version: None,
+        }
+    }
+
+    #[test]
+    fn test_envelope_keeps_the_flat_shape() {
+        let event = AgentEvent::JobStarted { job: job() };
+        let typed = serde_json::to_value(EventEnvelope::new("session:s1", event.clone())).unwrap();
+        let shimmed = envelope("session:s1", serde_json::to_value(&event).unwrap());
+        assert_eq!(typed, shimmed);
+        assert_eq!(typed["type"], "job_started");
+        assert_eq!(typed["job"]["id"], "j1");
+        assert_eq!(typed["version"], EVENT_VERSION);
+        assert_eq!(typed["session_id"], "s1");
+        assert_eq!(typed["job_id"], "j1");
+
+        // Fields an event has are its own
+        let diff = json!({"type": "workspace_diff", "job_id": "j2", "step_id": "x", "files": []});
+        assert_eq!(envelope("session:s1", diff.clone())["job_id"], "j2");
+        let typed = serde_json::to_value(EventEnvelope::new(
+            "session:s1",
+            AgentEvent::WorkspaceDiff { job_id: "j2".to_string(), step_id: "x".to_string(), files: vec![] },
+        ))
+        .unwrap();
+        assert_eq!(typed, envelope("session:s1", diff));
+
+        // Off a session channel, and not an object
+        assert!(envelope("agent_bus", json!({"type": "token", "content": "a"})).get("session_id").is_none());
+        assert_eq!(envelope("session:s1", json!("raw")), json!("raw"));
+    }
+}
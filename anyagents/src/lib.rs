@@ -1,11 +1,36 @@
 pub mod agents;
+pub mod archive;
+pub mod backup;
+pub mod bus;
+pub mod config;
 pub mod database;
+pub mod email;
+pub mod encryption;
+pub mod evals;
+pub mod event_channel;
+pub mod event_log;
 pub mod events;
 pub mod llm;
+pub mod locks;
+pub mod maintenance;
 pub mod mcp;
+pub mod messaging;
 pub mod models;
+pub mod notifications;
+pub mod observers;
+pub mod pages;
 pub mod permissions;
+pub mod prompts;
+pub mod retention;
+pub mod runtime;
 pub mod schema;
+pub mod search;
+pub mod secrets;
+#[cfg(feature = "postgres")]
+pub mod shared_db;
 pub mod skills;
 pub mod tools;
 pub mod snapshots;
+pub mod telemetry;
+
+pub use runtime::{AgentBuilder, Runtime, RuntimeBuilder};
@@ -0,0 +1,189 @@
+//! Models a provider offers, for the model pickers
+//!
+//! `list_models` asks the provider's model listing endpoint (the one
+//! `validate_api_key` pings) and describes each chat model with what we know
+//! of it: context window (`ModelContext`, or what Gemini reports), vision and
+//! tool support. Lists are cached per provider and key for `CACHE_TTL`;
+//! `ModelFilter` narrows them down by capability.
+
+use super::context::ModelContext;
+use super::image::supports_vision;
+use super::validation::models_response;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct ModelInfo {
+    pub id: String,
+    /// Display name, when the provider has one
+    pub name: String,
+    pub context_window: usize,
+    pub max_output: usize,
+    pub vision: bool,
+    pub tools: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ModelFilter {
+    /// Only models that accept images
+    pub vision: bool,
+    /// Only models that can call tools
+    pub tools: bool,
+    pub min_context: Option<usize>,
+}
+
+impl ModelFilter {
+    pub fn matches(&self, model: &ModelInfo) -> bool {
+        (!self.vision || model.vision)
+            && (!self.tools || model.tools)
+            && self.min_context.is_none_or(|min| model.context_window >= min)
+    }
+}
+
+/// Whether `model` supports function calling
+pub fn supports_tools(provider: &str, model: &str) -> bool {
+    let model = model.to_lowercase();
+    match provider {
+        "openai" => !(model.starts_with("o1-mini") || model.starts_with("o1-preview")),
+        "anthropic" => model.starts_with("claude-") && !model.starts_with("claude-2") && !model.starts_with("claude-instant"),
+        "gemini" => model.starts_with("gemini-") && !model.starts_with("gemini-1.0"),
+        _ => false,
+    }
+}
+
+/// OpenAI lists every model of the account: keep the ones that chat
+fn is_openai_chat_model(id: &str) -> bool {
+    let chat = id.starts_with("gpt-") || id.starts_with("chatgpt-") || (id.starts_with('o') && id[1..].starts_with(char::is_numeric));
+    let other = ["-audio", "-realtime", "-transcribe", "-tts", "-search", "-image", "instruct"];
+    chat && !other.iter().any(|part| id.contains(part))
+}
+
+fn describe(provider: &str, id: &str, name: Option<&str>) -> ModelInfo {
+    let context = ModelContext::for_model(provider, id);
+    ModelInfo {
+        id: id.to_string(),
+        name: name.unwrap_or(id).to_string(),
+        context_window: context.context_window,
+        max_output: context.max_output,
+        vision: supports_vision(provider, id),
+        tools: supports_tools(provider, id),
+    }
+}
+
+/// Chat models of a model listing response
+pub fn parse_models(provider: &str, body: &Value) -> Vec<ModelInfo> {
+    let entries = |key: &str| body[key].as_array().cloned().unwrap_or_default();
+    let mut models: Vec<ModelInfo> = match provider {
+        "openai" => entries("data")
+            .iter()
+            .filter_map(|m| m["id"].as_str())
+            .filter(|id| is_openai_chat_model(id))
+            .map(|id| describe(provider, id, None))
+            .collect(),
+        "anthropic" => entries("data")
+            .iter()
+            .filter_map(|m| Some(describe(provider, m["id"].as_str()?, m["display_name"].as_str())))
+            .collect(),
+        "gemini" => entries("models")
+            .iter()
+            .filter(|m| {
+                m["supportedGenerationMethods"]
+                    .as_array()
+                    .is_some_and(|methods| methods.iter().any(|method| method == "generateContent"))
+            })
+            .filter_map(|m| {
+                let id = m["name"].as_str()?.trim_start_matches("models/");
+                let mut model = describe(provider, id, m["displayName"].as_str());
+                // Gemini tells us its limits
+                if let Some(limit) = m["inputTokenLimit"].as_u64() {
+                    model.context_window = limit as usize;
+                }
+                if let Some(limit) = m["outputTokenLimit"].as_u64() {
+                    model.max_output = limit as usize;
+                }
+                Some(model)
+            })
+            .collect(),
+        _ => vec![],
+    };
+    models.sort_by(|a, b| a.id.cmp(&b.id));
+    models
+}
+
+type Cache = HashMap<(String, u64), (Instant, Vec<ModelInfo>)>;
+
+static CACHE: LazyLock<Mutex<Cache>> = LazyLock::new(Default::default);
+
+fn cache_key(provider: &str, api_key: &str) -> (String, u64) {
+    let mut hasher = DefaultHasher::new();
+    api_key.hash(&mut hasher);
+    (provider.to_string(), hasher.finish())
+}
+
+/// The chat models `api_key` gives access to, from the cache unless
+/// `refresh` or older than `CACHE_TTL`
+pub async fn list_models(provider: &str, api_key: &str, refresh: bool) -> Result<Vec<ModelInfo>, String> {
+    let key = cache_key(provider, api_key);
+    if !refresh {
+        let cache = CACHE.lock().unwrap_or_else(|p| p.into_inner());
+        if let Some((fetched, models)) = cache.get(&key).filter(|(fetched, _)| fetched.elapsed() < CACHE_TTL) {
+            log::debug!("Using {} models of {} cached {:?} ago", models.len(), provider, fetched.elapsed());
+            return Ok(models.clone());
+        }
+    }
+
+    let body: Value = models_response(provider, api_key)
+        .await?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid model list from {}: {}", provider, e))?;
+    let models = parse_models(provider, &body);
+    CACHE
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .insert(key, (Instant::now(), models.clone()));
+    Ok(models)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_models_keeps_chat_models_and_their_capabilities() {
+        let openai = json!({ "data": [
+            { "id": "gpt-4o" }, { "id": "text-embedding-3-small" }, { "id": "o1-mini" },
+            { "id": "gpt-4o-realtime-preview" }, { "id": "whisper-1" },
+        ]});
+        let models = parse_models("openai", &openai);
+        assert_eq!(models.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), ["gpt-4o", "o1-mini"]);
+        assert!(models[0].vision && models[0].tools);
+        assert_eq!(models[0].context_window, 128_000);
+        assert!(!models[1].tools);
+
+        let gemini = json!({ "models": [
+            { "name": "models/gemini-2.0-flash", "displayName": "Gemini 2.0 Flash", "inputTokenLimit": 1048576,
+              "outputTokenLimit": 8192, "supportedGenerationMethods": ["generateContent", "countTokens"] },
+            { "name": "models/text-embedding-004", "supportedGenerationMethods": ["embedContent"] },
+        ]});
+        let models = parse_models("gemini", &gemini);
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].id, "gemini-2.0-flash");
+        assert_eq!(models[0].name, "Gemini 2.0 Flash");
+
+        let filter = ModelFilter { vision: true, tools: true, min_context: Some(200_000) };
+        assert!(filter.matches(&models[0]));
+        let anthropic = parse_models("anthropic", &json!({ "data": [{ "id": "claude-2.1", "display_name": "Claude 2.1" }] }));
+        assert!(!filter.matches(&anthropic[0]));
+    }
+}
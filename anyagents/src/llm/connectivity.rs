@@ -0,0 +1,209 @@
+//! Provider reachability
+//!
+//! A call that fails because the provider can't be reached (no network,
+//! DNS, timeouts, 5xx) marks the provider offline; any successful call marks
+//! it online again. While a provider is offline a background probe asks its
+//! model listing endpoint every `PROBE_INTERVAL` whether it answers again,
+//! so recovery is noticed even when nothing else calls it.
+//!
+//! The job queue holds jobs for an offline provider as pending instead of
+//! starting them, and the agent loop waits for its provider instead of
+//! failing a job that lost the connection mid-way. Both wake up on
+//! `changed()`.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::Duration;
+use tokio::sync::futures::Notified;
+use tokio::sync::Notify;
+
+/// Time between probes of an offline provider
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(15);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Whether a provider error means it couldn't be reached, rather than that
+/// it refused the request
+pub fn is_connectivity_error(error: &str) -> bool {
+    let error = error.to_lowercase();
+    [
+        "error sending request",
+        "connection refused",
+        "connection reset",
+        "connection closed",
+        "dns error",
+        "failed to lookup address",
+        "network is unreachable",
+        "no route to host",
+        "timed out",
+        "tcp connect error",
+        "502 bad gateway",
+        "503 service unavailable",
+        "504 gateway timeout",
+    ]
+    .iter()
+    .any(|marker| error.contains(marker))
+}
+
+/// Endpoint that answers without a key when the provider is up
+fn probe_url(provider: &str) -> Option<&'static str> {
+    match provider {
+        "openai" => Some("https://api.openai.com/v1/models"),
+        "anthropic" => Some("https://api.anthropic.com/v1/models"),
+        "gemini" => Some("https://generativelanguage.googleapis.com/v1beta/models"),
+        _ => None,
+    }
+}
+
+/// Whether `provider` answers; any answer below 500 (401 without a key
+/// included) means it is up
+pub async fn probe(provider: &str) -> bool {
+    let Some(url) = probe_url(provider) else {
+        return false;
+    };
+    let Ok(client) = reqwest::Client::builder().timeout(PROBE_TIMEOUT).build() else {
+        return false;
+    };
+    match client.get(url).send().await {
+        Ok(response) => !response.status().is_server_error(),
+        Err(_) => false,
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct OfflineProvider {
+    pub provider: String,
+    pub since: String,
+    /// The error that took it offline
+    pub error: String,
+}
+
+#[derive(Default)]
+pub struct Connectivity {
+    offline: Mutex<HashMap<String, OfflineProvider>>,
+    notify: Notify,
+}
+
+/// Reachability every LLM client reports to
+pub fn global() -> &'static Connectivity {
+    static CONNECTIVITY: OnceLock<Connectivity> = OnceLock::new();
+    CONNECTIVITY.get_or_init(Default::default)
+}
+
+impl Connectivity {
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, OfflineProvider>> {
+        self.offline.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn is_online(&self, provider: &str) -> bool {
+        !self.lock().contains_key(provider)
+    }
+
+    pub fn offline_providers(&self) -> Vec<OfflineProvider> {
+        let mut offline: Vec<OfflineProvider> = self.lock().values().cloned().collect();
+        offline.sort_by(|a, b| a.since.cmp(&b.since));
+        offline
+    }
+
+    /// Completes on the next change; create it before checking `is_online`
+    /// so a change in between isn't missed
+    pub fn changed(&self) -> Notified<'_> {
+        self.notify.notified()
+    }
+
+    /// Mark `provider` offline. Returns true if it was online; the first
+    /// report also starts probing it.
+    pub fn report_offline(&'static self, provider: &str, error: &str) -> bool {
+        let newly = {
+            let mut offline = self.lock();
+            if offline.contains_key(provider) {
+                false
+            } else {
+                offline.insert(
+                    provider.to_string(),
+                    OfflineProvider {
+                        provider: provider.to_string(),
+                        since: chrono::Utc::now().to_rfc3339(),
+                        error: error.to_string(),
+                    },
+                );
+                true
+            }
+        };
+        if newly {
+            log::warn!("Provider {} is unreachable: {}", provider, error);
+            self.notify.notify_waiters();
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                let provider = provider.to_string();
+                runtime.spawn(async move {
+                    while !self.is_online(&provider) {
+                        tokio::time::sleep(PROBE_INTERVAL).await;
+                        if probe(&provider).await {
+                            self.report_online(&provider);
+                        }
+                    }
+                });
+            }
+        }
+        newly
+    }
+
+    /// Mark `provider` online. Returns true if it was offline.
+    pub fn report_online(&self, provider: &str) -> bool {
+        let was_offline = self.lock().remove(provider).is_some();
+        if was_offline {
+            log::info!("Provider {} is reachable again", provider);
+            self.notify.notify_waiters();
+        }
+        was_offline
+    }
+
+    /// Wait until `provider` is online; false if it still isn't after `timeout`
+    pub async fn wait_online(&self, provider: &str, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let changed = self.changed();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+            if self.is_online(provider) {
+                return true;
+            }
+            if tokio::time::timeout_at(deadline, changed).await.is_err() {
+                return self.is_online(provider);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_connectivity_error() {
+        assert!(is_connectivity_error(
+            "HttpError: error sending request for url (https://api.openai.com/v1/chat/completions)"
+        ));
+        assert!(is_connectivity_error("dns error: failed to lookup address information"));
+        assert!(is_connectivity_error("ProviderError: 503 Service Unavailable"));
+        assert!(!is_connectivity_error("ProviderError: 401 Unauthorized: invalid api key"));
+        assert!(!is_connectivity_error("429 Too Many Requests"));
+    }
+
+    #[tokio::test]
+    async fn test_wait_online_wakes_on_report() {
+        static CONNECTIVITY: OnceLock<Connectivity> = OnceLock::new();
+        let connectivity = CONNECTIVITY.get_or_init(Default::default);
+        // Not a provider we can probe, so only the report below brings it back
+        assert!(connectivity.report_offline("local", "connection refused"));
+        assert!(!connectivity.report_offline("local", "connection refused"));
+        assert!(!connectivity.wait_online("local", Duration::from_millis(20)).await);
+
+        let waiter = tokio::spawn(connectivity.wait_online("local", Duration::from_secs(5)));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(connectivity.offline_providers()[0].provider, "local");
+        assert!(connectivity.report_online("local"));
+        assert!(waiter.await.unwrap());
+        assert!(connectivity.offline_providers().is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+//! Context windows of the models we talk to
+//!
+//! How much history an agent keeps and how long a tool result may get
+//! depends on the model: a local 8k Llama needs a much tighter budget than a
+//! 200k Claude. `ModelContext::for_model` looks the model up in a table of
+//! known families (longest prefix wins); models it doesn't know get a
+//! conservative default. Users can describe their own models in the
+//! `model_context_windows` setting, e.g.
+//! `{"ollama/my-finetune": {"context_window": 32768, "max_output": 4096}}`,
+//! applied with `set_overrides`.
+//!
+//! Tokens are counted with the model's BPE where it is public (OpenAI's
+//! o200k_base and cl100k_base via tiktoken-rs). Anthropic and Gemini don't
+//! publish theirs; cl100k_base is a close enough stand-in for budgeting.
+
+use crate::database::DbPool;
+use crate::models::settings::get_setting;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{LazyLock, OnceLock, RwLock};
+use tiktoken_rs::CoreBPE;
+
+/// Setting holding user-defined models, keyed `provider/model`
+pub const SETTINGS_KEY: &str = "model_context_windows";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Tokenizer {
+    O200kBase,
+    #[default]
+    Cl100kBase,
+    /// Four characters a token; when no BPE is available
+    Chars,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelContext {
+    /// Tokens the model accepts, prompt and answer together
+    pub context_window: usize,
+    /// Tokens kept free for the answer
+    pub max_output: usize,
+    #[serde(default)]
+    pub tokenizer: Tokenizer,
+}
+
+const fn ctx(context_window: usize, max_output: usize, tokenizer: Tokenizer) -> ModelContext {
+    ModelContext { context_window, max_output, tokenizer }
+}
+
+use Tokenizer::{Cl100kBase, O200kBase};
+
+/// Known model families by provider and model prefix
+const KNOWN: &[(&str, &str, ModelContext)] = &[
+    ("openai", "gpt-5", ctx(400_000, 128_000, O200kBase)),
+    ("openai", "gpt-4.1", ctx(1_047_576, 32_768, O200kBase)),
+    ("openai", "gpt-4o", ctx(128_000, 16_384, O200kBase)),
+    ("openai", "o1-mini", ctx(128_000, 65_536, O200kBase)),
+    ("openai", "o1", ctx(200_000, 100_000, O200kBase)),
+    ("openai", "o3", ctx(200_000, 100_000, O200kBase)),
+    ("openai", "o4", ctx(200_000, 100_000, O200kBase)),
+    ("openai", "gpt-4-turbo", ctx(128_000, 4_096, Cl100kBase)),
+    ("openai", "gpt-4-1106", ctx(128_000, 4_096, Cl100kBase)),
+    ("openai", "gpt-4-0125", ctx(128_000, 4_096, Cl100kBase)),
+    ("openai", "gpt-4-32k", ctx(32_768, 4_096, Cl100kBase)),
+    ("openai", "gpt-4", ctx(8_192, 4_096, Cl100kBase)),
+    ("openai", "gpt-3.5", ctx(16_385, 4_096, Cl100kBase)),
+    ("anthropic", "claude-3-", ctx(200_000, 4_096, Cl100kBase)),
+    ("anthropic", "claude-", ctx(200_000, 8_192, Cl100kBase)),
+    ("gemini", "gemini-1.5-pro", ctx(2_097_152, 8_192, Cl100kBase)),
+    ("gemini", "gemini-1.0", ctx(32_760, 8_192, Cl100kBase)),
+    ("gemini", "gemini-pro", ctx(32_760, 8_192, Cl100kBase)),
+    ("gemini", "gemini-", ctx(1_048_576, 8_192, Cl100kBase)),
+    ("ollama", "llama3.1", ctx(131_072, 4_096, Cl100kBase)),
+    ("ollama", "llama3.2", ctx(131_072, 4_096, Cl100kBase)),
+    ("ollama", "llama3.3", ctx(131_072, 4_096, Cl100kBase)),
+    ("ollama", "qwen", ctx(32_768, 4_096, Cl100kBase)),
+    ("ollama", "mistral", ctx(32_768, 4_096, Cl100kBase)),
+];
+
+/// Models nobody told us about; local ones are usually run with small windows
+const DEFAULT_HOSTED: ModelContext = ctx(32_768, 4_096, Cl100kBase);
+const DEFAULT_LOCAL: ModelContext = ctx(8_192, 2_048, Cl100kBase);
+
+static OVERRIDES: LazyLock<RwLock<HashMap<String, ModelContext>>> = LazyLock::new(Default::default);
+
+/// Replace the user-defined models, keyed `provider/model`
+pub fn set_overrides(overrides: HashMap<String, ModelContext>) {
+    *OVERRIDES.write().unwrap_or_else(|p| p.into_inner()) = overrides;
+}
+
+/// User-defined models from the `model_context_windows` setting
+pub fn load_overrides(pool: &DbPool) -> HashMap<String, ModelContext> {
+    get_setting(pool, SETTINGS_KEY)
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_default()
+}
+
+fn bpe(tokenizer: Tokenizer) -> Option<&'static CoreBPE> {
+    static O200K: OnceLock<Option<CoreBPE>> = OnceLock::new();
+    static CL100K: OnceLock<Option<CoreBPE>> = OnceLock::new();
+    match tokenizer {
+        Tokenizer::O200kBase => O200K.get_or_init(|| tiktoken_rs::o200k_base().ok()).as_ref(),
+        Tokenizer::Cl100kBase => CL100K.get_or_init(|| tiktoken_rs::cl100k_base().ok()).as_ref(),
+        Tokenizer::Chars => None,
+    }
+}
+
+impl ModelContext {
+    pub fn for_model(provider: &str, model: &str) -> Self {
+        let model = model.to_lowercase();
+        let overrides = OVERRIDES.read().unwrap_or_else(|p| p.into_inner());
+        if let Some(found) = overrides.get(&format!("{}/{}", provider, model)) {
+            return *found;
+        }
+        KNOWN
+            .iter()
+            .filter(|(p, prefix, _)| *p == provider && model.starts_with(prefix))
+            .max_by_key(|(_, prefix, _)| prefix.len())
+            .map(|(_, _, context)| *context)
+            .unwrap_or(if provider == "ollama" { DEFAULT_LOCAL } else { DEFAULT_HOSTED })
+    }
+
+    pub fn count_tokens(&self, text: &str) -> usize {
+        match bpe(self.tokenizer) {
+            Some(bpe) => bpe.encode_ordinary(text).len(),
+            None => text.len().div_ceil(4),
+        }
+    }
+
+    /// Tokens the conversation history may take: the window minus the
+    /// answer and a tenth for the system prompt and tool schemas
+    pub fn history_budget(&self) -> usize {
+        let reserved = self.max_output + self.context_window / 10;
+        self.context_window.saturating_sub(reserved).max(1_024)
+    }
+
+    /// Tokens a single tool result (or user message) may take before it is
+    /// truncated; assistant messages get twice as much
+    pub fn message_budget(&self) -> usize {
+        (self.context_window / 32).clamp(500, 8_000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_prefers_longest_prefix_and_overrides() {
+        assert_eq!(ModelContext::for_model("openai", "gpt-4").context_window, 8_192);
+        assert_eq!(ModelContext::for_model("openai", "gpt-4o-mini").context_window, 128_000);
+        assert_eq!(ModelContext::for_model("openai", "GPT-4-turbo").context_window, 128_000);
+        assert_eq!(ModelContext::for_model("anthropic", "claude-3-haiku").max_output, 4_096);
+        assert_eq!(ModelContext::for_model("gemini", "gemini-1.5-pro-002").context_window, 2_097_152);
+        assert_eq!(ModelContext::for_model("ollama", "phi3"), DEFAULT_LOCAL);
+
+        let custom = ctx(65_536, 4_096, Tokenizer::Chars);
+        set_overrides(HashMap::from([("ollama/phi3".to_string(), custom)]));
+        assert_eq!(ModelContext::for_model("ollama", "phi3"), custom);
+        set_overrides(HashMap::new());
+    }
+
+    #[test]
+    fn test_budgets_scale_with_the_window() {
+        let small = ModelContext::for_model("openai", "gpt-4");
+        let large = ModelContext::for_model("anthropic", "claude-sonnet-4-5");
+        assert!(small.history_budget() < 4_000);
+        assert!(large.history_budget() > 150_000);
+        assert_eq!(small.message_budget(), 500);
+        assert_eq!(large.message_budget(), 6_250);
+    }
+
+    #[test]
+    fn test_count_tokens() {
+        let context = ModelContext::for_model("openai", "gpt-4o");
+        let tokens = context.count_tokens("The quick brown fox jumps over the lazy dog.");
+        assert!((8..=12).contains(&tokens), "{}", tokens);
+        assert_eq!(ctx(1, 1, Tokenizer::Chars).count_tokens("abcdefgh"), 2);
+    }
+}
@@ -0,0 +1,104 @@
+//! Provider fallback chains
+//!
+//! An agent can list fallback models in its execution settings:
+//!
+//! ```json
+//! {"fallbacks": [{"provider": "anthropic", "model": "claude-3-5-sonnet-latest"}]}
+//! ```
+//!
+//! When the current model still fails after the agent loop's retries, the
+//! loop moves on to the next model in the chain for the rest of the job and
+//! emits a `model_switched` event. Switching builds a new `LlmClient`, so the
+//! prompt and history are re-encoded for the new provider (images are
+//! dropped for models without vision). Assistant messages record the model
+//! that answered in their `metadata_json`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct ModelRoute {
+    pub provider: String,
+    pub model: String,
+}
+
+impl ModelRoute {
+    pub fn new(provider: &str, model: &str) -> Self {
+        Self {
+            provider: provider.to_string(),
+            model: model.to_string(),
+        }
+    }
+
+    /// `fallbacks` of an agent's execution settings, in order. Entries
+    /// without a provider or model are skipped.
+    pub fn fallbacks_from_execution_settings(settings: &Value) -> Vec<Self> {
+        settings
+            .get("fallbacks")
+            .and_then(|f| f.as_array())
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter_map(|entry| serde_json::from_value::<Self>(entry.clone()).ok())
+                    .filter(|route| !route.provider.is_empty() && !route.model.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// `metadata_json` of an assistant message this model wrote
+    pub fn message_metadata(&self, fallback: bool) -> String {
+        json!({ "answered_by": self, "fallback": fallback }).to_string()
+    }
+}
+
+impl std::fmt::Display for ModelRoute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.provider, self.model)
+    }
+}
+
+/// Whether a provider error means the request was rate limited
+pub fn is_rate_limit(error: &str) -> bool {
+    let error = error.to_lowercase();
+    ["429", "rate limit", "rate_limit", "too many requests", "resource_exhausted", "quota"]
+        .iter()
+        .any(|marker| error.contains(marker))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fallbacks_from_execution_settings() {
+        let settings = json!({
+            "mode": "autopilot",
+            "fallbacks": [
+                {"provider": "anthropic", "model": "claude-3-5-sonnet-latest"},
+                {"provider": "gemini"},
+                {"provider": "openai", "model": "gpt-4o-mini"}
+            ]
+        });
+        assert_eq!(
+            ModelRoute::fallbacks_from_execution_settings(&settings),
+            vec![
+                ModelRoute::new("anthropic", "claude-3-5-sonnet-latest"),
+                ModelRoute::new("openai", "gpt-4o-mini"),
+            ]
+        );
+        assert!(ModelRoute::fallbacks_from_execution_settings(&json!({})).is_empty());
+
+        let metadata: Value = serde_json::from_str(&ModelRoute::new("openai", "gpt-4o").message_metadata(true)).unwrap();
+        assert_eq!(metadata["answered_by"]["model"], "gpt-4o");
+        assert_eq!(metadata["fallback"], true);
+    }
+
+    #[test]
+    fn test_is_rate_limit() {
+        assert!(is_rate_limit("HTTP 429 Too Many Requests"));
+        assert!(is_rate_limit("Rate limit reached for gpt-4o"));
+        assert!(!is_rate_limit("invalid api key"));
+    }
+}
@@ -0,0 +1,270 @@
+//! Image attachments for vision-capable models
+//!
+//! Images travel as base64 inside the user message (`UserContent::Image`).
+//! User messages persist theirs in `messages.metadata_json` under `images`,
+//! so reloaded history shows the model the same pictures. Tools hand images
+//! to the agent loop through the `IMAGES_KEY` field of their result.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rig::completion::message::{ImageMediaType, UserContent};
+use rig::completion::Message;
+use rig::one_or_many::OneOrMany;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+/// Largest image accepted, before base64 encoding
+pub const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+/// Most images attached to a single message
+pub const MAX_IMAGES_PER_MESSAGE: usize = 8;
+/// Field of a tool result holding images for the model; removed before the
+/// result is rendered as text
+pub const IMAGES_KEY: &str = "_images";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImageAttachment {
+    /// MIME type: image/png, image/jpeg, image/gif or image/webp
+    pub media_type: String,
+    /// Base64-encoded bytes
+    pub data: String,
+}
+
+impl ImageAttachment {
+    /// Image from base64 `data` or a `data:image/...;base64,` URL
+    pub fn from_base64(data: &str, media_type: Option<&str>) -> Result<Self, String> {
+        let (url_type, data) = match data.strip_prefix("data:") {
+            Some(rest) => {
+                let (header, payload) = rest.split_once(',').ok_or("Malformed data URL")?;
+                let mime = header
+                    .strip_suffix(";base64")
+                    .ok_or("Only base64 data URLs are supported")?;
+                (Some(mime), payload)
+            }
+            None => (None, data),
+        };
+        let media_type = media_type.or(url_type).ok_or("Missing image media type")?;
+        let image = Self {
+            media_type: media_type.to_lowercase(),
+            data: data.trim().to_string(),
+        };
+        image.validate()?;
+        Ok(image)
+    }
+
+    /// Image read from `path`; the type comes from the extension
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let media_type = media_type_for_path(path)
+            .ok_or_else(|| format!("Unsupported image type: {}", path.display()))?;
+        let size = std::fs::metadata(path).map_err(|e| e.to_string())?.len() as usize;
+        if size > MAX_IMAGE_BYTES {
+            return Err(format!(
+                "Image is {} bytes; the limit is {} bytes",
+                size, MAX_IMAGE_BYTES
+            ));
+        }
+        let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+        Ok(Self {
+            media_type: media_type.to_string(),
+            data: STANDARD.encode(bytes),
+        })
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if rig_media_type(&self.media_type).is_none() {
+            return Err(format!("Unsupported image type: {}", self.media_type));
+        }
+        let bytes = STANDARD
+            .decode(&self.data)
+            .map_err(|e| format!("Invalid base64 image data: {}", e))?;
+        if bytes.is_empty() {
+            return Err("Image is empty".to_string());
+        }
+        if bytes.len() > MAX_IMAGE_BYTES {
+            return Err(format!(
+                "Image is {} bytes; the limit is {} bytes",
+                bytes.len(),
+                MAX_IMAGE_BYTES
+            ));
+        }
+        Ok(())
+    }
+
+    fn to_content(&self) -> UserContent {
+        UserContent::image_base64(self.data.clone(), rig_media_type(&self.media_type), None)
+    }
+}
+
+pub fn media_type_for_path(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    match ext.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+fn rig_media_type(media_type: &str) -> Option<ImageMediaType> {
+    match media_type {
+        "image/png" => Some(ImageMediaType::PNG),
+        "image/jpeg" | "image/jpg" => Some(ImageMediaType::JPEG),
+        "image/gif" => Some(ImageMediaType::GIF),
+        "image/webp" => Some(ImageMediaType::WEBP),
+        _ => None,
+    }
+}
+
+/// Whether `model` accepts image input
+pub fn supports_vision(provider: &str, model: &str) -> bool {
+    let model = model.to_lowercase();
+    match provider {
+        "openai" => {
+            !(model.starts_with("gpt-3.5")
+                || model == "gpt-4"
+                || model.starts_with("gpt-4-0")
+                || model.starts_with("o1-mini")
+                || model.starts_with("o3-mini"))
+        }
+        "anthropic" => model.starts_with("claude-") && !model.starts_with("claude-2") && !model.starts_with("claude-instant"),
+        "gemini" => model.starts_with("gemini-") && model != "gemini-pro" && !model.starts_with("gemini-1.0"),
+        _ => false,
+    }
+}
+
+/// User message with `text` followed by `images`
+pub fn user_message_with_images(text: &str, images: &[ImageAttachment]) -> Message {
+    if images.is_empty() {
+        return Message::user(text);
+    }
+    let mut content = vec![UserContent::text(text)];
+    content.extend(images.iter().map(ImageAttachment::to_content));
+    Message::User {
+        content: OneOrMany::many(content).expect("content has at least the text"),
+    }
+}
+
+/// `message` with its images replaced by a note, for models without vision
+pub fn strip_images(message: Message) -> Message {
+    match message {
+        Message::User { content } if content.iter().any(|c| matches!(c, UserContent::Image(_))) => {
+            let kept: Vec<UserContent> = content
+                .into_iter()
+                .map(|c| match c {
+                    UserContent::Image(_) => {
+                        UserContent::text("[image omitted: the current model cannot view images]")
+                    }
+                    other => other,
+                })
+                .collect();
+            Message::User {
+                content: OneOrMany::many(kept).expect("non-empty content stays non-empty"),
+            }
+        }
+        other => other,
+    }
+}
+
+/// `metadata_json` value recording `images` on a user message
+pub fn images_metadata(images: &[ImageAttachment]) -> Option<String> {
+    if images.is_empty() {
+        return None;
+    }
+    Some(serde_json::json!({ "images": images }).to_string())
+}
+
+/// Images recorded in a message's `metadata_json`
+pub fn images_from_metadata(metadata_json: Option<&str>) -> Vec<ImageAttachment> {
+    metadata_json
+        .and_then(|json| serde_json::from_str::<Value>(json).ok())
+        .and_then(|mut value| serde_json::from_value(value.get_mut("images")?.take()).ok())
+        .unwrap_or_default()
+}
+
+/// A persisted user message, with the images it was sent with
+pub fn stored_user_message(content: &str, metadata_json: Option<&str>) -> Message {
+    user_message_with_images(content, &images_from_metadata(metadata_json))
+}
+
+/// Remove and return the images a tool attached to its result
+pub fn take_images(result: &mut Value) -> Vec<ImageAttachment> {
+    result
+        .as_object_mut()
+        .and_then(|obj| obj.remove(IMAGES_KEY))
+        .and_then(|images| serde_json::from_value(images).ok())
+        .unwrap_or_default()
+}
+
+/// Check attachments coming from a client and normalize data URLs
+pub fn validate_attachments(images: Vec<ImageAttachment>) -> Result<Vec<ImageAttachment>, String> {
+    if images.len() > MAX_IMAGES_PER_MESSAGE {
+        return Err(format!(
+            "At most {} images can be attached to a message",
+            MAX_IMAGES_PER_MESSAGE
+        ));
+    }
+    images
+        .into_iter()
+        .map(|image| ImageAttachment::from_base64(&image.data, Some(&image.media_type)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const PIXEL: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+
+    fn image_count(message: &Message) -> usize {
+        match message {
+            Message::User { content } => content
+                .iter()
+                .filter(|c| matches!(c, UserContent::Image(_)))
+                .count(),
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn test_from_base64_accepts_data_urls() {
+        let image = ImageAttachment::from_base64(&format!("data:image/png;base64,{}", PIXEL), None).unwrap();
+        assert_eq!(image.media_type, "image/png");
+        assert_eq!(image.data, PIXEL);
+
+        assert!(ImageAttachment::from_base64(PIXEL, None).is_err());
+        assert!(ImageAttachment::from_base64(PIXEL, Some("image/tiff")).is_err());
+        assert!(ImageAttachment::from_base64("not base64!", Some("image/png")).is_err());
+    }
+
+    #[test]
+    fn test_metadata_round_trip() {
+        let image = ImageAttachment::from_base64(PIXEL, Some("image/png")).unwrap();
+        let metadata = images_metadata(&[image.clone()]);
+        assert_eq!(images_from_metadata(metadata.as_deref()), vec![image]);
+        assert!(images_metadata(&[]).is_none());
+        assert!(images_from_metadata(Some(r#"{"mode":"fast"}"#)).is_empty());
+
+        let message = stored_user_message("what is this?", metadata.as_deref());
+        assert_eq!(image_count(&message), 1);
+        assert_eq!(image_count(&strip_images(message)), 0);
+    }
+
+    #[test]
+    fn test_take_images() {
+        let mut result = json!({"path": "a.png", IMAGES_KEY: [{"media_type": "image/png", "data": PIXEL}]});
+        assert_eq!(take_images(&mut result).len(), 1);
+        assert_eq!(result, json!({"path": "a.png"}));
+        assert!(take_images(&mut json!("text")).is_empty());
+    }
+
+    #[test]
+    fn test_supports_vision() {
+        assert!(supports_vision("openai", "gpt-4o"));
+        assert!(!supports_vision("openai", "gpt-3.5-turbo"));
+        assert!(supports_vision("anthropic", "claude-3-5-sonnet-20241022"));
+        assert!(supports_vision("gemini", "gemini-2.0-flash"));
+        assert!(!supports_vision("ollama", "llama3"));
+    }
+}
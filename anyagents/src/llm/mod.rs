@@ -12,6 +12,41 @@ use rig::completion::{Chat, Message, Prompt};
 use rig::providers::{anthropic, gemini, openai};
 use rig::streaming::{StreamedAssistantContent, StreamingChat, StreamingPrompt};
 
+pub mod catalog;
+pub mod connectivity;
+pub mod context;
+pub mod fallback;
+pub mod image;
+pub mod params;
+pub mod scheduler;
+pub mod structured;
+pub mod usage;
+mod validation;
+pub use catalog::{list_models, ModelFilter, ModelInfo};
+pub use context::ModelContext;
+pub use fallback::ModelRoute;
+pub use image::ImageAttachment;
+pub use params::GenerationParams;
+pub use validation::validate_api_key;
+
+/// Apply the client's generation parameters and extra request parameters
+/// to a rig agent builder of any provider
+macro_rules! configure {
+    ($client:expr, $builder:expr) => {{
+        let mut builder = $builder;
+        if let Some(temperature) = $client.params.temperature {
+            builder = builder.temperature(temperature);
+        }
+        if let Some(max_tokens) = $client.params.max_tokens {
+            builder = builder.max_tokens(max_tokens);
+        }
+        if let Some(params) = $client.request_params() {
+            builder = builder.additional_params(params);
+        }
+        builder
+    }};
+}
+
 /// A token from the LLM stream
 #[derive(Debug, Clone)]
 pub enum StreamToken {
@@ -21,11 +56,20 @@ pub enum StreamToken {
 }
 
 /// Unified LLM client that abstracts provider differences
+#[derive(Clone)]
 pub struct LlmClient {
     provider: String,
     model: String,
     preamble: Option<String>,
     api_key: Option<String>,
+    /// Queue this client's calls wait in (see `scheduler`)
+    session: Option<String>,
+    /// Job whose usage the calls count toward (see `usage`)
+    job: Option<String>,
+    /// Extra request parameters, e.g. a JSON mode (see `structured`)
+    additional_params: Option<serde_json::Value>,
+    /// Temperature, answer length and sampling (see `params`)
+    params: GenerationParams,
 }
 
 impl LlmClient {
@@ -36,6 +80,10 @@ impl LlmClient {
             model: model.to_string(),
             preamble: None,
             api_key: None,
+            session: None,
+            job: None,
+            additional_params: None,
+            params: GenerationParams::default(),
         }
     }
 
@@ -45,6 +93,48 @@ impl LlmClient {
         self
     }
 
+    /// The same client and preamble on another provider and model. The API
+    /// key belongs to the old provider and is not carried over; generation
+    /// parameters are fitted to what the new provider accepts.
+    pub fn with_route(&self, route: &ModelRoute) -> Self {
+        Self {
+            provider: route.provider.clone(),
+            model: route.model.clone(),
+            preamble: self.preamble.clone(),
+            api_key: None,
+            session: self.session.clone(),
+            job: self.job.clone(),
+            additional_params: self.additional_params.clone(),
+            params: self.params.for_provider(&route.provider),
+        }
+    }
+
+    /// Sample with the agent's temperature, top_p, answer length, stop
+    /// sequences and seed
+    pub fn with_params(mut self, params: GenerationParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Additional parameters of a request: the client's own plus the
+    /// generation parameters rig has no builder method for
+    fn request_params(&self) -> Option<serde_json::Value> {
+        params::merge_params(self.additional_params.clone(), self.params.provider_params(&self.provider))
+    }
+
+    /// Calls wait their turn with the session's other calls; without a
+    /// session they queue as background work
+    pub fn with_session(mut self, session_id: &str) -> Self {
+        self.session = Some(session_id.to_string());
+        self
+    }
+
+    /// Count token usage toward `job_id` as well as the session
+    pub fn with_job(mut self, job_id: &str) -> Self {
+        self.job = Some(job_id.to_string());
+        self
+    }
+
     /// Set the API key explicitly
     pub fn with_api_key(mut self, api_key: &str) -> Self {
         if !api_key.is_empty() {
@@ -72,10 +162,82 @@ impl LlmClient {
         Ok(())
     }
 
+    /// Whether the configured model accepts image input
+    pub fn supports_vision(&self) -> bool {
+        image::supports_vision(&self.provider, &self.model)
+    }
+
+    /// Wait for the shared scheduler to let a call of this size through
+    async fn acquire(&self, message: &Message, history: &[Message]) -> scheduler::Permit {
+        use crate::agents::optimizations::get_message_content;
+
+        let context = ModelContext::for_model(&self.provider, &self.model);
+        let tokens = context.count_tokens(self.preamble.as_deref().unwrap_or_default())
+            + std::iter::once(message)
+                .chain(history)
+                .map(|m| context.count_tokens(&get_message_content(m)))
+                .sum::<usize>();
+        let started = std::time::Instant::now();
+        let permit = scheduler::global()
+            .acquire(
+                &self.provider,
+                self.session.as_deref().unwrap_or(scheduler::BACKGROUND),
+                u32::try_from(tokens).unwrap_or(u32::MAX),
+            )
+            .await;
+        tracing::Span::current().record("queued_ms", started.elapsed().as_millis() as u64);
+        permit
+    }
+
+    /// Report how the call went: rate limits back the provider off,
+    /// unreachable providers are marked offline (see `connectivity`)
+    fn release(&self, permit: scheduler::Permit, result: &Result<String, String>) {
+        match result {
+            Ok(response) => {
+                let tokens = ModelContext::for_model(&self.provider, &self.model).count_tokens(response);
+                permit.add_tokens(u32::try_from(tokens).unwrap_or(u32::MAX));
+                permit.succeeded();
+                connectivity::global().report_online(&self.provider);
+            }
+            Err(e) if fallback::is_rate_limit(e) => permit.rate_limited(),
+            Err(e) if connectivity::is_connectivity_error(e) => {
+                connectivity::global().report_offline(&self.provider, e);
+            }
+            Err(_) => {}
+        }
+    }
+
+    /// Images are replaced by a note when the model cannot see them
+    fn prepare(&self, message: Message) -> Message {
+        if self.supports_vision() {
+            message
+        } else {
+            image::strip_images(message)
+        }
+    }
+
     /// Simple prompt without history (non-streaming)
     pub async fn prompt(&self, message: &str) -> Result<String, String> {
+        self.prompt_message(Message::user(message)).await
+    }
+
+    /// Prompt with a full user message, e.g. one carrying images
+    #[tracing::instrument(
+        name = "llm.call",
+        skip_all,
+        fields(provider = %self.provider, model = %self.model, queued_ms = tracing::field::Empty)
+    )]
+    pub async fn prompt_message(&self, message: Message) -> Result<String, String> {
         self.check_api_key()?;
+        let permit = self.acquire(&message, &[]).await;
+        let result = self.send_prompt(message).await;
+        self.release(permit, &result);
+        result
+    }
+
+    async fn send_prompt(&self, message: Message) -> Result<String, String> {
         let preamble = self.preamble.clone().unwrap_or_default();
+        let message = self.prepare(message);
 
         match self.provider.as_str() {
             "openai" => {
@@ -84,7 +246,7 @@ impl LlmClient {
                 } else {
                     Ok(openai::Client::from_env())
                 }.map_err(|e| e.to_string())?;
-                let agent = client.agent(&self.model).preamble(&preamble).build();
+                let agent = configure!(self, client.agent(&self.model).preamble(&preamble)).build();
                 agent.prompt(message).await.map_err(|e| e.to_string())
             }
             "gemini" => {
@@ -93,7 +255,7 @@ impl LlmClient {
                 } else {
                     Ok(gemini::Client::from_env())
                 }.map_err(|e| e.to_string())?;
-                let agent = client.agent(&self.model).preamble(&preamble).build();
+                let agent = configure!(self, client.agent(&self.model).preamble(&preamble)).build();
                 agent.prompt(message).await.map_err(|e| e.to_string())
             }
             "anthropic" => {
@@ -102,7 +264,7 @@ impl LlmClient {
                 } else {
                     Ok(anthropic::Client::from_env())
                 }.map_err(|e| e.to_string())?;
-                let agent = client.agent(&self.model).preamble(&preamble).build();
+                let agent = configure!(self, client.agent(&self.model).preamble(&preamble)).build();
                 agent.prompt(message).await.map_err(|e| e.to_string())
             }
             _ => Err(format!("Unsupported provider: {}", self.provider)),
@@ -111,8 +273,27 @@ impl LlmClient {
 
     /// Chat with history (non-streaming)
     pub async fn chat(&self, message: &str, history: Vec<Message>) -> Result<String, String> {
+        self.chat_message(Message::user(message), history).await
+    }
+
+    /// Chat with a full user message, e.g. one carrying images
+    #[tracing::instrument(
+        name = "llm.call",
+        skip_all,
+        fields(provider = %self.provider, model = %self.model, queued_ms = tracing::field::Empty)
+    )]
+    pub async fn chat_message(&self, message: Message, history: Vec<Message>) -> Result<String, String> {
         self.check_api_key()?;
+        let permit = self.acquire(&message, &history).await;
+        let result = self.send_chat(message, history).await;
+        self.release(permit, &result);
+        result
+    }
+
+    async fn send_chat(&self, message: Message, history: Vec<Message>) -> Result<String, String> {
         let preamble = self.preamble.clone().unwrap_or_default();
+        let message = self.prepare(message);
+        let history = history.into_iter().map(|m| self.prepare(m)).collect::<Vec<_>>();
 
         match self.provider.as_str() {
             "openai" => {
@@ -121,7 +302,7 @@ impl LlmClient {
                 } else {
                     Ok(openai::Client::from_env())
                 }.map_err(|e| e.to_string())?;
-                let agent = client.agent(&self.model).preamble(&preamble).build();
+                let agent = configure!(self, client.agent(&self.model).preamble(&preamble)).build();
                 agent
                     .chat(message, history)
                     .await
@@ -134,7 +315,7 @@ impl LlmClient {
                 } else {
                     Ok(gemini::Client::from_env())
                 }.map_err(|e| e.to_string())?;
-                let agent = client.agent(&self.model).preamble(&preamble).build();
+                let agent = configure!(self, client.agent(&self.model).preamble(&preamble)).build();
                 agent
                     .chat(message, history)
                     .await
@@ -147,7 +328,7 @@ impl LlmClient {
                 } else {
                     Ok(anthropic::Client::from_env())
                 }.map_err(|e| e.to_string())?;
-                let agent = client.agent(&self.model).preamble(&preamble).build();
+                let agent = configure!(self, client.agent(&self.model).preamble(&preamble)).build();
                 agent
                     .chat(message, history)
                     .await
@@ -159,11 +340,26 @@ impl LlmClient {
     }
 
     /// Streaming prompt - calls the callback for each token
+    #[tracing::instrument(
+        name = "llm.call",
+        skip_all,
+        fields(provider = %self.provider, model = %self.model, queued_ms = tracing::field::Empty)
+    )]
     pub async fn stream_prompt<F>(&self, message: &str, on_token: &F) -> Result<String, String>
     where
         F: Fn(String) + Send + Sync,
     {
         self.check_api_key()?;
+        let permit = self.acquire(&Message::user(message), &[]).await;
+        let result = self.send_stream_prompt(message, on_token).await;
+        self.release(permit, &result);
+        result
+    }
+
+    async fn send_stream_prompt<F>(&self, message: &str, on_token: &F) -> Result<String, String>
+    where
+        F: Fn(String) + Send + Sync,
+    {
         let preamble = self.preamble.clone().unwrap_or_default();
         let mut full_response = String::new();
 
@@ -174,7 +370,7 @@ impl LlmClient {
                 } else {
                     Ok(openai::Client::from_env())
                 }.map_err(|e| e.to_string())?;
-                let agent = client.agent(&self.model).preamble(&preamble).build();
+                let agent = configure!(self, client.agent(&self.model).preamble(&preamble)).build();
                 let mut stream = agent.stream_prompt(message).await;
 
                 while let Some(chunk) = stream.next().await {
@@ -201,7 +397,7 @@ impl LlmClient {
                 } else {
                     Ok(gemini::Client::from_env())
                 }.map_err(|e| e.to_string())?;
-                let agent = client.agent(&self.model).preamble(&preamble).build();
+                let agent = configure!(self, client.agent(&self.model).preamble(&preamble)).build();
                 let mut stream = agent.stream_prompt(message).await;
 
                 while let Some(chunk) = stream.next().await {
@@ -228,7 +424,7 @@ impl LlmClient {
                 } else {
                     Ok(anthropic::Client::from_env())
                 }.map_err(|e| e.to_string())?;
-                let agent = client.agent(&self.model).preamble(&preamble).build();
+                let agent = configure!(self, client.agent(&self.model).preamble(&preamble)).build();
                 let mut stream = agent.stream_prompt(message).await;
 
                 while let Some(chunk) = stream.next().await {
@@ -262,11 +458,49 @@ impl LlmClient {
         history: Vec<Message>,
         on_token: F,
     ) -> Result<String, String>
+    where
+        F: Fn(String) + Send + Sync,
+    {
+        self.stream_chat_message(Message::user(message), history, on_token).await
+    }
+
+    /// Streaming chat with a full user message, e.g. one carrying images
+    #[tracing::instrument(
+        name = "llm.call",
+        skip_all,
+        fields(
+            provider = %self.provider,
+            model = %self.model,
+            queued_ms = tracing::field::Empty,
+            first_token_ms = tracing::field::Empty,
+            input_tokens = tracing::field::Empty,
+            output_tokens = tracing::field::Empty,
+        )
+    )]
+    pub async fn stream_chat_message<F>(
+        &self,
+        message: Message,
+        history: Vec<Message>,
+        on_token: F,
+    ) -> Result<String, String>
     where
         F: Fn(String) + Send + Sync,
     {
         self.check_api_key()?;
+        let permit = self.acquire(&message, &history).await;
+        let result = self.send_stream_chat(message, history, on_token).await;
+        self.release(permit, &result);
+        result
+    }
+
+    async fn send_stream_chat<F>(&self, message: Message, history: Vec<Message>, on_token: F) -> Result<String, String>
+    where
+        F: Fn(String) + Send + Sync,
+    {
+        let started = std::time::Instant::now();
         let preamble = self.preamble.clone().unwrap_or_default();
+        let message = self.prepare(message);
+        let history = history.into_iter().map(|m| self.prepare(m)).collect::<Vec<_>>();
         let mut full_response = String::new();
 
         match self.provider.as_str() {
@@ -276,20 +510,20 @@ impl LlmClient {
                 } else {
                     Ok(openai::Client::from_env())
                 }.map_err(|e| e.to_string())?;
-                let agent = client.agent(&self.model).preamble(&preamble).build();
+                let agent = configure!(self, client.agent(&self.model).preamble(&preamble)).build();
                 let mut stream = agent.stream_chat(message, history).await;
 
                 while let Some(chunk) = stream.next().await {
                     match chunk {
-                        Ok(token) => {
-                            if let MultiTurnStreamItem::StreamAssistantItem(
-                                StreamedAssistantContent::Text(t),
-                            ) = token
-                            {
-                                on_token(t.text.clone());
-                                full_response.push_str(&t.text);
+                        Ok(MultiTurnStreamItem::StreamAssistantItem(StreamedAssistantContent::Text(t))) => {
+                            if full_response.is_empty() {
+                                record_first_token(started);
                             }
+                            on_token(t.text.clone());
+                            full_response.push_str(&t.text);
                         }
+                        Ok(MultiTurnStreamItem::FinalResponse(res)) => self.record_usage(&res.usage()),
+                        Ok(_) => {}
                         Err(e) => {
                             error!("Error in stream: {}", e);
                             return Err(e.to_string());
@@ -303,20 +537,20 @@ impl LlmClient {
                 } else {
                     Ok(gemini::Client::from_env())
                 }.map_err(|e| e.to_string())?;
-                let agent = client.agent(&self.model).preamble(&preamble).build();
+                let agent = configure!(self, client.agent(&self.model).preamble(&preamble)).build();
                 let mut stream = agent.stream_chat(message, history).await;
 
                 while let Some(chunk) = stream.next().await {
                     match chunk {
-                        Ok(token) => {
-                            if let MultiTurnStreamItem::StreamAssistantItem(
-                                StreamedAssistantContent::Text(t),
-                            ) = token
-                            {
-                                on_token(t.text.clone());
-                                full_response.push_str(&t.text);
+                        Ok(MultiTurnStreamItem::StreamAssistantItem(StreamedAssistantContent::Text(t))) => {
+                            if full_response.is_empty() {
+                                record_first_token(started);
                             }
+                            on_token(t.text.clone());
+                            full_response.push_str(&t.text);
                         }
+                        Ok(MultiTurnStreamItem::FinalResponse(res)) => self.record_usage(&res.usage()),
+                        Ok(_) => {}
                         Err(e) => {
                             error!("Error in stream: {}", e);
                             return Err(e.to_string());
@@ -330,20 +564,20 @@ impl LlmClient {
                 } else {
                     Ok(anthropic::Client::from_env())
                 }.map_err(|e| e.to_string())?;
-                let agent = client.agent(&self.model).preamble(&preamble).build();
+                let agent = configure!(self, client.agent(&self.model).preamble(&preamble)).build();
                 let mut stream = agent.stream_chat(message, history).await;
 
                 while let Some(chunk) = stream.next().await {
                     match chunk {
-                        Ok(token) => {
-                            if let MultiTurnStreamItem::StreamAssistantItem(
-                                StreamedAssistantContent::Text(t),
-                            ) = token
-                            {
-                                on_token(t.text.clone());
-                                full_response.push_str(&t.text);
+                        Ok(MultiTurnStreamItem::StreamAssistantItem(StreamedAssistantContent::Text(t))) => {
+                            if full_response.is_empty() {
+                                record_first_token(started);
                             }
+                            on_token(t.text.clone());
+                            full_response.push_str(&t.text);
                         }
+                        Ok(MultiTurnStreamItem::FinalResponse(res)) => self.record_usage(&res.usage()),
+                        Ok(_) => {}
                         Err(e) => {
                             error!("Error in stream: {}", e);
                             return Err(e.to_string());
@@ -368,6 +602,23 @@ impl LlmClient {
     }
 }
 
+/// Time to first token, on the current `llm.call` span
+fn record_first_token(started: std::time::Instant) {
+    tracing::Span::current().record("first_token_ms", started.elapsed().as_millis() as u64);
+}
+
+impl LlmClient {
+    /// Token usage reported with the end of the stream, on the current
+    /// `llm.call` span and the session's and job's meters
+    fn record_usage(&self, usage: &rig::completion::Usage) {
+        let span = tracing::Span::current();
+        span.record("input_tokens", usage.input_tokens);
+        span.record("output_tokens", usage.output_tokens);
+        let keys: Vec<&str> = self.session.iter().chain(&self.job).map(String::as_str).collect();
+        self::usage::record(&keys, &self.provider, &self.model, usage.input_tokens, usage.output_tokens);
+    }
+}
+
 /// Helper to create Message for history
 pub fn user_message(content: &str) -> Message {
     Message::user(content)
@@ -0,0 +1,208 @@
+//! Sampling parameters of an agent's model calls
+//!
+//! Agents keep `temperature`, `top_p`, `max_tokens`, `stop` and `seed` in
+//! their `ai_config`. Temperature and the answer length go through rig's
+//! builder; the rest are provider-specific request fields, sent as
+//! additional parameters under each provider's own names.
+//!
+//! Providers accept different ranges. `validate` rejects what a provider
+//! would refuse, so a bad value is caught when the agent is saved rather
+//! than on every call; `for_provider` fits valid parameters of one provider
+//! to another, for fallback models.
+
+use super::context::ModelContext;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct GenerationParams {
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    #[serde(default)]
+    pub top_p: Option<f64>,
+    /// Longest answer, in tokens
+    #[serde(default)]
+    pub max_tokens: Option<u64>,
+    /// Sequences that end the answer
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    /// Makes sampling repeatable where the provider supports it
+    #[serde(default)]
+    pub seed: Option<i64>,
+}
+
+/// Highest temperature a provider accepts
+fn max_temperature(provider: &str) -> f64 {
+    match provider {
+        "anthropic" => 1.0,
+        _ => 2.0,
+    }
+}
+
+/// Most stop sequences a provider accepts
+fn max_stop_sequences(provider: &str) -> usize {
+    match provider {
+        "openai" => 4,
+        "gemini" => 5,
+        _ => 16,
+    }
+}
+
+fn supports_seed(provider: &str) -> bool {
+    matches!(provider, "openai" | "gemini")
+}
+
+impl GenerationParams {
+    /// Parameters stored in an agent's `ai_config`; the `ai_temperature`
+    /// column fills in the temperature when the JSON has none
+    pub fn from_ai_config(ai_config: &str, ai_temperature: f32) -> Self {
+        let mut params: Self = serde_json::from_str(ai_config).unwrap_or_default();
+        if params.temperature.is_none() {
+            params.temperature = Some(f64::from(ai_temperature));
+        }
+        params
+    }
+
+    /// Check the parameters against what `provider` and `model` accept
+    pub fn validate(&self, provider: &str, model: &str) -> Result<(), String> {
+        if let Some(temperature) = self.temperature {
+            let max = max_temperature(provider);
+            if !(0.0..=max).contains(&temperature) {
+                return Err(format!("temperature must be between 0 and {} for {}", max, provider));
+            }
+        }
+        if let Some(top_p) = self.top_p {
+            if !(0.0..=1.0).contains(&top_p) {
+                return Err("top_p must be between 0 and 1".to_string());
+            }
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            let window = ModelContext::for_model(provider, model).context_window as u64;
+            if max_tokens == 0 || max_tokens > window {
+                return Err(format!("max_tokens must be between 1 and {} for {}", window, model));
+            }
+        }
+        let max_stop = max_stop_sequences(provider);
+        if self.stop.len() > max_stop {
+            return Err(format!("{} accepts at most {} stop sequences", provider, max_stop));
+        }
+        if self.stop.iter().any(|s| s.is_empty()) {
+            return Err("Stop sequences must not be empty".to_string());
+        }
+        if self.seed.is_some() && !supports_seed(provider) {
+            return Err(format!("{} does not support a seed", provider));
+        }
+        Ok(())
+    }
+
+    /// The parameters clamped and trimmed to what `provider` accepts
+    pub fn for_provider(&self, provider: &str) -> Self {
+        Self {
+            temperature: self.temperature.map(|t| t.clamp(0.0, max_temperature(provider))),
+            top_p: self.top_p.map(|p| p.clamp(0.0, 1.0)),
+            max_tokens: self.max_tokens,
+            stop: self
+                .stop
+                .iter()
+                .filter(|s| !s.is_empty())
+                .take(max_stop_sequences(provider))
+                .cloned()
+                .collect(),
+            seed: self.seed.filter(|_| supports_seed(provider)),
+        }
+    }
+
+    /// Request fields for the parameters rig's builder doesn't cover, named
+    /// the way `provider` expects them
+    pub fn provider_params(&self, provider: &str) -> Option<Value> {
+        let mut fields = Map::new();
+        let stop = (!self.stop.is_empty()).then(|| json!(self.stop));
+        match provider {
+            "openai" => {
+                fields.extend(self.top_p.map(|p| ("top_p".to_string(), json!(p))));
+                fields.extend(stop.map(|s| ("stop".to_string(), s)));
+                fields.extend(self.seed.map(|s| ("seed".to_string(), json!(s))));
+            }
+            "anthropic" => {
+                fields.extend(self.top_p.map(|p| ("top_p".to_string(), json!(p))));
+                fields.extend(stop.map(|s| ("stop_sequences".to_string(), s)));
+            }
+            "gemini" => {
+                let mut config = Map::new();
+                config.extend(self.top_p.map(|p| ("topP".to_string(), json!(p))));
+                config.extend(stop.map(|s| ("stopSequences".to_string(), s)));
+                config.extend(self.seed.map(|s| ("seed".to_string(), json!(s))));
+                if !config.is_empty() {
+                    fields.insert("generationConfig".to_string(), Value::Object(config));
+                }
+            }
+            _ => {}
+        }
+        (!fields.is_empty()).then_some(Value::Object(fields))
+    }
+}
+
+/// `extra` merged into `base`, objects key by key
+pub(crate) fn merge_params(base: Option<Value>, extra: Option<Value>) -> Option<Value> {
+    match (base, extra) {
+        (Some(Value::Object(mut base)), Some(Value::Object(extra))) => {
+            for (key, value) in extra {
+                let merged = merge_params(base.remove(&key), Some(value));
+                base.extend(merged.map(|v| (key, v)));
+            }
+            Some(Value::Object(base))
+        }
+        (base, None) => base,
+        (_, extra) => extra,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_ai_config_and_validate() {
+        let params = GenerationParams::from_ai_config(
+            r#"{"provider": "openai", "model": "gpt-4o", "top_p": 0.9, "max_tokens": 2048, "stop": ["END"], "seed": 7}"#,
+            0.3,
+        );
+        assert!((params.temperature.unwrap() - 0.3).abs() < 1e-6);
+        assert_eq!(params.max_tokens, Some(2048));
+        assert!(params.validate("openai", "gpt-4o").is_ok());
+        assert!(params.validate("anthropic", "claude-sonnet-4-5").unwrap_err().contains("seed"));
+
+        let hot = GenerationParams { temperature: Some(1.5), ..Default::default() };
+        assert!(hot.validate("openai", "gpt-4o").is_ok());
+        assert!(hot.validate("anthropic", "claude-sonnet-4-5").is_err());
+        assert_eq!(hot.for_provider("anthropic").temperature, Some(1.0));
+
+        let long = GenerationParams { max_tokens: Some(100_000), ..Default::default() };
+        assert!(long.validate("openai", "gpt-4").is_err());
+        let stops = GenerationParams { stop: vec!["a".into(); 5], ..Default::default() };
+        assert!(stops.validate("openai", "gpt-4o").is_err());
+        assert_eq!(stops.for_provider("openai").stop.len(), 4);
+        assert_eq!(GenerationParams::from_ai_config("not json", 0.5).temperature, Some(0.5));
+    }
+
+    #[test]
+    fn test_provider_params_and_merge() {
+        let params = GenerationParams {
+            top_p: Some(0.5),
+            stop: vec!["END".to_string()],
+            seed: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(params.provider_params("openai").unwrap(), json!({"top_p": 0.5, "stop": ["END"], "seed": 1}));
+        assert_eq!(params.provider_params("anthropic").unwrap(), json!({"top_p": 0.5, "stop_sequences": ["END"]}));
+        let gemini = params.provider_params("gemini");
+        assert_eq!(gemini.clone().unwrap()["generationConfig"]["stopSequences"], json!(["END"]));
+        assert!(GenerationParams::default().provider_params("openai").is_none());
+
+        // Structured output's JSON mode keeps its fields next to ours
+        let json_mode = Some(json!({"generationConfig": {"responseMimeType": "application/json"}}));
+        let merged = merge_params(json_mode, gemini).unwrap();
+        assert_eq!(merged["generationConfig"]["responseMimeType"], "application/json");
+        assert_eq!(merged["generationConfig"]["topP"], 0.5);
+    }
+}
@@ -0,0 +1,440 @@
+//! Shared request scheduler for LLM calls
+//!
+//! Every `LlmClient` call takes a slot here first, so chat sessions,
+//! background mail processing and sub-agents share each provider's limits:
+//!
+//! - a cap on calls in flight, per provider and overall
+//! - requests and (estimated) tokens per minute, per provider
+//! - fair queueing: sessions waiting on a provider take turns, so one busy
+//!   session can't starve the others
+//! - adaptive backoff: a rate-limit error pauses the provider, doubling up to
+//!   a minute while they keep coming; successful calls wind it back down
+//!
+//! Limits come from the `llm_rate_limits` setting (see `RateLimits`) and are
+//! applied with `global().set_limits`.
+
+use crate::database::DbPool;
+use crate::models::settings::{get_setting, set_setting};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+const SETTINGS_KEY: &str = "llm_rate_limits";
+const WINDOW: Duration = Duration::from_secs(60);
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Queue of calls made outside a session (mail processing, triggers)
+pub const BACKGROUND: &str = "background";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ProviderLimits {
+    /// Calls in flight at once
+    pub max_concurrent: usize,
+    pub requests_per_minute: Option<u32>,
+    /// Estimated prompt and response tokens per minute
+    pub tokens_per_minute: Option<u32>,
+}
+
+impl Default for ProviderLimits {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 4,
+            requests_per_minute: None,
+            tokens_per_minute: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct RateLimits {
+    /// Calls in flight at once across all providers
+    pub max_concurrent: usize,
+    /// Limits by provider name; others get the defaults
+    pub providers: HashMap<String, ProviderLimits>,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 8,
+            providers: HashMap::new(),
+        }
+    }
+}
+
+impl RateLimits {
+    pub fn load(pool: &DbPool) -> Self {
+        get_setting(pool, SETTINGS_KEY)
+            .and_then(|v| serde_json::from_str(&v).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, pool: &DbPool) -> Result<(), String> {
+        set_setting(pool, SETTINGS_KEY, &serde_json::to_string(self).map_err(|e| e.to_string())?)
+    }
+
+    pub fn provider(&self, name: &str) -> ProviderLimits {
+        self.providers.get(name).cloned().unwrap_or_default()
+    }
+}
+
+#[derive(Default)]
+struct ProviderState {
+    in_flight: usize,
+    /// Sessions with waiting calls in turn order, each with its tickets
+    queue: VecDeque<(String, VecDeque<u64>)>,
+    /// Calls sent in the last minute
+    requests: VecDeque<Instant>,
+    /// Tokens counted in the last minute
+    tokens: VecDeque<(Instant, u32)>,
+    backoff: Duration,
+    paused_until: Option<Instant>,
+}
+
+/// Why a call can't go out yet
+enum Blocked {
+    /// Until a running call finishes or the queue moves
+    Slot,
+    /// Until this time (backoff or a per-minute budget)
+    Until(Instant),
+}
+
+impl ProviderState {
+    fn next_ticket(&self) -> Option<u64> {
+        self.queue.front().and_then(|(_, tickets)| tickets.front().copied())
+    }
+
+    fn remove_ticket(&mut self, ticket: u64) {
+        for (_, tickets) in self.queue.iter_mut() {
+            tickets.retain(|t| *t != ticket);
+        }
+        self.queue.retain(|(_, tickets)| !tickets.is_empty());
+    }
+
+    fn check(&mut self, limits: &ProviderLimits, tokens: u32, now: Instant) -> Result<(), Blocked> {
+        if let Some(until) = self.paused_until {
+            if until > now {
+                return Err(Blocked::Until(until));
+            }
+            self.paused_until = None;
+        }
+        if self.in_flight >= limits.max_concurrent.max(1) {
+            return Err(Blocked::Slot);
+        }
+
+        while self.requests.front().is_some_and(|at| *at + WINDOW <= now) {
+            self.requests.pop_front();
+        }
+        while self.tokens.front().is_some_and(|(at, _)| *at + WINDOW <= now) {
+            self.tokens.pop_front();
+        }
+        if let Some(rpm) = limits.requests_per_minute {
+            if self.requests.len() >= rpm.max(1) as usize {
+                return Err(Blocked::Until(self.requests[0] + WINDOW));
+            }
+        }
+        if let Some(tpm) = limits.tokens_per_minute {
+            // A call bigger than the whole budget still goes out on an empty window
+            let mut used: u64 = self.tokens.iter().map(|(_, t)| u64::from(*t)).sum();
+            if used > 0 && used + u64::from(tokens) > u64::from(tpm) {
+                for (at, t) in &self.tokens {
+                    used -= u64::from(*t);
+                    if used + u64::from(tokens) <= u64::from(tpm) {
+                        return Err(Blocked::Until(*at + WINDOW));
+                    }
+                }
+                return Err(Blocked::Until(self.tokens.back().map(|(at, _)| *at + WINDOW).unwrap_or(now)));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct State {
+    limits: RateLimits,
+    in_flight: usize,
+    providers: HashMap<String, ProviderState>,
+    next_ticket: u64,
+}
+
+impl State {
+    /// Send call `ticket` if it's its turn and the limits allow
+    fn try_grant(&mut self, provider: &str, ticket: u64, tokens: u32, now: Instant) -> Result<(), Blocked> {
+        if self.in_flight >= self.limits.max_concurrent.max(1) {
+            return Err(Blocked::Slot);
+        }
+        let limits = self.limits.provider(provider);
+        let state = self.providers.entry(provider.to_string()).or_default();
+        if state.next_ticket() != Some(ticket) {
+            return Err(Blocked::Slot);
+        }
+        state.check(&limits, tokens, now)?;
+
+        // The session goes to the back of the line if it has more calls waiting
+        if let Some((session, mut tickets)) = state.queue.pop_front() {
+            tickets.pop_front();
+            if !tickets.is_empty() {
+                state.queue.push_back((session, tickets));
+            }
+        }
+        state.in_flight += 1;
+        state.requests.push_back(now);
+        state.tokens.push_back((now, tokens));
+        self.in_flight += 1;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct Scheduler {
+    state: Mutex<State>,
+    notify: Notify,
+}
+
+/// The scheduler every `LlmClient` goes through
+pub fn global() -> &'static Arc<Scheduler> {
+    static SCHEDULER: OnceLock<Arc<Scheduler>> = OnceLock::new();
+    SCHEDULER.get_or_init(Default::default)
+}
+
+impl Scheduler {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn limits(&self) -> RateLimits {
+        self.lock().limits.clone()
+    }
+
+    pub fn set_limits(&self, limits: RateLimits) {
+        self.lock().limits = limits;
+        self.notify.notify_waiters();
+    }
+
+    /// Wait for a slot to send a call of about `tokens` tokens to `provider`
+    /// for `session`. The slot is held until the permit is dropped.
+    pub async fn acquire(self: &Arc<Self>, provider: &str, session: &str, tokens: u32) -> Permit {
+        let ticket = {
+            let mut state = self.lock();
+            let ticket = state.next_ticket;
+            state.next_ticket += 1;
+            let queue = &mut state.providers.entry(provider.to_string()).or_default().queue;
+            match queue.iter_mut().find(|(s, _)| s == session) {
+                Some((_, tickets)) => tickets.push_back(ticket),
+                None => queue.push_back((session.to_string(), VecDeque::from([ticket]))),
+            }
+            ticket
+        };
+        let mut waiting = Waiting {
+            scheduler: self,
+            provider,
+            ticket,
+            granted: false,
+        };
+
+        loop {
+            // Registered before checking, so a release in between isn't missed
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let blocked = self.lock().try_grant(provider, ticket, tokens, Instant::now());
+            match blocked {
+                Ok(()) => {
+                    waiting.granted = true;
+                    // The next caller in line may be able to go too
+                    self.notify.notify_waiters();
+                    return Permit {
+                        scheduler: self.clone(),
+                        provider: provider.to_string(),
+                    };
+                }
+                Err(Blocked::Slot) => notified.await,
+                Err(Blocked::Until(until)) => {
+                    tokio::select! {
+                        _ = notified => {}
+                        _ = tokio::time::sleep_until(until) => {}
+                    }
+                }
+            }
+        }
+    }
+
+    /// Calls waiting for `provider`
+    pub fn queued(&self, provider: &str) -> usize {
+        self.lock()
+            .providers
+            .get(provider)
+            .map(|p| p.queue.iter().map(|(_, tickets)| tickets.len()).sum())
+            .unwrap_or(0)
+    }
+}
+
+/// Takes a cancelled caller out of the queue
+struct Waiting<'a> {
+    scheduler: &'a Scheduler,
+    provider: &'a str,
+    ticket: u64,
+    granted: bool,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        if !self.granted {
+            if let Some(state) = self.scheduler.lock().providers.get_mut(self.provider) {
+                state.remove_ticket(self.ticket);
+            }
+            self.scheduler.notify.notify_waiters();
+        }
+    }
+}
+
+/// A call in flight; dropping it frees the slot
+pub struct Permit {
+    scheduler: Arc<Scheduler>,
+    provider: String,
+}
+
+impl Permit {
+    fn with_provider(&self, f: impl FnOnce(&mut ProviderState)) {
+        if let Some(state) = self.scheduler.lock().providers.get_mut(&self.provider) {
+            f(state);
+        }
+    }
+
+    /// Count tokens found after the call went out (the response)
+    pub fn add_tokens(&self, tokens: u32) {
+        self.with_provider(|state| state.tokens.push_back((Instant::now(), tokens)));
+    }
+
+    /// The provider answered with a rate-limit error: pause it
+    pub fn rate_limited(&self) {
+        self.with_provider(|state| {
+            state.backoff = (state.backoff * 2).clamp(MIN_BACKOFF, MAX_BACKOFF);
+            state.paused_until = Some(Instant::now() + state.backoff);
+            log::warn!("{} is rate limiting, pausing calls for {:?}", self.provider, state.backoff);
+        });
+    }
+
+    /// The call went through: ease off the backoff
+    pub fn succeeded(&self) {
+        self.with_provider(|state| {
+            state.backoff /= 2;
+            if state.backoff < MIN_BACKOFF {
+                state.backoff = Duration::ZERO;
+            }
+        });
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        {
+            let mut state = self.scheduler.lock();
+            state.in_flight = state.in_flight.saturating_sub(1);
+            if let Some(provider) = state.providers.get_mut(&self.provider) {
+                provider.in_flight = provider.in_flight.saturating_sub(1);
+            }
+        }
+        self.scheduler.notify.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduler(limits: ProviderLimits) -> Arc<Scheduler> {
+        let scheduler = Arc::new(Scheduler::default());
+        scheduler.set_limits(RateLimits {
+            max_concurrent: 8,
+            providers: HashMap::from([("openai".to_string(), limits)]),
+        });
+        scheduler
+    }
+
+    #[tokio::test]
+    async fn test_sessions_take_turns() {
+        let scheduler = scheduler(ProviderLimits { max_concurrent: 1, ..Default::default() });
+        let first = scheduler.acquire("openai", "busy", 10).await;
+
+        // "busy" queues three more calls before "quiet" asks for one
+        let order = Arc::new(Mutex::new(vec![]));
+        let mut handles = vec![];
+        for session in ["busy", "busy", "busy", "quiet"] {
+            let (task_scheduler, order) = (scheduler.clone(), order.clone());
+            handles.push(tokio::spawn(async move {
+                let _permit = task_scheduler.acquire("openai", session, 10).await;
+                order.lock().unwrap().push(session);
+            }));
+            while scheduler.queued("openai") < handles.len() {
+                tokio::task::yield_now().await;
+            }
+        }
+        drop(first);
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(*order.lock().unwrap(), vec!["busy", "quiet", "busy", "busy"]);
+    }
+
+    #[test]
+    fn test_per_minute_budgets() {
+        let limits = ProviderLimits {
+            requests_per_minute: Some(2),
+            tokens_per_minute: Some(100),
+            ..Default::default()
+        };
+        let mut state = ProviderState::default();
+        let now = Instant::now();
+        let later = |secs| now + Duration::from_secs(secs);
+
+        state.requests.extend([now, later(1)]);
+        assert!(matches!(state.check(&limits, 1, later(2)), Err(Blocked::Until(t)) if t == now + WINDOW));
+        assert!(state.check(&limits, 1, now + WINDOW).is_ok());
+        assert_eq!(state.requests.len(), 1);
+
+        // 90 of 100 tokens used: a 50 token call waits for the first 60 to expire
+        state.requests.clear();
+        state.tokens.extend([(later(1), 60), (later(10), 30)]);
+        assert!(state.check(&limits, 5, later(20)).is_ok());
+        assert!(matches!(state.check(&limits, 50, later(20)), Err(Blocked::Until(t)) if t == later(1) + WINDOW));
+        // Too big for any budget: waits for an empty window, then goes
+        assert!(matches!(state.check(&limits, 500, later(20)), Err(Blocked::Until(t)) if t == later(10) + WINDOW));
+        assert!(state.check(&limits, 500, later(10) + WINDOW).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_pauses_provider() {
+        let scheduler = scheduler(ProviderLimits::default());
+        let permit = scheduler.acquire("openai", "s", 1).await;
+        permit.rate_limited();
+        permit.rate_limited();
+        let paused_until = |scheduler: &Scheduler| scheduler.lock().providers["openai"].paused_until;
+        assert!(paused_until(&scheduler).unwrap() >= Instant::now() + Duration::from_millis(1500));
+
+        permit.succeeded();
+        permit.succeeded();
+        assert_eq!(scheduler.lock().providers["openai"].backoff, Duration::ZERO);
+        drop(permit);
+        assert!(scheduler.lock().providers["openai"].in_flight == 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter_leaves_queue() {
+        let scheduler = scheduler(ProviderLimits { max_concurrent: 1, ..Default::default() });
+        let permit = scheduler.acquire("openai", "a", 1).await;
+        let waiting = tokio::time::timeout(Duration::from_millis(20), scheduler.acquire("openai", "b", 1)).await;
+        assert!(waiting.is_err());
+        assert_eq!(scheduler.queued("openai"), 0);
+        drop(permit);
+        drop(scheduler.acquire("anthropic", "b", 1).await);
+    }
+}
@@ -0,0 +1,263 @@
+//! Typed model output
+//!
+//! `LlmClient::prompt_structured::<T>` asks for a reply matching `T`'s JSON
+//! schema. Providers with a JSON mode get it: OpenAI receives the schema as
+//! its `response_format`, Gemini is asked for `application/json` (its
+//! response schemas only take an OpenAPI subset). Every provider also gets
+//! the schema in the prompt. The reply is checked against the schema and
+//! deserialized; if that fails the model is shown the errors and asked
+//! again. Failed requests are retried with a short backoff. Both count
+//! towards `MAX_ATTEMPTS`.
+
+use super::LlmClient;
+use jsonschema::JSONSchema;
+use rig::completion::Message;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+/// Requests made, including the first
+pub const MAX_ATTEMPTS: usize = 3;
+
+/// Provider request parameters asking for JSON output, where the provider
+/// has a JSON mode
+pub fn response_format(provider: &str, name: &str, schema: &Value) -> Option<Value> {
+    match provider {
+        "openai" => Some(json!({
+            "response_format": {
+                "type": "json_schema",
+                // Strict mode needs every property required, which optional fields aren't
+                "json_schema": { "name": name, "schema": schema, "strict": false }
+            }
+        })),
+        "gemini" => Some(json!({
+            "generationConfig": { "responseMimeType": "application/json" }
+        })),
+        _ => None,
+    }
+}
+
+/// The JSON object in a reply that may wrap it in prose or a code fence
+pub(crate) fn clean_json_text(text: &str) -> String {
+    // robustly find the JSON object frame
+    let start = text.find('{');
+    let end = text.rfind('}');
+
+    match (start, end) {
+        (Some(s), Some(e)) if s <= e => text[s..=e].to_string(),
+        _ => text.trim().to_string(), // Fallback to original trim if no braces found
+    }
+}
+
+/// Parse and validate a reply; the error is what the model is shown
+fn parse_reply<T, C>(reply: &str, schema: &JSONSchema, check: &C) -> Result<T, String>
+where
+    T: DeserializeOwned,
+    C: Fn(&T) -> Result<(), String>,
+{
+    let value: Value = serde_json::from_str(&clean_json_text(reply)).map_err(|e| format!("Not valid JSON: {}", e))?;
+    if let Err(errors) = schema.validate(&value) {
+        let errors = errors.map(|e| format!("{} at '{}'", e, e.instance_path)).collect::<Vec<_>>();
+        return Err(format!("Does not match the schema: {}", errors.join("; ")));
+    }
+    let parsed = serde_json::from_value(value).map_err(|e| e.to_string())?;
+    check(&parsed)?;
+    Ok(parsed)
+}
+
+/// Name of the schema for providers that want one: letters, digits, `_`, `-`
+fn schema_name<T: JsonSchema>() -> String {
+    T::schema_name()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .take(64)
+        .collect()
+}
+
+impl LlmClient {
+    /// Prompt for a value of type `T`
+    pub async fn prompt_structured<T>(&self, message: &str) -> Result<T, String>
+    where
+        T: JsonSchema + DeserializeOwned,
+    {
+        self.structured(message, None, |_: &T| Ok(())).await
+    }
+
+    /// Prompt for a value of type `T`, streaming the replies to `on_token`.
+    /// `check` adds rules the schema can't express; its errors are sent back
+    /// to the model like schema errors.
+    pub async fn stream_prompt_structured<T, F, C>(&self, message: &str, on_token: &F, check: C) -> Result<T, String>
+    where
+        T: JsonSchema + DeserializeOwned,
+        F: Fn(String) + Send + Sync,
+        C: Fn(&T) -> Result<(), String>,
+    {
+        self.structured(message, Some(on_token as &(dyn Fn(String) + Send + Sync)), check).await
+    }
+
+    async fn structured<T, C>(
+        &self,
+        message: &str,
+        on_token: Option<&(dyn Fn(String) + Send + Sync)>,
+        check: C,
+    ) -> Result<T, String>
+    where
+        T: JsonSchema + DeserializeOwned,
+        C: Fn(&T) -> Result<(), String>,
+    {
+        let schema = serde_json::to_value(schemars::schema_for!(T)).map_err(|e| e.to_string())?;
+        let compiled = JSONSchema::compile(&schema).map_err(|e| format!("Invalid schema: {}", e))?;
+        let client = Self {
+            additional_params: response_format(&self.provider, &schema_name::<T>(), &schema),
+            ..self.clone()
+        };
+
+        let schema_text = serde_json::to_string_pretty(&schema).map_err(|e| e.to_string())?;
+        let mut request = format!(
+            "{}\n\nReply with only a JSON object matching this JSON schema, no other text:\n{}",
+            message, schema_text
+        );
+        let mut history: Vec<Message> = Vec::new();
+        let mut last_error = String::new();
+
+        for attempt in 1..=MAX_ATTEMPTS {
+            let reply = match on_token {
+                Some(on_token) => {
+                    client
+                        .stream_chat_message(Message::user(&request), history.clone(), on_token)
+                        .await
+                }
+                None => client.chat_message(Message::user(&request), history.clone()).await,
+            };
+            let reply = match reply {
+                Ok(reply) => reply,
+                Err(e) if attempt < MAX_ATTEMPTS => {
+                    log::warn!("Structured request {}/{} failed: {}", attempt, MAX_ATTEMPTS, e);
+                    tokio::time::sleep(std::time::Duration::from_secs(1 << (attempt - 1))).await;
+                    last_error = e;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            match parse_reply(&reply, &compiled, &check) {
+                Ok(parsed) => return Ok(parsed),
+                Err(e) => {
+                    log::warn!("Structured reply {}/{} rejected: {}", attempt, MAX_ATTEMPTS, e);
+                    history.push(Message::user(&request));
+                    history.push(Message::assistant(&reply));
+                    request = format!(
+                        "That reply can't be used. {}\nReply again with only the corrected JSON object.",
+                        e
+                    );
+                    last_error = e;
+                }
+            }
+        }
+        Err(format!("No valid reply after {} attempts: {}", MAX_ATTEMPTS, last_error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, JsonSchema, Debug, PartialEq)]
+    struct Title {
+        title: String,
+        #[serde(default)]
+        emoji: Option<String>,
+    }
+
+    fn compiled<T: JsonSchema>() -> JSONSchema {
+        JSONSchema::compile(&serde_json::to_value(schemars::schema_for!(T)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_reply_validates_against_schema() {
+        let schema = compiled::<Title>();
+        let ok = |_: &Title| Ok(());
+
+        let parsed: Title = parse_reply("```json\n{\"title\": \"Rust notes\"}\n```", &schema, &ok).unwrap();
+        assert_eq!(parsed, Title { title: "Rust notes".to_string(), emoji: None });
+
+        let missing = parse_reply::<Title, _>(r#"{"name": "Rust notes"}"#, &schema, &ok).unwrap_err();
+        assert!(missing.contains("schema"), "{}", missing);
+        assert!(parse_reply::<Title, _>("Rust notes", &schema, &ok).unwrap_err().contains("Not valid JSON"));
+
+        let short = |t: &Title| if t.title.len() < 40 { Ok(()) } else { Err("Title is too long".to_string()) };
+        let long = format!(r#"{{"title": "{}"}}"#, "a".repeat(50));
+        assert_eq!(parse_reply::<Title, _>(&long, &schema, &short).unwrap_err(), "Title is too long");
+    }
+
+    #[test]
+    fn test_response_format_per_provider() {
+        let schema = json!({"type": "object"});
+        let openai = response_format("openai", &schema_name::<Title>(), &schema).unwrap();
+        assert_eq!(openai["response_format"]["json_schema"]["name"], "Title");
+        assert_eq!(
+            response_format("gemini", "Title", &schema).unwrap()["generationConfig"]["responseMimeType"],
+            "application/json"
+        );
+        assert!(response_format("anthropic", "Title", &schema).is_none());
+    }
+
+    #[test]
+    fn test_clean_json_text_simple() {
+        let json = r#"{"name": "test"}"#;
+        assert_eq!(clean_json_text(json), json);
+    }
+
+    #[test]
+    fn test_clean_json_text_with_markdown() {
+        let text = r#"```json
+{"name": "test", "value": 42}
+```"#;
+        assert_eq!(clean_json_text(text), r#"{"name": "test", "value": 42}"#);
+    }
+
+    #[test]
+    fn test_clean_json_text_with_preamble() {
+        let text = r#"Here is the plan:
+
+{"tasks": [{"description": "Task 1"}]}
+
+I hope this helps!"#;
+        assert_eq!(
+            clean_json_text(text),
+            r#"{"tasks": [{"description": "Task 1"}]}"#
+        );
+    }
+
+    #[test]
+    fn test_clean_json_text_nested_braces() {
+        let text = r#"Sure, here's the JSON:
+{
+  "objective": "Create a test",
+  "tasks": [
+    {
+      "id": 1,
+      "description": "Write test"
+    }
+  ]
+}
+Done!"#;
+        let result = clean_json_text(text);
+        assert!(result.starts_with('{'));
+        assert!(result.ends_with('}'));
+        assert!(result.contains("\"objective\""));
+    }
+
+    #[test]
+    fn test_clean_json_text_no_braces() {
+        let text = "No JSON here, just plain text";
+        assert_eq!(clean_json_text(text), text);
+    }
+
+    #[test]
+    fn test_clean_json_text_whitespace() {
+        let text = "   { \"key\": \"value\" }   ";
+        assert_eq!(clean_json_text(text), "{ \"key\": \"value\" }");
+    }
+}
@@ -0,0 +1,158 @@
+//! Token usage per session or job
+//!
+//! Providers report usage at the end of each streamed response. Counting is
+//! opt-in per session or job (`start`), so sessions nobody reads don't pile
+//! up; `finish` returns the totals and stops counting. Calls count toward
+//! their client's session and job (`LlmClient::with_job`), each kept apart
+//! by provider and model so the agent loop can store what each model used.
+//!
+//! `estimate_cost` prices usage with the list prices of known model
+//! families; models it doesn't know cost nothing.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn total(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ModelUsage {
+    pub provider: String,
+    pub model: String,
+    pub usage: TokenUsage,
+}
+
+/// USD per million input and output tokens, by provider and model prefix
+const PRICES: &[(&str, &str, f64, f64)] = &[
+    ("openai", "gpt-5-mini", 0.25, 2.0),
+    ("openai", "gpt-5", 1.25, 10.0),
+    ("openai", "gpt-4.1-mini", 0.4, 1.6),
+    ("openai", "gpt-4.1", 2.0, 8.0),
+    ("openai", "gpt-4o-mini", 0.15, 0.6),
+    ("openai", "gpt-4o", 2.5, 10.0),
+    ("openai", "o4-mini", 1.1, 4.4),
+    ("openai", "o3", 2.0, 8.0),
+    ("openai", "gpt-3.5", 0.5, 1.5),
+    ("anthropic", "claude-3-haiku", 0.25, 1.25),
+    ("anthropic", "claude-3-5-haiku", 0.8, 4.0),
+    ("anthropic", "claude-haiku", 1.0, 5.0),
+    ("anthropic", "claude-3-opus", 15.0, 75.0),
+    ("anthropic", "claude-opus", 15.0, 75.0),
+    ("anthropic", "claude-", 3.0, 15.0),
+    ("gemini", "gemini-2.0-flash", 0.1, 0.4),
+    ("gemini", "gemini-2.5-flash", 0.3, 2.5),
+    ("gemini", "gemini-2.5-pro", 1.25, 10.0),
+    ("gemini", "gemini-1.5-flash", 0.075, 0.3),
+    ("gemini", "gemini-1.5-pro", 1.25, 5.0),
+];
+
+/// What `usage` of `provider`'s `model` costs at list prices, in USD; 0 for
+/// models without a known price (local ones included)
+pub fn estimate_cost(provider: &str, model: &str, usage: TokenUsage) -> f64 {
+    let model = model.to_lowercase();
+    PRICES
+        .iter()
+        .filter(|(p, prefix, _, _)| *p == provider && model.starts_with(prefix))
+        .max_by_key(|(_, prefix, _, _)| prefix.len())
+        .map(|(_, _, input, output)| {
+            (usage.input_tokens as f64 * input + usage.output_tokens as f64 * output) / 1_000_000.0
+        })
+        .unwrap_or(0.0)
+}
+
+type Meter = HashMap<(String, String), TokenUsage>;
+
+static METERS: LazyLock<Mutex<HashMap<String, Meter>>> = LazyLock::new(Default::default);
+
+/// Count the usage of `key`'s calls (a session or job id) from now on
+pub fn start(key: &str) {
+    if let Ok(mut meters) = METERS.lock() {
+        meters.insert(key.to_string(), Meter::default());
+    }
+}
+
+/// Stop counting and return what each model used since `start`
+pub fn finish_by_model(key: &str) -> Vec<ModelUsage> {
+    let meter = METERS
+        .lock()
+        .ok()
+        .and_then(|mut meters| meters.remove(key))
+        .unwrap_or_default();
+    let mut usage: Vec<ModelUsage> = meter
+        .into_iter()
+        .map(|((provider, model), usage)| ModelUsage { provider, model, usage })
+        .collect();
+    usage.sort_by(|a, b| (&a.provider, &a.model).cmp(&(&b.provider, &b.model)));
+    usage
+}
+
+/// Stop counting and return what was used since `start`
+pub fn finish(key: &str) -> TokenUsage {
+    finish_by_model(key).iter().fold(TokenUsage::default(), |total, m| TokenUsage {
+        input_tokens: total.input_tokens + m.usage.input_tokens,
+        output_tokens: total.output_tokens + m.usage.output_tokens,
+    })
+}
+
+/// Count a call toward each of `keys` that is being counted
+pub(crate) fn record(keys: &[&str], provider: &str, model: &str, input_tokens: u64, output_tokens: u64) {
+    let Ok(mut meters) = METERS.lock() else {
+        return;
+    };
+    for key in keys {
+        if let Some(meter) = meters.get_mut(*key) {
+            let usage = meter.entry((provider.to_string(), model.to_string())).or_default();
+            usage.input_tokens += input_tokens;
+            usage.output_tokens += output_tokens;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_started_sessions_are_counted() {
+        record(&["usage-test-a"], "openai", "gpt-4o", 10, 5);
+        start("usage-test-a");
+        record(&["usage-test-a"], "openai", "gpt-4o", 100, 20);
+        record(&["usage-test-a"], "openai", "gpt-4o", 50, 10);
+        record(&["usage-test-b"], "openai", "gpt-4o", 1, 1);
+        record(&[], "openai", "gpt-4o", 1, 1);
+
+        let usage = finish("usage-test-a");
+        assert_eq!(usage, TokenUsage { input_tokens: 150, output_tokens: 30 });
+        assert_eq!(usage.total(), 180);
+        assert_eq!(finish("usage-test-a"), TokenUsage::default());
+        assert_eq!(finish("usage-test-b"), TokenUsage::default());
+    }
+
+    #[test]
+    fn test_usage_by_model_and_cost() {
+        start("usage-test-session");
+        start("usage-test-job");
+        record(&["usage-test-session", "usage-test-job"], "openai", "gpt-4o", 1_000, 100);
+        record(&["usage-test-session", "usage-test-job"], "anthropic", "claude-3-haiku-20240307", 2_000, 200);
+
+        let by_model = finish_by_model("usage-test-job");
+        assert_eq!(by_model.len(), 2);
+        assert_eq!(by_model[0].provider, "anthropic");
+        assert_eq!(finish("usage-test-session").total(), 3_300);
+
+        let million = TokenUsage { input_tokens: 1_000_000, output_tokens: 1_000_000 };
+        assert_eq!(estimate_cost("openai", "gpt-4o-mini-2024-07-18", million), 0.75);
+        assert_eq!(estimate_cost("anthropic", "claude-sonnet-4-5", million), 18.0);
+        assert_eq!(estimate_cost("ollama", "llama3.1", million), 0.0);
+    }
+}
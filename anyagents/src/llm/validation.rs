@@ -0,0 +1,46 @@
+//! Lightweight API key checks against each provider's model listing endpoint
+
+use std::time::Duration;
+
+/// Verify that an API key is accepted by the provider without spending tokens
+pub async fn validate_api_key(provider: &str, api_key: &str) -> Result<(), String> {
+    models_response(provider, api_key).await.map(|_| ())
+}
+
+/// GET the provider's model listing with `api_key`; Err unless it answered 2xx
+pub(crate) async fn models_response(provider: &str, api_key: &str) -> Result<reqwest::Response, String> {
+    if api_key.trim().is_empty() {
+        return Err("API key is empty".to_string());
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let request = match provider {
+        "openai" => client
+            .get("https://api.openai.com/v1/models")
+            .bearer_auth(api_key),
+        "anthropic" => client
+            .get("https://api.anthropic.com/v1/models")
+            .query(&[("limit", "1000")])
+            .header("x-api-key", api_key)
+            .header("anthropic-version", "2023-06-01"),
+        "gemini" => client
+            .get("https://generativelanguage.googleapis.com/v1beta/models")
+            .query(&[("key", api_key), ("pageSize", "1000")]),
+        _ => return Err(format!("Unsupported provider: {}", provider)),
+    };
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Could not reach {}: {}", provider, e))?;
+
+    match response.status().as_u16() {
+        200..=299 => Ok(response),
+        400 | 401 | 403 => Err(format!("{} rejected the API key", provider)),
+        code => Err(format!("{} returned HTTP {}", provider, code)),
+    }
+}
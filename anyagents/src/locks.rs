@@ -0,0 +1,239 @@
+//! Advisory workspace locks between concurrent jobs
+//!
+//! Two jobs sharing a workspace would otherwise interleave their edits and
+//! commands. The agent loop takes a lock around every tool call that isn't
+//! read-only: the whole workspace by default, or a single file when the
+//! tool says that is all it touches (`Tool::lock_scope`). A workspace lock
+//! conflicts with every lock in that workspace, a file lock only with locks
+//! on the same file. Locks are reentrant for the job holding them, so
+//! parallel plan tasks of one job don't block each other.
+//!
+//! A job that has to wait is reported once (`on_wait`, which the loop turns
+//! into a `workspace_lock_waiting` event) and gives up after a timeout, so
+//! two jobs can't deadlock; the call then fails with an error the model sees.
+//! Locks are advisory: only calls made through the agent loop take them.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// How long a call waits for a lock before failing
+pub const LOCK_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// What a tool call needs to itself
+#[derive(Debug, Clone, PartialEq)]
+pub enum LockScope {
+    /// Nothing in the workspace (network calls, pages, mail)
+    None,
+    Workspace,
+    /// One file, relative to the workspace
+    File(String),
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct LockHolder {
+    pub job_id: String,
+    pub session_id: String,
+    pub tool: String,
+}
+
+struct Held {
+    holder: LockHolder,
+    file: Option<String>,
+    count: usize,
+}
+
+#[derive(Default)]
+pub struct LockManager {
+    held: Mutex<HashMap<PathBuf, Vec<Held>>>,
+    notify: Notify,
+}
+
+/// The lock manager every agent loop uses
+pub fn global() -> &'static LockManager {
+    static LOCKS: OnceLock<LockManager> = OnceLock::new();
+    LOCKS.get_or_init(Default::default)
+}
+
+/// Same workspace, however it was spelled
+fn workspace_key(workspace: &Path) -> PathBuf {
+    workspace.canonicalize().unwrap_or_else(|_| workspace.to_path_buf())
+}
+
+fn file_key(file: &str) -> String {
+    file.trim_start_matches("./").trim_end_matches('/').to_string()
+}
+
+impl LockManager {
+    fn lock(&self) -> MutexGuard<'_, HashMap<PathBuf, Vec<Held>>> {
+        self.held.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Take the lock `scope` needs in `workspace` for `holder`'s job. Calls
+    /// `on_wait` with the job in the way if it has to wait; fails after
+    /// `timeout`. `LockScope::None` returns at once.
+    pub async fn acquire(
+        &self,
+        workspace: &Path,
+        scope: LockScope,
+        holder: LockHolder,
+        timeout: Duration,
+        on_wait: impl FnOnce(&LockHolder),
+    ) -> Result<WorkspaceLock<'_>, String> {
+        let file = match scope {
+            LockScope::None => return Ok(WorkspaceLock { manager: self, key: None }),
+            LockScope::Workspace => None,
+            LockScope::File(file) => Some(file_key(&file)),
+        };
+        let workspace = workspace_key(workspace);
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut on_wait = Some(on_wait);
+
+        loop {
+            // Registered before checking, so a release in between isn't missed
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let blocker = {
+                let mut held = self.lock();
+                let locks = held.entry(workspace.clone()).or_default();
+                let blocker = locks
+                    .iter()
+                    .find(|h| {
+                        h.holder.job_id != holder.job_id
+                            && (file.is_none() || h.file.is_none() || h.file == file)
+                    })
+                    .map(|h| h.holder.clone());
+                if blocker.is_none() {
+                    match locks.iter_mut().find(|h| h.holder.job_id == holder.job_id && h.file == file) {
+                        Some(own) => own.count += 1,
+                        None => locks.push(Held { holder: holder.clone(), file: file.clone(), count: 1 }),
+                    }
+                    return Ok(WorkspaceLock {
+                        manager: self,
+                        key: Some((workspace, file, holder.job_id)),
+                    });
+                }
+                blocker
+            };
+
+            if let (Some(on_wait), Some(blocker)) = (on_wait.take(), &blocker) {
+                on_wait(blocker);
+            }
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                let blocker = blocker.map(|b| format!(" by job {} ({})", b.job_id, b.tool)).unwrap_or_default();
+                return Err(format!(
+                    "The workspace is locked{}; gave up after {}s. Try again later or work on something else.",
+                    blocker,
+                    timeout.as_secs()
+                ));
+            }
+        }
+    }
+
+    /// Locks held in `workspace`, with the file each covers (None: all of it)
+    pub fn holders(&self, workspace: &Path) -> Vec<(LockHolder, Option<String>)> {
+        self.lock()
+            .get(&workspace_key(workspace))
+            .map(|locks| locks.iter().map(|h| (h.holder.clone(), h.file.clone())).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// A held lock; dropping it releases the lock
+pub struct WorkspaceLock<'a> {
+    manager: &'a LockManager,
+    key: Option<(PathBuf, Option<String>, String)>,
+}
+
+impl Drop for WorkspaceLock<'_> {
+    fn drop(&mut self) {
+        let Some((workspace, file, job_id)) = self.key.take() else {
+            return;
+        };
+        let mut held = self.manager.lock();
+        if let Some(locks) = held.get_mut(&workspace) {
+            if let Some(index) = locks.iter().position(|h| h.holder.job_id == job_id && h.file == file) {
+                locks[index].count -= 1;
+                if locks[index].count == 0 {
+                    locks.remove(index);
+                }
+            }
+            if locks.is_empty() {
+                held.remove(&workspace);
+            }
+        }
+        drop(held);
+        self.manager.notify.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn holder(job_id: &str) -> LockHolder {
+        LockHolder { job_id: job_id.to_string(), session_id: "s".to_string(), tool: "bash".to_string() }
+    }
+
+    const SHORT: Duration = Duration::from_millis(50);
+
+    #[tokio::test]
+    async fn test_workspace_and_file_locks() {
+        let locks = LockManager::default();
+        let dir = tempfile::tempdir().unwrap();
+        let ws = dir.path();
+
+        let a = locks.acquire(ws, LockScope::File("src/main.rs".into()), holder("a"), SHORT, |_| {}).await.unwrap();
+        // Other files are free, the same file and the whole workspace are not
+        let b = locks.acquire(ws, LockScope::File("./README.md".into()), holder("b"), SHORT, |_| {}).await.unwrap();
+        let mut waited_on = None;
+        let err = locks
+            .acquire(ws, LockScope::File("src/main.rs".into()), holder("b"), SHORT, |h| waited_on = Some(h.job_id.clone()))
+            .await
+            .err()
+            .unwrap();
+        assert!(err.contains("job a"), "{}", err);
+        assert_eq!(waited_on.as_deref(), Some("a"));
+        assert!(locks.acquire(ws, LockScope::Workspace, holder("c"), SHORT, |_| {}).await.is_err());
+
+        // Reentrant for the holding job
+        let again = locks.acquire(ws, LockScope::Workspace, holder("a"), SHORT, |h| assert_eq!(h.job_id, "b")).await;
+        assert!(again.is_err(), "b still holds README.md");
+        drop(b);
+        let whole = locks.acquire(ws, LockScope::Workspace, holder("a"), SHORT, |_| panic!("own lock")).await.unwrap();
+        assert_eq!(locks.holders(ws).len(), 2);
+        drop(whole);
+        drop(a);
+        assert!(locks.holders(ws).is_empty());
+
+        assert!(locks.acquire(ws, LockScope::None, holder("d"), SHORT, |_| {}).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_waiter_starts_when_lock_is_released() {
+        let locks = std::sync::Arc::new(LockManager::default());
+        let dir = tempfile::tempdir().unwrap();
+        let ws = dir.path().to_path_buf();
+
+        let held = locks.acquire(&ws, LockScope::Workspace, holder("a"), SHORT, |_| {}).await.unwrap();
+        let waiter = {
+            let locks = locks.clone();
+            let ws = ws.clone();
+            tokio::spawn(async move {
+                locks
+                    .acquire(&ws, LockScope::Workspace, holder("b"), Duration::from_secs(5), |_| {})
+                    .await
+                    .map(|_| ())
+            })
+        };
+        tokio::time::sleep(SHORT).await;
+        drop(held);
+        assert!(waiter.await.unwrap().is_ok());
+    }
+}
@@ -0,0 +1,413 @@
+//! Database integrity checks and cleanup of orphaned data
+//!
+//! Several code paths leave rows behind: messages saved while `AgentLoop` still
+//! carries its "temp" session placeholder, messages of deleted sessions, blocks and
+//! attachments of deleted pages, etc. `check_integrity` reports them and
+//! `cleanup_orphans` removes them (including attachment files on disk).
+//! Sessions whose agent is gone are only reported: they still hold the user's
+//! conversations.
+//!
+//! "temp" messages are only orphans once their run is over: a run that never
+//! got a session keeps writing them while it goes. Runs register with
+//! `track_temp_run`, and only temp messages older than `TEMP_MESSAGE_MIN_AGE`
+//! and than every such run still going are removed.
+
+use crate::database::DbPool;
+use crate::pages::attachments as page_attachments;
+use crate::schema::{
+    agent_skill_assignments, agent_skills, agents, attachments, blocks, mail_messages,
+    mail_threads, messages, pages, sessions, skill_files,
+};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+/// Placeholder session id used by `AgentLoop` before a real session is assigned
+pub const TEMP_SESSION_ID: &str = "temp";
+
+/// Temp messages younger than this are left alone, runs or not
+pub const TEMP_MESSAGE_MIN_AGE: chrono::Duration = chrono::Duration::hours(1);
+
+/// Start times of the runs saving messages under `TEMP_SESSION_ID` right now
+static TEMP_RUNS: LazyLock<Mutex<HashMap<u64, chrono::NaiveDateTime>>> = LazyLock::new(Default::default);
+static NEXT_TEMP_RUN: AtomicU64 = AtomicU64::new(0);
+
+/// Keeps the temp messages of a run from being cleaned up until dropped
+pub struct TempRunGuard(u64);
+
+impl Drop for TempRunGuard {
+    fn drop(&mut self) {
+        TEMP_RUNS.lock().unwrap_or_else(|p| p.into_inner()).remove(&self.0);
+    }
+}
+
+/// Register a run that saves its messages under `TEMP_SESSION_ID`
+pub fn track_temp_run() -> TempRunGuard {
+    let id = NEXT_TEMP_RUN.fetch_add(1, Ordering::Relaxed);
+    TEMP_RUNS
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .insert(id, chrono::Utc::now().naive_utc());
+    TempRunGuard(id)
+}
+
+/// Temp messages created before this are orphans
+fn temp_cutoff() -> chrono::NaiveDateTime {
+    let oldest_run = TEMP_RUNS.lock().unwrap_or_else(|p| p.into_inner()).values().min().copied();
+    let cutoff = chrono::Utc::now().naive_utc() - TEMP_MESSAGE_MIN_AGE;
+    oldest_run.map_or(cutoff, |started| cutoff.min(started))
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct IntegrityReport {
+    /// Messages stored under the "temp" placeholder session
+    pub temp_messages: i64,
+    /// Messages whose session no longer exists
+    pub orphaned_messages: i64,
+    /// Sessions whose agent no longer exists
+    pub orphaned_sessions: i64,
+    pub orphaned_blocks: i64,
+    pub orphaned_attachments: i64,
+    pub orphaned_skill_files: i64,
+    pub orphaned_skill_assignments: i64,
+    pub orphaned_mail_messages: i64,
+    /// Files in the attachments directory not referenced by any attachment row
+    pub orphaned_attachment_files: i64,
+}
+
+impl IntegrityReport {
+    pub fn total(&self) -> i64 {
+        self.temp_messages
+            + self.orphaned_messages
+            + self.orphaned_sessions
+            + self.orphaned_blocks
+            + self.orphaned_attachments
+            + self.orphaned_skill_files
+            + self.orphaned_skill_assignments
+            + self.orphaned_mail_messages
+            + self.orphaned_attachment_files
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.total() == 0
+    }
+}
+
+/// Count orphaned rows without modifying anything
+pub fn check_integrity(pool: &DbPool, attachments_dir: Option<&Path>) -> Result<IntegrityReport, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+
+    let temp_messages = messages::table
+        .filter(messages::session_id.eq(TEMP_SESSION_ID))
+        .filter(messages::created_at.lt(temp_cutoff()))
+        .count()
+        .get_result::<i64>(&mut conn)
+        .map_err(|e| e.to_string())?;
+
+    let orphaned_messages = messages::table
+        .filter(messages::session_id.ne(TEMP_SESSION_ID))
+        .filter(diesel::dsl::not(
+            messages::session_id.eq_any(sessions::table.select(sessions::id)),
+        ))
+        .count()
+        .get_result::<i64>(&mut conn)
+        .map_err(|e| e.to_string())?;
+
+    let orphaned_sessions = sessions::table
+        .filter(diesel::dsl::not(sessions::agent_id.eq_any(agents::table.select(agents::id))))
+        .count()
+        .get_result::<i64>(&mut conn)
+        .map_err(|e| e.to_string())?;
+
+    let orphaned_blocks = blocks::table
+        .filter(diesel::dsl::not(blocks::page_id.eq_any(pages::table.select(pages::id))))
+        .count()
+        .get_result::<i64>(&mut conn)
+        .map_err(|e| e.to_string())?;
+
+    let orphaned_attachments = attachments::table
+        .filter(diesel::dsl::not(attachments::page_id.eq_any(pages::table.select(pages::id))))
+        .count()
+        .get_result::<i64>(&mut conn)
+        .map_err(|e| e.to_string())?;
+
+    let orphaned_skill_files = skill_files::table
+        .filter(diesel::dsl::not(
+            skill_files::skill_id.eq_any(agent_skills::table.select(agent_skills::id)),
+        ))
+        .count()
+        .get_result::<i64>(&mut conn)
+        .map_err(|e| e.to_string())?;
+
+    let orphaned_skill_assignments = agent_skill_assignments::table
+        .filter(
+            diesel::dsl::not(agent_skill_assignments::agent_id.eq_any(agents::table.select(agents::id)))
+                .or(diesel::dsl::not(
+                    agent_skill_assignments::skill_id.eq_any(agent_skills::table.select(agent_skills::id)),
+                )),
+        )
+        .count()
+        .get_result::<i64>(&mut conn)
+        .map_err(|e| e.to_string())?;
+
+    let orphaned_mail_messages = mail_messages::table
+        .filter(diesel::dsl::not(
+            mail_messages::thread_id.eq_any(mail_threads::table.select(mail_threads::id)),
+        ))
+        .count()
+        .get_result::<i64>(&mut conn)
+        .map_err(|e| e.to_string())?;
+
+    let orphaned_attachment_files = match attachments_dir {
+        Some(dir) => unreferenced_attachment_files(&mut conn, dir)?.len() as i64,
+        None => 0,
+    };
+
+    Ok(IntegrityReport {
+        temp_messages,
+        orphaned_messages,
+        orphaned_sessions,
+        orphaned_blocks,
+        orphaned_attachments,
+        orphaned_skill_files,
+        orphaned_skill_assignments,
+        orphaned_mail_messages,
+        orphaned_attachment_files,
+    })
+}
+
+/// Delete orphaned rows (and attachment files) and return what was removed.
+/// Orphaned sessions are left alone.
+pub fn cleanup_orphans(pool: &DbPool, attachments_dir: Option<&Path>) -> Result<IntegrityReport, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let temp_cutoff = temp_cutoff();
+
+    // Files are deleted once the transaction has committed
+    let (mut report, unused_files) = conn
+        .transaction::<(IntegrityReport, Vec<String>), diesel::result::Error, _>(|conn| {
+            let temp_messages = diesel::delete(
+                messages::table
+                    .filter(messages::session_id.eq(TEMP_SESSION_ID))
+                    .filter(messages::created_at.lt(temp_cutoff)),
+            )
+            .execute(conn)? as i64;
+
+            let orphaned_messages = diesel::delete(
+                messages::table
+                    .filter(messages::session_id.ne(TEMP_SESSION_ID))
+                    .filter(diesel::dsl::not(
+                        messages::session_id.eq_any(sessions::table.select(sessions::id)),
+                    )),
+            )
+            .execute(conn)? as i64;
+
+            let orphaned_blocks = diesel::delete(blocks::table.filter(diesel::dsl::not(
+                blocks::page_id.eq_any(pages::table.select(pages::id)),
+            )))
+            .execute(conn)? as i64;
+
+            let orphaned_attachment_paths: Vec<(String, Option<String>)> = attachments::table
+                .filter(diesel::dsl::not(attachments::page_id.eq_any(pages::table.select(pages::id))))
+                .select((attachments::file_path, attachments::thumbnail_path))
+                .load(conn)?;
+            let orphaned_attachments = diesel::delete(attachments::table.filter(diesel::dsl::not(
+                attachments::page_id.eq_any(pages::table.select(pages::id)),
+            )))
+            .execute(conn)? as i64;
+            // Files are shared by attachments with the same content
+            let paths: Vec<String> = orphaned_attachment_paths
+                .into_iter()
+                .flat_map(|(file, thumbnail)| std::iter::once(file).chain(thumbnail))
+                .collect();
+            let unused_files = page_attachments::unreferenced(conn, &paths)?;
+
+            let orphaned_skill_files = diesel::delete(skill_files::table.filter(diesel::dsl::not(
+                skill_files::skill_id.eq_any(agent_skills::table.select(agent_skills::id)),
+            )))
+            .execute(conn)? as i64;
+
+            let orphaned_skill_assignments = diesel::delete(
+                agent_skill_assignments::table.filter(
+                    diesel::dsl::not(
+                        agent_skill_assignments::agent_id.eq_any(agents::table.select(agents::id)),
+                    )
+                    .or(diesel::dsl::not(
+                        agent_skill_assignments::skill_id
+                            .eq_any(agent_skills::table.select(agent_skills::id)),
+                    )),
+                ),
+            )
+            .execute(conn)? as i64;
+
+            let orphaned_mail_messages = diesel::delete(mail_messages::table.filter(diesel::dsl::not(
+                mail_messages::thread_id.eq_any(mail_threads::table.select(mail_threads::id)),
+            )))
+            .execute(conn)? as i64;
+
+            let report = IntegrityReport {
+                temp_messages,
+                orphaned_messages,
+                orphaned_sessions: 0,
+                orphaned_blocks,
+                orphaned_attachments,
+                orphaned_skill_files,
+                orphaned_skill_assignments,
+                orphaned_mail_messages,
+                orphaned_attachment_files: 0,
+            };
+            Ok((report, unused_files))
+        })
+        .map_err(|e| format!("Cleanup failed: {}", e))?;
+    page_attachments::remove_files(&unused_files);
+
+    if let Some(dir) = attachments_dir {
+        let files = unreferenced_attachment_files(&mut conn, dir)?;
+        for file in &files {
+            if let Err(e) = std::fs::remove_file(file) {
+                log::warn!("Failed to remove orphaned attachment {:?}: {}", file, e);
+            }
+        }
+        report.orphaned_attachment_files = files.len() as i64;
+    }
+
+    if !report.is_clean() {
+        log::info!("Orphan cleanup removed {} item(s): {:?}", report.total(), report);
+    }
+
+    Ok(report)
+}
+
+fn unreferenced_attachment_files(
+    conn: &mut SqliteConnection,
+    dir: &Path,
+) -> Result<Vec<std::path::PathBuf>, String> {
+    page_attachments::unreferenced_files(conn, dir).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+    use crate::models::{NewMessage, NewSession};
+
+    /// Orphans predate foreign key enforcement, so it is off while they are written
+    fn insert_message(pool: &DbPool, session: &str) -> String {
+        let mut conn = pool.get().unwrap();
+        let id = uuid::Uuid::new_v4().to_string();
+        diesel::sql_query("PRAGMA foreign_keys = OFF").execute(&mut conn).unwrap();
+        diesel::insert_into(messages::table)
+            .values(&NewMessage {
+                id: id.clone(),
+                role: "user".to_string(),
+                content: "hello".to_string(),
+                session_id: session.to_string(),
+                metadata_json: None,
+                tokens: None,
+            })
+            .execute(&mut conn)
+            .unwrap();
+        diesel::sql_query("PRAGMA foreign_keys = ON").execute(&mut conn).unwrap();
+        id
+    }
+
+    fn backdate(pool: &DbPool, message_id: &str, age: chrono::Duration) {
+        let mut conn = pool.get().unwrap();
+        diesel::update(messages::table.find(message_id))
+            .set(messages::created_at.eq(chrono::Utc::now().naive_utc() - age))
+            .execute(&mut conn)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_cleanup_removes_temp_and_orphaned_messages() {
+        let pool = create_test_pool();
+
+        crate::database::ensure_default_characters(&pool);
+        let agent_id = {
+            let mut conn = pool.get().unwrap();
+            agents::table.select(agents::id).first::<String>(&mut conn).unwrap()
+        };
+
+        let session_id = uuid::Uuid::new_v4().to_string();
+        {
+            let mut conn = pool.get().unwrap();
+            diesel::insert_into(sessions::table)
+                .values(&NewSession {
+                    id: session_id.clone(),
+                    agent_id,
+                    title: None,
+                    created_at: chrono::Utc::now().naive_utc(),
+                    updated_at: chrono::Utc::now().naive_utc(),
+                    archived: 0,
+                    pinned: 0,
+                })
+                .execute(&mut conn)
+                .unwrap();
+        }
+
+        insert_message(&pool, &session_id);
+        let stale = insert_message(&pool, TEMP_SESSION_ID);
+        backdate(&pool, &stale, chrono::Duration::hours(5));
+        insert_message(&pool, "deleted-session");
+        {
+            let mut conn = pool.get().unwrap();
+            diesel::sql_query("PRAGMA foreign_keys = OFF").execute(&mut conn).unwrap();
+            diesel::insert_into(sessions::table)
+                .values((sessions::id.eq("agentless"), sessions::agent_id.eq("deleted-agent")))
+                .execute(&mut conn)
+                .unwrap();
+            diesel::sql_query("PRAGMA foreign_keys = ON").execute(&mut conn).unwrap();
+        }
+
+        let report = check_integrity(&pool, None).unwrap();
+        assert_eq!(report.temp_messages, 1);
+        assert_eq!(report.orphaned_messages, 1);
+        assert_eq!(report.orphaned_sessions, 1);
+
+        let removed = cleanup_orphans(&pool, None).unwrap();
+        assert_eq!(removed.temp_messages, 1);
+        assert_eq!(removed.orphaned_messages, 1);
+        assert_eq!(removed.orphaned_sessions, 0);
+
+        // The agentless session is still there, and still reported
+        let after = check_integrity(&pool, None).unwrap();
+        assert_eq!(after.total(), 1);
+        assert_eq!(after.orphaned_sessions, 1);
+
+        let mut conn = pool.get().unwrap();
+        let remaining = messages::table.count().get_result::<i64>(&mut conn).unwrap();
+        assert_eq!(remaining, 1);
+    }
+
+    #[test]
+    fn test_cleanup_keeps_temp_messages_of_running_jobs() {
+        let pool = create_test_pool();
+
+        // Written by a run that is still going
+        let in_flight = insert_message(&pool, TEMP_SESSION_ID);
+        let run = track_temp_run();
+        let during_run = insert_message(&pool, TEMP_SESSION_ID);
+        backdate(&pool, &during_run, chrono::Duration::hours(2));
+
+        // Nothing from before the run started is kept
+        let stale = insert_message(&pool, TEMP_SESSION_ID);
+        backdate(&pool, &stale, chrono::Duration::hours(3));
+        {
+            let mut guard = TEMP_RUNS.lock().unwrap();
+            let started = guard.get_mut(&run.0).unwrap();
+            *started -= chrono::Duration::hours(2) + chrono::Duration::minutes(30);
+        }
+
+        assert_eq!(cleanup_orphans(&pool, None).unwrap().temp_messages, 1);
+        let mut conn = pool.get().unwrap();
+        let left: Vec<String> = messages::table.select(messages::id).load(&mut conn).unwrap();
+        assert!(left.contains(&in_flight) && left.contains(&during_run));
+        assert!(!left.contains(&stale));
+
+        drop(run);
+        assert_eq!(cleanup_orphans(&pool, None).unwrap().temp_messages, 1, "older than the minimum age");
+    }
+}
@@ -1,106 +1,238 @@
+pub mod oauth;
+pub mod secrets;
+pub mod server;
+pub mod transport;
 pub mod types;
 
-use crate::mcp::types::{ClientInfo, JsonRpcRequest, JsonRpcResponse, McpInitializeParams};
-use serde_json::json;
+use crate::mcp::transport::{read_stdio, stdio_writer, Dispatcher, HttpTransport, SseTransport, Transport};
+use crate::mcp::types::{ClientInfo, JsonRpcRequest, McpInitializeParams};
+use serde_json::{json, Value};
 use std::process::Stdio;
+use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, BufReader};
 use tokio::process::Command;
-use tokio::sync::Mutex;
+use tokio::sync::{oneshot, Mutex};
 
+pub use transport::NotificationHandler;
+
+/// How long a request may wait for its response by default
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long the server gets to exit after stdin is closed before it is killed
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
+/// JSON-RPC client for an MCP server, over any of the transports in
+/// `transport`.
+///
+/// Incoming messages are handled in the background: responses are routed to
+/// the waiting request by id (so requests may run concurrently), server
+/// notifications go to the registered handlers, and server-to-client requests
+/// are answered (`ping`) or refused.
 pub struct McpClient {
-    _process: Arc<Mutex<tokio::process::Child>>,
-    stdin: Arc<Mutex<tokio::process::ChildStdin>>,
-    stdout_reader: Arc<Mutex<tokio::io::Lines<BufReader<tokio::process::ChildStdout>>>>,
-    next_id: Arc<Mutex<i64>>,
+    child: Mutex<Option<tokio::process::Child>>,
+    transport: Arc<Transport>,
+    dispatcher: Arc<Dispatcher>,
+    next_id: AtomicI64,
+    timeout: Duration,
+    tasks: Vec<tokio::task::JoinHandle<()>>,
 }
 
 impl McpClient {
     pub async fn new(command: &str, args: &[String]) -> Result<Self, String> {
+        Self::spawn(command, args, &[]).await
+    }
+
+    /// Connect using a saved `mcp_servers` record ("stdio", "sse" or "http"),
+    /// with secrets from the OS keyring
+    pub async fn from_server(server: &crate::models::mcp_server::McpServer) -> Result<Self, String> {
+        Self::from_server_with(server, &secrets::KEYRING).await
+    }
+
+    /// `from_server` with `${SECRET:...}` references resolved from `store`
+    pub async fn from_server_with(
+        server: &crate::models::mcp_server::McpServer,
+        store: &dyn secrets::SecretStore,
+    ) -> Result<Self, String> {
+        let expand = |value: &str| secrets::expand(value, store, &server.id);
+
+        match server.server_type.as_str() {
+            "stdio" => {
+                let command = server.command.as_deref().ok_or("MCP server has no command")?;
+                let args: Vec<String> = server
+                    .args
+                    .as_deref()
+                    .and_then(|a| serde_json::from_str::<Vec<String>>(a).ok())
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|a| expand(a))
+                    .collect::<Result<_, _>>()?;
+                let env = secrets::expand_pairs(server.env_vars(), store, &server.id)?;
+                Self::spawn(command, &args, &env).await
+            }
+            "sse" | "http" => {
+                let url = expand(server.url.as_deref().ok_or("MCP server has no URL")?)?;
+                let headers = secrets::expand_pairs(server.header_pairs(), store, &server.id)?;
+                if server.server_type == "sse" {
+                    Self::connect_sse(&url, &headers).await
+                } else {
+                    Self::connect_http(&url, &headers)
+                }
+            }
+            other => Err(format!("Unknown MCP server type '{}'", other)),
+        }
+    }
+
+    async fn spawn(command: &str, args: &[String], env: &[(String, String)]) -> Result<Self, String> {
         let mut child = Command::new(command)
             .args(args)
+            .envs(env.iter().map(|(k, v)| (k.as_str(), v.as_str())))
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped()) // Capture stderr to avoid polluting app logs
+            .kill_on_drop(true)
             .spawn()
             .map_err(|e| format!("Failed to spawn MCP server: {}", e))?;
 
         let stdin = child.stdin.take().ok_or("Failed to open stdin")?;
         let stdout = child.stdout.take().ok_or("Failed to open stdout")?;
-        let reader = BufReader::new(stdout).lines();
 
-        Ok(Self {
-            _process: Arc::new(Mutex::new(child)),
-            stdin: Arc::new(Mutex::new(stdin)),
-            stdout_reader: Arc::new(Mutex::new(reader)),
-            next_id: Arc::new(Mutex::new(1)),
-        })
+        // Drain stderr so a chatty server never blocks on a full pipe
+        if let Some(stderr) = child.stderr.take() {
+            let name = command.to_string();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    log::debug!("[mcp:{}] {}", name, line);
+                }
+            });
+        }
+
+        let client = Self::connect(stdout, stdin);
+        *client.child.lock().await = Some(child);
+        Ok(client)
     }
 
-    pub async fn send_request(
-        &self,
-        method: &str,
-        params: Option<serde_json::Value>,
-    ) -> Result<serde_json::Value, String> {
-        let id_val;
-        {
-            let mut id = self.next_id.lock().await;
-            id_val = *id;
-            *id += 1;
+    /// Speak MCP over an existing byte stream pair (used by `new` and tests)
+    pub fn connect<R, W>(reader: R, writer: W) -> Self
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let (dispatcher, outbox) = Dispatcher::new();
+        let reader_task = tokio::spawn(read_stdio(reader, dispatcher.clone()));
+        Self::with_transport(Transport::Stdio(stdio_writer(writer)), dispatcher, outbox, Some(reader_task))
+    }
+
+    /// Streamable HTTP server at `url`
+    pub fn connect_http(url: &str, headers: &[(String, String)]) -> Result<Self, String> {
+        let (dispatcher, outbox) = Dispatcher::new();
+        let transport = HttpTransport::new(url, headers, dispatcher.clone())?;
+        Ok(Self::with_transport(Transport::Http(transport), dispatcher, outbox, None))
+    }
+
+    /// HTTP+SSE server whose event stream is at `url`
+    pub async fn connect_sse(url: &str, headers: &[(String, String)]) -> Result<Self, String> {
+        let (dispatcher, outbox) = Dispatcher::new();
+        let transport = SseTransport::connect(url, headers, dispatcher.clone()).await?;
+        Ok(Self::with_transport(Transport::Sse(transport), dispatcher, outbox, None))
+    }
+
+    fn with_transport(
+        transport: Transport,
+        dispatcher: Arc<Dispatcher>,
+        mut outbox: tokio::sync::mpsc::UnboundedReceiver<Value>,
+        reader_task: Option<tokio::task::JoinHandle<()>>,
+    ) -> Self {
+        let transport = Arc::new(transport);
+
+        // Replies to server-to-client requests
+        let replies = transport.clone();
+        let outbox_task = tokio::spawn(async move {
+            while let Some(reply) = outbox.recv().await {
+                if let Err(e) = replies.send(&reply).await {
+                    log::debug!("[mcp] failed to reply to server request: {}", e);
+                }
+            }
+        });
+
+        Self {
+            child: Mutex::new(None),
+            transport,
+            dispatcher,
+            next_id: AtomicI64::new(1),
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+            tasks: reader_task.into_iter().chain(std::iter::once(outbox_task)).collect(),
         }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Register a handler for server notifications (`notifications/message`,
+    /// `notifications/progress`, ...). Log messages are also written to the
+    /// app log without a handler.
+    pub fn on_notification<F>(&self, handler: F)
+    where
+        F: Fn(&str, &Value) + Send + Sync + 'static,
+    {
+        self.dispatcher.handlers.write().unwrap().push(Arc::new(handler));
+    }
+
+    async fn write_message(&self, message: &JsonRpcRequest) -> Result<(), String> {
+        let message = serde_json::to_value(message).map_err(|e| e.to_string())?;
+        self.transport.send(&message).await
+    }
+
+    pub async fn send_request(&self, method: &str, params: Option<Value>) -> Result<Value, String> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+        self.dispatcher.pending.insert(id, tx);
 
         let request = JsonRpcRequest {
             jsonrpc: "2.0".to_string(),
-            id: Some(json!(id_val)),
+            id: Some(json!(id)),
             method: method.to_string(),
             params,
         };
-
-        let json_str = serde_json::to_string(&request).map_err(|e| e.to_string())?;
-
-        {
-            let mut stdin = self.stdin.lock().await;
-            stdin
-                .write_all(json_str.as_bytes())
-                .await
-                .map_err(|e| e.to_string())?;
-            stdin.write_all(b"\n").await.map_err(|e| e.to_string())?;
-            stdin.flush().await.map_err(|e| e.to_string())?;
+        if let Err(e) = self.write_message(&request).await {
+            self.dispatcher.pending.remove(&id);
+            return Err(e);
         }
 
-        // Extremely naive implementation: Response is assumed to be the next line.
-        // In reality, we need a message loop to handle notifications, logs, etc.
-        // But for "Initial MVP" connection test, this might suffice if protocol is strict.
-        // MCP protocol usually sends logs/notifications.
-        // We really need a background loop keying on ID.
-        // For now, I'll loop reading lines until I find matching ID.
-
-        // This lock prevents concurrent requests from working properly unless we're lucky.
-        // But for single-threaded usage (one agent step at a time), it might pass.
-        let mut reader = self.stdout_reader.lock().await;
-        loop {
-            let line = reader
-                .next_line()
-                .await
-                .map_err(|e| e.to_string())?
-                .ok_or("MCP Server closed connection")?;
-
-            if let Ok(response) = serde_json::from_str::<JsonRpcResponse>(&line) {
-                if let Some(resp_id) = &response.id {
-                    if resp_id.as_i64() == Some(id_val) {
-                        if let Some(error) = response.error {
-                            return Err(format!("MCP Error {}: {}", error.code, error.message));
-                        }
-                        return Ok(response.result.unwrap_or(json!(null)));
-                    }
-                }
-            } else {
-                // Log initialization logs or notifications?
-                // println!("MCP Ignored: {}", line);
+        match tokio::time::timeout(self.timeout, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err("MCP Server closed connection".to_string()),
+            Err(_) => {
+                self.dispatcher.pending.remove(&id);
+                let _ = self
+                    .send_notification(
+                        "notifications/cancelled",
+                        Some(json!({ "requestId": id, "reason": "timeout" })),
+                    )
+                    .await;
+                Err(format!(
+                    "MCP request '{}' timed out after {}s",
+                    method,
+                    self.timeout.as_secs()
+                ))
             }
         }
     }
 
+    pub async fn send_notification(&self, method: &str, params: Option<Value>) -> Result<(), String> {
+        self.write_message(&JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: None,
+            method: method.to_string(),
+            params,
+        })
+        .await
+    }
+
     pub async fn initialize(&self) -> Result<(), String> {
         let params = McpInitializeParams {
             protocol_version: "2024-11-05".to_string(),
@@ -112,28 +244,7 @@ impl McpClient {
         };
 
         let _result = self.send_request("initialize", Some(json!(params))).await?;
-        // We should handle the result (server capabilities)
-
-        // Send initialized notification
-        // Notifications don't have ID
-        let notification = JsonRpcRequest {
-            jsonrpc: "2.0".to_string(),
-            id: None,
-            method: "notifications/initialized".to_string(),
-            params: None,
-        };
-        let json_str = serde_json::to_string(&notification).map_err(|e| e.to_string())?;
-        {
-            let mut stdin = self.stdin.lock().await;
-            stdin
-                .write_all(json_str.as_bytes())
-                .await
-                .map_err(|e| e.to_string())?;
-            stdin.write_all(b"\n").await.map_err(|e| e.to_string())?;
-            stdin.flush().await.map_err(|e| e.to_string())?;
-        }
-
-        Ok(())
+        self.send_notification("notifications/initialized", None).await
     }
 
     pub async fn list_tools(&self) -> Result<Vec<crate::mcp::types::McpTool>, String> {
@@ -142,41 +253,38 @@ impl McpClient {
             .map_err(|e| format!("Failed to parse tools/list result: {}", e))?;
         Ok(tools_result.tools)
     }
-}
 
-use crate::tools::{Tool, ToolContext};
-use async_trait::async_trait;
-use std::collections::HashMap;
+    /// Close the transport (for stdio servers: close stdin so the server can
+    /// exit on its own, then kill it if it has not within a short grace
+    /// period). Pending requests fail.
+    pub async fn shutdown(&self) {
+        self.transport.close().await;
 
-pub struct ToolRegistry {
-    tools: HashMap<String, Box<dyn Tool>>,
-}
-
-impl Default for ToolRegistry {
-    fn default() -> Self {
-        Self::new()
+        if let Some(mut child) = self.child.lock().await.take() {
+            if tokio::time::timeout(SHUTDOWN_GRACE, child.wait()).await.is_err() {
+                let _ = child.kill().await;
+            }
+        }
+        for task in &self.tasks {
+            task.abort();
+        }
+        self.dispatcher.fail_pending("MCP client shut down");
     }
 }
 
-impl ToolRegistry {
-    pub fn new() -> Self {
-        Self {
-            tools: HashMap::new(),
+impl Drop for McpClient {
+    fn drop(&mut self) {
+        // The child itself is killed on drop (`kill_on_drop`)
+        for task in &self.tasks {
+            task.abort();
         }
     }
+}
 
-    pub fn register(&mut self, tool: Box<dyn Tool>) {
-        self.tools.insert(tool.name().to_string(), tool);
-    }
+use crate::tools::{Tool, ToolContext};
+use async_trait::async_trait;
 
-    pub fn get(&self, name: &str) -> Option<&dyn Tool> {
-        self.tools.get(name).map(|b| &**b)
-    }
-
-    pub fn list(&self) -> Vec<&dyn Tool> {
-        self.tools.values().map(|b| &**b).collect()
-    }
-}
+pub use crate::tools::registry::ToolRegistry;
 
 pub struct McpToolAdapter {
     pub client: Arc<McpClient>,
@@ -217,3 +325,130 @@ impl Tool for McpToolAdapter {
         Ok(result)
     }
 }
+
+/// How long an agent waits for each of its MCP servers to start and list tools
+const AGENT_CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Connect to the enabled servers among `server_ids` and wrap their tools.
+/// A server that fails to start is logged and skipped, so it never blocks
+/// the agent.
+pub async fn load_server_tools(db_pool: &crate::database::DbPool, server_ids: &[String]) -> Vec<Box<dyn Tool>> {
+    use crate::models::mcp_server::McpServer as McpServerRecord;
+    use crate::schema::mcp_servers;
+    use diesel::prelude::*;
+
+    if server_ids.is_empty() {
+        return vec![];
+    }
+    let servers: Vec<McpServerRecord> = match db_pool.get() {
+        Ok(mut conn) => mcp_servers::table
+            .filter(mcp_servers::id.eq_any(server_ids))
+            .filter(mcp_servers::is_enabled.eq(1))
+            .load(&mut conn)
+            .unwrap_or_default(),
+        Err(_) => vec![],
+    };
+
+    let mut tools: Vec<Box<dyn Tool>> = vec![];
+    for server in servers {
+        let connected = tokio::time::timeout(AGENT_CONNECT_TIMEOUT, async {
+            let client = McpClient::from_server(&server).await?;
+            client.initialize().await?;
+            let listed = client.list_tools().await?;
+            Ok::<_, String>((Arc::new(client), listed))
+        })
+        .await;
+
+        match connected {
+            Ok(Ok((client, listed))) => {
+                for tool in listed {
+                    tools.push(Box::new(McpToolAdapter {
+                        client: client.clone(),
+                        name: tool.name,
+                        description: tool.description.unwrap_or_default(),
+                        schema: tool.input_schema,
+                    }));
+                }
+            }
+            Ok(Err(e)) => log::warn!("Skipping MCP server '{}': {}", server.name, e),
+            Err(_) => log::warn!("Skipping MCP server '{}': timed out connecting", server.name),
+        }
+    }
+    tools
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use tokio::io::AsyncWriteExt;
+
+    /// Answers requests in reverse order, with a notification in between
+    async fn fake_server(stream: tokio::io::DuplexStream) {
+        let (read, mut write) = tokio::io::split(stream);
+        let mut lines = BufReader::new(read).lines();
+        let mut held: Vec<Value> = vec![];
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            let request: Value = serde_json::from_str(&line).unwrap();
+            if request["method"] == "slow" {
+                continue; // never answered
+            }
+            held.push(request);
+            if held.len() == 2 {
+                let notification = json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/progress",
+                    "params": { "progress": 1 }
+                });
+                write.write_all(format!("{}\n", notification).as_bytes()).await.unwrap();
+                for request in held.drain(..).rev() {
+                    let response = json!({
+                        "jsonrpc": "2.0",
+                        "id": request["id"],
+                        "result": { "method": request["method"] }
+                    });
+                    write.write_all(format!("{}\n", response).as_bytes()).await.unwrap();
+                }
+            }
+        }
+    }
+
+    fn test_client() -> McpClient {
+        let (client_side, server_side) = tokio::io::duplex(4096);
+        tokio::spawn(fake_server(server_side));
+        let (read, write) = tokio::io::split(client_side);
+        McpClient::connect(read, write)
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_and_notifications() {
+        let client = test_client();
+        let notifications = Arc::new(AtomicUsize::new(0));
+        let counter = notifications.clone();
+        client.on_notification(move |method, _| {
+            assert_eq!(method, "notifications/progress");
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let (a, b) = tokio::join!(client.send_request("first", None), client.send_request("second", None));
+        assert_eq!(a.unwrap()["method"], "first");
+        assert_eq!(b.unwrap()["method"], "second");
+        assert_eq!(notifications.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        let client = test_client().with_timeout(Duration::from_millis(50));
+        let err = client.send_request("slow", None).await.unwrap_err();
+        assert!(err.contains("timed out"));
+        assert!(client.dispatcher.pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_fails_requests() {
+        let client = test_client();
+        client.shutdown().await;
+        assert!(client.send_request("first", None).await.is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//! OAuth 2.0 device authorization grant (RFC 8628) for MCP servers
+//!
+//! The user opens `verification_uri`, enters `user_code`, and the resulting
+//! access token is stored as a server secret (`oauth_token` by default) that
+//! the server config references as `${SECRET:oauth_token}`.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Stored (as JSON) in the `oauth` column of an `mcp_servers` row
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OAuthConfig {
+    pub client_id: String,
+    pub device_authorization_url: String,
+    pub token_url: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    /// Secret the access token is saved as
+    #[serde(default = "default_token_secret")]
+    pub token_secret: String,
+}
+
+fn default_token_secret() -> String {
+    "oauth_token".to_string()
+}
+
+impl OAuthConfig {
+    /// GitHub's device flow endpoints, for the GitHub MCP server template
+    pub fn github(client_id: &str, scopes: &[&str]) -> Self {
+        Self {
+            client_id: client_id.to_string(),
+            device_authorization_url: "https://github.com/login/device/code".to_string(),
+            token_url: "https://github.com/login/oauth/access_token".to_string(),
+            scopes: scopes.iter().map(|s| s.to_string()).collect(),
+            token_secret: default_token_secret(),
+        }
+    }
+}
+
+/// What the user needs to finish signing in
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    #[serde(default)]
+    pub verification_uri_complete: Option<String>,
+    pub expires_in: u64,
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+}
+
+fn default_interval() -> u64 {
+    5
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenResponse {
+    access_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+enum PollOutcome {
+    Token(String),
+    Pending,
+    SlowDown,
+}
+
+fn interpret(response: TokenResponse) -> Result<PollOutcome, String> {
+    if let Some(token) = response.access_token {
+        return Ok(PollOutcome::Token(token));
+    }
+    match response.error.as_deref() {
+        Some("authorization_pending") => Ok(PollOutcome::Pending),
+        Some("slow_down") => Ok(PollOutcome::SlowDown),
+        Some("access_denied") => Err("Sign-in was cancelled".to_string()),
+        Some("expired_token") => Err("The sign-in code expired; start again".to_string()),
+        Some(other) => Err(format!(
+            "OAuth error '{}': {}",
+            other,
+            response.error_description.unwrap_or_default()
+        )),
+        None => Err("OAuth server returned neither a token nor an error".to_string()),
+    }
+}
+
+/// Ask for a device code and a user code to show
+pub async fn start_device_flow(config: &OAuthConfig) -> Result<DeviceAuthorization, String> {
+    let scope = config.scopes.join(" ");
+    reqwest::Client::new()
+        .post(&config.device_authorization_url)
+        .header("Accept", "application/json")
+        .form(&[("client_id", config.client_id.as_str()), ("scope", scope.as_str())])
+        .send()
+        .await
+        .map_err(|e| format!("Device authorization request failed: {}", e))?
+        .error_for_status()
+        .map_err(|e| format!("Device authorization request failed: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid device authorization response: {}", e))
+}
+
+/// Poll until the user approves, denies, or the code expires
+pub async fn poll_for_token(config: &OAuthConfig, authorization: &DeviceAuthorization) -> Result<String, String> {
+    let client = reqwest::Client::new();
+    let deadline = Instant::now() + Duration::from_secs(authorization.expires_in);
+    let mut interval = Duration::from_secs(authorization.interval.max(1));
+
+    loop {
+        tokio::time::sleep(interval).await;
+        if Instant::now() >= deadline {
+            return Err("The sign-in code expired; start again".to_string());
+        }
+
+        let response: TokenResponse = client
+            .post(&config.token_url)
+            .header("Accept", "application/json")
+            .form(&[
+                ("client_id", config.client_id.as_str()),
+                ("device_code", authorization.device_code.as_str()),
+                ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+            ])
+            .send()
+            .await
+            .map_err(|e| format!("Token request failed: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("Invalid token response: {}", e))?;
+
+        match interpret(response)? {
+            PollOutcome::Token(token) => return Ok(token),
+            PollOutcome::Pending => {}
+            PollOutcome::SlowDown => interval += Duration::from_secs(5),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(json: &str) -> TokenResponse {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_interpret_token_responses() {
+        assert!(matches!(
+            interpret(response(r#"{"access_token":"gho_1","token_type":"bearer"}"#)),
+            Ok(PollOutcome::Token(t)) if t == "gho_1"
+        ));
+        assert!(matches!(
+            interpret(response(r#"{"error":"authorization_pending"}"#)),
+            Ok(PollOutcome::Pending)
+        ));
+        assert!(matches!(interpret(response(r#"{"error":"slow_down"}"#)), Ok(PollOutcome::SlowDown)));
+        assert!(interpret(response(r#"{"error":"access_denied"}"#)).is_err());
+    }
+
+    #[test]
+    fn test_config_defaults() {
+        let config: OAuthConfig = serde_json::from_str(
+            r#"{"client_id":"abc","device_authorization_url":"https://x/device","token_url":"https://x/token"}"#,
+        )
+        .unwrap();
+        assert_eq!(config.token_secret, "oauth_token");
+        assert!(config.scopes.is_empty());
+    }
+}
@@ -0,0 +1,119 @@
+//! Secrets for MCP server configs
+//!
+//! Values live in the OS keyring under the `anycowork-mcp` service, one entry
+//! per `<server id>:<name>`; only the names are kept in the database. Server
+//! args, env values, URLs and headers reference them as `${SECRET:name}`, and
+//! process environment variables as `${ENV:NAME}`.
+
+use crate::secrets::KeyringStore;
+pub use crate::secrets::{MemoryStore, SecretStore};
+
+/// Where the app keeps MCP server secrets
+pub const KEYRING: KeyringStore = KeyringStore::new("anycowork-mcp");
+
+fn key(server_id: &str, name: &str) -> String {
+    format!("{}:{}", server_id, name)
+}
+
+pub fn get(store: &dyn SecretStore, server_id: &str, name: &str) -> Result<Option<String>, String> {
+    store
+        .get(&key(server_id, name))
+        .map_err(|e| format!("Failed to read secret '{}': {}", name, e))
+}
+
+pub fn set(store: &dyn SecretStore, server_id: &str, name: &str, value: &str) -> Result<(), String> {
+    store
+        .set(&key(server_id, name), value)
+        .map_err(|e| format!("Failed to store secret '{}': {}", name, e))
+}
+
+pub fn delete(store: &dyn SecretStore, server_id: &str, name: &str) -> Result<(), String> {
+    store
+        .delete(&key(server_id, name))
+        .map_err(|e| format!("Failed to delete secret '{}': {}", name, e))
+}
+
+/// Secret names must be simple identifiers so they are easy to reference
+pub fn validate_secret_name(name: &str) -> Result<(), String> {
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        Ok(())
+    } else {
+        Err(format!("Invalid secret name '{}' (use letters, digits, '_' and '-')", name))
+    }
+}
+
+/// Replace `${SECRET:name}` and `${ENV:NAME}` references in `template`
+pub fn expand(template: &str, store: &dyn SecretStore, server_id: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| format!("Unclosed '${{' in '{}'", template))?;
+        let reference = &after[..end];
+
+        let value = if let Some(name) = reference.strip_prefix("SECRET:") {
+            get(store, server_id, name)?
+                .ok_or_else(|| format!("Secret '{}' is not set for this MCP server", name))?
+        } else if let Some(name) = reference.strip_prefix("ENV:") {
+            std::env::var(name).map_err(|_| format!("Environment variable '{}' is not set", name))?
+        } else {
+            // Not ours (e.g. a shell-style ${VAR} meant for the server); keep as-is
+            format!("${{{}}}", reference)
+        };
+        out.push_str(&value);
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Expand every value of name/value pairs
+pub fn expand_pairs(
+    pairs: Vec<(String, String)>,
+    store: &dyn SecretStore,
+    server_id: &str,
+) -> Result<Vec<(String, String)>, String> {
+    pairs
+        .into_iter()
+        .map(|(k, v)| Ok((k, expand(&v, store, server_id)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_references() {
+        let store = MemoryStore::default();
+        set(&store, "srv", "github_token", "ghp_123").unwrap();
+        std::env::set_var("ANYCOWORK_TEST_REGION", "eu");
+
+        assert_eq!(
+            expand("Bearer ${SECRET:github_token}", &store, "srv").unwrap(),
+            "Bearer ghp_123"
+        );
+        assert_eq!(
+            expand("${ENV:ANYCOWORK_TEST_REGION}-${SECRET:github_token}", &store, "srv").unwrap(),
+            "eu-ghp_123"
+        );
+        assert_eq!(expand("${HOME}/data", &store, "srv").unwrap(), "${HOME}/data");
+
+        // Secrets are per server
+        assert!(expand("${SECRET:github_token}", &store, "other").is_err());
+        assert!(expand("${SECRET:github_token", &store, "srv").is_err());
+    }
+
+    #[test]
+    fn test_validate_secret_name() {
+        assert!(validate_secret_name("github_token").is_ok());
+        assert!(validate_secret_name("api-key-2").is_ok());
+        assert!(validate_secret_name("").is_err());
+        assert!(validate_secret_name("with space").is_err());
+        assert!(validate_secret_name("a}b").is_err());
+    }
+}
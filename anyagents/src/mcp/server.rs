@@ -0,0 +1,547 @@
+//! MCP server mode: serve AnyCowork tools (and optionally whole agents) to
+//! other MCP clients such as Claude Desktop over stdio
+//!
+//! There is nobody to ask for permission here: a request raised inside a
+//! call is settled by the agent's own policy and grants, or denied. A
+//! read-only server refuses calls with side effects up front. Exposed agents
+//! run read-only unless the server is told they may make changes.
+//!
+//! The tools of an agent are called the way its own loop calls them: under
+//! its read-only mode, hooks, tool middleware and DLP rules.
+
+use crate::agents::hooks::AgentHooks;
+use crate::agents::AgentLoop;
+use crate::database::DbPool;
+use crate::events::AgentObserver;
+use crate::mcp::types::{JsonRpcError, JsonRpcRequest, JsonRpcResponse};
+use crate::models::Agent;
+use crate::permissions::{
+    dlp, DlpFilter, DlpTarget, PermissionManager, PermissionRequest, PermissionResponse, ReadOnlyPolicy, ScopeEnforcer,
+};
+use crate::runtime::{Runtime, RuntimeBuilder};
+use crate::secrets::Redactor;
+use crate::tools::middleware::ToolMiddlewareChain;
+use crate::tools::{Tool, ToolContext};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Denies the permission requests that reach it: the agent's policy and
+/// grants had their say before a request is raised
+struct DenyingObserver {
+    permissions: Arc<PermissionManager>,
+}
+
+impl AgentObserver for DenyingObserver {
+    fn emit(&self, event: &str, payload: Value) -> Result<(), String> {
+        // Requests of a session come wrapped in an event on its channel; ones
+        // without a session are sent bare on the global channel
+        let request = if event == "permission_request" {
+            Some(payload)
+        } else if payload.get("type").and_then(|t| t.as_str()) == Some("permission_request") {
+            payload.get("request").cloned()
+        } else {
+            None
+        };
+        if let Some(request) = request.and_then(|r| serde_json::from_value::<PermissionRequest>(r).ok()) {
+            self.permissions.respond_as(&request.id, PermissionResponse::Deny, "mcp_server");
+        }
+        Ok(())
+    }
+}
+
+/// Runs a whole agent as one tool call and returns its final answer
+pub struct AgentTool {
+    agent: Agent,
+    tool_name: String,
+    description: String,
+    runtime: Arc<Runtime>,
+    /// Let the agent make changes; it runs read-only otherwise
+    writable: bool,
+}
+
+impl AgentTool {
+    pub fn new(agent: Agent, runtime: Arc<Runtime>, writable: bool) -> Self {
+        let tool_name = format!("agent_{}", slugify(&agent.name));
+        let description = format!(
+            "Ask the AnyCowork agent '{}' to carry out a task and return its final answer. {}",
+            agent.name,
+            agent.description.clone().unwrap_or_default()
+        )
+        .trim()
+        .to_string();
+        Self {
+            agent,
+            tool_name,
+            description,
+            runtime,
+            writable,
+        }
+    }
+}
+
+fn slugify(name: &str) -> String {
+    let slug: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    slug.split('_').filter(|s| !s.is_empty()).collect::<Vec<_>>().join("_")
+}
+
+#[async_trait]
+impl Tool for AgentTool {
+    fn name(&self) -> &str {
+        &self.tool_name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "prompt": { "type": "string", "description": "The task or question for the agent" }
+            },
+            "required": ["prompt"]
+        })
+    }
+
+    async fn execute(&self, args: Value, _ctx: &ToolContext) -> Result<Value, String> {
+        let prompt = args["prompt"].as_str().ok_or("Missing 'prompt' field")?;
+        let mut worker = AgentLoop::new(&self.agent, self.runtime.db_pool().clone()).await;
+        if !self.writable && worker.read_only_policy.is_none() {
+            worker.read_only_policy = Some(ReadOnlyPolicy::default());
+        }
+        let reply = self.runtime.chat(&mut worker, prompt).await?;
+        Ok(Value::String(reply))
+    }
+}
+
+pub struct McpServer {
+    name: String,
+    tools: Vec<Arc<dyn Tool>>,
+    read_only_policy: Option<ReadOnlyPolicy>,
+    permissions: Arc<PermissionManager>,
+    scope: ScopeEnforcer,
+    session_id: String,
+    /// What the agent loop runs around a tool call: hooks, the middleware
+    /// chain and DLP on the result (where incidents are recorded)
+    hooks: Option<Arc<AgentHooks>>,
+    middleware: ToolMiddlewareChain,
+    dlp: DlpFilter,
+    redactor: Redactor,
+    db_pool: Option<DbPool>,
+}
+
+impl McpServer {
+    pub fn new(name: &str, tools: Vec<Box<dyn Tool>>) -> Self {
+        Self {
+            name: name.to_string(),
+            tools: tools.into_iter().map(Arc::from).collect(),
+            read_only_policy: None,
+            permissions: Arc::new(PermissionManager::new()),
+            scope: Default::default(),
+            session_id: format!("mcp-{}", uuid::Uuid::new_v4()),
+            hooks: None,
+            middleware: ToolMiddlewareChain::default(),
+            dlp: DlpFilter::default(),
+            redactor: Redactor::default(),
+            db_pool: None,
+        }
+    }
+
+    /// Serve an agent's tools (filesystem, bash, skills, ...) in its
+    /// workspace, under its permission policy, scope, read-only mode, hooks,
+    /// middleware and DLP rules
+    pub async fn for_agent(agent: &Agent, db_pool: DbPool) -> Self {
+        let worker = AgentLoop::new(agent, db_pool.clone()).await;
+        let permissions = PermissionManager::new();
+        Self {
+            tools: worker.tools.list(),
+            read_only_policy: worker.read_only_policy,
+            permissions: Arc::new(match worker.policy {
+                Some(policy) => permissions.with_policy(policy),
+                None => permissions,
+            }),
+            scope: worker.scope.clone(),
+            hooks: worker.hooks,
+            middleware: worker.middleware,
+            dlp: worker.dlp,
+            redactor: worker.secrets.redactor().clone(),
+            db_pool: Some(db_pool),
+            ..Self::new(&agent.name, vec![])
+        }
+    }
+
+    /// Also expose every agent in `agents` as an `agent_<name>` tool. They
+    /// run read-only unless `writable`, and any approval they need is denied.
+    pub fn with_agents(mut self, agents: Vec<Agent>, db_pool: DbPool, writable: bool) -> Result<Self, String> {
+        let runtime = Arc::new(RuntimeBuilder::new().db_pool(db_pool).permission_handler(|_| false).build()?);
+        for agent in agents {
+            self.tools.push(Arc::new(AgentTool::new(agent, runtime.clone(), writable)));
+        }
+        Ok(self)
+    }
+
+    /// Refuse tool calls with side effects
+    pub fn read_only(mut self) -> Self {
+        self.read_only_policy.get_or_insert_with(ReadOnlyPolicy::default);
+        self
+    }
+
+    fn tool_list(&self) -> Value {
+        let tools: Vec<Value> = self
+            .tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "name": tool.name(),
+                    "description": tool.description(),
+                    "inputSchema": tool.parameters_schema(),
+                })
+            })
+            .collect();
+        json!({ "tools": tools })
+    }
+
+    async fn call_tool(&self, params: &Value) -> Result<Value, JsonRpcError> {
+        let name = params["name"].as_str().ok_or_else(|| rpc_error(INVALID_PARAMS, "Missing tool name"))?;
+        let tool = self
+            .tools
+            .iter()
+            .find(|t| t.name() == name)
+            .ok_or_else(|| rpc_error(INVALID_PARAMS, &format!("Unknown tool '{}'", name)))?;
+        let args = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+        let ctx = ToolContext {
+            permissions: self.permissions.clone(),
+            observer: Some(Arc::new(DenyingObserver {
+                permissions: self.permissions.clone(),
+            })),
+            session_id: self.session_id.clone(),
+            scope: self.scope.clone(),
+            job_id: None,
+        };
+
+        // Tool failures are results the client's model should see, not protocol errors
+        let outcome = self.run_tool(tool.as_ref(), args, &ctx).await;
+        Ok(match outcome {
+            Ok(value) => json!({ "content": [{ "type": "text", "text": result_text(&value) }], "isError": false }),
+            Err(e) => json!({ "content": [{ "type": "text", "text": e }], "isError": true }),
+        })
+    }
+
+    /// One call the way the agent loop makes it: hooks may veto or rewrite
+    /// it, then validation, the read-only policy, the middleware chain, and
+    /// DLP and hooks on the result
+    async fn run_tool(&self, tool: &dyn Tool, mut args: Value, ctx: &ToolContext) -> Result<Value, String> {
+        let name = tool.name();
+        if let Some(hooks) = &self.hooks {
+            hooks
+                .pre_tool_call(ctx, name, &mut args)
+                .await
+                .map_err(|reason| format!("Tool '{}' blocked by hook: {}", name, reason))?;
+        }
+        tool.validate_args(&args).await?;
+        if let Some(policy) = &self.read_only_policy {
+            policy.check(tool, &args)?;
+        }
+
+        let (mut result, success) = match self.middleware.execute(tool, args.clone(), ctx).await {
+            Ok(result) => {
+                let success = tool.verify_result(&result);
+                (result, success)
+            }
+            Err(e) => (Value::String(e.to_string()), false),
+        };
+
+        let report = self.dlp.scan_value(&mut result, DlpTarget::ToolResults);
+        if !report.is_clean() {
+            if let Some(pool) = &self.db_pool {
+                if let Err(e) = dlp::record_incident(pool, &self.session_id, Some(name), DlpTarget::ToolResults, &report) {
+                    log::error!("Failed to record DLP incident: {}", e);
+                }
+            }
+            if report.blocked {
+                return Err(format!("Result withheld: it matches DLP rule(s) {}", report.rule_names().join(", ")));
+            }
+        }
+
+        if let Some(hooks) = &self.hooks {
+            hooks.post_tool_call(ctx, name, &args, &mut result, success).await;
+            self.redactor.redact_value(&mut result);
+        }
+        if success {
+            Ok(result)
+        } else {
+            Err(result_text(&result))
+        }
+    }
+
+    /// Answer one JSON-RPC message; notifications get no response
+    pub async fn handle(&self, request: JsonRpcRequest) -> Option<JsonRpcResponse> {
+        let id = request.id.clone()?;
+        let params = request.params.unwrap_or(Value::Null);
+
+        let result = match request.method.as_str() {
+            "initialize" => Ok(json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": format!("anycowork-{}", slugify(&self.name)), "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(self.tool_list()),
+            "tools/call" => self.call_tool(&params).await,
+            other => Err(rpc_error(METHOD_NOT_FOUND, &format!("Method '{}' not found", other))),
+        };
+
+        Some(match result {
+            Ok(result) => JsonRpcResponse {
+                jsonrpc: "2.0".to_string(),
+                id: Some(id),
+                result: Some(result),
+                error: None,
+            },
+            Err(error) => JsonRpcResponse {
+                jsonrpc: "2.0".to_string(),
+                id: Some(id),
+                result: None,
+                error: Some(error),
+            },
+        })
+    }
+
+    /// Serve newline-delimited JSON-RPC on stdin/stdout until stdin closes.
+    /// Logs must go to stderr; anything else on stdout breaks the protocol.
+    pub async fn serve_stdio(&self) -> Result<(), String> {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        let mut stdout = tokio::io::stdout();
+
+        while let Some(line) = lines.next_line().await.map_err(|e| e.to_string())? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<JsonRpcRequest>(&line) {
+                Ok(request) => self.handle(request).await,
+                Err(e) => Some(JsonRpcResponse {
+                    jsonrpc: "2.0".to_string(),
+                    id: None,
+                    result: None,
+                    error: Some(rpc_error(-32700, &format!("Parse error: {}", e))),
+                }),
+            };
+            if let Some(response) = response {
+                let mut out = serde_json::to_vec(&response).map_err(|e| e.to_string())?;
+                out.push(b'\n');
+                stdout.write_all(&out).await.map_err(|e| e.to_string())?;
+                stdout.flush().await.map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn rpc_error(code: i64, message: &str) -> JsonRpcError {
+    JsonRpcError {
+        code,
+        message: message.to_string(),
+        data: None,
+    }
+}
+
+fn result_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => serde_json::to_string_pretty(other).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct EchoTool;
+
+    #[async_trait]
+    impl Tool for EchoTool {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn description(&self) -> &str {
+            "Echo the text back"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            json!({ "type": "object", "properties": { "text": { "type": "string" } } })
+        }
+
+        async fn execute(&self, args: Value, _ctx: &ToolContext) -> Result<Value, String> {
+            Ok(args["text"].clone())
+        }
+    }
+
+    fn request(id: i64, method: &str, params: Value) -> JsonRpcRequest {
+        JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: Some(json!(id)),
+            method: method.to_string(),
+            params: Some(params),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_and_call_tools() {
+        let server = McpServer::new("Test", vec![Box::new(EchoTool)]);
+
+        let init = server.handle(request(1, "initialize", json!({}))).await.unwrap();
+        assert_eq!(init.result.unwrap()["protocolVersion"], PROTOCOL_VERSION);
+
+        let list = server.handle(request(2, "tools/list", json!({}))).await.unwrap();
+        let tools = list.result.unwrap();
+        assert_eq!(tools["tools"][0]["name"], "echo");
+        assert!(tools["tools"][0]["inputSchema"].is_object());
+
+        let call = server
+            .handle(request(3, "tools/call", json!({ "name": "echo", "arguments": { "text": "hi" } })))
+            .await
+            .unwrap();
+        let result = call.result.unwrap();
+        assert_eq!(result["content"][0]["text"], "hi");
+        assert_eq!(result["isError"], false);
+
+        let unknown = server
+            .handle(request(4, "tools/call", json!({ "name": "nope" })))
+            .await
+            .unwrap();
+        assert_eq!(unknown.error.unwrap().code, INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_read_only_refuses_side_effects() {
+        let server = McpServer::new("Test", vec![Box::new(EchoTool)]).read_only();
+        let call = server
+            .handle(request(1, "tools/call", json!({ "name": "echo", "arguments": { "text": "hi" } })))
+            .await
+            .unwrap();
+        assert_eq!(call.result.unwrap()["isError"], true);
+    }
+
+    struct WriteTool;
+
+    #[async_trait]
+    impl Tool for WriteTool {
+        fn name(&self) -> &str {
+            "write"
+        }
+
+        fn description(&self) -> &str {
+            "Write something, with permission"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            json!({ "type": "object", "properties": {} })
+        }
+
+        async fn execute(&self, _args: Value, ctx: &ToolContext) -> Result<Value, String> {
+            let request = PermissionRequest {
+                id: uuid::Uuid::new_v4().to_string(),
+                permission_type: crate::permissions::PermissionType::FilesystemWrite,
+                message: "Write a file".to_string(),
+                metadata: Default::default(),
+                payload: None,
+            };
+            match ctx.permissions.request_permission(ctx.observer.as_ref(), request).await? {
+                true => Ok(json!("written")),
+                false => Err("Permission denied".to_string()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_permission_requests_are_denied() {
+        let server = McpServer::new("Test", vec![Box::new(WriteTool)]);
+        let call = server
+            .handle(request(1, "tools/call", json!({ "name": "write", "arguments": {} })))
+            .await
+            .unwrap();
+        let result = call.result.unwrap();
+        assert_eq!(result["isError"], true);
+        assert_eq!(result["content"][0]["text"], "Permission denied");
+    }
+
+    #[tokio::test]
+    async fn test_agent_tools_keep_its_read_only_mode_and_dlp() {
+        use crate::schema::agents;
+        use diesel::prelude::*;
+
+        let pool = crate::database::create_test_pool();
+        let workspace = tempfile::tempdir().unwrap();
+        std::fs::write(workspace.path().join("plans.txt"), "Project Falcon ships in May").unwrap();
+        crate::models::settings::set_setting(
+            &pool,
+            crate::permissions::dlp::DLP_SETTING_KEY,
+            "rules:\n  - name: codename\n    pattern: Project Falcon\n    action: block\n",
+        )
+        .unwrap();
+        let agent: Agent = {
+            let mut conn = pool.get().unwrap();
+            diesel::insert_into(agents::table)
+                .values((
+                    agents::id.eq("analyst"),
+                    agents::name.eq("Analyst"),
+                    agents::execution_settings.eq(json!({
+                        "read_only": true,
+                        "policy": { "rules": [{ "tool": "filesystem", "decision": "allow" }] }
+                    })
+                    .to_string()),
+                    agents::workspace_path.eq(workspace.path().to_string_lossy().to_string()),
+                ))
+                .execute(&mut conn)
+                .unwrap();
+            agents::table.find("analyst").first(&mut conn).unwrap()
+        };
+        let server = McpServer::for_agent(&agent, pool).await;
+        let call = |id: i64, args: Value| {
+            server.handle(request(id, "tools/call", json!({ "name": "filesystem", "arguments": args })))
+        };
+
+        let write = call(1, json!({ "operation": "write_file", "path": "notes.txt", "content": "x" }))
+            .await
+            .unwrap()
+            .result
+            .unwrap();
+        assert_eq!(write["isError"], true);
+        assert!(write["content"][0]["text"].as_str().unwrap().starts_with("Read-only mode"));
+        assert!(!workspace.path().join("notes.txt").exists());
+
+        let read = call(2, json!({ "operation": "read_file", "path": "plans.txt" }))
+            .await
+            .unwrap()
+            .result
+            .unwrap();
+        assert_eq!(read["isError"], true);
+        assert_eq!(read["content"][0]["text"], "Result withheld: it matches DLP rule(s) codename");
+    }
+
+    #[tokio::test]
+    async fn test_notifications_get_no_response() {
+        let server = McpServer::new("Test", vec![]);
+        let notification = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: None,
+            method: "notifications/initialized".to_string(),
+            params: None,
+        };
+        assert!(server.handle(notification).await.is_none());
+        assert_eq!(slugify("Riley the Researcher!"), "riley_the_researcher");
+    }
+}
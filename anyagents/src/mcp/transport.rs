@@ -0,0 +1,452 @@
+//! Transports carrying MCP JSON-RPC messages
+//!
+//! - `stdio`: newline-delimited JSON over a child process's stdin/stdout
+//! - `http` (streamable HTTP): every message is POSTed to one URL; the reply is
+//!   plain JSON or an SSE stream of messages, tied together by `Mcp-Session-Id`
+//! - `sse` (the older HTTP+SSE transport): a long-lived GET stream delivers
+//!   messages and announces, in an `endpoint` event, where to POST ours
+//!
+//! Whatever the transport, incoming messages go through the `Dispatcher`.
+
+use dashmap::DashMap;
+use futures::StreamExt;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, CONTENT_TYPE};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot, watch};
+
+/// Called with `(method, params)` for every notification from the server
+pub type NotificationHandler = Arc<dyn Fn(&str, &Value) + Send + Sync>;
+
+pub(crate) type Writer = Arc<tokio::sync::Mutex<Option<Box<dyn AsyncWrite + Send + Unpin>>>>;
+
+const SESSION_HEADER: &str = "mcp-session-id";
+
+/// How long an `sse` server gets to announce its POST endpoint
+const ENDPOINT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Routes incoming messages: responses to the waiting request, notifications
+/// to the handlers, and server requests to a reply
+pub(crate) struct Dispatcher {
+    pub pending: DashMap<i64, oneshot::Sender<Result<Value, String>>>,
+    pub handlers: RwLock<Vec<NotificationHandler>>,
+    /// Replies to server-to-client requests, sent by the client's outbox task
+    replies: mpsc::UnboundedSender<Value>,
+}
+
+impl Dispatcher {
+    pub fn new() -> (Arc<Self>, mpsc::UnboundedReceiver<Value>) {
+        let (replies, outbox) = mpsc::unbounded_channel();
+        let dispatcher = Arc::new(Self {
+            pending: DashMap::new(),
+            handlers: RwLock::new(vec![]),
+            replies,
+        });
+        (dispatcher, outbox)
+    }
+
+    pub fn dispatch(&self, message: Value) {
+        // Streamable HTTP may batch messages
+        if let Value::Array(messages) = message {
+            for message in messages {
+                self.dispatch(message);
+            }
+            return;
+        }
+
+        match (message.get("method").and_then(|m| m.as_str()), message.get("id")) {
+            // Response to one of our requests
+            (None, Some(id)) => {
+                let Some(id) = id.as_i64() else { return };
+                if let Some((_, tx)) = self.pending.remove(&id) {
+                    let result = match message.get("error") {
+                        Some(error) => Err(format!(
+                            "MCP Error {}: {}",
+                            error.get("code").and_then(|c| c.as_i64()).unwrap_or_default(),
+                            error.get("message").and_then(|m| m.as_str()).unwrap_or_default()
+                        )),
+                        None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                    };
+                    let _ = tx.send(result);
+                }
+            }
+            // Request from the server
+            (Some(method), Some(id)) => {
+                let reply = if method == "ping" {
+                    json!({ "jsonrpc": "2.0", "id": id, "result": {} })
+                } else {
+                    json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": -32601, "message": format!("Method '{}' not supported", method) }
+                    })
+                };
+                let _ = self.replies.send(reply);
+            }
+            // Notification
+            (Some(method), None) => {
+                let params = message.get("params").cloned().unwrap_or(Value::Null);
+                log_notification(method, &params);
+                let handlers = self.handlers.read().unwrap().clone();
+                for handler in handlers {
+                    handler(method, &params);
+                }
+            }
+            (None, None) => {}
+        }
+    }
+
+    pub fn fail_pending(&self, reason: &str) {
+        let ids: Vec<i64> = self.pending.iter().map(|entry| *entry.key()).collect();
+        for id in ids {
+            if let Some((_, tx)) = self.pending.remove(&id) {
+                let _ = tx.send(Err(reason.to_string()));
+            }
+        }
+    }
+}
+
+fn log_notification(method: &str, params: &Value) {
+    match method {
+        "notifications/message" => {
+            let text = params.get("data").map(|d| d.to_string()).unwrap_or_default();
+            match params.get("level").and_then(|l| l.as_str()) {
+                Some("error") | Some("critical") | Some("alert") | Some("emergency") => {
+                    log::error!("[mcp] {}", text)
+                }
+                Some("warning") => log::warn!("[mcp] {}", text),
+                Some("debug") => log::debug!("[mcp] {}", text),
+                _ => log::info!("[mcp] {}", text),
+            }
+        }
+        _ => log::debug!("[mcp] {} {}", method, params),
+    }
+}
+
+/// One server-sent event
+#[derive(Debug, PartialEq)]
+pub(crate) struct SseEvent {
+    pub event: String,
+    pub data: String,
+}
+
+/// Incremental `text/event-stream` parser; feed it chunks as they arrive
+#[derive(Default)]
+pub(crate) struct SseParser {
+    buffer: String,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    pub fn feed(&mut self, chunk: &str) -> Vec<SseEvent> {
+        self.buffer.push_str(chunk);
+        let mut events = vec![];
+
+        while let Some(pos) = self.buffer.find('\n') {
+            let line: String = self.buffer.drain(..=pos).collect();
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    events.push(SseEvent {
+                        event: self.event.take().unwrap_or_else(|| "message".to_string()),
+                        data: self.data.join("\n"),
+                    });
+                }
+                self.event = None;
+                self.data.clear();
+            } else if let Some(value) = line.strip_prefix("data:") {
+                self.data.push(value.strip_prefix(' ').unwrap_or(value).to_string());
+            } else if let Some(value) = line.strip_prefix("event:") {
+                self.event = Some(value.trim().to_string());
+            }
+            // `id:`, `retry:` and `:` comments are not needed here
+        }
+        events
+    }
+}
+
+fn header_map(headers: &[(String, String)]) -> Result<HeaderMap, String> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("Invalid header name '{}'", name))?;
+        let value = HeaderValue::from_str(value).map_err(|_| format!("Invalid value for header '{}'", name))?;
+        map.insert(name, value);
+    }
+    Ok(map)
+}
+
+/// Read an SSE body to the end, dispatching `message` events; other events go
+/// to `on_event`
+async fn read_sse(
+    response: reqwest::Response,
+    dispatcher: &Dispatcher,
+    mut on_event: impl FnMut(SseEvent),
+) -> Result<(), String> {
+    let mut parser = SseParser::default();
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        for event in parser.feed(&String::from_utf8_lossy(&chunk)) {
+            if event.event == "message" {
+                match serde_json::from_str::<Value>(&event.data) {
+                    Ok(message) => dispatcher.dispatch(message),
+                    Err(_) => log::debug!("[mcp] ignoring non-JSON event: {}", event.data),
+                }
+            } else {
+                on_event(event);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) struct HttpTransport {
+    client: reqwest::Client,
+    url: String,
+    headers: HeaderMap,
+    session_id: Mutex<Option<String>>,
+    dispatcher: Arc<Dispatcher>,
+}
+
+impl HttpTransport {
+    pub fn new(url: &str, headers: &[(String, String)], dispatcher: Arc<Dispatcher>) -> Result<Self, String> {
+        Ok(Self {
+            client: reqwest::Client::new(),
+            url: url.to_string(),
+            headers: header_map(headers)?,
+            session_id: Mutex::new(None),
+            dispatcher,
+        })
+    }
+
+    fn request(&self, method: reqwest::Method) -> reqwest::RequestBuilder {
+        let mut request = self.client.request(method, &self.url).headers(self.headers.clone());
+        if let Some(session_id) = self.session_id.lock().unwrap().clone() {
+            request = request.header(SESSION_HEADER, session_id);
+        }
+        request
+    }
+
+    async fn send(&self, message: &Value) -> Result<(), String> {
+        let response = self
+            .request(reqwest::Method::POST)
+            .header(ACCEPT, "application/json, text/event-stream")
+            .header(CONTENT_TYPE, "application/json")
+            .json(message)
+            .send()
+            .await
+            .map_err(|e| format!("MCP request failed: {}", e))?;
+
+        if let Some(session_id) = response.headers().get(SESSION_HEADER).and_then(|v| v.to_str().ok()) {
+            *self.session_id.lock().unwrap() = Some(session_id.to_string());
+        }
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("MCP server returned {}: {}", status, body));
+        }
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        if content_type.starts_with("text/event-stream") {
+            // The reply (and any notifications before it) arrive on this stream
+            let dispatcher = self.dispatcher.clone();
+            tokio::spawn(async move {
+                if let Err(e) = read_sse(response, &dispatcher, |_| {}).await {
+                    log::warn!("[mcp] response stream failed: {}", e);
+                }
+            });
+        } else if content_type.starts_with("application/json") {
+            let body: Value = response.json().await.map_err(|e| e.to_string())?;
+            self.dispatcher.dispatch(body);
+        }
+        // 202 Accepted (notifications and replies) has no body
+        Ok(())
+    }
+
+    async fn close(&self) {
+        if self.session_id.lock().unwrap().is_some() {
+            let _ = self.request(reqwest::Method::DELETE).send().await;
+        }
+    }
+}
+
+pub(crate) struct SseTransport {
+    client: reqwest::Client,
+    headers: HeaderMap,
+    endpoint: watch::Receiver<Option<String>>,
+    stream_task: tokio::task::JoinHandle<()>,
+}
+
+impl SseTransport {
+    pub async fn connect(
+        url: &str,
+        headers: &[(String, String)],
+        dispatcher: Arc<Dispatcher>,
+    ) -> Result<Self, String> {
+        let client = reqwest::Client::new();
+        let headers = header_map(headers)?;
+        let base = reqwest::Url::parse(url).map_err(|e| format!("Invalid MCP server URL: {}", e))?;
+
+        let response = client
+            .get(base.clone())
+            .headers(headers.clone())
+            .header(ACCEPT, "text/event-stream")
+            .send()
+            .await
+            .map_err(|e| format!("Failed to connect to MCP server: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("MCP server returned {}", response.status()));
+        }
+
+        let (endpoint_tx, endpoint) = watch::channel(None);
+        let stream_task = tokio::spawn(async move {
+            let result = read_sse(response, &dispatcher, |event| {
+                if event.event == "endpoint" {
+                    match base.join(event.data.trim()) {
+                        Ok(endpoint) => {
+                            let _ = endpoint_tx.send(Some(endpoint.to_string()));
+                        }
+                        Err(e) => log::warn!("[mcp] invalid endpoint '{}': {}", event.data, e),
+                    }
+                }
+            })
+            .await;
+            if let Err(e) = result {
+                log::warn!("[mcp] event stream failed: {}", e);
+            }
+            dispatcher.fail_pending("MCP Server closed connection");
+        });
+
+        Ok(Self {
+            client,
+            headers,
+            endpoint,
+            stream_task,
+        })
+    }
+
+    async fn send(&self, message: &Value) -> Result<(), String> {
+        let mut endpoint = self.endpoint.clone();
+        let endpoint = tokio::time::timeout(ENDPOINT_TIMEOUT, endpoint.wait_for(|e| e.is_some()))
+            .await
+            .map_err(|_| "MCP server did not announce its message endpoint".to_string())?
+            .map_err(|_| "MCP Server closed connection".to_string())?
+            .clone()
+            .unwrap_or_default();
+
+        let response = self
+            .client
+            .post(endpoint)
+            .headers(self.headers.clone())
+            .json(message)
+            .send()
+            .await
+            .map_err(|e| format!("MCP request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("MCP server returned {}", response.status()));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) enum Transport {
+    Stdio(Writer),
+    Http(HttpTransport),
+    Sse(SseTransport),
+}
+
+impl Transport {
+    pub async fn send(&self, message: &Value) -> Result<(), String> {
+        match self {
+            Transport::Stdio(writer) => write_line(writer, message).await,
+            Transport::Http(http) => http.send(message).await,
+            Transport::Sse(sse) => sse.send(message).await,
+        }
+    }
+
+    pub async fn close(&self) {
+        match self {
+            Transport::Stdio(writer) => {
+                writer.lock().await.take();
+            }
+            Transport::Http(http) => http.close().await,
+            Transport::Sse(sse) => sse.stream_task.abort(),
+        }
+    }
+}
+
+async fn write_line(writer: &Writer, message: &Value) -> Result<(), String> {
+    let mut line = serde_json::to_vec(message).map_err(|e| e.to_string())?;
+    line.push(b'\n');
+
+    let mut guard = writer.lock().await;
+    let writer = guard.as_mut().ok_or("MCP client is shut down")?;
+    writer.write_all(&line).await.map_err(|e| e.to_string())?;
+    writer.flush().await.map_err(|e| e.to_string())
+}
+
+pub(crate) fn stdio_writer<W: AsyncWrite + Send + Unpin + 'static>(writer: W) -> Writer {
+    Arc::new(tokio::sync::Mutex::new(Some(Box::new(writer))))
+}
+
+/// Dispatch every line a stdio server writes until it closes its stdout
+pub(crate) async fn read_stdio<R: AsyncRead + Unpin>(reader: R, dispatcher: Arc<Dispatcher>) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        match serde_json::from_str::<Value>(&line) {
+            Ok(message) => dispatcher.dispatch(message),
+            Err(_) => log::debug!("[mcp] ignoring non-JSON line: {}", line),
+        }
+    }
+    dispatcher.fail_pending("MCP Server closed connection");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sse_parser_handles_split_chunks() {
+        let mut parser = SseParser::default();
+        assert!(parser.feed("event: endpoint\ndata: /messages?session").is_empty());
+        let events = parser.feed("=1\n\ndata: {\"id\":1}\r\n\r\n: keep-alive\n\n");
+        assert_eq!(
+            events,
+            vec![
+                SseEvent { event: "endpoint".to_string(), data: "/messages?session=1".to_string() },
+                SseEvent { event: "message".to_string(), data: "{\"id\":1}".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dispatch_batch_and_server_ping() {
+        let (dispatcher, mut outbox) = Dispatcher::new();
+        let (tx, mut rx) = oneshot::channel();
+        dispatcher.pending.insert(7, tx);
+
+        dispatcher.dispatch(json!([
+            { "jsonrpc": "2.0", "id": "srv-1", "method": "ping" },
+            { "jsonrpc": "2.0", "id": 7, "result": { "ok": true } }
+        ]));
+
+        assert_eq!(rx.try_recv().unwrap().unwrap()["ok"], true);
+        assert_eq!(outbox.try_recv().unwrap()["id"], "srv-1");
+    }
+
+    #[test]
+    fn test_header_map_rejects_bad_names() {
+        assert!(header_map(&[("Authorization".to_string(), "Bearer x".to_string())]).is_ok());
+        assert!(header_map(&[("bad header".to_string(), "x".to_string())]).is_err());
+    }
+}
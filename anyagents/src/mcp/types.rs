@@ -25,6 +25,7 @@ pub struct JsonRpcError {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct McpInitializeParams {
     pub protocol_version: String,
     pub capabilities: Value,
@@ -41,6 +42,7 @@ pub struct ClientInfo {
 pub struct McpTool {
     pub name: String,
     pub description: Option<String>,
+    #[serde(rename = "inputSchema", alias = "input_schema")]
     pub input_schema: Value,
 }
 
@@ -0,0 +1,603 @@
+//! Matrix over the client-server API
+//!
+//! Long-polls `/sync` with the bot account's access token. Invites are
+//! accepted, and timeline messages from before the connector started are
+//! skipped. A room with only the bot and one other member is a direct chat.
+//! Everywhere else the bot only hears mentions. Threads (`m.thread`) are kept
+//! when replying, and edits use `m.replace`, so clients show a streamed
+//! answer as a single message.
+
+use super::{
+    AttachmentKind, ChatUser, ConnectorCapabilities, IncomingAttachment, IncomingMessage, MessagingConnector,
+    OutgoingAttachment, OutgoingMessage, SentMessage,
+};
+use async_trait::async_trait;
+use reqwest::Url;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// How long one `/sync` request waits for new events, in milliseconds
+const SYNC_TIMEOUT_MS: u64 = 30_000;
+
+/// Wait before retrying after a failed sync
+const RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Well under the 64 KiB event limit, formatting included
+const MAX_MESSAGE_LEN: usize = 30_000;
+
+/// Marks an `m.audio` message as a voice note (MSC3245)
+const VOICE_KEY: &str = "org.matrix.msc3245.voice";
+
+#[derive(Clone)]
+struct MatrixApi {
+    homeserver: Url,
+    access_token: String,
+    http: reqwest::Client,
+}
+
+impl MatrixApi {
+    /// `segments` are percent-encoded, so room and event ids can go in as is
+    fn url(&self, segments: &[&str]) -> Result<Url, String> {
+        let mut url = self.homeserver.clone();
+        url.path_segments_mut()
+            .map_err(|_| "Invalid homeserver URL".to_string())?
+            .pop_if_empty()
+            .extend(segments);
+        Ok(url)
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<Value, String> {
+        let response = request
+            .bearer_auth(&self.access_token)
+            .send()
+            .await
+            .map_err(|e| format!("Matrix request failed: {}", e))?;
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or(Value::Null);
+        if !status.is_success() {
+            return Err(format!(
+                "Matrix request failed ({}): {}",
+                status,
+                body["error"].as_str().unwrap_or("unknown error")
+            ));
+        }
+        Ok(body)
+    }
+
+    async fn get(&self, segments: &[&str], query: &[(&str, String)]) -> Result<Value, String> {
+        let url = self.url(segments)?;
+        self.send(self.http.get(url).query(query)).await
+    }
+
+    async fn post(&self, segments: &[&str], body: Value) -> Result<Value, String> {
+        let url = self.url(segments)?;
+        self.send(self.http.post(url).json(&body)).await
+    }
+
+    async fn put(&self, segments: &[&str], body: Value) -> Result<Value, String> {
+        let url = self.url(segments)?;
+        self.send(self.http.put(url).json(&body)).await
+    }
+}
+
+/// The bot's own account, to skip its messages and spot mentions
+#[derive(Clone, Debug)]
+struct BotAccount {
+    user_id: String,
+    display_name: Option<String>,
+}
+
+pub struct MatrixConnector {
+    api: MatrixApi,
+    /// Unique per connector run, so transaction ids never repeat
+    txn_prefix: String,
+    txn_counter: AtomicU64,
+    sync_task: std::sync::Mutex<Option<tokio::task::JoinHandle<()>>>,
+}
+
+impl MatrixConnector {
+    pub fn new(homeserver_url: &str, access_token: &str) -> Result<Self, String> {
+        let homeserver = Url::parse(homeserver_url.trim()).map_err(|e| format!("Invalid homeserver URL: {}", e))?;
+        let http = reqwest::Client::builder()
+            // Long enough for a long-polling sync
+            .timeout(Duration::from_millis(SYNC_TIMEOUT_MS) + Duration::from_secs(30))
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            api: MatrixApi {
+                homeserver,
+                access_token: access_token.trim().to_string(),
+                http,
+            },
+            txn_prefix: uuid::Uuid::new_v4().simple().to_string(),
+            txn_counter: AtomicU64::new(0),
+            sync_task: std::sync::Mutex::new(None),
+        })
+    }
+
+    fn next_txn_id(&self) -> String {
+        format!("{}-{}", self.txn_prefix, self.txn_counter.fetch_add(1, Ordering::Relaxed))
+    }
+
+    async fn send_event(&self, room_id: &str, content: Value) -> Result<String, String> {
+        let txn_id = self.next_txn_id();
+        let response = self
+            .api
+            .put(&["_matrix", "client", "v3", "rooms", room_id, "send", "m.room.message", txn_id.as_str()], content)
+            .await?;
+        response["event_id"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or("Matrix did not return an event id".to_string())
+    }
+
+    async fn upload(&self, attachment: &OutgoingAttachment) -> Result<String, String> {
+        let url = self.api.url(&["_matrix", "media", "v3", "upload"])?;
+        let request = self
+            .api
+            .http
+            .post(url)
+            .query(&[("filename", attachment.file_name.as_str())])
+            .header(reqwest::header::CONTENT_TYPE, attachment.mime_type.as_str())
+            .body(attachment.data.clone());
+        let response = self.api.send(request).await?;
+        response["content_uri"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or("Matrix did not return a content URI".to_string())
+    }
+}
+
+#[async_trait]
+impl MessagingConnector for MatrixConnector {
+    fn platform(&self) -> &'static str {
+        "matrix"
+    }
+
+    fn capabilities(&self) -> ConnectorCapabilities {
+        ConnectorCapabilities {
+            edit: true,
+            attachments: true,
+            max_message_len: MAX_MESSAGE_LEN,
+        }
+    }
+
+    async fn connect(&self) -> Result<mpsc::Receiver<IncomingMessage>, String> {
+        // Fails early on a bad token or homeserver
+        let whoami = self.api.get(&["_matrix", "client", "v3", "account", "whoami"], &[]).await?;
+        let user_id = whoami["user_id"]
+            .as_str()
+            .ok_or("Matrix did not say who the access token belongs to")?
+            .to_string();
+        let display_name = self
+            .api
+            .get(&["_matrix", "client", "v3", "profile", user_id.as_str(), "displayname"], &[])
+            .await
+            .ok()
+            .and_then(|p| p["displayname"].as_str().map(str::to_string));
+        let account = BotAccount { user_id, display_name };
+
+        // Only what happens from now on is for us
+        let initial = self
+            .api
+            .get(&["_matrix", "client", "v3", "sync"], &[("timeout", "0".to_string())])
+            .await?;
+        let since = initial["next_batch"].as_str().unwrap_or_default().to_string();
+
+        let (tx, rx) = mpsc::channel(64);
+        let task = tokio::spawn(sync_loop(self.api.clone(), account, since, tx));
+        if let Some(previous) = self.sync_task.lock().unwrap().replace(task) {
+            previous.abort();
+        }
+        Ok(rx)
+    }
+
+    async fn disconnect(&self) {
+        if let Some(task) = self.sync_task.lock().unwrap().take() {
+            task.abort();
+        }
+    }
+
+    async fn send(&self, conversation_id: &str, message: OutgoingMessage) -> Result<SentMessage, String> {
+        let mut first: Option<String> = None;
+        if !message.text.is_empty() || message.attachments.is_empty() {
+            let content = message_content(&message.text, message.thread_id.as_deref(), message.reply_to.as_deref());
+            first = Some(self.send_event(conversation_id, content).await?);
+        }
+        for attachment in &message.attachments {
+            let uri = self.upload(attachment).await?;
+            let mut content = attachment_content(attachment, &uri);
+            if let Some(relation) = relation(message.thread_id.as_deref(), message.reply_to.as_deref()) {
+                content["m.relates_to"] = relation;
+            }
+            let event_id = self.send_event(conversation_id, content).await?;
+            first.get_or_insert(event_id);
+        }
+        Ok(SentMessage {
+            conversation_id: conversation_id.to_string(),
+            message_id: first.unwrap_or_default(),
+        })
+    }
+
+    async fn edit(&self, message: &SentMessage, text: &str) -> Result<(), String> {
+        self.send_event(&message.conversation_id, edit_content(&message.message_id, text))
+            .await
+            .map(|_| ())
+    }
+
+    async fn download(&self, attachment: &IncomingAttachment) -> Result<Vec<u8>, String> {
+        let (server, media_id) = parse_mxc(&attachment.id).ok_or("Not a Matrix content URI")?;
+        let url = self.api.url(&["_matrix", "client", "v1", "media", "download", server, media_id])?;
+        let response = self
+            .api
+            .http
+            .get(url)
+            .bearer_auth(&self.api.access_token)
+            .send()
+            .await
+            .map_err(|e| format!("Download failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("Download failed ({})", response.status()));
+        }
+        response
+            .bytes()
+            .await
+            .map(|b| b.to_vec())
+            .map_err(|e| format!("Download failed: {}", e))
+    }
+}
+
+/// What the connector remembers about a room between syncs
+#[derive(Default)]
+struct RoomInfo {
+    joined_members: Option<u64>,
+    name: Option<String>,
+}
+
+async fn sync_loop(api: MatrixApi, account: BotAccount, mut since: String, tx: mpsc::Sender<IncomingMessage>) {
+    let mut rooms: HashMap<String, RoomInfo> = HashMap::new();
+    loop {
+        let query = [("since", since.clone()), ("timeout", SYNC_TIMEOUT_MS.to_string())];
+        let sync = match api.get(&["_matrix", "client", "v3", "sync"], &query).await {
+            Ok(sync) => sync,
+            Err(e) => {
+                log::error!("Matrix sync failed: {}", e);
+                tokio::time::sleep(RETRY_DELAY).await;
+                continue;
+            }
+        };
+        if let Some(next) = sync["next_batch"].as_str() {
+            since = next.to_string();
+        }
+
+        for room_id in invited_rooms(&sync) {
+            match api.post(&["_matrix", "client", "v3", "join", room_id.as_str()], json!({})).await {
+                Ok(_) => log::info!("Joined Matrix room {}", room_id),
+                Err(e) => log::error!("Failed to join Matrix room {}: {}", room_id, e),
+            }
+        }
+        for (room_id, update) in room_updates(&sync) {
+            let info = rooms.entry(room_id).or_default();
+            if update.joined_members.is_some() {
+                info.joined_members = update.joined_members;
+            }
+            if update.name.is_some() {
+                info.name = update.name;
+            }
+        }
+
+        for mut message in timeline_messages(&sync, &account) {
+            let info = rooms.entry(message.conversation_id.clone()).or_default();
+            if info.joined_members.is_none() {
+                let members = api
+                    .get(&["_matrix", "client", "v3", "rooms", message.conversation_id.as_str(), "joined_members"], &[])
+                    .await;
+                info.joined_members = members
+                    .ok()
+                    .and_then(|m| m["joined"].as_object().map(|joined| joined.len() as u64));
+            }
+            message.is_direct = info.joined_members == Some(2);
+            message.conversation_title = info.name.clone();
+            if tx.send(message).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn invited_rooms(sync: &Value) -> Vec<String> {
+    sync["rooms"]["invite"]
+        .as_object()
+        .map(|rooms| rooms.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Member counts and names that changed in a sync
+fn room_updates(sync: &Value) -> Vec<(String, RoomInfo)> {
+    let Some(joined) = sync["rooms"]["join"].as_object() else {
+        return vec![];
+    };
+    joined
+        .iter()
+        .map(|(room_id, room)| {
+            let name = room["state"]["events"]
+                .as_array()
+                .into_iter()
+                .chain(room["timeline"]["events"].as_array())
+                .flatten()
+                .filter(|e| e["type"].as_str() == Some("m.room.name"))
+                .filter_map(|e| e["content"]["name"].as_str())
+                .next_back()
+                .map(str::to_string);
+            let info = RoomInfo {
+                joined_members: room["summary"]["m.joined_member_count"].as_u64(),
+                name,
+            };
+            (room_id.clone(), info)
+        })
+        .collect()
+}
+
+/// Messages from others in joined rooms. `is_direct` is filled in by the
+/// caller, which knows the rooms' member counts.
+fn timeline_messages(sync: &Value, account: &BotAccount) -> Vec<IncomingMessage> {
+    let Some(joined) = sync["rooms"]["join"].as_object() else {
+        return vec![];
+    };
+    let mut messages = Vec::new();
+    for (room_id, room) in joined {
+        for event in room["timeline"]["events"].as_array().into_iter().flatten() {
+            if event["type"].as_str() != Some("m.room.message") {
+                continue;
+            }
+            let sender = event["sender"].as_str().unwrap_or_default();
+            if sender == account.user_id {
+                continue;
+            }
+            let content = &event["content"];
+            let relation = &content["m.relates_to"];
+            // Edits repeat a message we have already seen
+            if relation["rel_type"].as_str() == Some("m.replace") {
+                continue;
+            }
+            let body = content["body"].as_str().unwrap_or_default();
+            let kind = match content["msgtype"].as_str() {
+                Some("m.text") => None,
+                Some("m.image") => Some(AttachmentKind::Image),
+                Some("m.audio") if content.get(VOICE_KEY).is_some() => Some(AttachmentKind::Voice),
+                Some("m.audio") => Some(AttachmentKind::Audio),
+                Some("m.video") => Some(AttachmentKind::Video),
+                Some("m.file") => Some(AttachmentKind::File),
+                // Notices are other bots talking
+                _ => continue,
+            };
+            let (text, attachments) = match kind {
+                None => (body.to_string(), vec![]),
+                Some(kind) => {
+                    let Some(url) = content["url"].as_str() else {
+                        continue;
+                    };
+                    let attachment = IncomingAttachment {
+                        id: url.to_string(),
+                        kind,
+                        file_name: Some(body.to_string()).filter(|b| !b.is_empty()),
+                        mime_type: content["info"]["mimetype"].as_str().map(str::to_string),
+                    };
+                    (String::new(), vec![attachment])
+                }
+            };
+            let mentions_bot = mentions(content, &text, account);
+            let text = if mentions_bot { strip_mention(&text, account) } else { text };
+            messages.push(IncomingMessage {
+                conversation_id: room_id.clone(),
+                thread_id: match relation["rel_type"].as_str() {
+                    Some("m.thread") => relation["event_id"].as_str().map(str::to_string),
+                    _ => None,
+                },
+                message_id: event["event_id"].as_str().unwrap_or_default().to_string(),
+                sender: Some(ChatUser {
+                    id: sender.to_string(),
+                    name: sender.to_string(),
+                }),
+                text,
+                attachments,
+                is_direct: false,
+                mentions_bot,
+                conversation_title: None,
+            });
+        }
+    }
+    messages
+}
+
+/// Mentioned through `m.mentions`, by user id, or by display name the way
+/// Element inserts it ("Name: ...")
+fn mentions(content: &Value, text: &str, account: &BotAccount) -> bool {
+    let listed = content["m.mentions"]["user_ids"]
+        .as_array()
+        .is_some_and(|ids| ids.iter().any(|id| id.as_str() == Some(account.user_id.as_str())));
+    let lower = text.to_lowercase();
+    listed
+        || lower.contains(&account.user_id.to_lowercase())
+        || account
+            .display_name
+            .as_deref()
+            .is_some_and(|name| !name.is_empty() && lower.starts_with(&name.to_lowercase()))
+}
+
+/// Drop the bot's user id, or a leading display name, from a message
+fn strip_mention(text: &str, account: &BotAccount) -> String {
+    let mut text = text.trim();
+    if let Some(name) = account.display_name.as_deref().filter(|n| !n.is_empty()) {
+        if text.to_lowercase().starts_with(&name.to_lowercase()) && text.is_char_boundary(name.len()) {
+            text = text[name.len()..].trim_start_matches([':', ',']);
+        }
+    }
+    let user_id = account.user_id.to_lowercase();
+    text.split_whitespace()
+        .filter(|word| !word.to_lowercase().starts_with(&user_id))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `m.relates_to` for a message in a thread, or a reply outside of one
+fn relation(thread_id: Option<&str>, reply_to: Option<&str>) -> Option<Value> {
+    match (thread_id, reply_to) {
+        (Some(thread), reply) => Some(json!({
+            "rel_type": "m.thread",
+            "event_id": thread,
+            // Clients without threads show it as a reply
+            "is_falling_back": reply.is_none(),
+            "m.in_reply_to": { "event_id": reply.unwrap_or(thread) },
+        })),
+        (None, Some(reply)) => Some(json!({ "m.in_reply_to": { "event_id": reply } })),
+        (None, None) => None,
+    }
+}
+
+fn message_content(text: &str, thread_id: Option<&str>, reply_to: Option<&str>) -> Value {
+    let mut content = json!({ "msgtype": "m.text", "body": text });
+    if let Some(relation) = relation(thread_id, reply_to) {
+        content["m.relates_to"] = relation;
+    }
+    content
+}
+
+/// Replaces `event_id`; the starred body is for clients that don't apply edits
+fn edit_content(event_id: &str, text: &str) -> Value {
+    json!({
+        "msgtype": "m.text",
+        "body": format!("* {}", text),
+        "m.new_content": { "msgtype": "m.text", "body": text },
+        "m.relates_to": { "rel_type": "m.replace", "event_id": event_id },
+    })
+}
+
+fn attachment_content(attachment: &OutgoingAttachment, uri: &str) -> Value {
+    let msgtype = match attachment.kind {
+        AttachmentKind::Image => "m.image",
+        AttachmentKind::Audio | AttachmentKind::Voice => "m.audio",
+        AttachmentKind::Video => "m.video",
+        AttachmentKind::File => "m.file",
+    };
+    let mut content = json!({
+        "msgtype": msgtype,
+        "body": attachment.file_name,
+        "url": uri,
+        "info": { "mimetype": attachment.mime_type, "size": attachment.data.len() },
+    });
+    if attachment.kind == AttachmentKind::Voice {
+        content[VOICE_KEY] = json!({});
+    }
+    content
+}
+
+/// Server name and media id of an `mxc://server/media` URI
+fn parse_mxc(uri: &str) -> Option<(&str, &str)> {
+    let (server, media_id) = uri.strip_prefix("mxc://")?.split_once('/')?;
+    (!server.is_empty() && !media_id.is_empty() && !media_id.contains('/')).then_some((server, media_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account() -> BotAccount {
+        BotAccount {
+            user_id: "@cowork:example.org".to_string(),
+            display_name: Some("Cowork".to_string()),
+        }
+    }
+
+    fn sync() -> Value {
+        json!({
+            "next_batch": "s2",
+            "rooms": {
+                "invite": { "!new:example.org": {} },
+                "join": {
+                    "!team:example.org": {
+                        "summary": { "m.joined_member_count": 5 },
+                        "state": { "events": [
+                            { "type": "m.room.name", "content": { "name": "Team" } }
+                        ] },
+                        "timeline": { "events": [
+                            { "type": "m.room.message", "sender": "@ada:example.org", "event_id": "$1",
+                              "content": { "msgtype": "m.text", "body": "Cowork: summarize the thread" } },
+                            { "type": "m.room.message", "sender": "@ada:example.org", "event_id": "$2",
+                              "content": { "msgtype": "m.text", "body": "lunch?" } },
+                            { "type": "m.room.message", "sender": "@cowork:example.org", "event_id": "$3",
+                              "content": { "msgtype": "m.text", "body": "Here you go" } },
+                            { "type": "m.room.message", "sender": "@ada:example.org", "event_id": "$4",
+                              "content": { "msgtype": "m.text", "body": "* fixed",
+                                           "m.relates_to": { "rel_type": "m.replace", "event_id": "$2" } } },
+                            { "type": "m.room.message", "sender": "@bob:example.org", "event_id": "$5",
+                              "content": { "msgtype": "m.audio", "body": "voice.ogg",
+                                           "url": "mxc://example.org/abc", "org.matrix.msc3245.voice": {},
+                                           "info": { "mimetype": "audio/ogg" },
+                                           "m.mentions": { "user_ids": ["@cowork:example.org"] },
+                                           "m.relates_to": { "rel_type": "m.thread", "event_id": "$1" } } },
+                            { "type": "m.room.member", "sender": "@carl:example.org", "event_id": "$6",
+                              "content": { "membership": "join" } }
+                        ] }
+                    }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_timeline_messages() {
+        let messages = timeline_messages(&sync(), &account());
+        let ids: Vec<_> = messages.iter().map(|m| m.message_id.as_str()).collect();
+        assert_eq!(ids, ["$1", "$2", "$5"]);
+
+        assert!(messages[0].mentions_bot);
+        assert_eq!(messages[0].text, "summarize the thread");
+        assert!(!messages[1].mentions_bot);
+        assert_eq!(messages[1].text, "lunch?");
+
+        let voice = &messages[2];
+        assert!(voice.mentions_bot);
+        assert_eq!(voice.thread_id.as_deref(), Some("$1"));
+        assert_eq!(voice.attachments[0].kind, AttachmentKind::Voice);
+        assert_eq!(parse_mxc(&voice.attachments[0].id), Some(("example.org", "abc")));
+    }
+
+    #[test]
+    fn test_room_updates() {
+        assert_eq!(invited_rooms(&sync()), ["!new:example.org"]);
+        let updates = room_updates(&sync());
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].1.joined_members, Some(5));
+        assert_eq!(updates[0].1.name.as_deref(), Some("Team"));
+    }
+
+    #[test]
+    fn test_strip_mention() {
+        assert_eq!(strip_mention("Cowork: deploy the docs", &account()), "deploy the docs");
+        assert_eq!(strip_mention("hey @cowork:example.org, hi", &account()), "hey hi");
+        assert_eq!(strip_mention("  plain  text ", &account()), "plain text");
+    }
+
+    #[test]
+    fn test_outgoing_content() {
+        let reply = message_content("hi", None, Some("$1"));
+        assert_eq!(reply["m.relates_to"]["m.in_reply_to"]["event_id"], "$1");
+
+        let threaded = message_content("hi", Some("$root"), None);
+        assert_eq!(threaded["m.relates_to"]["rel_type"], "m.thread");
+        assert_eq!(threaded["m.relates_to"]["is_falling_back"], true);
+        assert!(message_content("hi", None, None).get("m.relates_to").is_none());
+
+        let edit = edit_content("$9", "final answer");
+        assert_eq!(edit["body"], "* final answer");
+        assert_eq!(edit["m.new_content"]["body"], "final answer");
+        assert_eq!(edit["m.relates_to"]["rel_type"], "m.replace");
+
+        assert_eq!(parse_mxc("mxc://example.org/"), None);
+        assert_eq!(parse_mxc("https://example.org/abc"), None);
+    }
+}
@@ -0,0 +1,382 @@
+//! Chat platforms agents can be reached on
+//!
+//! Each platform (Telegram, Matrix, ...) implements `MessagingConnector`:
+//! it turns the platform's updates into `IncomingMessage`s on a channel and
+//! can send, edit and attach files. What happens to a message is up to a
+//! `MessageHandler`. `ConnectorManager` owns the lifecycle shared by all of
+//! them: one running connector per config id, each message handled in its
+//! own task, and a clean disconnect on stop.
+
+pub mod matrix;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{mpsc, RwLock};
+
+/// Who wrote a message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatUser {
+    /// Platform user id, e.g. a Telegram user id or `@alice:matrix.org`
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttachmentKind {
+    Image,
+    Audio,
+    /// A recorded voice note
+    Voice,
+    Video,
+    File,
+}
+
+/// A file on the platform, fetched with `MessagingConnector::download`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IncomingAttachment {
+    /// Platform file reference (Telegram file id, Matrix `mxc://` URI)
+    pub id: String,
+    pub kind: AttachmentKind,
+    pub file_name: Option<String>,
+    pub mime_type: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IncomingMessage {
+    /// Chat, room or channel the message was posted in
+    pub conversation_id: String,
+    /// Thread or forum topic within the conversation
+    pub thread_id: Option<String>,
+    pub message_id: String,
+    pub sender: Option<ChatUser>,
+    pub text: String,
+    pub attachments: Vec<IncomingAttachment>,
+    /// A one-to-one chat with the bot
+    pub is_direct: bool,
+    /// The bot was mentioned or replied to; in group conversations only
+    /// these messages are meant for it
+    pub mentions_bot: bool,
+    /// Title of a group conversation, if the platform has one
+    pub conversation_title: Option<String>,
+}
+
+impl IncomingMessage {
+    /// Whether the bot should answer: anything in a direct chat, mentions
+    /// and replies elsewhere
+    pub fn addressed_to_bot(&self) -> bool {
+        self.is_direct || self.mentions_bot
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OutgoingAttachment {
+    pub kind: AttachmentKind,
+    pub file_name: String,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OutgoingMessage {
+    pub text: String,
+    pub thread_id: Option<String>,
+    /// Message to answer, shown as a reply where the platform supports it
+    pub reply_to: Option<String>,
+    pub attachments: Vec<OutgoingAttachment>,
+}
+
+impl OutgoingMessage {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Answer `message` in its thread, as a reply when it came from a group
+    pub fn reply_to(message: &IncomingMessage, text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            thread_id: message.thread_id.clone(),
+            reply_to: (!message.is_direct).then(|| message.message_id.clone()),
+            attachments: vec![],
+        }
+    }
+}
+
+/// A message the bot sent, for editing it later
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentMessage {
+    pub conversation_id: String,
+    pub message_id: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectorCapabilities {
+    /// Sent messages can be edited, so answers can stream in
+    pub edit: bool,
+    pub attachments: bool,
+    /// Longest text one message may carry
+    pub max_message_len: usize,
+}
+
+#[async_trait]
+pub trait MessagingConnector: Send + Sync {
+    /// Short platform name, e.g. "telegram"
+    fn platform(&self) -> &'static str;
+
+    fn capabilities(&self) -> ConnectorCapabilities;
+
+    /// Start receiving. Messages arrive on the returned channel until
+    /// `disconnect` is called or the connection is lost for good.
+    async fn connect(&self) -> Result<mpsc::Receiver<IncomingMessage>, String>;
+
+    async fn disconnect(&self);
+
+    async fn send(&self, conversation_id: &str, message: OutgoingMessage) -> Result<SentMessage, String>;
+
+    /// Replace the text of a sent message; an error where `capabilities`
+    /// says editing is not supported
+    async fn edit(&self, message: &SentMessage, text: &str) -> Result<(), String>;
+
+    async fn download(&self, attachment: &IncomingAttachment) -> Result<Vec<u8>, String>;
+
+    /// Show the bot as busy in a conversation, where the platform can
+    async fn typing(&self, _conversation_id: &str) {}
+}
+
+/// What to do with the messages of a connector
+#[async_trait]
+pub trait MessageHandler: Send + Sync {
+    async fn handle(&self, connector: Arc<dyn MessagingConnector>, message: IncomingMessage);
+}
+
+struct RunningConnector {
+    connector: Arc<dyn MessagingConnector>,
+    shutdown: mpsc::Sender<()>,
+}
+
+/// Starts, tracks and stops connectors, by config id
+#[derive(Default)]
+pub struct ConnectorManager {
+    running: Arc<RwLock<HashMap<String, RunningConnector>>>,
+}
+
+impl ConnectorManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Connect and hand every incoming message to `handler`, each in its own
+    /// task so a long agent run does not hold up the conversation
+    pub async fn start(
+        &self,
+        id: &str,
+        connector: Arc<dyn MessagingConnector>,
+        handler: Arc<dyn MessageHandler>,
+    ) -> Result<(), String> {
+        if self.running.read().await.contains_key(id) {
+            return Err("Bot is already running".to_string());
+        }
+        let mut incoming = connector.connect().await?;
+        let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
+        self.running.write().await.insert(
+            id.to_string(),
+            RunningConnector {
+                connector: connector.clone(),
+                shutdown: shutdown_tx,
+            },
+        );
+
+        let running = self.running.clone();
+        let id_owned = id.to_string();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    message = incoming.recv() => match message {
+                        Some(message) => {
+                            let connector = connector.clone();
+                            let handler = handler.clone();
+                            tokio::spawn(async move { handler.handle(connector, message).await });
+                        }
+                        None => {
+                            log::info!("{} connector {} closed", connector.platform(), id_owned);
+                            break;
+                        }
+                    },
+                    _ = shutdown_rx.recv() => break,
+                }
+            }
+            connector.disconnect().await;
+            // Unless stopped (and maybe started again) in the meantime
+            let mut running = running.write().await;
+            if running.get(&id_owned).is_some_and(|r| Arc::ptr_eq(&r.connector, &connector)) {
+                running.remove(&id_owned);
+            }
+            log::info!("{} connector {} has been stopped", connector.platform(), id_owned);
+        });
+        Ok(())
+    }
+
+    pub async fn stop(&self, id: &str) -> Result<(), String> {
+        let running = self.running.write().await.remove(id);
+        match running {
+            Some(running) => {
+                let _ = running.shutdown.send(()).await;
+                Ok(())
+            }
+            None => Err("Bot is not running".to_string()),
+        }
+    }
+
+    pub async fn stop_all(&self) {
+        let all: Vec<(String, RunningConnector)> = self.running.write().await.drain().collect();
+        for (id, running) in all {
+            log::info!("Stopping {} connector: {}", running.connector.platform(), id);
+            let _ = running.shutdown.send(()).await;
+        }
+    }
+
+    pub async fn is_running(&self, id: &str) -> bool {
+        self.running.read().await.contains_key(id)
+    }
+
+    pub async fn running_ids(&self) -> Vec<String> {
+        self.running.read().await.keys().cloned().collect()
+    }
+
+    /// The connector running under `id`, e.g. to post into one of its chats
+    pub async fn get(&self, id: &str) -> Option<Arc<dyn MessagingConnector>> {
+        self.running.read().await.get(id).map(|r| r.connector.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// Delivers whatever the test pushes and records what is sent
+    struct FakeConnector {
+        inbox: Mutex<Option<mpsc::Receiver<IncomingMessage>>>,
+        sent: Mutex<Vec<(String, String)>>,
+        disconnected: Mutex<bool>,
+    }
+
+    #[async_trait]
+    impl MessagingConnector for FakeConnector {
+        fn platform(&self) -> &'static str {
+            "fake"
+        }
+
+        fn capabilities(&self) -> ConnectorCapabilities {
+            ConnectorCapabilities {
+                edit: false,
+                attachments: false,
+                max_message_len: 100,
+            }
+        }
+
+        async fn connect(&self) -> Result<mpsc::Receiver<IncomingMessage>, String> {
+            self.inbox.lock().unwrap().take().ok_or("already connected".to_string())
+        }
+
+        async fn disconnect(&self) {
+            *self.disconnected.lock().unwrap() = true;
+        }
+
+        async fn send(&self, conversation_id: &str, message: OutgoingMessage) -> Result<SentMessage, String> {
+            self.sent
+                .lock()
+                .unwrap()
+                .push((conversation_id.to_string(), message.text));
+            Ok(SentMessage {
+                conversation_id: conversation_id.to_string(),
+                message_id: "1".to_string(),
+            })
+        }
+
+        async fn edit(&self, _message: &SentMessage, _text: &str) -> Result<(), String> {
+            Err("not supported".to_string())
+        }
+
+        async fn download(&self, _attachment: &IncomingAttachment) -> Result<Vec<u8>, String> {
+            Err("not supported".to_string())
+        }
+    }
+
+    struct Echo;
+
+    #[async_trait]
+    impl MessageHandler for Echo {
+        async fn handle(&self, connector: Arc<dyn MessagingConnector>, message: IncomingMessage) {
+            let reply = OutgoingMessage::reply_to(&message, format!("echo: {}", message.text));
+            let _ = connector.send(&message.conversation_id, reply).await;
+        }
+    }
+
+    fn message(text: &str) -> IncomingMessage {
+        IncomingMessage {
+            conversation_id: "room".to_string(),
+            thread_id: None,
+            message_id: "m1".to_string(),
+            sender: None,
+            text: text.to_string(),
+            attachments: vec![],
+            is_direct: true,
+            mentions_bot: false,
+            conversation_title: None,
+        }
+    }
+
+    async fn eventually(check: impl Fn() -> bool) {
+        for _ in 0..100 {
+            if check() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("condition not met in time");
+    }
+
+    #[tokio::test]
+    async fn test_connector_lifecycle() {
+        let (tx, rx) = mpsc::channel(8);
+        let connector = Arc::new(FakeConnector {
+            inbox: Mutex::new(Some(rx)),
+            sent: Mutex::new(vec![]),
+            disconnected: Mutex::new(false),
+        });
+        let manager = ConnectorManager::new();
+        manager.start("bot", connector.clone(), Arc::new(Echo)).await.unwrap();
+        assert!(manager.is_running("bot").await);
+        assert!(manager.start("bot", connector.clone(), Arc::new(Echo)).await.is_err());
+
+        tx.send(message("hi")).await.unwrap();
+        eventually(|| connector.sent.lock().unwrap().len() == 1).await;
+        assert_eq!(connector.sent.lock().unwrap()[0], ("room".to_string(), "echo: hi".to_string()));
+
+        manager.stop("bot").await.unwrap();
+        assert!(!manager.is_running("bot").await);
+        eventually(|| *connector.disconnected.lock().unwrap()).await;
+        assert!(manager.stop("bot").await.is_err());
+    }
+
+    #[test]
+    fn test_reply_threading() {
+        let mut incoming = message("hi");
+        assert_eq!(OutgoingMessage::reply_to(&incoming, "a").reply_to, None);
+        assert!(incoming.addressed_to_bot());
+
+        incoming.is_direct = false;
+        incoming.thread_id = Some("t1".to_string());
+        let reply = OutgoingMessage::reply_to(&incoming, "a");
+        assert_eq!(reply.reply_to.as_deref(), Some("m1"));
+        assert_eq!(reply.thread_id.as_deref(), Some("t1"));
+        assert!(!incoming.addressed_to_bot());
+    }
+}
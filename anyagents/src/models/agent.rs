@@ -32,6 +32,8 @@ pub struct Agent {
     pub scope_type: Option<String>,
     pub workspace_path: Option<String>,
     pub avatar: Option<String>,
+    /// JSON `AgentToolsConfig` (see `tools::config`)
+    pub agent_tools: Option<String>,
 }
 
 #[derive(Insertable, Deserialize)]
@@ -63,6 +65,8 @@ pub struct NewAgent {
     pub scope_type: Option<String>,
     pub workspace_path: Option<String>,
     pub avatar: Option<String>,
+    /// JSON `AgentToolsConfig` (see `tools::config`)
+    pub agent_tools: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -78,6 +82,31 @@ pub struct AIConfigDto {
     pub model: String,
     pub temperature: f32,
     pub max_tokens: Option<i32>,
+    #[serde(default)]
+    pub top_p: Option<f64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    #[serde(default)]
+    pub seed: Option<i64>,
+}
+
+impl AIConfigDto {
+    /// The sampling parameters this config asks for, checked against the
+    /// ranges its provider accepts
+    pub fn generation_params(&self) -> Result<crate::llm::GenerationParams, String> {
+        let params = crate::llm::GenerationParams {
+            temperature: Some(f64::from(self.temperature)),
+            top_p: self.top_p,
+            max_tokens: self
+                .max_tokens
+                .map(|m| u64::try_from(m).map_err(|_| "max_tokens must be positive".to_string()))
+                .transpose()?,
+            stop: self.stop.clone(),
+            seed: self.seed,
+        };
+        params.validate(&self.provider, &self.model)?;
+        Ok(params)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -94,6 +123,7 @@ pub struct AgentDto {
     pub created_at: i64,
     pub updated_at: i64,
     pub avatar: Option<String>,
+    pub agent_tools: Option<crate::tools::config::AgentToolsConfig>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -108,9 +138,16 @@ pub struct AgentUpdateDto {
     pub mcp_servers: Option<Vec<String>>,
     pub execution_settings: Option<serde_json::Value>,
     pub platform_configs: Option<String>,
+    #[serde(default)]
+    pub agent_tools: Option<crate::tools::config::AgentToolsConfig>,
 }
 
 impl Agent {
+    /// Sampling parameters for this agent's model calls, from `ai_config`
+    pub fn generation_params(&self) -> crate::llm::GenerationParams {
+        crate::llm::GenerationParams::from_ai_config(&self.ai_config, self.ai_temperature)
+    }
+
     pub fn into_dto(self) -> AgentDto {
         let characteristics = AgentCharacteristicsDto {
             personality: self.personality,
@@ -131,6 +168,9 @@ impl Agent {
             model: self.ai_model,
             temperature: self.ai_temperature,
             max_tokens: Some(4096),
+            top_p: None,
+            stop: Vec::new(),
+            seed: None,
         });
 
         AgentDto {
@@ -162,6 +202,10 @@ impl Agent {
             created_at: self.created_at,
             updated_at: self.updated_at,
             avatar: self.avatar,
+            agent_tools: self
+                .agent_tools
+                .as_deref()
+                .and_then(|t| serde_json::from_str(t).ok()),
         }
     }
 }
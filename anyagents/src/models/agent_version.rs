@@ -0,0 +1,276 @@
+//! Configuration history of the agents
+//!
+//! Every edit of an agent appends a version: a snapshot of what decides how
+//! it behaves (prompt, model, tools, skills, execution settings). Jobs carry
+//! the version they ran with (`ExecutionJob::agent_version`), so an old run
+//! can be read against the configuration of the time. Rolling back restores
+//! a snapshot and records that as a new version; history is never rewritten.
+
+use crate::database::DbPool;
+use crate::models::{Agent, NewAgentSkillAssignment};
+use crate::schema::{agent_skill_assignments, agent_versions, agents};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The versioned part of an agent
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AgentConfigSnapshot {
+    pub name: String,
+    pub description: Option<String>,
+    pub personality: Option<String>,
+    pub tone: Option<String>,
+    pub expertise: Option<String>,
+    pub ai_provider: String,
+    pub ai_model: String,
+    pub ai_temperature: f32,
+    pub ai_config: String,
+    pub system_prompt: Option<String>,
+    pub skills: Option<String>,
+    pub mcp_servers: Option<String>,
+    pub execution_settings: Option<String>,
+    pub agent_tools: Option<String>,
+}
+
+impl AgentConfigSnapshot {
+    pub fn of(agent: &Agent) -> Self {
+        Self {
+            name: agent.name.clone(),
+            description: agent.description.clone(),
+            personality: agent.personality.clone(),
+            tone: agent.tone.clone(),
+            expertise: agent.expertise.clone(),
+            ai_provider: agent.ai_provider.clone(),
+            ai_model: agent.ai_model.clone(),
+            ai_temperature: agent.ai_temperature,
+            ai_config: agent.ai_config.clone(),
+            system_prompt: agent.system_prompt.clone(),
+            skills: agent.skills.clone(),
+            mcp_servers: agent.mcp_servers.clone(),
+            execution_settings: agent.execution_settings.clone(),
+            agent_tools: agent.agent_tools.clone(),
+        }
+    }
+
+    fn skill_ids(&self) -> Vec<String> {
+        self.skills
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+}
+
+#[derive(Queryable, Selectable, Serialize, Debug, Clone)]
+#[diesel(table_name = agent_versions)]
+pub struct AgentVersion {
+    pub id: String,
+    pub agent_id: String,
+    pub version: i32,
+    /// JSON `AgentConfigSnapshot`
+    pub snapshot: String,
+    pub note: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl AgentVersion {
+    pub fn config(&self) -> Result<AgentConfigSnapshot, String> {
+        serde_json::from_str(&self.snapshot).map_err(|e| format!("Invalid snapshot of version {}: {}", self.version, e))
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = agent_versions)]
+struct NewAgentVersion {
+    id: String,
+    agent_id: String,
+    version: i32,
+    snapshot: String,
+    note: Option<String>,
+}
+
+/// Newest first
+pub fn list_versions(pool: &DbPool, agent_id: &str) -> Result<Vec<AgentVersion>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    agent_versions::table
+        .filter(agent_versions::agent_id.eq(agent_id))
+        .order(agent_versions::version.desc())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+pub fn get_version(pool: &DbPool, agent_id: &str, version: i32) -> Result<AgentVersion, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    agent_versions::table
+        .filter(agent_versions::agent_id.eq(agent_id))
+        .filter(agent_versions::version.eq(version))
+        .first(&mut conn)
+        .map_err(|_| format!("Version {} of agent {} not found", version, agent_id))
+}
+
+/// The version the agent runs with now; None before its first recorded edit
+pub fn current_version(pool: &DbPool, agent_id: &str) -> Option<i32> {
+    let mut conn = pool.get().ok()?;
+    agent_versions::table
+        .filter(agent_versions::agent_id.eq(agent_id))
+        .select(diesel::dsl::max(agent_versions::version))
+        .first::<Option<i32>>(&mut conn)
+        .ok()
+        .flatten()
+}
+
+fn latest(conn: &mut SqliteConnection, agent_id: &str) -> QueryResult<Option<AgentVersion>> {
+    agent_versions::table
+        .filter(agent_versions::agent_id.eq(agent_id))
+        .order(agent_versions::version.desc())
+        .first(conn)
+        .optional()
+}
+
+fn append(conn: &mut SqliteConnection, agent: &Agent, note: Option<String>) -> QueryResult<Option<AgentVersion>> {
+    let snapshot = AgentConfigSnapshot::of(agent);
+    let latest = latest(conn, &agent.id)?;
+    if latest.as_ref().and_then(|v| v.config().ok()).as_ref() == Some(&snapshot) {
+        return Ok(None);
+    }
+    let id = uuid::Uuid::new_v4().to_string();
+    diesel::insert_into(agent_versions::table)
+        .values(&NewAgentVersion {
+            id: id.clone(),
+            agent_id: agent.id.clone(),
+            version: latest.map_or(0, |v| v.version) + 1,
+            snapshot: serde_json::to_string(&snapshot).unwrap_or_default(),
+            note,
+        })
+        .execute(conn)?;
+    agent_versions::table.find(&id).first(conn).map(Some)
+}
+
+/// Append the agent's current configuration, unless it is the latest version
+/// already. Call it with the agent as it was before an edit too: agents
+/// edited before versioning existed (or behind its back) get that state kept.
+pub fn record(pool: &DbPool, agent: &Agent, note: Option<String>) -> Result<Option<AgentVersion>, String> {
+    crate::database::serialized_write(pool, |conn| append(conn, agent, note))
+}
+
+/// Put the agent back to `version`, recorded as a new version
+pub fn rollback(pool: &DbPool, agent_id: &str, version: i32) -> Result<Agent, String> {
+    let config = get_version(pool, agent_id, version)?.config()?;
+    crate::database::serialized_write(pool, |conn| {
+        let before: Agent = agents::table.find(agent_id).first(conn)?;
+        append(conn, &before, None)?;
+
+        diesel::update(agents::table.find(agent_id))
+            .set((
+                agents::name.eq(&config.name),
+                agents::description.eq(&config.description),
+                agents::personality.eq(&config.personality),
+                agents::tone.eq(&config.tone),
+                agents::expertise.eq(&config.expertise),
+                agents::ai_provider.eq(&config.ai_provider),
+                agents::ai_model.eq(&config.ai_model),
+                agents::ai_temperature.eq(config.ai_temperature),
+                agents::ai_config.eq(&config.ai_config),
+                agents::system_prompt.eq(&config.system_prompt),
+                agents::skills.eq(&config.skills),
+                agents::mcp_servers.eq(&config.mcp_servers),
+                agents::execution_settings.eq(&config.execution_settings),
+                agents::agent_tools.eq(&config.agent_tools),
+                agents::updated_at.eq(chrono::Utc::now().timestamp()),
+            ))
+            .execute(conn)?;
+
+        // Skill assignments follow the `skills` column, as in update_agent
+        diesel::delete(agent_skill_assignments::table.filter(agent_skill_assignments::agent_id.eq(agent_id)))
+            .execute(conn)?;
+        let assignments: Vec<NewAgentSkillAssignment> = config
+            .skill_ids()
+            .into_iter()
+            .map(|skill_id| NewAgentSkillAssignment {
+                agent_id: agent_id.to_string(),
+                skill_id,
+                created_at: chrono::Utc::now().naive_utc(),
+            })
+            .collect();
+        if !assignments.is_empty() {
+            diesel::insert_into(agent_skill_assignments::table)
+                .values(&assignments)
+                .execute(conn)?;
+        }
+
+        let restored: Agent = agents::table.find(agent_id).first(conn)?;
+        append(conn, &restored, Some(format!("Rolled back to version {}", version)))?;
+        Ok(restored)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+    use crate::models::NewAgent;
+
+    fn insert_agent(pool: &DbPool) -> Agent {
+        let mut conn = pool.get().unwrap();
+        let id = uuid::Uuid::new_v4().to_string();
+        diesel::insert_into(agents::table)
+            .values(&NewAgent {
+                id: id.clone(),
+                name: "Writer".to_string(),
+                description: None,
+                status: "active".to_string(),
+                personality: None,
+                tone: None,
+                expertise: None,
+                ai_provider: "openai".to_string(),
+                ai_model: "gpt-4o".to_string(),
+                ai_temperature: 0.7,
+                ai_config: "{}".to_string(),
+                system_prompt: Some("Write well.".to_string()),
+                permissions: None,
+                working_directories: None,
+                skills: None,
+                mcp_servers: None,
+                messaging_connections: None,
+                knowledge_bases: None,
+                api_keys: None,
+                created_at: 0,
+                updated_at: 0,
+                platform_configs: None,
+                execution_settings: None,
+                scope_type: None,
+                workspace_path: None,
+                avatar: None,
+                agent_tools: None,
+            })
+            .execute(&mut conn)
+            .unwrap();
+        agents::table.find(&id).first(&mut conn).unwrap()
+    }
+
+    #[test]
+    fn test_versions_record_changes_and_roll_back() {
+        let pool = create_test_pool();
+        let agent = insert_agent(&pool);
+        assert!(current_version(&pool, &agent.id).is_none());
+
+        let first = record(&pool, &agent, None).unwrap().unwrap();
+        assert_eq!(first.version, 1);
+        assert!(record(&pool, &agent, None).unwrap().is_none(), "unchanged config is not a new version");
+
+        let edited = Agent { system_prompt: Some("Write tersely.".to_string()), ..agent.clone() };
+        assert_eq!(record(&pool, &edited, None).unwrap().unwrap().version, 2);
+        assert_eq!(current_version(&pool, &agent.id), Some(2));
+
+        // The row still holds the original prompt: that state is kept before rolling back
+        let restored = rollback(&pool, &agent.id, 2).unwrap();
+        assert_eq!(restored.system_prompt.as_deref(), Some("Write tersely."));
+        let versions = list_versions(&pool, &agent.id).unwrap();
+        assert_eq!(versions.iter().map(|v| v.version).collect::<Vec<_>>(), [4, 3, 2, 1]);
+        assert_eq!(versions[0].note.as_deref(), Some("Rolled back to version 2"));
+        assert_eq!(versions[1].config().unwrap().system_prompt.as_deref(), Some("Write well."));
+
+        assert!(rollback(&pool, &agent.id, 9).is_err());
+    }
+}
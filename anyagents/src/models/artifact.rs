@@ -0,0 +1,167 @@
+use crate::database::DbPool;
+use crate::email::attachments::mime_type_for;
+use crate::schema::job_artifacts;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+/// Field of a tool result listing the files it produced; the agent loop
+/// removes it and registers each one as an artifact of the job
+pub const ARTIFACTS_KEY: &str = "_artifacts";
+/// Longest preview kept with an artifact
+pub const PREVIEW_LEN: usize = 500;
+
+/// A file a tool reports as output of the job
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ArtifactOutput {
+    pub kind: String, // file, image, document, report
+    /// Absolute, or relative to the workspace
+    pub path: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Shown in the artifact list; text files get their first lines when unset
+    #[serde(default)]
+    pub preview: Option<String>,
+}
+
+impl ArtifactOutput {
+    pub fn new(kind: &str, path: impl Into<String>) -> Self {
+        Self { kind: kind.to_string(), path: path.into(), title: None, preview: None }
+    }
+}
+
+/// Add `outputs` to a tool result (an object) as artifacts of the job
+pub fn attach(result: &mut Value, outputs: Vec<ArtifactOutput>) {
+    if let (Some(obj), Ok(outputs)) = (result.as_object_mut(), serde_json::to_value(outputs)) {
+        obj.insert(ARTIFACTS_KEY.to_string(), outputs);
+    }
+}
+
+/// Remove and return the artifacts a tool attached to its result
+pub fn take_artifacts(result: &mut Value) -> Vec<ArtifactOutput> {
+    result
+        .as_object_mut()
+        .and_then(|obj| obj.remove(ARTIFACTS_KEY))
+        .and_then(|outputs| serde_json::from_value(outputs).ok())
+        .unwrap_or_default()
+}
+
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = job_artifacts)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct Artifact {
+    pub id: String,
+    pub job_id: String,
+    pub session_id: String,
+    pub kind: String,
+    pub title: String,
+    pub path: String,
+    pub mime_type: String,
+    pub file_size: i32,
+    pub preview: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// Record `output` as produced by `job_id`. The file has to exist.
+pub fn register(
+    pool: &DbPool,
+    session_id: &str,
+    job_id: &str,
+    workspace: &Path,
+    output: ArtifactOutput,
+) -> Result<Artifact, String> {
+    let path = workspace.join(&output.path);
+    let metadata = std::fs::metadata(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if !metadata.is_file() {
+        return Err(format!("{} is not a file", path.display()));
+    }
+    let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let mime_type = mime_type_for(&file_name);
+    let preview = output.preview.or_else(|| {
+        let is_text = mime_type.starts_with("text/") || mime_type == "application/json";
+        is_text.then(|| std::fs::read_to_string(&path).ok()).flatten()
+    });
+
+    let artifact = Artifact {
+        id: uuid::Uuid::new_v4().to_string(),
+        job_id: job_id.to_string(),
+        session_id: session_id.to_string(),
+        kind: output.kind,
+        title: output.title.unwrap_or(file_name),
+        path: path.to_string_lossy().to_string(),
+        mime_type: mime_type.to_string(),
+        file_size: metadata.len().min(i32::MAX as u64) as i32,
+        preview: preview.map(|p| p.chars().take(PREVIEW_LEN).collect()),
+        created_at: chrono::Utc::now().naive_utc(),
+    };
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::insert_into(job_artifacts::table)
+        .values(&artifact)
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    Ok(artifact)
+}
+
+pub fn get_job_artifacts(pool: &DbPool, job_id: &str) -> Result<Vec<Artifact>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    job_artifacts::table
+        .filter(job_artifacts::job_id.eq(job_id))
+        .order(job_artifacts::created_at.asc())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+/// Artifacts of every job in a session, oldest first; only those created
+/// from `since` on when given
+pub fn get_session_artifacts(
+    pool: &DbPool,
+    session_id: &str,
+    since: Option<chrono::NaiveDateTime>,
+) -> Result<Vec<Artifact>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let mut query = job_artifacts::table
+        .filter(job_artifacts::session_id.eq(session_id))
+        .into_boxed();
+    if let Some(since) = since {
+        query = query.filter(job_artifacts::created_at.ge(since));
+    }
+    query
+        .order(job_artifacts::created_at.asc())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+    use serde_json::json;
+
+    #[test]
+    fn test_attach_take_and_register() {
+        let pool = create_test_pool();
+        let workspace = tempfile::tempdir().unwrap();
+        std::fs::write(workspace.path().join("report.md"), "# Q3\nRevenue is up.").unwrap();
+
+        let mut result = json!({"written": "report.md"});
+        attach(&mut result, vec![ArtifactOutput::new("report", "report.md")]);
+        let outputs = take_artifacts(&mut result);
+        assert_eq!(result, json!({"written": "report.md"}));
+        assert_eq!(outputs.len(), 1);
+
+        let artifact = register(&pool, "session-1", "job-1", workspace.path(), outputs[0].clone()).unwrap();
+        assert_eq!(artifact.title, "report.md");
+        assert_eq!(artifact.mime_type, "text/markdown");
+        assert_eq!(artifact.preview.as_deref(), Some("# Q3\nRevenue is up."));
+        assert!(Path::new(&artifact.path).is_absolute());
+
+        let missing = ArtifactOutput::new("file", "missing.txt");
+        assert!(register(&pool, "session-1", "job-1", workspace.path(), missing).is_err());
+
+        assert_eq!(get_job_artifacts(&pool, "job-1").unwrap().len(), 1);
+        let later = chrono::Utc::now().naive_utc() + chrono::Duration::seconds(1);
+        assert!(get_session_artifacts(&pool, "session-1", Some(later)).unwrap().is_empty());
+        assert_eq!(get_session_artifacts(&pool, "session-1", None).unwrap()[0].job_id, "job-1");
+    }
+}
@@ -0,0 +1,53 @@
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::discord_configs)]
+pub struct DiscordConfig {
+    pub id: String,
+    pub bot_token: String,
+    pub agent_id: String,
+    pub is_active: i32,
+    pub allowed_channel_ids: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Deserialize)]
+#[diesel(table_name = crate::schema::discord_configs)]
+pub struct NewDiscordConfig {
+    pub id: String,
+    pub bot_token: String,
+    pub agent_id: String,
+    pub is_active: i32,
+    pub allowed_channel_ids: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(AsChangeset, Deserialize)]
+#[diesel(table_name = crate::schema::discord_configs)]
+pub struct UpdateDiscordConfig {
+    pub bot_token: Option<String>,
+    pub agent_id: Option<String>,
+    pub is_active: Option<i32>,
+    pub allowed_channel_ids: Option<String>,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Serialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::discord_channel_sessions)]
+pub struct DiscordChannelSession {
+    pub config_id: String,
+    pub channel_id: String,
+    pub session_id: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::discord_channel_sessions)]
+pub struct NewDiscordChannelSession {
+    pub config_id: String,
+    pub channel_id: String,
+    pub session_id: String,
+}
@@ -0,0 +1,65 @@
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A real mailbox connected to an agent. The password is kept in the OS
+/// keyring (see `crate::email::credentials`), never in this table.
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::email_accounts)]
+pub struct EmailAccount {
+    pub id: String,
+    pub agent_id: String,
+    pub email_address: String,
+    pub display_name: Option<String>,
+    pub username: String,
+    pub smtp_host: String,
+    pub smtp_port: i32,
+    pub imap_host: String,
+    pub imap_port: i32,
+    pub imap_folder: String,
+    /// When 0, replies to external mail stay in the thread as drafts
+    pub deliver_outbound: i32,
+    pub poll_interval_seconds: i32,
+    /// Highest IMAP UID already injected into the mailbox
+    pub last_seen_uid: i64,
+    pub is_active: i32,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Deserialize)]
+#[diesel(table_name = crate::schema::email_accounts)]
+pub struct NewEmailAccount {
+    pub id: String,
+    pub agent_id: String,
+    pub email_address: String,
+    pub display_name: Option<String>,
+    pub username: String,
+    pub smtp_host: String,
+    pub smtp_port: i32,
+    pub imap_host: String,
+    pub imap_port: i32,
+    pub imap_folder: String,
+    pub deliver_outbound: i32,
+    pub poll_interval_seconds: i32,
+    pub last_seen_uid: i64,
+    pub is_active: i32,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(AsChangeset, Deserialize)]
+#[diesel(table_name = crate::schema::email_accounts)]
+pub struct UpdateEmailAccount {
+    pub email_address: Option<String>,
+    pub display_name: Option<String>,
+    pub username: Option<String>,
+    pub smtp_host: Option<String>,
+    pub smtp_port: Option<i32>,
+    pub imap_host: Option<String>,
+    pub imap_port: Option<i32>,
+    pub imap_folder: Option<String>,
+    pub deliver_outbound: Option<i32>,
+    pub poll_interval_seconds: Option<i32>,
+    pub is_active: Option<i32>,
+    pub updated_at: chrono::NaiveDateTime,
+}
@@ -10,6 +10,11 @@ pub struct Plan {
 pub struct TaskSpec {
     pub id: String,
     pub description: String,
-    /// List of Task IDs that must be completed before this task can start
-    pub dependencies: Vec<String>,
+    /// List of Task IDs that must be completed before this task can start.
+    /// Tasks without dependencies may run in parallel.
+    #[serde(default, alias = "dependencies")]
+    pub depends_on: Vec<String>,
+    /// Team mode: id of the agent that should perform this task (None = lead agent)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<String>,
 }
@@ -2,21 +2,31 @@ use crate::models::Plan;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
 pub struct PlanUpdate {
+    /// Id of the persisted plan (None if the plan could not be saved)
+    #[serde(default)]
+    pub plan_id: Option<String>,
     pub tasks: Vec<TaskState>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
 pub struct TaskState {
     pub id: String,
     pub description: String,
-    pub status: String, // "pending", "running", "completed", "failed"
+    pub status: String, // "pending", "running", "completed", "failed", "skipped"
     pub result: Option<String>,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub assignee: Option<String>,
 }
 
 impl From<Plan> for PlanUpdate {
     fn from(plan: Plan) -> Self {
         Self {
+            plan_id: None,
             tasks: plan
                 .tasks
                 .into_iter()
@@ -25,6 +35,8 @@ impl From<Plan> for PlanUpdate {
                     description: t.description,
                     status: "pending".to_string(),
                     result: None,
+                    depends_on: t.depends_on,
+                    assignee: t.assignee,
                 })
                 .collect(),
         }
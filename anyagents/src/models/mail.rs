@@ -34,6 +34,10 @@ pub struct MailMessage {
     pub recipient_agent_id: Option<String>,
     pub content: String,
     pub created_at: chrono::NaiveDateTime,
+    /// Real address for `external` senders and recipients
+    pub external_address: Option<String>,
+    /// RFC 5322 Message-ID, for mail that went through a real mailbox
+    pub external_message_id: Option<String>,
 }
 
 #[derive(Insertable)]
@@ -47,4 +51,117 @@ pub struct NewMailMessage {
     pub recipient_agent_id: Option<String>,
     pub content: String,
     pub created_at: chrono::NaiveDateTime,
+    /// Real address for `external` senders and recipients
+    pub external_address: Option<String>,
+    /// RFC 5322 Message-ID, for mail that went through a real mailbox
+    pub external_message_id: Option<String>,
+}
+
+/// A file attached to a mail message. The bytes are a copy kept under
+/// `storage_path`, so later edits to the original do not change sent mail.
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::mail_attachments)]
+pub struct MailAttachment {
+    pub id: String,
+    pub message_id: String,
+    pub file_name: String,
+    pub mime_type: String,
+    pub file_size: i32,
+    pub storage_path: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::mail_attachments)]
+pub struct NewMailAttachment {
+    pub id: String,
+    pub message_id: String,
+    pub file_name: String,
+    pub mime_type: String,
+    pub file_size: i32,
+    pub storage_path: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// A recipient of a mail message. The first `to` recipient is also stored on
+/// the message itself; this table holds the full list.
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::mail_recipients)]
+pub struct MailRecipient {
+    pub id: String,
+    pub message_id: String,
+    /// `to`, `cc` or `bcc`
+    pub kind: String,
+    pub recipient_type: String,
+    pub recipient_agent_id: Option<String>,
+    pub external_address: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::mail_recipients)]
+pub struct NewMailRecipient {
+    pub id: String,
+    pub message_id: String,
+    pub kind: String,
+    pub recipient_type: String,
+    pub recipient_agent_id: Option<String>,
+    pub external_address: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// A named set of agents, addressable as one recipient (e.g. `engineering-team`)
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::mail_groups)]
+pub struct MailGroup {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::mail_groups)]
+pub struct NewMailGroup {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::mail_group_members)]
+pub struct NewMailGroupMember {
+    pub group_id: String,
+    pub agent_id: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// A user-defined label; threads can carry any number of them
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::mail_labels)]
+pub struct MailLabel {
+    pub id: String,
+    pub name: String,
+    pub color: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::mail_labels)]
+pub struct NewMailLabel {
+    pub id: String,
+    pub name: String,
+    pub color: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::mail_thread_labels)]
+pub struct NewMailThreadLabel {
+    pub thread_id: String,
+    pub label_id: String,
+    pub created_at: chrono::NaiveDateTime,
 }
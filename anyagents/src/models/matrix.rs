@@ -0,0 +1,61 @@
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::matrix_configs)]
+pub struct MatrixConfig {
+    pub id: String,
+    pub homeserver_url: String,
+    pub access_token: String,
+    pub agent_id: String,
+    pub is_active: i32,
+    pub allowed_room_ids: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Deserialize)]
+#[diesel(table_name = crate::schema::matrix_configs)]
+pub struct NewMatrixConfig {
+    pub id: String,
+    pub homeserver_url: String,
+    pub access_token: String,
+    pub agent_id: String,
+    pub is_active: i32,
+    pub allowed_room_ids: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(AsChangeset, Deserialize)]
+#[diesel(table_name = crate::schema::matrix_configs)]
+pub struct UpdateMatrixConfig {
+    pub homeserver_url: Option<String>,
+    pub access_token: Option<String>,
+    pub agent_id: Option<String>,
+    pub is_active: Option<i32>,
+    pub allowed_room_ids: Option<String>,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// Session of a conversation on any connector built on `crate::messaging`
+#[derive(Queryable, Selectable, Serialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::connector_sessions)]
+pub struct ConnectorSession {
+    pub platform: String,
+    pub config_id: String,
+    pub conversation_id: String,
+    pub thread_id: String,
+    pub session_id: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::connector_sessions)]
+pub struct NewConnectorSession {
+    pub platform: String,
+    pub config_id: String,
+    pub conversation_id: String,
+    pub thread_id: String,
+    pub session_id: String,
+}
@@ -8,7 +8,7 @@ use serde_json;
 pub struct McpServer {
     pub id: String,
     pub name: String,
-    pub server_type: String, // "stdio" or "sse"
+    pub server_type: String, // "stdio", "sse" or "http" (streamable HTTP)
     pub command: Option<String>,
     pub args: Option<String>,
     pub env: Option<String>,
@@ -17,6 +17,9 @@ pub struct McpServer {
     pub template_id: Option<String>,
     pub created_at: i64,
     pub updated_at: i64,
+    pub headers: Option<String>,
+    pub secret_names: Option<String>,
+    pub oauth: Option<String>,
 }
 
 #[derive(Insertable, Deserialize)]
@@ -33,6 +36,9 @@ pub struct NewMcpServer {
     pub template_id: Option<String>,
     pub created_at: i64,
     pub updated_at: i64,
+    pub headers: Option<String>,
+    pub secret_names: Option<String>,
+    pub oauth: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -48,6 +54,10 @@ pub struct McpServerDto {
     pub template_id: Option<String>,
     pub created_at: i64,
     pub updated_at: i64,
+    pub headers: Option<serde_json::Value>,
+    /// Names of the secrets set for this server (values stay in the keyring)
+    pub secret_names: Vec<String>,
+    pub oauth: Option<crate::mcp::oauth::OAuthConfig>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -58,6 +68,8 @@ pub struct McpServerUpdateDto {
     pub args: Option<Vec<String>>,
     pub env: Option<serde_json::Value>,
     pub url: Option<String>,
+    pub headers: Option<serde_json::Value>,
+    pub oauth: Option<crate::mcp::oauth::OAuthConfig>,
     pub is_enabled: Option<bool>,
 }
 
@@ -71,10 +83,14 @@ pub struct McpTemplateDto {
     pub args: Option<Vec<String>>,
     pub env: Option<serde_json::Value>,
     pub url: Option<String>,
+    pub headers: Option<serde_json::Value>,
+    pub oauth: Option<crate::mcp::oauth::OAuthConfig>,
 }
 
 impl McpServer {
     pub fn into_dto(self) -> McpServerDto {
+        let secret_names = self.secret_name_list();
+        let oauth = self.oauth_config();
         McpServerDto {
             id: self.id,
             name: self.name,
@@ -87,6 +103,44 @@ impl McpServer {
             template_id: self.template_id,
             created_at: self.created_at,
             updated_at: self.updated_at,
+            headers: self.headers.and_then(|s| serde_json::from_str(&s).ok()),
+            secret_names,
+            oauth,
         }
     }
+
+    pub fn secret_name_list(&self) -> Vec<String> {
+        self.secret_names
+            .as_deref()
+            .and_then(|s| serde_json::from_str(s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn oauth_config(&self) -> Option<crate::mcp::oauth::OAuthConfig> {
+        self.oauth.as_deref().and_then(|s| serde_json::from_str(s).ok())
+    }
+
+    /// `env` as name/value pairs
+    pub fn env_vars(&self) -> Vec<(String, String)> {
+        string_map(self.env.as_deref())
+    }
+
+    /// `headers` as name/value pairs
+    pub fn header_pairs(&self) -> Vec<(String, String)> {
+        string_map(self.headers.as_deref())
+    }
+}
+
+fn string_map(json: Option<&str>) -> Vec<(String, String)> {
+    json.and_then(|s| serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(s).ok())
+        .map(|map| {
+            map.into_iter()
+                .filter_map(|(k, v)| match v {
+                    serde_json::Value::String(s) => Some((k, s)),
+                    serde_json::Value::Null => None,
+                    other => Some((k, other.to_string())),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
 }
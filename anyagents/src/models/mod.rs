@@ -1,27 +1,71 @@
 pub mod agent;
+pub mod agent_version;
+pub mod discord;
+pub mod email_account;
 pub mod mail;
+pub mod matrix;
 pub mod mcp_server;
 pub mod page;
+pub mod permission_audit;
+pub mod permission_grant;
+pub mod prompt_template;
+pub mod secret;
 pub mod session;
 pub mod settings;
 pub mod skill;
+pub mod slack;
 pub mod telegram;
 
 // Re-export commonly used types
 pub use agent::{AIConfigDto, Agent, AgentCharacteristicsDto, AgentDto, AgentUpdateDto, NewAgent};
-pub use mail::{MailMessage, MailThread, NewMailMessage, NewMailThread};
+pub use discord::{
+    DiscordChannelSession, DiscordConfig, NewDiscordChannelSession, NewDiscordConfig, UpdateDiscordConfig,
+};
+pub use email_account::{EmailAccount, NewEmailAccount, UpdateEmailAccount};
+pub use mail::{
+    MailAttachment, MailGroup, MailLabel, MailMessage, MailRecipient, MailThread, NewMailAttachment, NewMailGroup,
+    NewMailGroupMember, NewMailLabel, NewMailMessage, NewMailRecipient, NewMailThread, NewMailThreadLabel,
+};
+pub use matrix::{
+    ConnectorSession, MatrixConfig, NewConnectorSession, NewMatrixConfig, UpdateMatrixConfig,
+};
 pub use page::{
     Attachment, Block, NewAttachment, NewBlock, NewPage, Page, UpdateBlock, UpdatePage,
 };
-pub use session::{Message, NewMessage, NewSession, Session, UpdateSession};
+pub use permission_audit::{NewPermissionAuditEntry, PermissionAuditEntry, PermissionAuditFilter};
+pub use permission_grant::{GrantScope, NewPermissionGrant, PermissionGrant};
+pub use agent_version::{AgentConfigSnapshot, AgentVersion};
+pub use prompt_template::{NewPromptTemplateVersion, PromptTemplateVersion};
+pub use secret::{AgentSecretGrant, NewSecretRecord, SecretRecord};
+pub use session::{Message, NewMessage, NewSession, Rewind, Session, UpdateSession};
 pub use settings::{NewSetting, Setting, UpdateSetting};
 pub use skill::{
     AgentSkill, AgentSkillAssignment, MarketplaceSkill, NewAgentSkill, NewAgentSkillAssignment,
-    NewSkillFile, ParsedSkill, SandboxConfig, SkillFile, UpdateAgentSkill,
+    NewSkillFile, ParsedSkill, RecipeStep, SandboxConfig, SkillDependencies, SkillExample, SkillFile,
+    SkillInvocation, SkillRecipe, UpdateAgentSkill,
+};
+pub use slack::{
+    NewSlackConfig, NewSlackConversationSession, SlackConfig, SlackConversationSession, UpdateSlackConfig,
+};
+pub use telegram::{
+    NewTelegramChatSession, NewTelegramConfig, TelegramChatSession, TelegramConfig, UpdateTelegramConfig,
 };
-pub use telegram::{NewTelegramConfig, TelegramConfig, UpdateTelegramConfig};
 
+pub mod artifact;
+pub use artifact::{Artifact, ArtifactOutput};
 pub mod execution;
 pub use execution::{Plan, TaskSpec};
 pub mod execution_state;
 pub use execution_state::{PlanUpdate, TaskState};
+pub mod notification;
+pub use notification::Notification;
+pub mod session_event;
+pub use session_event::SessionEvent;
+pub mod plan;
+pub use plan::{PlanRecord, PlanTaskRecord};
+pub mod todo;
+pub use todo::{JobTodos, TodoItem};
+pub mod trigger;
+pub use trigger::AgentTrigger;
+pub mod usage;
+pub use usage::{UsageGroupBy, UsageRange, UsageStats};
@@ -0,0 +1,86 @@
+//! The notification inbox
+//!
+//! Every notification that wasn't muted is kept here (see
+//! `crate::notifications`), unread until the user opens it or marks it read.
+
+use crate::database::DbPool;
+use crate::schema::notifications;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::Serialize;
+
+#[derive(Queryable, Selectable, Insertable, Serialize, Debug, Clone, PartialEq)]
+#[diesel(table_name = notifications)]
+pub struct Notification {
+    pub id: String,
+    pub event: String, // job_completed, approval_needed, mail_received, task_failed
+    pub title: String,
+    pub body: String,
+    pub agent_id: Option<String>,
+    pub session_id: Option<String>,
+    pub is_read: i32,
+    pub created_at: NaiveDateTime,
+}
+
+pub fn insert_notification(pool: &DbPool, notification: &Notification) -> Result<(), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::insert_into(notifications::table)
+        .values(notification)
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Newest first
+pub fn list_notifications(pool: &DbPool, unread_only: bool, limit: i64) -> Result<Vec<Notification>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let mut query = notifications::table.into_boxed();
+    if unread_only {
+        query = query.filter(notifications::is_read.eq(0));
+    }
+    query
+        .order(notifications::created_at.desc())
+        .limit(limit)
+        .select(Notification::as_select())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+pub fn unread_count(pool: &DbPool) -> Result<i64, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    notifications::table
+        .filter(notifications::is_read.eq(0))
+        .count()
+        .get_result(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+/// Mark `ids` read or unread; returns how many changed
+pub fn set_read(pool: &DbPool, ids: &[String], read: bool) -> Result<usize, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let read = i32::from(read);
+    diesel::update(
+        notifications::table
+            .filter(notifications::id.eq_any(ids))
+            .filter(notifications::is_read.ne(read)),
+    )
+    .set(notifications::is_read.eq(read))
+    .execute(&mut conn)
+    .map_err(|e| e.to_string())
+}
+
+pub fn mark_all_read(pool: &DbPool) -> Result<usize, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::update(notifications::table.filter(notifications::is_read.eq(0)))
+        .set(notifications::is_read.eq(1))
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+/// Delete read notifications; returns how many went
+pub fn clear_read(pool: &DbPool) -> Result<usize, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::delete(notifications::table.filter(notifications::is_read.eq(1)))
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())
+}
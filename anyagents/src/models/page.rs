@@ -59,6 +59,10 @@ pub struct Block {
     pub order_index: i32,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
+    /// Bumped by every write, see `pages::collab`
+    pub version: i32,
+    /// Who made the last write: "user", "agent:<session id>" or "file"
+    pub updated_by: Option<String>,
 }
 
 #[derive(Insertable, Deserialize)]
@@ -95,6 +99,11 @@ pub struct Attachment {
     pub file_type: String,
     pub file_size: i32,
     pub created_at: chrono::NaiveDateTime,
+    /// SHA-256 of the content, hex; rows with the same hash share the file
+    pub content_hash: Option<String>,
+    pub mime_type: Option<String>,
+    /// PNG preview of images
+    pub thumbnail_path: Option<String>,
 }
 
 #[derive(Insertable, Deserialize)]
@@ -108,4 +117,9 @@ pub struct NewAttachment {
     pub file_type: String,
     pub file_size: i32,
     pub created_at: chrono::NaiveDateTime,
+    /// SHA-256 of the content, hex; rows with the same hash share the file
+    pub content_hash: Option<String>,
+    pub mime_type: Option<String>,
+    /// PNG preview of images
+    pub thumbnail_path: Option<String>,
 }
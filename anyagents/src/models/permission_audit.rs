@@ -0,0 +1,110 @@
+use crate::database::DbPool;
+use crate::schema::{permission_audit, sessions};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How a permission request was resolved; "mask" is a DLP filter masking
+/// what it found rather than blocking
+pub const RESOLUTIONS: [&str; 4] = ["allow", "deny", "always", "mask"];
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = permission_audit)]
+pub struct PermissionAuditEntry {
+    pub id: String,
+    pub session_id: Option<String>,
+    pub tool: Option<String>,
+    pub permission_type: String,
+    pub resource: Option<String>,
+    pub message: String,
+    pub metadata: String, // JSON object of the request metadata
+    pub resolution: String, // allow, deny, always, mask
+    pub decided_by: String,
+    pub requested_at: chrono::NaiveDateTime,
+    pub decided_at: chrono::NaiveDateTime,
+    pub payload: Option<String>, // JSON PermissionPayload
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = permission_audit)]
+pub struct NewPermissionAuditEntry {
+    pub id: String,
+    pub session_id: Option<String>,
+    pub tool: Option<String>,
+    pub permission_type: String,
+    pub resource: Option<String>,
+    pub message: String,
+    pub metadata: String,
+    pub resolution: String,
+    pub decided_by: String,
+    pub requested_at: chrono::NaiveDateTime,
+    pub decided_at: chrono::NaiveDateTime,
+    pub payload: Option<String>,
+}
+
+/// Which audit entries to list; unset fields match everything
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct PermissionAuditFilter {
+    pub session_id: Option<String>,
+    /// Entries of any session of this agent
+    pub agent_id: Option<String>,
+    pub tool: Option<String>,
+    pub resolution: Option<String>,
+    pub since: Option<chrono::NaiveDateTime>,
+    pub limit: Option<i64>,
+}
+
+const DEFAULT_LIMIT: i64 = 200;
+
+pub fn record_permission_decision(pool: &DbPool, entry: NewPermissionAuditEntry) -> Result<(), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    // A request id is only ever resolved once; replace rather than fail if a
+    // caller reuses one
+    diesel::replace_into(permission_audit::table)
+        .values(&entry)
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Newest first
+pub fn list_permission_audit(
+    pool: &DbPool,
+    filter: &PermissionAuditFilter,
+) -> Result<Vec<PermissionAuditEntry>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let mut query = permission_audit::table.into_boxed();
+    if let Some(session_id) = &filter.session_id {
+        query = query.filter(permission_audit::session_id.eq(session_id));
+    }
+    if let Some(agent_id) = &filter.agent_id {
+        let agent_sessions = sessions::table
+            .filter(sessions::agent_id.eq(agent_id.clone()))
+            .select(sessions::id.nullable());
+        query = query.filter(permission_audit::session_id.eq_any(agent_sessions));
+    }
+    if let Some(tool) = &filter.tool {
+        query = query.filter(permission_audit::tool.eq(tool));
+    }
+    if let Some(resolution) = &filter.resolution {
+        if !RESOLUTIONS.contains(&resolution.as_str()) {
+            return Err(format!("Unknown resolution '{}'", resolution));
+        }
+        query = query.filter(permission_audit::resolution.eq(resolution));
+    }
+    if let Some(since) = filter.since {
+        query = query.filter(permission_audit::decided_at.ge(since));
+    }
+    query
+        .order(permission_audit::decided_at.desc())
+        .limit(filter.limit.unwrap_or(DEFAULT_LIMIT).max(1))
+        .load(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+pub fn get_permission_audit_entry(pool: &DbPool, request_id: &str) -> Result<PermissionAuditEntry, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    permission_audit::table
+        .find(request_id)
+        .first(&mut conn)
+        .map_err(|_| "Audit entry not found".to_string())
+}
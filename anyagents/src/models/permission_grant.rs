@@ -0,0 +1,179 @@
+use crate::database::DbPool;
+use crate::schema::{agents, permission_grants, sessions};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How far an "always allow" answer reaches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GrantScope {
+    /// The session the request came from
+    Session,
+    /// Every session of the session's agent
+    Agent,
+    /// Every agent working in the agent's workspace
+    Workspace,
+    Global,
+}
+
+impl GrantScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GrantScope::Session => "session",
+            GrantScope::Agent => "agent",
+            GrantScope::Workspace => "workspace",
+            GrantScope::Global => "global",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Result<Self, String> {
+        match scope {
+            "session" => Ok(GrantScope::Session),
+            "agent" => Ok(GrantScope::Agent),
+            "workspace" => Ok(GrantScope::Workspace),
+            "global" => Ok(GrantScope::Global),
+            other => Err(format!("Unknown grant scope '{}'", other)),
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = permission_grants)]
+pub struct PermissionGrant {
+    pub id: String,
+    pub scope: String, // session, agent, workspace, global
+    pub scope_id: Option<String>, // session id, agent id or workspace path
+    pub permission_type: String,
+    pub resource: String,
+    pub granted_by: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = permission_grants)]
+pub struct NewPermissionGrant {
+    pub id: String,
+    pub scope: String,
+    pub scope_id: Option<String>,
+    pub permission_type: String,
+    pub resource: String,
+    pub granted_by: String,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+}
+
+/// Agent and workspace a session belongs to, for matching agent and
+/// workspace grants
+#[derive(Debug, Clone, Default)]
+pub struct GrantContext {
+    pub session_id: Option<String>,
+    pub agent_id: Option<String>,
+    pub workspace: Option<String>,
+}
+
+impl GrantContext {
+    pub fn for_session(pool: &DbPool, session_id: Option<&str>) -> Self {
+        let Some(session_id) = session_id else {
+            return Self::default();
+        };
+        let owner = pool.get().ok().and_then(|mut conn| {
+            sessions::table
+                .inner_join(agents::table)
+                .filter(sessions::id.eq(session_id))
+                .select((agents::id, agents::workspace_path))
+                .first::<(String, Option<String>)>(&mut conn)
+                .ok()
+        });
+        let (agent_id, workspace) = match owner {
+            Some((agent_id, workspace)) => (Some(agent_id), workspace),
+            None => (None, None),
+        };
+        Self {
+            session_id: Some(session_id.to_string()),
+            agent_id,
+            workspace,
+        }
+    }
+
+    /// What a grant of `scope` is tied to; None when the session has no such
+    /// owner (e.g. no workspace), Some(None) for global grants
+    pub fn scope_id(&self, scope: GrantScope) -> Option<Option<String>> {
+        match scope {
+            GrantScope::Session => self.session_id.clone().map(Some),
+            GrantScope::Agent => self.agent_id.clone().map(Some),
+            GrantScope::Workspace => self.workspace.clone().map(Some),
+            GrantScope::Global => Some(None),
+        }
+    }
+
+    fn covers(&self, grant: &PermissionGrant) -> bool {
+        GrantScope::parse(&grant.scope)
+            .ok()
+            .and_then(|scope| self.scope_id(scope))
+            .is_some_and(|id| id == grant.scope_id)
+    }
+}
+
+pub fn create_grant(pool: &DbPool, grant: NewPermissionGrant) -> Result<PermissionGrant, String> {
+    GrantScope::parse(&grant.scope)?;
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::insert_into(permission_grants::table)
+        .values(&grant)
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    permission_grants::table
+        .find(&grant.id)
+        .first(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+/// A live grant for this permission that applies in `context`
+pub fn find_grant(
+    pool: &DbPool,
+    permission_type: &str,
+    resource: &str,
+    context: &GrantContext,
+) -> Result<Option<PermissionGrant>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().naive_utc();
+    let candidates: Vec<PermissionGrant> = permission_grants::table
+        .filter(permission_grants::permission_type.eq(permission_type))
+        .filter(permission_grants::resource.eq(resource))
+        .filter(permission_grants::expires_at.is_null().or(permission_grants::expires_at.gt(now)))
+        .load(&mut conn)
+        .map_err(|e| e.to_string())?;
+    Ok(candidates.into_iter().find(|g| context.covers(g)))
+}
+
+/// Newest first
+pub fn list_grants(pool: &DbPool, include_expired: bool) -> Result<Vec<PermissionGrant>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let mut query = permission_grants::table.into_boxed();
+    if !include_expired {
+        let now = chrono::Utc::now().naive_utc();
+        query = query.filter(permission_grants::expires_at.is_null().or(permission_grants::expires_at.gt(now)));
+    }
+    query
+        .order(permission_grants::created_at.desc())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+pub fn revoke_grant(pool: &DbPool, grant_id: &str) -> Result<(), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let deleted = diesel::delete(permission_grants::table.find(grant_id))
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    if deleted == 0 {
+        return Err("Grant not found".to_string());
+    }
+    Ok(())
+}
+
+pub fn purge_expired_grants(pool: &DbPool) -> Result<usize, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().naive_utc();
+    diesel::delete(permission_grants::table.filter(permission_grants::expires_at.le(now)))
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())
+}
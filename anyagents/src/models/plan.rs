@@ -0,0 +1,341 @@
+use crate::database::DbPool;
+use crate::models::{Plan, PlanUpdate, TaskSpec, TaskState};
+use crate::schema::{plan_tasks, plans};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = plans)]
+pub struct PlanRecord {
+    pub id: String,
+    pub session_id: String,
+    pub job_id: String,
+    pub objective: String,
+    pub status: String, // proposed, running, completed, rejected, interrupted
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = plans)]
+pub struct NewPlanRecord {
+    pub id: String,
+    pub session_id: String,
+    pub job_id: String,
+    pub objective: String,
+    pub status: String,
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = plan_tasks)]
+pub struct PlanTaskRecord {
+    pub plan_id: String,
+    pub task_id: String,
+    pub description: String,
+    pub dependencies: String, // JSON array of task ids
+    pub position: i32,
+    pub status: String, // pending, running, completed, failed, skipped
+    pub result: Option<String>,
+    pub updated_at: chrono::NaiveDateTime,
+    pub assignee: Option<String>, // team mode: agent id (None = lead)
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = plan_tasks)]
+pub struct NewPlanTaskRecord {
+    pub plan_id: String,
+    pub task_id: String,
+    pub description: String,
+    pub dependencies: String,
+    pub position: i32,
+    pub status: String,
+    pub assignee: Option<String>,
+}
+
+/// Persist a freshly generated plan with all tasks pending; returns the plan id.
+/// `status` is "running", or "proposed" when the plan awaits user approval.
+pub fn create_plan(
+    pool: &DbPool,
+    session_id: &str,
+    job_id: &str,
+    objective: &str,
+    plan: &Plan,
+    status: &str,
+) -> Result<String, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let plan_id = uuid::Uuid::new_v4().to_string();
+
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        diesel::insert_into(plans::table)
+            .values(NewPlanRecord {
+                id: plan_id.clone(),
+                session_id: session_id.to_string(),
+                job_id: job_id.to_string(),
+                objective: objective.to_string(),
+                status: status.to_string(),
+            })
+            .execute(conn)?;
+        insert_tasks(conn, &plan_id, &plan.tasks)
+    })
+    .map_err(|e| e.to_string())?;
+
+    Ok(plan_id)
+}
+
+fn insert_tasks(conn: &mut SqliteConnection, plan_id: &str, tasks: &[TaskSpec]) -> QueryResult<()> {
+    let rows: Vec<NewPlanTaskRecord> = tasks
+        .iter()
+        .enumerate()
+        .map(|(i, task)| NewPlanTaskRecord {
+            plan_id: plan_id.to_string(),
+            task_id: task.id.clone(),
+            description: task.description.clone(),
+            dependencies: serde_json::to_string(&task.depends_on).unwrap_or_else(|_| "[]".to_string()),
+            position: i as i32,
+            status: "pending".to_string(),
+            assignee: task.assignee.clone(),
+        })
+        .collect();
+    diesel::insert_into(plan_tasks::table)
+        .values(&rows)
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Replace the task list of a plan that is still awaiting approval
+pub fn replace_tasks(pool: &DbPool, plan_id: &str, tasks: &[TaskSpec]) -> Result<(), String> {
+    if get_plan(pool, plan_id)?.status != "proposed" {
+        return Err("Only proposed plans can be edited this way".to_string());
+    }
+    if tasks.is_empty() {
+        return Err("A plan needs at least one task".to_string());
+    }
+    crate::agents::dag::validate_plan(&Plan { tasks: tasks.to_vec() })?;
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        diesel::delete(plan_tasks::table.filter(plan_tasks::plan_id.eq(plan_id))).execute(conn)?;
+        insert_tasks(conn, plan_id, tasks)
+    })
+    .map_err(|e| e.to_string())
+}
+
+pub fn get_plan(pool: &DbPool, plan_id: &str) -> Result<PlanRecord, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    plans::table
+        .find(plan_id)
+        .first(&mut conn)
+        .map_err(|_| format!("Plan not found: {}", plan_id))
+}
+
+/// Plans of a session, newest first
+pub fn get_session_plans(pool: &DbPool, session_id: &str) -> Result<Vec<PlanRecord>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    plans::table
+        .filter(plans::session_id.eq(session_id))
+        .order(plans::created_at.desc())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+pub fn get_plan_tasks(pool: &DbPool, plan_id: &str) -> Result<Vec<PlanTaskRecord>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    plan_tasks::table
+        .filter(plan_tasks::plan_id.eq(plan_id))
+        .order(plan_tasks::position.asc())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+/// Current task states in execution order, as emitted in PlanUpdate events
+pub fn get_plan_update(pool: &DbPool, plan_id: &str) -> Result<PlanUpdate, String> {
+    let tasks = get_plan_tasks(pool, plan_id)?
+        .into_iter()
+        .map(|t| TaskState {
+            id: t.task_id,
+            description: t.description,
+            status: t.status,
+            result: t.result,
+            depends_on: serde_json::from_str(&t.dependencies).unwrap_or_default(),
+            assignee: t.assignee,
+        })
+        .collect();
+
+    Ok(PlanUpdate {
+        plan_id: Some(plan_id.to_string()),
+        tasks,
+    })
+}
+
+pub fn set_plan_status(pool: &DbPool, plan_id: &str, status: &str) -> Result<(), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::update(plans::table.find(plan_id))
+        .set((
+            plans::status.eq(status),
+            plans::updated_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .execute(&mut conn)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+pub fn set_task_status(
+    pool: &DbPool,
+    plan_id: &str,
+    task_id: &str,
+    status: &str,
+    result: Option<&str>,
+) -> Result<(), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::update(plan_tasks::table.find((plan_id, task_id)))
+        .set((
+            plan_tasks::status.eq(status),
+            plan_tasks::result.eq(result),
+            plan_tasks::updated_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .execute(&mut conn)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Set the execution order. `task_ids` must list every task of the plan exactly once.
+pub fn reorder_tasks(pool: &DbPool, plan_id: &str, task_ids: &[String]) -> Result<(), String> {
+    let tasks = get_plan_tasks(pool, plan_id)?;
+    let mut current: Vec<&str> = tasks.iter().map(|t| t.task_id.as_str()).collect();
+    let mut requested: Vec<&str> = task_ids.iter().map(String::as_str).collect();
+    current.sort_unstable();
+    requested.sort_unstable();
+    if current != requested {
+        return Err("Task order must contain every task of the plan exactly once".to_string());
+    }
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        for (position, task_id) in task_ids.iter().enumerate() {
+            diesel::update(plan_tasks::table.find((plan_id, task_id)))
+                .set(plan_tasks::position.eq(position as i32))
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+    .map_err(|e| e.to_string())
+}
+
+/// Skip a task that has not started yet
+pub fn skip_task(pool: &DbPool, plan_id: &str, task_id: &str) -> Result<(), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let updated = diesel::update(
+        plan_tasks::table
+            .find((plan_id, task_id))
+            .filter(plan_tasks::status.eq_any(["pending", "failed"])),
+    )
+    .set((
+        plan_tasks::status.eq("skipped"),
+        plan_tasks::updated_at.eq(chrono::Utc::now().naive_utc()),
+    ))
+    .execute(&mut conn)
+    .map_err(|e| e.to_string())?;
+
+    if updated == 0 {
+        return Err(format!("Task {} is not pending", task_id));
+    }
+    Ok(())
+}
+
+/// Prepare a plan for another run: tasks cut off mid-run go back to pending
+pub fn reset_for_resume(pool: &DbPool, plan_id: &str) -> Result<(), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::update(
+        plan_tasks::table
+            .filter(plan_tasks::plan_id.eq(plan_id))
+            .filter(plan_tasks::status.eq("running")),
+    )
+    .set(plan_tasks::status.eq("pending"))
+    .execute(&mut conn)
+    .map_err(|e| e.to_string())?;
+    drop(conn);
+
+    set_plan_status(pool, plan_id, "running")
+}
+
+/// Mark plans left running (or awaiting approval) by a previous process as interrupted
+pub fn mark_interrupted_plans(pool: &DbPool) -> Result<usize, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::update(plans::table.filter(plans::status.eq_any(["running", "proposed"])))
+        .set(plans::status.eq("interrupted"))
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+
+    fn sample_plan() -> Plan {
+        Plan {
+            tasks: ["a", "b", "c"]
+                .iter()
+                .map(|id| TaskSpec {
+                    id: id.to_string(),
+                    description: format!("Task {}", id),
+                    depends_on: vec![],
+                    assignee: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_plan_lifecycle() {
+        let pool = create_test_pool();
+        let plan_id = create_plan(&pool, "s1", "job1", "Do things", &sample_plan(), "running").unwrap();
+
+        set_task_status(&pool, &plan_id, "a", "completed", Some("done")).unwrap();
+        set_task_status(&pool, &plan_id, "b", "running", None).unwrap();
+        reorder_tasks(&pool, &plan_id, &["a".into(), "c".into(), "b".into()]).unwrap();
+        skip_task(&pool, &plan_id, "c").unwrap();
+        assert!(skip_task(&pool, &plan_id, "a").is_err());
+
+        // Simulate a restart mid-run
+        assert_eq!(mark_interrupted_plans(&pool).unwrap(), 1);
+        assert_eq!(get_plan(&pool, &plan_id).unwrap().status, "interrupted");
+        reset_for_resume(&pool, &plan_id).unwrap();
+
+        let update = get_plan_update(&pool, &plan_id).unwrap();
+        let states: Vec<(&str, &str)> = update
+            .tasks
+            .iter()
+            .map(|t| (t.id.as_str(), t.status.as_str()))
+            .collect();
+        assert_eq!(states, vec![("a", "completed"), ("c", "skipped"), ("b", "pending")]);
+        assert_eq!(get_plan(&pool, &plan_id).unwrap().status, "running");
+    }
+
+    #[test]
+    fn test_replace_tasks_only_while_proposed() {
+        let pool = create_test_pool();
+        let plan_id = create_plan(&pool, "s1", "job1", "Do things", &sample_plan(), "proposed").unwrap();
+
+        let edited = vec![TaskSpec {
+            id: "x".to_string(),
+            description: "Single edited task".to_string(),
+            depends_on: vec![],
+            assignee: None,
+        }];
+        replace_tasks(&pool, &plan_id, &edited).unwrap();
+        let tasks = get_plan_tasks(&pool, &plan_id).unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].description, "Single edited task");
+
+        set_plan_status(&pool, &plan_id, "running").unwrap();
+        assert!(replace_tasks(&pool, &plan_id, &edited).is_err());
+    }
+
+    #[test]
+    fn test_reorder_requires_all_tasks() {
+        let pool = create_test_pool();
+        let plan_id = create_plan(&pool, "s1", "job1", "Do things", &sample_plan(), "running").unwrap();
+        assert!(reorder_tasks(&pool, &plan_id, &["a".into(), "b".into()]).is_err());
+    }
+}
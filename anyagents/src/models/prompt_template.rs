@@ -0,0 +1,110 @@
+use crate::database::DbPool;
+use crate::schema::prompt_templates;
+use diesel::prelude::*;
+use diesel::sqlite::Sqlite;
+use serde::{Deserialize, Serialize};
+
+/// One saved version of a prompt template override (see `crate::prompts`)
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = prompt_templates)]
+pub struct PromptTemplateVersion {
+    pub id: String,
+    pub name: String,
+    pub agent_id: Option<String>, // None: every agent
+    pub version: i32,
+    pub body: String,
+    pub note: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = prompt_templates)]
+pub struct NewPromptTemplateVersion {
+    pub id: String,
+    pub name: String,
+    pub agent_id: Option<String>,
+    pub version: i32,
+    pub body: String,
+    pub note: Option<String>,
+}
+
+/// Versions of one override: a template for one agent, or for all of them
+fn scope<'a>(name: &'a str, agent_id: Option<&'a str>) -> prompt_templates::BoxedQuery<'a, Sqlite> {
+    let query = prompt_templates::table
+        .filter(prompt_templates::name.eq(name))
+        .into_boxed();
+    match agent_id {
+        Some(agent_id) => query.filter(prompt_templates::agent_id.eq(agent_id)),
+        None => query.filter(prompt_templates::agent_id.is_null()),
+    }
+}
+
+/// Newest first
+pub fn list_versions(pool: &DbPool, name: &str, agent_id: Option<&str>) -> Result<Vec<PromptTemplateVersion>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    scope(name, agent_id)
+        .order(prompt_templates::version.desc())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+/// The version in use, if the template is overridden at all
+pub fn latest_version(pool: &DbPool, name: &str, agent_id: Option<&str>) -> Result<Option<PromptTemplateVersion>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    scope(name, agent_id)
+        .order(prompt_templates::version.desc())
+        .first(&mut conn)
+        .optional()
+        .map_err(|e| e.to_string())
+}
+
+pub fn get_version(
+    pool: &DbPool,
+    name: &str,
+    agent_id: Option<&str>,
+    version: i32,
+) -> Result<PromptTemplateVersion, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    scope(name, agent_id)
+        .filter(prompt_templates::version.eq(version))
+        .first(&mut conn)
+        .map_err(|_| format!("Version {} of '{}' not found", version, name))
+}
+
+/// Append a version; it becomes the one in use
+pub fn save_version(
+    pool: &DbPool,
+    name: &str,
+    agent_id: Option<&str>,
+    body: &str,
+    note: Option<String>,
+) -> Result<PromptTemplateVersion, String> {
+    let id = uuid::Uuid::new_v4().to_string();
+    crate::database::serialized_write(pool, |conn| {
+        let current: Option<i32> = scope(name, agent_id)
+            .select(diesel::dsl::max(prompt_templates::version))
+            .first(conn)?;
+        diesel::insert_into(prompt_templates::table)
+            .values(&NewPromptTemplateVersion {
+                id: id.clone(),
+                name: name.to_string(),
+                agent_id: agent_id.map(String::from),
+                version: current.unwrap_or(0) + 1,
+                body: body.to_string(),
+                note,
+            })
+            .execute(conn)?;
+        prompt_templates::table.find(&id).first(conn)
+    })
+}
+
+/// Drop every version of an override, going back to the template below it
+pub fn delete_versions(pool: &DbPool, name: &str, agent_id: Option<&str>) -> Result<usize, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let query = diesel::delete(prompt_templates::table).filter(prompt_templates::name.eq(name));
+    match agent_id {
+        Some(agent_id) => query.filter(prompt_templates::agent_id.eq(agent_id)).execute(&mut conn),
+        None => query.filter(prompt_templates::agent_id.is_null()).execute(&mut conn),
+    }
+    .map_err(|e| e.to_string())
+}
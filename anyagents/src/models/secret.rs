@@ -0,0 +1,145 @@
+use crate::database::DbPool;
+use crate::schema::{agent_secret_grants, secrets};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A named secret; the value itself is in the OS keyring (see `crate::secrets`)
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = secrets)]
+pub struct SecretRecord {
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = secrets)]
+pub struct NewSecretRecord {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = agent_secret_grants)]
+pub struct AgentSecretGrant {
+    pub agent_id: String,
+    pub secret_name: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// Create the record, or update its description if it exists
+pub fn upsert_secret_record(pool: &DbPool, name: &str, description: Option<String>) -> Result<SecretRecord, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let exists = secrets::table
+        .find(name)
+        .first::<SecretRecord>(&mut conn)
+        .optional()
+        .map_err(|e| e.to_string())?
+        .is_some();
+    if exists {
+        diesel::update(secrets::table.find(name))
+            .set((
+                secrets::description.eq(description),
+                secrets::updated_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .execute(&mut conn)
+            .map_err(|e| e.to_string())?;
+    } else {
+        diesel::insert_into(secrets::table)
+            .values(&NewSecretRecord {
+                name: name.to_string(),
+                description,
+            })
+            .execute(&mut conn)
+            .map_err(|e| e.to_string())?;
+    }
+    secrets::table
+        .find(name)
+        .first(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+pub fn list_secret_records(pool: &DbPool) -> Result<Vec<SecretRecord>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    secrets::table
+        .order(secrets::name.asc())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+/// Remove the record along with every agent's grant of it
+pub fn delete_secret_record(pool: &DbPool, name: &str) -> Result<(), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    conn.transaction(|conn| {
+        diesel::delete(agent_secret_grants::table.filter(agent_secret_grants::secret_name.eq(name)))
+            .execute(conn)?;
+        diesel::delete(secrets::table.find(name)).execute(conn)
+    })
+    .map_err(|e: diesel::result::Error| e.to_string())
+    .and_then(|deleted| match deleted {
+        0 => Err("Secret not found".to_string()),
+        _ => Ok(()),
+    })
+}
+
+pub fn grant_secret(pool: &DbPool, agent_id: &str, secret_name: &str) -> Result<(), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    secrets::table
+        .find(secret_name)
+        .first::<SecretRecord>(&mut conn)
+        .map_err(|_| format!("Secret '{}' not found", secret_name))?;
+    diesel::insert_or_ignore_into(agent_secret_grants::table)
+        .values((
+            agent_secret_grants::agent_id.eq(agent_id),
+            agent_secret_grants::secret_name.eq(secret_name),
+        ))
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn revoke_secret(pool: &DbPool, agent_id: &str, secret_name: &str) -> Result<(), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::delete(agent_secret_grants::table.find((agent_id, secret_name)))
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Names of the secrets an agent may use
+pub fn agent_secret_names(pool: &DbPool, agent_id: &str) -> Result<Vec<String>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    agent_secret_grants::table
+        .filter(agent_secret_grants::agent_id.eq(agent_id))
+        .select(agent_secret_grants::secret_name)
+        .order(agent_secret_grants::secret_name.asc())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+
+    #[test]
+    fn test_grants_follow_secret() {
+        let pool = create_test_pool();
+        assert!(grant_secret(&pool, "agent-1", "GITHUB_TOKEN").is_err());
+
+        upsert_secret_record(&pool, "GITHUB_TOKEN", None).unwrap();
+        let updated = upsert_secret_record(&pool, "GITHUB_TOKEN", Some("CI bot".to_string())).unwrap();
+        assert_eq!(updated.description.as_deref(), Some("CI bot"));
+        assert_eq!(list_secret_records(&pool).unwrap().len(), 1);
+
+        grant_secret(&pool, "agent-1", "GITHUB_TOKEN").unwrap();
+        grant_secret(&pool, "agent-1", "GITHUB_TOKEN").unwrap();
+        assert_eq!(agent_secret_names(&pool, "agent-1").unwrap(), vec!["GITHUB_TOKEN".to_string()]);
+        assert!(agent_secret_names(&pool, "agent-2").unwrap().is_empty());
+
+        delete_secret_record(&pool, "GITHUB_TOKEN").unwrap();
+        assert!(agent_secret_names(&pool, "agent-1").unwrap().is_empty());
+        assert!(delete_secret_record(&pool, "GITHUB_TOKEN").is_err());
+    }
+}
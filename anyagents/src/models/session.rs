@@ -1,3 +1,5 @@
+use crate::database::DbPool;
+use crate::schema::messages;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -11,6 +13,9 @@ pub struct Session {
     pub updated_at: chrono::NaiveDateTime,
     pub archived: i32,
     pub pinned: i32,
+    /// 1 once the user set the title; automatic titles leave it alone
+    #[serde(default)]
+    pub title_locked: i32,
 }
 
 #[derive(Insertable, Deserialize)]
@@ -31,11 +36,13 @@ pub struct UpdateSession {
     pub title: Option<String>,
     pub archived: Option<i32>,
     pub pinned: Option<i32>,
+    pub title_locked: Option<i32>,
     pub updated_at: chrono::NaiveDateTime,
 }
 
 #[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
 #[diesel(table_name = crate::schema::messages)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
 pub struct Message {
     pub id: String,
     pub role: String,
@@ -44,6 +51,8 @@ pub struct Message {
     pub created_at: chrono::NaiveDateTime,
     pub metadata_json: Option<String>,
     pub tokens: Option<i32>,
+    /// Set once an edit or regenerate rewound the session past this message
+    pub deleted_at: Option<chrono::NaiveDateTime>,
 }
 
 #[derive(Insertable, Deserialize)]
@@ -56,3 +65,159 @@ pub struct NewMessage {
     pub metadata_json: Option<String>,
     pub tokens: Option<i32>,
 }
+
+/// A session rewound to `message`; the messages after it are soft-deleted
+#[derive(Serialize, Debug, Clone)]
+pub struct Rewind {
+    pub message: Message,
+    pub removed_ids: Vec<String>,
+}
+
+fn live_message(conn: &mut SqliteConnection, message_id: &str) -> Result<Message, String> {
+    messages::table
+        .find(message_id)
+        .filter(messages::deleted_at.is_null())
+        .first(conn)
+        .map_err(|_| "Message not found".to_string())
+}
+
+/// Soft-delete every live message of the session that came after `message`
+fn truncate_after(conn: &mut SqliteConnection, message: &Message) -> QueryResult<Vec<String>> {
+    use diesel::dsl::sql;
+    use diesel::sql_types::{Bool, Text};
+
+    // created_at has one-second resolution; rowid orders messages saved within the same second
+    let later = messages::created_at.gt(message.created_at).or(messages::created_at
+        .eq(message.created_at)
+        .and(sql::<Bool>("rowid > (SELECT rowid FROM messages WHERE id = ").bind::<Text, _>(message.id.clone()).sql(")")));
+    let ids: Vec<String> = messages::table
+        .filter(messages::session_id.eq(&message.session_id))
+        .filter(messages::deleted_at.is_null())
+        .filter(later)
+        .order(messages::created_at.asc())
+        .select(messages::id)
+        .load(conn)?;
+    diesel::update(messages::table.filter(messages::id.eq_any(&ids)))
+        .set(messages::deleted_at.eq(chrono::Utc::now().naive_utc()))
+        .execute(conn)?;
+    Ok(ids)
+}
+
+/// Replace the text of a user message and drop everything after it
+pub fn edit_user_message(pool: &DbPool, message_id: &str, content: &str) -> Result<Rewind, String> {
+    if content.trim().is_empty() {
+        return Err("Message cannot be empty".to_string());
+    }
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let mut message = live_message(&mut conn, message_id)?;
+    if message.role != "user" {
+        return Err("Only user messages can be edited".to_string());
+    }
+    let removed_ids = crate::database::serialized_write(pool, |conn| {
+        diesel::update(messages::table.find(message_id))
+            .set(messages::content.eq(content))
+            .execute(conn)?;
+        truncate_after(conn, &message)
+    })
+    .map_err(|e| format!("Failed to edit message: {}", e))?;
+    message.content = content.to_string();
+    Ok(Rewind { message, removed_ids })
+}
+
+/// Rewind to the user message a regenerate of `message_id` re-runs: the
+/// message itself when the user sent it, otherwise the prompt it answered
+pub fn rewind_for_regenerate(pool: &DbPool, message_id: &str) -> Result<Rewind, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let target = live_message(&mut conn, message_id)?;
+    let message = if target.role == "user" {
+        target
+    } else {
+        messages::table
+            .filter(messages::session_id.eq(&target.session_id))
+            .filter(messages::deleted_at.is_null())
+            .filter(messages::role.eq("user"))
+            .filter(messages::created_at.le(target.created_at))
+            .order(messages::created_at.desc())
+            .first(&mut conn)
+            .map_err(|_| "No user message to regenerate from".to_string())?
+    };
+    let removed_ids = crate::database::serialized_write(pool, |conn| truncate_after(conn, &message))
+        .map_err(|e| format!("Failed to rewind session: {}", e))?;
+    Ok(Rewind { message, removed_ids })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+    use crate::schema::{agents, sessions};
+
+    fn seed(pool: &DbPool) -> Vec<String> {
+        let mut conn = pool.get().unwrap();
+        diesel::insert_into(agents::table)
+            .values((agents::id.eq("agent-1"), agents::name.eq("Writer")))
+            .execute(&mut conn)
+            .unwrap();
+        diesel::insert_into(sessions::table)
+            .values((sessions::id.eq("session-1"), sessions::agent_id.eq("agent-1")))
+            .execute(&mut conn)
+            .unwrap();
+        let start = chrono::Utc::now().naive_utc() - chrono::Duration::minutes(10);
+        [("user", "Draft a memo"), ("assistant", "Here it is"), ("user", "Shorter"), ("assistant", "Done")]
+            .iter()
+            .enumerate()
+            .map(|(i, (role, content))| {
+                let id = uuid::Uuid::new_v4().to_string();
+                diesel::insert_into(messages::table)
+                    .values((
+                        messages::id.eq(&id),
+                        messages::role.eq(role),
+                        messages::content.eq(content),
+                        messages::session_id.eq("session-1"),
+                        messages::created_at.eq(start + chrono::Duration::seconds(i as i64)),
+                    ))
+                    .execute(&mut conn)
+                    .unwrap();
+                id
+            })
+            .collect()
+    }
+
+    fn live_ids(pool: &DbPool) -> Vec<String> {
+        messages::table
+            .filter(messages::deleted_at.is_null())
+            .order(messages::created_at.asc())
+            .select(messages::id)
+            .load(&mut pool.get().unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_edit_truncates_history() {
+        let pool = create_test_pool();
+        let ids = seed(&pool);
+
+        assert!(edit_user_message(&pool, &ids[1], "x").is_err());
+        let rewind = edit_user_message(&pool, &ids[0], "Draft a letter").unwrap();
+        assert_eq!(rewind.message.content, "Draft a letter");
+        assert_eq!(rewind.removed_ids, ids[1..].to_vec());
+        assert_eq!(live_ids(&pool), vec![ids[0].clone()]);
+        // Removed messages can no longer be edited or regenerated
+        assert!(edit_user_message(&pool, &ids[2], "Longer").is_err());
+        assert!(rewind_for_regenerate(&pool, &ids[3]).is_err());
+    }
+
+    #[test]
+    fn test_regenerate_rewinds_to_prompt() {
+        let pool = create_test_pool();
+        let ids = seed(&pool);
+
+        let rewind = rewind_for_regenerate(&pool, &ids[3]).unwrap();
+        assert_eq!(rewind.message.id, ids[2]);
+        assert_eq!(rewind.removed_ids, vec![ids[3].clone()]);
+
+        let rewind = rewind_for_regenerate(&pool, &ids[0]).unwrap();
+        assert_eq!(rewind.removed_ids, vec![ids[1].clone(), ids[2].clone()]);
+        assert_eq!(live_ids(&pool), vec![ids[0].clone()]);
+    }
+}
@@ -0,0 +1,80 @@
+//! Events a session's jobs streamed, as kept by `crate::event_log`
+//!
+//! `id` grows with every event, so it doubles as the cursor a window passes
+//! back to read only what it hasn't seen.
+
+use crate::database::DbPool;
+use crate::schema::session_events;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::Serialize;
+
+#[derive(Queryable, Selectable, Serialize, Debug, Clone)]
+#[diesel(table_name = session_events)]
+pub struct SessionEvent {
+    pub id: i64,
+    pub session_id: String,
+    pub job_id: Option<String>,
+    pub event_type: String,
+    /// The event as emitted (an `AgentEvent`), JSON
+    pub payload: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = session_events)]
+pub struct NewSessionEvent {
+    pub session_id: String,
+    pub job_id: Option<String>,
+    pub event_type: String,
+    pub payload: String,
+    pub created_at: NaiveDateTime,
+}
+
+pub fn insert_session_events(pool: &DbPool, events: &[NewSessionEvent]) -> Result<(), String> {
+    if events.is_empty() {
+        return Ok(());
+    }
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::insert_into(session_events::table)
+        .values(events)
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Events of `session_id` after the `since` cursor, oldest first
+pub fn get_session_events(
+    pool: &DbPool,
+    session_id: &str,
+    since: Option<i64>,
+    limit: i64,
+) -> Result<Vec<SessionEvent>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    session_events::table
+        .filter(session_events::session_id.eq(session_id))
+        .filter(session_events::id.gt(since.unwrap_or(0)))
+        .order(session_events::id.asc())
+        .limit(limit)
+        .select(SessionEvent::as_select())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+/// Everything one job emitted, oldest first
+pub fn get_job_events(pool: &DbPool, job_id: &str) -> Result<Vec<SessionEvent>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    session_events::table
+        .filter(session_events::job_id.eq(job_id))
+        .order(session_events::id.asc())
+        .select(SessionEvent::as_select())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+pub fn delete_session_events(pool: &DbPool, session_id: &str) -> Result<usize, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::delete(session_events::table.filter(session_events::session_id.eq(session_id)))
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())
+}
@@ -41,3 +41,25 @@ pub fn get_setting(pool: &crate::database::DbPool, key: &str) -> Option<String>
         None
     }
 }
+
+/// Insert or update a setting
+pub fn set_setting(pool: &crate::database::DbPool, key: &str, value: &str) -> Result<(), String> {
+    use crate::schema::settings;
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::insert_into(settings::table)
+        .values(NewSetting {
+            id: uuid::Uuid::new_v4().to_string(),
+            key: key.to_string(),
+            value: Some(value.to_string()),
+        })
+        .on_conflict(settings::key)
+        .do_update()
+        .set(UpdateSetting {
+            value: Some(value.to_string()),
+            updated_at: chrono::Utc::now().naive_utc(),
+        })
+        .execute(&mut conn)
+        .map_err(|e| format!("Failed to update setting {}: {}", key, e))?;
+    Ok(())
+}
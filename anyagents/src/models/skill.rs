@@ -19,6 +19,21 @@ pub struct AgentSkill {
     pub requires_sandbox: i32,
     pub sandbox_config: Option<String>,
     pub execution_mode: String,
+    /// JSON array of trigger expressions (see `skills::triggers`)
+    pub triggers: Option<String>,
+    /// JSON `SkillDependencies` (see `skills::provision`)
+    pub dependencies: Option<String>,
+    /// Marketplace version this skill was installed from, if any
+    pub installed_version: Option<String>,
+    /// JSON `SkillInvocation` (parameter schema + command template)
+    pub invocation: Option<String>,
+    /// JSON array of `SkillExample`
+    pub examples: Option<String>,
+    /// JSON `SkillRecipe` (see `skills::recipe`)
+    pub recipe: Option<String>,
+    /// The manifest declares the skill free of side effects (`read_only: true`)
+    #[serde(default)]
+    pub read_only: i32,
 }
 
 #[derive(Insertable, Deserialize)]
@@ -39,6 +54,21 @@ pub struct NewAgentSkill {
     pub requires_sandbox: i32,
     pub sandbox_config: Option<String>,
     pub execution_mode: String,
+    /// JSON array of trigger expressions (see `skills::triggers`)
+    pub triggers: Option<String>,
+    /// JSON `SkillDependencies` (see `skills::provision`)
+    pub dependencies: Option<String>,
+    /// Marketplace version this skill was installed from, if any
+    pub installed_version: Option<String>,
+    /// JSON `SkillInvocation` (parameter schema + command template)
+    pub invocation: Option<String>,
+    /// JSON array of `SkillExample`
+    pub examples: Option<String>,
+    /// JSON `SkillRecipe` (see `skills::recipe`)
+    pub recipe: Option<String>,
+    /// The manifest declares the skill free of side effects (`read_only: true`)
+    #[serde(default)]
+    pub read_only: i32,
 }
 
 #[derive(AsChangeset, Deserialize)]
@@ -57,6 +87,13 @@ pub struct UpdateAgentSkill {
     pub requires_sandbox: Option<i32>,
     pub sandbox_config: Option<String>,
     pub execution_mode: Option<String>,
+    pub triggers: Option<String>,
+    pub dependencies: Option<String>,
+    pub installed_version: Option<String>,
+    pub invocation: Option<String>,
+    pub examples: Option<String>,
+    pub recipe: Option<String>,
+    pub read_only: Option<i32>,
 }
 
 // Skill file model for storing bundled files
@@ -103,18 +140,101 @@ pub struct ParsedSkill {
     pub category: Option<String>,
     pub triggers: Option<Vec<String>>,
     pub requires_sandbox: bool,
+    /// Declared in SKILL.md; read-only agents may only call skills that say so
+    #[serde(default)]
+    pub read_only: bool,
     pub sandbox_config: Option<SandboxConfig>,
     pub execution_mode: Option<String>, // "sandbox", "direct", "flexible"
+    #[serde(default)]
+    pub dependencies: Option<SkillDependencies>,
+    #[serde(default)]
+    pub invocation: Option<SkillInvocation>,
+    #[serde(default)]
+    pub examples: Vec<SkillExample>,
+    #[serde(default)]
+    pub recipe: Option<SkillRecipe>,
     pub body: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A SKILL.md example, run by the skill test harness
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkillExample {
+    pub name: String,
+    /// Tool arguments, as in a real call (`{"args": "<command>"}` for free-form skills)
+    pub args: serde_json::Value,
+    #[serde(default)]
+    pub expect_exit: i32,
+    /// Substring the output must contain
+    #[serde(default)]
+    pub expect_stdout: Option<String>,
+}
+
+/// Structured inputs declared in SKILL.md. The skill tool takes `parameters`
+/// (a JSON schema) instead of a free-form command and runs `command`, a
+/// template whose substituted values are always shell-quoted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkillInvocation {
+    pub parameters: serde_json::Value,
+    pub command: String,
+}
+
+/// A composite skill: instead of one command, a call runs `steps` in order,
+/// each invoking a built-in tool (filesystem, bash, http) with templated args
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkillRecipe {
+    /// JSON schema of the skill's inputs
+    pub parameters: serde_json::Value,
+    pub steps: Vec<RecipeStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecipeStep {
+    /// Name later steps use to read this step's result (`steps.<id>`)
+    #[serde(default)]
+    pub id: Option<String>,
+    pub tool: String,
+    /// Tool arguments; string values are minijinja templates
+    pub args: serde_json::Value,
+    /// Expression that must be truthy for the step to run
+    #[serde(default)]
+    pub when: Option<String>,
+    /// Keep going (recording the error) when the step fails
+    #[serde(default)]
+    pub continue_on_error: bool,
+}
+
+/// Runtime dependencies declared in SKILL.md, installed on first use
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct SkillDependencies {
+    #[serde(default)]
+    pub pip: Vec<String>,
+    #[serde(default)]
+    pub npm: Vec<String>,
+    /// Executables that must already be on PATH (or in the sandbox image)
+    #[serde(default)]
+    pub binaries: Vec<String>,
+}
+
+impl SkillDependencies {
+    pub fn is_empty(&self) -> bool {
+        self.pip.is_empty() && self.npm.is_empty() && self.binaries.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SandboxConfig {
     pub image: Option<String>,
     pub memory_limit: Option<String>,
     pub cpu_limit: Option<f32>,
     pub timeout_seconds: Option<u32>,
     pub network_enabled: Option<bool>,
+    /// Sandbox backend: "docker" (default) or "wasm"
+    #[serde(default)]
+    pub runtime: Option<String>,
+    /// Extra environment for the command (e.g. secrets); never persisted
+    #[serde(skip)]
+    pub env: Vec<(String, String)>,
 }
 
 #[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
@@ -0,0 +1,58 @@
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::slack_configs)]
+pub struct SlackConfig {
+    pub id: String,
+    pub app_token: String,
+    pub bot_token: String,
+    pub agent_id: String,
+    pub is_active: i32,
+    pub allowed_channel_ids: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Deserialize)]
+#[diesel(table_name = crate::schema::slack_configs)]
+pub struct NewSlackConfig {
+    pub id: String,
+    pub app_token: String,
+    pub bot_token: String,
+    pub agent_id: String,
+    pub is_active: i32,
+    pub allowed_channel_ids: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(AsChangeset, Deserialize)]
+#[diesel(table_name = crate::schema::slack_configs)]
+pub struct UpdateSlackConfig {
+    pub app_token: Option<String>,
+    pub bot_token: Option<String>,
+    pub agent_id: Option<String>,
+    pub is_active: Option<i32>,
+    pub allowed_channel_ids: Option<String>,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Serialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::slack_conversation_sessions)]
+pub struct SlackConversationSession {
+    pub config_id: String,
+    pub channel_id: String,
+    pub thread_ts: String,
+    pub session_id: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::slack_conversation_sessions)]
+pub struct NewSlackConversationSession {
+    pub config_id: String,
+    pub channel_id: String,
+    pub thread_ts: String,
+    pub session_id: String,
+}
@@ -11,6 +11,8 @@ pub struct TelegramConfig {
     pub allowed_chat_ids: Option<String>,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
+    pub voice_replies: i32,
+    pub group_allowlists: Option<String>,
 }
 
 #[derive(Insertable, Deserialize)]
@@ -23,6 +25,8 @@ pub struct NewTelegramConfig {
     pub allowed_chat_ids: Option<String>,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
+    pub voice_replies: i32,
+    pub group_allowlists: Option<String>,
 }
 
 #[derive(AsChangeset, Deserialize)]
@@ -32,5 +36,26 @@ pub struct UpdateTelegramConfig {
     pub agent_id: Option<String>,
     pub is_active: Option<i32>,
     pub allowed_chat_ids: Option<String>,
+    pub voice_replies: Option<i32>,
+    pub group_allowlists: Option<String>,
     pub updated_at: chrono::NaiveDateTime,
 }
+
+#[derive(Queryable, Selectable, Serialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::telegram_chat_sessions)]
+pub struct TelegramChatSession {
+    pub config_id: String,
+    pub chat_id: i64,
+    pub thread_id: i64,
+    pub session_id: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::telegram_chat_sessions)]
+pub struct NewTelegramChatSession {
+    pub config_id: String,
+    pub chat_id: i64,
+    pub thread_id: i64,
+    pub session_id: String,
+}
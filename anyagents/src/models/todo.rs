@@ -0,0 +1,67 @@
+use crate::database::DbPool;
+use crate::schema::job_todos;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// One entry of the checklist an agent keeps with the `todo` tool
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct TodoItem {
+    pub id: String,
+    pub content: String,
+    pub status: String, // pending, in_progress, completed
+}
+
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = job_todos)]
+pub struct JobTodos {
+    pub job_id: String,
+    pub session_id: String,
+    pub items: String, // JSON array of TodoItem
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl JobTodos {
+    pub fn items(&self) -> Vec<TodoItem> {
+        serde_json::from_str(&self.items).unwrap_or_default()
+    }
+}
+
+/// Replace the checklist of a job
+pub fn save_todos(pool: &DbPool, session_id: &str, job_id: &str, items: &[TodoItem]) -> Result<(), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let row = JobTodos {
+        job_id: job_id.to_string(),
+        session_id: session_id.to_string(),
+        items: serde_json::to_string(items).map_err(|e| e.to_string())?,
+        updated_at: chrono::Utc::now().naive_utc(),
+    };
+    diesel::insert_into(job_todos::table)
+        .values(&row)
+        .on_conflict(job_todos::job_id)
+        .do_update()
+        .set((job_todos::items.eq(&row.items), job_todos::updated_at.eq(row.updated_at)))
+        .execute(&mut conn)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+pub fn get_job_todos(pool: &DbPool, job_id: &str) -> Result<Option<JobTodos>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    job_todos::table
+        .find(job_id)
+        .first(&mut conn)
+        .optional()
+        .map_err(|e| e.to_string())
+}
+
+/// The checklist updated last in a session, to show again when it is reopened
+pub fn latest_session_todos(pool: &DbPool, session_id: &str) -> Result<Option<JobTodos>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    job_todos::table
+        .filter(job_todos::session_id.eq(session_id))
+        .order(job_todos::updated_at.desc())
+        .first(&mut conn)
+        .optional()
+        .map_err(|e| e.to_string())
+}
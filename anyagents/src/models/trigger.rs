@@ -0,0 +1,168 @@
+use crate::database::DbPool;
+use crate::schema::agent_triggers;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Event sources an agent can subscribe to
+pub const TRIGGER_KINDS: [&str; 3] = ["file_change", "mail", "telegram"];
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = agent_triggers)]
+pub struct AgentTrigger {
+    pub id: String,
+    pub agent_id: String,
+    pub kind: String, // file_change, mail, telegram
+    pub pattern: Option<String>, // glob for file_change, regex for mail and telegram
+    pub prompt_template: Option<String>,
+    pub enabled: i32,
+    pub max_runs_per_hour: i32,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = agent_triggers)]
+pub struct NewAgentTrigger {
+    pub id: String,
+    pub agent_id: String,
+    pub kind: String,
+    pub pattern: Option<String>,
+    pub prompt_template: Option<String>,
+    pub enabled: i32,
+    pub max_runs_per_hour: i32,
+}
+
+#[derive(AsChangeset, Deserialize, Default)]
+#[diesel(table_name = agent_triggers)]
+pub struct UpdateAgentTrigger {
+    pub pattern: Option<Option<String>>,
+    pub prompt_template: Option<Option<String>>,
+    pub enabled: Option<i32>,
+    pub max_runs_per_hour: Option<i32>,
+}
+
+pub fn create_trigger(pool: &DbPool, trigger: NewAgentTrigger) -> Result<AgentTrigger, String> {
+    if !TRIGGER_KINDS.contains(&trigger.kind.as_str()) {
+        return Err(format!("Unknown trigger kind '{}'", trigger.kind));
+    }
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::insert_into(agent_triggers::table)
+        .values(&trigger)
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    agent_triggers::table
+        .find(&trigger.id)
+        .first(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+pub fn get_trigger(pool: &DbPool, trigger_id: &str) -> Result<AgentTrigger, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    agent_triggers::table
+        .find(trigger_id)
+        .first(&mut conn)
+        .map_err(|_| "Trigger not found".to_string())
+}
+
+pub fn get_agent_triggers(pool: &DbPool, agent_id: &str) -> Result<Vec<AgentTrigger>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    agent_triggers::table
+        .filter(agent_triggers::agent_id.eq(agent_id))
+        .order(agent_triggers::created_at.asc())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+/// Enabled triggers of one kind, optionally for a single agent
+pub fn get_enabled_triggers(
+    pool: &DbPool,
+    kind: &str,
+    agent_id: Option<&str>,
+) -> Result<Vec<AgentTrigger>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let mut query = agent_triggers::table
+        .filter(agent_triggers::kind.eq(kind))
+        .filter(agent_triggers::enabled.eq(1))
+        .into_boxed();
+    if let Some(agent_id) = agent_id {
+        query = query.filter(agent_triggers::agent_id.eq(agent_id));
+    }
+    query.load(&mut conn).map_err(|e| e.to_string())
+}
+
+pub fn update_trigger(
+    pool: &DbPool,
+    trigger_id: &str,
+    changes: UpdateAgentTrigger,
+) -> Result<AgentTrigger, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::update(agent_triggers::table.find(trigger_id))
+        .set((
+            &changes,
+            agent_triggers::updated_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    agent_triggers::table
+        .find(trigger_id)
+        .first(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+pub fn delete_trigger(pool: &DbPool, trigger_id: &str) -> Result<(), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::delete(agent_triggers::table.find(trigger_id))
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+    use crate::schema::agents;
+
+    fn new_trigger(agent_id: &str, kind: &str) -> NewAgentTrigger {
+        NewAgentTrigger {
+            id: uuid::Uuid::new_v4().to_string(),
+            agent_id: agent_id.to_string(),
+            kind: kind.to_string(),
+            pattern: None,
+            prompt_template: None,
+            enabled: 1,
+            max_runs_per_hour: 6,
+        }
+    }
+
+    #[test]
+    fn test_trigger_crud() {
+        let pool = create_test_pool();
+        diesel::insert_into(agents::table)
+            .values((agents::id.eq("agent-1"), agents::name.eq("Inbox")))
+            .execute(&mut pool.get().unwrap())
+            .unwrap();
+        let mail = create_trigger(&pool, new_trigger("agent-1", "mail")).unwrap();
+        create_trigger(&pool, new_trigger("agent-1", "file_change")).unwrap();
+        assert!(create_trigger(&pool, new_trigger("agent-1", "sms")).is_err());
+
+        assert_eq!(get_agent_triggers(&pool, "agent-1").unwrap().len(), 2);
+        assert_eq!(get_enabled_triggers(&pool, "mail", None).unwrap().len(), 1);
+
+        let updated = update_trigger(
+            &pool,
+            &mail.id,
+            UpdateAgentTrigger {
+                enabled: Some(0),
+                pattern: Some(Some("invoice".to_string())),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(updated.pattern.as_deref(), Some("invoice"));
+        assert!(get_enabled_triggers(&pool, "mail", Some("agent-1")).unwrap().is_empty());
+
+        delete_trigger(&pool, &mail.id).unwrap();
+        assert_eq!(get_agent_triggers(&pool, "agent-1").unwrap().len(), 1);
+    }
+}
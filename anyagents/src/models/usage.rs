@@ -0,0 +1,298 @@
+//! What agent runs used, for the usage dashboard
+//!
+//! The agent loop stores a row per job, per tool call and per model a job
+//! called (with its tokens). `usage_stats` rolls them up in one query per
+//! dashboard view: jobs, tool calls, tokens and estimated cost by day,
+//! agent, provider or model, plus calls per tool.
+
+use crate::database::DbPool;
+use crate::llm::usage::{estimate_cost, ModelUsage, TokenUsage};
+use crate::schema::usage_records;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const KIND_JOB: &str = "job";
+pub const KIND_TOOL_CALL: &str = "tool_call";
+pub const KIND_TOKENS: &str = "tokens";
+
+#[derive(Queryable, Selectable, Insertable, Serialize, Debug, Clone)]
+#[diesel(table_name = usage_records)]
+pub struct UsageRecord {
+    pub id: String,
+    pub kind: String, // job, tool_call, tokens
+    pub job_id: String,
+    pub session_id: String,
+    pub agent_id: String,
+    pub provider: String,
+    pub model: String,
+    pub tool: Option<String>,
+    pub success: i32,
+    pub input_tokens: i32,
+    pub output_tokens: i32,
+    pub created_at: NaiveDateTime,
+}
+
+/// The job a record belongs to, and the model it was using
+#[derive(Debug, Clone, Copy)]
+pub struct JobRef<'a> {
+    pub job_id: &'a str,
+    pub session_id: &'a str,
+    pub agent_id: &'a str,
+    pub provider: &'a str,
+    pub model: &'a str,
+}
+
+impl UsageRecord {
+    fn new(kind: &str, job: JobRef) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            kind: kind.to_string(),
+            job_id: job.job_id.to_string(),
+            session_id: job.session_id.to_string(),
+            agent_id: job.agent_id.to_string(),
+            provider: job.provider.to_string(),
+            model: job.model.to_string(),
+            tool: None,
+            success: 1,
+            input_tokens: 0,
+            output_tokens: 0,
+            created_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+}
+
+fn clamp(tokens: u64) -> i32 {
+    i32::try_from(tokens).unwrap_or(i32::MAX)
+}
+
+fn insert(pool: &DbPool, records: &[UsageRecord]) -> Result<(), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::insert_into(usage_records::table)
+        .values(records)
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Record a finished job and the tokens each model it called used
+pub fn record_job(pool: &DbPool, job: JobRef, tokens: &[ModelUsage]) -> Result<(), String> {
+    let mut records = vec![UsageRecord::new(KIND_JOB, job)];
+    records.extend(tokens.iter().map(|m| UsageRecord {
+        provider: m.provider.clone(),
+        model: m.model.clone(),
+        input_tokens: clamp(m.usage.input_tokens),
+        output_tokens: clamp(m.usage.output_tokens),
+        ..UsageRecord::new(KIND_TOKENS, job)
+    }));
+    insert(pool, &records)
+}
+
+pub fn record_tool_call(pool: &DbPool, job: JobRef, tool: &str, success: bool) -> Result<(), String> {
+    let record = UsageRecord {
+        tool: Some(tool.to_string()),
+        success: i32::from(success),
+        ..UsageRecord::new(KIND_TOOL_CALL, job)
+    };
+    insert(pool, &[record])
+}
+
+/// Tool calls `agent_id` made since `since`, of `tool` or of every tool
+pub fn count_tool_calls_since(
+    pool: &DbPool,
+    agent_id: &str,
+    tool: Option<&str>,
+    since: NaiveDateTime,
+) -> Result<i64, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let mut query = usage_records::table
+        .filter(usage_records::kind.eq(KIND_TOOL_CALL))
+        .filter(usage_records::agent_id.eq(agent_id))
+        .filter(usage_records::created_at.ge(since))
+        .into_boxed();
+    if let Some(tool) = tool {
+        query = query.filter(usage_records::tool.eq(tool));
+    }
+    query.count().get_result(&mut conn).map_err(|e| e.to_string())
+}
+
+/// Records created from `from` (inclusive) to `to` (exclusive); open ends
+/// when unset
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+pub struct UsageRange {
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageGroupBy {
+    Day,
+    Agent,
+    Provider,
+    Model,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct UsageBucket {
+    /// `YYYY-MM-DD`, the agent id, the provider or `provider/model`
+    pub key: String,
+    pub jobs: u64,
+    pub tool_calls: u64,
+    pub failed_tool_calls: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// USD at list prices; models without a known price count as free
+    pub estimated_cost: f64,
+}
+
+impl UsageBucket {
+    fn add(&mut self, record: &UsageRecord) {
+        match record.kind.as_str() {
+            KIND_JOB => self.jobs += 1,
+            KIND_TOOL_CALL => {
+                self.tool_calls += 1;
+                if record.success == 0 {
+                    self.failed_tool_calls += 1;
+                }
+            }
+            KIND_TOKENS => {
+                let usage = TokenUsage {
+                    input_tokens: record.input_tokens.max(0) as u64,
+                    output_tokens: record.output_tokens.max(0) as u64,
+                };
+                self.input_tokens += usage.input_tokens;
+                self.output_tokens += usage.output_tokens;
+                self.estimated_cost += estimate_cost(&record.provider, &record.model, usage);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ToolUsage {
+    pub tool: String,
+    pub calls: u64,
+    pub failures: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct UsageStats {
+    pub group_by: UsageGroupBy,
+    /// By day: oldest first. Otherwise: most tokens first.
+    pub buckets: Vec<UsageBucket>,
+    /// Most called first
+    pub tools: Vec<ToolUsage>,
+    pub totals: UsageBucket,
+}
+
+fn bucket_key(record: &UsageRecord, group_by: UsageGroupBy) -> String {
+    match group_by {
+        UsageGroupBy::Day => record.created_at.date().to_string(),
+        UsageGroupBy::Agent => record.agent_id.clone(),
+        UsageGroupBy::Provider => record.provider.clone(),
+        UsageGroupBy::Model => format!("{}/{}", record.provider, record.model),
+    }
+}
+
+/// Jobs, tool calls, tokens and cost in `range`, grouped by `group_by`
+pub fn usage_stats(pool: &DbPool, range: UsageRange, group_by: UsageGroupBy) -> Result<UsageStats, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let mut query = usage_records::table.into_boxed();
+    if let Some(from) = range.from {
+        query = query.filter(usage_records::created_at.ge(from));
+    }
+    if let Some(to) = range.to {
+        query = query.filter(usage_records::created_at.lt(to));
+    }
+    let records: Vec<UsageRecord> = query
+        .order(usage_records::created_at.asc())
+        .select(UsageRecord::as_select())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())?;
+
+    let mut totals = UsageBucket { key: "total".to_string(), ..Default::default() };
+    let mut buckets: HashMap<String, UsageBucket> = HashMap::new();
+    let mut tools: HashMap<String, ToolUsage> = HashMap::new();
+    for record in &records {
+        totals.add(record);
+        let key = bucket_key(record, group_by);
+        buckets
+            .entry(key.clone())
+            .or_insert_with(|| UsageBucket { key, ..Default::default() })
+            .add(record);
+        if let (KIND_TOOL_CALL, Some(tool)) = (record.kind.as_str(), &record.tool) {
+            let usage = tools
+                .entry(tool.clone())
+                .or_insert_with(|| ToolUsage { tool: tool.clone(), calls: 0, failures: 0 });
+            usage.calls += 1;
+            if record.success == 0 {
+                usage.failures += 1;
+            }
+        }
+    }
+
+    let mut buckets: Vec<UsageBucket> = buckets.into_values().collect();
+    match group_by {
+        UsageGroupBy::Day => buckets.sort_by(|a, b| a.key.cmp(&b.key)),
+        _ => buckets.sort_by(|a, b| {
+            (b.input_tokens + b.output_tokens)
+                .cmp(&(a.input_tokens + a.output_tokens))
+                .then_with(|| a.key.cmp(&b.key))
+        }),
+    }
+    let mut tools: Vec<ToolUsage> = tools.into_values().collect();
+    tools.sort_by(|a, b| b.calls.cmp(&a.calls).then_with(|| a.tool.cmp(&b.tool)));
+
+    Ok(UsageStats { group_by, buckets, tools, totals })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+
+    fn job<'a>(job_id: &'a str, agent_id: &'a str) -> JobRef<'a> {
+        JobRef { job_id, session_id: "s", agent_id, provider: "openai", model: "gpt-4o" }
+    }
+
+    #[test]
+    fn test_usage_stats_roll_up() {
+        let pool = create_test_pool();
+        let tokens = |provider: &str, model: &str, input, output| ModelUsage {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            usage: TokenUsage { input_tokens: input, output_tokens: output },
+        };
+        record_tool_call(&pool, job("j1", "writer"), "bash", true).unwrap();
+        record_tool_call(&pool, job("j1", "writer"), "bash", false).unwrap();
+        record_tool_call(&pool, job("j1", "writer"), "filesystem", true).unwrap();
+        record_job(
+            &pool,
+            job("j1", "writer"),
+            &[tokens("openai", "gpt-4o", 1_000_000, 0), tokens("anthropic", "claude-3-haiku", 0, 1_000_000)],
+        )
+        .unwrap();
+        record_job(&pool, job("j2", "reviewer"), &[tokens("openai", "gpt-4o", 500, 500)]).unwrap();
+
+        let stats = usage_stats(&pool, UsageRange::default(), UsageGroupBy::Provider).unwrap();
+        assert_eq!(stats.totals.jobs, 2);
+        assert_eq!(stats.totals.tool_calls, 3);
+        assert_eq!(stats.totals.failed_tool_calls, 1);
+        assert_eq!(stats.buckets[0].key, "openai");
+        assert_eq!(stats.buckets[0].input_tokens, 1_000_500);
+        assert_eq!(stats.buckets[1].estimated_cost, 1.25);
+        assert_eq!(stats.tools[0], ToolUsage { tool: "bash".to_string(), calls: 2, failures: 1 });
+
+        let by_agent = usage_stats(&pool, UsageRange::default(), UsageGroupBy::Agent).unwrap();
+        let writer = by_agent.buckets.iter().find(|b| b.key == "writer").unwrap();
+        assert_eq!((writer.jobs, writer.tool_calls), (1, 3));
+
+        let by_day = usage_stats(&pool, UsageRange::default(), UsageGroupBy::Day).unwrap();
+        assert_eq!(by_day.buckets.len(), 1);
+        let later = UsageRange { from: Some(chrono::Utc::now().naive_utc() + chrono::Duration::hours(1)), to: None };
+        assert_eq!(usage_stats(&pool, later, UsageGroupBy::Day).unwrap().totals.jobs, 0);
+    }
+}
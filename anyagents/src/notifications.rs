@@ -0,0 +1,345 @@
+//! Notifications
+//!
+//! Events worth the user's attention become notifications: a job finished or
+//! failed, an approval is waiting, mail arrived. `from_event` picks them out
+//! of what observers emit. Each event has a rule naming the channels it goes
+//! out on (OS notification, Telegram, email), and agents and sessions can be
+//! muted; all of it is the `notification_preferences` setting
+//! (`NotificationPreferences`).
+//!
+//! `record` stores a notification that isn't muted or switched off in the
+//! inbox (see `models::notification`) and returns the channels it should be
+//! delivered on; delivering is up to the app.
+
+use crate::database::DbPool;
+use crate::models::notification::{insert_notification, Notification};
+use crate::models::settings::{get_setting, set_setting};
+use crate::schema::sessions;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const SETTINGS_KEY: &str = "notification_preferences";
+/// Characters of an answer or message shown in a notification
+const BODY_CHARS: usize = 280;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationEvent {
+    JobCompleted,
+    ApprovalNeeded,
+    MailReceived,
+    TaskFailed,
+}
+
+impl NotificationEvent {
+    pub const ALL: [Self; 4] = [Self::JobCompleted, Self::ApprovalNeeded, Self::MailReceived, Self::TaskFailed];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::JobCompleted => "job_completed",
+            Self::ApprovalNeeded => "approval_needed",
+            Self::MailReceived => "mail_received",
+            Self::TaskFailed => "task_failed",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationChannel {
+    /// A notification from the operating system
+    Os,
+    Telegram,
+    Email,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NotificationRule {
+    pub event: NotificationEvent,
+    /// Off: the event is neither kept in the inbox nor delivered
+    pub enabled: bool,
+    pub channels: Vec<NotificationChannel>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct NotificationPreferences {
+    /// Events without a rule follow the default one
+    pub rules: Vec<NotificationRule>,
+    pub muted_agents: Vec<String>,
+    pub muted_sessions: Vec<String>,
+    /// Bot (a Telegram config id) and chat the Telegram channel posts to
+    pub telegram_config_id: Option<String>,
+    pub telegram_chat_id: Option<i64>,
+    /// Email account the email channel sends from, and the address it sends to
+    pub email_account_id: Option<String>,
+    pub email_to: Option<String>,
+}
+
+impl Default for NotificationPreferences {
+    fn default() -> Self {
+        Self {
+            rules: NotificationEvent::ALL
+                .into_iter()
+                .map(|event| NotificationRule { event, enabled: true, channels: vec![NotificationChannel::Os] })
+                .collect(),
+            muted_agents: vec![],
+            muted_sessions: vec![],
+            telegram_config_id: None,
+            telegram_chat_id: None,
+            email_account_id: None,
+            email_to: None,
+        }
+    }
+}
+
+impl NotificationPreferences {
+    pub fn load(pool: &DbPool) -> Self {
+        get_setting(pool, SETTINGS_KEY)
+            .and_then(|v| serde_json::from_str(&v).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, pool: &DbPool) -> Result<(), String> {
+        self.validate()?;
+        set_setting(pool, SETTINGS_KEY, &serde_json::to_string(self).map_err(|e| e.to_string())?)
+    }
+
+    /// Channels in use must say where to deliver
+    pub fn validate(&self) -> Result<(), String> {
+        let uses = |channel| self.rules.iter().any(|r| r.enabled && r.channels.contains(&channel));
+        if uses(NotificationChannel::Telegram) && (self.telegram_config_id.is_none() || self.telegram_chat_id.is_none())
+        {
+            return Err("Telegram notifications need a bot and a chat id".to_string());
+        }
+        if uses(NotificationChannel::Email)
+            && (self.email_account_id.is_none() || !self.email_to.as_deref().is_some_and(|to| to.contains('@')))
+        {
+            return Err("Email notifications need an account to send from and an address".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn rule(&self, event: NotificationEvent) -> NotificationRule {
+        self.rules
+            .iter()
+            .find(|r| r.event == event)
+            .cloned()
+            .unwrap_or(NotificationRule { event, enabled: true, channels: vec![NotificationChannel::Os] })
+    }
+
+    pub fn set_agent_muted(&mut self, agent_id: &str, muted: bool) {
+        set_muted(&mut self.muted_agents, agent_id, muted);
+    }
+
+    pub fn set_session_muted(&mut self, session_id: &str, muted: bool) {
+        set_muted(&mut self.muted_sessions, session_id, muted);
+    }
+
+    fn is_muted(&self, notification: &PendingNotification) -> bool {
+        notification.agent_id.as_ref().is_some_and(|id| self.muted_agents.contains(id))
+            || notification.session_id.as_ref().is_some_and(|id| self.muted_sessions.contains(id))
+    }
+}
+
+fn set_muted(muted: &mut Vec<String>, id: &str, mute: bool) {
+    muted.retain(|m| m != id);
+    if mute {
+        muted.push(id.to_string());
+    }
+}
+
+/// A notification not yet recorded
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingNotification {
+    pub event: NotificationEvent,
+    pub title: String,
+    pub body: String,
+    pub agent_id: Option<String>,
+    pub session_id: Option<String>,
+}
+
+fn shorten(text: &str) -> String {
+    let text = text.trim();
+    if text.chars().count() <= BODY_CHARS {
+        return text.to_string();
+    }
+    let mut short: String = text.chars().take(BODY_CHARS - 1).collect();
+    short.push('…');
+    short
+}
+
+/// The notification `payload`, emitted on `channel`, calls for, if any
+pub fn from_event(channel: &str, payload: &Value) -> Option<PendingNotification> {
+    let text = |value: &Value| value.as_str().unwrap_or_default().to_string();
+    let mut session_id = channel
+        .strip_prefix("session:")
+        .or_else(|| payload["job"]["session_id"].as_str())
+        .map(str::to_string);
+    let mut agent_id = None;
+
+    let (event, title, body) = match payload.get("type").and_then(Value::as_str) {
+        Some("job_completed") => match payload["job"]["status"].as_str() {
+            Some("completed") => (NotificationEvent::JobCompleted, "Task completed".to_string(), text(&payload["message"])),
+            Some("failed") => (NotificationEvent::TaskFailed, "Task failed".to_string(), text(&payload["message"])),
+            _ => return None,
+        },
+        Some("approval_required") => (
+            NotificationEvent::ApprovalNeeded,
+            "Approval needed".to_string(),
+            format!("Run {}?", text(&payload["step"]["tool_name"])),
+        ),
+        Some("permission_request") => (
+            NotificationEvent::ApprovalNeeded,
+            "Approval needed".to_string(),
+            text(&payload["request"]["message"]),
+        ),
+        Some("new_mail") => {
+            agent_id = payload["agent_id"].as_str().map(str::to_string);
+            (
+                NotificationEvent::MailReceived,
+                format!("Mail from {}", text(&payload["from_name"])),
+                text(&payload["subject"]),
+            )
+        }
+        // Requests outside a session come bare on their own channel
+        _ if channel == "permission_request" => {
+            session_id = payload["metadata"]["session_id"].as_str().map(str::to_string);
+            (NotificationEvent::ApprovalNeeded, "Approval needed".to_string(), text(&payload["message"]))
+        }
+        _ => return None,
+    };
+    Some(PendingNotification { event, title, body: shorten(&body), agent_id, session_id })
+}
+
+/// Keep `notification` in the inbox unless `preferences` mute it or switch
+/// its event off. Returns it as stored and the channels to deliver it on.
+pub fn record(
+    pool: &DbPool,
+    preferences: &NotificationPreferences,
+    mut notification: PendingNotification,
+) -> Result<Option<(Notification, Vec<NotificationChannel>)>, String> {
+    let rule = preferences.rule(notification.event);
+    if !rule.enabled {
+        return Ok(None);
+    }
+    if notification.agent_id.is_none() {
+        if let Some(session_id) = &notification.session_id {
+            let mut conn = pool.get().map_err(|e| e.to_string())?;
+            notification.agent_id = sessions::table
+                .find(session_id)
+                .select(sessions::agent_id)
+                .first(&mut conn)
+                .optional()
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    if preferences.is_muted(&notification) {
+        return Ok(None);
+    }
+
+    let stored = Notification {
+        id: uuid::Uuid::new_v4().to_string(),
+        event: notification.event.as_str().to_string(),
+        title: notification.title,
+        body: notification.body,
+        agent_id: notification.agent_id,
+        session_id: notification.session_id,
+        is_read: 0,
+        created_at: chrono::Utc::now().naive_utc(),
+    };
+    insert_notification(pool, &stored)?;
+    Ok(Some((stored, rule.channels)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+    use crate::models::notification::{list_notifications, mark_all_read, set_read, unread_count};
+    use crate::models::NewSession;
+    use crate::schema::agents;
+    use serde_json::json;
+
+    #[test]
+    fn test_from_event() {
+        let done = json!({
+            "type": "job_completed",
+            "job": {"id": "j1", "session_id": "s1", "status": "completed"},
+            "message": "x".repeat(1000),
+        });
+        let notification = from_event("session:s1", &done).unwrap();
+        assert_eq!(notification.event, NotificationEvent::JobCompleted);
+        assert_eq!(notification.session_id.as_deref(), Some("s1"));
+        assert_eq!(notification.body.chars().count(), BODY_CHARS);
+
+        let failed = json!({"type": "job_completed", "job": {"status": "failed"}, "message": "Error: boom"});
+        assert_eq!(from_event("session:s1", &failed).unwrap().event, NotificationEvent::TaskFailed);
+        let cancelled = json!({"type": "job_completed", "job": {"status": "cancelled"}, "message": ""});
+        assert!(from_event("session:s1", &cancelled).is_none());
+        assert!(from_event("session:s1", &json!({"type": "token", "content": "hi"})).is_none());
+
+        let permission = json!({"message": "Write to notes.md?", "metadata": {"session_id": "s2"}});
+        let notification = from_event("permission_request", &permission).unwrap();
+        assert_eq!(notification.event, NotificationEvent::ApprovalNeeded);
+        assert_eq!(notification.session_id.as_deref(), Some("s2"));
+
+        let mail = json!({"type": "new_mail", "thread_id": "t", "subject": "Invoice", "from_name": "Ada", "agent_id": "a1"});
+        let notification = from_event("mail:received", &mail).unwrap();
+        assert_eq!((notification.title.as_str(), notification.agent_id.as_deref()), ("Mail from Ada", Some("a1")));
+    }
+
+    #[test]
+    fn test_rules_muting_and_inbox() {
+        let pool = create_test_pool();
+        let now = chrono::Utc::now().naive_utc();
+        diesel::insert_into(agents::table)
+            .values((agents::id.eq("writer"), agents::name.eq("Writer")))
+            .execute(&mut pool.get().unwrap())
+            .unwrap();
+        diesel::insert_into(sessions::table)
+            .values(&NewSession {
+                id: "s1".to_string(),
+                agent_id: "writer".to_string(),
+                title: None,
+                created_at: now,
+                updated_at: now,
+                archived: 0,
+                pinned: 0,
+            })
+            .execute(&mut pool.get().unwrap())
+            .unwrap();
+        let done = || from_event("session:s1", &json!({"type": "job_completed", "job": {"status": "completed"}, "message": "Done"})).unwrap();
+
+        let mut preferences = NotificationPreferences::default();
+        let (stored, channels) = record(&pool, &preferences, done()).unwrap().unwrap();
+        assert_eq!(stored.agent_id.as_deref(), Some("writer"));
+        assert_eq!(channels, vec![NotificationChannel::Os]);
+
+        preferences.set_agent_muted("writer", true);
+        assert!(record(&pool, &preferences, done()).unwrap().is_none());
+        preferences.set_agent_muted("writer", false);
+        preferences.set_session_muted("s1", true);
+        assert!(record(&pool, &preferences, done()).unwrap().is_none());
+        preferences.set_session_muted("s1", false);
+
+        preferences.rules = vec![NotificationRule {
+            event: NotificationEvent::JobCompleted,
+            enabled: true,
+            channels: vec![NotificationChannel::Email],
+        }];
+        assert!(preferences.validate().is_err());
+        preferences.email_account_id = Some("account".to_string());
+        preferences.email_to = Some("me@example.com".to_string());
+        assert!(preferences.validate().is_ok());
+        let (_, channels) = record(&pool, &preferences, done()).unwrap().unwrap();
+        assert_eq!(channels, vec![NotificationChannel::Email]);
+
+        assert_eq!(unread_count(&pool).unwrap(), 2);
+        assert_eq!(set_read(&pool, &[stored.id.clone()], true).unwrap(), 1);
+        assert_eq!(list_notifications(&pool, true, 10).unwrap().len(), 1);
+        assert_eq!(mark_all_read(&pool).unwrap(), 1);
+        assert_eq!(unread_count(&pool).unwrap(), 0);
+    }
+}
@@ -0,0 +1,117 @@
+//! Observer access to sessions
+//!
+//! An observer follows a session as it runs, for review by teammates: it
+//! receives every event of the session but cannot send messages or answer
+//! approvals. Adapters decide how an observer connects; the server hands out
+//! an `ObserverGrant` token that only unlocks the session's read-only routes,
+//! the desktop app opens an observer window.
+//!
+//! Grants live in memory, per process, and end with it (or at `expires_at`).
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct ObserverGrant {
+    pub token: String,
+    pub session_id: String,
+    /// Who the grant was made for, as shown to the session's owner
+    pub label: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl ObserverGrant {
+    fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|at| at <= Utc::now())
+    }
+}
+
+#[derive(Default)]
+pub struct ObserverGrants {
+    grants: Mutex<HashMap<String, ObserverGrant>>,
+}
+
+/// The grants of this process
+pub fn global() -> &'static ObserverGrants {
+    static GRANTS: OnceLock<ObserverGrants> = OnceLock::new();
+    GRANTS.get_or_init(Default::default)
+}
+
+impl ObserverGrants {
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, ObserverGrant>> {
+        let mut grants = self.grants.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        grants.retain(|_, grant| !grant.is_expired());
+        grants
+    }
+
+    /// Let the holder of the returned token observe `session_id`, for `ttl`
+    /// or until revoked
+    pub fn grant(&self, session_id: &str, label: Option<String>, ttl: Option<Duration>) -> ObserverGrant {
+        let now = Utc::now();
+        let grant = ObserverGrant {
+            token: format!("obs_{}", uuid::Uuid::new_v4().simple()),
+            session_id: session_id.to_string(),
+            label,
+            created_at: now,
+            expires_at: ttl.map(|ttl| now + ttl),
+        };
+        self.lock().insert(grant.token.clone(), grant.clone());
+        grant
+    }
+
+    /// The session `token` may observe, if it is a live grant
+    pub fn session_of(&self, token: &str) -> Option<String> {
+        self.lock().get(token).map(|grant| grant.session_id.clone())
+    }
+
+    pub fn for_session(&self, session_id: &str) -> Vec<ObserverGrant> {
+        let mut grants: Vec<ObserverGrant> =
+            self.lock().values().filter(|grant| grant.session_id == session_id).cloned().collect();
+        grants.sort_by_key(|grant| grant.created_at);
+        grants
+    }
+
+    /// Returns whether the grant existed
+    pub fn revoke(&self, token: &str) -> bool {
+        self.lock().remove(token).is_some()
+    }
+
+    /// End every grant on a session (it was deleted, or its owner stopped sharing)
+    pub fn revoke_session(&self, session_id: &str) -> usize {
+        let mut grants = self.lock();
+        let before = grants.len();
+        grants.retain(|_, grant| grant.session_id != session_id);
+        before - grants.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grants_resolve_until_revoked_or_expired() {
+        let grants = ObserverGrants::default();
+        let grant = grants.grant("s1", Some("Review".to_string()), None);
+        assert!(grant.token.starts_with("obs_"));
+        assert_eq!(grants.session_of(&grant.token).as_deref(), Some("s1"));
+        assert_eq!(grants.for_session("s1"), vec![grant.clone()]);
+        assert!(grants.session_of("obs_unknown").is_none());
+
+        assert!(grants.revoke(&grant.token));
+        assert!(grants.session_of(&grant.token).is_none());
+        assert!(!grants.revoke(&grant.token));
+
+        let expired = grants.grant("s1", None, Some(Duration::seconds(-1)));
+        assert!(grants.session_of(&expired.token).is_none());
+
+        grants.grant("s1", None, None);
+        grants.grant("s2", None, None);
+        assert_eq!(grants.revoke_session("s1"), 1);
+        assert_eq!(grants.for_session("s2").len(), 1);
+    }
+}
@@ -0,0 +1,372 @@
+//! Files attached to pages
+//!
+//! Files are stored once per content under `<dir>/blobs/`, named by their
+//! SHA-256, so uploading the same file again (to any page) adds a row but no
+//! bytes. A file is removed with the last row that uses it. Deleting a page
+//! drops its rows through the foreign key cascade, and `collect_garbage`
+//! removes the files they leave behind. Images get a PNG
+//! thumbnail under `<dir>/thumbnails/`. Uploads are checked against the
+//! per-file, per-page and global quotas in `AttachmentQuota`.
+
+use crate::database::DbPool;
+use crate::models::settings::{get_setting, set_setting};
+use crate::models::{Attachment, NewAttachment};
+use crate::schema::{attachments, pages};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+const MAX_FILE_KEY: &str = "attachment_max_file_bytes";
+const MAX_PAGE_KEY: &str = "attachment_max_page_bytes";
+const MAX_TOTAL_KEY: &str = "attachment_max_total_bytes";
+
+/// Longest side of a thumbnail, in pixels
+pub const THUMBNAIL_SIZE: u32 = 320;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct AttachmentQuota {
+    pub max_file_bytes: i64,
+    /// All attachments of one page, counting shared files on each page
+    pub max_page_bytes: i64,
+    /// Everything stored, counting shared files once
+    pub max_total_bytes: i64,
+}
+
+impl Default for AttachmentQuota {
+    fn default() -> Self {
+        Self {
+            max_file_bytes: 50 * 1024 * 1024,
+            max_page_bytes: 250 * 1024 * 1024,
+            max_total_bytes: 2 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+impl AttachmentQuota {
+    pub fn load(pool: &DbPool) -> Self {
+        let defaults = Self::default();
+        let read = |key: &str, default: i64| {
+            get_setting(pool, key)
+                .and_then(|v| v.parse().ok())
+                .filter(|v: &i64| *v > 0)
+                .unwrap_or(default)
+        };
+        Self {
+            max_file_bytes: read(MAX_FILE_KEY, defaults.max_file_bytes),
+            max_page_bytes: read(MAX_PAGE_KEY, defaults.max_page_bytes),
+            max_total_bytes: read(MAX_TOTAL_KEY, defaults.max_total_bytes),
+        }
+    }
+
+    pub fn save(&self, pool: &DbPool) -> Result<(), String> {
+        if self.max_file_bytes <= 0 || self.max_page_bytes <= 0 || self.max_total_bytes <= 0 {
+            return Err("Attachment quotas must be positive".to_string());
+        }
+        set_setting(pool, MAX_FILE_KEY, &self.max_file_bytes.to_string())?;
+        set_setting(pool, MAX_PAGE_KEY, &self.max_page_bytes.to_string())?;
+        set_setting(pool, MAX_TOTAL_KEY, &self.max_total_bytes.to_string())
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AttachmentUsage {
+    pub page_bytes: Option<i64>,
+    pub total_bytes: i64,
+    pub quota: AttachmentQuota,
+}
+
+/// What `collect_garbage` removed
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct AttachmentGc {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+pub fn content_hash(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// MIME type from the content's magic bytes, else from the extension
+pub fn sniff_mime(data: &[u8], file_name: &str) -> String {
+    match infer::get(data) {
+        Some(kind) => kind.mime_type().to_string(),
+        None => crate::email::attachments::mime_type_for(file_name).to_string(),
+    }
+}
+
+fn page_bytes(conn: &mut SqliteConnection, page_id: &str) -> QueryResult<i64> {
+    attachments::table
+        .filter(attachments::page_id.eq(page_id))
+        .select(diesel::dsl::sum(attachments::file_size))
+        .first::<Option<i64>>(conn)
+        .map(Option::unwrap_or_default)
+}
+
+fn total_bytes(conn: &mut SqliteConnection) -> QueryResult<i64> {
+    #[derive(QueryableByName)]
+    struct Total {
+        #[diesel(sql_type = diesel::sql_types::BigInt)]
+        bytes: i64,
+    }
+    diesel::sql_query(
+        "SELECT CAST(COALESCE(SUM(file_size), 0) AS INTEGER) AS bytes \
+         FROM (SELECT DISTINCT file_path, file_size FROM attachments)",
+    )
+    .get_result::<Total>(conn)
+    .map(|t| t.bytes)
+}
+
+pub fn usage(pool: &DbPool, page_id: Option<&str>) -> Result<AttachmentUsage, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    Ok(AttachmentUsage {
+        page_bytes: page_id.map(|id| page_bytes(&mut conn, id)).transpose().map_err(|e| e.to_string())?,
+        total_bytes: total_bytes(&mut conn).map_err(|e| e.to_string())?,
+        quota: AttachmentQuota::load(pool),
+    })
+}
+
+fn format_mb(bytes: i64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+fn write_thumbnail(data: &[u8], path: &Path) -> Result<(), String> {
+    let image = image::load_from_memory(data).map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(path.parent().unwrap_or(Path::new("."))).map_err(|e| e.to_string())?;
+    image
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .save_with_format(path, image::ImageFormat::Png)
+        .map_err(|e| e.to_string())
+}
+
+/// Store `data` as an attachment of the page, reusing the file of an earlier
+/// upload with the same content
+pub fn store_attachment(
+    pool: &DbPool,
+    dir: &Path,
+    page_id: &str,
+    file_name: &str,
+    data: &[u8],
+) -> Result<Attachment, String> {
+    let quota = AttachmentQuota::load(pool);
+    let size = data.len() as i64;
+    if size > quota.max_file_bytes {
+        return Err(format!("{} is larger than the {} limit for one file", file_name, format_mb(quota.max_file_bytes)));
+    }
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    pages::table
+        .find(page_id)
+        .select(pages::id)
+        .first::<String>(&mut conn)
+        .map_err(|_| format!("Page '{}' not found", page_id))?;
+    if page_bytes(&mut conn, page_id).map_err(|e| e.to_string())? + size > quota.max_page_bytes {
+        return Err(format!("This page's attachments would exceed its {} limit", format_mb(quota.max_page_bytes)));
+    }
+
+    let hash = content_hash(data);
+    let existing: Option<(String, Option<String>)> = attachments::table
+        .filter(attachments::content_hash.eq(&hash))
+        .select((attachments::file_path, attachments::thumbnail_path))
+        .load::<(String, Option<String>)>(&mut conn)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .find(|(path, _)| Path::new(path).exists());
+    let mime_type = sniff_mime(data, file_name);
+
+    let (file_path, thumbnail_path) = match existing {
+        Some(found) => found,
+        None => {
+            if total_bytes(&mut conn).map_err(|e| e.to_string())? + size > quota.max_total_bytes {
+                return Err(format!("Attachments would exceed the {} storage limit", format_mb(quota.max_total_bytes)));
+            }
+            let blob = dir.join("blobs").join(&hash[..2]).join(&hash);
+            std::fs::create_dir_all(blob.parent().expect("has a parent")).map_err(|e| e.to_string())?;
+            std::fs::write(&blob, data).map_err(|e| format!("Failed to save {}: {}", file_name, e))?;
+
+            let thumbnail = mime_type.starts_with("image/").then(|| dir.join("thumbnails").join(format!("{}.png", hash)));
+            let thumbnail = thumbnail.filter(|path| match write_thumbnail(data, path) {
+                Ok(()) => true,
+                Err(e) => {
+                    log::warn!("No thumbnail for {}: {}", file_name, e);
+                    false
+                }
+            });
+            (
+                blob.to_string_lossy().to_string(),
+                thumbnail.map(|p| p.to_string_lossy().to_string()),
+            )
+        }
+    };
+
+    let new_attachment = NewAttachment {
+        id: uuid::Uuid::new_v4().to_string(),
+        page_id: page_id.to_string(),
+        block_id: None,
+        file_path,
+        file_name: file_name.to_string(),
+        file_type: Path::new(file_name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("unknown")
+            .to_string(),
+        file_size: size as i32,
+        created_at: chrono::Utc::now().naive_utc(),
+        content_hash: Some(hash),
+        mime_type: Some(mime_type),
+        thumbnail_path,
+    };
+    diesel::insert_into(attachments::table)
+        .values(&new_attachment)
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    attachments::table
+        .find(&new_attachment.id)
+        .first(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+/// Every file (and thumbnail) some attachment row uses
+pub(crate) fn referenced_paths(conn: &mut SqliteConnection) -> QueryResult<HashSet<String>> {
+    let rows: Vec<(String, Option<String>)> = attachments::table
+        .select((attachments::file_path, attachments::thumbnail_path))
+        .load(conn)?;
+    Ok(rows
+        .into_iter()
+        .flat_map(|(file, thumbnail)| std::iter::once(file).chain(thumbnail))
+        .collect())
+}
+
+/// Those of `paths` no attachment row uses any more
+pub(crate) fn unreferenced(conn: &mut SqliteConnection, paths: &[String]) -> QueryResult<Vec<String>> {
+    let referenced = referenced_paths(conn)?;
+    Ok(paths.iter().filter(|p| !referenced.contains(*p)).cloned().collect())
+}
+
+/// Delete attachment files; returns how many went and the bytes freed.
+/// Call it outside transactions: a rollback can't bring the files back.
+pub(crate) fn remove_files(paths: &[String]) -> (usize, u64) {
+    let mut removed = (0, 0);
+    for path in paths {
+        let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or_default();
+        match std::fs::remove_file(path) {
+            Ok(()) => {
+                removed.0 += 1;
+                removed.1 += size;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("Failed to remove attachment file {}: {}", path, e),
+        }
+    }
+    removed
+}
+
+/// Remove those of `paths` no attachment row uses any more; returns the
+/// bytes freed
+pub(crate) fn remove_unreferenced(conn: &mut SqliteConnection, paths: &[String]) -> QueryResult<(usize, u64)> {
+    Ok(remove_files(&unreferenced(conn, paths)?))
+}
+
+fn row_paths(rows: Vec<(String, Option<String>)>) -> Vec<String> {
+    rows.into_iter()
+        .flat_map(|(file, thumbnail)| std::iter::once(file).chain(thumbnail))
+        .collect()
+}
+
+pub fn remove_attachment(pool: &DbPool, attachment_id: &str) -> Result<(), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let row: (String, Option<String>) = attachments::table
+        .find(attachment_id)
+        .select((attachments::file_path, attachments::thumbnail_path))
+        .first(&mut conn)
+        .map_err(|_| format!("Attachment '{}' not found", attachment_id))?;
+    diesel::delete(attachments::table.find(attachment_id))
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    remove_unreferenced(&mut conn, &row_paths(vec![row])).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Remove every file under `dir` no attachment uses, such as the files of
+/// deleted pages
+pub fn collect_garbage(pool: &DbPool, dir: &Path) -> Result<AttachmentGc, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let leftovers: Vec<String> = unreferenced_files(&mut conn, dir)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+    let (files, bytes) = remove_unreferenced(&mut conn, &leftovers).map_err(|e| e.to_string())?;
+    let report = AttachmentGc { files, bytes };
+    if report != AttachmentGc::default() {
+        log::info!("Attachment cleanup: {:?}", report);
+    }
+    Ok(report)
+}
+
+/// Files under `dir` no attachment row uses
+pub(crate) fn unreferenced_files(conn: &mut SqliteConnection, dir: &Path) -> QueryResult<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let referenced = referenced_paths(conn)?;
+    Ok(walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .filter(|p| !referenced.contains(p.to_string_lossy().as_ref()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png() -> Vec<u8> {
+        let mut out = std::io::Cursor::new(vec![]);
+        image::RgbaImage::new(800, 600)
+            .write_to(&mut out, image::ImageFormat::Png)
+            .unwrap();
+        out.into_inner()
+    }
+
+    #[test]
+    fn test_dedup_quota_and_gc() {
+        let pool = crate::database::create_test_pool();
+        let dir = tempfile::tempdir().unwrap();
+        let data = png();
+        let first = crate::pages::create_page(&pool, "One", None, "").unwrap();
+        let second = crate::pages::create_page(&pool, "Two", None, "").unwrap();
+
+        // The extension lies; the content decides
+        let a = store_attachment(&pool, dir.path(), &first.id, "dot.bin", &data).unwrap();
+        assert_eq!(a.mime_type.as_deref(), Some("image/png"));
+        let thumbnail = image::open(a.thumbnail_path.as_deref().unwrap()).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (THUMBNAIL_SIZE, 240));
+        let b = store_attachment(&pool, dir.path(), &second.id, "copy.png", &data).unwrap();
+        assert_eq!((&b.file_path, &b.thumbnail_path), (&a.file_path, &a.thumbnail_path));
+        assert_eq!(usage(&pool, Some(&first.id)).unwrap().total_bytes, data.len() as i64);
+
+        AttachmentQuota { max_page_bytes: data.len() as i64, ..Default::default() }.save(&pool).unwrap();
+        assert!(store_attachment(&pool, dir.path(), &first.id, "again.png", &data).is_err());
+
+        // The file stays while the second page still uses it
+        let mut conn = pool.get().unwrap();
+        diesel::delete(pages::table.find(&first.id)).execute(&mut conn).unwrap();
+        assert_eq!(collect_garbage(&pool, dir.path()).unwrap(), AttachmentGc::default());
+        assert!(Path::new(&b.file_path).exists());
+
+        remove_attachment(&pool, &b.id).unwrap();
+        assert!(!Path::new(&b.file_path).exists());
+        assert!(!Path::new(b.thumbnail_path.as_deref().unwrap()).exists());
+
+        // Deleting the page leaves its files to the garbage collection
+        let notes = store_attachment(&pool, dir.path(), &second.id, "notes.txt", b"notes").unwrap();
+        diesel::delete(pages::table.find(&second.id)).execute(&mut conn).unwrap();
+        assert!(Path::new(&notes.file_path).exists());
+        assert_eq!(collect_garbage(&pool, dir.path()).unwrap(), AttachmentGc { files: 1, bytes: 5 });
+        assert!(!Path::new(&notes.file_path).exists());
+    }
+}
@@ -0,0 +1,218 @@
+//! Concurrent block editing
+//!
+//! Every write bumps the block's `version`. A writer sends the version its
+//! edit started from; when someone else changed the type or content since,
+//! the edit is refused as a conflict carrying the block as it is now, so the
+//! editor can merge and retry instead of overwriting. Moving a block never
+//! conflicts. Applied changes go out on the page's channel so open editors
+//! pick up writes made elsewhere (an agent, a synced file, another window).
+
+use crate::database::DbPool;
+use crate::events::AgentObserver;
+use crate::models::Block;
+use crate::schema::blocks;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// `updated_by` of writes made in the app
+pub const USER_EDITOR: &str = "user";
+/// `updated_by` of writes imported from a markdown file
+pub const FILE_EDITOR: &str = "file";
+
+pub fn agent_editor(session_id: &str) -> String {
+    format!("agent:{}", session_id)
+}
+
+/// Event channel carrying a page's `BlockChange`s
+pub fn page_channel(page_id: &str) -> String {
+    format!("page:{}", page_id)
+}
+
+/// A change to one block, as sent by the editor
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BlockEdit {
+    pub id: String,
+    pub block_type: Option<String>,
+    pub content_json: Option<String>,
+    pub order_index: Option<i32>,
+    /// Version the edit started from; unset overwrites whatever is there
+    pub base_version: Option<i32>,
+}
+
+impl BlockEdit {
+    fn changes_content(&self, block: &Block) -> bool {
+        self.block_type.as_ref().is_some_and(|t| *t != block.type_)
+            || self.content_json.as_ref().is_some_and(|c| *c != block.content_json)
+    }
+
+    fn changes(&self, block: &Block) -> bool {
+        self.changes_content(block) || self.order_index.is_some_and(|i| i != block.order_index)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BlockWrite {
+    Applied { block: Block },
+    /// Nothing was written; `block` is the block as it is now
+    Conflict { block: Block, base_version: i32 },
+}
+
+impl BlockWrite {
+    pub fn block(&self) -> &Block {
+        match self {
+            BlockWrite::Applied { block } | BlockWrite::Conflict { block, .. } => block,
+        }
+    }
+}
+
+/// Sent on `page_channel` for every block written
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum BlockChange {
+    Created { block: Block },
+    Updated { block: Block },
+    Deleted { page_id: String, block_id: String, editor: String },
+}
+
+impl BlockChange {
+    pub fn page_id(&self) -> &str {
+        match self {
+            BlockChange::Created { block } | BlockChange::Updated { block } => &block.page_id,
+            BlockChange::Deleted { page_id, .. } => page_id,
+        }
+    }
+
+    pub fn block_id(&self) -> &str {
+        match self {
+            BlockChange::Created { block } | BlockChange::Updated { block } => &block.id,
+            BlockChange::Deleted { block_id, .. } => block_id,
+        }
+    }
+}
+
+pub fn emit_changes(observer: &dyn AgentObserver, changes: &[BlockChange]) {
+    for change in changes {
+        let payload = serde_json::to_value(change).unwrap_or_default();
+        if let Err(e) = observer.emit(&page_channel(change.page_id()), payload) {
+            log::warn!("Failed to send block change: {}", e);
+        }
+    }
+}
+
+/// Apply `edit` unless it conflicts with a write made since its base version
+pub fn write_block(conn: &mut SqliteConnection, edit: &BlockEdit, editor: &str) -> QueryResult<BlockWrite> {
+    let current: Block = blocks::table.find(&edit.id).first(conn)?;
+    if let Some(base) = edit.base_version {
+        if base != current.version && edit.changes_content(&current) {
+            return Ok(BlockWrite::Conflict { block: current, base_version: base });
+        }
+    }
+    if !edit.changes(&current) {
+        return Ok(BlockWrite::Applied { block: current });
+    }
+
+    // Guarded by the version read above, in case another connection wrote
+    // in between
+    let written = diesel::update(
+        blocks::table
+            .filter(blocks::id.eq(&edit.id))
+            .filter(blocks::version.eq(current.version)),
+    )
+    .set((
+        edit.block_type.as_ref().map(|t| blocks::type_.eq(t)),
+        edit.content_json.as_ref().map(|c| blocks::content_json.eq(c)),
+        edit.order_index.map(|i| blocks::order_index.eq(i)),
+        blocks::version.eq(blocks::version + 1),
+        blocks::updated_by.eq(editor),
+        blocks::updated_at.eq(chrono::Utc::now().naive_utc()),
+    ))
+    .execute(conn)?;
+    if written == 0 {
+        return write_block(conn, edit, editor);
+    }
+    let block = blocks::table.find(&edit.id).first(conn)?;
+    Ok(BlockWrite::Applied { block })
+}
+
+/// Apply each edit on its own; a conflict on one does not stop the others
+pub fn write_blocks(pool: &DbPool, edits: &[BlockEdit], editor: &str) -> Result<Vec<BlockWrite>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    conn.immediate_transaction(|conn| edits.iter().map(|edit| write_block(conn, edit, editor)).collect())
+        .map_err(|e| match e {
+            diesel::result::Error::NotFound => "Block not found".to_string(),
+            e => format!("Failed to update block: {}", e),
+        })
+}
+
+/// The applied writes, as changes to send
+pub fn applied_changes(writes: &[BlockWrite]) -> Vec<BlockChange> {
+    writes
+        .iter()
+        .filter_map(|write| match write {
+            BlockWrite::Applied { block } => Some(BlockChange::Updated { block: block.clone() }),
+            BlockWrite::Conflict { .. } => None,
+        })
+        .collect()
+}
+
+/// Changes for blocks just written, read back by id
+pub fn load_changes(pool: &DbPool, created: &[String], updated: &[String]) -> Result<Vec<BlockChange>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let load = |conn: &mut SqliteConnection, ids: &[String]| {
+        blocks::table
+            .filter(blocks::id.eq_any(ids))
+            .order(blocks::order_index.asc())
+            .load::<Block>(conn)
+            .map_err(|e| e.to_string())
+    };
+    let mut changes: Vec<BlockChange> = load(&mut conn, updated)?
+        .into_iter()
+        .map(|block| BlockChange::Updated { block })
+        .collect();
+    changes.extend(load(&mut conn, created)?.into_iter().map(|block| BlockChange::Created { block }));
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+
+    fn edit(id: &str, text: &str, base_version: Option<i32>) -> BlockEdit {
+        BlockEdit {
+            id: id.to_string(),
+            content_json: Some(serde_json::json!({ "text": text }).to_string()),
+            base_version,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_stale_edit_conflicts() {
+        let pool = create_test_pool();
+        let page = crate::pages::create_page(&pool, "Notes", None, "").unwrap();
+        let block_id = crate::pages::append_markdown(&pool, &page.id, "Draft").unwrap().remove(0);
+        assert!(write_blocks(&pool, &[edit("missing", "x", None)], USER_EDITOR).is_err());
+
+        // The agent writes first, both starting from version 1
+        let agent = write_blocks(&pool, &[edit(&block_id, "Agent text", Some(1))], "agent:s1").unwrap();
+        let BlockWrite::Applied { block } = &agent[0] else { panic!("expected applied") };
+        assert_eq!((block.version, block.updated_by.as_deref()), (2, Some("agent:s1")));
+
+        let user = write_blocks(&pool, &[edit(&block_id, "User text", Some(1))], USER_EDITOR).unwrap();
+        let BlockWrite::Conflict { block, base_version } = &user[0] else { panic!("expected conflict") };
+        assert_eq!((block.version, *base_version), (2, 1));
+        assert!(block.content_json.contains("Agent text"));
+        assert!(applied_changes(&user).is_empty());
+
+        // Moving a block is never a conflict
+        let moved = BlockEdit { id: block_id.clone(), order_index: Some(5), base_version: Some(1), ..Default::default() };
+        let writes = write_blocks(&pool, &[moved], USER_EDITOR).unwrap();
+        assert_eq!(writes[0].block().order_index, 5);
+        assert_eq!(applied_changes(&writes)[0].block_id(), block_id);
+
+        let merged = write_blocks(&pool, &[edit(&block_id, "Merged", Some(3))], USER_EDITOR).unwrap();
+        assert!(matches!(&merged[0], BlockWrite::Applied { block } if block.version == 4));
+    }
+}
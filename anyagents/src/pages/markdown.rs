@@ -0,0 +1,284 @@
+//! Markdown form of a page
+//!
+//! ```markdown
+//! ---
+//! page_id: 6f1c…
+//! title: Launch plan
+//! ---
+//!
+//! <!-- block:0b7e… -->
+//! # Goals
+//!
+//! <!-- block:a41d… -->
+//! - [x] Book the venue
+//! ```
+//!
+//! Each block is preceded by an anchor carrying its id, so a file edited
+//! outside the app maps back onto the same blocks. Text without an anchor
+//! becomes new blocks; when the text under one anchor now reads as several
+//! blocks, the first keeps the id.
+
+use crate::models::{Block, Page};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+
+/// Block types rendered as a link to their file
+pub const MEDIA_TYPES: [&str; 3] = ["file", "video", "audio"];
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FrontMatter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    page_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+}
+
+/// A block read from markdown; `content` holds only what markdown carries
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkdownBlock {
+    /// From the anchor, when the block had one
+    pub id: Option<String>,
+    pub type_: String,
+    pub content: Value,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedPage {
+    pub page_id: Option<String>,
+    pub title: Option<String>,
+    pub blocks: Vec<MarkdownBlock>,
+}
+
+fn anchor_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^<!--\s*block:([A-Za-z0-9_-]+)\s*-->$").unwrap())
+}
+
+fn link_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^(!?)\[([^\]]*)\]\(([^)\s]+)\)$").unwrap())
+}
+
+/// `(is_image, label, url)` of a line that is only a link or image
+pub fn parse_link(line: &str) -> Option<(bool, String, String)> {
+    let caps = link_regex().captures(line.trim())?;
+    Some((!caps[1].is_empty(), caps[2].to_string(), caps[3].to_string()))
+}
+
+fn text_of(content: &Value, key: &str) -> String {
+    content.get(key).and_then(Value::as_str).unwrap_or_default().to_string()
+}
+
+/// The markdown for one block, without its anchor
+pub fn render_block(type_: &str, content: &Value) -> String {
+    let text = text_of(content, "text");
+    match type_ {
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+            let level = type_[1..].parse::<usize>().unwrap_or(1);
+            format!("{} {}", "#".repeat(level), text)
+        }
+        "bullet" | "bullet_list" => format!("- {}", text),
+        "checkbox" => {
+            let checked = content.get("checked").and_then(Value::as_bool).unwrap_or(false);
+            format!("- [{}] {}", if checked { "x" } else { " " }, text)
+        }
+        "image" => format!("![{}]({})", text_of(content, "caption"), text_of(content, "url")),
+        t if MEDIA_TYPES.contains(&t) => {
+            let url = text_of(content, "url");
+            let label = [text_of(content, "name"), text_of(content, "caption")]
+                .into_iter()
+                .find(|s| !s.is_empty())
+                .unwrap_or_else(|| url.clone());
+            format!("[{}]({})", label, url)
+        }
+        _ => text,
+    }
+}
+
+/// The page as markdown, blocks in order
+pub fn render_page(page: &Page, blocks: &[Block]) -> String {
+    let front = FrontMatter {
+        page_id: Some(page.id.clone()),
+        title: Some(page.title.clone()),
+    };
+    let mut out = format!("---\n{}---\n", serde_yaml::to_string(&front).unwrap_or_default());
+    for block in blocks {
+        let content: Value = serde_json::from_str(&block.content_json).unwrap_or(Value::Null);
+        out.push_str(&format!("\n<!-- block:{} -->\n", block.id));
+        let body = render_block(&block.type_, &content);
+        if !body.is_empty() {
+            out.push_str(&body);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// One line that is a block by itself, if it is
+fn line_block(line: &str) -> Option<(String, Value)> {
+    let trimmed = line.trim_start();
+    for level in (1..=6).rev() {
+        let marker = format!("{} ", "#".repeat(level));
+        if let Some(text) = trimmed.strip_prefix(&marker) {
+            return Some((format!("h{}", level), json!({ "text": text.trim() })));
+        }
+    }
+    for (marker, checked) in [("- [ ] ", false), ("- [x] ", true), ("- [X] ", true)] {
+        if let Some(text) = trimmed.strip_prefix(marker) {
+            return Some(("checkbox".to_string(), json!({ "text": text.trim(), "checked": checked })));
+        }
+    }
+    if let Some(text) = trimmed.strip_prefix("- ").or_else(|| trimmed.strip_prefix("* ")) {
+        return Some(("bullet".to_string(), json!({ "text": text.trim() })));
+    }
+    if let Some((true, caption, url)) = parse_link(trimmed) {
+        return Some(("image".to_string(), json!({ "url": url, "caption": caption })));
+    }
+    None
+}
+
+pub fn parse_page(text: &str) -> ParsedPage {
+    let mut parsed = ParsedPage::default();
+    let mut body = text;
+    if let Some(rest) = text.strip_prefix("---\n") {
+        if let Some(end) = rest.find("\n---\n").or_else(|| rest.strip_suffix("\n---").map(|r| r.len())) {
+            let front: FrontMatter = serde_yaml::from_str(&rest[..end]).unwrap_or_default();
+            parsed.page_id = front.page_id;
+            parsed.title = front.title;
+            body = rest.get(end + 5..).unwrap_or_default();
+        }
+    }
+
+    let mut builder = Builder {
+        parsed,
+        pending_id: None,
+        paragraph: vec![],
+    };
+    for line in body.lines() {
+        if let Some(caps) = anchor_regex().captures(line.trim()) {
+            builder.close_anchor();
+            builder.pending_id = Some(caps[1].to_string());
+        } else if line.trim().is_empty() {
+            builder.flush();
+        } else if let Some((type_, content)) = line_block(line) {
+            builder.flush();
+            builder.push(type_, content);
+        } else {
+            builder.paragraph.push(line);
+        }
+    }
+    builder.close_anchor();
+    builder.parsed
+}
+
+struct Builder<'a> {
+    parsed: ParsedPage,
+    /// Anchor waiting for the block under it
+    pending_id: Option<String>,
+    paragraph: Vec<&'a str>,
+}
+
+impl Builder<'_> {
+    fn push(&mut self, type_: String, content: Value) {
+        self.parsed.blocks.push(MarkdownBlock {
+            id: self.pending_id.take(),
+            type_,
+            content,
+        });
+    }
+
+    fn flush(&mut self) {
+        if !self.paragraph.is_empty() {
+            let text = self.paragraph.join("\n");
+            self.paragraph.clear();
+            self.push("text".to_string(), json!({ "text": text }));
+        }
+    }
+
+    /// End the current block; an anchor with nothing under it is an empty block
+    fn close_anchor(&mut self) {
+        self.flush();
+        if self.pending_id.is_some() {
+            self.push("text".to_string(), json!({ "text": "" }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(id: &str, type_: &str, content: Value, order_index: i32) -> Block {
+        let now = chrono::Utc::now().naive_utc();
+        Block {
+            id: id.to_string(),
+            page_id: "p1".to_string(),
+            type_: type_.to_string(),
+            content_json: content.to_string(),
+            order_index,
+            created_at: now,
+            updated_at: now,
+            version: 1,
+            updated_by: None,
+        }
+    }
+
+    #[test]
+    fn test_render_and_parse_round_trip() {
+        let now = chrono::Utc::now().naive_utc();
+        let page = Page {
+            id: "p1".to_string(),
+            title: "Launch: plan".to_string(),
+            type_: "page".to_string(),
+            parent_id: None,
+            day_date: None,
+            icon: None,
+            cover_image: None,
+            is_archived: 0,
+            is_published: 0,
+            created_at: now,
+            updated_at: now,
+        };
+        let blocks = vec![
+            block("b1", "h2", json!({"text": "Goals", "richContent": []}), 0),
+            block("b2", "checkbox", json!({"text": "Book the venue", "checked": true}), 1),
+            block("b3", "text", json!({"text": "Line one\nline two"}), 2),
+            block("b4", "image", json!({"url": "https://x/y.png", "caption": "Map"}), 3),
+            block("b5", "text", json!({"text": ""}), 4),
+            block("b6", "video", json!({"url": "file:///clip.mp4", "name": "Clip"}), 5),
+        ];
+        let markdown = render_page(&page, &blocks);
+        assert!(markdown.contains("<!-- block:b2 -->\n- [x] Book the venue\n"));
+
+        let parsed = parse_page(&markdown);
+        assert_eq!(parsed.page_id.as_deref(), Some("p1"));
+        assert_eq!(parsed.title.as_deref(), Some("Launch: plan"));
+        let summary: Vec<(Option<&str>, &str)> =
+            parsed.blocks.iter().map(|b| (b.id.as_deref(), b.type_.as_str())).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (Some("b1"), "h2"),
+                (Some("b2"), "checkbox"),
+                (Some("b3"), "text"),
+                (Some("b4"), "image"),
+                (Some("b5"), "text"),
+                (Some("b6"), "text"),
+            ]
+        );
+        assert_eq!(parsed.blocks[2].content["text"], "Line one\nline two");
+        assert_eq!(parsed.blocks[3].content["caption"], "Map");
+    }
+
+    #[test]
+    fn test_unanchored_text_becomes_new_blocks() {
+        let parsed = parse_page("<!-- block:b1 -->\nIntro\n\nAdded by an agent\n- item\n");
+        assert_eq!(parsed.page_id, None);
+        assert_eq!(parsed.blocks.len(), 3);
+        assert_eq!(parsed.blocks[0].id.as_deref(), Some("b1"));
+        assert_eq!(parsed.blocks[1].id, None);
+        assert_eq!(parsed.blocks[2].type_, "bullet");
+    }
+}
@@ -0,0 +1,589 @@
+//! Pages as markdown files
+//!
+//! A page exports to a markdown file whose blocks carry their ids (see
+//! `markdown`), and a file imports back onto the same page and blocks. Syncing
+//! a file goes whichever way is newer, so an agent editing files in its
+//! workspace and a user editing the page in the app see each other's changes.
+
+pub mod attachments;
+pub mod collab;
+pub mod markdown;
+
+pub use collab::{BlockChange, BlockEdit, BlockWrite};
+pub use markdown::{parse_page, render_page, MarkdownBlock, ParsedPage};
+
+use crate::database::DbPool;
+use crate::models::{Block, NewBlock, NewPage, Page};
+use crate::schema::{blocks, pages};
+use diesel::prelude::*;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Folder of an agent's workspace synced by default
+pub const WORKSPACE_PAGES_DIR: &str = "pages";
+
+/// Blocks touched by an import
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct BlockChanges {
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+}
+
+impl BlockChanges {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncDirection {
+    /// The file had no page yet
+    Created,
+    /// The file was newer and changed the page
+    Imported,
+    /// The page was newer and was written to the file
+    Exported,
+    Unchanged,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PageSync {
+    pub path: String,
+    pub page_id: String,
+    pub title: String,
+    pub direction: SyncDirection,
+    pub blocks: BlockChanges,
+}
+
+fn load_page(conn: &mut SqliteConnection, page_id: &str) -> Result<(Page, Vec<Block>), String> {
+    let page: Page = pages::table
+        .find(page_id)
+        .first(conn)
+        .map_err(|_| format!("Page '{}' not found", page_id))?;
+    let page_blocks: Vec<Block> = blocks::table
+        .filter(blocks::page_id.eq(page_id))
+        .order(blocks::order_index.asc())
+        .load(conn)
+        .map_err(|e| e.to_string())?;
+    Ok((page, page_blocks))
+}
+
+/// The page and its blocks as markdown
+pub fn export_markdown(pool: &DbPool, page_id: &str) -> Result<String, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let (page, page_blocks) = load_page(&mut conn, page_id)?;
+    Ok(render_page(&page, &page_blocks))
+}
+
+/// Apply markdown to the page it names, or create a page (titled from the
+/// file, else `fallback_title`, under `parent_id`) when it names none that
+/// exists. Blocks keep their ids and whatever markdown cannot express, such
+/// as rich text formatting, unless their text changed. Also returns whether
+/// the page itself was created or retitled.
+pub fn import_markdown(
+    pool: &DbPool,
+    text: &str,
+    fallback_title: &str,
+    parent_id: Option<&str>,
+) -> Result<(Page, BlockChanges, bool), String> {
+    let parsed = parse_page(text);
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let existing = match parsed.page_id.as_deref() {
+        Some(id) => pages::table
+            .find(id)
+            .first::<Page>(&mut conn)
+            .optional()
+            .map_err(|e| e.to_string())?,
+        None => None,
+    };
+    let created = existing.is_none();
+
+    let (page_id, changes) = conn
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            let now = chrono::Utc::now().naive_utc();
+            let page_id = match &existing {
+                Some(page) => {
+                    if let Some(title) = parsed.title.as_ref().filter(|t| **t != page.title) {
+                        diesel::update(pages::table.find(&page.id))
+                            .set((pages::title.eq(title), pages::updated_at.eq(now)))
+                            .execute(conn)?;
+                    }
+                    page.id.clone()
+                }
+                None => {
+                    let page = NewPage {
+                        id: uuid::Uuid::new_v4().to_string(),
+                        title: parsed.title.clone().unwrap_or_else(|| fallback_title.to_string()),
+                        type_: "page".to_string(),
+                        parent_id: parent_id.map(String::from),
+                        day_date: None,
+                        icon: None,
+                        cover_image: None,
+                        is_archived: 0,
+                        is_published: 0,
+                        created_at: now,
+                        updated_at: now,
+                    };
+                    diesel::insert_into(pages::table).values(&page).execute(conn)?;
+                    page.id
+                }
+            };
+            let changes = apply_blocks(conn, &page_id, &parsed.blocks)?;
+            if !changes.is_empty() {
+                diesel::update(pages::table.find(&page_id))
+                    .set(pages::updated_at.eq(now))
+                    .execute(conn)?;
+            }
+            Ok((page_id, changes))
+        })
+        .map_err(|e| format!("Failed to import page: {}", e))?;
+
+    let page: Page = pages::table
+        .find(&page_id)
+        .first(&mut conn)
+        .map_err(|e| e.to_string())?;
+    let title_changed = existing.is_some_and(|old: Page| old.title != page.title);
+    Ok((page, changes, created || title_changed))
+}
+
+/// Make the page's blocks those of the markdown, in its order
+fn apply_blocks(
+    conn: &mut SqliteConnection,
+    page_id: &str,
+    parsed: &[MarkdownBlock],
+) -> QueryResult<BlockChanges> {
+    let mut existing: HashMap<String, Block> = blocks::table
+        .filter(blocks::page_id.eq(page_id))
+        .load::<Block>(conn)?
+        .into_iter()
+        .map(|b| (b.id.clone(), b))
+        .collect();
+    let now = chrono::Utc::now().naive_utc();
+    let mut changes = BlockChanges::default();
+
+    for (index, block) in parsed.iter().enumerate() {
+        let order_index = index as i32;
+        // An id seen twice (a copied anchor) or from another page makes a new block
+        match block.id.as_ref().and_then(|id| existing.remove(id)) {
+            Some(current) => {
+                let (type_, content_json) = merge_block(&current, block);
+                if type_ != current.type_ || content_json != current.content_json || order_index != current.order_index {
+                    diesel::update(blocks::table.find(&current.id))
+                        .set((
+                            blocks::type_.eq(type_),
+                            blocks::content_json.eq(content_json),
+                            blocks::order_index.eq(order_index),
+                            blocks::version.eq(blocks::version + 1),
+                            blocks::updated_by.eq(collab::FILE_EDITOR),
+                            blocks::updated_at.eq(now),
+                        ))
+                        .execute(conn)?;
+                    changes.updated += 1;
+                }
+            }
+            None => {
+                diesel::insert_into(blocks::table)
+                    .values(&NewBlock {
+                        id: uuid::Uuid::new_v4().to_string(),
+                        page_id: page_id.to_string(),
+                        type_: block.type_.clone(),
+                        content_json: block.content.to_string(),
+                        order_index,
+                        created_at: now,
+                        updated_at: now,
+                    })
+                    .execute(conn)?;
+                changes.created += 1;
+            }
+        }
+    }
+
+    let removed: Vec<String> = existing.into_keys().collect();
+    if !removed.is_empty() {
+        changes.deleted = diesel::delete(blocks::table.filter(blocks::id.eq_any(&removed))).execute(conn)?;
+    }
+    Ok(changes)
+}
+
+/// The type and content a stored block takes from its markdown
+fn merge_block(current: &Block, parsed: &MarkdownBlock) -> (String, String) {
+    let mut content: Value = serde_json::from_str(&current.content_json)
+        .ok()
+        .filter(Value::is_object)
+        .unwrap_or_else(|| json!({}));
+    if markdown::render_block(&current.type_, &content) == markdown::render_block(&parsed.type_, &parsed.content) {
+        return (current.type_.clone(), current.content_json.clone());
+    }
+
+    // Media blocks read back as plain links
+    let text = parsed.content.get("text").and_then(Value::as_str).unwrap_or_default();
+    if markdown::MEDIA_TYPES.contains(&current.type_.as_str()) {
+        if let Some((false, label, url)) = markdown::parse_link(text) {
+            content["url"] = json!(url);
+            let label_key = if content.get("name").is_some() { "name" } else { "caption" };
+            content[label_key] = json!(label);
+            return (current.type_.clone(), content.to_string());
+        }
+    }
+
+    let object = content.as_object_mut().expect("checked above");
+    object.remove("richContent");
+    if let Some(fields) = parsed.content.as_object() {
+        object.extend(fields.clone());
+    }
+    (parsed.type_.clone(), content.to_string())
+}
+
+fn insert_blocks(
+    conn: &mut SqliteConnection,
+    page_id: &str,
+    parsed: &[MarkdownBlock],
+    first_index: i32,
+) -> QueryResult<Vec<String>> {
+    let now = chrono::Utc::now().naive_utc();
+    let mut ids = vec![];
+    for (offset, block) in parsed.iter().enumerate() {
+        let id = uuid::Uuid::new_v4().to_string();
+        diesel::insert_into(blocks::table)
+            .values(&NewBlock {
+                id: id.clone(),
+                page_id: page_id.to_string(),
+                type_: block.type_.clone(),
+                content_json: block.content.to_string(),
+                order_index: first_index + offset as i32,
+                created_at: now,
+                updated_at: now,
+            })
+            .execute(conn)?;
+        ids.push(id);
+    }
+    diesel::update(pages::table.find(page_id))
+        .set(pages::updated_at.eq(now))
+        .execute(conn)?;
+    Ok(ids)
+}
+
+/// Markdown written by an agent: every block is new, whatever anchors it has
+fn new_blocks(markdown: &str) -> Result<Vec<MarkdownBlock>, String> {
+    let parsed = parse_page(markdown).blocks;
+    if parsed.is_empty() {
+        return Err("The markdown has no content".to_string());
+    }
+    Ok(parsed)
+}
+
+/// Pages that are not archived, most recently changed first; `query` filters
+/// on the title
+pub fn list_pages(pool: &DbPool, query: Option<&str>, limit: i64) -> Result<Vec<Page>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let mut select = pages::table.filter(pages::is_archived.eq(0)).into_boxed();
+    if let Some(query) = query.map(str::trim).filter(|q| !q.is_empty()) {
+        select = select.filter(pages::title.like(format!("%{}%", query)));
+    }
+    select
+        .order(pages::updated_at.desc())
+        .limit(limit)
+        .load(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+/// A new page with `markdown` as its blocks
+pub fn create_page(pool: &DbPool, title: &str, parent_id: Option<&str>, markdown: &str) -> Result<Page, String> {
+    if title.trim().is_empty() {
+        return Err("A page needs a title".to_string());
+    }
+    let parsed = parse_page(markdown).blocks;
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().naive_utc();
+    let page = NewPage {
+        id: uuid::Uuid::new_v4().to_string(),
+        title: title.trim().to_string(),
+        type_: "page".to_string(),
+        parent_id: parent_id.map(String::from),
+        day_date: None,
+        icon: None,
+        cover_image: None,
+        is_archived: 0,
+        is_published: 0,
+        created_at: now,
+        updated_at: now,
+    };
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        diesel::insert_into(pages::table).values(&page).execute(conn)?;
+        insert_blocks(conn, &page.id, &parsed, 0)
+    })
+    .map_err(|e| format!("Failed to create page: {}", e))?;
+    pages::table
+        .find(&page.id)
+        .first(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+/// Add `markdown` to the end of the page; returns the new block ids
+pub fn append_markdown(pool: &DbPool, page_id: &str, markdown: &str) -> Result<Vec<String>, String> {
+    let parsed = new_blocks(markdown)?;
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let (_, page_blocks) = load_page(&mut conn, page_id)?;
+    let next = page_blocks.last().map_or(0, |b| b.order_index + 1);
+    conn.transaction(|conn| insert_blocks(conn, page_id, &parsed, next))
+        .map_err(|e| format!("Failed to append to page: {}", e))
+}
+
+/// Replace one block with `markdown`. When it reads as several blocks the
+/// first keeps the block's id and the rest are inserted after it. With
+/// `base_version` set, a block changed since that version is left alone.
+pub fn replace_block(
+    pool: &DbPool,
+    block_id: &str,
+    markdown: &str,
+    base_version: Option<i32>,
+    editor: &str,
+) -> Result<Vec<String>, String> {
+    let mut parsed = new_blocks(markdown)?;
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let current: Block = blocks::table
+        .find(block_id)
+        .first(&mut conn)
+        .map_err(|_| format!("Block '{}' not found", block_id))?;
+    if let Some(base) = base_version.filter(|v| *v != current.version) {
+        return Err(format!(
+            "Block '{}' was changed since version {} (now {}); read the page again",
+            block_id, base, current.version
+        ));
+    }
+    let first = parsed.remove(0);
+    let (type_, content_json) = merge_block(&current, &first);
+
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        diesel::update(blocks::table.find(block_id))
+            .set((
+                blocks::type_.eq(type_),
+                blocks::content_json.eq(content_json),
+                blocks::version.eq(blocks::version + 1),
+                blocks::updated_by.eq(editor),
+                blocks::updated_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .execute(conn)?;
+        if parsed.is_empty() {
+            return Ok(vec![block_id.to_string()]);
+        }
+        // Make room after the block
+        diesel::update(
+            blocks::table
+                .filter(blocks::page_id.eq(&current.page_id))
+                .filter(blocks::order_index.gt(current.order_index)),
+        )
+        .set(blocks::order_index.eq(blocks::order_index + parsed.len() as i32))
+        .execute(conn)?;
+        let mut ids = vec![block_id.to_string()];
+        ids.extend(insert_blocks(conn, &current.page_id, &parsed, current.order_index + 1)?);
+        Ok(ids)
+    })
+    .map_err(|e| format!("Failed to update block: {}", e))
+}
+
+/// When the page or any of its blocks last changed
+fn page_modified(conn: &mut SqliteConnection, page: &Page) -> QueryResult<chrono::NaiveDateTime> {
+    let latest_block: Option<chrono::NaiveDateTime> = blocks::table
+        .filter(blocks::page_id.eq(&page.id))
+        .select(diesel::dsl::max(blocks::updated_at))
+        .first(conn)?;
+    Ok(latest_block.map_or(page.updated_at, |b| b.max(page.updated_at)))
+}
+
+/// Sync one markdown file with its page, in whichever direction is newer.
+/// The file is rewritten afterwards when its page or anchors changed.
+pub fn sync_file(pool: &DbPool, path: &Path, parent_id: Option<&str>) -> Result<PageSync, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let file_modified: chrono::NaiveDateTime = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map(|t| chrono::DateTime::<chrono::Utc>::from(t).naive_utc())
+        .map_err(|e| e.to_string())?;
+    let fallback_title = path.file_stem().and_then(|s| s.to_str()).unwrap_or("Untitled");
+
+    let page_id = parse_page(&text).page_id;
+    let existing = {
+        let mut conn = pool.get().map_err(|e| e.to_string())?;
+        match page_id.as_deref() {
+            Some(id) => match pages::table.find(id).first::<Page>(&mut conn).optional().map_err(|e| e.to_string())? {
+                Some(page) => {
+                    let modified = page_modified(&mut conn, &page).map_err(|e| e.to_string())?;
+                    Some((page, modified))
+                }
+                None => None,
+            },
+            None => None,
+        }
+    };
+
+    let (page, direction, changes) = match existing {
+        Some((page, modified)) if modified >= file_modified => (page, SyncDirection::Exported, BlockChanges::default()),
+        existing => {
+            let (page, changes, page_changed) = import_markdown(pool, &text, fallback_title, parent_id)?;
+            let direction = if existing.is_none() {
+                SyncDirection::Created
+            } else if changes.is_empty() && !page_changed {
+                SyncDirection::Unchanged
+            } else {
+                SyncDirection::Imported
+            };
+            (page, direction, changes)
+        }
+    };
+
+    let rendered = export_markdown(pool, &page.id)?;
+    let direction = if rendered == text {
+        if direction == SyncDirection::Exported { SyncDirection::Unchanged } else { direction }
+    } else {
+        std::fs::write(path, &rendered).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        direction
+    };
+
+    Ok(PageSync {
+        path: path.display().to_string(),
+        page_id: page.id,
+        title: page.title,
+        direction,
+        blocks: changes,
+    })
+}
+
+/// Sync every `.md` file directly in `dir`. A file that fails is logged
+/// and skipped so one bad file does not stop the rest.
+pub fn sync_directory(pool: &DbPool, dir: &Path, parent_id: Option<&str>) -> Result<Vec<PageSync>, String> {
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut files: Vec<_> = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "md"))
+        .collect();
+    files.sort();
+
+    Ok(files
+        .iter()
+        .filter_map(|path| match sync_file(pool, path, parent_id) {
+            Ok(sync) => Some(sync),
+            Err(e) => {
+                log::warn!("Skipping page file {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+
+    fn block_texts(pool: &DbPool, page_id: &str) -> Vec<(String, String)> {
+        let mut conn = pool.get().unwrap();
+        let (_, page_blocks) = load_page(&mut conn, page_id).unwrap();
+        page_blocks
+            .into_iter()
+            .map(|b| {
+                let content: Value = serde_json::from_str(&b.content_json).unwrap();
+                (b.type_, content["text"].as_str().unwrap_or_default().to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_import_keeps_block_ids() {
+        let pool = create_test_pool();
+        let (page, changes, _) = import_markdown(&pool, "# Plan\n\n- [ ] Book venue\n", "plan", None).unwrap();
+        assert_eq!(page.title, "plan");
+        assert_eq!(changes.created, 2);
+
+        // Formatting markdown cannot express survives an untouched block
+        let mut conn = pool.get().unwrap();
+        let (_, page_blocks) = load_page(&mut conn, &page.id).unwrap();
+        let heading_id = page_blocks[0].id.clone();
+        diesel::update(blocks::table.find(&heading_id))
+            .set(blocks::content_json.eq(r#"{"text":"Plan","richContent":[{"type":"text","text":"Plan","styles":{"bold":true}}]}"#))
+            .execute(&mut conn)
+            .unwrap();
+
+        let edited = export_markdown(&pool, &page.id)
+            .unwrap()
+            .replace("- [ ] Book venue", "- [x] Book venue\n\nBudget: 10k");
+        let (_, changes, _) = import_markdown(&pool, &edited, "plan", None).unwrap();
+        assert_eq!(changes, BlockChanges { created: 1, updated: 1, deleted: 0 });
+
+        let (_, page_blocks) = load_page(&mut conn, &page.id).unwrap();
+        assert_eq!(page_blocks[0].id, heading_id);
+        assert!(page_blocks[0].content_json.contains("richContent"));
+        assert!(page_blocks[1].content_json.contains("\"checked\":true"));
+        assert_eq!(
+            block_texts(&pool, &page.id)[2],
+            ("text".to_string(), "Budget: 10k".to_string())
+        );
+
+        // Removing a block's section, anchor included, removes the block
+        let markdown = export_markdown(&pool, &page.id).unwrap();
+        let trimmed = &markdown[..markdown.rfind("\n<!-- block:").unwrap()];
+        let (_, changes, _) = import_markdown(&pool, trimmed, "plan", None).unwrap();
+        assert_eq!(changes.deleted, 1);
+        assert_eq!(block_texts(&pool, &page.id).len(), 2);
+    }
+
+    #[test]
+    fn test_agent_edits() {
+        let pool = create_test_pool();
+        let page = create_page(&pool, "Research", None, "# Findings\n").unwrap();
+        assert!(append_markdown(&pool, &page.id, "  \n").is_err());
+
+        let appended = append_markdown(&pool, &page.id, "First point\n\nSecond point").unwrap();
+        assert_eq!(appended.len(), 2);
+        assert!(replace_block(&pool, &appended[0], "Stale", Some(0), "agent:s1").is_err());
+        let ids = replace_block(&pool, &appended[0], "- One\n- Two", Some(1), "agent:s1").unwrap();
+        assert_eq!(ids[0], appended[0]);
+        assert_eq!(
+            block_texts(&pool, &page.id),
+            vec![
+                ("h1".to_string(), "Findings".to_string()),
+                ("bullet".to_string(), "One".to_string()),
+                ("bullet".to_string(), "Two".to_string()),
+                ("text".to_string(), "Second point".to_string()),
+            ]
+        );
+        assert_eq!(list_pages(&pool, Some("resea"), 10).unwrap().len(), 1);
+        assert!(list_pages(&pool, Some("budget"), 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sync_file_both_ways() {
+        let pool = create_test_pool();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        std::fs::write(&path, "First idea\n").unwrap();
+
+        let sync = sync_file(&pool, &path, None).unwrap();
+        assert_eq!(sync.direction, SyncDirection::Created);
+        assert_eq!(sync.title, "notes");
+        // The file now names its page and blocks
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(written.contains(&format!("page_id: {}", sync.page_id)));
+        assert!(written.contains("<!-- block:"));
+
+        assert_eq!(sync_file(&pool, &path, None).unwrap().direction, SyncDirection::Unchanged);
+
+        // A change in the app is written out
+        let mut conn = pool.get().unwrap();
+        diesel::update(blocks::table.filter(blocks::page_id.eq(&sync.page_id)))
+            .set((
+                blocks::content_json.eq(r#"{"text":"Second idea"}"#),
+                blocks::updated_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .execute(&mut conn)
+            .unwrap();
+        assert_eq!(sync_file(&pool, &path, None).unwrap().direction, SyncDirection::Exported);
+        assert!(std::fs::read_to_string(&path).unwrap().contains("Second idea"));
+    }
+}
@@ -1,5 +1,13 @@
+pub mod dlp;
+pub mod payload;
+pub mod policy;
+pub mod read_only;
 pub mod scope;
 
+pub use dlp::{DlpFilter, DlpReport, DlpTarget};
+pub use payload::PermissionPayload;
+pub use policy::{AgentPolicy, PolicyDecision};
+pub use read_only::ReadOnlyPolicy;
 pub use scope::{ScopeEnforcer, ScopeType};
 
 use serde::{Deserialize, Serialize};
@@ -13,15 +21,44 @@ pub enum PermissionType {
     FilesystemWrite,
     ShellExecute,
     Network,
+    /// Creating or changing the user's pages
+    PagesWrite,
     Unknown,
 }
 
+impl PermissionType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PermissionType::FilesystemRead => "filesystem_read",
+            PermissionType::FilesystemWrite => "filesystem_write",
+            PermissionType::ShellExecute => "shell_execute",
+            PermissionType::Network => "network",
+            PermissionType::PagesWrite => "pages_write",
+            PermissionType::Unknown => "unknown",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PermissionRequest {
     pub id: String,
     pub permission_type: PermissionType,
     pub message: String,
     pub metadata: HashMap<String, String>,
+    /// Exactly what would happen, for approval UIs to show
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<PermissionPayload>,
+}
+
+impl PermissionRequest {
+    /// What to show under the message in a chat approval: the payload, or
+    /// the bare resource for requests without one
+    pub fn preview(&self, max_len: usize) -> Option<String> {
+        match &self.payload {
+            Some(payload) => Some(payload.preview(max_len)),
+            None => self.metadata.get("resource").cloned(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,14 +69,36 @@ pub enum PermissionResponse {
     AllowAlways,
 }
 
+use crate::database::DbPool;
+use crate::models::permission_audit::{record_permission_decision, NewPermissionAuditEntry};
+use crate::models::permission_grant::{self, GrantContext, NewPermissionGrant};
+pub use crate::models::permission_grant::GrantScope;
 use dashmap::DashMap;
 use tokio::sync::oneshot;
 
+/// Reach of an "always" answer that does not name a scope, as from the chat
+/// connectors' Always button
+pub const DEFAULT_GRANT_SCOPE: GrantScope = GrantScope::Global;
+
+struct Answer {
+    response: PermissionResponse,
+    decided_by: String,
+    // Scope and lifetime of an AllowAlways answer
+    grant: Option<(GrantScope, Option<chrono::Duration>)>,
+}
+
 pub struct PermissionManager {
     // Map of permission_key -> allowed (true/false)
     cache: Arc<Mutex<HashMap<String, bool>>>,
-    // Map of request_id -> sender
-    pending_requests: Arc<DashMap<String, oneshot::Sender<bool>>>,
+    // Map of request_id -> sender of the answer
+    pending_requests: Arc<DashMap<String, oneshot::Sender<Answer>>>,
+    // Where every resolution is recorded, if anywhere
+    audit: Option<DbPool>,
+    // Where "always" answers are kept; in `cache` for this process only
+    // when unset
+    grants: Option<DbPool>,
+    // Rules of the agent this manager was made for, see `with_policy`
+    policy: Option<Arc<AgentPolicy>>,
 }
 
 impl Default for PermissionManager {
@@ -53,9 +112,38 @@ impl PermissionManager {
         Self {
             cache: Arc::new(Mutex::new(HashMap::new())),
             pending_requests: Arc::new(DashMap::new()),
+            audit: None,
+            grants: None,
+            policy: None,
         }
     }
 
+    /// This manager, with `policy` checked before anyone is asked. Pending
+    /// requests, cache, grants and audit log stay shared, so the app answers
+    /// requests of the returned manager as usual.
+    pub fn with_policy(&self, policy: AgentPolicy) -> Self {
+        Self {
+            cache: self.cache.clone(),
+            pending_requests: self.pending_requests.clone(),
+            audit: self.audit.clone(),
+            grants: self.grants.clone(),
+            policy: Some(Arc::new(policy)),
+        }
+    }
+
+    /// Record every resolved request in the `permission_audit` table
+    pub fn with_audit_log(mut self, pool: DbPool) -> Self {
+        self.audit = Some(pool);
+        self
+    }
+
+    /// Keep "always" answers as scoped grants in the `permission_grants`
+    /// table, so they survive restarts and can be listed and revoked
+    pub fn with_persistent_grants(mut self, pool: DbPool) -> Self {
+        self.grants = Some(pool);
+        self
+    }
+
     pub async fn request_permission(
         &self,
         observer: Option<&Arc<dyn crate::events::AgentObserver>>,
@@ -66,27 +154,59 @@ impl PermissionManager {
             .get("session_id")
             .map(|s| format!("{}:", s))
             .unwrap_or_default();
-        let key = format!(
-            "{}{:?}:{}",
-            session_part,
-            req.permission_type,
-            req.metadata
-                .get("resource")
-                .unwrap_or(&"global".to_string())
-        );
+        let resource = req
+            .metadata
+            .get("resource")
+            .cloned()
+            .unwrap_or_else(|| "global".to_string());
+        // In-memory "always" answers apply to every session
+        let always_key = format!("{:?}:{}", req.permission_type, resource);
+        let key = format!("{}{}", session_part, always_key);
+        let requested_at = chrono::Utc::now().naive_utc();
+
+        if let Some(policy) = &self.policy {
+            match policy.evaluate(&req) {
+                (PolicyDecision::Allow, _) => {
+                    self.audit(&req, "allow", "policy", requested_at);
+                    return Ok(true);
+                }
+                (PolicyDecision::Deny, reason) => {
+                    log::info!("Policy denied {}: {}", req.message, reason.as_deref().unwrap_or("no reason given"));
+                    self.audit(&req, "deny", "policy", requested_at);
+                    return Ok(false);
+                }
+                (PolicyDecision::Ask, _) => {}
+            }
+        }
 
         // Check cache first
-        {
+        let cached = {
             let cache = self.cache.lock().unwrap();
-            if let Some(allowed) = cache.get(&key) {
-                return Ok(*allowed);
+            cache.get(&key).or_else(|| cache.get(&always_key)).copied()
+        };
+        if let Some(allowed) = cached {
+            self.audit(&req, if allowed { "allow" } else { "deny" }, "cache", requested_at);
+            return Ok(allowed);
+        }
+        if let Some(pool) = &self.grants {
+            let context = GrantContext::for_session(pool, req.metadata.get("session_id").map(String::as_str));
+            match permission_grant::find_grant(pool, req.permission_type.as_str(), &resource, &context) {
+                Ok(Some(grant)) => {
+                    self.audit(&req, "allow", &format!("grant:{}", grant.id), requested_at);
+                    return Ok(true);
+                }
+                Ok(None) => {}
+                Err(e) => log::error!("Failed to look up permission grants: {}", e),
             }
         }
 
         // If no observer is available, default to deny (safety)
         let observer = match observer {
             Some(o) => o,
-            None => return Ok(false),
+            None => {
+                self.audit(&req, "deny", "no_approver", requested_at);
+                return Ok(false);
+            }
         };
 
         // Create response channel
@@ -113,32 +233,141 @@ impl PermissionManager {
 
         // Wait for response
         match rx.await {
-            Ok(allowed) => {
-                if allowed {
-                    // Update cache
+            Ok(Answer { response: PermissionResponse::Allow, decided_by, .. }) => {
+                // Update cache
+                self.cache.lock().unwrap().insert(key, true);
+                self.audit(&req, "allow", &decided_by, requested_at);
+                Ok(true)
+            }
+            Ok(Answer { response: PermissionResponse::AllowAlways, decided_by, grant }) => {
+                let (scope, ttl) = grant.unwrap_or((DEFAULT_GRANT_SCOPE, None));
+                if !self.persist_grant(&req, &resource, scope, ttl, &decided_by) {
                     let mut cache = self.cache.lock().unwrap();
                     cache.insert(key, true);
-                    Ok(true)
-                } else {
-                    Ok(false)
+                    if scope == GrantScope::Global && ttl.is_none() {
+                        cache.insert(always_key, true);
+                    }
                 }
+                self.audit(&req, "always", &decided_by, requested_at);
+                Ok(true)
+            }
+            Ok(Answer { response: PermissionResponse::Deny, decided_by, .. }) => {
+                self.audit(&req, "deny", &decided_by, requested_at);
+                Ok(false)
             }
             Err(_) => {
                 // Sender dropped (timeout or app close)
+                self.audit(&req, "deny", "dropped", requested_at);
                 Ok(false)
             }
         }
     }
 
-    pub fn approve_request(&self, request_id: &str) {
-        if let Some((_, tx)) = self.pending_requests.remove(request_id) {
-            let _ = tx.send(true);
+    /// Store an "always" answer as a grant. False when there is no grant store
+    /// or the session has nothing to tie the scope to (e.g. no workspace);
+    /// the answer then only holds for the session, in memory.
+    fn persist_grant(
+        &self,
+        req: &PermissionRequest,
+        resource: &str,
+        scope: GrantScope,
+        ttl: Option<chrono::Duration>,
+        decided_by: &str,
+    ) -> bool {
+        let Some(pool) = &self.grants else {
+            return false;
+        };
+        let context = GrantContext::for_session(pool, req.metadata.get("session_id").map(String::as_str));
+        let Some(scope_id) = context.scope_id(scope) else {
+            log::warn!("No {} to grant {} to, allowing for this session only", scope.as_str(), req.id);
+            return false;
+        };
+        let grant = NewPermissionGrant {
+            id: uuid::Uuid::new_v4().to_string(),
+            scope: scope.as_str().to_string(),
+            scope_id,
+            permission_type: req.permission_type.as_str().to_string(),
+            resource: resource.to_string(),
+            granted_by: decided_by.to_string(),
+            expires_at: ttl.map(|ttl| chrono::Utc::now().naive_utc() + ttl),
+        };
+        match permission_grant::create_grant(pool, grant) {
+            Ok(_) => true,
+            Err(e) => {
+                log::error!("Failed to store permission grant for {}: {}", req.id, e);
+                false
+            }
+        }
+    }
+
+    fn audit(&self, req: &PermissionRequest, resolution: &str, decided_by: &str, requested_at: chrono::NaiveDateTime) {
+        let Some(pool) = &self.audit else {
+            return;
+        };
+        let entry = NewPermissionAuditEntry {
+            id: req.id.clone(),
+            session_id: req.metadata.get("session_id").cloned(),
+            tool: req.metadata.get("tool").cloned(),
+            permission_type: req.permission_type.as_str().to_string(),
+            resource: req.metadata.get("resource").cloned(),
+            message: req.message.clone(),
+            metadata: serde_json::to_string(&req.metadata).unwrap_or_else(|_| "{}".to_string()),
+            resolution: resolution.to_string(),
+            decided_by: decided_by.to_string(),
+            requested_at,
+            decided_at: chrono::Utc::now().naive_utc(),
+            payload: req.payload.as_ref().and_then(|p| serde_json::to_string(p).ok()),
+        };
+        // The decision stands even if it could not be written down
+        if let Err(e) = record_permission_decision(pool, entry) {
+            log::error!("Failed to record permission decision {}: {}", req.id, e);
         }
     }
 
+    pub fn approve_request(&self, request_id: &str) {
+        self.respond(request_id, PermissionResponse::Allow);
+    }
+
     pub fn reject_request(&self, request_id: &str) {
-        if let Some((_, tx)) = self.pending_requests.remove(request_id) {
-            let _ = tx.send(false);
+        self.respond(request_id, PermissionResponse::Deny);
+    }
+
+    /// Answer a pending request from the app. Returns false when it was
+    /// already answered (e.g. from another window or chat) or never existed.
+    pub fn respond(&self, request_id: &str, response: PermissionResponse) -> bool {
+        self.respond_as(request_id, response, "app")
+    }
+
+    /// Like `respond`, naming who decided for the audit log, e.g.
+    /// `telegram:12345`
+    pub fn respond_as(&self, request_id: &str, response: PermissionResponse, decided_by: &str) -> bool {
+        self.answer(request_id, Answer {
+            response,
+            decided_by: decided_by.to_string(),
+            grant: None,
+        })
+    }
+
+    /// Allow a pending request from now on within `scope`, for `ttl` or
+    /// until revoked
+    pub fn grant_as(
+        &self,
+        request_id: &str,
+        scope: GrantScope,
+        ttl: Option<chrono::Duration>,
+        decided_by: &str,
+    ) -> bool {
+        self.answer(request_id, Answer {
+            response: PermissionResponse::AllowAlways,
+            decided_by: decided_by.to_string(),
+            grant: Some((scope, ttl)),
+        })
+    }
+
+    fn answer(&self, request_id: &str, answer: Answer) -> bool {
+        match self.pending_requests.remove(request_id) {
+            Some((_, tx)) => tx.send(answer).is_ok(),
+            None => false,
         }
     }
 
@@ -214,3 +443,202 @@ impl AutonomousPermissionManager {
         &self.base
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::AgentObserver;
+    use serde_json::Value;
+
+    struct Silent;
+
+    impl AgentObserver for Silent {
+        fn emit(&self, _event: &str, _payload: Value) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    fn request(id: &str, session_id: &str) -> PermissionRequest {
+        PermissionRequest {
+            id: id.to_string(),
+            permission_type: PermissionType::ShellExecute,
+            message: "Run tests".to_string(),
+            metadata: HashMap::from([
+                ("session_id".to_string(), session_id.to_string()),
+                ("resource".to_string(), "cargo test".to_string()),
+            ]),
+            payload: None,
+        }
+    }
+
+    async fn answer(manager: Arc<PermissionManager>, req: PermissionRequest, response: PermissionResponse) -> bool {
+        let observer: Arc<dyn AgentObserver> = Arc::new(Silent);
+        let id = req.id.clone();
+        let pending = {
+            let manager = manager.clone();
+            tokio::spawn(async move { manager.request_permission(Some(&observer), req).await })
+        };
+        while !manager.get_pending_requests().contains(&id) {
+            tokio::task::yield_now().await;
+        }
+        assert!(manager.respond(&id, response));
+        assert!(!manager.respond(&id, PermissionResponse::Allow));
+        pending.await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_allow_always_covers_other_sessions() {
+        let manager = Arc::new(PermissionManager::new());
+        assert!(!answer(manager.clone(), request("r1", "s1"), PermissionResponse::Deny).await);
+        assert!(answer(manager.clone(), request("r2", "s1"), PermissionResponse::Allow).await);
+
+        // Allowed once in s1 only, so s2 still asks
+        assert!(answer(manager.clone(), request("r3", "s2"), PermissionResponse::AllowAlways).await);
+        assert!(manager.request_permission(None, request("r4", "s3")).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_policy_decides_before_asking() {
+        let manager = PermissionManager::new();
+        let policy = AgentPolicy::parse("rules: [{ commands: ['^cargo test$'], decision: allow }, { permission: network, decision: deny }]").unwrap();
+        let scoped = manager.with_policy(policy);
+        let mut command = request("r1", "s1");
+        command.payload = Some(PermissionPayload::Command {
+            command: "cargo test".to_string(),
+            working_dir: None,
+        });
+        assert!(scoped.request_permission(None, command).await.unwrap());
+        assert!(!scoped.request_permission(None, request("r3", "s1")).await.unwrap());
+
+        let mut network = request("r2", "s1");
+        network.permission_type = PermissionType::Network;
+        let observer: Arc<dyn AgentObserver> = Arc::new(Silent);
+        assert!(!scoped.request_permission(Some(&observer), network).await.unwrap());
+        assert!(manager.get_pending_requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_decisions_are_audited() {
+        use crate::models::permission_audit::{list_permission_audit, PermissionAuditFilter};
+
+        let pool = crate::database::create_test_pool();
+        let manager = Arc::new(PermissionManager::new().with_audit_log(pool.clone()));
+        let mut req = request("r1", "s1");
+        req.metadata.insert("tool".to_string(), "bash".to_string());
+        assert!(!answer(manager.clone(), req, PermissionResponse::Deny).await);
+
+        let id = "r2".to_string();
+        let pending = {
+            let manager = manager.clone();
+            tokio::spawn(async move {
+                let observer: Arc<dyn AgentObserver> = Arc::new(Silent);
+                manager.request_permission(Some(&observer), request("r2", "s1")).await
+            })
+        };
+        while !manager.get_pending_requests().contains(&id) {
+            tokio::task::yield_now().await;
+        }
+        assert!(manager.respond_as(&id, PermissionResponse::AllowAlways, "telegram:42"));
+        assert!(pending.await.unwrap().unwrap());
+        assert!(manager.request_permission(None, request("r3", "s2")).await.unwrap());
+        assert!(!PermissionManager::new().with_audit_log(pool.clone()).request_permission(None, request("r4", "s2")).await.unwrap());
+
+        let all = list_permission_audit(&pool, &PermissionAuditFilter::default()).unwrap();
+        let decided: HashMap<&str, (&str, &str)> = all
+            .iter()
+            .map(|e| (e.id.as_str(), (e.resolution.as_str(), e.decided_by.as_str())))
+            .collect();
+        assert_eq!(decided["r1"], ("deny", "app"));
+        assert_eq!(decided["r2"], ("always", "telegram:42"));
+        assert_eq!(decided["r3"], ("allow", "cache"));
+        assert_eq!(decided["r4"], ("deny", "no_approver"));
+
+        let r1 = all.iter().find(|e| e.id == "r1").unwrap();
+        assert_eq!(r1.tool.as_deref(), Some("bash"));
+        assert_eq!(r1.permission_type, "shell_execute");
+        assert_eq!(r1.resource.as_deref(), Some("cargo test"));
+
+        let filter = PermissionAuditFilter {
+            session_id: Some("s2".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(list_permission_audit(&pool, &filter).unwrap().len(), 2);
+        let filter = PermissionAuditFilter {
+            resolution: Some("maybe".to_string()),
+            ..Default::default()
+        };
+        assert!(list_permission_audit(&pool, &filter).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_grants_are_scoped_and_revocable() {
+        use crate::models::NewSession;
+        use crate::schema::{agents, sessions};
+        use diesel::prelude::*;
+
+        let pool = crate::database::create_test_pool();
+        crate::database::ensure_default_characters(&pool);
+        let agent_ids: Vec<String> = {
+            let mut conn = pool.get().unwrap();
+            agents::table.select(agents::id).limit(2).load(&mut conn).unwrap()
+        };
+        for (session_id, agent_id) in [("a1", &agent_ids[0]), ("a2", &agent_ids[0]), ("b1", &agent_ids[1])] {
+            let mut conn = pool.get().unwrap();
+            diesel::insert_into(sessions::table)
+                .values(&NewSession {
+                    id: session_id.to_string(),
+                    agent_id: agent_id.clone(),
+                    title: None,
+                    created_at: chrono::Utc::now().naive_utc(),
+                    updated_at: chrono::Utc::now().naive_utc(),
+                    archived: 0,
+                    pinned: 0,
+                })
+                .execute(&mut conn)
+                .unwrap();
+        }
+
+        let manager = Arc::new(PermissionManager::new().with_persistent_grants(pool.clone()));
+        let pending = {
+            let manager = manager.clone();
+            tokio::spawn(async move {
+                let observer: Arc<dyn AgentObserver> = Arc::new(Silent);
+                manager.request_permission(Some(&observer), request("r1", "a1")).await
+            })
+        };
+        while manager.get_pending_requests().is_empty() {
+            tokio::task::yield_now().await;
+        }
+        assert!(manager.grant_as("r1", GrantScope::Agent, None, "app"));
+        assert!(pending.await.unwrap().unwrap());
+
+        // Same agent, other session: granted, also by a fresh manager as
+        // after a restart. Other agent: still asks.
+        let restarted = PermissionManager::new().with_persistent_grants(pool.clone());
+        assert!(restarted.request_permission(None, request("r2", "a2")).await.unwrap());
+        assert!(!restarted.request_permission(None, request("r3", "b1")).await.unwrap());
+
+        let grants = permission_grant::list_grants(&pool, false).unwrap();
+        assert_eq!(grants.len(), 1);
+        assert_eq!(grants[0].scope, "agent");
+        assert_eq!(grants[0].scope_id.as_deref(), Some(agent_ids[0].as_str()));
+        permission_grant::revoke_grant(&pool, &grants[0].id).unwrap();
+        assert!(!restarted.request_permission(None, request("r4", "a2")).await.unwrap());
+        assert!(permission_grant::revoke_grant(&pool, &grants[0].id).is_err());
+
+        // Expired grants no longer apply
+        permission_grant::create_grant(&pool, NewPermissionGrant {
+            id: "old".to_string(),
+            scope: "global".to_string(),
+            scope_id: None,
+            permission_type: "shell_execute".to_string(),
+            resource: "cargo test".to_string(),
+            granted_by: "app".to_string(),
+            expires_at: Some(chrono::Utc::now().naive_utc() - chrono::Duration::minutes(1)),
+        })
+        .unwrap();
+        assert!(!restarted.request_permission(None, request("r5", "b1")).await.unwrap());
+        assert!(permission_grant::list_grants(&pool, false).unwrap().is_empty());
+        assert_eq!(permission_grant::purge_expired_grants(&pool).unwrap(), 1);
+    }
+}
@@ -0,0 +1,187 @@
+//! What a permission request would do, in a form approval UIs can render
+
+use crate::snapshots::changes::{is_binary, unified_diff, MAX_DIFFABLE_FILE_BYTES};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Cap on the diff shown before a file write
+pub const MAX_PREVIEW_DIFF_BYTES: usize = 8 * 1024;
+/// Cap on the email body shown before sending
+const MAX_PREVIEW_BODY_CHARS: usize = 2000;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PermissionPayload {
+    Command {
+        command: String,
+        working_dir: Option<String>,
+    },
+    FileRead {
+        path: String,
+    },
+    FileWrite {
+        path: String,
+        /// The file does not exist yet
+        created: bool,
+        /// Unified diff against the current content; None for binary or
+        /// oversized files
+        diff: Option<String>,
+        /// The diff was cut short at MAX_PREVIEW_DIFF_BYTES
+        truncated: bool,
+    },
+    FileDelete {
+        path: String,
+    },
+    CreateDir {
+        path: String,
+    },
+    Http {
+        method: String,
+        url: String,
+    },
+    Email {
+        to: Vec<String>,
+        subject: String,
+        body: String,
+    },
+    PageEdit {
+        /// Page title
+        page: String,
+        /// "create", "append" or "update_block"
+        operation: String,
+        markdown: String,
+    },
+}
+
+impl PermissionPayload {
+    /// Writing `content` to `path` (relative to `root`), with a diff against
+    /// what is there now
+    pub fn file_write(root: &Path, path: &str, content: &str) -> Self {
+        let target = root.join(path);
+        let current = match std::fs::metadata(&target) {
+            Ok(meta) if meta.len() <= MAX_DIFFABLE_FILE_BYTES => std::fs::read(&target).ok().map(Some),
+            Ok(_) => Some(None), // exists, too big to diff
+            Err(_) => None,
+        };
+        let (created, old) = match current {
+            None => (true, Some(String::new())),
+            Some(Some(bytes)) if !is_binary(&bytes) => (false, String::from_utf8(bytes).ok()),
+            Some(_) => (false, None),
+        };
+        let (diff, truncated) = match old {
+            Some(old) => {
+                let (diff, truncated) = unified_diff(path, &old, content, MAX_PREVIEW_DIFF_BYTES);
+                (Some(diff), truncated)
+            }
+            None => (None, false),
+        };
+        PermissionPayload::FileWrite {
+            path: path.to_string(),
+            created,
+            diff,
+            truncated,
+        }
+    }
+
+    pub fn email(to: Vec<String>, subject: &str, body: &str) -> Self {
+        PermissionPayload::Email {
+            to,
+            subject: subject.to_string(),
+            body: truncate(body, MAX_PREVIEW_BODY_CHARS),
+        }
+    }
+
+    /// Plain text for chat approvals, at most `max_len` bytes
+    pub fn preview(&self, max_len: usize) -> String {
+        let text = match self {
+            PermissionPayload::Command { command, working_dir } => match working_dir {
+                Some(dir) => format!("$ {}\n(in {})", command, dir),
+                None => format!("$ {}", command),
+            },
+            PermissionPayload::FileRead { path } => format!("Read {}", path),
+            PermissionPayload::FileWrite { path, created, diff, truncated } => {
+                let mut text = format!("{} {}", if *created { "Create" } else { "Overwrite" }, path);
+                if let Some(diff) = diff.as_deref().filter(|d| !d.is_empty()) {
+                    text.push('\n');
+                    text.push_str(diff.trim_end());
+                    if *truncated {
+                        text.push_str("\n…");
+                    }
+                }
+                text
+            }
+            PermissionPayload::FileDelete { path } => format!("Delete {}", path),
+            PermissionPayload::CreateDir { path } => format!("Create directory {}", path),
+            PermissionPayload::Http { method, url } => format!("{} {}", method, url),
+            PermissionPayload::Email { to, subject, body } => {
+                format!("To: {}\nSubject: {}\n\n{}", to.join(", "), subject, body)
+            }
+            PermissionPayload::PageEdit { page, operation, markdown } => {
+                let verb = match operation.as_str() {
+                    "create" => "Create page",
+                    "append" => "Append to page",
+                    _ => "Edit a block of page",
+                };
+                format!("{} '{}'\n\n{}", verb, page, markdown)
+            }
+        };
+        truncate(&text, max_len)
+    }
+}
+
+fn truncate(text: &str, max_len: usize) -> String {
+    if text.len() <= max_len {
+        return text.to_string();
+    }
+    let mut cut = max_len.saturating_sub('…'.len_utf8());
+    while !text.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    format!("{}…", &text[..cut])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_write_diff() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "one\ntwo\n").unwrap();
+
+        let payload = PermissionPayload::file_write(dir.path(), "a.txt", "one\nthree\n");
+        let PermissionPayload::FileWrite { created, diff, .. } = &payload else {
+            panic!("not a file write");
+        };
+        assert!(!created);
+        let diff = diff.as_deref().unwrap();
+        assert!(diff.contains("-two") && diff.contains("+three"));
+        assert!(payload.preview(1000).starts_with("Overwrite a.txt\n"));
+
+        let payload = PermissionPayload::file_write(dir.path(), "new.txt", "hello\n");
+        assert!(matches!(payload, PermissionPayload::FileWrite { created: true, .. }));
+        assert!(payload.preview(1000).contains("+hello"));
+    }
+
+    #[test]
+    fn test_preview_is_bounded() {
+        let payload = PermissionPayload::Command {
+            command: "é".repeat(100),
+            working_dir: None,
+        };
+        let preview = payload.preview(21);
+        assert!(preview.len() <= 21);
+        assert!(preview.ends_with('…'));
+    }
+
+    #[test]
+    fn test_serialized_with_kind_tag() {
+        let payload = PermissionPayload::Http {
+            method: "POST".to_string(),
+            url: "https://example.com".to_string(),
+        };
+        let value = serde_json::to_value(&payload).unwrap();
+        assert_eq!(value["kind"], "http");
+        assert_eq!(serde_json::from_value::<PermissionPayload>(value).unwrap(), payload);
+    }
+}
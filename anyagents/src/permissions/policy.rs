@@ -0,0 +1,371 @@
+//! Declarative per-agent permission policies
+//!
+//! A policy is a list of rules checked in order before the user is asked.
+//! Each rule matches on tool name, path globs, command patterns and network
+//! hosts; the first rule whose conditions all hold decides `allow`, `deny`
+//! or `ask`. Requests no rule matches get the policy's `default` (`ask`).
+//!
+//! ```yaml
+//! rules:
+//!   - tool: filesystem
+//!     paths: ["src/**", "docs/**"]
+//!     decision: allow
+//!   - commands: ["^rm\\s+-rf", "^sudo\\s"]
+//!     decision: deny
+//!     reason: Destructive commands are not allowed
+//!   - hosts: ["api.github.com", "*.example.com"]
+//!     decision: allow
+//! ```
+//!
+//! Policies live in the agent's execution settings under `policy`, as a
+//! JSON object or a YAML/JSON string. In `smart_approval` mode the
+//! `whitelisted_commands` and `blacklisted_commands` settings are turned into
+//! rules as well: blacklisted commands always ask, whitelisted ones are
+//! allowed.
+
+use super::{PermissionPayload, PermissionRequest, PermissionType};
+use globset::{Glob, GlobMatcher};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyDecision {
+    Allow,
+    Deny,
+    #[default]
+    Ask,
+}
+
+/// A rule as written by the user
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyRuleSpec {
+    /// Tool name, e.g. "bash"; "*" or unset for any tool
+    #[serde(default)]
+    pub tool: Option<String>,
+    #[serde(default)]
+    pub permission: Option<PermissionType>,
+    /// Globs on the workspace-relative path
+    #[serde(default)]
+    pub paths: Vec<String>,
+    /// Regexes on the shell command
+    #[serde(default)]
+    pub commands: Vec<String>,
+    /// Host names; "*.example.com" also matches subdomains
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    pub decision: PolicyDecision,
+    /// Shown when the rule denies
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicySpec {
+    #[serde(default)]
+    pub rules: Vec<PolicyRuleSpec>,
+    #[serde(default)]
+    pub default: PolicyDecision,
+}
+
+#[derive(Debug, Clone)]
+struct PolicyRule {
+    tool: Option<String>,
+    permission: Option<PermissionType>,
+    paths: Vec<GlobMatcher>,
+    commands: Vec<Regex>,
+    hosts: Vec<String>,
+    decision: PolicyDecision,
+    reason: Option<String>,
+}
+
+/// What a request is about, pulled from its payload or metadata
+struct Subject<'a> {
+    tool: Option<&'a str>,
+    path: Option<&'a str>,
+    command: Option<&'a str>,
+    host: Option<String>,
+}
+
+impl<'a> Subject<'a> {
+    fn of(req: &'a PermissionRequest) -> Self {
+        let meta = |key: &str| req.metadata.get(key).map(String::as_str);
+        let (path, command, url) = match &req.payload {
+            Some(PermissionPayload::Command { command, .. }) => (None, Some(command.as_str()), None),
+            Some(PermissionPayload::FileRead { path })
+            | Some(PermissionPayload::FileWrite { path, .. })
+            | Some(PermissionPayload::FileDelete { path })
+            | Some(PermissionPayload::CreateDir { path }) => (Some(path.as_str()), None, None),
+            Some(PermissionPayload::Http { url, .. }) => (None, None, Some(url.as_str())),
+            _ => (meta("path"), meta("command"), meta("url")),
+        };
+        Self {
+            tool: meta("tool"),
+            path,
+            command,
+            host: url
+                .and_then(|u| reqwest::Url::parse(u).ok())
+                .and_then(|u| u.host_str().map(str::to_lowercase)),
+        }
+    }
+}
+
+impl PolicyRule {
+    fn compile(spec: PolicyRuleSpec) -> Result<Self, String> {
+        let paths = spec
+            .paths
+            .iter()
+            .map(|p| {
+                Glob::new(p)
+                    .map(|g| g.compile_matcher())
+                    .map_err(|e| format!("Invalid path glob '{}': {}", p, e))
+            })
+            .collect::<Result<_, _>>()?;
+        let commands = spec
+            .commands
+            .iter()
+            .map(|c| Regex::new(c).map_err(|e| format!("Invalid command pattern '{}': {}", c, e)))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            tool: spec.tool.filter(|t| t != "*"),
+            permission: spec.permission,
+            paths,
+            commands,
+            hosts: spec.hosts.iter().map(|h| h.trim().to_lowercase()).collect(),
+            decision: spec.decision,
+            reason: spec.reason,
+        })
+    }
+
+    /// Every condition the rule sets must hold; a condition on something the
+    /// request does not have (e.g. paths for a shell command) fails
+    fn matches(&self, req: &PermissionRequest, subject: &Subject) -> bool {
+        if self.tool.as_deref().is_some_and(|t| subject.tool != Some(t)) {
+            return false;
+        }
+        if self.permission.as_ref().is_some_and(|p| *p != req.permission_type) {
+            return false;
+        }
+        if !self.paths.is_empty()
+            && !subject
+                .path
+                .is_some_and(|path| self.paths.iter().any(|g| g.is_match(path.trim_start_matches("./"))))
+        {
+            return false;
+        }
+        if !self.commands.is_empty()
+            && !subject
+                .command
+                .is_some_and(|command| self.commands.iter().any(|r| r.is_match(command.trim())))
+        {
+            return false;
+        }
+        if !self.hosts.is_empty()
+            && !subject
+                .host
+                .as_deref()
+                .is_some_and(|host| self.hosts.iter().any(|h| host_matches(h, host)))
+        {
+            return false;
+        }
+        true
+    }
+}
+
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host == domain || host.ends_with(&format!(".{}", domain)),
+        None => pattern == host,
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AgentPolicy {
+    rules: Vec<PolicyRule>,
+    default: PolicyDecision,
+}
+
+impl AgentPolicy {
+    pub fn from_spec(spec: PolicySpec) -> Result<Self, String> {
+        Ok(Self {
+            rules: spec
+                .rules
+                .into_iter()
+                .map(PolicyRule::compile)
+                .collect::<Result<_, _>>()?,
+            default: spec.default,
+        })
+    }
+
+    /// A policy written as YAML or JSON
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let spec: PolicySpec = serde_yaml::from_str(text).map_err(|e| format!("Invalid policy: {}", e))?;
+        Self::from_spec(spec)
+    }
+
+    /// The policy in an agent's execution settings; None when it has none
+    /// and is not in smart_approval mode
+    pub fn from_execution_settings(settings: &Value) -> Result<Option<Self>, String> {
+        let mut spec = match settings.get("policy") {
+            None | Some(Value::Null) => None,
+            Some(Value::String(text)) => {
+                Some(serde_yaml::from_str::<PolicySpec>(text).map_err(|e| format!("Invalid policy: {}", e))?)
+            }
+            Some(value) => Some(
+                serde_json::from_value::<PolicySpec>(value.clone()).map_err(|e| format!("Invalid policy: {}", e))?,
+            ),
+        };
+
+        if settings.get("mode").and_then(|m| m.as_str()) == Some("smart_approval") {
+            let spec = spec.get_or_insert_with(PolicySpec::default);
+            let commands = |key: &str| -> Vec<String> {
+                settings
+                    .get(key)
+                    .and_then(|v| v.as_array())
+                    .map(|arr| {
+                        arr.iter()
+                            .filter_map(|c| c.as_str().map(|s| s.trim().to_string()))
+                            .filter(|s| !s.is_empty())
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            };
+            // Blacklisted first: they ask even when a policy rule would allow
+            let mut rules = Vec::new();
+            let blacklisted = commands("blacklisted_commands");
+            if !blacklisted.is_empty() {
+                rules.push(PolicyRuleSpec {
+                    commands: blacklisted,
+                    decision: PolicyDecision::Ask,
+                    ..Default::default()
+                });
+            }
+            let whitelisted = commands("whitelisted_commands");
+            if !whitelisted.is_empty() {
+                rules.push(PolicyRuleSpec {
+                    commands: whitelisted,
+                    decision: PolicyDecision::Allow,
+                    ..Default::default()
+                });
+            }
+            rules.append(&mut spec.rules);
+            spec.rules = rules;
+        }
+
+        spec.map(Self::from_spec).transpose()
+    }
+
+    /// The first matching rule's decision and reason, else the default
+    pub fn evaluate(&self, req: &PermissionRequest) -> (PolicyDecision, Option<String>) {
+        let subject = Subject::of(req);
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(req, &subject))
+            .map(|rule| (rule.decision, rule.reason.clone()))
+            .unwrap_or((self.default, None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn request(tool: &str, permission_type: PermissionType, payload: PermissionPayload) -> PermissionRequest {
+        PermissionRequest {
+            id: "r1".to_string(),
+            permission_type,
+            message: String::new(),
+            metadata: HashMap::from([("tool".to_string(), tool.to_string())]),
+            payload: Some(payload),
+        }
+    }
+
+    fn command(command: &str) -> PermissionRequest {
+        request(
+            "bash",
+            PermissionType::ShellExecute,
+            PermissionPayload::Command {
+                command: command.to_string(),
+                working_dir: None,
+            },
+        )
+    }
+
+    fn write(path: &str) -> PermissionRequest {
+        request(
+            "filesystem",
+            PermissionType::FilesystemWrite,
+            PermissionPayload::FileWrite {
+                path: path.to_string(),
+                created: true,
+                diff: None,
+                truncated: false,
+            },
+        )
+    }
+
+    fn get(url: &str) -> PermissionRequest {
+        request(
+            "http",
+            PermissionType::Network,
+            PermissionPayload::Http {
+                method: "GET".to_string(),
+                url: url.to_string(),
+            },
+        )
+    }
+
+    #[test]
+    fn test_first_matching_rule_decides() {
+        let policy = AgentPolicy::parse(
+            r#"
+rules:
+  - tool: filesystem
+    paths: ["src/**"]
+    decision: allow
+  - commands: ["^rm\\s+-rf"]
+    decision: deny
+    reason: no
+  - hosts: ["*.example.com"]
+    decision: allow
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(policy.evaluate(&write("src/main.rs")).0, PolicyDecision::Allow);
+        assert_eq!(policy.evaluate(&write("secrets/key")).0, PolicyDecision::Ask);
+        assert_eq!(policy.evaluate(&command("rm -rf /")), (PolicyDecision::Deny, Some("no".to_string())));
+        assert_eq!(policy.evaluate(&command("ls")).0, PolicyDecision::Ask);
+        assert_eq!(policy.evaluate(&get("https://api.example.com/x")).0, PolicyDecision::Allow);
+        assert_eq!(policy.evaluate(&get("https://example.com.evil.io/")).0, PolicyDecision::Ask);
+    }
+
+    #[test]
+    fn test_smart_approval_uses_command_lists() {
+        assert!(AgentPolicy::from_execution_settings(&json!({"mode": "require_approval"})).unwrap().is_none());
+
+        let policy = AgentPolicy::from_execution_settings(&json!({
+            "mode": "smart_approval",
+            "whitelisted_commands": ["^cargo ", "^git status"],
+            "blacklisted_commands": ["cargo publish"],
+            "policy": { "rules": [{ "tool": "bash", "decision": "deny" }] }
+        }))
+        .unwrap()
+        .unwrap();
+        assert_eq!(policy.evaluate(&command("cargo test")).0, PolicyDecision::Allow);
+        assert_eq!(policy.evaluate(&command("cargo publish")).0, PolicyDecision::Ask);
+        assert_eq!(policy.evaluate(&command("make")).0, PolicyDecision::Deny);
+    }
+
+    #[test]
+    fn test_invalid_policies_are_rejected() {
+        assert!(AgentPolicy::parse("rules: [{ decision: maybe }]").is_err());
+        assert!(AgentPolicy::parse("rules: [{ commands: ['('], decision: deny }]").is_err());
+        assert!(AgentPolicy::from_execution_settings(&json!({ "policy": "rules: [{ pathz: [], decision: allow }]" })).is_err());
+    }
+}
@@ -0,0 +1,446 @@
+//! Read-only enforcement for analysis-only agents
+//!
+//! When an agent is marked `read_only` in its execution settings, the runtime
+//! blocks every tool call that may have side effects: filesystem writes, shell
+//! commands that are not recognised as read-only, mail sending and network
+//! requests that upload data.
+
+use crate::tools::Tool;
+use serde_json::Value;
+
+/// Commands that never modify the filesystem or external systems on their own.
+/// Flags that make some of them write or run other programs (e.g. `find -delete`,
+/// `rg --pre`) are checked separately. Interpreters of their own little languages
+/// (`sed`, `awk`), network clients and `env` are left out on purpose: their
+/// arguments can write files or run commands in too many ways to vet.
+const READ_ONLY_COMMANDS: &[&str] = &[
+    "ls", "cat", "head", "tail", "less", "more", "wc", "grep", "egrep", "fgrep", "rg", "ag",
+    "find", "fd", "tree", "pwd", "echo", "printf", "which", "whereis", "type", "file", "stat",
+    "du", "df", "sort", "uniq", "cut", "tr", "diff", "cmp", "comm", "jq", "date", "whoami", "id",
+    "uname", "printenv", "basename", "dirname", "realpath", "readlink", "md5sum", "sha1sum",
+    "sha256sum", "nl", "column", "true", "false", "test", "ps", "git",
+];
+
+/// Git subcommands that only inspect the repository
+const READ_ONLY_GIT_SUBCOMMANDS: &[&str] = &["status", "log", "diff", "show"];
+
+/// Shell constructs that run a nested command: process and command substitution
+const NESTED_COMMAND_MARKERS: &[&str] = &["$(", "`", "<(", ">("];
+
+/// xargs options that take no argument. Any other option may swallow the next
+/// token, which would then be mistaken for the program xargs runs.
+const XARGS_FLAGS: &[&str] = &["-0", "-r", "-t", "-x", "--null", "--no-run-if-empty", "--verbose", "--exit"];
+
+/// Policy applied to tool calls of agents running in read-only mode
+#[derive(Debug, Clone, Default)]
+pub struct ReadOnlyPolicy {
+    /// Extra command prefixes the user explicitly allowed (e.g. "cargo check")
+    allowed_commands: Vec<String>,
+}
+
+impl ReadOnlyPolicy {
+    pub fn new(allowed_commands: Vec<String>) -> Self {
+        Self { allowed_commands }
+    }
+
+    /// Build the policy from an agent's `execution_settings` JSON.
+    /// Returns `None` when the agent is not in read-only mode.
+    pub fn from_execution_settings(settings: &Value) -> Option<Self> {
+        if !settings
+            .get("read_only")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+        {
+            return None;
+        }
+
+        let allowed_commands = settings
+            .get("read_only_allowed_commands")
+            .and_then(|v| v.as_array())
+            .map(|arr| {
+                arr.iter()
+                    .filter_map(|c| c.as_str().map(|s| s.trim().to_string()))
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(Self::new(allowed_commands))
+    }
+
+    /// Check whether a tool call is permitted in read-only mode
+    pub fn check(&self, tool: &dyn Tool, args: &Value) -> Result<(), String> {
+        if tool.is_read_only(args) {
+            return Ok(());
+        }
+
+        if let Some(command) = args.get("command").and_then(|c| c.as_str()) {
+            if self.is_allowed_command(command) {
+                return Ok(());
+            }
+        }
+
+        Err(format!(
+            "Read-only mode: tool '{}' may modify files or external systems and was blocked. Only inspect and report.",
+            tool.name()
+        ))
+    }
+
+    /// The user vouched for the prefix, whatever arguments follow it: only make
+    /// sure nothing else gets chained, substituted or redirected into a file
+    fn is_allowed_command(&self, command: &str) -> bool {
+        let command = command.trim();
+        let prefixed = self.allowed_commands.iter().any(|prefix| {
+            command == prefix
+                || (command.starts_with(prefix.as_str()) && command[prefix.len()..].starts_with(' '))
+        });
+        prefixed && is_single_command(command)
+    }
+}
+
+/// Heuristically decide whether a shell command line is free of side effects.
+///
+/// This is intentionally conservative: anything it does not understand
+/// (command or process substitution, unknown binaries, output redirection,
+/// environment assignments) is treated as mutating.
+pub fn is_read_only_command(command: &str) -> bool {
+    // Even quoted, these are not worth telling apart from the real thing
+    if NESTED_COMMAND_MARKERS.iter().any(|m| command.contains(m)) {
+        return false;
+    }
+
+    let segments = match split_segments(command) {
+        Some(s) => s,
+        None => return false,
+    };
+
+    segments.iter().all(|tokens| is_read_only_segment(tokens))
+}
+
+/// A single command without chaining, substitution or redirects to a file
+fn is_single_command(command: &str) -> bool {
+    !NESTED_COMMAND_MARKERS.iter().any(|m| command.contains(m))
+        && split_segments(command).is_some_and(|segments| segments.len() == 1)
+}
+
+fn is_read_only_segment(tokens: &[String]) -> bool {
+    let mut rest = tokens.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+
+    // Environment assignments (FOO=bar cmd) can point pagers, preloads and
+    // config lookups at anything
+    if rest[0].contains('=') && !rest[0].starts_with('-') {
+        return false;
+    }
+
+    // xargs runs the following command for each input line
+    if rest[0] == "xargs" {
+        let options = rest[1..].iter().take_while(|t| t.starts_with('-'));
+        if options.clone().any(|t| !XARGS_FLAGS.contains(t)) {
+            return false;
+        }
+        rest = rest[1 + options.count()..].to_vec();
+        if rest.is_empty() {
+            // xargs defaults to echo
+            return true;
+        }
+    }
+
+    let program = rest[0].rsplit('/').next().unwrap_or(rest[0]);
+    if !READ_ONLY_COMMANDS.contains(&program) {
+        return false;
+    }
+
+    let args = &rest[1..];
+    match program {
+        "find" => !args.iter().any(|a| {
+            matches!(
+                *a,
+                "-delete" | "-exec" | "-execdir" | "-ok" | "-okdir" | "-fprint" | "-fprint0" | "-fprintf" | "-fls"
+            )
+        }),
+        "fd" => !args
+            .iter()
+            .any(|a| matches!(*a, "-x" | "-X" | "--exec" | "--exec-batch") || a.starts_with("--exec")),
+        "rg" => !args.iter().any(|a| *a == "--pre" || a.starts_with("--pre=")),
+        "sort" => !args
+            .iter()
+            .any(|a| a.starts_with("-o") || a.starts_with("--output") || a.starts_with("--compress-program")),
+        "tree" => !args.contains(&"-o"),
+        // `uniq in out` writes its output to `out`
+        "uniq" => args.iter().filter(|a| !a.starts_with('-')).count() <= 1,
+        // `file -C` compiles a magic file next to it
+        "file" => !args.iter().any(|a| *a == "-C" || *a == "--compile"),
+        "date" => !args.iter().any(|a| *a == "-s" || a.starts_with("--set")),
+        "git" => is_read_only_git(args),
+        _ => true,
+    }
+}
+
+fn is_read_only_git(args: &[&str]) -> bool {
+    // Only `-C path` and `--no-pager` come before the subcommand: `-c` and the
+    // other global options can make git run arbitrary programs
+    let mut i = 0;
+    while let Some(arg) = args.get(i) {
+        match *arg {
+            "-C" => i += 2,
+            "--no-pager" => i += 1,
+            _ => break,
+        }
+    }
+    let Some(sub) = args.get(i) else {
+        return false;
+    };
+    if !READ_ONLY_GIT_SUBCOMMANDS.contains(sub) {
+        return false;
+    }
+
+    // `--output` writes the diff to a file, external diff drivers run programs
+    !args[i + 1..]
+        .iter()
+        .any(|a| a.starts_with("--output") || *a == "--ext-diff" || *a == "--textconv")
+}
+
+/// Split a command line into pipeline/list segments of quote-aware tokens.
+/// Returns `None` if the command contains constructs we refuse to analyse
+/// (command or process substitution, output redirection to a file, unterminated quotes).
+fn split_segments(command: &str) -> Option<Vec<Vec<String>>> {
+    let chars: Vec<char> = command.chars().collect();
+    let mut segments: Vec<Vec<String>> = vec![];
+    let mut tokens: Vec<String> = vec![];
+    let mut current = String::new();
+    let mut in_single = false;
+    let mut in_double = false;
+    let mut i = 0;
+
+    let flush_token = |current: &mut String, tokens: &mut Vec<String>| {
+        if !current.is_empty() {
+            tokens.push(std::mem::take(current));
+        }
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if in_single {
+            if c == '\'' {
+                in_single = false;
+            } else {
+                current.push(c);
+            }
+            i += 1;
+            continue;
+        }
+
+        if in_double {
+            match c {
+                '"' => in_double = false,
+                '`' => return None,
+                '$' if chars.get(i + 1) == Some(&'(') => return None,
+                '\\' => {
+                    if let Some(next) = chars.get(i + 1) {
+                        current.push(*next);
+                        i += 1;
+                    }
+                }
+                _ => current.push(c),
+            }
+            i += 1;
+            continue;
+        }
+
+        match c {
+            '\'' => in_single = true,
+            '"' => in_double = true,
+            '`' => return None,
+            '$' if chars.get(i + 1) == Some(&'(') => return None,
+            '\\' => {
+                if let Some(next) = chars.get(i + 1) {
+                    current.push(*next);
+                    i += 1;
+                }
+            }
+            ' ' | '\t' => flush_token(&mut current, &mut tokens),
+            '|' | ';' | '\n' | '&' => {
+                flush_token(&mut current, &mut tokens);
+                if !tokens.is_empty() {
+                    segments.push(std::mem::take(&mut tokens));
+                }
+                // Treat `||` and `&&` as a single separator
+                if chars.get(i + 1) == Some(&c) {
+                    i += 1;
+                }
+            }
+            '<' | '>' if chars.get(i + 1) == Some(&'(') => return None,
+            '>' => {
+                // A file descriptor number directly before '>' belongs to the redirect
+                if current.chars().all(|ch| ch.is_ascii_digit()) {
+                    current.clear();
+                } else {
+                    flush_token(&mut current, &mut tokens);
+                }
+
+                if chars.get(i + 1) == Some(&'>') {
+                    return None; // append redirect always writes
+                }
+
+                // Duplicating descriptors (2>&1, >&2) is harmless
+                if chars.get(i + 1) == Some(&'&') {
+                    i += 2;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                    continue;
+                }
+
+                // Read the redirect target
+                let mut j = i + 1;
+                while j < chars.len() && chars[j] == ' ' {
+                    j += 1;
+                }
+                let mut target = String::new();
+                while j < chars.len() && !chars[j].is_whitespace() && !"|;&".contains(chars[j]) {
+                    target.push(chars[j]);
+                    j += 1;
+                }
+                if target != "/dev/null" {
+                    return None;
+                }
+                i = j;
+                continue;
+            }
+            _ => current.push(c),
+        }
+        i += 1;
+    }
+
+    if in_single || in_double {
+        return None;
+    }
+
+    flush_token(&mut current, &mut tokens);
+    if !tokens.is_empty() {
+        segments.push(tokens);
+    }
+
+    Some(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::{bash::BashTool, filesystem::FilesystemTool};
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_read_only_commands_allowed() {
+        assert!(is_read_only_command("ls -la"));
+        assert!(is_read_only_command("cat src/main.rs | grep fn | wc -l"));
+        assert!(is_read_only_command("git status && git log --oneline -5"));
+        assert!(is_read_only_command("grep -rn \"a|b\" src 2>/dev/null"));
+        assert!(is_read_only_command("find . -name '*.rs' 2>&1"));
+        assert!(is_read_only_command("git --no-pager diff HEAD~1 -- src"));
+    }
+
+    #[test]
+    fn test_mutating_commands_blocked() {
+        assert!(!is_read_only_command("rm -rf build"));
+        assert!(!is_read_only_command("echo hi > out.txt"));
+        assert!(!is_read_only_command("echo hi >> out.txt"));
+        assert!(!is_read_only_command("sed -i 's/a/b/' file.txt"));
+        assert!(!is_read_only_command("find . -name '*.tmp' -delete"));
+        assert!(!is_read_only_command("git commit -m 'x'"));
+        assert!(!is_read_only_command("git branch new-feature"));
+        assert!(!is_read_only_command("curl -X POST https://api.example.com"));
+        assert!(!is_read_only_command("curl -d 'a=1' https://api.example.com"));
+        assert!(!is_read_only_command("ls $(rm -rf /)"));
+        assert!(!is_read_only_command("ls; touch file"));
+        assert!(!is_read_only_command("wget https://example.com/file.zip"));
+    }
+
+    #[test]
+    fn test_known_bypasses_blocked() {
+        assert!(!is_read_only_command("env rm -rf x"));
+        assert!(!is_read_only_command("GIT_PAGER='rm -rf x' git log"));
+        assert!(!is_read_only_command("git push"));
+        assert!(!is_read_only_command("git checkout -- ."));
+        assert!(!is_read_only_command("git -c core.pager='rm x' log"));
+        assert!(!is_read_only_command("git diff --output=patch.txt"));
+        assert!(!is_read_only_command("cat <(rm -rf ~)"));
+        assert!(!is_read_only_command("diff >(rm x) a"));
+        assert!(!is_read_only_command("echo '`rm x`'"));
+        assert!(!is_read_only_command("echo '$(rm x)'"));
+        assert!(!is_read_only_command("sed 'w out' file.txt"));
+        assert!(!is_read_only_command("sed 'e rm x' file.txt"));
+        assert!(!is_read_only_command("awk '{print>\"f\"}' file.txt"));
+        assert!(!is_read_only_command("curl -ofile https://example.com"));
+        assert!(!is_read_only_command("curl -F@file https://example.com"));
+        assert!(!is_read_only_command("curl -d@file https://example.com"));
+        assert!(!is_read_only_command("cd .. && ls"));
+        assert!(!is_read_only_command("rg --pre rm x"));
+        assert!(!is_read_only_command("fd -x rm"));
+        assert!(!is_read_only_command("sort --compress-program=rm a"));
+        assert!(!is_read_only_command("uniq input.txt output.txt"));
+        assert!(!is_read_only_command("xargs -a list rm"));
+        assert!(!is_read_only_command("xargs -E echo rm"));
+        assert!(!is_read_only_command("xargs -n1 rm"));
+    }
+
+    #[test]
+    fn test_xargs_flags() {
+        assert!(is_read_only_command("find . -name '*.rs' | xargs -0 grep fn"));
+        assert!(is_read_only_command("ls | xargs"));
+        assert!(!is_read_only_command("ls | xargs -0 rm"));
+    }
+
+    #[test]
+    fn test_policy_checks_tools() {
+        let policy = ReadOnlyPolicy::default();
+        let fs_tool = FilesystemTool::new(PathBuf::from("."));
+        assert!(policy
+            .check(&fs_tool, &json!({"operation": "read_file", "path": "a.txt"}))
+            .is_ok());
+        assert!(policy
+            .check(&fs_tool, &json!({"operation": "write_file", "path": "a.txt"}))
+            .is_err());
+
+        let bash = BashTool::new(PathBuf::from("."), "direct".to_string());
+        assert!(policy.check(&bash, &json!({"command": "ls"})).is_ok());
+        assert!(policy.check(&bash, &json!({"command": "cargo build"})).is_err());
+    }
+
+    #[test]
+    fn test_policy_allowed_commands() {
+        let policy = ReadOnlyPolicy::new(vec!["cargo check".to_string()]);
+        let bash = BashTool::new(PathBuf::from("."), "direct".to_string());
+        assert!(policy.check(&bash, &json!({"command": "cargo check"})).is_ok());
+        assert!(policy.check(&bash, &json!({"command": "cargo build"})).is_err());
+        assert!(policy.check(&bash, &json!({"command": "cargo checkout"})).is_err());
+    }
+
+    #[test]
+    fn test_policy_allowed_command_suffix() {
+        let policy = ReadOnlyPolicy::new(vec!["cargo check".to_string()]);
+        let bash = BashTool::new(PathBuf::from("."), "direct".to_string());
+        let allowed = |command: &str| policy.check(&bash, &json!({ "command": command })).is_ok();
+        assert!(allowed("cargo check --all-targets"));
+        assert!(allowed("cargo check -p anyagents 2>&1"));
+        assert!(!allowed("cargo check; rm -rf x"));
+        assert!(!allowed("cargo check && cargo publish"));
+        assert!(!allowed("cargo check | tee log.txt"));
+        assert!(!allowed("cargo check > log.txt"));
+        assert!(!allowed("cargo check $(rm x)"));
+        assert!(!allowed("cargo check `rm x`"));
+    }
+
+    #[test]
+    fn test_from_execution_settings() {
+        assert!(ReadOnlyPolicy::from_execution_settings(&json!({"mode": "autopilot"})).is_none());
+        let policy = ReadOnlyPolicy::from_execution_settings(&json!({
+            "read_only": true,
+            "read_only_allowed_commands": ["cargo check"]
+        }))
+        .unwrap();
+        assert_eq!(policy.allowed_commands, vec!["cargo check".to_string()]);
+    }
+}
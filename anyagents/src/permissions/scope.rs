@@ -1,7 +1,7 @@
 //! Scope enforcement module for restricting agent operations to specific workspaces
 
 use serde::{Deserialize, Serialize};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
 /// Scope type for an agent
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -76,36 +76,31 @@ impl ScopeEnforcer {
     pub fn is_path_allowed(&self, path: &Path) -> bool {
         match self.scope_type {
             ScopeType::Global => true,
-            ScopeType::Workspace => {
-                if let Some(ref workspace) = self.workspace_path {
-                    // Canonicalize paths for comparison
-                    let canonical_workspace = match workspace.canonicalize() {
-                        Ok(p) => p,
-                        Err(_) => return false, // Workspace doesn't exist
-                    };
-
-                    let canonical_path = match path.canonicalize() {
-                        Ok(p) => p,
-                        Err(_) => {
-                            // Path doesn't exist yet, check if parent is within workspace
-                            if let Some(parent) = path.parent() {
-                                if let Ok(canonical_parent) = parent.canonicalize() {
-                                    return canonical_parent.starts_with(&canonical_workspace);
-                                }
-                            }
-                            // For new paths, check if the non-canonicalized path starts with workspace
-                            return path.starts_with(workspace);
-                        }
-                    };
+            ScopeType::Workspace => self.check_path(path).is_ok(),
+        }
+    }
 
-                    canonical_path.starts_with(&canonical_workspace)
-                } else {
-                    false // No workspace set, deny all
-                }
+    /// `path` with symlinks resolved, or an error when workspace scope
+    /// forbids it. Paths that don't exist yet are resolved through their
+    /// nearest existing ancestor.
+    pub fn check_path(&self, path: &Path) -> Result<PathBuf, String> {
+        match self.scope_type {
+            ScopeType::Global => canonicalize_lenient(path),
+            ScopeType::Workspace => {
+                let workspace = self
+                    .workspace_path
+                    .as_ref()
+                    .ok_or("No workspace path set for workspace scope")?;
+                resolve_within(workspace, path)
             }
         }
     }
 
+    /// A tool's path argument, relative to `base`, checked against the scope
+    pub fn resolve(&self, base: &Path, path: &str) -> Result<PathBuf, String> {
+        self.check_path(&base.join(path))
+    }
+
     /// Validate a shell command for potential path escapes
     /// Returns Ok(()) if command is safe, Err with reason if not
     pub fn validate_command(&self, command: &str) -> Result<(), String> {
@@ -172,6 +167,46 @@ impl ScopeEnforcer {
     }
 }
 
+/// `path` (relative to `root` unless absolute) with symlinks resolved, as
+/// long as it stays inside `root`
+pub fn resolve_within(root: &Path, path: &Path) -> Result<PathBuf, String> {
+    let root = root
+        .canonicalize()
+        .map_err(|e| format!("Workspace '{}' is not accessible: {}", root.display(), e))?;
+    let resolved = canonicalize_lenient(&root.join(path))?;
+    if resolved.starts_with(&root) {
+        Ok(resolved)
+    } else {
+        Err(format!("Path '{}' is outside the workspace", path.display()))
+    }
+}
+
+/// Canonicalize the longest existing prefix and append the rest, which may
+/// not contain `..` or dangling symlinks (writing through one would land
+/// wherever it points)
+fn canonicalize_lenient(path: &Path) -> Result<PathBuf, String> {
+    let mut existing = path.to_path_buf();
+    let mut missing = Vec::new();
+    loop {
+        match existing.canonicalize() {
+            Ok(mut resolved) => {
+                resolved.extend(missing.iter().rev());
+                return Ok(resolved);
+            }
+            Err(_) => {
+                if existing.symlink_metadata().is_ok() {
+                    return Err(format!("'{}' is a broken symlink", existing.display()));
+                }
+                match existing.components().next_back() {
+                    Some(Component::Normal(name)) => missing.push(name.to_os_string()),
+                    _ => return Err(format!("Cannot resolve path '{}'", path.display())),
+                }
+                existing.pop();
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -200,6 +235,25 @@ mod tests {
         assert!(!enforcer.is_path_allowed(Path::new("/tmp/outside")));
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_escapes_are_rejected() {
+        let workspace = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        std::os::unix::fs::symlink(outside.path(), workspace.path().join("link")).unwrap();
+        std::os::unix::fs::symlink(outside.path().join("gone"), workspace.path().join("dangling")).unwrap();
+        std::fs::create_dir(workspace.path().join("src")).unwrap();
+        let enforcer = ScopeEnforcer::workspace(workspace.path().to_path_buf());
+
+        let inside = enforcer.resolve(workspace.path(), "src/new/file.rs").unwrap();
+        assert!(inside.ends_with("src/new/file.rs"));
+        assert!(enforcer.resolve(workspace.path(), "link/secret").is_err());
+        assert!(enforcer.resolve(workspace.path(), "dangling").is_err());
+        assert!(enforcer.resolve(workspace.path(), "src/../../x").is_err());
+        assert!(enforcer.resolve(workspace.path(), "missing/../x").is_err());
+        assert!(resolve_within(workspace.path(), Path::new("link")).is_err());
+    }
+
     #[test]
     fn test_validate_command_global() {
         let enforcer = ScopeEnforcer::global();
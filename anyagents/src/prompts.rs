@@ -0,0 +1,311 @@
+//! Prompt templates and their overrides
+//!
+//! The runtime renders its system prompts from minijinja templates
+//! (`anyagents/prompts/*.j2`, compiled in). Each can be overridden without a
+//! rebuild; the first of these wins:
+//!
+//! 1. the newest saved version for the agent
+//! 2. the newest saved version for every agent
+//! 3. `<name>.j2` in `ANYCOWORK_PROMPTS_DIR`, for editing templates on disk
+//! 4. the built-in template
+//!
+//! Overrides are looked up on every render, so a saved version applies from
+//! the next job on. Saving never edits a version: it appends one, and
+//! restoring an old version saves it again as the newest. An override that
+//! fails to render is logged and the built-in template is used instead, so a
+//! bad edit can't leave an agent without a prompt.
+
+pub mod builder;
+pub use builder::{PromptBuilder, WorkspaceFacts};
+
+use crate::database::DbPool;
+use crate::models::prompt_template::{self, PromptTemplateVersion};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// Directory of `<name>.j2` files that replace the built-in templates
+pub const PROMPTS_DIR_ENV: &str = "ANYCOWORK_PROMPTS_DIR";
+
+pub const TOOL_USE_SYSTEM: &str = "tool_use_system";
+pub const PLANNING_AGENT: &str = "planning_agent";
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TemplateVariable {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// JSON value previews use when none is given
+    pub example: &'static str,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct BuiltinTemplate {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub variables: &'static [TemplateVariable],
+    pub body: &'static str,
+}
+
+pub const BUILTIN_TEMPLATES: &[BuiltinTemplate] = &[
+    BuiltinTemplate {
+        name: TOOL_USE_SYSTEM,
+        description: "System prompt of every agent run, after the agent's own system prompt",
+        variables: &[
+            TemplateVariable {
+                name: "tools_desc",
+                description: "JSON array of the available tools: name, description and parameters schema",
+                example: r#""[{\"name\": \"filesystem\", \"description\": \"Read and write files\", \"parameters\": {}}]""#,
+            },
+            TemplateVariable {
+                name: "read_only",
+                description: "Whether the agent runs under a read-only policy",
+                example: "false",
+            },
+            TemplateVariable {
+                name: "active_skills",
+                description: "Skills triggered by the message, each with a name and body",
+                example: r#"[{"name": "release-notes", "body": "Group changes by area."}]"#,
+            },
+            TemplateVariable {
+                name: "plan",
+                description: "The plan being executed, if any",
+                example: "null",
+            },
+            TemplateVariable {
+                name: "current_task",
+                description: "The plan task being worked on, if any",
+                example: "null",
+            },
+        ],
+        body: include_str!("../prompts/tool_use_system.j2"),
+    },
+    BuiltinTemplate {
+        name: PLANNING_AGENT,
+        description: "System prompt of the planner that splits complex requests into tasks",
+        variables: &[
+            TemplateVariable {
+                name: "schema",
+                description: "JSON schema the plan must follow",
+                example: r#""{\"type\": \"object\"}""#,
+            },
+            TemplateVariable {
+                name: "team",
+                description: "Agents tasks may be assigned to, each with id, name, description and expertise",
+                example: "[]",
+            },
+            TemplateVariable {
+                name: "context",
+                description: "Recent conversation history",
+                example: r#""user: Summarize the repo""#,
+            },
+            TemplateVariable {
+                name: "scratchpad",
+                description: "Notes from earlier planning attempts, if any",
+                example: "null",
+            },
+        ],
+        body: include_str!("../prompts/planning_agent.j2"),
+    },
+];
+
+pub fn builtin(name: &str) -> Result<&'static BuiltinTemplate, String> {
+    BUILTIN_TEMPLATES
+        .iter()
+        .find(|t| t.name == name)
+        .ok_or_else(|| format!("Unknown prompt template '{}'", name))
+}
+
+/// Where the template in use comes from
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TemplateSource {
+    Agent { version: i32 },
+    Global { version: i32 },
+    File { path: String },
+    Builtin,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ResolvedTemplate {
+    pub name: String,
+    pub source: TemplateSource,
+    pub body: String,
+}
+
+fn file_override(name: &str) -> Option<(PathBuf, String)> {
+    let dir = crate::config::current().prompts_dir?;
+    let path = dir.join(format!("{}.j2", name));
+    std::fs::read_to_string(&path).ok().map(|body| (path, body))
+}
+
+/// The template `agent_id` (or, without one, every agent) renders `name` with
+pub fn resolve(pool: Option<&DbPool>, name: &str, agent_id: Option<&str>) -> Result<ResolvedTemplate, String> {
+    let builtin = builtin(name)?;
+    let resolved = |source, body| ResolvedTemplate { name: name.to_string(), source, body };
+
+    if let Some(pool) = pool {
+        if let Some(agent_id) = agent_id {
+            if let Some(v) = prompt_template::latest_version(pool, name, Some(agent_id))? {
+                return Ok(resolved(TemplateSource::Agent { version: v.version }, v.body));
+            }
+        }
+        if let Some(v) = prompt_template::latest_version(pool, name, None)? {
+            return Ok(resolved(TemplateSource::Global { version: v.version }, v.body));
+        }
+    }
+    if let Some((path, body)) = file_override(name) {
+        return Ok(resolved(TemplateSource::File { path: path.display().to_string() }, body));
+    }
+    Ok(resolved(TemplateSource::Builtin, builtin.body.to_string()))
+}
+
+fn render_body(name: &str, body: &str, context: impl Serialize) -> Result<String, String> {
+    let mut env = minijinja::Environment::new();
+    env.add_template(name, body).map_err(|e| e.to_string())?;
+    env.get_template(name)
+        .and_then(|tmpl| tmpl.render(context))
+        .map_err(|e| e.to_string())
+}
+
+/// Render the template in use. A broken override falls back to the
+/// built-in template.
+pub fn render(pool: Option<&DbPool>, name: &str, agent_id: Option<&str>, context: impl Serialize) -> Result<String, String> {
+    let template = match resolve(pool, name, agent_id) {
+        Ok(template) => template,
+        Err(e) => {
+            log::warn!("Failed to load prompt template '{}', using the built-in one: {}", name, e);
+            ResolvedTemplate {
+                name: name.to_string(),
+                source: TemplateSource::Builtin,
+                body: builtin(name)?.body.to_string(),
+            }
+        }
+    };
+    match render_body(name, &template.body, &context) {
+        Err(e) if template.source != TemplateSource::Builtin => {
+            log::warn!(
+                "Prompt template '{}' ({:?}) failed to render, using the built-in one: {}",
+                name, template.source, e
+            );
+            render_body(name, builtin(name)?.body, &context)
+        }
+        result => result,
+    }
+}
+
+/// The examples of a template's variables, overlaid with `variables`
+pub fn sample_context(name: &str, variables: Option<&Value>) -> Result<Value, String> {
+    let mut context = serde_json::Map::new();
+    for variable in builtin(name)?.variables {
+        let example = serde_json::from_str(variable.example).unwrap_or(Value::Null);
+        context.insert(variable.name.to_string(), example);
+    }
+    if let Some(Value::Object(overrides)) = variables {
+        context.extend(overrides.clone());
+    }
+    Ok(Value::Object(context))
+}
+
+/// Render `body` (or the template in use) with example variables, without
+/// the fallback `render` has, so errors show up
+pub fn preview(
+    pool: Option<&DbPool>,
+    name: &str,
+    agent_id: Option<&str>,
+    body: Option<&str>,
+    variables: Option<&Value>,
+) -> Result<String, String> {
+    let context = sample_context(name, variables)?;
+    match body {
+        Some(body) => render_body(name, body, &context),
+        None => render_body(name, &resolve(pool, name, agent_id)?.body, &context),
+    }
+}
+
+/// Save a new version of an override after checking it renders
+pub fn save(
+    pool: &DbPool,
+    name: &str,
+    agent_id: Option<&str>,
+    body: &str,
+    note: Option<String>,
+) -> Result<PromptTemplateVersion, String> {
+    if body.trim().is_empty() {
+        return Err("Template cannot be empty".to_string());
+    }
+    preview(None, name, None, Some(body), None).map_err(|e| format!("Template does not render: {}", e))?;
+    prompt_template::save_version(pool, name, agent_id, body, note)
+}
+
+/// Make an old version the one in use again, as a new version
+pub fn restore(pool: &DbPool, name: &str, agent_id: Option<&str>, version: i32) -> Result<PromptTemplateVersion, String> {
+    let old = prompt_template::get_version(pool, name, agent_id, version)?;
+    save(pool, name, agent_id, &old.body, Some(format!("Restored version {}", version)))
+}
+
+/// Remove an override with all its versions
+pub fn reset(pool: &DbPool, name: &str, agent_id: Option<&str>) -> Result<(), String> {
+    builtin(name)?;
+    prompt_template::delete_versions(pool, name, agent_id).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+    use serde_json::json;
+
+    #[test]
+    fn test_overrides_resolve_agent_then_global_then_builtin() {
+        let pool = create_test_pool();
+        let tmpl = resolve(Some(&pool), TOOL_USE_SYSTEM, Some("agent-1")).unwrap();
+        if crate::config::current().prompts_dir.is_none() {
+            assert_eq!(tmpl.source, TemplateSource::Builtin);
+        }
+
+        save(&pool, TOOL_USE_SYSTEM, None, "Global {{ read_only }}", None).unwrap();
+        save(&pool, TOOL_USE_SYSTEM, Some("agent-1"), "Agent v1", None).unwrap();
+        let v2 = save(&pool, TOOL_USE_SYSTEM, Some("agent-1"), "Agent v2 {{ tools_desc }}", None).unwrap();
+        assert_eq!(v2.version, 2);
+
+        let context = json!({"tools_desc": "[]", "read_only": true});
+        assert_eq!(render(Some(&pool), TOOL_USE_SYSTEM, Some("agent-1"), &context).unwrap(), "Agent v2 []");
+        assert_eq!(render(Some(&pool), TOOL_USE_SYSTEM, Some("agent-2"), &context).unwrap(), "Global True");
+
+        let restored = restore(&pool, TOOL_USE_SYSTEM, Some("agent-1"), 1).unwrap();
+        assert_eq!(restored.version, 3);
+        assert_eq!(
+            resolve(Some(&pool), TOOL_USE_SYSTEM, Some("agent-1")).unwrap().source,
+            TemplateSource::Agent { version: 3 }
+        );
+        assert_eq!(prompt_template::list_versions(&pool, TOOL_USE_SYSTEM, Some("agent-1")).unwrap().len(), 3);
+
+        reset(&pool, TOOL_USE_SYSTEM, Some("agent-1")).unwrap();
+        assert_eq!(
+            resolve(Some(&pool), TOOL_USE_SYSTEM, Some("agent-1")).unwrap().source,
+            TemplateSource::Global { version: 1 }
+        );
+    }
+
+    #[test]
+    fn test_broken_templates_are_rejected_or_fall_back() {
+        let pool = create_test_pool();
+        assert!(save(&pool, PLANNING_AGENT, None, "{% if team %}unclosed", None).is_err());
+        assert!(save(&pool, "no_such_template", None, "hi", None).is_err());
+
+        // Renders with the examples (an empty team) but not with a real team
+        let body = "{{ context | upper }}{% for member in team %} ({{ member.expertise | length }}){% endfor %}";
+        save(&pool, PLANNING_AGENT, None, body, None).unwrap();
+        let context = json!({
+            "schema": "{}",
+            "team": [{"id": "ops", "name": "Ops", "description": null, "expertise": null}],
+            "context": "history",
+            "scratchpad": null
+        });
+        let rendered = render(Some(&pool), PLANNING_AGENT, None, &context).unwrap();
+        assert!(rendered.contains("- id: ops | Ops"), "fell back to the built-in template");
+
+        let preview = preview(Some(&pool), PLANNING_AGENT, None, None, Some(&json!({"context": "hello"}))).unwrap();
+        assert_eq!(preview, "HELLO");
+    }
+}
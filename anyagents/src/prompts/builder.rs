@@ -0,0 +1,238 @@
+//! System prompt composition
+//!
+//! Every system prompt is built from the same layers, always in this order:
+//!
+//! 1. persona: the agent's own system prompt, or a default
+//! 2. skills: one line per assigned skill, so the agent knows what it has
+//! 3. workspace: OS, working directory and git status
+//! 4. memory: notes recalled from pages for this message
+//! 5. instructions: what the mode needs (tool use, mail reply, ...)
+//!
+//! Layers left empty are skipped; the rest are joined with `---` rules.
+
+use crate::database::DbPool;
+use crate::models::ParsedSkill;
+use std::path::{Path, PathBuf};
+
+const SEPARATOR: &str = "\n\n---\n\n";
+/// Changed files listed in the workspace layer
+const MAX_GIT_CHANGES: usize = 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitFacts {
+    pub branch: Option<String>,
+    /// `git status --porcelain` lines
+    pub changes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceFacts {
+    pub os: String,
+    pub path: PathBuf,
+    /// None outside git repositories
+    pub git: Option<GitFacts>,
+}
+
+impl WorkspaceFacts {
+    /// Look at `path` now; runs `git status` when it is a repository
+    pub fn collect(path: &Path) -> Self {
+        Self {
+            os: std::env::consts::OS.to_string(),
+            path: path.to_path_buf(),
+            git: git_facts(path),
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut text = format!(
+            "## Workspace\n- OS: {}\n- Working directory: {}",
+            self.os,
+            self.path.display()
+        );
+        if let Some(git) = &self.git {
+            text.push_str(&format!("\n- Git branch: {}", git.branch.as_deref().unwrap_or("(detached)")));
+            if git.changes.is_empty() {
+                text.push_str("\n- No uncommitted changes");
+            } else {
+                text.push_str("\n- Uncommitted changes:");
+                for change in git.changes.iter().take(MAX_GIT_CHANGES) {
+                    text.push_str(&format!("\n  {}", change));
+                }
+                if git.changes.len() > MAX_GIT_CHANGES {
+                    text.push_str(&format!("\n  ... and {} more", git.changes.len() - MAX_GIT_CHANGES));
+                }
+            }
+        }
+        text
+    }
+}
+
+fn git_facts(path: &Path) -> Option<GitFacts> {
+    if !path.join(".git").exists() {
+        return None;
+    }
+    let output = std::process::Command::new("git")
+        .args(["status", "--porcelain", "--branch"])
+        .current_dir(path)
+        .output()
+        .ok()
+        .filter(|o| o.status.success())?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = stdout.lines();
+    let branch = lines
+        .next()
+        .and_then(|header| header.strip_prefix("## "))
+        .map(|header| header.split("...").next().unwrap_or(header).to_string())
+        .filter(|branch| !branch.starts_with("HEAD"));
+    Some(GitFacts { branch, changes: lines.map(str::to_string).collect() })
+}
+
+/// Notes from pages that share words with `message`, as `title: snippet`
+pub fn recall_notes(pool: &DbPool, message: &str, limit: i64) -> Vec<String> {
+    let hits = crate::search::recall_pages(pool, message, limit).unwrap_or_else(|e| {
+        log::warn!("Failed to recall notes: {}", e);
+        vec![]
+    });
+    hits.into_iter()
+        .map(|hit| {
+            let snippet = hit
+                .snippet
+                .replace(crate::search::MATCH_START, "")
+                .replace(crate::search::MATCH_END, "");
+            match hit.title {
+                Some(title) => format!("{}: {}", title, snippet),
+                None => snippet,
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PromptBuilder {
+    persona: Option<String>,
+    skills: Vec<(String, String)>,
+    workspace: Option<WorkspaceFacts>,
+    memory: Vec<String>,
+    instructions: Vec<String>,
+}
+
+impl PromptBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The agent's system prompt; `default` when it has none
+    pub fn persona(mut self, system_prompt: Option<&str>, default: &str) -> Self {
+        let persona = system_prompt.map(str::trim).filter(|p| !p.is_empty()).unwrap_or(default);
+        self.persona = Some(persona.to_string()).filter(|p| !p.is_empty());
+        self
+    }
+
+    pub fn skills(mut self, skills: &[ParsedSkill]) -> Self {
+        self.skills = skills.iter().map(|s| (s.name.clone(), s.description.clone())).collect();
+        self
+    }
+
+    pub fn workspace(mut self, facts: WorkspaceFacts) -> Self {
+        self.workspace = Some(facts);
+        self
+    }
+
+    pub fn memory(mut self, notes: Vec<String>) -> Self {
+        self.memory = notes;
+        self
+    }
+
+    /// Add mode-specific instructions; several are kept in the order given
+    pub fn instructions(mut self, text: impl Into<String>) -> Self {
+        let text = text.into();
+        if !text.trim().is_empty() {
+            self.instructions.push(text);
+        }
+        self
+    }
+
+    pub fn build(&self) -> String {
+        let mut layers: Vec<String> = vec![];
+        layers.extend(self.persona.clone());
+        if !self.skills.is_empty() {
+            let list: Vec<String> = self
+                .skills
+                .iter()
+                .map(|(name, description)| format!("- {}: {}", name, description))
+                .collect();
+            layers.push(format!("## Skills\n{}", list.join("\n")));
+        }
+        layers.extend(self.workspace.as_ref().map(WorkspaceFacts::render));
+        if !self.memory.is_empty() {
+            let notes: Vec<String> = self.memory.iter().map(|note| format!("- {}", note)).collect();
+            layers.push(format!("## Notes that may be relevant\n{}", notes.join("\n")));
+        }
+        layers.extend(self.instructions.iter().cloned());
+        layers.join(SEPARATOR)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn skill(name: &str, description: &str) -> ParsedSkill {
+        ParsedSkill {
+            name: name.to_string(),
+            description: description.to_string(),
+            license: None,
+            category: None,
+            triggers: None,
+            requires_sandbox: false,
+            read_only: false,
+            sandbox_config: None,
+            execution_mode: None,
+            dependencies: None,
+            invocation: None,
+            examples: Vec::new(),
+            recipe: None,
+            body: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_layers_in_fixed_order() {
+        let workspace = WorkspaceFacts {
+            os: "linux".to_string(),
+            path: PathBuf::from("/work"),
+            git: Some(GitFacts { branch: Some("main".to_string()), changes: vec![" M src/lib.rs".to_string()] }),
+        };
+        // Added out of order on purpose
+        let prompt = PromptBuilder::new()
+            .instructions("Use tools.")
+            .memory(vec!["Meeting notes: ship Friday".to_string()])
+            .workspace(workspace)
+            .skills(&[skill("pdf", "Read and fill PDFs")])
+            .persona(Some("You are Ada."), "You are a helpful assistant.")
+            .build();
+
+        let order: Vec<usize> = ["You are Ada.", "## Skills", "## Workspace", "## Notes", "Use tools."]
+            .iter()
+            .map(|part| prompt.find(part).unwrap())
+            .collect();
+        assert!(order.windows(2).all(|w| w[0] < w[1]), "{}", prompt);
+        assert!(prompt.contains("- pdf: Read and fill PDFs"));
+        assert!(prompt.contains("- Git branch: main\n- Uncommitted changes:\n   M src/lib.rs"));
+    }
+
+    #[test]
+    fn test_empty_layers_are_skipped() {
+        let prompt = PromptBuilder::new().persona(Some("  "), "Default persona.").instructions("").build();
+        assert_eq!(prompt, "Default persona.");
+        assert_eq!(PromptBuilder::new().persona(None, "").build(), "");
+    }
+
+    #[test]
+    fn test_collect_outside_git() {
+        let dir = tempfile::tempdir().unwrap();
+        let facts = WorkspaceFacts::collect(dir.path());
+        assert!(facts.git.is_none());
+        assert_eq!(facts.os, std::env::consts::OS);
+    }
+}
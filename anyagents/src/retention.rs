@@ -0,0 +1,336 @@
+//! Retention policies and storage statistics
+//!
+//! Chat history only grows. `RetentionPolicy` (stored in settings, every rule
+//! off by default) archives sessions nobody touched for a while and deletes
+//! old messages and tool output; pinned sessions are never touched.
+//! `storage_stats` shows which tables the space goes to.
+
+use crate::database::{serialized_write, DbPool};
+use crate::models::settings::{get_setting, set_setting};
+use crate::schema::{messages, session_events, sessions};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text};
+use serde::{Deserialize, Serialize};
+
+const ARCHIVE_IDLE_KEY: &str = "retention_archive_idle_days";
+const MESSAGE_KEY: &str = "retention_message_days";
+const TOOL_EVENT_KEY: &str = "retention_tool_event_days";
+const VACUUM_KEY: &str = "retention_vacuum";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct RetentionPolicy {
+    /// Archive sessions without activity for this many days
+    pub archive_idle_days: Option<u32>,
+    /// Delete messages older than this many days
+    pub message_days: Option<u32>,
+    /// Delete tool output (messages with role "tool") and the logged job
+    /// events older than this many days
+    pub tool_event_days: Option<u32>,
+    /// Compact the database file after something was deleted
+    pub vacuum: bool,
+}
+
+impl RetentionPolicy {
+    pub fn load(pool: &DbPool) -> Self {
+        let days = |key: &str| get_setting(pool, key).and_then(|v| v.parse().ok()).filter(|d: &u32| *d > 0);
+        Self {
+            archive_idle_days: days(ARCHIVE_IDLE_KEY),
+            message_days: days(MESSAGE_KEY),
+            tool_event_days: days(TOOL_EVENT_KEY),
+            vacuum: get_setting(pool, VACUUM_KEY).is_some_and(|v| v == "true"),
+        }
+    }
+
+    /// Save the policy; `None` or 0 days turns a rule off
+    pub fn save(&self, pool: &DbPool) -> Result<(), String> {
+        let days = |d: Option<u32>| d.unwrap_or(0).to_string();
+        set_setting(pool, ARCHIVE_IDLE_KEY, &days(self.archive_idle_days))?;
+        set_setting(pool, MESSAGE_KEY, &days(self.message_days))?;
+        set_setting(pool, TOOL_EVENT_KEY, &days(self.tool_event_days))?;
+        set_setting(pool, VACUUM_KEY, &self.vacuum.to_string())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.archive_idle_days.is_some() || self.message_days.is_some() || self.tool_event_days.is_some()
+    }
+}
+
+/// What `apply_retention` changed
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct RetentionReport {
+    pub archived_sessions: usize,
+    pub deleted_messages: usize,
+    pub deleted_tool_events: usize,
+    pub deleted_session_events: usize,
+    /// Bytes the file shrank by when it was vacuumed
+    pub reclaimed_bytes: Option<i64>,
+}
+
+fn cutoff(days: u32) -> chrono::NaiveDateTime {
+    chrono::Utc::now().naive_utc() - chrono::Duration::days(days as i64)
+}
+
+/// Apply every enabled rule of `policy`
+pub fn apply_retention(pool: &DbPool, policy: &RetentionPolicy) -> Result<RetentionReport, String> {
+    let pinned = sessions::table.filter(sessions::pinned.ne(0)).select(sessions::id);
+    let mut report = serialized_write(pool, |conn| {
+        let mut report = RetentionReport::default();
+
+        if let Some(days) = policy.archive_idle_days {
+            let cutoff = cutoff(days);
+            // Tool output alone doesn't make a conversation active
+            let active = messages::table
+                .filter(messages::created_at.ge(cutoff))
+                .filter(messages::role.ne("tool"))
+                .select(messages::session_id);
+            report.archived_sessions = diesel::update(
+                sessions::table
+                    .filter(sessions::archived.eq(0))
+                    .filter(sessions::pinned.eq(0))
+                    .filter(sessions::updated_at.lt(cutoff))
+                    .filter(diesel::dsl::not(sessions::id.eq_any(active))),
+            )
+            .set(sessions::archived.eq(1))
+            .execute(conn)?;
+        }
+
+        if let Some(days) = policy.tool_event_days {
+            report.deleted_tool_events = diesel::delete(
+                messages::table
+                    .filter(messages::role.eq("tool"))
+                    .filter(messages::created_at.lt(cutoff(days)))
+                    .filter(diesel::dsl::not(messages::session_id.eq_any(pinned))),
+            )
+            .execute(conn)?;
+            report.deleted_session_events = diesel::delete(
+                session_events::table
+                    .filter(session_events::created_at.lt(cutoff(days)))
+                    .filter(diesel::dsl::not(session_events::session_id.eq_any(pinned))),
+            )
+            .execute(conn)?;
+        }
+
+        if let Some(days) = policy.message_days {
+            report.deleted_messages = diesel::delete(
+                messages::table
+                    .filter(messages::created_at.lt(cutoff(days)))
+                    .filter(diesel::dsl::not(messages::session_id.eq_any(pinned))),
+            )
+            .execute(conn)?;
+        }
+
+        Ok(report)
+    })
+    .map_err(|e| format!("Retention failed: {}", e))?;
+
+    if policy.vacuum && report.deleted_messages + report.deleted_tool_events + report.deleted_session_events > 0 {
+        let before = storage_stats(pool)?.total_bytes;
+        let mut conn = pool.get().map_err(|e| e.to_string())?;
+        diesel::sql_query("VACUUM")
+            .execute(&mut conn)
+            .map_err(|e| format!("Vacuum failed: {}", e))?;
+        report.reclaimed_bytes = Some(before - storage_stats(pool)?.total_bytes);
+    }
+
+    if report != RetentionReport::default() {
+        log::info!("Retention: {:?}", report);
+    }
+    Ok(report)
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TableStorage {
+    pub name: String,
+    pub rows: i64,
+    /// Pages of the table and its indexes (and of its shadow tables, for a
+    /// search index)
+    pub bytes: i64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StorageStats {
+    pub total_bytes: i64,
+    /// Unused pages that a vacuum would give back
+    pub free_bytes: i64,
+    /// Largest first
+    pub tables: Vec<TableStorage>,
+}
+
+/// Size of the database file, broken down by table
+pub fn storage_stats(pool: &DbPool) -> Result<StorageStats, String> {
+    #[derive(QueryableByName)]
+    struct FileSize {
+        #[diesel(sql_type = BigInt)]
+        total_bytes: i64,
+        #[diesel(sql_type = BigInt)]
+        free_bytes: i64,
+    }
+    #[derive(QueryableByName)]
+    struct Table {
+        #[diesel(sql_type = Text)]
+        name: String,
+        #[diesel(sql_type = Text)]
+        kind: String,
+    }
+    #[derive(QueryableByName)]
+    struct Size {
+        #[diesel(sql_type = Text)]
+        name: String,
+        #[diesel(sql_type = BigInt)]
+        bytes: i64,
+    }
+    #[derive(QueryableByName)]
+    struct Count {
+        #[diesel(sql_type = BigInt)]
+        row_count: i64,
+    }
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let query_error = |e: diesel::result::Error| format!("Failed to read storage stats: {}", e);
+
+    let file: FileSize = diesel::sql_query(
+        "SELECT page_count * page_size AS total_bytes, freelist_count * page_size AS free_bytes \
+         FROM pragma_page_count, pragma_page_size, pragma_freelist_count",
+    )
+    .get_result(&mut conn)
+    .map_err(query_error)?;
+    let tables: Vec<Table> = diesel::sql_query(
+        "SELECT name, type AS kind FROM pragma_table_list \
+         WHERE schema = 'main' AND type IN ('table', 'virtual', 'shadow') AND name NOT LIKE 'sqlite_%'",
+    )
+    .load(&mut conn)
+    .map_err(query_error)?;
+    // Indexes are counted with their table through tbl_name
+    let sizes: Vec<Size> = diesel::sql_query(
+        "SELECT m.tbl_name AS name, SUM(d.pgsize) AS bytes \
+         FROM dbstat AS d JOIN sqlite_master AS m ON m.name = d.name GROUP BY m.tbl_name",
+    )
+    .load(&mut conn)
+    .map_err(query_error)?;
+
+    let mut stats: Vec<TableStorage> = Vec::new();
+    for table in tables.iter().filter(|t| t.kind != "shadow") {
+        let rows: Count = diesel::sql_query(format!("SELECT COUNT(*) AS row_count FROM \"{}\"", table.name))
+            .get_result(&mut conn)
+            .map_err(query_error)?;
+        stats.push(TableStorage { name: table.name.clone(), rows: rows.row_count, bytes: 0 });
+    }
+    for size in sizes {
+        // Shadow tables (search_index_data, ...) belong to their virtual table
+        let owner = match tables.iter().find(|t| t.name == size.name) {
+            Some(t) if t.kind == "shadow" => tables
+                .iter()
+                .filter(|v| v.kind == "virtual" && size.name.starts_with(&format!("{}_", v.name)))
+                .map(|v| v.name.as_str())
+                .next(),
+            Some(t) => Some(t.name.as_str()),
+            None => None,
+        };
+        if let Some(entry) = owner.and_then(|owner| stats.iter_mut().find(|s| s.name == owner)) {
+            entry.bytes += size.bytes;
+        }
+    }
+    stats.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+
+    Ok(StorageStats {
+        total_bytes: file.total_bytes,
+        free_bytes: file.free_bytes,
+        tables: stats,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+    use crate::models::{NewMessage, NewSession};
+    use crate::schema::agents;
+
+    fn add_session(pool: &DbPool, pinned: i32, age_days: i64) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        let at = chrono::Utc::now().naive_utc() - chrono::Duration::days(age_days);
+        let mut conn = pool.get().unwrap();
+        diesel::insert_or_ignore_into(agents::table)
+            .values((agents::id.eq("agent"), agents::name.eq("Agent")))
+            .execute(&mut conn)
+            .unwrap();
+        diesel::insert_into(sessions::table)
+            .values(&NewSession {
+                id: id.clone(),
+                agent_id: "agent".to_string(),
+                title: None,
+                created_at: at,
+                updated_at: at,
+                archived: 0,
+                pinned,
+            })
+            .execute(&mut conn)
+            .unwrap();
+        id
+    }
+
+    fn add_message(pool: &DbPool, session_id: &str, role: &str, age_days: i64) {
+        let id = uuid::Uuid::new_v4().to_string();
+        let mut conn = pool.get().unwrap();
+        diesel::insert_into(messages::table)
+            .values(&NewMessage {
+                id: id.clone(),
+                role: role.to_string(),
+                content: "x".repeat(2000),
+                session_id: session_id.to_string(),
+                metadata_json: None,
+                tokens: None,
+            })
+            .execute(&mut conn)
+            .unwrap();
+        diesel::update(messages::table.find(&id))
+            .set(messages::created_at.eq(chrono::Utc::now().naive_utc() - chrono::Duration::days(age_days)))
+            .execute(&mut conn)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_retention() {
+        let pool = create_test_pool();
+        let idle = add_session(&pool, 0, 40);
+        let pinned = add_session(&pool, 1, 40);
+        let recent = add_session(&pool, 0, 40);
+        add_message(&pool, &idle, "user", 40);
+        add_message(&pool, &idle, "tool", 20);
+        add_message(&pool, &pinned, "tool", 40);
+        add_message(&pool, &recent, "user", 1);
+
+        let stats = storage_stats(&pool).unwrap();
+        let messages_stats = stats.tables.iter().find(|t| t.name == "messages").unwrap();
+        assert_eq!(messages_stats.rows, 4);
+        assert!(messages_stats.bytes > 0);
+        assert!(stats.tables.iter().any(|t| t.name == "search_index" && t.bytes > 0));
+        assert!(!stats.tables.iter().any(|t| t.name.starts_with("search_index_")));
+
+        assert_eq!(RetentionPolicy::load(&pool), RetentionPolicy::default());
+        let policy = RetentionPolicy {
+            archive_idle_days: Some(30),
+            message_days: Some(30),
+            tool_event_days: Some(10),
+            vacuum: true,
+        };
+        policy.save(&pool).unwrap();
+        assert_eq!(RetentionPolicy::load(&pool), policy);
+
+        let report = apply_retention(&pool, &policy).unwrap();
+        assert_eq!(report.archived_sessions, 1);
+        assert_eq!(report.deleted_tool_events, 1);
+        assert_eq!(report.deleted_messages, 1);
+        assert!(report.reclaimed_bytes.is_some());
+
+        let mut conn = pool.get().unwrap();
+        let archived: Vec<String> = sessions::table
+            .filter(sessions::archived.eq(1))
+            .select(sessions::id)
+            .load(&mut conn)
+            .unwrap();
+        assert_eq!(archived, vec![idle]);
+        let left: i64 = messages::table.count().get_result(&mut conn).unwrap();
+        assert_eq!(left, 2);
+    }
+}
@@ -0,0 +1,537 @@
+//! Builder API for embedding the agent runtime in other applications
+//!
+//! The Tauri app wires agents up from database rows; library consumers can use
+//! these builders instead:
+//!
+//! ```no_run
+//! use anyagents::{AgentBuilder, RuntimeBuilder};
+//!
+//! # async fn demo() -> Result<(), String> {
+//! let runtime = RuntimeBuilder::new()
+//!     .auto_approve()
+//!     .event_sink(|_channel, event| println!("{}", event))
+//!     .build()?;
+//!
+//! let mut agent = AgentBuilder::new("openai", "gpt-4o")
+//!     .system_prompt("You are a helpful assistant.")
+//!     .workspace(".")
+//!     .with_default_tools()
+//!     .build();
+//!
+//! let reply = runtime.chat(&mut agent, "List the files in this folder").await?;
+//! println!("{}", reply);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::agents::optimizations::get_message_content;
+use crate::agents::trace::Replay;
+use crate::agents::AgentLoop;
+use crate::database::DbPool;
+use crate::events::AgentObserver;
+use crate::llm::{GenerationParams, ModelRoute};
+use crate::permissions::{PermissionManager, PermissionRequest, PermissionResponse, ReadOnlyPolicy};
+use crate::tools::bash::BashTool;
+use crate::tools::filesystem::FilesystemTool;
+use crate::tools::middleware::{ToolMiddleware, ToolMiddlewareChain};
+use crate::tools::registry::{ToolRegistry, GROUP_BUILTIN};
+use crate::tools::search::SearchTool;
+use crate::tools::Tool;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Decides permission requests raised by tools (return true to allow)
+pub type PermissionHandler = Arc<dyn Fn(&PermissionRequest) -> bool + Send + Sync>;
+
+/// Receives every event emitted by the runtime as `(channel, payload)`
+pub type EventSink = Arc<dyn Fn(&str, &Value) + Send + Sync>;
+
+/// Builds an `AgentLoop` without a database-backed agent row
+pub struct AgentBuilder {
+    agent_id: String,
+    provider: String,
+    model: String,
+    system_prompt: Option<String>,
+    workspace_path: Option<PathBuf>,
+    execution_mode: String,
+    default_tools: bool,
+    tools: Vec<Box<dyn Tool>>,
+    read_only_policy: Option<ReadOnlyPolicy>,
+    checkpoints: bool,
+    trace_dir: Option<PathBuf>,
+    replay: Option<Replay>,
+    fallbacks: Vec<ModelRoute>,
+    params: GenerationParams,
+    middleware: Vec<Arc<dyn ToolMiddleware>>,
+}
+
+impl AgentBuilder {
+    pub fn new(provider: &str, model: &str) -> Self {
+        Self {
+            agent_id: "embedded".to_string(),
+            provider: provider.to_string(),
+            model: model.to_string(),
+            system_prompt: None,
+            workspace_path: None,
+            execution_mode: "flexible".to_string(),
+            default_tools: false,
+            tools: vec![],
+            read_only_policy: None,
+            checkpoints: false,
+            trace_dir: None,
+            replay: None,
+            fallbacks: vec![],
+            params: GenerationParams::default(),
+            middleware: vec![],
+        }
+    }
+
+    /// Identifier stored with the agent's messages
+    pub fn agent_id(mut self, agent_id: &str) -> Self {
+        self.agent_id = agent_id.to_string();
+        self
+    }
+
+    pub fn system_prompt(mut self, prompt: &str) -> Self {
+        self.system_prompt = Some(prompt.to_string());
+        self
+    }
+
+    /// Root directory for filesystem/bash tools and workspace snapshots (default: cwd)
+    pub fn workspace(mut self, path: impl Into<PathBuf>) -> Self {
+        self.workspace_path = Some(path.into());
+        self
+    }
+
+    /// Tool execution mode: "sandbox", "direct", "flexible" or "microvm"
+    pub fn execution_mode(mut self, mode: &str) -> Self {
+        self.execution_mode = mode.to_string();
+        self
+    }
+
+    /// Register the built-in filesystem, search and bash tools
+    pub fn with_default_tools(mut self) -> Self {
+        self.default_tools = true;
+        self
+    }
+
+    pub fn tool(mut self, tool: impl Tool + 'static) -> Self {
+        self.tools.push(Box::new(tool));
+        self
+    }
+
+    /// Block tool calls with side effects
+    pub fn read_only(mut self, policy: ReadOnlyPolicy) -> Self {
+        self.read_only_policy = Some(policy);
+        self
+    }
+
+    /// Commit a git checkpoint before each mutating tool call (git workspaces only)
+    pub fn checkpoints(mut self, enabled: bool) -> Self {
+        self.checkpoints = enabled;
+        self
+    }
+
+    /// Model to fail over to when the previous one keeps failing (in call order)
+    pub fn fallback(mut self, provider: &str, model: &str) -> Self {
+        self.fallbacks.push(ModelRoute::new(provider, model));
+        self
+    }
+
+    /// Temperature, answer length and sampling of the agent's model calls
+    pub fn generation_params(mut self, params: GenerationParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Run every tool call through `middleware`, after the built-in ones
+    /// (see `tools::middleware`)
+    pub fn middleware(mut self, middleware: impl ToolMiddleware + 'static) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Write an execution trace of every job to `dir` (see `agents::trace`)
+    pub fn trace(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.trace_dir = Some(dir.into());
+        self
+    }
+
+    /// Replay a recorded trace: no provider calls, tools return their recorded results
+    pub fn replay(mut self, replay: Replay) -> Self {
+        self.replay = Some(replay);
+        self
+    }
+
+    pub fn build(self) -> AgentLoop {
+        let workspace_path = self.workspace_path.unwrap_or_else(|| {
+            std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
+        });
+
+        let mut tools: Vec<Box<dyn Tool>> = vec![];
+        if self.default_tools {
+            tools.push(Box::new(FilesystemTool::new(workspace_path.clone())));
+            tools.push(Box::new(SearchTool));
+            tools.push(Box::new(BashTool::new(workspace_path.clone(), self.execution_mode.clone())));
+        }
+        tools.extend(self.tools);
+
+        let mut middleware = ToolMiddlewareChain::for_agent(None, &self.agent_id, None, Default::default());
+        for extra in self.middleware {
+            middleware.push(extra);
+        }
+
+        let mut snapshot_manager = crate::snapshots::SnapshotManager::new(workspace_path);
+        if self.checkpoints {
+            snapshot_manager.enable_checkpoints();
+        }
+
+        AgentLoop {
+            agent_id: self.agent_id,
+            session_id: uuid::Uuid::new_v4().to_string(),
+            model: self.model,
+            provider: self.provider,
+            system_prompt: self.system_prompt,
+            history: vec![],
+            tools: Arc::new(ToolRegistry::from_tools(GROUP_BUILTIN, tools)),
+            skills: vec![],
+            snapshot_manager,
+            read_only_policy: self.read_only_policy,
+            policy: None,
+            scope: Default::default(),
+            secrets: Default::default(),
+            dlp: Default::default(),
+            bus: None,
+            images: vec![],
+            trace_dir: self.trace_dir,
+            replay: self.replay.map(Arc::new),
+            fallbacks: self.fallbacks,
+            params: self.params,
+            recall_notes: false,
+            hooks: None,
+            middleware,
+            dry_run: None,
+        }
+    }
+}
+
+/// Routes runtime events to the event sink and answers permission requests
+struct RuntimeObserver {
+    sink: Option<EventSink>,
+    permission_handler: Option<PermissionHandler>,
+    permission_manager: Arc<PermissionManager>,
+}
+
+impl AgentObserver for RuntimeObserver {
+    fn emit(&self, event: &str, payload: Value) -> Result<(), String> {
+        if let Some(sink) = &self.sink {
+            sink(event, &payload);
+        }
+
+        // Permission requests arrive either wrapped on the session channel
+        // or bare on the global "permission_request" channel
+        let request = if payload.get("type").and_then(|t| t.as_str()) == Some("permission_request") {
+            payload.get("request").cloned()
+        } else if event == "permission_request" {
+            Some(payload)
+        } else {
+            None
+        };
+
+        if let (Some(handler), Some(request)) = (&self.permission_handler, request) {
+            let request: PermissionRequest =
+                serde_json::from_value(request).map_err(|e| e.to_string())?;
+            let response = if handler(&request) {
+                PermissionResponse::Allow
+            } else {
+                PermissionResponse::Deny
+            };
+            self.permission_manager.respond_as(&request.id, response, "permission_handler");
+        }
+
+        Ok(())
+    }
+}
+
+/// Shared services (database, permissions, events) for running agents
+pub struct Runtime {
+    db_pool: DbPool,
+    permission_manager: Arc<PermissionManager>,
+    observer: Arc<dyn AgentObserver>,
+}
+
+impl Runtime {
+    pub fn db_pool(&self) -> &DbPool {
+        &self.db_pool
+    }
+
+    pub fn permission_manager(&self) -> Arc<PermissionManager> {
+        self.permission_manager.clone()
+    }
+
+    /// Built agents have no rows of their own: add the agent and session rows
+    /// their messages belong to, unless they are there already
+    fn ensure_session(&self, agent: &AgentLoop) -> Result<(), String> {
+        use crate::schema::{agents, sessions};
+        use diesel::prelude::*;
+
+        crate::database::serialized_write(&self.db_pool, |conn| {
+            diesel::insert_or_ignore_into(agents::table)
+                .values((
+                    agents::id.eq(&agent.agent_id),
+                    agents::name.eq(&agent.agent_id),
+                    agents::ai_provider.eq(&agent.provider),
+                    agents::ai_model.eq(&agent.model),
+                ))
+                .execute(conn)?;
+            diesel::insert_or_ignore_into(sessions::table)
+                .values((sessions::id.eq(&agent.session_id), sessions::agent_id.eq(&agent.agent_id)))
+                .execute(conn)?;
+            Ok(())
+        })
+    }
+
+    /// Run one user turn and return the agent's final reply
+    pub async fn chat(&self, agent: &mut AgentLoop, message: &str) -> Result<String, String> {
+        self.ensure_session(agent)?;
+        let history_len = agent.history.len();
+
+        agent
+            .run(
+                message.to_string(),
+                self.observer.clone(),
+                uuid::Uuid::new_v4().to_string(),
+                Arc::new(dashmap::DashMap::new()),
+                self.permission_manager.clone(),
+                self.db_pool.clone(),
+            )
+            .await;
+
+        agent.history[history_len..]
+            .iter()
+            .rev()
+            .find(|m| matches!(m, rig::completion::Message::Assistant { .. }))
+            .map(get_message_content)
+            .ok_or_else(|| "Agent did not produce a reply".to_string())
+    }
+}
+
+#[derive(Default)]
+pub struct RuntimeBuilder {
+    db_pool: Option<DbPool>,
+    database_url: Option<String>,
+    permission_handler: Option<PermissionHandler>,
+    sink: Option<EventSink>,
+}
+
+impl RuntimeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use an existing connection pool (migrations must already be applied)
+    pub fn db_pool(mut self, pool: DbPool) -> Self {
+        self.db_pool = Some(pool);
+        self
+    }
+
+    /// Open (and migrate) a SQLite database at this path/URL.
+    /// Without a database the runtime uses a throwaway file in the temp dir.
+    pub fn database_url(mut self, url: &str) -> Self {
+        self.database_url = Some(url.to_string());
+        self
+    }
+
+    pub fn permission_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&PermissionRequest) -> bool + Send + Sync + 'static,
+    {
+        self.permission_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Approve every permission request (unattended/trusted use only)
+    pub fn auto_approve(self) -> Self {
+        self.permission_handler(|_| true)
+    }
+
+    pub fn event_sink<F>(mut self, sink: F) -> Self
+    where
+        F: Fn(&str, &Value) + Send + Sync + 'static,
+    {
+        self.sink = Some(Arc::new(sink));
+        self
+    }
+
+    pub fn build(self) -> Result<Runtime, String> {
+        let db_pool = match (self.db_pool, self.database_url) {
+            (Some(pool), _) => pool,
+            (None, Some(url)) => crate::database::open_database(&url)?,
+            (None, None) => {
+                let path = std::env::temp_dir()
+                    .join(format!("anycowork_runtime_{}.db", uuid::Uuid::new_v4()));
+                crate::database::open_database(&path.to_string_lossy())?
+            }
+        };
+
+        let permission_manager = Arc::new(PermissionManager::new());
+        let observer = Arc::new(RuntimeObserver {
+            sink: self.sink,
+            permission_handler: self.permission_handler,
+            permission_manager: permission_manager.clone(),
+        });
+
+        Ok(Runtime {
+            db_pool,
+            permission_manager,
+            observer,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::permissions::PermissionType;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_agent_builder_registers_tools() {
+        let agent = AgentBuilder::new("openai", "gpt-4o")
+            .workspace(".")
+            .with_default_tools()
+            .tool(SearchTool)
+            .build();
+
+        assert_eq!(agent.tools.names(), vec!["filesystem", "search_files", "bash", "search_files"]);
+        assert_ne!(agent.session_id, "temp");
+    }
+
+    #[tokio::test]
+    async fn test_permission_handler_answers_requests() {
+        let runtime = RuntimeBuilder::new()
+            .db_pool(crate::database::create_test_pool())
+            .permission_handler(|req| req.permission_type == PermissionType::FilesystemRead)
+            .build()
+            .unwrap();
+
+        let mut metadata = HashMap::new();
+        metadata.insert("session_id".to_string(), "s1".to_string());
+        metadata.insert("resource".to_string(), "a.txt".to_string());
+
+        let read = PermissionRequest {
+            id: "r1".to_string(),
+            permission_type: PermissionType::FilesystemRead,
+            message: "read".to_string(),
+            metadata: metadata.clone(),
+            payload: None,
+        };
+        let allowed = runtime
+            .permission_manager()
+            .request_permission(Some(&runtime.observer), read)
+            .await
+            .unwrap();
+        assert!(allowed);
+
+        let write = PermissionRequest {
+            id: "r2".to_string(),
+            permission_type: PermissionType::FilesystemWrite,
+            message: "write".to_string(),
+            metadata,
+            payload: None,
+        };
+        let allowed = runtime
+            .permission_manager()
+            .request_permission(Some(&runtime.observer), write)
+            .await
+            .unwrap();
+        assert!(!allowed);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_answers_when_primary_fails() {
+        use crate::agents::trace::TraceEntry;
+        use diesel::prelude::*;
+
+        let switches = Arc::new(std::sync::Mutex::new(vec![]));
+        let seen = switches.clone();
+        let runtime = RuntimeBuilder::new()
+            .db_pool(crate::database::create_test_pool())
+            .auto_approve()
+            .event_sink(move |_, event| {
+                if event["type"] == "model_switched" {
+                    seen.lock().unwrap().push(event.clone());
+                }
+            })
+            .build()
+            .unwrap();
+
+        // Three failed attempts on the primary, then the fallback answers
+        let error = |attempt| TraceEntry::LlmError { step: 1, attempt, error: "429 Too Many Requests".to_string() };
+        let replay = Replay::from_entries([
+            error(1),
+            error(2),
+            error(3),
+            TraceEntry::LlmResponse { step: 1, text: "Hello from the fallback".to_string() },
+        ]);
+        let mut agent = AgentBuilder::new("openai", "gpt-4o")
+            .fallback("anthropic", "claude-3-5-sonnet-latest")
+            .replay(replay)
+            .build();
+
+        let reply = runtime.chat(&mut agent, "hi").await.unwrap();
+        assert_eq!(reply, "Hello from the fallback");
+
+        // The new session was created, with the reply recorded in it
+        use crate::schema::{messages, sessions};
+        let mut conn = runtime.db_pool().get().unwrap();
+        let agent_id: String = sessions::table
+            .find(&agent.session_id)
+            .select(sessions::agent_id)
+            .first(&mut conn)
+            .unwrap();
+        assert_eq!(agent_id, "embedded");
+        let recorded: Vec<(String, String)> = messages::table
+            .filter(messages::session_id.eq(&agent.session_id))
+            .select((messages::role, messages::content))
+            .load(&mut conn)
+            .unwrap();
+        assert_eq!(recorded, vec![("assistant".to_string(), reply)]);
+
+        let switches = switches.lock().unwrap();
+        assert_eq!(switches.len(), 1);
+        assert_eq!(switches[0]["to"]["provider"], "anthropic");
+        assert_eq!(switches[0]["rate_limited"], true);
+
+        let metadata: Option<String> = messages::table
+            .filter(messages::session_id.eq(&agent.session_id))
+            .filter(messages::role.eq("assistant"))
+            .select(messages::metadata_json)
+            .first(&mut conn)
+            .unwrap();
+        let metadata: Value = serde_json::from_str(&metadata.unwrap()).unwrap();
+        assert_eq!(metadata["answered_by"]["model"], "claude-3-5-sonnet-latest");
+        assert_eq!(metadata["fallback"], true);
+    }
+
+    #[test]
+    fn test_event_sink_receives_events() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = count.clone();
+        let runtime = RuntimeBuilder::new()
+            .db_pool(crate::database::create_test_pool())
+            .event_sink(move |_, _| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .build()
+            .unwrap();
+
+        runtime
+            .observer
+            .emit("session:test", serde_json::json!({"type": "thinking", "message": "hi"}))
+            .unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+}
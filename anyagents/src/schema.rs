@@ -26,6 +26,7 @@ diesel::table! {
         scope_type -> Nullable<Text>,
         workspace_path -> Nullable<Text>,
         avatar -> Nullable<Text>,
+        agent_tools -> Nullable<Text>,
     }
 }
 
@@ -38,6 +39,7 @@ diesel::table! {
         updated_at -> Timestamp,
         archived -> Integer,
         pinned -> Integer,
+        title_locked -> Integer,
     }
 }
 
@@ -50,6 +52,7 @@ diesel::table! {
         created_at -> Timestamp,
         metadata_json -> Nullable<Text>,
         tokens -> Nullable<Integer>,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -62,6 +65,156 @@ diesel::table! {
         allowed_chat_ids -> Nullable<Text>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        voice_replies -> Integer,
+        group_allowlists -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    discord_configs (id) {
+        id -> Text,
+        bot_token -> Text,
+        agent_id -> Text,
+        is_active -> Integer,
+        allowed_channel_ids -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    discord_channel_sessions (config_id, channel_id) {
+        config_id -> Text,
+        channel_id -> Text,
+        session_id -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    matrix_configs (id) {
+        id -> Text,
+        homeserver_url -> Text,
+        access_token -> Text,
+        agent_id -> Text,
+        is_active -> Integer,
+        allowed_room_ids -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    connector_sessions (platform, config_id, conversation_id, thread_id) {
+        platform -> Text,
+        config_id -> Text,
+        conversation_id -> Text,
+        thread_id -> Text,
+        session_id -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    permission_audit (id) {
+        id -> Text,
+        session_id -> Nullable<Text>,
+        tool -> Nullable<Text>,
+        permission_type -> Text,
+        resource -> Nullable<Text>,
+        message -> Text,
+        metadata -> Text,
+        resolution -> Text,
+        decided_by -> Text,
+        requested_at -> Timestamp,
+        decided_at -> Timestamp,
+        payload -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    permission_grants (id) {
+        id -> Text,
+        scope -> Text,
+        scope_id -> Nullable<Text>,
+        permission_type -> Text,
+        resource -> Text,
+        granted_by -> Text,
+        created_at -> Timestamp,
+        expires_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    slack_configs (id) {
+        id -> Text,
+        app_token -> Text,
+        bot_token -> Text,
+        agent_id -> Text,
+        is_active -> Integer,
+        allowed_channel_ids -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    slack_conversation_sessions (config_id, channel_id, thread_ts) {
+        config_id -> Text,
+        channel_id -> Text,
+        thread_ts -> Text,
+        session_id -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    telegram_chat_sessions (config_id, chat_id, thread_id) {
+        config_id -> Text,
+        chat_id -> BigInt,
+        thread_id -> BigInt,
+        session_id -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    secrets (name) {
+        name -> Text,
+        description -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    agent_secret_grants (agent_id, secret_name) {
+        agent_id -> Text,
+        secret_name -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    prompt_templates (id) {
+        id -> Text,
+        name -> Text,
+        agent_id -> Nullable<Text>,
+        version -> Integer,
+        body -> Text,
+        note -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    agent_versions (id) {
+        id -> Text,
+        agent_id -> Text,
+        version -> Integer,
+        snapshot -> Text,
+        note -> Nullable<Text>,
+        created_at -> Timestamp,
     }
 }
 
@@ -92,6 +245,8 @@ diesel::table! {
         order_index -> Integer,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        version -> Integer,
+        updated_by -> Nullable<Text>,
     }
 }
 
@@ -105,6 +260,9 @@ diesel::table! {
         file_type -> Text,
         file_size -> Integer,
         created_at -> Timestamp,
+        content_hash -> Nullable<Text>,
+        mime_type -> Nullable<Text>,
+        thumbnail_path -> Nullable<Text>,
     }
 }
 
@@ -125,6 +283,13 @@ diesel::table! {
         requires_sandbox -> Integer,
         sandbox_config -> Nullable<Text>,
         execution_mode -> Text,
+        triggers -> Nullable<Text>,
+        dependencies -> Nullable<Text>,
+        installed_version -> Nullable<Text>,
+        invocation -> Nullable<Text>,
+        examples -> Nullable<Text>,
+        recipe -> Nullable<Text>,
+        read_only -> Integer,
     }
 }
 
@@ -168,6 +333,88 @@ diesel::table! {
         recipient_agent_id -> Nullable<Text>,
         content -> Text,
         created_at -> Timestamp,
+        external_address -> Nullable<Text>,
+        external_message_id -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    mail_attachments (id) {
+        id -> Text,
+        message_id -> Text,
+        file_name -> Text,
+        mime_type -> Text,
+        file_size -> Integer,
+        storage_path -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    mail_recipients (id) {
+        id -> Text,
+        message_id -> Text,
+        kind -> Text,
+        recipient_type -> Text,
+        recipient_agent_id -> Nullable<Text>,
+        external_address -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    mail_groups (id) {
+        id -> Text,
+        name -> Text,
+        description -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    mail_group_members (group_id, agent_id) {
+        group_id -> Text,
+        agent_id -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    mail_labels (id) {
+        id -> Text,
+        name -> Text,
+        color -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    mail_thread_labels (thread_id, label_id) {
+        thread_id -> Text,
+        label_id -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    email_accounts (id) {
+        id -> Text,
+        agent_id -> Text,
+        email_address -> Text,
+        display_name -> Nullable<Text>,
+        username -> Text,
+        smtp_host -> Text,
+        smtp_port -> Integer,
+        imap_host -> Text,
+        imap_port -> Integer,
+        imap_folder -> Text,
+        deliver_outbound -> Integer,
+        poll_interval_seconds -> Integer,
+        last_seen_uid -> BigInt,
+        is_active -> Integer,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
     }
 }
 
@@ -181,21 +428,159 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    plans (id) {
+        id -> Text,
+        session_id -> Text,
+        job_id -> Text,
+        objective -> Text,
+        status -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    plan_tasks (plan_id, task_id) {
+        plan_id -> Text,
+        task_id -> Text,
+        description -> Text,
+        dependencies -> Text,
+        position -> Integer,
+        status -> Text,
+        result -> Nullable<Text>,
+        updated_at -> Timestamp,
+        assignee -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    job_artifacts (id) {
+        id -> Text,
+        job_id -> Text,
+        session_id -> Text,
+        kind -> Text,
+        title -> Text,
+        path -> Text,
+        mime_type -> Text,
+        file_size -> Integer,
+        preview -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    usage_records (id) {
+        id -> Text,
+        kind -> Text,
+        job_id -> Text,
+        session_id -> Text,
+        agent_id -> Text,
+        provider -> Text,
+        model -> Text,
+        tool -> Nullable<Text>,
+        success -> Integer,
+        input_tokens -> Integer,
+        output_tokens -> Integer,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    notifications (id) {
+        id -> Text,
+        event -> Text,
+        title -> Text,
+        body -> Text,
+        agent_id -> Nullable<Text>,
+        session_id -> Nullable<Text>,
+        is_read -> Integer,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    session_events (id) {
+        id -> BigInt,
+        session_id -> Text,
+        job_id -> Nullable<Text>,
+        event_type -> Text,
+        payload -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    job_todos (job_id) {
+        job_id -> Text,
+        session_id -> Text,
+        items -> Text,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    agent_triggers (id) {
+        id -> Text,
+        agent_id -> Text,
+        kind -> Text,
+        pattern -> Nullable<Text>,
+        prompt_template -> Nullable<Text>,
+        enabled -> Integer,
+        max_runs_per_hour -> Integer,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::joinable!(messages -> sessions (session_id));
 diesel::joinable!(sessions -> agents (agent_id));
 diesel::joinable!(telegram_configs -> agents (agent_id));
+diesel::joinable!(telegram_chat_sessions -> telegram_configs (config_id));
+diesel::joinable!(telegram_chat_sessions -> sessions (session_id));
+diesel::joinable!(discord_configs -> agents (agent_id));
+diesel::joinable!(discord_channel_sessions -> discord_configs (config_id));
+diesel::joinable!(discord_channel_sessions -> sessions (session_id));
+diesel::joinable!(slack_configs -> agents (agent_id));
+diesel::joinable!(slack_conversation_sessions -> slack_configs (config_id));
+diesel::joinable!(slack_conversation_sessions -> sessions (session_id));
+diesel::joinable!(matrix_configs -> agents (agent_id));
+diesel::joinable!(connector_sessions -> sessions (session_id));
 diesel::joinable!(blocks -> pages (page_id));
 diesel::joinable!(attachments -> pages (page_id));
 diesel::joinable!(agent_skill_assignments -> agents (agent_id));
 diesel::joinable!(agent_skill_assignments -> agent_skills (skill_id));
 diesel::joinable!(skill_files -> agent_skills (skill_id));
 diesel::joinable!(mail_messages -> mail_threads (thread_id));
+diesel::joinable!(email_accounts -> agents (agent_id));
+diesel::joinable!(mail_attachments -> mail_messages (message_id));
+diesel::joinable!(mail_recipients -> mail_messages (message_id));
+diesel::joinable!(mail_group_members -> mail_groups (group_id));
+diesel::joinable!(mail_group_members -> agents (agent_id));
+diesel::joinable!(mail_thread_labels -> mail_threads (thread_id));
+diesel::joinable!(mail_thread_labels -> mail_labels (label_id));
+diesel::joinable!(plan_tasks -> plans (plan_id));
+diesel::joinable!(agent_triggers -> agents (agent_id));
+diesel::joinable!(agent_secret_grants -> secrets (secret_name));
 
 diesel::allow_tables_to_appear_in_same_query!(
     agents,
     messages,
     sessions,
     telegram_configs,
+    telegram_chat_sessions,
+    discord_configs,
+    discord_channel_sessions,
+    slack_configs,
+    slack_conversation_sessions,
+    matrix_configs,
+    connector_sessions,
+    permission_audit,
+    permission_grants,
+    secrets,
+    agent_secret_grants,
+    prompt_templates,
+    agent_versions,
     pages,
     blocks,
     attachments,
@@ -205,7 +590,22 @@ diesel::allow_tables_to_appear_in_same_query!(
     mcp_servers,
     mail_threads,
     mail_messages,
+    mail_attachments,
+    mail_recipients,
+    mail_groups,
+    mail_group_members,
+    mail_labels,
+    mail_thread_labels,
+    email_accounts,
     settings,
+    plans,
+    plan_tasks,
+    job_todos,
+    job_artifacts,
+    usage_records,
+    notifications,
+    session_events,
+    agent_triggers,
 );
 
 diesel::table! {
@@ -221,5 +621,8 @@ diesel::table! {
         template_id -> Nullable<Text>,
         created_at -> BigInt,
         updated_at -> BigInt,
+        headers -> Nullable<Text>,
+        secret_names -> Nullable<Text>,
+        oauth -> Nullable<Text>,
     }
 }
@@ -0,0 +1,289 @@
+//! Global search over chats, pages and mail
+//!
+//! Session titles, messages, page titles and blocks are in the
+//! `search_index` FTS5 table and mail is in `mail_messages_fts`; triggers
+//! keep both in sync on every write, so nothing is reindexed here. Hits from
+//! both are ranked together by bm25.
+
+use crate::database::DbPool;
+use crate::email::search::fts_query;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Double, Nullable, Text};
+use serde::Serialize;
+
+pub const DEFAULT_SEARCH_LIMIT: i64 = 30;
+pub const MAX_SEARCH_LIMIT: i64 = 200;
+
+/// Snippets wrap matched words in these; the rest of the snippet is raw
+/// text, so escape it before rendering as HTML
+pub const MATCH_START: &str = "<mark>";
+pub const MATCH_END: &str = "</mark>";
+
+/// What a hit opens
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SearchTarget {
+    Session { session_id: String },
+    Message { session_id: String, message_id: String },
+    Page { page_id: String },
+    Block { page_id: String, block_id: String },
+    Mail { thread_id: String, message_id: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub target: SearchTarget,
+    /// Title of the session, page or mail thread the hit is in
+    pub title: Option<String>,
+    pub snippet: String,
+    /// bm25; lower is better
+    pub rank: f64,
+}
+
+#[derive(QueryableByName)]
+struct SearchRow {
+    #[diesel(sql_type = Text)]
+    kind: String,
+    #[diesel(sql_type = Text)]
+    doc_id: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    parent_id: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    context: Option<String>,
+    #[diesel(sql_type = Text)]
+    snippet: String,
+    #[diesel(sql_type = Double)]
+    rank: f64,
+}
+
+impl SearchRow {
+    fn into_hit(self) -> Option<SearchHit> {
+        let parent = self.parent_id.unwrap_or_default();
+        let target = match self.kind.as_str() {
+            "session" => SearchTarget::Session { session_id: self.doc_id },
+            "message" => SearchTarget::Message { session_id: parent, message_id: self.doc_id },
+            "page" => SearchTarget::Page { page_id: self.doc_id },
+            "block" => SearchTarget::Block { page_id: parent, block_id: self.doc_id },
+            "mail" => SearchTarget::Mail { thread_id: parent, message_id: self.doc_id },
+            other => {
+                log::warn!("Unknown search index kind '{}'", other);
+                return None;
+            }
+        };
+        Some(SearchHit {
+            target,
+            title: self.context,
+            snippet: self.snippet,
+            rank: self.rank,
+        })
+    }
+}
+
+/// Best matches for `query` everywhere, best first. Every word must match
+/// and the last one may be a prefix, as in mail search.
+pub fn global_search(pool: &DbPool, query: &str, limit: Option<i64>) -> Result<Vec<SearchHit>, String> {
+    let Some(expr) = fts_query(query) else {
+        return Ok(vec![]);
+    };
+    let limit = limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, MAX_SEARCH_LIMIT);
+    let sql = format!(
+        "SELECT kind, doc_id, parent_id, context, \
+             snippet(search_index, 4, '{start}', '{end}', '…', 12) AS snippet, \
+             bm25(search_index) AS rank \
+         FROM search_index WHERE search_index MATCH ? \
+         UNION ALL \
+         SELECT 'mail', message_id, thread_id, subject, \
+             snippet(mail_messages_fts, -1, '{start}', '{end}', '…', 12), \
+             bm25(mail_messages_fts) \
+         FROM mail_messages_fts WHERE mail_messages_fts MATCH ? \
+         ORDER BY rank LIMIT ?",
+        start = MATCH_START,
+        end = MATCH_END,
+    );
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let rows: Vec<SearchRow> = diesel::sql_query(sql)
+        .bind::<Text, _>(&expr)
+        .bind::<Text, _>(&expr)
+        .bind::<BigInt, _>(limit)
+        .load(&mut conn)
+        .map_err(|e| format!("Search failed: {}", e))?;
+    Ok(rows.into_iter().filter_map(SearchRow::into_hit).collect())
+}
+
+/// Pages and blocks that share words with `text`, best first; for recalling
+/// notes into an agent's prompt. Unlike `global_search` any word may match.
+pub fn recall_pages(pool: &DbPool, text: &str, limit: i64) -> Result<Vec<SearchHit>, String> {
+    let mut words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= 4)
+        .map(|w| w.to_lowercase())
+        .collect();
+    words.sort();
+    words.dedup();
+    if words.is_empty() {
+        return Ok(vec![]);
+    }
+    let expr = words
+        .iter()
+        .take(16)
+        .map(|w| format!("\"{}\"", w))
+        .collect::<Vec<_>>()
+        .join(" OR ");
+    let sql = format!(
+        "SELECT kind, doc_id, parent_id, context, \
+             snippet(search_index, 4, '{start}', '{end}', '…', 24) AS snippet, \
+             bm25(search_index) AS rank \
+         FROM search_index WHERE search_index MATCH ? AND kind IN ('page', 'block') \
+         ORDER BY rank LIMIT ?",
+        start = MATCH_START,
+        end = MATCH_END,
+    );
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let rows: Vec<SearchRow> = diesel::sql_query(sql)
+        .bind::<Text, _>(&expr)
+        .bind::<BigInt, _>(limit.clamp(1, MAX_SEARCH_LIMIT))
+        .load(&mut conn)
+        .map_err(|e| format!("Search failed: {}", e))?;
+    Ok(rows.into_iter().filter_map(SearchRow::into_hit).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+    use crate::schema::{agents, blocks, messages, pages, sessions};
+
+    fn seed(pool: &DbPool) {
+        let mut conn = pool.get().unwrap();
+        let now = chrono::Utc::now().naive_utc();
+        diesel::insert_into(agents::table)
+            .values((agents::id.eq("a1"), agents::name.eq("Analyst")))
+            .execute(&mut conn)
+            .unwrap();
+        diesel::insert_into(sessions::table)
+            .values((
+                sessions::id.eq("s1"),
+                sessions::agent_id.eq("a1"),
+                sessions::title.eq("Budget review"),
+                sessions::created_at.eq(now),
+                sessions::updated_at.eq(now),
+                sessions::archived.eq(0),
+                sessions::pinned.eq(0),
+            ))
+            .execute(&mut conn)
+            .unwrap();
+        for (id, role, content) in [
+            ("m1", "user", "Draft the quarterly budget memo"),
+            ("m2", "tool", "Tool 'bash' result: budget.xlsx"),
+        ] {
+            diesel::insert_into(messages::table)
+                .values((
+                    messages::id.eq(id),
+                    messages::role.eq(role),
+                    messages::content.eq(content),
+                    messages::session_id.eq("s1"),
+                ))
+                .execute(&mut conn)
+                .unwrap();
+        }
+        diesel::insert_into(pages::table)
+            .values((
+                pages::id.eq("p1"),
+                pages::title.eq("Meeting notes"),
+                pages::type_.eq("page"),
+                pages::is_archived.eq(0),
+                pages::is_published.eq(0),
+                pages::created_at.eq(now),
+                pages::updated_at.eq(now),
+            ))
+            .execute(&mut conn)
+            .unwrap();
+        diesel::insert_into(blocks::table)
+            .values((
+                blocks::id.eq("b1"),
+                blocks::page_id.eq("p1"),
+                blocks::type_.eq("paragraph"),
+                blocks::content_json.eq(r#"{"text": "Budgeting items for Q3"}"#),
+                blocks::order_index.eq(0),
+                blocks::created_at.eq(now),
+                blocks::updated_at.eq(now),
+            ))
+            .execute(&mut conn)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_recall_pages_matches_any_word() {
+        let pool = create_test_pool();
+        seed(&pool);
+
+        let hits = recall_pages(&pool, "What were the budgeting items we discussed?", 5).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].target, SearchTarget::Block { page_id: "p1".into(), block_id: "b1".into() });
+        assert!(recall_pages(&pool, "a b c", 5).unwrap().is_empty());
+    }
+
+    fn targets(pool: &DbPool, query: &str) -> Vec<SearchTarget> {
+        global_search(pool, query, None).unwrap().into_iter().map(|h| h.target).collect()
+    }
+
+    #[test]
+    fn test_finds_sessions_messages_and_blocks() {
+        let pool = create_test_pool();
+        seed(&pool);
+
+        let hits = global_search(&pool, "budg", None).unwrap();
+        assert_eq!(hits.len(), 3);
+        let message = hits
+            .iter()
+            .find(|h| matches!(h.target, SearchTarget::Message { .. }))
+            .unwrap();
+        assert_eq!(
+            message.target,
+            SearchTarget::Message { session_id: "s1".into(), message_id: "m1".into() }
+        );
+        assert_eq!(message.title.as_deref(), Some("Budget review"));
+        assert!(message.snippet.contains("<mark>budget</mark>"));
+        assert!(targets(&pool, "budg").contains(&SearchTarget::Block {
+            page_id: "p1".into(),
+            block_id: "b1".into()
+        }));
+        // Keys of content_json are not indexed
+        assert!(targets(&pool, "text").is_empty());
+        assert!(targets(&pool, "   ").is_empty());
+    }
+
+    #[test]
+    fn test_index_follows_writes() {
+        let pool = create_test_pool();
+        seed(&pool);
+        let mut conn = pool.get().unwrap();
+
+        diesel::update(messages::table.find("m1"))
+            .set(messages::content.eq("Draft the hiring plan"))
+            .execute(&mut conn)
+            .unwrap();
+        assert_eq!(targets(&pool, "hiring").len(), 1);
+        assert!(!targets(&pool, "memo").iter().any(|t| matches!(t, SearchTarget::Message { .. })));
+
+        diesel::update(messages::table.find("m1"))
+            .set(messages::deleted_at.eq(chrono::Utc::now().naive_utc()))
+            .execute(&mut conn)
+            .unwrap();
+        assert!(targets(&pool, "hiring").is_empty());
+
+        diesel::update(pages::table.find("p1"))
+            .set(pages::title.eq("Offsite agenda"))
+            .execute(&mut conn)
+            .unwrap();
+        assert_eq!(targets(&pool, "offsite"), vec![SearchTarget::Page { page_id: "p1".into() }]);
+        let block = global_search(&pool, "Q3", None).unwrap();
+        assert_eq!(block[0].title.as_deref(), Some("Offsite agenda"));
+
+        diesel::delete(blocks::table.find("b1")).execute(&mut conn).unwrap();
+        assert!(targets(&pool, "Q3").is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+//! Named secrets (API keys, tokens) agents can use without seeing them
+//!
+//! Values live in the OS keyring under the `anycowork-secrets` service, one
+//! entry per secret name; the database only keeps names, descriptions and
+//! which agents are granted which secret. A tool call asks for granted
+//! secrets by name and gets them as environment variables of the same name.
+//! Whatever a secret's value shows up in (tool output, events, saved
+//! messages) is redacted to `[REDACTED:<name>]`.
+
+pub mod redact;
+pub mod store;
+
+pub use redact::{RedactingObserver, Redactor};
+pub use store::{KeyringStore, MemoryStore, SecretStore};
+
+use crate::database::DbPool;
+use std::collections::BTreeMap;
+
+const KEYRING: KeyringStore = KeyringStore::new("anycowork-secrets");
+
+pub fn get_secret_value(name: &str) -> Result<Option<String>, String> {
+    KEYRING
+        .get(name)
+        .map_err(|e| format!("Failed to read secret '{}': {}", name, e))
+}
+
+pub fn set_secret_value(name: &str, value: &str) -> Result<(), String> {
+    validate_secret_name(name)?;
+    KEYRING
+        .set(name, value)
+        .map_err(|e| format!("Failed to store secret '{}': {}", name, e))
+}
+
+pub fn delete_secret_value(name: &str) -> Result<(), String> {
+    KEYRING
+        .delete(name)
+        .map_err(|e| format!("Failed to delete secret '{}': {}", name, e))
+}
+
+/// Secret names double as environment variable names
+pub fn validate_secret_name(name: &str) -> Result<(), String> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid secret name '{}' (use letters, digits and '_', not starting with a digit)",
+            name
+        ))
+    }
+}
+
+/// Compare a presented token with the expected one in time that doesn't
+/// depend on where they differ
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// The secrets one agent is granted, with their values
+#[derive(Clone, Default)]
+pub struct AgentSecrets {
+    values: BTreeMap<String, String>,
+    redactor: Redactor,
+}
+
+impl AgentSecrets {
+    /// Granted secrets whose value is in the keyring; the rest are skipped
+    /// with a warning so one missing key does not stop the agent
+    pub fn load(pool: &DbPool, agent_id: &str) -> Self {
+        let names = crate::models::secret::agent_secret_names(pool, agent_id).unwrap_or_else(|e| {
+            log::warn!("Failed to load secret grants of agent {}: {}", agent_id, e);
+            vec![]
+        });
+        let values = names.into_iter().filter_map(|name| match get_secret_value(&name) {
+            Ok(Some(value)) => Some((name, value)),
+            Ok(None) => {
+                log::warn!("Secret '{}' granted to agent {} has no value", name, agent_id);
+                None
+            }
+            Err(e) => {
+                log::warn!("{}", e);
+                None
+            }
+        });
+        Self::from_values(values)
+    }
+
+    pub fn from_values(values: impl IntoIterator<Item = (String, String)>) -> Self {
+        let values: BTreeMap<String, String> = values.into_iter().collect();
+        let redactor = Redactor::new(values.iter().map(|(k, v)| (k.clone(), v.clone())));
+        Self { values, redactor }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.values.keys().cloned().collect()
+    }
+
+    /// Environment variables for the requested secrets; asking for one the
+    /// agent is not granted is an error
+    pub fn env_for(&self, requested: &[String]) -> Result<Vec<(String, String)>, String> {
+        requested
+            .iter()
+            .map(|name| {
+                self.values
+                    .get(name)
+                    .map(|value| (name.clone(), value.clone()))
+                    .ok_or_else(|| format!("Secret '{}' is not available to this agent", name))
+            })
+            .collect()
+    }
+
+    pub fn redactor(&self) -> &Redactor {
+        &self.redactor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_secret_name() {
+        assert!(validate_secret_name("GITHUB_TOKEN").is_ok());
+        assert!(validate_secret_name("_private2").is_ok());
+        assert!(validate_secret_name("").is_err());
+        assert!(validate_secret_name("2FA").is_err());
+        assert!(validate_secret_name("api-key").is_err());
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"s3cret-token", b"s3cret-token"));
+        assert!(!constant_time_eq(b"s3cret-token", b"s3cret-tokeN"));
+        assert!(!constant_time_eq(b"s3cret", b"s3cret-token"));
+        assert!(constant_time_eq(b"", b""));
+    }
+
+    #[test]
+    fn test_env_only_for_granted_secrets() {
+        let secrets = AgentSecrets::from_values([("GITHUB_TOKEN".to_string(), "ghp_abcdef123".to_string())]);
+        assert_eq!(
+            secrets.env_for(&["GITHUB_TOKEN".to_string()]).unwrap(),
+            vec![("GITHUB_TOKEN".to_string(), "ghp_abcdef123".to_string())]
+        );
+        assert!(secrets.env_for(&["AWS_SECRET".to_string()]).is_err());
+        assert!(secrets.env_for(&[]).unwrap().is_empty());
+    }
+}
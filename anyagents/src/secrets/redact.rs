@@ -0,0 +1,120 @@
+//! Scrubbing secret values out of text before it leaves the agent loop
+
+use crate::events::AgentObserver;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::sync::Arc;
+
+/// Values shorter than this are not redacted: they would match ordinary text
+const MIN_REDACTED_LEN: usize = 6;
+
+#[derive(Clone, Default)]
+pub struct Redactor {
+    /// (name, value), longest value first so a secret containing another is
+    /// replaced whole
+    secrets: Vec<(String, String)>,
+}
+
+impl Redactor {
+    pub fn new(secrets: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut secrets: Vec<(String, String)> = secrets
+            .into_iter()
+            .filter(|(_, value)| value.len() >= MIN_REDACTED_LEN)
+            .collect();
+        secrets.sort_by_key(|(_, value)| std::cmp::Reverse(value.len()));
+        Self { secrets }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.secrets.is_empty()
+    }
+
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut out = Cow::Borrowed(text);
+        for (name, value) in &self.secrets {
+            if out.contains(value.as_str()) {
+                out = Cow::Owned(out.replace(value.as_str(), &format!("[REDACTED:{}]", name)));
+            }
+        }
+        out
+    }
+
+    /// Redact every string in a JSON value, keys included
+    pub fn redact_value(&self, value: &mut Value) {
+        if self.is_empty() {
+            return;
+        }
+        match value {
+            Value::String(s) => {
+                if let Cow::Owned(redacted) = self.redact(s) {
+                    *s = redacted;
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|v| self.redact_value(v)),
+            Value::Object(map) => {
+                let entries = std::mem::take(map);
+                for (key, mut v) in entries {
+                    self.redact_value(&mut v);
+                    map.insert(self.redact(&key).into_owned(), v);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Forwards events with secret values redacted
+pub struct RedactingObserver {
+    inner: Arc<dyn AgentObserver>,
+    redactor: Redactor,
+}
+
+impl RedactingObserver {
+    /// `inner` itself when there is nothing to redact
+    pub fn wrap(inner: Arc<dyn AgentObserver>, redactor: Redactor) -> Arc<dyn AgentObserver> {
+        if redactor.is_empty() {
+            inner
+        } else {
+            Arc::new(Self { inner, redactor })
+        }
+    }
+}
+
+impl AgentObserver for RedactingObserver {
+    fn emit(&self, event: &str, mut payload: Value) -> Result<(), String> {
+        self.redactor.redact_value(&mut payload);
+        self.inner.emit(event, payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redact_text_and_json() {
+        let redactor = Redactor::new([
+            ("TOKEN".to_string(), "abc123secret".to_string()),
+            ("SHORT".to_string(), "abc".to_string()),
+        ]);
+        assert_eq!(redactor.redact("key=abc123secret; abc"), "key=[REDACTED:TOKEN]; abc");
+        assert!(matches!(redactor.redact("nothing here"), Cow::Borrowed(_)));
+
+        let mut value = json!({"stdout": "abc123secret\n", "args": ["--token", "abc123secret"], "code": 0});
+        redactor.redact_value(&mut value);
+        assert_eq!(
+            value,
+            json!({"stdout": "[REDACTED:TOKEN]\n", "args": ["--token", "[REDACTED:TOKEN]"], "code": 0})
+        );
+    }
+
+    #[test]
+    fn test_longest_value_wins() {
+        let redactor = Redactor::new([
+            ("INNER".to_string(), "secret".to_string()),
+            ("OUTER".to_string(), "supersecret".to_string()),
+        ]);
+        assert_eq!(redactor.redact("supersecret secret"), "[REDACTED:OUTER] [REDACTED:INNER]");
+    }
+}
@@ -0,0 +1,74 @@
+//! Where secret values are kept
+//!
+//! Every secret of the app (named agent secrets, MCP server secrets, mailbox
+//! passwords, the database key) goes through a `SecretStore`: the OS keyring
+//! in the app, one service per kind of secret, or `MemoryStore` in tests.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+pub trait SecretStore: Send + Sync {
+    fn get(&self, key: &str) -> Result<Option<String>, String>;
+    fn set(&self, key: &str, value: &str) -> Result<(), String>;
+    /// Deleting a key that isn't there is not an error
+    fn delete(&self, key: &str) -> Result<(), String>;
+}
+
+/// The OS keyring (Keychain, Credential Manager, Secret Service), one entry
+/// per key under `service`
+pub struct KeyringStore {
+    service: &'static str,
+}
+
+impl KeyringStore {
+    pub const fn new(service: &'static str) -> Self {
+        Self { service }
+    }
+
+    fn entry(&self, key: &str) -> Result<keyring::Entry, String> {
+        keyring::Entry::new(self.service, key).map_err(|e| format!("Keyring unavailable: {}", e))
+    }
+}
+
+impl SecretStore for KeyringStore {
+    fn get(&self, key: &str) -> Result<Option<String>, String> {
+        match self.entry(key)?.get_password() {
+            Ok(value) => Ok(Some(value)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), String> {
+        self.entry(key)?.set_password(value).map_err(|e| e.to_string())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), String> {
+        match self.entry(key)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// In-process store, for tests and environments without a keyring
+#[derive(Default)]
+pub struct MemoryStore {
+    values: Mutex<HashMap<String, String>>,
+}
+
+impl SecretStore for MemoryStore {
+    fn get(&self, key: &str) -> Result<Option<String>, String> {
+        Ok(self.values.lock().unwrap().get(key).cloned())
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), String> {
+        self.values.lock().unwrap().insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), String> {
+        self.values.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
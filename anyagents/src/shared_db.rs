@@ -0,0 +1,284 @@
+//! One database for a server shared by several users
+//!
+//! The app keeps its data in a SQLite file (see `database`). A server
+//! deployment can point `DATABASE_URL` at Postgres instead: `open_shared`
+//! picks the backend from the URL, applies the matching migration set
+//! (`src-tauri/migrations` or `src-tauri/migrations-postgres`) and hands out
+//! `AnyConnection`s. The queries below run unchanged on either backend, with
+//! the same models and schema as the rest of the crate.
+//!
+//! Full-text search (`search_index`, `mail_messages_fts`) stays SQLite-only.
+
+use crate::models::{Agent, Message, NewAgent, NewMessage, NewSession, Session};
+use crate::schema::{agents, messages, sessions};
+use diesel::connection::SimpleConnection;
+use diesel::prelude::*;
+use diesel::r2d2::{self, ConnectionManager, CustomizeConnection};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+
+const SQLITE_MIGRATIONS: EmbeddedMigrations = embed_migrations!("../src-tauri/migrations");
+const POSTGRES_MIGRATIONS: EmbeddedMigrations = embed_migrations!("../src-tauri/migrations-postgres");
+
+/// A connection to whichever backend `DATABASE_URL` names
+#[derive(diesel::MultiConnection)]
+pub enum AnyConnection {
+    Sqlite(SqliteConnection),
+    Postgres(diesel::PgConnection),
+}
+
+pub type SharedPool = r2d2::Pool<ConnectionManager<AnyConnection>>;
+
+/// Which backend a `DATABASE_URL` points at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatabaseKind {
+    Sqlite,
+    Postgres,
+}
+
+impl DatabaseKind {
+    /// `postgres://` and `postgresql://` URLs are Postgres, anything else
+    /// (a `sqlite://` URL or a plain path) is SQLite
+    pub fn from_url(database_url: &str) -> Self {
+        if database_url.starts_with("postgres://") || database_url.starts_with("postgresql://") {
+            DatabaseKind::Postgres
+        } else {
+            DatabaseKind::Sqlite
+        }
+    }
+}
+
+/// SQLite connections get the same WAL and busy timeout as the app's pool
+#[derive(Debug)]
+struct SharedConnectionOptions;
+
+impl CustomizeConnection<AnyConnection, r2d2::Error> for SharedConnectionOptions {
+    fn on_acquire(&self, conn: &mut AnyConnection) -> Result<(), r2d2::Error> {
+        if let AnyConnection::Sqlite(conn) = conn {
+            conn.batch_execute(&format!(
+                "PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL; PRAGMA busy_timeout = {};",
+                crate::database::BUSY_TIMEOUT_MS
+            ))
+            .map_err(r2d2::Error::QueryError)?;
+        }
+        Ok(())
+    }
+}
+
+/// Open the database at `database_url`, SQLite or Postgres, and apply all
+/// pending migrations of that backend
+pub fn open_shared(database_url: &str) -> Result<SharedPool, String> {
+    let pool = r2d2::Pool::builder()
+        .connection_customizer(Box::new(SharedConnectionOptions))
+        .build(ConnectionManager::<AnyConnection>::new(database_url))
+        .map_err(|e| format!("Failed to create pool: {}", e))?;
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let applied = match &mut *conn {
+        AnyConnection::Sqlite(conn) => conn.run_pending_migrations(SQLITE_MIGRATIONS).map(|_| ()),
+        AnyConnection::Postgres(conn) => conn.run_pending_migrations(POSTGRES_MIGRATIONS).map(|_| ()),
+    };
+    applied.map_err(|e| format!("Failed to run migrations: {}", e))?;
+
+    Ok(pool)
+}
+
+pub fn kind(conn: &AnyConnection) -> DatabaseKind {
+    match conn {
+        AnyConnection::Sqlite(_) => DatabaseKind::Sqlite,
+        AnyConnection::Postgres(_) => DatabaseKind::Postgres,
+    }
+}
+
+pub fn list_agents(conn: &mut AnyConnection) -> Result<Vec<Agent>, String> {
+    agents::table
+        .order(agents::created_at.asc())
+        .select(Agent::as_select())
+        .load(conn)
+        .map_err(|e| e.to_string())
+}
+
+pub fn find_agent(conn: &mut AnyConnection, agent_id: &str) -> Result<Option<Agent>, String> {
+    agents::table
+        .find(agent_id)
+        .select(Agent::as_select())
+        .first(conn)
+        .optional()
+        .map_err(|e| e.to_string())
+}
+
+pub fn create_agent(conn: &mut AnyConnection, agent: &NewAgent) -> Result<Agent, String> {
+    diesel::insert_into(agents::table)
+        .values(agent)
+        .execute(conn)
+        .map_err(|e| e.to_string())?;
+    find_agent(conn, &agent.id)?.ok_or_else(|| "Agent not found".to_string())
+}
+
+/// Sessions, pinned first and then the most recently active
+pub fn list_sessions(conn: &mut AnyConnection, agent_id: Option<&str>, limit: Option<i64>) -> Result<Vec<Session>, String> {
+    let mut query = sessions::table
+        .order((sessions::pinned.desc(), sessions::updated_at.desc()))
+        .select(Session::as_select())
+        .into_boxed();
+    if let Some(agent_id) = agent_id {
+        query = query.filter(sessions::agent_id.eq(agent_id.to_string()));
+    }
+    if let Some(limit) = limit {
+        query = query.limit(limit);
+    }
+    query.load(conn).map_err(|e| e.to_string())
+}
+
+pub fn find_session(conn: &mut AnyConnection, session_id: &str) -> Result<Option<Session>, String> {
+    sessions::table
+        .find(session_id)
+        .select(Session::as_select())
+        .first(conn)
+        .optional()
+        .map_err(|e| e.to_string())
+}
+
+pub fn create_session(conn: &mut AnyConnection, session: &NewSession) -> Result<Session, String> {
+    diesel::insert_into(sessions::table)
+        .values(session)
+        .execute(conn)
+        .map_err(|e| e.to_string())?;
+    find_session(conn, &session.id)?.ok_or_else(|| "Session not found".to_string())
+}
+
+/// Messages of a session in order, without the ones rewound away
+pub fn list_messages(conn: &mut AnyConnection, session_id: &str) -> Result<Vec<Message>, String> {
+    messages::table
+        .filter(messages::session_id.eq(session_id))
+        .filter(messages::deleted_at.is_null())
+        .order(messages::created_at.asc())
+        .select(Message::as_select())
+        .load(conn)
+        .map_err(|e| e.to_string())
+}
+
+/// Add a message and touch its session so it sorts as the latest
+pub fn add_message(conn: &mut AnyConnection, message: &NewMessage) -> Result<(), String> {
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        diesel::insert_into(messages::table).values(message).execute(conn)?;
+        diesel::update(sessions::table.find(&message.session_id))
+            .set(sessions::updated_at.eq(chrono::Utc::now().naive_utc()))
+            .execute(conn)?;
+        Ok(())
+    })
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_agent(name: &str) -> NewAgent {
+        let now = chrono::Utc::now().timestamp();
+        NewAgent {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            description: None,
+            status: "active".to_string(),
+            personality: None,
+            tone: None,
+            expertise: None,
+            ai_provider: "openai".to_string(),
+            ai_model: "gpt-4o".to_string(),
+            ai_temperature: 0.5,
+            ai_config: "{}".to_string(),
+            system_prompt: Some("You help.".to_string()),
+            permissions: None,
+            working_directories: None,
+            skills: None,
+            mcp_servers: None,
+            messaging_connections: None,
+            knowledge_bases: None,
+            api_keys: None,
+            created_at: now,
+            updated_at: now,
+            platform_configs: None,
+            execution_settings: None,
+            scope_type: None,
+            workspace_path: None,
+            avatar: None,
+            agent_tools: None,
+        }
+    }
+
+    /// Agents, sessions and messages round-trip through the shared queries
+    fn exercise(pool: &SharedPool) {
+        let mut conn = pool.get().unwrap();
+        let agent = create_agent(&mut conn, &new_agent("Ops")).unwrap();
+        assert_eq!(agent.ai_temperature, 0.5);
+        assert!(list_agents(&mut conn).unwrap().iter().any(|a| a.id == agent.id));
+
+        let now = chrono::Utc::now().naive_utc();
+        let session = create_session(
+            &mut conn,
+            &NewSession {
+                id: uuid::Uuid::new_v4().to_string(),
+                agent_id: agent.id.clone(),
+                title: Some("Deploy".to_string()),
+                created_at: now,
+                updated_at: now,
+                archived: 0,
+                pinned: 0,
+            },
+        )
+        .unwrap();
+        assert_eq!(list_sessions(&mut conn, Some(&agent.id), Some(10)).unwrap().len(), 1);
+        assert!(list_sessions(&mut conn, Some("missing"), None).unwrap().is_empty());
+
+        for (role, content) in [("user", "Ship it"), ("assistant", "Shipped")] {
+            add_message(
+                &mut conn,
+                &NewMessage {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    role: role.to_string(),
+                    content: content.to_string(),
+                    session_id: session.id.clone(),
+                    metadata_json: None,
+                    tokens: Some(3),
+                },
+            )
+            .unwrap();
+        }
+        let history = list_messages(&mut conn, &session.id).unwrap();
+        assert_eq!(history.iter().map(|m| m.content.as_str()).collect::<Vec<_>>(), ["Ship it", "Shipped"]);
+        assert!(find_session(&mut conn, &session.id).unwrap().unwrap().updated_at >= session.updated_at);
+        assert!(find_agent(&mut conn, "missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_database_kind() {
+        assert_eq!(DatabaseKind::from_url("postgres://db/anycowork"), DatabaseKind::Postgres);
+        assert_eq!(DatabaseKind::from_url("postgresql://db/anycowork"), DatabaseKind::Postgres);
+        assert_eq!(DatabaseKind::from_url("sqlite:///tmp/a.db"), DatabaseKind::Sqlite);
+        assert_eq!(DatabaseKind::from_url("/tmp/a.db"), DatabaseKind::Sqlite);
+    }
+
+    #[test]
+    fn test_shared_queries_on_sqlite() {
+        let path = std::env::temp_dir().join(format!("anycowork_shared_{}.db", uuid::Uuid::new_v4()));
+        let pool = open_shared(&path.to_string_lossy()).unwrap();
+        assert_eq!(kind(&pool.get().unwrap()), DatabaseKind::Sqlite);
+        exercise(&pool);
+    }
+
+    /// Needs a scratch database, e.g.
+    /// `ANYCOWORK_TEST_POSTGRES_URL=postgres://localhost/anycowork_test`;
+    /// without one the Postgres half is skipped
+    #[test]
+    fn test_shared_queries_on_postgres() {
+        let Ok(url) = std::env::var("ANYCOWORK_TEST_POSTGRES_URL") else {
+            eprintln!("ANYCOWORK_TEST_POSTGRES_URL is not set, skipping the Postgres run");
+            return;
+        };
+        let pool = open_shared(&url).unwrap();
+        assert_eq!(kind(&pool.get().unwrap()), DatabaseKind::Postgres);
+        exercise(&pool);
+        // Applying the migrations again is a no-op
+        open_shared(&url).unwrap();
+    }
+}
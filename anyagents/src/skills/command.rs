@@ -0,0 +1,122 @@
+//! Command templates for skills with structured parameters
+//!
+//! A template such as
+//! `python3 /skill/convert.py {{ input }}{% if pages %} --pages {{ pages }}{% endif %}`
+//! is rendered with minijinja, but every value printed by `{{ ... }}` goes
+//! through a formatter that shell-quotes it, so arguments can never break out
+//! into the surrounding command. Lists expand to one quoted word per item.
+//! `{{ skill_dir }}` is where the skill's files are for the chosen runtime.
+
+use jsonschema::JSONSchema;
+use minijinja::value::ValueKind;
+use minijinja::{Environment, Error, ErrorKind, Value as TemplateValue};
+use serde_json::Value;
+
+/// Quote `value` as a single POSIX shell word
+pub fn shell_quote(value: &str) -> String {
+    if !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c))
+    {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn quote_value(value: &TemplateValue) -> String {
+    if value.is_undefined() || value.is_none() {
+        return String::new();
+    }
+    match value.kind() {
+        ValueKind::Seq => value
+            .try_iter()
+            .map(|items| items.map(|item| quote_value(&item)).collect::<Vec<_>>().join(" "))
+            .unwrap_or_default(),
+        _ => match value.as_str() {
+            Some(s) => shell_quote(s),
+            None => shell_quote(&value.to_string()),
+        },
+    }
+}
+
+fn environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_formatter(|out, _state, value| {
+        out.write_str(&quote_value(value))
+            .map_err(|_| Error::new(ErrorKind::WriteFailure, "failed to write command"))
+    });
+    env
+}
+
+/// Fail early (at parse time) on a template that cannot render
+pub fn check_template(template: &str) -> Result<(), String> {
+    environment()
+        .template_from_str(template)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid command template: {}", e))
+}
+
+/// Render `template` with the call's arguments
+pub fn render_command(template: &str, args: &Value) -> Result<String, String> {
+    let env = environment();
+    let template = env
+        .template_from_str(template)
+        .map_err(|e| format!("Invalid command template: {}", e))?;
+    let rendered = template
+        .render(TemplateValue::from_serialize(args))
+        .map_err(|e| format!("Failed to render command: {}", e))?;
+    Ok(rendered.trim().to_string())
+}
+
+/// Check arguments against the declared schema
+pub fn validate(schema: &Value, args: &Value) -> Result<(), String> {
+    let compiled = JSONSchema::compile(schema).map_err(|e| format!("Invalid parameter schema: {}", e))?;
+    let result = compiled.validate(args);
+    if let Err(errors) = result {
+        let messages: Vec<String> = errors.map(|e| e.to_string()).collect();
+        return Err(format!("Invalid arguments: {}", messages.join(", ")));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("report.pdf"), "report.pdf");
+        assert_eq!(shell_quote("my report.pdf"), "'my report.pdf'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote("$(rm -rf ~)"), "'$(rm -rf ~)'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn test_render_quotes_every_value() {
+        let template = "python3 /skill/convert.py {{ input }}{% if pages %} --pages {{ pages }}{% endif %} {{ tags }}";
+        let rendered = render_command(
+            template,
+            &json!({ "input": "a b.pdf; rm -rf /", "pages": 3, "tags": ["x", "y z"] }),
+        )
+        .unwrap();
+        assert_eq!(rendered, "python3 /skill/convert.py 'a b.pdf; rm -rf /' --pages 3 x 'y z'");
+
+        let without_pages = render_command(template, &json!({ "input": "in.pdf" })).unwrap();
+        assert_eq!(without_pages, "python3 /skill/convert.py in.pdf");
+    }
+
+    #[test]
+    fn test_validate() {
+        let schema = json!({
+            "type": "object",
+            "properties": { "input": { "type": "string" } },
+            "required": ["input"]
+        });
+        assert!(validate(&schema, &json!({ "input": "x" })).is_ok());
+        assert!(validate(&schema, &json!({})).is_err());
+        assert!(check_template("{% if %}").is_err());
+    }
+}
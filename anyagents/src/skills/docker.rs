@@ -71,7 +71,23 @@ impl DockerSandbox {
         }
     }
 
+    /// Version of the Docker daemon; Err when the CLI is missing or the
+    /// daemon isn't running
+    pub async fn version() -> Result<String, String> {
+        let output = Command::new("docker")
+            .args(["version", "--format", "{{.Server.Version}}"])
+            .output()
+            .await
+            .map_err(|e| format!("Docker not found: {}", e))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("Docker daemon is not running: {}", stderr.trim()));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
     /// Initialize the sandbox, checking Docker availability
+    #[tracing::instrument(name = "sandbox.startup", skip_all, fields(backend = "docker"))]
     pub async fn init(&mut self) {
         self.docker_available = Self::check_available().await;
     }
@@ -88,6 +104,11 @@ impl DockerSandbox {
     /// * `workspace_path` - Path to mount as /workspace (read-write)
     /// * `skill_files_path` - Optional path to mount as /skill (read-only)
     /// * `config` - Sandbox configuration
+    #[tracing::instrument(
+        name = "sandbox.execute",
+        skip_all,
+        fields(backend = "docker", image = tracing::field::Empty, exit_code = tracing::field::Empty)
+    )]
     pub async fn execute(
         &self,
         command: &str,
@@ -105,6 +126,7 @@ impl DockerSandbox {
             .as_ref()
             .map(|s| DockerImage::from_string(s).to_image_name())
             .unwrap_or_else(|| DockerImage::Python311.to_image_name());
+        tracing::Span::current().record("image", image.as_str());
 
         // Build docker run command
         let mut args = vec![
@@ -151,6 +173,13 @@ impl DockerSandbox {
             args.push(format!("{}:/skill:ro", skill_abs.display()));
         }
 
+        // Extra environment by name only; docker takes the values from its own
+        // environment, so they never show up in the process arguments
+        for (name, _) in &config.env {
+            args.push("-e".to_string());
+            args.push(name.clone());
+        }
+
         // Working directory
         args.push("-w".to_string());
         args.push("/workspace".to_string());
@@ -169,6 +198,7 @@ impl DockerSandbox {
         // Execute
         let output = Command::new("docker")
             .args(&args)
+            .envs(config.env.iter().cloned())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .output()
@@ -177,6 +207,7 @@ impl DockerSandbox {
 
         let exit_code = output.status.code().unwrap_or(-1);
         let timed_out = exit_code == 124; // timeout command returns 124 on timeout
+        tracing::Span::current().record("exit_code", exit_code);
 
         Ok(ExecutionResult {
             success: output.status.success(),
@@ -314,6 +345,8 @@ mod tests {
             cpu_limit: None,
             timeout_seconds: Some(10),
             network_enabled: Some(false),
+            runtime: None,
+            ..Default::default()
         };
 
         let temp_dir = tempfile::tempdir().unwrap();
@@ -0,0 +1,301 @@
+//! Skill test harness: runs the `examples:` declared in SKILL.md
+//!
+//! Each example gets a fresh temp workspace and runs through `SkillTool::run`,
+//! so it uses the same runtime selection (Docker, microVM, WASM or local),
+//! dependency provisioning and command rendering as a real agent call. A dry
+//! run stops after validating the arguments and rendering the command.
+//! Recipe skills run through `SkillTool::execute`; a failed step counts as
+//! exit code 1 and the recipe outcome (JSON) is the output.
+
+use crate::events::AgentObserver;
+use crate::models::SkillExample;
+use crate::permissions::{PermissionManager, PermissionResponse};
+use crate::skills::docker::ExecutionResult;
+use crate::skills::loader::LoadedSkill;
+use crate::skills::tool::{SkillRuntime, SkillTool};
+use crate::tools::{Tool, ToolContext};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Output kept per example; enough to diagnose a failure
+const MAX_OUTPUT_CHARS: usize = 4000;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ExampleResult {
+    pub name: String,
+    pub passed: bool,
+    /// The rendered shell command, with skill files shown at /skill
+    pub command: Option<String>,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration_ms: u64,
+    /// Why the example failed, in one line
+    pub failure: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SkillTestReport {
+    pub skill: String,
+    pub runtime: Option<SkillRuntime>,
+    pub dry_run: bool,
+    pub passed: bool,
+    pub results: Vec<ExampleResult>,
+}
+
+/// Examples run in a throwaway workspace at the user's request, so the
+/// permission prompts raised by recipe steps are approved
+struct ApproveExamples {
+    permissions: Arc<PermissionManager>,
+}
+
+impl AgentObserver for ApproveExamples {
+    fn emit(&self, _event: &str, payload: serde_json::Value) -> Result<(), String> {
+        if payload["type"] == "permission_request" {
+            if let Some(id) = payload["request"]["id"].as_str() {
+                self.permissions.respond_as(id, PermissionResponse::Allow, "skill_test");
+            }
+        }
+        Ok(())
+    }
+}
+
+fn truncate(output: String) -> String {
+    if output.chars().count() <= MAX_OUTPUT_CHARS {
+        output
+    } else {
+        let kept: String = output.chars().take(MAX_OUTPUT_CHARS).collect();
+        format!("{}\n... (truncated)", kept)
+    }
+}
+
+async fn run_example(skill: &LoadedSkill, execution_mode: &str, example: &SkillExample, dry_run: bool) -> ExampleResult {
+    let started = Instant::now();
+    let mut result = ExampleResult {
+        name: example.name.clone(),
+        passed: false,
+        command: None,
+        exit_code: None,
+        stdout: String::new(),
+        stderr: String::new(),
+        duration_ms: 0,
+        failure: None,
+    };
+
+    let workspace = match tempfile::tempdir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            result.failure = Some(format!("Failed to create workspace: {}", e));
+            return result;
+        }
+    };
+    let tool = SkillTool::new(skill.clone(), workspace.path().to_path_buf(), execution_mode.to_string());
+
+    if let Err(e) = tool.validate_args(&example.args).await {
+        result.failure = Some(e);
+        return result;
+    }
+    // Recipe steps are templated from earlier results, so only commands render up front
+    if skill.skill.recipe.is_none() {
+        match tool.render_command(&example.args, "/skill") {
+            Ok(command) => result.command = Some(command),
+            Err(e) => {
+                result.failure = Some(e);
+                return result;
+            }
+        }
+    }
+    if dry_run {
+        result.passed = true;
+        return result;
+    }
+
+    let permissions = Arc::new(PermissionManager::new());
+    let ctx = ToolContext {
+        permissions: permissions.clone(),
+        observer: Some(Arc::new(ApproveExamples { permissions })),
+        session_id: format!("skill-test-{}", uuid::Uuid::new_v4()),
+        scope: Default::default(),
+        job_id: None,
+    };
+    let outcome = if skill.skill.recipe.is_some() {
+        // A recipe passes (exit 0) when every step does; its outcome is the output
+        Ok(match tool.execute(example.args.clone(), &ctx).await {
+            Ok(outcome) => ExecutionResult {
+                success: true,
+                stdout: serde_json::to_string_pretty(&outcome).unwrap_or_default(),
+                stderr: String::new(),
+                exit_code: 0,
+                timed_out: false,
+            },
+            Err(e) => ExecutionResult {
+                success: false,
+                stdout: String::new(),
+                stderr: e,
+                exit_code: 1,
+                timed_out: false,
+            },
+        })
+    } else {
+        tool.run(&example.args, &ctx).await
+    };
+    result.duration_ms = started.elapsed().as_millis() as u64;
+
+    match outcome {
+        Ok(output) => {
+            result.exit_code = Some(output.exit_code);
+            result.failure = if output.timed_out {
+                Some("Timed out".to_string())
+            } else if output.exit_code != example.expect_exit {
+                Some(format!("Exit code {} (expected {})", output.exit_code, example.expect_exit))
+            } else {
+                match &example.expect_stdout {
+                    Some(expected) if !output.stdout.contains(expected.as_str()) => {
+                        Some(format!("Output does not contain '{}'", expected))
+                    }
+                    _ => None,
+                }
+            };
+            result.passed = result.failure.is_none();
+            result.stdout = truncate(output.stdout);
+            result.stderr = truncate(output.stderr);
+        }
+        Err(e) => result.failure = Some(e),
+    }
+    result
+}
+
+/// Run every example of `skill` and report pass/fail per example
+pub async fn test_skill(skill: &LoadedSkill, execution_mode: &str, dry_run: bool) -> SkillTestReport {
+    let probe = SkillTool::new(skill.clone(), std::env::temp_dir(), execution_mode.to_string());
+    let runtime = probe.resolve_runtime().await;
+
+    let mut results = Vec::new();
+    if let Err(e) = &runtime {
+        // No runtime means nothing can run; report it once per example
+        for example in &skill.skill.examples {
+            results.push(ExampleResult {
+                name: example.name.clone(),
+                passed: false,
+                command: None,
+                exit_code: None,
+                stdout: String::new(),
+                stderr: String::new(),
+                duration_ms: 0,
+                failure: Some(e.clone()),
+            });
+        }
+    } else {
+        for example in &skill.skill.examples {
+            results.push(run_example(skill, execution_mode, example, dry_run).await);
+        }
+    }
+
+    SkillTestReport {
+        skill: skill.skill.name.clone(),
+        runtime: runtime.ok(),
+        dry_run,
+        passed: !results.is_empty() && results.iter().all(|r| r.passed),
+        results,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skills::parser::parse_skill_md;
+    use std::collections::HashMap;
+
+    fn skill(content: &str) -> LoadedSkill {
+        LoadedSkill {
+            skill: parse_skill_md(content).unwrap(),
+            files: HashMap::new(),
+        }
+    }
+
+    const GREETER: &str = r#"---
+name: greeter
+description: Says hello
+examples:
+  - name: greets
+    command: echo hello
+    expect_stdout: hello
+  - name: wrong exit code
+    command: exit 3
+  - name: wrong output
+    command: echo bye
+    expect_stdout: hello
+---
+# Greeter
+"#;
+
+    #[tokio::test]
+    async fn test_runs_examples_locally() {
+        let report = test_skill(&skill(GREETER), "direct", false).await;
+        assert_eq!(report.runtime, Some(SkillRuntime::Local));
+        assert!(!report.passed);
+
+        assert!(report.results[0].passed);
+        assert_eq!(report.results[0].exit_code, Some(0));
+        assert_eq!(report.results[1].failure.as_deref(), Some("Exit code 3 (expected 0)"));
+        assert!(report.results[2].failure.as_deref().unwrap().contains("does not contain"));
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_renders_without_running() {
+        let content = r#"---
+name: convert
+description: Convert documents
+command: python3 {{ skill_dir }}/convert.py {{ input }}
+parameters:
+  input:
+    type: string
+    required: true
+examples:
+  - name: converts
+    args: {"input": "my file.pdf"}
+  - name: missing input
+    args: {}
+---
+# Convert
+"#;
+        let report = test_skill(&skill(content), "direct", true).await;
+        assert!(report.dry_run);
+        assert_eq!(
+            report.results[0].command.as_deref(),
+            Some("python3 /skill/convert.py 'my file.pdf'")
+        );
+        assert!(report.results[0].passed);
+        assert!(!report.results[1].passed);
+        assert!(report.results[1].exit_code.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_recipe_examples() {
+        let content = r#"---
+name: shout
+description: Upper-case a word
+parameters:
+  word:
+    type: string
+    required: true
+recipe:
+  - id: upper
+    tool: bash
+    args: {"command": "echo {{ word }} | tr a-z A-Z"}
+examples:
+  - name: shouts
+    args: {"word": "hello"}
+    expect_stdout: HELLO
+  - name: needs a word
+    args: {}
+---
+# Shout
+"#;
+        let report = test_skill(&skill(content), "direct", false).await;
+        assert!(report.results[0].passed, "{:?}", report.results[0]);
+        assert!(report.results[0].command.is_none());
+        assert!(!report.results[1].passed);
+    }
+}
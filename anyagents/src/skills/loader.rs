@@ -1,6 +1,6 @@
 //! Skill loader for loading skills from filesystem (directories and ZIP files)
 
-use crate::models::ParsedSkill;
+use crate::models::{AgentSkill, ParsedSkill, SkillFile};
 use crate::skills::parser::parse_skill_md;
 use std::collections::HashMap;
 use std::fs;
@@ -8,12 +8,68 @@ use std::io::Read;
 use std::path::Path;
 
 /// Loaded skill with all its files
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LoadedSkill {
     pub skill: ParsedSkill,
     pub files: HashMap<String, SkillFileContent>,
 }
 
+impl LoadedSkill {
+    /// Rebuild an installed skill from its database row and stored files
+    pub fn from_db(skill_db: &AgentSkill, files: Vec<SkillFile>) -> Self {
+        let files = files
+            .into_iter()
+            .map(|f| {
+                (
+                    f.relative_path,
+                    SkillFileContent {
+                        content: f.content,
+                        file_type: f.file_type,
+                    },
+                )
+            })
+            .collect();
+
+        let skill = ParsedSkill {
+            name: skill_db.name.clone(),
+            description: skill_db.description.clone(),
+            license: None,
+            triggers: skill_db
+                .triggers
+                .as_ref()
+                .and_then(|t| serde_json::from_str::<Vec<String>>(t).ok()),
+            sandbox_config: skill_db
+                .sandbox_config
+                .as_ref()
+                .and_then(|sc| serde_json::from_str(sc).ok()),
+            body: skill_db.skill_content.clone(),
+            category: skill_db.category.clone(),
+            requires_sandbox: skill_db.requires_sandbox == 1,
+            read_only: skill_db.read_only == 1,
+            execution_mode: Some(skill_db.execution_mode.clone()),
+            dependencies: skill_db
+                .dependencies
+                .as_ref()
+                .and_then(|d| serde_json::from_str(d).ok()),
+            invocation: skill_db
+                .invocation
+                .as_ref()
+                .and_then(|i| serde_json::from_str(i).ok()),
+            examples: skill_db
+                .examples
+                .as_ref()
+                .and_then(|e| serde_json::from_str(e).ok())
+                .unwrap_or_default(),
+            recipe: skill_db
+                .recipe
+                .as_ref()
+                .and_then(|r| serde_json::from_str(r).ok()),
+        };
+
+        Self { skill, files }
+    }
+}
+
 /// Content of a skill file
 #[derive(Debug, Clone)]
 pub struct SkillFileContent {
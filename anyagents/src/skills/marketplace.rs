@@ -0,0 +1,308 @@
+//! Remote skill marketplace client
+//!
+//! The marketplace is a JSON index served over HTTPS next to a detached
+//! Ed25519 signature (`<index url>.sig`, hex). Each skill lists its published
+//! versions with a zip URL and the zip's SHA-256, so a download is only
+//! installed when both the index signature and the checksum verify.
+
+use crate::database::DbPool;
+use crate::models::settings::{get_setting, set_setting};
+use crate::skills::loader::{load_skill_from_zip, LoadedSkill};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::io::Write;
+
+const INDEX_URL_KEY: &str = "skill_marketplace_url";
+const PUBLIC_KEY_KEY: &str = "skill_marketplace_public_key";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MarketplaceConfig {
+    pub index_url: Option<String>,
+    /// Hex-encoded Ed25519 public key the index is signed with
+    pub public_key: Option<String>,
+}
+
+impl MarketplaceConfig {
+    pub fn load(pool: &DbPool) -> Self {
+        Self {
+            index_url: get_setting(pool, INDEX_URL_KEY).filter(|v| !v.is_empty()),
+            public_key: get_setting(pool, PUBLIC_KEY_KEY).filter(|v| !v.is_empty()),
+        }
+    }
+
+    pub fn save(&self, pool: &DbPool) -> Result<(), String> {
+        if let Some(url) = &self.index_url {
+            if !url.is_empty() && !url.starts_with("https://") {
+                return Err("Marketplace index must be served over HTTPS".to_string());
+            }
+        }
+        if let Some(key) = &self.public_key {
+            if !key.is_empty() {
+                parse_public_key(key)?;
+            }
+        }
+        set_setting(pool, INDEX_URL_KEY, self.index_url.as_deref().unwrap_or(""))?;
+        set_setting(pool, PUBLIC_KEY_KEY, self.public_key.as_deref().unwrap_or(""))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RemoteIndex {
+    #[serde(default)]
+    pub skills: Vec<RemoteSkill>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RemoteSkill {
+    pub name: String,
+    #[serde(default)]
+    pub display_title: Option<String>,
+    pub description: String,
+    #[serde(default)]
+    pub category: Option<String>,
+    pub versions: Vec<RemoteSkillVersion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RemoteSkillVersion {
+    pub version: String,
+    pub url: String,
+    /// Hex SHA-256 of the zip at `url`
+    pub sha256: String,
+    #[serde(default)]
+    pub changelog: Option<String>,
+}
+
+impl RemoteSkill {
+    pub fn latest(&self) -> Option<&RemoteSkillVersion> {
+        self.versions
+            .iter()
+            .max_by(|a, b| compare_versions(&a.version, &b.version))
+    }
+
+    /// A specific version, or the latest when `version` is None
+    pub fn version(&self, version: Option<&str>) -> Option<&RemoteSkillVersion> {
+        match version {
+            Some(v) => self.versions.iter().find(|candidate| candidate.version == v),
+            None => self.latest(),
+        }
+    }
+}
+
+impl RemoteIndex {
+    pub fn find(&self, name: &str) -> Option<&RemoteSkill> {
+        self.skills.iter().find(|s| s.name == name)
+    }
+}
+
+/// An installed marketplace skill with a newer published version
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SkillUpdate {
+    pub skill_id: String,
+    pub name: String,
+    pub installed_version: String,
+    pub latest_version: String,
+    pub changelog: Option<String>,
+}
+
+/// Compare dotted versions numerically ("1.10.0" > "1.9.2"); a pre-release
+/// suffix ("1.0.0-beta") sorts before the release
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    fn split(v: &str) -> (Vec<u64>, Option<&str>) {
+        let v = v.trim().trim_start_matches('v');
+        let (core, pre) = match v.split_once('-') {
+            Some((core, pre)) => (core, Some(pre)),
+            None => (v, None),
+        };
+        (core.split('.').map(|p| p.parse().unwrap_or(0)).collect(), pre)
+    }
+
+    let (a_core, a_pre) = split(a);
+    let (b_core, b_pre) = split(b);
+    let len = a_core.len().max(b_core.len());
+    for i in 0..len {
+        let ordering = a_core.get(i).unwrap_or(&0).cmp(b_core.get(i).unwrap_or(&0));
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => a.cmp(b),
+    }
+}
+
+fn parse_public_key(hex_key: &str) -> Result<VerifyingKey, String> {
+    let bytes: [u8; 32] = hex::decode(hex_key.trim())
+        .map_err(|_| "Marketplace public key must be hex".to_string())?
+        .try_into()
+        .map_err(|_| "Marketplace public key must be 32 bytes".to_string())?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| format!("Invalid marketplace public key: {}", e))
+}
+
+/// Check the detached signature over the exact index bytes
+pub fn verify_index(body: &[u8], signature_hex: &str, public_key_hex: &str) -> Result<(), String> {
+    let key = parse_public_key(public_key_hex)?;
+    let signature: [u8; 64] = hex::decode(signature_hex.trim())
+        .map_err(|_| "Index signature must be hex".to_string())?
+        .try_into()
+        .map_err(|_| "Index signature must be 64 bytes".to_string())?;
+    key.verify(body, &Signature::from_bytes(&signature))
+        .map_err(|_| "Marketplace index signature does not match".to_string())
+}
+
+pub fn verify_checksum(bytes: &[u8], expected_hex: &str) -> Result<(), String> {
+    let actual = hex::encode(Sha256::digest(bytes));
+    if actual.eq_ignore_ascii_case(expected_hex.trim()) {
+        Ok(())
+    } else {
+        Err(format!("Checksum mismatch: expected {}, got {}", expected_hex, actual))
+    }
+}
+
+async fn get_bytes(client: &reqwest::Client, url: &str) -> Result<Vec<u8>, String> {
+    if !url.starts_with("https://") {
+        return Err(format!("Refusing to download over plain HTTP: {}", url));
+    }
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("Request to {} failed: {}", url, e))?
+        .error_for_status()
+        .map_err(|e| format!("Request to {} failed: {}", url, e))?;
+    response
+        .bytes()
+        .await
+        .map(|b| b.to_vec())
+        .map_err(|e| format!("Failed to read {}: {}", url, e))
+}
+
+/// Fetch and verify the configured index
+pub async fn fetch_index(config: &MarketplaceConfig) -> Result<RemoteIndex, String> {
+    let url = config
+        .index_url
+        .as_deref()
+        .ok_or("No skill marketplace configured")?;
+    let public_key = config
+        .public_key
+        .as_deref()
+        .ok_or("The skill marketplace has no public key configured, so its index cannot be verified")?;
+
+    let client = reqwest::Client::new();
+    let body = get_bytes(&client, url).await?;
+    let signature = get_bytes(&client, &format!("{}.sig", url)).await?;
+    verify_index(&body, &String::from_utf8_lossy(&signature), public_key)?;
+
+    serde_json::from_slice(&body).map_err(|e| format!("Invalid marketplace index: {}", e))
+}
+
+/// Download a published version and load it once the checksum matches
+pub async fn download_skill(version: &RemoteSkillVersion) -> Result<LoadedSkill, String> {
+    let bytes = get_bytes(&reqwest::Client::new(), &version.url).await?;
+    verify_checksum(&bytes, &version.sha256)?;
+
+    let mut file = tempfile::NamedTempFile::new().map_err(|e| e.to_string())?;
+    file.write_all(&bytes).map_err(|e| e.to_string())?;
+    load_skill_from_zip(file.path())
+}
+
+/// Installed skills (id, name, installed version) that the index has newer versions of
+pub fn find_updates(installed: &[(String, String, String)], index: &RemoteIndex) -> Vec<SkillUpdate> {
+    installed
+        .iter()
+        .filter_map(|(skill_id, name, installed_version)| {
+            let latest = index.find(name)?.latest()?;
+            (compare_versions(&latest.version, installed_version) == Ordering::Greater).then(|| SkillUpdate {
+                skill_id: skill_id.clone(),
+                name: name.clone(),
+                installed_version: installed_version.clone(),
+                latest_version: latest.version.clone(),
+                changelog: latest.changelog.clone(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn version(v: &str) -> RemoteSkillVersion {
+        RemoteSkillVersion {
+            version: v.to_string(),
+            url: format!("https://example.test/pdf-{}.zip", v),
+            sha256: String::new(),
+            changelog: None,
+        }
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("1.10.0", "1.9.2"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0", "1.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("v2.0.0", "1.99"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0.0-beta", "1.0.0"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_verify_index_signature() {
+        let signing = SigningKey::from_bytes(&[7u8; 32]);
+        let public_key = hex::encode(signing.verifying_key().to_bytes());
+        let body = br#"{"skills":[]}"#;
+        let signature = hex::encode(signing.sign(body).to_bytes());
+
+        assert!(verify_index(body, &signature, &public_key).is_ok());
+        assert!(verify_index(br#"{"skills":[1]}"#, &signature, &public_key).is_err());
+        assert!(verify_index(body, "abcd", &public_key).is_err());
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        let digest = hex::encode(Sha256::digest(b"zip bytes"));
+        assert!(verify_checksum(b"zip bytes", &digest).is_ok());
+        assert!(verify_checksum(b"zip bytes", &digest.to_uppercase()).is_ok());
+        assert!(verify_checksum(b"other", &digest).is_err());
+    }
+
+    #[test]
+    fn test_find_updates() {
+        let index = RemoteIndex {
+            skills: vec![RemoteSkill {
+                name: "pdf".to_string(),
+                display_title: None,
+                description: "PDF tools".to_string(),
+                category: None,
+                versions: vec![version("1.2.0"), version("1.10.0"), version("1.9.0")],
+            }],
+        };
+        assert_eq!(index.find("pdf").unwrap().latest().unwrap().version, "1.10.0");
+        assert_eq!(index.find("pdf").unwrap().version(Some("1.2.0")).unwrap().version, "1.2.0");
+
+        let installed = vec![
+            ("a".to_string(), "pdf".to_string(), "1.9.0".to_string()),
+            ("b".to_string(), "xlsx".to_string(), "1.0.0".to_string()),
+        ];
+        let updates = find_updates(&installed, &index);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].latest_version, "1.10.0");
+
+        let current = vec![("a".to_string(), "pdf".to_string(), "1.10.0".to_string())];
+        assert!(find_updates(&current, &index).is_empty());
+    }
+
+    #[test]
+    fn test_config_requires_https() {
+        let pool = crate::database::create_test_pool();
+        let config = MarketplaceConfig {
+            index_url: Some("http://skills.example.test/index.json".to_string()),
+            public_key: None,
+        };
+        assert!(config.save(&pool).is_err());
+    }
+}
@@ -0,0 +1,299 @@
+//! MicroVM sandbox for agents running with `isolation: strict`
+//!
+//! Boots a minimal Linux guest with cloud-hypervisor and shares the workspace
+//! through virtio-fs (virtiofsd), giving kernel-level isolation for fully
+//! autonomous agents. Linux + KVM only.
+//!
+//! Guest contract: the rootfs must provide `/sbin/anycowork-init`, which
+//! 1. mounts the virtio-fs tags `workspace` at /workspace and `job` at /job
+//!    (and `skill` at /skill read-only when present),
+//! 2. runs `sh /job/run.sh` from /workspace, redirecting output to
+//!    /job/stdout and /job/stderr,
+//! 3. writes the exit code to /job/exit_code and powers off.
+
+use crate::models::SandboxConfig;
+use crate::skills::docker::ExecutionResult;
+use crate::skills::sandbox::Sandbox;
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::{Child, Command};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 300;
+const DEFAULT_MEMORY_MB: u32 = 512;
+
+/// Host-side configuration for the microVM backend
+#[derive(Debug, Clone)]
+pub struct MicroVmConfig {
+    pub hypervisor_bin: PathBuf,
+    pub virtiofsd_bin: PathBuf,
+    pub kernel_path: PathBuf,
+    pub rootfs_path: PathBuf,
+    pub vcpus: u32,
+}
+
+impl Default for MicroVmConfig {
+    /// Binaries from PATH (overridable via env), images from ~/.anycowork/microvm
+    fn default() -> Self {
+        let image_dir = dirs::home_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(".anycowork")
+            .join("microvm");
+
+        let from_env = |key: &str, default: PathBuf| {
+            std::env::var(key).map(PathBuf::from).unwrap_or(default)
+        };
+
+        Self {
+            hypervisor_bin: from_env("ANYCOWORK_HYPERVISOR_BIN", PathBuf::from("cloud-hypervisor")),
+            virtiofsd_bin: from_env("ANYCOWORK_VIRTIOFSD_BIN", PathBuf::from("virtiofsd")),
+            kernel_path: from_env("ANYCOWORK_MICROVM_KERNEL", image_dir.join("vmlinux")),
+            rootfs_path: from_env("ANYCOWORK_MICROVM_ROOTFS", image_dir.join("rootfs.ext4")),
+            vcpus: 1,
+        }
+    }
+}
+
+impl MicroVmConfig {
+    /// Explain why the backend can't run here, if it can't
+    pub fn check(&self) -> Result<(), String> {
+        if !cfg!(target_os = "linux") {
+            return Err("MicroVM isolation is only supported on Linux".to_string());
+        }
+        if !Path::new("/dev/kvm").exists() {
+            return Err("/dev/kvm is not available".to_string());
+        }
+        for bin in [&self.hypervisor_bin, &self.virtiofsd_bin] {
+            if which(bin).is_none() {
+                return Err(format!("{} not found", bin.display()));
+            }
+        }
+        if !self.kernel_path.exists() {
+            return Err(format!("Guest kernel not found at {}", self.kernel_path.display()));
+        }
+        if !self.rootfs_path.exists() {
+            return Err(format!("Guest rootfs not found at {}", self.rootfs_path.display()));
+        }
+        Ok(())
+    }
+}
+
+fn which(bin: &Path) -> Option<PathBuf> {
+    if bin.components().count() > 1 {
+        return bin.exists().then(|| bin.to_path_buf());
+    }
+    std::env::var_os("PATH").and_then(|paths| {
+        std::env::split_paths(&paths)
+            .map(|dir| dir.join(bin))
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+pub struct MicroVmSandbox {
+    config: MicroVmConfig,
+    available: bool,
+}
+
+impl MicroVmSandbox {
+    pub fn new(config: MicroVmConfig) -> Self {
+        let available = match config.check() {
+            Ok(()) => true,
+            Err(e) => {
+                log::info!("MicroVM sandbox unavailable: {}", e);
+                false
+            }
+        };
+        Self { config, available }
+    }
+
+    fn spawn_virtiofsd(&self, socket: &Path, shared_dir: &Path, read_only: bool) -> Result<Child, String> {
+        let mut cmd = Command::new(&self.config.virtiofsd_bin);
+        cmd.arg(format!("--socket-path={}", socket.display()))
+            .arg(format!("--shared-dir={}", shared_dir.display()))
+            .arg("--cache=never")
+            .arg("--sandbox=none")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+        if read_only {
+            cmd.arg("--readonly");
+        }
+        cmd.spawn().map_err(|e| format!("Failed to start virtiofsd: {}", e))
+    }
+
+    /// Wait for virtiofsd to create its socket before booting the guest
+    async fn wait_for_socket(socket: &Path) -> Result<(), String> {
+        for _ in 0..50 {
+            if socket.exists() {
+                return Ok(());
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        Err(format!("virtiofsd socket {} did not appear", socket.display()))
+    }
+}
+
+#[async_trait]
+impl Sandbox for MicroVmSandbox {
+    fn name(&self) -> &str {
+        "microvm"
+    }
+
+    fn is_available(&self) -> bool {
+        self.available
+    }
+
+    #[tracing::instrument(
+        name = "sandbox.execute",
+        skip_all,
+        fields(backend = "microvm", startup_ms = tracing::field::Empty, exit_code = tracing::field::Empty)
+    )]
+    async fn execute(
+        &self,
+        command: &str,
+        workspace_path: &Path,
+        skill_files_path: Option<&Path>,
+        config: &SandboxConfig,
+    ) -> Result<ExecutionResult, String> {
+        let started = std::time::Instant::now();
+        if !self.available {
+            return Err(self.config.check().err().unwrap_or_else(|| "MicroVM sandbox is not available".to_string()));
+        }
+        if config.network_enabled == Some(true) {
+            log::warn!("MicroVM sandbox runs without network; network_enabled is ignored");
+        }
+
+        let workspace = workspace_path
+            .canonicalize()
+            .map_err(|e| format!("Invalid workspace path: {}", e))?;
+
+        // Job directory carries the command in and the results out
+        let job_dir = tempfile::tempdir().map_err(|e| format!("Failed to create job dir: {}", e))?;
+        std::fs::write(job_dir.path().join("run.sh"), job_script(command, &config.env))
+            .map_err(|e| format!("Failed to write job script: {}", e))?;
+
+        let sockets_dir = tempfile::tempdir().map_err(|e| format!("Failed to create socket dir: {}", e))?;
+        let mut shares = vec![
+            ("workspace", workspace.clone(), false),
+            ("job", job_dir.path().to_path_buf(), false),
+        ];
+        if let Some(skill_dir) = skill_files_path {
+            shares.push(("skill", skill_dir.to_path_buf(), true));
+        }
+
+        let mut daemons = Vec::new();
+        let mut fs_args = Vec::new();
+        for (tag, dir, read_only) in &shares {
+            let socket = sockets_dir.path().join(format!("{}.sock", tag));
+            daemons.push(self.spawn_virtiofsd(&socket, dir, *read_only)?);
+            Self::wait_for_socket(&socket).await?;
+            fs_args.push("--fs".to_string());
+            fs_args.push(format!("tag={},socket={}", tag, socket.display()));
+        }
+
+        let memory_mb = config
+            .memory_limit
+            .as_deref()
+            .and_then(crate::skills::wasm::parse_memory_limit)
+            .map(|bytes| (bytes / (1024 * 1024)) as u32)
+            .filter(|mb| *mb >= 128)
+            .unwrap_or(DEFAULT_MEMORY_MB);
+        let vcpus = config
+            .cpu_limit
+            .map(|c| c.ceil().max(1.0) as u32)
+            .unwrap_or(self.config.vcpus);
+
+        let mut vm = Command::new(&self.config.hypervisor_bin);
+        vm.arg("--kernel")
+            .arg(&self.config.kernel_path)
+            .arg("--disk")
+            .arg(format!("path={},readonly=on", self.config.rootfs_path.display()))
+            .arg("--cmdline")
+            .arg("console=hvc0 root=/dev/vda ro init=/sbin/anycowork-init")
+            .arg("--cpus")
+            .arg(format!("boot={}", vcpus))
+            .arg("--memory")
+            // virtio-fs requires shared guest memory
+            .arg(format!("size={}M,shared=on", memory_mb))
+            .args(&fs_args)
+            .arg("--serial")
+            .arg("off")
+            .arg("--console")
+            .arg("off")
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        let mut child = vm.spawn().map_err(|e| format!("Failed to start microVM: {}", e))?;
+        // File sharing daemons and the hypervisor process are up
+        tracing::Span::current().record("startup_ms", started.elapsed().as_millis() as u64);
+
+        let timeout = Duration::from_secs(config.timeout_seconds.map(u64::from).unwrap_or(DEFAULT_TIMEOUT_SECS));
+        let timed_out = match tokio::time::timeout(timeout, child.wait()).await {
+            Ok(Ok(_)) => false,
+            Ok(Err(e)) => return Err(format!("MicroVM failed: {}", e)),
+            Err(_) => {
+                let _ = child.kill().await;
+                true
+            }
+        };
+
+        for mut daemon in daemons {
+            let _ = daemon.kill().await;
+        }
+
+        let read = |name: &str| std::fs::read_to_string(job_dir.path().join(name)).unwrap_or_default();
+        let exit_code = read("exit_code").trim().parse::<i32>().unwrap_or(-1);
+        tracing::Span::current().record("exit_code", exit_code);
+
+        Ok(ExecutionResult {
+            success: !timed_out && exit_code == 0,
+            stdout: read("stdout"),
+            stderr: read("stderr"),
+            exit_code,
+            timed_out,
+        })
+    }
+}
+
+/// The command, preceded by exports of the extra environment (the guest only
+/// sees what is in the job directory)
+fn job_script(command: &str, env: &[(String, String)]) -> String {
+    let mut script = String::new();
+    for (name, value) in env {
+        script.push_str(&format!("export {}='{}'\n", name, value.replace('\'', "'\\''")));
+    }
+    script.push_str(command);
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_script_exports_env() {
+        let env = vec![("TOKEN".to_string(), "it's".to_string())];
+        assert_eq!(job_script("echo $TOKEN", &env), "export TOKEN='it'\\''s'\necho $TOKEN");
+        assert_eq!(job_script("ls", &[]), "ls");
+    }
+
+    #[test]
+    fn test_missing_images_are_reported() {
+        let config = MicroVmConfig {
+            hypervisor_bin: PathBuf::from("cloud-hypervisor"),
+            virtiofsd_bin: PathBuf::from("virtiofsd"),
+            kernel_path: PathBuf::from("/nonexistent/vmlinux"),
+            rootfs_path: PathBuf::from("/nonexistent/rootfs.ext4"),
+            vcpus: 1,
+        };
+        assert!(config.check().is_err());
+        assert!(!MicroVmSandbox::new(config).is_available());
+    }
+
+    #[test]
+    fn test_which_absolute_path() {
+        assert!(which(Path::new("/nonexistent/bin/tool")).is_none());
+    }
+}
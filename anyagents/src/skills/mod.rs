@@ -1,10 +1,23 @@
+pub mod command;
 pub mod docker;
+pub mod harness;
 pub mod loader;
+pub mod marketplace;
+pub mod microvm;
 pub mod parser;
+pub mod provision;
+pub mod recipe;
+pub mod sandbox;
+pub mod triggers;
+pub mod wasm;
 
 pub mod tool;
 
 pub use docker::DockerSandbox;
 pub use loader::{load_skill_from_directory, load_skill_from_zip};
+pub use microvm::{MicroVmConfig, MicroVmSandbox};
 pub use parser::parse_skill_md;
+pub use sandbox::Sandbox;
+#[cfg(feature = "wasm-sandbox")]
+pub use wasm::WasmSandbox;
 pub use tool::SkillTool;
@@ -9,15 +9,48 @@
 //! triggers:
 //!   - trigger1
 //!   - trigger2
+//!
 //! requires_sandbox: false
+//! read_only: false             (true only if the skill never writes or sends anything)
 //! sandbox_config:
 //!   image: python:3.11
 //!   memory_limit: 256m
 //!   timeout_seconds: 300
+//! dependencies:
+//!   pip:
+//!     - pandas>=2
+//!
+//!   npm: [xlsx]
+//!   binaries: [libreoffice]
+//!
+//! command: python3 /skill/convert.py {{ input }}{% if pages %} --pages {{ pages }}{% endif %}
+//! parameters:
+//!   input:
+//!     type: string
+//!     description: File to convert
+//!     required: true
+//!   pages:
+//!     type: integer
+//!
+//! examples:
+//!   - name: converts the sample
+//!     args: {"input": "sample.pdf"}
+//!     expect_stdout: Converted
+//!
+//! recipe:                       (instead of `command`; see `skills::recipe`)
+//!   - id: listing
+//!     tool: bash
+//!     args: {"command": "ls {{ dir }}"}
+//!   - tool: filesystem
+//!     when: steps.listing.exit_code == 0
+//!     args: {"operation": "write_file", "path": "listing.txt", "content": "{{ steps.listing.stdout }}"}
 //! ---
 //! # Markdown body...
 
-use crate::models::{ParsedSkill, SandboxConfig};
+use crate::models::{
+    ParsedSkill, RecipeStep, SandboxConfig, SkillDependencies, SkillExample, SkillInvocation, SkillRecipe,
+};
+use serde_json::{json, Map, Value};
 
 /// Parse a SKILL.md file content into a ParsedSkill struct
 pub fn parse_skill_md(content: &str) -> Result<ParsedSkill, String> {
@@ -42,6 +75,7 @@ pub fn parse_skill_md(content: &str) -> Result<ParsedSkill, String> {
     let mut category: Option<String> = None;
     let mut triggers: Option<Vec<String>> = None;
     let mut requires_sandbox = false;
+    let mut read_only = false;
     let mut sandbox_config: Option<SandboxConfig> = None;
 
     let mut in_triggers = false;
@@ -52,6 +86,19 @@ pub fn parse_skill_md(content: &str) -> Result<ParsedSkill, String> {
     let mut sandbox_cpu: Option<f32> = None;
     let mut sandbox_timeout: Option<u32> = None;
     let mut sandbox_network: Option<bool> = None;
+    let mut sandbox_runtime: Option<String> = None;
+    let mut in_dependencies = false;
+    let mut dependencies = SkillDependencies::default();
+    let mut dependency_list: Option<String> = None;
+    let mut command: Option<String> = None;
+    let mut in_parameters = false;
+    let mut parameters: Vec<(String, Map<String, Value>)> = Vec::new();
+    let mut required: Vec<String> = Vec::new();
+    let mut parameter_indent = 0;
+    let mut in_examples = false;
+    let mut examples: Vec<Map<String, Value>> = Vec::new();
+    let mut in_recipe = false;
+    let mut recipe: Vec<Map<String, Value>> = Vec::new();
 
     for line in yaml_content.lines() {
         let trimmed = line.trim();
@@ -83,12 +130,16 @@ pub fn parse_skill_md(content: &str) -> Result<ParsedSkill, String> {
                 let val = extract_value(trimmed, "network_enabled:");
                 sandbox_network = Some(val == "true" || val == "yes");
                 continue;
+            } else if trimmed.starts_with("runtime:") {
+                sandbox_runtime = Some(extract_value(trimmed, "runtime:"));
+                continue;
             } else if !trimmed.is_empty() && !trimmed.starts_with(' ') {
                 // End of sandbox_config section
                 in_sandbox_config = false;
                 if sandbox_image.is_some()
                     || sandbox_memory.is_some()
                     || sandbox_timeout.is_some()
+                    || sandbox_runtime.is_some()
                 {
                     sandbox_config = Some(SandboxConfig {
                         image: sandbox_image.take(),
@@ -96,11 +147,107 @@ pub fn parse_skill_md(content: &str) -> Result<ParsedSkill, String> {
                         cpu_limit: sandbox_cpu.take(),
                         timeout_seconds: sandbox_timeout.take(),
                         network_enabled: sandbox_network.take(),
+                        runtime: sandbox_runtime.take(),
+                        ..Default::default()
                     });
                 }
             }
         }
 
+        // Handle dependencies nested lists (pip / npm / binaries)
+        if in_dependencies {
+            if line.starts_with(' ') || line.starts_with('\t') || trimmed.is_empty() {
+                if let Some(item) = trimmed.strip_prefix("- ") {
+                    let list = dependency_list
+                        .as_deref()
+                        .ok_or("Dependency item must be under pip, npm or binaries")?;
+                    push_dependency(&mut dependencies, list, item.trim().to_string())?;
+                } else if let Some((key, value)) = trimmed.split_once(':') {
+                    let key = key.trim().to_string();
+                    for item in parse_inline_list(value) {
+                        push_dependency(&mut dependencies, &key, item)?;
+                    }
+                    dependency_list = Some(key);
+                }
+                continue;
+            }
+            in_dependencies = false;
+        }
+
+        // Handle parameters: one nested block of attributes per parameter
+        if in_parameters {
+            if line.starts_with(' ') || line.starts_with('\t') || trimmed.is_empty() {
+                if trimmed.is_empty() {
+                    continue;
+                }
+                let indent = line.len() - line.trim_start().len();
+                let (key, value) = trimmed
+                    .split_once(':')
+                    .ok_or_else(|| format!("Invalid parameter line '{}'", trimmed))?;
+                let (key, value) = (key.trim(), value.trim());
+                if value.is_empty() && (parameters.is_empty() || indent <= parameter_indent) {
+                    parameter_indent = indent;
+                    parameters.push((key.to_string(), Map::new()));
+                } else {
+                    let (name, attributes) = parameters
+                        .last_mut()
+                        .ok_or_else(|| format!("'{}' must be nested under a parameter name", key))?;
+                    if key == "required" {
+                        if value == "true" || value == "yes" {
+                            required.push(name.clone());
+                        }
+                    } else {
+                        let ty = attributes.get("type").and_then(|t| t.as_str()).map(str::to_string);
+                        attributes.insert(key.to_string(), parameter_attribute(key, value, ty.as_deref()));
+                    }
+                }
+                continue;
+            }
+            in_parameters = false;
+        }
+
+        // Handle examples: a list of `key: value` maps
+        if in_examples {
+            if line.starts_with(' ') || line.starts_with('\t') || trimmed.is_empty() {
+                let entry = match trimmed.strip_prefix("- ") {
+                    Some(rest) => {
+                        examples.push(Map::new());
+                        rest.trim()
+                    }
+                    None => trimmed,
+                };
+                if let Some((key, value)) = entry.split_once(':') {
+                    let example = examples
+                        .last_mut()
+                        .ok_or("Examples must be a list ('- name: ...')")?;
+                    example.insert(key.trim().to_string(), Value::String(value.trim().to_string()));
+                }
+                continue;
+            }
+            in_examples = false;
+        }
+
+        // Handle recipe: a list of steps, same shape as examples
+        if in_recipe {
+            if line.starts_with(' ') || line.starts_with('\t') || trimmed.is_empty() {
+                let entry = match trimmed.strip_prefix("- ") {
+                    Some(rest) => {
+                        recipe.push(Map::new());
+                        rest.trim()
+                    }
+                    None => trimmed,
+                };
+                if let Some((key, value)) = entry.split_once(':') {
+                    let step = recipe
+                        .last_mut()
+                        .ok_or("Recipe must be a list of steps ('- tool: ...')")?;
+                    step.insert(key.trim().to_string(), Value::String(value.trim().to_string()));
+                }
+                continue;
+            }
+            in_recipe = false;
+        }
+
         // Parse top-level keys
         if trimmed.starts_with("name:") {
             name = extract_value(trimmed, "name:");
@@ -116,8 +263,22 @@ pub fn parse_skill_md(content: &str) -> Result<ParsedSkill, String> {
         } else if trimmed.starts_with("requires_sandbox:") {
             let val = extract_value(trimmed, "requires_sandbox:");
             requires_sandbox = val == "true" || val == "yes" || val == "1";
+        } else if trimmed.starts_with("read_only:") {
+            let val = extract_value(trimmed, "read_only:");
+            read_only = val == "true" || val == "yes" || val == "1";
         } else if trimmed.starts_with("sandbox_config:") {
             in_sandbox_config = true;
+        } else if trimmed.starts_with("dependencies:") {
+            in_dependencies = true;
+            dependency_list = None;
+        } else if trimmed.starts_with("command:") {
+            command = Some(extract_value(trimmed, "command:"));
+        } else if trimmed.starts_with("parameters:") {
+            in_parameters = true;
+        } else if trimmed.starts_with("examples:") {
+            in_examples = true;
+        } else if trimmed.starts_with("recipe:") {
+            in_recipe = true;
         }
     }
 
@@ -126,7 +287,10 @@ pub fn parse_skill_md(content: &str) -> Result<ParsedSkill, String> {
         triggers = Some(current_triggers);
     }
     if in_sandbox_config
-        && (sandbox_image.is_some() || sandbox_memory.is_some() || sandbox_timeout.is_some())
+        && (sandbox_image.is_some()
+            || sandbox_memory.is_some()
+            || sandbox_timeout.is_some()
+            || sandbox_runtime.is_some())
     {
         sandbox_config = Some(SandboxConfig {
             image: sandbox_image,
@@ -134,6 +298,8 @@ pub fn parse_skill_md(content: &str) -> Result<ParsedSkill, String> {
             cpu_limit: sandbox_cpu,
             timeout_seconds: sandbox_timeout,
             network_enabled: sandbox_network,
+            runtime: sandbox_runtime,
+            ..Default::default()
         });
     }
 
@@ -164,6 +330,59 @@ pub fn parse_skill_md(content: &str) -> Result<ParsedSkill, String> {
         return Err("Skill description must be 1024 characters or less".to_string());
     }
 
+    let recipe = recipe
+        .into_iter()
+        .enumerate()
+        .map(|(i, step)| parse_recipe_step(i, step))
+        .collect::<Result<Vec<_>, _>>()?;
+    crate::skills::recipe::check_recipe(&recipe)?;
+
+    if command.is_none() && recipe.is_empty() && !parameters.is_empty() {
+        return Err("SKILL.md declares parameters but no 'command' template or 'recipe' to run".to_string());
+    }
+    let properties: Map<String, Value> = parameters
+        .into_iter()
+        .map(|(name, attributes)| (name, Value::Object(attributes)))
+        .collect();
+    let schema = json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    });
+    // Agent loops compile this schema for every call; reject bad ones now
+    jsonschema::JSONSchema::compile(&schema).map_err(|e| format!("Invalid parameters in SKILL.md: {}", e))?;
+
+    let (invocation, recipe) = match command {
+        Some(_) if !recipe.is_empty() => {
+            return Err("SKILL.md declares both a 'command' and a 'recipe'; use one".to_string())
+        }
+        Some(command) => {
+            crate::skills::command::check_template(&command)?;
+            (
+                Some(SkillInvocation {
+                    parameters: schema,
+                    command,
+                }),
+                None,
+            )
+        }
+        None if !recipe.is_empty() => (
+            None,
+            Some(SkillRecipe {
+                parameters: schema,
+                steps: recipe,
+            }),
+        ),
+        None => (None, None),
+    };
+
+    let examples = examples
+        .into_iter()
+        .enumerate()
+        .map(|(i, example)| parse_example(i, example))
+        .collect::<Result<Vec<_>, _>>()?;
+
     Ok(ParsedSkill {
         name,
         description,
@@ -171,12 +390,129 @@ pub fn parse_skill_md(content: &str) -> Result<ParsedSkill, String> {
         category,
         triggers,
         requires_sandbox,
+        read_only,
         sandbox_config,
         execution_mode: None,
+        dependencies: (!dependencies.is_empty()).then_some(dependencies),
+        invocation,
+        examples,
+        recipe,
         body,
     })
 }
 
+fn push_dependency(deps: &mut SkillDependencies, list: &str, item: String) -> Result<(), String> {
+    if item.is_empty() {
+        return Ok(());
+    }
+    match list {
+        "pip" => deps.pip.push(item),
+        "npm" => deps.npm.push(item),
+        "binaries" => deps.binaries.push(item),
+        other => {
+            return Err(format!(
+                "Unknown dependency type '{}' (expected pip, npm or binaries)",
+                other
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// One `examples:` entry; `command` is shorthand for `args: {"args": ...}`
+fn parse_example(index: usize, fields: Map<String, Value>) -> Result<SkillExample, String> {
+    let field = |key: &str| {
+        fields
+            .get(key)
+            .and_then(|v| v.as_str())
+            .map(|v| extract_value(&format!("{}: {}", key, v), &format!("{}:", key)))
+    };
+
+    let name = field("name").unwrap_or_else(|| format!("example {}", index + 1));
+    let args = match (field("args"), field("command")) {
+        (Some(args), _) => serde_json::from_str(&args)
+            .map_err(|e| format!("Example '{}' has invalid args JSON: {}", name, e))?,
+        (None, Some(command)) => json!({ "args": command }),
+        (None, None) => return Err(format!("Example '{}' needs 'args' or 'command'", name)),
+    };
+    let expect_exit = match field("expect_exit") {
+        Some(code) => code
+            .parse()
+            .map_err(|_| format!("Example '{}' has a non-numeric expect_exit", name))?,
+        None => 0,
+    };
+
+    Ok(SkillExample {
+        name,
+        args,
+        expect_exit,
+        expect_stdout: field("expect_stdout"),
+    })
+}
+
+/// One `recipe:` step; `args` is inline JSON, as in examples
+fn parse_recipe_step(index: usize, fields: Map<String, Value>) -> Result<RecipeStep, String> {
+    let field = |key: &str| {
+        fields
+            .get(key)
+            .and_then(|v| v.as_str())
+            .map(|v| extract_value(&format!("{}: {}", key, v), &format!("{}:", key)))
+            .filter(|v| !v.is_empty())
+    };
+
+    let tool = field("tool").ok_or_else(|| format!("Recipe step {} needs a 'tool'", index + 1))?;
+    let args = match field("args") {
+        Some(args) => serde_json::from_str(&args)
+            .map_err(|e| format!("Recipe step {} has invalid args JSON: {}", index + 1, e))?,
+        None => json!({}),
+    };
+
+    Ok(RecipeStep {
+        id: field("id"),
+        tool,
+        args,
+        when: field("when"),
+        continue_on_error: field("continue_on_error").map(|v| v == "true" || v == "yes").unwrap_or(false),
+    })
+}
+
+/// JSON value of one parameter attribute, typed after the parameter's `type`
+fn parameter_attribute(key: &str, value: &str, ty: Option<&str>) -> Value {
+    let typed = |raw: &str| -> Value {
+        let raw = raw.trim().trim_matches(|c| c == '"' || c == '\'');
+        match ty {
+            Some("integer") => raw.parse::<i64>().map(Value::from).unwrap_or_else(|_| raw.into()),
+            Some("number") => raw.parse::<f64>().map(Value::from).unwrap_or_else(|_| raw.into()),
+            Some("boolean") => Value::Bool(raw == "true" || raw == "yes"),
+            _ => Value::String(raw.to_string()),
+        }
+    };
+    match key {
+        "enum" => Value::Array(parse_inline_list(value).iter().map(|v| typed(v)).collect()),
+        "default" => typed(value),
+        "minimum" | "maximum" | "minLength" | "maxLength" => value
+            .parse::<f64>()
+            .map(|n| if n.fract() == 0.0 { Value::from(n as i64) } else { Value::from(n) })
+            .unwrap_or_else(|_| value.into()),
+        // `items: string` is shorthand for `items: { type: string }`
+        "items" => json!({ "type": extract_value(&format!("items: {}", value), "items:") }),
+        _ => Value::String(extract_value(&format!("{}: {}", key, value), &format!("{}:", key))),
+    }
+}
+
+/// Items of an inline `[a, b]` list; empty for anything else
+fn parse_inline_list(value: &str) -> Vec<String> {
+    let value = value.trim();
+    match value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        Some(inner) => inner
+            .split(',')
+            .map(|item| item.trim().trim_matches(|c| c == '"' || c == '\'').to_string())
+            .filter(|item| !item.is_empty())
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
 /// Extract value from a "key: value" line, handling quoted strings
 fn extract_value(line: &str, key: &str) -> String {
     let value = line[key.len()..].trim();
@@ -214,6 +550,7 @@ This is the body of the skill.
         assert_eq!(result.license, Some("MIT".to_string()));
         assert_eq!(result.category, Some("Testing".to_string()));
         assert!(!result.requires_sandbox);
+        assert!(!result.read_only);
         assert!(result.body.contains("# Test Skill"));
     }
 
@@ -223,6 +560,7 @@ This is the body of the skill.
 name: sandbox-skill
 description: A skill requiring sandbox
 requires_sandbox: true
+read_only: true
 sandbox_config:
   image: python:3.11
   memory_limit: 256m
@@ -233,6 +571,7 @@ sandbox_config:
 
         let result = parse_skill_md(content).unwrap();
         assert!(result.requires_sandbox);
+        assert!(result.read_only);
         assert!(result.sandbox_config.is_some());
 
         let config = result.sandbox_config.unwrap();
@@ -262,6 +601,129 @@ triggers:
         assert!(triggers.contains(&"document".to_string()));
     }
 
+    #[test]
+    fn test_parse_skill_with_dependencies() {
+        let content = r#"---
+name: xlsx
+description: Spreadsheet tools
+dependencies:
+  pip:
+    - pandas>=2
+    - openpyxl
+  npm: [xlsx, "csv-parse"]
+  binaries: [libreoffice]
+category: Office
+---
+# Spreadsheets
+"#;
+
+        let result = parse_skill_md(content).unwrap();
+        let deps = result.dependencies.unwrap();
+        assert_eq!(deps.pip, vec!["pandas>=2", "openpyxl"]);
+        assert_eq!(deps.npm, vec!["xlsx", "csv-parse"]);
+        assert_eq!(deps.binaries, vec!["libreoffice"]);
+        assert_eq!(result.category, Some("Office".to_string()));
+
+        let bad = "---\nname: x\ndescription: y\ndependencies:\n  cargo: [ripgrep]\n---\n";
+        assert!(parse_skill_md(bad).unwrap_err().contains("Unknown dependency type"));
+    }
+
+    #[test]
+    fn test_parse_skill_with_parameters() {
+        let content = r#"---
+name: convert
+description: Convert documents
+command: python3 /skill/convert.py {{ input }}{% if pages %} --pages {{ pages }}{% endif %}
+parameters:
+  input:
+    type: string
+    description: "File to convert"
+    required: true
+  pages:
+    type: integer
+    minimum: 1
+  format:
+    type: string
+    enum: [pdf, docx]
+    default: pdf
+---
+# Convert
+"#;
+
+        let invocation = parse_skill_md(content).unwrap().invocation.unwrap();
+        assert!(invocation.command.starts_with("python3 /skill/convert.py"));
+        let schema = invocation.parameters;
+        assert_eq!(schema["required"], json!(["input"]));
+        assert_eq!(schema["properties"]["input"]["description"], "File to convert");
+        assert_eq!(schema["properties"]["pages"]["minimum"], 1);
+        assert_eq!(schema["properties"]["format"]["enum"], json!(["pdf", "docx"]));
+        assert_eq!(schema["properties"]["format"]["default"], "pdf");
+
+        let no_command = "---\nname: x\ndescription: y\nparameters:\n  a:\n    type: string\n---\n";
+        assert!(parse_skill_md(no_command).unwrap_err().contains("command"));
+    }
+
+    #[test]
+    fn test_parse_skill_with_examples() {
+        let content = r#"---
+name: greeter
+description: Says hello
+examples:
+  - name: greets
+    command: echo hello
+    expect_stdout: "hello"
+  - args: {"args": "exit 3"}
+    expect_exit: 3
+---
+# Greeter
+"#;
+
+        let examples = parse_skill_md(content).unwrap().examples;
+        assert_eq!(examples.len(), 2);
+        assert_eq!(examples[0].name, "greets");
+        assert_eq!(examples[0].args, json!({ "args": "echo hello" }));
+        assert_eq!(examples[0].expect_stdout.as_deref(), Some("hello"));
+        assert_eq!(examples[1].name, "example 2");
+        assert_eq!(examples[1].expect_exit, 3);
+    }
+
+    #[test]
+    fn test_parse_skill_with_recipe() {
+        let content = r#"---
+name: snapshot
+description: Save a directory listing
+parameters:
+  dir:
+    type: string
+    required: true
+recipe:
+  - id: listing
+    tool: bash
+    args: {"command": "ls {{ dir }}"}
+  - tool: filesystem
+    when: steps.listing.exit_code == 0
+    continue_on_error: true
+    args: {"operation": "write_file", "path": "listing.txt", "content": "{{ steps.listing.stdout }}"}
+---
+# Snapshot
+"#;
+
+        let skill = parse_skill_md(content).unwrap();
+        assert!(skill.invocation.is_none());
+        let recipe = skill.recipe.unwrap();
+        assert_eq!(recipe.parameters["required"], json!(["dir"]));
+        assert_eq!(recipe.steps.len(), 2);
+        assert_eq!(recipe.steps[0].id.as_deref(), Some("listing"));
+        assert_eq!(recipe.steps[1].when.as_deref(), Some("steps.listing.exit_code == 0"));
+        assert!(recipe.steps[1].continue_on_error);
+        assert_eq!(recipe.steps[1].args["path"], "listing.txt");
+
+        let both = "---\nname: x\ndescription: y\ncommand: ls\nrecipe:\n  - tool: bash\n---\n";
+        assert!(parse_skill_md(both).unwrap_err().contains("both"));
+        let unknown = "---\nname: x\ndescription: y\nrecipe:\n  - tool: email\n---\n";
+        assert!(parse_skill_md(unknown).unwrap_err().contains("unknown tool"));
+    }
+
     #[test]
     fn test_missing_name() {
         let content = r#"---
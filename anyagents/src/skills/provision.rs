@@ -0,0 +1,392 @@
+//! Installs the runtime dependencies a skill declares in SKILL.md
+//!
+//! Local execution gets a skill-local environment under
+//! `~/.anycowork/skill-envs/<skill>/<fingerprint>`: a Python venv for pip
+//! packages and a `node_modules` for npm packages. Docker execution gets a
+//! derived image, `anycowork-skill-<skill>:<fingerprint>`, built on top of the
+//! skill's sandbox image, since the sandbox itself runs without network.
+//!
+//! The fingerprint hashes the dependency list (and base image), so editing the
+//! list provisions a fresh environment while unchanged skills reuse the cached
+//! one. Binaries are never installed, only checked for.
+
+use crate::models::SkillDependencies;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+const READY_MARKER: &str = ".provisioned";
+
+/// Serializes provisioning so two tool calls don't install into the same env
+static PROVISION_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// A ready local environment for one skill
+#[derive(Debug, Clone)]
+pub struct LocalEnv {
+    pub dir: PathBuf,
+    /// Directories to put in front of PATH
+    pub path_entries: Vec<PathBuf>,
+    /// Extra environment variables (VIRTUAL_ENV, NODE_PATH)
+    pub env: Vec<(String, String)>,
+    /// False when this call did the installing
+    pub cached: bool,
+}
+
+impl LocalEnv {
+    /// PATH value with the environment's bin directories first
+    pub fn path_var(&self) -> String {
+        let current = std::env::var_os("PATH").unwrap_or_default();
+        let entries = self
+            .path_entries
+            .iter()
+            .cloned()
+            .chain(std::env::split_paths(&current));
+        std::env::join_paths(entries)
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|_| current.to_string_lossy().to_string())
+    }
+
+    /// Apply PATH and the extra variables to a command
+    pub fn apply(&self, command: &mut Command) {
+        command.env("PATH", self.path_var());
+        for (key, value) in &self.env {
+            command.env(key, value);
+        }
+    }
+}
+
+pub fn env_root() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".anycowork")
+        .join("skill-envs")
+}
+
+/// Stable short hash of the dependency list plus any extra context
+pub fn fingerprint(deps: &SkillDependencies, extra: &str) -> String {
+    let mut hasher = Sha256::new();
+    for (kind, items) in [("pip", &deps.pip), ("npm", &deps.npm), ("bin", &deps.binaries)] {
+        let mut sorted = items.clone();
+        sorted.sort();
+        hasher.update(kind.as_bytes());
+        for item in sorted {
+            hasher.update(b"\0");
+            hasher.update(item.as_bytes());
+        }
+        hasher.update(b"\n");
+    }
+    hasher.update(extra.as_bytes());
+    hex::encode(hasher.finalize())[..16].to_string()
+}
+
+/// Skill names are validated by the parser, but DB-created skills are not
+fn safe_name(skill: &str) -> String {
+    skill
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c.to_ascii_lowercase() } else { '-' })
+        .collect()
+}
+
+/// Package specs go straight onto an installer command line
+fn validate_specs(kind: &str, specs: &[String]) -> Result<(), String> {
+    for spec in specs {
+        if spec.starts_with('-') || spec.chars().any(|c| c.is_whitespace() || "`$;&|<>\\".contains(c)) {
+            return Err(format!("Invalid {} dependency '{}'", kind, spec));
+        }
+    }
+    Ok(())
+}
+
+/// First `name` executable on PATH
+pub fn find_binary(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .flat_map(|dir| {
+            let candidate = dir.join(name);
+            [candidate.with_extension("exe"), candidate]
+        })
+        .find(|candidate| candidate.is_file())
+}
+
+fn missing_binaries(deps: &SkillDependencies) -> Vec<String> {
+    deps.binaries
+        .iter()
+        .filter(|b| find_binary(b).is_none())
+        .cloned()
+        .collect()
+}
+
+async fn run_step(program: &str, args: &[String], cwd: &Path) -> Result<(), String> {
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(cwd)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "`{} {}` failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+fn local_env(dir: &Path, deps: &SkillDependencies, cached: bool) -> LocalEnv {
+    let mut path_entries = Vec::new();
+    let mut env = Vec::new();
+    if !deps.pip.is_empty() {
+        let venv = dir.join("venv");
+        let bin = if cfg!(windows) { venv.join("Scripts") } else { venv.join("bin") };
+        path_entries.push(bin);
+        env.push(("VIRTUAL_ENV".to_string(), venv.to_string_lossy().to_string()));
+    }
+    if !deps.npm.is_empty() {
+        let modules = dir.join("node_modules");
+        path_entries.push(modules.join(".bin"));
+        env.push(("NODE_PATH".to_string(), modules.to_string_lossy().to_string()));
+    }
+    LocalEnv {
+        dir: dir.to_path_buf(),
+        path_entries,
+        env,
+        cached,
+    }
+}
+
+/// True when a previous call already provisioned this exact dependency list
+pub fn is_local_cached(skill: &str, deps: &SkillDependencies) -> bool {
+    env_root()
+        .join(safe_name(skill))
+        .join(fingerprint(deps, ""))
+        .join(READY_MARKER)
+        .exists()
+}
+
+/// Provision (or reuse) the local environment for `skill`
+pub async fn provision_local(skill: &str, deps: &SkillDependencies) -> Result<LocalEnv, String> {
+    provision_local_in(&env_root(), skill, deps).await
+}
+
+pub async fn provision_local_in(root: &Path, skill: &str, deps: &SkillDependencies) -> Result<LocalEnv, String> {
+    let missing = missing_binaries(deps);
+    if !missing.is_empty() {
+        return Err(format!(
+            "Skill '{}' needs {} on PATH; install it and try again",
+            skill,
+            missing.join(", ")
+        ));
+    }
+    validate_specs("pip", &deps.pip)?;
+    validate_specs("npm", &deps.npm)?;
+
+    let _guard = PROVISION_LOCK.lock().await;
+    let dir = root.join(safe_name(skill)).join(fingerprint(deps, ""));
+    if dir.join(READY_MARKER).exists() {
+        return Ok(local_env(&dir, deps, true));
+    }
+
+    // Start clean: a half-finished env from a failed attempt is not reusable
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let result = install_local(&dir, deps).await;
+    if let Err(e) = result {
+        let _ = std::fs::remove_dir_all(&dir);
+        return Err(format!("Provisioning skill '{}' failed: {}", skill, e));
+    }
+
+    std::fs::write(dir.join(READY_MARKER), serde_json::to_string(deps).unwrap_or_default())
+        .map_err(|e| format!("Failed to write provisioning marker: {}", e))?;
+    Ok(local_env(&dir, deps, false))
+}
+
+async fn install_local(dir: &Path, deps: &SkillDependencies) -> Result<(), String> {
+    if !deps.pip.is_empty() {
+        let python = ["python3", "python"]
+            .into_iter()
+            .find(|p| find_binary(p).is_some())
+            .ok_or("pip dependencies need python3 on PATH")?;
+        run_step(python, &["-m".to_string(), "venv".to_string(), "venv".to_string()], dir).await?;
+
+        let venv_python = if cfg!(windows) {
+            dir.join("venv").join("Scripts").join("python.exe")
+        } else {
+            dir.join("venv").join("bin").join("python")
+        };
+        let mut args = vec![
+            "-m".to_string(),
+            "pip".to_string(),
+            "install".to_string(),
+            "--disable-pip-version-check".to_string(),
+            "--quiet".to_string(),
+        ];
+        args.extend(deps.pip.iter().cloned());
+        run_step(&venv_python.to_string_lossy(), &args, dir).await?;
+    }
+
+    if !deps.npm.is_empty() {
+        if find_binary("npm").is_none() {
+            return Err("npm dependencies need npm on PATH".to_string());
+        }
+        let mut args = vec![
+            "install".to_string(),
+            "--no-audit".to_string(),
+            "--no-fund".to_string(),
+            "--prefix".to_string(),
+            dir.to_string_lossy().to_string(),
+        ];
+        args.extend(deps.npm.iter().cloned());
+        run_step("npm", &args, dir).await?;
+    }
+    Ok(())
+}
+
+/// Dockerfile for the derived sandbox image
+pub fn dockerfile(base_image: &str, deps: &SkillDependencies) -> String {
+    let mut lines = vec![format!("FROM {}", base_image)];
+    if !deps.pip.is_empty() {
+        lines.push(format!("RUN pip install --no-cache-dir {}", deps.pip.join(" ")));
+    }
+    if !deps.npm.is_empty() {
+        lines.push(format!("RUN npm install -g --no-audit --no-fund {}", deps.npm.join(" ")));
+    }
+    for binary in &deps.binaries {
+        lines.push(format!(
+            "RUN command -v {0} >/dev/null || (echo '{0} is not installed in {1}' >&2 && exit 1)",
+            binary, base_image
+        ));
+    }
+    lines.join("\n") + "\n"
+}
+
+pub fn image_tag(skill: &str, base_image: &str, deps: &SkillDependencies) -> String {
+    format!("anycowork-skill-{}:{}", safe_name(skill), fingerprint(deps, base_image))
+}
+
+async fn image_exists(tag: &str) -> bool {
+    Command::new("docker")
+        .args(["image", "inspect", tag])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// True when the derived image for this dependency list already exists
+pub async fn is_image_cached(skill: &str, base_image: &str, deps: &SkillDependencies) -> bool {
+    image_exists(&image_tag(skill, base_image, deps)).await
+}
+
+/// Build (or reuse) a sandbox image with the dependencies baked in; returns its tag
+pub async fn provision_image(skill: &str, base_image: &str, deps: &SkillDependencies) -> Result<String, String> {
+    validate_specs("pip", &deps.pip)?;
+    validate_specs("npm", &deps.npm)?;
+    validate_specs("binary", &deps.binaries)?;
+
+    let _guard = PROVISION_LOCK.lock().await;
+    let tag = image_tag(skill, base_image, deps);
+    if image_exists(&tag).await {
+        return Ok(tag);
+    }
+
+    let mut child = Command::new("docker")
+        .args(["build", "-t", &tag, "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run docker build: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(dockerfile(base_image, deps).as_bytes())
+            .await
+            .map_err(|e| format!("Failed to send Dockerfile: {}", e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|e| format!("docker build failed: {}", e))?;
+
+    if output.status.success() {
+        Ok(tag)
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let tail: Vec<&str> = stderr.lines().rev().take(15).collect();
+        Err(format!(
+            "Provisioning skill '{}' failed while building {}:\n{}",
+            skill,
+            tag,
+            tail.into_iter().rev().collect::<Vec<_>>().join("\n")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deps(pip: &[&str], npm: &[&str], binaries: &[&str]) -> SkillDependencies {
+        SkillDependencies {
+            pip: pip.iter().map(|s| s.to_string()).collect(),
+            npm: npm.iter().map(|s| s.to_string()).collect(),
+            binaries: binaries.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_is_order_independent() {
+        let a = deps(&["pandas", "openpyxl"], &[], &[]);
+        let b = deps(&["openpyxl", "pandas"], &[], &[]);
+        assert_eq!(fingerprint(&a, ""), fingerprint(&b, ""));
+        assert_ne!(fingerprint(&a, ""), fingerprint(&a, "python:3.11-slim"));
+        // Same name under a different installer is a different env
+        assert_ne!(fingerprint(&deps(&["xlsx"], &[], &[]), ""), fingerprint(&deps(&[], &["xlsx"], &[]), ""));
+    }
+
+    #[test]
+    fn test_dockerfile_and_tag() {
+        let d = deps(&["pandas>=2"], &["xlsx"], &["git"]);
+        let file = dockerfile("python:3.11-slim", &d);
+        assert!(file.starts_with("FROM python:3.11-slim\n"));
+        assert!(file.contains("RUN pip install --no-cache-dir pandas>=2"));
+        assert!(file.contains("RUN npm install -g --no-audit --no-fund xlsx"));
+        assert!(file.contains("command -v git"));
+        assert!(image_tag("My Skill", "python:3.11-slim", &d).starts_with("anycowork-skill-my-skill:"));
+    }
+
+    #[test]
+    fn test_rejects_unsafe_specs() {
+        assert!(validate_specs("pip", &["pandas==2.1".to_string()]).is_ok());
+        assert!(validate_specs("pip", &["--index-url=http://evil".to_string()]).is_err());
+        assert!(validate_specs("npm", &["xlsx; rm -rf /".to_string()]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_missing_binary_fails_clearly() {
+        let root = tempfile::tempdir().unwrap();
+        let err = provision_local_in(root.path(), "demo", &deps(&[], &[], &["definitely-not-a-real-binary-xyz"]))
+            .await
+            .unwrap_err();
+        assert!(err.contains("definitely-not-a-real-binary-xyz"));
+    }
+
+    #[tokio::test]
+    async fn test_binaries_only_env_is_cached() {
+        let root = tempfile::tempdir().unwrap();
+        let d = deps(&[], &[], &["sh"]);
+        let first = provision_local_in(root.path(), "demo", &d).await.unwrap();
+        assert!(!first.cached);
+        assert!(first.path_entries.is_empty());
+        let second = provision_local_in(root.path(), "demo", &d).await.unwrap();
+        assert!(second.cached);
+    }
+}
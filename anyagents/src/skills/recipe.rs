@@ -0,0 +1,520 @@
+//! Composite skills: a SKILL.md `recipe:` runs a sequence of tool calls
+//!
+//! ```yaml
+//! parameters:
+//!   url:
+//!     type: string
+//!     required: true
+//! recipe:
+//!   - id: page
+//!     tool: http
+//!     args: {"url": "{{ url }}"}
+//!   - tool: filesystem
+//!     when: steps.page.status == 200
+//!     args: {"operation": "write_file", "path": "page.html", "content": "{{ steps.page.body }}"}
+//!   - tool: bash
+//!     args: {"command": "wc -c page.html"}
+//! ```
+//!
+//! String arguments are minijinja templates over the skill's inputs (also
+//! available as `inputs`), the results of earlier steps by id (`steps.<id>`)
+//! and the last result (`previous`). A string that is a single `{{ expr }}`
+//! keeps the expression's JSON type. The bash `command` argument is rendered
+//! like a skill command template, so every substituted value is shell-quoted.
+//! Each step goes through the tool's own validation and permission request,
+//! and, for a read-only agent, the read-only policy on its rendered arguments.
+
+use crate::events::AgentEvent;
+use crate::models::{RecipeStep, SkillRecipe};
+use crate::permissions::ReadOnlyPolicy;
+use crate::skills::command;
+use crate::tools::{Tool, ToolContext};
+use minijinja::Environment;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// Tools a recipe step can call
+pub const RECIPE_TOOLS: &[&str] = &["filesystem", "bash", "http"];
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub enum StepStatus {
+    Running,
+    Completed,
+    Skipped,
+    Failed,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct StepOutcome {
+    pub id: Option<String>,
+    pub tool: String,
+    pub status: StepStatus,
+    /// Arguments after templating (absent for skipped steps)
+    pub args: Option<Value>,
+    pub result: Option<Value>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RecipeOutcome {
+    pub steps: Vec<StepOutcome>,
+    /// Result of the last step that ran
+    pub output: Value,
+}
+
+/// Executes a recipe's steps against a fixed set of tools
+pub struct RecipeRunner {
+    skill: String,
+    tools: Vec<Box<dyn Tool>>,
+    read_only_policy: Option<ReadOnlyPolicy>,
+}
+
+impl RecipeRunner {
+    pub fn new(skill: impl Into<String>, tools: Vec<Box<dyn Tool>>) -> Self {
+        Self {
+            skill: skill.into(),
+            tools,
+            read_only_policy: None,
+        }
+    }
+
+    /// Hold every step to the read-only policy of the agent running the recipe
+    pub fn with_read_only_policy(mut self, policy: Option<ReadOnlyPolicy>) -> Self {
+        self.read_only_policy = policy;
+        self
+    }
+
+    /// Whether every step is read-only whatever the inputs. A templated bash
+    /// command is not: a quoted value can still be a flag such as `-delete`.
+    pub fn is_read_only(&self, recipe: &SkillRecipe) -> bool {
+        recipe.steps.iter().all(|step| {
+            let templated = |value: &str| value.contains("{{") || value.contains("{%");
+            if step.tool == "bash" && step.args["command"].as_str().is_none_or(templated) {
+                return false;
+            }
+            self.tools
+                .iter()
+                .find(|t| t.name() == step.tool)
+                .is_some_and(|tool| tool.is_read_only(&step.args))
+        })
+    }
+
+    /// The filesystem, bash and http tools, scoped like the agent's own
+    pub fn with_builtin_tools(skill: impl Into<String>, workspace_path: PathBuf, execution_mode: String) -> Self {
+        Self::new(
+            skill,
+            vec![
+                Box::new(crate::tools::filesystem::FilesystemTool::new(workspace_path.clone())),
+                Box::new(crate::tools::bash::BashTool::new(workspace_path, execution_mode)),
+                Box::new(crate::tools::http::HttpTool::new()),
+            ],
+        )
+    }
+
+    /// Run every step in order; the first failing step (without
+    /// `continue_on_error`) stops the recipe
+    pub async fn run(&self, recipe: &SkillRecipe, inputs: &Value, ctx: &ToolContext) -> Result<RecipeOutcome, String> {
+        let env = Environment::new();
+        let mut results = Map::new();
+        let mut previous = Value::Null;
+        let mut outcomes = Vec::new();
+
+        for (index, step) in recipe.steps.iter().enumerate() {
+            let label = step_label(index, step);
+            let context = template_context(inputs, &results, &previous);
+            let mut outcome = StepOutcome {
+                id: step.id.clone(),
+                tool: step.tool.clone(),
+                status: StepStatus::Skipped,
+                args: None,
+                result: None,
+                error: None,
+            };
+
+            if let Some(condition) = &step.when {
+                let run = env
+                    .compile_expression(condition)
+                    .and_then(|expr| expr.eval(&context))
+                    .map_err(|e| format!("{}: invalid 'when' condition: {}", label, e))?;
+                if !run.is_true() {
+                    self.emit(ctx, index, step, StepStatus::Skipped);
+                    outcomes.push(outcome);
+                    continue;
+                }
+            }
+
+            self.emit(ctx, index, step, StepStatus::Running);
+            let executed = match render_args(&env, step, &context) {
+                Ok(args) => {
+                    outcome.args = Some(args.clone());
+                    self.call(step, args, ctx).await
+                }
+                Err(e) => Err(e),
+            };
+
+            match executed {
+                Ok(result) => {
+                    outcome.status = StepStatus::Completed;
+                    if let Some(id) = &step.id {
+                        results.insert(id.clone(), result.clone());
+                    }
+                    previous = result.clone();
+                    outcome.result = Some(result);
+                }
+                Err(error) => {
+                    outcome.status = StepStatus::Failed;
+                    outcome.error = Some(error.clone());
+                    if !step.continue_on_error {
+                        self.emit(ctx, index, step, StepStatus::Failed);
+                        return Err(format!("{} failed: {}", label, error));
+                    }
+                    let failed = serde_json::json!({ "error": error });
+                    if let Some(id) = &step.id {
+                        results.insert(id.clone(), failed.clone());
+                    }
+                    previous = failed;
+                }
+            }
+            self.emit(ctx, index, step, outcome.status);
+            outcomes.push(outcome);
+        }
+
+        Ok(RecipeOutcome {
+            steps: outcomes,
+            output: previous,
+        })
+    }
+
+    async fn call(&self, step: &RecipeStep, args: Value, ctx: &ToolContext) -> Result<Value, String> {
+        let tool = self
+            .tools
+            .iter()
+            .find(|t| t.name() == step.tool)
+            .ok_or_else(|| format!("Unknown tool '{}'", step.tool))?;
+        tool.validate_args(&args).await?;
+        if let Some(policy) = &self.read_only_policy {
+            policy.check(tool.as_ref(), &args)?;
+        }
+        let result = crate::tools::execute_with_policy(tool.as_ref(), &args, ctx)
+            .await
+            .map_err(|e| e.to_string())?;
+        if !tool.verify_result(&result) {
+            return Err(format!("Unsuccessful result: {}", result));
+        }
+        Ok(result)
+    }
+
+    fn emit(&self, ctx: &ToolContext, index: usize, step: &RecipeStep, status: StepStatus) {
+        if let Some(observer) = &ctx.observer {
+            let event = AgentEvent::SkillRecipeStep {
+                skill: self.skill.clone(),
+                index,
+                step_id: step.id.clone(),
+                tool: step.tool.clone(),
+                status,
+            };
+            let _ = observer.emit(
+                &format!("session:{}", ctx.session_id),
+                serde_json::to_value(event).unwrap_or_default(),
+            );
+        }
+    }
+}
+
+fn step_label(index: usize, step: &RecipeStep) -> String {
+    match &step.id {
+        Some(id) => format!("Step {} '{}' ({})", index + 1, id, step.tool),
+        None => format!("Step {} ({})", index + 1, step.tool),
+    }
+}
+
+fn template_context(inputs: &Value, results: &Map<String, Value>, previous: &Value) -> Value {
+    let mut context = inputs.as_object().cloned().unwrap_or_default();
+    context.insert("inputs".to_string(), inputs.clone());
+    context.insert("steps".to_string(), Value::Object(results.clone()));
+    context.insert("previous".to_string(), previous.clone());
+    Value::Object(context)
+}
+
+/// `Some(expr)` for a string that is exactly one `{{ expr }}`
+fn single_expression(template: &str) -> Option<&str> {
+    let inner = template.trim().strip_prefix("{{")?.strip_suffix("}}")?;
+    (!inner.contains("{{") && !inner.contains("}}")).then(|| inner.trim())
+}
+
+fn render_args(env: &Environment<'_>, step: &RecipeStep, context: &Value) -> Result<Value, String> {
+    let mut rendered = Map::new();
+    for (key, value) in step.args.as_object().into_iter().flatten() {
+        let value = match value {
+            Value::String(template) if step.tool == "bash" && key == "command" => {
+                Value::String(command::render_command(template, context)?)
+            }
+            other => render_value(env, other, context)?,
+        };
+        rendered.insert(key.clone(), value);
+    }
+    Ok(Value::Object(rendered))
+}
+
+fn render_value(env: &Environment<'_>, value: &Value, context: &Value) -> Result<Value, String> {
+    match value {
+        Value::String(template) if template.contains("{{") || template.contains("{%") => {
+            if let Some(expr) = single_expression(template) {
+                let value = env
+                    .compile_expression(expr)
+                    .and_then(|e| e.eval(context))
+                    .map_err(|e| format!("Failed to evaluate '{}': {}", template, e))?;
+                if value.is_undefined() {
+                    return Ok(Value::Null);
+                }
+                return serde_json::to_value(&value).map_err(|e| e.to_string());
+            }
+            env.render_str(template, context)
+                .map(Value::String)
+                .map_err(|e| format!("Failed to render '{}': {}", template, e))
+        }
+        Value::Array(items) => items
+            .iter()
+            .map(|item| render_value(env, item, context))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, item)| render_value(env, item, context).map(|v| (key.clone(), v)))
+            .collect::<Result<Map<_, _>, _>>()
+            .map(Value::Object),
+        other => Ok(other.clone()),
+    }
+}
+
+fn check_templates(env: &Environment<'_>, value: &Value) -> Result<(), String> {
+    match value {
+        Value::String(template) => {
+            let compiled = match single_expression(template) {
+                Some(expr) => env.compile_expression(expr).map(|_| ()),
+                None => env.template_from_str(template).map(|_| ()),
+            };
+            compiled.map_err(|e| format!("invalid template '{}': {}", template, e))
+        }
+        Value::Array(items) => items.iter().try_for_each(|item| check_templates(env, item)),
+        Value::Object(map) => map.values().try_for_each(|item| check_templates(env, item)),
+        _ => Ok(()),
+    }
+}
+
+/// Reject a recipe that could never run: unknown tools, duplicate ids, or
+/// templates and conditions that do not compile
+pub fn check_recipe(steps: &[RecipeStep]) -> Result<(), String> {
+    let env = Environment::new();
+    let mut ids = HashSet::new();
+    for (index, step) in steps.iter().enumerate() {
+        let label = step_label(index, step);
+        if !RECIPE_TOOLS.contains(&step.tool.as_str()) {
+            return Err(format!(
+                "{}: unknown tool (expected one of {})",
+                label,
+                RECIPE_TOOLS.join(", ")
+            ));
+        }
+        if !step.args.is_object() {
+            return Err(format!("{}: args must be a JSON object", label));
+        }
+        if let Some(id) = &step.id {
+            if !ids.insert(id.as_str()) {
+                return Err(format!("{}: duplicate step id", label));
+            }
+        }
+        if let Some(condition) = &step.when {
+            env.compile_expression(condition)
+                .map_err(|e| format!("{}: invalid 'when' condition: {}", label, e))?;
+        }
+        check_templates(&env, &step.args).map_err(|e| format!("{}: {}", label, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::permissions::PermissionManager;
+    use async_trait::async_trait;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    /// Records its arguments and echoes them back
+    struct Recorder {
+        name: &'static str,
+        calls: Arc<Mutex<Vec<Value>>>,
+    }
+
+    #[async_trait]
+    impl Tool for Recorder {
+        fn name(&self) -> &str {
+            self.name
+        }
+        fn description(&self) -> &str {
+            "records calls"
+        }
+        fn parameters_schema(&self) -> Value {
+            json!({ "type": "object" })
+        }
+        async fn execute(&self, args: Value, _ctx: &ToolContext) -> Result<Value, String> {
+            if args["fail"] == json!(true) {
+                return Err("boom".to_string());
+            }
+            self.calls.lock().unwrap().push(args.clone());
+            Ok(json!({ "status": 200, "echo": args }))
+        }
+    }
+
+    fn step(id: Option<&str>, tool: &str, args: Value) -> RecipeStep {
+        RecipeStep {
+            id: id.map(str::to_string),
+            tool: tool.to_string(),
+            args,
+            when: None,
+            continue_on_error: false,
+        }
+    }
+
+    fn runner(calls: &Arc<Mutex<Vec<Value>>>) -> RecipeRunner {
+        RecipeRunner::new(
+            "test",
+            ["http", "bash", "filesystem"]
+                .into_iter()
+                .map(|name| {
+                    Box::new(Recorder {
+                        name,
+                        calls: calls.clone(),
+                    }) as Box<dyn Tool>
+                })
+                .collect(),
+        )
+    }
+
+    fn ctx() -> ToolContext {
+        ToolContext {
+            permissions: Arc::new(PermissionManager::new()),
+            observer: None,
+            session_id: "test".to_string(),
+            scope: Default::default(),
+            job_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_steps_see_inputs_and_earlier_results() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut save = step(
+            None,
+            "filesystem",
+            json!({ "path": "out/{{ name }}.txt", "content": "{{ steps.fetch.echo.url }}", "size": "{{ steps.fetch.status }}" }),
+        );
+        save.when = Some("steps.fetch.status == 200".to_string());
+        let mut skipped = step(None, "bash", json!({ "command": "echo never" }));
+        skipped.when = Some("steps.fetch.status == 404".to_string());
+        let recipe = SkillRecipe {
+            parameters: json!({ "type": "object" }),
+            steps: vec![step(Some("fetch"), "http", json!({ "url": "https://x.test/{{ name }}" })), save, skipped],
+        };
+
+        let outcome = runner(&calls).run(&recipe, &json!({ "name": "report" }), &ctx()).await.unwrap();
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[1]["path"], "out/report.txt");
+        assert_eq!(calls[1]["content"], "https://x.test/report");
+        // A lone expression keeps its JSON type
+        assert_eq!(calls[1]["size"], 200);
+        assert_eq!(outcome.steps[2].status, StepStatus::Skipped);
+        assert_eq!(outcome.output["echo"]["path"], "out/report.txt");
+    }
+
+    #[tokio::test]
+    async fn test_bash_command_values_are_quoted() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let recipe = SkillRecipe {
+            parameters: json!({ "type": "object" }),
+            steps: vec![step(None, "bash", json!({ "command": "grep -c {{ pattern }} notes.txt" }))],
+        };
+        runner(&calls)
+            .run(&recipe, &json!({ "pattern": "x; rm -rf ~" }), &ctx())
+            .await
+            .unwrap();
+        assert_eq!(calls.lock().unwrap()[0]["command"], "grep -c 'x; rm -rf ~' notes.txt");
+    }
+
+    #[tokio::test]
+    async fn test_failure_stops_unless_continue_on_error() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut recipe = SkillRecipe {
+            parameters: json!({ "type": "object" }),
+            steps: vec![
+                step(Some("first"), "http", json!({ "fail": true })),
+                step(None, "bash", json!({ "command": "echo after" })),
+            ],
+        };
+        let error = runner(&calls).run(&recipe, &json!({}), &ctx()).await.unwrap_err();
+        assert_eq!(error, "Step 1 'first' (http) failed: boom");
+        assert!(calls.lock().unwrap().is_empty());
+
+        recipe.steps[0].continue_on_error = true;
+        let outcome = runner(&calls).run(&recipe, &json!({}), &ctx()).await.unwrap();
+        assert_eq!(outcome.steps[0].status, StepStatus::Failed);
+        assert_eq!(outcome.steps[1].status, StepStatus::Completed);
+    }
+
+    #[tokio::test]
+    async fn test_read_only_policy_checks_each_step() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let recipe = SkillRecipe {
+            parameters: json!({ "type": "object" }),
+            steps: vec![
+                step(None, "bash", json!({ "command": "echo {{ word }}" })),
+                step(None, "http", json!({ "url": "https://x.test", "method": "POST" })),
+            ],
+        };
+        let error = runner(&calls)
+            .with_read_only_policy(Some(ReadOnlyPolicy::new(vec!["echo".to_string()])))
+            .run(&recipe, &json!({ "word": "hi" }), &ctx())
+            .await
+            .unwrap_err();
+        assert!(error.starts_with("Step 2 (http) failed: Read-only mode"), "{}", error);
+        assert_eq!(calls.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_read_only_derived_from_steps() {
+        let runner = RecipeRunner::with_builtin_tools("test", PathBuf::from("."), "direct".to_string());
+        let mut recipe = SkillRecipe {
+            parameters: json!({ "type": "object" }),
+            steps: vec![
+                step(None, "http", json!({ "url": "{{ url }}" })),
+                step(None, "filesystem", json!({ "operation": "read_file", "path": "{{ path }}" })),
+                step(None, "bash", json!({ "command": "wc -c notes.txt" })),
+            ],
+        };
+        assert!(runner.is_read_only(&recipe));
+
+        recipe.steps.push(step(None, "bash", json!({ "command": "find . {{ flag }}" })));
+        assert!(!runner.is_read_only(&recipe));
+        recipe.steps.pop();
+        recipe.steps.push(step(None, "filesystem", json!({ "operation": "write_file", "path": "out.txt" })));
+        assert!(!runner.is_read_only(&recipe));
+    }
+
+    #[test]
+    fn test_check_recipe() {
+        assert!(check_recipe(&[step(None, "bash", json!({ "command": "ls" }))]).is_ok());
+        assert!(check_recipe(&[step(None, "email", json!({}))]).is_err());
+        assert!(check_recipe(&[step(Some("a"), "bash", json!({})), step(Some("a"), "http", json!({}))]).is_err());
+        assert!(check_recipe(&[step(None, "http", json!({ "url": "{{ url" }))]).is_err());
+
+        let mut bad_condition = step(None, "bash", json!({}));
+        bad_condition.when = Some("steps.a ==".to_string());
+        assert!(check_recipe(&[bad_condition]).is_err());
+    }
+}
@@ -0,0 +1,48 @@
+//! Sandbox abstraction shared by the skill execution backends
+
+use crate::models::SandboxConfig;
+use crate::skills::docker::{DockerSandbox, ExecutionResult};
+use async_trait::async_trait;
+use std::path::Path;
+
+/// An isolated execution environment for skill commands.
+///
+/// The workspace is exposed to the command as `/workspace` (read-write) and
+/// skill files as `/skill` (read-only), whatever the backend.
+#[async_trait]
+pub trait Sandbox: Send + Sync {
+    /// Backend name, e.g. "docker" or "wasm"
+    fn name(&self) -> &str;
+
+    /// Whether the backend can run on this machine
+    fn is_available(&self) -> bool;
+
+    async fn execute(
+        &self,
+        command: &str,
+        workspace_path: &Path,
+        skill_files_path: Option<&Path>,
+        config: &SandboxConfig,
+    ) -> Result<ExecutionResult, String>;
+}
+
+#[async_trait]
+impl Sandbox for DockerSandbox {
+    fn name(&self) -> &str {
+        "docker"
+    }
+
+    fn is_available(&self) -> bool {
+        DockerSandbox::is_available(self)
+    }
+
+    async fn execute(
+        &self,
+        command: &str,
+        workspace_path: &Path,
+        skill_files_path: Option<&Path>,
+        config: &SandboxConfig,
+    ) -> Result<ExecutionResult, String> {
+        DockerSandbox::execute(self, command, workspace_path, skill_files_path, config).await
+    }
+}
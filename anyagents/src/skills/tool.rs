@@ -1,8 +1,14 @@
+use crate::events::AgentEvent;
 use crate::models::SandboxConfig;
-use crate::skills::docker::DockerSandbox;
+use crate::permissions::ReadOnlyPolicy;
+use crate::skills::command;
+use crate::skills::docker::{DockerSandbox, ExecutionResult};
 use crate::skills::loader::LoadedSkill;
+use crate::skills::provision;
+use crate::skills::recipe::RecipeRunner;
 use crate::tools::{Tool, ToolContext};
 use async_trait::async_trait;
+use serde::Serialize;
 use serde_json::{json, Value};
 
 
@@ -12,15 +18,22 @@ pub struct SkillTool {
     pub skill: LoadedSkill,
     pub workspace_path: std::path::PathBuf,
     pub agent_execution_mode: String,
+    /// Applied to every step of a recipe, like to the agent's own tool calls
+    pub read_only_policy: Option<ReadOnlyPolicy>,
 }
 
 impl SkillTool {
     pub fn new(skill: LoadedSkill, workspace_path: std::path::PathBuf, agent_execution_mode: String) -> Self {
-        // Build an enhanced description that instructs the LLM to read the skill content first
-        let enhanced_description = format!(
-            "{}. IMPORTANT: Before using this skill, call it with args='read' to get detailed instructions and code examples.",
-            skill.skill.description.trim_end_matches('.')
-        );
+        // Build an enhanced description that instructs the LLM to read the skill content first.
+        // Skills with declared parameters (commands or recipes) are self-describing through their schema.
+        let enhanced_description = if skill.skill.invocation.is_some() || skill.skill.recipe.is_some() {
+            skill.skill.description.clone()
+        } else {
+            format!(
+                "{}. IMPORTANT: Before using this skill, call it with args='read' to get detailed instructions and code examples.",
+                skill.skill.description.trim_end_matches('.')
+            )
+        };
 
         Self {
             name: skill.skill.name.clone(),
@@ -28,8 +41,23 @@ impl SkillTool {
             skill,
             workspace_path,
             agent_execution_mode,
+            read_only_policy: None,
         }
     }
+
+    pub fn with_read_only_policy(mut self, policy: Option<ReadOnlyPolicy>) -> Self {
+        self.read_only_policy = policy;
+        self
+    }
+
+    fn recipe_runner(&self) -> RecipeRunner {
+        RecipeRunner::with_builtin_tools(
+            self.name.clone(),
+            self.workspace_path.clone(),
+            self.agent_execution_mode.clone(),
+        )
+        .with_read_only_policy(self.read_only_policy.clone())
+    }
 }
 
 #[async_trait]
@@ -43,6 +71,12 @@ impl Tool for SkillTool {
     }
 
     fn parameters_schema(&self) -> Value {
+        if let Some(invocation) = &self.skill.skill.invocation {
+            return invocation.parameters.clone();
+        }
+        if let Some(recipe) = &self.skill.skill.recipe {
+            return recipe.parameters.clone();
+        }
         json!({
             "type": "object",
             "properties": {
@@ -55,20 +89,85 @@ impl Tool for SkillTool {
         })
     }
 
-    async fn execute(&self, args: Value, _ctx: &ToolContext) -> Result<Value, String> {
+    async fn validate_args(&self, args: &Value) -> Result<(), String> {
+        if let Some(recipe) = &self.skill.skill.recipe {
+            return command::validate(&recipe.parameters, args);
+        }
+        match &self.skill.skill.invocation {
+            Some(invocation) => command::validate(&invocation.parameters, args),
+            None => Ok(()),
+        }
+    }
+
+    fn is_read_only(&self, _args: &Value) -> bool {
+        // A recipe only calls the built-in tools, which can judge their own
+        // steps. The args of a command go to the skill's own scripts,
+        // whatever they look like: only the manifest can vouch for those.
+        match &self.skill.skill.recipe {
+            Some(recipe) => self.recipe_runner().is_read_only(recipe),
+            None => self.skill.skill.read_only,
+        }
+    }
+
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<Value, String> {
         let command_str = args.get("args").and_then(|v| v.as_str()).unwrap_or("");
-        
-        // Handle "read" content request for Knowledge skills
-        if command_str.trim().eq_ignore_ascii_case("read") {
+
+        if let Some(recipe) = &self.skill.skill.recipe {
+            command::validate(&recipe.parameters, &args)?;
+            let outcome = self.recipe_runner().run(recipe, &args, ctx).await?;
+            return serde_json::to_value(outcome).map_err(|e| e.to_string());
+        }
+
+        if let Some(invocation) = &self.skill.skill.invocation {
+            command::validate(&invocation.parameters, &args)?;
+        } else if command_str.trim().eq_ignore_ascii_case("read") {
+             // Handle "read" content request for Knowledge skills
              return Ok(json!({ "content": self.skill.skill.body }));
         }
 
-        let mut sandbox = DockerSandbox::new();
-        sandbox.init().await;
+        let result = self.run(&args, ctx).await?;
+        if result.success {
+            Ok(json!({ "stdout": result.stdout, "stderr": result.stderr }))
+        } else if result.timed_out {
+            Err(format!("Skill execution timed out.\nStderr: {}", result.stderr))
+        } else {
+            Err(format!("Skill execution failed: {}\nStderr: {}", result.stdout, result.stderr))
+        }
+    }
+}
+
+/// Where a skill command runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkillRuntime {
+    Wasm,
+    MicroVm,
+    Docker,
+    Local,
+}
+
+impl SkillRuntime {
+    /// Isolated runtimes mount the skill files at /skill
+    fn is_isolated(self) -> bool {
+        self != SkillRuntime::Local
+    }
+}
+
+impl SkillTool {
+    /// Pick the runtime from the agent's execution mode and the skill's requirements
+    pub async fn resolve_runtime(&self) -> Result<SkillRuntime, String> {
+        // WASM skills carry their own isolation and never need Docker
+        if crate::skills::wasm::is_wasm_config(self.skill.skill.sandbox_config.as_ref()) {
+            return Ok(SkillRuntime::Wasm);
+        }
+        // Strict isolation agents run every skill command inside a microVM
+        if self.agent_execution_mode == "microvm" {
+            return Ok(SkillRuntime::MicroVm);
+        }
 
         // Check Docker availability first
-        let docker_available = sandbox.is_available();
-        
+        let docker_available = DockerSandbox::check_available().await;
+
         // Determine final mode based on Agent preference AND Skill requirement
         // Agent preference takes precedence for safety (e.g. if agent=sandbox, we MUST use sandbox)
         let use_docker = match self.agent_execution_mode.as_str() {
@@ -102,6 +201,29 @@ impl Tool for SkillTool {
                  }
              }
         };
+        Ok(if use_docker { SkillRuntime::Docker } else { SkillRuntime::Local })
+    }
+
+    /// The shell command a call runs; `skill_dir` is where the skill files are
+    pub fn render_command(&self, args: &Value, skill_dir: &str) -> Result<String, String> {
+        if let Some(invocation) = &self.skill.skill.invocation {
+            let mut values = args.clone();
+            if let Value::Object(map) = &mut values {
+                map.insert("skill_dir".to_string(), Value::String(skill_dir.to_string()));
+            }
+            return command::render_command(&invocation.command, &values);
+        }
+        let command_str = args.get("args").and_then(|v| v.as_str()).unwrap_or("");
+        Ok(if !command_str.is_empty() {
+             command_str.to_string()
+        } else {
+             args.to_string()
+        })
+    }
+
+    /// Run one call to completion and return its raw output, whatever the exit code
+    pub async fn run(&self, args: &Value, ctx: &ToolContext) -> Result<ExecutionResult, String> {
+        let runtime = self.resolve_runtime().await?;
 
         // Prepare workspace
         // Use configured workspace path (User CWD)
@@ -124,57 +246,138 @@ impl Tool for SkillTool {
            std::fs::write(&file_path, &file.content).map_err(|e| format!("Failed to write file {}: {}", rel_path, e))?;
         }
 
-        let command = if !command_str.is_empty() {
-             command_str.to_string()
+        let skill_dir = if runtime.is_isolated() {
+            "/skill".to_string()
         } else {
-             args.to_string()
+            skill_files_path.to_string_lossy().to_string()
         };
+        let command = self.render_command(args, &skill_dir)?;
 
-        if use_docker {
-            let config = self.skill.skill.sandbox_config.clone().unwrap_or(SandboxConfig {
-                image: Some("alpine:latest".to_string()),
-                memory_limit: Some("128m".to_string()),
-                cpu_limit: None,
-                timeout_seconds: Some(60),
-                network_enabled: Some(false),
-            });
-            
-            // Mount skill files separately to /skill (RO)
-            println!("Executing via Docker sandbox...");
-            let result = sandbox.execute(&command, workspace_path, Some(skill_files_path), &config).await?;
-            if result.success {
-                Ok(json!({ "stdout": result.stdout, "stderr": result.stderr }))
-            } else {
-                Err(format!("Skill execution failed: {}\nStderr: {}", result.stdout, result.stderr))
+        let dependencies = self.skill.skill.dependencies.clone().filter(|d| !d.is_empty());
+        if dependencies.is_some() && matches!(runtime, SkillRuntime::Wasm | SkillRuntime::MicroVm) {
+            let error = format!(
+                "Skill '{}' declares dependencies, which can only be provisioned for Docker or local execution",
+                self.name
+            );
+            self.emit(ctx, AgentEvent::SkillProvisioningFailed { skill: self.name.clone(), error: error.clone() });
+            return Err(error);
+        }
+
+        match runtime {
+            SkillRuntime::Wasm => self.execute_wasm(&command, workspace_path, skill_files_path).await,
+            SkillRuntime::MicroVm => {
+                let config = self.skill.skill.sandbox_config.clone().unwrap_or_default();
+                let vm = crate::skills::MicroVmSandbox::new(crate::skills::MicroVmConfig::default());
+                crate::skills::Sandbox::execute(&vm, &command, workspace_path, Some(skill_files_path), &config).await
             }
-        } else {
-            // Local Execution Fallback
-            // For local execution, we need skill files accessible. 
-            // If the command relies on "/skill/script.py", it won't work locally.
-            // Assumption: Local execution commands are adapted or skills are designed to work relatively.
-            // But we can add the skill_files_path to PATH or PYTHONPATH?
-            // Or we just strictly run in workspace_path.
-            
-            let output = tokio::process::Command::new("sh")
-                .arg("-c")
-                .arg(&command)
-                .current_dir(workspace_path)
-                .output()
-                .await
-                .map_err(|e| format!("Failed to execute local command: {}", e))?;
-            
-            println!("Local execution in workspace: {:?}, command: {}", workspace_path, command);
-                
-            let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-            
-            if output.status.success() {
-                 Ok(json!({ "stdout": stdout, "stderr": stderr }))
-            } else {
-                 Err(format!("Local execution failed: {}\nStderr: {}", stdout, stderr))
+            SkillRuntime::Docker => {
+                let mut config = self.skill.skill.sandbox_config.clone().unwrap_or(SandboxConfig {
+                    image: Some("alpine:latest".to_string()),
+                    memory_limit: Some("128m".to_string()),
+                    cpu_limit: None,
+                    timeout_seconds: Some(60),
+                    network_enabled: Some(false),
+                    runtime: None,
+                    ..Default::default()
+                });
+
+                // Bake declared dependencies into a derived image; the sandbox has no network
+                if let Some(deps) = &dependencies {
+                    let base = config
+                        .image
+                        .as_deref()
+                        .map(|i| crate::skills::docker::DockerImage::from_string(i).to_image_name())
+                        .unwrap_or_else(|| crate::skills::docker::DockerImage::Python311.to_image_name());
+                    if !provision::is_image_cached(&self.name, &base, deps).await {
+                        self.emit(ctx, AgentEvent::SkillProvisioning { skill: self.name.clone(), dependencies: deps.clone() });
+                    }
+                    let image = provision::provision_image(&self.name, &base, deps)
+                        .await
+                        .map_err(|e| self.provisioning_failed(ctx, e))?;
+                    config.image = Some(image);
+                }
+
+                let mut sandbox = DockerSandbox::new();
+                sandbox.init().await;
+
+                // Mount skill files separately to /skill (RO)
+                println!("Executing via Docker sandbox...");
+                sandbox.execute(&command, workspace_path, Some(skill_files_path), &config).await
             }
+            SkillRuntime::Local => {
+                // Local Execution Fallback
+                // Skill files are not at /skill here; templates should use {{ skill_dir }}.
+                // Or we just strictly run in workspace_path.
+                let mut local = tokio::process::Command::new("sh");
+                local.arg("-c").arg(&command).current_dir(workspace_path);
+                if let Some(deps) = &dependencies {
+                    if !provision::is_local_cached(&self.name, deps) {
+                        self.emit(ctx, AgentEvent::SkillProvisioning { skill: self.name.clone(), dependencies: deps.clone() });
+                    }
+                    provision::provision_local(&self.name, deps)
+                        .await
+                        .map_err(|e| self.provisioning_failed(ctx, e))?
+                        .apply(&mut local);
+                }
+
+                let output = local
+                    .output()
+                    .await
+                    .map_err(|e| format!("Failed to execute local command: {}", e))?;
+
+                println!("Local execution in workspace: {:?}, command: {}", workspace_path, command);
+
+                Ok(ExecutionResult {
+                    success: output.status.success(),
+                    stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+                    stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+                    exit_code: output.status.code().unwrap_or(-1),
+                    timed_out: false,
+                })
+            }
+        }
+    }
+
+    fn emit(&self, ctx: &ToolContext, event: AgentEvent) {
+        if let Some(observer) = &ctx.observer {
+            let _ = observer.emit(
+                &format!("session:{}", ctx.session_id),
+                serde_json::to_value(event).unwrap_or_default(),
+            );
         }
     }
+
+    /// Report a provisioning error to the UI and hand it back for the tool result
+    fn provisioning_failed(&self, ctx: &ToolContext, error: String) -> String {
+        self.emit(ctx, AgentEvent::SkillProvisioningFailed { skill: self.name.clone(), error: error.clone() });
+        error
+    }
+
+    #[cfg(feature = "wasm-sandbox")]
+    async fn execute_wasm(
+        &self,
+        command: &str,
+        workspace_path: &std::path::Path,
+        skill_files_path: &std::path::Path,
+    ) -> Result<ExecutionResult, String> {
+        use crate::skills::Sandbox;
+
+        let config = self.skill.skill.sandbox_config.clone().unwrap_or_default();
+        let sandbox = crate::skills::WasmSandbox::new()?;
+        sandbox
+            .execute(command, workspace_path, Some(skill_files_path), &config)
+            .await
+    }
+
+    #[cfg(not(feature = "wasm-sandbox"))]
+    async fn execute_wasm(
+        &self,
+        _command: &str,
+        _workspace_path: &std::path::Path,
+        _skill_files_path: &std::path::Path,
+    ) -> Result<ExecutionResult, String> {
+        Err("Skill requires the WASM sandbox, but this build does not include the `wasm-sandbox` feature.".to_string())
+    }
 }
 
 #[cfg(test)]
@@ -196,8 +399,13 @@ mod tests {
             body: "".to_string(),
             category: None,
             requires_sandbox,
+            read_only: false,
             license: None,
             execution_mode,
+            dependencies: None,
+            invocation: None,
+            examples: Vec::new(),
+            recipe: None,
         };
         LoadedSkill {
             skill: parsed,
@@ -205,6 +413,41 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_read_only_only_when_declared() {
+        let mut skill = create_dummy_skill(false, None);
+        let tool = SkillTool::new(skill.clone(), PathBuf::from("."), "direct".to_string());
+        assert!(!tool.is_read_only(&json!({"args": "ls"})));
+        assert!(!tool.is_read_only(&json!({"args": "read"})));
+
+        skill.skill.read_only = true;
+        let tool = SkillTool::new(skill, PathBuf::from("."), "direct".to_string());
+        assert!(tool.is_read_only(&json!({"args": "anything"})));
+    }
+
+    #[test]
+    fn test_recipe_read_only_comes_from_steps() {
+        let mut skill = create_dummy_skill(false, None);
+        skill.skill.read_only = true;
+        skill.skill.recipe = Some(crate::models::SkillRecipe {
+            parameters: json!({ "type": "object" }),
+            steps: vec![crate::models::RecipeStep {
+                id: None,
+                tool: "filesystem".to_string(),
+                args: json!({ "operation": "write_file", "path": "out.txt", "content": "x" }),
+                when: None,
+                continue_on_error: false,
+            }],
+        });
+        let tool = SkillTool::new(skill.clone(), PathBuf::from("."), "direct".to_string());
+        assert!(!tool.is_read_only(&json!({})));
+
+        skill.skill.read_only = false;
+        skill.skill.recipe.as_mut().unwrap().steps[0].args = json!({ "operation": "list_dir", "path": "." });
+        let tool = SkillTool::new(skill, PathBuf::from("."), "direct".to_string());
+        assert!(tool.is_read_only(&json!({})));
+    }
+
     #[tokio::test]
     async fn test_agent_direct_mode_conflict() {
         // Case: Agent says direct, Skill requires sandbox -> Should Fail
@@ -215,10 +458,62 @@ mod tests {
             permissions: std::sync::Arc::new(crate::permissions::PermissionManager::new()),
             observer: None,
             session_id: "test".to_string(),
+            scope: Default::default(),
+            job_id: None,
         };
 
         let result = tool.execute(json!({"args": "echo test"}), &ctx).await;
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), "Skill requires sandbox but Agent is in 'direct' execution mode.");
     }
+
+    #[tokio::test]
+    async fn test_dependencies_unsupported_in_microvm() {
+        let mut skill = create_dummy_skill(false, None);
+        skill.skill.dependencies = Some(crate::models::SkillDependencies {
+            pip: vec!["pandas".to_string()],
+            ..Default::default()
+        });
+        let tool = SkillTool::new(skill, PathBuf::from("."), "microvm".to_string());
+
+        let ctx = ToolContext {
+            permissions: std::sync::Arc::new(crate::permissions::PermissionManager::new()),
+            observer: None,
+            session_id: "test".to_string(),
+            scope: Default::default(),
+            job_id: None,
+        };
+
+        let result = tool.execute(json!({"args": "python3 -c 'import pandas'"}), &ctx).await;
+        assert!(result.unwrap_err().contains("declares dependencies"));
+    }
+
+    #[tokio::test]
+    async fn test_structured_parameters_are_quoted() {
+        let mut skill = create_dummy_skill(false, Some("direct".to_string()));
+        skill.skill.invocation = Some(crate::models::SkillInvocation {
+            parameters: json!({
+                "type": "object",
+                "properties": { "text": { "type": "string" } },
+                "required": ["text"],
+                "additionalProperties": false
+            }),
+            command: "echo {{ text }}".to_string(),
+        });
+        let tool = SkillTool::new(skill, PathBuf::from("."), "direct".to_string());
+        assert_eq!(tool.parameters_schema()["required"], json!(["text"]));
+
+        let ctx = ToolContext {
+            permissions: std::sync::Arc::new(crate::permissions::PermissionManager::new()),
+            observer: None,
+            session_id: "test".to_string(),
+            scope: Default::default(),
+            job_id: None,
+        };
+
+        let result = tool.execute(json!({"text": "a; echo pwned"}), &ctx).await.unwrap();
+        assert_eq!(result["stdout"].as_str().unwrap().trim(), "a; echo pwned");
+
+        assert!(tool.validate_args(&json!({"args": "rm -rf /"})).await.is_err());
+    }
 }
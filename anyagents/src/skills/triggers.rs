@@ -0,0 +1,180 @@
+//! Skill trigger matching
+//!
+//! Skills can declare `triggers` in their frontmatter. When a user message matches
+//! one of them, the skill body is injected into the system prompt for that turn,
+//! so agents with many skills don't carry every guide in every request.
+//!
+//! Supported trigger forms:
+//! - `pdf` - keyword, matched case-insensitively on word boundaries
+//! - `regex:invoice-\d+` (or `/invoice-\d+/`) - regular expression
+//! - `semantic:convert a spreadsheet to charts` - fuzzy match on the meaningful words
+
+use crate::models::ParsedSkill;
+use regex::Regex;
+use std::collections::HashSet;
+
+/// Share of a semantic trigger's words that must appear in the message
+const SEMANTIC_THRESHOLD: f32 = 0.6;
+
+const STOPWORDS: &[&str] = &[
+    "a", "an", "the", "and", "or", "of", "to", "in", "on", "for", "with", "from", "by", "at",
+    "is", "are", "be", "it", "this", "that", "my", "me", "i", "you", "your", "please", "can",
+    "could", "would", "some", "into", "as",
+];
+
+#[derive(Debug, Clone)]
+pub enum SkillTrigger {
+    Keyword(Regex),
+    Pattern(Regex),
+    Semantic(Vec<String>),
+}
+
+impl SkillTrigger {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let raw = raw.trim();
+
+        if let Some(pattern) = raw.strip_prefix("regex:") {
+            return Self::pattern(pattern.trim());
+        }
+        if raw.len() > 2 && raw.starts_with('/') && raw.ends_with('/') {
+            return Self::pattern(&raw[1..raw.len() - 1]);
+        }
+        if let Some(phrase) = raw.strip_prefix("semantic:") {
+            let terms = normalize_terms(phrase);
+            if terms.is_empty() {
+                return Err(format!("Semantic trigger '{}' has no meaningful words", raw));
+            }
+            return Ok(SkillTrigger::Semantic(terms));
+        }
+
+        if raw.is_empty() {
+            return Err("Empty trigger".to_string());
+        }
+        Regex::new(&format!(r"(?i)\b{}\b", regex::escape(raw)))
+            .map(SkillTrigger::Keyword)
+            .map_err(|e| format!("Invalid keyword trigger '{}': {}", raw, e))
+    }
+
+    fn pattern(pattern: &str) -> Result<Self, String> {
+        Regex::new(&format!("(?i){}", pattern))
+            .map(SkillTrigger::Pattern)
+            .map_err(|e| format!("Invalid regex trigger '{}': {}", pattern, e))
+    }
+
+    pub fn matches(&self, message: &str) -> bool {
+        match self {
+            SkillTrigger::Keyword(re) | SkillTrigger::Pattern(re) => re.is_match(message),
+            SkillTrigger::Semantic(terms) => {
+                let message_terms: HashSet<String> = normalize_terms(message).into_iter().collect();
+                let hits = terms.iter().filter(|t| message_terms.contains(*t)).count();
+                hits as f32 / terms.len() as f32 >= SEMANTIC_THRESHOLD
+            }
+        }
+    }
+}
+
+/// Lowercase, drop stopwords and strip common English suffixes so that
+/// "converting spreadsheets" and "convert spreadsheet" compare equal.
+fn normalize_terms(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .filter(|w| !STOPWORDS.contains(&w.as_str()))
+        .map(|w| stem(&w))
+        .collect()
+}
+
+fn stem(word: &str) -> String {
+    for suffix in ["ing", "ed", "es", "s"] {
+        if word.len() > suffix.len() + 2 && word.ends_with(suffix) {
+            return word[..word.len() - suffix.len()].to_string();
+        }
+    }
+    word.to_string()
+}
+
+/// Returns true if any of the skill's triggers match the message.
+/// Skills without triggers never match (their guide stays available via `args="read"`).
+pub fn skill_matches(skill: &ParsedSkill, message: &str) -> bool {
+    let Some(triggers) = &skill.triggers else {
+        return false;
+    };
+
+    triggers.iter().any(|raw| match SkillTrigger::parse(raw) {
+        Ok(trigger) => trigger.matches(message),
+        Err(e) => {
+            log::warn!("Skill '{}': {}", skill.name, e);
+            false
+        }
+    })
+}
+
+/// Select the skills whose bodies should be injected for this message
+pub fn select_triggered_skills<'a>(skills: &'a [ParsedSkill], message: &str) -> Vec<&'a ParsedSkill> {
+    skills.iter().filter(|s| skill_matches(s, message)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn skill(name: &str, triggers: Option<Vec<&str>>) -> ParsedSkill {
+        ParsedSkill {
+            name: name.to_string(),
+            description: "Test".to_string(),
+            license: None,
+            category: None,
+            triggers: triggers.map(|t| t.into_iter().map(String::from).collect()),
+            requires_sandbox: false,
+            read_only: false,
+            sandbox_config: None,
+            execution_mode: None,
+            dependencies: None,
+            invocation: None,
+            examples: Vec::new(),
+            recipe: None,
+            body: format!("# {}", name),
+        }
+    }
+
+    #[test]
+    fn test_keyword_trigger() {
+        let t = SkillTrigger::parse("pdf").unwrap();
+        assert!(t.matches("Please summarize this PDF file"));
+        assert!(!t.matches("Open the pdfium docs"));
+    }
+
+    #[test]
+    fn test_regex_trigger() {
+        let t = SkillTrigger::parse(r"regex:invoice[-_ ]?\d+").unwrap();
+        assert!(t.matches("Where is Invoice-1234?"));
+        assert!(!t.matches("Where is the invoice?"));
+
+        let t = SkillTrigger::parse(r"/\.xlsx?$/").unwrap();
+        assert!(t.matches("open report.xlsx"));
+
+        assert!(SkillTrigger::parse("regex:(unclosed").is_err());
+    }
+
+    #[test]
+    fn test_semantic_trigger() {
+        let t = SkillTrigger::parse("semantic:convert spreadsheet into charts").unwrap();
+        assert!(t.matches("Can you help converting my spreadsheets to a few charts?"));
+        assert!(!t.matches("Write a poem about the sea"));
+    }
+
+    #[test]
+    fn test_select_triggered_skills() {
+        let skills = vec![
+            skill("pdf", Some(vec!["pdf", "document"])),
+            skill("xlsx", Some(vec!["regex:\\bexcel\\b"])),
+            skill("no-triggers", None),
+        ];
+
+        let selected = select_triggered_skills(&skills, "Extract tables from this document");
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].name, "pdf");
+
+        assert!(select_triggered_skills(&skills, "hello").is_empty());
+    }
+}
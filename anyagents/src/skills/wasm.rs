@@ -0,0 +1,272 @@
+//! WASM sandbox for skills that ship WebAssembly modules
+//!
+//! Runs `wasm32-wasi` modules with wasmtime. This is a Docker-free isolation
+//! option: the guest only sees the directories we preopen and has no network
+//! access (WASI preview 1 has no sockets).
+//!
+//! A skill opts in with `runtime: wasm` in its `sandbox_config`. The command is
+//! `<module.wasm> [args...]`, with the module resolved relative to the skill files.
+//!
+//! The runtime itself is behind the `wasm-sandbox` cargo feature; the capability
+//! mapping is always available so callers can inspect what a skill would get.
+
+use crate::models::SandboxConfig;
+use std::time::Duration;
+
+pub const DEFAULT_WASM_TIMEOUT_SECS: u64 = 60;
+pub const DEFAULT_WASM_MEMORY_BYTES: usize = 256 * 1024 * 1024;
+
+/// What a WASM guest is allowed to do, derived from the skill's SandboxConfig
+#[derive(Debug, Clone, PartialEq)]
+pub struct WasmCapabilities {
+    /// Preopen the workspace as `/workspace` (read-write)
+    pub workspace_dir: bool,
+    /// Preopen skill files as `/skill` (read-only)
+    pub skill_dir: bool,
+    /// Requested network access. Not grantable under WASI preview 1.
+    pub network_requested: bool,
+    pub max_memory_bytes: usize,
+    pub timeout: Duration,
+}
+
+impl WasmCapabilities {
+    pub fn from_config(config: &SandboxConfig) -> Self {
+        Self {
+            workspace_dir: true,
+            skill_dir: true,
+            network_requested: config.network_enabled == Some(true),
+            max_memory_bytes: config
+                .memory_limit
+                .as_deref()
+                .and_then(parse_memory_limit)
+                .unwrap_or(DEFAULT_WASM_MEMORY_BYTES),
+            timeout: Duration::from_secs(
+                config
+                    .timeout_seconds
+                    .map(u64::from)
+                    .unwrap_or(DEFAULT_WASM_TIMEOUT_SECS),
+            ),
+        }
+    }
+}
+
+/// Whether a skill's sandbox config selects the WASM runtime
+pub fn is_wasm_config(config: Option<&SandboxConfig>) -> bool {
+    config
+        .and_then(|c| c.runtime.as_deref())
+        .map(|r| r.eq_ignore_ascii_case("wasm") || r.eq_ignore_ascii_case("wasi"))
+        .unwrap_or(false)
+}
+
+/// Parse Docker-style memory limits ("256m", "1g", "512k", "1048576")
+pub fn parse_memory_limit(limit: &str) -> Option<usize> {
+    let limit = limit.trim().to_lowercase();
+    let (number, multiplier) = match limit.chars().last()? {
+        'k' => (&limit[..limit.len() - 1], 1024),
+        'm' => (&limit[..limit.len() - 1], 1024 * 1024),
+        'g' => (&limit[..limit.len() - 1], 1024 * 1024 * 1024),
+        'b' => (&limit[..limit.len() - 1], 1),
+        _ => (limit.as_str(), 1),
+    };
+    number.trim().parse::<usize>().ok().map(|n| n * multiplier)
+}
+
+#[cfg(feature = "wasm-sandbox")]
+pub use runtime::WasmSandbox;
+
+#[cfg(feature = "wasm-sandbox")]
+mod runtime {
+    use super::WasmCapabilities;
+    use crate::models::SandboxConfig;
+    use crate::skills::docker::ExecutionResult;
+    use crate::skills::sandbox::Sandbox;
+    use async_trait::async_trait;
+    use std::path::Path;
+    use wasmtime::{Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, Trap};
+    use wasmtime_wasi::pipe::MemoryOutputPipe;
+    use wasmtime_wasi::preview1::{self, WasiP1Ctx};
+    use wasmtime_wasi::{DirPerms, FilePerms, I32Exit, WasiCtxBuilder};
+
+    /// Cap on captured stdout/stderr
+    const MAX_OUTPUT_BYTES: usize = 1024 * 1024;
+
+    struct GuestState {
+        wasi: WasiP1Ctx,
+        limits: StoreLimits,
+    }
+
+    pub struct WasmSandbox {
+        engine: Engine,
+    }
+
+    impl WasmSandbox {
+        pub fn new() -> Result<Self, String> {
+            let mut config = Config::new();
+            config.async_support(true);
+            config.epoch_interruption(true);
+            let engine = Engine::new(&config).map_err(|e| format!("Failed to create WASM engine: {}", e))?;
+            Ok(Self { engine })
+        }
+    }
+
+    #[async_trait]
+    impl Sandbox for WasmSandbox {
+        fn name(&self) -> &str {
+            "wasm"
+        }
+
+        fn is_available(&self) -> bool {
+            true
+        }
+
+        #[tracing::instrument(
+            name = "sandbox.execute",
+            skip_all,
+            fields(backend = "wasm", startup_ms = tracing::field::Empty, exit_code = tracing::field::Empty)
+        )]
+        async fn execute(
+            &self,
+            command: &str,
+            workspace_path: &Path,
+            skill_files_path: Option<&Path>,
+            config: &SandboxConfig,
+        ) -> Result<ExecutionResult, String> {
+            let started = std::time::Instant::now();
+            let caps = WasmCapabilities::from_config(config);
+            if caps.network_requested {
+                log::warn!("WASM sandbox has no network support; network_enabled is ignored");
+            }
+
+            let mut parts = command.split_whitespace();
+            let module_name = parts.next().ok_or("Empty WASM command")?;
+            let module_path = match skill_files_path {
+                Some(dir) if dir.join(module_name).exists() => dir.join(module_name),
+                _ => workspace_path.join(module_name),
+            };
+            if !module_path.exists() {
+                return Err(format!("WASM module not found: {}", module_name));
+            }
+
+            let module = Module::from_file(&self.engine, &module_path)
+                .map_err(|e| format!("Failed to load WASM module: {}", e))?;
+
+            let stdout = MemoryOutputPipe::new(MAX_OUTPUT_BYTES);
+            let stderr = MemoryOutputPipe::new(MAX_OUTPUT_BYTES);
+
+            let mut args = vec![module_name.to_string()];
+            args.extend(parts.map(String::from));
+
+            let mut builder = WasiCtxBuilder::new();
+            builder.args(&args).stdout(stdout.clone()).stderr(stderr.clone());
+            if caps.workspace_dir {
+                builder
+                    .preopened_dir(workspace_path, "/workspace", DirPerms::all(), FilePerms::all())
+                    .map_err(|e| format!("Failed to mount workspace: {}", e))?;
+            }
+            if let (true, Some(skill_dir)) = (caps.skill_dir, skill_files_path) {
+                builder
+                    .preopened_dir(skill_dir, "/skill", DirPerms::READ, FilePerms::READ)
+                    .map_err(|e| format!("Failed to mount skill files: {}", e))?;
+            }
+
+            let state = GuestState {
+                wasi: builder.build_p1(),
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(caps.max_memory_bytes)
+                    .build(),
+            };
+            let mut store = Store::new(&self.engine, state);
+            store.limiter(|s| &mut s.limits);
+            store.set_epoch_deadline(1);
+
+            let mut linker: Linker<GuestState> = Linker::new(&self.engine);
+            preview1::add_to_linker_async(&mut linker, |s| &mut s.wasi)
+                .map_err(|e| format!("Failed to link WASI: {}", e))?;
+
+            // Interrupt the guest once the timeout elapses
+            let engine = self.engine.clone();
+            let timeout = caps.timeout;
+            let timer = tokio::spawn(async move {
+                tokio::time::sleep(timeout).await;
+                engine.increment_epoch();
+            });
+
+            let run = async {
+                let instance = linker.instantiate_async(&mut store, &module).await?;
+                // Module compiled and instantiated
+                tracing::Span::current().record("startup_ms", started.elapsed().as_millis() as u64);
+                let start = instance.get_typed_func::<(), ()>(&mut store, "_start")?;
+                start.call_async(&mut store, ()).await
+            };
+            let outcome = run.await;
+            timer.abort();
+
+            let (exit_code, timed_out) = match outcome {
+                Ok(()) => (0, false),
+                Err(e) => {
+                    if let Some(exit) = e.downcast_ref::<I32Exit>() {
+                        (exit.0, false)
+                    } else if e.downcast_ref::<Trap>() == Some(&Trap::Interrupt) {
+                        (-1, true)
+                    } else {
+                        return Err(format!("WASM execution failed: {}", e));
+                    }
+                }
+            };
+            tracing::Span::current().record("exit_code", exit_code);
+
+            Ok(ExecutionResult {
+                success: exit_code == 0 && !timed_out,
+                stdout: String::from_utf8_lossy(&stdout.contents()).to_string(),
+                stderr: String::from_utf8_lossy(&stderr.contents()).to_string(),
+                exit_code,
+                timed_out,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> SandboxConfig {
+        SandboxConfig {
+            image: None,
+            memory_limit: Some("64m".to_string()),
+            cpu_limit: None,
+            timeout_seconds: Some(5),
+            network_enabled: None,
+            runtime: Some("wasm".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_memory_limit() {
+        assert_eq!(parse_memory_limit("256m"), Some(256 * 1024 * 1024));
+        assert_eq!(parse_memory_limit("1G"), Some(1024 * 1024 * 1024));
+        assert_eq!(parse_memory_limit("512k"), Some(512 * 1024));
+        assert_eq!(parse_memory_limit("4096"), Some(4096));
+        assert_eq!(parse_memory_limit("lots"), None);
+    }
+
+    #[test]
+    fn test_capabilities_from_config() {
+        let caps = WasmCapabilities::from_config(&config());
+        assert!(caps.workspace_dir);
+        assert!(caps.skill_dir);
+        assert!(!caps.network_requested);
+        assert_eq!(caps.max_memory_bytes, 64 * 1024 * 1024);
+        assert_eq!(caps.timeout, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_is_wasm_config() {
+        assert!(is_wasm_config(Some(&config())));
+        let mut docker = config();
+        docker.runtime = None;
+        assert!(!is_wasm_config(Some(&docker)));
+        assert!(!is_wasm_config(None));
+    }
+}
@@ -0,0 +1,89 @@
+//! Line-level change reports built from two snapshots
+
+use serde::Serialize;
+use similar::TextDiff;
+
+/// Text files above this size are reported by size only
+pub const MAX_DIFFABLE_FILE_BYTES: u64 = 256 * 1024;
+/// Per-file cap on the unified diff text
+pub const MAX_FILE_DIFF_BYTES: usize = 16 * 1024;
+/// Cap on diff text across all files of one event
+pub const MAX_TOTAL_DIFF_BYTES: usize = 64 * 1024;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Deleted,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct FileChange {
+    pub path: String,
+    pub kind: ChangeKind,
+    pub binary: bool,
+    /// Current size (previous size for deleted files), when known
+    #[cfg_attr(feature = "ts", ts(type = "number | null"))]
+    pub size: Option<u64>,
+    /// Unified diff for text files; None for binary, oversized or unavailable content
+    pub diff: Option<String>,
+    /// The diff was cut short (or dropped) to stay within the size budget
+    pub truncated: bool,
+}
+
+/// Git's heuristic: a NUL byte near the start means binary
+pub fn is_binary(content: &[u8]) -> bool {
+    content.iter().take(8000).any(|b| *b == 0) || std::str::from_utf8(content).is_err()
+}
+
+/// Unified diff with 3 lines of context, cut on a line boundary at `max_bytes`
+pub fn unified_diff(path: &str, old: &str, new: &str, max_bytes: usize) -> (String, bool) {
+    let text = TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", path), &format!("b/{}", path))
+        .to_string();
+
+    if text.len() <= max_bytes {
+        return (text, false);
+    }
+
+    let mut cut = max_bytes;
+    while !text.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    let cut = text[..cut].rfind('\n').map(|i| i + 1).unwrap_or(cut);
+    (text[..cut].to_string(), true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let (diff, truncated) = unified_diff("a.txt", "one\ntwo\nthree\n", "one\n2\nthree\n", 1024);
+        assert!(!truncated);
+        assert!(diff.contains("--- a/a.txt"));
+        assert!(diff.contains("-two\n"));
+        assert!(diff.contains("+2\n"));
+    }
+
+    #[test]
+    fn test_unified_diff_is_bounded() {
+        let new: String = (0..1000).map(|i| format!("line {}\n", i)).collect();
+        let (diff, truncated) = unified_diff("big.txt", "", &new, 200);
+        assert!(truncated);
+        assert!(diff.len() <= 200);
+        assert!(diff.ends_with('\n'));
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(is_binary(&[0x89, b'P', b'N', b'G', 0, 0]));
+        assert!(!is_binary(b"plain text"));
+    }
+}
@@ -0,0 +1,250 @@
+//! Git-backed checkpoints for agent runs
+//!
+//! Before a mutating tool call the workspace state is committed to a hidden ref
+//! (`refs/anycowork/job-<id>/step-<n>`) using a throwaway index, so the user's
+//! branch, index and working tree are never touched. Checkpoints can be
+//! browsed with plain git (`git log refs/anycowork/...`) or restored.
+
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+pub const CHECKPOINT_REF_PREFIX: &str = "refs/anycowork";
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Checkpoint {
+    pub ref_name: String,
+    pub commit: String,
+    pub message: String,
+    pub created_at: i64,
+}
+
+pub struct GitCheckpoints {
+    root: PathBuf,
+}
+
+impl GitCheckpoints {
+    /// Returns None unless `root` is inside a git work tree
+    pub fn open<P: AsRef<Path>>(root: P) -> Option<Self> {
+        let checkpoints = Self {
+            root: root.as_ref().to_path_buf(),
+        };
+        match checkpoints.git(&["rev-parse", "--is-inside-work-tree"], None, None) {
+            Ok(out) if out.trim() == "true" => Some(checkpoints),
+            _ => None,
+        }
+    }
+
+    pub fn ref_name(job_id: &str, step: usize) -> String {
+        format!("{}/job-{}/step-{}", CHECKPOINT_REF_PREFIX, job_id, step)
+    }
+
+    fn git(&self, args: &[&str], index: Option<&Path>, stdin: Option<&str>) -> Result<String, String> {
+        let mut cmd = Command::new("git");
+        cmd.arg("-C")
+            .arg(&self.root)
+            .args(args)
+            // Checkpoint commits must not depend on the user's git identity
+            .env("GIT_AUTHOR_NAME", "AnyCowork")
+            .env("GIT_AUTHOR_EMAIL", "checkpoints@anycowork.local")
+            .env("GIT_COMMITTER_NAME", "AnyCowork")
+            .env("GIT_COMMITTER_EMAIL", "checkpoints@anycowork.local")
+            .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(index) = index {
+            cmd.env("GIT_INDEX_FILE", index);
+        }
+
+        let mut child = cmd.spawn().map_err(|e| format!("Failed to run git: {}", e))?;
+        if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+            pipe.write_all(input.as_bytes()).map_err(|e| e.to_string())?;
+        }
+        let output = child.wait_with_output().map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            return Err(format!(
+                "git {} failed: {}",
+                args.first().unwrap_or(&""),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// Commit the current workspace state (HEAD + working tree changes) to `ref_name`
+    fn commit_to_ref(&self, ref_name: &str, message: &str) -> Result<Checkpoint, String> {
+        let index_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let index = index_dir.path().join("index");
+
+        let head = self.git(&["rev-parse", "--verify", "-q", "HEAD"], None, None).ok();
+        let head = head.as_deref().map(str::trim);
+        match head {
+            Some(head) => self.git(&["read-tree", head], Some(&index), None)?,
+            None => self.git(&["read-tree", "--empty"], Some(&index), None)?,
+        };
+        // Only the workspace subtree; .gitignore is respected
+        self.git(&["add", "-A", "--", "."], Some(&index), None)?;
+        let tree = self.git(&["write-tree"], Some(&index), None)?;
+
+        let mut args = vec!["commit-tree", tree.trim(), "-m", message];
+        if let Some(head) = head {
+            args.extend(["-p", head]);
+        }
+        let commit = self.git(&args, None, None)?.trim().to_string();
+        self.git(&["update-ref", ref_name, &commit], None, None)?;
+
+        Ok(Checkpoint {
+            ref_name: ref_name.to_string(),
+            commit,
+            message: message.to_string(),
+            created_at: chrono::Utc::now().timestamp(),
+        })
+    }
+
+    pub fn create(&self, job_id: &str, step: usize, message: &str) -> Result<Checkpoint, String> {
+        self.commit_to_ref(&Self::ref_name(job_id, step), message)
+    }
+
+    /// Checkpoints (optionally of one job), newest first
+    pub fn list(&self, job_id: Option<&str>) -> Result<Vec<Checkpoint>, String> {
+        let pattern = match job_id {
+            Some(id) => format!("{}/job-{}/", CHECKPOINT_REF_PREFIX, id),
+            None => format!("{}/", CHECKPOINT_REF_PREFIX),
+        };
+        let out = self.git(
+            &[
+                "for-each-ref",
+                "--sort=-committerdate",
+                "--format=%(refname)%00%(objectname)%00%(committerdate:unix)%00%(contents:subject)",
+                &pattern,
+            ],
+            None,
+            None,
+        )?;
+
+        Ok(out
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.split('\0');
+                Some(Checkpoint {
+                    ref_name: parts.next()?.to_string(),
+                    commit: parts.next()?.to_string(),
+                    created_at: parts.next()?.parse().unwrap_or(0),
+                    message: parts.next().unwrap_or_default().to_string(),
+                })
+            })
+            .collect())
+    }
+
+    /// Restore the workspace to a checkpoint. The current state is saved first
+    /// (returned) so the restore itself can be undone.
+    pub fn restore(&self, ref_name: &str) -> Result<Checkpoint, String> {
+        if !ref_name.starts_with(CHECKPOINT_REF_PREFIX) {
+            return Err(format!("Not a checkpoint ref: {}", ref_name));
+        }
+        let target = self
+            .git(&["rev-parse", "--verify", &format!("{}^{{commit}}", ref_name)], None, None)?
+            .trim()
+            .to_string();
+
+        let backup_ref = format!(
+            "{}/restore-{}",
+            CHECKPOINT_REF_PREFIX,
+            chrono::Utc::now().timestamp_millis()
+        );
+        let backup = self.commit_to_ref(&backup_ref, &format!("Before restoring {}", ref_name))?;
+
+        // Files created after the checkpoint (within the workspace) are removed
+        let created = self.git(
+            &["diff-tree", "-r", "-z", "--name-only", "--relative", "--diff-filter=A", &target, &backup.commit],
+            None,
+            None,
+        )?;
+        for path in created.split('\0').filter(|p| !p.is_empty()) {
+            let _ = std::fs::remove_file(self.root.join(path));
+        }
+
+        // Files that differ are written back from the checkpoint tree
+        let changed = self.git(
+            &["diff-tree", "-r", "-z", "--name-only", "--relative", "--diff-filter=AMT", &backup.commit, &target],
+            None,
+            None,
+        )?;
+        if !changed.trim_matches('\0').is_empty() {
+            let index_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+            let index = index_dir.path().join("index");
+            self.git(&["read-tree", &target], Some(&index), None)?;
+            self.git(&["checkout-index", "-f", "-z", "--stdin"], Some(&index), Some(&changed))?;
+        }
+
+        Ok(backup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn init_repo() -> Option<tempfile::TempDir> {
+        let dir = tempfile::tempdir().unwrap();
+        let ok = Command::new("git")
+            .arg("init")
+            .arg("-q")
+            .arg(dir.path())
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false);
+        ok.then_some(dir)
+    }
+
+    #[test]
+    fn test_checkpoint_and_restore() {
+        let Some(dir) = init_repo() else {
+            return; // git not installed
+        };
+        let root = dir.path();
+        fs::write(root.join("a.txt"), "one").unwrap();
+
+        let checkpoints = GitCheckpoints::open(root).unwrap();
+        let cp = checkpoints.create("job1", 1, "before edit").unwrap();
+        assert_eq!(cp.ref_name, "refs/anycowork/job-job1/step-1");
+
+        fs::write(root.join("a.txt"), "two").unwrap();
+        fs::write(root.join("b.txt"), "new").unwrap();
+
+        let listed = checkpoints.list(Some("job1")).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].message, "before edit");
+
+        let backup = checkpoints.restore(&cp.ref_name).unwrap();
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "one");
+        assert!(!root.join("b.txt").exists());
+
+        // The restore can itself be undone
+        checkpoints.restore(&backup.ref_name).unwrap();
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "two");
+        assert!(root.join("b.txt").exists());
+
+        // The user's index is untouched
+        let status = checkpoints.git(&["diff", "--cached", "--name-only"], None, None).unwrap();
+        assert!(status.trim().is_empty());
+    }
+
+    #[test]
+    fn test_open_outside_git() {
+        let dir = tempfile::tempdir().unwrap();
+        // tempdirs may live inside a repo on some machines; only assert the negative case
+        if Command::new("git")
+            .arg("-C")
+            .arg(dir.path())
+            .args(["rev-parse", "--is-inside-work-tree"])
+            .output()
+            .map(|o| !o.status.success())
+            .unwrap_or(false)
+        {
+            assert!(GitCheckpoints::open(dir.path()).is_none());
+        }
+    }
+}
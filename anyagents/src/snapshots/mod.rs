@@ -1,15 +1,47 @@
+//! Workspace snapshots used to report which files a tool call touched
+//!
+//! Snapshots are incremental: a persistent index remembers the size, mtime and
+//! hash of every file, so only files whose (size, mtime) changed are re-read.
+//! The walk respects .gitignore/.ignore files and hashing runs in parallel.
+//!
+//! Small text files are also kept in a content-addressed blob store next to the
+//! index so `diff_contents` can show line diffs of what a tool changed.
+
+pub mod changes;
+pub mod checkpoints;
+
+pub use changes::{ChangeKind, FileChange};
+pub use checkpoints::{Checkpoint, GitCheckpoints};
+
+use changes::{is_binary, unified_diff, MAX_DIFFABLE_FILE_BYTES, MAX_FILE_DIFF_BYTES, MAX_TOTAL_DIFF_BYTES};
+
+use ignore::WalkBuilder;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::io::Read;
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Directories skipped even when they are not gitignored
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules", "dist", "out"];
+
+/// Files modified this recently are always re-hashed: a write within the
+/// filesystem's mtime granularity could otherwise go unnoticed.
+const RACY_WINDOW: Duration = Duration::from_secs(2);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Snapshot {
     pub id: String,
     pub timestamp: i64,
     pub file_hashes: HashMap<String, String>, // relative_path -> sha256_hash
+    #[serde(default)]
+    pub file_sizes: HashMap<String, u64>,
+    #[serde(default)]
+    pub binary_files: HashSet<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -19,60 +51,231 @@ pub struct SnapshotDiff {
     pub deleted_files: Vec<String>,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct IndexEntry {
+    size: u64,
+    mtime_ns: u64,
+    hash: String,
+    #[serde(default)]
+    binary: bool,
+}
+
 pub struct SnapshotManager {
     root_path: PathBuf,
+    index_path: Option<PathBuf>,
+    blob_dir: Option<PathBuf>,
+    /// Set when checkpoint mode is enabled and the workspace is a git repository
+    checkpoints: Option<GitCheckpoints>,
+    index: Mutex<HashMap<String, IndexEntry>>, // relative_path -> last known state
 }
 
 impl SnapshotManager {
+    /// Index is persisted under ~/.anycowork/snapshots, keyed by workspace path
     pub fn new<P: AsRef<Path>>(root_path: P) -> Self {
+        let index_path = dirs::home_dir().map(|home| {
+            home.join(".anycowork")
+                .join("snapshots")
+                .join(format!("{}.json", Self::workspace_key(root_path.as_ref())))
+        });
+        Self::with_index_path(root_path, index_path)
+    }
+
+    /// Use an explicit index file (or none, keeping the index in memory only)
+    pub fn with_index_path<P: AsRef<Path>>(root_path: P, index_path: Option<PathBuf>) -> Self {
+        let index = index_path
+            .as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+
         Self {
             root_path: root_path.as_ref().to_path_buf(),
+            blob_dir: index_path.as_ref().map(|p| p.with_extension("blobs")),
+            index_path,
+            checkpoints: None,
+            index: Mutex::new(index),
         }
     }
 
-    pub fn create_snapshot(&self) -> Result<Snapshot, String> {
-        let mut file_hashes = HashMap::new();
+    /// Turn on git checkpoints. Returns false (and stays off) outside git workspaces.
+    pub fn enable_checkpoints(&mut self) -> bool {
+        self.checkpoints = GitCheckpoints::open(&self.root_path);
+        self.checkpoints.is_some()
+    }
+
+    pub fn checkpoints(&self) -> Option<&GitCheckpoints> {
+        self.checkpoints.as_ref()
+    }
+
+    pub fn root_path(&self) -> &Path {
+        &self.root_path
+    }
+
+    fn workspace_key(root: &Path) -> String {
+        let canonical = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        let digest = Sha256::digest(canonical.to_string_lossy().as_bytes());
+        hex::encode(&digest[..8])
+    }
+
+    /// Hash a file and classify it as binary, keeping a copy of small text files for later diffs
+    fn hash_file(&self, path: &Path, size: u64) -> Option<(String, bool)> {
+        if size > MAX_DIFFABLE_FILE_BYTES {
+            let mut file = fs::File::open(path).ok()?;
+            let mut head = vec![0u8; 8000];
+            let read = file.read(&mut head).ok()?;
+            head.truncate(read);
+            let mut hasher = Sha256::new();
+            hasher.update(&head);
+            std::io::copy(&mut file, &mut hasher).ok()?;
+            return Some((hex::encode(hasher.finalize()), head.contains(&0)));
+        }
 
-        let walker = WalkDir::new(&self.root_path).into_iter().filter_entry(|e| {
-            // Always allow the root directory itself
-            if e.depth() == 0 {
-                return true;
+        let content = fs::read(path).ok()?;
+        let hash = hex::encode(Sha256::digest(&content));
+        let binary = is_binary(&content);
+        if let Some(blob_dir) = &self.blob_dir {
+            let blob = blob_dir.join(&hash);
+            if !binary && !blob.exists() {
+                let _ = fs::create_dir_all(blob_dir);
+                let _ = fs::write(blob, &content);
             }
-            let name = e.file_name().to_string_lossy();
-            // Skip hidden files/dirs and common large directories (except root)
-            !name.starts_with('.')
-                && name != "target"
-                && name != "node_modules"
-                && name != "dist"
-                && name != "out"
-        });
+        }
+        Some((hash, binary))
+    }
+
+    fn read_blob(&self, hash: &str) -> Option<String> {
+        let blob_dir = self.blob_dir.as_ref()?;
+        fs::read_to_string(blob_dir.join(hash)).ok()
+    }
+
+    /// Relative path, size and mtime of every tracked file in the workspace
+    fn scan(&self) -> Vec<(String, PathBuf, u64, u64)> {
+        let walker = WalkBuilder::new(&self.root_path)
+            .hidden(true)
+            .git_ignore(true)
+            .git_exclude(true)
+            .git_global(false)
+            .require_git(false)
+            .filter_entry(|e| {
+                e.depth() == 0 || !SKIPPED_DIRS.contains(&e.file_name().to_string_lossy().as_ref())
+            })
+            .build();
 
-        for entry in walker.filter_map(|e| e.ok()) {
-            if entry.file_type().is_file() {
+        walker
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
+            .filter_map(|entry| {
                 let relative_path = entry
                     .path()
                     .strip_prefix(&self.root_path)
-                    .map_err(|e| e.to_string())?
+                    .ok()?
                     .to_string_lossy()
                     .to_string();
+                let metadata = entry.metadata().ok()?;
+                let mtime_ns = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_nanos() as u64)
+                    .unwrap_or(0);
+                Some((relative_path, entry.into_path(), metadata.len(), mtime_ns))
+            })
+            .collect()
+    }
 
-                if let Ok(content) = fs::read(entry.path()) {
-                    let mut hasher = Sha256::new();
-                    hasher.update(&content);
-                    let result = hasher.finalize();
-                    let hash = hex::encode(result);
-                    file_hashes.insert(relative_path, hash);
-                }
-            }
+    pub fn create_snapshot(&self) -> Result<Snapshot, String> {
+        let files = self.scan();
+
+        let racy_after = SystemTime::now()
+            .checked_sub(RACY_WINDOW)
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+
+        let previous = {
+            let mut index = self.index.lock().map_err(|e| e.to_string())?;
+            std::mem::take(&mut *index)
+        };
+
+        // Reuse hashes of files whose size and mtime are unchanged; hash the rest in parallel
+        let entries: HashMap<String, IndexEntry> = files
+            .into_par_iter()
+            .filter_map(|(relative_path, path, size, mtime_ns)| {
+                let (hash, binary) = match previous.get(&relative_path) {
+                    Some(entry) if entry.size == size && entry.mtime_ns == mtime_ns && mtime_ns < racy_after => {
+                        (entry.hash.clone(), entry.binary)
+                    }
+                    _ => self.hash_file(&path, size)?,
+                };
+                Some((relative_path, IndexEntry { size, mtime_ns, hash, binary }))
+            })
+            .collect();
+
+        let changed = entries.len() != previous.len()
+            || entries.iter().any(|(path, entry)| previous.get(path) != Some(entry));
+
+        let file_hashes = entries
+            .iter()
+            .map(|(path, entry)| (path.clone(), entry.hash.clone()))
+            .collect();
+        let file_sizes = entries
+            .iter()
+            .map(|(path, entry)| (path.clone(), entry.size))
+            .collect();
+        let binary_files = entries
+            .iter()
+            .filter(|(_, entry)| entry.binary)
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        if changed {
+            self.save_index(&entries);
+            // Keep the previous generation's blobs: the caller usually diffs against it
+            self.prune_blobs(entries.values().chain(previous.values()));
         }
+        *self.index.lock().map_err(|e| e.to_string())? = entries;
 
         Ok(Snapshot {
             id: uuid::Uuid::new_v4().to_string(),
             timestamp: chrono::Utc::now().timestamp(),
             file_hashes,
+            file_sizes,
+            binary_files,
         })
     }
 
+    fn prune_blobs<'a>(&self, live: impl Iterator<Item = &'a IndexEntry>) {
+        let Some(blob_dir) = &self.blob_dir else {
+            return;
+        };
+        let live: HashSet<&str> = live.map(|e| e.hash.as_str()).collect();
+        if let Ok(entries) = fs::read_dir(blob_dir) {
+            for entry in entries.filter_map(|e| e.ok()) {
+                if !live.contains(entry.file_name().to_string_lossy().as_ref()) {
+                    let _ = fs::remove_file(entry.path());
+                }
+            }
+        }
+    }
+
+    /// Best effort: a missing index only costs a full re-hash next time
+    fn save_index(&self, entries: &HashMap<String, IndexEntry>) {
+        let Some(path) = &self.index_path else {
+            return;
+        };
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        match serde_json::to_string(entries) {
+            Ok(json) => {
+                if let Err(e) = fs::write(path, json) {
+                    log::warn!("Failed to save snapshot index {}: {}", path.display(), e);
+                }
+            }
+            Err(e) => log::warn!("Failed to serialize snapshot index: {}", e),
+        }
+    }
+
     pub fn diff(&self, old: &Snapshot, new: &Snapshot) -> SnapshotDiff {
         let mut new_files = Vec::new();
         let mut modified_files = Vec::new();
@@ -105,6 +308,78 @@ impl SnapshotManager {
             deleted_files,
         }
     }
+
+    /// Per-file changes between two snapshots, with bounded line diffs for text files
+    pub fn diff_contents(&self, old: &Snapshot, new: &Snapshot) -> Vec<FileChange> {
+        let diff = self.diff(old, new);
+        let mut changed: Vec<(String, ChangeKind)> = diff
+            .new_files
+            .into_iter()
+            .map(|p| (p, ChangeKind::Added))
+            .chain(diff.modified_files.into_iter().map(|p| (p, ChangeKind::Modified)))
+            .chain(diff.deleted_files.into_iter().map(|p| (p, ChangeKind::Deleted)))
+            .collect();
+        changed.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut budget = MAX_TOTAL_DIFF_BYTES;
+        changed
+            .into_iter()
+            .map(|(path, kind)| {
+                let size = match kind {
+                    ChangeKind::Deleted => old.file_sizes.get(&path).copied(),
+                    _ => new.file_sizes.get(&path).copied(),
+                };
+
+                let binary = old.binary_files.contains(&path) || new.binary_files.contains(&path);
+                let oversized = size.is_some_and(|s| s > MAX_DIFFABLE_FILE_BYTES);
+
+                // Content comes from the blob store; the current side can fall
+                // back to the workspace file if it still matches the snapshot
+                let content = |snapshot: &Snapshot, from_workspace: bool| -> Option<String> {
+                    let hash = snapshot.file_hashes.get(&path)?;
+                    self.read_blob(hash).or_else(|| {
+                        if !from_workspace {
+                            return None;
+                        }
+                        let text = fs::read_to_string(self.root_path.join(&path)).ok()?;
+                        (hex::encode(Sha256::digest(text.as_bytes())) == *hash).then_some(text)
+                    })
+                };
+
+                let (diff, truncated) = if binary || oversized {
+                    (None, false)
+                } else {
+                    let before = match kind {
+                        ChangeKind::Added => Some(String::new()),
+                        _ => content(old, false),
+                    };
+                    let after = match kind {
+                        ChangeKind::Deleted => Some(String::new()),
+                        _ => content(new, true),
+                    };
+                    match (before, after) {
+                        (Some(_), Some(_)) if budget == 0 => (None, true),
+                        (Some(before), Some(after)) => {
+                            let (text, truncated) =
+                                unified_diff(&path, &before, &after, MAX_FILE_DIFF_BYTES.min(budget));
+                            budget -= text.len();
+                            (Some(text), truncated)
+                        }
+                        _ => (None, false),
+                    }
+                };
+
+                FileChange {
+                    path,
+                    kind,
+                    binary,
+                    size,
+                    diff,
+                    truncated,
+                }
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -161,4 +436,90 @@ mod tests {
         let diff2 = manager.diff(&snap2, &snap3);
         assert!(diff2.deleted_files.contains(&"file_b.txt".to_string()));
     }
+
+    #[test]
+    fn test_snapshot_respects_gitignore() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join(".gitignore"), "build/\n*.log\n").unwrap();
+        fs::create_dir(root.join("build")).unwrap();
+        fs::write(root.join("build/output.bin"), "binary").unwrap();
+        fs::write(root.join("debug.log"), "log").unwrap();
+        fs::write(root.join("main.rs"), "fn main() {}").unwrap();
+
+        let manager = SnapshotManager::with_index_path(root, None);
+        let snap = manager.create_snapshot().unwrap();
+
+        let mut paths: Vec<&String> = snap.file_hashes.keys().collect();
+        paths.sort();
+        assert_eq!(paths, vec!["main.rs"]);
+    }
+
+    #[test]
+    fn test_diff_contents() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("workspace");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("notes.txt"), "alpha\nbeta\n").unwrap();
+        fs::write(root.join("image.png"), [0x89, b'P', b'N', b'G', 0, 0, 1]).unwrap();
+
+        let manager = SnapshotManager::with_index_path(&root, Some(dir.path().join("index.json")));
+        let before = manager.create_snapshot().unwrap();
+
+        fs::write(root.join("notes.txt"), "alpha\ngamma\n").unwrap();
+        fs::write(root.join("image.png"), [0x89, b'P', b'N', b'G', 0, 0, 2, 3]).unwrap();
+        fs::write(root.join("new.txt"), "hello\n").unwrap();
+        let after = manager.create_snapshot().unwrap();
+
+        let changes = manager.diff_contents(&before, &after);
+        assert_eq!(changes.len(), 3);
+
+        let image = changes.iter().find(|c| c.path == "image.png").unwrap();
+        assert!(image.binary);
+        assert_eq!(image.size, Some(8));
+        assert!(image.diff.is_none());
+
+        let notes = changes.iter().find(|c| c.path == "notes.txt").unwrap();
+        assert_eq!(notes.kind, ChangeKind::Modified);
+        let diff = notes.diff.as_ref().unwrap();
+        assert!(diff.contains("-beta\n") && diff.contains("+gamma\n"));
+
+        let new = changes.iter().find(|c| c.path == "new.txt").unwrap();
+        assert_eq!(new.kind, ChangeKind::Added);
+        assert!(new.diff.as_ref().unwrap().contains("+hello\n"));
+    }
+
+    #[test]
+    fn test_index_is_persisted_and_reused() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("workspace");
+        fs::create_dir(&root).unwrap();
+        let index_path = dir.path().join("index.json");
+
+        let file = root.join("old.txt");
+        fs::write(&file, "original").unwrap();
+        // Backdate the file so it is outside the racy window
+        let old = SystemTime::now() - Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+
+        let manager = SnapshotManager::with_index_path(&root, Some(index_path.clone()));
+        let snap1 = manager.create_snapshot().unwrap();
+        assert!(index_path.exists());
+
+        // Same size and mtime: a fresh manager trusts the stored hash without reading
+        let mut index: HashMap<String, IndexEntry> =
+            serde_json::from_str(&fs::read_to_string(&index_path).unwrap()).unwrap();
+        index.get_mut("old.txt").unwrap().hash = "cached".to_string();
+        fs::write(&index_path, serde_json::to_string(&index).unwrap()).unwrap();
+
+        let manager = SnapshotManager::with_index_path(&root, Some(index_path));
+        let snap2 = manager.create_snapshot().unwrap();
+        assert_eq!(snap2.file_hashes["old.txt"], "cached");
+        assert_ne!(snap1.file_hashes["old.txt"], "cached");
+    }
 }
@@ -0,0 +1,135 @@
+//! OpenTelemetry export of the runtime's `tracing` spans
+//!
+//! The runtime is instrumented with `tracing` spans:
+//!
+//! - `agent.job`: one user turn, with the number of steps it took
+//! - `llm.call`: provider and model; streaming calls add time to first token
+//!   and token usage
+//! - `tool.execute`: tool, attempts and outcome (ok, error, timed_out)
+//! - `sandbox.startup` / `sandbox.execute`: backend, startup time, exit code
+//! - `db.query`: the SQL of every query, without bound values
+//!
+//! Spans nest, so a slow step in a multi-step job shows up under its job.
+//! Nothing is collected unless the crate is built with the `otel` feature and
+//! `OTEL_EXPORTER_OTLP_ENDPOINT` is set; spans then go to that OTLP (gRPC)
+//! collector. `log` output is unaffected.
+
+use diesel::connection::{Instrumentation, InstrumentationEvent};
+
+/// Collector endpoint, e.g. `http://localhost:4317`
+pub const ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+/// Flushes pending spans when dropped; keep it for the life of the process
+pub struct TelemetryGuard {
+    #[cfg(feature = "otel")]
+    provider: opentelemetry_sdk::trace::TracerProvider,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Err(e) = self.provider.shutdown() {
+            log::warn!("Failed to flush telemetry: {}", e);
+        }
+    }
+}
+
+/// Start exporting spans if an endpoint is configured. Call once, from
+/// within the tokio runtime, before the database pool is opened.
+pub fn init(service_name: &str) -> Result<Option<TelemetryGuard>, String> {
+    let Some(endpoint) = crate::config::current().otel_endpoint else {
+        return Ok(None);
+    };
+    let guard = init_exporter(service_name, &endpoint)?;
+    log::info!("Exporting telemetry to {}", endpoint);
+    Ok(Some(guard))
+}
+
+#[cfg(feature = "otel")]
+fn init_exporter(service_name: &str, endpoint: &str) -> Result<TelemetryGuard, String> {
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::WithExportConfig;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()
+        .map_err(|e| format!("Failed to create OTLP exporter: {}", e))?;
+    let provider = opentelemetry_sdk::trace::TracerProvider::builder()
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        .with_resource(opentelemetry_sdk::Resource::new([opentelemetry::KeyValue::new(
+            "service.name",
+            service_name.to_string(),
+        )]))
+        .build();
+
+    let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("anycowork"));
+    tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer))
+        .map_err(|e| format!("Failed to install tracing subscriber: {}", e))?;
+    diesel::connection::set_default_instrumentation(|| Some(Box::new(QuerySpans::default())))
+        .map_err(|e| format!("Failed to instrument database queries: {}", e))?;
+
+    Ok(TelemetryGuard { provider })
+}
+
+#[cfg(not(feature = "otel"))]
+fn init_exporter(_service_name: &str, _endpoint: &str) -> Result<TelemetryGuard, String> {
+    Err(format!(
+        "{} is set, but this build has no OpenTelemetry export (build with the `otel` feature)",
+        ENDPOINT_ENV
+    ))
+}
+
+/// Diesel instrumentation that opens a `db.query` span per query
+#[derive(Default)]
+pub struct QuerySpans {
+    current: Option<tracing::Span>,
+}
+
+impl Instrumentation for QuerySpans {
+    fn on_connection_event(&mut self, event: InstrumentationEvent<'_>) {
+        match event {
+            InstrumentationEvent::StartQuery { query, .. } => {
+                self.current = Some(tracing::info_span!(
+                    "db.query",
+                    sql = %query_sql(&query.to_string()),
+                    error = tracing::field::Empty,
+                ));
+            }
+            InstrumentationEvent::FinishQuery { error, .. } => {
+                // Dropping the span ends it
+                if let (Some(span), Some(error)) = (self.current.take(), error) {
+                    span.record("error", tracing::field::display(error));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The statement of a debug-printed query, without its bound values
+fn query_sql(debug: &str) -> &str {
+    debug.split(" -- binds: ").next().unwrap_or(debug).trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_sql_drops_binds() {
+        assert_eq!(
+            query_sql("SELECT * FROM `messages` WHERE `id` = ? -- binds: [\"secret\"]"),
+            "SELECT * FROM `messages` WHERE `id` = ?"
+        );
+        assert_eq!(query_sql("PRAGMA foreign_keys = ON"), "PRAGMA foreign_keys = ON");
+    }
+
+    #[test]
+    fn test_init_without_endpoint_is_off() {
+        if crate::config::current().otel_endpoint.is_none() {
+            assert!(init("test").unwrap().is_none());
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use super::{Tool, ToolContext};
+use crate::bus::{AgentBus, BusMessage, BusPayload};
+use crate::locks::LockScope;
+use crate::models::Agent;
+use async_trait::async_trait;
+use diesel::prelude::*;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+/// Sends typed messages to other agents over the `AgentBus`
+pub struct MessageAgentTool {
+    bus: Arc<AgentBus>,
+    agent_id: String,
+}
+
+impl MessageAgentTool {
+    pub fn new(bus: Arc<AgentBus>, agent_id: String) -> Self {
+        Self { bus, agent_id }
+    }
+
+    fn resolve_recipient(&self, to: &str) -> Result<Agent, String> {
+        let mut conn = self.bus.db_pool().get().map_err(|e| format!("DB error: {}", e))?;
+        use crate::schema::agents::dsl::*;
+        let all: Vec<Agent> = agents.load::<Agent>(&mut conn).map_err(|e| format!("DB error: {}", e))?;
+
+        let to_lower = to.to_lowercase();
+        all.into_iter()
+            .filter(|a| a.id != self.agent_id)
+            .find(|a| a.id == to || a.name.to_lowercase() == to_lower)
+            .ok_or_else(|| format!("Agent '{}' not found. Use list_colleagues to see who is available.", to))
+    }
+}
+
+#[async_trait]
+impl Tool for MessageAgentTool {
+    fn name(&self) -> &str {
+        "message_agent"
+    }
+
+    fn description(&self) -> &str {
+        "Send a message to another agent. 'task_request' asks a colleague to do something and 'question' asks them something; the answer arrives later as a message in this conversation. 'broadcast' informs every agent currently working."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "kind": {
+                    "type": "string",
+                    "enum": ["task_request", "question", "broadcast"]
+                },
+                "to": {
+                    "type": "string",
+                    "description": "Recipient agent name (not needed for broadcast)"
+                },
+                "content": {
+                    "type": "string",
+                    "description": "The task, question or announcement"
+                }
+            },
+            "required": ["kind", "content"]
+        })
+    }
+
+    fn lock_scope(&self, _args: &Value) -> LockScope {
+        LockScope::None
+    }
+
+    async fn execute(&self, args: Value, _ctx: &ToolContext) -> Result<Value, String> {
+        let kind = args["kind"].as_str().ok_or("Missing 'kind' field")?;
+        let content = args["content"].as_str().ok_or("Missing 'content' field")?.to_string();
+
+        let (to, payload) = match kind {
+            "broadcast" => (None, BusPayload::Broadcast { text: content }),
+            "task_request" | "question" => {
+                let to = args["to"].as_str().ok_or("Missing 'to' field")?;
+                let recipient = self.resolve_recipient(to)?;
+                let payload = if kind == "question" {
+                    BusPayload::Question { question: content }
+                } else {
+                    BusPayload::TaskRequest { task: content }
+                };
+                (Some(recipient), payload)
+            }
+            other => return Err(format!("Unknown message kind '{}'", other)),
+        };
+
+        let message = BusMessage::new(&self.agent_id, to.as_ref().map(|a| a.id.as_str()), payload);
+        let message_id = message.id.clone();
+        let delivery = self.bus.send(message);
+
+        Ok(json!({
+            "status": "sent",
+            "message_id": message_id,
+            "delivery": delivery,
+            "to": to.map(|a| a.name).unwrap_or_else(|| "all running agents".to_string())
+        }))
+    }
+}
@@ -1,20 +1,50 @@
 use crate::models::SandboxConfig;
-use crate::permissions::{PermissionRequest, PermissionType};
+use crate::permissions::{PermissionPayload, PermissionRequest, PermissionType};
+use crate::secrets::AgentSecrets;
 use crate::skills::docker::DockerSandbox;
+use crate::skills::{MicroVmConfig, MicroVmSandbox, Sandbox};
 use crate::tools::{Tool, ToolContext};
 use async_trait::async_trait;
 use serde_json::{json, Value};
+use std::sync::Arc;
 use tokio::process::Command;
 
 
+/// Commands get this long unless the agent's tool config says otherwise
+const DEFAULT_TIMEOUT_SECONDS: u64 = 300;
+
+/// Checking the command line can't stop a running command from reading or
+/// writing outside the workspace (`cat ../x`, scripts, symlinks); only a
+/// sandbox that mounts nothing else can
+const WORKSPACE_NEEDS_SANDBOX: &str =
+    "Workspace scope runs commands in a sandbox only: enable Docker or use the 'sandbox' or 'microvm' execution mode";
+
 pub struct BashTool {
     pub workspace_path: std::path::PathBuf,
     pub execution_mode: String,
+    pub timeout_seconds: u64,
+    /// Secrets the agent is granted; a command gets the ones it asks for
+    pub secrets: Arc<AgentSecrets>,
 }
 
 impl BashTool {
     pub fn new(workspace_path: std::path::PathBuf, execution_mode: String) -> Self {
-        Self { workspace_path, execution_mode }
+        Self {
+            workspace_path,
+            execution_mode,
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            secrets: Arc::new(AgentSecrets::default()),
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout_seconds: u64) -> Self {
+        self.timeout_seconds = timeout_seconds;
+        self
+    }
+
+    pub fn with_secrets(mut self, secrets: Arc<AgentSecrets>) -> Self {
+        self.secrets = secrets;
+        self
     }
 }
 
@@ -29,7 +59,7 @@ impl Tool for BashTool {
     }
 
     fn parameters_schema(&self) -> Value {
-        json!({
+        let mut schema = json!({
             "type": "object",
             "properties": {
                 "command": {
@@ -38,7 +68,15 @@ impl Tool for BashTool {
                 }
             },
             "required": ["command"]
-        })
+        });
+        if !self.secrets.is_empty() {
+            schema["properties"]["secrets"] = json!({
+                "type": "array",
+                "items": { "type": "string", "enum": self.secrets.names() },
+                "description": "Secrets the command needs, set as environment variables of the same name (their values are never shown)"
+            });
+        }
+        schema
     }
 
     fn verify_result(&self, result: &Value) -> bool {
@@ -54,20 +92,56 @@ impl Tool for BashTool {
         true
     }
 
+    fn is_read_only(&self, args: &Value) -> bool {
+        args["command"]
+            .as_str()
+            .map(crate::permissions::read_only::is_read_only_command)
+            .unwrap_or(false)
+    }
+
     async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<Value, String> {
         let command = args["command"].as_str().ok_or("Missing command argument")?;
+        let requested: Vec<String> = args["secrets"]
+            .as_array()
+            .map(|names| names.iter().filter_map(|n| n.as_str().map(String::from)).collect())
+            .unwrap_or_default();
+        let env = self.secrets.env_for(&requested)?;
+
+        // In workspace scope the command runs in (and, in Docker or a microVM,
+        // only sees) the agent's workspace
+        let working_dir = if ctx.scope.is_workspace_scope() {
+            if self.execution_mode == "direct" {
+                return Err(WORKSPACE_NEEDS_SANDBOX.to_string());
+            }
+            ctx.scope.validate_command(command)?;
+            ctx.scope.check_path(ctx.scope.workspace_path().ok_or("No workspace path set for workspace scope")?)?
+        } else {
+            self.workspace_path.clone()
+        };
 
         let perm_req = PermissionRequest {
             id: uuid::Uuid::new_v4().to_string(),
             permission_type: PermissionType::ShellExecute,
-            message: format!("Agent wants to run command: {}", command),
+            message: if requested.is_empty() {
+                format!("Agent wants to run command: {}", command)
+            } else {
+                format!("Agent wants to run command: {} (with secrets: {})", command, requested.join(", "))
+            },
             metadata: {
                 let mut map = std::collections::HashMap::new();
                 map.insert("command".to_string(), command.to_string());
+                if !requested.is_empty() {
+                    map.insert("secrets".to_string(), requested.join(","));
+                }
                 map.insert("resource".to_string(), command.to_string());
                 map.insert("session_id".to_string(), ctx.session_id.clone());
+                map.insert("tool".to_string(), self.name().to_string());
                 map
             },
+            payload: Some(PermissionPayload::Command {
+                command: command.to_string(),
+                working_dir: Some(working_dir.display().to_string()),
+            }),
         };
 
         let allowed = ctx
@@ -79,6 +153,25 @@ impl Tool for BashTool {
             return Err("Permission denied by user".to_string());
         }
 
+        // Strict isolation: always run inside a microVM, never fall back
+        if self.execution_mode == "microvm" {
+            let vm = MicroVmSandbox::new(MicroVmConfig::default());
+            let config = SandboxConfig {
+                timeout_seconds: Some(self.timeout_seconds as u32),
+                env: env.clone(),
+                ..Default::default()
+            };
+            let result = Sandbox::execute(&vm, command, &working_dir, None, &config)
+                .await
+                .map_err(|e| format!("Strict isolation is enabled but the microVM sandbox failed: {}", e))?;
+
+            return Ok(json!({
+                "stdout": result.stdout,
+                "stderr": result.stderr,
+                "exit_code": result.exit_code
+            }));
+        }
+
         // Initialize sandbox to check availability
         let mut sandbox = DockerSandbox::new();
         sandbox.init().await;
@@ -96,18 +189,23 @@ impl Tool for BashTool {
              "flexible" => docker_available,
              _ => docker_available, // Default to flexible
         };
+        if !use_docker && ctx.scope.is_workspace_scope() {
+            return Err(WORKSPACE_NEEDS_SANDBOX.to_string());
+        }
 
         if use_docker {
             let config = SandboxConfig {
                 image: Some("debian:stable-slim".to_string()),
                 memory_limit: Some("256m".to_string()),
                 cpu_limit: None,
-                timeout_seconds: Some(300),
+                timeout_seconds: Some(self.timeout_seconds as u32),
                 network_enabled: Some(true), // Allow network for system bash
+                runtime: None,
+                env,
             };
 
             println!("Executing Bash via Docker sandbox...");
-            let result = sandbox.execute(command, &self.workspace_path, None, &config).await?;
+            let result = sandbox.execute(command, &working_dir, None, &config).await?;
             
             Ok(json!({
                 "stdout": result.stdout,
@@ -115,12 +213,16 @@ impl Tool for BashTool {
                 "exit_code": result.exit_code
             }))
         } else {
-            let output = Command::new("bash")
+            let mut local = Command::new("bash");
+            local
                 .arg("-c")
                 .arg(command)
-                .current_dir(&self.workspace_path)
-                .output()
+                .current_dir(&working_dir)
+                .envs(env)
+                .kill_on_drop(true);
+            let output = tokio::time::timeout(std::time::Duration::from_secs(self.timeout_seconds), local.output())
                 .await
+                .map_err(|_| format!("Command timed out after {} seconds", self.timeout_seconds))?
                 .map_err(|e| e.to_string())?;
 
             let stdout = String::from_utf8_lossy(&output.stdout).to_string();
@@ -156,4 +258,54 @@ mod tests {
         let tool: Box<dyn Tool> = Box::new(BashTool::new(std::path::PathBuf::from("."), "flexible".to_string()));
         assert!(tool.needs_summarization(&json!({}), &json!({})));
     }
+
+    #[tokio::test]
+    async fn test_workspace_scope_confines_commands() {
+        use crate::permissions::{AgentPolicy, PermissionManager, ScopeEnforcer};
+
+        let workspace = tempfile::tempdir().unwrap();
+        let elsewhere = tempfile::tempdir().unwrap();
+        let tool = BashTool::new(elsewhere.path().to_path_buf(), "direct".to_string());
+        let ctx = ToolContext {
+            permissions: std::sync::Arc::new(PermissionManager::new().with_policy(AgentPolicy::parse("default: allow").unwrap())),
+            observer: None,
+            session_id: "test".to_string(),
+            scope: ScopeEnforcer::workspace(workspace.path().to_path_buf()),
+            job_id: None,
+        };
+
+        assert!(tool.execute(json!({"command": "cd / && ls"}), &ctx).await.is_err());
+        let escape = tool.execute(json!({"command": "cat ../outside"}), &ctx).await;
+        assert_eq!(escape.unwrap_err(), WORKSPACE_NEEDS_SANDBOX);
+        // Unconfined commands are refused outright, not only the suspicious ones
+        assert!(tool.execute(json!({"command": "pwd"}), &ctx).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_secrets_injected_on_request() {
+        use crate::permissions::{AgentPolicy, PermissionManager};
+
+        let secrets = AgentSecrets::from_values([("API_TOKEN".to_string(), "tok-123456".to_string())]);
+        let tool = BashTool::new(std::env::temp_dir(), "direct".to_string()).with_secrets(Arc::new(secrets));
+        let ctx = ToolContext {
+            permissions: Arc::new(PermissionManager::new().with_policy(AgentPolicy::parse("default: allow").unwrap())),
+            observer: None,
+            session_id: "test".to_string(),
+            scope: Default::default(),
+            job_id: None,
+        };
+        assert!(tool.parameters_schema()["properties"]["secrets"].is_object());
+
+        let without = tool.execute(json!({"command": "echo -n \"$API_TOKEN\""}), &ctx).await.unwrap();
+        assert_eq!(without["stdout"], "");
+        let with = tool
+            .execute(json!({"command": "echo -n \"$API_TOKEN\"", "secrets": ["API_TOKEN"]}), &ctx)
+            .await
+            .unwrap();
+        assert_eq!(with["stdout"], "tok-123456");
+        assert!(tool
+            .execute(json!({"command": "true", "secrets": ["OTHER"]}), &ctx)
+            .await
+            .is_err());
+    }
 }
@@ -0,0 +1,162 @@
+//! Per-agent tool selection, stored as JSON in `agents.agent_tools`
+//!
+//! ```json
+//! {
+//!   "builtin": ["filesystem", "search_files", "bash", "http"],
+//!   "disabled": ["pdf", "github_create_issue"],
+//!   "filesystem": { "root": "/Users/me/reports" },
+//!   "bash": { "timeout_seconds": 60 }
+//! }
+//! ```
+//!
+//! `builtin` picks the built-in tools (the defaults when unset), `disabled`
+//! removes any tool by name, whether built-in, a skill or an MCP tool. MCP
+//! tools come from the servers listed in the agent's `mcp_servers`.
+
+use crate::models::Agent;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Built-in tools every agent gets unless its config says otherwise
+/// (`view_image` only with a vision-capable model)
+pub const DEFAULT_TOOLS: &[&str] = &[
+    "filesystem",
+    "search_files",
+    "bash",
+    "transcribe",
+    "list_colleagues",
+    "send_email",
+    "check_mail",
+    "read_email_thread",
+    "pdf",
+    "view_image",
+    "pages",
+    "todo",
+];
+
+/// Built-in tools an agent only gets when its config lists them
+pub const OPTIONAL_TOOLS: &[&str] = &["http", "generate_image"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct AgentToolsConfig {
+    /// Built-in tools to register; `DEFAULT_TOOLS` when unset
+    #[serde(default)]
+    pub builtin: Option<Vec<String>>,
+    /// Tools never registered for this agent, by name
+    #[serde(default)]
+    pub disabled: Vec<String>,
+    #[serde(default)]
+    pub filesystem: FilesystemToolConfig,
+    #[serde(default)]
+    pub bash: BashToolConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct FilesystemToolConfig {
+    /// Directory the filesystem tool is confined to, instead of the workspace
+    #[serde(default)]
+    pub root: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct BashToolConfig {
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
+}
+
+impl AgentToolsConfig {
+    /// The agent's config; defaults when it has none (or it does not parse)
+    pub fn from_agent(agent: &Agent) -> Self {
+        match agent.agent_tools.as_deref() {
+            Some(json) if !json.trim().is_empty() => serde_json::from_str(json).unwrap_or_else(|e| {
+                log::warn!("Ignoring invalid tool config for agent {}: {}", agent.id, e);
+                Self::default()
+            }),
+            _ => Self::default(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for name in self.builtin.iter().flatten() {
+            if !DEFAULT_TOOLS.contains(&name.as_str()) && !OPTIONAL_TOOLS.contains(&name.as_str()) {
+                return Err(format!("Unknown built-in tool '{}'", name));
+            }
+        }
+        if let Some(root) = &self.filesystem.root {
+            if !Path::new(root).is_dir() {
+                return Err(format!("Filesystem root '{}' is not a directory", root));
+            }
+        }
+        if self.bash.timeout_seconds == Some(0) {
+            return Err("Bash timeout must be at least one second".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether the built-in tool `name` is registered
+    pub fn builtin_enabled(&self, name: &str) -> bool {
+        let selected = match &self.builtin {
+            Some(builtin) => builtin.iter().any(|b| b == name),
+            None => DEFAULT_TOOLS.contains(&name),
+        };
+        selected && self.is_enabled(name)
+    }
+
+    /// Whether a skill or MCP tool called `name` is registered
+    pub fn is_enabled(&self, name: &str) -> bool {
+        !self.disabled.iter().any(|d| d == name)
+    }
+
+    pub fn filesystem_root(&self, workspace_path: &Path) -> PathBuf {
+        self.filesystem
+            .root
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| workspace_path.to_path_buf())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let config = AgentToolsConfig::default();
+        assert!(config.builtin_enabled("bash"));
+        assert!(!config.builtin_enabled("http"));
+        assert!(config.is_enabled("pdf"));
+        assert_eq!(config.filesystem_root(Path::new("/work")), PathBuf::from("/work"));
+    }
+
+    #[test]
+    fn test_selection() {
+        let config: AgentToolsConfig = serde_json::from_str(
+            r#"{"builtin": ["filesystem", "http", "bash"], "disabled": ["bash", "pdf"], "bash": {"timeout_seconds": 30}}"#,
+        )
+        .unwrap();
+        assert!(config.builtin_enabled("http"));
+        assert!(!config.builtin_enabled("search_files"));
+        assert!(!config.builtin_enabled("bash"));
+        assert!(!config.is_enabled("pdf"));
+        assert_eq!(config.bash.timeout_seconds, Some(30));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate() {
+        let unknown = AgentToolsConfig {
+            builtin: Some(vec!["teleport".to_string()]),
+            ..Default::default()
+        };
+        assert!(unknown.validate().is_err());
+
+        let mut bad_root = AgentToolsConfig::default();
+        bad_root.filesystem.root = Some("/definitely/not/here".to_string());
+        assert!(bad_root.validate().is_err());
+
+        let mut zero = AgentToolsConfig::default();
+        zero.bash.timeout_seconds = Some(0);
+        assert!(zero.validate().is_err());
+    }
+}
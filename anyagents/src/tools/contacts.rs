@@ -38,14 +38,19 @@ impl Tool for ListColleaguesTool {
         })
     }
 
-    async fn execute(&self, _args: Value, _ctx: &ToolContext) -> Result<Value, String> {
-        let mut conn = self.db_pool.get().map_err(|e| format!("DB error: {}", e))?;
+    fn is_read_only(&self, _args: &Value) -> bool {
+        true
+    }
 
-        use crate::schema::agents::dsl::*;
-        let all_agents: Vec<Agent> = agents
-            .filter(status.eq("active"))
-            .load::<Agent>(&mut conn)
-            .map_err(|e| format!("DB error: {}", e))?;
+    async fn execute(&self, _args: Value, _ctx: &ToolContext) -> Result<Value, String> {
+        let all_agents: Vec<Agent> = crate::database::blocking(&self.db_pool, |conn| {
+            use crate::schema::agents::dsl::*;
+            agents
+                .filter(status.eq("active"))
+                .load::<Agent>(conn)
+                .map_err(|e| format!("DB error: {}", e))
+        })
+        .await?;
 
         let mut colleagues = Vec::new();
 
@@ -1,10 +1,16 @@
 use super::{Tool, ToolContext};
 use crate::database::DbPool;
+use crate::email::{attachments, recipients};
 use crate::events::AgentObserver;
+use crate::locks::LockScope;
 use crate::models::{Agent, NewMailMessage, NewMailThread};
+use crate::permissions::{PermissionManager, PermissionPayload, PermissionRequest, PermissionType};
 use async_trait::async_trait;
 use diesel::prelude::*;
 use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 /// No-op observer for background agent tasks (no UI to emit to)
 pub struct NoOpObserver;
@@ -20,6 +26,8 @@ pub struct SendEmailTool {
     agent_id: String,
     agent_name: String,
     colleagues_description: String,
+    /// Where `attachments` paths are resolved; without it nothing can be attached
+    workspace_path: Option<PathBuf>,
 }
 
 impl SendEmailTool {
@@ -45,8 +53,14 @@ impl SendEmailTool {
             agent_id,
             agent_name,
             colleagues_description,
+            workspace_path: None,
         }
     }
+
+    pub fn with_workspace(mut self, workspace_path: PathBuf) -> Self {
+        self.workspace_path = Some(workspace_path);
+        self
+    }
 }
 
 #[async_trait]
@@ -56,7 +70,7 @@ impl Tool for SendEmailTool {
     }
 
     fn description(&self) -> &str {
-        "Send an INTERNAL email to a colleague or to the user's mailbox. This is an internal messaging system - you do NOT need external email addresses. Just use the person's name (e.g., 'Jordan', 'Jordan the PM', or 'user'). Use list_colleagues tool first if you need to see who's available."
+        "Send an email to colleagues, groups of colleagues or the user's mailbox. For colleagues you do NOT need email addresses. Just use the person's name (e.g., 'Jordan', 'Jordan the PM', or 'user'), and cc or bcc others as needed. Use list_colleagues tool first if you need to see who's available. If you have a connected email account you can also write to a real email address."
     }
 
     fn parameters_schema(&self) -> Value {
@@ -65,7 +79,15 @@ impl Tool for SendEmailTool {
             "properties": {
                 "to": {
                     "type": "string",
-                    "description": format!("Recipient's NAME (not email address). Just use their name like 'Jordan' or 'Alex'. Use 'user' for the workspace owner. Available colleagues: {}", self.colleagues_description)
+                    "description": format!("Recipient's NAME, like 'Jordan' or 'Alex'; separate several with commas. Use 'user' for the workspace owner, a group name like 'engineering-team' for everyone in a group, or an email address for someone outside the workspace. Available colleagues: {}", self.colleagues_description)
+                },
+                "cc": {
+                    "type": "string",
+                    "description": "Optional comma-separated recipients to copy, in the same form as 'to'"
+                },
+                "bcc": {
+                    "type": "string",
+                    "description": "Optional comma-separated recipients to copy without the others seeing"
                 },
                 "subject": {
                     "type": "string",
@@ -74,38 +96,94 @@ impl Tool for SendEmailTool {
                 "body": {
                     "type": "string",
                     "description": "Email body content"
+                },
+                "attachments": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Optional workspace-relative paths of files to attach"
                 }
             },
             "required": ["to", "subject", "body"]
         })
     }
 
-    async fn execute(&self, args: Value, _ctx: &ToolContext) -> Result<Value, String> {
-        let to = args["to"].as_str().ok_or("Missing 'to' field")?;
+    fn lock_scope(&self, _args: &Value) -> LockScope {
+        // Attachments are only read
+        LockScope::None
+    }
+
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<Value, String> {
         let subject = args["subject"].as_str().ok_or("Missing 'subject' field")?;
         let body = args["body"].as_str().ok_or("Missing 'body' field")?;
+        let attachment_paths: Vec<&str> = args["attachments"]
+            .as_array()
+            .map(|paths| paths.iter().filter_map(|p| p.as_str()).collect())
+            .unwrap_or_default();
+
+        // Resolve attachments up front so a bad path doesn't leave a half-sent mail
+        let attachment_files = if attachment_paths.is_empty() {
+            vec![]
+        } else {
+            let workspace = self
+                .workspace_path
+                .as_ref()
+                .ok_or("Attachments are not available without a workspace")?;
+            if attachment_paths.len() > attachments::MAX_ATTACHMENTS_PER_MESSAGE {
+                return Err(format!(
+                    "At most {} attachments can be sent at once",
+                    attachments::MAX_ATTACHMENTS_PER_MESSAGE
+                ));
+            }
+            attachment_paths
+                .iter()
+                .map(|p| attachments::workspace_file(workspace, p))
+                .collect::<Result<Vec<_>, _>>()?
+        };
 
         let now = chrono::Utc::now().naive_utc();
 
-        // Determine recipient
-        let (recipient_type, recipient_agent_id, _recipient_name) = if to.to_lowercase() == "user" {
-            ("user".to_string(), None, "User".to_string())
-        } else {
-            // Look up agent by name (case-insensitive partial match)
-            let mut conn = self.db_pool.get().map_err(|e| format!("DB error: {}", e))?;
-            use crate::schema::agents::dsl::*;
-            let all: Vec<Agent> = agents.load::<Agent>(&mut conn).map_err(|e| format!("DB error: {}", e))?;
-
-            let target = all.iter().find(|a| {
-                a.name.to_lowercase().contains(&to.to_lowercase())
-                    || to.to_lowercase().contains(&a.name.to_lowercase())
-            });
+        let to = address_list(&args["to"]);
+        let cc = address_list(&args["cc"]);
+        let bcc = address_list(&args["bcc"]);
+        if to.is_empty() {
+            return Err("Missing 'to' field".to_string());
+        }
+        let recipients = recipients::resolve(&self.db_pool, &to, &cc, &bcc, Some(&self.agent_id))
+            .map_err(|e| format!("{}. Available: {}", e, self.colleagues_description))?;
 
-            match target {
-                Some(agent) => ("agent".to_string(), Some(agent.id.clone()), agent.name.clone()),
-                None => return Err(format!("Recipient '{}' not found. Available: {}", to, self.colleagues_description)),
+        let external: Vec<&str> = recipients
+            .iter()
+            .filter_map(|r| r.external_address.as_deref())
+            .collect();
+        if !external.is_empty() {
+            if crate::email::account_for_agent(&self.db_pool, &self.agent_id)?.is_none() {
+                return Err("You have no connected email account, so you can only write to colleagues and the user".to_string());
             }
-        };
+            let addresses = external.join(", ");
+            let perm_req = PermissionRequest {
+                id: uuid::Uuid::new_v4().to_string(),
+                permission_type: PermissionType::Network,
+                message: format!("Agent wants to email {}: {}", addresses, subject),
+                metadata: {
+                    let mut map = HashMap::new();
+                    map.insert("operation".to_string(), "send_email".to_string());
+                    map.insert("to".to_string(), addresses.clone());
+                    map.insert("resource".to_string(), addresses.clone());
+                    map.insert("session_id".to_string(), ctx.session_id.clone());
+                    map.insert("tool".to_string(), self.name().to_string());
+                    map
+                },
+                payload: Some(PermissionPayload::email(external.iter().map(|a| a.to_string()).collect(), subject, body)),
+            };
+            if !ctx
+                .permissions
+                .request_permission(ctx.observer.as_ref(), perm_req)
+                .await?
+            {
+                return Err("Permission denied".to_string());
+            }
+        }
+        let (recipient_type, recipient_agent_id, external_address) = recipients::primary_columns(&recipients)?;
 
         // Create mail thread
         let thread_id = uuid::Uuid::new_v4().to_string();
@@ -133,55 +211,184 @@ impl Tool for SendEmailTool {
                 thread_id: thread_id.clone(),
                 sender_type: "agent".to_string(),
                 sender_agent_id: Some(self.agent_id.clone()),
-                recipient_type: recipient_type.clone(),
-                recipient_agent_id: recipient_agent_id.clone(),
+                recipient_type,
+                recipient_agent_id,
                 content: body.to_string(),
                 created_at: now,
+                external_address,
+                external_message_id: None,
             };
 
             diesel::insert_into(crate::schema::mail_messages::table)
                 .values(&new_message)
                 .execute(&mut conn)
                 .map_err(|e| format!("Failed to create mail message: {}", e))?;
+            recipients::record(&mut conn, &message_id, &recipients)?;
         }
 
-        // If recipient is another agent, spawn background processing
-        if recipient_type == "agent" {
-            if let Some(ref target_agent_id) = recipient_agent_id {
-                let db_pool = self.db_pool.clone();
-                let target_id = target_agent_id.clone();
-                let sender_name = self.agent_name.clone();
-                let sender_id = self.agent_id.clone();
-                let subject_clone = subject.to_string();
-                let body_clone = body.to_string();
-                let thread_id_clone = thread_id.clone();
-
-                tokio::spawn(async move {
-                    if let Err(e) = process_email_in_background(
-                        db_pool,
-                        target_id,
-                        sender_name,
-                        sender_id,
-                        subject_clone,
-                        body_clone,
-                        thread_id_clone,
-                    ).await {
-                        log::error!("Background email processing failed: {}", e);
-                    }
-                });
-            }
+        let root = attachments::storage_dir();
+        for file in &attachment_files {
+            attachments::attach_file(&self.db_pool, &root, &message_id, file)?;
+        }
+
+        // Every agent on the message reads and answers it in the background
+        let context = MailAgentContext {
+            permissions: ctx.permissions.clone(),
+            observer: ctx.observer.clone().unwrap_or_else(|| Arc::new(NoOpObserver)),
+        };
+        for target_id in recipients.iter().filter_map(|r| r.agent_id.clone()) {
+            let db_pool = self.db_pool.clone();
+            let sender_name = self.agent_name.clone();
+            let sender_id = self.agent_id.clone();
+            let subject_clone = subject.to_string();
+            let body_clone = body.to_string();
+            let thread_id_clone = thread_id.clone();
+            let context = context.clone();
+
+            tokio::spawn(async move {
+                if let Err(e) = process_email_in_background(
+                    db_pool,
+                    target_id,
+                    sender_name,
+                    sender_id,
+                    subject_clone,
+                    body_clone,
+                    thread_id_clone,
+                    context,
+                ).await {
+                    log::error!("Background email processing failed: {}", e);
+                }
+            });
+        }
+
+        let to_label = to.join(", ");
+        if !external.is_empty() {
+            let delivered = crate::email::deliver(&self.db_pool, &message_id).await?;
+            return Ok(if delivered {
+                json!({
+                    "status": "sent",
+                    "thread_id": thread_id,
+                    "recipients": recipients.len(),
+                    "attachments": attachment_files.len(),
+                    "message": format!("Email sent to {}", to_label)
+                })
+            } else {
+                json!({
+                    "status": "draft",
+                    "thread_id": thread_id,
+                    "message": format!("Outbound delivery is off for your account; the email to {} was saved as a draft for the user to review", external.join(", "))
+                })
+            });
         }
 
         Ok(json!({
             "status": "sent",
             "thread_id": thread_id,
-            "message": format!("Email sent to {}", to)
+            "recipients": recipients.len(),
+            "attachments": attachment_files.len(),
+            "message": format!("Email sent to {}", to_label)
         }))
     }
 }
 
-/// Process an email in the background using a direct LLM call (no tools/agent loop).
-/// This prevents the agent from using tools or giving meta-responses.
+/// How an agent answers mail, set by `mail_replies` in its execution settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MailReplyMode {
+    /// A single model call that only writes the reply (the default)
+    Direct,
+    /// A full agent run with tools, whose final answer is the reply
+    Tools,
+}
+
+impl MailReplyMode {
+    pub fn for_agent(agent: &Agent) -> Self {
+        let tools = agent
+            .execution_settings
+            .as_ref()
+            .and_then(|s| serde_json::from_str::<Value>(s).ok())
+            .and_then(|json| json.get("mail_replies").and_then(|m| m.as_str()).map(|m| m == "tools"))
+            .unwrap_or(false);
+        if tools {
+            MailReplyMode::Tools
+        } else {
+            MailReplyMode::Direct
+        }
+    }
+}
+
+/// What a tool-enabled mail reply needs to run the agent: permission prompts
+/// and events go through these like in any other headless run
+#[derive(Clone)]
+pub struct MailAgentContext {
+    pub permissions: Arc<PermissionManager>,
+    pub observer: Arc<dyn AgentObserver>,
+}
+
+/// Work on an email with the agent's tools in a session of its own. Returns
+/// the final answer (the reply, possibly with `[attach: ...]` lines) and the
+/// session id, so the run can be inspected afterwards.
+pub async fn run_mail_session(
+    db_pool: &DbPool,
+    agent: &Agent,
+    subject: &str,
+    request: &str,
+    context: &MailAgentContext,
+) -> Result<(String, String), String> {
+    let now = chrono::Utc::now().naive_utc();
+    let session = crate::models::NewSession {
+        id: uuid::Uuid::new_v4().to_string(),
+        agent_id: agent.id.clone(),
+        title: Some(format!("Mail: {}", subject)),
+        created_at: now,
+        updated_at: now,
+        archived: 0,
+        pinned: 0,
+    };
+    {
+        let mut conn = db_pool.get().map_err(|e| format!("DB error: {}", e))?;
+        diesel::insert_into(crate::schema::sessions::table)
+            .values(&session)
+            .execute(&mut conn)
+            .map_err(|e| format!("Failed to create session: {}", e))?;
+    }
+    let _ = context.observer.emit("sessions_updated", Value::Null);
+
+    let prompt = format!(
+        "{request}\n\nCarry out what this email asks, using your tools where needed. Your final answer is sent back as the email reply, so write it as one: report what you actually did and found, and never describe work you did not do. {}",
+        attachments::ATTACH_INSTRUCTIONS
+    );
+    crate::agents::save_message(db_pool, "user", &prompt, &session.id, None);
+
+    let mut worker = crate::agents::AgentLoop::new(agent, db_pool.clone()).await;
+    worker.session_id = session.id.clone();
+    // The final answer is the reply; new mail from inside the run could loop
+    worker.tools.unregister("send_email");
+    worker
+        .run(
+            prompt,
+            context.observer.clone(),
+            uuid::Uuid::new_v4().to_string(),
+            Arc::new(dashmap::DashMap::new()),
+            context.permissions.clone(),
+            db_pool.clone(),
+        )
+        .await;
+
+    let answer = worker
+        .history
+        .iter()
+        .rev()
+        .find(|m| matches!(m, rig::completion::Message::Assistant { .. }))
+        .map(crate::agents::optimizations::get_message_content)
+        .filter(|a| !a.trim().is_empty())
+        .ok_or_else(|| format!("{} did not produce an answer (session {})", agent.name, session.id))?;
+    Ok((answer, session.id))
+}
+
+/// Process an email in the background. By default this is a direct LLM call
+/// (no tools/agent loop), which keeps the agent from using tools or giving
+/// meta-responses; agents set to `MailReplyMode::Tools` do the work instead.
+#[allow(clippy::too_many_arguments)]
 pub async fn process_email_in_background(
     db_pool: DbPool,
     target_agent_id: String,
@@ -190,6 +397,7 @@ pub async fn process_email_in_background(
     subject: String,
     body: String,
     thread_id: String,
+    context: MailAgentContext,
 ) -> Result<(), String> {
     use crate::schema::agents::dsl::*;
 
@@ -202,58 +410,155 @@ pub async fn process_email_in_background(
             .map_err(|e| format!("Agent not found: {}", e))?
     };
 
-    // 2. Build system prompt incorporating the agent's personality
-    // IMPORTANT: Instruct the agent to write ONLY the reply content, no tool usage
-    let preamble = format!(
-        "{}\n\n---\n\nYou are composing an email reply. Write ONLY the reply content — no subject line, no meta-commentary, no tool usage, no JSON. Write naturally as yourself, addressing the request directly as if writing an email.",
-        agent_db.system_prompt.as_deref().unwrap_or("You are a helpful assistant.")
-    );
-
-    // 3. Direct LLM call — no tools, no agent loop
-    let client = crate::llm::LlmClient::new(&agent_db.ai_provider, &agent_db.ai_model)
-        .with_preamble(&preamble);
-
-    let prompt = format!(
-        "You received an email from {sender_name}.\n\nSubject: {subject}\n\n{body}\n\nWrite your reply:",
+    let attached = thread_attachments(&db_pool, &thread_id)?;
+    let request = format!(
+        "You received an email from {sender_name}.\n\nSubject: {subject}\n\n{body}\n\n{attached}",
     );
 
-    let reply = client.prompt(&prompt).await.map_err(|e| format!("LLM error: {}", e))?;
-
-    // Clean up the reply - remove any tool calls or meta-commentary
-    let clean_reply = clean_email_reply(&reply);
+    let mut mail_session = None;
+    let clean_reply = if MailReplyMode::for_agent(&agent_db) == MailReplyMode::Tools {
+        let (answer, session_id) = run_mail_session(&db_pool, &agent_db, &subject, &request, &context).await?;
+        log::info!("Mail in thread {} handled in session {}", thread_id, session_id);
+        mail_session = Some(session_id);
+        answer
+    } else {
+        // 2. Build system prompt incorporating the agent's personality
+        // IMPORTANT: Instruct the agent to write ONLY the reply content, no tool usage
+        let preamble = mail_reply_preamble(agent_db.system_prompt.as_deref());
+
+        // 3. Direct LLM call — no tools, no agent loop
+        let client = crate::llm::LlmClient::new(&agent_db.ai_provider, &agent_db.ai_model)
+            .with_preamble(&preamble)
+            .with_params(agent_db.generation_params().for_provider(&agent_db.ai_provider));
+        let reply = client
+            .prompt(&format!("{request}Write your reply:"))
+            .await
+            .map_err(|e| format!("LLM error: {}", e))?;
+
+        // Clean up the reply - remove any tool calls or meta-commentary
+        clean_email_reply(&reply)
+    };
+    let (clean_reply, attach_paths) = attachments::extract_attach_markers(&clean_reply);
 
     // 4. Save reply as a mail_message in the thread
     if !clean_reply.trim().is_empty() {
         let now = chrono::Utc::now().naive_utc();
         let mut conn = db_pool.get().map_err(|e| format!("DB error: {}", e))?;
 
+        // Reply to all, so everyone on the original message sees the answer
+        let reply_to = recipients::background_reply_recipients(
+            &db_pool,
+            &thread_id,
+            &target_agent_id,
+            recipients::Recipient::agent(recipients::TO, &sender_agent_id),
+        )?;
+        let (recipient_type, recipient_agent_id, external_address) = recipients::primary_columns(&reply_to)?;
+
+        let reply_id = uuid::Uuid::new_v4().to_string();
         let new_reply = NewMailMessage {
-            id: uuid::Uuid::new_v4().to_string(),
+            id: reply_id.clone(),
             thread_id: thread_id.clone(),
             sender_type: "agent".to_string(),
             sender_agent_id: Some(target_agent_id.clone()),
-            recipient_type: "agent".to_string(),
-            recipient_agent_id: Some(sender_agent_id),
+            recipient_type,
+            recipient_agent_id,
             content: clean_reply.trim().to_string(),
             created_at: now,
+            external_address,
+            external_message_id: None,
         };
 
         diesel::insert_into(crate::schema::mail_messages::table)
             .values(&new_reply)
             .execute(&mut conn)
             .map_err(|e| format!("Failed to save reply: {}", e))?;
+        recipients::record(&mut conn, &reply_id, &reply_to)?;
+
+        let workspace = attachments::agent_workspace(&agent_db);
+        attachments::attach_workspace_files(&db_pool, &attachments::storage_dir(), &reply_id, &workspace, &attach_paths);
+
+        // Files the run produced go along too, unless the reply attached them already
+        if let Some(session_id) = &mail_session {
+            let attached: Vec<PathBuf> = attach_paths.iter().map(|p| workspace.join(p)).collect();
+            for artifact in crate::models::artifact::get_session_artifacts(&db_pool, session_id, None)? {
+                let path = PathBuf::from(&artifact.path);
+                if attached.contains(&path) {
+                    continue;
+                }
+                if let Err(e) = attachments::attach_file(&db_pool, &attachments::storage_dir(), &reply_id, &path) {
+                    log::warn!("Could not attach artifact '{}' to mail {}: {}", artifact.path, reply_id, e);
+                }
+            }
+        }
 
         // Update thread timestamp
         diesel::update(crate::schema::mail_threads::table.filter(crate::schema::mail_threads::id.eq(&thread_id)))
             .set(crate::schema::mail_threads::updated_at.eq(now))
             .execute(&mut conn)
             .map_err(|e| format!("Failed to update thread: {}", e))?;
+
+        if reply_to.iter().any(|r| r.recipient_type == "external") {
+            drop(conn);
+            if !crate::email::deliver(&db_pool, &reply_id).await? {
+                log::info!("Email reply in thread {} kept as a draft", thread_id);
+            }
+        }
     }
 
     log::info!("Background email processing completed for thread {}", thread_id);
     Ok(())
 }
 
+/// System prompt of a direct (tool-less) mail reply in the agent's voice
+pub fn mail_reply_preamble(system_prompt: Option<&str>) -> String {
+    crate::prompts::PromptBuilder::new()
+        .persona(system_prompt, "You are a helpful assistant.")
+        .instructions(format!(
+            "You are composing an email reply. Write ONLY the reply content — no subject line, no meta-commentary, no tool usage, no JSON. Write naturally as yourself, addressing the request directly as if writing an email. {}",
+            attachments::ATTACH_INSTRUCTIONS
+        ))
+        .build()
+}
+
+/// Addresses from a comma-separated string or an array of strings
+fn address_list(value: &Value) -> Vec<String> {
+    let items: Vec<&str> = match value {
+        Value::String(s) => s.split(',').collect(),
+        Value::Array(items) => items.iter().filter_map(|v| v.as_str()).collect(),
+        _ => vec![],
+    };
+    items
+        .into_iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Everything attached so far in `thread_id`, described for a reply prompt
+pub fn thread_attachments(db_pool: &DbPool, thread_id: &str) -> Result<String, String> {
+    let message_ids: Vec<String> = {
+        use crate::schema::mail_messages;
+        let mut conn = db_pool.get().map_err(|e| format!("DB error: {}", e))?;
+        mail_messages::table
+            .filter(mail_messages::thread_id.eq(thread_id))
+            .order(mail_messages::created_at.asc())
+            .select(mail_messages::id)
+            .load(&mut conn)
+            .map_err(|e| format!("DB error: {}", e))?
+    };
+    let by_message = attachments::for_messages(db_pool, &message_ids)?;
+    let all: Vec<_> = message_ids
+        .iter()
+        .filter_map(|id| by_message.get(id))
+        .flatten()
+        .cloned()
+        .collect();
+    if all.is_empty() {
+        return Ok(String::new());
+    }
+    Ok(format!("Attached files:\n{}\n", attachments::describe_for_prompt(&all)))
+}
+
 /// Clean email reply by removing any tool calls or JSON that might have been generated
 fn clean_email_reply(reply: &str) -> String {
     let mut cleaned = reply.trim().to_string();
@@ -282,3 +587,59 @@ fn clean_email_reply(reply: &str) -> String {
 
     cleaned
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+    use crate::models::NewAgent;
+
+    fn insert_agent(pool: &DbPool, execution_settings: Option<String>) -> Agent {
+        use crate::schema::agents;
+
+        let agent = NewAgent {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: "Mailer".to_string(),
+            description: None,
+            status: "active".to_string(),
+            personality: None,
+            tone: None,
+            expertise: None,
+            ai_provider: "openai".to_string(),
+            ai_model: "gpt-4o".to_string(),
+            ai_temperature: 0.7,
+            ai_config: "{}".to_string(),
+            system_prompt: None,
+            permissions: None,
+            working_directories: None,
+            skills: None,
+            mcp_servers: None,
+            messaging_connections: None,
+            knowledge_bases: None,
+            api_keys: None,
+            created_at: chrono::Utc::now().timestamp(),
+            updated_at: chrono::Utc::now().timestamp(),
+            platform_configs: None,
+            execution_settings,
+            scope_type: None,
+            workspace_path: None,
+            avatar: None,
+            agent_tools: None,
+        };
+        let mut conn = pool.get().unwrap();
+        diesel::insert_into(agents::table).values(&agent).execute(&mut conn).unwrap();
+        agents::table.find(&agent.id).first(&mut conn).unwrap()
+    }
+
+    #[test]
+    fn test_mail_reply_mode() {
+        let pool = create_test_pool();
+        assert_eq!(MailReplyMode::for_agent(&insert_agent(&pool, None)), MailReplyMode::Direct);
+
+        let tools = json!({ "mode": "autonomous", "mail_replies": "tools" }).to_string();
+        assert_eq!(MailReplyMode::for_agent(&insert_agent(&pool, Some(tools))), MailReplyMode::Tools);
+
+        let other = json!({ "mail_replies": "direct" }).to_string();
+        assert_eq!(MailReplyMode::for_agent(&insert_agent(&pool, Some(other))), MailReplyMode::Direct);
+    }
+}
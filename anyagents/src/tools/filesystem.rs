@@ -1,10 +1,13 @@
 use super::{Tool, ToolContext};
-use crate::permissions::{PermissionRequest, PermissionType};
+use crate::locks::LockScope;
+use crate::permissions::scope::resolve_within;
+use crate::permissions::{PermissionPayload, PermissionRequest, PermissionType};
 use async_trait::async_trait;
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 
 pub struct FilesystemTool {
@@ -66,11 +69,30 @@ impl Tool for FilesystemTool {
         false
     }
 
+    fn is_read_only(&self, args: &Value) -> bool {
+        matches!(args["operation"].as_str(), Some("read_file") | Some("list_dir"))
+    }
+
+    fn lock_scope(&self, args: &Value) -> LockScope {
+        // Edits are to one path, so other jobs can keep working on other files
+        let Some(path) = args["path"].as_str() else {
+            return LockScope::Workspace;
+        };
+        let relative = Path::new(path).strip_prefix(&self.workspace_path).unwrap_or(Path::new(path));
+        LockScope::File(relative.to_string_lossy().to_string())
+    }
+
+    fn cache_ttl(&self) -> Option<Duration> {
+        Some(Duration::from_secs(300))
+    }
+
     async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<Value, String> {
         let op = args["operation"].as_str().ok_or("Missing operation")?;
         let path_str = args["path"].as_str().ok_or("Missing path")?;
 
-        // Security check moved to validate_args
+        // Resolve symlinks so a link inside the root can't lead out of it
+        let target_path = resolve_within(&self.workspace_path, Path::new(path_str))?;
+        ctx.scope.check_path(&target_path)?;
 
         // Permission check
         let (permission_type, msg_verb) = match op {
@@ -89,8 +111,15 @@ impl Tool for FilesystemTool {
                 map.insert("path".to_string(), path_str.to_string());
                 map.insert("resource".to_string(), path_str.to_string());
                 map.insert("session_id".to_string(), ctx.session_id.clone());
+                map.insert("tool".to_string(), self.name().to_string());
                 map
             },
+            payload: Some(match op {
+                "read_file" | "list_dir" => PermissionPayload::FileRead { path: path_str.to_string() },
+                "write_file" => PermissionPayload::file_write(&self.workspace_path, path_str, args["content"].as_str().unwrap_or("")),
+                "delete_file" => PermissionPayload::FileDelete { path: path_str.to_string() },
+                _ => PermissionPayload::CreateDir { path: path_str.to_string() },
+            }),
         };
 
         if !ctx
@@ -101,9 +130,6 @@ impl Tool for FilesystemTool {
             return Err("Permission denied".to_string());
         }
 
-        let root = self.workspace_path.clone();
-        let target_path = root.join(path_str);
-
         match op {
             "read_file" => {
                 let content = fs::read_to_string(target_path).map_err(|e| e.to_string())?;
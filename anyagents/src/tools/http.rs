@@ -0,0 +1,205 @@
+use crate::locks::LockScope;
+use crate::permissions::{PermissionPayload, PermissionRequest, PermissionType};
+use crate::tools::{RetryPolicy, Tool, ToolContext};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// Response bodies longer than this are cut before they reach the model
+const MAX_BODY_CHARS: usize = 100_000;
+
+/// Covers the permission prompt as well as the request itself
+const EXECUTION_TIMEOUT: Duration = Duration::from_secs(120);
+
+pub struct HttpTool {
+    client: reqwest::Client,
+}
+
+impl HttpTool {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(60))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl Default for HttpTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn method(args: &Value) -> String {
+    args["method"].as_str().unwrap_or("GET").to_uppercase()
+}
+
+#[async_trait]
+impl Tool for HttpTool {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    fn description(&self) -> &str {
+        "Make an HTTP request and return the status code and response body."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "method": {
+                    "type": "string",
+                    "enum": ["GET", "POST", "PUT", "PATCH", "DELETE", "HEAD"],
+                    "description": "HTTP method (default GET)"
+                },
+                "url": {
+                    "type": "string",
+                    "description": "http:// or https:// URL"
+                },
+                "headers": {
+                    "type": "object",
+                    "description": "Request headers",
+                    "additionalProperties": { "type": "string" }
+                },
+                "body": {
+                    "description": "Request body; objects and arrays are sent as JSON"
+                }
+            },
+            "required": ["url"]
+        })
+    }
+
+    async fn validate_args(&self, args: &Value) -> Result<(), String> {
+        let url = args["url"].as_str().ok_or("Missing url argument")?;
+        let parsed = reqwest::Url::parse(url).map_err(|e| format!("Invalid URL '{}': {}", url, e))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(format!("Unsupported URL scheme '{}'", parsed.scheme()));
+        }
+        reqwest::Method::from_bytes(method(args).as_bytes()).map_err(|_| format!("Invalid method '{}'", method(args)))?;
+        Ok(())
+    }
+
+    fn verify_result(&self, result: &Value) -> bool {
+        result["status"].as_u64().map(|s| s < 400).unwrap_or(false)
+    }
+
+    fn needs_summarization(&self, _args: &Value, _result: &Value) -> bool {
+        true
+    }
+
+    fn is_read_only(&self, args: &Value) -> bool {
+        matches!(method(args).as_str(), "GET" | "HEAD")
+    }
+
+    fn lock_scope(&self, _args: &Value) -> LockScope {
+        // Talks to the network, not the workspace
+        LockScope::None
+    }
+
+    fn execution_timeout(&self) -> Option<Duration> {
+        Some(EXECUTION_TIMEOUT)
+    }
+
+    /// Only requests without side effects are sent again
+    fn retry_policy(&self, args: &Value) -> RetryPolicy {
+        if self.is_read_only(args) {
+            RetryPolicy::with_backoff(3, Duration::from_secs(2))
+        } else {
+            RetryPolicy::none()
+        }
+    }
+
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<Value, String> {
+        self.validate_args(&args).await?;
+        let url = args["url"].as_str().unwrap_or_default();
+        let method = method(&args);
+
+        let perm_req = PermissionRequest {
+            id: uuid::Uuid::new_v4().to_string(),
+            permission_type: PermissionType::Network,
+            message: format!("Agent wants to send {} {}", method, url),
+            metadata: {
+                let mut map = std::collections::HashMap::new();
+                map.insert("method".to_string(), method.clone());
+                map.insert("url".to_string(), url.to_string());
+                map.insert("resource".to_string(), url.to_string());
+                map.insert("session_id".to_string(), ctx.session_id.clone());
+                map.insert("tool".to_string(), self.name().to_string());
+                map
+            },
+            payload: Some(PermissionPayload::Http { method: method.clone(), url: url.to_string() }),
+        };
+        let allowed = ctx
+            .permissions
+            .request_permission(ctx.observer.as_ref(), perm_req)
+            .await?;
+        if !allowed {
+            return Err("Permission denied by user".to_string());
+        }
+
+        let mut request = self
+            .client
+            .request(reqwest::Method::from_bytes(method.as_bytes()).map_err(|e| e.to_string())?, url);
+        if let Some(headers) = args["headers"].as_object() {
+            for (name, value) in headers {
+                request = request.header(name.as_str(), value.as_str().unwrap_or_default());
+            }
+        }
+        request = match &args["body"] {
+            Value::Null => request,
+            Value::String(text) => request.body(text.clone()),
+            other => request.json(other),
+        };
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Request to {} failed: {}", url, e))?;
+        let status = response.status().as_u16();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read response from {}: {}", url, e))?;
+
+        let json_body = serde_json::from_str::<Value>(&text).unwrap_or(Value::Null);
+        let body = if text.chars().count() > MAX_BODY_CHARS {
+            format!("{}\n... (truncated)", text.chars().take(MAX_BODY_CHARS).collect::<String>())
+        } else {
+            text
+        };
+
+        Ok(json!({
+            "status": status,
+            "body": body,
+            "json": json_body
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_validate_args() {
+        let tool = HttpTool::new();
+        assert!(tool.validate_args(&json!({"url": "https://example.com/api"})).await.is_ok());
+        assert!(tool.validate_args(&json!({"url": "file:///etc/passwd"})).await.is_err());
+        assert!(tool.validate_args(&json!({"url": "not a url"})).await.is_err());
+        assert!(tool.validate_args(&json!({})).await.is_err());
+    }
+
+    #[test]
+    fn test_read_only_and_verify() {
+        let tool = HttpTool::new();
+        assert!(tool.is_read_only(&json!({"url": "https://x"})));
+        assert!(!tool.is_read_only(&json!({"url": "https://x", "method": "post"})));
+        assert!(tool.verify_result(&json!({"status": 200})));
+        assert!(!tool.verify_result(&json!({"status": 404})));
+        assert_eq!(tool.retry_policy(&json!({"url": "https://x"})).max_attempts, 3);
+        assert_eq!(tool.retry_policy(&json!({"url": "https://x", "method": "POST"})), RetryPolicy::none());
+    }
+}
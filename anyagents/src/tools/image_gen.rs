@@ -0,0 +1,513 @@
+//! `generate_image`: text-to-image through OpenAI Images, Gemini Imagen or a
+//! local Stable Diffusion webui (AUTOMATIC1111 API)
+//!
+//! Images are written into the workspace (`attachments/` unless the call names
+//! a directory) and announced with an `image_generated` event carrying a
+//! preview, so the UI can show them before the agent replies.
+
+use crate::database::DbPool;
+use crate::events::AgentEvent;
+use crate::models::ArtifactOutput;
+use crate::permissions::{PermissionPayload, PermissionRequest, PermissionType};
+use crate::tools::{Tool, ToolContext};
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub const DEFAULT_OUTPUT_DIR: &str = "attachments";
+const DEFAULT_SD_WEBUI_URL: &str = "http://127.0.0.1:7860";
+const MAX_IMAGES: u64 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageProvider {
+    OpenAi,
+    Gemini,
+    StableDiffusion,
+}
+
+impl ImageProvider {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "openai" => Some(Self::OpenAi),
+            "gemini" => Some(Self::Gemini),
+            "stable_diffusion" | "sd" | "local" => Some(Self::StableDiffusion),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::OpenAi => "openai",
+            Self::Gemini => "gemini",
+            Self::StableDiffusion => "stable_diffusion",
+        }
+    }
+
+    fn default_model(&self) -> &'static str {
+        match self {
+            Self::OpenAi => "gpt-image-1",
+            Self::Gemini => "imagen-3.0-generate-002",
+            Self::StableDiffusion => "",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageRequest {
+    pub prompt: String,
+    pub negative_prompt: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub count: u32,
+    pub model: Option<String>,
+}
+
+/// Raw image returned by a provider
+pub struct ImageOutput {
+    pub bytes: Vec<u8>,
+    pub media_type: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct GeneratedImage {
+    /// Relative to the workspace
+    pub path: String,
+    pub media_type: String,
+    /// Base64 of the image, for the UI preview
+    pub data: String,
+}
+
+/// Parse "1024x768" into (width, height)
+pub fn parse_size(size: &str) -> Result<(u32, u32), String> {
+    let (w, h) = size
+        .to_lowercase()
+        .split_once('x')
+        .and_then(|(w, h)| Some((w.trim().parse::<u32>().ok()?, h.trim().parse::<u32>().ok()?)))
+        .ok_or_else(|| format!("Invalid size '{}', expected WIDTHxHEIGHT", size))?;
+    if !(64..=4096).contains(&w) || !(64..=4096).contains(&h) {
+        return Err(format!("Size '{}' is out of range (64-4096 per side)", size));
+    }
+    Ok((w, h))
+}
+
+/// Imagen takes an aspect ratio instead of a size; pick the closest one
+pub fn aspect_ratio(width: u32, height: u32) -> &'static str {
+    const RATIOS: &[(&str, f64)] = &[
+        ("1:1", 1.0),
+        ("3:4", 0.75),
+        ("4:3", 4.0 / 3.0),
+        ("9:16", 9.0 / 16.0),
+        ("16:9", 16.0 / 9.0),
+    ];
+    let target = width as f64 / height as f64;
+    RATIOS
+        .iter()
+        .min_by(|a, b| (a.1 - target).abs().total_cmp(&(b.1 - target).abs()))
+        .map(|r| r.0)
+        .unwrap_or("1:1")
+}
+
+fn extension(media_type: &str) -> &'static str {
+    match media_type {
+        "image/jpeg" => "jpg",
+        "image/webp" => "webp",
+        _ => "png",
+    }
+}
+
+/// File stem from the first words of the prompt
+fn file_stem(prompt: &str) -> String {
+    let words: Vec<String> = prompt
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .take(6)
+        .map(str::to_lowercase)
+        .collect();
+    if words.is_empty() {
+        "image".to_string()
+    } else {
+        words.join("-")
+    }
+}
+
+fn decode(data: &str) -> Result<Vec<u8>, String> {
+    STANDARD
+        .decode(data)
+        .map_err(|e| format!("Provider returned invalid image data: {}", e))
+}
+
+pub struct GenerateImageTool {
+    workspace_path: PathBuf,
+    db_pool: DbPool,
+    default_provider: ImageProvider,
+    client: reqwest::Client,
+}
+
+impl GenerateImageTool {
+    /// `agent_provider` picks the default backend when the call does not name one
+    pub fn new(workspace_path: PathBuf, db_pool: DbPool, agent_provider: &str) -> Self {
+        Self {
+            workspace_path,
+            db_pool,
+            default_provider: match ImageProvider::parse(agent_provider) {
+                Some(ImageProvider::Gemini) => ImageProvider::Gemini,
+                _ => ImageProvider::OpenAi,
+            },
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(180))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Settings first, then the environment
+    fn setting(&self, key: &str) -> Option<String> {
+        crate::models::settings::get_setting(&self.db_pool, key)
+            .or_else(|| std::env::var(key).ok())
+            .filter(|v| !v.trim().is_empty())
+    }
+
+    fn endpoint(&self, provider: ImageProvider, model: &str) -> String {
+        match provider {
+            ImageProvider::OpenAi => "https://api.openai.com/v1/images/generations".to_string(),
+            ImageProvider::Gemini => format!(
+                "https://generativelanguage.googleapis.com/v1beta/models/{}:predict",
+                model
+            ),
+            ImageProvider::StableDiffusion => format!(
+                "{}/sdapi/v1/txt2img",
+                self.setting("SD_WEBUI_URL")
+                    .unwrap_or_else(|| DEFAULT_SD_WEBUI_URL.to_string())
+                    .trim_end_matches('/')
+            ),
+        }
+    }
+
+    async fn generate(
+        &self,
+        provider: ImageProvider,
+        req: &ImageRequest,
+    ) -> Result<Vec<ImageOutput>, String> {
+        let model = req.model.as_deref().unwrap_or(provider.default_model());
+        let url = self.endpoint(provider, model);
+        let request = match provider {
+            ImageProvider::OpenAi => {
+                let key = self.setting("OPENAI_API_KEY").ok_or("OPENAI_API_KEY not set (env or settings)")?;
+                self.client.post(&url).bearer_auth(key).json(&json!({
+                    "model": model,
+                    "prompt": req.prompt,
+                    "n": req.count,
+                    "size": format!("{}x{}", req.width, req.height),
+                }))
+            }
+            ImageProvider::Gemini => {
+                let key = self.setting("GEMINI_API_KEY").ok_or("GEMINI_API_KEY not set (env or settings)")?;
+                let mut parameters = json!({
+                    "sampleCount": req.count,
+                    "aspectRatio": aspect_ratio(req.width, req.height),
+                });
+                if let Some(negative) = &req.negative_prompt {
+                    parameters["negativePrompt"] = json!(negative);
+                }
+                self.client.post(&url).header("x-goog-api-key", key).json(&json!({
+                    "instances": [{ "prompt": req.prompt }],
+                    "parameters": parameters,
+                }))
+            }
+            ImageProvider::StableDiffusion => self.client.post(&url).json(&json!({
+                "prompt": req.prompt,
+                "negative_prompt": req.negative_prompt.clone().unwrap_or_default(),
+                "width": req.width,
+                "height": req.height,
+                "batch_size": req.count,
+                "steps": 30,
+            })),
+        };
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Image request to {} failed: {}", provider.name(), e))?;
+        let status = response.status();
+        let body: Value = response.json().await.map_err(|e| e.to_string())?;
+        if !status.is_success() {
+            let message = body["error"]["message"]
+                .as_str()
+                .or_else(|| body["error"].as_str())
+                .or_else(|| body["detail"].as_str())
+                .unwrap_or("unknown error");
+            return Err(format!("{} image generation failed ({}): {}", provider.name(), status, message));
+        }
+
+        let mut outputs = Vec::new();
+        match provider {
+            ImageProvider::OpenAi => {
+                for item in body["data"].as_array().into_iter().flatten() {
+                    let bytes = if let Some(b64) = item["b64_json"].as_str() {
+                        decode(b64)?
+                    } else if let Some(link) = item["url"].as_str() {
+                        self.client
+                            .get(link)
+                            .send()
+                            .await
+                            .and_then(|r| r.error_for_status())
+                            .map_err(|e| format!("Failed to download image: {}", e))?
+                            .bytes()
+                            .await
+                            .map_err(|e| e.to_string())?
+                            .to_vec()
+                    } else {
+                        continue;
+                    };
+                    outputs.push(ImageOutput { bytes, media_type: "image/png".to_string() });
+                }
+            }
+            ImageProvider::Gemini => {
+                for item in body["predictions"].as_array().into_iter().flatten() {
+                    if let Some(b64) = item["bytesBase64Encoded"].as_str() {
+                        outputs.push(ImageOutput {
+                            bytes: decode(b64)?,
+                            media_type: item["mimeType"].as_str().unwrap_or("image/png").to_string(),
+                        });
+                    }
+                }
+            }
+            ImageProvider::StableDiffusion => {
+                for b64 in body["images"].as_array().into_iter().flatten().filter_map(Value::as_str) {
+                    outputs.push(ImageOutput { bytes: decode(b64)?, media_type: "image/png".to_string() });
+                }
+            }
+        }
+        if outputs.is_empty() {
+            return Err(format!("{} returned no images (the prompt may have been filtered)", provider.name()));
+        }
+        Ok(outputs)
+    }
+
+    /// Write `outputs` under `dir` with names that do not clash with existing files
+    fn save(&self, dir: &str, prompt: &str, outputs: Vec<ImageOutput>) -> Result<Vec<GeneratedImage>, String> {
+        let target_dir = self.workspace_path.join(dir);
+        std::fs::create_dir_all(&target_dir).map_err(|e| e.to_string())?;
+        let stem = file_stem(prompt);
+        let mut saved = Vec::new();
+        let mut n = 1;
+        for output in outputs {
+            let ext = extension(&output.media_type);
+            let name = loop {
+                let candidate = if n == 1 { format!("{}.{}", stem, ext) } else { format!("{}-{}.{}", stem, n, ext) };
+                n += 1;
+                if !target_dir.join(&candidate).exists() {
+                    break candidate;
+                }
+            };
+            std::fs::write(target_dir.join(&name), &output.bytes).map_err(|e| e.to_string())?;
+            saved.push(GeneratedImage {
+                path: format!("{}/{}", dir.trim_end_matches('/'), name),
+                media_type: output.media_type,
+                data: STANDARD.encode(&output.bytes),
+            });
+        }
+        Ok(saved)
+    }
+}
+
+#[async_trait]
+impl Tool for GenerateImageTool {
+    fn name(&self) -> &str {
+        "generate_image"
+    }
+
+    fn description(&self) -> &str {
+        "Generate images from a text prompt and save them into the workspace. Providers: openai, gemini \
+         (Imagen) or stable_diffusion (local webui). Returns the saved file paths."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "prompt": {
+                    "type": "string",
+                    "description": "Description of the image"
+                },
+                "negative_prompt": {
+                    "type": "string",
+                    "description": "What to avoid (gemini and stable_diffusion only)"
+                },
+                "provider": {
+                    "type": "string",
+                    "enum": ["openai", "gemini", "stable_diffusion"],
+                    "description": "Image backend (default: the agent's provider, or openai)"
+                },
+                "model": {
+                    "type": "string",
+                    "description": "Provider model, e.g. gpt-image-1 or imagen-3.0-generate-002"
+                },
+                "size": {
+                    "type": "string",
+                    "description": "WIDTHxHEIGHT (default 1024x1024)"
+                },
+                "count": {
+                    "type": "integer",
+                    "description": "Number of images, 1-4 (default 1)"
+                },
+                "output_dir": {
+                    "type": "string",
+                    "description": "Relative directory to save into (default: attachments)"
+                }
+            },
+            "required": ["prompt"]
+        })
+    }
+
+    async fn validate_args(&self, args: &Value) -> Result<(), String> {
+        let prompt = args["prompt"].as_str().ok_or("Missing prompt")?;
+        if prompt.trim().is_empty() {
+            return Err("Prompt is empty".to_string());
+        }
+        if let Some(provider) = args["provider"].as_str() {
+            ImageProvider::parse(provider).ok_or_else(|| format!("Unknown image provider '{}'", provider))?;
+        }
+        if let Some(size) = args["size"].as_str() {
+            parse_size(size)?;
+        }
+        if let Some(count) = args.get("count").filter(|v| !v.is_null()) {
+            match count.as_u64() {
+                Some(n) if (1..=MAX_IMAGES).contains(&n) => {}
+                _ => return Err(format!("count must be between 1 and {}", MAX_IMAGES)),
+            }
+        }
+        if let Some(dir) = args["output_dir"].as_str() {
+            if dir.contains("..") || dir.starts_with('/') {
+                return Err("Access denied: Paths must be relative and cannot contain '..'".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    fn execution_timeout(&self) -> Option<Duration> {
+        // Covers the permission prompt and a slow local backend
+        Some(Duration::from_secs(300))
+    }
+
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<Value, String> {
+        let provider = args["provider"]
+            .as_str()
+            .and_then(ImageProvider::parse)
+            .unwrap_or(self.default_provider);
+        let (width, height) = parse_size(args["size"].as_str().unwrap_or("1024x1024"))?;
+        let req = ImageRequest {
+            prompt: args["prompt"].as_str().ok_or("Missing prompt")?.to_string(),
+            negative_prompt: args["negative_prompt"].as_str().map(str::to_string),
+            width,
+            height,
+            count: args["count"].as_u64().unwrap_or(1).clamp(1, MAX_IMAGES) as u32,
+            model: args["model"].as_str().map(str::to_string),
+        };
+        let output_dir = args["output_dir"].as_str().unwrap_or(DEFAULT_OUTPUT_DIR);
+
+        let url = self.endpoint(provider, req.model.as_deref().unwrap_or(provider.default_model()));
+        let perm_req = PermissionRequest {
+            id: uuid::Uuid::new_v4().to_string(),
+            permission_type: PermissionType::Network,
+            message: format!(
+                "Agent wants to generate {} image(s) with {}: \"{}\"",
+                req.count,
+                provider.name(),
+                req.prompt
+            ),
+            metadata: {
+                let mut map = std::collections::HashMap::new();
+                map.insert("provider".to_string(), provider.name().to_string());
+                map.insert("url".to_string(), url.clone());
+                map.insert("resource".to_string(), url.clone());
+                map.insert("session_id".to_string(), ctx.session_id.clone());
+                map.insert("tool".to_string(), self.name().to_string());
+                map
+            },
+            payload: Some(PermissionPayload::Http { method: "POST".to_string(), url }),
+        };
+        if !ctx
+            .permissions
+            .request_permission(ctx.observer.as_ref(), perm_req)
+            .await?
+        {
+            return Err("Permission denied by user".to_string());
+        }
+
+        let outputs = self.generate(provider, &req).await?;
+        let images = self.save(output_dir, &req.prompt, outputs)?;
+
+        if let Some(observer) = &ctx.observer {
+            let _ = observer.emit(
+                &format!("session:{}", ctx.session_id),
+                serde_json::to_value(AgentEvent::ImageGenerated {
+                    prompt: req.prompt.clone(),
+                    provider: provider.name().to_string(),
+                    images: images.clone(),
+                })
+                .unwrap_or(Value::Null),
+            );
+        }
+
+        let mut result = json!({
+            "provider": provider.name(),
+            "paths": images.iter().map(|i| i.path.clone()).collect::<Vec<_>>(),
+        });
+        let outputs = images
+            .iter()
+            .map(|image| ArtifactOutput {
+                preview: Some(req.prompt.clone()),
+                ..ArtifactOutput::new("image", self.workspace_path.join(&image.path).to_string_lossy())
+            })
+            .collect();
+        crate::models::artifact::attach(&mut result, outputs);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+
+    #[test]
+    fn test_size_and_aspect_ratio() {
+        assert_eq!(parse_size("1024x768").unwrap(), (1024, 768));
+        assert!(parse_size("huge").is_err());
+        assert!(parse_size("10x10").is_err());
+        assert_eq!(aspect_ratio(1024, 1024), "1:1");
+        assert_eq!(aspect_ratio(1920, 1080), "16:9");
+        assert_eq!(aspect_ratio(768, 1024), "3:4");
+    }
+
+    #[tokio::test]
+    async fn test_validate_args() {
+        let tool = GenerateImageTool::new(PathBuf::from("/tmp"), create_test_pool(), "anthropic");
+        assert_eq!(tool.default_provider, ImageProvider::OpenAi);
+        assert!(tool.validate_args(&json!({"prompt": "a red fox"})).await.is_ok());
+        assert!(tool.validate_args(&json!({"prompt": " "})).await.is_err());
+        assert!(tool.validate_args(&json!({"prompt": "x", "provider": "midjourney"})).await.is_err());
+        assert!(tool.validate_args(&json!({"prompt": "x", "count": 9})).await.is_err());
+        assert!(tool.validate_args(&json!({"prompt": "x", "output_dir": "../out"})).await.is_err());
+    }
+
+    #[test]
+    fn test_save_avoids_clashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = GenerateImageTool::new(dir.path().to_path_buf(), create_test_pool(), "gemini");
+        let output = || ImageOutput { bytes: vec![1, 2, 3], media_type: "image/png".to_string() };
+
+        let first = tool.save("attachments", "A red fox, at dawn!", vec![output()]).unwrap();
+        let second = tool.save("attachments", "A red fox, at dawn!", vec![output(), output()]).unwrap();
+        assert_eq!(first[0].path, "attachments/a-red-fox-at-dawn.png");
+        assert_eq!(second[0].path, "attachments/a-red-fox-at-dawn-2.png");
+        assert_eq!(second[1].path, "attachments/a-red-fox-at-dawn-3.png");
+        assert!(dir.path().join("attachments/a-red-fox-at-dawn-3.png").exists());
+    }
+}
@@ -1,9 +1,13 @@
 use super::{Tool, ToolContext};
 use crate::database::DbPool;
+use crate::email::{attachments, recipients, search};
 use crate::models::{Agent, MailMessage, MailThread};
+use crate::permissions::{PermissionRequest, PermissionType};
 use async_trait::async_trait;
 use diesel::prelude::*;
 use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
 
 /// Tool to read and search mail messages
 pub struct MailReaderTool {
@@ -52,7 +56,7 @@ impl Tool for MailReaderTool {
                 },
                 "search": {
                     "type": "string",
-                    "description": "Optional: Search term to filter emails by subject or content"
+                    "description": "Optional: Words to search for in subjects, message text and email addresses"
                 },
                 "from": {
                     "type": "string",
@@ -68,10 +72,12 @@ impl Tool for MailReaderTool {
         })
     }
 
-    async fn execute(&self, args: Value, _ctx: &ToolContext) -> Result<Value, String> {
-        use crate::schema::{mail_messages, mail_threads};
+    fn is_read_only(&self, _args: &Value) -> bool {
+        true
+    }
 
-        let mut conn = self.db_pool.get().map_err(|e| format!("DB error: {}", e))?;
+    async fn execute(&self, args: Value, _ctx: &ToolContext) -> Result<Value, String> {
+        use crate::schema::{agents, mail_messages};
 
         let folder = args.get("folder")
             .and_then(|f| f.as_str())
@@ -79,7 +85,8 @@ impl Tool for MailReaderTool {
 
         let limit = args.get("limit")
             .and_then(|l| l.as_i64())
-            .unwrap_or(10) as i64;
+            .unwrap_or(10)
+            .clamp(1, 50);
 
         let search_term = args.get("search").and_then(|s| s.as_str());
         let from_filter = args.get("from").and_then(|f| f.as_str());
@@ -87,86 +94,48 @@ impl Tool for MailReaderTool {
             .and_then(|u| u.as_bool())
             .unwrap_or(false);
 
-        // Get all threads first
-        let mut threads_query = mail_threads::table.into_boxed();
-
-        if unread_only {
-            threads_query = threads_query.filter(mail_threads::is_read.eq(0));
-        }
-
-        if let Some(search) = search_term {
-            threads_query = threads_query.filter(
-                mail_threads::subject.like(format!("%{}%", search))
-            );
-        }
-
-        threads_query = threads_query
-            .order(mail_threads::updated_at.desc())
-            .limit(limit);
-
-        let threads: Vec<MailThread> = threads_query
-            .load(&mut conn)
-            .map_err(|e| format!("DB error: {}", e))?;
-
-        // Get all agents for name resolution
-        use crate::schema::agents;
-        let all_agents: Vec<Agent> = agents::table
-            .load(&mut conn)
-            .map_err(|e| format!("DB error: {}", e))?;
+        let threads = search::search_threads(
+            &self.db_pool,
+            &search::MailSearch {
+                query: search_term.map(String::from),
+                folder: Some(folder.to_string()),
+                account_id: Some(self.agent_id.clone()),
+                sender: from_filter.map(String::from),
+                unread_only,
+                limit: Some(limit),
+                ..Default::default()
+            },
+        )?
+        .threads;
+
+        let thread_ids: Vec<String> = threads.iter().map(|t| t.id.clone()).collect();
+        let (all_agents, thread_messages) = crate::database::blocking(&self.db_pool, move |conn| {
+            // Get all agents for name resolution
+            let all_agents: Vec<Agent> = agents::table
+                .load(conn)
+                .map_err(|e| format!("DB error: {}", e))?;
+            // Get messages for each thread
+            let thread_messages = thread_ids
+                .iter()
+                .map(|thread_id| {
+                    mail_messages::table
+                        .filter(mail_messages::thread_id.eq(thread_id))
+                        .order(mail_messages::created_at.asc())
+                        .load::<MailMessage>(conn)
+                        .map_err(|e| format!("DB error: {}", e))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            Ok((all_agents, thread_messages))
+        })
+        .await?;
 
         let mut results = Vec::new();
 
-        for thread in threads {
-            // Get messages for this thread
-            let messages: Vec<MailMessage> = mail_messages::table
-                .filter(mail_messages::thread_id.eq(&thread.id))
-                .order(mail_messages::created_at.asc())
-                .load(&mut conn)
-                .map_err(|e| format!("DB error: {}", e))?;
-
+        for (thread, messages) in threads.into_iter().zip(thread_messages) {
             if messages.is_empty() {
                 continue;
             }
 
-            // Filter by folder
-            let matches_folder = match folder {
-                "inbox" => {
-                    messages.iter().any(|m| {
-                        m.recipient_agent_id.as_ref() == Some(&self.agent_id)
-                    })
-                }
-                "sent" => {
-                    messages.iter().any(|m| {
-                        m.sender_agent_id.as_ref() == Some(&self.agent_id)
-                    })
-                }
-                "all" => true,
-                _ => true,
-            };
-
-            if !matches_folder {
-                continue;
-            }
-
-            // Filter by sender if specified
-            if let Some(from_name) = from_filter {
-                let has_matching_sender = messages.iter().any(|m| {
-                    if let Some(ref sender_id) = m.sender_agent_id {
-                        all_agents.iter().any(|a| {
-                            &a.id == sender_id &&
-                            a.name.to_lowercase().contains(&from_name.to_lowercase())
-                        })
-                    } else {
-                        m.sender_type == "user" &&
-                        "user".to_lowercase().contains(&from_name.to_lowercase())
-                    }
-                });
-
-                if !has_matching_sender {
-                    continue;
-                }
-            }
-
             // Get last message for preview
             let last_msg = messages.last().unwrap();
             let sender_name = if last_msg.sender_type == "user" {
@@ -177,7 +146,7 @@ impl Tool for MailReaderTool {
                     .map(|a| a.name.clone())
                     .unwrap_or_else(|| "Unknown".to_string())
             } else {
-                "Unknown".to_string()
+                last_msg.external_address.clone().unwrap_or_else(|| "Unknown".to_string())
             };
 
             let preview = last_msg.content
@@ -216,11 +185,21 @@ impl Tool for MailReaderTool {
 /// Tool to read a specific email thread
 pub struct ReadEmailThreadTool {
     db_pool: DbPool,
+    /// Where `save_attachments` copies files to
+    workspace_path: Option<PathBuf>,
 }
 
 impl ReadEmailThreadTool {
     pub fn new(db_pool: DbPool) -> Self {
-        Self { db_pool }
+        Self {
+            db_pool,
+            workspace_path: None,
+        }
+    }
+
+    pub fn with_workspace(mut self, workspace_path: PathBuf) -> Self {
+        self.workspace_path = Some(workspace_path);
+        self
     }
 }
 
@@ -231,7 +210,7 @@ impl Tool for ReadEmailThreadTool {
     }
 
     fn description(&self) -> &str {
-        "Read all messages in a specific email thread. Use this after check_mail to see the full conversation."
+        "Read all messages in a specific email thread, including the names of attached files. Use this after check_mail to see the full conversation. Set save_attachments to copy the thread's attachments into your workspace."
     }
 
     fn parameters_schema(&self) -> Value {
@@ -241,39 +220,105 @@ impl Tool for ReadEmailThreadTool {
                 "thread_id": {
                     "type": "string",
                     "description": "The thread_id from check_mail results"
+                },
+                "save_attachments": {
+                    "type": "boolean",
+                    "description": "Copy the thread's attachments into mail_attachments/<thread_id>/ in your workspace"
                 }
             },
             "required": ["thread_id"]
         })
     }
 
-    async fn execute(&self, args: Value, _ctx: &ToolContext) -> Result<Value, String> {
-        use crate::schema::{mail_messages, mail_threads};
+    fn is_read_only(&self, args: &Value) -> bool {
+        !args["save_attachments"].as_bool().unwrap_or(false)
+    }
+
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<Value, String> {
+        use crate::schema::{agents, mail_messages, mail_threads};
 
         let thread_id = args.get("thread_id")
             .and_then(|t| t.as_str())
             .ok_or("Missing thread_id")?;
 
-        let mut conn = self.db_pool.get().map_err(|e| format!("DB error: {}", e))?;
+        let id = thread_id.to_string();
+        let (thread, messages, all_agents) = crate::database::blocking(&self.db_pool, move |conn| {
+            // Get thread
+            let thread: MailThread = mail_threads::table
+                .filter(mail_threads::id.eq(&id))
+                .first(conn)
+                .map_err(|_| "Thread not found")?;
 
-        // Get thread
-        let thread: MailThread = mail_threads::table
-            .filter(mail_threads::id.eq(thread_id))
-            .first(&mut conn)
-            .map_err(|_| "Thread not found")?;
+            // Get messages
+            let messages: Vec<MailMessage> = mail_messages::table
+                .filter(mail_messages::thread_id.eq(&id))
+                .order(mail_messages::created_at.asc())
+                .load(conn)
+                .map_err(|e| format!("DB error: {}", e))?;
 
-        // Get messages
-        let messages: Vec<MailMessage> = mail_messages::table
-            .filter(mail_messages::thread_id.eq(thread_id))
-            .order(mail_messages::created_at.asc())
-            .load(&mut conn)
-            .map_err(|e| format!("DB error: {}", e))?;
+            // Get all agents for name resolution
+            let all_agents: Vec<Agent> = agents::table
+                .load(conn)
+                .map_err(|e| format!("DB error: {}", e))?;
+            Ok((thread, messages, all_agents))
+        })
+        .await?;
+
+        let message_ids: Vec<String> = messages.iter().map(|m| m.id.clone()).collect();
+        let mut attached = attachments::for_messages(&self.db_pool, &message_ids)?;
+        let mut recipient_rows = recipients::for_messages(&self.db_pool, &message_ids)?;
+        let name_of = |recipient_type: &str, agent_id: Option<&String>, address: Option<&String>| -> String {
+            match (recipient_type, agent_id, address) {
+                ("user", _, _) => "User".to_string(),
+                (_, Some(agent_id), _) => all_agents
+                    .iter()
+                    .find(|a| &a.id == agent_id)
+                    .map(|a| a.name.clone())
+                    .unwrap_or_else(|| "Unknown".to_string()),
+                (_, None, Some(address)) => address.clone(),
+                _ => "Unknown".to_string(),
+            }
+        };
+
+        let mut saved = Vec::new();
+        if args["save_attachments"].as_bool().unwrap_or(false) && !attached.is_empty() {
+            let workspace = self
+                .workspace_path
+                .as_ref()
+                .ok_or("Attachments cannot be saved without a workspace")?;
+            let target = workspace.join("mail_attachments").join(thread_id);
+
+            let perm_req = PermissionRequest {
+                id: uuid::Uuid::new_v4().to_string(),
+                permission_type: PermissionType::FilesystemWrite,
+                message: format!("Agent wants to save mail attachments to {}", target.display()),
+                metadata: {
+                    let mut map = HashMap::new();
+                    map.insert("operation".to_string(), "save_attachments".to_string());
+                    map.insert("path".to_string(), target.to_string_lossy().to_string());
+                    map.insert("resource".to_string(), target.to_string_lossy().to_string());
+                    map.insert("session_id".to_string(), ctx.session_id.clone());
+                    map.insert("tool".to_string(), self.name().to_string());
+                    map
+                },
+                payload: None,
+            };
+            if !ctx
+                .permissions
+                .request_permission(ctx.observer.as_ref(), perm_req)
+                .await?
+            {
+                return Err("Permission denied".to_string());
+            }
 
-        // Get all agents for name resolution
-        use crate::schema::agents;
-        let all_agents: Vec<Agent> = agents::table
-            .load(&mut conn)
-            .map_err(|e| format!("DB error: {}", e))?;
+            std::fs::create_dir_all(&target).map_err(|e| format!("Failed to create {}: {}", target.display(), e))?;
+            for attachment in attached.values().flatten() {
+                let dest = target.join(&attachment.file_name);
+                std::fs::copy(&attachment.storage_path, &dest)
+                    .map_err(|e| format!("Failed to save {}: {}", attachment.file_name, e))?;
+                saved.push(format!("mail_attachments/{}/{}", thread_id, attachment.file_name));
+            }
+        }
 
         let mut formatted_messages = Vec::new();
 
@@ -286,20 +331,55 @@ impl Tool for ReadEmailThreadTool {
                     .map(|a| a.name.clone())
                     .unwrap_or_else(|| "Unknown".to_string())
             } else {
-                "Unknown".to_string()
+                msg.external_address.clone().unwrap_or_else(|| "Unknown".to_string())
+            };
+
+            let files: Vec<Value> = attached
+                .remove(&msg.id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|a| {
+                    json!({
+                        "id": a.id,
+                        "file_name": a.file_name,
+                        "mime_type": a.mime_type,
+                        "file_size": a.file_size,
+                    })
+                })
+                .collect();
+
+            // Bcc stays hidden, as it would for any reader of the mail
+            let rows = recipient_rows.remove(&msg.id).unwrap_or_default();
+            let names = |kind: &str| -> Vec<String> {
+                rows.iter()
+                    .filter(|r| r.kind == kind)
+                    .map(|r| name_of(&r.recipient_type, r.recipient_agent_id.as_ref(), r.external_address.as_ref()))
+                    .collect()
+            };
+            let to = if rows.is_empty() {
+                vec![name_of(&msg.recipient_type, msg.recipient_agent_id.as_ref(), msg.external_address.as_ref().filter(|_| msg.sender_type != "external"))]
+            } else {
+                names(recipients::TO)
             };
 
             formatted_messages.push(json!({
                 "from": sender_name,
+                "to": to,
+                "cc": names(recipients::CC),
                 "content": msg.content,
                 "timestamp": msg.created_at.to_string(),
+                "attachments": files,
             }));
         }
 
-        Ok(json!({
+        let mut result = json!({
             "subject": thread.subject,
             "messages": formatted_messages,
             "message_count": formatted_messages.len(),
-        }))
+        });
+        if !saved.is_empty() {
+            result["saved_attachments"] = json!(saved);
+        }
+        Ok(result)
     }
 }
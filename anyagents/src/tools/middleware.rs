@@ -0,0 +1,386 @@
+//! Middleware around tool execution
+//!
+//! Every tool call of the agent loop goes through the agent's
+//! `ToolMiddlewareChain` on its way to `execute_with_policy`. Each
+//! `ToolMiddleware` gets the call and the rest of the chain (`Next`): it can
+//! rewrite the arguments, refuse the call, or change what comes back. Built
+//! in, in chain order:
+//!
+//! - `LoggingMiddleware`: logs each call, its outcome and how long it took
+//! - `QuotaMiddleware`: per-job and per-day call limits (`tool_quotas`
+//!   execution setting)
+//! - `PathNormalizer`: tidies path arguments before the tool sees them
+//! - `RedactionMiddleware`: masks the agent's secrets in results and errors
+//!
+//! Embedders add their own with `AgentBuilder::middleware`.
+
+use super::{execute_with_policy, Tool, ToolContext, ToolRunError};
+use crate::database::DbPool;
+use crate::secrets::Redactor;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Component, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Quota key counting calls of every tool
+pub const ANY_TOOL: &str = "*";
+
+#[async_trait]
+pub trait ToolMiddleware: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Handle the call, usually by passing it on with `next.run`
+    async fn handle(
+        &self,
+        tool: &dyn Tool,
+        args: Value,
+        ctx: &ToolContext,
+        next: Next<'_>,
+    ) -> Result<Value, ToolRunError>;
+}
+
+/// The rest of the chain, ending in the tool itself
+pub struct Next<'a> {
+    chain: &'a [Arc<dyn ToolMiddleware>],
+}
+
+impl Next<'_> {
+    pub async fn run(self, tool: &dyn Tool, args: Value, ctx: &ToolContext) -> Result<Value, ToolRunError> {
+        match self.chain.split_first() {
+            Some((middleware, rest)) => middleware.handle(tool, args, ctx, Next { chain: rest }).await,
+            None => execute_with_policy(tool, &args, ctx).await,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ToolMiddlewareChain {
+    middlewares: Vec<Arc<dyn ToolMiddleware>>,
+}
+
+impl ToolMiddlewareChain {
+    /// The built-in chain of an agent
+    pub fn for_agent(
+        db_pool: Option<DbPool>,
+        agent_id: &str,
+        execution_settings: Option<&Value>,
+        redactor: Redactor,
+    ) -> Self {
+        let mut chain = Self::default().with(LoggingMiddleware);
+        match execution_settings.map(ToolQuotas::from_execution_settings) {
+            Some(Ok(quotas)) if !quotas.is_empty() => {
+                chain = chain.with(QuotaMiddleware::new(quotas, db_pool, agent_id));
+            }
+            Some(Err(e)) => log::error!("Ignoring tool quotas of agent {}: {}", agent_id, e),
+            _ => {}
+        }
+        chain = chain.with(PathNormalizer);
+        if !redactor.is_empty() {
+            chain = chain.with(RedactionMiddleware { redactor });
+        }
+        chain
+    }
+
+    /// Append `middleware` (innermost so far, closest to the tool)
+    pub fn with(mut self, middleware: impl ToolMiddleware + 'static) -> Self {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    pub fn push(&mut self, middleware: Arc<dyn ToolMiddleware>) {
+        self.middlewares.push(middleware);
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.middlewares.iter().map(|m| m.name()).collect()
+    }
+
+    pub async fn execute(&self, tool: &dyn Tool, args: Value, ctx: &ToolContext) -> Result<Value, ToolRunError> {
+        Next { chain: &self.middlewares }.run(tool, args, ctx).await
+    }
+}
+
+pub struct LoggingMiddleware;
+
+#[async_trait]
+impl ToolMiddleware for LoggingMiddleware {
+    fn name(&self) -> &str {
+        "logging"
+    }
+
+    async fn handle(
+        &self,
+        tool: &dyn Tool,
+        args: Value,
+        ctx: &ToolContext,
+        next: Next<'_>,
+    ) -> Result<Value, ToolRunError> {
+        let started = Instant::now();
+        log::debug!("Tool '{}' called in session {}", tool.name(), ctx.session_id);
+        let result = next.run(tool, args, ctx).await;
+        match &result {
+            Ok(_) => log::info!("Tool '{}' finished in {:?}", tool.name(), started.elapsed()),
+            Err(e) => log::warn!("Tool '{}' failed after {:?}: {}", tool.name(), started.elapsed(), e),
+        }
+        result
+    }
+}
+
+/// Secret values never reach the model, the UI or the database through a
+/// tool's result
+pub struct RedactionMiddleware {
+    pub redactor: Redactor,
+}
+
+#[async_trait]
+impl ToolMiddleware for RedactionMiddleware {
+    fn name(&self) -> &str {
+        "redaction"
+    }
+
+    async fn handle(
+        &self,
+        tool: &dyn Tool,
+        args: Value,
+        ctx: &ToolContext,
+        next: Next<'_>,
+    ) -> Result<Value, ToolRunError> {
+        match next.run(tool, args, ctx).await {
+            Ok(mut result) => {
+                self.redactor.redact_value(&mut result);
+                Ok(result)
+            }
+            Err(ToolRunError::Failed(e)) => Err(ToolRunError::Failed(self.redactor.redact(&e).into_owned())),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Tidies path arguments (`path`, `output_dir`, `*_path`): surrounding
+/// whitespace and quotes go, `~/` becomes the home directory and `.`
+/// segments go. `..` is kept; resolving it is the scope check's job.
+pub struct PathNormalizer;
+
+impl PathNormalizer {
+    fn is_path_key(key: &str) -> bool {
+        matches!(key, "path" | "output_dir") || key.ends_with("_path")
+    }
+
+    pub fn normalize(&self, path: &str) -> String {
+        let path = path.trim().trim_matches(|c| c == '"' || c == '\'');
+        if path.is_empty() {
+            return String::new();
+        }
+        let expanded = match (path.strip_prefix("~/"), dirs::home_dir()) {
+            (Some(rest), Some(home)) => home.join(rest),
+            _ => PathBuf::from(path),
+        };
+        let normalized: PathBuf = expanded.components().filter(|c| *c != Component::CurDir).collect();
+        match normalized.as_os_str().is_empty() {
+            true => ".".to_string(),
+            false => normalized.to_string_lossy().into_owned(),
+        }
+    }
+
+    fn rewrite(&self, args: &mut Value) {
+        let Some(fields) = args.as_object_mut() else {
+            return;
+        };
+        for (key, value) in fields.iter_mut() {
+            if let (true, Some(path)) = (Self::is_path_key(key), value.as_str()) {
+                *value = Value::String(self.normalize(path));
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ToolMiddleware for PathNormalizer {
+    fn name(&self) -> &str {
+        "paths"
+    }
+
+    async fn handle(
+        &self,
+        tool: &dyn Tool,
+        mut args: Value,
+        ctx: &ToolContext,
+        next: Next<'_>,
+    ) -> Result<Value, ToolRunError> {
+        self.rewrite(&mut args);
+        next.run(tool, args, ctx).await
+    }
+}
+
+/// The `tool_quotas` execution setting: calls allowed, by tool name (or
+/// `"*"` for all tools together)
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ToolQuotas {
+    /// Per job
+    pub per_job: HashMap<String, u32>,
+    /// Per UTC day, counted from the usage records
+    pub per_day: HashMap<String, u32>,
+}
+
+impl ToolQuotas {
+    pub fn from_execution_settings(settings: &Value) -> Result<Self, String> {
+        match settings.get("tool_quotas") {
+            None | Some(Value::Null) => Ok(Self::default()),
+            Some(value) => serde_json::from_value(value.clone()).map_err(|e| format!("Invalid tool quotas: {}", e)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.per_job.is_empty() && self.per_day.is_empty()
+    }
+
+    /// Limits that apply to `tool`: its own, and the one for every tool
+    fn limits<'a>(limits: &'a HashMap<String, u32>, tool: &'a str) -> impl Iterator<Item = (&'a str, u32)> {
+        [tool, ANY_TOOL]
+            .into_iter()
+            .filter_map(move |key| limits.get(key).map(|limit| (key, *limit)))
+    }
+}
+
+pub struct QuotaMiddleware {
+    quotas: ToolQuotas,
+    db_pool: Option<DbPool>,
+    agent_id: String,
+    /// Calls so far, by job and quota key
+    calls: Mutex<HashMap<(String, String), u32>>,
+}
+
+impl QuotaMiddleware {
+    pub fn new(quotas: ToolQuotas, db_pool: Option<DbPool>, agent_id: &str) -> Self {
+        Self { quotas, db_pool, agent_id: agent_id.to_string(), calls: Mutex::default() }
+    }
+
+    /// Count the call against the job's quotas; Err when one is used up
+    fn take_job_quota(&self, job_id: &str, tool: &str) -> Result<(), String> {
+        let mut calls = self.calls.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        for (key, limit) in ToolQuotas::limits(&self.quotas.per_job, tool) {
+            if calls.get(&(job_id.to_string(), key.to_string())).copied().unwrap_or(0) >= limit {
+                return Err(quota_error(key, limit, "this job"));
+            }
+        }
+        for (key, _) in ToolQuotas::limits(&self.quotas.per_job, tool) {
+            *calls.entry((job_id.to_string(), key.to_string())).or_default() += 1;
+        }
+        Ok(())
+    }
+
+    fn check_day_quota(&self, tool: &str) -> Result<(), String> {
+        let Some(pool) = &self.db_pool else {
+            return Ok(());
+        };
+        let today = chrono::Utc::now().date_naive().and_hms_opt(0, 0, 0).unwrap_or_default();
+        for (key, limit) in ToolQuotas::limits(&self.quotas.per_day, tool) {
+            let of_tool = (key != ANY_TOOL).then_some(key);
+            let used = crate::models::usage::count_tool_calls_since(pool, &self.agent_id, of_tool, today)?;
+            if used >= i64::from(limit) {
+                return Err(quota_error(key, limit, "today"));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn quota_error(key: &str, limit: u32, period: &str) -> String {
+    match key {
+        ANY_TOOL => format!("Tool call quota reached: {} calls {}", limit, period),
+        tool => format!("Tool call quota reached: {} calls of '{}' {}", limit, tool, period),
+    }
+}
+
+#[async_trait]
+impl ToolMiddleware for QuotaMiddleware {
+    fn name(&self) -> &str {
+        "quota"
+    }
+
+    async fn handle(
+        &self,
+        tool: &dyn Tool,
+        args: Value,
+        ctx: &ToolContext,
+        next: Next<'_>,
+    ) -> Result<Value, ToolRunError> {
+        self.check_day_quota(tool.name()).map_err(ToolRunError::Failed)?;
+        if let Some(job_id) = &ctx.job_id {
+            self.take_job_quota(job_id, tool.name()).map_err(ToolRunError::Failed)?;
+        }
+        next.run(tool, args, ctx).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::permissions::{PermissionManager, ScopeEnforcer};
+    use serde_json::json;
+
+    struct Echo;
+
+    #[async_trait]
+    impl Tool for Echo {
+        fn name(&self) -> &str {
+            "echo"
+        }
+        fn description(&self) -> &str {
+            "Returns its arguments"
+        }
+        fn parameters_schema(&self) -> Value {
+            json!({ "type": "object" })
+        }
+        async fn execute(&self, args: Value, _ctx: &ToolContext) -> Result<Value, String> {
+            Ok(args)
+        }
+    }
+
+    fn ctx() -> ToolContext {
+        ToolContext {
+            permissions: Arc::new(PermissionManager::new()),
+            observer: None,
+            session_id: "s1".to_string(),
+            scope: ScopeEnforcer::global(),
+            job_id: Some("j1".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_path_normalizer() {
+        let paths = PathNormalizer;
+        assert_eq!(paths.normalize(" ./src/./main.rs "), "src/main.rs");
+        assert_eq!(paths.normalize("'/work/space//notes/a.md'"), "/work/space/notes/a.md");
+        assert_eq!(paths.normalize("./"), ".");
+        assert_eq!(paths.normalize("../outside.txt"), "../outside.txt");
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(paths.normalize("~/notes.md"), home.join("notes.md").to_str().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_chain_rewrites_redacts_and_enforces_quotas() {
+        let redactor = Redactor::new([("TOKEN".to_string(), "s3cret".to_string())]);
+        let settings = json!({ "tool_quotas": { "per_job": { "echo": 2 } } });
+        let chain = ToolMiddlewareChain::for_agent(None, "a1", Some(&settings), redactor);
+        assert_eq!(chain.names(), ["logging", "quota", "paths", "redaction"]);
+
+        let result = chain
+            .execute(&Echo, json!({ "path": "./work/./a.txt", "content": "key=s3cret" }), &ctx())
+            .await
+            .unwrap();
+        assert_eq!(result["path"], "work/a.txt");
+        assert!(!result["content"].as_str().unwrap().contains("s3cret"));
+
+        chain.execute(&Echo, json!({}), &ctx()).await.unwrap();
+        let refused = chain.execute(&Echo, json!({}), &ctx()).await.unwrap_err();
+        assert!(refused.to_string().contains("quota reached"));
+
+        let other_job = ToolContext { job_id: Some("j2".to_string()), ..ctx() };
+        assert!(chain.execute(&Echo, json!({}), &other_job).await.is_ok());
+    }
+}
@@ -1,25 +1,42 @@
+pub mod agent_message;
 pub mod bash;
+pub mod config;
 pub mod contacts;
 pub mod email;
 pub mod filesystem;
+pub mod http;
+pub mod image_gen;
 pub mod mail_reader;
+pub mod middleware;
 pub mod office;
+pub mod pages;
+pub mod pdf;
+pub mod registry;
 pub mod search;
+pub mod spreadsheet;
+pub mod todo;
 pub mod transcribe;
+pub mod view_image;
 
 #[cfg(test)]
 pub mod workflow_tests;
 
-use crate::permissions::PermissionManager;
+use crate::locks::LockScope;
+use crate::permissions::{PermissionManager, ScopeEnforcer};
 use async_trait::async_trait;
-use serde_json::Value;
+use serde_json::{json, Value};
 use std::sync::Arc;
-use crate::events::AgentObserver;
+use std::time::Duration;
+use crate::events::{AgentEvent, AgentObserver};
 
 pub struct ToolContext {
     pub permissions: Arc<PermissionManager>,
     pub observer: Option<Arc<dyn AgentObserver>>,
     pub session_id: String,
+    /// Where the agent may touch files; path arguments are checked against it
+    pub scope: ScopeEnforcer,
+    /// Job the call belongs to; None outside the agent loop
+    pub job_id: Option<String>,
 }
 
 #[async_trait]
@@ -49,4 +66,231 @@ pub trait Tool: Send + Sync {
     fn requires_approval(&self, _args: &Value) -> bool {
         false
     }
+
+    // 5. Side effects (used by read-only agents). Conservative by default.
+    fn is_read_only(&self, _args: &Value) -> bool {
+        false
+    }
+
+    // 6. Limits. A call still running after the timeout is cancelled; None waits forever.
+    // The timeout includes any time spent waiting for the user to approve a permission.
+    fn execution_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    // Retries after a timeout. Only idempotent calls should be retried.
+    fn retry_policy(&self, _args: &Value) -> RetryPolicy {
+        RetryPolicy::none()
+    }
+
+    // 7. Caching. Read-only calls with identical arguments reuse the result for this long
+    // within a session, until the workspace changes. None disables caching.
+    fn cache_ttl(&self) -> Option<Duration> {
+        None
+    }
+
+    // 8. Locking. What a call that isn't read-only keeps other jobs out of while it runs.
+    // The whole workspace by default; narrow it for tools that touch one file or none.
+    fn lock_scope(&self, _args: &Value) -> LockScope {
+        LockScope::Workspace
+    }
+}
+
+/// How often a timed-out tool call is attempted again
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first
+    pub max_attempts: u32,
+    /// Wait before the first retry; doubled for each further one
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub const fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            backoff: Duration::ZERO,
+        }
+    }
+
+    pub const fn with_backoff(max_attempts: u32, backoff: Duration) -> Self {
+        Self { max_attempts, backoff }
+    }
+
+    fn delay(&self, attempt: u32) -> Duration {
+        self.backoff * 2u32.saturating_pow(attempt.saturating_sub(1))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// Why a tool call produced no result
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolRunError {
+    /// The tool returned an error
+    Failed(String),
+    /// Every attempt ran into the tool's execution timeout
+    TimedOut { timeout: Duration, attempts: u32 },
+}
+
+impl ToolRunError {
+    /// Step result for the model and the UI
+    pub fn to_result(&self, tool_name: &str) -> Value {
+        match self {
+            Self::Failed(e) => Value::String(format!("Error: {}", e)),
+            Self::TimedOut { timeout, attempts } => json!({
+                "status": "timed_out",
+                "tool": tool_name,
+                "timeout_seconds": timeout.as_secs(),
+                "attempts": attempts,
+                "error": self.to_string(),
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for ToolRunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Failed(e) => write!(f, "{}", e),
+            Self::TimedOut { timeout, attempts } => write!(
+                f,
+                "Timed out after {}s ({} attempt{})",
+                timeout.as_secs(),
+                attempts,
+                if *attempts == 1 { "" } else { "s" }
+            ),
+        }
+    }
+}
+
+/// Run `tool` under its execution timeout and retry policy
+#[tracing::instrument(
+    name = "tool.execute",
+    skip_all,
+    fields(tool = tool.name(), session_id = %ctx.session_id, attempts = 1, outcome = tracing::field::Empty)
+)]
+pub async fn execute_with_policy(tool: &dyn Tool, args: &Value, ctx: &ToolContext) -> Result<Value, ToolRunError> {
+    let span = tracing::Span::current();
+    let Some(timeout) = tool.execution_timeout() else {
+        let result = tool.execute(args.clone(), ctx).await;
+        span.record("outcome", if result.is_ok() { "ok" } else { "error" });
+        return result.map_err(ToolRunError::Failed);
+    };
+    let policy = tool.retry_policy(args);
+    let max_attempts = policy.max_attempts.max(1);
+
+    for attempt in 1..=max_attempts {
+        span.record("attempts", attempt);
+        if let Ok(result) = tokio::time::timeout(timeout, tool.execute(args.clone(), ctx)).await {
+            span.record("outcome", if result.is_ok() { "ok" } else { "error" });
+            return result.map_err(ToolRunError::Failed);
+        }
+        log::warn!("Tool '{}' timed out after {:?} (attempt {}/{})", tool.name(), timeout, attempt, max_attempts);
+        if attempt < max_attempts {
+            let delay = policy.delay(attempt);
+            if let Some(observer) = &ctx.observer {
+                let _ = observer.emit(
+                    &format!("session:{}", ctx.session_id),
+                    serde_json::to_value(AgentEvent::Thinking {
+                        message: format!(
+                            "{} timed out, retrying in {}s (attempt {}/{})",
+                            tool.name(),
+                            delay.as_secs_f64(),
+                            attempt + 1,
+                            max_attempts
+                        ),
+                    })
+                    .unwrap_or_default(),
+                );
+            }
+            tokio::time::sleep(delay).await;
+        }
+    }
+    span.record("outcome", "timed_out");
+    Err(ToolRunError::TimedOut {
+        timeout,
+        attempts: max_attempts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Hangs on the first `hangs` calls, then answers
+    struct Flaky {
+        hangs: u32,
+        calls: AtomicU32,
+        retry: RetryPolicy,
+    }
+
+    #[async_trait]
+    impl Tool for Flaky {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+        fn description(&self) -> &str {
+            "Hangs, then answers"
+        }
+        fn parameters_schema(&self) -> Value {
+            json!({ "type": "object" })
+        }
+        fn execution_timeout(&self) -> Option<Duration> {
+            Some(Duration::from_millis(20))
+        }
+        fn retry_policy(&self, _args: &Value) -> RetryPolicy {
+            self.retry
+        }
+        async fn execute(&self, _args: Value, _ctx: &ToolContext) -> Result<Value, String> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.hangs {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            }
+            Ok(json!("done"))
+        }
+    }
+
+    fn ctx() -> ToolContext {
+        ToolContext {
+            permissions: Arc::new(PermissionManager::new()),
+            observer: None,
+            session_id: "test".to_string(),
+            scope: Default::default(),
+            job_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timeout_then_retry() {
+        let tool = Flaky {
+            hangs: 1,
+            calls: AtomicU32::new(0),
+            retry: RetryPolicy::with_backoff(2, Duration::from_millis(1)),
+        };
+        assert_eq!(execute_with_policy(&tool, &json!({}), &ctx()).await, Ok(json!("done")));
+        assert_eq!(tool.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_result() {
+        let tool = Flaky {
+            hangs: 5,
+            calls: AtomicU32::new(0),
+            retry: RetryPolicy::none(),
+        };
+        let err = execute_with_policy(&tool, &json!({}), &ctx()).await.unwrap_err();
+        assert_eq!(
+            err,
+            ToolRunError::TimedOut {
+                timeout: Duration::from_millis(20),
+                attempts: 1
+            }
+        );
+        assert_eq!(err.to_result("flaky")["status"], "timed_out");
+    }
 }
@@ -1,9 +1,10 @@
 use super::{Tool, ToolContext};
-use crate::permissions::{PermissionRequest, PermissionType};
+use crate::models::ArtifactOutput;
+use crate::permissions::{PermissionPayload, PermissionRequest, PermissionType};
 use async_trait::async_trait;
 use serde_json::{json, Value};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 // CSV
 use csv::ReaderBuilder;
 
@@ -16,6 +17,8 @@ use docx_rs::*;
 use pdf_extract::extract_text;
 use std::fs::File; // Needed for docx writing
 
+use super::spreadsheet::{parse_cell, Workbook};
+
 // Docx
 // docx-rs is mainly for writing. For simple reading, let's use a zip extractor approach
 // akin to 'zip' crate if we wanted to be pure, but since we added docx-rs, let's see if we can use it.
@@ -40,7 +43,10 @@ impl Tool for OfficeTool {
     }
 
     fn description(&self) -> &str {
-        "Read content from Office files (Excel, CSV, PDF, Word). Supported formats: .xlsx, .csv, .pdf"
+        "Read content from Office files (Excel, CSV, PDF, Word) and edit spreadsheets. \
+         Spreadsheet operations (.xlsx, .csv): list_sheets, read_sheet (optional sheet and A1 range), \
+         write_cells (cells like {\"A1\": 5, \"B1\": \"=SUM(A1:A3)\"}; creates the file if needed), \
+         add_sheet, evaluate (compute a formula against a sheet)."
     }
 
     fn parameters_schema(&self) -> Value {
@@ -49,7 +55,10 @@ impl Tool for OfficeTool {
             "properties": {
                 "operation": {
                     "type": "string",
-                    "enum": ["read_excel", "read_csv", "read_pdf", "write_docx"],
+                    "enum": [
+                        "read_excel", "read_csv", "read_pdf", "write_docx",
+                        "list_sheets", "read_sheet", "write_cells", "add_sheet", "evaluate"
+                    ],
                     "description": "The operation to perform"
                 },
                 "path": {
@@ -59,6 +68,22 @@ impl Tool for OfficeTool {
                 "content": {
                     "type": "string",
                     "description": "Content to write (for write_docx)"
+                },
+                "sheet": {
+                    "type": "string",
+                    "description": "Sheet name (default: the first sheet; the new sheet for add_sheet)"
+                },
+                "range": {
+                    "type": "string",
+                    "description": "A1 range to read, e.g. A1:D20 (default: the used area)"
+                },
+                "cells": {
+                    "type": "object",
+                    "description": "Cells to write by A1 reference; strings starting with '=' are formulas, null clears a cell"
+                },
+                "formula": {
+                    "type": "string",
+                    "description": "Formula to evaluate, e.g. =AVERAGE(B2:B40)"
                 }
             },
             "required": ["operation", "path"]
@@ -70,6 +95,21 @@ impl Tool for OfficeTool {
         if path_str.contains("..") || path_str.starts_with("/") {
             return Err("Access denied: Paths must be relative".to_string());
         }
+        match args["operation"].as_str() {
+            Some("write_cells") => {
+                let cells = args["cells"].as_object().ok_or("write_cells needs a 'cells' object")?;
+                for reference in cells.keys() {
+                    parse_cell(reference)?;
+                }
+            }
+            Some("add_sheet") if args["sheet"].as_str().is_none() => {
+                return Err("add_sheet needs a 'sheet' name".to_string());
+            }
+            Some("evaluate") if args["formula"].as_str().is_none() => {
+                return Err("evaluate needs a 'formula'".to_string());
+            }
+            _ => {}
+        }
         Ok(())
     }
 
@@ -77,12 +117,27 @@ impl Tool for OfficeTool {
         true // Always summarize office file content reading
     }
 
+    fn is_read_only(&self, args: &Value) -> bool {
+        args["operation"]
+            .as_str()
+            .map(|op| op.starts_with("read_") || op == "list_sheets" || op == "evaluate")
+            .unwrap_or(false)
+    }
+
     async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<Value, String> {
         let op = args["operation"].as_str().ok_or("Missing operation")?;
         let path_str = args["path"].as_str().ok_or("Missing path")?;
 
+        let root = ctx
+            .scope
+            .workspace_path()
+            .cloned()
+            .unwrap_or_else(|| std::env::current_dir().unwrap_or(PathBuf::from(".")));
+        let target_path = ctx.scope.resolve(&root, path_str)?;
+
         // Permission check
-        let permission_type = if op == "write_docx" {
+        let creates_file = matches!(op, "write_docx" | "write_cells" | "add_sheet");
+        let permission_type = if creates_file {
             PermissionType::FilesystemWrite
         } else {
             PermissionType::FilesystemRead
@@ -102,8 +157,10 @@ impl Tool for OfficeTool {
                 map.insert("path".to_string(), path_str.to_string());
                 map.insert("resource".to_string(), path_str.to_string());
                 map.insert("session_id".to_string(), ctx.session_id.clone());
+                map.insert("tool".to_string(), self.name().to_string());
                 map
             },
+            payload: (!creates_file).then(|| PermissionPayload::FileRead { path: path_str.to_string() }),
         };
 
         if !ctx
@@ -114,24 +171,73 @@ impl Tool for OfficeTool {
             return Err("Permission denied".to_string());
         }
 
-        let root = std::env::current_dir().unwrap_or(PathBuf::from("."));
-        let target_path = root.join(path_str);
 
-        if !target_path.exists() {
+        if !creates_file && !target_path.exists() {
             return Err("File not found".to_string());
         }
 
-        match op {
+        let mut result = match op {
             "read_csv" => self.read_csv(&target_path),
             "read_excel" => self.read_excel(&target_path),
             "read_pdf" => self.read_pdf(&target_path),
             "write_docx" => self.write_docx(&target_path, &args),
+            "list_sheets" => Ok(json!({ "sheets": Workbook::open(&target_path)?.sheet_names() })),
+            "read_sheet" => Workbook::open(&target_path)?
+                .sheet(args["sheet"].as_str())?
+                .read(args["range"].as_str()),
+            "write_cells" => self.write_cells(&target_path, &args),
+            "add_sheet" => {
+                let name = args["sheet"].as_str().ok_or("Missing sheet")?;
+                let mut workbook = Workbook::open_or_new(&target_path)?;
+                workbook.add_sheet(name)?;
+                workbook.save(&target_path)?;
+                Ok(json!({ "sheets": workbook.sheet_names() }))
+            }
+            "evaluate" => {
+                let formula = args["formula"].as_str().ok_or("Missing formula")?;
+                let workbook = Workbook::open(&target_path)?;
+                let value = workbook.sheet(args["sheet"].as_str())?.evaluate(formula)?;
+                Ok(json!({ "formula": formula, "value": value }))
+            }
             _ => Err(format!("Unknown operation: {}", op)),
+        }?;
+
+        // Documents the agent writes are outputs of the job
+        if creates_file {
+            crate::models::artifact::attach(
+                &mut result,
+                vec![ArtifactOutput::new("document", target_path.to_string_lossy())],
+            );
         }
+        Ok(result)
     }
 }
 
 impl OfficeTool {
+    /// Set cells, recompute formulas and save; returns the new formula values
+    fn write_cells(&self, path: &Path, args: &Value) -> Result<Value, String> {
+        let cells = args["cells"].as_object().ok_or("Missing cells")?;
+        let mut workbook = Workbook::open_or_new(path)?;
+        let sheet = workbook.sheet_mut(args["sheet"].as_str())?;
+        for (reference, value) in cells {
+            sheet.set(parse_cell(reference)?, value);
+        }
+        workbook.recalculate();
+        workbook.save(path)?;
+
+        let sheet = workbook.sheet(args["sheet"].as_str())?;
+        let formulas: serde_json::Map<String, Value> = cells
+            .iter()
+            .filter(|(_, value)| value.as_str().is_some_and(|v| v.starts_with('=')))
+            .map(|(reference, _)| Ok((reference.clone(), sheet.get(parse_cell(reference)?))))
+            .collect::<Result<_, String>>()?;
+        Ok(json!({
+            "sheet": sheet.name,
+            "updated": cells.len(),
+            "formulas": formulas,
+        }))
+    }
+
     fn write_docx(&self, path: &PathBuf, args: &Value) -> Result<Value, String> {
         let content = args["content"]
             .as_str()
@@ -154,7 +260,7 @@ impl OfficeTool {
             .pack(file)
             .map_err(|e| format!("Failed to write docx: {}", e))?;
 
-        Ok(json!("Document created successfully"))
+        Ok(json!({ "message": "Document created successfully" }))
     }
     fn read_csv(&self, path: &PathBuf) -> Result<Value, String> {
         let mut rdr = ReaderBuilder::new()
@@ -300,4 +406,28 @@ mod tests {
         // We do not have a robust read_docx method implemented, but we verified the file was created.
         println!("Created DOCX at {:?}", file_path);
     }
+
+    #[tokio::test]
+    async fn test_spreadsheet_operations() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("totals.xlsx");
+        let tool = OfficeTool;
+
+        let written = tool
+            .write_cells(
+                &path,
+                &json!({ "cells": { "A1": 4, "A2": 6, "B1": "=SUM(A1:A2)" } }),
+            )
+            .unwrap();
+        assert_eq!(written["formulas"]["B1"], 10);
+
+        let args = json!({ "operation": "write_cells", "path": "totals.xlsx", "cells": { "Z0": 1 } });
+        assert!(tool.validate_args(&args).await.is_err());
+        assert!(tool.is_read_only(&json!({ "operation": "evaluate" })));
+
+        let sheet = Workbook::open(&path).unwrap();
+        let read = sheet.sheet(None).unwrap().read(None).unwrap();
+        assert_eq!(read["range"], "A1:B2");
+        assert_eq!(read["rows"][0], json!([4, 10]));
+    }
 }
@@ -0,0 +1,286 @@
+use super::{Tool, ToolContext};
+use crate::database::{blocking, DbPool};
+use crate::locks::LockScope;
+use crate::pages::{self, collab};
+use crate::permissions::{PermissionPayload, PermissionRequest, PermissionType};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+const LIST_LIMIT: i64 = 50;
+
+/// Read and write the user's pages (notes). Pages are exchanged as markdown;
+/// every change asks for the `pages_write` permission.
+pub struct PagesTool {
+    db_pool: DbPool,
+}
+
+impl PagesTool {
+    pub fn new(db_pool: DbPool) -> Self {
+        Self { db_pool }
+    }
+
+    async fn page_title(&self, page_id: &str) -> Result<String, String> {
+        use crate::schema::pages;
+        use diesel::prelude::*;
+
+        let page_id = page_id.to_string();
+        blocking(&self.db_pool, move |conn| {
+            pages::table
+                .find(&page_id)
+                .select(pages::title)
+                .first(conn)
+                .map_err(|_| format!("Page '{}' not found", page_id))
+        })
+        .await
+    }
+
+    async fn block_page_title(&self, block_id: &str) -> Result<String, String> {
+        use crate::schema::{blocks, pages};
+        use diesel::prelude::*;
+
+        let block_id = block_id.to_string();
+        blocking(&self.db_pool, move |conn| {
+            blocks::table
+                .inner_join(pages::table)
+                .filter(blocks::id.eq(&block_id))
+                .select(pages::title)
+                .first(conn)
+                .map_err(|_| format!("Block '{}' not found", block_id))
+        })
+        .await
+    }
+
+    async fn ask(&self, ctx: &ToolContext, operation: &str, page: &str, markdown: &str) -> Result<(), String> {
+        let perm_req = PermissionRequest {
+            id: uuid::Uuid::new_v4().to_string(),
+            permission_type: PermissionType::PagesWrite,
+            message: match operation {
+                "create" => format!("Agent wants to create the page '{}'", page),
+                "append" => format!("Agent wants to add to the page '{}'", page),
+                _ => format!("Agent wants to edit the page '{}'", page),
+            },
+            metadata: {
+                let mut map = HashMap::new();
+                map.insert("operation".to_string(), operation.to_string());
+                map.insert("resource".to_string(), page.to_string());
+                map.insert("session_id".to_string(), ctx.session_id.clone());
+                map.insert("tool".to_string(), self.name().to_string());
+                map
+            },
+            payload: Some(PermissionPayload::PageEdit {
+                page: page.to_string(),
+                operation: operation.to_string(),
+                markdown: markdown.to_string(),
+            }),
+        };
+        if ctx
+            .permissions
+            .request_permission(ctx.observer.as_ref(), perm_req)
+            .await?
+        {
+            Ok(())
+        } else {
+            Err("Permission denied".to_string())
+        }
+    }
+
+    /// Let open editors of the page show what the agent wrote
+    fn announce(&self, ctx: &ToolContext, created: &[String], updated: &[String]) {
+        let Some(observer) = &ctx.observer else { return };
+        match collab::load_changes(&self.db_pool, created, updated) {
+            Ok(changes) => collab::emit_changes(observer.as_ref(), &changes),
+            Err(e) => log::warn!("Failed to load written blocks: {}", e),
+        }
+    }
+
+    async fn block_versions(&self, page_id: &str) -> Result<HashMap<String, i32>, String> {
+        use crate::schema::blocks;
+        use diesel::prelude::*;
+
+        let page_id = page_id.to_string();
+        let versions: Vec<(String, i32)> = blocking(&self.db_pool, move |conn| {
+            blocks::table
+                .filter(blocks::page_id.eq(&page_id))
+                .select((blocks::id, blocks::version))
+                .load(conn)
+                .map_err(|e| e.to_string())
+        })
+        .await?;
+        Ok(versions.into_iter().collect())
+    }
+}
+
+fn str_arg<'a>(args: &'a Value, key: &str) -> Result<&'a str, String> {
+    args.get(key)
+        .and_then(Value::as_str)
+        .filter(|s| !s.trim().is_empty())
+        .ok_or_else(|| format!("Missing '{}'", key))
+}
+
+#[async_trait]
+impl Tool for PagesTool {
+    fn name(&self) -> &str {
+        "pages"
+    }
+
+    fn description(&self) -> &str {
+        "Read and write the user's pages (their notes). Use this to:\n\
+         - list: find pages by title\n\
+         - read: get a page as markdown; each block is preceded by <!-- block:ID -->\n\
+         - create: start a new page with markdown content\n\
+         - append: add markdown to the end of a page\n\
+         - update_block: replace one block (by its ID from read) with markdown; pass the block's \
+         version from read so an edit the user made since is not overwritten\n\
+         \n\
+         Markdown supports headings, '- ' bullets, '- [ ] ' checkboxes, images and paragraphs. \
+         Put research results and summaries the user should keep here."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "action": {
+                    "type": "string",
+                    "enum": ["list", "read", "create", "append", "update_block"]
+                },
+                "query": {
+                    "type": "string",
+                    "description": "list: words in the page title"
+                },
+                "page_id": {
+                    "type": "string",
+                    "description": "read, append: the page"
+                },
+                "block_id": {
+                    "type": "string",
+                    "description": "update_block: the block to replace"
+                },
+                "version": {
+                    "type": "integer",
+                    "description": "update_block: the block's version from read"
+                },
+                "title": {
+                    "type": "string",
+                    "description": "create: title of the new page"
+                },
+                "parent_id": {
+                    "type": "string",
+                    "description": "create: optional page to nest the new page under"
+                },
+                "markdown": {
+                    "type": "string",
+                    "description": "create, append, update_block: the content"
+                }
+            },
+            "required": ["action"]
+        })
+    }
+
+    fn is_read_only(&self, args: &Value) -> bool {
+        matches!(args.get("action").and_then(Value::as_str), Some("list" | "read"))
+    }
+
+    fn lock_scope(&self, _args: &Value) -> LockScope {
+        // Pages live in the database, not the workspace
+        LockScope::None
+    }
+
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<Value, String> {
+        let action = str_arg(&args, "action")?;
+        match action {
+            "list" => {
+                let query = args.get("query").and_then(Value::as_str);
+                let found: Vec<Value> = pages::list_pages(&self.db_pool, query, LIST_LIMIT)?
+                    .into_iter()
+                    .map(|p| json!({ "id": p.id, "title": p.title, "parent_id": p.parent_id, "updated_at": p.updated_at }))
+                    .collect();
+                Ok(json!({ "pages": found, "count": found.len() }))
+            }
+            "read" => {
+                let page_id = str_arg(&args, "page_id")?;
+                Ok(json!({
+                    "page_id": page_id,
+                    "markdown": pages::export_markdown(&self.db_pool, page_id)?,
+                    "versions": self.block_versions(page_id).await?,
+                }))
+            }
+            "create" => {
+                let title = str_arg(&args, "title")?;
+                let markdown = args.get("markdown").and_then(Value::as_str).unwrap_or_default();
+                let parent_id = args.get("parent_id").and_then(Value::as_str).filter(|s| !s.is_empty());
+                if let Some(parent) = parent_id {
+                    self.page_title(parent).await?;
+                }
+                self.ask(ctx, "create", title, markdown).await?;
+                let page = pages::create_page(&self.db_pool, title, parent_id, markdown)?;
+                let block_ids: Vec<String> = self.block_versions(&page.id).await?.into_keys().collect();
+                self.announce(ctx, &block_ids, &[]);
+                Ok(json!({ "status": "created", "page_id": page.id, "title": page.title }))
+            }
+            "append" => {
+                let page_id = str_arg(&args, "page_id")?;
+                let markdown = str_arg(&args, "markdown")?;
+                let title = self.page_title(page_id).await?;
+                self.ask(ctx, "append", &title, markdown).await?;
+                let block_ids = pages::append_markdown(&self.db_pool, page_id, markdown)?;
+                self.announce(ctx, &block_ids, &[]);
+                Ok(json!({ "status": "appended", "page_id": page_id, "block_ids": block_ids }))
+            }
+            "update_block" => {
+                let block_id = str_arg(&args, "block_id")?;
+                let markdown = str_arg(&args, "markdown")?;
+                let title = self.block_page_title(block_id).await?;
+                self.ask(ctx, "update_block", &title, markdown).await?;
+                let version = args.get("version").and_then(Value::as_i64).map(|v| v as i32);
+                let editor = collab::agent_editor(&ctx.session_id);
+                let block_ids = pages::replace_block(&self.db_pool, block_id, markdown, version, &editor)?;
+                self.announce(ctx, &block_ids[1..], &block_ids[..1]);
+                Ok(json!({ "status": "updated", "block_ids": block_ids }))
+            }
+            other => Err(format!("Unknown action '{}'", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+    use crate::permissions::{AgentPolicy, PermissionManager};
+    use std::sync::Arc;
+
+    fn context(policy: &str) -> ToolContext {
+        ToolContext {
+            permissions: Arc::new(PermissionManager::new().with_policy(AgentPolicy::parse(policy).unwrap())),
+            observer: None,
+            session_id: "test".to_string(),
+            scope: Default::default(),
+            job_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_needs_permission() {
+        let pool = create_test_pool();
+        let tool = PagesTool::new(pool.clone());
+        let args = json!({"action": "create", "title": "Research", "markdown": "# Findings"});
+        assert!(!tool.is_read_only(&args));
+        assert!(tool.is_read_only(&json!({"action": "read"})));
+
+        assert_eq!(tool.execute(args.clone(), &context("default: deny")).await.unwrap_err(), "Permission denied");
+        assert!(pages::list_pages(&pool, None, 10).unwrap().is_empty());
+
+        let ctx = context("default: allow");
+        let created = tool.execute(args, &ctx).await.unwrap();
+        let page_id = created["page_id"].as_str().unwrap();
+        let read = tool.execute(json!({"action": "read", "page_id": page_id}), &ctx).await.unwrap();
+        assert!(read["markdown"].as_str().unwrap().contains("# Findings"));
+        let (block_id, version) = read["versions"].as_object().unwrap().iter().next().unwrap();
+        assert_eq!(version, 1);
+        let edit = json!({"action": "update_block", "block_id": block_id, "markdown": "# Results", "version": 1});
+        assert_eq!(tool.execute(edit.clone(), &ctx).await.unwrap()["block_ids"][0], block_id.as_str());
+        assert!(tool.execute(edit, &ctx).await.unwrap_err().contains("changed since version 1"));
+    }
+}
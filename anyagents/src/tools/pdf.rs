@@ -0,0 +1,474 @@
+//! PDF text extraction with an OCR fallback for scanned pages
+//!
+//! Text comes from the PDF's content streams (lopdf). Pages that yield no text
+//! are usually scans; when Docker is available they are rasterized and run
+//! through tesseract in a sandbox without network access. The text is returned
+//! in chunks small enough to drop into the LLM context one at a time.
+
+use super::{Tool, ToolContext};
+use crate::models::SandboxConfig;
+use crate::permissions::scope::resolve_within;
+use crate::permissions::{PermissionPayload, PermissionRequest, PermissionType};
+use crate::skills::command::shell_quote;
+use crate::skills::docker::DockerSandbox;
+use async_trait::async_trait;
+use lopdf::{Dictionary, Document, Object};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Sandbox image with poppler-utils and tesseract installed
+pub const OCR_IMAGE: &str = "anycowork/ocr:latest";
+
+pub const DEFAULT_CHUNK_CHARS: usize = 4000;
+const MIN_CHUNK_CHARS: usize = 500;
+/// Pages OCR'd per call; each one is a separate container run
+const MAX_OCR_PAGES: usize = 20;
+const INFO_KEYS: &[&str] = &[
+    "Title",
+    "Author",
+    "Subject",
+    "Keywords",
+    "Creator",
+    "Producer",
+    "CreationDate",
+    "ModDate",
+];
+
+/// Text of one page, and whether it came from OCR
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageText {
+    pub page: u32,
+    pub text: String,
+    pub ocr: bool,
+}
+
+pub struct PdfTool {
+    workspace_path: PathBuf,
+}
+
+impl PdfTool {
+    pub fn new(workspace_path: PathBuf) -> Self {
+        Self { workspace_path }
+    }
+
+    async fn ocr_page(
+        &self,
+        sandbox: &DockerSandbox,
+        path: &str,
+        page: u32,
+    ) -> Result<String, String> {
+        let script = format!(
+            "pdftoppm -f {page} -l {page} -r 200 -gray -png {} /tmp/page && tesseract /tmp/page*.png - 2>/dev/null",
+            shell_quote(&format!("/workspace/{}", path))
+        );
+        let config = SandboxConfig {
+            image: Some(OCR_IMAGE.to_string()),
+            memory_limit: Some("512m".to_string()),
+            cpu_limit: Some(1.0),
+            timeout_seconds: Some(60),
+            network_enabled: Some(false),
+            runtime: None,
+            ..Default::default()
+        };
+        let result = sandbox
+            .execute(
+                &format!("sh -c {}", shell_quote(&script)),
+                &self.workspace_path,
+                None,
+                &config,
+            )
+            .await?;
+        if result.success {
+            Ok(result.stdout.trim().to_string())
+        } else if result.timed_out {
+            Err(format!("OCR of page {} timed out", page))
+        } else {
+            Err(format!("OCR of page {} failed: {}", page, result.stderr.trim()))
+        }
+    }
+}
+
+/// Parse a page selection like "1-3,5" against a document of `page_count` pages
+pub fn parse_pages(spec: &str, page_count: u32) -> Result<Vec<u32>, String> {
+    let mut pages = Vec::new();
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (start, end) = match part.split_once('-') {
+            Some((a, b)) => (parse_page(a)?, parse_page(b)?),
+            None => {
+                let page = parse_page(part)?;
+                (page, page)
+            }
+        };
+        if start > end || end > page_count {
+            return Err(format!(
+                "Invalid page range '{}' (document has {} pages)",
+                part, page_count
+            ));
+        }
+        for page in start..=end {
+            if !pages.contains(&page) {
+                pages.push(page);
+            }
+        }
+    }
+    if pages.is_empty() {
+        return Err("No pages selected".to_string());
+    }
+    Ok(pages)
+}
+
+fn parse_page(s: &str) -> Result<u32, String> {
+    match s.trim().parse::<u32>() {
+        Ok(page) if page > 0 => Ok(page),
+        _ => Err(format!("Invalid page number '{}'", s.trim())),
+    }
+}
+
+/// Split page texts into chunks of at most `max_chars`, keeping page boundaries
+/// where possible and breaking long pages on paragraphs, then whitespace
+pub fn chunk_pages(pages: &[PageText], max_chars: usize) -> Vec<Value> {
+    let mut chunks = Vec::new();
+    let mut text = String::new();
+    let mut span: Option<(u32, u32)> = None;
+
+    let mut flush = |text: &mut String, span: &mut Option<(u32, u32)>| {
+        if let Some((first, last)) = span.take() {
+            chunks.push(json!({
+                "index": chunks.len(),
+                "pages": [first, last],
+                "text": std::mem::take(text),
+            }));
+        }
+    };
+
+    for page in pages {
+        for piece in split_text(&page.text, max_chars) {
+            let needed = if text.is_empty() { piece.len() } else { piece.len() + 2 };
+            if !text.is_empty() && text.len() + needed > max_chars {
+                flush(&mut text, &mut span);
+            }
+            if !text.is_empty() {
+                text.push_str("\n\n");
+            }
+            text.push_str(piece);
+            span = Some(match span {
+                Some((first, _)) => (first, page.page),
+                None => (page.page, page.page),
+            });
+        }
+    }
+    flush(&mut text, &mut span);
+    chunks
+}
+
+fn split_text(text: &str, max_chars: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut rest = text.trim();
+    while rest.len() > max_chars {
+        let mut limit = max_chars;
+        while !rest.is_char_boundary(limit) {
+            limit -= 1;
+        }
+        let window = &rest[..limit];
+        let cut = window
+            .rfind("\n\n")
+            .or_else(|| window.rfind(char::is_whitespace))
+            .filter(|&i| i > 0)
+            .unwrap_or(limit);
+        pieces.push(rest[..cut].trim_end());
+        rest = rest[cut..].trim_start();
+    }
+    if !rest.is_empty() {
+        pieces.push(rest);
+    }
+    pieces
+}
+
+/// Document info (title, author, dates...) plus page count and version
+pub fn metadata(doc: &Document) -> Value {
+    let mut info = Map::new();
+    if let Some(dict) = info_dict(doc) {
+        for key in INFO_KEYS {
+            if let Ok(value) = dict.get(key.as_bytes()) {
+                if let Ok(text) = lopdf::decode_text_string(value) {
+                    if !text.trim().is_empty() {
+                        info.insert(key.to_lowercase(), json!(text.trim()));
+                    }
+                }
+            }
+        }
+    }
+    json!({
+        "page_count": doc.get_pages().len(),
+        "version": doc.version,
+        "encrypted": doc.is_encrypted(),
+        "info": info,
+    })
+}
+
+fn info_dict(doc: &Document) -> Option<&Dictionary> {
+    match doc.trailer.get(b"Info").ok()? {
+        Object::Reference(id) => doc.get_dictionary(*id).ok(),
+        Object::Dictionary(dict) => Some(dict),
+        _ => None,
+    }
+}
+
+fn extract_pages(doc: &Document, pages: &[u32]) -> Vec<PageText> {
+    pages
+        .iter()
+        .map(|&page| PageText {
+            page,
+            // A page whose fonts lopdf cannot decode is treated like a scan
+            text: doc.extract_text(&[page]).unwrap_or_default().trim().to_string(),
+            ocr: false,
+        })
+        .collect()
+}
+
+fn load(path: &Path) -> Result<Document, String> {
+    let doc = Document::load(path).map_err(|e| format!("Failed to open PDF: {}", e))?;
+    if doc.is_encrypted() {
+        return Err("PDF is encrypted".to_string());
+    }
+    Ok(doc)
+}
+
+#[async_trait]
+impl Tool for PdfTool {
+    fn name(&self) -> &str {
+        "pdf"
+    }
+
+    fn description(&self) -> &str {
+        "Extract text and metadata from a PDF in the workspace. Operations: extract (text in chunks, \
+         optionally for selected pages like \"1-3,5\"; scanned pages are OCR'd when ocr is true) and \
+         metadata (title, author, dates, page count)."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "operation": {
+                    "type": "string",
+                    "enum": ["extract", "metadata"],
+                    "description": "The operation to perform (default: extract)"
+                },
+                "path": {
+                    "type": "string",
+                    "description": "Relative path to the PDF"
+                },
+                "pages": {
+                    "type": "string",
+                    "description": "Pages to extract, e.g. \"1-3,5\" (default: all)"
+                },
+                "chunk_chars": {
+                    "type": "integer",
+                    "description": "Maximum characters per chunk (default: 4000)"
+                },
+                "ocr": {
+                    "type": "boolean",
+                    "description": "OCR pages without a text layer (default: true)"
+                }
+            },
+            "required": ["path"]
+        })
+    }
+
+    async fn validate_args(&self, args: &Value) -> Result<(), String> {
+        let path = args["path"].as_str().ok_or("Missing path")?;
+        if path.contains("..") || path.starts_with('/') {
+            return Err("Access denied: Paths must be relative and cannot contain '..'".to_string());
+        }
+        match args["operation"].as_str() {
+            None | Some("extract") | Some("metadata") => {}
+            Some(op) => return Err(format!("Unknown operation: {}", op)),
+        }
+        if let Some(chunk) = args.get("chunk_chars").filter(|v| !v.is_null()) {
+            match chunk.as_u64() {
+                Some(n) if n as usize >= MIN_CHUNK_CHARS => {}
+                _ => return Err(format!("chunk_chars must be at least {}", MIN_CHUNK_CHARS)),
+            }
+        }
+        Ok(())
+    }
+
+    fn needs_summarization(&self, args: &Value, _result: &Value) -> bool {
+        args["operation"].as_str().unwrap_or("extract") == "extract"
+    }
+
+    fn is_read_only(&self, _args: &Value) -> bool {
+        true
+    }
+
+    fn cache_ttl(&self) -> Option<Duration> {
+        Some(Duration::from_secs(300))
+    }
+
+    fn execution_timeout(&self) -> Option<Duration> {
+        // Leaves room for a few sandboxed OCR runs
+        Some(Duration::from_secs(600))
+    }
+
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<Value, String> {
+        let path_str = args["path"].as_str().ok_or("Missing path")?.to_string();
+        let op = args["operation"].as_str().unwrap_or("extract");
+        let full_path = resolve_within(&self.workspace_path, Path::new(&path_str))?;
+        ctx.scope.check_path(&full_path)?;
+
+        let perm_req = PermissionRequest {
+            id: uuid::Uuid::new_v4().to_string(),
+            permission_type: PermissionType::FilesystemRead,
+            message: format!("Agent wants to read PDF {}", path_str),
+            metadata: {
+                let mut map = HashMap::new();
+                map.insert("operation".to_string(), op.to_string());
+                map.insert("path".to_string(), path_str.clone());
+                map.insert("resource".to_string(), path_str.clone());
+                map.insert("session_id".to_string(), ctx.session_id.clone());
+                map.insert("tool".to_string(), self.name().to_string());
+                map
+            },
+            payload: Some(PermissionPayload::FileRead { path: path_str.clone() }),
+        };
+        if !ctx
+            .permissions
+            .request_permission(ctx.observer.as_ref(), perm_req)
+            .await?
+        {
+            return Err("Permission denied".to_string());
+        }
+
+        if !full_path.is_file() {
+            return Err(format!("File not found: {}", path_str));
+        }
+
+        if op == "metadata" {
+            return tokio::task::spawn_blocking(move || load(&full_path).map(|doc| metadata(&doc)))
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        let spec = args["pages"].as_str().map(str::to_string);
+        let (page_count, mut pages) = tokio::task::spawn_blocking(move || {
+            let doc = load(&full_path)?;
+            let page_count = doc.get_pages().len() as u32;
+            let selected = match spec.as_deref() {
+                Some(spec) => parse_pages(spec, page_count)?,
+                None => (1..=page_count).collect(),
+            };
+            Ok::<_, String>((page_count, extract_pages(&doc, &selected)))
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+
+        let mut warnings = Vec::new();
+        let blank: Vec<u32> = pages.iter().filter(|p| p.text.is_empty()).map(|p| p.page).collect();
+        if !blank.is_empty() && args["ocr"].as_bool().unwrap_or(true) {
+            let mut sandbox = DockerSandbox::new();
+            sandbox.init().await;
+            if !sandbox.is_available() {
+                warnings.push(format!(
+                    "{} page(s) have no text layer; OCR needs Docker, which is not available",
+                    blank.len()
+                ));
+            } else {
+                if blank.len() > MAX_OCR_PAGES {
+                    warnings.push(format!(
+                        "Only the first {} of {} pages without text were OCR'd; request the rest with 'pages'",
+                        MAX_OCR_PAGES,
+                        blank.len()
+                    ));
+                }
+                for number in blank.into_iter().take(MAX_OCR_PAGES) {
+                    match self.ocr_page(&sandbox, &path_str, number).await {
+                        Ok(text) => {
+                            if let Some(page) = pages.iter_mut().find(|p| p.page == number) {
+                                page.text = text;
+                                page.ocr = true;
+                            }
+                        }
+                        Err(e) => warnings.push(e),
+                    }
+                }
+            }
+        }
+
+        let chunk_chars = args["chunk_chars"]
+            .as_u64()
+            .map(|n| n as usize)
+            .unwrap_or(DEFAULT_CHUNK_CHARS);
+        let chunks = chunk_pages(&pages, chunk_chars);
+        Ok(json!({
+            "path": path_str,
+            "page_count": page_count,
+            "pages_extracted": pages.len(),
+            "ocr_pages": pages.iter().filter(|p| p.ocr).map(|p| p.page).collect::<Vec<_>>(),
+            "empty_pages": pages.iter().filter(|p| p.text.is_empty()).map(|p| p.page).collect::<Vec<_>>(),
+            "total_chars": pages.iter().map(|p| p.text.len()).sum::<usize>(),
+            "chunks": chunks,
+            "warnings": warnings,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(page: u32, text: &str) -> PageText {
+        PageText {
+            page,
+            text: text.to_string(),
+            ocr: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_pages() {
+        assert_eq!(parse_pages("1-3,5", 10).unwrap(), vec![1, 2, 3, 5]);
+        assert_eq!(parse_pages(" 2 , 2-3 ", 3).unwrap(), vec![2, 3]);
+        assert!(parse_pages("0", 3).is_err());
+        assert!(parse_pages("3-1", 3).is_err());
+        assert!(parse_pages("4", 3).is_err());
+        assert!(parse_pages(",", 3).is_err());
+    }
+
+    #[test]
+    fn test_chunk_pages_keeps_page_spans() {
+        let pages = vec![page(1, "alpha"), page(2, "beta"), page(3, &"x".repeat(30))];
+        let chunks = chunk_pages(&pages, 20);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0]["text"], "alpha\n\nbeta");
+        assert_eq!(chunks[0]["pages"], json!([1, 2]));
+        assert_eq!(chunks[1]["pages"], json!([3, 3]));
+        assert_eq!(chunks[1]["text"].as_str().unwrap().len(), 20);
+        assert_eq!(chunks[2]["index"], 2);
+    }
+
+    #[test]
+    fn test_split_text_prefers_paragraphs() {
+        let text = "first paragraph\n\nsecond one here";
+        assert_eq!(split_text(text, 20), vec!["first paragraph", "second one here"]);
+        assert_eq!(split_text("aaa bbb ccc", 8), vec!["aaa bbb", "ccc"]);
+        assert!(split_text("   ", 8).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_validate_args() {
+        let tool = PdfTool::new(PathBuf::from("/tmp"));
+        assert!(tool.validate_args(&json!({"path": "report.pdf"})).await.is_ok());
+        assert!(tool.validate_args(&json!({"path": "../secret.pdf"})).await.is_err());
+        assert!(tool
+            .validate_args(&json!({"path": "a.pdf", "operation": "render"}))
+            .await
+            .is_err());
+        assert!(tool
+            .validate_args(&json!({"path": "a.pdf", "chunk_chars": 10}))
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,247 @@
+//! Shared, mutable tool set of a running agent
+//!
+//! An `AgentLoop` owns an `Arc<ToolRegistry>`. Tools are grouped by where they
+//! came from (built-ins, skills, MCP servers) so a group can be reloaded as a
+//! whole when a skill is assigned or an MCP server starts mid-session. Every
+//! change bumps the version and is broadcast to subscribers; the loop re-renders
+//! its tool prompt before the next step.
+
+use crate::tools::Tool;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, RwLock, Weak};
+use tokio::sync::broadcast;
+
+pub const GROUP_BUILTIN: &str = "builtin";
+pub const GROUP_SKILLS: &str = "skills";
+pub const GROUP_MCP: &str = "mcp";
+
+/// Names of the tools added and removed by one registry change
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ToolsChanged {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl ToolsChanged {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+struct Entry {
+    group: String,
+    tool: Arc<dyn Tool>,
+}
+
+pub struct ToolRegistry {
+    entries: RwLock<Vec<Entry>>,
+    version: AtomicU64,
+    changes: broadcast::Sender<ToolsChanged>,
+}
+
+impl Default for ToolRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        let (changes, _) = broadcast::channel(32);
+        Self {
+            entries: RwLock::new(vec![]),
+            version: AtomicU64::new(0),
+            changes,
+        }
+    }
+
+    /// Registry holding `tools` in `group`, in order
+    pub fn from_tools(group: &str, tools: Vec<Box<dyn Tool>>) -> Self {
+        let registry = Self::new();
+        registry.entries.write().unwrap().extend(tools.into_iter().map(|tool| Entry {
+            group: group.to_string(),
+            tool: Arc::from(tool),
+        }));
+        registry
+    }
+
+    /// Add `tool` to `group`, replacing any tool with the same name
+    pub fn register(&self, group: &str, tool: Box<dyn Tool>) {
+        let tool: Arc<dyn Tool> = Arc::from(tool);
+        let name = tool.name().to_string();
+        let replaced = {
+            let mut entries = self.entries.write().unwrap();
+            let before = entries.len();
+            entries.retain(|e| e.tool.name() != name);
+            let replaced = entries.len() != before;
+            entries.push(Entry {
+                group: group.to_string(),
+                tool,
+            });
+            replaced
+        };
+        // A replaced tool keeps its name, so only its schema may have changed
+        self.notify(ToolsChanged {
+            added: if replaced { vec![] } else { vec![name] },
+            removed: vec![],
+        });
+    }
+
+    /// Remove the tool called `name`; false when there was none
+    pub fn unregister(&self, name: &str) -> bool {
+        let removed = {
+            let mut entries = self.entries.write().unwrap();
+            let before = entries.len();
+            entries.retain(|e| e.tool.name() != name);
+            entries.len() != before
+        };
+        if removed {
+            self.notify(ToolsChanged {
+                added: vec![],
+                removed: vec![name.to_string()],
+            });
+        }
+        removed
+    }
+
+    /// Swap every tool in `group` for `tools` and report the difference
+    pub fn replace_group(&self, group: &str, tools: Vec<Arc<dyn Tool>>) -> ToolsChanged {
+        let change = {
+            let mut entries = self.entries.write().unwrap();
+            let old: Vec<String> = entries
+                .iter()
+                .filter(|e| e.group == group)
+                .map(|e| e.tool.name().to_string())
+                .collect();
+            let new: Vec<String> = tools.iter().map(|t| t.name().to_string()).collect();
+            entries.retain(|e| e.group != group);
+            entries.extend(tools.into_iter().map(|tool| Entry {
+                group: group.to_string(),
+                tool,
+            }));
+            ToolsChanged {
+                added: new.iter().filter(|n| !old.contains(n)).cloned().collect(),
+                removed: old.into_iter().filter(|n| !new.contains(n)).collect(),
+            }
+        };
+        self.notify(change.clone());
+        change
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.entries
+            .read()
+            .unwrap()
+            .iter()
+            .find(|e| e.tool.name() == name)
+            .map(|e| e.tool.clone())
+    }
+
+    pub fn list(&self) -> Vec<Arc<dyn Tool>> {
+        self.entries.read().unwrap().iter().map(|e| e.tool.clone()).collect()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.entries
+            .read()
+            .unwrap()
+            .iter()
+            .map(|e| e.tool.name().to_string())
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bumped on every change, including in-place replacements
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ToolsChanged> {
+        self.changes.subscribe()
+    }
+
+    fn notify(&self, change: ToolsChanged) {
+        self.version.fetch_add(1, Ordering::SeqCst);
+        let _ = self.changes.send(change);
+    }
+}
+
+/// Registries of running agents, by agent id
+static LIVE: LazyLock<Mutex<HashMap<String, Vec<Weak<ToolRegistry>>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Make `registry` reachable through `live_registries` while it is alive
+pub fn track(agent_id: &str, registry: &Arc<ToolRegistry>) {
+    let mut live = LIVE.lock().unwrap();
+    let entries = live.entry(agent_id.to_string()).or_default();
+    entries.retain(|r| r.strong_count() > 0);
+    entries.push(Arc::downgrade(registry));
+}
+
+/// Registries of the running loops for `agent_id`
+pub fn live_registries(agent_id: &str) -> Vec<Arc<ToolRegistry>> {
+    let mut live = LIVE.lock().unwrap();
+    let Some(entries) = live.get_mut(agent_id) else {
+        return vec![];
+    };
+    entries.retain(|r| r.strong_count() > 0);
+    let registries: Vec<_> = entries.iter().filter_map(Weak::upgrade).collect();
+    if registries.is_empty() {
+        live.remove(agent_id);
+    }
+    registries
+}
+
+/// Ids of agents with at least one running loop
+pub fn live_agent_ids() -> Vec<String> {
+    let ids: Vec<String> = LIVE.lock().unwrap().keys().cloned().collect();
+    ids.into_iter().filter(|id| !live_registries(id).is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::search::SearchTool;
+    use crate::tools::transcribe::TranscribeTool;
+
+    #[test]
+    fn test_register_and_replace_group() {
+        let registry = ToolRegistry::from_tools(GROUP_BUILTIN, vec![Box::new(SearchTool)]);
+        let mut changes = registry.subscribe();
+        assert_eq!(registry.names(), vec!["search_files"]);
+
+        registry.register(GROUP_SKILLS, Box::new(TranscribeTool::new()));
+        assert_eq!(changes.try_recv().unwrap().added, vec!["transcribe"]);
+        assert!(registry.get("transcribe").is_some());
+
+        let change = registry.replace_group(GROUP_SKILLS, vec![]);
+        assert_eq!(change.removed, vec!["transcribe"]);
+        assert_eq!(changes.try_recv().unwrap(), change);
+        assert_eq!(registry.names(), vec!["search_files"]);
+
+        assert!(registry.unregister("search_files"));
+        assert!(!registry.unregister("search_files"));
+        assert!(registry.is_empty());
+        assert_eq!(registry.version(), 3);
+    }
+
+    #[test]
+    fn test_live_registries() {
+        let registry = Arc::new(ToolRegistry::new());
+        track("registry-test-agent", &registry);
+        assert_eq!(live_registries("registry-test-agent").len(), 1);
+        assert!(live_agent_ids().contains(&"registry-test-agent".to_string()));
+
+        drop(registry);
+        assert!(live_registries("registry-test-agent").is_empty());
+    }
+}
@@ -3,6 +3,7 @@ use async_trait::async_trait;
 use serde_json::{json, Value};
 use std::path::PathBuf;
 use std::process::Command;
+use std::time::Duration;
 
 
 pub struct SearchTool;
@@ -60,12 +61,24 @@ impl Tool for SearchTool {
         false
     }
 
-    async fn execute(&self, args: Value, _ctx: &ToolContext) -> Result<Value, String> {
+    fn is_read_only(&self, _args: &Value) -> bool {
+        true
+    }
+
+    fn cache_ttl(&self) -> Option<Duration> {
+        Some(Duration::from_secs(300))
+    }
+
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<Value, String> {
         let query = args["query"].as_str().ok_or("Missing query")?;
         let path_str = args["path"].as_str().unwrap_or(".");
 
-        let root = std::env::current_dir().unwrap_or(PathBuf::from("."));
-        let target_path = root.join(path_str);
+        let root = ctx
+            .scope
+            .workspace_path()
+            .cloned()
+            .unwrap_or_else(|| std::env::current_dir().unwrap_or(PathBuf::from(".")));
+        let target_path = ctx.scope.resolve(&root, path_str)?;
 
         // Permission check
         let perm_req = crate::permissions::PermissionRequest {
@@ -77,15 +90,18 @@ impl Tool for SearchTool {
                 map.insert("operation".to_string(), "search".to_string());
                 map.insert("path".to_string(), path_str.to_string());
                 map.insert("resource".to_string(), path_str.to_string());
+                map.insert("session_id".to_string(), ctx.session_id.clone());
+                map.insert("tool".to_string(), self.name().to_string());
                 map
             },
+            payload: Some(crate::permissions::PermissionPayload::FileRead { path: path_str.to_string() }),
         };
 
         // Note: We need to handle potential absence of window in ToolContext slightly better or rely on default deny?
         // Method signature of request_permission handles Option<Window>.
-        if !_ctx
+        if !ctx
             .permissions
-            .request_permission(_ctx.observer.as_ref(), perm_req)
+            .request_permission(ctx.observer.as_ref(), perm_req)
             .await?
         {
             return Err("Permission denied".to_string());
@@ -0,0 +1,750 @@
+//! Workbook model behind the office tool's spreadsheet operations
+//!
+//! `.xlsx` files are read with calamine and written with rust_xlsxwriter, so
+//! writing rebuilds the file: values and formulas survive, cell formatting and
+//! charts do not. `.csv` files are a single sheet named after the file.
+//!
+//! Formulas are evaluated when a workbook is read or written. The evaluator
+//! knows cell references, ranges, `+ - * /`, parentheses and SUM, AVERAGE,
+//! MIN, MAX, COUNT, ABS and ROUND; cells using anything else keep the value
+//! last saved by Excel.
+
+use calamine::{open_workbook, Data, Reader, Xlsx};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Rows returned by one read, to keep results within the model's context
+pub const MAX_ROWS: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cell {
+    /// Literal value, or the last computed result of `formula`
+    pub value: Value,
+    /// Formula without the leading '='
+    pub formula: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Sheet {
+    pub name: String,
+    /// Cells by zero-based (row, column)
+    pub cells: BTreeMap<(u32, u32), Cell>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Workbook {
+    pub sheets: Vec<Sheet>,
+}
+
+enum Format {
+    Xlsx,
+    Csv,
+}
+
+fn format(path: &Path) -> Result<Format, String> {
+    match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+        Some("xlsx") => Ok(Format::Xlsx),
+        Some("csv") => Ok(Format::Csv),
+        _ => Err(format!("{} is not a spreadsheet (expected .xlsx or .csv)", path.display())),
+    }
+}
+
+impl Workbook {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let mut workbook = match format(path)? {
+            Format::Xlsx => Self::open_xlsx(path)?,
+            Format::Csv => Self::open_csv(path)?,
+        };
+        workbook.recalculate();
+        Ok(workbook)
+    }
+
+    /// `open`, or an empty workbook with one sheet when the file does not exist yet
+    pub fn open_or_new(path: &Path) -> Result<Self, String> {
+        if path.exists() {
+            return Self::open(path);
+        }
+        let name = match format(path)? {
+            Format::Xlsx => "Sheet1".to_string(),
+            Format::Csv => csv_sheet_name(path),
+        };
+        Ok(Self {
+            sheets: vec![Sheet {
+                name,
+                cells: BTreeMap::new(),
+            }],
+        })
+    }
+
+    fn open_xlsx(path: &Path) -> Result<Self, String> {
+        let mut xlsx: Xlsx<_> = open_workbook(path).map_err(|e| format!("Cannot open Excel file: {}", e))?;
+        let mut sheets = vec![];
+        for name in xlsx.sheet_names().to_owned() {
+            let mut sheet = Sheet {
+                name: name.clone(),
+                cells: BTreeMap::new(),
+            };
+            if let Ok(range) = xlsx.worksheet_range(&name) {
+                let (row0, col0) = range.start().unwrap_or((0, 0));
+                for (row, col, data) in range.cells() {
+                    let value = data_value(data);
+                    if !value.is_null() {
+                        sheet.cells.insert(
+                            (row0 + row as u32, col0 + col as u32),
+                            Cell { value, formula: None },
+                        );
+                    }
+                }
+            }
+            if let Ok(formulas) = xlsx.worksheet_formula(&name) {
+                let (row0, col0) = formulas.start().unwrap_or((0, 0));
+                for (row, col, formula) in formulas.cells() {
+                    if !formula.is_empty() {
+                        let cell = sheet
+                            .cells
+                            .entry((row0 + row as u32, col0 + col as u32))
+                            .or_insert(Cell {
+                                value: Value::Null,
+                                formula: None,
+                            });
+                        cell.formula = Some(formula.trim_start_matches('=').to_string());
+                    }
+                }
+            }
+            sheets.push(sheet);
+        }
+        Ok(Self { sheets })
+    }
+
+    fn open_csv(path: &Path) -> Result<Self, String> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(path)
+            .map_err(|e| format!("Failed to open CSV: {}", e))?;
+        let mut sheet = Sheet {
+            name: csv_sheet_name(path),
+            cells: BTreeMap::new(),
+        };
+        for (row, record) in reader.records().enumerate() {
+            let record = record.map_err(|e| e.to_string())?;
+            for (col, field) in record.iter().enumerate() {
+                if !field.is_empty() {
+                    sheet.set((row as u32, col as u32), &text_value(field));
+                }
+            }
+        }
+        Ok(Self { sheets: vec![sheet] })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        match format(path)? {
+            Format::Xlsx => self.save_xlsx(path),
+            Format::Csv => self.save_csv(path),
+        }
+    }
+
+    fn save_xlsx(&self, path: &Path) -> Result<(), String> {
+        let mut workbook = rust_xlsxwriter::Workbook::new();
+        for sheet in &self.sheets {
+            let worksheet = workbook.add_worksheet();
+            worksheet.set_name(&sheet.name).map_err(|e| e.to_string())?;
+            for (&(row, col), cell) in &sheet.cells {
+                let col = u16::try_from(col).map_err(|_| format!("Column {} is out of range", col + 1))?;
+                let written = if let Some(formula) = &cell.formula {
+                    let mut formula = rust_xlsxwriter::Formula::new(format!("={}", formula));
+                    if !cell.value.is_null() {
+                        formula = formula.set_result(display_value(&cell.value));
+                    }
+                    worksheet.write_formula(row, col, formula)
+                } else {
+                    match &cell.value {
+                        Value::Null => continue,
+                        Value::Number(n) => worksheet.write_number(row, col, n.as_f64().unwrap_or_default()),
+                        Value::Bool(b) => worksheet.write_boolean(row, col, *b),
+                        other => worksheet.write_string(row, col, display_value(other)),
+                    }
+                };
+                written.map_err(|e| e.to_string())?;
+            }
+        }
+        workbook
+            .save(path)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    fn save_csv(&self, path: &Path) -> Result<(), String> {
+        let [sheet] = self.sheets.as_slice() else {
+            return Err("A CSV file holds exactly one sheet".to_string());
+        };
+        let mut writer = csv::WriterBuilder::new()
+            .flexible(true)
+            .from_path(path)
+            .map_err(|e| format!("Failed to write CSV: {}", e))?;
+        if let Some((_, (last_row, last_col))) = sheet.bounds() {
+            for row in 0..=last_row {
+                let record: Vec<String> = (0..=last_col)
+                    .map(|col| match sheet.cells.get(&(row, col)) {
+                        // Formulas are kept so the file still computes when reopened
+                        Some(Cell { formula: Some(f), .. }) => format!("={}", f),
+                        Some(cell) => display_value(&cell.value),
+                        None => String::new(),
+                    })
+                    .collect();
+                writer.write_record(&record).map_err(|e| e.to_string())?;
+            }
+        }
+        writer.flush().map_err(|e| e.to_string())
+    }
+
+    pub fn sheet_names(&self) -> Vec<String> {
+        self.sheets.iter().map(|s| s.name.clone()).collect()
+    }
+
+    /// The sheet called `name`, or the first sheet
+    pub fn sheet(&self, name: Option<&str>) -> Result<&Sheet, String> {
+        match name {
+            Some(name) => self
+                .sheets
+                .iter()
+                .find(|s| s.name == name)
+                .ok_or_else(|| format!("No sheet named '{}'", name)),
+            None => self.sheets.first().ok_or_else(|| "Workbook has no sheets".to_string()),
+        }
+    }
+
+    pub fn sheet_mut(&mut self, name: Option<&str>) -> Result<&mut Sheet, String> {
+        match name {
+            Some(name) => self
+                .sheets
+                .iter_mut()
+                .find(|s| s.name == name)
+                .ok_or_else(|| format!("No sheet named '{}'", name)),
+            None => self.sheets.first_mut().ok_or_else(|| "Workbook has no sheets".to_string()),
+        }
+    }
+
+    pub fn add_sheet(&mut self, name: &str) -> Result<(), String> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > 31 || name.contains(['[', ']', ':', '*', '?', '/', '\\']) {
+            return Err(format!("Invalid sheet name '{}'", name));
+        }
+        if self.sheets.iter().any(|s| s.name.eq_ignore_ascii_case(name)) {
+            return Err(format!("Sheet '{}' already exists", name));
+        }
+        self.sheets.push(Sheet {
+            name: name.to_string(),
+            cells: BTreeMap::new(),
+        });
+        Ok(())
+    }
+
+    pub fn recalculate(&mut self) {
+        for sheet in &mut self.sheets {
+            sheet.recalculate();
+        }
+    }
+}
+
+impl Sheet {
+    /// Set the cell at `pos`; strings starting with '=' are formulas, null clears it
+    pub fn set(&mut self, pos: (u32, u32), value: &Value) {
+        match value {
+            Value::Null => {
+                self.cells.remove(&pos);
+            }
+            Value::String(s) if s.starts_with('=') && s.len() > 1 => {
+                self.cells.insert(
+                    pos,
+                    Cell {
+                        value: Value::Null,
+                        formula: Some(s[1..].to_string()),
+                    },
+                );
+            }
+            other => {
+                self.cells.insert(
+                    pos,
+                    Cell {
+                        value: other.clone(),
+                        formula: None,
+                    },
+                );
+            }
+        }
+    }
+
+    pub fn get(&self, pos: (u32, u32)) -> Value {
+        self.cells.get(&pos).map(|c| c.value.clone()).unwrap_or(Value::Null)
+    }
+
+    /// Top-left and bottom-right of the used area
+    pub fn bounds(&self) -> Option<CellRange> {
+        if self.cells.is_empty() {
+            return None;
+        }
+        let last_row = self.cells.keys().map(|(r, _)| *r).max().unwrap_or(0);
+        let last_col = self.cells.keys().map(|(_, c)| *c).max().unwrap_or(0);
+        Some(((0, 0), (last_row, last_col)))
+    }
+
+    /// Values in `range` (A1 notation; the used area when None), row by row
+    pub fn read(&self, range: Option<&str>) -> Result<Value, String> {
+        let ((first_row, first_col), (last_row, last_col)) = match range {
+            Some(range) => parse_range(range)?,
+            None => match self.bounds() {
+                Some(bounds) => bounds,
+                None => {
+                    return Ok(json!({ "sheet": self.name, "range": Value::Null, "rows": [], "truncated": false }));
+                }
+            },
+        };
+        let rows_in_range = (last_row - first_row + 1) as usize;
+        let rows: Vec<Vec<Value>> = (first_row..=last_row)
+            .take(MAX_ROWS)
+            .map(|row| (first_col..=last_col).map(|col| self.get((row, col))).collect())
+            .collect();
+        Ok(json!({
+            "sheet": self.name,
+            "range": format!("{}:{}", cell_name(first_row, first_col), cell_name(last_row, last_col)),
+            "rows": rows,
+            "truncated": rows_in_range > MAX_ROWS,
+        }))
+    }
+
+    /// Evaluate `formula` (with or without '=') against this sheet
+    pub fn evaluate(&self, formula: &str) -> Result<Value, String> {
+        Evaluator::new(self)
+            .eval_formula(formula.trim().trim_start_matches('='))
+            .map(number_value)
+    }
+
+    fn recalculate(&mut self) {
+        let formulas: Vec<((u32, u32), String)> = self
+            .cells
+            .iter()
+            .filter_map(|(pos, cell)| cell.formula.clone().map(|f| (*pos, f)))
+            .collect();
+        if formulas.is_empty() {
+            return;
+        }
+        let results: Vec<((u32, u32), Result<f64, String>)> = {
+            let mut evaluator = Evaluator::new(self);
+            formulas
+                .iter()
+                .map(|(pos, formula)| (*pos, evaluator.formula_result(*pos, formula)))
+                .collect()
+        };
+        for (pos, result) in results {
+            let Some(cell) = self.cells.get_mut(&pos) else { continue };
+            match result {
+                Ok(n) => cell.value = number_value(n),
+                // Formulas we cannot evaluate keep the value Excel saved
+                Err(code) if code == NAME_ERROR && !cell.value.is_null() => {}
+                Err(code) => cell.value = Value::String(code),
+            }
+        }
+    }
+}
+
+/// Zero-based (row, column) of an A1 reference like "B3" or "$B$3"
+pub fn parse_cell(reference: &str) -> Result<(u32, u32), String> {
+    let cleaned = reference.trim().replace('$', "").to_uppercase();
+    let split = cleaned.find(|c: char| c.is_ascii_digit()).unwrap_or(cleaned.len());
+    let (letters, digits) = cleaned.split_at(split);
+    let invalid = || format!("Invalid cell reference '{}'", reference);
+    if letters.is_empty() || letters.len() > 3 || !letters.chars().all(|c| c.is_ascii_uppercase()) {
+        return Err(invalid());
+    }
+    let row: u32 = digits.parse().map_err(|_| invalid())?;
+    if row == 0 {
+        return Err(invalid());
+    }
+    let col = letters.chars().fold(0u32, |acc, c| acc * 26 + (c as u32 - 'A' as u32 + 1));
+    Ok((row - 1, col - 1))
+}
+
+/// Top-left and bottom-right corners of a range, as zero-based (row, col)
+pub type CellRange = ((u32, u32), (u32, u32));
+
+/// Corners of an A1 range like "A1:C10" (or a single cell), top-left first
+pub fn parse_range(range: &str) -> Result<CellRange, String> {
+    let (start, end) = match range.split_once(':') {
+        Some((start, end)) => (parse_cell(start)?, parse_cell(end)?),
+        None => {
+            let cell = parse_cell(range)?;
+            (cell, cell)
+        }
+    };
+    Ok((
+        (start.0.min(end.0), start.1.min(end.1)),
+        (start.0.max(end.0), start.1.max(end.1)),
+    ))
+}
+
+/// A1 name of zero-based (row, column)
+pub fn cell_name(row: u32, col: u32) -> String {
+    let mut letters = String::new();
+    let mut n = col + 1;
+    while n > 0 {
+        let rem = (n - 1) % 26;
+        letters.insert(0, (b'A' + rem as u8) as char);
+        n = (n - 1) / 26;
+    }
+    format!("{}{}", letters, row + 1)
+}
+
+fn csv_sheet_name(path: &Path) -> String {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("Sheet1")
+        .to_string()
+}
+
+fn data_value(data: &Data) -> Value {
+    match data {
+        Data::Empty => Value::Null,
+        Data::String(s) => json!(s),
+        Data::Float(f) => number_value(*f),
+        Data::Int(i) => json!(i),
+        Data::Bool(b) => json!(b),
+        Data::DateTime(d) => json!(d.to_string()),
+        Data::DateTimeIso(d) => json!(d),
+        Data::DurationIso(d) => json!(d),
+        Data::Error(e) => json!(e.to_string()),
+    }
+}
+
+/// CSV fields: numbers become numbers, everything else stays text
+fn text_value(field: &str) -> Value {
+    match field.trim().parse::<f64>() {
+        Ok(n) if n.is_finite() => number_value(n),
+        _ => json!(field),
+    }
+}
+
+/// Whole numbers as integers, so 3 reads back as 3 rather than 3.0
+fn number_value(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        json!(n as i64)
+    } else {
+        json!(n)
+    }
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+const NAME_ERROR: &str = "#NAME?";
+const VALUE_ERROR: &str = "#VALUE!";
+const DIV_ERROR: &str = "#DIV/0!";
+const CYCLE_ERROR: &str = "#CYCLE!";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ref(String),
+    Name(String),
+    Op(char),
+}
+
+fn tokenize(formula: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = formula.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            tokens.push(Token::Number(text.parse().map_err(|_| VALUE_ERROR.to_string())?));
+        } else if c.is_ascii_alphabetic() || c == '$' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '$' || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            if parse_cell(&word).is_ok() && chars.get(i) != Some(&'(') {
+                tokens.push(Token::Ref(word));
+            } else {
+                tokens.push(Token::Name(word.to_uppercase()));
+            }
+        } else if "+-*/(),:".contains(c) {
+            tokens.push(Token::Op(c));
+            i += 1;
+        } else {
+            // Strings, comparisons and other sheets' cells are not supported
+            return Err(NAME_ERROR.to_string());
+        }
+    }
+    Ok(tokens)
+}
+
+struct Evaluator<'a> {
+    sheet: &'a Sheet,
+    results: HashMap<(u32, u32), Result<f64, String>>,
+    visiting: Vec<(u32, u32)>,
+}
+
+impl<'a> Evaluator<'a> {
+    fn new(sheet: &'a Sheet) -> Self {
+        Self {
+            sheet,
+            results: HashMap::new(),
+            visiting: vec![],
+        }
+    }
+
+    fn formula_result(&mut self, pos: (u32, u32), formula: &str) -> Result<f64, String> {
+        if let Some(result) = self.results.get(&pos) {
+            return result.clone();
+        }
+        if self.visiting.contains(&pos) {
+            return Err(CYCLE_ERROR.to_string());
+        }
+        self.visiting.push(pos);
+        let result = self.eval_formula(formula);
+        self.visiting.pop();
+        self.results.insert(pos, result.clone());
+        result
+    }
+
+    fn eval_formula(&mut self, formula: &str) -> Result<f64, String> {
+        let tokens = tokenize(formula)?;
+        let mut pos = 0;
+        let value = self.expr(&tokens, &mut pos)?;
+        if pos != tokens.len() {
+            return Err(VALUE_ERROR.to_string());
+        }
+        Ok(value)
+    }
+
+    /// Numeric value of a cell; None when it is empty or holds text
+    fn cell_number(&mut self, pos: (u32, u32)) -> Result<Option<f64>, String> {
+        let Some(cell) = self.sheet.cells.get(&pos) else {
+            return Ok(None);
+        };
+        if let Some(formula) = &cell.formula {
+            return self.formula_result(pos, formula).map(Some);
+        }
+        Ok(match &cell.value {
+            Value::Number(n) => n.as_f64(),
+            Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+            Value::String(s) if s.starts_with('#') => return Err(s.clone()),
+            _ => None,
+        })
+    }
+
+    fn expr(&mut self, tokens: &[Token], pos: &mut usize) -> Result<f64, String> {
+        let mut value = self.term(tokens, pos)?;
+        while let Some(Token::Op(op @ ('+' | '-'))) = tokens.get(*pos) {
+            *pos += 1;
+            let rhs = self.term(tokens, pos)?;
+            value = if *op == '+' { value + rhs } else { value - rhs };
+        }
+        Ok(value)
+    }
+
+    fn term(&mut self, tokens: &[Token], pos: &mut usize) -> Result<f64, String> {
+        let mut value = self.factor(tokens, pos)?;
+        while let Some(Token::Op(op @ ('*' | '/'))) = tokens.get(*pos) {
+            *pos += 1;
+            let rhs = self.factor(tokens, pos)?;
+            value = if *op == '*' {
+                value * rhs
+            } else if rhs == 0.0 {
+                return Err(DIV_ERROR.to_string());
+            } else {
+                value / rhs
+            };
+        }
+        Ok(value)
+    }
+
+    fn factor(&mut self, tokens: &[Token], pos: &mut usize) -> Result<f64, String> {
+        let token = tokens.get(*pos).cloned().ok_or_else(|| VALUE_ERROR.to_string())?;
+        *pos += 1;
+        match token {
+            Token::Number(n) => Ok(n),
+            Token::Op('-') => Ok(-self.factor(tokens, pos)?),
+            Token::Op('+') => self.factor(tokens, pos),
+            Token::Op('(') => {
+                let value = self.expr(tokens, pos)?;
+                self.expect(tokens, pos, ')')?;
+                Ok(value)
+            }
+            Token::Ref(reference) => {
+                if tokens.get(*pos) == Some(&Token::Op(':')) {
+                    // Ranges only make sense as function arguments
+                    return Err(VALUE_ERROR.to_string());
+                }
+                Ok(self.cell_number(parse_cell(&reference)?)?.unwrap_or(0.0))
+            }
+            Token::Name(name) => {
+                self.expect(tokens, pos, '(')?;
+                let values = self.arguments(tokens, pos)?;
+                call_function(&name, &values)
+            }
+            Token::Op(_) => Err(VALUE_ERROR.to_string()),
+        }
+    }
+
+    /// Numbers in a function's argument list, ranges flattened; consumes ')'
+    fn arguments(&mut self, tokens: &[Token], pos: &mut usize) -> Result<Vec<f64>, String> {
+        let mut values = vec![];
+        if tokens.get(*pos) == Some(&Token::Op(')')) {
+            *pos += 1;
+            return Ok(values);
+        }
+        loop {
+            match (tokens.get(*pos), tokens.get(*pos + 1), tokens.get(*pos + 2)) {
+                (Some(Token::Ref(start)), Some(Token::Op(':')), Some(Token::Ref(end))) => {
+                    let ((first_row, first_col), (last_row, last_col)) = parse_range(&format!("{}:{}", start, end))?;
+                    *pos += 3;
+                    for row in first_row..=last_row {
+                        for col in first_col..=last_col {
+                            // Functions skip empty and text cells, like Excel
+                            values.extend(self.cell_number((row, col))?);
+                        }
+                    }
+                }
+                _ => values.push(self.expr(tokens, pos)?),
+            }
+            match tokens.get(*pos) {
+                Some(Token::Op(',')) => *pos += 1,
+                Some(Token::Op(')')) => {
+                    *pos += 1;
+                    return Ok(values);
+                }
+                _ => return Err(VALUE_ERROR.to_string()),
+            }
+        }
+    }
+
+    fn expect(&self, tokens: &[Token], pos: &mut usize, op: char) -> Result<(), String> {
+        if tokens.get(*pos) == Some(&Token::Op(op)) {
+            *pos += 1;
+            Ok(())
+        } else {
+            Err(VALUE_ERROR.to_string())
+        }
+    }
+}
+
+fn call_function(name: &str, values: &[f64]) -> Result<f64, String> {
+    match name {
+        "SUM" => Ok(values.iter().sum()),
+        "COUNT" => Ok(values.len() as f64),
+        "AVERAGE" if values.is_empty() => Err(DIV_ERROR.to_string()),
+        "AVERAGE" => Ok(values.iter().sum::<f64>() / values.len() as f64),
+        "MIN" => Ok(values.iter().copied().reduce(f64::min).unwrap_or(0.0)),
+        "MAX" => Ok(values.iter().copied().reduce(f64::max).unwrap_or(0.0)),
+        "ABS" => match values {
+            [x] => Ok(x.abs()),
+            _ => Err(VALUE_ERROR.to_string()),
+        },
+        "ROUND" => match values {
+            [x] => Ok(x.round()),
+            [x, digits] => {
+                let factor = 10f64.powi(*digits as i32);
+                Ok((x * factor).round() / factor)
+            }
+            _ => Err(VALUE_ERROR.to_string()),
+        },
+        _ => Err(NAME_ERROR.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cell_references() {
+        assert_eq!(parse_cell("A1"), Ok((0, 0)));
+        assert_eq!(parse_cell("$AB$12"), Ok((11, 27)));
+        assert!(parse_cell("A0").is_err());
+        assert!(parse_cell("12").is_err());
+        assert_eq!(cell_name(11, 27), "AB12");
+        assert_eq!(parse_range("C3:A1"), Ok(((0, 0), (2, 2))));
+    }
+
+    #[test]
+    fn test_formulas() {
+        let mut sheet = Sheet::default();
+        sheet.set((0, 0), &json!(2));
+        sheet.set((1, 0), &json!(3));
+        sheet.set((2, 0), &json!("note"));
+        sheet.set((0, 1), &json!("=SUM(A1:A3) * 2"));
+        sheet.set((1, 1), &json!("=B1 / (A2 - 3)"));
+        sheet.set((2, 1), &json!("=B3"));
+        sheet.recalculate();
+
+        assert_eq!(sheet.get((0, 1)), json!(10));
+        assert_eq!(sheet.get((1, 1)), json!(DIV_ERROR));
+        assert_eq!(sheet.get((2, 1)), json!(CYCLE_ERROR));
+        assert_eq!(sheet.evaluate("=ROUND(AVERAGE(A1:A2), 0) + MAX(A1, 7)"), Ok(json!(10)));
+        assert_eq!(sheet.evaluate("VLOOKUP(A1)"), Err(NAME_ERROR.to_string()));
+    }
+
+    #[test]
+    fn test_xlsx_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("budget.xlsx");
+
+        let mut workbook = Workbook::open_or_new(&path).unwrap();
+        let sheet = workbook.sheet_mut(None).unwrap();
+        sheet.set((0, 0), &json!("Item"));
+        sheet.set((0, 1), &json!("Cost"));
+        sheet.set((1, 1), &json!(40));
+        sheet.set((2, 1), &json!(2.5));
+        sheet.set((3, 1), &json!("=SUM(B2:B3)"));
+        workbook.add_sheet("Notes").unwrap();
+        assert!(workbook.add_sheet("notes").is_err());
+        workbook.recalculate();
+        workbook.save(&path).unwrap();
+
+        let reopened = Workbook::open(&path).unwrap();
+        assert_eq!(reopened.sheet_names(), vec!["Sheet1", "Notes"]);
+        let sheet = reopened.sheet(Some("Sheet1")).unwrap();
+        assert_eq!(sheet.cells[&(3, 1)].formula.as_deref(), Some("SUM(B2:B3)"));
+        let read = sheet.read(Some("B1:B4")).unwrap();
+        assert_eq!(read["rows"], json!([["Cost"], [40], [2.5], [42.5]]));
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scores.csv");
+        std::fs::write(&path, "name,score\nana,3\nbo,4\n").unwrap();
+
+        let mut workbook = Workbook::open(&path).unwrap();
+        workbook.sheet_mut(None).unwrap().set((3, 1), &json!("=SUM(B2:B3)"));
+        workbook.recalculate();
+        workbook.save(&path).unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().contains("=SUM(B2:B3)"));
+
+        let reopened = Workbook::open(&path).unwrap();
+        let sheet = reopened.sheet(None).unwrap();
+        assert_eq!(sheet.name, "scores");
+        assert_eq!(sheet.get((3, 1)), json!(7));
+
+        let mut two_sheets = reopened.clone();
+        two_sheets.add_sheet("More").unwrap();
+        assert!(two_sheets.save(&path).is_err());
+    }
+}
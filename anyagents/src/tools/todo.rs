@@ -0,0 +1,174 @@
+use super::{Tool, ToolContext};
+use crate::database::DbPool;
+use crate::events::AgentEvent;
+use crate::models::todo::{self, TodoItem};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+const STATUSES: [&str; 3] = ["pending", "in_progress", "completed"];
+const MAX_ITEMS: usize = 50;
+
+/// A checklist the agent keeps for itself during a long job. Every call
+/// replaces the whole list; the UI shows it live (`todo_updated` events) and
+/// it is saved with the job.
+pub struct TodoTool {
+    db_pool: Option<DbPool>,
+}
+
+impl TodoTool {
+    pub fn new(db_pool: Option<DbPool>) -> Self {
+        Self { db_pool }
+    }
+
+    fn parse(args: &Value) -> Result<Vec<TodoItem>, String> {
+        let entries = args["todos"].as_array().ok_or("Missing todos")?;
+        if entries.len() > MAX_ITEMS {
+            return Err(format!("At most {} todos", MAX_ITEMS));
+        }
+        let mut items = Vec::with_capacity(entries.len());
+        for (i, entry) in entries.iter().enumerate() {
+            let content = entry["content"].as_str().unwrap_or_default().trim();
+            if content.is_empty() {
+                return Err(format!("Todo {} has no content", i + 1));
+            }
+            let status = entry["status"].as_str().unwrap_or("pending");
+            if !STATUSES.contains(&status) {
+                return Err(format!("Unknown status '{}'; use {}", status, STATUSES.join(", ")));
+            }
+            let id = match &entry["id"] {
+                Value::String(id) if !id.is_empty() => id.clone(),
+                Value::Number(n) => n.to_string(),
+                _ => (i + 1).to_string(),
+            };
+            items.push(TodoItem { id, content: content.to_string(), status: status.to_string() });
+        }
+        if items.iter().filter(|t| t.status == "in_progress").count() > 1 {
+            return Err("Only one todo can be in_progress at a time".to_string());
+        }
+        Ok(items)
+    }
+}
+
+#[async_trait]
+impl Tool for TodoTool {
+    fn name(&self) -> &str {
+        "todo"
+    }
+
+    fn description(&self) -> &str {
+        "Keep a checklist of the steps of a longer task, shown to the user as you work. \
+         Send the whole list every time: mark a step in_progress before starting it and \
+         completed as soon as it is done. Skip it for tasks of one or two steps."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "todos": {
+                    "type": "array",
+                    "description": "The full checklist, in order",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "id": { "type": "string" },
+                            "content": { "type": "string", "description": "What the step does" },
+                            "status": { "type": "string", "enum": STATUSES }
+                        },
+                        "required": ["content", "status"]
+                    }
+                }
+            },
+            "required": ["todos"]
+        })
+    }
+
+    async fn validate_args(&self, args: &Value) -> Result<(), String> {
+        Self::parse(args).map(|_| ())
+    }
+
+    fn is_read_only(&self, _args: &Value) -> bool {
+        true
+    }
+
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<Value, String> {
+        let items = Self::parse(&args)?;
+
+        if let (Some(pool), Some(job_id)) = (&self.db_pool, &ctx.job_id) {
+            if let Err(e) = todo::save_todos(pool, &ctx.session_id, job_id, &items) {
+                log::warn!("Failed to save todos of job {}: {}", job_id, e);
+            }
+        }
+        if let Some(observer) = &ctx.observer {
+            let event = AgentEvent::TodoUpdated { job_id: ctx.job_id.clone(), items: items.clone() };
+            let _ = observer.emit(
+                &format!("session:{}", ctx.session_id),
+                serde_json::to_value(event).map_err(|e| e.to_string())?,
+            );
+        }
+
+        let done = items.iter().filter(|t| t.status == "completed").count();
+        Ok(json!({ "completed": done, "total": items.len() }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_test_pool;
+    use crate::events::AgentObserver;
+    use crate::permissions::PermissionManager;
+    use std::sync::{Arc, Mutex};
+
+    struct Recorder(Mutex<Vec<Value>>);
+
+    impl AgentObserver for Recorder {
+        fn emit(&self, _event: &str, payload: Value) -> Result<(), String> {
+            self.0.lock().unwrap().push(payload);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_todo_replaces_list_saves_and_emits() {
+        let pool = create_test_pool();
+        let recorder = Arc::new(Recorder(Mutex::new(vec![])));
+        let ctx = ToolContext {
+            permissions: Arc::new(PermissionManager::new()),
+            observer: Some(recorder.clone()),
+            session_id: "session-1".to_string(),
+            scope: Default::default(),
+            job_id: Some("job-1".to_string()),
+        };
+        let tool = TodoTool::new(Some(pool.clone()));
+
+        let args = json!({"todos": [
+            {"content": "Read the config", "status": "completed"},
+            {"id": "b", "content": "Fix the parser", "status": "in_progress"},
+            {"content": "Run the tests", "status": "pending"},
+        ]});
+        let result = tool.execute(args, &ctx).await.unwrap();
+        assert_eq!(result, json!({"completed": 1, "total": 3}));
+
+        let events = recorder.0.lock().unwrap();
+        assert_eq!(events[0]["type"], "todo_updated");
+        assert_eq!(events[0]["items"][1]["id"], "b");
+
+        let saved = todo::latest_session_todos(&pool, "session-1").unwrap().unwrap();
+        assert_eq!(saved.job_id, "job-1");
+        assert_eq!(saved.items()[2].content, "Run the tests");
+    }
+
+    #[tokio::test]
+    async fn test_todo_rejects_bad_lists() {
+        let tool = TodoTool::new(None);
+        let two_active = json!({"todos": [
+            {"content": "a", "status": "in_progress"},
+            {"content": "b", "status": "in_progress"},
+        ]});
+        assert!(tool.validate_args(&two_active).await.is_err());
+        assert!(tool.validate_args(&json!({"todos": [{"content": "a", "status": "done"}]})).await.is_err());
+        assert!(tool.validate_args(&json!({"todos": [{"content": " ", "status": "pending"}]})).await.is_err());
+        assert!(tool.validate_args(&json!({"todos": []})).await.is_ok());
+    }
+}
@@ -120,6 +120,10 @@ impl Tool for TranscribeTool {
         false
     }
 
+    fn is_read_only(&self, _args: &Value) -> bool {
+        true
+    }
+
     fn needs_summarization(&self, _args: &Value, _result: &Value) -> bool {
         false
     }
@@ -134,6 +138,8 @@ mod tests {
             permissions: std::sync::Arc::new(crate::permissions::PermissionManager::new()),
             observer: None,
             session_id: "test".to_string(),
+            scope: Default::default(),
+            job_id: None,
         }
     }
 
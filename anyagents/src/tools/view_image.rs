@@ -0,0 +1,121 @@
+use super::{Tool, ToolContext};
+use crate::llm::image::{ImageAttachment, IMAGES_KEY};
+use crate::permissions::scope::resolve_within;
+use crate::permissions::{PermissionPayload, PermissionRequest, PermissionType};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Shows a workspace image (screenshot, diagram, chart) to the model. The
+/// image rides along with the tool result, so only vision-capable models get it.
+pub struct ViewImageTool {
+    workspace_path: PathBuf,
+}
+
+impl ViewImageTool {
+    pub fn new(workspace_path: PathBuf) -> Self {
+        Self { workspace_path }
+    }
+
+    fn view(&self, path_str: &str) -> Result<Value, String> {
+        let path = resolve_within(&self.workspace_path, Path::new(path_str))?;
+        let image = ImageAttachment::from_path(&path)?;
+        Ok(json!({
+            "path": path_str,
+            "media_type": image.media_type,
+            "bytes": image.data.len() / 4 * 3,
+            IMAGES_KEY: [image],
+        }))
+    }
+}
+
+#[async_trait]
+impl Tool for ViewImageTool {
+    fn name(&self) -> &str {
+        "view_image"
+    }
+
+    fn description(&self) -> &str {
+        "Look at an image in the workspace (PNG, JPEG, GIF or WebP), such as a screenshot or diagram. \
+         The image is shown to you with the tool result."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Relative path to the image"
+                }
+            },
+            "required": ["path"]
+        })
+    }
+
+    async fn validate_args(&self, args: &Value) -> Result<(), String> {
+        let path = args["path"].as_str().ok_or("Missing path")?;
+        if path.contains("..") || path.starts_with('/') {
+            return Err("Access denied: Paths must be relative and cannot contain '..'".to_string());
+        }
+        if crate::llm::image::media_type_for_path(std::path::Path::new(path)).is_none() {
+            return Err("Unsupported image type; use PNG, JPEG, GIF or WebP".to_string());
+        }
+        Ok(())
+    }
+
+    fn is_read_only(&self, _args: &Value) -> bool {
+        true
+    }
+
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<Value, String> {
+        let path_str = args["path"].as_str().ok_or("Missing path")?;
+        ctx.scope.resolve(&self.workspace_path, path_str)?;
+
+        let perm_req = PermissionRequest {
+            id: uuid::Uuid::new_v4().to_string(),
+            permission_type: PermissionType::FilesystemRead,
+            message: format!("Agent wants to view image {}", path_str),
+            metadata: {
+                let mut map = HashMap::new();
+                map.insert("operation".to_string(), "view_image".to_string());
+                map.insert("path".to_string(), path_str.to_string());
+                map.insert("resource".to_string(), path_str.to_string());
+                map.insert("session_id".to_string(), ctx.session_id.clone());
+                map.insert("tool".to_string(), self.name().to_string());
+                map
+            },
+            payload: Some(PermissionPayload::FileRead { path: path_str.to_string() }),
+        };
+        if !ctx
+            .permissions
+            .request_permission(ctx.observer.as_ref(), perm_req)
+            .await?
+        {
+            return Err("Permission denied".to_string());
+        }
+
+        self.view(path_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_view_image_attaches_image() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("shot.png"), [0x89, b'P', b'N', b'G']).unwrap();
+        let tool = ViewImageTool::new(dir.path().to_path_buf());
+        assert!(tool.validate_args(&json!({"path": "notes.txt"})).await.is_err());
+        assert!(tool.validate_args(&json!({"path": "../shot.png"})).await.is_err());
+
+        let mut result = tool.view("shot.png").unwrap();
+        let images = crate::llm::image::take_images(&mut result);
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].media_type, "image/png");
+        assert_eq!(result["path"], "shot.png");
+    }
+}
@@ -0,0 +1,425 @@
+//! `anycowork` — run AnyCowork agents from the terminal
+//!
+//! Uses the desktop app's database (`~/.anycowork/anycowork.db`, or
+//! `DATABASE_URL`), so agents, settings and API keys are shared, and the
+//! session shows up in the app afterwards.
+
+mod render;
+
+use anyagents::agents::AgentLoop;
+use anyagents::database::DbPool;
+use anyagents::models::{Agent, NewMessage, NewSession};
+use anyagents::RuntimeBuilder;
+use clap::{Parser, Subcommand};
+use diesel::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[derive(Parser)]
+#[command(name = "anycowork", version, about = "Run AnyCowork agents from the terminal")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Send a prompt to an agent and stream its work
+    Run {
+        /// Agent name (or a unique part of it) or id
+        #[arg(short, long)]
+        agent: String,
+        /// Continue an existing session instead of starting a new one
+        #[arg(short, long)]
+        session: Option<String>,
+        /// Override the agent's model for this run
+        #[arg(long)]
+        model: Option<String>,
+        /// Approve every permission request without asking
+        #[arg(short, long)]
+        yes: bool,
+        /// Print every event as a JSON line instead of formatted output
+        #[arg(long)]
+        json: bool,
+        prompt: Vec<String>,
+    },
+    /// List available agents
+    Agents,
+    /// Serve an agent's tools over MCP on stdio (for Claude Desktop and other MCP clients)
+    Mcp {
+        /// Agent whose tools and workspace are served
+        #[arg(short, long)]
+        agent: String,
+        /// Also expose every agent as an `agent_<name>` tool
+        #[arg(long)]
+        expose_agents: bool,
+        /// Let the exposed agents make changes (they run read-only otherwise)
+        #[arg(long, requires = "expose_agents")]
+        agents_writable: bool,
+        /// Refuse tool calls with side effects
+        #[arg(long)]
+        read_only: bool,
+    },
+    /// Run a task suite against agent configurations and report how they did
+    Eval {
+        /// Suite file (YAML or JSON)
+        suite: std::path::PathBuf,
+        /// Agent configuration as provider/model; repeat to compare several.
+        /// Replaces the suite's own configurations.
+        #[arg(short, long = "agent")]
+        agents: Vec<String>,
+        /// Write the full report as JSON
+        #[arg(short, long)]
+        output: Option<std::path::PathBuf>,
+        /// Keep each run's trace in this directory
+        #[arg(long)]
+        traces: Option<std::path::PathBuf>,
+    },
+}
+
+fn open_pool() -> Result<DbPool, String> {
+    let database_url = match std::env::var("DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            let home = std::env::var("HOME").map_err(|_| "HOME is not set".to_string())?;
+            let dir = std::path::Path::new(&home).join(".anycowork");
+            std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+            format!("sqlite://{}", dir.join("anycowork.db").to_string_lossy())
+        }
+    };
+    let pool = anyagents::database::open_database(&database_url)?;
+    anyagents::database::ensure_default_characters(&pool);
+    Ok(pool)
+}
+
+/// Exact id or name first, then a unique case-insensitive name match
+fn find_agent(pool: &DbPool, query: &str) -> Result<Agent, String> {
+    use anyagents::schema::agents;
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let all: Vec<Agent> = agents::table.load(&mut conn).map_err(|e| e.to_string())?;
+    let query_lower = query.to_lowercase();
+
+    if let Some(agent) = all
+        .iter()
+        .find(|a| a.id == query || a.name.to_lowercase() == query_lower)
+    {
+        return Ok(agent.clone());
+    }
+    let matches: Vec<&Agent> = all
+        .iter()
+        .filter(|a| a.name.to_lowercase().contains(&query_lower))
+        .collect();
+    match matches.as_slice() {
+        [agent] => Ok((*agent).clone()),
+        [] => Err(format!("No agent matches '{}' (see `anycowork agents`)", query)),
+        _ => Err(format!(
+            "'{}' matches several agents: {}",
+            query,
+            matches.iter().map(|a| a.name.as_str()).collect::<Vec<_>>().join(", ")
+        )),
+    }
+}
+
+/// Create the session if needed and save the prompt as the user message
+fn prepare_session(
+    pool: &DbPool,
+    agent: &Agent,
+    session_id: Option<String>,
+    prompt: &str,
+) -> Result<String, String> {
+    use anyagents::schema::{messages, sessions};
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let session_id = match session_id {
+        Some(id) => {
+            sessions::table
+                .find(&id)
+                .select(sessions::id)
+                .first::<String>(&mut conn)
+                .map_err(|_| format!("Session '{}' not found", id))?;
+            id
+        }
+        None => {
+            let now = chrono::Utc::now().naive_utc();
+            let title: String = prompt.chars().take(60).collect();
+            let session = NewSession {
+                id: uuid::Uuid::new_v4().to_string(),
+                agent_id: agent.id.clone(),
+                title: Some(format!("CLI: {}", title)),
+                created_at: now,
+                updated_at: now,
+                archived: 0,
+                pinned: 0,
+            };
+            diesel::insert_into(sessions::table)
+                .values(&session)
+                .execute(&mut conn)
+                .map_err(|e| e.to_string())?;
+            session.id
+        }
+    };
+
+    diesel::insert_into(messages::table)
+        .values(&NewMessage {
+            id: uuid::Uuid::new_v4().to_string(),
+            role: "user".to_string(),
+            content: prompt.to_string(),
+            session_id: session_id.clone(),
+            metadata_json: None,
+            tokens: None,
+        })
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    Ok(session_id)
+}
+
+async fn run(
+    pool: DbPool,
+    agent_query: String,
+    session_id: Option<String>,
+    model: Option<String>,
+    yes: bool,
+    json: bool,
+    prompt: String,
+) -> Result<(), String> {
+    if prompt.trim().is_empty() {
+        return Err("Prompt is empty".to_string());
+    }
+    let mut agent = find_agent(&pool, &agent_query)?;
+    if let Some(model) = model {
+        agent.ai_model = model;
+    }
+    let session_id = prepare_session(&pool, &agent, session_id, &prompt)?;
+
+    let streamed = Arc::new(AtomicBool::new(false));
+    let streamed_sink = streamed.clone();
+    let mut builder = RuntimeBuilder::new()
+        .db_pool(pool.clone())
+        .event_sink(move |channel, event| {
+            if json {
+                println!("{}", serde_json::json!({ "channel": channel, "event": event }));
+            } else if let Some(output) = render::render_event(event) {
+                if matches!(output, render::Output::Stdout(_)) {
+                    streamed_sink.store(true, Ordering::SeqCst);
+                }
+                render::print(output);
+            }
+        });
+    builder = if yes {
+        builder.auto_approve()
+    } else if json {
+        // No terminal to ask on when the output is being parsed
+        builder.permission_handler(|_| false)
+    } else {
+        builder.permission_handler(|request| tokio::task::block_in_place(|| render::prompt_permission(request)))
+    };
+    let runtime = builder.build()?;
+
+    let mut worker = AgentLoop::new(&agent, pool).await;
+    worker.session_id = session_id.clone();
+    let reply = runtime.chat(&mut worker, &prompt).await;
+
+    if json {
+        let result = match &reply {
+            Ok(content) => serde_json::json!({ "type": "reply", "session_id": session_id, "content": content }),
+            Err(e) => serde_json::json!({ "type": "error", "session_id": session_id, "message": e }),
+        };
+        println!("{}", serde_json::json!({ "channel": "result", "event": result }));
+        return reply.map(|_| ());
+    }
+
+    let reply = reply?;
+    if streamed.load(Ordering::SeqCst) {
+        println!();
+    } else {
+        println!("{}", reply);
+    }
+    eprintln!("(session {})", session_id);
+    Ok(())
+}
+
+async fn serve_mcp(
+    pool: DbPool,
+    agent_query: String,
+    expose_agents: bool,
+    agents_writable: bool,
+    read_only: bool,
+) -> Result<(), String> {
+    use anyagents::mcp::server::McpServer;
+
+    let agent = find_agent(&pool, &agent_query)?;
+    let mut server = McpServer::for_agent(&agent, pool.clone()).await;
+    if expose_agents {
+        use anyagents::schema::agents;
+
+        let mut conn = pool.get().map_err(|e| e.to_string())?;
+        let all: Vec<Agent> = agents::table.load(&mut conn).map_err(|e| e.to_string())?;
+        server = server.with_agents(all, pool, agents_writable)?;
+    }
+    if read_only {
+        server = server.read_only();
+    }
+    server.serve_stdio().await
+}
+
+async fn eval(
+    suite: std::path::PathBuf,
+    agents: Vec<String>,
+    output: Option<std::path::PathBuf>,
+    traces: Option<std::path::PathBuf>,
+) -> Result<(), String> {
+    use anyagents::evals::{self, AgentConfig, Suite};
+
+    let suite = Suite::load(&suite)?;
+    let agents = agents.iter().map(|spec| AgentConfig::parse(spec)).collect::<Result<Vec<_>, _>>()?;
+    // A throwaway database, so eval runs stay out of the app's history.
+    // API keys come from the environment.
+    let runtime = RuntimeBuilder::new().auto_approve().build()?;
+
+    let report = evals::run_suite(&runtime, &suite, &agents, traces.as_deref(), |result| {
+        let outcome = match (&result.error, result.passed) {
+            (Some(e), _) => format!("error: {}", e),
+            (None, true) => "pass".to_string(),
+            (None, false) => "fail".to_string(),
+        };
+        eprintln!(
+            "{} / {}: {} ({} steps, {} tokens, {:.1}s)",
+            result.agent,
+            result.task,
+            outcome,
+            result.steps,
+            result.tokens.total(),
+            result.wall_ms as f64 / 1000.0
+        );
+    })
+    .await?;
+
+    print!("{}", report.to_table());
+    if let Some(path) = output {
+        let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+        std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+fn list_agents(pool: &DbPool) -> Result<(), String> {
+    use anyagents::schema::agents;
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let all: Vec<Agent> = agents::table
+        .order(agents::name.asc())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())?;
+    for agent in all {
+        println!(
+            "{:<28} {:<20} {}",
+            agent.name,
+            agent.ai_model,
+            agent.description.unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    dotenvy::dotenv().ok();
+    env_logger::init();
+    let telemetry = anyagents::telemetry::init("anycowork-cli").unwrap_or_else(|e| {
+        log::warn!("Telemetry disabled: {}", e);
+        None
+    });
+
+    let cli = Cli::parse();
+    let result = match (open_pool(), cli.command) {
+        (Err(e), _) => Err(e),
+        (Ok(pool), Command::Agents) => list_agents(&pool),
+        (Ok(pool), Command::Run { agent, session, model, yes, json, prompt }) => {
+            run(pool, agent, session, model, yes, json, prompt.join(" ")).await
+        }
+        (Ok(pool), Command::Mcp { agent, expose_agents, agents_writable, read_only }) => {
+            serve_mcp(pool, agent, expose_agents, agents_writable, read_only).await
+        }
+        (Ok(_), Command::Eval { suite, agents, output, traces }) => eval(suite, agents, output, traces).await,
+    };
+    // Flush spans before a possible exit
+    drop(telemetry);
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyagents::database::create_test_pool;
+    use anyagents::models::NewAgent;
+
+    fn insert_agent(pool: &DbPool, name: &str) -> String {
+        use anyagents::schema::agents;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let agent = NewAgent {
+            id: id.clone(),
+            name: name.to_string(),
+            description: None,
+            status: "active".to_string(),
+            personality: None,
+            tone: None,
+            expertise: None,
+            ai_provider: "openai".to_string(),
+            ai_model: "gpt-4o".to_string(),
+            ai_temperature: 0.7,
+            ai_config: "{}".to_string(),
+            system_prompt: None,
+            permissions: None,
+            working_directories: None,
+            skills: None,
+            mcp_servers: None,
+            messaging_connections: None,
+            knowledge_bases: None,
+            api_keys: None,
+            created_at: 0,
+            updated_at: 0,
+            platform_configs: None,
+            execution_settings: None,
+            scope_type: None,
+            workspace_path: None,
+            avatar: None,
+            agent_tools: None,
+        };
+        let mut conn = pool.get().unwrap();
+        diesel::insert_into(agents::table).values(&agent).execute(&mut conn).unwrap();
+        id
+    }
+
+    #[test]
+    fn test_find_agent() {
+        let pool = create_test_pool();
+        let riley = insert_agent(&pool, "Riley the Researcher");
+        insert_agent(&pool, "Rita the Writer");
+        insert_agent(&pool, "Rob the Writer");
+
+        assert_eq!(find_agent(&pool, "researcher").unwrap().id, riley);
+        assert_eq!(find_agent(&pool, &riley).unwrap().id, riley);
+        assert!(find_agent(&pool, "writer").unwrap_err().contains("several agents"));
+        assert!(find_agent(&pool, "nobody").is_err());
+    }
+
+    #[test]
+    fn test_prepare_session() {
+        let pool = create_test_pool();
+        let agent_id = insert_agent(&pool, "Riley");
+        let agent = find_agent(&pool, &agent_id).unwrap();
+
+        let session_id = prepare_session(&pool, &agent, None, "summarize this repo").unwrap();
+        assert_eq!(
+            prepare_session(&pool, &agent, Some(session_id.clone()), "and the tests").unwrap(),
+            session_id
+        );
+        assert!(prepare_session(&pool, &agent, Some("missing".to_string()), "hi").is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use anyagents::permissions::PermissionRequest;
+use serde_json::Value;
+use std::io::{BufRead, Write};
+
+/// Longest tool argument summary shown for a step
+const MAX_ARGS_LEN: usize = 120;
+
+/// Where a rendered event goes: the answer on stdout, progress on stderr, so
+/// `anycowork run ... > answer.md` captures only the reply
+#[derive(Debug, PartialEq)]
+pub enum Output {
+    Stdout(String),
+    Stderr(String),
+}
+
+fn summarize_args(args: &Value) -> String {
+    let text = match args {
+        Value::Object(map) if map.is_empty() => return String::new(),
+        Value::Null => return String::new(),
+        other => other.to_string(),
+    };
+    if text.chars().count() > MAX_ARGS_LEN {
+        format!(" {}…", text.chars().take(MAX_ARGS_LEN).collect::<String>())
+    } else {
+        format!(" {}", text)
+    }
+}
+
+/// Human-readable form of an `AgentEvent` payload, if it is worth showing
+pub fn render_event(payload: &Value) -> Option<Output> {
+    let kind = payload.get("type")?.as_str()?;
+    let text = |key: &str| payload.get(key).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+
+    match kind {
+        "token" => Some(Output::Stdout(text("content"))),
+        "thinking" => Some(Output::Stderr(format!("· {}\n", text("message")))),
+        "step_started" => {
+            let step = payload.get("step")?;
+            Some(Output::Stderr(format!(
+                "▶ {}{}\n",
+                step.get("tool_name")?.as_str()?,
+                summarize_args(step.get("tool_args").unwrap_or(&Value::Null))
+            )))
+        }
+        "step_completed" => {
+            let step = payload.get("step")?;
+            let failed = step.get("status").and_then(|s| s.as_str()) == Some("failed");
+            Some(Output::Stderr(format!(
+                "{} {}\n",
+                if failed { "✗" } else { "✓" },
+                step.get("tool_name")?.as_str()?
+            )))
+        }
+        "error" => Some(Output::Stderr(format!("error: {}\n", text("message")))),
+        _ => None,
+    }
+}
+
+pub fn print(output: Output) {
+    match output {
+        Output::Stdout(text) => {
+            print!("{}", text);
+            let _ = std::io::stdout().flush();
+        }
+        Output::Stderr(text) => eprint!("{}", text),
+    }
+}
+
+/// Ask on the terminal; anything but "y"/"yes" denies
+pub fn prompt_permission(request: &PermissionRequest) -> bool {
+    eprint!("\n? {} [y/N] ", request.message);
+    let _ = std::io::stderr().flush();
+
+    let mut answer = String::new();
+    if std::io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render_event() {
+        assert_eq!(
+            render_event(&json!({ "type": "token", "content": "Hello" })),
+            Some(Output::Stdout("Hello".to_string()))
+        );
+        assert_eq!(
+            render_event(&json!({
+                "type": "step_started",
+                "step": { "tool_name": "bash", "tool_args": { "command": "ls" } }
+            })),
+            Some(Output::Stderr("▶ bash {\"command\":\"ls\"}\n".to_string()))
+        );
+        assert_eq!(
+            render_event(&json!({
+                "type": "step_completed",
+                "step": { "tool_name": "bash", "status": "failed" }
+            })),
+            Some(Output::Stderr("✗ bash\n".to_string()))
+        );
+        assert_eq!(render_event(&json!({ "type": "permission_request", "request": {} })), None);
+        assert_eq!(render_event(&json!({ "content": "untyped" })), None);
+    }
+
+    #[test]
+    fn test_long_args_are_truncated() {
+        let args = json!({ "content": "x".repeat(500) });
+        let summary = summarize_args(&args);
+        assert!(summary.ends_with('…'));
+        assert_eq!(summary.chars().count(), MAX_ARGS_LEN + 2);
+    }
+}
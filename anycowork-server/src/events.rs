@@ -0,0 +1,91 @@
+use anyagents::events::{self, AgentObserver};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+/// Events kept for slow subscribers before they start missing some
+const EVENT_BUFFER: usize = 1024;
+
+/// One emitted event; `channel` is what Tauri would use as the event name
+/// (`session:<id>`, `agent_bus`, ...)
+#[derive(Serialize, Clone, Debug)]
+pub struct ServerEvent {
+    pub channel: String,
+    pub event: Value,
+}
+
+impl ServerEvent {
+    /// Whether a subscriber filtering on `session_id` should see this event
+    pub fn matches(&self, session_id: Option<&str>) -> bool {
+        match session_id {
+            Some(id) => self.channel.strip_prefix("session:") == Some(id),
+            None => true,
+        }
+    }
+}
+
+/// Fan-out of agent events to SSE and WebSocket clients
+pub struct EventHub {
+    sender: broadcast::Sender<ServerEvent>,
+}
+
+impl Default for EventHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventHub {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER);
+        Self { sender }
+    }
+
+    pub fn publish(&self, channel: &str, event: Value) {
+        // No subscribers is fine; events are not persisted here
+        let _ = self.sender.send(ServerEvent {
+            channel: channel.to_string(),
+            event,
+        });
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ServerEvent> {
+        self.sender.subscribe()
+    }
+}
+
+/// `AgentObserver` that publishes to the hub, in place of `TauriAgentObserver`
+#[derive(Clone)]
+pub struct HubObserver {
+    pub hub: Arc<EventHub>,
+}
+
+impl AgentObserver for HubObserver {
+    fn emit(&self, event: &str, payload: Value) -> Result<(), String> {
+        self.hub.publish(event, events::envelope(event, payload));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_hub_observer_publishes() {
+        let hub = Arc::new(EventHub::new());
+        let mut rx = hub.subscribe();
+        let observer = HubObserver { hub: hub.clone() };
+
+        observer.emit("session:abc", json!({ "type": "token", "content": "hi" })).unwrap();
+        let event = rx.recv().await.unwrap();
+        assert_eq!(event.channel, "session:abc");
+        assert!(event.matches(Some("abc")));
+        assert!(!event.matches(Some("other")));
+        assert!(event.matches(None));
+        assert_eq!(event.event["session_id"], "abc");
+        assert_eq!(event.event["version"], events::EVENT_VERSION);
+    }
+}
@@ -0,0 +1,291 @@
+//! Headless HTTP adapter for the AnyCowork agent core
+//!
+//! Exposes what the desktop app does over Tauri IPC as a REST API, so the same
+//! agents can run on a server:
+//!
+//! - `GET/POST /api/agents`, `GET/PATCH /api/agents/:id`
+//! - `GET/POST /api/sessions`, `GET /api/sessions/:id/messages`
+//! - `POST /api/sessions/:id/messages` starts a run (like the `chat` command)
+//! - `POST /api/permissions/:request_id` answers a permission request
+//! - `GET /api/events` (SSE) and `GET /api/ws` (WebSocket) stream `AgentEvent`s,
+//!   optionally filtered with `?session_id=`
+//! - `GET/POST /api/sessions/:id/observers`, `DELETE /api/observers/:token`
+//!   manage read-only observer tokens (see `anyagents::observers`)
+//!
+//! When a token is configured every request needs `Authorization: Bearer <token>`
+//! (or `?token=` for EventSource clients, which cannot set headers). An
+//! observer token works in its place on the event streams and the messages
+//! of its session, and nowhere else.
+
+pub mod events;
+pub mod routes;
+
+use anyagents::bus::AgentBus;
+use anyagents::database::DbPool;
+use anyagents::observers;
+use anyagents::permissions::PermissionManager;
+use anyagents::secrets::constant_time_eq;
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use dashmap::DashMap;
+use events::{EventHub, HubObserver};
+use std::sync::Arc;
+use tokio::sync::oneshot;
+
+#[derive(Clone)]
+pub struct ServerState {
+    pub db_pool: DbPool,
+    pub pending_approvals: Arc<DashMap<String, oneshot::Sender<bool>>>,
+    pub permission_manager: Arc<PermissionManager>,
+    pub agent_bus: Arc<AgentBus>,
+    pub events: Arc<EventHub>,
+    pub token: Option<String>,
+}
+
+impl ServerState {
+    pub fn new(db_pool: DbPool, token: Option<String>) -> Self {
+        let permission_manager = Arc::new(PermissionManager::new());
+        let agent_bus = Arc::new(AgentBus::new(db_pool.clone(), permission_manager.clone()));
+        let events = Arc::new(EventHub::new());
+        agent_bus.set_observer(Arc::new(HubObserver { hub: events.clone() }));
+
+        Self {
+            db_pool,
+            pending_approvals: Arc::new(DashMap::new()),
+            permission_manager,
+            agent_bus,
+            events,
+            token,
+        }
+    }
+}
+
+/// Error body shared by every route: `{ "error": "..." }`
+pub struct ApiError(pub StatusCode, pub String);
+
+impl ApiError {
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self(StatusCode::NOT_FOUND, message.into())
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self(StatusCode::BAD_REQUEST, message.into())
+    }
+}
+
+impl From<String> for ApiError {
+    fn from(message: String) -> Self {
+        Self(StatusCode::INTERNAL_SERVER_ERROR, message)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+pub type ApiResult<T> = Result<Json<T>, ApiError>;
+
+/// Set on requests made with an observer token: the session it may follow
+#[derive(Clone, Debug)]
+pub struct ObserverAccess {
+    pub session_id: String,
+}
+
+fn presented_token(request: &Request) -> Option<&str> {
+    let bearer = request
+        .headers()
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let query = request
+        .uri()
+        .query()
+        .and_then(|q| q.split('&').find_map(|pair| pair.strip_prefix("token=")));
+    bearer.or(query).map(str::trim)
+}
+
+/// Observers read: the event streams and their session's messages
+fn observer_may(request: &Request, session_id: &str) -> bool {
+    let path = request.uri().path();
+    request.method() == axum::http::Method::GET
+        && (path == "/api/events"
+            || path == "/api/ws"
+            || path == format!("/api/sessions/{}/messages", session_id))
+}
+
+async fn require_token(State(state): State<ServerState>, mut request: Request, next: Next) -> Response {
+    let presented = presented_token(&request).map(str::to_string);
+    if let Some(session_id) = presented.as_deref().and_then(|t| observers::global().session_of(t)) {
+        if !observer_may(&request, &session_id) {
+            return ApiError(StatusCode::FORBIDDEN, "Observers can only follow their session".to_string())
+                .into_response();
+        }
+        request.extensions_mut().insert(ObserverAccess { session_id });
+        return next.run(request).await;
+    }
+    match &state.token {
+        Some(token) if !presented.as_deref().is_some_and(|p| constant_time_eq(p.as_bytes(), token.as_bytes())) => {
+            ApiError(StatusCode::UNAUTHORIZED, "Unauthorized".to_string()).into_response()
+        }
+        _ => next.run(request).await,
+    }
+}
+
+pub fn router(state: ServerState) -> Router {
+    Router::new()
+        .route("/api/agents", get(routes::agents::list).post(routes::agents::create))
+        .route("/api/agents/:agent_id", get(routes::agents::get).patch(routes::agents::update))
+        .route("/api/agents/:agent_id/versions", get(routes::agents::versions))
+        .route("/api/agents/:agent_id/versions/:version/rollback", post(routes::agents::rollback))
+        .route("/api/sessions", get(routes::sessions::list).post(routes::sessions::create))
+        .route(
+            "/api/sessions/:session_id/messages",
+            get(routes::sessions::messages).post(routes::sessions::send_message),
+        )
+        .route(
+            "/api/sessions/:session_id/observers",
+            get(routes::sessions::list_observers).post(routes::sessions::create_observer),
+        )
+        .route("/api/observers/:token", delete(routes::sessions::revoke_observer))
+        .route("/api/permissions/:request_id", post(routes::permissions::respond))
+        .route("/api/events", get(routes::stream::sse))
+        .route("/api/ws", get(routes::stream::websocket))
+        .layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyagents::database::create_test_pool;
+    use axum::body::Body;
+    use http_body_util::BodyExt;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    async fn call(app: &Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let mut request = axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("authorization", "Bearer secret");
+        let body = match body {
+            Some(body) => {
+                request = request.header("content-type", "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        let response = app.clone().oneshot(request.body(body).unwrap()).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_requires_token() {
+        let app = router(ServerState::new(create_test_pool(), Some("secret".to_string())));
+        let request = axum::http::Request::builder()
+            .uri("/api/agents")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let request = axum::http::Request::builder()
+            .uri("/api/agents")
+            .header("authorization", "Bearer secreT")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let (status, _) = call(&app, "GET", "/api/agents", None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_agent_and_session_routes() {
+        let app = router(ServerState::new(create_test_pool(), Some("secret".to_string())));
+
+        let (status, agent) = call(
+            &app,
+            "POST",
+            "/api/agents",
+            Some(json!({ "name": "Ops", "system_prompt": "You run deployments." })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let agent_id = agent["id"].as_str().unwrap().to_string();
+
+        let (status, agent) = call(
+            &app,
+            "PATCH",
+            &format!("/api/agents/{}", agent_id),
+            Some(json!({ "description": "Deploys things" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(agent["description"], "Deploys things");
+
+        let (status, session) = call(&app, "POST", "/api/sessions", Some(json!({ "agent_id": agent_id }))).await;
+        assert_eq!(status, StatusCode::OK);
+        let session_id = session["id"].as_str().unwrap();
+
+        let (_, sessions) = call(&app, "GET", &format!("/api/sessions?agent_id={}", agent_id), None).await;
+        assert_eq!(sessions.as_array().unwrap().len(), 1);
+
+        let (status, messages) = call(&app, "GET", &format!("/api/sessions/{}/messages", session_id), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(messages.as_array().unwrap().is_empty());
+
+        let (status, _) = call(&app, "POST", "/api/sessions", Some(json!({ "agent_id": "missing" }))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_observer_token_is_read_only() {
+        let app = router(ServerState::new(create_test_pool(), Some("secret".to_string())));
+        let (_, agent) = call(&app, "POST", "/api/agents", Some(json!({ "name": "Ops", "system_prompt": "Hi" }))).await;
+        let (_, session) = call(&app, "POST", "/api/sessions", Some(json!({ "agent_id": agent["id"] }))).await;
+        let session_id = session["id"].as_str().unwrap();
+
+        let (status, grant) = call(
+            &app,
+            "POST",
+            &format!("/api/sessions/{}/observers", session_id),
+            Some(json!({ "label": "Teammate" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let token = grant["token"].as_str().unwrap();
+
+        let as_observer = |method: &str, uri: String| {
+            axum::http::Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(json!({ "message": "hi", "approved": true }).to_string()))
+                .unwrap()
+        };
+        let messages = format!("/api/sessions/{}/messages", session_id);
+        let response = app.clone().oneshot(as_observer("GET", messages.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app.clone().oneshot(as_observer("POST", messages)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = app.clone().oneshot(as_observer("POST", "/api/permissions/r1".to_string())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = app.clone().oneshot(as_observer("GET", "/api/agents".to_string())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let (status, _) = call(&app, "DELETE", &format!("/api/observers/{}", token), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let response = app.clone().oneshot(as_observer("GET", "/api/agents".to_string())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
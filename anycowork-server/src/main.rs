@@ -0,0 +1,38 @@
+use anycowork_server::{router, ServerState};
+
+#[tokio::main]
+async fn main() {
+    dotenvy::dotenv().ok();
+    env_logger::init();
+    let _telemetry = anyagents::telemetry::init("anycowork-server").unwrap_or_else(|e| {
+        log::warn!("Telemetry disabled: {}", e);
+        None
+    });
+
+    let addr = std::env::var("ANYCOWORK_SERVER_ADDR").unwrap_or_else(|_| "127.0.0.1:8080".to_string());
+    let token = std::env::var("ANYCOWORK_SERVER_TOKEN").ok().filter(|t| !t.is_empty());
+    if token.is_none() && !addr.starts_with("127.0.0.1") && !addr.starts_with("localhost") {
+        log::warn!("Listening on {} without ANYCOWORK_SERVER_TOKEN; the API is open to anyone who can reach it", addr);
+    }
+
+    // Same database as the desktop app unless the config (or DATABASE_URL) says otherwise
+    let database_url = anyagents::config::current().database_url.unwrap_or_else(|| {
+        let dir = dirs_home().join(".anycowork");
+        std::fs::create_dir_all(&dir).expect("Failed to create .anycowork directory");
+        format!("sqlite://{}", dir.join("anycowork.db").to_string_lossy())
+    });
+    let pool = anyagents::database::open_database(&database_url).expect("Failed to open database");
+    anyagents::database::ensure_default_characters(&pool);
+    anyagents::llm::scheduler::global().set_limits(anyagents::llm::scheduler::RateLimits::load(&pool));
+
+    let state = ServerState::new(pool, token);
+    let listener = tokio::net::TcpListener::bind(&addr)
+        .await
+        .unwrap_or_else(|e| panic!("Failed to listen on {}: {}", addr, e));
+    log::info!("AnyCowork server listening on {}", addr);
+    axum::serve(listener, router(state)).await.expect("Server error");
+}
+
+fn dirs_home() -> std::path::PathBuf {
+    std::path::PathBuf::from(std::env::var("HOME").expect("HOME environment variable not set"))
+}
@@ -0,0 +1,187 @@
+use crate::{ApiError, ApiResult, ServerState};
+use anyagents::models::agent_version::{self, AgentVersion};
+use anyagents::models::{AIConfigDto, Agent, AgentDto, NewAgent};
+use anyagents::schema::agents;
+use anyagents::tools::config::AgentToolsConfig;
+use axum::extract::{Path, State};
+use axum::Json;
+use diesel::prelude::*;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct CreateAgentRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub system_prompt: Option<String>,
+    pub ai_provider: Option<String>,
+    pub ai_model: Option<String>,
+    pub execution_settings: Option<serde_json::Value>,
+    pub workspace_path: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateAgentRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub system_prompt: Option<String>,
+    pub ai_provider: Option<String>,
+    pub ai_model: Option<String>,
+    /// Replaces provider, model and sampling parameters at once
+    pub ai_config: Option<AIConfigDto>,
+    pub execution_settings: Option<serde_json::Value>,
+    pub workspace_path: Option<String>,
+    pub agent_tools: Option<AgentToolsConfig>,
+}
+
+/// `existing` with another provider and model; sampling parameters are kept
+fn ai_config_json(existing: &str, provider: &str, model: &str, temperature: f32) -> String {
+    let mut config = serde_json::from_str::<serde_json::Value>(existing)
+        .ok()
+        .filter(|c| c.is_object())
+        .unwrap_or_else(|| serde_json::json!({}));
+    config["provider"] = provider.into();
+    config["model"] = model.into();
+    config["temperature"] = temperature.into();
+    config.to_string()
+}
+
+pub(crate) fn load_agent(state: &ServerState, agent_id: &str) -> Result<Agent, ApiError> {
+    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+    agents::table
+        .find(agent_id)
+        .first(&mut conn)
+        .map_err(|_| ApiError::not_found("Agent not found"))
+}
+
+pub async fn list(State(state): State<ServerState>) -> ApiResult<Vec<AgentDto>> {
+    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+    let results = agents::table.load::<Agent>(&mut conn).map_err(|e| e.to_string())?;
+    Ok(Json(results.into_iter().map(|a| a.into_dto()).collect()))
+}
+
+pub async fn get(State(state): State<ServerState>, Path(agent_id): Path<String>) -> ApiResult<AgentDto> {
+    Ok(Json(load_agent(&state, &agent_id)?.into_dto()))
+}
+
+/// Defaults match the desktop app's `create_agent` command
+pub async fn create(
+    State(state): State<ServerState>,
+    Json(body): Json<CreateAgentRequest>,
+) -> ApiResult<AgentDto> {
+    if body.name.trim().is_empty() {
+        return Err(ApiError::bad_request("Agent name is required"));
+    }
+    let ai_provider = body.ai_provider.unwrap_or_else(|| "gemini".to_string());
+    let ai_model = body.ai_model.unwrap_or_else(|| "gemini-3-flash-preview".to_string());
+    let now = chrono::Utc::now().timestamp();
+
+    let new_agent = NewAgent {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: body.name,
+        description: body.description,
+        status: "active".to_string(),
+        personality: None,
+        tone: None,
+        expertise: None,
+        ai_config: ai_config_json("{}", &ai_provider, &ai_model, 0.7),
+        ai_provider,
+        ai_model,
+        ai_temperature: 0.7,
+        system_prompt: body.system_prompt,
+        permissions: None,
+        working_directories: None,
+        skills: None,
+        mcp_servers: None,
+        messaging_connections: None,
+        knowledge_bases: None,
+        api_keys: None,
+        created_at: now,
+        updated_at: now,
+        platform_configs: None,
+        execution_settings: body.execution_settings.map(|s| s.to_string()),
+        scope_type: None,
+        workspace_path: body.workspace_path,
+        avatar: None,
+        agent_tools: None,
+    };
+
+    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+    diesel::insert_into(agents::table)
+        .values(&new_agent)
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+
+    let agent = load_agent(&state, &new_agent.id)?;
+    agent_version::record(&state.db_pool, &agent, Some("Created".to_string()))?;
+    Ok(Json(agent.into_dto()))
+}
+
+pub async fn update(
+    State(state): State<ServerState>,
+    Path(agent_id): Path<String>,
+    Json(body): Json<UpdateAgentRequest>,
+) -> ApiResult<AgentDto> {
+    let agent = load_agent(&state, &agent_id)?;
+    // Keeps the config as it was if this agent predates its history
+    agent_version::record(&state.db_pool, &agent, None)?;
+    let agent_tools = match body.agent_tools {
+        Some(config) => {
+            config.validate().map_err(ApiError::bad_request)?;
+            Some(serde_json::to_string(&config).map_err(|e| e.to_string())?)
+        }
+        None => agent.agent_tools,
+    };
+    let (ai_config, ai_provider, ai_model, ai_temperature) = match body.ai_config {
+        Some(config) => {
+            config.generation_params().map_err(ApiError::bad_request)?;
+            let json = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+            (json, config.provider, config.model, config.temperature)
+        }
+        None => {
+            let ai_provider = body.ai_provider.unwrap_or(agent.ai_provider);
+            let ai_model = body.ai_model.unwrap_or(agent.ai_model);
+            let json = ai_config_json(&agent.ai_config, &ai_provider, &ai_model, agent.ai_temperature);
+            (json, ai_provider, ai_model, agent.ai_temperature)
+        }
+    };
+
+    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+    diesel::update(agents::table.find(&agent_id))
+        .set((
+            agents::name.eq(body.name.unwrap_or(agent.name)),
+            agents::description.eq(body.description.or(agent.description)),
+            agents::system_prompt.eq(body.system_prompt.or(agent.system_prompt)),
+            agents::ai_config.eq(ai_config),
+            agents::ai_provider.eq(ai_provider),
+            agents::ai_model.eq(ai_model),
+            agents::ai_temperature.eq(ai_temperature),
+            agents::execution_settings.eq(body
+                .execution_settings
+                .map(|s| s.to_string())
+                .or(agent.execution_settings)),
+            agents::workspace_path.eq(body.workspace_path.or(agent.workspace_path)),
+            agents::agent_tools.eq(agent_tools),
+            agents::updated_at.eq(chrono::Utc::now().timestamp()),
+        ))
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+
+    let agent = load_agent(&state, &agent_id)?;
+    agent_version::record(&state.db_pool, &agent, None)?;
+    Ok(Json(agent.into_dto()))
+}
+
+/// Configuration history, newest first
+pub async fn versions(State(state): State<ServerState>, Path(agent_id): Path<String>) -> ApiResult<Vec<AgentVersion>> {
+    load_agent(&state, &agent_id)?;
+    Ok(Json(agent_version::list_versions(&state.db_pool, &agent_id)?))
+}
+
+pub async fn rollback(
+    State(state): State<ServerState>,
+    Path((agent_id, version)): Path<(String, i32)>,
+) -> ApiResult<AgentDto> {
+    load_agent(&state, &agent_id)?;
+    agent_version::get_version(&state.db_pool, &agent_id, version).map_err(ApiError::not_found)?;
+    Ok(Json(agent_version::rollback(&state.db_pool, &agent_id, version)?.into_dto()))
+}
@@ -0,0 +1,4 @@
+pub mod agents;
+pub mod permissions;
+pub mod sessions;
+pub mod stream;
@@ -0,0 +1,28 @@
+use crate::{ApiResult, ServerState};
+use axum::extract::{Path, State};
+use axum::Json;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize)]
+pub struct PermissionResponse {
+    pub approved: bool,
+}
+
+/// Answer an approval or permission request, like the desktop app's
+/// `approve_action` and `reject_action` commands
+pub async fn respond(
+    State(state): State<ServerState>,
+    Path(request_id): Path<String>,
+    Json(body): Json<PermissionResponse>,
+) -> ApiResult<Value> {
+    if body.approved {
+        state.permission_manager.approve_request(&request_id);
+    } else {
+        state.permission_manager.reject_request(&request_id);
+    }
+    if let Some((_, tx)) = state.pending_approvals.remove(&request_id) {
+        let _ = tx.send(body.approved);
+    }
+    Ok(Json(json!({ "request_id": request_id, "approved": body.approved })))
+}
@@ -0,0 +1,212 @@
+use super::agents::load_agent;
+use crate::events::HubObserver;
+use crate::{ApiError, ApiResult, ServerState};
+use anyagents::agents::coordinator::Coordinator;
+use anyagents::models::{Agent, Message, NewMessage, NewSession, Session};
+use anyagents::observers::{self, ObserverGrant};
+use anyagents::permissions::AutonomousPermissionManager;
+use anyagents::schema::{messages, sessions};
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use diesel::prelude::*;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct ListSessionsQuery {
+    pub agent_id: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateSessionRequest {
+    pub agent_id: String,
+    pub title: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SendMessageRequest {
+    pub message: String,
+    pub mode: Option<String>,
+    pub model: Option<String>,
+    #[serde(default)]
+    pub images: Vec<anyagents::llm::ImageAttachment>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateObserverRequest {
+    pub label: Option<String>,
+    /// Grant lasts until revoked (or the server restarts) when unset
+    pub ttl_secs: Option<i64>,
+}
+
+fn load_session(state: &ServerState, session_id: &str) -> Result<Session, ApiError> {
+    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+    sessions::table
+        .find(session_id)
+        .first(&mut conn)
+        .map_err(|_| ApiError::not_found("Session not found"))
+}
+
+pub async fn list(
+    State(state): State<ServerState>,
+    Query(query): Query<ListSessionsQuery>,
+) -> ApiResult<Vec<Session>> {
+    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+    let mut q = sessions::table
+        .order((sessions::pinned.desc(), sessions::updated_at.desc()))
+        .into_boxed();
+    if let Some(agent_id) = query.agent_id {
+        q = q.filter(sessions::agent_id.eq(agent_id));
+    }
+    if let Some(limit) = query.limit {
+        q = q.limit(limit);
+    }
+    Ok(Json(q.load(&mut conn).map_err(|e| e.to_string())?))
+}
+
+pub async fn create(
+    State(state): State<ServerState>,
+    Json(body): Json<CreateSessionRequest>,
+) -> ApiResult<Session> {
+    let agent = load_agent(&state, &body.agent_id)?;
+    let now = chrono::Utc::now().naive_utc();
+    let new_session = NewSession {
+        id: uuid::Uuid::new_v4().to_string(),
+        agent_id: agent.id,
+        title: Some(body.title.unwrap_or_else(|| format!("Chat with {}", agent.name))),
+        created_at: now,
+        updated_at: now,
+        archived: 0,
+        pinned: 0,
+    };
+
+    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+    diesel::insert_into(sessions::table)
+        .values(&new_session)
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    Ok(Json(load_session(&state, &new_session.id)?))
+}
+
+pub async fn messages(
+    State(state): State<ServerState>,
+    Path(session_id): Path<String>,
+) -> ApiResult<Vec<Message>> {
+    load_session(&state, &session_id)?;
+    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+    let results = messages::table
+        .filter(messages::session_id.eq(&session_id))
+        .filter(messages::deleted_at.is_null())
+        .order(messages::created_at.asc())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())?;
+    Ok(Json(results))
+}
+
+/// Invite an observer: the returned token follows the session's events and
+/// reads its messages, but cannot send messages or answer approvals
+pub async fn create_observer(
+    State(state): State<ServerState>,
+    Path(session_id): Path<String>,
+    Json(body): Json<CreateObserverRequest>,
+) -> ApiResult<ObserverGrant> {
+    load_session(&state, &session_id)?;
+    if body.ttl_secs.is_some_and(|ttl| ttl <= 0) {
+        return Err(ApiError::bad_request("ttl_secs must be positive"));
+    }
+    let ttl = body.ttl_secs.map(chrono::Duration::seconds);
+    Ok(Json(observers::global().grant(&session_id, body.label, ttl)))
+}
+
+pub async fn list_observers(
+    State(state): State<ServerState>,
+    Path(session_id): Path<String>,
+) -> ApiResult<Vec<ObserverGrant>> {
+    load_session(&state, &session_id)?;
+    Ok(Json(observers::global().for_session(&session_id)))
+}
+
+pub async fn revoke_observer(Path(token): Path<String>) -> ApiResult<Value> {
+    match observers::global().revoke(&token) {
+        true => Ok(Json(json!({ "revoked": true }))),
+        false => Err(ApiError::not_found("Observer not found")),
+    }
+}
+
+/// Save the user message and start the coordinator in the background; progress
+/// arrives on the event stream under `session:<id>`
+pub async fn send_message(
+    State(state): State<ServerState>,
+    Path(session_id): Path<String>,
+    Json(body): Json<SendMessageRequest>,
+) -> ApiResult<Value> {
+    if body.message.trim().is_empty() {
+        return Err(ApiError::bad_request("Message is empty"));
+    }
+    let images = anyagents::llm::image::validate_attachments(body.images)
+        .map_err(ApiError::bad_request)?;
+    let session = load_session(&state, &session_id)?;
+    let agent = load_agent(&state, &session.agent_id)?;
+
+    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+    diesel::insert_into(messages::table)
+        .values(&NewMessage {
+            id: uuid::Uuid::new_v4().to_string(),
+            role: "user".to_string(),
+            content: body.message.clone(),
+            session_id: session_id.clone(),
+            metadata_json: anyagents::llm::image::images_metadata(&images),
+            tokens: None,
+        })
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+
+    let coordinator = build_coordinator(
+        &state,
+        agent,
+        session_id.clone(),
+        body.mode.unwrap_or_else(|| "planning".to_string()),
+        body.model,
+    )
+    .with_images(images);
+    tokio::spawn(async move {
+        coordinator.run(body.message).await;
+    });
+
+    Ok(Json(json!({ "status": "started", "session_id": session_id })))
+}
+
+/// Same wiring as the desktop app, with events going to the hub
+fn build_coordinator(
+    state: &ServerState,
+    agent: Agent,
+    session_id: String,
+    mode: String,
+    model: Option<String>,
+) -> Coordinator {
+    let observer = Arc::new(HubObserver {
+        hub: state.events.clone(),
+    });
+
+    let is_autonomous = agent
+        .execution_settings
+        .as_ref()
+        .and_then(|s| serde_json::from_str::<Value>(s).ok())
+        .and_then(|json| json.get("mode")?.as_str().map(|m| m == "autopilot" || m == "autonomous"))
+        .unwrap_or(false);
+    let autonomous_pm = Arc::new(AutonomousPermissionManager::new(is_autonomous));
+
+    Coordinator::new_with_autonomous(
+        session_id,
+        agent,
+        observer,
+        state.db_pool.clone(),
+        autonomous_pm,
+        state.pending_approvals.clone(),
+        mode,
+        model,
+    )
+    .with_bus(state.agent_bus.clone())
+}
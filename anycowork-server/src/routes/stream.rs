@@ -0,0 +1,93 @@
+use crate::events::ServerEvent;
+use crate::{ObserverAccess, ServerState};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::Extension;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::Response;
+use futures::stream::{Stream, StreamExt};
+use serde::Deserialize;
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_stream::wrappers::BroadcastStream;
+
+#[derive(Deserialize)]
+pub struct StreamQuery {
+    pub session_id: Option<String>,
+}
+
+impl StreamQuery {
+    /// Session to filter on; an observer only ever gets the one it was invited to
+    fn session_id(self, observer: Option<Extension<ObserverAccess>>) -> Option<String> {
+        match observer {
+            Some(Extension(access)) => Some(access.session_id),
+            None => self.session_id,
+        }
+    }
+}
+
+/// Server-sent events; the SSE event name is the channel
+pub async fn sse(
+    State(state): State<ServerState>,
+    observer: Option<Extension<ObserverAccess>>,
+    Query(query): Query<StreamQuery>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let session_id = query.session_id(observer);
+    let stream = BroadcastStream::new(state.events.subscribe()).filter_map(move |event| {
+        let event = match event {
+            Ok(event) if event.matches(session_id.as_deref()) => Some(
+                Event::default()
+                    .event(event.channel.clone())
+                    .json_data(&event.event)
+                    .unwrap_or_default(),
+            ),
+            Ok(_) => None,
+            // Slow client: tell it how much it missed instead of closing the stream
+            Err(e) => Some(Event::default().event("lagged").data(e.to_string())),
+        };
+        futures::future::ready(event.map(Ok))
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// WebSocket stream of `{ "channel": ..., "event": ... }` messages
+pub async fn websocket(
+    ws: WebSocketUpgrade,
+    State(state): State<ServerState>,
+    observer: Option<Extension<ObserverAccess>>,
+    Query(query): Query<StreamQuery>,
+) -> Response {
+    let session_id = query.session_id(observer);
+    ws.on_upgrade(move |socket| forward_events(socket, state, session_id))
+}
+
+async fn forward_events(mut socket: WebSocket, state: ServerState, session_id: Option<String>) {
+    let mut events = state.events.subscribe();
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let event: ServerEvent = match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(n)) => {
+                        log::warn!("WebSocket client lagged, {} events dropped", n);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                if !event.matches(session_id.as_deref()) {
+                    continue;
+                }
+                let Ok(text) = serde_json::to_string(&event) else { continue };
+                if socket.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+            incoming = socket.recv() => {
+                match incoming {
+                    Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                    _ => {}
+                }
+            }
+        }
+    }
+}
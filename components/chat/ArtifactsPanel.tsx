@@ -0,0 +1,63 @@
+import { useState } from "react";
+import { cn } from "@/lib/utils";
+import { Badge } from "@/components/ui/badge";
+import { ChevronRight, FileText, Image, Package } from "lucide-react";
+import type { Artifact } from "@/lib/anycowork-api";
+
+interface ArtifactsPanelProps {
+  artifacts: Artifact[];
+}
+
+function formatSize(bytes: number) {
+  if (bytes < 1024) return `${bytes} B`;
+  if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(1)} KB`;
+  return `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+}
+
+// Files the session's jobs produced, shown above the input
+export function ArtifactsPanel({ artifacts }: ArtifactsPanelProps) {
+  const [isOpen, setIsOpen] = useState(false);
+
+  return (
+    <div className="border-b border-border/40">
+      <button
+        type="button"
+        onClick={() => setIsOpen(!isOpen)}
+        className="w-full flex items-center justify-between px-4 py-2 hover:bg-muted/50 transition-colors"
+      >
+        <div className="flex items-center gap-2 min-w-0">
+          <Package className="h-4 w-4 flex-shrink-0 text-muted-foreground" />
+          <span className="text-sm font-medium">Files produced</span>
+          <Badge variant="outline" className="text-xs h-5">
+            {artifacts.length}
+          </Badge>
+        </div>
+        <ChevronRight className={cn(
+          "h-4 w-4 text-muted-foreground transition-transform duration-200",
+          isOpen && "rotate-90"
+        )} />
+      </button>
+
+      {isOpen && (
+        <ul className="px-4 pb-3 space-y-1 max-h-[160px] overflow-y-auto">
+          {artifacts.map(artifact => (
+            <li key={artifact.id} className="flex items-start gap-2 text-xs leading-relaxed" title={artifact.path}>
+              <span className="mt-0.5 flex-shrink-0">
+                {artifact.kind === 'image' ? (
+                  <Image className="w-3.5 h-3.5 text-muted-foreground" />
+                ) : (
+                  <FileText className="w-3.5 h-3.5 text-muted-foreground" />
+                )}
+              </span>
+              <span className="font-medium truncate">{artifact.title}</span>
+              <span className="text-muted-foreground flex-shrink-0">{formatSize(artifact.file_size)}</span>
+              {artifact.preview && (
+                <span className="text-muted-foreground truncate">{artifact.preview.split('\n')[0]}</span>
+              )}
+            </li>
+          ))}
+        </ul>
+      )}
+    </div>
+  );
+}
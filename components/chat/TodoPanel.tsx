@@ -0,0 +1,68 @@
+import { useState } from "react";
+import { cn } from "@/lib/utils";
+import { Badge } from "@/components/ui/badge";
+import { CheckCircle, ChevronRight, Circle, ListChecks, Loader2 } from "lucide-react";
+import type { TodoItem } from "@/lib/anycowork-api";
+
+interface TodoPanelProps {
+  items: TodoItem[];
+}
+
+// The checklist the agent keeps with the `todo` tool, shown above the input
+export function TodoPanel({ items }: TodoPanelProps) {
+  const [isOpen, setIsOpen] = useState(true);
+  const completed = items.filter(t => t.status === 'completed').length;
+  const active = items.find(t => t.status === 'in_progress');
+
+  return (
+    <div className="border-b border-border/40">
+      <button
+        type="button"
+        onClick={() => setIsOpen(!isOpen)}
+        className="w-full flex items-center justify-between px-4 py-2 hover:bg-muted/50 transition-colors"
+      >
+        <div className="flex items-center gap-2 min-w-0">
+          <ListChecks className={cn(
+            "h-4 w-4 flex-shrink-0",
+            active ? "text-primary animate-pulse" : "text-muted-foreground"
+          )} />
+          <span className="text-sm font-medium">Checklist</span>
+          <Badge variant="outline" className="text-xs h-5">
+            {completed}/{items.length}
+          </Badge>
+          {active && !isOpen && (
+            <span className="text-xs text-muted-foreground truncate">• {active.content}</span>
+          )}
+        </div>
+        <ChevronRight className={cn(
+          "h-4 w-4 text-muted-foreground transition-transform duration-200",
+          isOpen && "rotate-90"
+        )} />
+      </button>
+
+      {isOpen && (
+        <ul className="px-4 pb-3 space-y-1 max-h-[160px] overflow-y-auto">
+          {items.map(item => (
+            <li key={item.id} className="flex items-start gap-2 text-xs leading-relaxed">
+              <span className="mt-0.5 flex-shrink-0">
+                {item.status === 'completed' ? (
+                  <CheckCircle className="w-3.5 h-3.5 text-green-600" />
+                ) : item.status === 'in_progress' ? (
+                  <Loader2 className="w-3.5 h-3.5 text-primary animate-spin" />
+                ) : (
+                  <Circle className="w-3.5 h-3.5 text-muted-foreground/50" />
+                )}
+              </span>
+              <span className={cn(
+                item.status === 'completed' && "line-through text-muted-foreground",
+                item.status === 'in_progress' && "font-medium"
+              )}>
+                {item.content}
+              </span>
+            </li>
+          ))}
+        </ul>
+      )}
+    </div>
+  );
+}
@@ -32,6 +32,62 @@ export interface Agent {
   execution_settings?: ExecutionSettings;
   workspace_path?: string;
   scope_type?: string;
+  agent_tools?: AgentToolsConfig;
+}
+
+// What an agent edit changed is kept as a version of its configuration
+export interface AgentConfigSnapshot {
+  name: string;
+  description?: string | null;
+  ai_provider: string;
+  ai_model: string;
+  ai_temperature: number;
+  system_prompt?: string | null;
+  skills?: string | null;
+  mcp_servers?: string | null;
+  execution_settings?: string | null;
+  agent_tools?: string | null;
+}
+
+export interface AgentVersion {
+  id: string;
+  agent_id: string;
+  version: number;
+  // JSON AgentConfigSnapshot
+  snapshot: string;
+  note?: string | null;
+  created_at: string;
+}
+
+// Built-in tools registered when `agent_tools.builtin` is unset; `http` and
+// `generate_image` are opt-in
+export const DEFAULT_AGENT_TOOLS = [
+  'filesystem',
+  'search_files',
+  'bash',
+  'transcribe',
+  'list_colleagues',
+  'send_email',
+  'check_mail',
+  'read_email_thread',
+  'pdf',
+  'view_image', // vision-capable models only
+  'pages',
+  'todo',
+];
+
+// Image sent with a chat message; `data` is base64 or a data: URL.
+// Stored on the user message as `metadata_json.images`.
+export interface ImageAttachment {
+  media_type: string; // image/png, image/jpeg, image/gif or image/webp
+  data: string;
+}
+
+export interface AgentToolsConfig {
+  builtin?: string[] | null;
+  disabled: string[]; // any tool name: built-in, skill or MCP
+  filesystem: { root?: string | null };
+  bash: { timeout_seconds?: number | null };
 }
 
 export interface AgentCharacteristics {
@@ -53,6 +109,59 @@ export interface ExecutionSettings {
   whitelisted_commands?: string[];
   whitelisted_tools?: string[];
   blacklisted_commands?: string[];
+  read_only?: boolean;
+  read_only_allowed_commands?: string[];
+  isolation?: 'standard' | 'strict';
+  checkpoints?: boolean;
+  memory?: boolean; // recall matching notes from pages into the system prompt
+  max_parallel_tasks?: number;
+  max_concurrent_jobs?: number; // jobs of this agent running at once; JobLimits.per_agent when unset
+  verify?: boolean | { model?: string }; // critic pass before finishing; model defaults to the provider's fast one
+  team_member_ids?: string[]; // team mode; defaults to every other agent
+  mail_replies?: 'direct' | 'tools'; // 'tools' answers mail with a full agent run
+  policy?: PermissionPolicy | string; // object, or YAML/JSON text
+  hooks?: AgentHook[];
+  tool_quotas?: ToolQuotas;
+  dry_run?: boolean; // hold back changes and propose them as a change plan
+}
+
+// Tool calls allowed, by tool name; '*' counts every tool together
+export interface ToolQuotas {
+  per_job?: Record<string, number>;
+  per_day?: Record<string, number>; // UTC day
+}
+
+export type HookEvent = 'pre_tool_call' | 'post_tool_call' | 'pre_llm' | 'on_job_complete';
+
+// A command (run with the hook context as JSON on stdin) or a skill, run at
+// `event`. Pre hooks veto by printing {"decision": "deny", "reason"} or
+// exiting non-zero; {"args"} / {"result"} replace the tool call's arguments
+// or result.
+export interface AgentHook {
+  event: HookEvent;
+  command?: string;
+  skill?: string;
+  args?: Record<string, unknown>; // arguments of `skill`
+  tools?: string[]; // tool calls the hook applies to; all when empty
+  timeout_seconds?: number; // default 30
+}
+
+// Rules checked before the user is asked; the first match decides
+export type PolicyDecision = 'allow' | 'deny' | 'ask';
+
+export interface PolicyRule {
+  tool?: string;
+  permission?: 'filesystem_read' | 'filesystem_write' | 'shell_execute' | 'network' | 'pages_write' | 'unknown';
+  paths?: string[]; // globs
+  commands?: string[]; // regexes
+  hosts?: string[]; // "*.example.com" matches subdomains
+  decision: PolicyDecision;
+  reason?: string;
+}
+
+export interface PermissionPolicy {
+  rules: PolicyRule[];
+  default?: PolicyDecision;
 }
 
 export interface AgentCreate {
@@ -75,6 +184,7 @@ export interface AgentUpdate {
   mcp_servers?: string[];
   execution_settings?: ExecutionSettings;
   platform_configs?: Record<string, any>;
+  agent_tools?: AgentToolsConfig;
 }
 
 export interface AIConfig {
@@ -83,6 +193,10 @@ export interface AIConfig {
   max_tokens?: number;
   temperature?: number;
   top_p?: number;
+  /** Sequences that end the answer; at most 4 for OpenAI, 5 for Gemini */
+  stop?: string[];
+  /** Repeatable sampling; OpenAI and Gemini only */
+  seed?: number;
   frequency_penalty?: number;
   presence_penalty?: number;
   anthropic_api_key?: string;
@@ -93,6 +207,149 @@ export interface AIConfig {
   gemini_model?: string;
 }
 
+// Limits of the shared LLM request scheduler; providers not listed get the defaults
+export interface ProviderLimits {
+  max_concurrent: number;
+  requests_per_minute: number | null;
+  tokens_per_minute: number | null; // estimated
+}
+
+// How streamed events are batched on their way to the windows
+export interface EventChannelConfig {
+  flush_interval_ms: number; // 0 sends every event as it comes
+  batch_size: number; // characters of streamed text sent without waiting
+  thinking: 'keep' | 'merge' | 'drop';
+}
+
+export interface RateLimits {
+  max_concurrent: number; // across all providers
+  providers: Record<string, ProviderLimits>;
+}
+
+// How many jobs run at once; more wait in the job queue
+export interface JobLimits {
+  max_concurrent: number; // across all agents
+  per_agent: number; // unless the agent's execution settings say otherwise
+}
+
+export interface QueuedJob {
+  id: string;
+  agent_id: string;
+  session_id: string;
+  label: string; // the message or prompt, shortened
+  priority: number; // higher starts first; chats 10, triggers and webhooks 0
+  provider?: string;
+  status: 'queued' | 'pending' | 'running' | 'completed' | 'cancelled'; // pending: provider offline
+  queued_at: string;
+  started_at?: string;
+  finished_at?: string;
+}
+
+// The job waits in the queue behind `position` others
+export interface JobQueuedEvent {
+  type: 'job_queued';
+  job: any;
+  position: number;
+}
+
+// Usage dashboard: records created in [from, to), open ends when unset
+export interface UsageRange {
+  from?: string; // naive UTC datetime, e.g. "2026-03-01T00:00:00"
+  to?: string;
+}
+
+export type UsageGroupBy = 'day' | 'agent' | 'provider' | 'model';
+
+export interface UsageBucket {
+  key: string; // YYYY-MM-DD, agent id, provider or provider/model
+  jobs: number;
+  tool_calls: number;
+  failed_tool_calls: number;
+  input_tokens: number;
+  output_tokens: number;
+  estimated_cost: number; // USD at list prices; unknown models count as free
+}
+
+export interface UsageStats {
+  group_by: UsageGroupBy;
+  buckets: UsageBucket[]; // by day oldest first, otherwise most tokens first
+  tools: { tool: string; calls: number; failures: number }[];
+  totals: UsageBucket;
+}
+
+// The job waits for its provider to be reachable again, then resumes
+export interface JobPendingEvent {
+  type: 'job_pending';
+  job: any;
+  provider: string;
+}
+
+// A provider that can't be reached; calls to it wait
+export interface OfflineProvider {
+  provider: string;
+  since: string;
+  error: string;
+}
+
+// An event a session's jobs streamed, as logged; `id` is the cursor
+export interface SessionEvent {
+  id: number;
+  session_id: string;
+  job_id?: string;
+  event_type: string;
+  payload: string; // the event as emitted, JSON (tokens coalesced)
+  created_at: string;
+}
+
+// Version of the event schema this client reads (EVENT_VERSION in
+// anyagents/src/events.rs). The typed events are generated into
+// lib/generated/ with `npm run gen:events`.
+export const EVENT_VERSION = 1;
+
+// Every event arrives in an envelope: its `type` and fields as always, plus
+// the schema version and the session and job it belongs to. Events from
+// before the envelope have no `version` and are version 1.
+export type EventEnvelope<E extends { type: string } = { type: string }> = E & {
+  version?: number;
+  session_id?: string;
+  job_id?: string;
+};
+
+export function eventVersion(event: { version?: number }): number {
+  return event.version ?? 1;
+}
+
+// Notifications: the inbox, and what notifies where
+export type NotificationEvent = 'job_completed' | 'approval_needed' | 'mail_received' | 'task_failed';
+export type NotificationChannel = 'os' | 'telegram' | 'email';
+
+export interface AppNotification {
+  id: string;
+  event: NotificationEvent;
+  title: string;
+  body: string;
+  agent_id?: string;
+  session_id?: string;
+  is_read: number;
+  created_at: string;
+}
+
+export interface NotificationRule {
+  event: NotificationEvent;
+  enabled: boolean; // off: neither kept in the inbox nor delivered
+  channels: NotificationChannel[];
+}
+
+export interface NotificationPreferences {
+  rules: NotificationRule[];
+  muted_agents: string[];
+  muted_sessions: string[];
+  telegram_config_id?: string;
+  telegram_chat_id?: number;
+  email_account_id?: string;
+  email_to?: string;
+}
+
 export interface MessagingConfig {
   telegram?: {
     enabled: boolean;
@@ -106,6 +363,7 @@ export interface ExecutionSettingsUpdate {
   whitelisted_commands?: string[];
   whitelisted_tools?: string[];
   blacklisted_commands?: string[];
+  policy?: PermissionPolicy | string;
 }
 
 export interface Task {
@@ -117,6 +375,172 @@ export interface Task {
   agent_id?: string;
   created_at: string;
   updated_at: string;
+  schedule?: string; // cron expression ("0 9 * * 1-5") or interval ("every 30m")
+  prompt_template?: string; // minijinja; variables: title, description, now, last_run_at
+  schedule_enabled?: number;
+  next_run_at?: string;
+  last_run_at?: string;
+}
+
+// One execution of a scheduled task; also the payload of `scheduler:run_finished`
+export interface TaskRun {
+  id: string;
+  task_id: string;
+  agent_id: string;
+  session_id?: string;
+  status: 'running' | 'completed' | 'failed';
+  prompt: string;
+  output?: string;
+  error?: string;
+  started_at: string;
+  finished_at?: string;
+}
+
+// Agent triggers: start a run when an event matches
+export type TriggerKind = 'file_change' | 'mail' | 'telegram';
+
+export interface AgentTrigger {
+  id: string;
+  agent_id: string;
+  kind: TriggerKind;
+  pattern?: string; // glob for file_change, regex for mail/telegram
+  prompt_template?: string;
+  enabled: number;
+  max_runs_per_hour: number;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface AgentTriggerUpdate {
+  pattern?: string | null;
+  prompt_template?: string | null;
+  enabled?: number;
+  max_runs_per_hour?: number;
+}
+
+// Payload of the `triggers:fired` event
+export interface TriggerFiredEvent {
+  trigger_id: string;
+  agent_id: string;
+  kind: TriggerKind;
+  session_id: string;
+}
+
+// What a permission request would do, sent along with it
+export type PermissionPayload =
+  | { kind: 'command'; command: string; working_dir?: string }
+  | { kind: 'file_read'; path: string }
+  | { kind: 'file_write'; path: string; created: boolean; diff?: string; truncated: boolean }
+  | { kind: 'file_delete'; path: string }
+  | { kind: 'create_dir'; path: string }
+  | { kind: 'http'; method: string; url: string }
+  | { kind: 'email'; to: string[]; subject: string; body: string }
+  | { kind: 'page_edit'; page: string; operation: 'create' | 'append' | 'update_block'; markdown: string };
+
+// A resolved permission request, as kept in the audit log
+export type PermissionResolution = 'allow' | 'deny' | 'always' | 'mask'; // mask: DLP incident
+
+export interface PermissionAuditEntry {
+  id: string;
+  session_id?: string;
+  tool?: string;
+  permission_type: string;
+  resource?: string;
+  message: string;
+  metadata: string; // JSON object of the request metadata
+  payload?: string; // JSON PermissionPayload
+  resolution: PermissionResolution;
+  decided_by: string; // "app", "cache", "telegram:<user id>", ...
+  requested_at: string;
+  decided_at: string;
+}
+
+export interface PermissionAuditFilter {
+  session_id?: string;
+  agent_id?: string;
+  tool?: string;
+  resolution?: PermissionResolution;
+  since?: string;
+  limit?: number;
+}
+
+// A persisted "always allow" answer
+export type GrantScope = 'session' | 'agent' | 'workspace' | 'global';
+
+export interface PermissionGrant {
+  id: string;
+  scope: GrantScope;
+  scope_id?: string; // session id, agent id or workspace path
+  permission_type: string;
+  resource: string;
+  granted_by: string;
+  created_at: string;
+  expires_at?: string;
+}
+
+// How to import a bundled skill whose name is taken by a different skill;
+// 'ask' fails with the list of conflicts
+export type SkillConflict = 'ask' | 'reuse' | 'rename' | 'replace';
+
+export interface AgentImportOptions {
+  skill_conflict?: SkillConflict;
+  name?: string;
+  workspace_path?: string;
+}
+
+export interface AgentImportReport {
+  agent_id: string;
+  skills_created: string[];
+  skills_reused: string[];
+  skills_replaced: string[];
+  skills_renamed: [string, string][]; // [bundled name, imported as]
+  mcp_servers_created: string[];
+  mcp_servers_reused: string[];
+  missing_secrets: string[]; // "server: secret" to set after import
+}
+
+// Named secret; the value stays in the OS keyring and is never returned
+export interface SecretRecord {
+  name: string; // also the environment variable name
+  description?: string;
+  created_at: string;
+  updated_at: string;
+}
+
+// Prompt templates (see anyagents/src/prompts.rs)
+export interface PromptTemplateVariable {
+  name: string;
+  description: string;
+  example: string; // JSON, used by previews
+}
+
+export interface BuiltinPromptTemplate {
+  name: string; // 'tool_use_system' | 'planning_agent'
+  description: string;
+  variables: PromptTemplateVariable[];
+  body: string;
+}
+
+export type PromptTemplateSource =
+  | { kind: 'agent'; version: number }
+  | { kind: 'global'; version: number }
+  | { kind: 'file'; path: string }
+  | { kind: 'builtin' };
+
+export interface ResolvedPromptTemplate {
+  name: string;
+  source: PromptTemplateSource;
+  body: string;
+}
+
+export interface PromptTemplateVersion {
+  id: string;
+  name: string;
+  agent_id?: string; // absent: every agent
+  version: number;
+  body: string;
+  note?: string;
+  created_at: string;
 }
 
 export interface TaskCreate {
@@ -150,14 +574,321 @@ export interface FederationNode {
 export interface TaskState {
   id: string;
   description: string;
-  status: 'pending' | 'running' | 'completed' | 'failed';
+  status: 'pending' | 'running' | 'completed' | 'failed' | 'skipped';
   result?: string;
+  depends_on?: string[];
+  assignee?: string; // agent id (team mode)
 }
 
 export interface PlanUpdate {
+  plan_id?: string;
   tasks: TaskState[];
 }
 
+// Persisted plans
+export interface PlanRecord {
+  id: string;
+  session_id: string;
+  job_id: string;
+  objective: string;
+  status: 'proposed' | 'running' | 'completed' | 'rejected' | 'interrupted';
+  created_at: string;
+  updated_at: string;
+}
+
+export interface PlanTaskRecord {
+  plan_id: string;
+  task_id: string;
+  description: string;
+  dependencies: string; // JSON array of task ids
+  position: number;
+  status: TaskState['status'];
+  result?: string;
+  updated_at: string;
+  assignee?: string;
+}
+
+// Payload of the `plan_proposed` event (mode 'plan_approval')
+export interface PlanProposedEvent {
+  type: 'plan_proposed';
+  job: any;
+  plan: PlanUpdate;
+}
+
+// Core configuration (config.toml → env → agent execution settings)
+export interface CoreConfig {
+  database_url: string | null;
+  otel_endpoint: string | null;
+  trace_dir: string | null;
+  prompts_dir: string | null;
+  max_parallel_tasks: number;
+}
+
+export type ConfigSource = 'default' | 'file' | 'env' | 'agent';
+
+export interface EffectiveConfig {
+  config: CoreConfig;
+  sources: Record<keyof CoreConfig, ConfigSource>;
+  file: string;
+}
+
+// Result of reload_config, and payload of the `config_changed` event (on the `config` channel)
+export interface ConfigChange {
+  applied: string[];
+  restart_required: string[];
+}
+
+// A tool call a dry run held back
+export interface PlannedCall {
+  agent_id: string;
+  tool: string;
+  args: unknown;
+  summary: string; // "Would ..."
+}
+
+// Payload of the `change_plan_proposed` event, sent after a dry run's job_completed
+export interface ChangePlanProposedEvent {
+  type: 'change_plan_proposed';
+  job_id: string;
+  calls: PlannedCall[];
+}
+
+export interface AppliedCall {
+  tool: string;
+  summary: string;
+  success: boolean;
+  result: unknown;
+}
+
+export interface TaskSpec {
+  id: string;
+  description: string;
+  depends_on: string[];
+  assignee?: string;
+}
+
+// Team mode: a plan task delegated to another agent
+export interface ChildJobStartedEvent {
+  type: 'child_job_started';
+  parent_job_id: string;
+  job: any;
+  agent_id: string;
+  agent_name: string;
+}
+
+export interface ChildJobCompletedEvent {
+  type: 'child_job_completed';
+  parent_job_id: string;
+  job: any;
+  agent_id: string;
+  result?: string;
+}
+
+// Agent bus: typed messages between agents (`agent_bus` event channel)
+export type BusPayload =
+  | { kind: 'task_request'; task: string }
+  | { kind: 'task_result'; result: string; success: boolean }
+  | { kind: 'question'; question: string }
+  | { kind: 'broadcast'; text: string };
+
+export interface BusMessage {
+  id: string;
+  from: string; // agent id or 'user'
+  to?: string; // omitted for broadcasts
+  in_reply_to?: string;
+  payload: BusPayload;
+  created_at: string;
+}
+
+export type BusDelivery = 'delivered' | 'woken' | 'queued';
+
+export interface BusMessageEvent {
+  type: 'bus_message';
+  message: BusMessage;
+  delivery: BusDelivery;
+}
+
+// Webhook ingress: POST /webhooks/<agent_id> on 127.0.0.1:<port>
+export interface WebhookConfig {
+  enabled: boolean;
+  port: number;
+  token?: string;
+}
+
+// Emitted on the session channel and as `webhooks:received`
+export interface WebhookReceivedEvent {
+  type: 'webhook_received';
+  webhook_id: string;
+  agent_id: string;
+  session_id: string;
+  source: string;
+  payload: any;
+}
+
+// A skill's dependencies are being installed on first use
+export interface SkillProvisioningEvent {
+  type: 'skill_provisioning';
+  skill: string;
+  dependencies: SkillDependencies;
+}
+
+export interface SkillProvisioningFailedEvent {
+  type: 'skill_provisioning_failed';
+  skill: string;
+  error: string;
+}
+
+export type RecipeStepStatus = 'running' | 'completed' | 'skipped' | 'failed';
+
+export interface SkillRecipeStepEvent {
+  type: 'skill_recipe_step';
+  skill: string;
+  index: number;
+  step_id: string | null;
+  tool: string;
+  status: RecipeStepStatus;
+}
+
+export interface GeneratedImage {
+  path: string; // relative to the workspace
+  media_type: string;
+  data: string; // base64 preview
+}
+
+export interface ImageGeneratedEvent {
+  type: 'image_generated';
+  prompt: string;
+  provider: 'openai' | 'gemini' | 'stable_diffusion';
+  images: GeneratedImage[];
+}
+
+export interface DictationEventPayload {
+  dictation_id: string;
+  event:
+    | { type: 'partial'; text: string } // open utterance so far
+    | { type: 'segment'; text: string } // finished utterance
+    | { type: 'final'; text: string } // whole transcript, after stop
+    | { type: 'error'; message: string };
+}
+
+// An edit or regenerate rewound the session: drop `removed_ids` and show
+// `message` (the edited text) before the re-run job's events arrive
+export interface HistoryRewoundEvent {
+  type: 'history_rewound';
+  session_id: string;
+  message: any;
+  removed_ids: string[];
+}
+
+export interface ToolsChangedEvent {
+  type: 'tools_changed';
+  added: string[];
+  removed: string[];
+  tools: string[];
+}
+
+// An entry of `fallbacks` in an agent's execution settings
+export interface ModelRoute {
+  provider: string;
+  model: string;
+}
+
+// The model kept failing and the job moved on to the next fallback.
+// Assistant messages record the model that answered in
+// `metadata_json.answered_by` (with `fallback: true` when it wasn't the agent's own).
+export interface ModelSwitchedEvent {
+  type: 'model_switched';
+  from: ModelRoute;
+  to: ModelRoute;
+  reason: string;
+  rate_limited: boolean;
+}
+
+// The critic's verdict on a job; when `retrying`, a corrective run follows
+export interface VerificationEvent {
+  type: 'verification';
+  complete: boolean;
+  critique: string;
+  retrying: boolean;
+}
+
+// The checklist an agent keeps with the `todo` tool (`todo_updated` event)
+export interface TodoItem {
+  id: string;
+  content: string;
+  status: 'pending' | 'in_progress' | 'completed';
+}
+
+export interface TodoUpdatedEvent {
+  type: 'todo_updated';
+  job_id?: string;
+  items: TodoItem[];
+}
+
+export interface JobTodos {
+  job_id: string;
+  session_id: string;
+  items: string; // JSON array of TodoItem
+  updated_at: string;
+}
+
+// A file a job produced, registered by the tool that wrote it
+export interface Artifact {
+  id: string;
+  job_id: string;
+  session_id: string;
+  kind: 'file' | 'image' | 'document' | 'report';
+  title: string;
+  path: string;
+  mime_type: string;
+  file_size: number;
+  preview?: string;
+  created_at: string;
+}
+
+export interface ArtifactCreatedEvent {
+  type: 'artifact_created';
+  job_id: string;
+  artifact: Artifact;
+}
+
+export interface PlanDetails {
+  plan: PlanRecord;
+  tasks: PlanTaskRecord[];
+}
+
+// Workspace changes emitted after a tool step (`workspace_diff` event)
+export interface WorkspaceFileChange {
+  path: string;
+  kind: 'added' | 'modified' | 'deleted';
+  binary: boolean;
+  size?: number;
+  diff?: string;
+  truncated: boolean;
+}
+
+export interface WorkspaceDiffEvent {
+  type: 'workspace_diff';
+  job_id: string;
+  step_id: string;
+  files: WorkspaceFileChange[];
+}
+
+// A tool call waiting for another job to release the workspace or a file in it
+export interface LockHolder {
+  job_id: string;
+  session_id: string;
+  tool: string;
+}
+
+export interface WorkspaceLockWaitingEvent {
+  type: 'workspace_lock_waiting';
+  job_id: string;
+  workspace: string;
+  file?: string;
+  held_by: LockHolder;
+}
+
 export interface Session {
   id: string;
   agent_id: string;
@@ -167,6 +898,8 @@ export interface Session {
   updated_at?: number;
   archived?: number;
   pinned?: number;
+  /** 1 once the user renamed the session; titles are no longer generated */
+  title_locked?: number;
 }
 
 // Telegram Config types (for new Telegram integration)
@@ -178,6 +911,10 @@ export interface TelegramConfig {
   allowed_chat_ids?: string;
   created_at: string;
   updated_at: string;
+  /** Answer voice notes with a spoken reply as well as text */
+  voice_replies: number;
+  /** JSON object of group chat id -> user ids allowed to address the bot there; [] lets every member in */
+  group_allowlists?: string;
 }
 
 export interface TelegramBotStatus {
@@ -185,6 +922,60 @@ export interface TelegramBotStatus {
   is_running: boolean;
 }
 
+export interface DiscordConfig {
+  id: string;
+  bot_token: string;
+  agent_id: string;
+  is_active: number;
+  /** Comma separated channel ids the bot answers in; all when unset */
+  allowed_channel_ids?: string;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface DiscordBotStatus {
+  config_id: string;
+  is_running: boolean;
+}
+
+export interface SlackConfig {
+  id: string;
+  /** App-level token (xapp-) for Socket Mode */
+  app_token: string;
+  /** Bot token (xoxb-) for the Web API */
+  bot_token: string;
+  agent_id: string;
+  is_active: number;
+  /** Comma separated channel ids the bot answers in; all when unset */
+  allowed_channel_ids?: string;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface SlackBotStatus {
+  config_id: string;
+  is_running: boolean;
+}
+
+export interface MatrixConfig {
+  id: string;
+  /** e.g. https://matrix.org */
+  homeserver_url: string;
+  /** Access token of the bot's own Matrix account */
+  access_token: string;
+  agent_id: string;
+  is_active: number;
+  /** Comma separated room ids the bot answers in; all when unset */
+  allowed_room_ids?: string;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface MatrixBotStatus {
+  config_id: string;
+  is_running: boolean;
+}
+
 // Skill types
 export interface AgentSkill {
   id: string;
@@ -202,6 +993,66 @@ export interface AgentSkill {
   requires_sandbox: number;
   sandbox_config?: string;
   execution_mode: string; // "sandbox", "direct", "flexible"
+  triggers?: string; // JSON array: keywords, "regex:<pattern>" or "semantic:<phrase>"
+  dependencies?: string; // JSON SkillDependencies
+  installed_version?: string; // set for skills installed from the remote marketplace
+  invocation?: string; // JSON SkillInvocation, for skills with structured parameters
+  examples?: string; // JSON SkillExample[]
+  recipe?: string; // JSON SkillRecipe, for composite skills
+}
+
+// SKILL.md `examples:` entry, run by `test_skill`
+export interface SkillExample {
+  name: string;
+  args: Record<string, any>; // { args: "<command>" } for free-form skills
+  expect_exit: number;
+  expect_stdout?: string;
+}
+
+export interface SkillExampleResult {
+  name: string;
+  passed: boolean;
+  command?: string;
+  exit_code?: number;
+  stdout: string;
+  stderr: string;
+  duration_ms: number;
+  failure?: string;
+}
+
+export interface SkillTestReport {
+  skill: string;
+  runtime?: 'wasm' | 'micro_vm' | 'docker' | 'local';
+  dry_run: boolean;
+  passed: boolean;
+  results: SkillExampleResult[];
+}
+
+// SKILL.md `parameters` (as a JSON schema) and the `command` template they fill in
+export interface SkillInvocation {
+  parameters: Record<string, any>;
+  command: string;
+}
+
+// SKILL.md `recipe:` — tool calls run in order instead of a single command
+export interface SkillRecipe {
+  parameters: Record<string, any>;
+  steps: RecipeStep[];
+}
+
+export interface RecipeStep {
+  id?: string; // later steps read the result as `steps.<id>`
+  tool: 'filesystem' | 'bash' | 'http';
+  args: Record<string, any>; // string values are templates
+  when?: string;
+  continue_on_error: boolean;
+}
+
+// Runtime dependencies installed on a skill's first use
+export interface SkillDependencies {
+  pip: string[];
+  npm: string[];
+  binaries: string[]; // must already be installed
 }
 
 export interface MarketplaceSkill {
@@ -214,6 +1065,38 @@ export interface MarketplaceSkill {
   is_installed: boolean;
 }
 
+// Remote marketplace: a signed JSON index (Ed25519 signature at `<index_url>.sig`)
+export interface SkillMarketplaceConfig {
+  index_url?: string; // must be https://
+  public_key?: string; // hex Ed25519 public key
+}
+
+export interface RemoteSkillVersion {
+  version: string;
+  url: string;
+  sha256: string;
+  changelog?: string;
+}
+
+export interface RemoteSkillListing {
+  name: string;
+  display_title?: string;
+  description: string;
+  category?: string;
+  versions: RemoteSkillVersion[];
+  latest_version?: string;
+  installed_skill_id?: string;
+  installed_version?: string;
+}
+
+export interface SkillUpdate {
+  skill_id: string;
+  name: string;
+  installed_version: string;
+  latest_version: string;
+  changelog?: string;
+}
+
 export interface SkillFile {
   id: string;
   skill_id: string;
@@ -227,11 +1110,14 @@ export interface SkillFile {
 export interface McpServer {
   id: string;
   name: string;
-  server_type: 'stdio' | 'sse';
+  server_type: 'stdio' | 'sse' | 'http';
   command?: string;
   args?: string[];
   env?: Record<string, string>;
   url?: string;
+  headers?: Record<string, string>; // sse/http only, e.g. Authorization
+  secret_names: string[]; // referenced as ${SECRET:name}; values stay in the OS keyring
+  oauth?: McpOAuthConfig;
   is_enabled: boolean;
   template_id?: string;
   created_at: number;
@@ -241,23 +1127,57 @@ export interface McpServer {
 
 export interface McpServerUpdate {
   name?: string;
-  server_type?: 'stdio' | 'sse';
+  server_type?: 'stdio' | 'sse' | 'http';
   command?: string;
   args?: string[];
   env?: Record<string, string>;
   url?: string;
+  headers?: Record<string, string>;
+  oauth?: McpOAuthConfig;
   is_enabled?: boolean;
 }
 
+// OAuth device-code flow for an MCP server
+export interface McpOAuthConfig {
+  client_id: string;
+  device_authorization_url: string;
+  token_url: string;
+  scopes?: string[];
+  token_secret?: string; // default "oauth_token"
+}
+
+export interface DeviceAuthorization {
+  device_code: string;
+  user_code: string;
+  verification_uri: string;
+  verification_uri_complete?: string;
+  expires_in: number;
+  interval: number;
+}
+
+// Payload of the `mcp:oauth_finished` event
+export interface McpOAuthFinishedEvent {
+  server_id: string;
+  error?: string;
+}
+
+export interface McpToolInfo {
+  name: string;
+  description?: string;
+  inputSchema: any;
+}
+
 export interface McpTemplate {
   id: string;
   name: string;
   description: string;
-  server_type: 'stdio' | 'sse';
+  server_type: 'stdio' | 'sse' | 'http';
   command?: string;
   args?: string[];
   env?: Record<string, string>;
   url?: string;
+  headers?: Record<string, string>;
+  oauth?: McpOAuthConfig;
 }
 
 // Mail types
@@ -272,22 +1192,259 @@ export interface MailThread {
   last_sender_name?: string;
   last_sender_avatar?: string;
   message_count?: number;
+  labels?: MailLabel[];
+}
+
+export interface MailLabel {
+  id: string;
+  name: string;
+  color?: string;
+  created_at: string;
+}
+
+// Filters for searchMail; dates are YYYY-MM-DD or ISO timestamps
+export interface MailSearchFilters {
+  query?: string;
+  folder?: 'inbox' | 'sent' | 'all';
+  account_id?: string;
+  sender?: string; // agent name or id, part of an email address, or 'user'
+  since?: string;
+  until?: string;
+  label?: string; // label name or id
+  is_archived?: boolean;
+  unread_only?: boolean;
+  limit?: number;
+  offset?: number;
+}
+
+export interface MailSearchResults {
+  threads: MailThread[];
+  total: number;
+}
+
+// Global search: what a hit opens
+export type SearchTarget =
+  | { kind: 'session'; session_id: string }
+  | { kind: 'message'; session_id: string; message_id: string }
+  | { kind: 'page'; page_id: string }
+  | { kind: 'block'; page_id: string; block_id: string }
+  | { kind: 'mail'; thread_id: string; message_id: string };
+
+export type SearchHit = SearchTarget & {
+  title: string | null; // the session, page or thread the hit is in
+  snippet: string; // matches wrapped in <mark>; escape the rest before rendering
+  rank: number; // lower is better
+};
+
+export interface MailAttachment {
+  id: string;
+  message_id: string;
+  file_name: string;
+  mime_type: string;
+  file_size: number;
+  storage_path: string;
+  created_at: string;
+}
+
+export interface MailRecipient {
+  id: string;
+  message_id: string;
+  kind: 'to' | 'cc' | 'bcc';
+  recipient_type: 'user' | 'agent' | 'external';
+  recipient_agent_id?: string;
+  external_address?: string;
+  created_at: string;
+}
+
+/** Agent ids or names, group names, email addresses or 'user' */
+export interface MailAddressing {
+  to?: string[];
+  cc?: string[];
+  bcc?: string[];
+}
+
+export interface MailGroup {
+  id: string;
+  name: string;
+  description?: string;
+  member_ids: string[];
+  created_at: string;
+  updated_at: string;
 }
 
 export interface MailMessage {
   id: string;
   thread_id: string;
-  sender_type: 'user' | 'agent';
+  sender_type: 'user' | 'agent' | 'external';
   sender_agent_id?: string;
   sender_name?: string;
   sender_avatar?: string;
-  recipient_type: 'user' | 'agent';
+  recipient_type: 'user' | 'agent' | 'external';
   recipient_agent_id?: string;
+  /** Real address of an external sender or recipient */
+  external_address?: string;
   content: string;
   created_at: string;
+  attachments: MailAttachment[];
+  recipients: MailRecipient[];
+}
+
+// Email bridge: an agent's real mailbox (the password lives in the OS keyring)
+export interface EmailAccount {
+  id: string;
+  agent_id: string;
+  email_address: string;
+  display_name?: string;
+  username: string;
+  smtp_host: string;
+  smtp_port: number;
+  imap_host: string;
+  imap_port: number;
+  imap_folder: string;
+  /** 0 keeps replies to outside senders as drafts */
+  deliver_outbound: number;
+  poll_interval_seconds: number;
+  last_seen_uid: number;
+  is_active: number;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface EmailAccountInput {
+  agent_id: string;
+  email_address: string;
+  display_name?: string;
+  username?: string;
+  password: string;
+  smtp_host: string;
+  smtp_port?: number;
+  imap_host: string;
+  imap_port?: number;
+  imap_folder?: string;
+  deliver_outbound?: boolean;
+  poll_interval_seconds?: number;
+}
+
+export type EmailAccountChanges = Partial<Omit<EmailAccountInput, 'agent_id'>> & {
+  is_active?: boolean;
+};
+
+export interface EmailAccountTestResponse {
+  imap_ok: boolean;
+  smtp_ok: boolean;
+  error?: string;
 }
 
 // API Methods
+// Maintenance types
+export interface IntegrityReport {
+  temp_messages: number;
+  orphaned_messages: number;
+  orphaned_sessions: number;
+  orphaned_blocks: number;
+  orphaned_attachments: number;
+  orphaned_skill_files: number;
+  orphaned_skill_assignments: number;
+  orphaned_mail_messages: number;
+  orphaned_attachment_files: number;
+}
+
+// A chat model of a provider, from its model listing
+export interface ModelInfo {
+  id: string;
+  name: string;
+  context_window: number;
+  max_output: number;
+  vision: boolean;
+  tools: boolean;
+}
+
+export interface ModelFilter {
+  vision?: boolean;
+  tools?: boolean;
+  min_context?: number;
+}
+
+export type HealthStatus = 'ok' | 'warning' | 'error' | 'skipped';
+
+export interface HealthCheckItem {
+  name: string;
+  status: HealthStatus;
+  message: string;
+}
+
+export interface HealthReport {
+  healthy: boolean;
+  checks: HealthCheckItem[];
+  // True until completeOnboarding is called
+  first_run: boolean;
+}
+
+// Result of SQLite's integrity check
+export interface DatabaseCheck {
+  ok: boolean;
+  problems: string[];
+}
+
+export interface DatabaseBackup {
+  file_name: string;
+  path: string;
+  size_bytes: number;
+  created_at: string;
+}
+
+// Every rule is off when its days are null
+export interface RetentionPolicy {
+  archive_idle_days: number | null;
+  message_days: number | null;
+  tool_event_days: number | null;
+  vacuum: boolean;
+}
+
+export interface RetentionReport {
+  archived_sessions: number;
+  deleted_messages: number;
+  deleted_tool_events: number;
+  deleted_session_events: number;
+  reclaimed_bytes: number | null;
+}
+
+export interface TableStorage {
+  name: string;
+  rows: number;
+  bytes: number;
+}
+
+export interface StorageStats {
+  total_bytes: number;
+  free_bytes: number;
+  /** Largest first */
+  tables: TableStorage[];
+}
+
+export interface EncryptionStatus {
+  /** Built with SQLCipher */
+  supported: boolean;
+  encrypted: boolean;
+  /** Happens on the next start of the app */
+  pending: 'encrypt' | 'decrypt' | null;
+}
+
+export interface BackupSchedule {
+  enabled: boolean;
+  interval_hours: number;
+  /** Older backups beyond this many are deleted */
+  keep: number;
+}
+
+// Git checkpoint (ref under refs/anycowork/)
+export interface Checkpoint {
+  ref_name: string;
+  commit: string;
+  message: string;
+  created_at: number;
+}
+
 export const anycoworkApi = {
   // Agents
   listAgents: async (_status?: 'active' | 'inactive' | 'error', _limit?: number) => {
@@ -305,13 +1462,26 @@ export const anycoworkApi = {
 
   // Chat
   // Note: Rust 'chat' command returns string, not stream yet.
-  sendMessage: async (sessionId: string, message: string, mode?: string, model?: string) => {
-    return invoke<string>('chat', { sessionId: sessionId, message, mode, model });
+  sendMessage: async (sessionId: string, message: string, mode?: string, model?: string, images?: ImageAttachment[]) => {
+    return invoke<string>('chat', { sessionId: sessionId, message, mode, model, images });
   },
 
-  approveAction: async (stepId: string) => {
+  // Rewrite a user message and re-run the chat from it
+  editMessage: async (messageId: string, content: string, mode?: string, model?: string) =>
+    invoke<string>('edit_message', { messageId, content, mode, model }),
+
+  // Re-send a user message, or re-answer the prompt before any other message
+  regenerateFrom: async (messageId: string, mode?: string, model?: string) =>
+    invoke<string>('regenerate_from', { messageId, mode, model }),
+
+  approveAction: async (stepId: string, grant?: { scope: GrantScope; ttlSecs?: number }) => {
     console.log("Approving action with stepId:", stepId);
-    return invoke('approve_action', { stepId: stepId, step_id: stepId });
+    return invoke('approve_action', {
+      stepId: stepId,
+      step_id: stepId,
+      scope: grant?.scope ?? null,
+      ttlSecs: grant?.ttlSecs ?? null,
+    });
   },
 
   rejectAction: async (stepId: string) => {
@@ -328,6 +1498,10 @@ export const anycoworkApi = {
     messaging: { telegram: { enabled: false } },
     gateway: { host: 'localhost', port: 0, url: '' }
   }),
+  // Search chats, pages and mail, best hits first
+  globalSearch: async (query: string, limit?: number) =>
+    invoke<SearchHit[]>('global_search', { query, limit: limit ?? null }),
+
   // Sessions
   listSessions: async () => {
     try {
@@ -351,6 +1525,12 @@ export const anycoworkApi = {
     return invoke<any[]>('get_session_messages', { sessionId: sessionId });
   },
 
+  // JSON Lines archive of a session, for bug reports and other installations
+  exportSession: async (sessionId: string, path: string) =>
+    invoke<void>('export_session', { sessionId, path }),
+  importSession: async (path: string, agentId?: string) =>
+    invoke<Session>('import_session', { path, agentId }),
+
   getGatewayStatus: async () => ({ status: 'ok', connected_clients: 0, uptime: 0 }),
   // Messaging
   getMessagingStatus: async () => ({
@@ -393,6 +1573,101 @@ export const anycoworkApi = {
     return { success: true };
   },
 
+  setTaskSchedule: async (taskId: string, schedule: string | null, promptTemplate: string | null, enabled: boolean) =>
+    invoke<Task>('set_task_schedule', {
+      taskId,
+      scheduleVal: schedule,
+      promptTemplateVal: promptTemplate,
+      enabled
+    }),
+
+  listTaskRuns: async (taskId: string, limit?: number) =>
+    invoke<TaskRun[]>('list_task_runs', { taskId, limit: limit ?? null }),
+
+  runTaskNow: async (taskId: string) => invoke<void>('run_task_now', { taskId }),
+
+  // Permission audit log
+  listPermissionAudit: async (filter: PermissionAuditFilter = {}) =>
+    invoke<PermissionAuditEntry[]>('list_permission_audit', { filter }),
+  getPermissionAuditEntry: async (requestId: string) =>
+    invoke<PermissionAuditEntry>('get_permission_audit_entry', { requestId }),
+  validatePermissionPolicy: async (policy: string) =>
+    invoke<void>('validate_permission_policy', { policy }),
+  // Checks a YAML/JSON policy for the `dlp_policy` setting
+  validateDlpPolicy: async (policy: string) =>
+    invoke<void>('validate_dlp_policy', { policy }),
+  listPermissionGrants: async (includeExpired = false) =>
+    invoke<PermissionGrant[]>('list_permission_grants', { includeExpired }),
+  revokePermissionGrant: async (grantId: string) =>
+    invoke<void>('revoke_permission_grant', { grantId }),
+
+  // Secrets
+  listSecrets: async () => invoke<SecretRecord[]>('list_secrets'),
+  setSecret: async (name: string, value: string, description?: string) =>
+    invoke<SecretRecord>('set_secret', { name, value, description }),
+  deleteSecret: async (name: string) => invoke<void>('delete_secret', { name }),
+  listAgentSecrets: async (agentId: string) =>
+    invoke<string[]>('list_agent_secrets', { agentId }),
+  grantAgentSecret: async (agentId: string, name: string) =>
+    invoke<void>('grant_agent_secret', { agentId, name }),
+  revokeAgentSecret: async (agentId: string, name: string) =>
+    invoke<void>('revoke_agent_secret', { agentId, name }),
+
+  // Prompt templates; omit agentId for the override every agent uses
+  listPromptTemplates: async () => invoke<BuiltinPromptTemplate[]>('list_prompt_templates'),
+  getPromptTemplate: async (name: string, agentId?: string) =>
+    invoke<ResolvedPromptTemplate>('get_prompt_template', { name, agentId }),
+  listPromptTemplateVersions: async (name: string, agentId?: string) =>
+    invoke<PromptTemplateVersion[]>('list_prompt_template_versions', { name, agentId }),
+  savePromptTemplate: async (name: string, body: string, agentId?: string, note?: string) =>
+    invoke<PromptTemplateVersion>('save_prompt_template', { name, agentId, body, note }),
+  restorePromptTemplateVersion: async (name: string, version: number, agentId?: string) =>
+    invoke<PromptTemplateVersion>('restore_prompt_template_version', { name, agentId, version }),
+  resetPromptTemplate: async (name: string, agentId?: string) =>
+    invoke<void>('reset_prompt_template', { name, agentId }),
+  previewPromptTemplate: async (
+    name: string,
+    options: { agentId?: string; body?: string; variables?: Record<string, unknown> } = {}
+  ) =>
+    invoke<string>('preview_prompt_template', {
+      name,
+      agentId: options.agentId,
+      body: options.body,
+      variables: options.variables,
+    }),
+
+  // Agent triggers
+  listAgentTriggers: async (agentId: string) =>
+    invoke<AgentTrigger[]>('list_agent_triggers', { agentId }),
+  createAgentTrigger: async (
+    agentId: string,
+    kind: TriggerKind,
+    options: { pattern?: string; promptTemplate?: string; maxRunsPerHour?: number } = {}
+  ) =>
+    invoke<AgentTrigger>('create_agent_trigger', {
+      agentId,
+      kind,
+      pattern: options.pattern ?? null,
+      promptTemplate: options.promptTemplate ?? null,
+      maxRunsPerHour: options.maxRunsPerHour ?? null
+    }),
+  updateAgentTrigger: async (triggerId: string, data: AgentTriggerUpdate) =>
+    invoke<AgentTrigger>('update_agent_trigger', { triggerId, data }),
+  deleteAgentTrigger: async (triggerId: string) =>
+    invoke<void>('delete_agent_trigger', { triggerId }),
+
+  // Webhooks
+  getWebhookConfig: async () => invoke<WebhookConfig>('get_webhook_config'),
+  updateWebhookConfig: async (
+    enabled: boolean,
+    options: { port?: number; regenerateToken?: boolean } = {}
+  ) =>
+    invoke<WebhookConfig>('update_webhook_config', {
+      enabled,
+      port: options.port ?? null,
+      regenerateToken: options.regenerateToken ?? null
+    }),
+
   // Configuration
   getAIConfig: async () => {
     return invoke<AIConfig>('get_ai_config');
@@ -400,6 +1675,53 @@ export const anycoworkApi = {
   updateAIConfig: async (config: any) => {
     return invoke('update_ai_config', { config });
   },
+  getRateLimits: async () => {
+    return invoke<RateLimits>('get_rate_limits');
+  },
+  setRateLimits: async (limits: RateLimits) => {
+    return invoke('set_rate_limits', { limits });
+  },
+  getEventChannelConfig: async () => invoke<EventChannelConfig>('get_event_channel_config'),
+  setEventChannelConfig: async (config: EventChannelConfig) => invoke('set_event_channel_config', { config }),
+  getEffectiveConfig: async (agentId?: string) =>
+    invoke<EffectiveConfig>('get_effective_config', { agentId: agentId ?? null }),
+  reloadConfig: async () => invoke<ConfigChange>('reload_config'),
+  getJobLimits: async () => {
+    return invoke<JobLimits>('get_job_limits');
+  },
+  setJobLimits: async (limits: JobLimits) => {
+    return invoke('set_job_limits', { limits });
+  },
+
+  // Job queue
+  listJobs: async () => invoke<QueuedJob[]>('list_jobs'),
+  cancelQueuedJob: async (jobId: string) => invoke('cancel_queued_job', { jobId }),
+  getOfflineProviders: async () => invoke<OfflineProvider[]>('get_offline_providers'),
+  // Files jobs produced (reports, documents, images)
+  getJobArtifacts: async (jobId: string) => invoke<Artifact[]>('get_job_artifacts', { jobId }),
+  getSessionArtifacts: async (sessionId: string) =>
+    invoke<Artifact[]>('get_session_artifacts', { sessionId }),
+  getUsageStats: async (groupBy: UsageGroupBy, range?: UsageRange) =>
+    invoke<UsageStats>('get_usage_stats', { range, groupBy }),
+  // Logged session events, to rebuild job progress after a reload
+  getSessionEvents: async (sessionId: string, since?: number, limit?: number) =>
+    invoke<SessionEvent[]>('get_session_events', { sessionId, since, limit }),
+  getJobEvents: async (jobId: string) => invoke<SessionEvent[]>('get_job_events', { jobId }),
+  // Notifications; new ones also arrive on the "notification" event
+  getNotifications: async (unreadOnly?: boolean, limit?: number) =>
+    invoke<AppNotification[]>('get_notifications', { unreadOnly, limit }),
+  getUnreadNotificationCount: async () => invoke<number>('get_unread_notification_count'),
+  markNotificationsRead: async (ids: string[], read = true) =>
+    invoke<number>('mark_notifications_read', { ids, read }),
+  markAllNotificationsRead: async () => invoke<number>('mark_all_notifications_read'),
+  clearReadNotifications: async () => invoke<number>('clear_read_notifications'),
+  getNotificationPreferences: async () => invoke<NotificationPreferences>('get_notification_preferences'),
+  setNotificationPreferences: async (preferences: NotificationPreferences) =>
+    invoke('set_notification_preferences', { preferences }),
+  muteAgentNotifications: async (agentId: string, muted: boolean) =>
+    invoke('mute_agent_notifications', { agentId, muted }),
+  muteSessionNotifications: async (sessionId: string, muted: boolean) =>
+    invoke('mute_session_notifications', { sessionId, muted }),
   // Messaging (Bridge for UI single-config view)
   getMessagingConfig: async () => {
     const configs = await invoke<any[]>('get_telegram_configs');
@@ -472,11 +1794,13 @@ export const anycoworkApi = {
   getTelegramConfig: async (configId: string) => {
     return invoke<TelegramConfig>('get_telegram_config', { config_id: configId });
   },
-  createTelegramConfig: async (botToken: string, agentId: string, allowedChatIds?: string) => {
+  createTelegramConfig: async (botToken: string, agentId: string, allowedChatIds?: string, voiceReplies?: boolean, groupAllowlists?: string) => {
     return invoke<TelegramConfig>('create_telegram_config', {
       bot_token: botToken,
       agent_id: agentId,
-      allowed_chat_ids: allowedChatIds
+      allowed_chat_ids: allowedChatIds,
+      voice_replies: voiceReplies,
+      group_allowlists: groupAllowlists,
     });
   },
   updateTelegramConfig: async (configId: string, data: {
@@ -484,6 +1808,8 @@ export const anycoworkApi = {
     new_agent_id?: string;
     new_is_active?: number;
     new_allowed_chat_ids?: string;
+    new_voice_replies?: boolean;
+    new_group_allowlists?: string;
   }) => {
     return invoke<TelegramConfig>('update_telegram_config', {
       config_id: configId,
@@ -491,6 +1817,8 @@ export const anycoworkApi = {
       new_agent_id: data.new_agent_id,
       new_is_active: data.new_is_active,
       new_allowed_chat_ids: data.new_allowed_chat_ids,
+      new_voice_replies: data.new_voice_replies,
+      new_group_allowlists: data.new_group_allowlists,
     });
   },
   deleteTelegramConfig: async (configId: string) => {
@@ -509,6 +1837,155 @@ export const anycoworkApi = {
     return invoke<string[]>('get_running_telegram_bots');
   },
 
+  // Discord Bot Config (same shape as Telegram, so connector screens can share code)
+  listDiscordConfigs: async () => {
+    return invoke<DiscordConfig[]>('get_discord_configs');
+  },
+  getDiscordConfig: async (configId: string) => {
+    return invoke<DiscordConfig>('get_discord_config', { config_id: configId });
+  },
+  createDiscordConfig: async (botToken: string, agentId: string, allowedChannelIds?: string) => {
+    return invoke<DiscordConfig>('create_discord_config', {
+      bot_token: botToken,
+      agent_id: agentId,
+      allowed_channel_ids: allowedChannelIds,
+    });
+  },
+  updateDiscordConfig: async (configId: string, data: {
+    new_bot_token?: string;
+    new_agent_id?: string;
+    new_is_active?: number;
+    new_allowed_channel_ids?: string;
+  }) => {
+    return invoke<DiscordConfig>('update_discord_config', {
+      config_id: configId,
+      new_bot_token: data.new_bot_token,
+      new_agent_id: data.new_agent_id,
+      new_is_active: data.new_is_active,
+      new_allowed_channel_ids: data.new_allowed_channel_ids,
+    });
+  },
+  deleteDiscordConfig: async (configId: string) => {
+    return invoke('delete_discord_config', { config_id: configId });
+  },
+  startDiscordBot: async (configId: string) => {
+    return invoke('start_discord_bot', { config_id: configId });
+  },
+  stopDiscordBot: async (configId: string) => {
+    return invoke('stop_discord_bot', { config_id: configId });
+  },
+  getDiscordBotStatus: async (configId: string) => {
+    return invoke<DiscordBotStatus>('get_discord_bot_status', { config_id: configId });
+  },
+  getRunningDiscordBots: async () => {
+    return invoke<string[]>('get_running_discord_bots');
+  },
+
+  // Slack Bot Config (Socket Mode: an app token plus a bot token)
+  listSlackConfigs: async () => {
+    return invoke<SlackConfig[]>('get_slack_configs');
+  },
+  getSlackConfig: async (configId: string) => {
+    return invoke<SlackConfig>('get_slack_config', { config_id: configId });
+  },
+  createSlackConfig: async (appToken: string, botToken: string, agentId: string, allowedChannelIds?: string) => {
+    return invoke<SlackConfig>('create_slack_config', {
+      app_token: appToken,
+      bot_token: botToken,
+      agent_id: agentId,
+      allowed_channel_ids: allowedChannelIds,
+    });
+  },
+  updateSlackConfig: async (configId: string, data: {
+    new_app_token?: string;
+    new_bot_token?: string;
+    new_agent_id?: string;
+    new_is_active?: number;
+    new_allowed_channel_ids?: string;
+  }) => {
+    return invoke<SlackConfig>('update_slack_config', {
+      config_id: configId,
+      new_app_token: data.new_app_token,
+      new_bot_token: data.new_bot_token,
+      new_agent_id: data.new_agent_id,
+      new_is_active: data.new_is_active,
+      new_allowed_channel_ids: data.new_allowed_channel_ids,
+    });
+  },
+  deleteSlackConfig: async (configId: string) => {
+    return invoke('delete_slack_config', { config_id: configId });
+  },
+  startSlackBot: async (configId: string) => {
+    return invoke('start_slack_bot', { config_id: configId });
+  },
+  stopSlackBot: async (configId: string) => {
+    return invoke('stop_slack_bot', { config_id: configId });
+  },
+  getSlackBotStatus: async (configId: string) => {
+    return invoke<SlackBotStatus>('get_slack_bot_status', { config_id: configId });
+  },
+  getRunningSlackBots: async () => {
+    return invoke<string[]>('get_running_slack_bots');
+  },
+
+  // Matrix Bot Config (a bot account's access token on a homeserver)
+  listMatrixConfigs: async () => {
+    return invoke<MatrixConfig[]>('get_matrix_configs');
+  },
+  getMatrixConfig: async (configId: string) => {
+    return invoke<MatrixConfig>('get_matrix_config', { config_id: configId });
+  },
+  createMatrixConfig: async (homeserverUrl: string, accessToken: string, agentId: string, allowedRoomIds?: string) => {
+    return invoke<MatrixConfig>('create_matrix_config', {
+      homeserver_url: homeserverUrl,
+      access_token: accessToken,
+      agent_id: agentId,
+      allowed_room_ids: allowedRoomIds,
+    });
+  },
+  updateMatrixConfig: async (configId: string, data: {
+    new_homeserver_url?: string;
+    new_access_token?: string;
+    new_agent_id?: string;
+    new_is_active?: number;
+    new_allowed_room_ids?: string;
+  }) => {
+    return invoke<MatrixConfig>('update_matrix_config', {
+      config_id: configId,
+      new_homeserver_url: data.new_homeserver_url,
+      new_access_token: data.new_access_token,
+      new_agent_id: data.new_agent_id,
+      new_is_active: data.new_is_active,
+      new_allowed_room_ids: data.new_allowed_room_ids,
+    });
+  },
+  deleteMatrixConfig: async (configId: string) => {
+    return invoke('delete_matrix_config', { config_id: configId });
+  },
+  startMatrixBot: async (configId: string) => {
+    return invoke('start_matrix_bot', { config_id: configId });
+  },
+  stopMatrixBot: async (configId: string) => {
+    return invoke('stop_matrix_bot', { config_id: configId });
+  },
+  getMatrixBotStatus: async (configId: string) => {
+    return invoke<MatrixBotStatus>('get_matrix_bot_status', { config_id: configId });
+  },
+  getRunningMatrixBots: async () => {
+    return invoke<string[]>('get_running_matrix_bots');
+  },
+
+  // Agent bundles (shareable agent configuration with skills and MCP servers)
+  exportAgentBundle: async (agentId: string, path: string) =>
+    invoke<void>('export_agent_bundle', { agentId, path }),
+  importAgentBundle: async (path: string, options?: AgentImportOptions) =>
+    invoke<AgentImportReport>('import_agent_bundle', { path, options }),
+
+  /** Newest first */
+  getAgentHistory: async (agentId: string) => invoke<AgentVersion[]>('get_agent_history', { agentId }),
+  rollbackAgent: async (agentId: string, version: number) =>
+    invoke<Agent>('rollback_agent', { agentId, version }),
+
   // Agent Definitions
   getAgent: async (id: string) => ({ id, name: 'Agent', description: '', system_prompt: '' }),
   updateAgent: async (agentId: string, data: any) => {
@@ -553,13 +2030,14 @@ export const anycoworkApi = {
   getSkill: async (skillId: string) => {
     return invoke<AgentSkill>('get_skill', { skillId });
   },
-  createSkill: async (name: string, displayTitle: string, description: string, skillContent: string, additionalFilesJson?: string) => {
+  createSkill: async (name: string, displayTitle: string, description: string, skillContent: string, additionalFilesJson?: string, triggers?: string[]) => {
     return invoke<AgentSkill>('create_skill', {
       nameParam: name,
       displayTitle,
       description,
       skillContent,
-      additionalFilesJson
+      additionalFilesJson,
+      triggers
     });
   },
   updateSkill: async (skillId: string, data: {
@@ -572,6 +2050,8 @@ export const anycoworkApi = {
     category?: string;
     requiresSandbox?: boolean;
     sandboxConfig?: string;
+    triggers?: string[];
+    dependencies?: SkillDependencies;
   }) => {
     return invoke<AgentSkill>('update_skill', {
       skillId,
@@ -584,6 +2064,8 @@ export const anycoworkApi = {
       category: data.category,
       requiresSandbox: data.requiresSandbox,
       sandboxConfig: data.sandboxConfig,
+      triggers: data.triggers,
+      dependencies: data.dependencies,
     });
   },
   deleteSkill: async (skillId: string) => {
@@ -595,6 +2077,14 @@ export const anycoworkApi = {
   getSkillFiles: async (skillId: string) => {
     return invoke<SkillFile[]>('get_skill_files', { skillId });
   },
+  // Runs the skill's SKILL.md examples; dryRun only validates and renders commands
+  testSkill: async (skillId: string, options?: { dryRun?: boolean; executionMode?: string }) => {
+    return invoke<SkillTestReport>('test_skill', {
+      skillId,
+      dryRun: options?.dryRun,
+      executionMode: options?.executionMode,
+    });
+  },
 
   // Skill Import/Marketplace
   listMarketplaceSkills: async () => {
@@ -603,6 +2093,22 @@ export const anycoworkApi = {
   installSkill: async (skillDirName: string) => {
     return invoke<AgentSkill>('install_marketplace_skill', { skillDirName });
   },
+  getSkillMarketplaceConfig: async () => {
+    return invoke<SkillMarketplaceConfig>('get_skill_marketplace_config', {});
+  },
+  updateSkillMarketplaceConfig: async (config: SkillMarketplaceConfig) => {
+    return invoke<void>('update_skill_marketplace_config', { config });
+  },
+  listRemoteSkills: async () => {
+    return invoke<RemoteSkillListing[]>('list_remote_skills', {});
+  },
+  // Installs, or upgrades in place when already installed; defaults to the latest version
+  installRemoteSkill: async (name: string, version?: string) => {
+    return invoke<AgentSkill>('install_remote_skill', { nameParam: name, version });
+  },
+  checkSkillUpdates: async () => {
+    return invoke<SkillUpdate[]>('check_skill_updates', {});
+  },
   importSkillFromDirectory: async (directoryPath: string) => {
     return invoke<AgentSkill>('import_skill_from_directory', { directoryPath });
   },
@@ -614,6 +2120,9 @@ export const anycoworkApi = {
   checkDockerAvailable: async () => {
     return invoke<boolean>('check_docker_available', {});
   },
+  checkMicrovmAvailable: async () => {
+    return invoke<boolean>('check_microvm_available', {});
+  },
 
   // Agent Scope
   updateAgentScope: async (agentId: string, scopeType: string, workspacePath?: string) => {
@@ -636,6 +2145,18 @@ export const anycoworkApi = {
   deleteMCPServer: async (id: string) => {
     return invoke<void>('delete_mcp_server', { id });
   },
+  testMCPServer: async (id: string) => {
+    return invoke<McpToolInfo[]>('test_mcp_server', { id });
+  },
+  setMCPSecret: async (serverId: string, name: string, value: string) => {
+    return invoke<McpServer>('set_mcp_secret', { serverId, name, value });
+  },
+  deleteMCPSecret: async (serverId: string, name: string) => {
+    return invoke<McpServer>('delete_mcp_secret', { serverId, name });
+  },
+  startMCPOAuth: async (serverId: string) => {
+    return invoke<DeviceAuthorization>('start_mcp_oauth', { serverId });
+  },
   getMCPTemplates: async () => {
     return invoke<McpTemplate[]>('get_mcp_templates');
   },
@@ -668,21 +2189,89 @@ export const anycoworkApi = {
   getAvailableModels: async () => {
     return invoke('get_available_models');
   },
+  /** Without apiKey, checks the saved key of the provider */
+  validateApiKey: async (provider: string, apiKey?: string) =>
+    invoke<void>('validate_api_key', { provider, apiKey }),
+  listModels: async (provider: string, filter?: ModelFilter, refresh?: boolean) =>
+    invoke<ModelInfo[]>('list_models', { provider, filter, refresh }),
 
   // Window commands
   toggleDevtools: async () => invoke<void>('toggle_devtools'),
   isDevMode: async () => invoke<boolean>('is_dev_mode'),
   getCurrentWorkingDirectory: async () => invoke<string>('get_current_working_directory'),
+  // Receive a session's events in this window, whichever window started its jobs
+  subscribeSession: async (sessionId: string) => invoke<void>('subscribe_session', { sessionId }),
+  unsubscribeSession: async (sessionId: string) => invoke<void>('unsubscribe_session', { sessionId }),
+  openSessionWindow: async (sessionId: string) => invoke<void>('open_session_window', { sessionId }),
+  // Read-only window: gets the session's events, cannot send or approve
+  openObserverWindow: async (sessionId: string) => invoke<void>('open_observer_window', { sessionId }),
 
   // Mail
-  getMailThreads: async (accountId?: string, folder?: string, isArchived?: boolean) =>
-    invoke<MailThread[]>('get_mail_threads', { accountId: accountId ?? null, folder: folder ?? null, isArchived: isArchived ?? null }),
+  getMailThreads: async (accountId?: string, folder?: string, isArchived?: boolean, limit?: number, offset?: number) =>
+    invoke<MailThread[]>('get_mail_threads', {
+      accountId: accountId ?? null,
+      folder: folder ?? null,
+      isArchived: isArchived ?? null,
+      limit: limit ?? null,
+      offset: offset ?? null,
+    }),
+  searchMail: async (filters: MailSearchFilters) =>
+    invoke<MailSearchResults>('search_mail', { filters }),
   getMailThreadMessages: async (threadId: string) =>
     invoke<MailMessage[]>('get_mail_thread_messages', { threadId }),
-  sendMail: async (fromAgentId: string | null, toAgentId: string | null, subject: string, body: string) =>
-    invoke<MailThread>('send_mail', { fromAgentId, toAgentId, subject, body }),
-  replyToMail: async (threadId: string, fromAgentId: string | null, content: string) =>
-    invoke<MailMessage>('reply_to_mail', { threadId, fromAgentId, content }),
+  sendMail: async (
+    fromAgentId: string | null,
+    toAgentId: string | null,
+    subject: string,
+    body: string,
+    attachments?: string[],
+    addressing?: MailAddressing,
+  ) =>
+    invoke<MailThread>('send_mail', {
+      fromAgentId,
+      toAgentId,
+      subject,
+      body,
+      attachments: attachments ?? null,
+      to: addressing?.to ?? null,
+      cc: addressing?.cc ?? null,
+      bcc: addressing?.bcc ?? null,
+    }),
+  replyToMail: async (threadId: string, fromAgentId: string | null, content: string, attachments?: string[], replyAll?: boolean) =>
+    invoke<MailMessage>('reply_to_mail', {
+      threadId,
+      fromAgentId,
+      content,
+      attachments: attachments ?? null,
+      replyAll: replyAll ?? null,
+    }),
+  /** Copy a mail attachment to a path chosen by the user */
+  saveMailAttachment: async (attachmentId: string, destination: string) =>
+    invoke<void>('save_mail_attachment', { attachmentId, destination }),
+  getMailGroups: async () =>
+    invoke<MailGroup[]>('get_mail_groups'),
+  createMailGroup: async (name: string, memberIds: string[], description?: string) =>
+    invoke<MailGroup>('create_mail_group', { name, description: description ?? null, memberIds }),
+  updateMailGroup: async (groupId: string, changes: { name?: string; description?: string; memberIds?: string[] }) =>
+    invoke<MailGroup>('update_mail_group', {
+      groupId,
+      name: changes.name ?? null,
+      description: changes.description ?? null,
+      memberIds: changes.memberIds ?? null,
+    }),
+  deleteMailGroup: async (groupId: string) =>
+    invoke<void>('delete_mail_group', { groupId }),
+  getMailLabels: async () =>
+    invoke<MailLabel[]>('get_mail_labels'),
+  createMailLabel: async (name: string, color?: string) =>
+    invoke<MailLabel>('create_mail_label', { name, color: color ?? null }),
+  updateMailLabel: async (labelId: string, changes: { name?: string; color?: string }) =>
+    invoke<MailLabel>('update_mail_label', { labelId, name: changes.name ?? null, color: changes.color ?? null }),
+  deleteMailLabel: async (labelId: string) =>
+    invoke<void>('delete_mail_label', { labelId }),
+  /** Replace the labels on a thread */
+  setMailThreadLabels: async (threadId: string, labelIds: string[]) =>
+    invoke<MailLabel[]>('set_mail_thread_labels', { threadId, labelIds }),
   markThreadRead: async (threadId: string) =>
     invoke<void>('mark_thread_read', { threadId }),
   archiveThread: async (threadId: string) =>
@@ -690,6 +2279,20 @@ export const anycoworkApi = {
   getUnreadMailCount: async (accountId?: string) =>
     invoke<number>('get_unread_mail_count', { accountId: accountId ?? null }),
 
+  // Email bridge
+  createEmailAccount: async (account: EmailAccountInput) =>
+    invoke<EmailAccount>('create_email_account', { account }),
+  getEmailAccounts: async () =>
+    invoke<EmailAccount[]>('get_email_accounts'),
+  updateEmailAccount: async (accountId: string, changes: EmailAccountChanges) =>
+    invoke<EmailAccount>('update_email_account', { accountId, changes }),
+  deleteEmailAccount: async (accountId: string) =>
+    invoke<void>('delete_email_account', { accountId }),
+  testEmailAccount: async (accountId: string) =>
+    invoke<EmailAccountTestResponse>('test_email_account', { accountId }),
+  getEmailWatcherStatus: async (accountId: string) =>
+    invoke<boolean>('get_email_watcher_status', { accountId }),
+
   // Voice Calls
   startVoiceCall: async (agentId: string, apiKey: string) =>
     invoke<string>('start_voice_call', { agentId, apiKey }),
@@ -699,4 +2302,76 @@ export const anycoworkApi = {
     invoke<void>('send_voice_audio', { sessionId, audioData }),
   isVoiceCallActive: async (sessionId: string) =>
     invoke<boolean>('is_voice_call_active', { sessionId }),
+
+  // Live dictation: 16-bit mono PCM chunks in, `dictation_event` events out
+  startDictation: async (sampleRate?: number) =>
+    invoke<string>('start_dictation', { sampleRate: sampleRate ?? null }),
+  pushDictationAudio: async (dictationId: string, audioData: number[]) =>
+    invoke<void>('push_dictation_audio', { dictationId, audioData }),
+  stopDictation: async (dictationId: string) =>
+    invoke<string>('stop_dictation', { dictationId }),
+
+  // Maintenance
+  checkDataIntegrity: async () => invoke<IntegrityReport>('check_data_integrity'),
+  cleanupOrphanedData: async () => invoke<IntegrityReport>('cleanup_orphaned_data'),
+  runHealthCheck: async () => invoke<HealthReport>('run_health_check'),
+  /** Health check plus workspace disk space and MCP server reachability */
+  systemDoctor: async () => invoke<HealthReport>('system_doctor'),
+  isFirstRun: async () => invoke<boolean>('is_first_run'),
+  completeOnboarding: async () => invoke<void>('complete_onboarding'),
+  checkDatabaseIntegrity: async () => invoke<DatabaseCheck>('check_database_integrity'),
+
+  // Backups
+  backupDatabase: async () => invoke<DatabaseBackup>('backup_database'),
+  listDatabaseBackups: async () => invoke<DatabaseBackup[]>('list_database_backups'),
+  deleteDatabaseBackup: async (fileName: string) =>
+    invoke<void>('delete_database_backup', { fileName }),
+  /** Returns the backup taken of the current data before restoring */
+  restoreDatabase: async (fileName: string) =>
+    invoke<DatabaseBackup>('restore_database', { fileName }),
+  getBackupSchedule: async () => invoke<BackupSchedule>('get_backup_schedule'),
+  setBackupSchedule: async (schedule: BackupSchedule) =>
+    invoke<void>('set_backup_schedule', { schedule }),
+
+  // Retention
+  getRetentionPolicy: async () => invoke<RetentionPolicy>('get_retention_policy'),
+  setRetentionPolicy: async (policy: RetentionPolicy) =>
+    invoke<void>('set_retention_policy', { policy }),
+  applyRetentionPolicy: async () => invoke<RetentionReport>('apply_retention_policy'),
+  getStorageStats: async () => invoke<StorageStats>('get_storage_stats'),
+
+  // Encryption at rest (applied on the next start)
+  getEncryptionStatus: async () => invoke<EncryptionStatus>('get_encryption_status'),
+  enableDatabaseEncryption: async () => invoke<EncryptionStatus>('enable_database_encryption'),
+  disableDatabaseEncryption: async () => invoke<EncryptionStatus>('disable_database_encryption'),
+
+  // Checkpoints
+  listCheckpoints: async (agentId: string, jobId?: string) =>
+    invoke<Checkpoint[]>('list_checkpoints', { agentId, jobId: jobId ?? null }),
+  restoreCheckpoint: async (agentId: string, refName: string) =>
+    invoke<Checkpoint>('restore_checkpoint', { agentId, refName }),
+
+  // Plans
+  getSessionPlans: async (sessionId: string) =>
+    invoke<PlanRecord[]>('get_session_plans', { sessionId }),
+
+  // Checklist of the session's most recent job that kept one
+  getSessionTodos: async (sessionId: string) =>
+    invoke<JobTodos | null>('get_session_todos', { sessionId }),
+  getPlan: async (planId: string) => invoke<PlanDetails>('get_plan', { planId }),
+  reorderPlanTasks: async (planId: string, taskIds: string[]) =>
+    invoke<void>('reorder_plan_tasks', { planId, taskIds }),
+  skipPlanTask: async (planId: string, taskId: string) =>
+    invoke<void>('skip_plan_task', { planId, taskId }),
+  resumePlan: async (planId: string) => invoke<string>('resume_plan', { planId }),
+  approvePlan: async (planId: string, approved: boolean, tasks?: TaskSpec[]) =>
+    invoke<void>('approve_plan', { planId, approved, tasks: tasks ?? null }),
+  applyChangePlan: async (jobId: string) => invoke<AppliedCall[]>('apply_change_plan', { jobId }),
+  discardChangePlan: async (jobId: string) => invoke<void>('discard_change_plan', { jobId }),
+
+  // Agent bus
+  getAgentBusMessages: async (limit?: number) =>
+    invoke<BusMessage[]>('get_agent_bus_messages', { limit: limit ?? null }),
+  sendAgentMessage: async (to: string | null, payload: BusPayload) =>
+    invoke<BusDelivery>('send_agent_message', { to, payload }),
 };
@@ -3,6 +3,7 @@
  * Handles pages, blocks, and attachments
  */
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 
 // ============================================================================
 // TYPES
@@ -30,6 +31,10 @@ export interface Block {
   order_index: number;
   created_at: string;
   updated_at: string;
+  // Bumped by every write; send it back as `base_version` when editing
+  version: number;
+  // 'user', 'agent:<session id>' or 'file'
+  updated_by?: string;
 }
 
 export interface Attachment {
@@ -41,6 +46,27 @@ export interface Attachment {
   file_type: string;
   file_size: number;
   created_at: string;
+  // SHA-256 of the content; attachments with the same hash share the file
+  content_hash?: string;
+  mime_type?: string;
+  thumbnail_path?: string;
+}
+
+export interface AttachmentQuota {
+  max_file_bytes: number;
+  max_page_bytes: number;
+  max_total_bytes: number;
+}
+
+export interface AttachmentUsage {
+  page_bytes?: number;
+  total_bytes: number;
+  quota: AttachmentQuota;
+}
+
+export interface AttachmentGc {
+  files: number;
+  bytes: number;
 }
 
 export interface BlockUpdate {
@@ -48,6 +74,34 @@ export interface BlockUpdate {
   block_type?: string;
   content_json?: string;
   order_index?: number;
+  // Version the edit started from; a block changed since comes back as a conflict
+  base_version?: number;
+}
+
+// `block` is the block as it is now; on a conflict nothing was written
+export type BlockWrite =
+  | { status: 'applied'; block: Block }
+  | { status: 'conflict'; block: Block; base_version: number };
+
+export interface BatchBlockUpdate {
+  blocks: Block[];
+  conflicts: Block[];
+}
+
+// Sent on `page:{id}` for every block written, by the app or an agent
+export type BlockChange =
+  | { change: 'created'; block: Block }
+  | { change: 'updated'; block: Block }
+  | { change: 'deleted'; page_id: string; block_id: string; editor: string };
+
+export type PageSyncDirection = 'created' | 'imported' | 'exported' | 'unchanged';
+
+export interface PageSync {
+  path: string;
+  page_id: string;
+  title: string;
+  direction: PageSyncDirection;
+  blocks: { created: number; updated: number; deleted: number };
 }
 
 // ============================================================================
@@ -166,13 +220,15 @@ export const blocksApi = {
       block_type?: string;
       content_json?: string;
       order_index?: number;
+      base_version?: number;
     }
-  ): Promise<Block> => {
-    return invoke<Block>('update_block', {
+  ): Promise<BlockWrite> => {
+    return invoke<BlockWrite>('update_block', {
       blockId,
       blockType: data.block_type,
       contentJsonParam: data.content_json,
       orderIndexParam: data.order_index,
+      baseVersion: data.base_version,
     });
   },
 
@@ -186,12 +242,21 @@ export const blocksApi = {
   /**
    * Batch update multiple blocks
    */
-  batchUpdate: async (pageId: string, updates: BlockUpdate[]): Promise<Block[]> => {
-    return invoke<Block[]>('batch_update_blocks', {
+  batchUpdate: async (pageId: string, updates: BlockUpdate[]): Promise<BatchBlockUpdate> => {
+    return invoke<BatchBlockUpdate>('batch_update_blocks', {
       pageIdParam: pageId,
       updates,
     });
   },
+
+  /**
+   * Listen to block changes of a page, including those made by agents
+   */
+  onChange: (pageId: string, callback: (change: BlockChange) => void): Promise<() => void> => {
+    return listen<BlockChange>(`page:${pageId}`, (event) => {
+      callback(event.payload);
+    });
+  },
 };
 
 // ============================================================================
@@ -225,6 +290,55 @@ export const attachmentsApi = {
   delete: async (attachmentId: string): Promise<void> => {
     return invoke<void>('delete_attachment', { attachmentId });
   },
+
+  /**
+   * Bytes used by a page's attachments and by all attachments, with the quotas
+   */
+  getUsage: async (pageId?: string): Promise<AttachmentUsage> => {
+    return invoke<AttachmentUsage>('get_attachment_usage', { pageId: pageId ?? null });
+  },
+
+  setQuota: async (quota: AttachmentQuota): Promise<void> => {
+    return invoke<void>('set_attachment_quota', { quota });
+  },
+
+  /**
+   * Remove attachments of deleted pages and unused files
+   */
+  collectGarbage: async (): Promise<AttachmentGc> => {
+    return invoke<AttachmentGc>('collect_attachment_garbage');
+  },
+};
+
+// ============================================================================
+// MARKDOWN API
+// ============================================================================
+
+export const markdownApi = {
+  /**
+   * Write a page to a markdown file; block ids are kept as comments
+   */
+  exportPage: async (pageId: string, path: string): Promise<void> => {
+    return invoke<void>('export_page_markdown', { pageId, path });
+  },
+
+  /**
+   * Import a markdown file, or sync it with its page in whichever direction is newer
+   */
+  syncFile: async (path: string, parentId?: string): Promise<PageSync> => {
+    return invoke<PageSync>('sync_page_file', { path, parentId: parentId ?? null });
+  },
+
+  /**
+   * Sync the markdown files in a folder of an agent's workspace (default `pages`)
+   */
+  syncWorkspace: async (agentId: string, dir?: string, parentId?: string): Promise<PageSync[]> => {
+    return invoke<PageSync[]>('sync_workspace_pages', {
+      agentId,
+      dir: dir ?? null,
+      parentId: parentId ?? null,
+    });
+  },
 };
 
 // ============================================================================
@@ -3,7 +3,7 @@
  */
 
 import { useQuery, useMutation, useQueryClient } from '@tanstack/react-query';
-import { anycoworkApi, AIConfig, MessagingConfig, Agent, AgentCreate, AgentUpdate, ExecutionMode, ExecutionSettingsUpdate, MailThread, MailMessage } from '../anycowork-api';
+import { anycoworkApi, AIConfig, MessagingConfig, Agent, AgentCreate, AgentUpdate, ExecutionMode, ExecutionSettingsUpdate, MailThread, MailMessage, ModelInfo } from '../anycowork-api';
 import { toast } from 'sonner';
 
 // Query keys
@@ -16,6 +16,7 @@ export const queryKeys = {
   messagingConfig: ['config', 'messaging'],
   executionSettings: ['config', 'execution'],
   availableModels: ['config', 'models'],
+  providerModels: (provider: string) => ['config', 'models', provider],
   agents: ['agents'],
   agent: (id: string) => ['agents', id],
   agentSkills: (id: string) => ['agents', id, 'skills'],
@@ -428,6 +429,16 @@ export function useAvailableModels() {
   });
 }
 
+// Models the provider's API key gives access to; empty when it has no usable key
+export function useProviderModels(provider?: string) {
+  return useQuery({
+    queryKey: queryKeys.providerModels(provider ?? ''),
+    queryFn: () => anycoworkApi.listModels(provider!).catch(() => [] as ModelInfo[]),
+    enabled: !!provider,
+    staleTime: 60 * 60 * 1000,
+  });
+}
+
 // Mail hooks
 export function useMailThreads(accountId?: string, folder?: string, isArchived?: boolean) {
   return useQuery({
@@ -0,0 +1,37 @@
+DROP TABLE IF EXISTS agent_secret_grants;
+DROP TABLE IF EXISTS secrets;
+DROP TABLE IF EXISTS permission_grants;
+DROP TABLE IF EXISTS permission_audit;
+DROP TABLE IF EXISTS connector_sessions;
+DROP TABLE IF EXISTS matrix_configs;
+DROP TABLE IF EXISTS slack_conversation_sessions;
+DROP TABLE IF EXISTS slack_configs;
+DROP TABLE IF EXISTS discord_channel_sessions;
+DROP TABLE IF EXISTS discord_configs;
+DROP TABLE IF EXISTS telegram_chat_sessions;
+DROP TABLE IF EXISTS mail_thread_labels;
+DROP TABLE IF EXISTS mail_labels;
+DROP TABLE IF EXISTS mail_group_members;
+DROP TABLE IF EXISTS mail_groups;
+DROP TABLE IF EXISTS mail_recipients;
+DROP TABLE IF EXISTS mail_attachments;
+DROP TABLE IF EXISTS email_accounts;
+DROP TABLE IF EXISTS agent_triggers;
+DROP TABLE IF EXISTS task_runs;
+DROP TABLE IF EXISTS plan_tasks;
+DROP TABLE IF EXISTS plans;
+DROP TABLE IF EXISTS settings;
+DROP TABLE IF EXISTS mail_messages;
+DROP TABLE IF EXISTS mail_threads;
+DROP TABLE IF EXISTS tasks;
+DROP TABLE IF EXISTS mcp_servers;
+DROP TABLE IF EXISTS skill_files;
+DROP TABLE IF EXISTS messages;
+DROP TABLE IF EXISTS sessions;
+DROP TABLE IF EXISTS agent_skill_assignments;
+DROP TABLE IF EXISTS agent_skills;
+DROP TABLE IF EXISTS attachments;
+DROP TABLE IF EXISTS blocks;
+DROP TABLE IF EXISTS pages;
+DROP TABLE IF EXISTS telegram_configs;
+DROP TABLE IF EXISTS agents;
@@ -0,0 +1,525 @@
+-- Postgres baseline: every table of the SQLite migrations up to
+-- 2026-03-14-000000_add_attachment_hashes. The full-text indexes (search_index,
+-- mail_messages_fts and their triggers) are SQLite-only and not created here.
+
+CREATE TABLE agents (
+  id TEXT NOT NULL PRIMARY KEY,
+  name TEXT NOT NULL,
+  description TEXT,
+  status TEXT NOT NULL DEFAULT 'active',
+  personality TEXT,
+  tone TEXT,
+  expertise TEXT,
+  ai_provider TEXT NOT NULL DEFAULT 'openai',
+  ai_model TEXT NOT NULL DEFAULT 'gpt-4o',
+  ai_temperature REAL NOT NULL DEFAULT 0.7,
+  ai_config TEXT NOT NULL DEFAULT '{}',
+  system_prompt TEXT,
+  permissions TEXT,
+  working_directories TEXT,
+  skills TEXT,
+  mcp_servers TEXT,
+  messaging_connections TEXT,
+  knowledge_bases TEXT,
+  api_keys TEXT,
+  created_at BIGINT NOT NULL DEFAULT 0,
+  updated_at BIGINT NOT NULL DEFAULT 0,
+  platform_configs TEXT,
+  execution_settings TEXT,
+  scope_type TEXT DEFAULT 'global',
+  workspace_path TEXT,
+  avatar TEXT,
+  agent_tools TEXT
+);
+
+CREATE TABLE telegram_configs (
+  id TEXT NOT NULL PRIMARY KEY,
+  bot_token TEXT NOT NULL,
+  agent_id TEXT NOT NULL,
+  is_active INTEGER NOT NULL DEFAULT 0,
+  allowed_chat_ids TEXT,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  voice_replies INTEGER NOT NULL DEFAULT 0,
+  group_allowlists TEXT,
+  FOREIGN KEY (agent_id) REFERENCES agents(id)
+);
+
+CREATE INDEX idx_telegram_configs_agent_id ON telegram_configs(agent_id);
+
+CREATE INDEX idx_telegram_configs_is_active ON telegram_configs(is_active);
+
+CREATE TABLE pages (
+  id TEXT NOT NULL PRIMARY KEY,
+  title TEXT NOT NULL,
+  type TEXT NOT NULL DEFAULT 'page',
+  parent_id TEXT,
+  day_date TEXT,
+  icon TEXT,
+  cover_image TEXT,
+  is_archived INTEGER NOT NULL DEFAULT 0,
+  is_published INTEGER NOT NULL DEFAULT 0,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (parent_id) REFERENCES pages(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_pages_parent ON pages(parent_id);
+
+CREATE INDEX idx_pages_archived ON pages(is_archived);
+
+CREATE INDEX idx_pages_type ON pages(type);
+
+CREATE TABLE blocks (
+  id TEXT NOT NULL PRIMARY KEY,
+  page_id TEXT NOT NULL,
+  type TEXT NOT NULL,
+  content_json TEXT NOT NULL,
+  order_index INTEGER NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  version INTEGER NOT NULL DEFAULT 1,
+  updated_by TEXT,
+  FOREIGN KEY (page_id) REFERENCES pages(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_blocks_page ON blocks(page_id);
+
+CREATE INDEX idx_blocks_order ON blocks(page_id, order_index);
+
+CREATE TABLE attachments (
+  id TEXT NOT NULL PRIMARY KEY,
+  page_id TEXT NOT NULL,
+  block_id TEXT,
+  file_path TEXT NOT NULL,
+  file_name TEXT NOT NULL,
+  file_type TEXT NOT NULL,
+  file_size INTEGER NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  content_hash TEXT,
+  mime_type TEXT,
+  thumbnail_path TEXT,
+  FOREIGN KEY (page_id) REFERENCES pages(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_attachments_page ON attachments(page_id);
+
+CREATE TABLE agent_skills (
+  id TEXT NOT NULL PRIMARY KEY,
+  name TEXT NOT NULL UNIQUE,
+  display_title TEXT NOT NULL,
+  description TEXT NOT NULL,
+  skill_content TEXT NOT NULL,
+  additional_files_json TEXT,
+  enabled INTEGER NOT NULL DEFAULT 1,
+  version INTEGER NOT NULL DEFAULT 1,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  source_path TEXT,
+  category TEXT DEFAULT 'General',
+  requires_sandbox INTEGER NOT NULL DEFAULT 0,
+  sandbox_config TEXT,
+  execution_mode TEXT NOT NULL DEFAULT 'flexible',
+  triggers TEXT,
+  dependencies TEXT,
+  installed_version TEXT,
+  invocation TEXT,
+  examples TEXT,
+  recipe TEXT
+);
+
+CREATE INDEX idx_agent_skills_enabled ON agent_skills(enabled);
+
+CREATE INDEX idx_agent_skills_name ON agent_skills(name);
+
+CREATE TABLE agent_skill_assignments (
+  agent_id TEXT NOT NULL,
+  skill_id TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (agent_id, skill_id),
+  FOREIGN KEY (agent_id) REFERENCES agents(id) ON DELETE CASCADE,
+  FOREIGN KEY (skill_id) REFERENCES agent_skills(id) ON DELETE CASCADE
+);
+
+CREATE TABLE sessions (
+  id TEXT NOT NULL PRIMARY KEY,
+  agent_id TEXT NOT NULL,
+  title TEXT,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  archived INTEGER NOT NULL DEFAULT 0,
+  pinned INTEGER NOT NULL DEFAULT 0,
+  FOREIGN KEY (agent_id) REFERENCES agents(id)
+);
+
+CREATE TABLE messages (
+  id TEXT NOT NULL PRIMARY KEY,
+  role TEXT NOT NULL,
+  content TEXT NOT NULL,
+  session_id TEXT NOT NULL,
+  metadata_json TEXT,
+  tokens INTEGER,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  deleted_at TIMESTAMP,
+  FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+);
+
+CREATE TABLE skill_files (
+    id TEXT NOT NULL PRIMARY KEY,
+    skill_id TEXT NOT NULL,
+    relative_path TEXT NOT NULL,
+    content TEXT NOT NULL,
+    file_type TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (skill_id) REFERENCES agent_skills(id) ON DELETE CASCADE,
+    UNIQUE(skill_id, relative_path)
+);
+
+CREATE INDEX idx_skill_files_skill_id ON skill_files(skill_id);
+
+CREATE TABLE mcp_servers (
+  id TEXT NOT NULL PRIMARY KEY,
+  name TEXT NOT NULL,
+  server_type TEXT NOT NULL,
+  command TEXT,
+  args TEXT,
+  env TEXT,
+  url TEXT,
+  is_enabled INTEGER NOT NULL DEFAULT 1,
+  template_id TEXT,
+  created_at BIGINT NOT NULL,
+  updated_at BIGINT NOT NULL,
+  headers TEXT,
+  secret_names TEXT,
+  oauth TEXT
+);
+
+CREATE TABLE tasks (
+  id TEXT NOT NULL PRIMARY KEY,
+  title TEXT NOT NULL,
+  description TEXT,
+  status TEXT NOT NULL DEFAULT 'pending',
+  priority INTEGER NOT NULL DEFAULT 0,
+  session_id TEXT,
+  agent_id TEXT,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  schedule TEXT,
+  prompt_template TEXT,
+  schedule_enabled INTEGER NOT NULL DEFAULT 0,
+  next_run_at TEXT,
+  last_run_at TEXT,
+  FOREIGN KEY(session_id) REFERENCES sessions(id) ON DELETE SET NULL,
+  FOREIGN KEY(agent_id) REFERENCES agents(id) ON DELETE SET NULL
+);
+
+CREATE TABLE mail_threads (
+    id TEXT PRIMARY KEY NOT NULL,
+    subject TEXT NOT NULL,
+    is_read INTEGER NOT NULL DEFAULT 0,
+    is_archived INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE mail_messages (
+    id TEXT PRIMARY KEY NOT NULL,
+    thread_id TEXT NOT NULL REFERENCES mail_threads(id) ON DELETE CASCADE,
+    sender_type TEXT NOT NULL,
+    sender_agent_id TEXT,
+    recipient_type TEXT NOT NULL,
+    recipient_agent_id TEXT,
+    content TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  external_address TEXT,
+  external_message_id TEXT
+);
+
+CREATE TABLE settings (
+    id TEXT PRIMARY KEY NOT NULL,
+    key TEXT NOT NULL UNIQUE,
+    value TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE plans (
+    id TEXT PRIMARY KEY NOT NULL,
+    session_id TEXT NOT NULL,
+    job_id TEXT NOT NULL,
+    objective TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'running',
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE plan_tasks (
+    plan_id TEXT NOT NULL REFERENCES plans(id) ON DELETE CASCADE,
+    task_id TEXT NOT NULL,
+    description TEXT NOT NULL,
+    dependencies TEXT NOT NULL DEFAULT '[]',
+    position INTEGER NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    result TEXT,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  assignee TEXT,
+    PRIMARY KEY (plan_id, task_id)
+);
+
+CREATE INDEX idx_plans_session_id ON plans(session_id);
+
+CREATE TABLE task_runs (
+  id TEXT NOT NULL PRIMARY KEY,
+  task_id TEXT NOT NULL,
+  agent_id TEXT NOT NULL,
+  session_id TEXT,
+  status TEXT NOT NULL,
+  prompt TEXT NOT NULL,
+  output TEXT,
+  error TEXT,
+  started_at TEXT NOT NULL,
+  finished_at TEXT,
+  FOREIGN KEY(task_id) REFERENCES tasks(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_task_runs_task_id ON task_runs(task_id, started_at);
+
+CREATE TABLE agent_triggers (
+  id TEXT NOT NULL PRIMARY KEY,
+  agent_id TEXT NOT NULL,
+  kind TEXT NOT NULL,
+  pattern TEXT,
+  prompt_template TEXT,
+  enabled INTEGER NOT NULL DEFAULT 1,
+  max_runs_per_hour INTEGER NOT NULL DEFAULT 6,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY(agent_id) REFERENCES agents(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_agent_triggers_agent_id ON agent_triggers(agent_id);
+
+CREATE TABLE email_accounts (
+  id TEXT NOT NULL PRIMARY KEY,
+  agent_id TEXT NOT NULL,
+  email_address TEXT NOT NULL,
+  display_name TEXT,
+  username TEXT NOT NULL,
+  smtp_host TEXT NOT NULL,
+  smtp_port INTEGER NOT NULL DEFAULT 587,
+  imap_host TEXT NOT NULL,
+  imap_port INTEGER NOT NULL DEFAULT 993,
+  imap_folder TEXT NOT NULL DEFAULT 'INBOX',
+  deliver_outbound INTEGER NOT NULL DEFAULT 0,
+  poll_interval_seconds INTEGER NOT NULL DEFAULT 60,
+  last_seen_uid BIGINT NOT NULL DEFAULT 0,
+  is_active INTEGER NOT NULL DEFAULT 0,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (agent_id) REFERENCES agents(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX idx_email_accounts_agent_id ON email_accounts(agent_id);
+
+CREATE INDEX idx_mail_messages_external_message_id ON mail_messages(external_message_id);
+
+CREATE TABLE mail_attachments (
+    id TEXT PRIMARY KEY NOT NULL,
+    message_id TEXT NOT NULL REFERENCES mail_messages(id) ON DELETE CASCADE,
+    file_name TEXT NOT NULL,
+    mime_type TEXT NOT NULL,
+    file_size INTEGER NOT NULL,
+    storage_path TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_mail_attachments_message_id ON mail_attachments(message_id);
+
+CREATE TABLE mail_recipients (
+    id TEXT PRIMARY KEY NOT NULL,
+    message_id TEXT NOT NULL REFERENCES mail_messages(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL DEFAULT 'to',
+    recipient_type TEXT NOT NULL,
+    recipient_agent_id TEXT,
+    external_address TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_mail_recipients_message_id ON mail_recipients(message_id);
+
+CREATE INDEX idx_mail_recipients_agent_id ON mail_recipients(recipient_agent_id);
+
+CREATE TABLE mail_groups (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL UNIQUE,
+    description TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE mail_group_members (
+    group_id TEXT NOT NULL REFERENCES mail_groups(id) ON DELETE CASCADE,
+    agent_id TEXT NOT NULL REFERENCES agents(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (group_id, agent_id)
+);
+
+CREATE INDEX idx_mail_messages_thread_id ON mail_messages(thread_id);
+
+CREATE INDEX idx_mail_threads_updated_at ON mail_threads(updated_at);
+
+CREATE TABLE mail_labels (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL UNIQUE,
+    color TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE mail_thread_labels (
+    thread_id TEXT NOT NULL REFERENCES mail_threads(id) ON DELETE CASCADE,
+    label_id TEXT NOT NULL REFERENCES mail_labels(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (thread_id, label_id)
+);
+
+CREATE INDEX idx_mail_thread_labels_label_id ON mail_thread_labels(label_id);
+
+CREATE TABLE telegram_chat_sessions (
+  config_id TEXT NOT NULL,
+  chat_id BIGINT NOT NULL,
+  thread_id BIGINT NOT NULL DEFAULT 0,
+  session_id TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (config_id, chat_id, thread_id),
+  FOREIGN KEY (config_id) REFERENCES telegram_configs(id) ON DELETE CASCADE,
+  FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+);
+
+CREATE TABLE discord_configs (
+  id TEXT NOT NULL PRIMARY KEY,
+  bot_token TEXT NOT NULL,
+  agent_id TEXT NOT NULL,
+  is_active INTEGER NOT NULL DEFAULT 0,
+  allowed_channel_ids TEXT,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (agent_id) REFERENCES agents(id)
+);
+
+CREATE INDEX idx_discord_configs_agent_id ON discord_configs(agent_id);
+
+CREATE INDEX idx_discord_configs_is_active ON discord_configs(is_active);
+
+CREATE TABLE discord_channel_sessions (
+  config_id TEXT NOT NULL,
+  channel_id TEXT NOT NULL,
+  session_id TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (config_id, channel_id),
+  FOREIGN KEY (config_id) REFERENCES discord_configs(id) ON DELETE CASCADE,
+  FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+);
+
+CREATE TABLE slack_configs (
+  id TEXT NOT NULL PRIMARY KEY,
+  app_token TEXT NOT NULL,
+  bot_token TEXT NOT NULL,
+  agent_id TEXT NOT NULL,
+  is_active INTEGER NOT NULL DEFAULT 0,
+  allowed_channel_ids TEXT,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (agent_id) REFERENCES agents(id)
+);
+
+CREATE INDEX idx_slack_configs_agent_id ON slack_configs(agent_id);
+
+CREATE INDEX idx_slack_configs_is_active ON slack_configs(is_active);
+
+CREATE TABLE slack_conversation_sessions (
+  config_id TEXT NOT NULL,
+  channel_id TEXT NOT NULL,
+  thread_ts TEXT NOT NULL DEFAULT '',
+  session_id TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (config_id, channel_id, thread_ts),
+  FOREIGN KEY (config_id) REFERENCES slack_configs(id) ON DELETE CASCADE,
+  FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+);
+
+CREATE TABLE matrix_configs (
+  id TEXT NOT NULL PRIMARY KEY,
+  homeserver_url TEXT NOT NULL,
+  access_token TEXT NOT NULL,
+  agent_id TEXT NOT NULL,
+  is_active INTEGER NOT NULL DEFAULT 0,
+  allowed_room_ids TEXT,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (agent_id) REFERENCES agents(id)
+);
+
+CREATE INDEX idx_matrix_configs_agent_id ON matrix_configs(agent_id);
+
+CREATE INDEX idx_matrix_configs_is_active ON matrix_configs(is_active);
+
+CREATE TABLE connector_sessions (
+  platform TEXT NOT NULL,
+  config_id TEXT NOT NULL,
+  conversation_id TEXT NOT NULL,
+  thread_id TEXT NOT NULL DEFAULT '',
+  session_id TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (platform, config_id, conversation_id, thread_id),
+  FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+);
+
+CREATE TABLE permission_audit (
+  id TEXT NOT NULL PRIMARY KEY,
+  session_id TEXT,
+  tool TEXT,
+  permission_type TEXT NOT NULL,
+  resource TEXT,
+  message TEXT NOT NULL,
+  metadata TEXT NOT NULL DEFAULT '{}',
+  resolution TEXT NOT NULL,
+  decided_by TEXT NOT NULL,
+  requested_at TIMESTAMP NOT NULL,
+  decided_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  payload TEXT
+);
+
+CREATE INDEX idx_permission_audit_session_id ON permission_audit(session_id);
+
+CREATE INDEX idx_permission_audit_decided_at ON permission_audit(decided_at);
+
+CREATE TABLE permission_grants (
+  id TEXT NOT NULL PRIMARY KEY,
+  scope TEXT NOT NULL,
+  scope_id TEXT,
+  permission_type TEXT NOT NULL,
+  resource TEXT NOT NULL,
+  granted_by TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  expires_at TIMESTAMP
+);
+
+CREATE INDEX idx_permission_grants_lookup ON permission_grants(permission_type, resource);
+
+CREATE TABLE secrets (
+  name TEXT NOT NULL PRIMARY KEY,
+  description TEXT,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE agent_secret_grants (
+  agent_id TEXT NOT NULL,
+  secret_name TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (agent_id, secret_name)
+);
+
+CREATE INDEX idx_messages_session_live ON messages(session_id, deleted_at, created_at);
+
+CREATE INDEX idx_attachments_content_hash ON attachments(content_hash);
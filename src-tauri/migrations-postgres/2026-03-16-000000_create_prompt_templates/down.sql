@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_prompt_templates_lookup;
+DROP TABLE IF EXISTS prompt_templates;
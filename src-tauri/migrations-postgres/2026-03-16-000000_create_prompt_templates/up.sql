@@ -0,0 +1,14 @@
+-- Saved versions of the prompt templates (see anyagents/src/prompts.rs).
+-- Rows are never edited; the highest version of a (name, agent_id) pair is
+-- the one in use. agent_id NULL overrides the template for every agent.
+CREATE TABLE prompt_templates (
+  id TEXT NOT NULL PRIMARY KEY,
+  name TEXT NOT NULL,
+  agent_id TEXT,
+  version INTEGER NOT NULL,
+  body TEXT NOT NULL,
+  note TEXT,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_prompt_templates_lookup ON prompt_templates(name, agent_id, version);
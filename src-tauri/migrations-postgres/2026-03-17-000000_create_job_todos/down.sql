@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_job_todos_session;
+DROP TABLE IF EXISTS job_todos;
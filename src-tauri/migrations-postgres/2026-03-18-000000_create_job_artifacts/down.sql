@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_job_artifacts_session;
+DROP INDEX IF EXISTS idx_job_artifacts_job;
+DROP TABLE IF EXISTS job_artifacts;
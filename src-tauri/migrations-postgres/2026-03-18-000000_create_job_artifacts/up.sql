@@ -0,0 +1,17 @@
+-- Files and reports a job produced, registered by the tools that wrote them.
+-- path is absolute; preview is a short text excerpt or description.
+CREATE TABLE job_artifacts (
+  id TEXT NOT NULL PRIMARY KEY,
+  job_id TEXT NOT NULL,
+  session_id TEXT NOT NULL,
+  kind TEXT NOT NULL,
+  title TEXT NOT NULL,
+  path TEXT NOT NULL,
+  mime_type TEXT NOT NULL,
+  file_size INTEGER NOT NULL,
+  preview TEXT,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_job_artifacts_job ON job_artifacts(job_id);
+CREATE INDEX idx_job_artifacts_session ON job_artifacts(session_id, created_at);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_usage_records_job;
+DROP INDEX IF EXISTS idx_usage_records_created;
+DROP TABLE IF EXISTS usage_records;
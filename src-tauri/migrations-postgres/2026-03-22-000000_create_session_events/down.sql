@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_session_events_job;
+DROP INDEX IF EXISTS idx_session_events_session;
+DROP TABLE IF EXISTS session_events;
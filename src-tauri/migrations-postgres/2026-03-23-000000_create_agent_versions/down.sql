@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_agent_versions_lookup;
+DROP TABLE IF EXISTS agent_versions;
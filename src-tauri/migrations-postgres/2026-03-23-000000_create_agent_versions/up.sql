@@ -0,0 +1,13 @@
+-- Configuration history of the agents (see anyagents/src/models/agent_version.rs).
+-- Each edit appends a snapshot of the agent's prompt, model and tools; rows
+-- are never edited. Jobs record the version they ran with.
+CREATE TABLE agent_versions (
+  id TEXT NOT NULL PRIMARY KEY,
+  agent_id TEXT NOT NULL,
+  version INTEGER NOT NULL,
+  snapshot TEXT NOT NULL,
+  note TEXT,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX idx_agent_versions_lookup ON agent_versions(agent_id, version);
@@ -0,0 +1 @@
+ALTER TABLE agent_skills ADD COLUMN triggers TEXT;
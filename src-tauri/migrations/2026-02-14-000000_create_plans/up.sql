@@ -0,0 +1,24 @@
+-- Persisted plans so multi-step runs survive restarts and can be edited/resumed
+CREATE TABLE plans (
+    id TEXT PRIMARY KEY NOT NULL,
+    session_id TEXT NOT NULL,
+    job_id TEXT NOT NULL,
+    objective TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'running', -- running, completed, failed, interrupted
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE plan_tasks (
+    plan_id TEXT NOT NULL REFERENCES plans(id) ON DELETE CASCADE,
+    task_id TEXT NOT NULL,
+    description TEXT NOT NULL,
+    dependencies TEXT NOT NULL DEFAULT '[]', -- JSON array of task ids
+    position INTEGER NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending', -- pending, running, completed, failed, skipped
+    result TEXT,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (plan_id, task_id)
+);
+
+CREATE INDEX idx_plans_session_id ON plans(session_id);
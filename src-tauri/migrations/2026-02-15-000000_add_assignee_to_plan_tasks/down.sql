@@ -0,0 +1 @@
+ALTER TABLE plan_tasks DROP COLUMN assignee;
@@ -0,0 +1,2 @@
+-- Agent id a task is assigned to in team mode (NULL = lead agent)
+ALTER TABLE plan_tasks ADD COLUMN assignee TEXT;
@@ -0,0 +1,7 @@
+DROP TABLE task_runs;
+
+ALTER TABLE tasks DROP COLUMN last_run_at;
+ALTER TABLE tasks DROP COLUMN next_run_at;
+ALTER TABLE tasks DROP COLUMN schedule_enabled;
+ALTER TABLE tasks DROP COLUMN prompt_template;
+ALTER TABLE tasks DROP COLUMN schedule;
@@ -0,0 +1,22 @@
+-- Schedule: cron expression ("0 9 * * 1-5") or interval ("every 30m")
+ALTER TABLE tasks ADD COLUMN schedule TEXT;
+ALTER TABLE tasks ADD COLUMN prompt_template TEXT;
+ALTER TABLE tasks ADD COLUMN schedule_enabled INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE tasks ADD COLUMN next_run_at TEXT;
+ALTER TABLE tasks ADD COLUMN last_run_at TEXT;
+
+CREATE TABLE task_runs (
+  id TEXT NOT NULL PRIMARY KEY,
+  task_id TEXT NOT NULL,
+  agent_id TEXT NOT NULL,
+  session_id TEXT,
+  status TEXT NOT NULL, -- running, completed, failed
+  prompt TEXT NOT NULL,
+  output TEXT,
+  error TEXT,
+  started_at TEXT NOT NULL,
+  finished_at TEXT,
+  FOREIGN KEY(task_id) REFERENCES tasks(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_task_runs_task_id ON task_runs(task_id, started_at);
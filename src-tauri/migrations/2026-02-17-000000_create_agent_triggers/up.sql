@@ -0,0 +1,14 @@
+CREATE TABLE agent_triggers (
+  id TEXT NOT NULL PRIMARY KEY,
+  agent_id TEXT NOT NULL,
+  kind TEXT NOT NULL, -- file_change, mail, telegram
+  pattern TEXT, -- glob for file_change, regex for mail and telegram
+  prompt_template TEXT,
+  enabled INTEGER NOT NULL DEFAULT 1,
+  max_runs_per_hour INTEGER NOT NULL DEFAULT 6,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY(agent_id) REFERENCES agents(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_agent_triggers_agent_id ON agent_triggers(agent_id);
@@ -0,0 +1,2 @@
+-- HTTP headers (JSON object) sent to "sse" and "http" MCP servers, e.g. Authorization
+ALTER TABLE mcp_servers ADD COLUMN headers TEXT;
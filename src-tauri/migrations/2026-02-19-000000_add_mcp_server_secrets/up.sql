@@ -0,0 +1,5 @@
+-- Names of the secrets stored in the OS keyring for this server (JSON array);
+-- values are referenced from args, env, url and headers as ${SECRET:name}
+ALTER TABLE mcp_servers ADD COLUMN secret_names TEXT;
+-- OAuth device-code flow settings (JSON object), see anyagents::mcp::oauth
+ALTER TABLE mcp_servers ADD COLUMN oauth TEXT;
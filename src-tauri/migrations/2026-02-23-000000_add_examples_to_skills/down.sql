@@ -0,0 +1 @@
+ALTER TABLE agent_skills DROP COLUMN examples;
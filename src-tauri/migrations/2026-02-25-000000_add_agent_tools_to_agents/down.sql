@@ -0,0 +1 @@
+ALTER TABLE agents DROP COLUMN agent_tools;
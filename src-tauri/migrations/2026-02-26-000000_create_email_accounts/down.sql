@@ -0,0 +1,4 @@
+DROP INDEX idx_mail_messages_external_message_id;
+ALTER TABLE mail_messages DROP COLUMN external_message_id;
+ALTER TABLE mail_messages DROP COLUMN external_address;
+DROP TABLE email_accounts;
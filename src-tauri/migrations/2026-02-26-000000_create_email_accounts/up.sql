@@ -0,0 +1,26 @@
+CREATE TABLE email_accounts (
+  id TEXT NOT NULL PRIMARY KEY,
+  agent_id TEXT NOT NULL,
+  email_address TEXT NOT NULL,
+  display_name TEXT,
+  username TEXT NOT NULL,
+  smtp_host TEXT NOT NULL,
+  smtp_port INTEGER NOT NULL DEFAULT 587,
+  imap_host TEXT NOT NULL,
+  imap_port INTEGER NOT NULL DEFAULT 993,
+  imap_folder TEXT NOT NULL DEFAULT 'INBOX',
+  deliver_outbound INTEGER NOT NULL DEFAULT 0,
+  poll_interval_seconds INTEGER NOT NULL DEFAULT 60,
+  last_seen_uid INTEGER NOT NULL DEFAULT 0,
+  is_active INTEGER NOT NULL DEFAULT 0,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (agent_id) REFERENCES agents(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX idx_email_accounts_agent_id ON email_accounts(agent_id);
+
+ALTER TABLE mail_messages ADD COLUMN external_address TEXT;
+ALTER TABLE mail_messages ADD COLUMN external_message_id TEXT;
+
+CREATE INDEX idx_mail_messages_external_message_id ON mail_messages(external_message_id);
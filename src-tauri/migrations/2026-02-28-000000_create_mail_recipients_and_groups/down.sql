@@ -0,0 +1,3 @@
+DROP TABLE mail_group_members;
+DROP TABLE mail_groups;
+DROP TABLE mail_recipients;
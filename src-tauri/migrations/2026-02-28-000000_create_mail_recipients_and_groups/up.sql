@@ -0,0 +1,30 @@
+-- Every recipient of a message beyond the primary one kept on mail_messages.
+-- kind is 'to', 'cc' or 'bcc'; recipient_type follows mail_messages.
+CREATE TABLE mail_recipients (
+    id TEXT PRIMARY KEY NOT NULL,
+    message_id TEXT NOT NULL REFERENCES mail_messages(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL DEFAULT 'to',
+    recipient_type TEXT NOT NULL,
+    recipient_agent_id TEXT,
+    external_address TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_mail_recipients_message_id ON mail_recipients(message_id);
+CREATE INDEX idx_mail_recipients_agent_id ON mail_recipients(recipient_agent_id);
+
+-- Named groups of agents that can be addressed like a single recipient
+CREATE TABLE mail_groups (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL UNIQUE,
+    description TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE mail_group_members (
+    group_id TEXT NOT NULL REFERENCES mail_groups(id) ON DELETE CASCADE,
+    agent_id TEXT NOT NULL REFERENCES agents(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (group_id, agent_id)
+);
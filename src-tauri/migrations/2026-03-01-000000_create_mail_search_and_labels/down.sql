@@ -0,0 +1,9 @@
+DROP TABLE mail_thread_labels;
+DROP TABLE mail_labels;
+DROP INDEX idx_mail_threads_updated_at;
+DROP INDEX idx_mail_messages_thread_id;
+DROP TRIGGER mail_threads_fts_subject;
+DROP TRIGGER mail_messages_fts_delete;
+DROP TRIGGER mail_messages_fts_update;
+DROP TRIGGER mail_messages_fts_insert;
+DROP TABLE mail_messages_fts;
@@ -0,0 +1,66 @@
+-- Full-text index over mail. Kept in sync with mail_messages (and thread
+-- subjects) by the triggers below; message_id and thread_id are only carried
+-- along to map hits back.
+CREATE VIRTUAL TABLE mail_messages_fts USING fts5(
+    message_id UNINDEXED,
+    thread_id UNINDEXED,
+    subject,
+    content,
+    sender,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO mail_messages_fts (message_id, thread_id, subject, content, sender)
+SELECT m.id, m.thread_id, t.subject, m.content, COALESCE(m.external_address, '')
+FROM mail_messages m JOIN mail_threads t ON t.id = m.thread_id;
+
+CREATE TRIGGER mail_messages_fts_insert AFTER INSERT ON mail_messages BEGIN
+    INSERT INTO mail_messages_fts (message_id, thread_id, subject, content, sender)
+    VALUES (
+        NEW.id,
+        NEW.thread_id,
+        COALESCE((SELECT subject FROM mail_threads WHERE id = NEW.thread_id), ''),
+        NEW.content,
+        COALESCE(NEW.external_address, '')
+    );
+END;
+
+CREATE TRIGGER mail_messages_fts_update AFTER UPDATE OF content, thread_id, external_address ON mail_messages BEGIN
+    DELETE FROM mail_messages_fts WHERE message_id = OLD.id;
+    INSERT INTO mail_messages_fts (message_id, thread_id, subject, content, sender)
+    VALUES (
+        NEW.id,
+        NEW.thread_id,
+        COALESCE((SELECT subject FROM mail_threads WHERE id = NEW.thread_id), ''),
+        NEW.content,
+        COALESCE(NEW.external_address, '')
+    );
+END;
+
+CREATE TRIGGER mail_messages_fts_delete AFTER DELETE ON mail_messages BEGIN
+    DELETE FROM mail_messages_fts WHERE message_id = OLD.id;
+END;
+
+CREATE TRIGGER mail_threads_fts_subject AFTER UPDATE OF subject ON mail_threads BEGIN
+    UPDATE mail_messages_fts SET subject = NEW.subject WHERE thread_id = NEW.id;
+END;
+
+CREATE INDEX idx_mail_messages_thread_id ON mail_messages(thread_id);
+CREATE INDEX idx_mail_threads_updated_at ON mail_threads(updated_at);
+
+-- User-defined labels, any number per thread
+CREATE TABLE mail_labels (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL UNIQUE,
+    color TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE mail_thread_labels (
+    thread_id TEXT NOT NULL REFERENCES mail_threads(id) ON DELETE CASCADE,
+    label_id TEXT NOT NULL REFERENCES mail_labels(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (thread_id, label_id)
+);
+
+CREATE INDEX idx_mail_thread_labels_label_id ON mail_thread_labels(label_id);
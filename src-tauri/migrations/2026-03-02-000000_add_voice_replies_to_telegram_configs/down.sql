@@ -0,0 +1 @@
+ALTER TABLE telegram_configs DROP COLUMN voice_replies;
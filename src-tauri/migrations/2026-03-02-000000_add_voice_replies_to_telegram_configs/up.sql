@@ -0,0 +1 @@
+ALTER TABLE telegram_configs ADD COLUMN voice_replies INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS telegram_chat_sessions;
+ALTER TABLE telegram_configs DROP COLUMN group_allowlists;
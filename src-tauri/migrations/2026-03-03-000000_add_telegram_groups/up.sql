@@ -0,0 +1,15 @@
+-- JSON object of group chat id -> Telegram user ids allowed to address the
+-- bot there (an empty list lets every member in)
+ALTER TABLE telegram_configs ADD COLUMN group_allowlists TEXT;
+
+-- One agent session per chat, and per forum topic in groups that use them
+CREATE TABLE telegram_chat_sessions (
+  config_id TEXT NOT NULL,
+  chat_id BIGINT NOT NULL,
+  thread_id BIGINT NOT NULL DEFAULT 0,
+  session_id TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (config_id, chat_id, thread_id),
+  FOREIGN KEY (config_id) REFERENCES telegram_configs(id) ON DELETE CASCADE,
+  FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+);
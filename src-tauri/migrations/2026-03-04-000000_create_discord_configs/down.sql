@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS discord_channel_sessions;
+DROP TABLE IF EXISTS discord_configs;
@@ -0,0 +1,24 @@
+CREATE TABLE discord_configs (
+  id TEXT NOT NULL PRIMARY KEY,
+  bot_token TEXT NOT NULL,
+  agent_id TEXT NOT NULL,
+  is_active INTEGER NOT NULL DEFAULT 0,
+  allowed_channel_ids TEXT,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (agent_id) REFERENCES agents(id)
+);
+
+CREATE INDEX idx_discord_configs_agent_id ON discord_configs(agent_id);
+CREATE INDEX idx_discord_configs_is_active ON discord_configs(is_active);
+
+-- One agent session per DM or server channel
+CREATE TABLE discord_channel_sessions (
+  config_id TEXT NOT NULL,
+  channel_id TEXT NOT NULL,
+  session_id TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (config_id, channel_id),
+  FOREIGN KEY (config_id) REFERENCES discord_configs(id) ON DELETE CASCADE,
+  FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS slack_conversation_sessions;
+DROP TABLE IF EXISTS slack_configs;
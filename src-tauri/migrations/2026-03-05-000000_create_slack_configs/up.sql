@@ -0,0 +1,28 @@
+-- app_token (xapp-) opens the Socket Mode connection, bot_token (xoxb-)
+-- calls the Web API
+CREATE TABLE slack_configs (
+  id TEXT NOT NULL PRIMARY KEY,
+  app_token TEXT NOT NULL,
+  bot_token TEXT NOT NULL,
+  agent_id TEXT NOT NULL,
+  is_active INTEGER NOT NULL DEFAULT 0,
+  allowed_channel_ids TEXT,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (agent_id) REFERENCES agents(id)
+);
+
+CREATE INDEX idx_slack_configs_agent_id ON slack_configs(agent_id);
+CREATE INDEX idx_slack_configs_is_active ON slack_configs(is_active);
+
+-- One agent session per DM, and per thread in channels ('' for a DM)
+CREATE TABLE slack_conversation_sessions (
+  config_id TEXT NOT NULL,
+  channel_id TEXT NOT NULL,
+  thread_ts TEXT NOT NULL DEFAULT '',
+  session_id TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (config_id, channel_id, thread_ts),
+  FOREIGN KEY (config_id) REFERENCES slack_configs(id) ON DELETE CASCADE,
+  FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+);
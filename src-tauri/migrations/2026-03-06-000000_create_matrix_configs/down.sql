@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS connector_sessions;
+DROP TABLE IF EXISTS matrix_configs;
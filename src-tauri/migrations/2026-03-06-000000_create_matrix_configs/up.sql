@@ -0,0 +1,28 @@
+-- access_token belongs to the bot's own Matrix account on homeserver_url
+CREATE TABLE matrix_configs (
+  id TEXT NOT NULL PRIMARY KEY,
+  homeserver_url TEXT NOT NULL,
+  access_token TEXT NOT NULL,
+  agent_id TEXT NOT NULL,
+  is_active INTEGER NOT NULL DEFAULT 0,
+  allowed_room_ids TEXT,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (agent_id) REFERENCES agents(id)
+);
+
+CREATE INDEX idx_matrix_configs_agent_id ON matrix_configs(agent_id);
+CREATE INDEX idx_matrix_configs_is_active ON matrix_configs(is_active);
+
+-- Agent sessions of connectors built on the shared messaging layer: one per
+-- conversation, and per thread within it ('' outside of threads)
+CREATE TABLE connector_sessions (
+  platform TEXT NOT NULL,
+  config_id TEXT NOT NULL,
+  conversation_id TEXT NOT NULL,
+  thread_id TEXT NOT NULL DEFAULT '',
+  session_id TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (platform, config_id, conversation_id, thread_id),
+  FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+);
@@ -0,0 +1,18 @@
+-- One row per resolved permission request. id is the request id; decided_by
+-- names who answered ("app", "telegram:<user id>", "cache", ...)
+CREATE TABLE permission_audit (
+  id TEXT NOT NULL PRIMARY KEY,
+  session_id TEXT,
+  tool TEXT,
+  permission_type TEXT NOT NULL,
+  resource TEXT,
+  message TEXT NOT NULL,
+  metadata TEXT NOT NULL DEFAULT '{}',
+  resolution TEXT NOT NULL,
+  decided_by TEXT NOT NULL,
+  requested_at TIMESTAMP NOT NULL,
+  decided_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_permission_audit_session_id ON permission_audit(session_id);
+CREATE INDEX idx_permission_audit_decided_at ON permission_audit(decided_at);
@@ -0,0 +1,14 @@
+-- "Always allow" answers. scope is session, agent, workspace or global;
+-- scope_id is the session id, agent id or workspace path (NULL for global)
+CREATE TABLE permission_grants (
+  id TEXT NOT NULL PRIMARY KEY,
+  scope TEXT NOT NULL,
+  scope_id TEXT,
+  permission_type TEXT NOT NULL,
+  resource TEXT NOT NULL,
+  granted_by TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  expires_at TIMESTAMP
+);
+
+CREATE INDEX idx_permission_grants_lookup ON permission_grants(permission_type, resource);
@@ -0,0 +1,2 @@
+-- JSON PermissionPayload of the request, when it had one
+ALTER TABLE permission_audit ADD COLUMN payload TEXT;
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS agent_secret_grants;
+DROP TABLE IF EXISTS secrets;
@@ -0,0 +1,15 @@
+-- Named secrets; the values live in the OS keyring, keyed by name
+CREATE TABLE secrets (
+  name TEXT NOT NULL PRIMARY KEY,
+  description TEXT,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Which agents may use which secrets
+CREATE TABLE agent_secret_grants (
+  agent_id TEXT NOT NULL,
+  secret_name TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (agent_id, secret_name)
+);
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_messages_session_live;
+ALTER TABLE messages DROP COLUMN deleted_at;
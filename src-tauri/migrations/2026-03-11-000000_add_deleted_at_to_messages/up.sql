@@ -0,0 +1,4 @@
+-- Set when an edit or regenerate rewinds the session past the message;
+-- such messages are kept but no longer part of the conversation
+ALTER TABLE messages ADD COLUMN deleted_at TIMESTAMP;
+CREATE INDEX IF NOT EXISTS idx_messages_session_live ON messages(session_id, deleted_at, created_at);
@@ -0,0 +1,13 @@
+DROP TRIGGER search_blocks_delete;
+DROP TRIGGER search_blocks_update;
+DROP TRIGGER search_blocks_insert;
+DROP TRIGGER search_pages_delete;
+DROP TRIGGER search_pages_update;
+DROP TRIGGER search_pages_insert;
+DROP TRIGGER search_messages_delete;
+DROP TRIGGER search_messages_update;
+DROP TRIGGER search_messages_insert;
+DROP TRIGGER search_sessions_delete;
+DROP TRIGGER search_sessions_update;
+DROP TRIGGER search_sessions_insert;
+DROP TABLE search_index;
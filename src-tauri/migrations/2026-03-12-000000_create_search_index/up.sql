@@ -0,0 +1,105 @@
+-- Full-text index behind global search: session titles, chat messages,
+-- page titles and page blocks (mail has mail_messages_fts). The triggers
+-- below keep it in sync. Only `body` is searched; `kind`, `doc_id` and
+-- `parent_id` map a hit back to what to open, and `context` is the title
+-- shown with it (the session or page it is in).
+CREATE VIRTUAL TABLE search_index USING fts5(
+    kind UNINDEXED,
+    doc_id UNINDEXED,
+    parent_id UNINDEXED,
+    context UNINDEXED,
+    body,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+-- Sessions
+INSERT INTO search_index (kind, doc_id, parent_id, context, body)
+SELECT 'session', id, NULL, title, title FROM sessions WHERE title IS NOT NULL;
+
+CREATE TRIGGER search_sessions_insert AFTER INSERT ON sessions WHEN NEW.title IS NOT NULL BEGIN
+    INSERT INTO search_index (kind, doc_id, parent_id, context, body)
+    VALUES ('session', NEW.id, NULL, NEW.title, NEW.title);
+END;
+
+CREATE TRIGGER search_sessions_update AFTER UPDATE OF title ON sessions BEGIN
+    DELETE FROM search_index WHERE kind = 'session' AND doc_id = OLD.id;
+    INSERT INTO search_index (kind, doc_id, parent_id, context, body)
+    SELECT 'session', NEW.id, NULL, NEW.title, NEW.title WHERE NEW.title IS NOT NULL;
+    UPDATE search_index SET context = NEW.title WHERE kind = 'message' AND parent_id = NEW.id;
+END;
+
+CREATE TRIGGER search_sessions_delete AFTER DELETE ON sessions BEGIN
+    DELETE FROM search_index WHERE kind = 'session' AND doc_id = OLD.id;
+END;
+
+-- Messages: what the user and agents wrote; tool output and messages
+-- removed by an edit or regenerate are left out
+INSERT INTO search_index (kind, doc_id, parent_id, context, body)
+SELECT 'message', m.id, m.session_id, s.title, m.content
+FROM messages m LEFT JOIN sessions s ON s.id = m.session_id
+WHERE m.role != 'tool' AND m.deleted_at IS NULL;
+
+CREATE TRIGGER search_messages_insert AFTER INSERT ON messages
+WHEN NEW.role != 'tool' AND NEW.deleted_at IS NULL BEGIN
+    INSERT INTO search_index (kind, doc_id, parent_id, context, body)
+    VALUES ('message', NEW.id, NEW.session_id, (SELECT title FROM sessions WHERE id = NEW.session_id), NEW.content);
+END;
+
+CREATE TRIGGER search_messages_update AFTER UPDATE OF content, deleted_at ON messages BEGIN
+    DELETE FROM search_index WHERE kind = 'message' AND doc_id = OLD.id;
+    INSERT INTO search_index (kind, doc_id, parent_id, context, body)
+    SELECT 'message', NEW.id, NEW.session_id, (SELECT title FROM sessions WHERE id = NEW.session_id), NEW.content
+    WHERE NEW.role != 'tool' AND NEW.deleted_at IS NULL;
+END;
+
+CREATE TRIGGER search_messages_delete AFTER DELETE ON messages BEGIN
+    DELETE FROM search_index WHERE kind = 'message' AND doc_id = OLD.id;
+END;
+
+-- Pages
+INSERT INTO search_index (kind, doc_id, parent_id, context, body)
+SELECT 'page', id, NULL, title, title FROM pages;
+
+CREATE TRIGGER search_pages_insert AFTER INSERT ON pages BEGIN
+    INSERT INTO search_index (kind, doc_id, parent_id, context, body)
+    VALUES ('page', NEW.id, NULL, NEW.title, NEW.title);
+END;
+
+CREATE TRIGGER search_pages_update AFTER UPDATE OF title ON pages BEGIN
+    UPDATE search_index SET context = NEW.title, body = NEW.title WHERE kind = 'page' AND doc_id = NEW.id;
+    UPDATE search_index SET context = NEW.title WHERE kind = 'block' AND parent_id = NEW.id;
+END;
+
+CREATE TRIGGER search_pages_delete AFTER DELETE ON pages BEGIN
+    DELETE FROM search_index WHERE kind = 'page' AND doc_id = OLD.id;
+    DELETE FROM search_index WHERE kind = 'block' AND parent_id = OLD.id;
+END;
+
+-- Blocks: the text values in content_json, not its keys
+INSERT INTO search_index (kind, doc_id, parent_id, context, body)
+SELECT 'block', b.id, b.page_id, p.title,
+    CASE WHEN json_valid(b.content_json)
+        THEN (SELECT group_concat(value, ' ') FROM json_tree(b.content_json) WHERE type = 'text')
+        ELSE b.content_json END
+FROM blocks b LEFT JOIN pages p ON p.id = b.page_id;
+
+CREATE TRIGGER search_blocks_insert AFTER INSERT ON blocks BEGIN
+    INSERT INTO search_index (kind, doc_id, parent_id, context, body)
+    VALUES ('block', NEW.id, NEW.page_id, (SELECT title FROM pages WHERE id = NEW.page_id),
+        CASE WHEN json_valid(NEW.content_json)
+            THEN (SELECT group_concat(value, ' ') FROM json_tree(NEW.content_json) WHERE type = 'text')
+            ELSE NEW.content_json END);
+END;
+
+CREATE TRIGGER search_blocks_update AFTER UPDATE OF content_json, page_id ON blocks BEGIN
+    DELETE FROM search_index WHERE kind = 'block' AND doc_id = OLD.id;
+    INSERT INTO search_index (kind, doc_id, parent_id, context, body)
+    VALUES ('block', NEW.id, NEW.page_id, (SELECT title FROM pages WHERE id = NEW.page_id),
+        CASE WHEN json_valid(NEW.content_json)
+            THEN (SELECT group_concat(value, ' ') FROM json_tree(NEW.content_json) WHERE type = 'text')
+            ELSE NEW.content_json END);
+END;
+
+CREATE TRIGGER search_blocks_delete AFTER DELETE ON blocks BEGIN
+    DELETE FROM search_index WHERE kind = 'block' AND doc_id = OLD.id;
+END;
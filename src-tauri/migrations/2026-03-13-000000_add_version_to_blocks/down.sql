@@ -0,0 +1,2 @@
+ALTER TABLE blocks DROP COLUMN updated_by;
+ALTER TABLE blocks DROP COLUMN version;
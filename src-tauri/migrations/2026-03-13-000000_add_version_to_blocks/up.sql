@@ -0,0 +1,5 @@
+-- Bumped by every write; editors send the version they edited so
+-- concurrent writes to the same block are detected instead of lost
+ALTER TABLE blocks ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+-- "user", "agent:<session id>" or "file"
+ALTER TABLE blocks ADD COLUMN updated_by TEXT;
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_attachments_content_hash;
+ALTER TABLE attachments DROP COLUMN thumbnail_path;
+ALTER TABLE attachments DROP COLUMN mime_type;
+ALTER TABLE attachments DROP COLUMN content_hash;
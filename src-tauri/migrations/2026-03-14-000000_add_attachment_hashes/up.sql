@@ -0,0 +1,7 @@
+-- SHA-256 of the file, hex; uploads with the same content share one file.
+-- Rows from before this are not deduplicated.
+ALTER TABLE attachments ADD COLUMN content_hash TEXT;
+-- Sniffed from the content, falling back to the extension
+ALTER TABLE attachments ADD COLUMN mime_type TEXT;
+ALTER TABLE attachments ADD COLUMN thumbnail_path TEXT;
+CREATE INDEX IF NOT EXISTS idx_attachments_content_hash ON attachments(content_hash);
@@ -0,0 +1,10 @@
+-- The checklist an agent keeps with the `todo` tool, one row per job.
+-- items is a JSON array of {id, content, status}, replaced on every update.
+CREATE TABLE job_todos (
+  job_id TEXT NOT NULL PRIMARY KEY,
+  session_id TEXT NOT NULL,
+  items TEXT NOT NULL,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_job_todos_session ON job_todos(session_id, updated_at);
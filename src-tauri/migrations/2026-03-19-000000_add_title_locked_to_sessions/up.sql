@@ -0,0 +1,2 @@
+-- Set when the user renames a session; generated titles never replace it
+ALTER TABLE sessions ADD COLUMN title_locked INTEGER NOT NULL DEFAULT 0;
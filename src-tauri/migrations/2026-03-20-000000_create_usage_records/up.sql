@@ -0,0 +1,19 @@
+-- What agent runs used, one row per job, per tool call and per model a job
+-- called. Rolled up by get_usage_stats for the usage dashboard.
+CREATE TABLE usage_records (
+  id TEXT NOT NULL PRIMARY KEY,
+  kind TEXT NOT NULL,
+  job_id TEXT NOT NULL,
+  session_id TEXT NOT NULL,
+  agent_id TEXT NOT NULL,
+  provider TEXT NOT NULL,
+  model TEXT NOT NULL,
+  tool TEXT,
+  success INTEGER NOT NULL DEFAULT 1,
+  input_tokens INTEGER NOT NULL DEFAULT 0,
+  output_tokens INTEGER NOT NULL DEFAULT 0,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_usage_records_created ON usage_records(created_at);
+CREATE INDEX idx_usage_records_job ON usage_records(job_id);
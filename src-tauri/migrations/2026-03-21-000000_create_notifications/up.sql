@@ -0,0 +1,15 @@
+-- Notification inbox: what agents raised for the user's attention, read or
+-- not. Which events land here and where else they go is the
+-- notification_preferences setting.
+CREATE TABLE notifications (
+  id TEXT NOT NULL PRIMARY KEY,
+  event TEXT NOT NULL,
+  title TEXT NOT NULL,
+  body TEXT NOT NULL,
+  agent_id TEXT,
+  session_id TEXT,
+  is_read INTEGER NOT NULL DEFAULT 0,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_notifications_created ON notifications(created_at);
@@ -0,0 +1,13 @@
+-- Events streamed to the UI while a session's jobs run, tokens coalesced.
+-- The id is the cursor get_session_events reads from.
+CREATE TABLE session_events (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  session_id TEXT NOT NULL,
+  job_id TEXT,
+  event_type TEXT NOT NULL,
+  payload TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_session_events_session ON session_events(session_id, id);
+CREATE INDEX idx_session_events_job ON session_events(job_id);
@@ -0,0 +1 @@
+ALTER TABLE agent_skills ADD COLUMN read_only INTEGER NOT NULL DEFAULT 0;
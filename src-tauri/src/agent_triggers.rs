@@ -0,0 +1,509 @@
+//! Event-triggered agent runs
+//!
+//! Agents subscribe to events through `agent_triggers` rows: file changes in
+//! their workspace, new mail in their inbox, or Telegram messages matching a
+//! pattern. A matching event starts a headless run with the event described in
+//! the prompt. Each trigger is rate limited (`max_runs_per_hour`), and file
+//! events are ignored while the agent is already handling one of its triggers,
+//! so an agent does not retrigger itself by editing its own workspace.
+
+use crate::scheduler::{load_agent, prepare_session, run_agent, run_agent_with_observer};
+use anyagents::database::DbPool;
+use anyagents::models::trigger::get_enabled_triggers;
+use anyagents::models::AgentTrigger;
+use dashmap::{DashMap, DashSet};
+use diesel::prelude::*;
+use notify::{RecursiveMode, Watcher};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tokio::sync::mpsc;
+
+/// File events are collected until the workspace has been quiet this long
+const FILE_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// How often the inbox is checked for new mail
+const MAIL_POLL_SECS: u64 = 10;
+
+/// Most changed paths listed in a prompt
+const MAX_LISTED_PATHS: usize = 50;
+
+/// Directories whose changes never trigger an agent
+const IGNORED_DIRS: [&str; 4] = [".git", "node_modules", "target", ".anycowork"];
+
+pub const TRIGGER_FIRED_EVENT: &str = "triggers:fired";
+
+#[derive(Debug, Clone)]
+pub enum TriggerEvent {
+    FileChanged {
+        agent_id: String,
+        paths: Vec<String>, // relative to the workspace
+    },
+    NewMail {
+        agent_id: String,
+        thread_id: String,
+        from: String,
+        subject: String,
+        body: String,
+    },
+    TelegramMessage {
+        agent_id: String,
+        chat_id: i64,
+        text: String,
+    },
+}
+
+impl TriggerEvent {
+    pub fn agent_id(&self) -> &str {
+        match self {
+            TriggerEvent::FileChanged { agent_id, .. }
+            | TriggerEvent::NewMail { agent_id, .. }
+            | TriggerEvent::TelegramMessage { agent_id, .. } => agent_id,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TriggerEvent::FileChanged { .. } => "file_change",
+            TriggerEvent::NewMail { .. } => "mail",
+            TriggerEvent::TelegramMessage { .. } => "telegram",
+        }
+    }
+
+    /// Whether a trigger's pattern accepts this event (no pattern matches everything)
+    pub fn matches(&self, pattern: Option<&str>) -> bool {
+        let Some(pattern) = pattern.filter(|p| !p.trim().is_empty()) else {
+            return true;
+        };
+        match self {
+            TriggerEvent::FileChanged { paths, .. } => match glob::Pattern::new(pattern) {
+                Ok(glob) => paths.iter().any(|p| glob.matches(p)),
+                Err(_) => false,
+            },
+            TriggerEvent::NewMail { subject, body, .. } => match regex::Regex::new(pattern) {
+                Ok(re) => re.is_match(subject) || re.is_match(body),
+                Err(_) => false,
+            },
+            TriggerEvent::TelegramMessage { text, .. } => {
+                regex::Regex::new(pattern).is_ok_and(|re| re.is_match(text))
+            }
+        }
+    }
+
+    /// The triggering context injected into the prompt
+    pub fn describe(&self) -> String {
+        match self {
+            TriggerEvent::FileChanged { paths, .. } => {
+                let mut listed: Vec<&str> = paths.iter().take(MAX_LISTED_PATHS).map(String::as_str).collect();
+                let more = paths.len().saturating_sub(MAX_LISTED_PATHS);
+                let extra = if more > 0 { format!("\n(and {} more)", more) } else { String::new() };
+                listed.sort_unstable();
+                format!("Files changed in your workspace:\n{}{}", listed.join("\n"), extra)
+            }
+            TriggerEvent::NewMail { from, subject, body, thread_id, .. } => format!(
+                "New mail from {} (thread {}).\nSubject: {}\n\n{}",
+                from, thread_id, subject, body
+            ),
+            TriggerEvent::TelegramMessage { chat_id, text, .. } => {
+                format!("New Telegram message in chat {}:\n{}", chat_id, text)
+            }
+        }
+    }
+}
+
+pub fn render_trigger_prompt(trigger: &AgentTrigger, event: &TriggerEvent) -> String {
+    let instructions = trigger
+        .prompt_template
+        .as_deref()
+        .filter(|t| !t.trim().is_empty())
+        .unwrap_or("Decide whether this needs any action and, if so, take it.");
+    format!("[Trigger: {}]\n{}\n\n{}", trigger.kind, event.describe(), instructions)
+}
+
+#[derive(Serialize, Clone)]
+struct TriggerFired {
+    trigger_id: String,
+    agent_id: String,
+    kind: String,
+    session_id: String,
+}
+
+pub struct TriggerEngine {
+    sender: mpsc::UnboundedSender<TriggerEvent>,
+    receiver: Mutex<Option<mpsc::UnboundedReceiver<TriggerEvent>>>,
+    /// Recent fire times per trigger, for rate limiting
+    fired: DashMap<String, VecDeque<Instant>>,
+    /// Agents currently handling a trigger
+    busy_agents: Arc<DashSet<String>>,
+    watchers: Mutex<HashMap<String, notify::RecommendedWatcher>>,
+}
+
+impl TriggerEngine {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self {
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+            fired: DashMap::new(),
+            busy_agents: Arc::new(DashSet::new()),
+            watchers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Report an event (e.g. from the Telegram bot); it is matched against triggers asynchronously
+    pub fn emit(&self, event: TriggerEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    /// Record a run if `trigger_id` is under its hourly limit
+    pub fn try_acquire(&self, trigger_id: &str, max_runs_per_hour: i32, now: Instant) -> bool {
+        let mut fired = self.fired.entry(trigger_id.to_string()).or_default();
+        while fired
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= Duration::from_secs(3600))
+        {
+            fired.pop_front();
+        }
+        if fired.len() >= max_runs_per_hour.max(0) as usize {
+            return false;
+        }
+        fired.push_back(now);
+        true
+    }
+
+    /// Watch the workspace of every agent with an enabled file trigger (and stop watching the rest)
+    pub fn refresh_watchers(&self, pool: &DbPool) -> Result<(), String> {
+        use anyagents::schema::agents;
+
+        let triggers = get_enabled_triggers(pool, "file_change", None)?;
+        let wanted: Vec<String> = triggers.into_iter().map(|t| t.agent_id).collect();
+
+        let mut watchers = self.watchers.lock().unwrap();
+        watchers.retain(|agent_id, _| wanted.contains(agent_id));
+
+        let mut conn = pool.get().map_err(|e| e.to_string())?;
+        for agent_id in wanted {
+            if watchers.contains_key(&agent_id) {
+                continue;
+            }
+            let workspace: Option<String> = agents::table
+                .find(&agent_id)
+                .select(agents::workspace_path)
+                .first(&mut conn)
+                .unwrap_or(None);
+            let Some(workspace) = workspace else {
+                log::warn!("Agent {} has a file trigger but no workspace", agent_id);
+                continue;
+            };
+
+            let root = PathBuf::from(&workspace);
+            let sender = self.sender.clone();
+            let watched_agent = agent_id.clone();
+            let watch_root = root.clone();
+            let watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+                let Ok(event) = res else {
+                    return;
+                };
+                if event.kind.is_access() {
+                    return;
+                }
+                let paths: Vec<String> = event
+                    .paths
+                    .iter()
+                    .filter_map(|p| relative_path(&watch_root, p))
+                    .collect();
+                if !paths.is_empty() {
+                    let _ = sender.send(TriggerEvent::FileChanged {
+                        agent_id: watched_agent.clone(),
+                        paths,
+                    });
+                }
+            });
+
+            match watcher {
+                Ok(mut watcher) => match watcher.watch(&root, RecursiveMode::Recursive) {
+                    Ok(()) => {
+                        watchers.insert(agent_id, watcher);
+                    }
+                    Err(e) => log::error!("Failed to watch {}: {}", workspace, e),
+                },
+                Err(e) => log::error!("Failed to create file watcher: {}", e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Start processing events and polling the inbox. Call once.
+    pub fn start<R: Runtime>(self: &Arc<Self>, app: AppHandle<R>, pool: DbPool) {
+        let Some(mut receiver) = self.receiver.lock().unwrap().take() else {
+            log::warn!("Trigger engine already started");
+            return;
+        };
+        if let Err(e) = self.refresh_watchers(&pool) {
+            log::error!("Failed to start file watchers: {}", e);
+        }
+
+        let engine = self.clone();
+        let mail_pool = pool.clone();
+        tauri::async_runtime::spawn(async move {
+            let mut last_check = chrono::Utc::now().naive_utc();
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(MAIL_POLL_SECS));
+            loop {
+                interval.tick().await;
+                match new_mail_events(&mail_pool, last_check) {
+                    Ok((events, checked_at)) => {
+                        last_check = checked_at;
+                        for event in events {
+                            engine.emit(event);
+                        }
+                    }
+                    Err(e) => log::error!("Failed to check mail for triggers: {}", e),
+                }
+            }
+        });
+
+        let engine = self.clone();
+        tauri::async_runtime::spawn(async move {
+            let mut pending_files: HashMap<String, (Vec<String>, Instant)> = HashMap::new();
+            let mut flush = tokio::time::interval(Duration::from_millis(500));
+            loop {
+                tokio::select! {
+                    event = receiver.recv() => {
+                        let Some(event) = event else { break };
+                        match event {
+                            TriggerEvent::FileChanged { agent_id, paths } => {
+                                if engine.busy_agents.contains(&agent_id) {
+                                    continue;
+                                }
+                                let entry = pending_files.entry(agent_id).or_insert_with(|| (vec![], Instant::now()));
+                                for path in paths {
+                                    if !entry.0.contains(&path) {
+                                        entry.0.push(path);
+                                    }
+                                }
+                                entry.1 = Instant::now();
+                            }
+                            event => engine.dispatch(&app, &pool, event),
+                        }
+                    }
+                    _ = flush.tick() => {
+                        let quiet: Vec<String> = pending_files
+                            .iter()
+                            .filter(|(_, (_, last))| last.elapsed() >= FILE_DEBOUNCE)
+                            .map(|(agent_id, _)| agent_id.clone())
+                            .collect();
+                        for agent_id in quiet {
+                            if let Some((paths, _)) = pending_files.remove(&agent_id) {
+                                engine.dispatch(&app, &pool, TriggerEvent::FileChanged { agent_id, paths });
+                            }
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    /// Start a run for every enabled, matching trigger that is under its rate limit
+    fn dispatch<R: Runtime>(&self, app: &AppHandle<R>, pool: &DbPool, event: TriggerEvent) {
+        let triggers = match get_enabled_triggers(pool, event.kind(), Some(event.agent_id())) {
+            Ok(triggers) => triggers,
+            Err(e) => {
+                log::error!("Failed to load triggers: {}", e);
+                return;
+            }
+        };
+
+        for trigger in triggers {
+            if !event.matches(trigger.pattern.as_deref()) {
+                continue;
+            }
+            if !self.try_acquire(&trigger.id, trigger.max_runs_per_hour, Instant::now()) {
+                log::info!("Trigger {} is rate limited; skipping", trigger.id);
+                continue;
+            }
+
+            let app = app.clone();
+            let pool = pool.clone();
+            let event = event.clone();
+            let busy_agents = self.busy_agents.clone();
+            busy_agents.insert(trigger.agent_id.clone());
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = run_trigger(&app, &pool, &trigger, &event).await {
+                    log::error!("Trigger {} failed: {}", trigger.id, e);
+                }
+                busy_agents.remove(&trigger.agent_id);
+            });
+        }
+    }
+}
+
+impl Default for TriggerEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn run_trigger<R: Runtime>(
+    app: &AppHandle<R>,
+    pool: &DbPool,
+    trigger: &AgentTrigger,
+    event: &TriggerEvent,
+) -> Result<(), String> {
+    let agent = load_agent(pool, &trigger.agent_id)?;
+    let prompt = render_trigger_prompt(trigger, event);
+    let title = match event {
+        TriggerEvent::FileChanged { .. } => "Triggered: file changes".to_string(),
+        TriggerEvent::NewMail { subject, .. } => format!("Triggered: {}", subject),
+        TriggerEvent::TelegramMessage { .. } => "Triggered: Telegram message".to_string(),
+    };
+    let session_id = prepare_session(pool, &agent.id, None, &title, &prompt)?;
+
+    let _ = app.emit(
+        TRIGGER_FIRED_EVENT,
+        TriggerFired {
+            trigger_id: trigger.id.clone(),
+            agent_id: agent.id.clone(),
+            kind: trigger.kind.clone(),
+            session_id: session_id.clone(),
+        },
+    );
+    let job_id = uuid::Uuid::new_v4().to_string();
+
+    // Runs started from a chat report back to it and take approvals there
+    if let TriggerEvent::TelegramMessage { chat_id, .. } = event {
+        let telegram = app.state::<crate::AppState>().telegram_manager.clone();
+        let inner = Arc::new(crate::events::TauriAppObserver { app: app.clone() });
+        if let Some(reporter) = telegram.run_reporter(&agent.id, *chat_id, &session_id, inner).await {
+            let answer = run_agent_with_observer(app, &agent, &session_id, prompt, job_id, reporter.clone()).await;
+            reporter.finish(answer);
+            return Ok(());
+        }
+    }
+    run_agent(app, &agent, &session_id, prompt, job_id).await;
+    Ok(())
+}
+
+fn relative_path(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    if relative
+        .components()
+        .any(|c| IGNORED_DIRS.contains(&c.as_os_str().to_string_lossy().as_ref()))
+    {
+        return None;
+    }
+    Some(relative.to_string_lossy().replace('\\', "/"))
+}
+
+/// Mail delivered to agents since `since`, and the time this check covers up to.
+/// A message to several agents gives each of them an event.
+fn new_mail_events(
+    pool: &DbPool,
+    since: chrono::NaiveDateTime,
+) -> Result<(Vec<TriggerEvent>, chrono::NaiveDateTime), String> {
+    use anyagents::email::recipients;
+    use anyagents::models::{Agent, MailMessage, MailThread};
+    use anyagents::schema::{agents, mail_messages, mail_threads};
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let messages: Vec<(MailMessage, MailThread)> = mail_messages::table
+        .inner_join(mail_threads::table)
+        .filter(mail_messages::created_at.gt(since))
+        .order(mail_messages::created_at.asc())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())?;
+
+    let checked_at = messages.last().map(|(m, _)| m.created_at).unwrap_or(since);
+    let message_ids: Vec<String> = messages.iter().map(|(m, _)| m.id.clone()).collect();
+    let mut recipient_rows = recipients::for_messages(pool, &message_ids)?;
+
+    let mut events = Vec::new();
+    for (message, thread) in messages {
+        let mut agent_ids: Vec<String> = message.recipient_agent_id.iter().cloned().collect();
+        for row in recipient_rows.remove(&message.id).unwrap_or_default() {
+            if let Some(agent_id) = row.recipient_agent_id {
+                if !agent_ids.contains(&agent_id) {
+                    agent_ids.push(agent_id);
+                }
+            }
+        }
+        if agent_ids.is_empty() {
+            continue;
+        }
+
+        let from = match &message.sender_agent_id {
+            Some(sender) => agents::table
+                .find(sender)
+                .first::<Agent>(&mut conn)
+                .map(|a| a.name)
+                .unwrap_or_else(|_| sender.clone()),
+            None => message
+                .external_address
+                .clone()
+                .unwrap_or_else(|| "the user".to_string()),
+        };
+        for agent_id in agent_ids {
+            events.push(TriggerEvent::NewMail {
+                agent_id,
+                thread_id: thread.id.clone(),
+                from: from.clone(),
+                subject: thread.subject.clone(),
+                body: message.content.clone(),
+            });
+        }
+    }
+    Ok((events, checked_at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_event(paths: &[&str]) -> TriggerEvent {
+        TriggerEvent::FileChanged {
+            agent_id: "a1".to_string(),
+            paths: paths.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_pattern_matching() {
+        let event = file_event(&["docs/readme.md", "src/main.rs"]);
+        assert!(event.matches(None));
+        assert!(event.matches(Some("**/*.rs")));
+        assert!(!event.matches(Some("*.py")));
+
+        let telegram = TriggerEvent::TelegramMessage {
+            agent_id: "a1".to_string(),
+            chat_id: 1,
+            text: "deploy staging please".to_string(),
+        };
+        assert!(telegram.matches(Some(r"^deploy\b")));
+        assert!(!telegram.matches(Some("rollback")));
+        // Invalid regexes never match
+        assert!(!telegram.matches(Some("(")));
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let engine = TriggerEngine::new();
+        let start = Instant::now();
+        assert!(engine.try_acquire("t1", 2, start));
+        assert!(engine.try_acquire("t1", 2, start));
+        assert!(!engine.try_acquire("t1", 2, start));
+        // Other triggers have their own budget
+        assert!(engine.try_acquire("t2", 2, start));
+        // Slots free up after an hour
+        assert!(engine.try_acquire("t1", 2, start + Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn test_relative_path_skips_ignored_dirs() {
+        let root = Path::new("/work");
+        assert_eq!(relative_path(root, Path::new("/work/src/a.rs")).as_deref(), Some("src/a.rs"));
+        assert_eq!(relative_path(root, Path::new("/work/.git/index")), None);
+        assert_eq!(relative_path(root, Path::new("/elsewhere/a.rs")), None);
+    }
+}
@@ -1,12 +1,16 @@
-use anyagents::models::{Agent, AgentDto, NewAgent};
+use anyagents::models::agent_version::{self, AgentVersion};
+use anyagents::models::{Agent, AgentDto, GrantScope, NewAgent};
 use anyagents::schema;
+use anyagents::tools::registry::{GROUP_MCP, GROUP_SKILLS};
 use crate::AppState;
 use diesel::prelude::*;
 use tauri::State;
 use std::sync::Arc;
 use tauri::{Runtime, WebviewWindow};
 use anyagents::agents::coordinator::Coordinator;
+use crate::commands::window::ensure_can_act;
 use crate::events::TauriAgentObserver;
+use anyagents::event_channel::{EventChannel, EventChannelConfig};
 
 #[tauri::command]
 pub async fn create_agent(
@@ -17,57 +21,62 @@ pub async fn create_agent(
 ) -> Result<AgentDto, String> {
     use anyagents::schema::agents;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    // Default AI config (could be passed in future)
-    let ai_provider = "gemini".to_string();
-    let ai_model = "gemini-3-flash-preview".to_string();
-    let ai_config_json = serde_json::json!({
-        "provider": ai_provider,
-        "model": ai_model
-    })
-    .to_string();
-
-    let new_agent = NewAgent {
-        id: uuid::Uuid::new_v4().to_string(),
-        name,
-        description: Some(description),
-        status: "active".to_string(),
-        personality: None,
-        tone: None,
-        expertise: None,
-        ai_provider,
-        ai_model,
-        ai_temperature: 0.7,
-        ai_config: ai_config_json,
-        system_prompt: Some(system_prompt),
-        permissions: None,
-        working_directories: None,
-        skills: None,
-        mcp_servers: None,
-        messaging_connections: None,
-        knowledge_bases: None,
-        api_keys: None,
-        created_at: chrono::Utc::now().timestamp(),
-        updated_at: chrono::Utc::now().timestamp(),
-        platform_configs: None,
-        execution_settings: None,
-        scope_type: None,
-        workspace_path: None,
-        avatar: None,
-    };
-
-    diesel::insert_into(agents::table)
-        .values(&new_agent)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+    let pool = state.db_pool.clone();
+    let agent = anyagents::database::blocking(&state.db_pool, move |conn| {
+        // Default AI config (could be passed in future)
+        let ai_provider = "gemini".to_string();
+        let ai_model = "gemini-3-flash-preview".to_string();
+        let ai_config_json = serde_json::json!({
+            "provider": ai_provider,
+            "model": ai_model
+        })
+        .to_string();
+
+        let new_agent = NewAgent {
+            id: uuid::Uuid::new_v4().to_string(),
+            name,
+            description: Some(description),
+            status: "active".to_string(),
+            personality: None,
+            tone: None,
+            expertise: None,
+            ai_provider,
+            ai_model,
+            ai_temperature: 0.7,
+            ai_config: ai_config_json,
+            system_prompt: Some(system_prompt),
+            permissions: None,
+            working_directories: None,
+            skills: None,
+            mcp_servers: None,
+            messaging_connections: None,
+            knowledge_bases: None,
+            api_keys: None,
+            created_at: chrono::Utc::now().timestamp(),
+            updated_at: chrono::Utc::now().timestamp(),
+            platform_configs: None,
+            execution_settings: None,
+            scope_type: None,
+            workspace_path: None,
+            avatar: None,
+            agent_tools: None,
+        };
+
+        diesel::insert_into(agents::table)
+            .values(&new_agent)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
 
-    let created_id = new_agent.id.to_string();
+        let created_id = new_agent.id.to_string();
 
-    let agent: Agent = agents::table
-        .filter(agents::id.eq(created_id))
-        .first::<Agent>(&mut conn)
-        .map_err(|e| e.to_string())?;
+        let agent: Agent = agents::table
+            .filter(agents::id.eq(created_id))
+            .first::<Agent>(conn)
+            .map_err(|e| e.to_string())?;
+        agent_version::record(&pool, &agent, Some("Created".to_string()))?;
+        Ok(agent)
+    })
+    .await?;
 
     Ok(agent.into_dto())
 }
@@ -76,10 +85,35 @@ pub async fn create_agent(
 pub async fn get_agents(state: State<'_, AppState>) -> Result<Vec<AgentDto>, String> {
     use anyagents::schema::agents::dsl::*;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-    let results = agents.load::<Agent>(&mut conn).map_err(|e| e.to_string())?;
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let results = agents.load::<Agent>(conn).map_err(|e| e.to_string())?;
 
-    Ok(results.into_iter().map(|a| a.into_dto()).collect())
+        Ok(results.into_iter().map(|a| a.into_dto()).collect())
+    })
+    .await
+}
+
+/// Write the agent's shareable configuration, skills and MCP servers to
+/// `path` as a bundle
+#[tauri::command]
+pub async fn export_agent_bundle(state: State<'_, AppState>, agent_id: String, path: String) -> Result<(), String> {
+    let bundle = anyagents::archive::export_agent(&state.db_pool, &agent_id)?;
+    let json = serde_json::to_string_pretty(&bundle).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+/// Create an agent from a bundle. Skills installed with different content
+/// make the import fail unless `options.skill_conflict` says what to do.
+#[tauri::command]
+pub async fn import_agent_bundle(
+    state: State<'_, AppState>,
+    path: String,
+    options: Option<anyagents::archive::AgentImportOptions>,
+) -> Result<anyagents::archive::AgentImportReport, String> {
+    let json = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let bundle: anyagents::archive::AgentBundle =
+        serde_json::from_str(&json).map_err(|e| format!("Not an agent bundle: {}", e))?;
+    anyagents::archive::import_agent(&state.db_pool, bundle, &options.unwrap_or_default())
 }
 
 #[tauri::command]
@@ -90,131 +124,164 @@ pub async fn update_agent(
 ) -> Result<AgentDto, String> {
     use anyagents::schema::agents::dsl::*;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    // Fetch existing agent
-    let mut agent = agents
-        .filter(id.eq(&agent_id))
-        .first::<Agent>(&mut conn)
-        .map_err(|_| "Agent not found".to_string())?;
+    let pool = state.db_pool.clone();
+    let agent = anyagents::database::blocking(&state.db_pool, move |conn| {
+        // Fetch existing agent
+        let mut agent = agents
+            .filter(id.eq(&agent_id))
+            .first::<Agent>(conn)
+            .map_err(|_| "Agent not found".to_string())?;
+        // Keeps the config as it was if this agent predates its history
+        agent_version::record(&pool, &agent, None)?;
+
+        // Check if this is the default agent - prevent skills/mcp changes
+        let is_default_agent = agent.name == "AnyCoworker Default";
+
+        // Update fields if present
+        if let Some(n) = data.name {
+            agent.name = n;
+        }
+        if let Some(d) = data.description {
+            agent.description = Some(d);
+        }
+        // Status update logic if needed
+        if let Some(s) = data.status {
+            agent.status = s;
+        }
 
-    // Check if this is the default agent - prevent skills/mcp changes
-    let is_default_agent = agent.name == "AnyCoworker Default";
+        // Characteristics update
+        if let Some(chars) = data.characteristics {
+            agent.personality = chars.personality;
+            agent.tone = chars.tone;
+            agent.expertise = Some(chars.expertise.join(", "));
+        }
 
-    // Update fields if present
-    if let Some(n) = data.name {
-        agent.name = n;
-    }
-    if let Some(d) = data.description {
-        agent.description = Some(d);
-    }
-    // Status update logic if needed
-    if let Some(s) = data.status {
-        agent.status = s;
-    }
+        // AI Config update
+        if let Some(config) = data.ai_config {
+            config.generation_params()?;
+            agent.ai_provider = config.provider.clone(); // Update root fields too
+            agent.ai_model = config.model.clone();
+            agent.ai_temperature = config.temperature;
 
-    // Characteristics update
-    if let Some(chars) = data.characteristics {
-        agent.personality = chars.personality;
-        agent.tone = chars.tone;
-        agent.expertise = Some(chars.expertise.join(", "));
-    }
+            let json_config = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+            agent.ai_config = json_config;
+        }
 
-    // AI Config update
-    if let Some(config) = data.ai_config {
-        agent.ai_provider = config.provider.clone(); // Update root fields too
-        agent.ai_model = config.model.clone();
-        agent.ai_temperature = config.temperature;
+        if let Some(prompt) = data.system_prompt {
+            agent.system_prompt = Some(prompt);
+        }
 
-        let json_config = serde_json::to_string(&config).map_err(|e| e.to_string())?;
-        agent.ai_config = json_config;
-    }
+        // Skills and MCP servers - skip for default agent (it uses all enabled)
+        if !is_default_agent {
+            if let Some(s) = data.skills {
+                agent.skills = Some(s.join(", "));
+
+                // Sync agent_skill_assignments
+                use anyagents::models::NewAgentSkillAssignment;
+                use anyagents::schema::agent_skill_assignments::dsl::{agent_skill_assignments, agent_id as col_agent_id};
+
+                // 1. Delete existing assignments for this agent
+                diesel::delete(agent_skill_assignments.filter(col_agent_id.eq(&agent_id)))
+                    .execute(conn)
+                    .map_err(|e| format!("Failed to clear old skill assignments: {}", e))?;
+
+                // 2. Insert new assignments
+                if !s.is_empty() {
+                     let new_assignments: Vec<NewAgentSkillAssignment> = s.iter().map(|sid| NewAgentSkillAssignment {
+                        agent_id: agent_id.clone(),
+                        skill_id: sid.clone(),
+                        created_at: chrono::Utc::now().naive_utc(),
+                    }).collect();
 
-    if let Some(prompt) = data.system_prompt {
-        agent.system_prompt = Some(prompt);
-    }
+                    diesel::insert_into(agent_skill_assignments)
+                        .values(&new_assignments)
+                        .execute(conn)
+                        .map_err(|e| format!("Failed to insert new skill assignments: {}", e))?;
+                }
+            }
 
-    // Skills and MCP servers - skip for default agent (it uses all enabled)
-    if !is_default_agent {
-        if let Some(s) = data.skills {
-            agent.skills = Some(s.join(", "));
-
-            // Sync agent_skill_assignments
-            use anyagents::models::NewAgentSkillAssignment;
-            use anyagents::schema::agent_skill_assignments::dsl::{agent_skill_assignments, agent_id as col_agent_id};
-
-            // 1. Delete existing assignments for this agent
-            diesel::delete(agent_skill_assignments.filter(col_agent_id.eq(&agent_id)))
-                .execute(&mut conn)
-                .map_err(|e| format!("Failed to clear old skill assignments: {}", e))?;
-
-            // 2. Insert new assignments
-            if !s.is_empty() {
-                 let new_assignments: Vec<NewAgentSkillAssignment> = s.iter().map(|sid| NewAgentSkillAssignment {
-                    agent_id: agent_id.clone(),
-                    skill_id: sid.clone(),
-                    created_at: chrono::Utc::now().naive_utc(),
-                }).collect();
-
-                diesel::insert_into(agent_skill_assignments)
-                    .values(&new_assignments)
-                    .execute(&mut conn)
-                    .map_err(|e| format!("Failed to insert new skill assignments: {}", e))?;
+            if let Some(m) = data.mcp_servers {
+                agent.mcp_servers = Some(m.join(", "));
             }
         }
 
-        if let Some(m) = data.mcp_servers {
-            agent.mcp_servers = Some(m.join(", "));
+        // Execution Settings
+        if let Some(settings) = data.execution_settings {
+            agent.execution_settings = Some(settings.to_string());
         }
-    }
 
-    // Execution Settings
-    if let Some(settings) = data.execution_settings {
-        agent.execution_settings = Some(settings.to_string());
-    }
+        // Tool selection and per-tool settings
+        if let Some(tools_config) = data.agent_tools {
+            tools_config.validate()?;
+            agent.agent_tools = Some(serde_json::to_string(&tools_config).map_err(|e| e.to_string())?);
+        }
 
-    // Platform Configs - especially for Telegram
-    if let Some(platform_configs_str) = data.platform_configs {
-        agent.platform_configs = Some(platform_configs_str.clone());
-    }
+        // Platform Configs - especially for Telegram
+        if let Some(platform_configs_str) = data.platform_configs {
+            agent.platform_configs = Some(platform_configs_str.clone());
+        }
 
-    agent.updated_at = chrono::Utc::now().timestamp();
-
-    // Save changes
-    diesel::update(agents.filter(id.eq(&agent_id)))
-        .set((
-            name.eq(&agent.name),
-            description.eq(&agent.description),
-            status.eq(&agent.status),
-            personality.eq(&agent.personality),
-            tone.eq(&agent.tone),
-            expertise.eq(&agent.expertise),
-            ai_provider.eq(&agent.ai_provider),
-            ai_model.eq(&agent.ai_model),
-            ai_temperature.eq(&agent.ai_temperature),
-            ai_config.eq(&agent.ai_config),
-            system_prompt.eq(&agent.system_prompt),
-            skills.eq(&agent.skills),
-            mcp_servers.eq(&agent.mcp_servers),
-            execution_settings.eq(&agent.execution_settings),
-            platform_configs.eq(&agent.platform_configs),
-            updated_at.eq(&agent.updated_at),
-        ))
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+        agent.updated_at = chrono::Utc::now().timestamp();
+
+        // Save changes
+        diesel::update(agents.filter(id.eq(&agent_id)))
+            .set((
+                name.eq(&agent.name),
+                description.eq(&agent.description),
+                status.eq(&agent.status),
+                personality.eq(&agent.personality),
+                tone.eq(&agent.tone),
+                expertise.eq(&agent.expertise),
+                ai_provider.eq(&agent.ai_provider),
+                ai_model.eq(&agent.ai_model),
+                ai_temperature.eq(&agent.ai_temperature),
+                ai_config.eq(&agent.ai_config),
+                system_prompt.eq(&agent.system_prompt),
+                skills.eq(&agent.skills),
+                mcp_servers.eq(&agent.mcp_servers),
+                execution_settings.eq(&agent.execution_settings),
+                platform_configs.eq(&agent.platform_configs),
+                agent_tools.eq(&agent.agent_tools),
+                updated_at.eq(&agent.updated_at),
+            ))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+        agent_version::record(&pool, &agent, None)?;
+        Ok(agent)
+    })
+    .await?;
 
     // Sync Telegram configuration if platform_configs changed
     if let Some(ref platform_configs_json) = agent.platform_configs {
-        log::info!("Syncing Telegram config for agent {}", agent_id);
-        match sync_agent_telegram_config(&state, &agent_id, platform_configs_json).await {
-            Ok(_) => log::info!("Successfully synced Telegram config for agent {}", agent_id),
-            Err(e) => log::error!("Failed to sync Telegram config for agent {}: {}", agent_id, e),
+        log::info!("Syncing Telegram config for agent {}", agent.id);
+        match sync_agent_telegram_config(&state, &agent.id, platform_configs_json).await {
+            Ok(_) => log::info!("Successfully synced Telegram config for agent {}", agent.id),
+            Err(e) => log::error!("Failed to sync Telegram config for agent {}: {}", agent.id, e),
         }
     }
 
     Ok(agent.into_dto())
 }
 
+/// Configuration history of an agent, newest first
+#[tauri::command]
+pub async fn get_agent_history(state: State<'_, AppState>, agent_id: String) -> Result<Vec<AgentVersion>, String> {
+    agent_version::list_versions(&state.db_pool, &agent_id)
+}
+
+/// Put an agent back to an earlier version of its configuration
+#[tauri::command]
+pub async fn rollback_agent(state: State<'_, AppState>, agent_id: String, version: i32) -> Result<AgentDto, String> {
+    let agent = agent_version::rollback(&state.db_pool, &agent_id, version)?;
+    // Running loops pick up the restored skills and MCP servers
+    for group in [GROUP_SKILLS, GROUP_MCP] {
+        if let Err(e) = anyagents::agents::refresh_agent_tools(&state.db_pool, &agent_id, group).await {
+            log::warn!("Failed to reload {} tools of agent {}: {}", group, agent_id, e);
+        }
+    }
+    Ok(agent.into_dto())
+}
+
 /// Synchronize Telegram configuration for an agent based on platform_configs
 async fn sync_agent_telegram_config(
     state: &State<'_, AppState>,
@@ -228,23 +295,26 @@ async fn sync_agent_telegram_config(
     let platform_configs: serde_json::Value =
         serde_json::from_str(platform_configs_json).map_err(|e| format!("Failed to parse platform_configs: {}", e))?;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
     // Check for Telegram configuration
     if let Some(telegram) = platform_configs.get("telegram") {
         let bot_token = telegram.get("bot_token").and_then(|t| t.as_str());
         let enabled = telegram.get("enabled").and_then(|e| e.as_bool()).unwrap_or(false);
+        let active = if enabled { 1 } else { 0 };
 
         if let Some(token) = bot_token {
             if !token.is_empty() {
                 log::info!("Found Telegram bot token for agent {}, creating/updating config", agent_id);
 
                 // Find existing telegram_config for this agent
-                let existing: Option<TelegramConfig> = telegram_configs::table
-                    .filter(telegram_configs::agent_id.eq(agent_id))
-                    .first::<TelegramConfig>(&mut conn)
-                    .optional()
-                    .map_err(|e| e.to_string())?;
+                let owner = agent_id.to_string();
+                let existing: Option<TelegramConfig> = anyagents::database::blocking(&state.db_pool, move |conn| {
+                    telegram_configs::table
+                        .filter(telegram_configs::agent_id.eq(owner))
+                        .first::<TelegramConfig>(conn)
+                        .optional()
+                        .map_err(|e| e.to_string())
+                })
+                .await?;
 
                 let config_id = if let Some(existing_config) = existing {
                     log::info!("Updating existing Telegram config {} for agent {}", existing_config.id, agent_id);
@@ -256,23 +326,31 @@ async fn sync_agent_telegram_config(
                         let _ = state.telegram_manager.stop_bot(&existing_config.id).await;
                         
                         // Update token
-                        diesel::update(telegram_configs::table.filter(telegram_configs::id.eq(&existing_config.id)))
-                            .set((
-                                telegram_configs::bot_token.eq(token),
-                                telegram_configs::is_active.eq(if enabled { 1 } else { 0 }),
-                                telegram_configs::updated_at.eq(chrono::Utc::now().naive_utc()),
-                            ))
-                            .execute(&mut conn)
-                            .map_err(|e| e.to_string())?;
-                    } else if existing_config.is_active != if enabled { 1 } else { 0 } {
+                        let (config_id, token) = (existing_config.id.clone(), token.to_string());
+                        anyagents::database::blocking(&state.db_pool, move |conn| {
+                            diesel::update(telegram_configs::table.filter(telegram_configs::id.eq(&config_id)))
+                                .set((
+                                    telegram_configs::bot_token.eq(token),
+                                    telegram_configs::is_active.eq(active),
+                                    telegram_configs::updated_at.eq(chrono::Utc::now().naive_utc()),
+                                ))
+                                .execute(conn)
+                                .map_err(|e| e.to_string())
+                        })
+                        .await?;
+                    } else if existing_config.is_active != active {
                         // Only enabled status changed
-                        diesel::update(telegram_configs::table.filter(telegram_configs::id.eq(&existing_config.id)))
-                            .set((
-                                telegram_configs::is_active.eq(if enabled { 1 } else { 0 }),
-                                telegram_configs::updated_at.eq(chrono::Utc::now().naive_utc()),
-                            ))
-                            .execute(&mut conn)
-                            .map_err(|e| e.to_string())?;
+                        let config_id = existing_config.id.clone();
+                        anyagents::database::blocking(&state.db_pool, move |conn| {
+                            diesel::update(telegram_configs::table.filter(telegram_configs::id.eq(&config_id)))
+                                .set((
+                                    telegram_configs::is_active.eq(active),
+                                    telegram_configs::updated_at.eq(chrono::Utc::now().naive_utc()),
+                                ))
+                                .execute(conn)
+                                .map_err(|e| e.to_string())
+                        })
+                        .await?;
                     }
 
                     existing_config.id
@@ -283,18 +361,24 @@ async fn sync_agent_telegram_config(
                         id: uuid::Uuid::new_v4().to_string(),
                         bot_token: token.to_string(),
                         agent_id: agent_id.to_string(),
-                        is_active: if enabled { 1 } else { 0 },
+                        is_active: active,
                         allowed_chat_ids: None,
                         created_at: chrono::Utc::now().naive_utc(),
                         updated_at: chrono::Utc::now().naive_utc(),
+                        voice_replies: 0,
+                        group_allowlists: None,
                     };
+                    let config_id = new_config.id.clone();
 
-                    diesel::insert_into(telegram_configs::table)
-                        .values(&new_config)
-                        .execute(&mut conn)
-                        .map_err(|e| e.to_string())?;
+                    anyagents::database::blocking(&state.db_pool, move |conn| {
+                        diesel::insert_into(telegram_configs::table)
+                            .values(&new_config)
+                            .execute(conn)
+                            .map_err(|e| e.to_string())
+                    })
+                    .await?;
 
-                    new_config.id
+                    config_id
                 };
 
                 // Start/stop bot based on enabled flag
@@ -316,17 +400,24 @@ async fn sync_agent_telegram_config(
 
     // No telegram config or empty token - delete existing config if any
     log::info!("No Telegram config for agent {}, cleaning up", agent_id);
-    let existing_configs: Vec<TelegramConfig> = telegram_configs::table
-        .filter(telegram_configs::agent_id.eq(agent_id))
-        .load::<TelegramConfig>(&mut conn)
-        .map_err(|e| e.to_string())?;
+    let owner = agent_id.to_string();
+    let existing_configs: Vec<TelegramConfig> = anyagents::database::blocking(&state.db_pool, move |conn| {
+        telegram_configs::table
+            .filter(telegram_configs::agent_id.eq(owner))
+            .load::<TelegramConfig>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await?;
 
     for config in existing_configs {
         log::info!("Deleting Telegram config {} and stopping bot for agent {}", config.id, agent_id);
         let _ = state.telegram_manager.stop_bot(&config.id).await;
-        diesel::delete(telegram_configs::table.filter(telegram_configs::id.eq(&config.id)))
-            .execute(&mut conn)
-            .map_err(|e| e.to_string())?;
+        anyagents::database::blocking(&state.db_pool, move |conn| {
+            diesel::delete(telegram_configs::table.filter(telegram_configs::id.eq(&config.id)))
+                .execute(conn)
+                .map_err(|e| e.to_string())
+        })
+        .await?;
     }
 
     Ok(())
@@ -340,25 +431,29 @@ pub async fn chat_internal<R: Runtime>(
     message: String,
     mode: Option<String>,
     model: Option<String>,
+    images: Vec<anyagents::llm::ImageAttachment>,
 ) -> Result<String, String> {
     use anyagents::models::Session;
     use anyagents::schema::agents::dsl::agents;
     use anyagents::schema::sessions::dsl::id as session_id_col;
     use anyagents::schema::sessions::dsl::sessions;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    // 1. Get Session to get Agent ID
-    let session_record: Session = sessions
-        .filter(session_id_col.eq(&session_id))
-        .first(&mut conn)
-        .map_err(|_| "Session not found".to_string())?;
-
-    // 2. Get Agent
-    let agent_record: Agent = agents
-        .filter(schema::agents::dsl::id.eq(&session_record.agent_id))
-        .first(&mut conn)
-        .map_err(|_| "Agent not found".to_string())?;
+    let images = anyagents::llm::image::validate_attachments(images)?;
+    let session = session_id.clone();
+    let agent_record: Agent = anyagents::database::blocking(&state.db_pool, move |conn| {
+        // 1. Get Session to get Agent ID
+        let session_record: Session = sessions
+            .filter(session_id_col.eq(&session))
+            .first(conn)
+            .map_err(|_| "Session not found".to_string())?;
+
+        // 2. Get Agent
+        agents
+            .filter(schema::agents::dsl::id.eq(&session_record.agent_id))
+            .first(conn)
+            .map_err(|_| "Agent not found".to_string())
+    })
+    .await?;
 
     // 3. Save User Message
     use anyagents::models::NewMessage;
@@ -368,13 +463,12 @@ pub async fn chat_internal<R: Runtime>(
         role: "user".to_string(),
         content: message.clone(),
         session_id: session_id.clone(),
-        metadata_json: None,
+        metadata_json: anyagents::llm::image::images_metadata(&images),
         tokens: None,
     };
-    diesel::insert_into(messages::table)
-        .values(&user_msg)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+    anyagents::database::serialized_write(&state.db_pool, |conn| {
+        diesel::insert_into(messages::table).values(&user_msg).execute(conn)
+    })?;
 
     // 4. Start Background Task
     start_chat_task(
@@ -385,8 +479,10 @@ pub async fn chat_internal<R: Runtime>(
         state.pending_approvals.clone(),
         state.permission_manager.clone(),
         state.db_pool.clone(),
+        state.agent_bus.clone(),
         mode.unwrap_or_else(|| "planning".to_string()),
         model,
+        images,
     );
 
     Ok("started".to_string())
@@ -400,14 +496,122 @@ pub async fn chat(
     message: String,
     mode: Option<String>,
     model: Option<String>,
+    images: Option<Vec<anyagents::llm::ImageAttachment>>,
 ) -> Result<String, String> {
-    chat_internal(window, state, session_id, message, mode, model).await
+    ensure_can_act(&window)?;
+    chat_internal(window, state, session_id, message, mode, model, images.unwrap_or_default()).await
 }
 
+/// Rewrite a user message and re-run the chat from it. Everything after the
+/// message is soft-deleted and reported in a `history_rewound` event.
 #[tauri::command]
-pub async fn approve_action(state: State<'_, AppState>, step_id: String) -> Result<(), String> {
+pub async fn edit_message(
+    window: tauri::WebviewWindow,
+    state: State<'_, AppState>,
+    message_id: String,
+    content: String,
+    mode: Option<String>,
+    model: Option<String>,
+) -> Result<String, String> {
+    ensure_can_act(&window)?;
+    let agent = agent_for_message(&state, &message_id).await?;
+    let rewind = anyagents::models::session::edit_user_message(&state.db_pool, &message_id, &content)?;
+    rerun_from(window, state, agent, rewind, mode, model)
+}
+
+/// Run a message again: a user message is re-sent, any other message is
+/// replaced by a new answer to the prompt before it
+#[tauri::command]
+pub async fn regenerate_from(
+    window: tauri::WebviewWindow,
+    state: State<'_, AppState>,
+    message_id: String,
+    mode: Option<String>,
+    model: Option<String>,
+) -> Result<String, String> {
+    ensure_can_act(&window)?;
+    let agent = agent_for_message(&state, &message_id).await?;
+    let rewind = anyagents::models::session::rewind_for_regenerate(&state.db_pool, &message_id)?;
+    rerun_from(window, state, agent, rewind, mode, model)
+}
+
+/// The agent of the session a message belongs to
+async fn agent_for_message(state: &AppState, message_id: &str) -> Result<Agent, String> {
+    let message_id = message_id.to_string();
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        schema::messages::table
+            .inner_join(schema::sessions::table.inner_join(schema::agents::table))
+            .filter(schema::messages::id.eq(message_id))
+            .select(Agent::as_select())
+            .first(conn)
+            .map_err(|_| "Message not found".to_string())
+    })
+    .await
+}
+
+fn rerun_from<R: Runtime>(
+    window: tauri::WebviewWindow<R>,
+    state: State<'_, AppState>,
+    agent: Agent,
+    rewind: anyagents::models::Rewind,
+    mode: Option<String>,
+    model: Option<String>,
+) -> Result<String, String> {
+    use anyagents::events::AgentObserver;
+
+    let session_id = rewind.message.session_id.clone();
+    let _ = TauriAgentObserver::for_session(&window, &session_id).emit(
+        &format!("session:{}", session_id),
+        serde_json::to_value(anyagents::events::AgentEvent::HistoryRewound {
+            session_id: session_id.clone(),
+            message: rewind.message.clone(),
+            removed_ids: rewind.removed_ids,
+        })
+        .unwrap_or_default(),
+    );
+
+    // The user message is already stored; the job answers it again
+    let images = anyagents::llm::image::images_from_metadata(rewind.message.metadata_json.as_deref());
+    start_chat_task(
+        agent,
+        rewind.message.content,
+        session_id,
+        window,
+        state.pending_approvals.clone(),
+        state.permission_manager.clone(),
+        state.db_pool.clone(),
+        state.agent_bus.clone(),
+        mode.unwrap_or_else(|| "planning".to_string()),
+        model,
+        images,
+    );
+
+    Ok("started".to_string())
+}
+
+/// `scope` ("session", "agent", "workspace" or "global") allows the same
+/// request from now on, for `ttl_secs` or until the grant is revoked
+#[tauri::command]
+pub async fn approve_action(
+    window: tauri::WebviewWindow,
+    state: State<'_, AppState>,
+    step_id: String,
+    scope: Option<String>,
+    ttl_secs: Option<i64>,
+) -> Result<(), String> {
+    ensure_can_act(&window)?;
     // Try PermissionManager first
-    state.permission_manager.approve_request(&step_id);
+    match scope {
+        Some(scope) => {
+            let scope = GrantScope::parse(&scope)?;
+            if ttl_secs.is_some_and(|ttl| ttl <= 0) {
+                return Err("ttl_secs must be positive".to_string());
+            }
+            let ttl = ttl_secs.map(chrono::Duration::seconds);
+            state.permission_manager.grant_as(&step_id, scope, ttl, "app");
+        }
+        None => state.permission_manager.approve_request(&step_id),
+    }
 
     // Fallback to legacy pending_approvals if needed (optional)
     if let Some((_, tx)) = state.pending_approvals.remove(&step_id) {
@@ -417,7 +621,8 @@ pub async fn approve_action(state: State<'_, AppState>, step_id: String) -> Resu
 }
 
 #[tauri::command]
-pub async fn reject_action(state: State<'_, AppState>, step_id: String) -> Result<(), String> {
+pub async fn reject_action(window: tauri::WebviewWindow, state: State<'_, AppState>, step_id: String) -> Result<(), String> {
+    ensure_can_act(&window)?;
     // Try PermissionManager first
     state.permission_manager.reject_request(&step_id);
 
@@ -444,7 +649,28 @@ mod tests {
             telegram_manager: std::sync::Arc::new(crate::telegram::TelegramBotManager::new(
                 create_test_pool(),
             )),
+            discord_manager: std::sync::Arc::new(crate::discord::DiscordBotManager::new(
+                create_test_pool(),
+                std::sync::Arc::new(anyagents::permissions::PermissionManager::new()),
+            )),
+            slack_manager: std::sync::Arc::new(crate::slack::SlackBotManager::new(
+                create_test_pool(),
+                std::sync::Arc::new(anyagents::permissions::PermissionManager::new()),
+            )),
+            matrix_manager: std::sync::Arc::new(crate::matrix::MatrixBotManager::new(
+                create_test_pool(),
+                std::sync::Arc::new(anyagents::permissions::PermissionManager::new()),
+            )),
             permission_manager: std::sync::Arc::new(PermissionManager::new()),
+            agent_bus: std::sync::Arc::new(anyagents::bus::AgentBus::new(
+                create_test_pool(),
+                std::sync::Arc::new(PermissionManager::new()),
+            )),
+            trigger_engine: std::sync::Arc::new(crate::agent_triggers::TriggerEngine::new()),
+            email_bridge: std::sync::Arc::new(crate::email_bridge::EmailBridgeManager::new(
+                create_test_pool(),
+                std::sync::Arc::new(PermissionManager::new()),
+            )),
         }
     }
 
@@ -481,10 +707,69 @@ pub fn start_chat_task<R: Runtime>(
     pending_approvals: Arc<dashmap::DashMap<String, tokio::sync::oneshot::Sender<bool>>>,
     permission_manager: Arc<anyagents::permissions::PermissionManager>,
     db_pool: anyagents::database::DbPool,
+    agent_bus: Arc<anyagents::bus::AgentBus>,
     mode: String,
     model: Option<String>,
+    images: Vec<anyagents::llm::ImageAttachment>,
 ) {
-    let observer = Arc::new(TauriAgentObserver { window });
+    let coordinator = build_coordinator(
+        agent,
+        session_id,
+        window,
+        pending_approvals,
+        db_pool,
+        agent_bus,
+        mode,
+        model,
+    )
+    .with_images(images);
+
+    tauri::async_runtime::spawn(async move {
+        coordinator.run(message).await;
+    });
+}
+
+/// Continue a persisted plan in the background
+pub fn start_plan_resume_task<R: Runtime>(
+    agent: Agent,
+    plan_id: String,
+    session_id: String,
+    window: WebviewWindow<R>,
+    pending_approvals: Arc<dashmap::DashMap<String, tokio::sync::oneshot::Sender<bool>>>,
+    db_pool: anyagents::database::DbPool,
+    agent_bus: Arc<anyagents::bus::AgentBus>,
+) {
+    let coordinator = build_coordinator(
+        agent,
+        session_id,
+        window,
+        pending_approvals,
+        db_pool,
+        agent_bus,
+        "planning".to_string(),
+        None,
+    );
+
+    tauri::async_runtime::spawn(async move {
+        coordinator.resume_plan(&plan_id).await;
+    });
+}
+
+fn build_coordinator<R: Runtime>(
+    agent: Agent,
+    session_id: String,
+    window: WebviewWindow<R>,
+    pending_approvals: Arc<dashmap::DashMap<String, tokio::sync::oneshot::Sender<bool>>>,
+    db_pool: anyagents::database::DbPool,
+    agent_bus: Arc<anyagents::bus::AgentBus>,
+    mode: String,
+    model: Option<String>,
+) -> Coordinator {
+    // Streamed text goes over IPC in chunks, not a message per token
+    let observer = EventChannel::wrap(
+        Arc::new(TauriAgentObserver::for_session(&window, &session_id)),
+        EventChannelConfig::load(&db_pool),
+    );
 
     // Check if agent is in autonomous mode
     let is_autonomous = if let Some(ref settings_str) = agent.execution_settings {
@@ -507,17 +792,15 @@ pub fn start_chat_task<R: Runtime>(
     // Create autonomous permission manager if needed
     let autonomous_pm = Arc::new(anyagents::permissions::AutonomousPermissionManager::new(is_autonomous));
 
-    tauri::async_runtime::spawn(async move {
-        let coordinator = Coordinator::new_with_autonomous(
-            session_id,
-            agent,
-            observer,
-            db_pool,
-            autonomous_pm,
-            pending_approvals,
-            mode,
-            model,
-        );
-        coordinator.run(message).await;
-    });
+    Coordinator::new_with_autonomous(
+        session_id,
+        agent,
+        observer,
+        db_pool,
+        autonomous_pm,
+        pending_approvals,
+        mode,
+        model,
+    )
+    .with_bus(agent_bus)
 }
@@ -0,0 +1,26 @@
+use crate::AppState;
+use anyagents::bus::{BusMessage, BusPayload, Delivery, USER_SENDER};
+use tauri::State;
+
+/// Recent inter-agent traffic, oldest first (live updates arrive as `agent_bus` events)
+#[tauri::command]
+pub async fn get_agent_bus_messages(
+    state: State<'_, AppState>,
+    limit: Option<usize>,
+) -> Result<Vec<BusMessage>, String> {
+    Ok(state.agent_bus.recent(limit.unwrap_or(100)))
+}
+
+/// Send a message from the user to an agent (or every running agent when `to` is None)
+#[tauri::command]
+pub async fn send_agent_message(
+    state: State<'_, AppState>,
+    to: Option<String>,
+    payload: BusPayload,
+) -> Result<Delivery, String> {
+    if to.is_none() && payload.wants_reply() {
+        return Err("Task requests and questions need a recipient".to_string());
+    }
+    let message = BusMessage::new(USER_SENDER, to.as_deref(), payload);
+    Ok(state.agent_bus.send(message))
+}
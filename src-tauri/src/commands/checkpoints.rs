@@ -0,0 +1,45 @@
+use crate::AppState;
+use anyagents::models::Agent;
+use anyagents::snapshots::{Checkpoint, GitCheckpoints};
+use diesel::prelude::*;
+use tauri::State;
+
+async fn agent_checkpoints(state: &State<'_, AppState>, agent_id: &str) -> Result<GitCheckpoints, String> {
+    use anyagents::schema::agents;
+
+    let agent_id = agent_id.to_string();
+    let agent: Agent = anyagents::database::blocking(&state.db_pool, move |conn| {
+        agents::table
+            .find(agent_id)
+            .first(conn)
+            .map_err(|e| format!("Agent not found: {}", e))
+    })
+    .await?;
+
+    let workspace = agent
+        .workspace_path
+        .ok_or_else(|| "Agent has no workspace".to_string())?;
+    GitCheckpoints::open(&workspace)
+        .ok_or_else(|| format!("Workspace {} is not a git repository", workspace))
+}
+
+/// List git checkpoints in an agent's workspace, optionally for a single job
+#[tauri::command]
+pub async fn list_checkpoints(
+    state: State<'_, AppState>,
+    agent_id: String,
+    job_id: Option<String>,
+) -> Result<Vec<Checkpoint>, String> {
+    agent_checkpoints(&state, &agent_id).await?.list(job_id.as_deref())
+}
+
+/// Restore an agent's workspace to a checkpoint. Returns the checkpoint of the
+/// state before restoring, so the restore can be undone.
+#[tauri::command]
+pub async fn restore_checkpoint(
+    state: State<'_, AppState>,
+    agent_id: String,
+    ref_name: String,
+) -> Result<Checkpoint, String> {
+    agent_checkpoints(&state, &agent_id).await?.restore(&ref_name)
+}
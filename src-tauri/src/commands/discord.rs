@@ -0,0 +1,150 @@
+use anyagents::models::{DiscordConfig, NewDiscordConfig, UpdateDiscordConfig};
+use crate::AppState;
+use diesel::prelude::*;
+use tauri::State;
+
+#[tauri::command]
+pub async fn create_discord_config(
+    state: State<'_, AppState>,
+    bot_token: String,
+    agent_id: String,
+    allowed_channel_ids: Option<String>,
+) -> Result<DiscordConfig, String> {
+    use anyagents::schema::discord_configs;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let new_config = NewDiscordConfig {
+            id: uuid::Uuid::new_v4().to_string(),
+            bot_token,
+            agent_id,
+            is_active: 0,
+            allowed_channel_ids,
+            created_at: chrono::Utc::now().naive_utc(),
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
+
+        diesel::insert_into(discord_configs::table)
+            .values(&new_config)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        discord_configs::table
+            .filter(discord_configs::id.eq(&new_config.id))
+            .first::<DiscordConfig>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_discord_configs(state: State<'_, AppState>) -> Result<Vec<DiscordConfig>, String> {
+    use anyagents::schema::discord_configs::dsl::*;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        discord_configs
+            .load::<DiscordConfig>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_discord_config(
+    state: State<'_, AppState>,
+    config_id: String,
+) -> Result<DiscordConfig, String> {
+    use anyagents::schema::discord_configs::dsl::*;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        discord_configs
+            .filter(id.eq(config_id))
+            .first::<DiscordConfig>(conn)
+            .map_err(|e| format!("Config not found: {}", e))
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn update_discord_config(
+    state: State<'_, AppState>,
+    config_id: String,
+    new_bot_token: Option<String>,
+    new_agent_id: Option<String>,
+    new_is_active: Option<i32>,
+    new_allowed_channel_ids: Option<String>,
+) -> Result<DiscordConfig, String> {
+    use anyagents::schema::discord_configs::dsl::*;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let update = UpdateDiscordConfig {
+            bot_token: new_bot_token,
+            agent_id: new_agent_id,
+            is_active: new_is_active,
+            allowed_channel_ids: new_allowed_channel_ids,
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
+
+        diesel::update(discord_configs.filter(id.eq(&config_id)))
+            .set(&update)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        discord_configs
+            .filter(id.eq(&config_id))
+            .first::<DiscordConfig>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn delete_discord_config(
+    state: State<'_, AppState>,
+    config_id: String,
+) -> Result<(), String> {
+    use anyagents::schema::{discord_channel_sessions, discord_configs};
+
+    let _ = state.discord_manager.stop_bot(&config_id).await;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        diesel::delete(discord_channel_sessions::table.filter(discord_channel_sessions::config_id.eq(&config_id)))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+        diesel::delete(discord_configs::table.filter(discord_configs::id.eq(&config_id)))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn start_discord_bot(state: State<'_, AppState>, config_id: String) -> Result<(), String> {
+    state.discord_manager.start_bot(&config_id).await
+}
+
+#[tauri::command]
+pub async fn stop_discord_bot(state: State<'_, AppState>, config_id: String) -> Result<(), String> {
+    state.discord_manager.stop_bot(&config_id).await
+}
+
+#[derive(serde::Serialize)]
+pub struct DiscordBotStatus {
+    pub config_id: String,
+    pub is_running: bool,
+}
+
+#[tauri::command]
+pub async fn get_discord_bot_status(
+    state: State<'_, AppState>,
+    config_id: String,
+) -> Result<DiscordBotStatus, String> {
+    let is_running = state.discord_manager.is_bot_running(&config_id).await;
+    Ok(DiscordBotStatus { config_id, is_running })
+}
+
+#[tauri::command]
+pub async fn get_running_discord_bots(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    Ok(state.discord_manager.get_running_bot_ids().await)
+}
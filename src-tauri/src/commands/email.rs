@@ -0,0 +1,233 @@
+use anyagents::email::{self, credentials};
+use anyagents::models::{EmailAccount, NewEmailAccount, UpdateEmailAccount};
+use crate::AppState;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+/// Connection settings for a new email account
+#[derive(Deserialize)]
+pub struct EmailAccountInput {
+    pub agent_id: String,
+    pub email_address: String,
+    pub display_name: Option<String>,
+    /// Defaults to the email address
+    pub username: Option<String>,
+    pub password: String,
+    pub smtp_host: String,
+    pub smtp_port: Option<i32>,
+    pub imap_host: String,
+    pub imap_port: Option<i32>,
+    pub imap_folder: Option<String>,
+    pub deliver_outbound: Option<bool>,
+    pub poll_interval_seconds: Option<i32>,
+}
+
+/// Changes to an email account; unset fields are left alone
+#[derive(Deserialize)]
+pub struct EmailAccountChanges {
+    pub email_address: Option<String>,
+    pub display_name: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub smtp_host: Option<String>,
+    pub smtp_port: Option<i32>,
+    pub imap_host: Option<String>,
+    pub imap_port: Option<i32>,
+    pub imap_folder: Option<String>,
+    pub deliver_outbound: Option<bool>,
+    pub poll_interval_seconds: Option<i32>,
+    pub is_active: Option<bool>,
+}
+
+fn load_account(conn: &mut SqliteConnection, account_id: &str) -> Result<EmailAccount, String> {
+    use anyagents::schema::email_accounts::dsl::*;
+
+    email_accounts
+        .filter(id.eq(account_id))
+        .first::<EmailAccount>(conn)
+        .map_err(|e| format!("Email account not found: {}", e))
+}
+
+#[tauri::command]
+pub async fn create_email_account(
+    state: State<'_, AppState>,
+    account: EmailAccountInput,
+) -> Result<EmailAccount, String> {
+    use anyagents::schema::email_accounts;
+
+    let now = chrono::Utc::now().naive_utc();
+    let new_account = NewEmailAccount {
+        id: uuid::Uuid::new_v4().to_string(),
+        agent_id: account.agent_id,
+        username: account.username.unwrap_or_else(|| account.email_address.clone()),
+        email_address: account.email_address,
+        display_name: account.display_name,
+        smtp_host: account.smtp_host,
+        smtp_port: account.smtp_port.unwrap_or(587),
+        imap_host: account.imap_host,
+        imap_port: account.imap_port.unwrap_or(993),
+        imap_folder: account.imap_folder.unwrap_or_else(|| "INBOX".to_string()),
+        deliver_outbound: account.deliver_outbound.unwrap_or(false) as i32,
+        poll_interval_seconds: account.poll_interval_seconds.unwrap_or(60),
+        last_seen_uid: 0,
+        is_active: 0,
+        created_at: now,
+        updated_at: now,
+    };
+    email::validate_account(
+        &new_account.email_address,
+        &new_account.smtp_host,
+        new_account.smtp_port,
+        &new_account.imap_host,
+        new_account.imap_port,
+        new_account.poll_interval_seconds,
+    )?;
+
+    let pool = state.db_pool.clone();
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        if email::account_for_agent(&pool, &new_account.agent_id)?.is_some() {
+            return Err("This agent already has an email account".to_string());
+        }
+
+        credentials::set_password(&new_account.id, &account.password)?;
+        if let Err(e) = diesel::insert_into(email_accounts::table)
+            .values(&new_account)
+            .execute(conn)
+        {
+            let _ = credentials::delete_password(&new_account.id);
+            return Err(e.to_string());
+        }
+
+        load_account(conn, &new_account.id)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_email_accounts(state: State<'_, AppState>) -> Result<Vec<EmailAccount>, String> {
+    use anyagents::schema::email_accounts::dsl::*;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        email_accounts
+            .order(created_at.asc())
+            .load::<EmailAccount>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+/// Apply `changes`, then start, stop or restart the inbox watcher to match
+#[tauri::command]
+pub async fn update_email_account(
+    state: State<'_, AppState>,
+    account_id: String,
+    changes: EmailAccountChanges,
+) -> Result<EmailAccount, String> {
+    use anyagents::schema::email_accounts::dsl::*;
+
+    let updated_id = account_id.clone();
+    let updated = anyagents::database::blocking(&state.db_pool, move |conn| {
+        let current = load_account(conn, &updated_id)?;
+
+        email::validate_account(
+            changes.email_address.as_deref().unwrap_or(&current.email_address),
+            changes.smtp_host.as_deref().unwrap_or(&current.smtp_host),
+            changes.smtp_port.unwrap_or(current.smtp_port),
+            changes.imap_host.as_deref().unwrap_or(&current.imap_host),
+            changes.imap_port.unwrap_or(current.imap_port),
+            changes.poll_interval_seconds.unwrap_or(current.poll_interval_seconds),
+        )?;
+        if let Some(password) = &changes.password {
+            credentials::set_password(&updated_id, password)?;
+        }
+
+        let update = UpdateEmailAccount {
+            email_address: changes.email_address,
+            display_name: changes.display_name,
+            username: changes.username,
+            smtp_host: changes.smtp_host,
+            smtp_port: changes.smtp_port,
+            imap_host: changes.imap_host,
+            imap_port: changes.imap_port,
+            imap_folder: changes.imap_folder,
+            deliver_outbound: changes.deliver_outbound.map(|d| d as i32),
+            poll_interval_seconds: changes.poll_interval_seconds,
+            is_active: changes.is_active.map(|a| a as i32),
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
+        diesel::update(email_accounts.filter(id.eq(&updated_id)))
+            .set(&update)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+        load_account(conn, &updated_id)
+    })
+    .await?;
+
+    let bridge = &state.email_bridge;
+    if bridge.is_watching(&account_id).await {
+        let _ = bridge.stop_watcher(&account_id).await;
+    }
+    if updated.is_active == 1 {
+        bridge.start_watcher(&account_id).await?;
+    }
+    Ok(updated)
+}
+
+#[tauri::command]
+pub async fn delete_email_account(
+    state: State<'_, AppState>,
+    account_id: String,
+) -> Result<(), String> {
+    use anyagents::schema::email_accounts::dsl::*;
+
+    let _ = state.email_bridge.stop_watcher(&account_id).await;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        diesel::delete(email_accounts.filter(id.eq(&account_id)))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+        credentials::delete_password(&account_id)
+    })
+    .await
+}
+
+#[derive(Serialize)]
+pub struct EmailAccountTestResponse {
+    pub imap_ok: bool,
+    pub smtp_ok: bool,
+    pub error: Option<String>,
+}
+
+/// Log in to both servers with the stored password
+#[tauri::command]
+pub async fn test_email_account(
+    state: State<'_, AppState>,
+    account_id: String,
+) -> Result<EmailAccountTestResponse, String> {
+    let loaded_id = account_id.clone();
+    let account = anyagents::database::blocking(&state.db_pool, move |conn| load_account(conn, &loaded_id)).await?;
+    let password = credentials::get_password(&account_id)?;
+
+    let imap = email::imap::test_connection(&account, &password).await;
+    let smtp = email::smtp::test_connection(&account, &password).await;
+    let error = [imap.as_ref().err(), smtp.as_ref().err()]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect::<Vec<_>>();
+
+    Ok(EmailAccountTestResponse {
+        imap_ok: imap.is_ok(),
+        smtp_ok: smtp.is_ok(),
+        error: if error.is_empty() { None } else { Some(error.join("; ")) },
+    })
+}
+
+#[tauri::command]
+pub async fn get_email_watcher_status(
+    state: State<'_, AppState>,
+    account_id: String,
+) -> Result<bool, String> {
+    Ok(state.email_bridge.is_watching(&account_id).await)
+}
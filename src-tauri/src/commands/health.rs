@@ -0,0 +1,356 @@
+use crate::AppState;
+use anyagents::mcp::McpClient;
+use anyagents::models::mcp_server::McpServer;
+use anyagents::models::{NewSetting, TelegramConfig};
+use anyagents::schema::{agents, mcp_servers, settings, telegram_configs};
+use anyagents::skills::DockerSandbox;
+use diesel::prelude::*;
+use diesel_migrations::MigrationHarness;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::State;
+
+/// Default port of the headless server adapter (override with ANYCOWORK_SERVER_PORT)
+pub const DEFAULT_SERVER_PORT: u16 = 7878;
+
+/// Set once the user is through the first-launch report
+const ONBOARDING_SETTING: &str = "onboarding_completed";
+const MCP_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+const LOW_DISK_SPACE: u64 = 1024 * 1024 * 1024;
+const CRITICAL_DISK_SPACE: u64 = 100 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Ok,
+    Warning,
+    Error,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthCheckItem {
+    pub name: String,
+    pub status: HealthStatus,
+    pub message: String,
+}
+
+impl HealthCheckItem {
+    fn new(name: &str, status: HealthStatus, message: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthReport {
+    /// False if any check failed with `Error`
+    pub healthy: bool,
+    pub checks: Vec<HealthCheckItem>,
+    /// True until onboarding is completed, so the UI shows the report on first launch
+    #[serde(default)]
+    pub first_run: bool,
+}
+
+fn check_database(pool: &anyagents::database::DbPool) -> HealthCheckItem {
+    let mut conn = match pool.get() {
+        Ok(c) => c,
+        Err(e) => return HealthCheckItem::new("database", HealthStatus::Error, format!("Cannot connect: {}", e)),
+    };
+
+    match conn.has_pending_migration(crate::database::MIGRATIONS) {
+        Ok(false) => {}
+        Ok(true) => {
+            return HealthCheckItem::new("database", HealthStatus::Error, "Database has pending migrations")
+        }
+        Err(e) => {
+            return HealthCheckItem::new("database", HealthStatus::Error, format!("Migration check failed: {}", e))
+        }
+    }
+
+    // Probe writability with an insert that is always rolled back
+    let probe = conn.transaction::<(), diesel::result::Error, _>(|conn| {
+        diesel::insert_into(settings::table)
+            .values(NewSetting {
+                id: uuid::Uuid::new_v4().to_string(),
+                key: format!("__health_check_{}", uuid::Uuid::new_v4()),
+                value: None,
+            })
+            .execute(conn)?;
+        Err(diesel::result::Error::RollbackTransaction)
+    });
+
+    match probe {
+        Err(diesel::result::Error::RollbackTransaction) => {
+            HealthCheckItem::new("database", HealthStatus::Ok, "Writable and up to date")
+        }
+        Err(e) => HealthCheckItem::new("database", HealthStatus::Error, format!("Database is not writable: {}", e)),
+        Ok(()) => HealthCheckItem::new("database", HealthStatus::Ok, "Writable and up to date"),
+    }
+}
+
+fn check_database_integrity(pool: &anyagents::database::DbPool) -> HealthCheckItem {
+    match anyagents::backup::check_database(pool) {
+        Ok(check) if check.ok => HealthCheckItem::new("database_integrity", HealthStatus::Ok, "No corruption found"),
+        Ok(check) => HealthCheckItem::new(
+            "database_integrity",
+            HealthStatus::Error,
+            format!("Database is damaged, restore a backup: {}", check.problems.join("; ")),
+        ),
+        Err(e) => HealthCheckItem::new("database_integrity", HealthStatus::Warning, e),
+    }
+}
+
+/// The database checks lock and scan the file, so they run off the async runtime
+async fn check_database_blocking(pool: &anyagents::database::DbPool) -> Vec<HealthCheckItem> {
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || vec![check_database(&pool), check_database_integrity(&pool)])
+        .await
+        .unwrap_or_else(|e| vec![HealthCheckItem::new("database", HealthStatus::Error, e.to_string())])
+}
+
+async fn check_providers(pool: &anyagents::database::DbPool) -> Vec<HealthCheckItem> {
+    let mut checks = Vec::new();
+    let mut any_valid = false;
+
+    for provider in ["openai", "anthropic", "gemini"] {
+        let key = crate::commands::settings::provider_api_key(pool, provider);
+
+        let name = format!("provider:{}", provider);
+        match key {
+            None => checks.push(HealthCheckItem::new(&name, HealthStatus::Skipped, "No API key configured")),
+            Some(key) => match anyagents::llm::validate_api_key(provider, &key).await {
+                Ok(()) => {
+                    any_valid = true;
+                    checks.push(HealthCheckItem::new(&name, HealthStatus::Ok, "API key is valid"));
+                }
+                Err(e) => checks.push(HealthCheckItem::new(&name, HealthStatus::Error, e)),
+            },
+        }
+    }
+
+    if !any_valid {
+        checks.push(HealthCheckItem::new(
+            "providers",
+            HealthStatus::Error,
+            "No working AI provider. Add an API key in Settings.",
+        ));
+    }
+
+    checks
+}
+
+async fn check_docker() -> HealthCheckItem {
+    if !DockerSandbox::check_available().await {
+        return HealthCheckItem::new(
+            "docker",
+            HealthStatus::Warning,
+            "Docker not found; sandboxed skills and 'sandbox' mode agents won't run",
+        );
+    }
+    match DockerSandbox::version().await {
+        Ok(version) => HealthCheckItem::new("docker", HealthStatus::Ok, format!("Docker {} is running", version)),
+        Err(e) => HealthCheckItem::new("docker", HealthStatus::Warning, e),
+    }
+}
+
+fn check_transcription_model() -> HealthCheckItem {
+    match crate::commands::transcribe::find_model_path() {
+        Ok(Some(path)) => HealthCheckItem::new(
+            "transcription_model",
+            HealthStatus::Ok,
+            format!("Model found at {}", path.display()),
+        ),
+        Ok(None) => HealthCheckItem::new(
+            "transcription_model",
+            HealthStatus::Warning,
+            "Speech model not downloaded; voice input is disabled",
+        ),
+        Err(e) => HealthCheckItem::new("transcription_model", HealthStatus::Warning, e),
+    }
+}
+
+async fn check_telegram(pool: &anyagents::database::DbPool) -> Vec<HealthCheckItem> {
+    let configs: Vec<TelegramConfig> = anyagents::database::blocking(pool, |conn| {
+        Ok(telegram_configs::table
+            .filter(telegram_configs::is_active.eq(1))
+            .load(conn)
+            .unwrap_or_default())
+    })
+    .await
+    .unwrap_or_default();
+
+    if configs.is_empty() {
+        return vec![HealthCheckItem::new("telegram", HealthStatus::Skipped, "No active Telegram bots")];
+    }
+
+    let mut checks = Vec::new();
+    for config in configs {
+        let name = format!("telegram:{}", config.id);
+        match crate::commands::telegram::test_telegram_bot(config.bot_token).await {
+            Ok(res) if res.success => checks.push(HealthCheckItem::new(
+                &name,
+                HealthStatus::Ok,
+                format!("Reachable as @{}", res.bot_username.unwrap_or_default()),
+            )),
+            Ok(res) => checks.push(HealthCheckItem::new(
+                &name,
+                HealthStatus::Error,
+                res.error.unwrap_or_else(|| "Bot token rejected".to_string()),
+            )),
+            Err(e) => checks.push(HealthCheckItem::new(&name, HealthStatus::Error, e)),
+        }
+    }
+    checks
+}
+
+/// Free space where agents write: their workspaces and the app's data directory
+async fn check_disk_space(pool: &anyagents::database::DbPool) -> Vec<HealthCheckItem> {
+    let mut paths: Vec<PathBuf> = anyagents::database::blocking(pool, |conn| {
+        Ok(agents::table
+            .select(agents::workspace_path)
+            .filter(agents::workspace_path.is_not_null())
+            .distinct()
+            .load::<Option<String>>(conn)
+            .unwrap_or_default())
+    })
+    .await
+    .unwrap_or_default()
+    .into_iter()
+    .flatten()
+    .map(PathBuf::from)
+    .collect();
+    paths.push(dirs::home_dir().unwrap_or_default().join(".anycowork"));
+
+    paths
+        .into_iter()
+        .filter(|path| path.exists())
+        .map(|path| {
+            let name = format!("disk:{}", path.display());
+            match fs2::available_space(&path) {
+                Ok(free) => {
+                    let message = format!("{:.1} GiB free", free as f64 / (1024.0 * 1024.0 * 1024.0));
+                    let status = match free {
+                        free if free < CRITICAL_DISK_SPACE => HealthStatus::Error,
+                        free if free < LOW_DISK_SPACE => HealthStatus::Warning,
+                        _ => HealthStatus::Ok,
+                    };
+                    HealthCheckItem::new(&name, status, message)
+                }
+                Err(e) => HealthCheckItem::new(&name, HealthStatus::Warning, format!("Cannot read free space: {}", e)),
+            }
+        })
+        .collect()
+}
+
+async fn check_mcp_server(server: McpServer) -> HealthCheckItem {
+    let name = format!("mcp:{}", server.name);
+    let connect = async {
+        let client = McpClient::from_server(&server).await?.with_timeout(MCP_CHECK_TIMEOUT);
+        let result = match client.initialize().await {
+            Ok(()) => client.list_tools().await,
+            Err(e) => Err(e),
+        };
+        client.shutdown().await;
+        result
+    };
+    match tokio::time::timeout(MCP_CHECK_TIMEOUT, connect).await {
+        Ok(Ok(tools)) => HealthCheckItem::new(&name, HealthStatus::Ok, format!("Reachable, {} tool(s)", tools.len())),
+        Ok(Err(e)) => HealthCheckItem::new(&name, HealthStatus::Error, e),
+        Err(_) => HealthCheckItem::new(
+            &name,
+            HealthStatus::Error,
+            format!("No answer within {}s", MCP_CHECK_TIMEOUT.as_secs()),
+        ),
+    }
+}
+
+async fn check_mcp_servers(pool: &anyagents::database::DbPool) -> Vec<HealthCheckItem> {
+    let servers: Vec<McpServer> = anyagents::database::blocking(pool, |conn| {
+        Ok(mcp_servers::table
+            .filter(mcp_servers::is_enabled.eq(1))
+            .load(conn)
+            .unwrap_or_default())
+    })
+    .await
+    .unwrap_or_default();
+
+    if servers.is_empty() {
+        return vec![HealthCheckItem::new("mcp", HealthStatus::Skipped, "No enabled MCP servers")];
+    }
+    futures::future::join_all(servers.into_iter().map(check_mcp_server)).await
+}
+
+fn check_server_port() -> HealthCheckItem {
+    let port = std::env::var("ANYCOWORK_SERVER_PORT")
+        .ok()
+        .and_then(|p| p.parse::<u16>().ok())
+        .unwrap_or(DEFAULT_SERVER_PORT);
+
+    match std::net::TcpListener::bind(("127.0.0.1", port)) {
+        Ok(_) => HealthCheckItem::new("server_port", HealthStatus::Ok, format!("Port {} is free", port)),
+        Err(e) => HealthCheckItem::new(
+            "server_port",
+            HealthStatus::Warning,
+            format!("Port {} is unavailable for the server adapter: {}", port, e),
+        ),
+    }
+}
+
+/// Run all first-run diagnostics and return a structured report
+#[tauri::command]
+pub async fn run_health_check(state: State<'_, AppState>) -> Result<HealthReport, String> {
+    let pool = state.db_pool.clone();
+
+    let mut checks = check_database_blocking(&pool).await;
+    checks.extend(check_providers(&pool).await);
+    checks.push(check_docker().await);
+    checks.push(check_transcription_model());
+    checks.extend(check_telegram(&pool).await);
+    checks.push(check_server_port());
+
+    Ok(report(&pool, checks))
+}
+
+fn report(pool: &anyagents::database::DbPool, checks: Vec<HealthCheckItem>) -> HealthReport {
+    let healthy = !checks.iter().any(|c| c.status == HealthStatus::Error);
+    HealthReport { healthy, checks, first_run: first_run(pool) }
+}
+
+fn first_run(pool: &anyagents::database::DbPool) -> bool {
+    anyagents::models::settings::get_setting(pool, ONBOARDING_SETTING).is_none()
+}
+
+/// Everything `run_health_check` covers, plus disk space of the workspaces
+/// and MCP server reachability. Shown on first launch and in Settings.
+#[tauri::command]
+pub async fn system_doctor(state: State<'_, AppState>) -> Result<HealthReport, String> {
+    let pool = state.db_pool.clone();
+
+    let mut checks = check_database_blocking(&pool).await;
+    let (providers, docker, mcp) = tokio::join!(check_providers(&pool), check_docker(), check_mcp_servers(&pool));
+    checks.extend(providers);
+    checks.push(docker);
+    checks.push(check_transcription_model());
+    checks.extend(check_disk_space(&pool).await);
+    checks.extend(mcp);
+    checks.push(check_server_port());
+
+    Ok(report(&pool, checks))
+}
+
+/// Whether onboarding is still due; cheap enough to ask on every launch
+#[tauri::command]
+pub async fn is_first_run(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(first_run(&state.db_pool))
+}
+
+/// Stop showing the report on launch
+#[tauri::command]
+pub async fn complete_onboarding(state: State<'_, AppState>) -> Result<(), String> {
+    anyagents::models::settings::set_setting(&state.db_pool, ONBOARDING_SETTING, "true")
+}
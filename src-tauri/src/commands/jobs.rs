@@ -0,0 +1,52 @@
+use crate::AppState;
+use anyagents::agents::queue::{self, JobLimits, QueuedJob};
+use anyagents::llm::connectivity::{self, OfflineProvider};
+use anyagents::models::artifact::{self, Artifact};
+use tauri::State;
+
+/// Running and queued jobs, then recently finished ones
+#[tauri::command]
+pub async fn list_jobs() -> Result<Vec<QueuedJob>, String> {
+    Ok(queue::global().list())
+}
+
+/// Drop a job that is still waiting for a slot
+#[tauri::command]
+pub async fn cancel_queued_job(job_id: String) -> Result<(), String> {
+    queue::global().cancel_queued(&job_id)
+}
+
+/// Providers that can't be reached now; their jobs are pending
+#[tauri::command]
+pub async fn get_offline_providers() -> Result<Vec<OfflineProvider>, String> {
+    Ok(connectivity::global().offline_providers())
+}
+
+/// How many jobs may run at once, overall and per agent
+#[tauri::command]
+pub async fn get_job_limits(state: State<'_, AppState>) -> Result<JobLimits, String> {
+    Ok(JobLimits::load(&state.db_pool))
+}
+
+/// Save the limits; waiting jobs start right away if the new limits allow
+#[tauri::command]
+pub async fn set_job_limits(state: State<'_, AppState>, limits: JobLimits) -> Result<(), String> {
+    if limits.max_concurrent == 0 || limits.per_agent == 0 {
+        return Err("Limits must be at least 1".to_string());
+    }
+    limits.save(&state.db_pool)?;
+    queue::global().set_limits(limits);
+    Ok(())
+}
+
+/// Files a job produced, oldest first
+#[tauri::command]
+pub async fn get_job_artifacts(state: State<'_, AppState>, job_id: String) -> Result<Vec<Artifact>, String> {
+    artifact::get_job_artifacts(&state.db_pool, &job_id)
+}
+
+/// Files every job of a session produced, oldest first
+#[tauri::command]
+pub async fn get_session_artifacts(state: State<'_, AppState>, session_id: String) -> Result<Vec<Artifact>, String> {
+    artifact::get_session_artifacts(&state.db_pool, &session_id, None)
+}
@@ -1,8 +1,15 @@
-use anyagents::models::{Agent, MailMessage, MailThread, NewMailMessage, NewMailThread};
+use anyagents::email::{attachments, recipients, search};
+use anyagents::models::{
+    Agent, MailAttachment, MailGroup, MailLabel, MailMessage, MailRecipient, MailThread, NewMailGroup,
+    NewMailGroupMember, NewMailLabel, NewMailMessage, NewMailThread,
+};
 use anyagents::schema;
+use anyagents::tools::email::{run_mail_session, MailAgentContext, MailReplyMode};
 use crate::AppState;
 use diesel::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
 use tauri::State;
 
 
@@ -18,6 +25,7 @@ pub struct MailThreadWithPreview {
     pub last_sender_name: Option<String>,
     pub last_sender_avatar: Option<String>,
     pub message_count: i64,
+    pub labels: Vec<MailLabel>,
 }
 
 #[derive(Serialize, Clone, Debug)]
@@ -30,80 +38,64 @@ pub struct MailMessageWithSender {
     pub sender_avatar: Option<String>,
     pub recipient_type: String,
     pub recipient_agent_id: Option<String>,
+    /// Real address of an `external` sender or recipient
+    pub external_address: Option<String>,
     pub content: String,
     pub created_at: String,
+    pub attachments: Vec<MailAttachment>,
+    /// Everyone the message went to, by kind (`to`, `cc`, `bcc`)
+    pub recipients: Vec<MailRecipient>,
 }
 
-#[tauri::command]
-pub async fn get_mail_threads(
-    state: State<'_, AppState>,
-    account_id: Option<String>,
-    folder: Option<String>,
-    is_archived: Option<bool>,
-) -> Result<Vec<MailThreadWithPreview>, String> {
-    use schema::mail_messages;
-    use schema::mail_threads;
-
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    // Get all threads
-    let mut threads_query = mail_threads::table.into_boxed();
-
-    if let Some(archived) = is_archived {
-        threads_query = threads_query.filter(mail_threads::is_archived.eq(if archived { 1 } else { 0 }));
-    } else {
-        threads_query = threads_query.filter(mail_threads::is_archived.eq(0));
+/// Attach user-picked files to a message that was just stored
+fn attach_files(db_pool: &anyagents::database::DbPool, message_id: &str, files: &[String]) -> Result<Vec<MailAttachment>, String> {
+    if files.len() > attachments::MAX_ATTACHMENTS_PER_MESSAGE {
+        return Err(format!(
+            "A message can have at most {} attachments",
+            attachments::MAX_ATTACHMENTS_PER_MESSAGE
+        ));
     }
+    let root = attachments::storage_dir();
+    files
+        .iter()
+        .map(|file| attachments::attach_file(db_pool, &root, message_id, std::path::Path::new(file)))
+        .collect()
+}
 
-    threads_query = threads_query.order(mail_threads::updated_at.desc());
-
-    let all_threads: Vec<MailThread> = threads_query
-        .load::<MailThread>(&mut conn)
-        .map_err(|e| e.to_string())?;
-
-    let folder_str = folder.unwrap_or_else(|| "inbox".to_string());
-
-    // Load all agents for name/avatar lookup
-    let all_agents: Vec<Agent> = schema::agents::table
-        .load::<Agent>(&mut conn)
-        .map_err(|e| e.to_string())?;
-
-    let mut result = Vec::new();
+/// List entries for a page of threads: last message, sender and labels
+async fn thread_previews(
+    db_pool: &anyagents::database::DbPool,
+    threads: Vec<MailThread>,
+) -> Result<Vec<MailThreadWithPreview>, String> {
+    use schema::mail_messages;
 
-    for thread in all_threads {
-        // Get messages for this thread
+    let thread_ids: Vec<String> = threads.iter().map(|t| t.id.clone()).collect();
+    let queried = thread_ids.clone();
+    let (msgs, all_agents) = anyagents::database::blocking(db_pool, move |conn| {
         let msgs: Vec<MailMessage> = mail_messages::table
-            .filter(mail_messages::thread_id.eq(&thread.id))
+            .filter(mail_messages::thread_id.eq_any(&queried))
             .order(mail_messages::created_at.asc())
-            .load::<MailMessage>(&mut conn)
+            .load::<MailMessage>(conn)
             .map_err(|e| e.to_string())?;
+        // Load all agents for name/avatar lookup
+        let all_agents: Vec<Agent> = schema::agents::table
+            .load::<Agent>(conn)
+            .map_err(|e| e.to_string())?;
+        Ok((msgs, all_agents))
+    })
+    .await?;
+    let mut by_thread: HashMap<String, Vec<MailMessage>> = HashMap::new();
+    for msg in msgs {
+        by_thread.entry(msg.thread_id.clone()).or_default().push(msg);
+    }
+    let mut labels = search::labels_for_threads(db_pool, &thread_ids)?;
 
-        if msgs.is_empty() {
+    let mut result = Vec::new();
+    for thread in threads {
+        let Some(msgs) = by_thread.get(&thread.id).filter(|m| !m.is_empty()) else {
             continue;
-        }
-
-        // Filter by folder and account
-        let thread_matches = match folder_str.as_str() {
-            "sent" => {
-                // Account is a sender in at least one message
-                msgs.iter().any(|m| match &account_id {
-                    None => m.sender_type == "user" && m.sender_agent_id.is_none(),
-                    Some(aid) => m.sender_agent_id.as_ref() == Some(aid),
-                })
-            }
-            _ => {
-                // inbox: account is a recipient in at least one message
-                msgs.iter().any(|m| match &account_id {
-                    None => m.recipient_type == "user" && m.recipient_agent_id.is_none(),
-                    Some(aid) => m.recipient_agent_id.as_ref() == Some(aid),
-                })
-            }
         };
 
-        if !thread_matches {
-            continue;
-        }
-
         // Get last message for preview
         let last_msg = msgs.last().unwrap();
         let preview = last_msg.content.chars().take(100).collect::<String>();
@@ -114,6 +106,8 @@ pub async fn get_mail_threads(
 
         let sender_name = if last_msg.sender_type == "user" {
             Some("You".to_string())
+        } else if last_msg.sender_type == "external" {
+            last_msg.external_address.clone()
         } else {
             sender_agent.map(|a| a.name.clone())
         };
@@ -121,6 +115,7 @@ pub async fn get_mail_threads(
         let sender_avatar = sender_agent.and_then(|a| a.avatar.clone());
 
         result.push(MailThreadWithPreview {
+            labels: labels.remove(&thread.id).unwrap_or_default(),
             id: thread.id,
             subject: thread.subject,
             is_read: thread.is_read,
@@ -137,6 +132,96 @@ pub async fn get_mail_threads(
     Ok(result)
 }
 
+/// One page of a mailbox folder, most recently active first
+#[tauri::command]
+pub async fn get_mail_threads(
+    state: State<'_, AppState>,
+    account_id: Option<String>,
+    folder: Option<String>,
+    is_archived: Option<bool>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Result<Vec<MailThreadWithPreview>, String> {
+    let page = search::search_threads(
+        &state.db_pool,
+        &search::MailSearch {
+            folder: Some(folder.unwrap_or_else(|| "inbox".to_string())),
+            account_id,
+            archived: Some(is_archived.unwrap_or(false)),
+            limit,
+            offset,
+            ..Default::default()
+        },
+    )?;
+    thread_previews(&state.db_pool, page.threads).await
+}
+
+/// Filters for `search_mail`. Dates are `YYYY-MM-DD` or RFC 3339; a bare
+/// `until` date includes that whole day.
+#[derive(Deserialize)]
+pub struct MailSearchInput {
+    pub query: Option<String>,
+    /// `inbox`, `sent` or `all` (the default)
+    pub folder: Option<String>,
+    pub account_id: Option<String>,
+    pub sender: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub label: Option<String>,
+    /// Unset searches archived and active threads alike
+    pub is_archived: Option<bool>,
+    pub unread_only: Option<bool>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MailSearchResults {
+    pub threads: Vec<MailThreadWithPreview>,
+    /// Matching threads across all pages
+    pub total: i64,
+}
+
+fn parse_search_date(value: &str, end_of_day: bool) -> Result<chrono::NaiveDateTime, String> {
+    if let Ok(at) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(at.naive_utc());
+    }
+    let day = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("'{}' is not a date; use YYYY-MM-DD", value))?;
+    Ok(if end_of_day {
+        day.and_hms_opt(23, 59, 59).unwrap()
+    } else {
+        day.and_hms_opt(0, 0, 0).unwrap()
+    })
+}
+
+#[tauri::command]
+pub async fn search_mail(
+    state: State<'_, AppState>,
+    filters: MailSearchInput,
+) -> Result<MailSearchResults, String> {
+    let page = search::search_threads(
+        &state.db_pool,
+        &search::MailSearch {
+            query: filters.query,
+            folder: filters.folder,
+            account_id: filters.account_id,
+            sender: filters.sender,
+            since: filters.since.as_deref().map(|d| parse_search_date(d, false)).transpose()?,
+            until: filters.until.as_deref().map(|d| parse_search_date(d, true)).transpose()?,
+            label: filters.label,
+            archived: filters.is_archived,
+            unread_only: filters.unread_only.unwrap_or(false),
+            limit: filters.limit,
+            offset: filters.offset,
+        },
+    )?;
+    Ok(MailSearchResults {
+        threads: thread_previews(&state.db_pool, page.threads).await?,
+        total: page.total,
+    })
+}
+
 #[tauri::command]
 pub async fn get_mail_thread_messages(
     state: State<'_, AppState>,
@@ -144,17 +229,23 @@ pub async fn get_mail_thread_messages(
 ) -> Result<Vec<MailMessageWithSender>, String> {
     use schema::mail_messages;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+    let (msgs, all_agents) = anyagents::database::blocking(&state.db_pool, move |conn| {
+        let msgs: Vec<MailMessage> = mail_messages::table
+            .filter(mail_messages::thread_id.eq(&thread_id))
+            .order(mail_messages::created_at.asc())
+            .load::<MailMessage>(conn)
+            .map_err(|e| e.to_string())?;
 
-    let msgs: Vec<MailMessage> = mail_messages::table
-        .filter(mail_messages::thread_id.eq(&thread_id))
-        .order(mail_messages::created_at.asc())
-        .load::<MailMessage>(&mut conn)
-        .map_err(|e| e.to_string())?;
+        let all_agents: Vec<Agent> = schema::agents::table
+            .load::<Agent>(conn)
+            .map_err(|e| e.to_string())?;
+        Ok((msgs, all_agents))
+    })
+    .await?;
 
-    let all_agents: Vec<Agent> = schema::agents::table
-        .load::<Agent>(&mut conn)
-        .map_err(|e| e.to_string())?;
+    let message_ids: Vec<String> = msgs.iter().map(|m| m.id.clone()).collect();
+    let mut attached = attachments::for_messages(&state.db_pool, &message_ids)?;
+    let mut recipient_rows = recipients::for_messages(&state.db_pool, &message_ids)?;
 
     let result: Vec<MailMessageWithSender> = msgs
         .into_iter()
@@ -165,6 +256,8 @@ pub async fn get_mail_thread_messages(
 
             let sender_name = if m.sender_type == "user" {
                 Some("You".to_string())
+            } else if m.sender_type == "external" {
+                m.external_address.clone()
             } else {
                 sender_agent.map(|a| a.name.clone())
             };
@@ -176,6 +269,8 @@ pub async fn get_mail_thread_messages(
             };
 
             MailMessageWithSender {
+                attachments: attached.remove(&m.id).unwrap_or_default(),
+                recipients: recipient_rows.remove(&m.id).unwrap_or_default(),
                 id: m.id,
                 thread_id: m.thread_id,
                 sender_type: m.sender_type,
@@ -184,6 +279,7 @@ pub async fn get_mail_thread_messages(
                 sender_avatar,
                 recipient_type: m.recipient_type,
                 recipient_agent_id: m.recipient_agent_id,
+                external_address: m.external_address,
                 content: m.content,
                 created_at: m.created_at.to_string(),
             }
@@ -193,18 +289,122 @@ pub async fn get_mail_thread_messages(
     Ok(result)
 }
 
+/// Start background replies from every agent on a message
+fn spawn_agent_replies(
+    db_pool: &anyagents::database::DbPool,
+    recipients: &[recipients::Recipient],
+    sender_name: &str,
+    sender_agent_id: Option<String>,
+    subject: &str,
+    body: &str,
+    thread_id: &str,
+    context: &MailAgentContext,
+) {
+    for target_id in recipients.iter().filter_map(|r| r.agent_id.clone()) {
+        let db_pool = db_pool.clone();
+        let context = context.clone();
+        let sender_name = sender_name.to_string();
+        let sender_id_for_reply = sender_agent_id.clone();
+        let subject = subject.to_string();
+        let body = body.to_string();
+        let thread_id = thread_id.to_string();
+
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = process_mail_background(
+                db_pool,
+                target_id,
+                sender_name,
+                sender_id_for_reply,
+                subject,
+                body,
+                thread_id,
+                context,
+            ).await {
+                log::error!("Background mail processing failed: {}", e);
+            }
+        });
+    }
+}
+
+/// Agents replying with tools ask the user for permissions in the app
+fn reply_context(app: tauri::AppHandle, state: &AppState) -> MailAgentContext {
+    MailAgentContext {
+        permissions: state.permission_manager.clone(),
+        observer: Arc::new(crate::events::TauriAppObserver { app }),
+    }
+}
+
+/// Send real email for a message with outside recipients
+fn spawn_delivery(
+    db_pool: &anyagents::database::DbPool,
+    from_agent_id: Option<&str>,
+    recipients: &[recipients::Recipient],
+    message_id: &str,
+) -> Result<(), String> {
+    if !recipients.iter().any(|r| r.recipient_type == "external") {
+        return Ok(());
+    }
+    // Checked up front; delivery itself runs in the background
+    let has_account = match from_agent_id {
+        Some(agent_id) => anyagents::email::account_for_agent(db_pool, agent_id)?.is_some(),
+        None => false,
+    };
+    if !has_account {
+        return Err("Only agents with an email account can write to email addresses".to_string());
+    }
+    let db_pool = db_pool.clone();
+    let message_id = message_id.to_string();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = anyagents::email::deliver(&db_pool, &message_id).await {
+            log::error!("Failed to deliver email: {}", e);
+        }
+    });
+    Ok(())
+}
+
+fn sender_display_name(conn: &mut SqliteConnection, from_agent_id: Option<&String>) -> String {
+    match from_agent_id {
+        Some(fid) => schema::agents::table
+            .filter(schema::agents::id.eq(fid))
+            .first::<Agent>(conn)
+            .map(|a| a.name)
+            .unwrap_or_else(|_| "Unknown".to_string()),
+        None => "User".to_string(),
+    }
+}
+
+/// Send a new message. `to`, `cc` and `bcc` take agent ids or names, group
+/// names, email addresses or `user`; `to_agent_id` is the single-recipient
+/// form, and with neither the message goes to the user.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn send_mail(
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
     from_agent_id: Option<String>,
     to_agent_id: Option<String>,
     subject: String,
     body: String,
+    attachments: Option<Vec<String>>,
+    to: Option<Vec<String>>,
+    cc: Option<Vec<String>>,
+    bcc: Option<Vec<String>>,
 ) -> Result<MailThreadWithPreview, String> {
     use schema::mail_messages;
     use schema::mail_threads;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+    let to = to
+        .filter(|to| !to.is_empty())
+        .unwrap_or_else(|| vec![to_agent_id.unwrap_or_else(|| "user".to_string())]);
+    let recipients = recipients::resolve(
+        &state.db_pool,
+        &to,
+        &cc.unwrap_or_default(),
+        &bcc.unwrap_or_default(),
+        from_agent_id.as_deref(),
+    )?;
+    let (recipient_type, recipient_agent_id, external_address) = recipients::primary_columns(&recipients)?;
+
     let now = chrono::Utc::now().naive_utc();
 
     let thread_id = uuid::Uuid::new_v4().to_string();
@@ -218,67 +418,56 @@ pub async fn send_mail(
         updated_at: now,
     };
 
-    diesel::insert_into(mail_threads::table)
-        .values(&new_thread)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
-
     let sender_type = if from_agent_id.is_some() { "agent" } else { "user" };
-    let recipient_type = if to_agent_id.is_some() { "agent" } else { "user" };
 
+    let message_id = uuid::Uuid::new_v4().to_string();
     let new_message = NewMailMessage {
-        id: uuid::Uuid::new_v4().to_string(),
+        id: message_id.clone(),
         thread_id: thread_id.clone(),
         sender_type: sender_type.to_string(),
         sender_agent_id: from_agent_id.clone(),
-        recipient_type: recipient_type.to_string(),
-        recipient_agent_id: to_agent_id.clone(),
+        recipient_type,
+        recipient_agent_id,
         content: body.clone(),
         created_at: now,
+        external_address,
+        external_message_id: None,
     };
 
-    diesel::insert_into(mail_messages::table)
-        .values(&new_message)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
-
-    // If recipient is an agent, process in background
-    if let Some(ref target_agent_id) = to_agent_id {
-        let db_pool = state.db_pool.clone();
-        let target_id = target_agent_id.clone();
-        let sender_name = if let Some(ref fid) = from_agent_id {
-            let agent: Option<Agent> = schema::agents::table
-                .filter(schema::agents::id.eq(fid))
-                .first::<Agent>(&mut conn)
-                .ok();
-            agent.map(|a| a.name).unwrap_or_else(|| "Unknown".to_string())
-        } else {
-            "User".to_string()
-        };
-        let sender_id_for_reply = from_agent_id.clone();
-        let subject_clone = subject.clone();
-        let body_clone = body.clone();
-        let thread_id_clone = thread_id.clone();
-
-        tauri::async_runtime::spawn(async move {
-            if let Err(e) = process_mail_background(
-                db_pool,
-                target_id,
-                sender_name,
-                sender_id_for_reply,
-                subject_clone,
-                body_clone,
-                thread_id_clone,
-            ).await {
-                log::error!("Background mail processing failed: {}", e);
-            }
-        });
-    }
+    let (stored_recipients, sender) = (recipients.clone(), from_agent_id.clone());
+    let (sender_name, all_agents) = anyagents::database::blocking(&state.db_pool, move |conn| {
+        diesel::insert_into(mail_threads::table)
+            .values(&new_thread)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+        diesel::insert_into(mail_messages::table)
+            .values(&new_message)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+        recipients::record(conn, &new_message.id, &stored_recipients)?;
 
-    // Get sender info for preview
-    let all_agents: Vec<Agent> = schema::agents::table
-        .load::<Agent>(&mut conn)
-        .map_err(|e| e.to_string())?;
+        // Get sender info for preview
+        let all_agents: Vec<Agent> = schema::agents::table
+            .load::<Agent>(conn)
+            .map_err(|e| e.to_string())?;
+        Ok((sender_display_name(conn, sender.as_ref()), all_agents))
+    })
+    .await?;
+
+    // Attached before the recipient agents start reading
+    attach_files(&state.db_pool, &message_id, &attachments.unwrap_or_default())?;
+
+    spawn_delivery(&state.db_pool, from_agent_id.as_deref(), &recipients, &message_id)?;
+    spawn_agent_replies(
+        &state.db_pool,
+        &recipients,
+        &sender_name,
+        from_agent_id.clone(),
+        &subject,
+        &body,
+        &thread_id,
+        &reply_context(app, &state),
+    );
 
     let sender_agent = from_agent_id.as_ref().and_then(|fid| {
         all_agents.iter().find(|a| &a.id == fid)
@@ -299,46 +488,64 @@ pub async fn send_mail(
         },
         last_sender_avatar: sender_agent.and_then(|a| a.avatar.clone()),
         message_count: 1,
+        labels: vec![],
     })
 }
 
+/// Reply in a thread. With `reply_all` the sender of the last message is
+/// answered and everyone else it was visibly sent to is copied.
 #[tauri::command]
 pub async fn reply_to_mail(
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
     thread_id: String,
     from_agent_id: Option<String>,
     content: String,
+    attachments: Option<Vec<String>>,
+    reply_all: Option<bool>,
 ) -> Result<MailMessageWithSender, String> {
     use schema::mail_messages;
     use schema::mail_threads;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
     let now = chrono::Utc::now().naive_utc();
 
     // Get existing messages to determine the "other side"
-    let existing_msgs: Vec<MailMessage> = mail_messages::table
-        .filter(mail_messages::thread_id.eq(&thread_id))
-        .order(mail_messages::created_at.desc())
-        .load::<MailMessage>(&mut conn)
-        .map_err(|e| e.to_string())?;
-
-    // Determine recipient (the "other side" of the conversation)
-    let (recipient_type, recipient_agent_id) = if let Some(last_msg) = existing_msgs.first() {
+    let replied = thread_id.clone();
+    let existing_msgs: Vec<MailMessage> = anyagents::database::blocking(&state.db_pool, move |conn| {
+        mail_messages::table
+            .filter(mail_messages::thread_id.eq(&replied))
+            .order(mail_messages::created_at.desc())
+            .load::<MailMessage>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await?;
+    let last_msg = existing_msgs.first().ok_or("Thread has no messages")?;
+
+    let reply_recipients = if reply_all.unwrap_or(false) {
+        let rows = recipients::for_messages(&state.db_pool, std::slice::from_ref(&last_msg.id))?
+            .remove(&last_msg.id)
+            .unwrap_or_default();
+        recipients::reply_all(last_msg, &rows, from_agent_id.as_deref())
+    } else {
         // Reply to the last sender (unless it's us)
         let is_self = match &from_agent_id {
             None => last_msg.sender_type == "user" && last_msg.sender_agent_id.is_none(),
             Some(fid) => last_msg.sender_agent_id.as_ref() == Some(fid),
         };
-
-        if is_self {
-            // We are replying to ourselves? Use the original recipient
-            (last_msg.recipient_type.clone(), last_msg.recipient_agent_id.clone())
+        // Replying to ourselves goes to the original recipient
+        let (kind, agent_id) = if is_self {
+            (last_msg.recipient_type.as_str(), last_msg.recipient_agent_id.as_deref())
         } else {
-            (last_msg.sender_type.clone(), last_msg.sender_agent_id.clone())
-        }
-    } else {
-        return Err("Thread has no messages".to_string());
+            (last_msg.sender_type.as_str(), last_msg.sender_agent_id.as_deref())
+        };
+        let recipient = match (kind, agent_id, last_msg.external_address.as_deref()) {
+            ("agent", Some(agent_id), _) => recipients::Recipient::agent(recipients::TO, agent_id),
+            ("external", _, Some(address)) => recipients::Recipient::external(recipients::TO, address),
+            _ => recipients::Recipient::user(recipients::TO),
+        };
+        vec![recipient]
     };
+    let (recipient_type, recipient_agent_id, external_address) = recipients::primary_columns(&reply_recipients)?;
 
     let sender_type = if from_agent_id.is_some() { "agent" } else { "user" };
 
@@ -352,71 +559,62 @@ pub async fn reply_to_mail(
         recipient_agent_id: recipient_agent_id.clone(),
         content: content.clone(),
         created_at: now,
+        external_address: external_address.clone(),
+        external_message_id: None,
     };
 
-    diesel::insert_into(mail_messages::table)
-        .values(&new_message)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
-
-    // Update thread timestamp and mark unread
-    diesel::update(mail_threads::table.filter(mail_threads::id.eq(&thread_id)))
-        .set((
-            mail_threads::updated_at.eq(now),
-            mail_threads::is_read.eq(0),
-        ))
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
-
-    // If recipient is an agent, process in background
-    if recipient_type == "agent" {
-        if let Some(ref target_agent_id) = recipient_agent_id {
-            let db_pool = state.db_pool.clone();
-            let target_id = target_agent_id.clone();
-            let sender_name = if let Some(ref fid) = from_agent_id {
-                let agent: Option<Agent> = schema::agents::table
-                    .filter(schema::agents::id.eq(fid))
-                    .first::<Agent>(&mut conn)
-                    .ok();
-                agent.map(|a| a.name).unwrap_or_else(|| "Unknown".to_string())
-            } else {
-                "User".to_string()
-            };
-            let sender_id_for_reply = from_agent_id.clone();
-
-            // Get thread subject
-            let thread: MailThread = mail_threads::table
-                .filter(mail_threads::id.eq(&thread_id))
-                .first::<MailThread>(&mut conn)
-                .map_err(|e| e.to_string())?;
+    let (stored_recipients, sender, replied) = (reply_recipients.clone(), from_agent_id.clone(), thread_id.clone());
+    let (thread, sender_name, all_agents) = anyagents::database::blocking(&state.db_pool, move |conn| {
+        diesel::insert_into(mail_messages::table)
+            .values(&new_message)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+        recipients::record(conn, &new_message.id, &stored_recipients)?;
 
-            let thread_id_clone = thread_id.clone();
-            let content_clone = content.clone();
-
-            tauri::async_runtime::spawn(async move {
-                if let Err(e) = process_mail_background(
-                    db_pool,
-                    target_id,
-                    sender_name,
-                    sender_id_for_reply,
-                    thread.subject,
-                    content_clone,
-                    thread_id_clone,
-                ).await {
-                    log::error!("Background reply processing failed: {}", e);
-                }
-            });
-        }
-    }
+        // Update thread timestamp and mark unread
+        diesel::update(mail_threads::table.filter(mail_threads::id.eq(&replied)))
+            .set((
+                mail_threads::updated_at.eq(now),
+                mail_threads::is_read.eq(0),
+            ))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
 
-    // Build response with sender info
-    let all_agents: Vec<Agent> = schema::agents::table
-        .load::<Agent>(&mut conn)
-        .map_err(|e| e.to_string())?;
+        let thread: MailThread = mail_threads::table
+            .filter(mail_threads::id.eq(&replied))
+            .first::<MailThread>(conn)
+            .map_err(|e| e.to_string())?;
+        // Build response with sender info
+        let all_agents: Vec<Agent> = schema::agents::table
+            .load::<Agent>(conn)
+            .map_err(|e| e.to_string())?;
+        Ok((thread, sender_display_name(conn, sender.as_ref()), all_agents))
+    })
+    .await?;
+
+    let attached = attach_files(&state.db_pool, &message_id, &attachments.unwrap_or_default())?;
+
+    // Replies to outside senders leave through the agent's email account
+    spawn_delivery(&state.db_pool, from_agent_id.as_deref(), &reply_recipients, &message_id)?;
+
+    // Agents on the reply answer in the background
+    spawn_agent_replies(
+        &state.db_pool,
+        &reply_recipients,
+        &sender_name,
+        from_agent_id.clone(),
+        &thread.subject,
+        &content,
+        &thread_id,
+        &reply_context(app, &state),
+    );
 
     let sender_agent = from_agent_id.as_ref().and_then(|fid| {
         all_agents.iter().find(|a| &a.id == fid)
     });
+    let recipient_rows = recipients::for_messages(&state.db_pool, std::slice::from_ref(&message_id))?
+        .remove(&message_id)
+        .unwrap_or_default();
 
     Ok(MailMessageWithSender {
         id: message_id,
@@ -431,85 +629,422 @@ pub async fn reply_to_mail(
         sender_avatar: sender_agent.and_then(|a| a.avatar.clone()),
         recipient_type,
         recipient_agent_id,
+        external_address,
         content,
         created_at: now.to_string(),
+        attachments: attached,
+        recipients: recipient_rows,
     })
 }
 
+/// Copy a stored attachment to `destination`, e.g. a path from a save dialog
 #[tauri::command]
-pub async fn mark_thread_read(
+pub async fn save_mail_attachment(
     state: State<'_, AppState>,
-    thread_id: String,
+    attachment_id: String,
+    destination: String,
 ) -> Result<(), String> {
-    use schema::mail_threads;
+    use schema::mail_attachments;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let attachment: MailAttachment = mail_attachments::table
+            .find(&attachment_id)
+            .first(conn)
+            .map_err(|e| format!("Attachment not found: {}", e))?;
+        std::fs::copy(&attachment.storage_path, &destination)
+            .map_err(|e| format!("Failed to save attachment: {}", e))?;
+        Ok(())
+    })
+    .await
+}
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+#[derive(Serialize, Clone, Debug)]
+pub struct MailGroupWithMembers {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub member_ids: Vec<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
 
-    diesel::update(mail_threads::table.filter(mail_threads::id.eq(&thread_id)))
-        .set(mail_threads::is_read.eq(1))
-        .execute(&mut conn)
+fn load_group(conn: &mut SqliteConnection, group_id: &str) -> Result<MailGroupWithMembers, String> {
+    use schema::{mail_group_members, mail_groups};
+
+    let group: MailGroup = mail_groups::table
+        .find(group_id)
+        .first(conn)
+        .map_err(|e| format!("Group not found: {}", e))?;
+    let member_ids = mail_group_members::table
+        .filter(mail_group_members::group_id.eq(group_id))
+        .order(mail_group_members::created_at.asc())
+        .select(mail_group_members::agent_id)
+        .load::<String>(conn)
         .map_err(|e| e.to_string())?;
+    Ok(MailGroupWithMembers {
+        id: group.id,
+        name: group.name,
+        description: group.description,
+        member_ids,
+        created_at: group.created_at.to_string(),
+        updated_at: group.updated_at.to_string(),
+    })
+}
+
+fn check_group_name(name: &str) -> Result<(), String> {
+    if name == "user" || !recipients::is_valid_group_name(name) {
+        return Err(format!(
+            "'{}' is not a valid group name; use lowercase letters, digits and dashes, like engineering-team",
+            name
+        ));
+    }
+    Ok(())
+}
+
+fn set_group_members(conn: &mut SqliteConnection, group_id: &str, member_ids: &[String]) -> QueryResult<()> {
+    use schema::mail_group_members;
 
+    let now = chrono::Utc::now().naive_utc();
+    diesel::delete(mail_group_members::table.filter(mail_group_members::group_id.eq(group_id))).execute(conn)?;
+    let mut seen = std::collections::HashSet::new();
+    let rows: Vec<NewMailGroupMember> = member_ids
+        .iter()
+        .filter(|id| seen.insert(id.as_str()))
+        .map(|agent_id| NewMailGroupMember {
+            group_id: group_id.to_string(),
+            agent_id: agent_id.clone(),
+            created_at: now,
+        })
+        .collect();
+    if !rows.is_empty() {
+        diesel::insert_into(mail_group_members::table).values(&rows).execute(conn)?;
+    }
     Ok(())
 }
 
 #[tauri::command]
-pub async fn archive_thread(
+pub async fn get_mail_groups(state: State<'_, AppState>) -> Result<Vec<MailGroupWithMembers>, String> {
+    use schema::mail_groups;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let ids: Vec<String> = mail_groups::table
+            .order(mail_groups::name.asc())
+            .select(mail_groups::id)
+            .load(conn)
+            .map_err(|e| e.to_string())?;
+        ids.iter().map(|group_id| load_group(conn, group_id)).collect()
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn create_mail_group(
     state: State<'_, AppState>,
-    thread_id: String,
-) -> Result<(), String> {
-    use schema::mail_threads;
+    name: String,
+    description: Option<String>,
+    member_ids: Vec<String>,
+) -> Result<MailGroupWithMembers, String> {
+    use schema::mail_groups;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+    let name = name.trim().to_lowercase();
+    check_group_name(&name)?;
 
-    diesel::update(mail_threads::table.filter(mail_threads::id.eq(&thread_id)))
-        .set(mail_threads::is_archived.eq(1))
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let now = chrono::Utc::now().naive_utc();
+        let group = NewMailGroup {
+            id: uuid::Uuid::new_v4().to_string(),
+            name,
+            description,
+            created_at: now,
+            updated_at: now,
+        };
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            diesel::insert_into(mail_groups::table).values(&group).execute(conn)?;
+            set_group_members(conn, &group.id, &member_ids)
+        })
+        .map_err(|e| match e {
+            diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::UniqueViolation, _) => {
+                format!("A group named '{}' already exists", group.name)
+            }
+            e => e.to_string(),
+        })?;
+        load_group(conn, &group.id)
+    })
+    .await
+}
+
+/// Rename a group, change its description or replace its members
+#[tauri::command]
+pub async fn update_mail_group(
+    state: State<'_, AppState>,
+    group_id: String,
+    name: Option<String>,
+    description: Option<String>,
+    member_ids: Option<Vec<String>>,
+) -> Result<MailGroupWithMembers, String> {
+    use schema::mail_groups;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        load_group(conn, &group_id)?;
+        let now = chrono::Utc::now().naive_utc();
+
+        if let Some(name) = name {
+            let name = name.trim().to_lowercase();
+            check_group_name(&name)?;
+            diesel::update(mail_groups::table.find(&group_id))
+                .set(mail_groups::name.eq(&name))
+                .execute(conn)
+                .map_err(|e| match e {
+                    diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::UniqueViolation, _) => {
+                        format!("A group named '{}' already exists", name)
+                    }
+                    e => e.to_string(),
+                })?;
+        }
+        if let Some(description) = description {
+            diesel::update(mail_groups::table.find(&group_id))
+                .set(mail_groups::description.eq(Some(description).filter(|d| !d.trim().is_empty())))
+                .execute(conn)
+                .map_err(|e| e.to_string())?;
+        }
+        if let Some(member_ids) = member_ids {
+            set_group_members(conn, &group_id, &member_ids).map_err(|e| e.to_string())?;
+        }
+        diesel::update(mail_groups::table.find(&group_id))
+            .set(mail_groups::updated_at.eq(now))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+        load_group(conn, &group_id)
+    })
+    .await
+}
+
+/// Delete a group. Mail already sent to it keeps its expanded recipients.
+#[tauri::command]
+pub async fn delete_mail_group(state: State<'_, AppState>, group_id: String) -> Result<(), String> {
+    use schema::{mail_group_members, mail_groups};
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        diesel::delete(mail_group_members::table.filter(mail_group_members::group_id.eq(&group_id)))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+        diesel::delete(mail_groups::table.find(&group_id))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    })
+    .await
+}
 
+fn label_error(name: &str) -> impl Fn(diesel::result::Error) -> String + '_ {
+    move |e| match e {
+        diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::UniqueViolation, _) => {
+            format!("A label named '{}' already exists", name)
+        }
+        e => e.to_string(),
+    }
+}
+
+fn check_label_name(name: &str) -> Result<(), String> {
+    if !search::is_valid_label_name(name) {
+        return Err(format!(
+            "Label names must be 1 to {} characters",
+            search::MAX_LABEL_NAME_LEN
+        ));
+    }
     Ok(())
 }
 
 #[tauri::command]
-pub async fn get_unread_mail_count(
+pub async fn get_mail_labels(state: State<'_, AppState>) -> Result<Vec<MailLabel>, String> {
+    use schema::mail_labels;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        mail_labels::table
+            .order(mail_labels::name.asc())
+            .load::<MailLabel>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn create_mail_label(
     state: State<'_, AppState>,
-    account_id: Option<String>,
-) -> Result<i64, String> {
-    use schema::mail_messages;
-    use schema::mail_threads;
+    name: String,
+    color: Option<String>,
+) -> Result<MailLabel, String> {
+    use schema::mail_labels;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+    let name = name.trim().to_string();
+    check_label_name(&name)?;
 
-    // Get unread, non-archived threads
-    let unread_threads: Vec<MailThread> = mail_threads::table
-        .filter(mail_threads::is_read.eq(0))
-        .filter(mail_threads::is_archived.eq(0))
-        .load::<MailThread>(&mut conn)
-        .map_err(|e| e.to_string())?;
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let label = NewMailLabel {
+            id: uuid::Uuid::new_v4().to_string(),
+            name,
+            color,
+            created_at: chrono::Utc::now().naive_utc(),
+        };
+        diesel::insert_into(mail_labels::table)
+            .values(&label)
+            .execute(conn)
+            .map_err(label_error(&label.name))?;
+        mail_labels::table
+            .find(&label.id)
+            .first::<MailLabel>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
 
-    let mut count = 0i64;
-    for thread in unread_threads {
-        let msgs: Vec<MailMessage> = mail_messages::table
-            .filter(mail_messages::thread_id.eq(&thread.id))
-            .load::<MailMessage>(&mut conn)
-            .map_err(|e| e.to_string())?;
+/// Rename or recolor a label
+#[tauri::command]
+pub async fn update_mail_label(
+    state: State<'_, AppState>,
+    label_id: String,
+    name: Option<String>,
+    color: Option<String>,
+) -> Result<MailLabel, String> {
+    use schema::mail_labels;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        if let Some(name) = name {
+            let name = name.trim().to_string();
+            check_label_name(&name)?;
+            diesel::update(mail_labels::table.find(&label_id))
+                .set(mail_labels::name.eq(&name))
+                .execute(conn)
+                .map_err(label_error(&name))?;
+        }
+        if let Some(color) = color {
+            diesel::update(mail_labels::table.find(&label_id))
+                .set(mail_labels::color.eq(Some(color).filter(|c| !c.trim().is_empty())))
+                .execute(conn)
+                .map_err(|e| e.to_string())?;
+        }
+        mail_labels::table
+            .find(&label_id)
+            .first::<MailLabel>(conn)
+            .map_err(|e| format!("Label not found: {}", e))
+    })
+    .await
+}
 
-        let is_inbox = msgs.iter().any(|m| match &account_id {
-            None => m.recipient_type == "user" && m.recipient_agent_id.is_none(),
-            Some(aid) => m.recipient_agent_id.as_ref() == Some(aid),
-        });
+/// Delete a label and take it off every thread
+#[tauri::command]
+pub async fn delete_mail_label(state: State<'_, AppState>, label_id: String) -> Result<(), String> {
+    use schema::{mail_labels, mail_thread_labels};
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        diesel::delete(mail_thread_labels::table.filter(mail_thread_labels::label_id.eq(&label_id)))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+        diesel::delete(mail_labels::table.find(&label_id))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    })
+    .await
+}
 
-        if is_inbox {
-            count += 1;
+/// Replace the labels on a thread; returns the labels it now has
+#[tauri::command]
+pub async fn set_mail_thread_labels(
+    state: State<'_, AppState>,
+    thread_id: String,
+    label_ids: Vec<String>,
+) -> Result<Vec<MailLabel>, String> {
+    use schema::{mail_labels, mail_threads};
+
+    let labelled = thread_id.clone();
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        mail_threads::table
+            .find(&labelled)
+            .select(mail_threads::id)
+            .first::<String>(conn)
+            .map_err(|e| format!("Thread not found: {}", e))?;
+        let known = mail_labels::table
+            .filter(mail_labels::id.eq_any(&label_ids))
+            .count()
+            .get_result::<i64>(conn)
+            .map_err(|e| e.to_string())?;
+        let distinct: std::collections::HashSet<&String> = label_ids.iter().collect();
+        if known as usize != distinct.len() {
+            return Err("Unknown label".to_string());
         }
-    }
 
-    Ok(count)
+        search::set_thread_labels(conn, &labelled, &label_ids).map_err(|e| e.to_string())
+    })
+    .await?;
+    Ok(search::labels_for_threads(&state.db_pool, &[thread_id.clone()])?
+        .remove(&thread_id)
+        .unwrap_or_default())
+}
+
+#[tauri::command]
+pub async fn mark_thread_read(
+    state: State<'_, AppState>,
+    thread_id: String,
+) -> Result<(), String> {
+    use schema::mail_threads;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        diesel::update(mail_threads::table.filter(mail_threads::id.eq(&thread_id)))
+            .set(mail_threads::is_read.eq(1))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn archive_thread(
+    state: State<'_, AppState>,
+    thread_id: String,
+) -> Result<(), String> {
+    use schema::mail_threads;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        diesel::update(mail_threads::table.filter(mail_threads::id.eq(&thread_id)))
+            .set(mail_threads::is_archived.eq(1))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_unread_mail_count(
+    state: State<'_, AppState>,
+    account_id: Option<String>,
+) -> Result<i64, String> {
+    // Unread, non-archived threads in the inbox
+    let page = search::search_threads(
+        &state.db_pool,
+        &search::MailSearch {
+            folder: Some("inbox".to_string()),
+            account_id,
+            archived: Some(false),
+            unread_only: true,
+            limit: Some(1),
+            ..Default::default()
+        },
+    )?;
+    Ok(page.total)
 }
 
 /// Background processing: agent reads email and composes a reply
-/// Uses direct LLM call without tools to prevent meta-responses
-async fn process_mail_background(
+/// Uses direct LLM call without tools to prevent meta-responses, unless the
+/// agent is set to answer mail with a full tool-enabled run.
+/// When the latest incoming message came from a real inbox, the reply is
+/// addressed to it and delivered over the agent's email account.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn process_mail_background(
     db_pool: anyagents::database::DbPool,
     target_agent_id: String,
     _sender_name: String,
@@ -517,26 +1052,40 @@ async fn process_mail_background(
     subject: String,
     _body: String,
     thread_id: String,
+    context: MailAgentContext,
 ) -> Result<(), String> {
     use schema::agents::dsl::*;
 
     // 1. Load data from DB (Agent + Thread History)
-    let (agent_db, conversation_history) = {
-        let mut conn = db_pool.get().map_err(|e| format!("DB error: {}", e))?;
-        
-        // Load agent
-        let agent = agents
-            .filter(id.eq(&target_agent_id))
-            .first::<Agent>(&mut conn)
-            .map_err(|e| format!("Agent not found: {}", e))?;
-
-        // Load thread history
-        use schema::mail_messages;
-        let thread_messages: Vec<MailMessage> = mail_messages::table
-            .filter(mail_messages::thread_id.eq(&thread_id))
-            .order(mail_messages::created_at.asc())
-            .load::<MailMessage>(&mut conn)
-            .map_err(|e| format!("Failed to load thread history: {}", e))?;
+    let (agent_db, conversation_history, external_sender) = {
+        let (agent_key, history_of) = (target_agent_id.clone(), thread_id.clone());
+        let (agent, thread_messages) = anyagents::database::blocking(&db_pool, move |conn| {
+            // Load agent
+            let agent = agents
+                .filter(id.eq(&agent_key))
+                .first::<Agent>(conn)
+                .map_err(|e| format!("Agent not found: {}", e))?;
+
+            // Load thread history
+            use schema::mail_messages;
+            let thread_messages: Vec<MailMessage> = mail_messages::table
+                .filter(mail_messages::thread_id.eq(&history_of))
+                .order(mail_messages::created_at.asc())
+                .load::<MailMessage>(conn)
+                .map_err(|e| format!("Failed to load thread history: {}", e))?;
+            Ok((agent, thread_messages))
+        })
+        .await?;
+
+        let external_sender = thread_messages
+            .iter()
+            .rev()
+            .find(|m| m.sender_agent_id.as_ref() != Some(&target_agent_id))
+            .filter(|m| m.sender_type == "external")
+            .and_then(|m| m.external_address.clone());
+
+        let message_ids: Vec<String> = thread_messages.iter().map(|m| m.id.clone()).collect();
+        let attached = attachments::for_messages(&db_pool, &message_ids)?;
 
         // Format history
         let mut history = String::new();
@@ -545,58 +1094,79 @@ async fn process_mail_background(
                 "You".to_string()
             } else if msg.sender_type == "user" {
                 "User".to_string()
+            } else if let Some(address) = msg.external_address.as_ref().filter(|_| msg.sender_type == "external") {
+                address.clone()
             } else {
                 "Sender".to_string()
             };
 
             history.push_str(&format!("{}: {}\n\n", name_label, msg.content));
+            if let Some(files) = attached.get(&msg.id) {
+                history.push_str(&attachments::describe_for_prompt(files));
+                history.push('\n');
+            }
         }
         
-        (agent, history)
+        (agent, history, external_sender)
     };
 
-    // 2. Build system prompt incorporating the agent's personality
-    // IMPORTANT: Instruct the agent to write ONLY the reply content, no tool usage
-    let preamble = format!(
-        "{}\n\n---\n\nYou are composing an email reply. Write ONLY the reply content — no subject line, no meta-commentary, no tool usage, no JSON. Write naturally as yourself, addressing the request directly as if writing an email.",
-        agent_db.system_prompt.as_deref().unwrap_or("You are a helpful assistant.")
-    );
+    let reply = if MailReplyMode::for_agent(&agent_db) == MailReplyMode::Tools {
+        // The agent does the work in a session of its own and its final
+        // answer, which reports what actually happened, becomes the reply
+        let request = format!(
+            "You received an email.\n\nSubject: {subject}\n\nHere is the conversation history:\n\n{conversation_history}",
+        );
+        let (answer, session_id) =
+            run_mail_session(&db_pool, &agent_db, &subject, &request, &context).await?;
+        log::info!("Mail in thread {} handled in session {}", thread_id, session_id);
+        answer
+    } else {
+        // 2. Build system prompt incorporating the agent's personality
+        // IMPORTANT: Instruct the agent to write ONLY the reply content, no tool usage
+        let preamble = anyagents::tools::email::mail_reply_preamble(agent_db.system_prompt.as_deref());
+
+        // 4. LLM call
+        let key_name = match agent_db.ai_provider.as_str() {
+            "openai" => "OPENAI_API_KEY",
+            "gemini" => "GEMINI_API_KEY",
+            "anthropic" => "ANTHROPIC_API_KEY",
+            _ => "",
+        };
+        let api_key = anyagents::models::settings::get_setting(&db_pool, key_name);
 
-    // 4. LLM call
-    let key_name = match agent_db.ai_provider.as_str() {
-        "openai" => "OPENAI_API_KEY",
-        "gemini" => "GEMINI_API_KEY",
-        "anthropic" => "ANTHROPIC_API_KEY",
-        _ => "",
-    };
-    let api_key = anyagents::models::settings::get_setting(&db_pool, key_name);
+        let mut client = anyagents::llm::LlmClient::new(&agent_db.ai_provider, &agent_db.ai_model)
+            .with_preamble(&preamble)
+            .with_params(agent_db.generation_params().for_provider(&agent_db.ai_provider));
 
-    let mut client = anyagents::llm::LlmClient::new(&agent_db.ai_provider, &agent_db.ai_model)
-        .with_preamble(&preamble);
-    
-    if let Some(key) = api_key {
-        client = client.with_api_key(&key);
-    }
+        if let Some(key) = api_key {
+            client = client.with_api_key(&key);
+        }
 
-    let prompt = format!(
-        "Subject: {subject}\n\nHere is the conversation history:\n\n{conversation_history}\n\nWrite your reply:",
-    );
+        let prompt = format!(
+            "Subject: {subject}\n\nHere is the conversation history:\n\n{conversation_history}\n\nWrite your reply:",
+        );
 
-    let reply = client.prompt(&prompt).await.map_err(|e| format!("LLM error: {}", e))?;
+        let reply = client.prompt(&prompt).await.map_err(|e| format!("LLM error: {}", e))?;
+        // Clean up the reply - remove any tool calls or meta-commentary
+        clean_email_reply(&reply)
+    };
 
     // 5. Save reply as a mail_message in the thread
     if !reply.trim().is_empty() {
         let now = chrono::Utc::now().naive_utc();
-        let mut conn = db_pool.get().map_err(|e| format!("DB error: {}", e))?;
 
-        // Reply goes to the original sender or to user if sender was user
-        let (reply_recipient_type, reply_recipient_agent_id) = match &sender_agent_id {
-            Some(sid) => ("agent".to_string(), Some(sid.clone())),
-            None => ("user".to_string(), None),
+        // Reply to all on the message the agent received; without one, to
+        // the original sender or to user if sender was user
+        let fallback = match (&external_sender, &sender_agent_id) {
+            (Some(address), _) => recipients::Recipient::external(recipients::TO, address),
+            (None, Some(sid)) => recipients::Recipient::agent(recipients::TO, sid),
+            (None, None) => recipients::Recipient::user(recipients::TO),
         };
+        let reply_to = recipients::background_reply_recipients(&db_pool, &thread_id, &target_agent_id, fallback)?;
+        let (reply_recipient_type, reply_recipient_agent_id, reply_external_address) =
+            recipients::primary_columns(&reply_to)?;
 
-        // Clean up the reply - remove any tool calls or meta-commentary
-        let clean_reply = clean_email_reply(&reply);
+        let (clean_reply, attach_paths) = attachments::extract_attach_markers(&reply);
 
         let new_reply = NewMailMessage {
             id: uuid::Uuid::new_v4().to_string(),
@@ -605,23 +1175,46 @@ async fn process_mail_background(
             sender_agent_id: Some(target_agent_id.clone()),
             recipient_type: reply_recipient_type,
             recipient_agent_id: reply_recipient_agent_id,
-            content: clean_reply,
+            content: clean_reply.trim().to_string(),
             created_at: now,
+            external_address: reply_external_address,
+            external_message_id: None,
         };
 
-        diesel::insert_into(schema::mail_messages::table)
-            .values(&new_reply)
-            .execute(&mut conn)
-            .map_err(|e| format!("Failed to save reply: {}", e))?;
-
-        // Update thread timestamp and mark unread
-        diesel::update(schema::mail_threads::table.filter(schema::mail_threads::id.eq(&thread_id)))
-            .set((
-                schema::mail_threads::updated_at.eq(now),
-                schema::mail_threads::is_read.eq(0),
-            ))
-            .execute(&mut conn)
-            .map_err(|e| format!("Failed to update thread: {}", e))?;
+        let reply_id = new_reply.id.clone();
+        let (stored_recipients, replied) = (reply_to.clone(), thread_id.clone());
+        anyagents::database::blocking(&db_pool, move |conn| {
+            diesel::insert_into(schema::mail_messages::table)
+                .values(&new_reply)
+                .execute(conn)
+                .map_err(|e| format!("Failed to save reply: {}", e))?;
+            recipients::record(conn, &new_reply.id, &stored_recipients)?;
+
+            // Update thread timestamp and mark unread
+            diesel::update(schema::mail_threads::table.filter(schema::mail_threads::id.eq(&replied)))
+                .set((
+                    schema::mail_threads::updated_at.eq(now),
+                    schema::mail_threads::is_read.eq(0),
+                ))
+                .execute(conn)
+                .map_err(|e| format!("Failed to update thread: {}", e))?;
+            Ok(())
+        })
+        .await?;
+
+        attachments::attach_workspace_files(
+            &db_pool,
+            &attachments::storage_dir(),
+            &reply_id,
+            &attachments::agent_workspace(&agent_db),
+            &attach_paths,
+        );
+
+        if reply_to.iter().any(|r| r.recipient_type == "external") {
+            if !anyagents::email::deliver(&db_pool, &reply_id).await? {
+                log::info!("Email reply in thread {} kept as a draft", thread_id);
+            }
+        }
     }
 
     log::info!("Background mail processing completed for thread {}", thread_id);
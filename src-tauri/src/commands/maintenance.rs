@@ -0,0 +1,247 @@
+use crate::AppState;
+use anyagents::backup::{self, BackupInfo, BackupSchedule, DatabaseCheck};
+use anyagents::database::DbPool;
+use anyagents::encryption::{self, EncryptionStatus};
+use anyagents::maintenance::{self, IntegrityReport};
+use anyagents::retention::{self, RetentionPolicy, RetentionReport, StorageStats};
+use diesel::prelude::*;
+use std::path::PathBuf;
+use tauri::{Manager, State};
+
+/// How often the background cleanup runs
+pub const CLEANUP_INTERVAL_SECS: u64 = 6 * 60 * 60;
+
+/// How often the backup scheduler checks whether a backup is due
+pub const BACKUP_CHECK_INTERVAL_SECS: u64 = 15 * 60;
+
+fn attachments_dir(app: &tauri::AppHandle) -> Option<PathBuf> {
+    app.path()
+        .app_data_dir()
+        .ok()
+        .map(|dir| dir.join("attachments"))
+}
+
+fn backups_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join("backups"))
+        .map_err(|e| format!("No app data directory: {}", e))
+}
+
+/// Detach tasks that still point at deleted sessions. Tasks live in the app
+/// schema, so this can't be part of the core cleanup.
+fn detach_orphaned_tasks(pool: &DbPool) -> Result<usize, String> {
+    use crate::schema::tasks;
+    use anyagents::schema::sessions;
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let session_ids: Vec<String> = sessions::table
+        .select(sessions::id)
+        .load(&mut conn)
+        .map_err(|e| e.to_string())?;
+
+    diesel::update(
+        tasks::table
+            .filter(tasks::session_id.is_not_null())
+            .filter(diesel::dsl::not(tasks::session_id.eq_any(session_ids))),
+    )
+    .set(tasks::session_id.eq(None::<String>))
+    .execute(&mut conn)
+    .map_err(|e| e.to_string())
+}
+
+/// Run the full cleanup pass (core tables, attachment files and tasks)
+pub fn run_cleanup(pool: &DbPool, attachments_dir: Option<PathBuf>) -> Result<IntegrityReport, String> {
+    let report = maintenance::cleanup_orphans(pool, attachments_dir.as_deref())?;
+    let detached = detach_orphaned_tasks(pool)?;
+    if detached > 0 {
+        log::info!("Detached {} task(s) from deleted sessions", detached);
+    }
+    Ok(report)
+}
+
+/// Spawn the periodic cleanup loop, which also applies the retention policy.
+/// The first pass runs shortly after startup.
+pub fn spawn_cleanup_scheduler(app: tauri::AppHandle, pool: DbPool) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_secs(CLEANUP_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            let pool = pool.clone();
+            let dir = attachments_dir(&app);
+            match tokio::task::spawn_blocking(move || {
+                run_cleanup(&pool, dir)?;
+                let policy = RetentionPolicy::load(&pool);
+                if policy.is_enabled() {
+                    retention::apply_retention(&pool, &policy)?;
+                }
+                Ok::<_, String>(())
+            })
+            .await
+            {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => log::error!("Scheduled cleanup failed: {}", e),
+                Err(e) => log::error!("Scheduled cleanup task panicked: {}", e),
+            }
+        }
+    });
+}
+
+/// Take a backup if the schedule says one is due, then drop the oldest
+fn run_scheduled_backup(pool: &DbPool, dir: &std::path::Path) -> Result<(), String> {
+    let schedule = BackupSchedule::load(pool);
+    let backups = backup::list_backups(dir)?;
+    if !schedule.is_due(backups.first()) {
+        return Ok(());
+    }
+    let info = backup::backup_database(pool, dir)?;
+    log::info!("Backed up database to {}", info.path);
+    let removed = backup::prune_backups(dir, schedule.keep)?;
+    if removed > 0 {
+        log::info!("Removed {} old backup(s)", removed);
+    }
+    Ok(())
+}
+
+/// Spawn the loop that takes scheduled backups (off unless enabled in settings)
+pub fn spawn_backup_scheduler(app: tauri::AppHandle, pool: DbPool) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_secs(BACKUP_CHECK_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            let Ok(dir) = backups_dir(&app) else { continue };
+            let pool = pool.clone();
+            match tokio::task::spawn_blocking(move || run_scheduled_backup(&pool, &dir)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => log::error!("Scheduled backup failed: {}", e),
+                Err(e) => log::error!("Scheduled backup task panicked: {}", e),
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn check_data_integrity(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<IntegrityReport, String> {
+    let (pool, dir) = (state.db_pool.clone(), attachments_dir(&app));
+    tokio::task::spawn_blocking(move || maintenance::check_integrity(&pool, dir.as_deref()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn cleanup_orphaned_data(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<IntegrityReport, String> {
+    let (pool, dir) = (state.db_pool.clone(), attachments_dir(&app));
+    tokio::task::spawn_blocking(move || run_cleanup(&pool, dir))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn check_database_integrity(state: State<'_, AppState>) -> Result<DatabaseCheck, String> {
+    let pool = state.db_pool.clone();
+    tokio::task::spawn_blocking(move || backup::check_database(&pool))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn backup_database(app: tauri::AppHandle, state: State<'_, AppState>) -> Result<BackupInfo, String> {
+    let dir = backups_dir(&app)?;
+    let pool = state.db_pool.clone();
+    tokio::task::spawn_blocking(move || backup::backup_database(&pool, &dir))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn list_database_backups(app: tauri::AppHandle) -> Result<Vec<BackupInfo>, String> {
+    backup::list_backups(&backups_dir(&app)?)
+}
+
+#[tauri::command]
+pub async fn delete_database_backup(app: tauri::AppHandle, file_name: String) -> Result<(), String> {
+    let path = backup::find_backup(&backups_dir(&app)?, &file_name)?;
+    std::fs::remove_file(path).map_err(|e| format!("Failed to delete backup: {}", e))
+}
+
+/// Replace all data with the backup's. The current data is backed up first,
+/// so a restore can itself be undone.
+#[tauri::command]
+pub async fn restore_database(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    file_name: String,
+) -> Result<BackupInfo, String> {
+    let dir = backups_dir(&app)?;
+    let path = backup::find_backup(&dir, &file_name)?;
+    let pool = state.db_pool.clone();
+    tokio::task::spawn_blocking(move || {
+        let before = backup::backup_database(&pool, &dir)?;
+        backup::restore_database(&pool, &path)?;
+        Ok(before)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn get_backup_schedule(state: State<'_, AppState>) -> Result<BackupSchedule, String> {
+    Ok(BackupSchedule::load(&state.db_pool))
+}
+
+#[tauri::command]
+pub async fn set_backup_schedule(state: State<'_, AppState>, schedule: BackupSchedule) -> Result<(), String> {
+    schedule.save(&state.db_pool)
+}
+
+#[tauri::command]
+pub async fn get_retention_policy(state: State<'_, AppState>) -> Result<RetentionPolicy, String> {
+    Ok(RetentionPolicy::load(&state.db_pool))
+}
+
+#[tauri::command]
+pub async fn set_retention_policy(state: State<'_, AppState>, policy: RetentionPolicy) -> Result<(), String> {
+    policy.save(&state.db_pool)
+}
+
+/// Apply the saved retention policy now instead of waiting for the next cleanup
+#[tauri::command]
+pub async fn apply_retention_policy(state: State<'_, AppState>) -> Result<RetentionReport, String> {
+    let pool = state.db_pool.clone();
+    tokio::task::spawn_blocking(move || retention::apply_retention(&pool, &RetentionPolicy::load(&pool)))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn get_storage_stats(state: State<'_, AppState>) -> Result<StorageStats, String> {
+    let pool = state.db_pool.clone();
+    tokio::task::spawn_blocking(move || retention::storage_stats(&pool))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn get_encryption_status() -> Result<EncryptionStatus, String> {
+    encryption::status()
+}
+
+/// Encrypt the database at the next start
+#[tauri::command]
+pub async fn enable_database_encryption() -> Result<EncryptionStatus, String> {
+    encryption::enable_encryption()
+}
+
+/// Decrypt the database at the next start
+#[tauri::command]
+pub async fn disable_database_encryption() -> Result<EncryptionStatus, String> {
+    encryption::disable_encryption()
+}
@@ -0,0 +1,179 @@
+use anyagents::models::{MatrixConfig, NewMatrixConfig, UpdateMatrixConfig};
+use crate::AppState;
+use diesel::prelude::*;
+use tauri::State;
+
+#[tauri::command]
+pub async fn create_matrix_config(
+    state: State<'_, AppState>,
+    homeserver_url: String,
+    access_token: String,
+    agent_id: String,
+    allowed_room_ids: Option<String>,
+) -> Result<MatrixConfig, String> {
+    use anyagents::schema::matrix_configs;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let new_config = NewMatrixConfig {
+            id: uuid::Uuid::new_v4().to_string(),
+            homeserver_url: validate_homeserver_url(&homeserver_url)?,
+            access_token,
+            agent_id,
+            is_active: 0,
+            allowed_room_ids,
+            created_at: chrono::Utc::now().naive_utc(),
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
+
+        diesel::insert_into(matrix_configs::table)
+            .values(&new_config)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        matrix_configs::table
+            .filter(matrix_configs::id.eq(&new_config.id))
+            .first::<MatrixConfig>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_matrix_configs(state: State<'_, AppState>) -> Result<Vec<MatrixConfig>, String> {
+    use anyagents::schema::matrix_configs::dsl::*;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        matrix_configs
+            .load::<MatrixConfig>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_matrix_config(
+    state: State<'_, AppState>,
+    config_id: String,
+) -> Result<MatrixConfig, String> {
+    use anyagents::schema::matrix_configs::dsl::*;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        matrix_configs
+            .filter(id.eq(config_id))
+            .first::<MatrixConfig>(conn)
+            .map_err(|e| format!("Config not found: {}", e))
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn update_matrix_config(
+    state: State<'_, AppState>,
+    config_id: String,
+    new_homeserver_url: Option<String>,
+    new_access_token: Option<String>,
+    new_agent_id: Option<String>,
+    new_is_active: Option<i32>,
+    new_allowed_room_ids: Option<String>,
+) -> Result<MatrixConfig, String> {
+    use anyagents::schema::matrix_configs::dsl::*;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let update = UpdateMatrixConfig {
+            homeserver_url: new_homeserver_url.as_deref().map(validate_homeserver_url).transpose()?,
+            access_token: new_access_token,
+            agent_id: new_agent_id,
+            is_active: new_is_active,
+            allowed_room_ids: new_allowed_room_ids,
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
+
+        diesel::update(matrix_configs.filter(id.eq(&config_id)))
+            .set(&update)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        matrix_configs
+            .filter(id.eq(&config_id))
+            .first::<MatrixConfig>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn delete_matrix_config(
+    state: State<'_, AppState>,
+    config_id: String,
+) -> Result<(), String> {
+    use anyagents::schema::{connector_sessions, matrix_configs};
+
+    let _ = state.matrix_manager.stop_bot(&config_id).await;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        diesel::delete(
+            connector_sessions::table
+                .filter(connector_sessions::platform.eq("matrix"))
+                .filter(connector_sessions::config_id.eq(&config_id)),
+        )
+        .execute(conn)
+        .map_err(|e| e.to_string())?;
+        diesel::delete(matrix_configs::table.filter(matrix_configs::id.eq(&config_id)))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    })
+    .await
+}
+
+/// e.g. `https://matrix.org`; the client API lives under it
+fn validate_homeserver_url(url: &str) -> Result<String, String> {
+    let url = url.trim().trim_end_matches('/');
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "https" | "http") => Ok(url.to_string()),
+        _ => Err(format!("Invalid homeserver URL: {}", url)),
+    }
+}
+
+#[tauri::command]
+pub async fn start_matrix_bot(state: State<'_, AppState>, config_id: String) -> Result<(), String> {
+    state.matrix_manager.start_bot(&config_id).await
+}
+
+#[tauri::command]
+pub async fn stop_matrix_bot(state: State<'_, AppState>, config_id: String) -> Result<(), String> {
+    state.matrix_manager.stop_bot(&config_id).await
+}
+
+#[derive(serde::Serialize)]
+pub struct MatrixBotStatus {
+    pub config_id: String,
+    pub is_running: bool,
+}
+
+#[tauri::command]
+pub async fn get_matrix_bot_status(
+    state: State<'_, AppState>,
+    config_id: String,
+) -> Result<MatrixBotStatus, String> {
+    let is_running = state.matrix_manager.is_bot_running(&config_id).await;
+    Ok(MatrixBotStatus { config_id, is_running })
+}
+
+#[tauri::command]
+pub async fn get_running_matrix_bots(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    Ok(state.matrix_manager.get_running_bot_ids().await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_homeserver_url() {
+        assert_eq!(validate_homeserver_url(" https://matrix.org/ ").unwrap(), "https://matrix.org");
+        assert!(validate_homeserver_url("matrix.org").is_err());
+        assert!(validate_homeserver_url("ftp://matrix.org").is_err());
+    }
+}
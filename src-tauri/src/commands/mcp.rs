@@ -1,5 +1,13 @@
 use anyagents::models::mcp_server::{McpServer, McpServerDto, McpServerUpdateDto, McpTemplateDto, NewMcpServer};
+use anyagents::models::agent_version;
 use anyagents::models::{Agent, AgentDto};
+use anyagents::mcp::types::McpTool;
+use anyagents::mcp::oauth::{self, DeviceAuthorization, OAuthConfig};
+use anyagents::mcp::secrets::{self, validate_secret_name};
+use anyagents::mcp::McpClient;
+use anyagents::tools::registry::GROUP_MCP;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
 use crate::AppState;
 use diesel::prelude::*;
 use tauri::State;
@@ -8,12 +16,14 @@ use tauri::State;
 pub async fn get_mcp_servers(state: State<'_, AppState>) -> Result<Vec<McpServerDto>, String> {
     use anyagents::schema::mcp_servers::dsl::*;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-    let results = mcp_servers
-        .load::<McpServer>(&mut conn)
-        .map_err(|e| e.to_string())?;
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let results = mcp_servers
+            .load::<McpServer>(conn)
+            .map_err(|e| e.to_string())?;
 
-    Ok(results.into_iter().map(|s| s.into_dto()).collect())
+        Ok(results.into_iter().map(|s| s.into_dto()).collect())
+    })
+    .await
 }
 
 #[tauri::command]
@@ -24,40 +34,47 @@ pub async fn create_mcp_server(
 ) -> Result<McpServerDto, String> {
     use anyagents::schema::mcp_servers;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    let new_id = uuid::Uuid::new_v4().to_string();
-    let name = data.name.unwrap_or_else(|| "New MCP Server".to_string());
-    let server_type = data.server_type.unwrap_or_else(|| "stdio".to_string());
-    
-    let args_json = data.args.map(|a| serde_json::to_string(&a).unwrap_or_default());
-    let env_json = data.env.map(|e| serde_json::to_string(&e).unwrap_or_default());
-
-    let new_server = NewMcpServer {
-        id: new_id.clone(),
-        name,
-        server_type,
-        command: data.command,
-        args: args_json,
-        env: env_json,
-        url: data.url,
-        is_enabled: if data.is_enabled.unwrap_or(true) { 1 } else { 0 },
-        template_id,
-        created_at: chrono::Utc::now().timestamp(),
-        updated_at: chrono::Utc::now().timestamp(),
-    };
-
-    diesel::insert_into(mcp_servers::table)
-        .values(&new_server)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
-
-    let server: McpServer = mcp_servers::table
-        .filter(mcp_servers::id.eq(new_id))
-        .first::<McpServer>(&mut conn)
-        .map_err(|e| e.to_string())?;
-
-    Ok(server.into_dto())
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let new_id = uuid::Uuid::new_v4().to_string();
+        let name = data.name.unwrap_or_else(|| "New MCP Server".to_string());
+        let server_type = data.server_type.unwrap_or_else(|| "stdio".to_string());
+        validate_server_type(&server_type)?;
+
+        let args_json = data.args.map(|a| serde_json::to_string(&a).unwrap_or_default());
+        let env_json = data.env.map(|e| serde_json::to_string(&e).unwrap_or_default());
+        let headers_json = data.headers.map(|h| serde_json::to_string(&h).unwrap_or_default());
+        let oauth_json = data.oauth.map(|o| serde_json::to_string(&o).unwrap_or_default());
+
+        let new_server = NewMcpServer {
+            id: new_id.clone(),
+            name,
+            server_type,
+            command: data.command,
+            args: args_json,
+            env: env_json,
+            url: data.url,
+            is_enabled: if data.is_enabled.unwrap_or(true) { 1 } else { 0 },
+            template_id,
+            created_at: chrono::Utc::now().timestamp(),
+            updated_at: chrono::Utc::now().timestamp(),
+            headers: headers_json,
+            secret_names: None,
+            oauth: oauth_json,
+        };
+
+        diesel::insert_into(mcp_servers::table)
+            .values(&new_server)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        let server: McpServer = mcp_servers::table
+            .filter(mcp_servers::id.eq(new_id))
+            .first::<McpServer>(conn)
+            .map_err(|e| e.to_string())?;
+
+        Ok(server.into_dto())
+    })
+    .await
 }
 
 #[tauri::command]
@@ -66,67 +83,223 @@ pub async fn update_mcp_server(
     id: String,
     data: McpServerUpdateDto,
 ) -> Result<McpServerDto, String> {
-    use anyagents::schema::mcp_servers::dsl::{mcp_servers, id as id_col, name, server_type, command, args, env, url, is_enabled, updated_at};
+    use anyagents::schema::mcp_servers::dsl::{mcp_servers, id as id_col, name, server_type, command, args, env, url, headers, oauth, is_enabled, updated_at};
+
+    let server = anyagents::database::blocking(&state.db_pool, move |conn| {
+        let mut server = mcp_servers
+            .filter(id_col.eq(&id))
+            .first::<McpServer>(conn)
+            .map_err(|_| "MCP Server not found".to_string())?;
+
+        if let Some(n) = data.name {
+            server.name = n;
+        }
+        if let Some(t) = data.server_type {
+            validate_server_type(&t)?;
+            server.server_type = t;
+        }
+        if let Some(c) = data.command {
+            server.command = Some(c);
+        }
+        if let Some(a) = data.args {
+            server.args = Some(serde_json::to_string(&a).unwrap_or_default());
+        }
+        if let Some(e) = data.env {
+            server.env = Some(serde_json::to_string(&e).unwrap_or_default());
+        }
+        if let Some(u) = data.url {
+            server.url = Some(u);
+        }
+        if let Some(h) = data.headers {
+            server.headers = Some(serde_json::to_string(&h).unwrap_or_default());
+        }
+        if let Some(o) = data.oauth {
+            server.oauth = Some(serde_json::to_string(&o).unwrap_or_default());
+        }
+        if let Some(e) = data.is_enabled {
+            server.is_enabled = if e { 1 } else { 0 };
+        }
+
+        server.updated_at = chrono::Utc::now().timestamp();
+
+        diesel::update(mcp_servers.filter(id_col.eq(&id)))
+            .set((
+                name.eq(&server.name),
+                server_type.eq(&server.server_type),
+                command.eq(&server.command),
+                args.eq(&server.args),
+                env.eq(&server.env),
+                url.eq(&server.url),
+                headers.eq(&server.headers),
+                oauth.eq(&server.oauth),
+                is_enabled.eq(&server.is_enabled),
+                updated_at.eq(&server.updated_at),
+            ))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+        Ok(server)
+    })
+    .await?;
+
+    // Agents running with this server reconnect and see its current tools
+    anyagents::agents::refresh_live_agents(&state.db_pool, GROUP_MCP).await;
+    Ok(server.into_dto())
+}
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+fn validate_server_type(value: &str) -> Result<(), String> {
+    match value {
+        "stdio" | "sse" | "http" => Ok(()),
+        other => Err(format!("Unknown MCP server type '{}' (expected stdio, sse or http)", other)),
+    }
+}
 
-    let mut server = mcp_servers
-        .filter(id_col.eq(&id))
-        .first::<McpServer>(&mut conn)
-        .map_err(|_| "MCP Server not found".to_string())?;
+async fn load_server(state: &State<'_, AppState>, server_id: &str) -> Result<McpServer, String> {
+    use anyagents::schema::mcp_servers::dsl::{mcp_servers, id as id_col};
 
-    if let Some(n) = data.name {
-        server.name = n;
-    }
-    if let Some(t) = data.server_type {
-        server.server_type = t;
-    }
-    if let Some(c) = data.command {
-        server.command = Some(c);
-    }
-    if let Some(a) = data.args {
-        server.args = Some(serde_json::to_string(&a).unwrap_or_default());
-    }
-    if let Some(e) = data.env {
-        server.env = Some(serde_json::to_string(&e).unwrap_or_default());
-    }
-    if let Some(u) = data.url {
-        server.url = Some(u);
-    }
-    if let Some(e) = data.is_enabled {
-        server.is_enabled = if e { 1 } else { 0 };
+    let server_id = server_id.to_string();
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        mcp_servers
+            .filter(id_col.eq(&server_id))
+            .first::<McpServer>(conn)
+            .map_err(|_| "MCP Server not found".to_string())
+    })
+    .await
+}
+
+/// Store a secret in the keyring and remember its name on the server
+async fn store_secret(pool: &anyagents::database::DbPool, server: &McpServer, name: &str, value: &str) -> Result<(), String> {
+    use anyagents::schema::mcp_servers::dsl::{mcp_servers, id as id_col, secret_names};
+
+    validate_secret_name(name)?;
+    secrets::set(&secrets::KEYRING, &server.id, name, value)?;
+
+    let mut names = server.secret_name_list();
+    if !names.iter().any(|n| n == name) {
+        names.push(name.to_string());
+        let server_id = server.id.clone();
+        anyagents::database::blocking(pool, move |conn| {
+            diesel::update(mcp_servers.filter(id_col.eq(&server_id)))
+                .set(secret_names.eq(serde_json::to_string(&names).ok()))
+                .execute(conn)
+                .map_err(|e| e.to_string())
+        })
+        .await?;
     }
+    Ok(())
+}
 
-    server.updated_at = chrono::Utc::now().timestamp();
-
-    diesel::update(mcp_servers.filter(id_col.eq(&id)))
-        .set((
-            name.eq(&server.name),
-            server_type.eq(&server.server_type),
-            command.eq(&server.command),
-            args.eq(&server.args),
-            env.eq(&server.env),
-            url.eq(&server.url),
-            is_enabled.eq(&server.is_enabled),
-            updated_at.eq(&server.updated_at),
-        ))
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+/// Set a secret referenced from the server config as `${SECRET:<name>}`
+#[tauri::command]
+pub async fn set_mcp_secret(
+    state: State<'_, AppState>,
+    server_id: String,
+    name: String,
+    value: String,
+) -> Result<McpServerDto, String> {
+    let server = load_server(&state, &server_id).await?;
+    store_secret(&state.db_pool, &server, &name, &value).await?;
+    Ok(load_server(&state, &server_id).await?.into_dto())
+}
 
-    Ok(server.into_dto())
+#[tauri::command]
+pub async fn delete_mcp_secret(
+    state: State<'_, AppState>,
+    server_id: String,
+    name: String,
+) -> Result<McpServerDto, String> {
+    use anyagents::schema::mcp_servers::dsl::{mcp_servers, id as id_col, secret_names};
+
+    let server = load_server(&state, &server_id).await?;
+    secrets::delete(&secrets::KEYRING, &server_id, &name)?;
+
+    let names: Vec<String> = server.secret_name_list().into_iter().filter(|n| n != &name).collect();
+    let updated = server_id.clone();
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        diesel::update(mcp_servers.filter(id_col.eq(&updated)))
+            .set(secret_names.eq(serde_json::to_string(&names).ok()))
+            .execute(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await?;
+
+    Ok(load_server(&state, &server_id).await?.into_dto())
+}
+
+#[derive(Serialize, Clone)]
+struct OAuthFinished {
+    server_id: String,
+    error: Option<String>,
 }
 
+/// Start the server's OAuth device flow. Returns the code to show the user;
+/// the token is saved as a secret in the background, followed by an
+/// `mcp:oauth_finished` event.
 #[tauri::command]
-pub async fn delete_mcp_server(state: State<'_, AppState>, id: String) -> Result<(), String> {
-    use anyagents::schema::mcp_servers::dsl::{mcp_servers, id as id_col};
+pub async fn start_mcp_oauth(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    server_id: String,
+) -> Result<DeviceAuthorization, String> {
+    let server = load_server(&state, &server_id).await?;
+    let config = server
+        .oauth_config()
+        .ok_or("This MCP server has no OAuth configuration")?;
+    let authorization = oauth::start_device_flow(&config).await?;
+
+    let pool = state.db_pool.clone();
+    let pending = authorization.clone();
+    tauri::async_runtime::spawn(async move {
+        let result = match oauth::poll_for_token(&config, &pending).await {
+            Ok(token) => store_secret(&pool, &server, &config.token_secret, &token).await,
+            Err(e) => Err(e),
+        };
+        let _ = app.emit(
+            "mcp:oauth_finished",
+            OAuthFinished {
+                server_id: server.id.clone(),
+                error: result.err(),
+            },
+        );
+    });
+
+    Ok(authorization)
+}
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+/// Connect to a server with its saved settings and list its tools
+#[tauri::command]
+pub async fn test_mcp_server(state: State<'_, AppState>, id: String) -> Result<Vec<McpTool>, String> {
+    let server = load_server(&state, &id).await?;
 
-    diesel::delete(mcp_servers.filter(id_col.eq(&id)))
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+    let client = McpClient::from_server(&server).await?;
+    let result = match client.initialize().await {
+        Ok(()) => client.list_tools().await,
+        Err(e) => Err(e),
+    };
+    client.shutdown().await;
+    result
+}
 
-    Ok(())
+#[tauri::command]
+pub async fn delete_mcp_server(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    use anyagents::schema::mcp_servers::dsl::{mcp_servers, id as id_col};
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        // Don't leave the server's secrets behind in the keyring
+        if let Ok(server) = mcp_servers.filter(id_col.eq(&id)).first::<McpServer>(conn) {
+            for secret in server.secret_name_list() {
+                if let Err(e) = secrets::delete(&secrets::KEYRING, &id, &secret) {
+                    log::warn!("{}", e);
+                }
+            }
+        }
+
+        diesel::delete(mcp_servers.filter(id_col.eq(&id)))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    })
+    .await
 }
 
 #[tauri::command]
@@ -139,8 +312,10 @@ pub async fn get_mcp_templates() -> Result<Vec<McpTemplateDto>, String> {
             server_type: "stdio".to_string(),
             command: Some("npx".to_string()),
             args: Some(vec!["-y".to_string(), "@modelcontextprotocol/server-brave-search".to_string()]),
-            env: Some(serde_json::json!({ "BRAVE_API_KEY": "YOUR_API_KEY" })),
+            env: Some(serde_json::json!({ "BRAVE_API_KEY": "${SECRET:brave_api_key}" })),
             url: None,
+            headers: None,
+            oauth: None,
         },
         McpTemplateDto {
             id: "filesystem".to_string(),
@@ -151,6 +326,8 @@ pub async fn get_mcp_templates() -> Result<Vec<McpTemplateDto>, String> {
             args: Some(vec!["-y".to_string(), "@modelcontextprotocol/server-filesystem".to_string(), "/path/to/allow".to_string()]),
             env: None,
             url: None,
+            headers: None,
+            oauth: None,
         },
         McpTemplateDto {
             id: "memory".to_string(),
@@ -161,6 +338,8 @@ pub async fn get_mcp_templates() -> Result<Vec<McpTemplateDto>, String> {
             args: Some(vec!["-y".to_string(), "@modelcontextprotocol/server-memory".to_string()]),
             env: None,
             url: None,
+            headers: None,
+            oauth: None,
         },
         McpTemplateDto {
             id: "fetch".to_string(),
@@ -171,6 +350,20 @@ pub async fn get_mcp_templates() -> Result<Vec<McpTemplateDto>, String> {
             args: Some(vec!["mcp-server-fetch".to_string()]),
             env: None,
             url: None,
+            headers: None,
+            oauth: None,
+        },
+        McpTemplateDto {
+            id: "github".to_string(),
+            name: "GitHub".to_string(),
+            description: "Repositories, issues and pull requests (sign in with GitHub)".to_string(),
+            server_type: "http".to_string(),
+            command: None,
+            args: None,
+            env: None,
+            url: Some("https://api.githubcopilot.com/mcp/".to_string()),
+            headers: Some(serde_json::json!({ "Authorization": "Bearer ${SECRET:oauth_token}" })),
+            oauth: Some(OAuthConfig::github("YOUR_OAUTH_APP_CLIENT_ID", &["repo", "read:org"])),
         },
     ];
 
@@ -185,39 +378,48 @@ pub async fn add_mcp_to_agent(
 ) -> Result<AgentDto, String> {
     use anyagents::schema::agents::dsl::{agents, id as agent_id_col, mcp_servers as mcp_servers_col};
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    // Verify MCP server exists
-    use anyagents::schema::mcp_servers::dsl::{mcp_servers as mcp_servers_dsl, id as mcp_id_col};
-    let _ = mcp_servers_dsl
-        .filter(mcp_id_col.eq(&mcp_server_id))
-        .first::<McpServer>(&mut conn)
-        .map_err(|_| "MCP Server not found".to_string())?;
-
-    // Get Agent
-    let mut agent = agents
-        .filter(agent_id_col.eq(&agent_id))
-        .first::<Agent>(&mut conn)
-        .map_err(|_| "Agent not found".to_string())?;
-
-    let mut current_servers: Vec<String> = agent
-        .mcp_servers
-        .clone()
-        .map(|s| s.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
-        .unwrap_or_default();
-
-    if !current_servers.contains(&mcp_server_id) {
-        current_servers.push(mcp_server_id);
+    let pool = state.db_pool.clone();
+    let updated = agent_id.clone();
+    let agent = anyagents::database::blocking(&state.db_pool, move |conn| {
+        // Verify MCP server exists
+        use anyagents::schema::mcp_servers::dsl::{mcp_servers as mcp_servers_dsl, id as mcp_id_col};
+        let _ = mcp_servers_dsl
+            .filter(mcp_id_col.eq(&mcp_server_id))
+            .first::<McpServer>(conn)
+            .map_err(|_| "MCP Server not found".to_string())?;
+
+        // Get Agent
+        let mut agent = agents
+            .filter(agent_id_col.eq(&updated))
+            .first::<Agent>(conn)
+            .map_err(|_| "Agent not found".to_string())?;
+        agent_version::record(&pool, &agent, None)?;
+
+        let mut current_servers: Vec<String> = agent
+            .mcp_servers
+            .clone()
+            .map(|s| s.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default();
+
+        if !current_servers.contains(&mcp_server_id) {
+            current_servers.push(mcp_server_id);
+        }
+
+        let new_servers_str = current_servers.join(", ");
+        agent.mcp_servers = Some(new_servers_str.clone());
+
+        diesel::update(agents.filter(agent_id_col.eq(&updated)))
+            .set(mcp_servers_col.eq(new_servers_str))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+        agent_version::record(&pool, &agent, None)?;
+        Ok(agent)
+    })
+    .await?;
+
+    if let Err(e) = anyagents::agents::refresh_agent_tools(&state.db_pool, &agent_id, GROUP_MCP).await {
+        log::warn!("Failed to reload MCP tools of agent {}: {}", agent_id, e);
     }
-
-    let new_servers_str = current_servers.join(", ");
-    agent.mcp_servers = Some(new_servers_str.clone());
-
-    diesel::update(agents.filter(agent_id_col.eq(&agent_id)))
-        .set(mcp_servers_col.eq(new_servers_str))
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
-
     Ok(agent.into_dto())
 }
 
@@ -229,31 +431,40 @@ pub async fn remove_mcp_from_agent(
 ) -> Result<AgentDto, String> {
     use anyagents::schema::agents::dsl::{agents, id as agent_id_col, mcp_servers as mcp_servers_col};
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    // Get Agent
-    let mut agent = agents
-        .filter(agent_id_col.eq(&agent_id))
-        .first::<Agent>(&mut conn)
-        .map_err(|_| "Agent not found".to_string())?;
-
-    let mut current_servers: Vec<String> = agent
-        .mcp_servers
-        .clone()
-        .map(|s| s.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
-        .unwrap_or_default();
-
-    if let Some(pos) = current_servers.iter().position(|x| *x == mcp_server_id) {
-        current_servers.remove(pos);
+    let pool = state.db_pool.clone();
+    let updated = agent_id.clone();
+    let agent = anyagents::database::blocking(&state.db_pool, move |conn| {
+        // Get Agent
+        let mut agent = agents
+            .filter(agent_id_col.eq(&updated))
+            .first::<Agent>(conn)
+            .map_err(|_| "Agent not found".to_string())?;
+        agent_version::record(&pool, &agent, None)?;
+
+        let mut current_servers: Vec<String> = agent
+            .mcp_servers
+            .clone()
+            .map(|s| s.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default();
+
+        if let Some(pos) = current_servers.iter().position(|x| *x == mcp_server_id) {
+            current_servers.remove(pos);
+        }
+
+        let new_servers_str = current_servers.join(", ");
+        agent.mcp_servers = Some(new_servers_str.clone());
+
+        diesel::update(agents.filter(agent_id_col.eq(&updated)))
+            .set(mcp_servers_col.eq(new_servers_str))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+        agent_version::record(&pool, &agent, None)?;
+        Ok(agent)
+    })
+    .await?;
+
+    if let Err(e) = anyagents::agents::refresh_agent_tools(&state.db_pool, &agent_id, GROUP_MCP).await {
+        log::warn!("Failed to reload MCP tools of agent {}: {}", agent_id, e);
     }
-
-    let new_servers_str = current_servers.join(", ");
-    agent.mcp_servers = Some(new_servers_str.clone());
-
-    diesel::update(agents.filter(agent_id_col.eq(&agent_id)))
-        .set(mcp_servers_col.eq(new_servers_str))
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
-
     Ok(agent.into_dto())
 }
@@ -1,19 +1,27 @@
 pub mod agents;
+pub mod discord;
+pub mod email;
 pub mod mail;
+pub mod matrix;
 pub mod mcp;
 pub mod pages;
 pub mod sessions;
 pub mod skills;
+pub mod slack;
 pub mod telegram;
 pub mod window;
 
 // Re-export commands for easy registration
 pub use agents::*;
+pub use discord::*;
+pub use email::*;
 pub use mail::*;
+pub use matrix::*;
 pub use mcp::*;
 pub use pages::*;
 pub use sessions::*;
 pub use skills::*;
+pub use slack::*;
 pub use telegram::*;
 pub mod transcribe;
 pub use transcribe::*;
@@ -26,3 +34,45 @@ pub use voice::*;
 
 pub mod settings;
 pub use settings::*;
+
+pub mod maintenance;
+pub use maintenance::*;
+
+pub mod health;
+pub use health::*;
+
+pub mod checkpoints;
+pub use checkpoints::*;
+
+pub mod plans;
+pub use plans::*;
+
+pub mod bus;
+pub use bus::*;
+
+pub mod triggers;
+pub use triggers::*;
+
+pub mod webhooks;
+pub use webhooks::*;
+
+pub mod permissions;
+pub use permissions::*;
+
+pub mod secrets;
+pub use secrets::*;
+
+pub mod search;
+pub use search::*;
+
+pub mod prompts;
+pub use prompts::*;
+
+pub mod jobs;
+pub use jobs::*;
+
+pub mod usage;
+pub use usage::*;
+
+pub mod notifications;
+pub use notifications::*;
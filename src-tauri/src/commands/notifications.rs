@@ -0,0 +1,70 @@
+use crate::AppState;
+use anyagents::models::notification::{self, Notification};
+use anyagents::notifications::NotificationPreferences;
+use tauri::State;
+
+const DEFAULT_LIMIT: i64 = 100;
+
+/// The inbox, newest first
+#[tauri::command]
+pub async fn get_notifications(
+    state: State<'_, AppState>,
+    unread_only: Option<bool>,
+    limit: Option<i64>,
+) -> Result<Vec<Notification>, String> {
+    notification::list_notifications(&state.db_pool, unread_only.unwrap_or(false), limit.unwrap_or(DEFAULT_LIMIT))
+}
+
+#[tauri::command]
+pub async fn get_unread_notification_count(state: State<'_, AppState>) -> Result<i64, String> {
+    notification::unread_count(&state.db_pool)
+}
+
+#[tauri::command]
+pub async fn mark_notifications_read(state: State<'_, AppState>, ids: Vec<String>, read: bool) -> Result<usize, String> {
+    notification::set_read(&state.db_pool, &ids, read)
+}
+
+#[tauri::command]
+pub async fn mark_all_notifications_read(state: State<'_, AppState>) -> Result<usize, String> {
+    notification::mark_all_read(&state.db_pool)
+}
+
+/// Delete the notifications already read
+#[tauri::command]
+pub async fn clear_read_notifications(state: State<'_, AppState>) -> Result<usize, String> {
+    notification::clear_read(&state.db_pool)
+}
+
+/// Rules per event, muted agents and sessions, and where Telegram and email
+/// notifications go
+#[tauri::command]
+pub async fn get_notification_preferences(state: State<'_, AppState>) -> Result<NotificationPreferences, String> {
+    Ok(NotificationPreferences::load(&state.db_pool))
+}
+
+#[tauri::command]
+pub async fn set_notification_preferences(
+    state: State<'_, AppState>,
+    preferences: NotificationPreferences,
+) -> Result<(), String> {
+    preferences.save(&state.db_pool)
+}
+
+#[tauri::command]
+pub async fn mute_agent_notifications(state: State<'_, AppState>, agent_id: String, muted: bool) -> Result<(), String> {
+    let mut preferences = NotificationPreferences::load(&state.db_pool);
+    preferences.set_agent_muted(&agent_id, muted);
+    preferences.save(&state.db_pool)
+}
+
+#[tauri::command]
+pub async fn mute_session_notifications(
+    state: State<'_, AppState>,
+    session_id: String,
+    muted: bool,
+) -> Result<(), String> {
+    let mut preferences = NotificationPreferences::load(&state.db_pool);
+    preferences.set_session_muted(&session_id, muted);
+    preferences.save(&state.db_pool)
+}
@@ -1,10 +1,12 @@
-use anyagents::models::{
-    Attachment, Block, NewAttachment, NewBlock, NewPage, Page, UpdateBlock, UpdatePage,
-};
+use anyagents::models::{Attachment, Block, NewBlock, NewPage, Page, UpdatePage};
+use anyagents::pages::attachments::{self as page_attachments, AttachmentGc, AttachmentQuota, AttachmentUsage};
+use anyagents::pages::collab::{self, BlockChange, BlockEdit, BlockWrite};
+use anyagents::pages::PageSync;
+use crate::events::TauriAppObserver;
 use crate::AppState;
 use diesel::prelude::*;
-use serde::Deserialize;
-use tauri::State;
+use serde::Serialize;
+use tauri::{AppHandle, Runtime, State};
 
 // ============================================================================
 // PAGE COMMANDS
@@ -19,33 +21,34 @@ pub async fn create_page(
 ) -> Result<Page, String> {
     use anyagents::schema::pages;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    let new_page = NewPage {
-        id: uuid::Uuid::new_v4().to_string(),
-        title,
-        type_: page_type,
-        parent_id,
-        day_date: None,
-        icon: None,
-        cover_image: None,
-        is_archived: 0,
-        is_published: 0,
-        created_at: chrono::Utc::now().naive_utc(),
-        updated_at: chrono::Utc::now().naive_utc(),
-    };
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let new_page = NewPage {
+            id: uuid::Uuid::new_v4().to_string(),
+            title,
+            type_: page_type,
+            parent_id,
+            day_date: None,
+            icon: None,
+            cover_image: None,
+            is_archived: 0,
+            is_published: 0,
+            created_at: chrono::Utc::now().naive_utc(),
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
 
-    diesel::insert_into(pages::table)
-        .values(&new_page)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+        diesel::insert_into(pages::table)
+            .values(&new_page)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
 
-    let created_id = new_page.id.clone();
+        let created_id = new_page.id.clone();
 
-    pages::table
-        .filter(pages::id.eq(created_id))
-        .first::<Page>(&mut conn)
-        .map_err(|e| e.to_string())
+        pages::table
+            .filter(pages::id.eq(created_id))
+            .first::<Page>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 #[tauri::command]
@@ -56,36 +59,39 @@ pub async fn get_pages(
 ) -> Result<Vec<Page>, String> {
     use anyagents::schema::pages::dsl::{is_archived, pages, parent_id, updated_at};
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let mut query = pages.into_boxed();
 
-    let mut query = pages.into_boxed();
-
-    if let Some(pid) = parent_id_param {
-        query = query.filter(parent_id.eq(pid));
-    } else {
-        query = query.filter(parent_id.is_null());
-    }
+        if let Some(pid) = parent_id_param {
+            query = query.filter(parent_id.eq(pid));
+        } else {
+            query = query.filter(parent_id.is_null());
+        }
 
-    if let Some(arch) = archived {
-        let arch_val = if arch { 1 } else { 0 };
-        query = query.filter(is_archived.eq(arch_val));
-    }
+        if let Some(arch) = archived {
+            let arch_val = if arch { 1 } else { 0 };
+            query = query.filter(is_archived.eq(arch_val));
+        }
 
-    query
-        .order(updated_at.desc())
-        .load::<Page>(&mut conn)
-        .map_err(|e| e.to_string())
+        query
+            .order(updated_at.desc())
+            .load::<Page>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 #[tauri::command]
 pub async fn get_page(state: State<'_, AppState>, page_id: String) -> Result<Page, String> {
     use anyagents::schema::pages::dsl::*;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-    pages
-        .filter(id.eq(page_id))
-        .first::<Page>(&mut conn)
-        .map_err(|e| format!("Page not found: {}", e))
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        pages
+            .filter(id.eq(page_id))
+            .first::<Page>(conn)
+            .map_err(|e| format!("Page not found: {}", e))
+    })
+    .await
 }
 
 #[tauri::command]
@@ -99,93 +105,98 @@ pub async fn update_page(
 ) -> Result<Page, String> {
     use anyagents::schema::pages::dsl::{id, pages};
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    let update = UpdatePage {
-        title: title_param,
-        type_: None,
-        icon: icon_param,
-        cover_image: cover_image_param,
-        is_archived: None,
-        is_published: is_published_param.map(|v| if v { 1 } else { 0 }),
-        updated_at: chrono::Utc::now().naive_utc(),
-    };
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let update = UpdatePage {
+            title: title_param,
+            type_: None,
+            icon: icon_param,
+            cover_image: cover_image_param,
+            is_archived: None,
+            is_published: is_published_param.map(|v| if v { 1 } else { 0 }),
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
 
-    diesel::update(pages.filter(id.eq(&page_id)))
-        .set(&update)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+        diesel::update(pages.filter(id.eq(&page_id)))
+            .set(&update)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
 
-    pages
-        .filter(id.eq(&page_id))
-        .first::<Page>(&mut conn)
-        .map_err(|e| e.to_string())
+        pages
+            .filter(id.eq(&page_id))
+            .first::<Page>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 #[tauri::command]
 pub async fn archive_page(state: State<'_, AppState>, page_id: String) -> Result<Page, String> {
     use anyagents::schema::pages::dsl::*;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    let update = UpdatePage {
-        title: None,
-        type_: None,
-        icon: None,
-        cover_image: None,
-        is_archived: Some(1),
-        is_published: None,
-        updated_at: chrono::Utc::now().naive_utc(),
-    };
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let update = UpdatePage {
+            title: None,
+            type_: None,
+            icon: None,
+            cover_image: None,
+            is_archived: Some(1),
+            is_published: None,
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
 
-    diesel::update(pages.filter(id.eq(&page_id)))
-        .set(&update)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+        diesel::update(pages.filter(id.eq(&page_id)))
+            .set(&update)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
 
-    pages
-        .filter(id.eq(&page_id))
-        .first::<Page>(&mut conn)
-        .map_err(|e| e.to_string())
+        pages
+            .filter(id.eq(&page_id))
+            .first::<Page>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 #[tauri::command]
 pub async fn restore_page(state: State<'_, AppState>, page_id: String) -> Result<Page, String> {
     use anyagents::schema::pages::dsl::*;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    let update = UpdatePage {
-        title: None,
-        type_: None,
-        icon: None,
-        cover_image: None,
-        is_archived: Some(0),
-        is_published: None,
-        updated_at: chrono::Utc::now().naive_utc(),
-    };
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let update = UpdatePage {
+            title: None,
+            type_: None,
+            icon: None,
+            cover_image: None,
+            is_archived: Some(0),
+            is_published: None,
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
 
-    diesel::update(pages.filter(id.eq(&page_id)))
-        .set(&update)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+        diesel::update(pages.filter(id.eq(&page_id)))
+            .set(&update)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
 
-    pages
-        .filter(id.eq(&page_id))
-        .first::<Page>(&mut conn)
-        .map_err(|e| e.to_string())
+        pages
+            .filter(id.eq(&page_id))
+            .first::<Page>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 #[tauri::command]
 pub async fn delete_page(state: State<'_, AppState>, page_id: String) -> Result<(), String> {
     use anyagents::schema::pages::dsl::*;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-    diesel::delete(pages.filter(id.eq(page_id)))
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        diesel::delete(pages.filter(id.eq(page_id)))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
 
-    Ok(())
+        Ok(())
+    })
+    .await
 }
 
 // ============================================================================
@@ -199,16 +210,24 @@ pub async fn get_page_blocks(
 ) -> Result<Vec<Block>, String> {
     use anyagents::schema::blocks::dsl::*;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-    blocks
-        .filter(page_id.eq(page_id_param))
-        .order(order_index.asc())
-        .load::<Block>(&mut conn)
-        .map_err(|e| e.to_string())
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        blocks
+            .filter(page_id.eq(page_id_param))
+            .order(order_index.asc())
+            .load::<Block>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+/// Tell every window with the page open
+fn announce<R: Runtime>(app: &AppHandle<R>, changes: &[BlockChange]) {
+    collab::emit_changes(&TauriAppObserver { app: app.clone() }, changes);
 }
 
 #[tauri::command]
-pub async fn create_block(
+pub async fn create_block<R: Runtime>(
+    app: AppHandle<R>,
     state: State<'_, AppState>,
     page_id_param: String,
     block_type: String,
@@ -218,130 +237,150 @@ pub async fn create_block(
     use anyagents::schema::blocks;
     use anyagents::schema::blocks::dsl::{blocks as blocks_table, id as block_id, order_index, page_id};
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    // If order_index not provided, find max and add 1
-    let computed_order = if let Some(idx) = order_index_param {
-        idx
-    } else {
-        let max_order: Option<i32> = blocks_table
-            .filter(page_id.eq(&page_id_param))
-            .select(diesel::dsl::max(order_index))
-            .first(&mut conn)
-            .map_err(|e| e.to_string())?;
-        max_order.unwrap_or(0) + 1
-    };
+    let block = anyagents::database::blocking(&state.db_pool, move |conn| {
+        // If order_index not provided, find max and add 1
+        let computed_order = if let Some(idx) = order_index_param {
+            idx
+        } else {
+            let max_order: Option<i32> = blocks_table
+                .filter(page_id.eq(&page_id_param))
+                .select(diesel::dsl::max(order_index))
+                .first(conn)
+                .map_err(|e| e.to_string())?;
+            max_order.unwrap_or(0) + 1
+        };
 
-    let new_block = NewBlock {
-        id: uuid::Uuid::new_v4().to_string(),
-        page_id: page_id_param,
-        type_: block_type,
-        content_json: content_json_param,
-        order_index: computed_order,
-        created_at: chrono::Utc::now().naive_utc(),
-        updated_at: chrono::Utc::now().naive_utc(),
-    };
+        let new_block = NewBlock {
+            id: uuid::Uuid::new_v4().to_string(),
+            page_id: page_id_param,
+            type_: block_type,
+            content_json: content_json_param,
+            order_index: computed_order,
+            created_at: chrono::Utc::now().naive_utc(),
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
 
-    diesel::insert_into(blocks::table)
-        .values(&new_block)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+        diesel::insert_into(blocks::table)
+            .values(&new_block)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
 
-    let created_id = new_block.id.clone();
+        let created_id = new_block.id.clone();
 
-    blocks_table
-        .filter(block_id.eq(created_id))
-        .first::<Block>(&mut conn)
-        .map_err(|e| e.to_string())
+        blocks_table
+            .filter(block_id.eq(created_id))
+            .first::<Block>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await?;
+    announce(&app, &[BlockChange::Created { block: block.clone() }]);
+    Ok(block)
 }
 
+/// Update a block. With `base_version` set, an edit to a block someone else
+/// changed since comes back as a conflict with the current block.
 #[tauri::command]
-pub async fn update_block(
+pub async fn update_block<R: Runtime>(
+    app: AppHandle<R>,
     state: State<'_, AppState>,
     block_id: String,
     block_type: Option<String>,
     content_json_param: Option<String>,
     order_index_param: Option<i32>,
-) -> Result<Block, String> {
-    use anyagents::schema::blocks::dsl::{blocks, id};
-
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    let update = UpdateBlock {
-        type_: block_type,
+    base_version: Option<i32>,
+) -> Result<BlockWrite, String> {
+    let edit = BlockEdit {
+        id: block_id,
+        block_type,
         content_json: content_json_param,
         order_index: order_index_param,
-        updated_at: chrono::Utc::now().naive_utc(),
+        base_version,
     };
-
-    diesel::update(blocks.filter(id.eq(&block_id)))
-        .set(&update)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
-
-    blocks
-        .filter(id.eq(&block_id))
-        .first::<Block>(&mut conn)
-        .map_err(|e| e.to_string())
+    let mut writes = collab::write_blocks(&state.db_pool, &[edit], collab::USER_EDITOR)?;
+    announce(&app, &collab::applied_changes(&writes));
+    Ok(writes.remove(0))
 }
 
 #[tauri::command]
-pub async fn delete_block(state: State<'_, AppState>, block_id: String) -> Result<(), String> {
+pub async fn delete_block<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, AppState>,
+    block_id: String,
+) -> Result<(), String> {
     use anyagents::schema::blocks::dsl::*;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-    diesel::delete(blocks.filter(id.eq(block_id)))
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
-
+    let deleted = block_id.clone();
+    let owner: Option<String> = anyagents::database::blocking(&state.db_pool, move |conn| {
+        let owner = blocks
+            .filter(id.eq(&deleted))
+            .select(page_id)
+            .first(conn)
+            .optional()
+            .map_err(|e| e.to_string())?;
+        diesel::delete(blocks.filter(id.eq(&deleted)))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+        Ok(owner)
+    })
+    .await?;
+
+    if let Some(owner) = owner {
+        announce(
+            &app,
+            &[BlockChange::Deleted {
+                page_id: owner,
+                block_id,
+                editor: collab::USER_EDITOR.to_string(),
+            }],
+        );
+    }
     Ok(())
 }
 
-#[derive(Deserialize)]
-pub struct BlockUpdate {
-    pub id: String,
-    pub block_type: Option<String>,
-    pub content_json: Option<String>,
-    pub order_index: Option<i32>,
+#[derive(Serialize)]
+pub struct BatchBlockUpdate {
+    /// All blocks of the page, after the update
+    pub blocks: Vec<Block>,
+    /// Blocks whose edit was refused, as they are now
+    pub conflicts: Vec<Block>,
 }
 
 #[tauri::command]
-pub async fn batch_update_blocks(
+pub async fn batch_update_blocks<R: Runtime>(
+    app: AppHandle<R>,
     state: State<'_, AppState>,
     page_id_param: String,
-    updates: Vec<BlockUpdate>,
-) -> Result<Vec<Block>, String> {
+    updates: Vec<BlockEdit>,
+) -> Result<BatchBlockUpdate, String> {
     use anyagents::schema::blocks::dsl::*;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    // Update each block
-    for update_data in updates {
-        let update = UpdateBlock {
-            type_: update_data.block_type,
-            content_json: update_data.content_json,
-            order_index: update_data.order_index,
-            updated_at: chrono::Utc::now().naive_utc(),
-        };
-
-        diesel::update(blocks.filter(id.eq(&update_data.id)))
-            .set(&update)
-            .execute(&mut conn)
-            .map_err(|e| e.to_string())?;
-    }
+    let writes = collab::write_blocks(&state.db_pool, &updates, collab::USER_EDITOR)?;
+    announce(&app, &collab::applied_changes(&writes));
+    let conflicts: Vec<Block> = writes
+        .into_iter()
+        .filter_map(|write| match write {
+            BlockWrite::Conflict { block, .. } => Some(block),
+            BlockWrite::Applied { .. } => None,
+        })
+        .collect();
 
     // Return all blocks for the page
-    blocks
-        .filter(page_id.eq(page_id_param))
-        .order(order_index.asc())
-        .load::<Block>(&mut conn)
-        .map_err(|e| e.to_string())
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let page_blocks = blocks
+            .filter(page_id.eq(page_id_param))
+            .order(order_index.asc())
+            .load::<Block>(conn)
+            .map_err(|e| e.to_string())?;
+        Ok(BatchBlockUpdate { blocks: page_blocks, conflicts })
+    })
+    .await
 }
 
 // ============================================================================
 // ATTACHMENT COMMANDS
 // ============================================================================
 
+/// Store an attachment; the same content uploaded again shares the file
 #[tauri::command]
 pub async fn upload_attachment(
     app: tauri::AppHandle,
@@ -350,50 +389,16 @@ pub async fn upload_attachment(
     file_name: String,
     file_data: Vec<u8>,
 ) -> Result<Attachment, String> {
-    use anyagents::schema::attachments;
-    use std::fs;
     use tauri::Manager;
 
     let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
-    let attachments_base = app_data_dir.join("attachments");
-    let attachments_dir = attachments_base.join(&page_id_param);
-    fs::create_dir_all(&attachments_dir).map_err(|e| e.to_string())?;
-
-    // Save file
-    let file_path = attachments_dir.join(&file_name);
-    fs::write(&file_path, &file_data).map_err(|e| e.to_string())?;
-
-    // Determine file type from extension
-    let file_type = file_path
-        .extension()
-        .and_then(|ext| ext.to_str())
-        .unwrap_or("unknown")
-        .to_string();
-
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    let new_attachment = NewAttachment {
-        id: uuid::Uuid::new_v4().to_string(),
-        page_id: page_id_param,
-        block_id: None,
-        file_path: file_path.to_string_lossy().to_string(),
-        file_name,
-        file_type,
-        file_size: file_data.len() as i32,
-        created_at: chrono::Utc::now().naive_utc(),
-    };
-
-    diesel::insert_into(attachments::table)
-        .values(&new_attachment)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
-
-    let created_id = new_attachment.id.clone();
-
-    attachments::table
-        .filter(attachments::id.eq(created_id))
-        .first::<Attachment>(&mut conn)
-        .map_err(|e| e.to_string())
+    page_attachments::store_attachment(
+        &state.db_pool,
+        &app_data_dir.join("attachments"),
+        &page_id_param,
+        &file_name,
+        &file_data,
+    )
 }
 
 #[tauri::command]
@@ -403,40 +408,92 @@ pub async fn get_page_attachments(
 ) -> Result<Vec<Attachment>, String> {
     use anyagents::schema::attachments::dsl::*;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-    attachments
-        .filter(page_id.eq(page_id_param))
-        .order(created_at.desc())
-        .load::<Attachment>(&mut conn)
-        .map_err(|e| e.to_string())
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        attachments
+            .filter(page_id.eq(page_id_param))
+            .order(created_at.desc())
+            .load::<Attachment>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn delete_attachment(
-    _app: tauri::AppHandle, // Included for consistency/future use if needed, though file_path is already absolute
+pub async fn delete_attachment(state: State<'_, AppState>, attachment_id: String) -> Result<(), String> {
+    page_attachments::remove_attachment(&state.db_pool, &attachment_id)
+}
+
+/// Bytes used by the page's attachments (when given) and by all of them
+#[tauri::command]
+pub async fn get_attachment_usage(
     state: State<'_, AppState>,
-    attachment_id: String,
-) -> Result<(), String> {
-    use anyagents::schema::attachments::dsl::*;
-    use std::fs;
+    page_id: Option<String>,
+) -> Result<AttachmentUsage, String> {
+    page_attachments::usage(&state.db_pool, page_id.as_deref())
+}
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+#[tauri::command]
+pub async fn set_attachment_quota(state: State<'_, AppState>, quota: AttachmentQuota) -> Result<(), String> {
+    quota.save(&state.db_pool)
+}
 
-    // Get attachment to delete file
-    let attachment: Attachment = attachments
-        .filter(id.eq(&attachment_id))
-        .first(&mut conn)
-        .map_err(|e| e.to_string())?;
+/// Remove the files no attachment uses, such as those of deleted pages
+#[tauri::command]
+pub async fn collect_attachment_garbage(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<AttachmentGc, String> {
+    use tauri::Manager;
 
-    // Delete file from filesystem
-    let _ = fs::remove_file(&attachment.file_path);
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?.join("attachments");
+    page_attachments::collect_garbage(&state.db_pool, &dir)
+}
 
-    // Delete from database
-    diesel::delete(attachments.filter(id.eq(attachment_id)))
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+// ============================================================================
+// MARKDOWN COMMANDS
+// ============================================================================
 
-    Ok(())
+/// Write the page to `path` as markdown, block ids embedded
+#[tauri::command]
+pub async fn export_page_markdown(state: State<'_, AppState>, page_id: String, path: String) -> Result<(), String> {
+    let markdown = anyagents::pages::export_markdown(&state.db_pool, &page_id)?;
+    std::fs::write(&path, markdown).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+/// Import a markdown file as a page, or sync it with the page it came from
+#[tauri::command]
+pub async fn sync_page_file(
+    state: State<'_, AppState>,
+    path: String,
+    parent_id: Option<String>,
+) -> Result<PageSync, String> {
+    anyagents::pages::sync_file(&state.db_pool, std::path::Path::new(&path), parent_id.as_deref())
+}
+
+/// Sync the markdown files in a folder of the agent's workspace (`pages` by
+/// default) with their pages, creating pages for new files
+#[tauri::command]
+pub async fn sync_workspace_pages(
+    state: State<'_, AppState>,
+    agent_id: String,
+    dir: Option<String>,
+    parent_id: Option<String>,
+) -> Result<Vec<PageSync>, String> {
+    use anyagents::schema::agents;
+
+    let agent: anyagents::models::Agent = anyagents::database::blocking(&state.db_pool, move |conn| {
+        agents::table
+            .find(&agent_id)
+            .first(conn)
+            .map_err(|_| "Agent not found".to_string())
+    })
+    .await?;
+    let relative = std::path::PathBuf::from(dir.as_deref().unwrap_or(anyagents::pages::WORKSPACE_PAGES_DIR));
+    if relative.is_absolute() || relative.components().any(|c| matches!(c, std::path::Component::ParentDir)) {
+        return Err("The folder must be inside the agent's workspace".to_string());
+    }
+    let folder = anyagents::email::attachments::agent_workspace(&agent).join(relative);
+    anyagents::pages::sync_directory(&state.db_pool, &folder, parent_id.as_deref())
 }
 
 #[cfg(test)]
@@ -454,7 +511,28 @@ mod tests {
             telegram_manager: std::sync::Arc::new(crate::telegram::TelegramBotManager::new(
                 create_test_pool(),
             )),
+            discord_manager: std::sync::Arc::new(crate::discord::DiscordBotManager::new(
+                create_test_pool(),
+                std::sync::Arc::new(anyagents::permissions::PermissionManager::new()),
+            )),
+            slack_manager: std::sync::Arc::new(crate::slack::SlackBotManager::new(
+                create_test_pool(),
+                std::sync::Arc::new(anyagents::permissions::PermissionManager::new()),
+            )),
+            matrix_manager: std::sync::Arc::new(crate::matrix::MatrixBotManager::new(
+                create_test_pool(),
+                std::sync::Arc::new(anyagents::permissions::PermissionManager::new()),
+            )),
             permission_manager: std::sync::Arc::new(anyagents::permissions::PermissionManager::new()),
+            agent_bus: std::sync::Arc::new(anyagents::bus::AgentBus::new(
+                create_test_pool(),
+                std::sync::Arc::new(anyagents::permissions::PermissionManager::new()),
+            )),
+            trigger_engine: std::sync::Arc::new(crate::agent_triggers::TriggerEngine::new()),
+            email_bridge: std::sync::Arc::new(crate::email_bridge::EmailBridgeManager::new(
+                create_test_pool(),
+                std::sync::Arc::new(anyagents::permissions::PermissionManager::new()),
+            )),
         }
     }
 
@@ -502,6 +580,7 @@ mod tests {
 
         // Test create_block
         let block = create_block(
+            app.handle().clone(),
             state_handle.clone().into(),
             page.id.clone(),
             "text".to_string(),
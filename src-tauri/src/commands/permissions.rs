@@ -0,0 +1,51 @@
+use crate::AppState;
+use anyagents::models::permission_audit::{self, PermissionAuditEntry, PermissionAuditFilter};
+use anyagents::models::permission_grant::{self, PermissionGrant};
+use tauri::State;
+
+/// Resolved permission requests, newest first. Every filter is optional;
+/// `resolution` is "allow", "deny", "always" or "mask" (DLP incidents have
+/// `permission_type` "dlp").
+#[tauri::command]
+pub async fn list_permission_audit(
+    state: State<'_, AppState>,
+    filter: Option<PermissionAuditFilter>,
+) -> Result<Vec<PermissionAuditEntry>, String> {
+    permission_audit::list_permission_audit(&state.db_pool, &filter.unwrap_or_default())
+}
+
+#[tauri::command]
+pub async fn get_permission_audit_entry(
+    state: State<'_, AppState>,
+    request_id: String,
+) -> Result<PermissionAuditEntry, String> {
+    permission_audit::get_permission_audit_entry(&state.db_pool, &request_id)
+}
+
+/// Live "always allow" grants, newest first
+#[tauri::command]
+pub async fn list_permission_grants(
+    state: State<'_, AppState>,
+    include_expired: Option<bool>,
+) -> Result<Vec<PermissionGrant>, String> {
+    permission_grant::list_grants(&state.db_pool, include_expired.unwrap_or(false))
+}
+
+/// Requests the grant covered will be asked about again
+#[tauri::command]
+pub async fn revoke_permission_grant(state: State<'_, AppState>, grant_id: String) -> Result<(), String> {
+    permission_grant::revoke_grant(&state.db_pool, &grant_id)
+}
+
+/// Check a YAML or JSON policy before it is saved to an agent
+#[tauri::command]
+pub async fn validate_permission_policy(policy: String) -> Result<(), String> {
+    anyagents::permissions::AgentPolicy::parse(&policy).map(|_| ())
+}
+
+/// Check a YAML or JSON DLP policy before it is saved as the `dlp_policy`
+/// setting
+#[tauri::command]
+pub async fn validate_dlp_policy(policy: String) -> Result<(), String> {
+    anyagents::permissions::DlpFilter::parse(&policy).map(|_| ())
+}
@@ -0,0 +1,174 @@
+use crate::commands::window::ensure_can_act;
+use crate::events::TauriAgentObserver;
+use crate::AppState;
+use anyagents::agents::dry_run::{self, AppliedCall};
+use anyagents::agents::AgentLoop;
+use anyagents::events::AgentObserver;
+use anyagents::models::plan::{self, PlanRecord, PlanTaskRecord};
+use anyagents::models::todo::{self, JobTodos};
+use anyagents::models::{Agent, Session, TaskSpec};
+use diesel::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tauri::State;
+
+#[derive(Debug, Serialize)]
+pub struct PlanDetails {
+    pub plan: PlanRecord,
+    pub tasks: Vec<PlanTaskRecord>,
+}
+
+#[tauri::command]
+pub async fn get_session_plans(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<Vec<PlanRecord>, String> {
+    plan::get_session_plans(&state.db_pool, &session_id)
+}
+
+/// The checklist the session's latest job kept with the `todo` tool
+#[tauri::command]
+pub async fn get_session_todos(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<Option<JobTodos>, String> {
+    todo::latest_session_todos(&state.db_pool, &session_id)
+}
+
+#[tauri::command]
+pub async fn get_plan(state: State<'_, AppState>, plan_id: String) -> Result<PlanDetails, String> {
+    Ok(PlanDetails {
+        plan: plan::get_plan(&state.db_pool, &plan_id)?,
+        tasks: plan::get_plan_tasks(&state.db_pool, &plan_id)?,
+    })
+}
+
+/// Change the execution order; `task_ids` must list every task of the plan
+#[tauri::command]
+pub async fn reorder_plan_tasks(
+    state: State<'_, AppState>,
+    plan_id: String,
+    task_ids: Vec<String>,
+) -> Result<(), String> {
+    plan::reorder_tasks(&state.db_pool, &plan_id, &task_ids)
+}
+
+#[tauri::command]
+pub async fn skip_plan_task(
+    state: State<'_, AppState>,
+    plan_id: String,
+    task_id: String,
+) -> Result<(), String> {
+    plan::skip_task(&state.db_pool, &plan_id, &task_id)
+}
+
+/// Answer a `plan_proposed` event. When approving, `tasks` optionally replaces
+/// the proposed task list (edited descriptions, order, added/removed tasks).
+#[tauri::command]
+pub async fn approve_plan(
+    window: tauri::WebviewWindow,
+    state: State<'_, AppState>,
+    plan_id: String,
+    approved: bool,
+    tasks: Option<Vec<TaskSpec>>,
+) -> Result<(), String> {
+    ensure_can_act(&window)?;
+    if !state.pending_approvals.contains_key(&plan_id) {
+        return Err("No plan is awaiting approval with this id".to_string());
+    }
+    if let (true, Some(tasks)) = (approved, tasks) {
+        plan::replace_tasks(&state.db_pool, &plan_id, &tasks)?;
+    }
+
+    if let Some((_, tx)) = state.pending_approvals.remove(&plan_id) {
+        let _ = tx.send(approved);
+    }
+    Ok(())
+}
+
+/// Resume an interrupted plan; progress is streamed on the session channel
+#[tauri::command]
+pub async fn resume_plan(
+    window: tauri::WebviewWindow,
+    state: State<'_, AppState>,
+    plan_id: String,
+) -> Result<String, String> {
+    use anyagents::schema::{agents, sessions};
+
+    ensure_can_act(&window)?;
+
+    let record = plan::get_plan(&state.db_pool, &plan_id)?;
+    if record.status == "running" {
+        return Err("Plan is already running".to_string());
+    }
+    if state.pending_approvals.contains_key(&plan_id) {
+        return Err("Plan is awaiting approval".to_string());
+    }
+
+    let session_id = record.session_id.clone();
+    let agent: Agent = anyagents::database::blocking(&state.db_pool, move |conn| {
+        let session: Session = sessions::table
+            .find(&session_id)
+            .first(conn)
+            .map_err(|_| "Session not found".to_string())?;
+        agents::table
+            .find(&session.agent_id)
+            .first(conn)
+            .map_err(|_| "Agent not found".to_string())
+    })
+    .await?;
+
+    crate::commands::agents::start_plan_resume_task(
+        agent,
+        plan_id,
+        record.session_id,
+        window,
+        state.pending_approvals.clone(),
+        state.db_pool.clone(),
+        state.agent_bus.clone(),
+    );
+
+    Ok("started".to_string())
+}
+
+/// Answer a `change_plan_proposed` event by making the held-back calls for
+/// real. Progress is streamed on the session channel as a job of its own.
+#[tauri::command]
+pub async fn apply_change_plan(
+    window: tauri::WebviewWindow,
+    state: State<'_, AppState>,
+    job_id: String,
+) -> Result<Vec<AppliedCall>, String> {
+    use anyagents::schema::agents;
+
+    ensure_can_act(&window)?;
+
+    let plan = dry_run::take(&job_id).ok_or("No change plan is awaiting approval for this job")?;
+    let mut workers = HashMap::new();
+    for call in plan.calls() {
+        if workers.contains_key(&call.agent_id) {
+            continue;
+        }
+        let agent_id = call.agent_id.clone();
+        let agent: Agent = anyagents::database::blocking(&state.db_pool, move |conn| {
+            agents::table
+                .find(&agent_id)
+                .first(conn)
+                .map_err(|_| "Agent not found".to_string())
+        })
+        .await?;
+        let mut worker = AgentLoop::new(&agent, state.db_pool.clone()).await;
+        worker.session_id = plan.session_id.clone();
+        workers.insert(call.agent_id, worker);
+    }
+
+    let observer: Arc<dyn AgentObserver> = Arc::new(TauriAgentObserver::for_session(&window, &plan.session_id));
+    Ok(dry_run::apply(&plan, &workers, state.permission_manager.clone(), observer).await)
+}
+
+#[tauri::command]
+pub async fn discard_change_plan(window: tauri::WebviewWindow, job_id: String) -> Result<(), String> {
+    ensure_can_act(&window)?;
+    dry_run::take(&job_id).map(|_| ()).ok_or_else(|| "No change plan is awaiting approval for this job".to_string())
+}
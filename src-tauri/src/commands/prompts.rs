@@ -0,0 +1,83 @@
+use crate::AppState;
+use anyagents::models::prompt_template::{self, PromptTemplateVersion};
+use anyagents::prompts::{self, BuiltinTemplate, ResolvedTemplate};
+use serde_json::Value;
+use tauri::State;
+
+/// Built-in templates with their documented variables
+#[tauri::command]
+pub async fn list_prompt_templates() -> Result<Vec<BuiltinTemplate>, String> {
+    Ok(prompts::BUILTIN_TEMPLATES.to_vec())
+}
+
+/// The template an agent (or, without one, every agent) renders with now
+#[tauri::command]
+pub async fn get_prompt_template(
+    state: State<'_, AppState>,
+    name: String,
+    agent_id: Option<String>,
+) -> Result<ResolvedTemplate, String> {
+    prompts::resolve(Some(&state.db_pool), &name, agent_id.as_deref())
+}
+
+/// Saved versions of an override, newest first
+#[tauri::command]
+pub async fn list_prompt_template_versions(
+    state: State<'_, AppState>,
+    name: String,
+    agent_id: Option<String>,
+) -> Result<Vec<PromptTemplateVersion>, String> {
+    prompts::builtin(&name)?;
+    prompt_template::list_versions(&state.db_pool, &name, agent_id.as_deref())
+}
+
+/// Used from the next job on; no restart needed
+#[tauri::command]
+pub async fn save_prompt_template(
+    state: State<'_, AppState>,
+    name: String,
+    agent_id: Option<String>,
+    body: String,
+    note: Option<String>,
+) -> Result<PromptTemplateVersion, String> {
+    prompts::save(&state.db_pool, &name, agent_id.as_deref(), &body, note)
+}
+
+#[tauri::command]
+pub async fn restore_prompt_template_version(
+    state: State<'_, AppState>,
+    name: String,
+    agent_id: Option<String>,
+    version: i32,
+) -> Result<PromptTemplateVersion, String> {
+    prompts::restore(&state.db_pool, &name, agent_id.as_deref(), version)
+}
+
+/// Delete an override and its history
+#[tauri::command]
+pub async fn reset_prompt_template(
+    state: State<'_, AppState>,
+    name: String,
+    agent_id: Option<String>,
+) -> Result<(), String> {
+    prompts::reset(&state.db_pool, &name, agent_id.as_deref())
+}
+
+/// Render `body` (or the template in use) with example variables, overlaid
+/// with `variables`
+#[tauri::command]
+pub async fn preview_prompt_template(
+    state: State<'_, AppState>,
+    name: String,
+    agent_id: Option<String>,
+    body: Option<String>,
+    variables: Option<Value>,
+) -> Result<String, String> {
+    prompts::preview(
+        Some(&state.db_pool),
+        &name,
+        agent_id.as_deref(),
+        body.as_deref(),
+        variables.as_ref(),
+    )
+}
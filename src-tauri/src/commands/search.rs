@@ -0,0 +1,17 @@
+use crate::AppState;
+use anyagents::search::{self, SearchHit};
+use tauri::State;
+
+/// Search chats, pages and mail at once; hits are best first and say what
+/// to open (session and message, page and block, or mail thread)
+#[tauri::command]
+pub async fn global_search(
+    state: State<'_, AppState>,
+    query: String,
+    limit: Option<i64>,
+) -> Result<Vec<SearchHit>, String> {
+    let pool = state.db_pool.clone();
+    tauri::async_runtime::spawn_blocking(move || search::global_search(&pool, &query, limit))
+        .await
+        .map_err(|e| e.to_string())?
+}
@@ -0,0 +1,51 @@
+use crate::AppState;
+use anyagents::models::secret::{self, SecretRecord};
+use anyagents::secrets;
+use tauri::State;
+
+/// Every named secret; values are never returned
+#[tauri::command]
+pub async fn list_secrets(state: State<'_, AppState>) -> Result<Vec<SecretRecord>, String> {
+    secret::list_secret_records(&state.db_pool)
+}
+
+/// Create or replace a secret. The name is also the environment variable an
+/// agent's command sees it as.
+#[tauri::command]
+pub async fn set_secret(
+    state: State<'_, AppState>,
+    name: String,
+    value: String,
+    description: Option<String>,
+) -> Result<SecretRecord, String> {
+    secrets::validate_secret_name(&name)?;
+    if value.is_empty() {
+        return Err("Secret value cannot be empty".to_string());
+    }
+    secrets::set_secret_value(&name, &value)?;
+    secret::upsert_secret_record(&state.db_pool, &name, description)
+}
+
+/// Remove a secret from the keyring and from every agent it was granted to
+#[tauri::command]
+pub async fn delete_secret(state: State<'_, AppState>, name: String) -> Result<(), String> {
+    secrets::delete_secret_value(&name)?;
+    secret::delete_secret_record(&state.db_pool, &name)
+}
+
+/// Names of the secrets an agent may use
+#[tauri::command]
+pub async fn list_agent_secrets(state: State<'_, AppState>, agent_id: String) -> Result<Vec<String>, String> {
+    secret::agent_secret_names(&state.db_pool, &agent_id)
+}
+
+/// Takes effect the next time the agent is loaded
+#[tauri::command]
+pub async fn grant_agent_secret(state: State<'_, AppState>, agent_id: String, name: String) -> Result<(), String> {
+    secret::grant_secret(&state.db_pool, &agent_id, &name)
+}
+
+#[tauri::command]
+pub async fn revoke_agent_secret(state: State<'_, AppState>, agent_id: String, name: String) -> Result<(), String> {
+    secret::revoke_secret(&state.db_pool, &agent_id, &name)
+}
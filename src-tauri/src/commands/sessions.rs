@@ -1,3 +1,5 @@
+use anyagents::event_log;
+use anyagents::models::session_event::{self, SessionEvent};
 use anyagents::models::{Message, NewMessage, NewSession, Session, UpdateSession};
 use anyagents::schema;
 use crate::AppState;
@@ -5,6 +7,8 @@ use diesel::prelude::*;
 use serde::Serialize;
 use tauri::State;
 
+const SESSION_EVENT_LIMIT: i64 = 5_000;
+
 #[tauri::command]
 pub async fn create_session(
     state: State<'_, AppState>,
@@ -12,38 +16,39 @@ pub async fn create_session(
 ) -> Result<Session, String> {
     use anyagents::schema::{agents, sessions};
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    // Get agent name to create default title
-    let agent = agents::table
-        .filter(agents::id.eq(&agent_id))
-        .first::<anyagents::models::Agent>(&mut conn)
-        .map_err(|e| format!("Agent not found: {}", e))?;
-
-    // Create default title: "Chat with {agent_name}"
-    let default_title = format!("Chat with {}", agent.name);
-
-    let new_session = NewSession {
-        id: uuid::Uuid::new_v4().to_string(),
-        agent_id,
-        title: Some(default_title),
-        created_at: chrono::Utc::now().naive_utc(),
-        updated_at: chrono::Utc::now().naive_utc(),
-        archived: 0,
-        pinned: 0,
-    };
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        // Get agent name to create default title
+        let agent = agents::table
+            .filter(agents::id.eq(&agent_id))
+            .first::<anyagents::models::Agent>(conn)
+            .map_err(|e| format!("Agent not found: {}", e))?;
+
+        // Create default title: "Chat with {agent_name}"
+        let default_title = format!("Chat with {}", agent.name);
+
+        let new_session = NewSession {
+            id: uuid::Uuid::new_v4().to_string(),
+            agent_id,
+            title: Some(default_title),
+            created_at: chrono::Utc::now().naive_utc(),
+            updated_at: chrono::Utc::now().naive_utc(),
+            archived: 0,
+            pinned: 0,
+        };
 
-    diesel::insert_into(sessions::table)
-        .values(&new_session)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+        diesel::insert_into(sessions::table)
+            .values(&new_session)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
 
-    let created_id = new_session.id.clone();
+        let created_id = new_session.id.clone();
 
-    sessions::table
-        .filter(sessions::id.eq(created_id))
-        .first::<Session>(&mut conn)
-        .map_err(|e| e.to_string())
+        sessions::table
+            .filter(sessions::id.eq(created_id))
+            .first::<Session>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 #[tauri::command]
@@ -55,40 +60,64 @@ pub async fn get_sessions(
 ) -> Result<Vec<Session>, String> {
     use anyagents::schema::sessions::dsl::{archived, pinned, sessions, updated_at};
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let mut query = sessions.into_boxed();
 
-    let mut query = sessions.into_boxed();
-
-    // Filter by archived status if specified
-    if let Some(arch) = archived_param {
-        let arch_val = if arch { 1 } else { 0 };
-        query = query.filter(archived.eq(arch_val));
-    }
+        // Filter by archived status if specified
+        if let Some(arch) = archived_param {
+            let arch_val = if arch { 1 } else { 0 };
+            query = query.filter(archived.eq(arch_val));
+        }
 
-    // Order by pinned first, then by updated_at
-    query = query.order((pinned.desc(), updated_at.desc()));
+        // Order by pinned first, then by updated_at
+        query = query.order((pinned.desc(), updated_at.desc()));
 
-    // Apply pagination if specified
-    if let Some(lim) = limit {
-        query = query.limit(lim);
-    }
-    if let Some(off) = offset {
-        query = query.offset(off);
-    }
+        // Apply pagination if specified
+        if let Some(lim) = limit {
+            query = query.limit(lim);
+        }
+        if let Some(off) = offset {
+            query = query.offset(off);
+        }
 
-    query.load::<Session>(&mut conn).map_err(|e| e.to_string())
+        query.load::<Session>(conn).map_err(|e| e.to_string())
+    })
+    .await
 }
 
 #[tauri::command]
 pub async fn delete_session(state: State<'_, AppState>, session_id: String) -> Result<(), String> {
     use anyagents::schema::sessions::dsl::*;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-    diesel::delete(sessions.filter(id.eq(session_id)))
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+    let pool = state.db_pool.clone();
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        // Remove the session's messages too, otherwise they are left orphaned
+        diesel::delete(schema::messages::table.filter(schema::messages::session_id.eq(&session_id)))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
 
-    Ok(())
+        anyagents::models::session_event::delete_session_events(&pool, &session_id)?;
+
+        // Plans (and their tasks) belong to the session as well
+        let plan_ids: Vec<String> = schema::plans::table
+            .filter(schema::plans::session_id.eq(&session_id))
+            .select(schema::plans::id)
+            .load(conn)
+            .map_err(|e| e.to_string())?;
+        diesel::delete(schema::plan_tasks::table.filter(schema::plan_tasks::plan_id.eq_any(&plan_ids)))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+        diesel::delete(schema::plans::table.filter(schema::plans::session_id.eq(&session_id)))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        diesel::delete(sessions.filter(id.eq(session_id)))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    })
+    .await
 }
 
 #[tauri::command]
@@ -96,16 +125,17 @@ pub async fn get_session_messages(
     state: State<'_, AppState>,
     session_id: String,
 ) -> Result<Vec<Message>, String> {
-    use anyagents::schema::messages::dsl::{created_at, messages};
-
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-    let results = messages
-        .filter(schema::messages::dsl::session_id.eq(session_id))
-        .order(created_at.asc())
-        .load::<Message>(&mut conn)
-        .map_err(|e| e.to_string())?;
-
-    Ok(results)
+    use anyagents::schema::messages::dsl::{created_at, deleted_at, messages};
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        messages
+            .filter(schema::messages::dsl::session_id.eq(session_id))
+            .filter(deleted_at.is_null())
+            .order(created_at.asc())
+            .load::<Message>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 // ============================================================================
@@ -122,24 +152,27 @@ pub async fn update_session(
 ) -> Result<Session, String> {
     use anyagents::schema::sessions::dsl::{id, sessions};
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    let update = UpdateSession {
-        title,
-        archived: archived_param.map(|v| if v { 1 } else { 0 }),
-        pinned: pinned_param.map(|v| if v { 1 } else { 0 }),
-        updated_at: chrono::Utc::now().naive_utc(),
-    };
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let update = UpdateSession {
+            // A title the user chose is never replaced by a generated one
+            title_locked: title.as_ref().map(|_| 1),
+            title,
+            archived: archived_param.map(|v| if v { 1 } else { 0 }),
+            pinned: pinned_param.map(|v| if v { 1 } else { 0 }),
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
 
-    diesel::update(sessions.filter(id.eq(&session_id)))
-        .set(&update)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+        diesel::update(sessions.filter(id.eq(&session_id)))
+            .set(&update)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
 
-    sessions
-        .filter(id.eq(&session_id))
-        .first::<Session>(&mut conn)
-        .map_err(|e| e.to_string())
+        sessions
+            .filter(id.eq(&session_id))
+            .first::<Session>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 #[derive(Serialize)]
@@ -153,28 +186,30 @@ pub async fn get_session_with_messages(
     state: State<'_, AppState>,
     session_id: String,
 ) -> Result<SessionWithMessages, String> {
-    use anyagents::schema::messages::dsl::{created_at, messages, session_id as msg_session_id};
+    use anyagents::schema::messages::dsl::{created_at, deleted_at, messages, session_id as msg_session_id};
     use anyagents::schema::sessions::dsl::{id as session_id_col, sessions};
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    // Get session
-    let session = sessions
-        .filter(session_id_col.eq(&session_id))
-        .first::<Session>(&mut conn)
-        .map_err(|e| format!("Session not found: {}", e))?;
-
-    // Get messages
-    let msgs = messages
-        .filter(msg_session_id.eq(&session_id))
-        .order(created_at.asc())
-        .load::<Message>(&mut conn)
-        .map_err(|e| e.to_string())?;
-
-    Ok(SessionWithMessages {
-        session,
-        messages: msgs,
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        // Get session
+        let session = sessions
+            .filter(session_id_col.eq(&session_id))
+            .first::<Session>(conn)
+            .map_err(|e| format!("Session not found: {}", e))?;
+
+        // Get messages
+        let msgs = messages
+            .filter(msg_session_id.eq(&session_id))
+            .filter(deleted_at.is_null())
+            .order(created_at.asc())
+            .load::<Message>(conn)
+            .map_err(|e| e.to_string())?;
+
+        Ok(SessionWithMessages {
+            session,
+            messages: msgs,
+        })
     })
+    .await
 }
 
 #[tauri::command]
@@ -188,8 +223,6 @@ pub async fn add_message(
 ) -> Result<Message, String> {
     use anyagents::schema::messages;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
     let new_message = NewMessage {
         id: uuid::Uuid::new_v4().to_string(),
         role,
@@ -199,29 +232,24 @@ pub async fn add_message(
         tokens,
     };
 
-    diesel::insert_into(messages::table)
-        .values(&new_message)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
-
-    let created_id = new_message.id.clone();
-
-    messages::table
-        .filter(messages::id.eq(created_id))
-        .first::<Message>(&mut conn)
-        .map_err(|e| e.to_string())
+    anyagents::database::serialized_write(&state.db_pool, |conn| {
+        diesel::insert_into(messages::table).values(&new_message).execute(conn)?;
+        messages::table.find(&new_message.id).first::<Message>(conn)
+    })
 }
 
 #[tauri::command]
 pub async fn delete_message(state: State<'_, AppState>, message_id: String) -> Result<(), String> {
     use anyagents::schema::messages::dsl::{id, messages};
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-    diesel::delete(messages.filter(id.eq(message_id)))
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        diesel::delete(messages.filter(id.eq(message_id)))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
 
-    Ok(())
+        Ok(())
+    })
+    .await
 }
 
 #[derive(Serialize)]
@@ -236,30 +264,53 @@ pub async fn get_session_stats(
     state: State<'_, AppState>,
     session_id: String,
 ) -> Result<SessionStats, String> {
-    use anyagents::schema::messages::dsl::{messages, session_id as msg_session_id, tokens};
-
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    // Count messages
-    let count: i64 = messages
-        .filter(msg_session_id.eq(&session_id))
-        .count()
-        .get_result(&mut conn)
-        .map_err(|e| e.to_string())?;
-
-    // Sum tokens (if available)
-    let total_tokens: Option<i64> = messages
-        .filter(msg_session_id.eq(&session_id))
-        .select(diesel::dsl::sum(tokens))
-        .first(&mut conn)
-        .ok()
-        .flatten();
-
-    Ok(SessionStats {
-        session_id,
-        message_count: count,
-        total_tokens,
+    use anyagents::schema::messages::dsl::{deleted_at, messages, session_id as msg_session_id, tokens};
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        // Count messages
+        let count: i64 = messages
+            .filter(msg_session_id.eq(&session_id))
+            .filter(deleted_at.is_null())
+            .count()
+            .get_result(conn)
+            .map_err(|e| e.to_string())?;
+
+        // Sum tokens (if available)
+        let total_tokens: Option<i64> = messages
+            .filter(msg_session_id.eq(&session_id))
+            .filter(deleted_at.is_null())
+            .select(diesel::dsl::sum(tokens))
+            .first(conn)
+            .ok()
+            .flatten();
+
+        Ok(SessionStats {
+            session_id,
+            message_count: count,
+            total_tokens,
+        })
     })
+    .await
+}
+
+/// Write the session (messages, tool steps, plans, permission decisions) to
+/// `path` as a JSON Lines archive
+#[tauri::command]
+pub async fn export_session(state: State<'_, AppState>, session_id: String, path: String) -> Result<(), String> {
+    let archive = anyagents::archive::export_session(&state.db_pool, &session_id)?;
+    std::fs::write(&path, archive).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+/// Restore an archive written by `export_session` as a new session, into
+/// `agent_id` or else the archived agent if it exists here
+#[tauri::command]
+pub async fn import_session(
+    state: State<'_, AppState>,
+    path: String,
+    agent_id: Option<String>,
+) -> Result<Session, String> {
+    let archive = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    anyagents::archive::import_session(&state.db_pool, &archive, agent_id.as_deref())
 }
 
 #[cfg(test)]
@@ -301,6 +352,7 @@ mod tests {
             scope_type: None,
             workspace_path: None,
             avatar: None,
+            agent_tools: None,
         };
 
         diesel::insert_into(agents::table)
@@ -319,7 +371,28 @@ mod tests {
             telegram_manager: std::sync::Arc::new(crate::telegram::TelegramBotManager::new(
                 create_test_pool(),
             )),
+            discord_manager: std::sync::Arc::new(crate::discord::DiscordBotManager::new(
+                create_test_pool(),
+                std::sync::Arc::new(anyagents::permissions::PermissionManager::new()),
+            )),
+            slack_manager: std::sync::Arc::new(crate::slack::SlackBotManager::new(
+                create_test_pool(),
+                std::sync::Arc::new(anyagents::permissions::PermissionManager::new()),
+            )),
+            matrix_manager: std::sync::Arc::new(crate::matrix::MatrixBotManager::new(
+                create_test_pool(),
+                std::sync::Arc::new(anyagents::permissions::PermissionManager::new()),
+            )),
             permission_manager: std::sync::Arc::new(anyagents::permissions::PermissionManager::new()),
+            agent_bus: std::sync::Arc::new(anyagents::bus::AgentBus::new(
+                create_test_pool(),
+                std::sync::Arc::new(anyagents::permissions::PermissionManager::new()),
+            )),
+            trigger_engine: std::sync::Arc::new(crate::agent_triggers::TriggerEngine::new()),
+            email_bridge: std::sync::Arc::new(crate::email_bridge::EmailBridgeManager::new(
+                create_test_pool(),
+                std::sync::Arc::new(anyagents::permissions::PermissionManager::new()),
+            )),
         }
     }
 
@@ -381,3 +454,22 @@ mod tests {
         assert_eq!(messages[0].id, msg.id);
     }
 }
+
+/// Events the session's jobs streamed after the `since` cursor (an event id),
+/// oldest first; a window rebuilds job progress from them after a reload
+#[tauri::command]
+pub async fn get_session_events(
+    state: State<'_, AppState>,
+    session_id: String,
+    since: Option<i64>,
+    limit: Option<i64>,
+) -> Result<Vec<SessionEvent>, String> {
+    event_log::global().flush(&state.db_pool, &session_id)?;
+    session_event::get_session_events(&state.db_pool, &session_id, since, limit.unwrap_or(SESSION_EVENT_LIMIT))
+}
+
+/// Everything a job emitted, for going through it afterwards
+#[tauri::command]
+pub async fn get_job_events(state: State<'_, AppState>, job_id: String) -> Result<Vec<SessionEvent>, String> {
+    session_event::get_job_events(&state.db_pool, &job_id)
+}
@@ -1,6 +1,10 @@
 use crate::AppState;
-use anyagents::models::{NewSetting, Setting, UpdateSetting};
-use anyagents::schema::settings;
+use anyagents::config::{self, ConfigChange, EffectiveConfig};
+use anyagents::event_channel::EventChannelConfig;
+use anyagents::llm::scheduler::{self, RateLimits};
+use anyagents::llm::{ModelFilter, ModelInfo};
+use anyagents::models::{Agent, NewSetting, Setting, UpdateSetting};
+use anyagents::schema::{agents, settings};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 use tauri::State;
@@ -21,33 +25,34 @@ pub struct AIConfig {
 /// Get AI configuration from settings
 #[tauri::command]
 pub async fn get_ai_config(state: State<'_, AppState>) -> Result<AIConfig, String> {
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    // Fetch all settings
-    let all_settings = settings::table
-        .load::<Setting>(&mut conn)
-        .map_err(|e| format!("Failed to load settings: {}", e))?;
-
-    // Helper function to get value
-    let get_value = |key: &str| -> Option<String> {
-        all_settings
-            .iter()
-            .find(|s| s.key == key)
-            .and_then(|s| s.value.clone())
-            .filter(|v| !v.is_empty())
-    };
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        // Fetch all settings
+        let all_settings = settings::table
+            .load::<Setting>(conn)
+            .map_err(|e| format!("Failed to load settings: {}", e))?;
+
+        // Helper function to get value
+        let get_value = |key: &str| -> Option<String> {
+            all_settings
+                .iter()
+                .find(|s| s.key == key)
+                .and_then(|s| s.value.clone())
+                .filter(|v| !v.is_empty())
+        };
 
-    Ok(AIConfig {
-        provider: get_value("ai_provider").or(Some("openai".to_string())),
-        anthropic_api_key: get_value("anthropic_api_key"),
-        anthropic_model: get_value("anthropic_model").or(Some("claude-opus-4-5-20251101".to_string())),
-        openai_api_key: get_value("openai_api_key"),
-        openai_model: get_value("openai_model").or(Some("gpt-4o".to_string())),
-        gemini_api_key: get_value("gemini_api_key"),
-        gemini_model: get_value("gemini_model").or(Some("gemini-2.0-flash-exp".to_string())),
-        max_tokens: get_value("max_tokens").and_then(|v| v.parse().ok()).or(Some(4096)),
-        temperature: get_value("temperature").and_then(|v| v.parse().ok()).or(Some(0.7)),
+        Ok(AIConfig {
+            provider: get_value("ai_provider").or(Some("openai".to_string())),
+            anthropic_api_key: get_value("anthropic_api_key"),
+            anthropic_model: get_value("anthropic_model").or(Some("claude-opus-4-5-20251101".to_string())),
+            openai_api_key: get_value("openai_api_key"),
+            openai_model: get_value("openai_model").or(Some("gpt-4o".to_string())),
+            gemini_api_key: get_value("gemini_api_key"),
+            gemini_model: get_value("gemini_model").or(Some("gemini-2.0-flash-exp".to_string())),
+            max_tokens: get_value("max_tokens").and_then(|v| v.parse().ok()).or(Some(4096)),
+            temperature: get_value("temperature").and_then(|v| v.parse().ok()).or(Some(0.7)),
+        })
     })
+    .await
 }
 
 /// Update AI configuration
@@ -56,61 +61,159 @@ pub async fn update_ai_config(
     state: State<'_, AppState>,
     config: AIConfig,
 ) -> Result<(), String> {
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    // Update each setting if present
-    let mut settings_to_update = Vec::new();
-
-    if let Some(v) = config.provider {
-        settings_to_update.push(("ai_provider", v));
-    }
-    if let Some(v) = config.anthropic_api_key {
-        settings_to_update.push(("anthropic_api_key", v));
-    }
-    if let Some(v) = config.anthropic_model {
-        settings_to_update.push(("anthropic_model", v));
-    }
-    if let Some(v) = config.openai_api_key {
-        settings_to_update.push(("openai_api_key", v));
-    }
-    if let Some(v) = config.openai_model {
-        settings_to_update.push(("openai_model", v));
-    }
-    if let Some(v) = config.gemini_api_key {
-        settings_to_update.push(("gemini_api_key", v));
-    }
-    if let Some(v) = config.gemini_model {
-        settings_to_update.push(("gemini_model", v));
-    }
-    if let Some(v) = config.max_tokens {
-        settings_to_update.push(("max_tokens", v.to_string()));
-    }
-    if let Some(v) = config.temperature {
-        settings_to_update.push(("temperature", v.to_string()));
-    }
-
-    for (key, value) in settings_to_update {
-        diesel::insert_into(settings::table)
-            .values(NewSetting {
-                id: uuid::Uuid::new_v4().to_string(), // New ID for insert
-                key: key.to_string(),
-                value: Some(value.clone()),
-            })
-            .on_conflict(settings::key)
-            .do_update()
-            .set(UpdateSetting {
-                value: Some(value),
-                updated_at: chrono::Utc::now().naive_utc(),
-            })
-            .execute(&mut conn)
-            .map_err(|e| format!("Failed to update setting {}: {}", key, e))?;
-    }
-
-    log::info!("AI configuration updated successfully");
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        // Update each setting if present
+        let mut settings_to_update = Vec::new();
+
+        if let Some(v) = config.provider {
+            settings_to_update.push(("ai_provider", v));
+        }
+        if let Some(v) = config.anthropic_api_key {
+            settings_to_update.push(("anthropic_api_key", v));
+        }
+        if let Some(v) = config.anthropic_model {
+            settings_to_update.push(("anthropic_model", v));
+        }
+        if let Some(v) = config.openai_api_key {
+            settings_to_update.push(("openai_api_key", v));
+        }
+        if let Some(v) = config.openai_model {
+            settings_to_update.push(("openai_model", v));
+        }
+        if let Some(v) = config.gemini_api_key {
+            settings_to_update.push(("gemini_api_key", v));
+        }
+        if let Some(v) = config.gemini_model {
+            settings_to_update.push(("gemini_model", v));
+        }
+        if let Some(v) = config.max_tokens {
+            settings_to_update.push(("max_tokens", v.to_string()));
+        }
+        if let Some(v) = config.temperature {
+            settings_to_update.push(("temperature", v.to_string()));
+        }
+
+        for (key, value) in settings_to_update {
+            diesel::insert_into(settings::table)
+                .values(NewSetting {
+                    id: uuid::Uuid::new_v4().to_string(), // New ID for insert
+                    key: key.to_string(),
+                    value: Some(value.clone()),
+                })
+                .on_conflict(settings::key)
+                .do_update()
+                .set(UpdateSetting {
+                    value: Some(value),
+                    updated_at: chrono::Utc::now().naive_utc(),
+                })
+                .execute(conn)
+                .map_err(|e| format!("Failed to update setting {}: {}", key, e))?;
+        }
+
+        log::info!("AI configuration updated successfully");
+        Ok(())
+    })
+    .await
+}
+
+/// Concurrency caps and per-minute budgets for LLM calls
+#[tauri::command]
+pub async fn get_rate_limits(state: State<'_, AppState>) -> Result<RateLimits, String> {
+    Ok(RateLimits::load(&state.db_pool))
+}
+
+/// Save the limits and apply them to calls already waiting
+#[tauri::command]
+pub async fn set_rate_limits(state: State<'_, AppState>, limits: RateLimits) -> Result<(), String> {
+    limits.save(&state.db_pool)?;
+    scheduler::global().set_limits(limits);
     Ok(())
 }
 
+/// How streamed events are batched on their way to the windows
+#[tauri::command]
+pub async fn get_event_channel_config(state: State<'_, AppState>) -> Result<EventChannelConfig, String> {
+    Ok(EventChannelConfig::load(&state.db_pool))
+}
+
+/// Save the batching; runs started from now on use it
+#[tauri::command]
+pub async fn set_event_channel_config(state: State<'_, AppState>, config: EventChannelConfig) -> Result<(), String> {
+    config.save(&state.db_pool)
+}
+
 /// Get available models for each provider
+/// The core configuration in effect, with the layer each key came from;
+/// with `agent_id`, as that agent sees it
+#[tauri::command]
+pub async fn get_effective_config(
+    state: State<'_, AppState>,
+    agent_id: Option<String>,
+) -> Result<EffectiveConfig, String> {
+    let Some(agent_id) = agent_id else {
+        return Ok(config::effective());
+    };
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let agent: Agent = agents::table
+            .find(&agent_id)
+            .first(conn)
+            .map_err(|_| "Agent not found".to_string())?;
+        config::effective_for_agent(&agent)
+    })
+    .await
+}
+
+/// Re-read config.toml and the environment now, instead of waiting for the
+/// file watcher
+#[tauri::command]
+pub async fn reload_config(app: tauri::AppHandle) -> Result<ConfigChange, String> {
+    crate::config_watcher::reload(&app)
+}
+
+/// The saved API key of a provider, or its environment variable
+pub(crate) fn provider_api_key(pool: &anyagents::database::DbPool, provider: &str) -> Option<String> {
+    let env_key = match provider {
+        "openai" => "OPENAI_API_KEY",
+        "anthropic" => "ANTHROPIC_API_KEY",
+        "gemini" => "GEMINI_API_KEY",
+        _ => return None,
+    };
+    anyagents::models::settings::get_setting(pool, &format!("{}_api_key", provider))
+        .filter(|k| !k.is_empty())
+        .or_else(|| std::env::var(env_key).ok().filter(|k| !k.is_empty()))
+}
+
+/// Check a key against the provider's model listing, before it is saved.
+/// Without `api_key`, checks the one in use. The model list fetched along
+/// the way is cached for `list_models`.
+#[tauri::command]
+pub async fn validate_api_key(
+    state: State<'_, AppState>,
+    provider: String,
+    api_key: Option<String>,
+) -> Result<(), String> {
+    let api_key = api_key
+        .filter(|k| !k.trim().is_empty())
+        .or_else(|| provider_api_key(&state.db_pool, &provider))
+        .ok_or_else(|| format!("No API key configured for {}", provider))?;
+    anyagents::llm::list_models(&provider, &api_key, true).await.map(|_| ())
+}
+
+/// Chat models the provider's key gives access to, narrowed by `filter`
+#[tauri::command]
+pub async fn list_models(
+    state: State<'_, AppState>,
+    provider: String,
+    filter: Option<ModelFilter>,
+    refresh: Option<bool>,
+) -> Result<Vec<ModelInfo>, String> {
+    let api_key = provider_api_key(&state.db_pool, &provider)
+        .ok_or_else(|| format!("No API key configured for {}", provider))?;
+    let filter = filter.unwrap_or_default();
+    let models = anyagents::llm::list_models(&provider, &api_key, refresh.unwrap_or(false)).await?;
+    Ok(models.into_iter().filter(|model| filter.matches(model)).collect())
+}
+
 #[tauri::command]
 pub async fn get_available_models() -> Result<serde_json::Value, String> {
     Ok(serde_json::json!({
@@ -1,12 +1,16 @@
 use anyagents::models::{
     AgentSkill, AgentSkillAssignment, MarketplaceSkill, NewAgentSkill, NewAgentSkillAssignment,
-    NewSkillFile, SkillFile, UpdateAgentSkill,
+    NewSkillFile, SkillDependencies, SkillFile, UpdateAgentSkill,
 };
 use anyagents::schema;
 use anyagents::skills::docker::DockerSandbox;
-use anyagents::skills::loader::{load_skill_from_directory, load_skill_from_zip, list_marketplace_skills as scan_marketplace_skills};
+use anyagents::skills::marketplace::{self, MarketplaceConfig, RemoteSkill, SkillUpdate};
+use anyagents::skills::harness::{self, SkillTestReport};
+use anyagents::tools::registry::GROUP_SKILLS;
+use anyagents::skills::loader::{load_skill_from_directory, load_skill_from_zip, list_marketplace_skills as scan_marketplace_skills, LoadedSkill};
 use crate::AppState;
 use diesel::prelude::*;
+use serde::Serialize;
 use std::path::Path;
 use tauri::State;
 
@@ -19,29 +23,32 @@ pub async fn get_skills(
 ) -> Result<Vec<AgentSkill>, String> {
     use anyagents::schema::agent_skills::dsl::{agent_skills, enabled, name};
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let mut query = agent_skills.into_boxed();
 
-    let mut query = agent_skills.into_boxed();
-
-    if let Some(true) = enabled_only {
-        query = query.filter(enabled.eq(1));
-    }
+        if let Some(true) = enabled_only {
+            query = query.filter(enabled.eq(1));
+        }
 
-    query
-        .order(name.asc())
-        .load::<AgentSkill>(&mut conn)
-        .map_err(|e| e.to_string())
+        query
+            .order(name.asc())
+            .load::<AgentSkill>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 #[tauri::command]
 pub async fn get_skill(state: State<'_, AppState>, skill_id: String) -> Result<AgentSkill, String> {
     use anyagents::schema::agent_skills::dsl::{agent_skills, id};
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-    agent_skills
-        .filter(id.eq(skill_id))
-        .first::<AgentSkill>(&mut conn)
-        .map_err(|e| format!("Skill not found: {}", e))
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        agent_skills
+            .filter(id.eq(skill_id))
+            .first::<AgentSkill>(conn)
+            .map_err(|e| format!("Skill not found: {}", e))
+    })
+    .await
 }
 
 #[tauri::command]
@@ -52,40 +59,51 @@ pub async fn create_skill(
     description: String,
     skill_content: String,
     additional_files_json: Option<String>,
+    triggers: Option<Vec<String>>,
 ) -> Result<AgentSkill, String> {
     use anyagents::schema::agent_skills;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    let new_skill = NewAgentSkill {
-        id: uuid::Uuid::new_v4().to_string(),
-        name: name_param,
-        display_title,
-        description,
-        skill_content,
-        additional_files_json,
-        enabled: 1,
-        version: 1,
-        created_at: chrono::Utc::now().naive_utc(),
-        updated_at: chrono::Utc::now().naive_utc(),
-        source_path: None,
-        category: Some("General".to_string()),
-        requires_sandbox: 0,
-        sandbox_config: None,
-        execution_mode: "direct".to_string(),
-    };
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let new_skill = NewAgentSkill {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name_param,
+            display_title,
+            description,
+            skill_content,
+            additional_files_json,
+            enabled: 1,
+            version: 1,
+            created_at: chrono::Utc::now().naive_utc(),
+            updated_at: chrono::Utc::now().naive_utc(),
+            source_path: None,
+            category: Some("General".to_string()),
+            requires_sandbox: 0,
+            read_only: 0,
+            sandbox_config: None,
+            execution_mode: "direct".to_string(),
+            triggers: triggers
+                .map(|t| serde_json::to_string(&t).map_err(|e| e.to_string()))
+                .transpose()?,
+            dependencies: None,
+            installed_version: None,
+            invocation: None,
+            examples: None,
+            recipe: None,
+        };
 
-    diesel::insert_into(agent_skills::table)
-        .values(&new_skill)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+        diesel::insert_into(agent_skills::table)
+            .values(&new_skill)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
 
-    let created_id = new_skill.id.clone();
+        let created_id = new_skill.id.clone();
 
-    agent_skills::table
-        .filter(agent_skills::id.eq(created_id))
-        .first::<AgentSkill>(&mut conn)
-        .map_err(|e| e.to_string())
+        agent_skills::table
+            .filter(agent_skills::id.eq(created_id))
+            .first::<AgentSkill>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 #[tauri::command]
@@ -102,70 +120,89 @@ pub async fn update_skill(
     category: Option<String>,
     requires_sandbox: Option<bool>,
     sandbox_config: Option<String>,
+    triggers: Option<Vec<String>>,
+    dependencies: Option<SkillDependencies>,
 ) -> Result<AgentSkill, String> {
     use anyagents::schema::agent_skills::dsl::{agent_skills, id};
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    // Increment version on update
-    let current_version: i32 = agent_skills
-        .filter(id.eq(&skill_id))
-        .select(schema::agent_skills::version)
-        .first(&mut conn)
-        .unwrap_or(1);
-
-    let update = UpdateAgentSkill {
-        name: name_param,
-        display_title,
-        description,
-        skill_content,
-        additional_files_json,
-        enabled: enabled_param.map(|v| if v { 1 } else { 0 }),
-        version: Some(current_version + 1),
-        updated_at: chrono::Utc::now().naive_utc(),
-        source_path: None,
-        category,
-        requires_sandbox: requires_sandbox.map(|v| if v { 1 } else { 0 }),
-        sandbox_config,
-        execution_mode: None,
-    };
-
-    diesel::update(agent_skills.filter(id.eq(&skill_id)))
-        .set(&update)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+    let skill = anyagents::database::blocking(&state.db_pool, move |conn| {
+        // Increment version on update
+        let current_version: i32 = agent_skills
+            .filter(id.eq(&skill_id))
+            .select(schema::agent_skills::version)
+            .first(conn)
+            .unwrap_or(1);
+
+        let update = UpdateAgentSkill {
+            name: name_param,
+            display_title,
+            description,
+            skill_content,
+            additional_files_json,
+            enabled: enabled_param.map(|v| if v { 1 } else { 0 }),
+            version: Some(current_version + 1),
+            updated_at: chrono::Utc::now().naive_utc(),
+            source_path: None,
+            category,
+            requires_sandbox: requires_sandbox.map(|v| if v { 1 } else { 0 }),
+            read_only: None,
+            sandbox_config,
+            execution_mode: None,
+            triggers: triggers
+                .map(|t| serde_json::to_string(&t).map_err(|e| e.to_string()))
+                .transpose()?,
+            dependencies: dependencies
+                .map(|d| serde_json::to_string(&d).map_err(|e| e.to_string()))
+                .transpose()?,
+            installed_version: None,
+            invocation: None,
+            examples: None,
+            recipe: None,
+        };
 
-    agent_skills
-        .filter(id.eq(&skill_id))
-        .first::<AgentSkill>(&mut conn)
-        .map_err(|e| e.to_string())
+        diesel::update(agent_skills.filter(id.eq(&skill_id)))
+            .set(&update)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        agent_skills
+            .filter(id.eq(&skill_id))
+            .first::<AgentSkill>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await?;
+
+    // Running agents pick up the change before their next step
+    anyagents::agents::refresh_live_agents(&state.db_pool, GROUP_SKILLS).await;
+    Ok(skill)
 }
 
 #[tauri::command]
 pub async fn delete_skill(state: State<'_, AppState>, skill_id: String) -> Result<(), String> {
     use anyagents::schema::agent_skills::dsl::{agent_skills, id};
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    // Delete skill files first (cascade should handle this, but explicit is safer)
-    diesel::delete(schema::skill_files::table.filter(schema::skill_files::skill_id.eq(&skill_id)))
-        .execute(&mut conn)
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        // Delete skill files first (cascade should handle this, but explicit is safer)
+        diesel::delete(schema::skill_files::table.filter(schema::skill_files::skill_id.eq(&skill_id)))
+            .execute(conn)
+            .ok();
+
+        // Delete skill assignments
+        diesel::delete(
+            schema::agent_skill_assignments::table
+                .filter(schema::agent_skill_assignments::skill_id.eq(&skill_id)),
+        )
+        .execute(conn)
         .ok();
 
-    // Delete skill assignments
-    diesel::delete(
-        schema::agent_skill_assignments::table
-            .filter(schema::agent_skill_assignments::skill_id.eq(&skill_id)),
-    )
-    .execute(&mut conn)
-    .ok();
-
-    // Delete skill
-    diesel::delete(agent_skills.filter(id.eq(skill_id)))
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+        // Delete skill
+        diesel::delete(agent_skills.filter(id.eq(skill_id)))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
 
-    Ok(())
+        Ok(())
+    })
+    .await
 }
 
 #[tauri::command]
@@ -175,42 +212,54 @@ pub async fn toggle_skill(
 ) -> Result<AgentSkill, String> {
     use anyagents::schema::agent_skills::dsl::{agent_skills, enabled, id};
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    // Get current enabled status
-    let current_enabled: i32 = agent_skills
-        .filter(id.eq(&skill_id))
-        .select(enabled)
-        .first(&mut conn)
-        .map_err(|e| format!("Skill not found: {}", e))?;
-
-    let new_enabled = if current_enabled == 1 { 0 } else { 1 };
-
-    let update = UpdateAgentSkill {
-        name: None,
-        display_title: None,
-        description: None,
-        skill_content: None,
-        additional_files_json: None,
-        enabled: Some(new_enabled),
-        version: None,
-        updated_at: chrono::Utc::now().naive_utc(),
-        source_path: None,
-        category: None,
-        requires_sandbox: None,
-        sandbox_config: None,
-        execution_mode: None,
-    };
-
-    diesel::update(agent_skills.filter(id.eq(&skill_id)))
-        .set(&update)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+    let skill = anyagents::database::blocking(&state.db_pool, move |conn| {
+        // Get current enabled status
+        let current_enabled: i32 = agent_skills
+            .filter(id.eq(&skill_id))
+            .select(enabled)
+            .first(conn)
+            .map_err(|e| format!("Skill not found: {}", e))?;
+
+        let new_enabled = if current_enabled == 1 { 0 } else { 1 };
+
+        let update = UpdateAgentSkill {
+            name: None,
+            display_title: None,
+            description: None,
+            skill_content: None,
+            additional_files_json: None,
+            enabled: Some(new_enabled),
+            version: None,
+            updated_at: chrono::Utc::now().naive_utc(),
+            source_path: None,
+            category: None,
+            requires_sandbox: None,
+            read_only: None,
+            sandbox_config: None,
+            execution_mode: None,
+            triggers: None,
+            dependencies: None,
+            installed_version: None,
+            invocation: None,
+            examples: None,
+            recipe: None,
+        };
 
-    agent_skills
-        .filter(id.eq(&skill_id))
-        .first::<AgentSkill>(&mut conn)
-        .map_err(|e| e.to_string())
+        diesel::update(agent_skills.filter(id.eq(&skill_id)))
+            .set(&update)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        agent_skills
+            .filter(id.eq(&skill_id))
+            .first::<AgentSkill>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await?;
+
+    // Running agents pick up the change before their next step
+    anyagents::agents::refresh_live_agents(&state.db_pool, GROUP_SKILLS).await;
+    Ok(skill)
 }
 
 // ==================== IMPORT COMMANDS ====================
@@ -223,7 +272,7 @@ pub async fn import_skill_from_directory(
     let path = Path::new(&directory_path);
     let loaded = load_skill_from_directory(path)?;
 
-    save_loaded_skill(&state, loaded, Some(directory_path)).await
+    save_loaded_skill(&state, loaded, Some(directory_path), None).await
 }
 
 #[tauri::command]
@@ -234,7 +283,7 @@ pub async fn import_skill_from_zip(
     let path = Path::new(&zip_path);
     let loaded = load_skill_from_zip(path)?;
 
-    save_loaded_skill(&state, loaded, Some(zip_path)).await
+    save_loaded_skill(&state, loaded, Some(zip_path), None).await
 }
 
 /// Helper function to save a loaded skill to the database
@@ -242,11 +291,10 @@ async fn save_loaded_skill(
     state: &State<'_, AppState>,
     loaded: anyagents::skills::loader::LoadedSkill,
     source_path: Option<String>,
+    installed_version: Option<String>,
 ) -> Result<AgentSkill, String> {
     use anyagents::schema::agent_skills;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
     let skill = &loaded.skill;
     let skill_id = uuid::Uuid::new_v4().to_string();
 
@@ -270,20 +318,123 @@ async fn save_loaded_skill(
         source_path,
         category: skill.category.clone(),
         requires_sandbox: if skill.requires_sandbox { 1 } else { 0 },
+        read_only: if skill.read_only { 1 } else { 0 },
         sandbox_config: sandbox_config_json,
         execution_mode: "direct".to_string(),
+        triggers: skill
+            .triggers
+            .as_ref()
+            .map(|t| serde_json::to_string(t).unwrap_or_default()),
+        dependencies: skill
+            .dependencies
+            .as_ref()
+            .filter(|d| !d.is_empty())
+            .map(|d| serde_json::to_string(d).unwrap_or_default()),
+        installed_version,
+        invocation: skill
+            .invocation
+            .as_ref()
+            .map(|i| serde_json::to_string(i).unwrap_or_default()),
+        examples: (!skill.examples.is_empty())
+            .then(|| serde_json::to_string(&skill.examples).unwrap_or_default()),
+        recipe: skill
+            .recipe
+            .as_ref()
+            .map(|r| serde_json::to_string(r).unwrap_or_default()),
     };
 
-    diesel::insert_into(agent_skills::table)
-        .values(&new_skill)
-        .execute(&mut conn)
-        .map_err(|e| format!("Failed to insert skill: {}", e))?;
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        diesel::insert_into(agent_skills::table)
+            .values(&new_skill)
+            .execute(conn)
+            .map_err(|e| format!("Failed to insert skill: {}", e))?;
+
+        insert_skill_files(conn, &skill_id, loaded.files);
+
+        agent_skills::table
+            .filter(agent_skills::id.eq(&skill_id))
+            .first::<AgentSkill>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+/// Replace an installed skill's content and files in place, keeping its id
+/// (and so its agent assignments)
+async fn upgrade_loaded_skill(
+    state: &State<'_, AppState>,
+    existing: &AgentSkill,
+    loaded: anyagents::skills::loader::LoadedSkill,
+    source_path: Option<String>,
+    installed_version: Option<String>,
+) -> Result<AgentSkill, String> {
+    use anyagents::schema::{agent_skills, skill_files};
+
+    let skill = &loaded.skill;
 
-    // Insert skill files
-    for (relative_path, file_content) in loaded.files {
+    let update = UpdateAgentSkill {
+        name: None,
+        display_title: None,
+        description: Some(skill.description.clone()),
+        skill_content: Some(skill.body.clone()),
+        additional_files_json: None,
+        enabled: None,
+        version: Some(existing.version + 1),
+        updated_at: chrono::Utc::now().naive_utc(),
+        source_path,
+        category: skill.category.clone(),
+        requires_sandbox: Some(if skill.requires_sandbox { 1 } else { 0 }),
+        read_only: Some(if skill.read_only { 1 } else { 0 }),
+        sandbox_config: skill
+            .sandbox_config
+            .as_ref()
+            .map(|c| serde_json::to_string(c).unwrap_or_default()),
+        execution_mode: None,
+        triggers: skill
+            .triggers
+            .as_ref()
+            .map(|t| serde_json::to_string(t).unwrap_or_default()),
+        // Always written so a release that drops dependencies clears them
+        dependencies: Some(serde_json::to_string(&skill.dependencies.clone().unwrap_or_default()).unwrap_or_default()),
+        installed_version,
+        invocation: skill
+            .invocation
+            .as_ref()
+            .map(|i| serde_json::to_string(i).unwrap_or_default()),
+        examples: Some(serde_json::to_string(&skill.examples).unwrap_or_default()),
+        recipe: Some(serde_json::to_string(&skill.recipe).unwrap_or_default()),
+    };
+
+    let skill_id = existing.id.clone();
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            diesel::update(agent_skills::table.filter(agent_skills::id.eq(&skill_id)))
+                .set(&update)
+                .execute(conn)?;
+            diesel::delete(skill_files::table.filter(skill_files::skill_id.eq(&skill_id))).execute(conn)?;
+            Ok(())
+        })
+        .map_err(|e| format!("Failed to upgrade skill: {}", e))?;
+
+        insert_skill_files(conn, &skill_id, loaded.files);
+
+        agent_skills::table
+            .filter(agent_skills::id.eq(&skill_id))
+            .first::<AgentSkill>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+fn insert_skill_files(
+    conn: &mut SqliteConnection,
+    skill_id: &str,
+    files: std::collections::HashMap<String, anyagents::skills::loader::SkillFileContent>,
+) {
+    for (relative_path, file_content) in files {
         let file = NewSkillFile {
             id: uuid::Uuid::new_v4().to_string(),
-            skill_id: skill_id.clone(),
+            skill_id: skill_id.to_string(),
             relative_path,
             content: file_content.content,
             file_type: file_content.file_type,
@@ -292,14 +443,9 @@ async fn save_loaded_skill(
 
         diesel::insert_into(schema::skill_files::table)
             .values(&file)
-            .execute(&mut conn)
+            .execute(conn)
             .ok(); // Ignore errors for individual files
     }
-
-    agent_skills::table
-        .filter(agent_skills::id.eq(&skill_id))
-        .first::<AgentSkill>(&mut conn)
-        .map_err(|e| e.to_string())
 }
 
 // ==================== MARKETPLACE COMMANDS ====================
@@ -327,13 +473,13 @@ pub async fn list_marketplace_skills(
         if dir.is_dir() {
             if let Ok(skills) = scan_marketplace_skills(&dir) {
                 // Get installed skill names
-                let installed_names: Vec<String> = {
-                    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-                    schema::agent_skills::table
+                let installed_names: Vec<String> = anyagents::database::blocking(&state.db_pool, |conn| {
+                    Ok(schema::agent_skills::table
                         .select(schema::agent_skills::name)
-                        .load::<String>(&mut conn)
-                        .unwrap_or_default()
-                };
+                        .load::<String>(conn)
+                        .unwrap_or_default())
+                })
+                .await?;
 
                 for skill_info in skills {
                     let is_installed = installed_names.contains(&skill_info.name);
@@ -374,7 +520,7 @@ pub async fn install_marketplace_skill(
         let skill_path = dir.join(&skill_dir_name);
         if skill_path.is_dir() && skill_path.join("SKILL.md").exists() {
             let loaded = load_skill_from_directory(&skill_path)?;
-            return save_loaded_skill(&state, loaded, Some(skill_path.to_string_lossy().to_string()))
+            return save_loaded_skill(&state, loaded, Some(skill_path.to_string_lossy().to_string()), None)
                 .await;
         }
     }
@@ -382,6 +528,136 @@ pub async fn install_marketplace_skill(
     Err(format!("Skill '{}' not found in marketplace", skill_dir_name))
 }
 
+// ==================== REMOTE MARKETPLACE ====================
+
+/// A skill from the remote index, with what is installed locally
+#[derive(Serialize)]
+pub struct RemoteSkillListing {
+    #[serde(flatten)]
+    pub skill: RemoteSkill,
+    pub latest_version: Option<String>,
+    pub installed_skill_id: Option<String>,
+    pub installed_version: Option<String>,
+}
+
+async fn installed_skills(state: &State<'_, AppState>) -> Result<Vec<AgentSkill>, String> {
+    anyagents::database::blocking(&state.db_pool, |conn| {
+        schema::agent_skills::table
+            .load::<AgentSkill>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_skill_marketplace_config(state: State<'_, AppState>) -> Result<MarketplaceConfig, String> {
+    Ok(MarketplaceConfig::load(&state.db_pool))
+}
+
+#[tauri::command]
+pub async fn update_skill_marketplace_config(
+    state: State<'_, AppState>,
+    config: MarketplaceConfig,
+) -> Result<(), String> {
+    config.save(&state.db_pool)
+}
+
+#[tauri::command]
+pub async fn list_remote_skills(state: State<'_, AppState>) -> Result<Vec<RemoteSkillListing>, String> {
+    let index = marketplace::fetch_index(&MarketplaceConfig::load(&state.db_pool)).await?;
+    let installed = installed_skills(&state).await?;
+
+    Ok(index
+        .skills
+        .into_iter()
+        .map(|skill| {
+            let local = installed.iter().find(|s| s.name == skill.name);
+            RemoteSkillListing {
+                latest_version: skill.latest().map(|v| v.version.clone()),
+                installed_skill_id: local.map(|s| s.id.clone()),
+                installed_version: local.and_then(|s| s.installed_version.clone()),
+                skill,
+            }
+        })
+        .collect())
+}
+
+/// Install a skill from the remote marketplace, or upgrade it in place if a
+/// skill with that name is already installed. `version` defaults to the latest.
+#[tauri::command]
+pub async fn install_remote_skill(
+    state: State<'_, AppState>,
+    name_param: String,
+    version: Option<String>,
+) -> Result<AgentSkill, String> {
+    let index = marketplace::fetch_index(&MarketplaceConfig::load(&state.db_pool)).await?;
+    let remote = index
+        .find(&name_param)
+        .ok_or_else(|| format!("Skill '{}' is not in the marketplace", name_param))?;
+    let release = remote.version(version.as_deref()).ok_or_else(|| match &version {
+        Some(v) => format!("Skill '{}' has no version {}", name_param, v),
+        None => format!("Skill '{}' has no published versions", name_param),
+    })?;
+
+    let loaded = marketplace::download_skill(release).await?;
+    if loaded.skill.name != remote.name {
+        return Err(format!(
+            "Downloaded package is '{}', expected '{}'",
+            loaded.skill.name, remote.name
+        ));
+    }
+
+    let source = Some(release.url.clone());
+    let installed_version = Some(release.version.clone());
+    match installed_skills(&state).await?.into_iter().find(|s| s.name == remote.name) {
+        Some(existing) => upgrade_loaded_skill(&state, &existing, loaded, source, installed_version).await,
+        None => save_loaded_skill(&state, loaded, source, installed_version).await,
+    }
+}
+
+/// Installed marketplace skills with a newer version in the index
+#[tauri::command]
+pub async fn check_skill_updates(state: State<'_, AppState>) -> Result<Vec<SkillUpdate>, String> {
+    let index = marketplace::fetch_index(&MarketplaceConfig::load(&state.db_pool)).await?;
+    let installed: Vec<(String, String, String)> = installed_skills(&state).await?
+        .into_iter()
+        .filter_map(|s| Some((s.id, s.name, s.installed_version?)))
+        .collect();
+    Ok(marketplace::find_updates(&installed, &index))
+}
+
+// ==================== SKILL TESTS ====================
+
+/// Run the examples declared in a skill's SKILL.md against temp workspaces.
+/// `execution_mode` defaults to "flexible" (the skill's own preference);
+/// `dry_run` only validates arguments and renders commands.
+#[tauri::command]
+pub async fn test_skill(
+    state: State<'_, AppState>,
+    skill_id: String,
+    dry_run: Option<bool>,
+    execution_mode: Option<String>,
+) -> Result<SkillTestReport, String> {
+    let loaded = anyagents::database::blocking(&state.db_pool, move |conn| {
+        let skill = schema::agent_skills::table
+            .find(&skill_id)
+            .first::<AgentSkill>(conn)
+            .map_err(|e| format!("Skill not found: {}", e))?;
+        let files = schema::skill_files::table
+            .filter(schema::skill_files::skill_id.eq(&skill_id))
+            .load::<SkillFile>(conn)
+            .map_err(|e| e.to_string())?;
+        Ok(LoadedSkill::from_db(&skill, files))
+    })
+    .await?;
+
+    if loaded.skill.examples.is_empty() {
+        return Err(format!("Skill '{}' declares no examples to test", loaded.skill.name));
+    }
+    let mode = execution_mode.unwrap_or_else(|| "flexible".to_string());
+    Ok(harness::test_skill(&loaded, &mode, dry_run.unwrap_or(false)).await)
+}
+
 // ==================== SKILL FILES ====================
 
 #[tauri::command]
@@ -389,13 +665,14 @@ pub async fn get_skill_files(
     state: State<'_, AppState>,
     skill_id: String,
 ) -> Result<Vec<SkillFile>, String> {
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    schema::skill_files::table
-        .filter(schema::skill_files::skill_id.eq(skill_id))
-        .order(schema::skill_files::relative_path.asc())
-        .load::<SkillFile>(&mut conn)
-        .map_err(|e| e.to_string())
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        schema::skill_files::table
+            .filter(schema::skill_files::skill_id.eq(skill_id))
+            .order(schema::skill_files::relative_path.asc())
+            .load::<SkillFile>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 // ==================== AGENT SKILL ASSIGNMENTS ====================
@@ -406,30 +683,37 @@ pub async fn assign_skill_to_agent(
     agent_id: String,
     skill_id: String,
 ) -> Result<(), String> {
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+    let assigned = agent_id.clone();
+    let inserted = anyagents::database::blocking(&state.db_pool, move |conn| {
+        // Check if assignment already exists
+        let exists: bool = schema::agent_skill_assignments::table
+            .filter(schema::agent_skill_assignments::agent_id.eq(&assigned))
+            .filter(schema::agent_skill_assignments::skill_id.eq(&skill_id))
+            .first::<AgentSkillAssignment>(conn)
+            .is_ok();
+
+        if exists {
+            return Ok(false); // Already assigned
+        }
 
-    // Check if assignment already exists
-    let exists: bool = schema::agent_skill_assignments::table
-        .filter(schema::agent_skill_assignments::agent_id.eq(&agent_id))
-        .filter(schema::agent_skill_assignments::skill_id.eq(&skill_id))
-        .first::<AgentSkillAssignment>(&mut conn)
-        .is_ok();
+        let assignment = NewAgentSkillAssignment {
+            agent_id: assigned,
+            skill_id,
+            created_at: chrono::Utc::now().naive_utc(),
+        };
 
-    if exists {
-        return Ok(()); // Already assigned
+        diesel::insert_into(schema::agent_skill_assignments::table)
+            .values(&assignment)
+            .execute(conn)
+            .map_err(|e| format!("Failed to assign skill: {}", e))?;
+        Ok(true)
+    })
+    .await?;
+    if !inserted {
+        return Ok(());
     }
 
-    let assignment = NewAgentSkillAssignment {
-        agent_id,
-        skill_id,
-        created_at: chrono::Utc::now().naive_utc(),
-    };
-
-    diesel::insert_into(schema::agent_skill_assignments::table)
-        .values(&assignment)
-        .execute(&mut conn)
-        .map_err(|e| format!("Failed to assign skill: {}", e))?;
-
+    refresh_running_agent(&state, &agent_id).await;
     Ok(())
 }
 
@@ -439,43 +723,54 @@ pub async fn unassign_skill_from_agent(
     agent_id: String,
     skill_id: String,
 ) -> Result<(), String> {
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    diesel::delete(
-        schema::agent_skill_assignments::table
-            .filter(schema::agent_skill_assignments::agent_id.eq(agent_id))
-            .filter(schema::agent_skill_assignments::skill_id.eq(skill_id)),
-    )
-    .execute(&mut conn)
-    .map_err(|e| format!("Failed to unassign skill: {}", e))?;
-
+    let unassigned = agent_id.clone();
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        diesel::delete(
+            schema::agent_skill_assignments::table
+                .filter(schema::agent_skill_assignments::agent_id.eq(&unassigned))
+                .filter(schema::agent_skill_assignments::skill_id.eq(skill_id)),
+        )
+        .execute(conn)
+        .map_err(|e| format!("Failed to unassign skill: {}", e))
+    })
+    .await?;
+
+    refresh_running_agent(&state, &agent_id).await;
     Ok(())
 }
 
+/// Reload the skill tools of `agent_id` if it is running right now
+async fn refresh_running_agent(state: &State<'_, AppState>, agent_id: &str) {
+    if let Err(e) = anyagents::agents::refresh_agent_tools(&state.db_pool, agent_id, GROUP_SKILLS).await {
+        log::warn!("Failed to reload skills of agent {}: {}", agent_id, e);
+    }
+}
+
 #[tauri::command]
 pub async fn get_agent_skills(
     state: State<'_, AppState>,
     agent_id: String,
 ) -> Result<Vec<AgentSkill>, String> {
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    // Get skill IDs assigned to this agent
-    let skill_ids: Vec<String> = schema::agent_skill_assignments::table
-        .filter(schema::agent_skill_assignments::agent_id.eq(&agent_id))
-        .select(schema::agent_skill_assignments::skill_id)
-        .load(&mut conn)
-        .map_err(|e| e.to_string())?;
-
-    if skill_ids.is_empty() {
-        return Ok(vec![]);
-    }
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        // Get skill IDs assigned to this agent
+        let skill_ids: Vec<String> = schema::agent_skill_assignments::table
+            .filter(schema::agent_skill_assignments::agent_id.eq(&agent_id))
+            .select(schema::agent_skill_assignments::skill_id)
+            .load(conn)
+            .map_err(|e| e.to_string())?;
+
+        if skill_ids.is_empty() {
+            return Ok(vec![]);
+        }
 
-    // Get the actual skills
-    schema::agent_skills::table
-        .filter(schema::agent_skills::id.eq_any(skill_ids))
-        .order(schema::agent_skills::name.asc())
-        .load::<AgentSkill>(&mut conn)
-        .map_err(|e| e.to_string())
+        // Get the actual skills
+        schema::agent_skills::table
+            .filter(schema::agent_skills::id.eq_any(skill_ids))
+            .order(schema::agent_skills::name.asc())
+            .load::<AgentSkill>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 // ==================== DOCKER SANDBOX ====================
@@ -485,6 +780,18 @@ pub async fn check_docker_available() -> Result<bool, String> {
     Ok(DockerSandbox::check_available().await)
 }
 
+/// Check whether strict (microVM) isolation can run on this machine
+#[tauri::command]
+pub async fn check_microvm_available() -> Result<bool, String> {
+    match anyagents::skills::MicroVmConfig::default().check() {
+        Ok(()) => Ok(true),
+        Err(reason) => {
+            log::info!("MicroVM isolation unavailable: {}", reason);
+            Ok(false)
+        }
+    }
+}
+
 // ==================== AGENT SCOPE ====================
 
 #[tauri::command]
@@ -496,25 +803,26 @@ pub async fn update_agent_scope(
 ) -> Result<(), String> {
     use anyagents::schema::agents::dsl::agents;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    // Validate scope_type
-    if scope_type != "global" && scope_type != "workspace" {
-        return Err("Invalid scope_type. Must be 'global' or 'workspace'".to_string());
-    }
-
-    // If workspace scope, require workspace_path
-    if scope_type == "workspace" && workspace_path.is_none() {
-        return Err("workspace_path is required for 'workspace' scope".to_string());
-    }
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        // Validate scope_type
+        if scope_type != "global" && scope_type != "workspace" {
+            return Err("Invalid scope_type. Must be 'global' or 'workspace'".to_string());
+        }
 
-    diesel::update(agents.filter(schema::agents::id.eq(&agent_id)))
-        .set((
-            schema::agents::scope_type.eq(Some(scope_type)),
-            schema::agents::workspace_path.eq(workspace_path),
-        ))
-        .execute(&mut conn)
-        .map_err(|e| format!("Failed to update agent scope: {}", e))?;
+        // If workspace scope, require workspace_path
+        if scope_type == "workspace" && workspace_path.is_none() {
+            return Err("workspace_path is required for 'workspace' scope".to_string());
+        }
 
-    Ok(())
+        diesel::update(agents.filter(schema::agents::id.eq(&agent_id)))
+            .set((
+                schema::agents::scope_type.eq(Some(scope_type)),
+                schema::agents::workspace_path.eq(workspace_path),
+            ))
+            .execute(conn)
+            .map_err(|e| format!("Failed to update agent scope: {}", e))?;
+
+        Ok(())
+    })
+    .await
 }
@@ -0,0 +1,154 @@
+use anyagents::models::{SlackConfig, NewSlackConfig, UpdateSlackConfig};
+use crate::AppState;
+use diesel::prelude::*;
+use tauri::State;
+
+#[tauri::command]
+pub async fn create_slack_config(
+    state: State<'_, AppState>,
+    app_token: String,
+    bot_token: String,
+    agent_id: String,
+    allowed_channel_ids: Option<String>,
+) -> Result<SlackConfig, String> {
+    use anyagents::schema::slack_configs;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let new_config = NewSlackConfig {
+            id: uuid::Uuid::new_v4().to_string(),
+            app_token,
+            bot_token,
+            agent_id,
+            is_active: 0,
+            allowed_channel_ids,
+            created_at: chrono::Utc::now().naive_utc(),
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
+
+        diesel::insert_into(slack_configs::table)
+            .values(&new_config)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        slack_configs::table
+            .filter(slack_configs::id.eq(&new_config.id))
+            .first::<SlackConfig>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_slack_configs(state: State<'_, AppState>) -> Result<Vec<SlackConfig>, String> {
+    use anyagents::schema::slack_configs::dsl::*;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        slack_configs
+            .load::<SlackConfig>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_slack_config(
+    state: State<'_, AppState>,
+    config_id: String,
+) -> Result<SlackConfig, String> {
+    use anyagents::schema::slack_configs::dsl::*;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        slack_configs
+            .filter(id.eq(config_id))
+            .first::<SlackConfig>(conn)
+            .map_err(|e| format!("Config not found: {}", e))
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn update_slack_config(
+    state: State<'_, AppState>,
+    config_id: String,
+    new_app_token: Option<String>,
+    new_bot_token: Option<String>,
+    new_agent_id: Option<String>,
+    new_is_active: Option<i32>,
+    new_allowed_channel_ids: Option<String>,
+) -> Result<SlackConfig, String> {
+    use anyagents::schema::slack_configs::dsl::*;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let update = UpdateSlackConfig {
+            app_token: new_app_token,
+            bot_token: new_bot_token,
+            agent_id: new_agent_id,
+            is_active: new_is_active,
+            allowed_channel_ids: new_allowed_channel_ids,
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
+
+        diesel::update(slack_configs.filter(id.eq(&config_id)))
+            .set(&update)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        slack_configs
+            .filter(id.eq(&config_id))
+            .first::<SlackConfig>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn delete_slack_config(
+    state: State<'_, AppState>,
+    config_id: String,
+) -> Result<(), String> {
+    use anyagents::schema::{slack_conversation_sessions, slack_configs};
+
+    let _ = state.slack_manager.stop_bot(&config_id).await;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        diesel::delete(slack_conversation_sessions::table.filter(slack_conversation_sessions::config_id.eq(&config_id)))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+        diesel::delete(slack_configs::table.filter(slack_configs::id.eq(&config_id)))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn start_slack_bot(state: State<'_, AppState>, config_id: String) -> Result<(), String> {
+    state.slack_manager.start_bot(&config_id).await
+}
+
+#[tauri::command]
+pub async fn stop_slack_bot(state: State<'_, AppState>, config_id: String) -> Result<(), String> {
+    state.slack_manager.stop_bot(&config_id).await
+}
+
+#[derive(serde::Serialize)]
+pub struct SlackBotStatus {
+    pub config_id: String,
+    pub is_running: bool,
+}
+
+#[tauri::command]
+pub async fn get_slack_bot_status(
+    state: State<'_, AppState>,
+    config_id: String,
+) -> Result<SlackBotStatus, String> {
+    let is_running = state.slack_manager.is_bot_running(&config_id).await;
+    Ok(SlackBotStatus { config_id, is_running })
+}
+
+#[tauri::command]
+pub async fn get_running_slack_bots(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    Ok(state.slack_manager.get_running_bot_ids().await)
+}
@@ -1,6 +1,6 @@
 use tauri::{command, State};
 use crate::AppState;
-use crate::models::tasks::{Task, NewTask, UpdateTask};
+use crate::models::tasks::{Task, NewTask, TaskRun, UpdateTask};
 use uuid::Uuid;
 use chrono::Utc;
 use diesel::prelude::*;
@@ -31,26 +31,32 @@ pub async fn create_task(
         updated_at: now,
     };
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    diesel::insert_into(tasks)
-        .values(&new_task)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
-
-    // Return the created task
-    // We construct it manually to return it immediately without another query
-    Ok(Task {
-        id: new_task.id,
-        title: new_task.title,
-        description: new_task.description,
-        status: new_task.status,
-        priority: new_task.priority,
-        session_id: new_task.session_id,
-        agent_id: new_task.agent_id,
-        created_at: new_task.created_at,
-        updated_at: new_task.updated_at,
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        diesel::insert_into(tasks)
+            .values(&new_task)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        // Return the created task
+        // We construct it manually to return it immediately without another query
+        Ok(Task {
+            id: new_task.id,
+            title: new_task.title,
+            description: new_task.description,
+            status: new_task.status,
+            priority: new_task.priority,
+            session_id: new_task.session_id,
+            agent_id: new_task.agent_id,
+            created_at: new_task.created_at,
+            updated_at: new_task.updated_at,
+            schedule: None,
+            prompt_template: None,
+            schedule_enabled: 0,
+            next_run_at: None,
+            last_run_at: None,
+        })
     })
+    .await
 }
 
 #[command]
@@ -61,24 +67,25 @@ pub async fn list_tasks(
 ) -> Result<Vec<Task>, String> {
     use crate::schema::tasks::dsl::*;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-    
-    let mut query = tasks.into_boxed();
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let mut query = tasks.into_boxed();
 
-    if let Some(sid) = session_id_filter {
-        query = query.filter(session_id.eq(sid));
-    }
+        if let Some(sid) = session_id_filter {
+            query = query.filter(session_id.eq(sid));
+        }
 
-    if let Some(s_status) = status_filter {
-         query = query.filter(status.eq(s_status));
-    }
+        if let Some(s_status) = status_filter {
+             query = query.filter(status.eq(s_status));
+        }
 
-    let results = query
-        .order(created_at.desc())
-        .load::<Task>(&mut conn)
-        .map_err(|e| e.to_string())?;
+        let results = query
+            .order(created_at.desc())
+            .load::<Task>(conn)
+            .map_err(|e| e.to_string())?;
 
-    Ok(results)
+        Ok(results)
+    })
+    .await
 }
 
 #[command]
@@ -89,23 +96,24 @@ pub async fn update_task(
 ) -> Result<Task, String> {
     use crate::schema::tasks::dsl::*;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        // Add updated_at
+        let mut update_data = data;
+        update_data.updated_at = Utc::now().to_rfc3339();
 
-    // Add updated_at
-    let mut update_data = data;
-    update_data.updated_at = Utc::now().to_rfc3339();
+        diesel::update(tasks.find(task_id.clone()))
+            .set(&update_data)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
 
-    diesel::update(tasks.find(task_id.clone()))
-        .set(&update_data)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+        let updated_task = tasks
+            .find(task_id)
+            .first::<Task>(conn)
+            .map_err(|e| e.to_string())?;
 
-    let updated_task = tasks
-        .find(task_id)
-        .first::<Task>(&mut conn)
-        .map_err(|e| e.to_string())?;
-
-    Ok(updated_task)
+        Ok(updated_task)
+    })
+    .await
 }
 
 #[command]
@@ -115,11 +123,99 @@ pub async fn delete_task(
 ) -> Result<(), String> {
     use crate::schema::tasks::dsl::*;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        diesel::delete(tasks.find(task_id))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    })
+    .await
+}
+
+/// Configure (or clear) a task's schedule. `schedule` is a cron expression or "every 30m".
+#[command]
+pub async fn set_task_schedule(
+    state: State<'_, AppState>,
+    task_id: String,
+    schedule_val: Option<String>,
+    prompt_template_val: Option<String>,
+    enabled: bool,
+) -> Result<Task, String> {
+    use crate::schema::tasks::dsl::*;
+
+    let schedule_val = schedule_val.filter(|s| !s.trim().is_empty());
+    let next = match &schedule_val {
+        Some(s) => crate::scheduler::next_run_at(s, Utc::now())?,
+        None => None,
+    };
+    if enabled && schedule_val.is_none() {
+        return Err("A schedule is required to enable it".to_string());
+    }
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let existing = tasks.find(&task_id).first::<Task>(conn).map_err(|e| e.to_string())?;
+        if enabled && existing.agent_id.is_none() {
+            return Err("Assign an agent to the task before scheduling it".to_string());
+        }
+
+        diesel::update(tasks.find(&task_id))
+            .set((
+                schedule.eq(&schedule_val),
+                prompt_template.eq(&prompt_template_val),
+                schedule_enabled.eq(enabled as i32),
+                next_run_at.eq(&next),
+                updated_at.eq(Utc::now().to_rfc3339()),
+            ))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        tasks.find(task_id).first::<Task>(conn).map_err(|e| e.to_string())
+    })
+    .await
+}
+
+/// Run history of a task, newest first
+#[command]
+pub async fn list_task_runs(
+    state: State<'_, AppState>,
+    task_id: String,
+    limit: Option<i64>,
+) -> Result<Vec<TaskRun>, String> {
+    use crate::schema::task_runs;
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        task_runs::table
+            .filter(task_runs::task_id.eq(task_id))
+            .order(task_runs::started_at.desc())
+            .limit(limit.unwrap_or(50))
+            .load::<TaskRun>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
 
-    diesel::delete(tasks.find(task_id))
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+/// Run a task immediately, outside its schedule. Returns once the run has started.
+#[command]
+pub async fn run_task_now(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    task_id: String,
+) -> Result<(), String> {
+    use crate::schema::tasks::dsl::*;
+
+    let task = anyagents::database::blocking(&state.db_pool, move |conn| {
+        tasks.find(task_id).first::<Task>(conn).map_err(|e| e.to_string())
+    })
+    .await?;
+    if task.agent_id.is_none() {
+        return Err("Task has no agent".to_string());
+    }
 
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = crate::scheduler::run_task(&app, task).await {
+            log::error!("Manual task run failed: {}", e);
+        }
+    });
     Ok(())
 }
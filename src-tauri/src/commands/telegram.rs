@@ -9,32 +9,41 @@ pub async fn create_telegram_config(
     bot_token: String,
     agent_id: String,
     allowed_chat_ids: Option<String>,
+    voice_replies: Option<bool>,
+    group_allowlists: Option<String>,
 ) -> Result<TelegramConfig, String> {
     use anyagents::schema::telegram_configs;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    let new_config = NewTelegramConfig {
-        id: uuid::Uuid::new_v4().to_string(),
-        bot_token,
-        agent_id,
-        is_active: 0,
-        allowed_chat_ids,
-        created_at: chrono::Utc::now().naive_utc(),
-        updated_at: chrono::Utc::now().naive_utc(),
-    };
-
-    diesel::insert_into(telegram_configs::table)
-        .values(&new_config)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
-
-    let created_id = new_config.id.clone();
-
-    telegram_configs::table
-        .filter(telegram_configs::id.eq(created_id))
-        .first::<TelegramConfig>(&mut conn)
-        .map_err(|e| e.to_string())
+    if let Some(lists) = &group_allowlists {
+        crate::telegram::GroupAllowlists::parse(lists)?;
+    }
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let new_config = NewTelegramConfig {
+            id: uuid::Uuid::new_v4().to_string(),
+            bot_token,
+            agent_id,
+            is_active: 0,
+            allowed_chat_ids,
+            created_at: chrono::Utc::now().naive_utc(),
+            updated_at: chrono::Utc::now().naive_utc(),
+            voice_replies: voice_replies.unwrap_or(false) as i32,
+            group_allowlists,
+        };
+
+        diesel::insert_into(telegram_configs::table)
+            .values(&new_config)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        let created_id = new_config.id.clone();
+
+        telegram_configs::table
+            .filter(telegram_configs::id.eq(created_id))
+            .first::<TelegramConfig>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 #[tauri::command]
@@ -43,10 +52,12 @@ pub async fn get_telegram_configs(
 ) -> Result<Vec<TelegramConfig>, String> {
     use anyagents::schema::telegram_configs::dsl::*;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-    telegram_configs
-        .load::<TelegramConfig>(&mut conn)
-        .map_err(|e| e.to_string())
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        telegram_configs
+            .load::<TelegramConfig>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 #[tauri::command]
@@ -56,11 +67,13 @@ pub async fn get_telegram_config(
 ) -> Result<TelegramConfig, String> {
     use anyagents::schema::telegram_configs::dsl::*;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-    telegram_configs
-        .filter(id.eq(config_id))
-        .first::<TelegramConfig>(&mut conn)
-        .map_err(|e| format!("Config not found: {}", e))
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        telegram_configs
+            .filter(id.eq(config_id))
+            .first::<TelegramConfig>(conn)
+            .map_err(|e| format!("Config not found: {}", e))
+    })
+    .await
 }
 
 #[tauri::command]
@@ -71,28 +84,37 @@ pub async fn update_telegram_config(
     new_agent_id: Option<String>,
     new_is_active: Option<i32>,
     new_allowed_chat_ids: Option<String>,
+    new_voice_replies: Option<bool>,
+    new_group_allowlists: Option<String>,
 ) -> Result<TelegramConfig, String> {
     use anyagents::schema::telegram_configs::dsl::*;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-
-    let update = UpdateTelegramConfig {
-        bot_token: new_bot_token,
-        agent_id: new_agent_id,
-        is_active: new_is_active,
-        allowed_chat_ids: new_allowed_chat_ids,
-        updated_at: chrono::Utc::now().naive_utc(),
-    };
-
-    diesel::update(telegram_configs.filter(id.eq(&config_id)))
-        .set(&update)
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
-
-    telegram_configs
-        .filter(id.eq(&config_id))
-        .first::<TelegramConfig>(&mut conn)
-        .map_err(|e| e.to_string())
+    if let Some(lists) = &new_group_allowlists {
+        crate::telegram::GroupAllowlists::parse(lists)?;
+    }
+
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        let update = UpdateTelegramConfig {
+            bot_token: new_bot_token,
+            agent_id: new_agent_id,
+            is_active: new_is_active,
+            allowed_chat_ids: new_allowed_chat_ids,
+            voice_replies: new_voice_replies.map(|v| v as i32),
+            group_allowlists: new_group_allowlists,
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
+
+        diesel::update(telegram_configs.filter(id.eq(&config_id)))
+            .set(&update)
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
+
+        telegram_configs
+            .filter(id.eq(&config_id))
+            .first::<TelegramConfig>(conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 #[tauri::command]
@@ -105,12 +127,14 @@ pub async fn delete_telegram_config(
     // Stop bot if running
     let _ = state.telegram_manager.stop_bot(&config_id).await;
 
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-    diesel::delete(telegram_configs.filter(id.eq(&config_id)))
-        .execute(&mut conn)
-        .map_err(|e| e.to_string())?;
+    anyagents::database::blocking(&state.db_pool, move |conn| {
+        diesel::delete(telegram_configs.filter(id.eq(&config_id)))
+            .execute(conn)
+            .map_err(|e| e.to_string())?;
 
-    Ok(())
+        Ok(())
+    })
+    .await
 }
 
 #[tauri::command]
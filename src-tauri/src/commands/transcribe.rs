@@ -1,10 +1,15 @@
-use tauri::{command, Emitter};
+use tauri::{command, AppHandle, Emitter, Manager};
 use transcribe_rs::{TranscriptionEngine, engines::parakeet::ParakeetEngine};
+use crate::dictation::{DictationEvent, DictationInput};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::fs;
 use std::io::Write;
+use std::sync::Arc;
+use serde::Serialize;
 use serde_json::json;
 use futures::StreamExt;
+use tokio::sync::{mpsc, oneshot, Mutex};
 
 // Constants for model
 const PARAKEET_MODEL_URL: &str = "https://blob.handy.computer/parakeet-v3-int8.tar.gz";
@@ -169,3 +174,78 @@ pub async fn transcribe_file(path: String) -> Result<String, String> {
         .map_err(|e| e.to_string())?;
     Ok(result.text)
 }
+
+/// Open dictations, by id; `push_dictation_audio` feeds them
+pub type DictationMap = Arc<Mutex<HashMap<String, mpsc::UnboundedSender<DictationInput>>>>;
+
+#[derive(Clone, Serialize)]
+struct DictationEventPayload {
+    dictation_id: String,
+    event: DictationEvent,
+}
+
+/// Start live dictation. Send 16-bit mono PCM chunks (at `sample_rate`, default
+/// 16 kHz) with `push_dictation_audio`; transcripts arrive as `dictation_event`.
+#[command]
+pub async fn start_dictation(
+    app_handle: AppHandle,
+    sample_rate: Option<u32>,
+) -> Result<String, String> {
+    let model_path = find_model_path()?
+        .ok_or("Model not found. Please download the model first.")?;
+    let sample_rate = sample_rate.unwrap_or(16000);
+    if !(8000..=192000).contains(&sample_rate) {
+        return Err(format!("Unsupported sample rate: {}", sample_rate));
+    }
+
+    let dictation_id = uuid::Uuid::new_v4().to_string();
+    let (tx, rx) = mpsc::unbounded_channel();
+    let emitter = app_handle.clone();
+    let id = dictation_id.clone();
+    crate::dictation::spawn(model_path, sample_rate, rx, move |event| {
+        let payload = DictationEventPayload {
+            dictation_id: id.clone(),
+            event,
+        };
+        if let Err(e) = emitter.emit("dictation_event", &payload) {
+            log::error!("Failed to emit dictation event: {}", e);
+        }
+    })
+    .await?;
+
+    let dictations: DictationMap = app_handle.state::<DictationMap>().inner().clone();
+    dictations.lock().await.insert(dictation_id.clone(), tx);
+    Ok(dictation_id)
+}
+
+#[command]
+pub async fn push_dictation_audio(
+    app_handle: AppHandle,
+    dictation_id: String,
+    audio_data: Vec<u8>,
+) -> Result<(), String> {
+    let dictations: DictationMap = app_handle.state::<DictationMap>().inner().clone();
+    let dictations = dictations.lock().await;
+    let sender = dictations
+        .get(&dictation_id)
+        .ok_or_else(|| format!("No active dictation: {}", dictation_id))?;
+    sender
+        .send(DictationInput::Audio(audio_data))
+        .map_err(|e| format!("Failed to send audio: {}", e))
+}
+
+/// Stop dictation and return the whole transcript
+#[command]
+pub async fn stop_dictation(app_handle: AppHandle, dictation_id: String) -> Result<String, String> {
+    let dictations: DictationMap = app_handle.state::<DictationMap>().inner().clone();
+    let sender = dictations
+        .lock()
+        .await
+        .remove(&dictation_id)
+        .ok_or_else(|| format!("No active dictation: {}", dictation_id))?;
+    let (tx, rx) = oneshot::channel();
+    sender
+        .send(DictationInput::Stop(tx))
+        .map_err(|_| "Dictation already ended".to_string())?;
+    rx.await.map_err(|_| "Dictation ended without a transcript".to_string())
+}
@@ -0,0 +1,84 @@
+use crate::AppState;
+use anyagents::models::trigger::{self, AgentTrigger, NewAgentTrigger, UpdateAgentTrigger};
+use tauri::State;
+
+const DEFAULT_MAX_RUNS_PER_HOUR: i32 = 6;
+
+fn validate_pattern(kind: &str, pattern: Option<&str>) -> Result<(), String> {
+    let Some(pattern) = pattern.filter(|p| !p.trim().is_empty()) else {
+        return Ok(());
+    };
+    match kind {
+        "file_change" => glob::Pattern::new(pattern)
+            .map(|_| ())
+            .map_err(|e| format!("Invalid glob pattern: {}", e)),
+        _ => regex::Regex::new(pattern)
+            .map(|_| ())
+            .map_err(|e| format!("Invalid regex: {}", e)),
+    }
+}
+
+/// File watchers follow the enabled file_change triggers
+fn refresh_watchers(state: &State<'_, AppState>) {
+    if let Err(e) = state.trigger_engine.refresh_watchers(&state.db_pool) {
+        log::error!("Failed to refresh file watchers: {}", e);
+    }
+}
+
+#[tauri::command]
+pub async fn list_agent_triggers(
+    state: State<'_, AppState>,
+    agent_id: String,
+) -> Result<Vec<AgentTrigger>, String> {
+    trigger::get_agent_triggers(&state.db_pool, &agent_id)
+}
+
+/// `kind` is "file_change" (glob `pattern` on workspace paths), "mail" or
+/// "telegram" (regex `pattern` on the message)
+#[tauri::command]
+pub async fn create_agent_trigger(
+    state: State<'_, AppState>,
+    agent_id: String,
+    kind: String,
+    pattern: Option<String>,
+    prompt_template: Option<String>,
+    max_runs_per_hour: Option<i32>,
+) -> Result<AgentTrigger, String> {
+    validate_pattern(&kind, pattern.as_deref())?;
+    let created = trigger::create_trigger(
+        &state.db_pool,
+        NewAgentTrigger {
+            id: uuid::Uuid::new_v4().to_string(),
+            agent_id,
+            kind,
+            pattern,
+            prompt_template,
+            enabled: 1,
+            max_runs_per_hour: max_runs_per_hour.unwrap_or(DEFAULT_MAX_RUNS_PER_HOUR),
+        },
+    )?;
+    refresh_watchers(&state);
+    Ok(created)
+}
+
+#[tauri::command]
+pub async fn update_agent_trigger(
+    state: State<'_, AppState>,
+    trigger_id: String,
+    data: UpdateAgentTrigger,
+) -> Result<AgentTrigger, String> {
+    if let Some(pattern) = &data.pattern {
+        let existing = trigger::get_trigger(&state.db_pool, &trigger_id)?;
+        validate_pattern(&existing.kind, pattern.as_deref())?;
+    }
+    let updated = trigger::update_trigger(&state.db_pool, &trigger_id, data)?;
+    refresh_watchers(&state);
+    Ok(updated)
+}
+
+#[tauri::command]
+pub async fn delete_agent_trigger(state: State<'_, AppState>, trigger_id: String) -> Result<(), String> {
+    trigger::delete_trigger(&state.db_pool, &trigger_id)?;
+    refresh_watchers(&state);
+    Ok(())
+}
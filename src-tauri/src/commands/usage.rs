@@ -0,0 +1,14 @@
+use crate::AppState;
+use anyagents::models::usage::{self, UsageGroupBy, UsageRange, UsageStats};
+use tauri::State;
+
+/// Jobs, tool calls, tokens and estimated cost for the usage dashboard,
+/// grouped by day, agent, provider or model
+#[tauri::command]
+pub async fn get_usage_stats(
+    state: State<'_, AppState>,
+    range: Option<UsageRange>,
+    group_by: UsageGroupBy,
+) -> Result<UsageStats, String> {
+    usage::usage_stats(&state.db_pool, range.unwrap_or_default(), group_by)
+}
@@ -30,11 +30,13 @@ pub async fn start_voice_call(
     log::info!("Starting voice call with agent: {}", agent_id);
 
     // Get agent details
-    let mut conn = state.db_pool.get().map_err(|e| e.to_string())?;
-    let agent = agents::table
-        .filter(agents::id.eq(&agent_id))
-        .first::<Agent>(&mut conn)
-        .map_err(|e| format!("Agent not found: {}", e))?;
+    let agent = anyagents::database::blocking(&state.db_pool, move |conn| {
+        agents::table
+            .filter(agents::id.eq(&agent_id))
+            .first::<Agent>(conn)
+            .map_err(|e| format!("Agent not found: {}", e))
+    })
+    .await?;
 
     // Generate session ID
     let session_id = uuid::Uuid::new_v4().to_string();
@@ -0,0 +1,36 @@
+use crate::webhooks::{generate_token, WebhookConfig, WebhookServer};
+use crate::AppState;
+use tauri::{AppHandle, State};
+
+#[tauri::command]
+pub async fn get_webhook_config(state: State<'_, AppState>) -> Result<WebhookConfig, String> {
+    Ok(WebhookConfig::load(&state.db_pool))
+}
+
+/// Save the listener settings and restart it; a token is generated the first
+/// time the listener is enabled, or on request
+#[tauri::command]
+pub async fn update_webhook_config(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    server: State<'_, WebhookServer>,
+    enabled: bool,
+    port: Option<u16>,
+    regenerate_token: Option<bool>,
+) -> Result<WebhookConfig, String> {
+    let mut config = WebhookConfig::load(&state.db_pool);
+    config.enabled = enabled;
+    if let Some(port) = port {
+        if port < 1024 {
+            return Err("Webhook port must be 1024 or higher".to_string());
+        }
+        config.port = port;
+    }
+    if regenerate_token.unwrap_or(false) || (enabled && config.token.is_none()) {
+        config.token = Some(generate_token());
+    }
+    config.save(&state.db_pool)?;
+
+    server.restart(app, state.db_pool.clone())?;
+    Ok(config)
+}
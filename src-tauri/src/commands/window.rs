@@ -1,6 +1,5 @@
-use tauri::AppHandle;
-#[cfg(debug_assertions)]
-use tauri::Manager;
+use crate::events::SessionSubscribers;
+use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindow, WebviewWindowBuilder};
 
 /// Toggle devtools for the main window
 #[tauri::command]
@@ -31,3 +30,74 @@ pub fn get_current_working_directory() -> Result<String, String> {
         .map(|p| p.to_string_lossy().to_string())
         .map_err(|e| format!("Failed to get current directory: {}", e))
 }
+
+/// Receive a session's events in the calling window, whichever window
+/// started its jobs
+#[tauri::command]
+pub fn subscribe_session(window: WebviewWindow, session_id: String) {
+    if let Some(subscribers) = window.try_state::<SessionSubscribers>() {
+        subscribers.subscribe(&session_id, window.label());
+    }
+}
+
+#[tauri::command]
+pub fn unsubscribe_session(window: WebviewWindow, session_id: String) {
+    if let Some(subscribers) = window.try_state::<SessionSubscribers>() {
+        subscribers.unsubscribe(&session_id, window.label());
+    }
+}
+
+/// Labels of observer windows start with this
+const OBSERVER_WINDOW_PREFIX: &str = "observer-";
+
+/// Follow a session read-only (for review while sharing the screen): the
+/// window gets every event of the session, but commands that would act on it
+/// are refused (see `ensure_can_act`)
+#[tauri::command]
+pub fn open_observer_window(app: AppHandle, session_id: String) -> Result<(), String> {
+    let label = format!("{}{}", OBSERVER_WINDOW_PREFIX, session_id);
+    if let Some(window) = app.get_webview_window(&label) {
+        return window.set_focus().map_err(|e| e.to_string());
+    }
+    let window = WebviewWindowBuilder::new(
+        &app,
+        &label,
+        WebviewUrl::App(format!("chat/{}?observe=1", session_id).into()),
+    )
+    .title("AnyCowork (observing)")
+    .inner_size(900.0, 720.0)
+    .build()
+    .map_err(|e| e.to_string())?;
+    if let Some(subscribers) = window.try_state::<SessionSubscribers>() {
+        subscribers.subscribe(&session_id, &label);
+    }
+    Ok(())
+}
+
+pub fn is_observer(window: &WebviewWindow) -> bool {
+    window.label().starts_with(OBSERVER_WINDOW_PREFIX)
+}
+
+/// Err for observer windows, which may watch but not send messages or
+/// answer approvals
+pub fn ensure_can_act(window: &WebviewWindow) -> Result<(), String> {
+    match is_observer(window) {
+        true => Err("This window is observing the session and cannot act on it".to_string()),
+        false => Ok(()),
+    }
+}
+
+/// Show a session in a window of its own, or focus the one already open
+#[tauri::command]
+pub fn open_session_window(app: AppHandle, session_id: String) -> Result<(), String> {
+    let label = format!("session-{}", session_id);
+    if let Some(window) = app.get_webview_window(&label) {
+        return window.set_focus().map_err(|e| e.to_string());
+    }
+    WebviewWindowBuilder::new(&app, &label, WebviewUrl::App(format!("chat/{}", session_id).into()))
+        .title("AnyCowork")
+        .inner_size(900.0, 720.0)
+        .build()
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -0,0 +1,62 @@
+//! Reloads the core configuration (see `anyagents::config`) when its file
+//! changes, and tells the windows about it
+
+use anyagents::config::{self, ConfigChange};
+use anyagents::events::AgentEvent;
+use notify::{RecursiveMode, Watcher};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, Runtime};
+
+/// Keeps the watcher alive for as long as the app runs
+struct ConfigWatcher(#[allow(dead_code)] Mutex<notify::RecommendedWatcher>);
+
+/// Re-read the configuration and emit `config_changed` when something changed
+pub fn reload<R: Runtime>(app: &AppHandle<R>) -> Result<ConfigChange, String> {
+    let change = config::reload()?;
+    if !change.is_empty() {
+        log::info!(
+            "Configuration reloaded: applied {:?}, restart required for {:?}",
+            change.applied,
+            change.restart_required
+        );
+        let event = AgentEvent::ConfigChanged {
+            applied: change.applied.clone(),
+            restart_required: change.restart_required.clone(),
+        };
+        crate::events::emit_routed(app, "config", serde_json::to_value(event).map_err(|e| e.to_string())?)?;
+    }
+    Ok(change)
+}
+
+/// Watch the config file's directory (the file may not exist yet). Call once.
+pub fn start<R: Runtime>(app: &AppHandle<R>) {
+    let file = config::config_file();
+    let Some(dir) = file.parent().filter(|dir| dir.is_dir()).map(|dir| dir.to_path_buf()) else {
+        log::warn!("Not watching {}: its directory does not exist", file.display());
+        return;
+    };
+
+    let handle = app.clone();
+    let watched = file.clone();
+    let watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let Ok(event) = res else {
+            return;
+        };
+        if event.kind.is_access() || !event.paths.iter().any(|p| p == &watched) {
+            return;
+        }
+        if let Err(e) = reload(&handle) {
+            log::error!("Failed to reload {}: {}", watched.display(), e);
+        }
+    });
+
+    match watcher {
+        Ok(mut watcher) => match watcher.watch(&dir, RecursiveMode::NonRecursive) {
+            Ok(()) => {
+                app.manage(ConfigWatcher(Mutex::new(watcher)));
+            }
+            Err(e) => log::error!("Failed to watch {}: {}", dir.display(), e),
+        },
+        Err(e) => log::error!("Failed to create config watcher: {}", e),
+    }
+}
@@ -0,0 +1,250 @@
+// Live dictation: microphone audio in, partial transcripts out
+//
+// The local Parakeet model transcribes whole clips, so the stream is cut into
+// utterances. While an utterance grows it is re-transcribed about once a
+// second (a `partial` event); a pause or the length cap closes it (a `segment`
+// event) and the text is kept. `stop` flushes the rest and returns everything.
+
+use serde::Serialize;
+use std::path::PathBuf;
+use tokio::sync::{mpsc, oneshot};
+use transcribe_rs::{engines::parakeet::ParakeetEngine, TranscriptionEngine};
+
+const SAMPLE_RATE: u32 = 16000;
+/// New audio needed before the open utterance is transcribed again
+const PARTIAL_INTERVAL: usize = SAMPLE_RATE as usize;
+/// Trailing quiet that ends an utterance
+const SILENCE_SAMPLES: usize = (SAMPLE_RATE as usize) * 7 / 10;
+/// Utterances are closed at this length even without a pause
+const MAX_UTTERANCE: usize = (SAMPLE_RATE as usize) * 20;
+/// RMS below this counts as silence
+const SILENCE_RMS: f32 = 0.01;
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DictationEvent {
+    /// Best guess for the utterance in progress; replaced by later events
+    Partial { text: String },
+    /// A finished utterance
+    Segment { text: String },
+    /// All text, once dictation stops
+    Final { text: String },
+    Error { message: String },
+}
+
+pub enum DictationInput {
+    Audio(Vec<u8>),
+    Stop(oneshot::Sender<String>),
+}
+
+/// Decode little-endian 16-bit mono PCM and resample it to 16 kHz
+pub fn pcm16_to_samples(bytes: &[u8], sample_rate: u32) -> Vec<f32> {
+    let samples: Vec<f32> = bytes
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / i16::MAX as f32)
+        .collect();
+    if sample_rate == SAMPLE_RATE || samples.is_empty() {
+        return samples;
+    }
+    // Linear interpolation is plenty for speech recognition
+    let ratio = sample_rate as f64 / SAMPLE_RATE as f64;
+    let len = (samples.len() as f64 / ratio) as usize;
+    (0..len)
+        .map(|i| {
+            let pos = i as f64 * ratio;
+            let idx = pos as usize;
+            let frac = (pos - idx as f64) as f32;
+            let a = samples[idx];
+            let b = *samples.get(idx + 1).unwrap_or(&a);
+            a + (b - a) * frac
+        })
+        .collect()
+}
+
+fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+/// Utterance segmentation, independent of the speech model
+pub struct Dictation<T: FnMut(Vec<f32>) -> Result<String, String>> {
+    transcribe: T,
+    utterance: Vec<f32>,
+    since_partial: usize,
+    heard_speech: bool,
+    segments: Vec<String>,
+}
+
+impl<T: FnMut(Vec<f32>) -> Result<String, String>> Dictation<T> {
+    pub fn new(transcribe: T) -> Self {
+        Self {
+            transcribe,
+            utterance: Vec::new(),
+            since_partial: 0,
+            heard_speech: false,
+            segments: Vec::new(),
+        }
+    }
+
+    /// Add 16 kHz samples; returns the events they produce
+    pub fn push(&mut self, samples: &[f32]) -> Vec<DictationEvent> {
+        self.utterance.extend_from_slice(samples);
+        self.since_partial += samples.len();
+        if rms(samples) >= SILENCE_RMS {
+            self.heard_speech = true;
+        }
+
+        let tail = &self.utterance[self.utterance.len().saturating_sub(SILENCE_SAMPLES)..];
+        let paused = self.utterance.len() >= SILENCE_SAMPLES && rms(tail) < SILENCE_RMS;
+        if paused && !self.heard_speech {
+            // Nothing but quiet so far; do not let it pile up
+            self.utterance.clear();
+            self.since_partial = 0;
+            return vec![];
+        }
+        if (paused && self.heard_speech) || self.utterance.len() >= MAX_UTTERANCE {
+            return self.close_utterance().into_iter().collect();
+        }
+        if self.since_partial >= PARTIAL_INTERVAL && self.heard_speech {
+            self.since_partial = 0;
+            return vec![match (self.transcribe)(self.utterance.clone()) {
+                Ok(text) => DictationEvent::Partial { text },
+                Err(message) => DictationEvent::Error { message },
+            }];
+        }
+        vec![]
+    }
+
+    /// Flush the open utterance and return all text
+    pub fn finish(mut self) -> (Vec<DictationEvent>, String) {
+        let mut events: Vec<DictationEvent> = self.close_utterance().into_iter().collect();
+        let text = self.segments.join(" ");
+        events.push(DictationEvent::Final { text: text.clone() });
+        (events, text)
+    }
+
+    fn close_utterance(&mut self) -> Option<DictationEvent> {
+        let samples = std::mem::take(&mut self.utterance);
+        let heard_speech = std::mem::replace(&mut self.heard_speech, false);
+        self.since_partial = 0;
+        if samples.is_empty() || !heard_speech {
+            return None;
+        }
+        match (self.transcribe)(samples) {
+            Ok(text) => {
+                let text = text.trim().to_string();
+                if text.is_empty() {
+                    return None;
+                }
+                self.segments.push(text.clone());
+                Some(DictationEvent::Segment { text })
+            }
+            Err(message) => Some(DictationEvent::Error { message }),
+        }
+    }
+}
+
+/// Run a dictation on a blocking thread until `Stop` arrives (or every sender
+/// is dropped). The model is loaded once per dictation.
+pub async fn spawn(
+    model_path: PathBuf,
+    sample_rate: u32,
+    mut input: mpsc::UnboundedReceiver<DictationInput>,
+    on_event: impl Fn(DictationEvent) + Send + 'static,
+) -> Result<(), String> {
+    let mut engine = tokio::task::spawn_blocking(move || {
+        let mut engine = ParakeetEngine::new();
+        engine.load_model(&model_path).map_err(|e| e.to_string())?;
+        Ok::<_, String>(engine)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    tokio::task::spawn_blocking(move || {
+        let mut dictation = Dictation::new(|samples| {
+            engine
+                .transcribe_samples(samples, None)
+                .map(|r| r.text)
+                .map_err(|e| e.to_string())
+        });
+        let mut reply = None;
+        while let Some(message) = input.blocking_recv() {
+            match message {
+                DictationInput::Audio(bytes) => {
+                    for event in dictation.push(&pcm16_to_samples(&bytes, sample_rate)) {
+                        on_event(event);
+                    }
+                }
+                DictationInput::Stop(tx) => {
+                    reply = Some(tx);
+                    break;
+                }
+            }
+        }
+        let (events, text) = dictation.finish();
+        for event in events {
+            on_event(event);
+        }
+        if let Some(tx) = reply {
+            let _ = tx.send(text);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(len: usize) -> Vec<f32> {
+        (0..len).map(|i| if i % 2 == 0 { 0.3 } else { -0.3 }).collect()
+    }
+
+    #[test]
+    fn test_pcm16_resampling() {
+        let bytes: Vec<u8> = [0i16, i16::MAX, 0, i16::MIN + 1]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        assert_eq!(pcm16_to_samples(&bytes, 16000), vec![0.0, 1.0, 0.0, -1.0]);
+        assert_eq!(pcm16_to_samples(&bytes, 32000).len(), 2);
+    }
+
+    #[test]
+    fn test_partials_then_segment_on_pause() {
+        let mut dictation =
+            Dictation::new(|samples: Vec<f32>| Ok(format!("{} samples", samples.len())));
+
+        assert!(dictation.push(&tone(8000)).is_empty());
+        assert_eq!(
+            dictation.push(&tone(8000)),
+            vec![DictationEvent::Partial { text: "16000 samples".to_string() }]
+        );
+        let events = dictation.push(&vec![0.0; SILENCE_SAMPLES]);
+        assert!(matches!(&events[..], [DictationEvent::Segment { .. }]));
+
+        // Silence alone never reaches the model
+        assert!(dictation.push(&vec![0.0; SILENCE_SAMPLES * 2]).is_empty());
+        let (events, text) = dictation.finish();
+        assert_eq!(text, format!("{} samples", 16000 + SILENCE_SAMPLES));
+        assert_eq!(events, vec![DictationEvent::Final { text }]);
+    }
+
+    #[test]
+    fn test_long_utterance_is_split() {
+        let mut dictation = Dictation::new(|_samples: Vec<f32>| Ok("words".to_string()));
+        let mut segments = 0;
+        for _ in 0..(MAX_UTTERANCE / 16000 + 1) {
+            segments += dictation
+                .push(&tone(16000))
+                .iter()
+                .filter(|e| matches!(e, DictationEvent::Segment { .. }))
+                .count();
+        }
+        assert_eq!(segments, 1);
+        let (_, text) = dictation.finish();
+        assert_eq!(text, "words words");
+    }
+}
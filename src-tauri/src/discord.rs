@@ -0,0 +1,554 @@
+// Discord connector, the counterpart of the Telegram bots: each active
+// discord_config runs a bot that answers DMs, mentions in server channels and
+// the /ask command with a full agent run. Runs are reported by
+// `messaging::run_in_conversation` through a `DiscordConnector`: the answer
+// streams in by editing the reply message, and permission requests come up
+// as buttons.
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use diesel::prelude::*;
+use serenity::all::{
+    ButtonStyle, ChannelId, Client, Command, CommandInteraction, CommandOptionType, ComponentInteraction, Context,
+    CreateActionRow, CreateButton, CreateCommand, CreateCommandOption, CreateInteractionResponse,
+    CreateInteractionResponseMessage, CreateMessage, EditMessage, EventHandler, GatewayIntents, Http, Interaction,
+    Message, MessageId, Ready, User, UserId,
+};
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+use std::sync::{Arc, OnceLock};
+use tokio::sync::{mpsc, RwLock};
+
+use anyagents::database::DbPool;
+use anyagents::events::AgentObserver;
+use anyagents::messaging::{
+    ChatUser, ConnectorCapabilities, IncomingAttachment, IncomingMessage, MessagingConnector, OutgoingMessage,
+    SentMessage,
+};
+use anyagents::models::{Agent, DiscordConfig, NewDiscordChannelSession};
+use anyagents::permissions::{PermissionManager, PermissionRequest, PermissionResponse};
+use anyagents::tools::email::NoOpObserver;
+
+use crate::messaging::ApprovalPrompt;
+use crate::telegram::{approval_data, parse_approval_data, APPROVAL_PREVIEW_LEN};
+
+pub type BotShutdownSender = mpsc::Sender<()>;
+
+/// Discord rejects messages longer than 2000 characters
+const MAX_MESSAGE_LEN: usize = 1900;
+
+const ASK_COMMAND: &str = "ask";
+
+pub struct DiscordBotManager {
+    pub db_pool: DbPool,
+    pub running_bots: Arc<RwLock<HashMap<String, BotShutdownSender>>>,
+    permission_manager: Arc<PermissionManager>,
+    /// Where runs' events go besides Discord; only known once the app is up,
+    /// see `set_observer`
+    observer: Arc<std::sync::RwLock<Arc<dyn AgentObserver>>>,
+    /// Permission requests sent to a channel as buttons, by request id
+    approvals: Arc<DashMap<String, ChannelId>>,
+}
+
+impl DiscordBotManager {
+    pub fn new(db_pool: DbPool, permission_manager: Arc<PermissionManager>) -> Self {
+        Self {
+            db_pool,
+            running_bots: Arc::new(RwLock::new(HashMap::new())),
+            permission_manager,
+            observer: Arc::new(std::sync::RwLock::new(Arc::new(NoOpObserver))),
+            approvals: Arc::new(DashMap::new()),
+        }
+    }
+
+    pub fn set_observer(&self, observer: Arc<dyn AgentObserver>) {
+        *self.observer.write().unwrap() = observer;
+    }
+
+    pub async fn start_bot(&self, config_id: &str) -> Result<(), String> {
+        let config = self.get_config(config_id)?;
+        if config.is_active == 0 {
+            return Err("Bot configuration is not active".to_string());
+        }
+        if self.running_bots.read().await.contains_key(config_id) {
+            return Err("Bot is already running".to_string());
+        }
+
+        let agent = self.get_agent(&config.agent_id)?;
+        let handler = Handler {
+            config_id: config_id.to_string(),
+            agent,
+            db_pool: self.db_pool.clone(),
+            allowed_channels: parse_channel_ids(config.allowed_channel_ids.as_deref()),
+            permissions: self.permission_manager.clone(),
+            observer: self.observer.clone(),
+            approvals: self.approvals.clone(),
+            bot_id: OnceLock::new(),
+        };
+
+        // Reading what is said in server channels needs the privileged
+        // message content intent, enabled in the developer portal
+        let intents =
+            GatewayIntents::GUILD_MESSAGES | GatewayIntents::DIRECT_MESSAGES | GatewayIntents::MESSAGE_CONTENT;
+        let mut client = Client::builder(&config.bot_token, intents)
+            .event_handler(handler)
+            .await
+            .map_err(|e| format!("Failed to create Discord client: {}", e))?;
+
+        let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
+        let shard_manager = client.shard_manager.clone();
+        let running_bots = self.running_bots.clone();
+        let config_id_owned = config_id.to_string();
+
+        tokio::spawn(async move {
+            tokio::select! {
+                result = client.start() => {
+                    if let Err(e) = result {
+                        log::error!("Discord bot {} stopped: {}", config_id_owned, e);
+                    }
+                }
+                _ = shutdown_rx.recv() => {
+                    log::info!("Received shutdown signal for Discord bot");
+                    shard_manager.shutdown_all().await;
+                }
+            }
+
+            running_bots.write().await.remove(&config_id_owned);
+            log::info!("Discord bot {} has been stopped", config_id_owned);
+        });
+
+        self.running_bots
+            .write()
+            .await
+            .insert(config_id.to_string(), shutdown_tx);
+        log::info!("Started Discord bot for config: {}", config_id);
+        Ok(())
+    }
+
+    pub async fn stop_bot(&self, config_id: &str) -> Result<(), String> {
+        let shutdown_tx = self.running_bots.write().await.remove(config_id);
+        match shutdown_tx {
+            Some(tx) => {
+                let _ = tx.send(()).await;
+                Ok(())
+            }
+            None => Err("Bot is not running".to_string()),
+        }
+    }
+
+    pub async fn stop_all_bots(&self) {
+        let bots: Vec<(String, BotShutdownSender)> = self.running_bots.write().await.drain().collect();
+        for (id, tx) in bots {
+            log::info!("Stopping Discord bot: {}", id);
+            let _ = tx.send(()).await;
+        }
+    }
+
+    pub async fn is_bot_running(&self, config_id: &str) -> bool {
+        self.running_bots.read().await.contains_key(config_id)
+    }
+
+    pub async fn get_running_bot_ids(&self) -> Vec<String> {
+        self.running_bots.read().await.keys().cloned().collect()
+    }
+
+    pub async fn start_all_active_bots(&self) -> Result<(), String> {
+        use anyagents::schema::discord_configs::dsl::*;
+
+        let configs: Vec<DiscordConfig> = {
+            let mut conn = self.db_pool.get().map_err(|e| e.to_string())?;
+            discord_configs
+                .filter(is_active.eq(1))
+                .load::<DiscordConfig>(&mut conn)
+                .map_err(|e| e.to_string())?
+        };
+        for config in configs {
+            if let Err(e) = self.start_bot(&config.id).await {
+                log::error!("Failed to start Discord bot {}: {}", config.id, e);
+            }
+        }
+        Ok(())
+    }
+
+    fn get_config(&self, config_id: &str) -> Result<DiscordConfig, String> {
+        use anyagents::schema::discord_configs::dsl::*;
+
+        let mut conn = self.db_pool.get().map_err(|e| e.to_string())?;
+        discord_configs
+            .filter(id.eq(config_id))
+            .first::<DiscordConfig>(&mut conn)
+            .map_err(|e| format!("Config not found: {}", e))
+    }
+
+    fn get_agent(&self, agent_id: &str) -> Result<Agent, String> {
+        crate::scheduler::load_agent(&self.db_pool, agent_id)
+    }
+}
+
+/// Comma separated channel ids; None when there is no restriction
+fn parse_channel_ids(ids: Option<&str>) -> Option<Vec<u64>> {
+    ids.map(|ids| ids.split(',').filter_map(|s| s.trim().parse().ok()).collect())
+}
+
+struct Handler {
+    config_id: String,
+    agent: Agent,
+    db_pool: DbPool,
+    allowed_channels: Option<Vec<u64>>,
+    permissions: Arc<PermissionManager>,
+    observer: Arc<std::sync::RwLock<Arc<dyn AgentObserver>>>,
+    approvals: Arc<DashMap<String, ChannelId>>,
+    /// Our own account, to spot mentions
+    bot_id: OnceLock<UserId>,
+}
+
+#[async_trait]
+impl EventHandler for Handler {
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        let _ = self.bot_id.set(ready.user.id);
+        let ask = CreateCommand::new(ASK_COMMAND)
+            .description("Ask the agent")
+            .add_option(CreateCommandOption::new(CommandOptionType::String, "prompt", "What to ask").required(true));
+        if let Err(e) = Command::create_global_command(&ctx.http, ask).await {
+            log::error!("Failed to register the /{} command: {}", ASK_COMMAND, e);
+        }
+        log::info!("Discord bot {} connected as {}", self.config_id, ready.user.name);
+    }
+
+    async fn message(&self, ctx: Context, msg: Message) {
+        if msg.author.bot || !self.channel_allowed(msg.channel_id) {
+            return;
+        }
+        let Some(bot_id) = self.bot_id.get().copied() else {
+            return;
+        };
+        // In servers only messages that mention the bot are ours
+        let in_server = msg.guild_id.is_some();
+        if in_server && !msg.mentions_user_id(bot_id) {
+            return;
+        }
+        let text = strip_mention(&msg.content, bot_id);
+        if text.is_empty() {
+            return;
+        }
+        let connector = self.connector(ctx.http.clone());
+        let conversation_id = msg.channel_id.to_string();
+        connector.typing(&conversation_id).await;
+        let title = if in_server { format!("#{}", msg.channel_id) } else { msg.author.name.clone() };
+        let incoming = IncomingMessage {
+            conversation_id,
+            thread_id: None,
+            message_id: msg.id.to_string(),
+            sender: Some(chat_user(&msg.author)),
+            text,
+            attachments: vec![],
+            is_direct: !in_server,
+            mentions_bot: in_server,
+            conversation_title: Some(title),
+        };
+        self.answer(connector, incoming).await;
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        match interaction {
+            Interaction::Command(command) if command.data.name == ASK_COMMAND => self.ask(&ctx, command).await,
+            Interaction::Component(component) => {
+                let answer = self.handle_approval(&component);
+                let text = format!("{}\n\n{}", component.message.content, answer);
+                let response = CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new().content(text).components(vec![]),
+                );
+                if let Err(e) = component.create_response(&ctx.http, response).await {
+                    log::error!("Failed to answer approval button: {}", e);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Handler {
+    fn channel_allowed(&self, channel_id: ChannelId) -> bool {
+        self.allowed_channels
+            .as_ref()
+            .map_or(true, |allowed| allowed.contains(&channel_id.get()))
+    }
+
+    async fn ask(&self, ctx: &Context, command: CommandInteraction) {
+        let prompt = command
+            .data
+            .options
+            .iter()
+            .find(|o| o.name == "prompt")
+            .and_then(|o| o.value.as_str())
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        let allowed = self.channel_allowed(command.channel_id);
+        let reply = if allowed { format!("> {}", prompt) } else { "Not allowed in this channel".to_string() };
+        let response = CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new().content(reply).ephemeral(!allowed),
+        );
+        if let Err(e) = command.create_response(&ctx.http, response).await {
+            log::error!("Failed to answer /{}: {}", ASK_COMMAND, e);
+            return;
+        }
+        if allowed && !prompt.is_empty() {
+            let in_server = command.guild_id.is_some();
+            let title = if in_server { format!("#{}", command.channel_id) } else { command.user.name.clone() };
+            let incoming = IncomingMessage {
+                conversation_id: command.channel_id.to_string(),
+                thread_id: None,
+                message_id: command.id.to_string(),
+                sender: Some(chat_user(&command.user)),
+                text: prompt,
+                attachments: vec![],
+                is_direct: !in_server,
+                mentions_bot: in_server,
+                conversation_title: Some(title),
+            };
+            self.answer(self.connector(ctx.http.clone()), incoming).await;
+        }
+    }
+
+    fn connector(&self, http: Arc<Http>) -> Arc<DiscordConnector> {
+        Arc::new(DiscordConnector {
+            http,
+            approvals: self.approvals.clone(),
+        })
+    }
+
+    /// Run the agent on the message in the channel's session, streaming the
+    /// answer into the channel
+    async fn answer(&self, connector: Arc<DiscordConnector>, msg: IncomingMessage) {
+        // In servers the transcript has to say who is talking
+        let prompt = match &msg.sender {
+            Some(sender) if !msg.is_direct => format!("[{}] {}", sender.name, msg.text),
+            _ => msg.text.clone(),
+        };
+        let title = msg.conversation_title.as_deref().unwrap_or(&msg.conversation_id);
+        let session_id = match self.session_for(&msg.conversation_id, title, &prompt) {
+            Ok(session_id) => session_id,
+            Err(e) => {
+                log::error!("Failed to prepare Discord session: {}", e);
+                let text = format!("Sorry, I encountered an error: {}", e);
+                let _ = connector.send(&msg.conversation_id, OutgoingMessage::text(text)).await;
+                return;
+            }
+        };
+
+        let observer = self.observer.read().unwrap().clone();
+        crate::messaging::run_in_conversation(
+            &self.db_pool,
+            &self.agent,
+            &session_id,
+            prompt,
+            observer,
+            self.permissions.clone(),
+            connector.clone(),
+            connector,
+            &msg,
+        )
+        .await;
+    }
+
+    /// The channel's session with `prompt` saved to it, created on first use
+    fn session_for(&self, channel: &str, title: &str, prompt: &str) -> Result<String, String> {
+        use anyagents::schema::discord_channel_sessions;
+
+        let existing = {
+            let mut conn = self.db_pool.get().map_err(|e| e.to_string())?;
+            discord_channel_sessions::table
+                .filter(discord_channel_sessions::config_id.eq(&self.config_id))
+                .filter(discord_channel_sessions::channel_id.eq(&channel))
+                .select(discord_channel_sessions::session_id)
+                .first::<String>(&mut conn)
+                .optional()
+                .map_err(|e| e.to_string())?
+        };
+        let session_id = crate::scheduler::prepare_session(
+            &self.db_pool,
+            &self.agent.id,
+            existing.as_deref(),
+            &format!("Discord: {}", title),
+            prompt,
+        )?;
+        if existing.is_none() {
+            let mut conn = self.db_pool.get().map_err(|e| e.to_string())?;
+            diesel::insert_into(discord_channel_sessions::table)
+                .values(&NewDiscordChannelSession {
+                    config_id: self.config_id.clone(),
+                    channel_id: channel.to_string(),
+                    session_id: session_id.clone(),
+                })
+                .execute(&mut conn)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(session_id)
+    }
+
+    /// Answer the permission request behind a pressed button; returns what
+    /// to tell the channel
+    fn handle_approval(&self, component: &ComponentInteraction) -> String {
+        let Some((response, request_id)) = parse_approval_data(&component.data.custom_id) else {
+            return "Unknown button".to_string();
+        };
+        if !self.channel_allowed(component.channel_id) {
+            return "Not allowed in this channel".to_string();
+        }
+        // Only requests that were sent to this very channel can be answered here
+        if self.approvals.get(request_id).map(|c| *c) != Some(component.channel_id) {
+            return "This request is no longer pending".to_string();
+        }
+        self.approvals.remove(request_id);
+
+        let verdict = match response {
+            PermissionResponse::Allow => "Approved",
+            PermissionResponse::Deny => "Denied",
+            PermissionResponse::AllowAlways => "Always allowed",
+        };
+        if self.permissions.respond_as(request_id, response, &format!("discord:{}", component.user.id)) {
+            format!("{} by {}", verdict, component.user.name)
+        } else {
+            "This request is no longer pending".to_string()
+        }
+    }
+}
+
+/// Drop `<@id>` mentions of the bot from a message addressed to it
+fn strip_mention(text: &str, bot_id: UserId) -> String {
+    text.replace(&format!("<@{}>", bot_id), "")
+        .replace(&format!("<@!{}>", bot_id), "")
+        .trim()
+        .to_string()
+}
+
+fn chat_user(user: &User) -> ChatUser {
+    ChatUser {
+        id: user.id.to_string(),
+        name: user.name.clone(),
+    }
+}
+
+/// Snowflake ids, as `conversation_id`s and `message_id`s
+fn snowflake(id: &str) -> Result<u64, String> {
+    id.parse::<NonZeroU64>()
+        .map(NonZeroU64::get)
+        .map_err(|_| format!("Invalid Discord id: {}", id))
+}
+
+/// Sends to and edits in a channel. Events come in over the bot's gateway
+/// connection rather than `connect`.
+struct DiscordConnector {
+    http: Arc<Http>,
+    approvals: Arc<DashMap<String, ChannelId>>,
+}
+
+#[async_trait]
+impl MessagingConnector for DiscordConnector {
+    fn platform(&self) -> &'static str {
+        "discord"
+    }
+
+    fn capabilities(&self) -> ConnectorCapabilities {
+        ConnectorCapabilities {
+            edit: true,
+            attachments: false,
+            max_message_len: MAX_MESSAGE_LEN,
+        }
+    }
+
+    async fn connect(&self) -> Result<mpsc::Receiver<IncomingMessage>, String> {
+        Err("Discord events arrive over the bot's gateway connection".to_string())
+    }
+
+    async fn disconnect(&self) {}
+
+    async fn send(&self, conversation_id: &str, message: OutgoingMessage) -> Result<SentMessage, String> {
+        let channel_id = ChannelId::new(snowflake(conversation_id)?);
+        let sent = channel_id
+            .say(&self.http, message.text)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(SentMessage {
+            conversation_id: conversation_id.to_string(),
+            message_id: sent.id.to_string(),
+        })
+    }
+
+    async fn edit(&self, message: &SentMessage, text: &str) -> Result<(), String> {
+        let channel_id = ChannelId::new(snowflake(&message.conversation_id)?);
+        let message_id = MessageId::new(snowflake(&message.message_id)?);
+        channel_id
+            .edit_message(&self.http, message_id, EditMessage::new().content(text))
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    async fn download(&self, _attachment: &IncomingAttachment) -> Result<Vec<u8>, String> {
+        Err("Discord attachments are not supported".to_string())
+    }
+
+    async fn typing(&self, conversation_id: &str) {
+        if let Ok(id) = snowflake(conversation_id) {
+            let _ = ChannelId::new(id).broadcast_typing(&self.http).await;
+        }
+    }
+}
+
+#[async_trait]
+impl ApprovalPrompt for DiscordConnector {
+    async fn ask(&self, _connector: &dyn MessagingConnector, msg: &IncomingMessage, request: &PermissionRequest) {
+        let Ok(id) = snowflake(&msg.conversation_id) else {
+            return;
+        };
+        let channel_id = ChannelId::new(id);
+        self.approvals.insert(request.id.clone(), channel_id);
+        let mut text = format!("🔐 Permission needed: {}", request.message);
+        if let Some(preview) = request.preview(APPROVAL_PREVIEW_LEN) {
+            text.push_str(&format!("\n```\n{}\n```", preview));
+        }
+        let buttons = CreateActionRow::Buttons(vec![
+            CreateButton::new(approval_data(&PermissionResponse::Allow, &request.id))
+                .label("Approve")
+                .style(ButtonStyle::Success),
+            CreateButton::new(approval_data(&PermissionResponse::Deny, &request.id))
+                .label("Deny")
+                .style(ButtonStyle::Danger),
+            CreateButton::new(approval_data(&PermissionResponse::AllowAlways, &request.id))
+                .label("Always")
+                .style(ButtonStyle::Secondary),
+        ]);
+        let message = CreateMessage::new().content(text).components(vec![buttons]);
+        if let Err(e) = channel_id.send_message(&self.http, message).await {
+            log::error!("Failed to send approval request: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_mention() {
+        let bot = UserId::new(1234);
+        assert_eq!(strip_mention("<@1234> what's the weather?", bot), "what's the weather?");
+        assert_eq!(strip_mention("hey <@!1234>", bot), "hey");
+        assert_eq!(strip_mention("<@999> hi", bot), "<@999> hi");
+    }
+
+    #[test]
+    fn test_parse_channel_ids() {
+        assert_eq!(parse_channel_ids(Some("1, 2,x")), Some(vec![1, 2]));
+        assert_eq!(parse_channel_ids(None), None);
+    }
+
+    #[test]
+    fn test_snowflake() {
+        assert_eq!(snowflake("1234"), Ok(1234));
+        assert!(snowflake("0").is_err());
+        assert!(snowflake("#general").is_err());
+    }
+}
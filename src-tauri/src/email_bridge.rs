@@ -0,0 +1,244 @@
+// Email bridge: watches each active agent email account over IMAP and feeds
+// new mail into the agent's mailbox, where it gets the usual background reply.
+
+use anyagents::database::DbPool;
+use anyagents::email::{self, attachments, credentials, imap};
+use anyagents::events::{AgentEvent, AgentObserver};
+use anyagents::models::EmailAccount;
+use anyagents::permissions::PermissionManager;
+use anyagents::tools::email::{MailAgentContext, NoOpObserver};
+use diesel::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, RwLock};
+
+/// Wait before reconnecting after the server drops or refuses us
+const RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+pub type WatcherShutdownSender = mpsc::Sender<()>;
+
+/// Where agents answering with tools ask for permissions and report progress.
+/// The observer is only known once the app is up, see `set_observer`.
+#[derive(Clone)]
+struct ReplyContext {
+    permissions: Arc<PermissionManager>,
+    observer: Arc<std::sync::RwLock<Arc<dyn AgentObserver>>>,
+}
+
+impl ReplyContext {
+    fn current(&self) -> MailAgentContext {
+        MailAgentContext {
+            permissions: self.permissions.clone(),
+            observer: self.observer.read().unwrap().clone(),
+        }
+    }
+}
+
+pub struct EmailBridgeManager {
+    pub db_pool: DbPool,
+    pub running_watchers: Arc<RwLock<HashMap<String, WatcherShutdownSender>>>,
+    reply_context: ReplyContext,
+}
+
+impl EmailBridgeManager {
+    pub fn new(db_pool: DbPool, permission_manager: Arc<PermissionManager>) -> Self {
+        Self {
+            db_pool,
+            running_watchers: Arc::new(RwLock::new(HashMap::new())),
+            reply_context: ReplyContext {
+                permissions: permission_manager,
+                observer: Arc::new(std::sync::RwLock::new(Arc::new(NoOpObserver))),
+            },
+        }
+    }
+
+    pub fn set_observer(&self, observer: Arc<dyn AgentObserver>) {
+        *self.reply_context.observer.write().unwrap() = observer;
+    }
+
+    pub async fn start_watcher(&self, account_id: &str) -> Result<(), String> {
+        let account = self.get_account(account_id)?;
+        if account.is_active == 0 {
+            return Err("Email account is not active".to_string());
+        }
+        // Fail early rather than inside the background task
+        credentials::get_password(account_id)?;
+
+        {
+            let watchers = self.running_watchers.read().await;
+            if watchers.contains_key(account_id) {
+                return Err("Email account is already being watched".to_string());
+            }
+        }
+
+        let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
+        let db_pool = self.db_pool.clone();
+        let running_watchers = self.running_watchers.clone();
+        let reply_context = self.reply_context.clone();
+        let account_id_owned = account_id.to_string();
+
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    result = watch(&db_pool, &account_id_owned, &reply_context) => {
+                        if let Err(e) = result {
+                            log::error!("Email watcher {} failed: {}", account_id_owned, e);
+                        }
+                    }
+                    _ = shutdown_rx.recv() => break,
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(RECONNECT_DELAY) => {}
+                    _ = shutdown_rx.recv() => break,
+                }
+            }
+
+            running_watchers.write().await.remove(&account_id_owned);
+            log::info!("Email watcher {} has been stopped", account_id_owned);
+        });
+
+        self.running_watchers
+            .write()
+            .await
+            .insert(account_id.to_string(), shutdown_tx);
+        log::info!("Watching email account {}", account.email_address);
+        Ok(())
+    }
+
+    pub async fn stop_watcher(&self, account_id: &str) -> Result<(), String> {
+        let shutdown_tx = self.running_watchers.write().await.remove(account_id);
+        match shutdown_tx {
+            Some(tx) => {
+                let _ = tx.send(()).await;
+                Ok(())
+            }
+            None => Err("Email account is not being watched".to_string()),
+        }
+    }
+
+    pub async fn is_watching(&self, account_id: &str) -> bool {
+        self.running_watchers.read().await.contains_key(account_id)
+    }
+
+    pub async fn start_all_active_watchers(&self) -> Result<(), String> {
+        use anyagents::schema::email_accounts::dsl::*;
+
+        let accounts: Vec<EmailAccount> = {
+            let mut conn = self.db_pool.get().map_err(|e| e.to_string())?;
+            email_accounts
+                .filter(is_active.eq(1))
+                .load::<EmailAccount>(&mut conn)
+                .map_err(|e| e.to_string())?
+        };
+
+        for account in accounts {
+            if let Err(e) = self.start_watcher(&account.id).await {
+                log::error!("Failed to watch email account {}: {}", account.email_address, e);
+            }
+        }
+        Ok(())
+    }
+
+    fn get_account(&self, account_id: &str) -> Result<EmailAccount, String> {
+        use anyagents::schema::email_accounts::dsl::*;
+
+        let mut conn = self.db_pool.get().map_err(|e| e.to_string())?;
+        email_accounts
+            .filter(id.eq(account_id))
+            .first::<EmailAccount>(&mut conn)
+            .map_err(|e| format!("Email account not found: {}", e))
+    }
+}
+
+/// One IMAP session: catch up on missed mail, then wait for more. Returns
+/// when the connection fails.
+async fn watch(db_pool: &DbPool, account_id: &str, reply_context: &ReplyContext) -> Result<(), String> {
+    let account = {
+        use anyagents::schema::email_accounts::dsl::*;
+        let mut conn = db_pool.get().map_err(|e| e.to_string())?;
+        email_accounts
+            .filter(id.eq(account_id))
+            .first::<EmailAccount>(&mut conn)
+            .map_err(|e| e.to_string())?
+    };
+    let password = credentials::get_password(&account.id)?;
+    let mut session = imap::connect(&account, &password).await?;
+    let idle = imap::supports_idle(&mut session).await;
+    let interval = Duration::from_secs(account.poll_interval_seconds.max(email::MIN_POLL_INTERVAL_SECS) as u64);
+
+    // A new account starts with what arrives from now on, not the whole folder
+    let mut last_uid = account.last_seen_uid as u32;
+    if last_uid == 0 {
+        last_uid = imap::latest_uid(&mut session).await?;
+        email::record_last_seen_uid(db_pool, &account.id, last_uid)?;
+    }
+
+    loop {
+        for mail in imap::fetch_since(&mut session, last_uid).await? {
+            if let Err(e) = handle_mail(db_pool, &account, &mail.raw, reply_context.current()) {
+                log::error!("Failed to store email {} for {}: {}", mail.uid, account.email_address, e);
+            }
+            last_uid = mail.uid;
+            email::record_last_seen_uid(db_pool, &account.id, last_uid)?;
+        }
+        session = imap::wait_for_mail(session, interval, idle).await?;
+    }
+}
+
+fn handle_mail(
+    db_pool: &DbPool,
+    account: &EmailAccount,
+    raw: &[u8],
+    context: MailAgentContext,
+) -> Result<(), String> {
+    let Some(inbound) = email::parse_email(raw) else {
+        return Err("Unreadable message".to_string());
+    };
+    if inbound.from_address.eq_ignore_ascii_case(&account.email_address) {
+        // Our own mail, e.g. a copy filed into the watched folder
+        return Ok(());
+    }
+    let Some(injected) = email::inject_inbound(db_pool, &attachments::storage_dir(), account, &inbound)? else {
+        return Ok(());
+    };
+    log::info!(
+        "Email from {} delivered to agent {} (thread {})",
+        inbound.from_address,
+        account.agent_id,
+        injected.thread_id
+    );
+
+    if inbound.automated {
+        return Ok(());
+    }
+    let new_mail = AgentEvent::NewMail {
+        agent_id: account.agent_id.clone(),
+        thread_id: injected.thread_id.clone(),
+        subject: injected.subject.clone(),
+        from_name: inbound.from_name.clone().unwrap_or_else(|| inbound.from_address.clone()),
+    };
+    let _ = context
+        .observer
+        .emit("mail:received", serde_json::to_value(new_mail).unwrap_or_default());
+
+    let db_pool = db_pool.clone();
+    let agent_id = account.agent_id.clone();
+    tokio::spawn(async move {
+        if let Err(e) = crate::commands::mail::process_mail_background(
+            db_pool,
+            agent_id,
+            inbound.from_address,
+            None,
+            injected.subject,
+            inbound.body,
+            injected.thread_id,
+            context,
+        )
+        .await
+        {
+            log::error!("Background email reply failed: {}", e);
+        }
+    });
+    Ok(())
+}
@@ -1,17 +1,134 @@
-use anyagents::events::AgentObserver;
+use anyagents::event_log;
+use anyagents::events::{self, AgentObserver};
 use serde_json::Value;
-use tauri::{Emitter, Runtime, WebviewWindow};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, MutexGuard};
+use tauri::{AppHandle, Emitter, Manager, Runtime, WebviewWindow};
 
+/// Windows showing each session, by label. A session's events go to the
+/// windows subscribed to it, wherever its job was started; a session nobody
+/// subscribed to is broadcast to every window.
+#[derive(Default)]
+pub struct SessionSubscribers {
+    sessions: Mutex<HashMap<String, HashSet<String>>>,
+}
+
+impl SessionSubscribers {
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, HashSet<String>>> {
+        self.sessions.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn subscribe(&self, session_id: &str, label: &str) {
+        self.lock().entry(session_id.to_string()).or_default().insert(label.to_string());
+    }
+
+    /// Returns whether the window was subscribed
+    pub fn unsubscribe(&self, session_id: &str, label: &str) -> bool {
+        let mut sessions = self.lock();
+        let Some(labels) = sessions.get_mut(session_id) else {
+            return false;
+        };
+        let removed = labels.remove(label);
+        if labels.is_empty() {
+            sessions.remove(session_id);
+        }
+        removed
+    }
+
+    /// Drop every subscription of a closed window
+    pub fn remove_window(&self, label: &str) {
+        self.lock().retain(|_, labels| {
+            labels.remove(label);
+            !labels.is_empty()
+        });
+    }
+
+    pub fn subscribers(&self, session_id: &str) -> Vec<String> {
+        let mut labels: Vec<String> = self.lock().get(session_id).into_iter().flatten().cloned().collect();
+        labels.sort();
+        labels
+    }
+}
+
+/// Emit `payload` on `channel` through the app handle: session channels to
+/// their subscribers, everything else to every window. Session events are
+/// logged too, for windows that reload mid-job.
+pub fn emit_routed<R: Runtime>(app: &AppHandle<R>, channel: &str, payload: Value) -> Result<(), String> {
+    let payload = events::envelope(channel, payload);
+    crate::notifications::notify(app, channel, &payload);
+    if let Some(state) = app.try_state::<crate::AppState>() {
+        if let Err(e) = event_log::global().record(&state.db_pool, channel, &payload) {
+            log::warn!("Failed to log event on {}: {}", channel, e);
+        }
+    }
+
+    let labels = match (channel.strip_prefix("session:"), app.try_state::<SessionSubscribers>()) {
+        (Some(session_id), Some(subscribers)) => subscribers.subscribers(session_id),
+        _ => vec![],
+    };
+    if labels.is_empty() {
+        return app.emit(channel, payload).map_err(|e| e.to_string());
+    }
+    for label in labels {
+        app.emit_to(label.as_str(), channel, payload.clone())
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Streams a session's jobs to the windows showing it. The window that
+/// started the job is subscribed; others join with `subscribe_session`.
 #[derive(Clone)]
 pub struct TauriAgentObserver<R: Runtime> {
-    pub window: WebviewWindow<R>,
+    pub app: AppHandle<R>,
+}
+
+impl<R: Runtime> TauriAgentObserver<R> {
+    pub fn for_session(window: &WebviewWindow<R>, session_id: &str) -> Self {
+        if let Some(subscribers) = window.try_state::<SessionSubscribers>() {
+            subscribers.subscribe(session_id, window.label());
+        }
+        Self { app: window.app_handle().clone() }
+    }
 }
 
 impl<R: Runtime> AgentObserver for TauriAgentObserver<R> {
     fn emit(&self, event_name: &str, payload: Value) -> Result<(), String> {
         log::info!("TauriAgentObserver: Emitting event '{}'", event_name);
-        self.window
-            .emit(event_name, payload)
-            .map_err(|e| e.to_string())
+        emit_routed(&self.app, event_name, payload)
+    }
+}
+
+/// For runs no window started (agent bus, triggers, connectors); routed the
+/// same way
+#[derive(Clone)]
+pub struct TauriAppObserver<R: Runtime> {
+    pub app: AppHandle<R>,
+}
+
+impl<R: Runtime> AgentObserver for TauriAppObserver<R> {
+    fn emit(&self, event_name: &str, payload: Value) -> Result<(), String> {
+        emit_routed(&self.app, event_name, payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_subscribers() {
+        let subscribers = SessionSubscribers::default();
+        subscribers.subscribe("s1", "main");
+        subscribers.subscribe("s1", "session-s1");
+        subscribers.subscribe("s1", "main");
+        subscribers.subscribe("s2", "main");
+        assert_eq!(subscribers.subscribers("s1"), vec!["main", "session-s1"]);
+
+        assert!(subscribers.unsubscribe("s1", "session-s1"));
+        assert!(!subscribers.unsubscribe("s1", "session-s1"));
+        subscribers.remove_window("main");
+        assert!(subscribers.subscribers("s1").is_empty());
+        assert!(subscribers.subscribers("s2").is_empty());
     }
 }
@@ -1,9 +1,21 @@
 pub mod commands;
 pub mod telegram;
+pub mod discord;
+pub mod slack;
+pub mod matrix;
+pub mod messaging;
+pub mod email_bridge;
 pub mod events;
+pub mod notifications;
 pub mod schema;
 pub mod models;
 pub mod voice_call;
+pub mod dictation;
+pub mod speech;
+pub mod scheduler;
+pub mod agent_triggers;
+pub mod config_watcher;
+pub mod webhooks;
 
 use std::sync::Arc;
 use dashmap::DashMap;
@@ -11,9 +23,16 @@ pub mod database;
 use anyagents::database::DbPool;
 
 // Setup DB
+use agent_triggers::TriggerEngine;
+use anyagents::bus::AgentBus;
 use anyagents::permissions::PermissionManager;
+use discord::DiscordBotManager;
+use email_bridge::EmailBridgeManager;
+use matrix::MatrixBotManager;
+use slack::SlackBotManager;
 use telegram::TelegramBotManager;
 use tokio::sync::oneshot;
+use tauri::Manager;
 
 // AppState definition
 #[derive(Clone)]
@@ -21,7 +40,13 @@ pub struct AppState {
     pub db_pool: DbPool,
     pub pending_approvals: Arc<DashMap<String, oneshot::Sender<bool>>>,
     pub telegram_manager: Arc<TelegramBotManager>,
+    pub discord_manager: Arc<DiscordBotManager>,
+    pub slack_manager: Arc<SlackBotManager>,
+    pub matrix_manager: Arc<MatrixBotManager>,
     pub permission_manager: Arc<PermissionManager>,
+    pub agent_bus: Arc<AgentBus>,
+    pub trigger_engine: Arc<TriggerEngine>,
+    pub email_bridge: Arc<EmailBridgeManager>,
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -32,6 +57,14 @@ pub fn run() {
     // Initialize logger
     env_logger::init();
 
+    // Opt-in span export; the exporter runs on Tauri's runtime
+    let _telemetry = tauri::async_runtime::block_on(async {
+        anyagents::telemetry::init("anycowork").unwrap_or_else(|e| {
+            log::warn!("Telemetry disabled: {}", e);
+            None
+        })
+    });
+
     // Setup DB
     let pool = anyagents::database::establish_connection();
     crate::database::run_migrations(&pool);
@@ -39,26 +72,89 @@ pub fn run() {
     // Ensure default characters exist
     anyagents::database::ensure_default_characters(&pool);
 
+    // Provider rate limits shared by every LLM call in the app
+    anyagents::llm::scheduler::global().set_limits(anyagents::llm::scheduler::RateLimits::load(&pool));
+    // How many jobs run at once, overall and per agent
+    anyagents::agents::queue::global().set_limits(anyagents::agents::queue::JobLimits::load(&pool));
+    // Context windows of models the built-in table doesn't know
+    anyagents::llm::context::set_overrides(anyagents::llm::context::load_overrides(&pool));
+
+    if let Err(e) = anyagents::models::permission_grant::purge_expired_grants(&pool) {
+        log::error!("Failed to purge expired permission grants: {}", e);
+    }
+
     let pending_approvals = Arc::new(DashMap::new());
-    let telegram_manager = Arc::new(TelegramBotManager::new(pool.clone()));
-    let permission_manager = Arc::new(PermissionManager::new());
+    let trigger_engine = Arc::new(TriggerEngine::new());
+    let permission_manager = Arc::new(
+        PermissionManager::new()
+            .with_audit_log(pool.clone())
+            .with_persistent_grants(pool.clone()),
+    );
+    let telegram_manager = Arc::new(
+        TelegramBotManager::new(pool.clone())
+            .with_triggers(trigger_engine.clone())
+            .with_permissions(permission_manager.clone()),
+    );
+    let discord_manager = Arc::new(DiscordBotManager::new(pool.clone(), permission_manager.clone()));
+    let slack_manager = Arc::new(SlackBotManager::new(pool.clone(), permission_manager.clone()));
+    let matrix_manager = Arc::new(MatrixBotManager::new(pool.clone(), permission_manager.clone()));
+    let email_bridge = Arc::new(EmailBridgeManager::new(pool.clone(), permission_manager.clone()));
+    let agent_bus = Arc::new(AgentBus::new(pool.clone(), permission_manager.clone()));
 
     // Clone for async startup task
     let telegram_manager_clone = telegram_manager.clone();
+    let discord_manager_clone = discord_manager.clone();
+    let slack_manager_clone = slack_manager.clone();
+    let matrix_manager_clone = matrix_manager.clone();
+    let email_bridge_clone = email_bridge.clone();
+    let cleanup_pool = pool.clone();
+    let agent_bus_clone = agent_bus.clone();
+    let trigger_engine_clone = trigger_engine.clone();
 
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_notification::init())
+        .manage(events::SessionSubscribers::default())
         .manage(AppState {
             db_pool: pool,
             pending_approvals,
             telegram_manager,
+            discord_manager,
+            slack_manager,
+            matrix_manager,
             permission_manager,
+            agent_bus,
+            trigger_engine,
+            email_bridge,
         })
         .setup(move |app| {
             // Initialize voice call state
             commands::voice::init_voice_state(app);
 
+            // Apply edits to config.toml without a restart
+            config_watcher::start(app.handle());
+            app.manage(commands::transcribe::DictationMap::default());
+
+            // Inter-agent traffic goes to every window
+            agent_bus_clone.set_observer(Arc::new(crate::events::TauriAppObserver {
+                app: app.handle().clone(),
+            }));
+            // So do the runs of agents answering email with their tools
+            email_bridge_clone.set_observer(Arc::new(crate::events::TauriAppObserver {
+                app: app.handle().clone(),
+            }));
+            // And the runs answering Discord, Slack and Matrix messages
+            discord_manager_clone.set_observer(Arc::new(crate::events::TauriAppObserver {
+                app: app.handle().clone(),
+            }));
+            slack_manager_clone.set_observer(Arc::new(crate::events::TauriAppObserver {
+                app: app.handle().clone(),
+            }));
+            matrix_manager_clone.set_observer(Arc::new(crate::events::TauriAppObserver {
+                app: app.handle().clone(),
+            }));
+
             // Start all active Telegram bots on app startup
             let manager = telegram_manager_clone.clone();
             tauri::async_runtime::spawn(async move {
@@ -66,13 +162,73 @@ pub fn run() {
                     log::error!("Failed to start active Telegram bots: {}", e);
                 }
             });
+
+            // And the Discord, Slack and Matrix ones
+            let manager = discord_manager_clone.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = manager.start_all_active_bots().await {
+                    log::error!("Failed to start active Discord bots: {}", e);
+                }
+            });
+            let manager = slack_manager_clone.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = manager.start_all_active_bots().await {
+                    log::error!("Failed to start active Slack bots: {}", e);
+                }
+            });
+            let manager = matrix_manager_clone.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = manager.start_all_active_bots().await {
+                    log::error!("Failed to start active Matrix bots: {}", e);
+                }
+            });
+
+            // Watch the inboxes of agents with an active email account
+            let bridge = email_bridge_clone.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = bridge.start_all_active_watchers().await {
+                    log::error!("Failed to start email watchers: {}", e);
+                }
+            });
+
+            // Plans still "running" were cut off by the last shutdown
+            match anyagents::models::plan::mark_interrupted_plans(&cleanup_pool) {
+                Ok(0) => {}
+                Ok(n) => log::info!("Marked {} unfinished plan(s) as interrupted", n),
+                Err(e) => log::error!("Failed to mark interrupted plans: {}", e),
+            }
+
+            // Periodically remove orphaned messages, attachments, etc.
+            commands::maintenance::spawn_cleanup_scheduler(app.handle().clone(), cleanup_pool.clone());
+
+            // Back up the database on the schedule set in settings
+            commands::maintenance::spawn_backup_scheduler(app.handle().clone(), cleanup_pool.clone());
+
+            // Run scheduled tasks when they fall due
+            scheduler::spawn_scheduler(app.handle().clone(), cleanup_pool.clone());
+
+            // Start agents on file changes, new mail and Telegram messages
+            trigger_engine_clone.start(app.handle().clone(), cleanup_pool.clone());
+
+            // Accept webhooks from external systems, if enabled
+            let webhook_server = webhooks::WebhookServer::new();
+            if let Err(e) = webhook_server.restart(app.handle().clone(), cleanup_pool.clone()) {
+                log::error!("Failed to start webhook listener: {}", e);
+            }
+            app.manage(webhook_server);
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             commands::create_agent,
             commands::get_agents,
             commands::update_agent,
+            commands::get_agent_history,
+            commands::rollback_agent,
+            commands::export_agent_bundle,
+            commands::import_agent_bundle,
             commands::chat,
+            commands::edit_message,
+            commands::regenerate_from,
             commands::approve_action,
             commands::reject_action,
             commands::create_session,
@@ -81,9 +237,13 @@ pub fn run() {
             commands::delete_session,
             commands::get_session_messages,
             commands::get_session_with_messages,
+            commands::get_session_events,
+            commands::get_job_events,
             commands::add_message,
             commands::delete_message,
             commands::get_session_stats,
+            commands::export_session,
+            commands::import_session,
             commands::create_telegram_config,
             commands::get_telegram_configs,
             commands::get_telegram_config,
@@ -94,6 +254,33 @@ pub fn run() {
             commands::get_telegram_bot_status,
             commands::get_running_telegram_bots,
             commands::test_telegram_bot,
+            commands::create_discord_config,
+            commands::get_discord_configs,
+            commands::get_discord_config,
+            commands::update_discord_config,
+            commands::delete_discord_config,
+            commands::start_discord_bot,
+            commands::stop_discord_bot,
+            commands::get_discord_bot_status,
+            commands::get_running_discord_bots,
+            commands::create_slack_config,
+            commands::get_slack_configs,
+            commands::get_slack_config,
+            commands::update_slack_config,
+            commands::delete_slack_config,
+            commands::start_slack_bot,
+            commands::stop_slack_bot,
+            commands::get_slack_bot_status,
+            commands::get_running_slack_bots,
+            commands::create_matrix_config,
+            commands::get_matrix_configs,
+            commands::get_matrix_config,
+            commands::update_matrix_config,
+            commands::delete_matrix_config,
+            commands::start_matrix_bot,
+            commands::stop_matrix_bot,
+            commands::get_matrix_bot_status,
+            commands::get_running_matrix_bots,
             // Page commands
             commands::create_page,
             commands::get_pages,
@@ -112,6 +299,12 @@ pub fn run() {
             commands::upload_attachment,
             commands::get_page_attachments,
             commands::delete_attachment,
+            commands::get_attachment_usage,
+            commands::set_attachment_quota,
+            commands::collect_attachment_garbage,
+            commands::export_page_markdown,
+            commands::sync_page_file,
+            commands::sync_workspace_pages,
             // Skill commands
             commands::get_skills,
             commands::get_skill,
@@ -124,12 +317,22 @@ pub fn run() {
             commands::import_skill_from_zip,
             commands::list_marketplace_skills,
             commands::install_marketplace_skill,
+            commands::get_skill_marketplace_config,
+            commands::update_skill_marketplace_config,
+            commands::list_remote_skills,
+            commands::install_remote_skill,
+            commands::check_skill_updates,
             commands::get_skill_files,
+            commands::test_skill,
             // MCP commands
             commands::get_mcp_servers,
             commands::create_mcp_server,
             commands::update_mcp_server,
             commands::delete_mcp_server,
+            commands::test_mcp_server,
+            commands::set_mcp_secret,
+            commands::delete_mcp_secret,
+            commands::start_mcp_oauth,
             commands::get_mcp_templates,
             commands::add_mcp_to_agent,
             commands::remove_mcp_from_agent,
@@ -139,6 +342,7 @@ pub fn run() {
             commands::get_agent_skills,
             // Docker sandbox commands
             commands::check_docker_available,
+            commands::check_microvm_available,
             // Agent scope commands
             commands::update_agent_scope,
             // Window commands
@@ -146,24 +350,79 @@ pub fn run() {
             
             commands::window::is_dev_mode,
             commands::window::get_current_working_directory,
+            commands::window::subscribe_session,
+            commands::window::unsubscribe_session,
+            commands::window::open_observer_window,
+            commands::window::open_session_window,
             // App commands
             commands::transcribe_file,
             commands::check_model_status,
             commands::download_model,
             commands::get_sample_audio_path,
+            commands::start_dictation,
+            commands::push_dictation_audio,
+            commands::stop_dictation,
             // Task commands
             commands::create_task,
             commands::list_tasks,
             commands::update_task,
             commands::delete_task,
+            commands::set_task_schedule,
+            commands::list_task_runs,
+            commands::run_task_now,
+            // Agent trigger commands
+            commands::list_agent_triggers,
+            commands::create_agent_trigger,
+            commands::update_agent_trigger,
+            commands::delete_agent_trigger,
+            commands::list_permission_audit,
+            commands::get_permission_audit_entry,
+            commands::list_permission_grants,
+            commands::revoke_permission_grant,
+            commands::validate_permission_policy,
+            commands::validate_dlp_policy,
+            commands::list_secrets,
+            commands::set_secret,
+            commands::delete_secret,
+            commands::list_agent_secrets,
+            commands::grant_agent_secret,
+            commands::revoke_agent_secret,
+            commands::list_prompt_templates,
+            commands::get_prompt_template,
+            commands::list_prompt_template_versions,
+            commands::save_prompt_template,
+            commands::restore_prompt_template_version,
+            commands::reset_prompt_template,
+            commands::preview_prompt_template,
+            commands::global_search,
+            // Webhook commands
+            commands::get_webhook_config,
+            commands::update_webhook_config,
             // Mail commands
             commands::get_mail_threads,
+            commands::search_mail,
             commands::get_mail_thread_messages,
             commands::send_mail,
             commands::reply_to_mail,
+            commands::save_mail_attachment,
+            commands::get_mail_labels,
+            commands::create_mail_label,
+            commands::update_mail_label,
+            commands::delete_mail_label,
+            commands::set_mail_thread_labels,
+            commands::get_mail_groups,
+            commands::create_mail_group,
+            commands::update_mail_group,
+            commands::delete_mail_group,
             commands::mark_thread_read,
             commands::archive_thread,
             commands::get_unread_mail_count,
+            commands::create_email_account,
+            commands::get_email_accounts,
+            commands::update_email_account,
+            commands::delete_email_account,
+            commands::test_email_account,
+            commands::get_email_watcher_status,
             // Voice call commands
             commands::start_voice_call,
             commands::stop_voice_call,
@@ -174,7 +433,76 @@ pub fn run() {
             commands::get_ai_config,
             commands::update_ai_config,
             commands::get_available_models,
+            commands::validate_api_key,
+            commands::list_models,
+            commands::get_rate_limits,
+            commands::set_rate_limits,
+            commands::get_event_channel_config,
+            commands::set_event_channel_config,
+            commands::get_effective_config,
+            commands::reload_config,
+            commands::list_jobs,
+            commands::get_offline_providers,
+            commands::cancel_queued_job,
+            commands::get_job_limits,
+            commands::set_job_limits,
+            commands::get_job_artifacts,
+            commands::get_session_artifacts,
+            commands::get_usage_stats,
+            commands::get_notifications,
+            commands::get_unread_notification_count,
+            commands::mark_notifications_read,
+            commands::mark_all_notifications_read,
+            commands::clear_read_notifications,
+            commands::get_notification_preferences,
+            commands::set_notification_preferences,
+            commands::mute_agent_notifications,
+            commands::mute_session_notifications,
+            // Maintenance commands
+            commands::check_data_integrity,
+            commands::cleanup_orphaned_data,
+            commands::check_database_integrity,
+            commands::backup_database,
+            commands::list_database_backups,
+            commands::delete_database_backup,
+            commands::restore_database,
+            commands::get_backup_schedule,
+            commands::set_backup_schedule,
+            commands::get_retention_policy,
+            commands::set_retention_policy,
+            commands::apply_retention_policy,
+            commands::get_storage_stats,
+            commands::get_encryption_status,
+            commands::enable_database_encryption,
+            commands::disable_database_encryption,
+            commands::run_health_check,
+            commands::system_doctor,
+            commands::is_first_run,
+            commands::complete_onboarding,
+            // Checkpoint commands
+            commands::list_checkpoints,
+            commands::restore_checkpoint,
+            // Agent bus commands
+            commands::get_agent_bus_messages,
+            commands::send_agent_message,
+            // Plan commands
+            commands::get_session_plans,
+            commands::get_session_todos,
+            commands::get_plan,
+            commands::reorder_plan_tasks,
+            commands::skip_plan_task,
+            commands::resume_plan,
+            commands::approve_plan,
+            commands::apply_change_plan,
+            commands::discard_change_plan,
         ])
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::Destroyed = event {
+                if let Some(subscribers) = window.try_state::<events::SessionSubscribers>() {
+                    subscribers.remove_window(window.label());
+                }
+            }
+        })
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
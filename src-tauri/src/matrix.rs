@@ -0,0 +1,130 @@
+// Matrix connector: each active matrix_config runs a `MatrixConnector` under
+// the shared `ConnectorManager`, answered by the generic agent handler.
+
+use dashmap::DashMap;
+use diesel::prelude::*;
+use std::sync::Arc;
+
+use anyagents::database::DbPool;
+use anyagents::events::AgentObserver;
+use anyagents::messaging::matrix::MatrixConnector;
+use anyagents::messaging::ConnectorManager;
+use anyagents::models::MatrixConfig;
+use anyagents::permissions::PermissionManager;
+use anyagents::tools::email::NoOpObserver;
+
+use crate::messaging::AgentChatHandler;
+
+pub struct MatrixBotManager {
+    pub db_pool: DbPool,
+    connectors: ConnectorManager,
+    permission_manager: Arc<PermissionManager>,
+    /// Where runs' events go besides Matrix; only known once the app is up,
+    /// see `set_observer`
+    observer: Arc<std::sync::RwLock<Arc<dyn AgentObserver>>>,
+}
+
+impl MatrixBotManager {
+    pub fn new(db_pool: DbPool, permission_manager: Arc<PermissionManager>) -> Self {
+        Self {
+            db_pool,
+            connectors: ConnectorManager::new(),
+            permission_manager,
+            observer: Arc::new(std::sync::RwLock::new(Arc::new(NoOpObserver))),
+        }
+    }
+
+    pub fn set_observer(&self, observer: Arc<dyn AgentObserver>) {
+        *self.observer.write().unwrap() = observer;
+    }
+
+    pub async fn start_bot(&self, config_id: &str) -> Result<(), String> {
+        let config = self.get_config(config_id)?;
+        if config.is_active == 0 {
+            return Err("Bot configuration is not active".to_string());
+        }
+
+        let agent = crate::scheduler::load_agent(&self.db_pool, &config.agent_id)?;
+        let connector = Arc::new(MatrixConnector::new(&config.homeserver_url, &config.access_token)?);
+        let handler = Arc::new(AgentChatHandler {
+            platform: "matrix",
+            config_id: config_id.to_string(),
+            agent,
+            db_pool: self.db_pool.clone(),
+            allowed_conversations: config.allowed_room_ids.as_deref().map(parse_room_ids),
+            permissions: self.permission_manager.clone(),
+            observer: self.observer.clone(),
+            pending_approvals: Arc::new(DashMap::new()),
+        });
+        self.connectors.start(config_id, connector, handler).await?;
+
+        log::info!("Started Matrix bot for config: {}", config_id);
+        Ok(())
+    }
+
+    pub async fn stop_bot(&self, config_id: &str) -> Result<(), String> {
+        self.connectors.stop(config_id).await
+    }
+
+    pub async fn stop_all_bots(&self) {
+        self.connectors.stop_all().await;
+    }
+
+    pub async fn is_bot_running(&self, config_id: &str) -> bool {
+        self.connectors.is_running(config_id).await
+    }
+
+    pub async fn get_running_bot_ids(&self) -> Vec<String> {
+        self.connectors.running_ids().await
+    }
+
+    fn get_config(&self, config_id: &str) -> Result<MatrixConfig, String> {
+        use anyagents::schema::matrix_configs::dsl::*;
+
+        let mut conn = self.db_pool.get().map_err(|e| e.to_string())?;
+        matrix_configs
+            .filter(id.eq(config_id))
+            .first::<MatrixConfig>(&mut conn)
+            .map_err(|e| format!("Config not found: {}", e))
+    }
+
+    pub async fn start_all_active_bots(&self) -> Result<(), String> {
+        let configs = {
+            use anyagents::schema::matrix_configs::dsl::*;
+
+            let mut conn = self.db_pool.get().map_err(|e| e.to_string())?;
+            matrix_configs
+                .filter(is_active.eq(1))
+                .load::<MatrixConfig>(&mut conn)
+                .map_err(|e| e.to_string())?
+        };
+        for config in configs {
+            if let Err(e) = self.start_bot(&config.id).await {
+                log::error!("Failed to start Matrix bot {}: {}", config.id, e);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Comma separated room ids, e.g. `!abc:example.org, !def:example.org`
+fn parse_room_ids(ids: &str) -> Vec<String> {
+    ids.split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_room_ids() {
+        assert_eq!(
+            parse_room_ids(" !abc:example.org,,!def:example.org "),
+            ["!abc:example.org", "!def:example.org"]
+        );
+        assert!(parse_room_ids("").is_empty());
+    }
+}
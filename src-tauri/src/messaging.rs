@@ -0,0 +1,510 @@
+// Agent runs for connectors built on `anyagents::messaging`. A connector
+// that needs nothing platform specific (Matrix so far) gets DMs and mentions
+// answered by `AgentChatHandler`: a full agent run per message, streamed by
+// editing the reply where the platform allows it. Permission requests are
+// posted as text and answered by replying "approve", "deny" or "always".
+// Slack and Discord receive events their own way but report runs through
+// `run_in_conversation` too, asking for permission with buttons instead.
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use diesel::prelude::*;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use anyagents::database::DbPool;
+use anyagents::events::AgentObserver;
+use anyagents::messaging::{
+    AttachmentKind, IncomingAttachment, IncomingMessage, MessageHandler, MessagingConnector, OutgoingAttachment,
+    OutgoingMessage, SentMessage,
+};
+use anyagents::models::{Agent, NewConnectorSession};
+use anyagents::permissions::{PermissionManager, PermissionRequest, PermissionResponse};
+
+use crate::telegram::{split_message, APPROVAL_PREVIEW_LEN};
+
+/// Minimum time between edits of a streaming reply
+const EDIT_INTERVAL: Duration = Duration::from_millis(1500);
+/// Larger artifacts stay on disk instead of being sent to the chat
+const MAX_ARTIFACT_BYTES: i32 = 20 * 1024 * 1024;
+
+pub struct AgentChatHandler {
+    pub platform: &'static str,
+    pub config_id: String,
+    pub agent: Agent,
+    pub db_pool: DbPool,
+    /// Conversations the bot answers in; all when None
+    pub allowed_conversations: Option<Vec<String>>,
+    pub permissions: Arc<PermissionManager>,
+    /// Where runs' events go besides the chat
+    pub observer: Arc<std::sync::RwLock<Arc<dyn AgentObserver>>>,
+    /// Permission requests posted to a conversation, oldest first
+    pub pending_approvals: Arc<DashMap<String, Vec<String>>>,
+}
+
+#[async_trait]
+impl MessageHandler for AgentChatHandler {
+    async fn handle(&self, connector: Arc<dyn MessagingConnector>, msg: IncomingMessage) {
+        if !msg.addressed_to_bot() {
+            return;
+        }
+        if self
+            .allowed_conversations
+            .as_ref()
+            .is_some_and(|allowed| !allowed.contains(&msg.conversation_id))
+        {
+            log::info!("Ignoring {} message from unauthorized conversation: {}", self.platform, msg.conversation_id);
+            return;
+        }
+
+        let text = match msg.attachments.iter().find(|a| a.kind == AttachmentKind::Voice) {
+            Some(voice) => {
+                connector.typing(&msg.conversation_id).await;
+                match transcribe_voice(connector.as_ref(), voice).await {
+                    Ok(text) => text,
+                    Err(e) => {
+                        log::error!("Failed to transcribe voice message: {}", e);
+                        let text = format!("Sorry, I couldn't understand that voice message: {}", e);
+                        reply(connector.as_ref(), &msg, &text).await;
+                        return;
+                    }
+                }
+            }
+            None => msg.text.trim().to_string(),
+        };
+        if text.is_empty() {
+            return;
+        }
+        if let Some(response) = approval_reply(&text) {
+            if let Some(verdict) = self.answer_approval(&msg, response) {
+                reply(connector.as_ref(), &msg, &verdict).await;
+                return;
+            }
+        }
+
+        // Outside of direct chats the transcript has to say who is talking
+        let prompt = match &msg.sender {
+            Some(sender) if !msg.is_direct => format!("[{}] {}", sender.name, text),
+            _ => text,
+        };
+        let session_id = match self.session_for(&msg, &prompt) {
+            Ok(session_id) => session_id,
+            Err(e) => {
+                log::error!("Failed to prepare {} session: {}", self.platform, e);
+                reply(connector.as_ref(), &msg, &format!("Sorry, I encountered an error: {}", e)).await;
+                return;
+            }
+        };
+        connector.typing(&msg.conversation_id).await;
+
+        let started = chrono::Utc::now().naive_utc();
+        let observer = self.observer.read().unwrap().clone();
+        run_in_conversation(
+            &self.db_pool,
+            &self.agent,
+            &session_id,
+            prompt,
+            observer,
+            self.permissions.clone(),
+            connector.clone(),
+            Arc::new(TextApprovals(self.pending_approvals.clone())),
+            &msg,
+        )
+        .await;
+        send_artifacts(&self.db_pool, connector.as_ref(), &msg, &session_id, started).await;
+    }
+}
+
+impl AgentChatHandler {
+    /// Answer the oldest permission request waiting in the conversation;
+    /// None when there is none, so the message goes to the agent instead
+    fn answer_approval(&self, msg: &IncomingMessage, response: PermissionResponse) -> Option<String> {
+        let request_id = {
+            let mut pending = self.pending_approvals.get_mut(&msg.conversation_id)?;
+            if pending.is_empty() {
+                return None;
+            }
+            pending.remove(0)
+        };
+        let verdict = match response {
+            PermissionResponse::Allow => "Approved",
+            PermissionResponse::Deny => "Denied",
+            PermissionResponse::AllowAlways => "Always allowed",
+        };
+        let decided_by = match &msg.sender {
+            Some(sender) => format!("{}:{}", self.platform, sender.id),
+            None => self.platform.to_string(),
+        };
+        if self.permissions.respond_as(&request_id, response, &decided_by) {
+            Some(verdict.to_string())
+        } else {
+            Some("This request is no longer pending".to_string())
+        }
+    }
+
+    /// The conversation's session with `prompt` saved to it, created on
+    /// first use
+    fn session_for(&self, msg: &IncomingMessage, prompt: &str) -> Result<String, String> {
+        let title = match (&msg.conversation_title, &msg.sender) {
+            (Some(title), _) => title.clone(),
+            (None, Some(sender)) if msg.is_direct => sender.name.clone(),
+            _ => msg.conversation_id.clone(),
+        };
+        connector_session(
+            &self.db_pool,
+            self.platform,
+            &self.config_id,
+            &msg.conversation_id,
+            msg.thread_id.as_deref().unwrap_or_default(),
+            &self.agent.id,
+            &format!("{}: {}", platform_label(self.platform), title),
+            prompt,
+        )
+    }
+}
+
+fn platform_label(platform: &str) -> String {
+    let mut chars = platform.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Session of a conversation (or thread) on a connector, with `prompt` saved
+/// to it
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn connector_session(
+    db_pool: &DbPool,
+    platform: &str,
+    config_id: &str,
+    conversation_id: &str,
+    thread_id: &str,
+    agent_id: &str,
+    title: &str,
+    prompt: &str,
+) -> Result<String, String> {
+    use anyagents::schema::connector_sessions as sessions;
+
+    let existing = {
+        let mut conn = db_pool.get().map_err(|e| e.to_string())?;
+        sessions::table
+            .filter(sessions::platform.eq(platform))
+            .filter(sessions::config_id.eq(config_id))
+            .filter(sessions::conversation_id.eq(conversation_id))
+            .filter(sessions::thread_id.eq(thread_id))
+            .select(sessions::session_id)
+            .first::<String>(&mut conn)
+            .optional()
+            .map_err(|e| e.to_string())?
+    };
+    let session_id = crate::scheduler::prepare_session(db_pool, agent_id, existing.as_deref(), title, prompt)?;
+    if existing.is_none() {
+        let mut conn = db_pool.get().map_err(|e| e.to_string())?;
+        diesel::insert_into(sessions::table)
+            .values(&NewConnectorSession {
+                platform: platform.to_string(),
+                config_id: config_id.to_string(),
+                conversation_id: conversation_id.to_string(),
+                thread_id: thread_id.to_string(),
+                session_id: session_id.clone(),
+            })
+            .execute(&mut conn)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(session_id)
+}
+
+/// Download a voice note and transcribe it with the local speech model
+pub(crate) async fn transcribe_voice(
+    connector: &dyn MessagingConnector,
+    attachment: &IncomingAttachment,
+) -> Result<String, String> {
+    let data = connector.download(attachment).await?;
+    let dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+    let path = dir.path().join("voice.oga");
+    tokio::fs::write(&path, data).await.map_err(|e| e.to_string())?;
+    crate::speech::transcribe_recording(&path).await
+}
+
+/// Send the files the run in `session_id` produced since `since`, after its answer
+pub(crate) async fn send_artifacts(
+    db_pool: &DbPool,
+    connector: &dyn MessagingConnector,
+    msg: &IncomingMessage,
+    session_id: &str,
+    since: chrono::NaiveDateTime,
+) {
+    if !connector.capabilities().attachments {
+        return;
+    }
+    let artifacts = match anyagents::models::artifact::get_session_artifacts(db_pool, session_id, Some(since)) {
+        Ok(artifacts) => artifacts,
+        Err(e) => {
+            log::error!("Failed to load artifacts of session {}: {}", session_id, e);
+            return;
+        }
+    };
+    for artifact in artifacts {
+        if artifact.file_size > MAX_ARTIFACT_BYTES {
+            log::info!("Not sending {}: too large for a chat", artifact.path);
+            continue;
+        }
+        let data = match tokio::fs::read(&artifact.path).await {
+            Ok(data) => data,
+            Err(e) => {
+                log::warn!("Could not read artifact {}: {}", artifact.path, e);
+                continue;
+            }
+        };
+        let mut reply = OutgoingMessage::reply_to(msg, "");
+        reply.attachments.push(OutgoingAttachment {
+            kind: if artifact.kind == "image" { AttachmentKind::Image } else { AttachmentKind::File },
+            file_name: std::path::Path::new(&artifact.path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or(artifact.title),
+            mime_type: artifact.mime_type,
+            data,
+        });
+        if let Err(e) = connector.send(&msg.conversation_id, reply).await {
+            log::error!("Failed to send artifact {}: {}", artifact.path, e);
+        }
+    }
+}
+
+/// How a run asks its conversation for permission
+#[async_trait]
+pub(crate) trait ApprovalPrompt: Send + Sync {
+    async fn ask(&self, connector: &dyn MessagingConnector, msg: &IncomingMessage, request: &PermissionRequest);
+}
+
+/// Requests posted as text, answered with `approval_reply`; the pending ones
+/// by conversation, oldest first
+struct TextApprovals(Arc<DashMap<String, Vec<String>>>);
+
+#[async_trait]
+impl ApprovalPrompt for TextApprovals {
+    async fn ask(&self, connector: &dyn MessagingConnector, msg: &IncomingMessage, request: &PermissionRequest) {
+        self.0
+            .entry(msg.conversation_id.clone())
+            .or_default()
+            .push(request.id.clone());
+        reply(connector, msg, &approval_text(request)).await;
+    }
+}
+
+/// A reply that answers a pending permission request
+fn approval_reply(text: &str) -> Option<PermissionResponse> {
+    match text.trim().trim_end_matches(['.', '!']).to_lowercase().as_str() {
+        "approve" | "allow" | "yes" => Some(PermissionResponse::Allow),
+        "deny" | "no" => Some(PermissionResponse::Deny),
+        "always" | "always allow" => Some(PermissionResponse::AllowAlways),
+        _ => None,
+    }
+}
+
+fn approval_text(request: &PermissionRequest) -> String {
+    let mut text = format!("🔐 Permission needed\n{}", request.message);
+    if let Some(preview) = request.preview(APPROVAL_PREVIEW_LEN) {
+        text.push_str(&format!("\n\n{}", preview));
+    }
+    text.push_str("\n\nReply \"approve\", \"deny\" or \"always\".");
+    text
+}
+
+async fn reply(connector: &dyn MessagingConnector, msg: &IncomingMessage, text: &str) -> Option<SentMessage> {
+    match connector
+        .send(&msg.conversation_id, OutgoingMessage::reply_to(msg, text))
+        .await
+    {
+        Ok(sent) => Some(sent),
+        Err(e) => {
+            log::error!("Failed to send message: {}", e);
+            None
+        }
+    }
+}
+
+/// Run the agent on `prompt` in `session_id` and answer `msg` with it, in
+/// its conversation
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn run_in_conversation(
+    db_pool: &DbPool,
+    agent: &Agent,
+    session_id: &str,
+    prompt: String,
+    observer: Arc<dyn AgentObserver>,
+    permissions: Arc<PermissionManager>,
+    connector: Arc<dyn MessagingConnector>,
+    approvals: Arc<dyn ApprovalPrompt>,
+    msg: &IncomingMessage,
+) {
+    let (updates, rx) = mpsc::unbounded_channel();
+    let reporter = tokio::spawn(report_run(connector, msg.clone(), approvals, rx));
+    let observer = Arc::new(ChatRunObserver {
+        inner: observer,
+        channel: format!("session:{}", session_id),
+        updates: updates.clone(),
+    });
+    let _ = observer.inner.emit("sessions_updated", Value::Null);
+
+    let answer =
+        crate::scheduler::run_agent_in_session(db_pool, agent, session_id, prompt, observer, permissions).await;
+    let _ = updates.send(RunUpdate::Finished(answer));
+    let _ = reporter.await;
+}
+
+/// What a run tells its conversation
+enum RunUpdate {
+    Token(String),
+    Step(String),
+    Approval(PermissionRequest),
+    Finished(Option<String>),
+}
+
+/// Forwards a run's events to the app and reports them to the chat
+struct ChatRunObserver {
+    inner: Arc<dyn AgentObserver>,
+    channel: String,
+    updates: mpsc::UnboundedSender<RunUpdate>,
+}
+
+impl AgentObserver for ChatRunObserver {
+    fn emit(&self, event: &str, payload: Value) -> Result<(), String> {
+        if event == self.channel {
+            let update = match payload.get("type").and_then(|t| t.as_str()) {
+                Some("token") => payload["content"].as_str().map(|t| RunUpdate::Token(t.to_string())),
+                Some("step_started") => payload["step"]["tool_name"].as_str().map(|t| RunUpdate::Step(t.to_string())),
+                Some("permission_request") => payload
+                    .get("request")
+                    .and_then(|r| serde_json::from_value(r.clone()).ok())
+                    .map(RunUpdate::Approval),
+                _ => None,
+            };
+            if let Some(update) = update {
+                let _ = self.updates.send(update);
+            }
+        }
+        self.inner.emit(event, payload)
+    }
+}
+
+/// Apply one update at a time. Where messages can be edited the reply shows
+/// what the model has streamed so far, edited at most every `EDIT_INTERVAL`;
+/// elsewhere only the final answer is sent.
+async fn report_run(
+    connector: Arc<dyn MessagingConnector>,
+    msg: IncomingMessage,
+    approvals: Arc<dyn ApprovalPrompt>,
+    mut updates: mpsc::UnboundedReceiver<RunUpdate>,
+) {
+    let capabilities = connector.capabilities();
+    let mut sent: Option<SentMessage> = None;
+    let mut streamed = String::new();
+    let mut last_edit: Option<Instant> = None;
+
+    while let Some(update) = updates.recv().await {
+        match update {
+            RunUpdate::Token(token) if capabilities.edit => {
+                streamed.push_str(&token);
+                if last_edit.map_or(true, |at| at.elapsed() >= EDIT_INTERVAL) {
+                    let text = format!("{} ▌", tail(&streamed, capabilities.max_message_len - 10));
+                    sent = show(connector.as_ref(), &msg, sent, &text).await;
+                    last_edit = Some(Instant::now());
+                }
+            }
+            RunUpdate::Token(_) => {}
+            RunUpdate::Step(tool) => {
+                // Text before a tool call is the model thinking out loud
+                streamed.clear();
+                if capabilities.edit {
+                    sent = show(connector.as_ref(), &msg, sent, &format!("🔧 {}…", tool)).await;
+                    last_edit = Some(Instant::now());
+                }
+            }
+            RunUpdate::Approval(request) => approvals.ask(connector.as_ref(), &msg, &request).await,
+            RunUpdate::Finished(answer) => {
+                let answer = answer.unwrap_or_else(|| "Sorry, I couldn't come up with an answer.".to_string());
+                let mut chunks = split_message(&answer, capabilities.max_message_len).into_iter();
+                if let Some(first) = chunks.next() {
+                    show(connector.as_ref(), &msg, sent.take(), &first).await;
+                }
+                for chunk in chunks {
+                    reply(connector.as_ref(), &msg, &chunk).await;
+                }
+                break;
+            }
+        }
+    }
+}
+
+/// Edit the reply, or send it if there is none yet
+async fn show(
+    connector: &dyn MessagingConnector,
+    msg: &IncomingMessage,
+    sent: Option<SentMessage>,
+    text: &str,
+) -> Option<SentMessage> {
+    if let Some(sent) = sent {
+        match connector.edit(&sent, text).await {
+            Ok(()) => return Some(sent),
+            Err(e) => log::error!("Failed to edit message: {}", e),
+        }
+    }
+    reply(connector, msg, text).await
+}
+
+/// The last `limit` bytes of `text`, on a character boundary
+fn tail(text: &str, limit: usize) -> &str {
+    if text.len() <= limit {
+        return text;
+    }
+    let mut start = text.len() - limit;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    &text[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_approval_reply() {
+        assert!(matches!(approval_reply("Approve"), Some(PermissionResponse::Allow)));
+        assert!(matches!(approval_reply(" deny. "), Some(PermissionResponse::Deny)));
+        assert!(matches!(approval_reply("always"), Some(PermissionResponse::AllowAlways)));
+        assert!(approval_reply("approve the budget please").is_none());
+    }
+
+    #[test]
+    fn test_connector_session_per_thread() {
+        let pool = anyagents::database::create_test_pool();
+        let session = |platform, conversation, thread| {
+            connector_session(&pool, platform, "config", conversation, thread, "agent", "Matrix: Team", "hi").unwrap()
+        };
+        let room = session("matrix", "!team:example.org", "");
+        assert_eq!(session("matrix", "!team:example.org", ""), room);
+        assert_ne!(session("matrix", "!team:example.org", "$root"), room);
+        assert_ne!(session("other", "!team:example.org", ""), room);
+
+        let mut conn = pool.get().unwrap();
+        let prompts: i64 = anyagents::schema::messages::table
+            .filter(anyagents::schema::messages::session_id.eq(&room))
+            .count()
+            .get_result(&mut conn)
+            .unwrap();
+        assert_eq!(prompts, 2);
+    }
+
+    #[test]
+    fn test_tail() {
+        assert_eq!(tail("hello", 10), "hello");
+        assert_eq!(tail("hello world", 5), "world");
+        assert_eq!(tail("héllo", 4), "llo");
+    }
+}
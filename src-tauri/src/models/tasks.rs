@@ -14,6 +14,11 @@ pub struct Task {
     pub agent_id: Option<String>,
     pub created_at: String, // Stored as string in SQLite for simplicity with current setup
     pub updated_at: String,
+    pub schedule: Option<String>, // cron expression or "every 30m"
+    pub prompt_template: Option<String>,
+    pub schedule_enabled: i32,
+    pub next_run_at: Option<String>,
+    pub last_run_at: Option<String>,
 }
 
 #[derive(Insertable, Deserialize)]
@@ -40,3 +45,31 @@ pub struct UpdateTask {
     pub session_id: Option<String>, // Can assign to session
     pub updated_at: String,
 }
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::task_runs)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct TaskRun {
+    pub id: String,
+    pub task_id: String,
+    pub agent_id: String,
+    pub session_id: Option<String>,
+    pub status: String, // running, completed, failed
+    pub prompt: String,
+    pub output: Option<String>,
+    pub error: Option<String>,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::task_runs)]
+pub struct NewTaskRun {
+    pub id: String,
+    pub task_id: String,
+    pub agent_id: String,
+    pub session_id: Option<String>,
+    pub status: String,
+    pub prompt: String,
+    pub started_at: String,
+}
@@ -0,0 +1,125 @@
+//! Delivering notifications
+//!
+//! Every event the app's observers emit passes through `notify`, which asks
+//! `anyagents::notifications` whether it calls for a notification, records
+//! it in the inbox and sends it on the channels its rule names: an OS
+//! notification, a Telegram message from the configured bot, or an email
+//! from the configured account. Windows hear about each one on the
+//! `notification` channel, to keep the inbox badge current.
+
+use crate::AppState;
+use anyagents::database::DbPool;
+use anyagents::email::{self, credentials, smtp};
+use anyagents::models::{EmailAccount, Notification, TelegramConfig};
+use anyagents::notifications::{self, NotificationChannel, NotificationPreferences};
+use diesel::prelude::*;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tauri_plugin_notification::NotificationExt;
+use teloxide::prelude::*;
+
+pub fn notify<R: Runtime>(app: &AppHandle<R>, channel: &str, payload: &Value) {
+    let Some(pending) = notifications::from_event(channel, payload) else {
+        return;
+    };
+    let Some(state) = app.try_state::<AppState>() else {
+        return;
+    };
+    let pool = state.db_pool.clone();
+    let preferences = NotificationPreferences::load(&pool);
+    let (notification, channels) = match notifications::record(&pool, &preferences, pending) {
+        Ok(Some(recorded)) => recorded,
+        Ok(None) => return,
+        Err(e) => {
+            log::warn!("Failed to record notification: {}", e);
+            return;
+        }
+    };
+    let _ = app.emit("notification", &notification);
+
+    for channel in channels {
+        match channel {
+            NotificationChannel::Os => {
+                if let Err(e) = app
+                    .notification()
+                    .builder()
+                    .title(&notification.title)
+                    .body(&notification.body)
+                    .show()
+                {
+                    log::warn!("Failed to show notification: {}", e);
+                }
+            }
+            NotificationChannel::Telegram => {
+                let (pool, preferences, notification) = (pool.clone(), preferences.clone(), notification.clone());
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = send_telegram(&pool, &preferences, &notification).await {
+                        log::warn!("Failed to send notification to Telegram: {}", e);
+                    }
+                });
+            }
+            NotificationChannel::Email => {
+                let (pool, preferences, notification) = (pool.clone(), preferences.clone(), notification.clone());
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = send_email(&pool, &preferences, &notification).await {
+                        log::warn!("Failed to email notification: {}", e);
+                    }
+                });
+            }
+        }
+    }
+}
+
+async fn send_telegram(
+    pool: &DbPool,
+    preferences: &NotificationPreferences,
+    notification: &Notification,
+) -> Result<(), String> {
+    let (Some(config_id), Some(chat_id)) = (&preferences.telegram_config_id, preferences.telegram_chat_id) else {
+        return Err("No Telegram bot or chat set for notifications".to_string());
+    };
+    let config = {
+        use anyagents::schema::telegram_configs::dsl::*;
+        let mut conn = pool.get().map_err(|e| e.to_string())?;
+        telegram_configs
+            .filter(id.eq(config_id))
+            .first::<TelegramConfig>(&mut conn)
+            .map_err(|e| format!("Telegram bot not found: {}", e))?
+    };
+    Bot::new(&config.bot_token)
+        .send_message(ChatId(chat_id), format!("{}\n\n{}", notification.title, notification.body))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+async fn send_email(
+    pool: &DbPool,
+    preferences: &NotificationPreferences,
+    notification: &Notification,
+) -> Result<(), String> {
+    let (Some(account_id), Some(to)) = (&preferences.email_account_id, &preferences.email_to) else {
+        return Err("No email account or address set for notifications".to_string());
+    };
+    let account = {
+        use anyagents::schema::email_accounts::dsl::*;
+        let mut conn = pool.get().map_err(|e| e.to_string())?;
+        email_accounts
+            .filter(id.eq(account_id))
+            .first::<EmailAccount>(&mut conn)
+            .map_err(|e| format!("Email account not found: {}", e))?
+    };
+    let password = credentials::get_password(&account.id)?;
+    let outbound = smtp::OutboundEmail {
+        to: vec![to.clone()],
+        cc: vec![],
+        bcc: vec![],
+        subject: notification.title.clone(),
+        body: notification.body.clone(),
+        message_id: email::new_message_id(&account.email_address),
+        in_reply_to: None,
+        references: vec![],
+        attachments: vec![],
+    };
+    smtp::send(&account, &password, &outbound).await
+}
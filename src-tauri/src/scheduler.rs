@@ -0,0 +1,493 @@
+//! Runs scheduled tasks
+//!
+//! A task with a `schedule` (cron expression or "every 30m") and an agent is
+//! run by that agent whenever it falls due. Each run renders the task's prompt
+//! template, executes in the task's session (or a new one), is recorded in
+//! `task_runs` and ends with a `scheduler:run_finished` event.
+
+use crate::events::TauriAppObserver;
+use anyagents::event_channel::{EventChannel, EventChannelConfig};
+use crate::models::tasks::{NewTaskRun, Task, TaskRun};
+use crate::AppState;
+use anyagents::agents::queue::{self, QueuedJob};
+use anyagents::agents::AgentLoop;
+use anyagents::database::DbPool;
+use anyagents::events::AgentObserver;
+use anyagents::models::{Agent, NewMessage, NewSession};
+use anyagents::permissions::PermissionManager;
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashSet;
+use diesel::prelude::*;
+use std::str::FromStr;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+
+/// How often due tasks are checked
+const SCHEDULER_TICK_SECS: u64 = 30;
+
+/// Used when a task has no prompt template
+const DEFAULT_PROMPT_TEMPLATE: &str = "{{ title }}{% if description %}\n\n{{ description }}{% endif %}";
+
+/// Messages of a session given as history to `run_agent_in_session`
+const SESSION_HISTORY_LIMIT: i64 = 20;
+
+pub const RUN_FINISHED_EVENT: &str = "scheduler:run_finished";
+
+#[derive(Debug, Clone)]
+pub enum Trigger {
+    Interval(Duration),
+    Cron(Box<cron::Schedule>),
+}
+
+impl Trigger {
+    /// Accepts "every <n><s|m|h|d>" or a cron expression (5 fields, or 6-7 with seconds)
+    pub fn parse(schedule: &str) -> Result<Self, String> {
+        let schedule = schedule.trim();
+        if let Some(every) = schedule.strip_prefix("every ") {
+            let every = every.trim();
+            let split = every.find(|c: char| !c.is_ascii_digit()).unwrap_or(every.len());
+            let (amount, unit) = every.split_at(split);
+            let amount: i64 = amount
+                .parse()
+                .map_err(|_| format!("Invalid interval '{}'", schedule))?;
+            if amount <= 0 {
+                return Err("Interval must be positive".to_string());
+            }
+            let duration = match unit.trim() {
+                "s" => Duration::seconds(amount),
+                "m" => Duration::minutes(amount),
+                "h" => Duration::hours(amount),
+                "d" => Duration::days(amount),
+                other => return Err(format!("Unknown interval unit '{}' (use s, m, h or d)", other)),
+            };
+            return Ok(Trigger::Interval(duration));
+        }
+
+        // The cron crate expects a leading seconds field
+        let expression = if schedule.split_whitespace().count() == 5 {
+            format!("0 {}", schedule)
+        } else {
+            schedule.to_string()
+        };
+        cron::Schedule::from_str(&expression)
+            .map(|s| Trigger::Cron(Box::new(s)))
+            .map_err(|e| format!("Invalid cron expression '{}': {}", schedule, e))
+    }
+
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Trigger::Interval(duration) => Some(after + *duration),
+            Trigger::Cron(schedule) => schedule.after(&after).next(),
+        }
+    }
+}
+
+/// Next run time for a schedule, as stored in `tasks.next_run_at`
+pub fn next_run_at(schedule: &str, after: DateTime<Utc>) -> Result<Option<String>, String> {
+    Ok(Trigger::parse(schedule)?.next_after(after).map(|t| t.to_rfc3339()))
+}
+
+pub fn render_prompt(task: &Task, now: DateTime<Utc>) -> Result<String, String> {
+    let template = task
+        .prompt_template
+        .as_deref()
+        .filter(|t| !t.trim().is_empty())
+        .unwrap_or(DEFAULT_PROMPT_TEMPLATE);
+    let env = minijinja::Environment::new();
+    env.render_str(
+        template,
+        minijinja::context! {
+            title => task.title,
+            description => task.description,
+            now => now.to_rfc3339(),
+            last_run_at => task.last_run_at,
+        },
+    )
+    .map_err(|e| format!("Invalid prompt template: {}", e))
+}
+
+fn due_tasks(pool: &DbPool, now: DateTime<Utc>) -> Result<Vec<Task>, String> {
+    use crate::schema::tasks;
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let scheduled: Vec<Task> = tasks::table
+        .filter(tasks::schedule_enabled.eq(1))
+        .filter(tasks::schedule.is_not_null())
+        .load(&mut conn)
+        .map_err(|e| e.to_string())?;
+
+    Ok(scheduled
+        .into_iter()
+        .filter(|task| {
+            task.next_run_at
+                .as_deref()
+                .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                .is_some_and(|t| t <= now)
+        })
+        .collect())
+}
+
+/// Move a task's next run forward so it is not picked up again
+fn advance_schedule(pool: &DbPool, task: &Task, now: DateTime<Utc>) -> Result<(), String> {
+    use crate::schema::tasks;
+
+    let next = match task.schedule.as_deref() {
+        Some(schedule) => next_run_at(schedule, now)?,
+        None => None,
+    };
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    diesel::update(tasks::table.find(&task.id))
+        .set((
+            tasks::next_run_at.eq(next),
+            tasks::last_run_at.eq(now.to_rfc3339()),
+        ))
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn spawn_scheduler<R: Runtime>(app: AppHandle<R>, pool: DbPool) {
+    tauri::async_runtime::spawn(async move {
+        let running: Arc<DashSet<String>> = Arc::new(DashSet::new());
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(SCHEDULER_TICK_SECS));
+        loop {
+            interval.tick().await;
+            let now = Utc::now();
+            let due = match due_tasks(&pool, now) {
+                Ok(due) => due,
+                Err(e) => {
+                    log::error!("Failed to load scheduled tasks: {}", e);
+                    continue;
+                }
+            };
+
+            for task in due {
+                if let Err(e) = advance_schedule(&pool, &task, now) {
+                    log::error!("Failed to advance schedule of task {}: {}", task.id, e);
+                    continue;
+                }
+                // A slow run is not started again while it is still going
+                if !running.insert(task.id.clone()) {
+                    log::warn!("Skipping run of task {}: previous run still in progress", task.id);
+                    continue;
+                }
+                let app = app.clone();
+                let running = running.clone();
+                tauri::async_runtime::spawn(async move {
+                    let task_id = task.id.clone();
+                    if let Err(e) = run_task(&app, task).await {
+                        log::error!("Scheduled task {} failed to start: {}", task_id, e);
+                    }
+                    running.remove(&task_id);
+                });
+            }
+        }
+    });
+}
+
+/// Session for a headless run (`session_id`, or a new one titled `title`),
+/// with `prompt` saved as the user message
+pub(crate) fn prepare_session(
+    pool: &DbPool,
+    agent_id: &str,
+    session_id: Option<&str>,
+    title: &str,
+    prompt: &str,
+) -> Result<String, String> {
+    use anyagents::schema::{messages, sessions};
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let session_id = match session_id {
+        Some(id) => id.to_string(),
+        None => {
+            let now = Utc::now().naive_utc();
+            let session = NewSession {
+                id: uuid::Uuid::new_v4().to_string(),
+                agent_id: agent_id.to_string(),
+                title: Some(title.to_string()),
+                created_at: now,
+                updated_at: now,
+                archived: 0,
+                pinned: 0,
+            };
+            diesel::insert_into(sessions::table)
+                .values(&session)
+                .execute(&mut conn)
+                .map_err(|e| e.to_string())?;
+            session.id
+        }
+    };
+
+    diesel::insert_into(messages::table)
+        .values(&NewMessage {
+            id: uuid::Uuid::new_v4().to_string(),
+            role: "user".to_string(),
+            content: prompt.to_string(),
+            session_id: session_id.clone(),
+            metadata_json: None,
+            tokens: None,
+        })
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    Ok(session_id)
+}
+
+/// Run an agent without a chat window (events go to every window) and return
+/// its final answer, if any
+pub(crate) async fn run_agent<R: Runtime>(
+    app: &AppHandle<R>,
+    agent: &Agent,
+    session_id: &str,
+    prompt: String,
+    job_id: String,
+) -> Option<String> {
+    let observer = EventChannel::wrap(
+        Arc::new(TauriAppObserver { app: app.clone() }),
+        EventChannelConfig::load(&app.state::<AppState>().db_pool),
+    );
+    run_agent_with_observer(app, agent, session_id, prompt, job_id, observer).await
+}
+
+/// `run_agent` with the run's events going to `observer`, which should still
+/// pass them on to the windows
+pub(crate) async fn run_agent_with_observer<R: Runtime>(
+    app: &AppHandle<R>,
+    agent: &Agent,
+    session_id: &str,
+    prompt: String,
+    job_id: String,
+    observer: Arc<dyn AgentObserver>,
+) -> Option<String> {
+    let state = app.state::<AppState>();
+    let _ = app.emit("sessions_updated", serde_json::Value::Null);
+
+    let entry = QueuedJob::for_agent(&job_id, agent, session_id, &prompt, queue::PRIORITY_BACKGROUND);
+    let _turn = match queue::global().admit(entry, |_| {}).await {
+        Ok(turn) => turn,
+        Err(e) => {
+            log::info!("Job {} for agent {} did not run: {}", job_id, agent.name, e);
+            return None;
+        }
+    };
+
+    let mut worker = AgentLoop::new(agent, state.db_pool.clone()).await;
+    worker.session_id = session_id.to_string();
+    worker.attach_bus(state.agent_bus.clone());
+    worker
+        .run(
+            prompt,
+            observer,
+            job_id,
+            state.pending_approvals.clone(),
+            state.permission_manager.clone(),
+            state.db_pool.clone(),
+        )
+        .await;
+
+    worker
+        .history
+        .iter()
+        .rev()
+        .find(|m| matches!(m, rig::completion::Message::Assistant { .. }))
+        .map(anyagents::agents::optimizations::get_message_content)
+}
+
+/// A run for the chat connectors, which have no app handle: the session's
+/// earlier messages are the history and the final answer is returned. The
+/// prompt must already be saved to the session (see `prepare_session`).
+pub(crate) async fn run_agent_in_session(
+    pool: &DbPool,
+    agent: &Agent,
+    session_id: &str,
+    prompt: String,
+    observer: Arc<dyn AgentObserver>,
+    permissions: Arc<PermissionManager>,
+) -> Option<String> {
+    let job_id = uuid::Uuid::new_v4().to_string();
+    let entry = QueuedJob::for_agent(&job_id, agent, session_id, &prompt, queue::PRIORITY_INTERACTIVE);
+    let _turn = match queue::global().admit(entry, |_| {}).await {
+        Ok(turn) => turn,
+        Err(e) => {
+            log::info!("Job {} for agent {} did not run: {}", job_id, agent.name, e);
+            return None;
+        }
+    };
+
+    let mut worker = AgentLoop::new(agent, pool.clone()).await;
+    worker.session_id = session_id.to_string();
+    worker.history = session_history(pool, session_id);
+    worker
+        .run(
+            prompt,
+            observer,
+            job_id,
+            Arc::new(dashmap::DashMap::new()),
+            permissions,
+            pool.clone(),
+        )
+        .await;
+
+    worker
+        .history
+        .iter()
+        .rev()
+        .find(|m| matches!(m, rig::completion::Message::Assistant { .. }))
+        .map(anyagents::agents::optimizations::get_message_content)
+        .filter(|a| !a.trim().is_empty())
+}
+
+/// The last `SESSION_HISTORY_LIMIT` messages before the newest one, oldest
+/// first
+fn session_history(pool: &DbPool, session_id: &str) -> Vec<rig::completion::Message> {
+    use anyagents::schema::messages;
+
+    let Ok(mut conn) = pool.get() else {
+        return vec![];
+    };
+    let recent = messages::table
+        .filter(messages::session_id.eq(session_id))
+        .filter(messages::deleted_at.is_null())
+        .order(messages::created_at.desc())
+        .limit(SESSION_HISTORY_LIMIT + 1)
+        .load::<anyagents::models::Message>(&mut conn)
+        .unwrap_or_default();
+    recent
+        .into_iter()
+        .skip(1)
+        .rev()
+        .filter_map(|m| match m.role.as_str() {
+            "user" => Some(rig::completion::Message::user(&m.content)),
+            "assistant" | "model" => Some(rig::completion::Message::assistant(&m.content)),
+            _ => None,
+        })
+        .collect()
+}
+
+pub(crate) fn load_agent(pool: &DbPool, agent_id: &str) -> Result<Agent, String> {
+    use anyagents::schema::agents;
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    agents::table
+        .find(agent_id)
+        .first(&mut conn)
+        .map_err(|_| "Agent not found".to_string())
+}
+
+/// Run a task once with its agent and record the run
+pub async fn run_task<R: Runtime>(app: &AppHandle<R>, task: Task) -> Result<TaskRun, String> {
+    use crate::schema::task_runs;
+
+    let pool = app.state::<AppState>().db_pool.clone();
+    let agent_id = task.agent_id.clone().ok_or("Task has no agent")?;
+    let prompt = render_prompt(&task, Utc::now())?;
+    let agent = load_agent(&pool, &agent_id)?;
+    let session_id = prepare_session(
+        &pool,
+        &agent_id,
+        task.session_id.as_deref(),
+        &format!("Scheduled: {}", task.title),
+        &prompt,
+    )?;
+
+    let run = NewTaskRun {
+        id: uuid::Uuid::new_v4().to_string(),
+        task_id: task.id.clone(),
+        agent_id: agent_id.clone(),
+        session_id: Some(session_id.clone()),
+        status: "running".to_string(),
+        prompt: prompt.clone(),
+        started_at: Utc::now().to_rfc3339(),
+    };
+    {
+        let mut conn = pool.get().map_err(|e| e.to_string())?;
+        diesel::insert_into(task_runs::table)
+            .values(&run)
+            .execute(&mut conn)
+            .map_err(|e| e.to_string())?;
+    }
+
+    let output = run_agent(app, &agent, &session_id, prompt, run.id.clone()).await;
+    let (status, error) = match &output {
+        Some(_) => ("completed", None),
+        None => ("failed", Some("The agent did not produce a response".to_string())),
+    };
+
+    let finished: TaskRun = {
+        let mut conn = pool.get().map_err(|e| e.to_string())?;
+        diesel::update(task_runs::table.find(&run.id))
+            .set((
+                task_runs::status.eq(status),
+                task_runs::output.eq(&output),
+                task_runs::error.eq(&error),
+                task_runs::finished_at.eq(Utc::now().to_rfc3339()),
+            ))
+            .execute(&mut conn)
+            .map_err(|e| e.to_string())?;
+        task_runs::table
+            .find(&run.id)
+            .first(&mut conn)
+            .map_err(|e| e.to_string())?
+    };
+
+    let _ = app.emit(RUN_FINISHED_EVENT, &finished);
+    Ok(finished)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn task(template: Option<&str>) -> Task {
+        Task {
+            id: "t1".to_string(),
+            title: "Daily report".to_string(),
+            description: Some("Summarize yesterday's commits".to_string()),
+            status: "pending".to_string(),
+            priority: 0,
+            session_id: None,
+            agent_id: Some("a1".to_string()),
+            created_at: String::new(),
+            updated_at: String::new(),
+            schedule: Some("every 1d".to_string()),
+            prompt_template: template.map(str::to_string),
+            schedule_enabled: 1,
+            next_run_at: None,
+            last_run_at: None,
+        }
+    }
+
+    #[test]
+    fn test_interval_trigger() {
+        let start = Utc.with_ymd_and_hms(2026, 1, 1, 12, 0, 0).unwrap();
+        let trigger = Trigger::parse("every 30m").unwrap();
+        assert_eq!(trigger.next_after(start), Some(start + Duration::minutes(30)));
+
+        assert!(Trigger::parse("every 0m").is_err());
+        assert!(Trigger::parse("every 5w").is_err());
+    }
+
+    #[test]
+    fn test_cron_trigger() {
+        let start = Utc.with_ymd_and_hms(2026, 1, 1, 12, 0, 0).unwrap();
+        // Five-field expressions get an implicit seconds field
+        let trigger = Trigger::parse("0 9 * * *").unwrap();
+        assert_eq!(
+            trigger.next_after(start),
+            Some(Utc.with_ymd_and_hms(2026, 1, 2, 9, 0, 0).unwrap())
+        );
+        assert!(Trigger::parse("not a schedule").is_err());
+    }
+
+    #[test]
+    fn test_render_prompt() {
+        let now = Utc::now();
+        let prompt = render_prompt(&task(None), now).unwrap();
+        assert_eq!(prompt, "Daily report\n\nSummarize yesterday's commits");
+
+        let prompt = render_prompt(&task(Some("{{ title }} at {{ now }}")), now).unwrap();
+        assert_eq!(prompt, format!("Daily report at {}", now.to_rfc3339()));
+
+        assert!(render_prompt(&task(Some("{{ unclosed")), now).is_err());
+    }
+}
@@ -9,5 +9,29 @@ diesel::table! {
         agent_id -> Nullable<Text>,
         created_at -> Text,
         updated_at -> Text,
+        schedule -> Nullable<Text>,
+        prompt_template -> Nullable<Text>,
+        schedule_enabled -> Integer,
+        next_run_at -> Nullable<Text>,
+        last_run_at -> Nullable<Text>,
     }
 }
+
+diesel::table! {
+    task_runs (id) {
+        id -> Text,
+        task_id -> Text,
+        agent_id -> Text,
+        session_id -> Nullable<Text>,
+        status -> Text,
+        prompt -> Text,
+        output -> Nullable<Text>,
+        error -> Nullable<Text>,
+        started_at -> Text,
+        finished_at -> Nullable<Text>,
+    }
+}
+
+diesel::joinable!(task_runs -> tasks (task_id));
+
+diesel::allow_tables_to_appear_in_same_query!(task_runs, tasks,);
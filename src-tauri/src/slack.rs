@@ -0,0 +1,635 @@
+// Slack connector over Socket Mode, so the app needs no public URL: each
+// active slack_config keeps a websocket to Slack open with its app token and
+// answers DMs and mentions with a full agent run, replying in the message's
+// thread. Runs are reported by `messaging::run_in_conversation` through a
+// `SlackConnector`; permission requests come up as Block Kit buttons.
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use diesel::prelude::*;
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, RwLock};
+use tokio_tungstenite::{connect_async, tungstenite::Message as WsMessage};
+
+use anyagents::database::DbPool;
+use anyagents::events::AgentObserver;
+use anyagents::messaging::{
+    ChatUser, ConnectorCapabilities, IncomingAttachment, IncomingMessage, MessagingConnector, OutgoingMessage,
+    SentMessage,
+};
+use anyagents::models::{Agent, NewSlackConversationSession, SlackConfig};
+use anyagents::permissions::{PermissionManager, PermissionRequest, PermissionResponse};
+use anyagents::tools::email::NoOpObserver;
+
+use crate::messaging::ApprovalPrompt;
+use crate::telegram::{approval_data, parse_approval_data, APPROVAL_PREVIEW_LEN};
+
+pub type BotShutdownSender = mpsc::Sender<()>;
+
+const SLACK_API: &str = "https://slack.com/api";
+
+/// Slack truncates longer message text
+const MAX_MESSAGE_LEN: usize = 3900;
+
+/// Wait before reconnecting after the socket fails
+const RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+pub struct SlackBotManager {
+    pub db_pool: DbPool,
+    pub running_bots: Arc<RwLock<HashMap<String, BotShutdownSender>>>,
+    permission_manager: Arc<PermissionManager>,
+    /// Where runs' events go besides Slack; only known once the app is up,
+    /// see `set_observer`
+    observer: Arc<std::sync::RwLock<Arc<dyn AgentObserver>>>,
+}
+
+impl SlackBotManager {
+    pub fn new(db_pool: DbPool, permission_manager: Arc<PermissionManager>) -> Self {
+        Self {
+            db_pool,
+            running_bots: Arc::new(RwLock::new(HashMap::new())),
+            permission_manager,
+            observer: Arc::new(std::sync::RwLock::new(Arc::new(NoOpObserver))),
+        }
+    }
+
+    pub fn set_observer(&self, observer: Arc<dyn AgentObserver>) {
+        *self.observer.write().unwrap() = observer;
+    }
+
+    pub async fn start_bot(&self, config_id: &str) -> Result<(), String> {
+        let config = self.get_config(config_id)?;
+        if config.is_active == 0 {
+            return Err("Bot configuration is not active".to_string());
+        }
+        if self.running_bots.read().await.contains_key(config_id) {
+            return Err("Bot is already running".to_string());
+        }
+
+        let agent = crate::scheduler::load_agent(&self.db_pool, &config.agent_id)?;
+        let api = SlackApi::new(&config.bot_token);
+        // Fails early on a bad bot token, and tells us who we are
+        let auth = api.call("auth.test", json!({})).await?;
+        let bot_user_id = auth["user_id"].as_str().unwrap_or_default().to_string();
+
+        let bot = Arc::new(SlackBot {
+            config_id: config_id.to_string(),
+            agent,
+            db_pool: self.db_pool.clone(),
+            connector: Arc::new(SlackConnector {
+                api,
+                approvals: DashMap::new(),
+            }),
+            bot_user_id,
+            allowed_channels: config
+                .allowed_channel_ids
+                .as_deref()
+                .map(|ids| ids.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect()),
+            permissions: self.permission_manager.clone(),
+            observer: self.observer.clone(),
+        });
+        let app_api = SlackApi::new(&config.app_token);
+
+        let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
+        let running_bots = self.running_bots.clone();
+        let config_id_owned = config_id.to_string();
+
+        tokio::spawn(async move {
+            loop {
+                let delay = tokio::select! {
+                    result = socket_session(&app_api, &bot) => match result {
+                        // Slack asked us to reconnect
+                        Ok(()) => Duration::from_secs(1),
+                        Err(e) => {
+                            log::error!("Slack bot {} disconnected: {}", config_id_owned, e);
+                            RECONNECT_DELAY
+                        }
+                    },
+                    _ = shutdown_rx.recv() => break,
+                };
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = shutdown_rx.recv() => break,
+                }
+            }
+
+            running_bots.write().await.remove(&config_id_owned);
+            log::info!("Slack bot {} has been stopped", config_id_owned);
+        });
+
+        self.running_bots
+            .write()
+            .await
+            .insert(config_id.to_string(), shutdown_tx);
+        log::info!("Started Slack bot for config: {}", config_id);
+        Ok(())
+    }
+
+    pub async fn stop_bot(&self, config_id: &str) -> Result<(), String> {
+        let shutdown_tx = self.running_bots.write().await.remove(config_id);
+        match shutdown_tx {
+            Some(tx) => {
+                let _ = tx.send(()).await;
+                Ok(())
+            }
+            None => Err("Bot is not running".to_string()),
+        }
+    }
+
+    pub async fn stop_all_bots(&self) {
+        let bots: Vec<(String, BotShutdownSender)> = self.running_bots.write().await.drain().collect();
+        for (id, tx) in bots {
+            log::info!("Stopping Slack bot: {}", id);
+            let _ = tx.send(()).await;
+        }
+    }
+
+    pub async fn is_bot_running(&self, config_id: &str) -> bool {
+        self.running_bots.read().await.contains_key(config_id)
+    }
+
+    pub async fn get_running_bot_ids(&self) -> Vec<String> {
+        self.running_bots.read().await.keys().cloned().collect()
+    }
+
+    pub async fn start_all_active_bots(&self) -> Result<(), String> {
+        use anyagents::schema::slack_configs::dsl::*;
+
+        let configs: Vec<SlackConfig> = {
+            let mut conn = self.db_pool.get().map_err(|e| e.to_string())?;
+            slack_configs
+                .filter(is_active.eq(1))
+                .load::<SlackConfig>(&mut conn)
+                .map_err(|e| e.to_string())?
+        };
+        for config in configs {
+            if let Err(e) = self.start_bot(&config.id).await {
+                log::error!("Failed to start Slack bot {}: {}", config.id, e);
+            }
+        }
+        Ok(())
+    }
+
+    fn get_config(&self, config_id: &str) -> Result<SlackConfig, String> {
+        use anyagents::schema::slack_configs::dsl::*;
+
+        let mut conn = self.db_pool.get().map_err(|e| e.to_string())?;
+        slack_configs
+            .filter(id.eq(config_id))
+            .first::<SlackConfig>(&mut conn)
+            .map_err(|e| format!("Config not found: {}", e))
+    }
+}
+
+/// Web API calls with one token
+#[derive(Clone)]
+struct SlackApi {
+    http: reqwest::Client,
+    token: String,
+}
+
+impl SlackApi {
+    fn new(token: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            token: token.to_string(),
+        }
+    }
+
+    async fn call(&self, method: &str, body: Value) -> Result<Value, String> {
+        let response: Value = self
+            .http
+            .post(format!("{}/{}", SLACK_API, method))
+            .bearer_auth(&self.token)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("Slack {} failed: {}", method, e))?
+            .json()
+            .await
+            .map_err(|e| format!("Slack {} failed: {}", method, e))?;
+        if response["ok"].as_bool() == Some(true) {
+            Ok(response)
+        } else {
+            Err(format!(
+                "Slack {} failed: {}",
+                method,
+                response["error"].as_str().unwrap_or("unknown error")
+            ))
+        }
+    }
+
+    /// Post to a channel, in `thread_ts` unless it is empty
+    async fn post(&self, channel: &str, thread_ts: &str, mut body: Value) -> Result<String, String> {
+        body["channel"] = json!(channel);
+        if !thread_ts.is_empty() {
+            body["thread_ts"] = json!(thread_ts);
+        }
+        let response = self.call("chat.postMessage", body).await?;
+        Ok(response["ts"].as_str().unwrap_or_default().to_string())
+    }
+
+    async fn update(&self, channel: &str, ts: &str, mut body: Value) -> Result<(), String> {
+        body["channel"] = json!(channel);
+        body["ts"] = json!(ts);
+        self.call("chat.update", body).await.map(|_| ())
+    }
+}
+
+/// Posts and edits replies in the conversation's thread. Events come in over
+/// the bot's Socket Mode session rather than `connect`.
+struct SlackConnector {
+    api: SlackApi,
+    /// Permission requests posted as buttons, with the channel they went to
+    approvals: DashMap<String, String>,
+}
+
+#[async_trait]
+impl MessagingConnector for SlackConnector {
+    fn platform(&self) -> &'static str {
+        "slack"
+    }
+
+    fn capabilities(&self) -> ConnectorCapabilities {
+        ConnectorCapabilities {
+            edit: true,
+            attachments: false,
+            max_message_len: MAX_MESSAGE_LEN,
+        }
+    }
+
+    async fn connect(&self) -> Result<mpsc::Receiver<IncomingMessage>, String> {
+        Err("Slack events arrive over the bot's Socket Mode session".to_string())
+    }
+
+    async fn disconnect(&self) {}
+
+    async fn send(&self, conversation_id: &str, message: OutgoingMessage) -> Result<SentMessage, String> {
+        let thread_ts = message.thread_id.unwrap_or_default();
+        let ts = self
+            .api
+            .post(conversation_id, &thread_ts, json!({ "text": message.text }))
+            .await?;
+        Ok(SentMessage {
+            conversation_id: conversation_id.to_string(),
+            message_id: ts,
+        })
+    }
+
+    async fn edit(&self, message: &SentMessage, text: &str) -> Result<(), String> {
+        self.api
+            .update(&message.conversation_id, &message.message_id, json!({ "text": text }))
+            .await
+    }
+
+    async fn download(&self, _attachment: &IncomingAttachment) -> Result<Vec<u8>, String> {
+        Err("Slack files are not supported".to_string())
+    }
+}
+
+#[async_trait]
+impl ApprovalPrompt for SlackConnector {
+    async fn ask(&self, _connector: &dyn MessagingConnector, msg: &IncomingMessage, request: &PermissionRequest) {
+        self.approvals.insert(request.id.clone(), msg.conversation_id.clone());
+        let thread_ts = msg.thread_id.as_deref().unwrap_or_default();
+        if let Err(e) = self.api.post(&msg.conversation_id, thread_ts, approval_message(request)).await {
+            log::error!("Failed to send approval request: {}", e);
+        }
+    }
+}
+
+/// One open Socket Mode connection. Returns Ok when Slack asks us to
+/// reconnect, which it does every few hours.
+async fn socket_session(app_api: &SlackApi, bot: &Arc<SlackBot>) -> Result<(), String> {
+    let opened = app_api.call("apps.connections.open", json!({})).await?;
+    let url = opened["url"].as_str().ok_or("Slack returned no Socket Mode URL")?;
+    let (socket, _) = connect_async(url)
+        .await
+        .map_err(|e| format!("Socket Mode connection failed: {}", e))?;
+    let (mut write, mut read) = socket.split();
+
+    while let Some(frame) = read.next().await {
+        let text = match frame.map_err(|e| e.to_string())? {
+            WsMessage::Text(text) => text,
+            WsMessage::Ping(data) => {
+                let _ = write.send(WsMessage::Pong(data)).await;
+                continue;
+            }
+            WsMessage::Close(_) => return Ok(()),
+            _ => continue,
+        };
+        let Ok(envelope) = serde_json::from_str::<Value>(&text) else {
+            continue;
+        };
+        // Unacknowledged envelopes are delivered again after 3 seconds
+        if let Some(envelope_id) = envelope["envelope_id"].as_str() {
+            write
+                .send(WsMessage::Text(json!({ "envelope_id": envelope_id }).to_string()))
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        match envelope["type"].as_str() {
+            Some("events_api") => {
+                let bot = bot.clone();
+                let event = envelope["payload"]["event"].clone();
+                tokio::spawn(async move { bot.handle_event(event).await });
+            }
+            Some("interactive") => {
+                let bot = bot.clone();
+                let payload = envelope["payload"].clone();
+                tokio::spawn(async move { bot.handle_action(payload).await });
+            }
+            Some("disconnect") => return Ok(()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+struct SlackBot {
+    config_id: String,
+    agent: Agent,
+    db_pool: DbPool,
+    connector: Arc<SlackConnector>,
+    bot_user_id: String,
+    allowed_channels: Option<Vec<String>>,
+    permissions: Arc<PermissionManager>,
+    observer: Arc<std::sync::RwLock<Arc<dyn AgentObserver>>>,
+}
+
+impl SlackBot {
+    fn channel_allowed(&self, channel: &str) -> bool {
+        self.allowed_channels
+            .as_ref()
+            .map_or(true, |allowed| allowed.iter().any(|c| c == channel))
+    }
+
+    /// DMs and mentions; everything else the app is subscribed to is ignored
+    async fn handle_event(self: Arc<Self>, event: Value) {
+        // Bots (ourselves included) and edits, joins etc. are not prompts
+        if event.get("bot_id").is_some() || event.get("subtype").is_some() {
+            return;
+        }
+        let is_dm = event["channel_type"].as_str() == Some("im");
+        match event["type"].as_str() {
+            Some("app_mention") => {}
+            Some("message") if is_dm => {}
+            _ => return,
+        }
+        let Some(channel) = event["channel"].as_str() else {
+            return;
+        };
+        if !self.channel_allowed(channel) {
+            log::info!("Ignoring Slack message from unauthorized channel: {}", channel);
+            return;
+        }
+        let text = strip_mention(event["text"].as_str().unwrap_or_default(), &self.bot_user_id);
+        if text.is_empty() {
+            return;
+        }
+
+        let thread_ts = reply_thread(&event, is_dm);
+        let user = event["user"].as_str();
+        // In channels the transcript has to say who is talking
+        let prompt = match user {
+            Some(user) if !is_dm => format!("[<@{}>] {}", user, text),
+            _ => text.clone(),
+        };
+        let session_id = match self.session_for(channel, &thread_ts, is_dm, &prompt).await {
+            Ok(session_id) => session_id,
+            Err(e) => {
+                log::error!("Failed to prepare Slack session: {}", e);
+                let body = json!({ "text": format!("Sorry, I encountered an error: {}", e) });
+                let _ = self.connector.api.post(channel, &thread_ts, body).await;
+                return;
+            }
+        };
+
+        let msg = IncomingMessage {
+            conversation_id: channel.to_string(),
+            thread_id: (!thread_ts.is_empty()).then_some(thread_ts),
+            message_id: event["ts"].as_str().unwrap_or_default().to_string(),
+            sender: user.map(|user| ChatUser {
+                id: user.to_string(),
+                name: format!("<@{}>", user),
+            }),
+            text,
+            attachments: vec![],
+            is_direct: is_dm,
+            mentions_bot: !is_dm,
+            conversation_title: None,
+        };
+        let observer = self.observer.read().unwrap().clone();
+        crate::messaging::run_in_conversation(
+            &self.db_pool,
+            &self.agent,
+            &session_id,
+            prompt,
+            observer,
+            self.permissions.clone(),
+            self.connector.clone(),
+            self.connector.clone(),
+            &msg,
+        )
+        .await;
+    }
+
+    /// The conversation's session with `prompt` saved to it, created on
+    /// first use
+    async fn session_for(&self, channel: &str, thread_ts: &str, is_dm: bool, prompt: &str) -> Result<String, String> {
+        use anyagents::schema::slack_conversation_sessions as conversations;
+
+        let existing = {
+            let mut conn = self.db_pool.get().map_err(|e| e.to_string())?;
+            conversations::table
+                .filter(conversations::config_id.eq(&self.config_id))
+                .filter(conversations::channel_id.eq(channel))
+                .filter(conversations::thread_ts.eq(thread_ts))
+                .select(conversations::session_id)
+                .first::<String>(&mut conn)
+                .optional()
+                .map_err(|e| e.to_string())?
+        };
+        let title = match &existing {
+            Some(_) => String::new(),
+            None if is_dm => "Slack: direct message".to_string(),
+            None => {
+                let info = self.connector.api.call("conversations.info", json!({ "channel": channel })).await;
+                match info.ok().and_then(|i| i["channel"]["name"].as_str().map(str::to_string)) {
+                    Some(name) => format!("Slack: #{}", name),
+                    None => format!("Slack: {}", channel),
+                }
+            }
+        };
+        let session_id =
+            crate::scheduler::prepare_session(&self.db_pool, &self.agent.id, existing.as_deref(), &title, prompt)?;
+        if existing.is_none() {
+            let mut conn = self.db_pool.get().map_err(|e| e.to_string())?;
+            diesel::insert_into(conversations::table)
+                .values(&NewSlackConversationSession {
+                    config_id: self.config_id.clone(),
+                    channel_id: channel.to_string(),
+                    thread_ts: thread_ts.to_string(),
+                    session_id: session_id.clone(),
+                })
+                .execute(&mut conn)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(session_id)
+    }
+
+    /// A pressed approval button
+    async fn handle_action(self: Arc<Self>, payload: Value) {
+        if payload["type"].as_str() != Some("block_actions") {
+            return;
+        }
+        let channel = payload["container"]["channel_id"]
+            .as_str()
+            .or_else(|| payload["channel"]["id"].as_str())
+            .unwrap_or_default()
+            .to_string();
+        let user = payload["user"]["id"].as_str().unwrap_or_default();
+        let value = payload["actions"][0]["value"].as_str().unwrap_or_default();
+        let answer = self.answer_approval(value, &channel, user);
+
+        let Some(ts) = payload["container"]["message_ts"].as_str() else {
+            return;
+        };
+        let original = payload["message"]["text"].as_str().unwrap_or_default();
+        let text = format!("{}\n\n{}", original, answer);
+        let body = json!({
+            "text": text,
+            "blocks": [{ "type": "section", "text": { "type": "mrkdwn", "text": text } }],
+        });
+        if let Err(e) = self.connector.api.update(&channel, ts, body).await {
+            log::error!("Failed to update approval message: {}", e);
+        }
+    }
+
+    /// Answer the permission request behind a button; returns what to tell
+    /// the channel
+    fn answer_approval(&self, value: &str, channel: &str, user: &str) -> String {
+        let Some((response, request_id)) = parse_approval_data(value) else {
+            return "Unknown button".to_string();
+        };
+        if !self.channel_allowed(channel) {
+            return "Not allowed in this channel".to_string();
+        }
+        // Only requests that were posted to this very channel can be answered here
+        let approvals = &self.connector.approvals;
+        if approvals.get(request_id).map(|c| c.clone()).as_deref() != Some(channel) {
+            return "This request is no longer pending".to_string();
+        }
+        approvals.remove(request_id);
+
+        let verdict = match response {
+            PermissionResponse::Allow => "Approved",
+            PermissionResponse::Deny => "Denied",
+            PermissionResponse::AllowAlways => "Always allowed",
+        };
+        if self.permissions.respond_as(request_id, response, &format!("slack:{}", user)) {
+            format!("{} by <@{}>", verdict, user)
+        } else {
+            "This request is no longer pending".to_string()
+        }
+    }
+}
+
+/// Drop `<@BOT>` mentions from a message addressed to the bot
+fn strip_mention(text: &str, bot_user_id: &str) -> String {
+    if bot_user_id.is_empty() {
+        return text.trim().to_string();
+    }
+    text.replace(&format!("<@{}>", bot_user_id), "").trim().to_string()
+}
+
+/// Where to answer a message: in its thread, or (in channels) a new thread
+/// under it. Empty for the main conversation of a DM.
+fn reply_thread(event: &Value, is_dm: bool) -> String {
+    match event["thread_ts"].as_str() {
+        Some(thread_ts) => thread_ts.to_string(),
+        None if is_dm => String::new(),
+        None => event["ts"].as_str().unwrap_or_default().to_string(),
+    }
+}
+
+/// A permission request with Approve/Deny/Always buttons
+fn approval_message(request: &PermissionRequest) -> Value {
+    let mut text = format!("🔐 *Permission needed:* {}", request.message);
+    if let Some(preview) = request.preview(APPROVAL_PREVIEW_LEN) {
+        text.push_str(&format!("\n```{}```", preview));
+    }
+    let button = |label: &str, response: PermissionResponse, style: Option<&str>| {
+        let mut button = json!({
+            "type": "button",
+            "text": { "type": "plain_text", "text": label },
+            "action_id": format!("permission_{}", label.to_lowercase()),
+            "value": approval_data(&response, &request.id),
+        });
+        if let Some(style) = style {
+            button["style"] = json!(style);
+        }
+        button
+    };
+    json!({
+        "text": text,
+        "blocks": [
+            { "type": "section", "text": { "type": "mrkdwn", "text": text } },
+            {
+                "type": "actions",
+                "elements": [
+                    button("Approve", PermissionResponse::Allow, Some("primary")),
+                    button("Deny", PermissionResponse::Deny, Some("danger")),
+                    button("Always", PermissionResponse::AllowAlways, None),
+                ],
+            },
+        ],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_mention() {
+        assert_eq!(strip_mention("<@U0BOT> deploy the docs", "U0BOT"), "deploy the docs");
+        assert_eq!(strip_mention("<@U0BOT>", "U0BOT"), "");
+        assert_eq!(strip_mention(" hi <@U0OTHER> ", "U0BOT"), "hi <@U0OTHER>");
+    }
+
+    #[test]
+    fn test_reply_thread() {
+        let top_level = json!({ "ts": "1700000000.000100" });
+        let in_thread = json!({ "ts": "1700000000.000200", "thread_ts": "1700000000.000100" });
+        assert_eq!(reply_thread(&top_level, false), "1700000000.000100");
+        assert_eq!(reply_thread(&top_level, true), "");
+        assert_eq!(reply_thread(&in_thread, false), "1700000000.000100");
+        assert_eq!(reply_thread(&in_thread, true), "1700000000.000100");
+    }
+
+    #[test]
+    fn test_approval_message() {
+        let request = PermissionRequest {
+            id: "req-1".to_string(),
+            permission_type: anyagents::permissions::PermissionType::ShellExecute,
+            message: "Run tests".to_string(),
+            metadata: HashMap::from([("resource".to_string(), "cargo test".to_string())]),
+            payload: Some(anyagents::permissions::PermissionPayload::Command {
+                command: "cargo test".to_string(),
+                working_dir: None,
+            }),
+        };
+        let message = approval_message(&request);
+        assert!(message["text"].as_str().unwrap().contains("```$ cargo test```"));
+        let buttons = message["blocks"][1]["elements"].as_array().unwrap();
+        assert_eq!(buttons.len(), 3);
+        let values: Vec<_> = buttons.iter().map(|b| b["value"].as_str().unwrap()).collect();
+        assert_eq!(values, ["perm:allow:req-1", "perm:deny:req-1", "perm:always:req-1"]);
+        assert!(values.iter().all(|v| parse_approval_data(v).is_some_and(|(_, id)| id == "req-1")));
+    }
+}
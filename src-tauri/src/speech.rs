@@ -0,0 +1,141 @@
+// Speech for chat bridges: turning recorded voice notes into text with the
+// local Parakeet model, and text into spoken replies with OpenAI's TTS.
+
+use anyagents::database::DbPool;
+use serde_json::json;
+use std::path::Path;
+use std::time::Duration;
+use transcribe_rs::engines::parakeet::ParakeetEngine;
+use transcribe_rs::TranscriptionEngine;
+
+/// Sample rate the transcription model expects
+const SAMPLE_RATE: u32 = 16_000;
+
+/// Longest input the speech endpoint accepts, in characters
+const MAX_SPEECH_CHARS: usize = 4096;
+
+const SPEECH_URL: &str = "https://api.openai.com/v1/audio/speech";
+const SPEECH_MODEL: &str = "gpt-4o-mini-tts";
+const DEFAULT_VOICE: &str = "alloy";
+
+/// Transcribe a compressed recording (e.g. a Telegram OGG/Opus voice note).
+/// Needs the dictation model to be downloaded and `ffmpeg` on the PATH.
+pub async fn transcribe_recording(path: &Path) -> Result<String, String> {
+    let model_path = crate::commands::transcribe::find_model_path()?
+        .ok_or("Speech model not downloaded. Download it from the dictation settings first.")?;
+    let pcm = decode_to_pcm16(path).await?;
+
+    tokio::task::spawn_blocking(move || {
+        let samples = crate::dictation::pcm16_to_samples(&pcm, SAMPLE_RATE);
+        let mut engine = ParakeetEngine::new();
+        engine.load_model(&model_path).map_err(|e| e.to_string())?;
+        engine
+            .transcribe_samples(samples, None)
+            .map(|r| r.text.trim().to_string())
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Decode any audio ffmpeg understands to 16 kHz mono 16-bit PCM
+async fn decode_to_pcm16(path: &Path) -> Result<Vec<u8>, String> {
+    let output = tokio::process::Command::new("ffmpeg")
+        .args(["-nostdin", "-loglevel", "error", "-i"])
+        .arg(path)
+        .args(["-f", "s16le", "-ac", "1", "-ar", &SAMPLE_RATE.to_string(), "pipe:1"])
+        .output()
+        .await
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => "ffmpeg is required to decode voice messages but was not found".to_string(),
+            _ => format!("Failed to run ffmpeg: {}", e),
+        })?;
+    if !output.status.success() {
+        return Err(format!(
+            "ffmpeg could not decode the recording: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Speak `text` as OGG/Opus, the format Telegram plays as a voice note.
+/// The voice can be changed with the `TTS_VOICE` setting.
+pub async fn synthesize_voice(db_pool: &DbPool, text: &str) -> Result<Vec<u8>, String> {
+    let key = setting(db_pool, "OPENAI_API_KEY").ok_or("OPENAI_API_KEY not set (env or settings)")?;
+    let voice = setting(db_pool, "TTS_VOICE").unwrap_or_else(|| DEFAULT_VOICE.to_string());
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(120))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client
+        .post(SPEECH_URL)
+        .bearer_auth(key)
+        .json(&json!({
+            "model": SPEECH_MODEL,
+            "voice": voice,
+            "input": speech_input(text),
+            "response_format": "opus",
+        }))
+        .send()
+        .await
+        .map_err(|e| format!("Speech request failed: {}", e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("Speech request failed ({}): {}", status, body));
+    }
+    response
+        .bytes()
+        .await
+        .map(|b| b.to_vec())
+        .map_err(|e| e.to_string())
+}
+
+/// Settings first, then the environment
+fn setting(db_pool: &DbPool, key: &str) -> Option<String> {
+    anyagents::models::settings::get_setting(db_pool, key)
+        .or_else(|| std::env::var(key).ok())
+        .filter(|v| !v.trim().is_empty())
+}
+
+/// Trim a reply to what the speech endpoint accepts, cutting at a sentence
+/// or word boundary where possible
+fn speech_input(text: &str) -> String {
+    let text = text.trim();
+    if text.chars().count() <= MAX_SPEECH_CHARS {
+        return text.to_string();
+    }
+    let end = text
+        .char_indices()
+        .nth(MAX_SPEECH_CHARS)
+        .map(|(i, _)| i)
+        .unwrap_or(text.len());
+    let head = &text[..end];
+    let cut = head
+        .rfind(['.', '!', '?', '\n'])
+        .map(|i| i + 1)
+        .or_else(|| head.rfind(' '))
+        .unwrap_or(end);
+    head[..cut].trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_speech_input() {
+        assert_eq!(speech_input("  Hello there.  "), "Hello there.");
+
+        let long = format!("{} Second sentence {}", "First sentence.", "word ".repeat(1000));
+        let trimmed = speech_input(&long);
+        assert_eq!(trimmed, "First sentence.");
+
+        let words = "word ".repeat(1000);
+        let trimmed = speech_input(&words);
+        assert!(trimmed.chars().count() <= MAX_SPEECH_CHARS);
+        assert!(trimmed.ends_with("word"));
+    }
+}
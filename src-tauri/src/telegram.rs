@@ -1,48 +1,109 @@
+// Telegram connector: the transport is a `MessagingConnector` run by the
+// shared `ConnectorManager`, and `ChatBridge` answers its messages. Approval
+// buttons are Telegram specific and answered inside the connector.
+
+use async_trait::async_trait;
+use dashmap::DashMap;
 use diesel::prelude::*;
-use rig::client::CompletionClient;
-use rig::client::ProviderClient;
-use rig::completion::Prompt;
-use rig::providers::openai;
+use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::Arc;
+use teloxide::dispatching::ShutdownToken;
+use teloxide::net::Download;
 use teloxide::prelude::*;
+use teloxide::types::{
+    ChatAction, FileId, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, MessageId, ReplyParameters, ThreadId,
+    UserId,
+};
 use tokio::sync::{mpsc, RwLock};
 
 use anyagents::database::DbPool;
-use anyagents::models::{Agent, TelegramConfig};
+use anyagents::events::AgentObserver;
+use anyagents::llm::LlmClient;
+use anyagents::messaging::{
+    AttachmentKind, ChatUser, ConnectorCapabilities, ConnectorManager, IncomingAttachment, IncomingMessage,
+    MessageHandler, MessagingConnector, OutgoingAttachment, OutgoingMessage, SentMessage,
+};
+use anyagents::models::{Agent, NewMessage, NewSession, NewTelegramChatSession, TelegramConfig};
+use anyagents::permissions::{PermissionManager, PermissionRequest, PermissionResponse};
+use crate::agent_triggers::{TriggerEngine, TriggerEvent};
+
+/// Steps listed in a run's progress message
+const MAX_PROGRESS_STEPS: usize = 15;
 
-pub type BotShutdownSender = mpsc::Sender<()>;
+/// Telegram rejects messages over 4096 characters
+const MAX_MESSAGE_LEN: usize = 4000;
+
+/// Longest command, diff or email preview shown with an approval request
+pub(crate) const APPROVAL_PREVIEW_LEN: usize = 1500;
 
 pub struct TelegramBotManager {
     pub db_pool: DbPool,
-    pub running_bots: Arc<RwLock<HashMap<String, BotShutdownSender>>>,
+    connectors: ConnectorManager,
+    /// Receives incoming messages for `telegram` agent triggers
+    pub trigger_engine: Option<Arc<TriggerEngine>>,
+    /// Answers approval buttons pressed in a chat
+    pub permission_manager: Option<Arc<PermissionManager>>,
+    /// Bots of running configs, by agent id, for reporting triggered runs
+    agent_bots: Arc<RwLock<HashMap<String, Bot>>>,
+    /// Permission requests sent to a chat as buttons, by request id
+    approvals: Arc<DashMap<String, ChatId>>,
 }
 
 impl TelegramBotManager {
     pub fn new(db_pool: DbPool) -> Self {
         Self {
             db_pool,
-            running_bots: Arc::new(RwLock::new(HashMap::new())),
+            connectors: ConnectorManager::new(),
+            trigger_engine: None,
+            permission_manager: None,
+            agent_bots: Arc::new(RwLock::new(HashMap::new())),
+            approvals: Arc::new(DashMap::new()),
         }
     }
 
+    pub fn with_triggers(mut self, engine: Arc<TriggerEngine>) -> Self {
+        self.trigger_engine = Some(engine);
+        self
+    }
+
+    pub fn with_permissions(mut self, permission_manager: Arc<PermissionManager>) -> Self {
+        self.permission_manager = Some(permission_manager);
+        self
+    }
+
+    /// An observer for an agent run started from `chat_id`: permission
+    /// requests become approval buttons there and step progress is kept in
+    /// one edited message. Events are passed on to `inner` as well, so the app
+    /// can answer too. None when the agent has no running bot.
+    pub async fn run_reporter(
+        &self,
+        agent_id: &str,
+        chat_id: i64,
+        session_id: &str,
+        inner: Arc<dyn AgentObserver>,
+    ) -> Option<Arc<TelegramRunObserver>> {
+        let bot = self.agent_bots.read().await.get(agent_id)?.clone();
+        let (updates, rx) = mpsc::unbounded_channel();
+        tokio::spawn(report_run(bot, ChatId(chat_id), self.approvals.clone(), rx));
+        Some(Arc::new(TelegramRunObserver {
+            inner,
+            channel: format!("session:{}", session_id),
+            updates,
+        }))
+    }
+
     pub async fn start_bot(&self, config_id: &str) -> Result<(), String> {
         let config = self.get_config(config_id)?;
 
         if config.is_active == 0 {
             return Err("Bot configuration is not active".to_string());
         }
-
-        // Check if bot is already running
-        {
-            let bots = self.running_bots.read().await;
-            if bots.contains_key(config_id) {
-                return Err("Bot is already running".to_string());
-            }
+        if self.connectors.is_running(config_id).await {
+            return Err("Bot is already running".to_string());
         }
 
         let agent = self.get_agent(&config.agent_id)?;
-        let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
 
         // Parse allowed chat IDs
         let allowed_chats: Option<Vec<i64>> = config.allowed_chat_ids.as_ref().map(|ids| {
@@ -50,127 +111,59 @@ impl TelegramBotManager {
                 .filter_map(|s| s.trim().parse().ok())
                 .collect()
         });
+        let groups = match config.group_allowlists.as_deref() {
+            Some(lists) => Some(GroupAllowlists::parse(lists)?),
+            None => None,
+        };
 
         let bot = Bot::new(&config.bot_token);
-        let config_id_owned = config_id.to_string();
-        let running_bots = self.running_bots.clone();
-        let db_pool = self.db_pool.clone();
-
-        tokio::spawn(async move {
-            let handler = Update::filter_message()
-                .filter_map(|msg: Message| {
-                    let text = msg.text()?.to_string();
-                    Some((msg, text))
-                })
-                .endpoint(move |bot: Bot, (msg, text): (Message, String)| {
-                    let agent = agent.clone();
-                    let allowed_chats = allowed_chats.clone();
-                    let _db_pool = db_pool.clone(); // Reserved for future use (storing messages)
-
-                    async move {
-                        // Check if chat is allowed (if restrictions exist)
-                        if let Some(ref allowed) = allowed_chats {
-                            if !allowed.contains(&msg.chat.id.0) {
-                                log::info!(
-                                    "Ignoring message from unauthorized chat: {}",
-                                    msg.chat.id.0
-                                );
-                                return Ok::<(), teloxide::RequestError>(());
-                            }
-                        }
-
-                        // Send typing indicator
-                        let _ = bot
-                            .send_chat_action(msg.chat.id, teloxide::types::ChatAction::Typing)
-                            .await;
-
-                        // Process message with agent
-                        match process_message_with_agent(&agent, &text).await {
-                            Ok(response) => {
-                                // Split long messages (Telegram has 4096 char limit)
-                                for chunk in split_message(&response, 4000) {
-                                    if let Err(e) = bot.send_message(msg.chat.id, chunk).await {
-                                        log::error!("Failed to send message: {}", e);
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                let error_msg = format!("Sorry, I encountered an error: {}", e);
-                                let _ = bot.send_message(msg.chat.id, error_msg).await;
-                            }
-                        }
-
-                        Ok(())
-                    }
-                });
-
-            let mut dispatcher = Dispatcher::builder(bot, handler)
-                .enable_ctrlc_handler()
-                .build();
-
-            let shutdown_token = dispatcher.shutdown_token();
-
-            tokio::select! {
-                _ = dispatcher.dispatch() => {
-                    log::info!("Bot dispatcher stopped");
-                }
-                _ = shutdown_rx.recv() => {
-                    log::info!("Received shutdown signal for bot");
-                    shutdown_token.shutdown().ok();
-                }
-            }
-
-            // Clean up when done
-            let mut bots = running_bots.write().await;
-            bots.remove(&config_id_owned);
-            log::info!("Bot {} has been stopped", config_id_owned);
+        let connector = Arc::new(TelegramConnector {
+            bot: bot.clone(),
+            buttons: ApprovalButtons {
+                permission_manager: self.permission_manager.clone(),
+                approvals: self.approvals.clone(),
+                allowed_chats: allowed_chats.clone(),
+                groups: groups.clone(),
+            },
+            shutdown: std::sync::Mutex::new(None),
+        });
+        let bridge = Arc::new(ChatBridge {
+            config_id: config_id.to_string(),
+            agent,
+            db_pool: self.db_pool.clone(),
+            trigger_engine: self.trigger_engine.clone(),
+            allowed_chats,
+            groups,
+            voice_replies: config.voice_replies != 0,
         });
 
-        // Store the shutdown sender
-        {
-            let mut bots = self.running_bots.write().await;
-            bots.insert(config_id.to_string(), shutdown_tx);
-        }
+        let agent_id = bridge.agent.id.clone();
+        self.connectors.start(config_id, connector, bridge).await?;
+        self.agent_bots.write().await.insert(agent_id, bot);
 
         log::info!("Started Telegram bot for config: {}", config_id);
         Ok(())
     }
 
     pub async fn stop_bot(&self, config_id: &str) -> Result<(), String> {
-        let shutdown_tx = {
-            let mut bots = self.running_bots.write().await;
-            bots.remove(config_id)
-        };
-
-        match shutdown_tx {
-            Some(tx) => {
-                let _ = tx.send(()).await;
-                Ok(())
-            }
-            None => Err("Bot is not running".to_string()),
+        self.connectors.stop(config_id).await?;
+        if let Ok(config) = self.get_config(config_id) {
+            self.agent_bots.write().await.remove(&config.agent_id);
         }
+        Ok(())
     }
 
     pub async fn stop_all_bots(&self) {
-        let bots: Vec<(String, BotShutdownSender)> = {
-            let mut running = self.running_bots.write().await;
-            running.drain().collect()
-        };
-
-        for (id, tx) in bots {
-            log::info!("Stopping bot: {}", id);
-            let _ = tx.send(()).await;
-        }
+        self.connectors.stop_all().await;
+        self.agent_bots.write().await.clear();
     }
 
     pub async fn is_bot_running(&self, config_id: &str) -> bool {
-        let bots = self.running_bots.read().await;
-        bots.contains_key(config_id)
+        self.connectors.is_running(config_id).await
     }
 
     pub async fn get_running_bot_ids(&self) -> Vec<String> {
-        let bots = self.running_bots.read().await;
-        bots.keys().cloned().collect()
+        self.connectors.running_ids().await
     }
 
     fn get_config(&self, config_id: &str) -> Result<TelegramConfig, String> {
@@ -216,27 +209,779 @@ impl TelegramBotManager {
     }
 }
 
-async fn process_message_with_agent(agent: &Agent, user_message: &str) -> Result<String, String> {
-    let client = openai::Client::from_env();
+/// What the connector needs to answer approval buttons
+#[derive(Clone)]
+struct ApprovalButtons {
+    permission_manager: Option<Arc<PermissionManager>>,
+    approvals: Arc<DashMap<String, ChatId>>,
+    allowed_chats: Option<Vec<i64>>,
+    groups: Option<GroupAllowlists>,
+}
 
-    // Build the agent with system prompt (preamble)
-    let ai_agent = if let Some(prompt) = &agent.system_prompt {
-        if !prompt.is_empty() {
-            client.agent("gpt-4").preamble(prompt).build()
-        } else {
-            client.agent("gpt-4").build()
+/// A bot account as a `MessagingConnector`
+pub struct TelegramConnector {
+    bot: Bot,
+    buttons: ApprovalButtons,
+    shutdown: std::sync::Mutex<Option<ShutdownToken>>,
+}
+
+impl TelegramConnector {
+    fn chat_id(conversation_id: &str) -> Result<ChatId, String> {
+        conversation_id
+            .parse()
+            .map(ChatId)
+            .map_err(|_| format!("Invalid Telegram chat id: {}", conversation_id))
+    }
+
+    fn message_id(message_id: &str) -> Result<MessageId, String> {
+        message_id
+            .parse()
+            .map(MessageId)
+            .map_err(|_| format!("Invalid Telegram message id: {}", message_id))
+    }
+
+    async fn send_attachment(
+        &self,
+        chat_id: ChatId,
+        attachment: &OutgoingAttachment,
+        reply: Option<ReplyParameters>,
+    ) -> Result<Message, teloxide::RequestError> {
+        let file = InputFile::memory(attachment.data.clone()).file_name(attachment.file_name.clone());
+        match attachment.kind {
+            AttachmentKind::Voice => {
+                let mut request = self.bot.send_voice(chat_id, file);
+                if let Some(reply) = reply {
+                    request = request.reply_parameters(reply);
+                }
+                request.await
+            }
+            AttachmentKind::Image => {
+                let mut request = self.bot.send_photo(chat_id, file);
+                if let Some(reply) = reply {
+                    request = request.reply_parameters(reply);
+                }
+                request.await
+            }
+            _ => {
+                let mut request = self.bot.send_document(chat_id, file);
+                if let Some(reply) = reply {
+                    request = request.reply_parameters(reply);
+                }
+                request.await
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl MessagingConnector for TelegramConnector {
+    fn platform(&self) -> &'static str {
+        "telegram"
+    }
+
+    fn capabilities(&self) -> ConnectorCapabilities {
+        ConnectorCapabilities {
+            edit: true,
+            attachments: true,
+            max_message_len: MAX_MESSAGE_LEN,
+        }
+    }
+
+    async fn connect(&self) -> Result<mpsc::Receiver<IncomingMessage>, String> {
+        // Needed to tell whether a group message is meant for us
+        let bot_user = match self.bot.get_me().await {
+            Ok(me) => Some((me.user.id, me.user.username.clone().unwrap_or_default())),
+            Err(e) => {
+                log::warn!("Could not look up the bot account, group messages will be ignored: {}", e);
+                None
+            }
+        };
+
+        let (tx, rx) = mpsc::channel(64);
+        let messages = Update::filter_message().endpoint(move |msg: Message| {
+            let tx = tx.clone();
+            let incoming = incoming_message(&msg, bot_user.as_ref());
+            async move {
+                if let Some(incoming) = incoming {
+                    let _ = tx.send(incoming).await;
+                }
+                Ok::<(), teloxide::RequestError>(())
+            }
+        });
+
+        // Approve/Deny/Always buttons on permission requests
+        let buttons = self.buttons.clone();
+        let callbacks = Update::filter_callback_query().endpoint(move |bot: Bot, query: CallbackQuery| {
+            let buttons = buttons.clone();
+            async move {
+                let answer = handle_approval(
+                    &query,
+                    buttons.permission_manager.as_deref(),
+                    &buttons.approvals,
+                    buttons.allowed_chats.as_deref(),
+                    buttons.groups.as_ref(),
+                );
+                let _ = bot.answer_callback_query(query.id.clone()).text(answer.clone()).await;
+                if let Some(message) = query.message.as_ref() {
+                    let text = match message.regular_message().and_then(|m| m.text()) {
+                        Some(text) => format!("{}\n\n{}", text, answer),
+                        None => answer,
+                    };
+                    let _ = bot.edit_message_text(message.chat().id, message.id(), text).await;
+                }
+                Ok::<(), teloxide::RequestError>(())
+            }
+        });
+
+        let handler = dptree::entry().branch(messages).branch(callbacks);
+        let mut dispatcher = Dispatcher::builder(self.bot.clone(), handler)
+            .enable_ctrlc_handler()
+            .build();
+        *self.shutdown.lock().unwrap() = Some(dispatcher.shutdown_token());
+        // The message channel closes with the dispatcher
+        tokio::spawn(async move {
+            dispatcher.dispatch().await;
+            log::info!("Bot dispatcher stopped");
+        });
+        Ok(rx)
+    }
+
+    async fn disconnect(&self) {
+        if let Some(token) = self.shutdown.lock().unwrap().take() {
+            token.shutdown().ok();
+        }
+    }
+
+    async fn send(&self, conversation_id: &str, message: OutgoingMessage) -> Result<SentMessage, String> {
+        let chat_id = Self::chat_id(conversation_id)?;
+        let reply = match message.reply_to.as_deref() {
+            Some(id) => Some(ReplyParameters::new(Self::message_id(id)?)),
+            None => None,
+        };
+        let mut first: Option<MessageId> = None;
+        if !message.text.is_empty() || message.attachments.is_empty() {
+            let mut request = self.bot.send_message(chat_id, &message.text);
+            match (&reply, message.thread_id.as_deref()) {
+                (Some(reply), _) => request = request.reply_parameters(reply.clone()),
+                (None, Some(thread)) => request = request.message_thread_id(ThreadId(Self::message_id(thread)?)),
+                (None, None) => {}
+            }
+            first = Some(request.await.map_err(|e| e.to_string())?.id);
+        }
+        for attachment in &message.attachments {
+            let sent = self
+                .send_attachment(chat_id, attachment, reply.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+            first.get_or_insert(sent.id);
+        }
+        Ok(SentMessage {
+            conversation_id: conversation_id.to_string(),
+            message_id: first.map(|id| id.0.to_string()).unwrap_or_default(),
+        })
+    }
+
+    async fn edit(&self, message: &SentMessage, text: &str) -> Result<(), String> {
+        self.bot
+            .edit_message_text(
+                Self::chat_id(&message.conversation_id)?,
+                Self::message_id(&message.message_id)?,
+                text,
+            )
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    async fn download(&self, attachment: &IncomingAttachment) -> Result<Vec<u8>, String> {
+        let file = self
+            .bot
+            .get_file(FileId(attachment.id.clone()))
+            .await
+            .map_err(|e| e.to_string())?;
+        let mut data = Vec::new();
+        self.bot
+            .download_file(&file.path, &mut data)
+            .await
+            .map_err(|e| format!("Download failed: {}", e))?;
+        Ok(data)
+    }
+
+    async fn typing(&self, conversation_id: &str) {
+        if let Ok(chat_id) = Self::chat_id(conversation_id) {
+            let _ = self.bot.send_chat_action(chat_id, ChatAction::Typing).await;
+        }
+    }
+}
+
+/// A message the bot may answer: text, or a voice note still to transcribe.
+/// `bot_user` is the bot's own account and username, to spot mentions and
+/// replies in groups.
+fn incoming_message(msg: &Message, bot_user: Option<&(UserId, String)>) -> Option<IncomingMessage> {
+    let (text, attachments) = match (msg.text(), msg.voice()) {
+        (Some(text), _) => (text.to_string(), vec![]),
+        (None, Some(voice)) => (
+            String::new(),
+            vec![IncomingAttachment {
+                id: voice.file.id.0.clone(),
+                kind: AttachmentKind::Voice,
+                file_name: None,
+                mime_type: voice.mime_type.as_ref().map(|m| m.to_string()),
+            }],
+        ),
+        _ => return None,
+    };
+    let is_direct = msg.chat.is_private();
+    let mentions_bot = !is_direct && bot_user.is_some_and(|(id, username)| addressed(msg, *id, username));
+    let text = match bot_user {
+        Some((_, username)) if mentions_bot => strip_mention(&text, username),
+        _ => text,
+    };
+    let topic = topic_id(msg);
+    Some(IncomingMessage {
+        conversation_id: msg.chat.id.0.to_string(),
+        thread_id: (topic != 0).then(|| topic.to_string()),
+        message_id: msg.id.0.to_string(),
+        sender: msg.from.as_ref().map(|user| ChatUser {
+            id: user.id.0.to_string(),
+            name: user_label(user),
+        }),
+        text,
+        attachments,
+        is_direct,
+        mentions_bot,
+        conversation_title: msg.chat.title().map(str::to_string),
+    })
+}
+
+/// In groups only messages that mention the bot or reply to it are ours
+fn addressed(msg: &Message, bot_id: UserId, username: &str) -> bool {
+    let replied_to_bot = msg
+        .reply_to_message()
+        .and_then(|m| m.from.as_ref())
+        .is_some_and(|u| u.id == bot_id);
+    replied_to_bot || msg.text().is_some_and(|text| mentions(text, username))
+}
+
+/// What a running bot needs to answer its chats
+struct ChatBridge {
+    config_id: String,
+    agent: Agent,
+    db_pool: DbPool,
+    trigger_engine: Option<Arc<TriggerEngine>>,
+    allowed_chats: Option<Vec<i64>>,
+    groups: Option<GroupAllowlists>,
+    voice_replies: bool,
+}
+
+#[async_trait]
+impl MessageHandler for ChatBridge {
+    async fn handle(&self, connector: Arc<dyn MessagingConnector>, msg: IncomingMessage) {
+        let Ok(chat_id) = msg.conversation_id.parse::<i64>() else {
+            return;
+        };
+        // Check if chat is allowed (if restrictions exist)
+        if let Some(ref allowed) = self.allowed_chats {
+            if !allowed.contains(&chat_id) {
+                log::info!("Ignoring message from unauthorized chat: {}", chat_id);
+                return;
+            }
+        }
+
+        let user_id = msg.sender.as_ref().and_then(|s| s.id.parse::<i64>().ok());
+        if !msg.is_direct {
+            if !msg.mentions_bot {
+                return;
+            }
+            if self.groups.as_ref().is_some_and(|g| !g.allows(chat_id, user_id)) {
+                log::info!("Ignoring message from {:?} in group {}: not allowlisted", user_id, chat_id);
+                return;
+            }
+        }
+
+        let voice = msg.attachments.iter().find(|a| a.kind == AttachmentKind::Voice);
+        let text = match voice {
+            None => msg.text.clone(),
+            Some(voice) => {
+                connector.typing(&msg.conversation_id).await;
+                match crate::messaging::transcribe_voice(connector.as_ref(), voice).await {
+                    Ok(text) if !text.is_empty() => text,
+                    Ok(_) => {
+                        self.reply(connector.as_ref(), &msg, "I couldn't hear anything in that voice message.")
+                            .await;
+                        return;
+                    }
+                    Err(e) => {
+                        log::error!("Failed to transcribe voice message: {}", e);
+                        let text = format!("Sorry, I couldn't understand that voice message: {}", e);
+                        self.reply(connector.as_ref(), &msg, &text).await;
+                        return;
+                    }
+                }
+            }
+        };
+        if text.is_empty() {
+            return;
+        }
+
+        if let Some(engine) = &self.trigger_engine {
+            engine.emit(TriggerEvent::TelegramMessage {
+                agent_id: self.agent.id.clone(),
+                chat_id,
+                text: text.clone(),
+            });
         }
-    } else {
-        client.agent("gpt-4").build()
+
+        connector.typing(&msg.conversation_id).await;
+
+        // In groups the transcript has to say who is talking
+        let content = match &msg.sender {
+            Some(sender) if !msg.is_direct => format!("[{}] {}", sender.name, text),
+            _ => text,
+        };
+        let metadata = serde_json::json!({
+            "source": "telegram",
+            "chat_id": chat_id,
+            "telegram_user": user_id.map(|id| format!("telegram:{}", id)),
+        });
+        let title = match (&msg.conversation_title, &msg.sender) {
+            (Some(title), _) => title.clone(),
+            (None, Some(sender)) => sender.name.clone(),
+            (None, None) => chat_id.to_string(),
+        };
+        let topic = msg.thread_id.as_deref().and_then(|t| t.parse().ok()).unwrap_or(0);
+
+        let started = chrono::Utc::now().naive_utc();
+        let result = match chat_session(
+            &self.db_pool,
+            &self.config_id,
+            chat_id,
+            topic,
+            &self.agent.id,
+            &format!("Telegram: {}", title),
+        ) {
+            Ok(session_id) => process_message_with_agent(&self.db_pool, &self.agent, &session_id, &content, metadata)
+                .await
+                .map(|response| (session_id, response)),
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok((session_id, response)) => {
+                for chunk in split_message(&response, connector.capabilities().max_message_len) {
+                    self.reply(connector.as_ref(), &msg, &chunk).await;
+                }
+                crate::messaging::send_artifacts(&self.db_pool, connector.as_ref(), &msg, &session_id, started).await;
+                if voice.is_some() && self.voice_replies {
+                    match crate::speech::synthesize_voice(&self.db_pool, &response).await {
+                        Ok(audio) => {
+                            let mut reply = OutgoingMessage::reply_to(&msg, "");
+                            reply.attachments.push(OutgoingAttachment {
+                                kind: AttachmentKind::Voice,
+                                file_name: "reply.ogg".to_string(),
+                                mime_type: "audio/ogg".to_string(),
+                                data: audio,
+                            });
+                            if let Err(e) = connector.send(&msg.conversation_id, reply).await {
+                                log::error!("Failed to send voice reply: {}", e);
+                            }
+                        }
+                        Err(e) => log::error!("Failed to synthesize voice reply: {}", e),
+                    }
+                }
+            }
+            Err(e) => {
+                self.reply(connector.as_ref(), &msg, &format!("Sorry, I encountered an error: {}", e))
+                    .await;
+            }
+        }
+    }
+}
+
+impl ChatBridge {
+    /// Answer in the chat; in groups as a reply, which keeps forum topics too
+    async fn reply(&self, connector: &dyn MessagingConnector, msg: &IncomingMessage, text: &str) {
+        if let Err(e) = connector
+            .send(&msg.conversation_id, OutgoingMessage::reply_to(msg, text))
+            .await
+        {
+            log::error!("Failed to send message: {}", e);
+        }
+    }
+}
+
+/// Group chats a bot may answer in, each with the Telegram users allowed to
+/// address it there. An empty list lets every member in.
+#[derive(Clone, Debug, Default)]
+pub struct GroupAllowlists(HashMap<i64, Vec<i64>>);
+
+impl GroupAllowlists {
+    /// Parse the JSON stored on a config, e.g. `{"-100123": [42, 7]}`
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let raw: HashMap<String, Vec<i64>> =
+            serde_json::from_str(json).map_err(|e| format!("Invalid group allowlists: {}", e))?;
+        raw.into_iter()
+            .map(|(chat, users)| {
+                chat.trim()
+                    .parse::<i64>()
+                    .map(|chat| (chat, users))
+                    .map_err(|_| format!("Invalid group chat id: {}", chat))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    /// Groups that are not listed are closed
+    pub fn allows(&self, chat_id: i64, user_id: Option<i64>) -> bool {
+        match self.0.get(&chat_id) {
+            Some(users) => users.is_empty() || user_id.is_some_and(|u| users.contains(&u)),
+            None => false,
+        }
+    }
+}
+
+/// How a Telegram user is named in the transcript
+fn user_label(user: &teloxide::types::User) -> String {
+    match &user.username {
+        Some(username) => format!("{} (@{})", user.full_name(), username),
+        None => user.full_name(),
+    }
+}
+
+fn mentions(text: &str, username: &str) -> bool {
+    !username.is_empty() && text.to_lowercase().contains(&format!("@{}", username.to_lowercase()))
+}
+
+/// Drop `@username` from a message addressed to the bot
+fn strip_mention(text: &str, username: &str) -> String {
+    if username.is_empty() {
+        return text.trim().to_string();
+    }
+    let mention = format!("@{}", username.to_lowercase());
+    text.split_whitespace()
+        .filter(|word| !word.to_lowercase().starts_with(&mention))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Forum topic of a message, 0 outside of topics
+fn topic_id(msg: &Message) -> i64 {
+    match msg.thread_id {
+        Some(thread) if msg.is_topic_message => thread.0 .0 as i64,
+        _ => 0,
+    }
+}
+
+/// The agent session a chat (or forum topic) talks in, created on first use
+fn chat_session(
+    db_pool: &DbPool,
+    config_id: &str,
+    chat_id: i64,
+    thread_id: i64,
+    agent_id: &str,
+    title: &str,
+) -> Result<String, String> {
+    use anyagents::schema::{sessions, telegram_chat_sessions};
+
+    let mut conn = db_pool.get().map_err(|e| e.to_string())?;
+    let existing = telegram_chat_sessions::table
+        .filter(telegram_chat_sessions::config_id.eq(config_id))
+        .filter(telegram_chat_sessions::chat_id.eq(chat_id))
+        .filter(telegram_chat_sessions::thread_id.eq(thread_id))
+        .select(telegram_chat_sessions::session_id)
+        .first::<String>(&mut conn)
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if let Some(session_id) = existing {
+        return Ok(session_id);
+    }
+
+    let now = chrono::Utc::now().naive_utc();
+    let session = NewSession {
+        id: uuid::Uuid::new_v4().to_string(),
+        agent_id: agent_id.to_string(),
+        title: Some(title.to_string()),
+        created_at: now,
+        updated_at: now,
+        archived: 0,
+        pinned: 0,
+    };
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        diesel::insert_into(sessions::table).values(&session).execute(conn)?;
+        diesel::insert_into(telegram_chat_sessions::table)
+            .values(&NewTelegramChatSession {
+                config_id: config_id.to_string(),
+                chat_id,
+                thread_id,
+                session_id: session.id.clone(),
+            })
+            .execute(conn)?;
+        Ok(())
+    })
+    .map_err(|e| e.to_string())?;
+    Ok(session.id)
+}
+
+/// Messages of a session the agent sees as history
+const HISTORY_LIMIT: i64 = 20;
+
+async fn process_message_with_agent(
+    db_pool: &DbPool,
+    agent: &Agent,
+    session_id: &str,
+    user_message: &str,
+    metadata: serde_json::Value,
+) -> Result<String, String> {
+    use anyagents::schema::messages;
+
+    let history = {
+        let mut conn = db_pool.get().map_err(|e| e.to_string())?;
+        let mut recent = messages::table
+            .filter(messages::session_id.eq(session_id))
+            .filter(messages::deleted_at.is_null())
+            .order(messages::created_at.desc())
+            .limit(HISTORY_LIMIT)
+            .load::<anyagents::models::Message>(&mut conn)
+            .map_err(|e| e.to_string())?;
+        recent.reverse();
+        recent
+            .into_iter()
+            .filter_map(|m| match m.role.as_str() {
+                "user" => Some(rig::completion::Message::user(&m.content)),
+                "assistant" | "model" => Some(rig::completion::Message::assistant(&m.content)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
     };
+    save_message(db_pool, session_id, "user", user_message, Some(metadata.to_string()))?;
 
-    ai_agent
-        .prompt(user_message)
+    let preamble = anyagents::prompts::PromptBuilder::new()
+        .persona(agent.system_prompt.as_deref(), "")
+        .build();
+    let response = LlmClient::new(&agent.ai_provider, &agent.ai_model)
+        .with_preamble(&preamble)
+        .with_params(agent.generation_params().for_provider(&agent.ai_provider))
+        .with_session(session_id)
+        .chat(user_message, history)
         .await
-        .map_err(|e| format!("AI error: {}", e))
+        .map_err(|e| format!("AI error: {}", e))?;
+
+    save_message(db_pool, session_id, "assistant", &response, None)?;
+    Ok(response)
+}
+
+fn save_message(
+    db_pool: &DbPool,
+    session_id: &str,
+    role: &str,
+    content: &str,
+    metadata_json: Option<String>,
+) -> Result<(), String> {
+    use anyagents::schema::{messages, sessions};
+
+    let mut conn = db_pool.get().map_err(|e| e.to_string())?;
+    diesel::insert_into(messages::table)
+        .values(&NewMessage {
+            id: uuid::Uuid::new_v4().to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            session_id: session_id.to_string(),
+            metadata_json,
+            tokens: None,
+        })
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    diesel::update(sessions::table.filter(sessions::id.eq(session_id)))
+        .set(sessions::updated_at.eq(chrono::Utc::now().naive_utc()))
+        .execute(&mut conn)
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// What a reported run tells its chat
+enum RunUpdate {
+    Step { id: String, tool: String, status: String },
+    Approval(PermissionRequest),
+    Finished(Option<String>),
+}
+
+/// Forwards a run's events to the app and reports its progress to Telegram
+pub struct TelegramRunObserver {
+    inner: Arc<dyn AgentObserver>,
+    channel: String,
+    updates: mpsc::UnboundedSender<RunUpdate>,
+}
+
+impl TelegramRunObserver {
+    /// Post the final answer and close the progress message
+    pub fn finish(&self, answer: Option<String>) {
+        let _ = self.updates.send(RunUpdate::Finished(answer));
+    }
+}
+
+impl AgentObserver for TelegramRunObserver {
+    fn emit(&self, event: &str, payload: Value) -> Result<(), String> {
+        if event == self.channel {
+            let update = match payload.get("type").and_then(|t| t.as_str()) {
+                Some("permission_request") => payload
+                    .get("request")
+                    .and_then(|r| serde_json::from_value(r.clone()).ok())
+                    .map(RunUpdate::Approval),
+                Some("step_started") | Some("step_completed") => payload.get("step").map(|step| RunUpdate::Step {
+                    id: step["id"].as_str().unwrap_or_default().to_string(),
+                    tool: step["tool_name"].as_str().unwrap_or_default().to_string(),
+                    status: step["status"].as_str().unwrap_or_default().to_string(),
+                }),
+                _ => None,
+            };
+            if let Some(update) = update {
+                let _ = self.updates.send(update);
+            }
+        }
+        self.inner.emit(event, payload)
+    }
 }
 
-fn split_message(text: &str, max_len: usize) -> Vec<String> {
+/// Apply one update at a time, so edits of the progress message stay in order
+async fn report_run(
+    bot: Bot,
+    chat_id: ChatId,
+    approvals: Arc<DashMap<String, ChatId>>,
+    mut updates: mpsc::UnboundedReceiver<RunUpdate>,
+) {
+    let mut steps: Vec<(String, String, String)> = Vec::new();
+    let mut progress: Option<MessageId> = None;
+
+    while let Some(update) = updates.recv().await {
+        match update {
+            RunUpdate::Step { id, tool, status } => {
+                match steps.iter_mut().find(|(step_id, _, _)| *step_id == id) {
+                    Some(step) => step.2 = status,
+                    None => steps.push((id, tool, status)),
+                }
+                let text = progress_text(&steps, false);
+                match progress {
+                    Some(message_id) => {
+                        let _ = bot.edit_message_text(chat_id, message_id, text).await;
+                    }
+                    None => match bot.send_message(chat_id, text).await {
+                        Ok(message) => progress = Some(message.id),
+                        Err(e) => log::error!("Failed to send progress message: {}", e),
+                    },
+                }
+            }
+            RunUpdate::Approval(request) => {
+                approvals.insert(request.id.clone(), chat_id);
+                let mut text = format!("🔐 Permission needed\n{}", request.message);
+                if let Some(preview) = request.preview(APPROVAL_PREVIEW_LEN) {
+                    text.push_str(&format!("\n\n{}", preview));
+                }
+                let buttons = InlineKeyboardMarkup::new(vec![vec![
+                    InlineKeyboardButton::callback("Approve", approval_data(&PermissionResponse::Allow, &request.id)),
+                    InlineKeyboardButton::callback("Deny", approval_data(&PermissionResponse::Deny, &request.id)),
+                    InlineKeyboardButton::callback("Always", approval_data(&PermissionResponse::AllowAlways, &request.id)),
+                ]]);
+                if let Err(e) = bot.send_message(chat_id, text).reply_markup(buttons).await {
+                    log::error!("Failed to send approval request: {}", e);
+                }
+            }
+            RunUpdate::Finished(answer) => {
+                if let Some(message_id) = progress {
+                    let _ = bot.edit_message_text(chat_id, message_id, progress_text(&steps, true)).await;
+                }
+                let answer = answer
+                    .filter(|a| !a.trim().is_empty())
+                    .unwrap_or_else(|| "Finished without an answer.".to_string());
+                for chunk in split_message(&answer, 4000) {
+                    if let Err(e) = bot.send_message(chat_id, chunk).await {
+                        log::error!("Failed to send message: {}", e);
+                    }
+                }
+                break;
+            }
+        }
+    }
+}
+
+fn progress_text(steps: &[(String, String, String)], done: bool) -> String {
+    let mut text = if done { "✅ Done".to_string() } else { "⏳ Working on it…".to_string() };
+    let skipped = steps.len().saturating_sub(MAX_PROGRESS_STEPS);
+    if skipped > 0 {
+        text.push_str(&format!("\n… {} earlier steps", skipped));
+    }
+    for (_, tool, status) in &steps[skipped..] {
+        let mark = match status.as_str() {
+            "completed" => "✓",
+            "failed" | "rejected" => "✗",
+            _ => "…",
+        };
+        text.push_str(&format!("\n{} {}", mark, tool));
+    }
+    text
+}
+
+/// Callback data of an approval button; well under Telegram's 64 bytes
+pub(crate) fn approval_data(response: &PermissionResponse, request_id: &str) -> String {
+    let action = match response {
+        PermissionResponse::Allow => "allow",
+        PermissionResponse::Deny => "deny",
+        PermissionResponse::AllowAlways => "always",
+    };
+    format!("perm:{}:{}", action, request_id)
+}
+
+pub(crate) fn parse_approval_data(data: &str) -> Option<(PermissionResponse, &str)> {
+    let (action, request_id) = data.strip_prefix("perm:")?.split_once(':')?;
+    let response = match action {
+        "allow" => PermissionResponse::Allow,
+        "deny" => PermissionResponse::Deny,
+        "always" => PermissionResponse::AllowAlways,
+        _ => return None,
+    };
+    Some((response, request_id))
+}
+
+/// Answer the permission request behind a pressed button; returns what to
+/// tell the chat
+fn handle_approval(
+    query: &CallbackQuery,
+    permission_manager: Option<&PermissionManager>,
+    approvals: &DashMap<String, ChatId>,
+    allowed_chats: Option<&[i64]>,
+    groups: Option<&GroupAllowlists>,
+) -> String {
+    let Some((response, request_id)) = query.data.as_deref().and_then(parse_approval_data) else {
+        return "Unknown button".to_string();
+    };
+    let Some(chat_id) = query.message.as_ref().map(|m| m.chat().id) else {
+        return "Unknown chat".to_string();
+    };
+    if allowed_chats.is_some_and(|allowed| !allowed.contains(&chat_id.0)) {
+        return "Not allowed in this chat".to_string();
+    }
+    // In groups, only allowlisted members decide
+    let in_group = query.message.as_ref().is_some_and(|m| !m.chat().is_private());
+    if in_group && groups.is_some_and(|g| !g.allows(chat_id.0, Some(query.from.id.0 as i64))) {
+        return "You are not allowed to approve requests here".to_string();
+    }
+    // Only requests that were sent to this very chat can be answered here
+    if approvals.get(request_id).map(|c| *c) != Some(chat_id) {
+        return "This request is no longer pending".to_string();
+    }
+    approvals.remove(request_id);
+
+    let verdict = match response {
+        PermissionResponse::Allow => "Approved",
+        PermissionResponse::Deny => "Denied",
+        PermissionResponse::AllowAlways => "Always allowed",
+    };
+    match permission_manager {
+        Some(manager) if manager.respond_as(request_id, response, &format!("telegram:{}", query.from.id)) => verdict.to_string(),
+        _ => "This request is no longer pending".to_string(),
+    }
+}
+
+pub(crate) fn split_message(text: &str, max_len: usize) -> Vec<String> {
     if text.len() <= max_len {
         return vec![text.to_string()];
     }
@@ -279,6 +1024,73 @@ fn split_message(text: &str, max_len: usize) -> Vec<String> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_approval_data_round_trip() {
+        for response in [PermissionResponse::Allow, PermissionResponse::Deny, PermissionResponse::AllowAlways] {
+            let data = approval_data(&response, "0b5c6f2e-8d1a-4e8b-9d0f-2f6c1b7a9e31");
+            assert!(data.len() <= 64);
+            let (parsed, id) = parse_approval_data(&data).unwrap();
+            assert_eq!(approval_data(&parsed, id), data);
+        }
+        assert!(parse_approval_data("perm:maybe:123").is_none());
+        assert!(parse_approval_data("other").is_none());
+    }
+
+    #[test]
+    fn test_progress_text() {
+        let steps: Vec<(String, String, String)> = (0..17)
+            .map(|i| (i.to_string(), format!("tool_{}", i), "completed".to_string()))
+            .chain(std::iter::once(("17".to_string(), "run_command".to_string(), "running".to_string())))
+            .collect();
+        let text = progress_text(&steps, false);
+        assert!(text.starts_with("⏳ Working on it…\n… 3 earlier steps\n✓ tool_3"));
+        assert!(text.ends_with("… run_command"));
+        assert!(progress_text(&[], true).starts_with("✅ Done"));
+    }
+
+    #[test]
+    fn test_group_allowlists() {
+        let groups = GroupAllowlists::parse(r#"{"-100123": [42, 7], "-100456": []}"#).unwrap();
+        assert!(groups.allows(-100123, Some(42)));
+        assert!(!groups.allows(-100123, Some(8)));
+        assert!(!groups.allows(-100123, None));
+        assert!(groups.allows(-100456, Some(8)));
+        assert!(!groups.allows(-100789, Some(42)));
+
+        assert!(GroupAllowlists::parse(r#"{"general": []}"#).is_err());
+        assert!(GroupAllowlists::parse("[]").is_err());
+    }
+
+    #[test]
+    fn test_mentions() {
+        assert!(mentions("hey @CoworkBot what's up", "coworkbot"));
+        assert!(!mentions("hey there", "coworkbot"));
+        assert!(!mentions("@someone", ""));
+        assert_eq!(strip_mention("@CoworkBot summarize this", "coworkbot"), "summarize this");
+        assert_eq!(strip_mention("can you help, @coworkbot?", "coworkbot"), "can you help,");
+    }
+
+    #[test]
+    fn test_chat_session_per_topic() {
+        let pool = anyagents::database::create_test_pool();
+        let first = chat_session(&pool, "config", -100123, 0, "agent", "Telegram: Team").unwrap();
+        let again = chat_session(&pool, "config", -100123, 0, "agent", "Telegram: Team").unwrap();
+        let topic = chat_session(&pool, "config", -100123, 15, "agent", "Telegram: Team").unwrap();
+        let other_bot = chat_session(&pool, "other", -100123, 0, "agent", "Telegram: Team").unwrap();
+        assert_eq!(first, again);
+        assert_ne!(first, topic);
+        assert_ne!(first, other_bot);
+
+        save_message(&pool, &first, "user", "[Ada (@ada)] hello", None).unwrap();
+        let mut conn = pool.get().unwrap();
+        let count: i64 = anyagents::schema::messages::table
+            .filter(anyagents::schema::messages::session_id.eq(&first))
+            .count()
+            .get_result(&mut conn)
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
     #[test]
     fn test_split_message_short() {
         let text = "Hello world";
@@ -0,0 +1,330 @@
+//! Webhook ingress
+//!
+//! An optional HTTP listener on `127.0.0.1:<webhook_port>` that lets external
+//! systems (CI, Zapier, GitHub, ...) start an agent: `POST /webhooks/<agent_id>`
+//! with any body starts a new session with that agent. Requests authenticate
+//! with `Authorization: Bearer <webhook_token>`, or with a GitHub
+//! `X-Hub-Signature-256` signature using the token as the webhook secret.
+//!
+//! The body is exposed to the agent's `webhook_prompt_template` (an execution
+//! setting) as `webhook_payload`, and announced with a `webhook_received` event.
+
+use crate::scheduler::{load_agent, prepare_session, run_agent};
+use anyagents::database::DbPool;
+use anyagents::events::AgentEvent;
+use anyagents::models::settings::{get_setting, set_setting};
+use anyagents::models::Agent;
+use anyagents::secrets::constant_time_eq;
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::{Json, Router};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Runtime};
+use tokio::sync::oneshot;
+
+pub const DEFAULT_WEBHOOK_PORT: u16 = 8765;
+
+pub const WEBHOOK_RECEIVED_EVENT: &str = "webhooks:received";
+
+/// Largest accepted request body
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+const DEFAULT_PROMPT_TEMPLATE: &str = "A webhook from {{ source }} was received with this payload:\n\n```json\n{{ webhook_payload_json }}\n```\n\nDecide whether this needs any action and, if so, take it.";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WebhookConfig {
+    pub enabled: bool,
+    pub port: u16,
+    pub token: Option<String>,
+}
+
+impl WebhookConfig {
+    pub fn load(pool: &DbPool) -> Self {
+        Self {
+            enabled: get_setting(pool, "webhook_enabled").as_deref() == Some("true"),
+            port: get_setting(pool, "webhook_port")
+                .and_then(|p| p.parse().ok())
+                .unwrap_or(DEFAULT_WEBHOOK_PORT),
+            token: get_setting(pool, "webhook_token").filter(|t| !t.is_empty()),
+        }
+    }
+
+    pub fn save(&self, pool: &DbPool) -> Result<(), String> {
+        set_setting(pool, "webhook_enabled", if self.enabled { "true" } else { "false" })?;
+        set_setting(pool, "webhook_port", &self.port.to_string())?;
+        set_setting(pool, "webhook_token", self.token.as_deref().unwrap_or(""))
+    }
+}
+
+pub fn generate_token() -> String {
+    format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())
+}
+
+/// Whether the request carries the bearer token or a valid GitHub signature
+pub fn is_authorized(headers: &HeaderMap, body: &[u8], token: &str) -> bool {
+    if let Some(bearer) = headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+    {
+        return constant_time_eq(bearer.trim().as_bytes(), token.as_bytes());
+    }
+
+    let Some(signature) = headers
+        .get("x-hub-signature-256")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("sha256="))
+        .and_then(|v| hex::decode(v).ok())
+    else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(token.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// `?source=` if given, `github:<event>` for GitHub deliveries, else "webhook"
+pub fn webhook_source(headers: &HeaderMap, query: &HashMap<String, String>) -> String {
+    if let Some(source) = query.get("source").filter(|s| !s.trim().is_empty()) {
+        return source.trim().to_string();
+    }
+    match headers.get("x-github-event").and_then(|v| v.to_str().ok()) {
+        Some(event) => format!("github:{}", event),
+        None => "webhook".to_string(),
+    }
+}
+
+/// JSON bodies are passed through as-is, anything else as a string
+pub fn parse_payload(body: &[u8]) -> Value {
+    serde_json::from_slice(body)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).into_owned()))
+}
+
+pub fn render_webhook_prompt(
+    template: Option<&str>,
+    source: &str,
+    payload: &Value,
+) -> Result<String, String> {
+    let template = template
+        .filter(|t| !t.trim().is_empty())
+        .unwrap_or(DEFAULT_PROMPT_TEMPLATE);
+    let payload_json = serde_json::to_string_pretty(payload).unwrap_or_default();
+
+    let env = minijinja::Environment::new();
+    env.render_str(
+        template,
+        minijinja::context! {
+            source => source,
+            webhook_payload => minijinja::Value::from_serialize(payload),
+            webhook_payload_json => payload_json,
+        },
+    )
+    .map_err(|e| format!("Invalid webhook prompt template: {}", e))
+}
+
+fn prompt_template(agent: &Agent) -> Option<String> {
+    agent
+        .execution_settings
+        .as_ref()
+        .and_then(|s| serde_json::from_str::<Value>(s).ok())
+        .and_then(|json| json.get("webhook_prompt_template")?.as_str().map(String::from))
+}
+
+#[derive(Clone)]
+struct WebhookState<R: Runtime> {
+    app: AppHandle<R>,
+    pool: DbPool,
+    token: String,
+}
+
+async fn handle_webhook<R: Runtime>(
+    State(state): State<WebhookState<R>>,
+    Path(agent_id): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> (StatusCode, Json<Value>) {
+    if !is_authorized(&headers, &body, &state.token) {
+        return (StatusCode::UNAUTHORIZED, Json(json!({ "error": "Unauthorized" })));
+    }
+    let agent = match load_agent(&state.pool, &agent_id) {
+        Ok(agent) => agent,
+        Err(e) => return (StatusCode::NOT_FOUND, Json(json!({ "error": e }))),
+    };
+
+    let source = webhook_source(&headers, &query);
+    let payload = parse_payload(&body);
+    let prompt = match render_webhook_prompt(prompt_template(&agent).as_deref(), &source, &payload) {
+        Ok(prompt) => prompt,
+        Err(e) => return (StatusCode::UNPROCESSABLE_ENTITY, Json(json!({ "error": e }))),
+    };
+    let title = format!("Webhook: {}", source);
+    let session_id = match prepare_session(&state.pool, &agent.id, None, &title, &prompt) {
+        Ok(id) => id,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e }))),
+    };
+
+    let webhook_id = uuid::Uuid::new_v4().to_string();
+    let job_id = uuid::Uuid::new_v4().to_string();
+    let event = serde_json::to_value(AgentEvent::WebhookReceived {
+        webhook_id: webhook_id.clone(),
+        agent_id: agent.id.clone(),
+        session_id: session_id.clone(),
+        source,
+        payload,
+    })
+    .unwrap();
+    let _ = state.app.emit(&format!("session:{}", session_id), event.clone());
+    let _ = state.app.emit(WEBHOOK_RECEIVED_EVENT, event);
+
+    let app = state.app.clone();
+    let run_session_id = session_id.clone();
+    let run_job_id = job_id.clone();
+    tauri::async_runtime::spawn(async move {
+        run_agent(&app, &agent, &run_session_id, prompt, run_job_id).await;
+    });
+
+    (
+        StatusCode::ACCEPTED,
+        Json(json!({ "webhook_id": webhook_id, "session_id": session_id, "job_id": job_id })),
+    )
+}
+
+/// The running listener, if any; restarted whenever its settings change
+#[derive(Default)]
+pub struct WebhookServer {
+    shutdown: Mutex<Option<oneshot::Sender<()>>>,
+}
+
+impl WebhookServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.shutdown.lock().unwrap().is_some()
+    }
+
+    pub fn stop(&self) {
+        if let Some(tx) = self.shutdown.lock().unwrap().take() {
+            let _ = tx.send(());
+        }
+    }
+
+    /// Stop the listener and start it again from the saved settings
+    pub fn restart<R: Runtime>(&self, app: AppHandle<R>, pool: DbPool) -> Result<(), String> {
+        self.stop();
+
+        let mut config = WebhookConfig::load(&pool);
+        if !config.enabled {
+            return Ok(());
+        }
+        let token = match config.token.clone() {
+            Some(token) => token,
+            None => {
+                let token = generate_token();
+                config.token = Some(token.clone());
+                config.save(&pool)?;
+                token
+            }
+        };
+
+        // Bind here so a port conflict is reported to the caller
+        let listener = std::net::TcpListener::bind(("127.0.0.1", config.port))
+            .map_err(|e| format!("Failed to listen on port {}: {}", config.port, e))?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+        let router = Router::new()
+            .route("/webhooks/:agent_id", post(handle_webhook::<R>))
+            .layer(axum::extract::DefaultBodyLimit::max(MAX_BODY_BYTES))
+            .with_state(WebhookState { app, pool, token });
+
+        let (tx, rx) = oneshot::channel::<()>();
+        *self.shutdown.lock().unwrap() = Some(tx);
+
+        let port = config.port;
+        tauri::async_runtime::spawn(async move {
+            let listener = match tokio::net::TcpListener::from_std(listener) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    log::error!("Webhook listener failed: {}", e);
+                    return;
+                }
+            };
+            log::info!("Webhook listener on 127.0.0.1:{}", port);
+            let shutdown = async {
+                let _ = rx.await;
+            };
+            if let Err(e) = axum::serve(listener, router).with_graceful_shutdown(shutdown).await {
+                log::error!("Webhook listener failed: {}", e);
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sign(secret: &str, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    #[test]
+    fn test_is_authorized() {
+        let body = br#"{"ref":"main"}"#;
+        let mut headers = HeaderMap::new();
+        assert!(!is_authorized(&headers, body, "secret"));
+
+        headers.insert("authorization", "Bearer secret".parse().unwrap());
+        assert!(is_authorized(&headers, body, "secret"));
+        headers.insert("authorization", "Bearer wrong".parse().unwrap());
+        assert!(!is_authorized(&headers, body, "secret"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-hub-signature-256", sign("secret", body).parse().unwrap());
+        assert!(is_authorized(&headers, body, "secret"));
+        assert!(!is_authorized(&headers, b"tampered", "secret"));
+    }
+
+    #[test]
+    fn test_webhook_source() {
+        let mut headers = HeaderMap::new();
+        let mut query = HashMap::new();
+        assert_eq!(webhook_source(&headers, &query), "webhook");
+
+        headers.insert("x-github-event", "push".parse().unwrap());
+        assert_eq!(webhook_source(&headers, &query), "github:push");
+
+        query.insert("source".to_string(), "zapier".to_string());
+        assert_eq!(webhook_source(&headers, &query), "zapier");
+    }
+
+    #[test]
+    fn test_render_webhook_prompt() {
+        let payload = parse_payload(br#"{"repository":{"name":"anycowork"},"ref":"main"}"#);
+        let prompt = render_webhook_prompt(
+            Some("Build {{ webhook_payload.repository.name }} at {{ webhook_payload.ref }} ({{ source }})"),
+            "github:push",
+            &payload,
+        )
+        .unwrap();
+        assert_eq!(prompt, "Build anycowork at main (github:push)");
+
+        let prompt = render_webhook_prompt(None, "ci", &parse_payload(b"build failed")).unwrap();
+        assert!(prompt.contains("\"build failed\""));
+        assert!(render_webhook_prompt(Some("{{ unclosed"), "ci", &payload).is_err());
+    }
+}
@@ -35,8 +35,13 @@ fn create_test_skill(
         body: "Test skill body content".to_string(),
         category: Some("Testing".to_string()),
         requires_sandbox,
+        read_only: false,
         license: None,
         execution_mode: execution_mode.map(|s| s.to_string()),
+        dependencies: None,
+        invocation: None,
+        examples: Vec::new(),
+        recipe: None,
     };
     LoadedSkill {
         skill: parsed,
@@ -49,6 +54,8 @@ fn create_test_context() -> ToolContext {
         permissions: Arc::new(PermissionManager::new()),
         observer: None,
         session_id: "e2e_test_session".to_string(),
+        scope: Default::default(),
+        job_id: None,
     }
 }
 
@@ -80,6 +87,7 @@ async fn test_docker_sandbox_basic_execution() {
         cpu_limit: Some(0.5),
         timeout_seconds: Some(30),
         network_enabled: Some(false),
+        runtime: None,
     };
 
     // Pull image first
@@ -125,6 +133,7 @@ async fn test_docker_sandbox_file_creation() {
         cpu_limit: None,
         timeout_seconds: Some(30),
         network_enabled: Some(false),
+        runtime: None,
     };
 
     // Create a file in the mounted workspace
@@ -171,6 +180,7 @@ async fn test_docker_sandbox_timeout() {
         cpu_limit: None,
         timeout_seconds: Some(2), // Very short timeout
         network_enabled: Some(false),
+        runtime: None,
     };
 
     // Sleep longer than timeout
@@ -210,6 +220,7 @@ async fn test_docker_sandbox_network_isolation() {
         cpu_limit: None,
         timeout_seconds: Some(10),
         network_enabled: Some(false),
+        runtime: None,
     };
 
     // Try to ping - should fail due to network isolation
@@ -431,6 +442,8 @@ async fn test_bash_tool_sandbox_execution() {
         permissions: pm,
         observer: None,
         session_id: "bash_test".to_string(),
+        scope: Default::default(),
+        job_id: None,
     };
 
     let result = tool
@@ -475,6 +488,8 @@ async fn test_bash_tool_sandbox_not_available_fails() {
         permissions: pm,
         observer: None,
         session_id: "bash_test".to_string(),
+        scope: Default::default(),
+        job_id: None,
     };
 
     let result = tool
@@ -562,12 +577,18 @@ async fn test_skill_with_files_docker_execution() {
                 cpu_limit: None,
                 timeout_seconds: Some(30),
                 network_enabled: Some(false),
+                runtime: None,
             }),
             body: "Skill with embedded script".to_string(),
             category: Some("Testing".to_string()),
             requires_sandbox: false,
+            read_only: false,
             license: None,
             execution_mode: Some("flexible".to_string()),
+            dependencies: None,
+            invocation: None,
+            examples: Vec::new(),
+            recipe: None,
         },
         files,
     };
@@ -653,12 +674,18 @@ async fn test_e2e_skill_docker_python_execution() {
                 cpu_limit: None,
                 timeout_seconds: Some(60),
                 network_enabled: Some(false),
+                runtime: None,
             }),
             body: "Python skill".to_string(),
             category: Some("Testing".to_string()),
             requires_sandbox: false,
+            read_only: false,
             license: None,
             execution_mode: Some("sandbox".to_string()),
+            dependencies: None,
+            invocation: None,
+            examples: Vec::new(),
+            recipe: None,
         },
         files: HashMap::new(),
     };
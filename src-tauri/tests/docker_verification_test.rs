@@ -36,6 +36,8 @@ async fn test_verify_docker_execution() {
         permissions: std::sync::Arc::new(anyagents::permissions::PermissionManager::new()),
         observer: None,
         session_id: "test_session".to_string(),
+        scope: Default::default(),
+        job_id: None,
     };
 
     // 4. Execute
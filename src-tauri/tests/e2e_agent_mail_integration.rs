@@ -61,6 +61,7 @@ fn create_test_agent(pool: &anyagents::database::DbPool, name: &str, description
         scope_type: None,
         workspace_path: None,
         avatar: Some("🤖".to_string()),
+        agent_tools: None,
     };
 
     let mut conn = pool.get().expect("Failed to get DB connection");
@@ -100,6 +101,8 @@ async fn test_send_email_tool_execution() {
         permissions: Arc::new(PermissionManager::new()),
         observer: Some(Arc::new(TestObserver)),
         session_id: uuid::Uuid::new_v4().to_string(),
+        scope: Default::default(),
+        job_id: None,
     };
 
     let args = json!({
@@ -157,6 +160,8 @@ async fn test_email_body_escape_sequences() {
         permissions: Arc::new(PermissionManager::new()),
         observer: Some(Arc::new(TestObserver)),
         session_id: uuid::Uuid::new_v4().to_string(),
+        scope: Default::default(),
+        job_id: None,
     };
 
     // Test with newlines and special characters
@@ -204,6 +209,8 @@ async fn test_tool_validation_missing_recipient() {
         permissions: Arc::new(PermissionManager::new()),
         observer: Some(Arc::new(TestObserver)),
         session_id: uuid::Uuid::new_v4().to_string(),
+        scope: Default::default(),
+        job_id: None,
     };
 
     // Missing 'to' field
@@ -238,6 +245,8 @@ async fn test_tool_validation_unknown_recipient() {
         permissions: Arc::new(PermissionManager::new()),
         observer: Some(Arc::new(TestObserver)),
         session_id: uuid::Uuid::new_v4().to_string(),
+        scope: Default::default(),
+        job_id: None,
     };
 
     let args = json!({
@@ -273,6 +282,8 @@ async fn test_partial_name_matching() {
         permissions: Arc::new(PermissionManager::new()),
         observer: Some(Arc::new(TestObserver)),
         session_id: uuid::Uuid::new_v4().to_string(),
+        scope: Default::default(),
+        job_id: None,
     };
 
     // Search for "Jordan" should find "Jordan the PM"
@@ -318,6 +329,8 @@ async fn test_send_to_user_mailbox() {
         permissions: Arc::new(PermissionManager::new()),
         observer: Some(Arc::new(TestObserver)),
         session_id: uuid::Uuid::new_v4().to_string(),
+        scope: Default::default(),
+        job_id: None,
     };
 
     let args = json!({
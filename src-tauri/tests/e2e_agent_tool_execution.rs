@@ -100,6 +100,7 @@ fn create_file_agent(pool: &anyagents::database::DbPool, workspace: &str) -> Age
         scope_type: None,
         workspace_path: Some(workspace.to_string()),
         avatar: Some("📁".to_string()),
+        agent_tools: None,
     };
 
     let mut conn = pool.get().expect("Failed to get DB connection");
@@ -150,6 +150,7 @@ fn create_autonomous_agent(
         scope_type: None,
         workspace_path: Some(std::env::temp_dir().to_str().unwrap().to_string()),
         avatar: Some(avatar.to_string()),
+        agent_tools: None,
     };
 
     let mut conn = pool.get().expect("Failed to get DB connection");
@@ -430,6 +431,7 @@ async fn test_autonomous_permission_manager() {
         permission_type: anyagents::permissions::PermissionType::ShellExecute,
         message: "Execute bash command".to_string(),
         metadata: std::collections::HashMap::new(),
+        payload: None,
     };
 
     // In autonomous mode, should auto-approve
@@ -444,6 +446,7 @@ async fn test_autonomous_permission_manager() {
         permission_type: anyagents::permissions::PermissionType::FilesystemWrite,
         message: "Write file".to_string(),
         metadata: std::collections::HashMap::new(),
+        payload: None,
     };
 
     // Without observer, should deny
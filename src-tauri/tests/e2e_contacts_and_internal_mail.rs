@@ -55,6 +55,7 @@ fn create_test_agent(pool: &anyagents::database::DbPool, name: &str, avatar: &st
         scope_type: None,
         workspace_path: None,
         avatar: Some(avatar.to_string()),
+        agent_tools: None,
     };
 
     let mut conn = pool.get().expect("Failed to get DB connection");
@@ -86,6 +87,8 @@ async fn test_list_colleagues_tool() {
         permissions: Arc::new(PermissionManager::new()),
         observer: Some(Arc::new(TestObserver)),
         session_id: uuid::Uuid::new_v4().to_string(),
+        scope: Default::default(),
+        job_id: None,
     };
 
     let result = tool.execute(json!({}), &ctx).await;
@@ -158,6 +161,8 @@ async fn test_send_email_with_just_name() {
         permissions: Arc::new(PermissionManager::new()),
         observer: Some(Arc::new(TestObserver)),
         session_id: uuid::Uuid::new_v4().to_string(),
+        scope: Default::default(),
+        job_id: None,
     };
 
     // Test 1: Send using full name
@@ -220,6 +225,8 @@ async fn test_send_to_user_mailbox() {
         permissions: Arc::new(PermissionManager::new()),
         observer: Some(Arc::new(TestObserver)),
         session_id: uuid::Uuid::new_v4().to_string(),
+        scope: Default::default(),
+        job_id: None,
     };
 
     // Send to "user"
@@ -362,6 +369,8 @@ async fn test_check_mail_tool() {
         permissions: Arc::new(PermissionManager::new()),
         observer: Some(Arc::new(TestObserver)),
         session_id: uuid::Uuid::new_v4().to_string(),
+        scope: Default::default(),
+        job_id: None,
     };
 
     // Send 2 emails
@@ -441,6 +450,8 @@ async fn test_read_email_thread_tool() {
         permissions: Arc::new(PermissionManager::new()),
         observer: Some(Arc::new(TestObserver)),
         session_id: uuid::Uuid::new_v4().to_string(),
+        scope: Default::default(),
+        job_id: None,
     };
 
     let send_result = send_tool.execute(
@@ -500,6 +511,8 @@ async fn test_check_sent_folder() {
         permissions: Arc::new(PermissionManager::new()),
         observer: Some(Arc::new(TestObserver)),
         session_id: uuid::Uuid::new_v4().to_string(),
+        scope: Default::default(),
+        job_id: None,
     };
 
     let _ = send_tool.execute(
@@ -51,6 +51,7 @@ fn create_test_agent(pool: &anyagents::database::DbPool, name: &str, avatar: &st
         scope_type: None,
         workspace_path: None,
         avatar: Some(avatar.to_string()),
+        agent_tools: None,
     };
 
     let mut conn = pool.get().expect("Failed to get DB connection");
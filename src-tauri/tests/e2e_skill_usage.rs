@@ -68,8 +68,15 @@ async fn setup_app_and_agent(skill_name: &str, skill_content: &str, requires_san
         source_path: None,
         category: Some("Test".to_string()),
         requires_sandbox: if requires_sandbox { 1 } else { 0 },
+        read_only: 0,
         sandbox_config: None,
         execution_mode: execution_mode.to_string(),
+        triggers: None,
+        dependencies: None,
+        installed_version: None,
+        invocation: None,
+        examples: None,
+        recipe: None,
     };
 
     diesel::insert_into(agent_skills::table)
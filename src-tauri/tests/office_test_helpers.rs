@@ -61,6 +61,7 @@ pub fn create_test_agent(
         scope_type: None,
         workspace_path: None,
         avatar: Some(avatar.to_string()),
+        agent_tools: None,
     };
 
     let mut conn = pool.get().expect("Failed to get DB connection");
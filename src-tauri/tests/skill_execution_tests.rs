@@ -90,8 +90,15 @@ fn load_and_assign_skill(pool: &DbPool, agent_id: &str, skill_dirname: &str, tar
         source_path: Some(skill_path.to_string_lossy().to_string()),
         category: loaded.skill.category.clone(),
         requires_sandbox: if loaded.skill.requires_sandbox { 1 } else { 0 },
+        read_only: if loaded.skill.read_only { 1 } else { 0 },
         sandbox_config: sandbox_config_json,
         execution_mode: loaded.skill.execution_mode.clone().unwrap_or_else(|| "direct".to_string()),
+        triggers: loaded.skill.triggers.as_ref().and_then(|t| serde_json::to_string(t).ok()),
+        dependencies: loaded.skill.dependencies.as_ref().and_then(|d| serde_json::to_string(d).ok()),
+        installed_version: None,
+        invocation: loaded.skill.invocation.as_ref().and_then(|i| serde_json::to_string(i).ok()),
+        examples: serde_json::to_string(&loaded.skill.examples).ok(),
+        recipe: loaded.skill.recipe.as_ref().and_then(|r| serde_json::to_string(r).ok()),
     };
 
     diesel::insert_into(agent_skills::table)
@@ -249,8 +256,13 @@ async fn test_skill_tool_local_execution() {
         body: "Test Body".to_string(),
         category: Some("Utility".to_string()),
         requires_sandbox: false, // Ensure local execution
+        read_only: false,
         license: None,
         execution_mode: Some("direct".to_string()),
+        dependencies: None,
+        invocation: None,
+        examples: Vec::new(),
+        recipe: None,
     };
     
     let loaded = LoadedSkill {
@@ -267,6 +279,8 @@ async fn test_skill_tool_local_execution() {
         permissions: std::sync::Arc::new(anyagents::permissions::PermissionManager::new()),
         observer: None,
         session_id: "test".to_string(),
+        scope: Default::default(),
+        job_id: None,
     };
     
     // Execute command to create file in CWD (which is now temp dir)
@@ -299,8 +313,13 @@ async fn test_skill_tool_read_content() {
         body: "This is the skill content.".to_string(),
         category: Some("Info".to_string()),
         requires_sandbox: false,
+        read_only: false,
         license: None,
         execution_mode: Some("direct".to_string()),
+        dependencies: None,
+        invocation: None,
+        examples: Vec::new(),
+        recipe: None,
     };
     
     let loaded = LoadedSkill {
@@ -316,6 +335,8 @@ async fn test_skill_tool_read_content() {
         permissions: std::sync::Arc::new(anyagents::permissions::PermissionManager::new()),
         observer: None,
         session_id: "test".to_string(),
+        scope: Default::default(),
+        job_id: None,
     };
     
     // Execute 'read' command
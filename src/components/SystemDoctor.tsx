@@ -0,0 +1,144 @@
+/**
+ * System Doctor - Renders the `system_doctor` report, in Settings and on first launch
+ */
+
+import { useEffect, useState } from "react";
+import { AlertTriangle, CheckCircle2, Loader2, MinusCircle, RefreshCw, Stethoscope, XCircle } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { Card, CardContent, CardDescription, CardHeader, CardTitle } from "@/components/ui/card";
+import {
+  Dialog,
+  DialogContent,
+  DialogDescription,
+  DialogFooter,
+  DialogHeader,
+  DialogTitle,
+} from "@/components/ui/dialog";
+import { anycoworkApi, HealthCheckItem, HealthReport, HealthStatus } from "@/lib/anycowork-api";
+
+const STATUS_ICONS: Record<HealthStatus, JSX.Element> = {
+  ok: <CheckCircle2 className="h-4 w-4 text-green-600" />,
+  warning: <AlertTriangle className="h-4 w-4 text-yellow-600" />,
+  error: <XCircle className="h-4 w-4 text-red-600" />,
+  skipped: <MinusCircle className="h-4 w-4 text-muted-foreground" />,
+};
+
+function useSystemDoctor() {
+  const [report, setReport] = useState<HealthReport | null>(null);
+  const [running, setRunning] = useState(false);
+
+  const run = async () => {
+    setRunning(true);
+    try {
+      setReport(await anycoworkApi.systemDoctor());
+    } catch (e) {
+      console.error("System doctor failed:", e);
+    } finally {
+      setRunning(false);
+    }
+  };
+
+  return { report, running, run };
+}
+
+function CheckList({ checks }: { checks: HealthCheckItem[] }) {
+  return (
+    <ul className="space-y-2">
+      {checks.map((check) => (
+        <li key={check.name} className="flex items-start gap-2 text-sm">
+          <span className="mt-0.5">{STATUS_ICONS[check.status]}</span>
+          <div className="min-w-0">
+            <div className="font-medium break-all">{check.name}</div>
+            <div className="text-muted-foreground">{check.message}</div>
+          </div>
+        </li>
+      ))}
+    </ul>
+  );
+}
+
+/** Settings card: runs on demand */
+export function SystemDoctorCard() {
+  const { report, running, run } = useSystemDoctor();
+
+  return (
+    <Card className="border-primary/50">
+      <CardHeader className="pb-3">
+        <div className="flex items-center justify-between">
+          <div className="flex items-center gap-2">
+            <Stethoscope className="h-4 w-4" />
+            <CardTitle>System Doctor</CardTitle>
+          </div>
+          <Button variant="outline" onClick={run} disabled={running} className="gap-2">
+            {running ? <Loader2 className="h-4 w-4 animate-spin" /> : <RefreshCw className="h-4 w-4" />}
+            {report ? "Run again" : "Run checks"}
+          </Button>
+        </div>
+        <CardDescription>
+          Provider keys, Docker, speech model, database, disk space and MCP servers
+        </CardDescription>
+      </CardHeader>
+      {report && (
+        <CardContent>
+          <CheckList checks={report.checks} />
+        </CardContent>
+      )}
+    </Card>
+  );
+}
+
+/** Shown once, on the first launch of the desktop app */
+export function OnboardingDialog() {
+  const { report, running, run } = useSystemDoctor();
+  const [open, setOpen] = useState(false);
+
+  useEffect(() => {
+    anycoworkApi
+      .isFirstRun()
+      .then((firstRun) => {
+        if (!firstRun) return;
+        setOpen(true);
+        run();
+      })
+      .catch((e) => console.error("Failed to read onboarding state:", e));
+  }, []);
+
+  const finish = async () => {
+    setOpen(false);
+    await anycoworkApi.completeOnboarding().catch((e) => console.error("Failed to complete onboarding:", e));
+  };
+
+  return (
+    <Dialog open={open} onOpenChange={(next) => !next && finish()}>
+      <DialogContent className="max-w-lg">
+        <DialogHeader>
+          <DialogTitle>Welcome to AnyCowork</DialogTitle>
+          <DialogDescription>
+            {!report
+              ? "Let's make sure everything is set up."
+              : report.healthy
+                ? "Everything needed is in place. Warnings only limit optional features."
+                : "Some checks failed. Fix them in Settings before running agents."}
+          </DialogDescription>
+        </DialogHeader>
+        <div className="max-h-80 overflow-y-auto">
+          {report ? (
+            <CheckList checks={report.checks} />
+          ) : (
+            <div className="flex items-center gap-2 text-sm text-muted-foreground">
+              <Loader2 className="h-4 w-4 animate-spin" />
+              Checking your system...
+            </div>
+          )}
+        </div>
+        <DialogFooter>
+          <Button variant="outline" onClick={run} disabled={running} className="gap-2">
+            {running ? <Loader2 className="h-4 w-4 animate-spin" /> : <RefreshCw className="h-4 w-4" />}
+            Check again
+          </Button>
+          <Button onClick={finish}>Get started</Button>
+        </DialogFooter>
+      </DialogContent>
+    </Dialog>
+  );
+}
@@ -7,6 +7,7 @@ import { Sidebar } from "@/src/components/layout/Sidebar";
 import { useIsDesktopApp } from "@/src/hooks/useIsDesktopApp";
 
 import { TitleBar } from "@/src/components/layout/TitleBar";
+import { OnboardingDialog } from "@/src/components/SystemDoctor";
 
 function MainLayout() {
   const isDesktop = useIsDesktopApp();
@@ -14,6 +15,7 @@ function MainLayout() {
   return (
     <div className="flex flex-col h-screen w-screen overflow-hidden bg-background rounded-xl border border-border shadow-2xl">
       {isDesktop && <TitleBar />}
+      {isDesktop && <OnboardingDialog />}
 
       {/* Main app content with adjustment for titlebar */}
       <div className={`flex flex-1 overflow-hidden`}>
@@ -5,10 +5,10 @@
 
 import { useState, useEffect } from "react";
 import {
-  useAgents, useCreateAgent, useUpdateAgent, useDeleteAgent,
+  useAgents, useCreateAgent, useUpdateAgent, useDeleteAgent, useProviderModels,
 } from "@/lib/hooks/use-anycowork";
 import { anycoworkApi } from "@/lib/anycowork-api";
-import type { Agent as AgentType, AgentCreate as AgentCreateType } from "@/lib/anycowork-api";
+import type { Agent as AgentType, AgentCreate as AgentCreateType, AgentConfigSnapshot } from "@/lib/anycowork-api";
 import { Button } from "@/components/ui/button";
 import { Card, CardContent, CardDescription, CardHeader, CardTitle } from "@/components/ui/card";
 import { Input } from "@/components/ui/input";
@@ -25,16 +25,85 @@ import {
 } from "@/components/ui/dialog";
 import { Tabs, TabsContent, TabsList, TabsTrigger } from "@/components/ui/tabs";
 import {
-  Bot, Plus, Trash2, Edit, Brain, Sparkles, MessageSquare, Server, Wrench, Key, Database, Shield, ShieldCheck, ShieldAlert, CheckCircle2, AlertCircle, Info, Loader2, Box, Users
+  Bot, Plus, Trash2, Edit, History, Brain, Sparkles, MessageSquare, Server, Wrench, Key, Database, Shield, ShieldCheck, ShieldAlert, CheckCircle2, AlertCircle, Info, Loader2, Box, Users
 } from "lucide-react";
 import { cn } from "@/lib/utils";
 import { Tooltip, TooltipContent, TooltipProvider, TooltipTrigger } from "@/components/ui/tooltip";
 import { ScrollArea, ScrollBar } from "@/components/ui/scroll-area";
 import { Alert, AlertDescription } from "@/components/ui/alert";
 import { toast } from "sonner";
-import { useQuery } from "@tanstack/react-query";
+import { useQuery, useQueryClient } from "@tanstack/react-query";
 import { useConfirm } from "@/components/ui/confirm-dialog";
 
+function AgentHistory({ agentId, onRestored }: { agentId: string; onRestored: () => void }) {
+  const queryClient = useQueryClient();
+  const { data: versions = [], isLoading } = useQuery({
+    queryKey: ['agents', agentId, 'history'],
+    queryFn: () => anycoworkApi.getAgentHistory(agentId),
+  });
+  const [restoring, setRestoring] = useState<number | null>(null);
+
+  const restore = async (version: number) => {
+    setRestoring(version);
+    try {
+      await anycoworkApi.rollbackAgent(agentId, version);
+      await queryClient.invalidateQueries({ queryKey: ['agents'] });
+      toast.success(`Restored version ${version}`);
+      onRestored();
+    } catch (e) {
+      toast.error(`Failed to restore version ${version}: ${e}`);
+    } finally {
+      setRestoring(null);
+    }
+  };
+
+  if (isLoading) {
+    return <Loader2 className="h-4 w-4 animate-spin" />;
+  }
+  if (versions.length === 0) {
+    return <p className="text-sm text-muted-foreground">No edits recorded yet.</p>;
+  }
+
+  return (
+    <div className="space-y-2">
+      {versions.map((v, i) => {
+        const config: Partial<AgentConfigSnapshot> = JSON.parse(v.snapshot || "{}");
+        return (
+          <div key={v.id} className="flex items-start justify-between gap-4 rounded-md border p-3">
+            <div className="min-w-0 space-y-1">
+              <div className="flex items-center gap-2 text-sm font-medium">
+                Version {v.version}
+                {i === 0 && <Badge variant="secondary" className="text-xs">Current</Badge>}
+                <span className="text-xs font-normal text-muted-foreground">
+                  {new Date(v.created_at + "Z").toLocaleString()}
+                </span>
+              </div>
+              <div className="text-xs text-muted-foreground">
+                {config.ai_provider}/{config.ai_model}
+                {v.note ? ` · ${v.note}` : ""}
+              </div>
+              {config.system_prompt && (
+                <p className="text-xs text-muted-foreground line-clamp-2">{config.system_prompt}</p>
+              )}
+            </div>
+            {i > 0 && (
+              <Button
+                variant="outline"
+                size="sm"
+                className="h-8 text-xs shrink-0"
+                disabled={restoring !== null}
+                onClick={() => restore(v.version)}
+              >
+                {restoring === v.version ? <Loader2 className="h-3.5 w-3.5 animate-spin" /> : "Restore"}
+              </Button>
+            )}
+          </div>
+        );
+      })}
+    </div>
+  );
+}
+
 interface AgentFormProps {
   agent?: AgentType;
   onClose: () => void;
@@ -48,6 +117,7 @@ export default function AgentsPage() {
   const [selectedAgent, setSelectedAgent] = useState<AgentType | null>(null);
   const [isCreateDialogOpen, setIsCreateDialogOpen] = useState(false);
   const [isEditDialogOpen, setIsEditDialogOpen] = useState(false);
+  const [historyAgent, setHistoryAgent] = useState<AgentType | null>(null);
 
   const handleCreateAgent = () => {
     setIsCreateDialogOpen(true);
@@ -219,6 +289,15 @@ export default function AgentsPage() {
                         <Edit className="h-3.5 w-3.5" />
                         Edit
                       </Button>
+                      <Button
+                        variant="outline"
+                        size="sm"
+                        className="gap-2 h-8 text-xs"
+                        onClick={() => setHistoryAgent(agent)}
+                      >
+                        <History className="h-3.5 w-3.5" />
+                        History
+                      </Button>
                       <Button
                         variant="outline"
                         size="sm"
@@ -236,6 +315,19 @@ export default function AgentsPage() {
           )}
         </div>
 
+        {/* Configuration History Dialog */}
+        <Dialog open={!!historyAgent} onOpenChange={(open) => !open && setHistoryAgent(null)}>
+          <DialogContent className="max-w-2xl max-h-[90vh] overflow-y-auto">
+            <DialogHeader>
+              <DialogTitle>History of {historyAgent?.name}</DialogTitle>
+              <DialogDescription>
+                Every edit is kept as a version. Restoring one records it as a new version.
+              </DialogDescription>
+            </DialogHeader>
+            {historyAgent && <AgentHistory agentId={historyAgent.id} onRestored={() => setHistoryAgent(null)} />}
+          </DialogContent>
+        </Dialog>
+
         {/* Edit Agent Dialog */}
         <Dialog open={isEditDialogOpen} onOpenChange={setIsEditDialogOpen}>
           <DialogContent className="max-w-4xl max-h-[90vh] overflow-y-auto">
@@ -328,6 +420,7 @@ You should:
       return {
         ...agent,
         ai_config: {
+          ...agent.ai_config,
           provider: agent.ai_config?.provider || "gemini",
           model: agent.ai_config?.model || "gemini-3-flash-preview",
           temperature: agent.ai_config?.temperature ?? 0.7,
@@ -344,6 +437,7 @@ You should:
       setFormData({
         ...agent,
         ai_config: {
+          ...agent.ai_config,
           provider: agent.ai_config?.provider || "gemini",
           model: agent.ai_config?.model || "gemini-3-flash-preview",
           temperature: agent.ai_config?.temperature ?? 0.7,
@@ -353,6 +447,11 @@ You should:
     }
   }, [agent]);
 
+  // Real model list when the provider's key is set; the built-in one otherwise
+  const { data: providerModels = [] } = useProviderModels(formData.ai_config?.provider);
+  const [toolModelsOnly, setToolModelsOnly] = useState(true);
+  const pickableModels = providerModels.filter((m) => !toolModelsOnly || m.tools);
+
   const handleSave = () => {
     if (agent) {
       updateAgent.mutate(
@@ -587,7 +686,19 @@ You should:
               <SelectValue placeholder="Select Model" />
             </SelectTrigger>
             <SelectContent>
-              {formData.ai_config?.provider === "gemini" && (
+              {providerModels.length > 0 && (
+                <>
+                  {formData.ai_config?.model && !pickableModels.some((m) => m.id === formData.ai_config?.model) && (
+                    <SelectItem value={formData.ai_config.model}>{formData.ai_config.model}</SelectItem>
+                  )}
+                  {pickableModels.map((m) => (
+                    <SelectItem key={m.id} value={m.id}>
+                      {m.name} · {Math.round(m.context_window / 1000)}k{m.vision ? " · vision" : ""}
+                    </SelectItem>
+                  ))}
+                </>
+              )}
+              {providerModels.length === 0 && formData.ai_config?.provider === "gemini" && (
                 <>
                   <SelectItem value="gemini-3-flash-preview">Gemini 3 Pro (Preview) - Most Intelligent</SelectItem>
                   <SelectItem value="gemini-3-flash-preview">Gemini 3 Flash (Preview) - Balanced</SelectItem>
@@ -597,7 +708,7 @@ You should:
                   <SelectItem value="gemini-1.5-flash">Gemini 1.5 Flash</SelectItem>
                 </>
               )}
-              {formData.ai_config?.provider === "anthropic" && (
+              {providerModels.length === 0 && formData.ai_config?.provider === "anthropic" && (
                 <>
                   <SelectItem value="claude-3-5-sonnet-20241022">Claude 3.5 Sonnet - Most Capable</SelectItem>
                   <SelectItem value="claude-3-5-haiku-20241022">Claude 3.5 Haiku - Fast</SelectItem>
@@ -606,7 +717,7 @@ You should:
                   <SelectItem value="claude-3-haiku-20240307">Claude 3 Haiku</SelectItem>
                 </>
               )}
-              {formData.ai_config?.provider === "openai" && (
+              {providerModels.length === 0 && formData.ai_config?.provider === "openai" && (
                 <>
                   <SelectItem value="gpt-5.2">GPT-5.2 - Best for Coding & Agentic</SelectItem>
                   <SelectItem value="gpt-5">GPT-5 - Reasoning Model</SelectItem>
@@ -620,6 +731,18 @@ You should:
               )}
             </SelectContent>
           </Select>
+          {providerModels.length > 0 && (
+            <div className="flex items-center gap-2">
+              <Checkbox
+                id="tool_models_only"
+                checked={toolModelsOnly}
+                onCheckedChange={(checked) => setToolModelsOnly(checked === true)}
+              />
+              <Label htmlFor="tool_models_only" className="text-xs text-muted-foreground font-normal">
+                Only models that can use tools
+              </Label>
+            </div>
+          )}
         </div>
 
         <div className="grid grid-cols-2 gap-4">
@@ -628,7 +751,7 @@ You should:
             <Input
               type="number"
               min="0"
-              max="2"
+              max={formData.ai_config?.provider === "anthropic" ? "1" : "2"}
               step="0.1"
               placeholder="0.7"
               value={formData.ai_config?.temperature ?? 0.7}
@@ -665,6 +788,64 @@ You should:
           </div>
         </div>
 
+        <div className="grid grid-cols-3 gap-4">
+          <div className="space-y-2">
+            <Label>Top P</Label>
+            <Input
+              type="number"
+              min="0"
+              max="1"
+              step="0.05"
+              placeholder="Provider default"
+              value={formData.ai_config?.top_p ?? ""}
+              onChange={(e) =>
+                setFormData({
+                  ...formData,
+                  ai_config: {
+                    ...formData.ai_config!,
+                    top_p: e.target.value === "" ? undefined : parseFloat(e.target.value)
+                  }
+                })
+              }
+            />
+          </div>
+          <div className="space-y-2">
+            <Label>Stop Sequences</Label>
+            <Input
+              placeholder="Comma separated"
+              value={(formData.ai_config?.stop ?? []).join(", ")}
+              onChange={(e) =>
+                setFormData({
+                  ...formData,
+                  ai_config: {
+                    ...formData.ai_config!,
+                    stop: e.target.value.split(",").map(s => s.trim()).filter(Boolean)
+                  }
+                })
+              }
+            />
+          </div>
+          <div className="space-y-2">
+            <Label>Seed</Label>
+            <Input
+              type="number"
+              step="1"
+              placeholder="Random"
+              disabled={formData.ai_config?.provider === "anthropic"}
+              value={formData.ai_config?.seed ?? ""}
+              onChange={(e) =>
+                setFormData({
+                  ...formData,
+                  ai_config: {
+                    ...formData.ai_config!,
+                    seed: e.target.value === "" ? undefined : parseInt(e.target.value)
+                  }
+                })
+              }
+            />
+          </div>
+        </div>
+
         <div className="space-y-2">
           <Label>System Prompt</Label>
           <Textarea
@@ -3,7 +3,7 @@
  */
 
 import React, { useState, useRef, useEffect } from "react";
-import { useParams, useNavigate, Link } from "react-router-dom";
+import { useParams, useNavigate, useSearchParams, Link } from "react-router-dom";
 import { Button } from "@/components/ui/button";
 import { Textarea } from "@/components/ui/textarea";
 import { Avatar, AvatarFallback } from "@/components/ui/avatar";
@@ -48,16 +48,19 @@ import {
   StopCircle,
   Edit2,
   Phone,
+  Eye,
 } from "lucide-react";
 import { Separator } from "@/components/ui/separator";
 import ReactMarkdown from "react-markdown";
 import remarkGfm from "remark-gfm";
-import { listen } from '@tauri-apps/api/event';
+import { getCurrentWebviewWindow } from '@tauri-apps/api/webviewWindow';
 import { cn } from "@/lib/utils";
 import { useCreateSession, useSessions, useAgents, useDeleteSession, useServerInfo } from "@/lib/hooks/use-anycowork";
-import { anycoworkApi, PlanUpdate } from "@/lib/anycowork-api"; // Added
+import { anycoworkApi, Artifact, PermissionPayload, PlanUpdate, TodoItem } from "@/lib/anycowork-api"; // Added
 import { A2UIRenderer } from "@/components/a2ui/A2UIRenderer";
 import { CharacterPanel } from "@/components/chat/CharacterPanel";
+import { TodoPanel } from "@/components/chat/TodoPanel";
+import { ArtifactsPanel } from "@/components/chat/ArtifactsPanel";
 import { A2UIMessage } from "@/src/lib/a2ui-processor";
 import { useConfirm } from "@/components/ui/confirm-dialog";
 import { VoiceCallDialog } from "../components/voice/VoiceCallDialog";
@@ -111,6 +114,7 @@ interface ExecutionStep {
   error?: string;
   requires_approval: boolean;
   approval_reason?: string;
+  payload?: PermissionPayload;
   created_at: string;
   completed_at?: string;
 }
@@ -132,6 +136,8 @@ interface ExecutionJob {
   status: "running" | "waiting_approval" | "completed" | "failed" | "cancelled";
   steps: ExecutionStep[];
   current_step_index: number;
+  // Agent configuration version the job ran with
+  agent_version?: number | null;
   final_response?: string;
   error?: string;
   created_at: string;
@@ -184,6 +190,8 @@ function ThinkingItem({ content, isFinished }: ThinkingItemProps) {
 export default function ChatPage() {
   const { sessionId } = useParams();
   const navigate = useNavigate();
+  // Observer windows (open_observer_window) follow the session read-only
+  const observing = useSearchParams()[0].get("observe") === "1";
 
   // Hooks
   const { data: sessionsData, refetch: refetchSessions } = useSessions();
@@ -451,10 +459,20 @@ export default function ChatPage() {
         }
       };
       loadMessages();
+      setTodos([]);
+      anycoworkApi.getSessionTodos(sessionId)
+        .then(saved => setTodos(saved ? JSON.parse(saved.items) : []))
+        .catch(error => console.error("Error loading todos:", error));
+      setArtifacts([]);
+      anycoworkApi.getSessionArtifacts(sessionId)
+        .then(setArtifacts)
+        .catch(error => console.error("Error loading artifacts:", error));
     } else {
       // Clear messages for new session or when no session selected (after deletion)
       setMessages([]);
       setPendingApproval(null);
+      setTodos([]);
+      setArtifacts([]);
     }
   }, [sessionId]);
 
@@ -758,6 +776,8 @@ export default function ChatPage() {
   const [currentJob, setCurrentJob] = useState<ExecutionJob | null>(null);
   const [pendingApproval, setPendingApproval] = useState<ExecutionStep | null>(null);
   const [activePlan, setActivePlan] = useState<PlanState | null>(null);
+  const [todos, setTodos] = useState<TodoItem[]>([]);
+  const [artifacts, setArtifacts] = useState<Artifact[]>([]);
   const [isPlanOpen, setIsPlanOpen] = useState(false);
 
   // Auto-expand task list when tasks start running
@@ -787,8 +807,9 @@ export default function ChatPage() {
     async function startListening() {
       if (!sessionId || sessionId === 'new') return;
 
-      unlisten = await listen(`session:${sessionId}`, (event: any) => {
-        const payload = event.payload;
+      // Session events go to the windows subscribed to the session
+      await anycoworkApi.subscribeSession(sessionId);
+      const handleEvent = (payload: any) => {
         console.debug("🔥 [DEBUG Event]:", JSON.stringify(payload));
 
         // Handle Event Types
@@ -823,6 +844,16 @@ export default function ChatPage() {
               ];
             }
           });
+        } else if (payload.type === 'job_queued') {
+          setCurrentJob(payload.job);
+          setThinkingMessage(payload.position > 0
+            ? `Queued behind ${payload.position} other job${payload.position === 1 ? '' : 's'}...`
+            : "Waiting for the agent's current job to finish...");
+        } else if (payload.type === 'job_pending') {
+          setCurrentJob(payload.job);
+          setThinkingMessage(`Offline: ${payload.provider} can't be reached. The job will resume when it's back...`);
+        } else if (payload.type === 'workspace_lock_waiting') {
+          setThinkingMessage(`Waiting for another job to finish with ${payload.file ?? 'the workspace'} (${payload.held_by.tool})...`);
         } else if (payload.type === 'job_started') {
           setCurrentJob(payload.job);
           setThinkingMessage("Starting job...");
@@ -833,6 +864,7 @@ export default function ChatPage() {
             id: req.id,
             tool_name: req.metadata && req.metadata.command ? "bash" : (req.metadata && req.metadata.operation ? "filesystem" : "System"),
             tool_args: req.metadata || {},
+            payload: req.payload,
             status: "waiting_approval",
             requires_approval: true,
             created_at: new Date().toISOString()
@@ -963,6 +995,10 @@ export default function ChatPage() {
         } else if (payload.type === 'plan_update') {
           // Sync plan state
           setActivePlan(payload.plan as PlanUpdate);
+        } else if (payload.type === 'todo_updated') {
+          setTodos(payload.items as TodoItem[]);
+        } else if (payload.type === 'artifact_created') {
+          setArtifacts(prev => [...prev, payload.artifact as Artifact]);
         } else if (payload.type === 'step_completed') {
           // Show tool output
           setPendingApproval(null); // Clear approval state
@@ -1063,13 +1099,23 @@ export default function ChatPage() {
             }
           });
         }
-      });
+      };
+
+      unlisten = await getCurrentWebviewWindow().listen(`session:${sessionId}`, (event: any) => handleEvent(event.payload));
+
+      // After a reload, catch up on a job that is still running
+      const events = await anycoworkApi.getSessionEvents(sessionId);
+      const lastStart = events.map((e) => e.event_type).lastIndexOf('job_started');
+      if (lastStart >= 0 && !events.slice(lastStart).some((e) => e.event_type === 'job_completed')) {
+        events.slice(lastStart).forEach((e) => handleEvent(JSON.parse(e.payload)));
+      }
     }
 
     startListening();
 
     return () => {
       if (unlisten) unlisten();
+      if (sessionId && sessionId !== 'new') anycoworkApi.unsubscribeSession(sessionId);
     };
   }, [sessionId]);
 
@@ -1350,9 +1396,46 @@ export default function ChatPage() {
   const renderApprovalContent = (step: ExecutionStep) => {
     let label = step.tool_name;
     let details = "";
-
+    let diff: string | undefined;
+    const payload = step.payload;
+
+    // 0. Structured payload: show exactly what will happen
+    if (payload) {
+      switch (payload.kind) {
+        case 'command':
+          label = "Execute Command";
+          details = payload.command;
+          break;
+        case 'file_read':
+          label = "Read File";
+          details = payload.path;
+          break;
+        case 'file_write':
+          label = payload.created ? "Create File" : "Overwrite File";
+          details = payload.path;
+          diff = payload.diff && (payload.truncated ? `${payload.diff}\n…` : payload.diff);
+          break;
+        case 'file_delete':
+          label = "Delete File";
+          details = payload.path;
+          break;
+        case 'create_dir':
+          label = "Create Directory";
+          details = payload.path;
+          break;
+        case 'http':
+          label = `HTTP ${payload.method}`;
+          details = payload.url;
+          break;
+        case 'email':
+          label = "Send Email";
+          details = `To: ${payload.to.join(", ")} | ${payload.subject}`;
+          diff = payload.body;
+          break;
+      }
+    }
     // 1. Filesystem
-    if (step.tool_name === "filesystem" || (step.tool_args && step.tool_args.operation)) {
+    else if (step.tool_name === "filesystem" || (step.tool_args && step.tool_args.operation)) {
       label = `Filesystem ${(step.tool_args.operation || "access").toUpperCase()}`;
       details = step.tool_args.path || step.tool_args.directory || "unknown path";
     }
@@ -1383,6 +1466,11 @@ export default function ChatPage() {
             {details}
           </span>
         )}
+        {diff && (
+          <pre className="text-xs text-amber-900 dark:text-amber-100 font-mono mt-1 bg-black/5 dark:bg-white/10 px-1.5 py-1 rounded max-h-[240px] overflow-auto whitespace-pre">
+            {diff}
+          </pre>
+        )}
       </div>
     );
   };
@@ -1822,6 +1910,7 @@ export default function ChatPage() {
                         </div>
                       )}
                     </div>
+                    {!observing && (
                     <div className="flex items-center justify-end gap-2 mt-1 pt-2 border-t border-amber-200/50 dark:border-amber-800/50">
                       <Button
                         size="sm"
@@ -1839,6 +1928,7 @@ export default function ChatPage() {
                         <CheckCircle className="h-3.5 w-3.5" /> Approve
                       </Button>
                     </div>
+                    )}
                   </div>
                 </div>
               )}
@@ -1944,6 +2034,9 @@ export default function ChatPage() {
             {/* Input Area - Enhanced with task list integration */}
             <div className="border-t border-border/60 bg-muted/30">
               <div className="mx-auto max-w-3xl">
+                {todos.length > 0 && <TodoPanel items={todos} />}
+                {artifacts.length > 0 && <ArtifactsPanel artifacts={artifacts} />}
+
                 {/* Task List Panel - Collapsible, above input */}
                 {activePlan && activePlan.tasks.length > 0 && (
                   <div className="border-b border-border/40">
@@ -2023,6 +2116,11 @@ export default function ChatPage() {
                 )}
 
                 {/* Main Input Form */}
+                {observing ? (
+                  <div className="p-3 flex items-center gap-2 text-sm text-muted-foreground">
+                    <Eye className="h-4 w-4" /> Observing this session: read-only
+                  </div>
+                ) : (
                 <form onSubmit={handleSubmit} className="p-3">
                   {/* Textarea with send button */}
                   <div className="relative">
@@ -2132,6 +2230,7 @@ export default function ChatPage() {
                     )}
                   </div>
                 </form>
+                )}
               </div>
             </div>
           </div>
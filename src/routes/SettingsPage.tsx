@@ -31,6 +31,7 @@ import {
   Terminal,
   Wrench,
   BrainCircuit,
+  Stethoscope,
 } from "lucide-react";
 import {
   useAIConfig,
@@ -45,6 +46,7 @@ import {
 } from "@/lib/hooks/use-anycowork";
 import { ExecutionMode } from "@/lib/anycowork-api";
 import { toast } from "sonner";
+import { SystemDoctorCard } from "@/src/components/SystemDoctor";
 
 function SettingsPage() {
   const { data: aiConfig, isLoading: aiLoading } = useAIConfig();
@@ -190,7 +192,7 @@ function SettingsPage() {
       {/* Content */}
       <div className="max-w-6xl mx-auto px-6 py-6">
         <Tabs value={activeTab} onValueChange={setActiveTab} className="space-y-6">
-          <TabsList className="grid w-full grid-cols-3 max-w-xl">
+          <TabsList className="grid w-full grid-cols-4 max-w-2xl">
             <TabsTrigger value="ai" className="gap-2">
               <Sparkles className="h-4 w-4" />
               AI Providers
@@ -203,6 +205,10 @@ function SettingsPage() {
               <MessageSquare className="h-4 w-4" />
               Messaging
             </TabsTrigger>
+            <TabsTrigger value="system" className="gap-2">
+              <Stethoscope className="h-4 w-4" />
+              System
+            </TabsTrigger>
           </TabsList>
 
           {/* AI Providers Tab */}
@@ -715,6 +721,11 @@ function SettingsPage() {
               </Button>
             </div>
           </TabsContent>
+
+          {/* System Tab */}
+          <TabsContent value="system" className="space-y-6">
+            <SystemDoctorCard />
+          </TabsContent>
         </Tabs>
       </div>
     </div>